@@ -1,8 +1,17 @@
+use alloc::vec::Vec;
+
 use crate::block_io::{BlockBuf, BlockNum, BlockIO, BlockIOExt, BLOCK_SIZE};
 use crate::fs::FsError;
 
 const BITS_PER_BLOCK: u64 = (BLOCK_SIZE * 8) as u64;
 
+/// Runs of freed blocks one sync may carry to the device as discards.
+///
+/// A bound on memory and not on correctness: a run freed past it is simply not
+/// discarded until a trim walks the bitmap, which finds every free block
+/// whether or not anybody remembered freeing it.
+const MAX_FREED_RUNS: usize = 1024;
+
 /// A run of blocks the allocator actually reserved.
 ///
 /// `len` is what you got, never what you asked for. The pair this replaced was
@@ -18,6 +27,91 @@ pub struct Run {
     pub len: u32,
 }
 
+/// Blocks freed since the last sync, waiting to be discarded.
+///
+/// **Held until the sync and not discarded as they are freed**, because a free
+/// is not durable until the bitmap and the tree that stopped naming the blocks
+/// are: a crash before the sync comes back to a volume on which the deleted
+/// file still exists and still names those blocks, and a device that had
+/// already been told they were garbage would hand the file back as whatever
+/// the flash keeps in an erased block.
+///
+/// A block reallocated before the sync is carved back out — see
+/// [`Freed::carve`] — so nothing written since it was freed is ever discarded.
+#[derive(Default)]
+pub(crate) struct Freed {
+    /// `(start, count)`, in the order they were freed, with a run that extends
+    /// the last one merged into it.
+    runs: Vec<(u64, u64)>,
+}
+
+impl Freed {
+    /// Remember `count` blocks from `start` as freed.
+    fn note(&mut self, start: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+        if let Some(last) = self.runs.last_mut() {
+            if last.0 + last.1 == start {
+                last.1 += count;
+                return;
+            }
+        }
+        if self.runs.len() < MAX_FREED_RUNS {
+            self.runs.push((start, count));
+        }
+    }
+
+    /// Forget whatever of `[start, start + len)` is waiting, because it has
+    /// just been allocated again.
+    fn carve(&mut self, start: u64, len: u64) {
+        let end = start + len;
+        let mut i = 0;
+        while i < self.runs.len() {
+            let (s, n) = self.runs[i];
+            let e = s + n;
+            if e <= start || end <= s {
+                i += 1;
+                continue;
+            }
+            self.runs.remove(i);
+            if end < e {
+                self.runs.insert(i, (end, e - end));
+            }
+            if s < start {
+                self.runs.insert(i, (s, start - s));
+                i += 1;
+            }
+        }
+    }
+
+    /// Every run waiting, sorted, with neighbours merged.
+    fn take(&mut self) -> Vec<(u64, u64)> {
+        let mut runs = core::mem::take(&mut self.runs);
+        runs.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(runs.len());
+        for (s, n) in runs {
+            match merged.last_mut() {
+                Some(last) if last.0 + last.1 >= s => last.1 = last.1.max(s + n - last.0),
+                _ => merged.push((s, n)),
+            }
+        }
+        merged
+    }
+}
+
+/// Tell `io` that `count` blocks from `start` are free, in pieces the
+/// `u32` count of a discard can carry.
+fn discard_run(io: &dyn BlockIO, start: u64, count: u64) -> Result<(), FsError> {
+    let mut done = 0;
+    while done < count {
+        let piece = (count - done).min(u32::MAX as u64) as u32;
+        io.trim(BlockNum::new(start + done), piece)?;
+        done += piece as u64;
+    }
+    Ok(())
+}
+
 /// Bitmap-based block allocator.
 ///
 /// The bitmap is stored on disk starting at `bitmap_start` and spanning
@@ -28,6 +122,7 @@ pub struct BitmapAllocator {
     pub total_blocks: u64,
     pub free_blocks: u64,
     pub next_alloc: u64, // cursor — scan starts here, wraps once
+    pub(crate) freed: Freed,
 }
 
 impl BitmapAllocator {
@@ -114,6 +209,7 @@ impl BitmapAllocator {
     fn reserve(&mut self, io: &dyn BlockIO, start: u64, len: u32) -> Result<Run, FsError> {
         let start_block = BlockNum::new(start);
         self.set_range_used(io, start_block, len as u64)?;
+        self.freed.carve(start, len as u64);
         self.free_blocks -= len as u64;
         self.next_alloc = start + len as u64;
         if self.next_alloc >= self.total_blocks {
@@ -208,7 +304,8 @@ impl BitmapAllocator {
         Ok((start, best_count))
     }
 
-    /// Free a contiguous range of blocks.
+    /// Free a contiguous range of blocks, and remember as many of them as
+    /// the bitmap now calls free for [`Self::discard_freed`].
     pub fn free_range(
         &mut self,
         io: &dyn BlockIO,
@@ -216,11 +313,90 @@ impl BitmapAllocator {
         count: u32,
     ) -> Result<(), FsError> {
         for i in 0..count as u64 {
-            self.set_free(io, BlockNum::new(start.raw() + i))?;
+            if let Err(err) = self.set_free(io, BlockNum::new(start.raw() + i)) {
+                self.freed.note(start.raw(), i);
+                return Err(err);
+            }
         }
+        self.freed.note(start.raw(), count as u64);
         Ok(())
     }
 
+    /// Discard every run freed since the last call, and answer how many blocks
+    /// that was.
+    ///
+    /// **Only once the frees are durable** — the caller's sync has written the
+    /// superblock and flushed — for [`Freed`]'s reason. The first run the
+    /// device refuses ends it and the rest are forgotten: a device that will
+    /// not take one hint will not take the next, the blocks are free either
+    /// way, and [`Self::trim_free`] is what gets them erased after all.
+    pub fn discard_freed(&mut self, io: &dyn BlockIO) -> Result<u64, FsError> {
+        let mut blocks = 0;
+        for (start, count) in self.freed.take() {
+            discard_run(io, start, count)?;
+            blocks += count;
+        }
+        Ok(blocks)
+    }
+
+    /// Discard every block the bitmap calls free, and answer how many.
+    ///
+    /// What a deleted file's blocks are discarded by when [`Self::discard_freed`]
+    /// never saw them: freed past [`MAX_FREED_RUNS`], freed on a device that
+    /// refused the hint then, or freed by a kernel older than discard. Walks
+    /// the whole bitmap, a byte at a time where a byte is all one thing. The
+    /// caller must have synced first, for [`Freed`]'s reason; the batch is
+    /// dropped on success because every block in it was free and is covered.
+    pub fn trim_free(&mut self, io: &dyn BlockIO) -> Result<u64, FsError> {
+        let total = self.total_blocks;
+        let mut buf = BlockBuf::zeroed();
+        let mut cached = u64::MAX;
+        let mut run: Option<u64> = None;
+        let mut trimmed = 0;
+        let mut pos = 0;
+        while pos < total {
+            let byte_idx = pos / 8;
+            let bblock = self.bitmap_start.raw() + byte_idx / BLOCK_SIZE as u64;
+            if bblock != cached {
+                io.read(BlockNum::new(bblock), &mut buf)?;
+                cached = bblock;
+            }
+            let byte = buf.0[(byte_idx % BLOCK_SIZE as u64) as usize];
+            // A whole byte of one kind moves eight blocks at once; the last
+            // byte may hold bits past `total`, so it goes bit by bit.
+            if pos % 8 == 0 && pos + 8 <= total && (byte == 0 || byte == 0xFF) {
+                match (byte, run) {
+                    (0, None) => run = Some(pos),
+                    (0xFF, Some(start)) => {
+                        discard_run(io, start, pos - start)?;
+                        trimmed += pos - start;
+                        run = None;
+                    }
+                    _ => {}
+                }
+                pos += 8;
+                continue;
+            }
+            let free = (byte >> (pos % 8)) & 1 == 0;
+            match (free, run) {
+                (true, None) => run = Some(pos),
+                (false, Some(start)) => {
+                    discard_run(io, start, pos - start)?;
+                    trimmed += pos - start;
+                    run = None;
+                }
+                _ => {}
+            }
+            pos += 1;
+        }
+        if let Some(start) = run {
+            discard_run(io, start, total - start)?;
+            trimmed += total - start;
+        }
+        self.freed = Freed::default();
+        Ok(trimmed)
+    }
+
     /// Initialize bitmap on disk: zero all bitmap blocks, then mark metadata blocks as used.
     pub fn format(
         io: &dyn BlockIO,
@@ -240,6 +416,7 @@ impl BitmapAllocator {
             total_blocks,
             free_blocks: total_blocks - metadata_blocks,
             next_alloc: metadata_blocks,
+            freed: Freed::default(),
         };
 
         // Metadata blocks (superblock, bitmap, journal area), and the backup
@@ -108,9 +108,16 @@ pub trait BlockIO {
     fn sync(&self) -> Result<(), DeviceError> {
         Ok(())
     }
+    /// Tell the device `count` blocks from `start` hold nothing this
+    /// filesystem will read again. A hint: what a later read of them answers
+    /// is the device's business, and a device with no such command — every
+    /// host-side implementation here — has nothing to do.
+    fn discard(&self, _start: BlockNum, _count: u32) -> Result<(), DeviceError> {
+        Ok(())
+    }
 }
 
-/// The same three operations, reported as [`FsError`] with the block attached.
+/// The same four operations, reported as [`FsError`] with the block attached.
 ///
 /// Every call site inside this crate goes through these rather than through
 /// [`BlockIO`] directly, so the block number in the error is the one the caller
@@ -119,6 +126,7 @@ pub(crate) trait BlockIOExt {
     fn read(&self, block: BlockNum, buf: &mut BlockBuf) -> Result<(), FsError>;
     fn write(&self, block: BlockNum, buf: &BlockBuf) -> Result<(), FsError>;
     fn flush(&self) -> Result<(), FsError>;
+    fn trim(&self, start: BlockNum, count: u32) -> Result<(), FsError>;
 }
 
 impl<T: BlockIO + ?Sized> BlockIOExt for T {
@@ -133,6 +141,10 @@ impl<T: BlockIO + ?Sized> BlockIOExt for T {
     fn flush(&self) -> Result<(), FsError> {
         self.sync().map_err(|DeviceError| FsError::DeviceSync)
     }
+
+    fn trim(&self, start: BlockNum, count: u32) -> Result<(), FsError> {
+        self.discard(start, count).map_err(|DeviceError| FsError::DeviceDiscard(start))
+    }
 }
 
 // --- Host-side implementations ---
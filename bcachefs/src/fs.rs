@@ -3,7 +3,7 @@ use alloc::vec;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use crate::alloc_bitmap::BitmapAllocator;
+use crate::alloc_bitmap::{BitmapAllocator, Freed};
 use crate::block_io::{BlockBuf, BlockNum, BlockIO, BlockIOExt, BLOCK_SIZE};
 use crate::btree::{self, Entry, Key, KeyType, Node};
 use crate::superblock::Superblock;
@@ -64,6 +64,7 @@ pub enum FsError {
     DeviceRead(BlockNum),
     DeviceWrite(BlockNum),
     DeviceSync,
+    DeviceDiscard(BlockNum),
     NotFound,
     NoSpace { requested: u32, available: u64 },
     NameTooLong { len: usize, max: usize },
@@ -528,6 +529,7 @@ impl<IO: BlockIO, Mode> Mounted<IO, Mode> {
             total_blocks: sb.block_count,
             free_blocks: sb.free_blocks,
             next_alloc: sb.next_alloc,
+            freed: Freed::default(),
         };
         Ok(Mounted {
             io,
@@ -537,6 +539,11 @@ impl<IO: BlockIO, Mode> Mounted<IO, Mode> {
         })
     }
 
+    /// The device this filesystem was opened on.
+    pub fn io(&self) -> &IO {
+        &self.io
+    }
+
     /// Find a file entry by name. Tries File key first, then Symlink.
     fn find_by_name(&self, name: &str) -> Result<Option<(Key, Vec<u8>)>, FsError> {
         // Try as File first (most common)
@@ -739,13 +746,31 @@ impl<IO: BlockIO> Mounted<IO, ReadWrite> {
         Ok(())
     }
 
-    /// Sync filesystem state to disk.
+    /// Sync filesystem state to disk, then discard what was freed since the
+    /// last sync.
+    ///
+    /// The discards come after the flush and never before it — see
+    /// `alloc_bitmap::Freed` — and a device that refuses one does not fail the
+    /// sync: everything the caller asked to be durable is, and a hint the
+    /// device would not take is [`Self::trim`]'s to retry.
     pub fn sync(&mut self) -> Result<(), FsError> {
         self.sb.free_blocks = self.alloc.free_blocks;
         self.sb.next_alloc = self.alloc.next_alloc;
         self.sb.set_clean(true);
         self.sb.write(&self.io)?;
-        self.io.flush()
+        self.io.flush()?;
+        let _ = self.alloc.discard_freed(&self.io);
+        Ok(())
+    }
+
+    /// Sync, then discard every free block on the volume, answering how many
+    /// were discarded — what `fstrim` asks for.
+    ///
+    /// Unlike [`Self::sync`]'s batch, a refused discard is an error here: the
+    /// caller asked for exactly this and nothing else.
+    pub fn trim(&mut self) -> Result<u64, FsError> {
+        self.sync()?;
+        self.alloc.trim_free(&self.io)
     }
 
     /// Delete a file/symlink by name, freeing its data blocks. Returns true if found.
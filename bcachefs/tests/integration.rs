@@ -1164,3 +1164,154 @@ fn a_write_the_device_refuses_is_reported_and_gives_its_blocks_back() {
         .expect("keep.bin");
     assert_eq!(extents[0].start_block + 1, next_free);
 }
+
+// --- Discard: freed blocks reach the device as hints, and only once durable ---
+
+/// What the device was told, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Told {
+    Sync,
+    Discard(u64, u32),
+}
+
+/// A volume that records every flush and every discard it is handed.
+struct Records {
+    inner: VecBlockIO,
+    told: std::cell::RefCell<Vec<Told>>,
+}
+
+impl Records {
+    fn new(raw: Vec<u8>) -> Self {
+        Self { inner: VecBlockIO::from_vec(raw), told: Default::default() }
+    }
+
+    /// Every discard since the last call, as `(start, count)`.
+    fn discards(&self) -> Vec<(u64, u32)> {
+        let told = core::mem::take(&mut *self.told.borrow_mut());
+        told.into_iter()
+            .filter_map(|t| match t {
+                Told::Discard(start, count) => Some((start, count)),
+                Told::Sync => None,
+            })
+            .collect()
+    }
+}
+
+impl bcachefs::BlockIO for Records {
+    fn read_block(
+        &self,
+        block: bcachefs::BlockNum,
+        buf: &mut bcachefs::BlockBuf,
+    ) -> Result<(), bcachefs::DeviceError> {
+        self.inner.read_block(block, buf)
+    }
+
+    fn write_block(
+        &self,
+        block: bcachefs::BlockNum,
+        buf: &bcachefs::BlockBuf,
+    ) -> Result<(), bcachefs::DeviceError> {
+        self.inner.write_block(block, buf)
+    }
+
+    fn block_count(&self) -> u64 {
+        self.inner.block_count()
+    }
+
+    fn sync(&self) -> Result<(), bcachefs::DeviceError> {
+        self.told.borrow_mut().push(Told::Sync);
+        Ok(())
+    }
+
+    fn discard(&self, start: bcachefs::BlockNum, count: u32) -> Result<(), bcachefs::DeviceError> {
+        self.told.borrow_mut().push(Told::Discard(start.raw(), count));
+        Ok(())
+    }
+}
+
+fn recorded(blocks: u64) -> Mounted<Records, ReadWrite> {
+    let raw = Formatted::format(VecBlockIO::new(blocks)).expect("format")
+        .into_io().expect("sync").into_vec();
+    Mounted::<_, ReadWrite>::open(Records::new(raw)).expect("open")
+}
+
+fn blocks_of(extents: &[Extent]) -> Vec<u64> {
+    extents.iter()
+        .flat_map(|e| e.start_block..e.start_block + e.block_count as u64)
+        .collect()
+}
+
+#[test]
+fn a_deleted_file_is_discarded_by_the_next_sync_and_not_before_it() {
+    let mut fs = recorded(256);
+    fs.create("gone.bin", &vec![0x5Au8; 10 * 4096], 0).expect("create");
+    fs.sync().expect("sync");
+    let (extents, _) = fs.file_extents("gone.bin").expect("file_extents").expect("gone.bin");
+    fs.io().discards();
+
+    assert!(fs.delete("gone.bin").expect("delete"));
+    assert_eq!(fs.io().discards(), vec![], "discarded before the free was durable");
+
+    fs.sync().expect("sync");
+    let told = fs.io().told.borrow().clone();
+    let first = told.iter().position(|t| matches!(t, Told::Discard(..))).expect("a discard");
+    assert!(told[..first].contains(&Told::Sync), "a discard went out ahead of the flush: {told:?}");
+    let discarded: Vec<u64> = fs.io().discards().iter()
+        .flat_map(|&(s, n)| s..s + n as u64)
+        .collect();
+    assert_eq!(discarded, blocks_of(&extents));
+
+    fs.sync().expect("sync");
+    assert_eq!(fs.io().discards(), vec![], "one free, discarded twice");
+}
+
+#[test]
+fn a_block_reallocated_before_the_sync_is_not_discarded() {
+    let mut fs = recorded(256);
+    fs.create("old.bin", &vec![0x11u8; 8 * 4096], 0).expect("create old");
+    fs.sync().expect("sync");
+    fs.io().discards();
+
+    assert!(fs.delete("old.bin").expect("delete"));
+    // The cursor moved back to the freed run, so the new file lands in it.
+    fs.create("new.bin", &vec![0x22u8; 3 * 4096], 0).expect("create new");
+    let (extents, _) = fs.file_extents("new.bin").expect("file_extents").expect("new.bin");
+    let live = blocks_of(&extents);
+
+    fs.sync().expect("sync");
+    for (start, count) in fs.io().discards() {
+        for block in start..start + count as u64 {
+            assert!(!live.contains(&block), "discarded block {block} of a live file");
+        }
+    }
+    assert_eq!(fs.read_file("new.bin").expect("read"), vec![0x22u8; 3 * 4096]);
+}
+
+#[test]
+fn a_trim_discards_every_free_block_and_nothing_else() {
+    let mut fs = recorded(1024);
+    for i in 0..6 {
+        fs.create(&format!("f{i}"), &vec![i as u8; (i + 1) * 4096], 0).expect("create");
+    }
+    assert!(fs.delete("f1").expect("delete"));
+    assert!(fs.delete("f4").expect("delete"));
+    fs.sync().expect("sync");
+    fs.io().discards();
+
+    let trimmed = fs.trim().expect("trim");
+    let free = bcachefs::Superblock::read(fs.io()).expect("superblock").free_blocks;
+    assert_eq!(trimmed, free);
+
+    let discarded: Vec<u64> = fs.io().discards().iter()
+        .flat_map(|&(s, n)| s..s + n as u64)
+        .collect();
+    assert_eq!(discarded.len() as u64, trimmed);
+    for name in ["f0", "f2", "f3", "f5"] {
+        let (extents, _) = fs.file_extents(name).expect("file_extents").expect(name);
+        for block in blocks_of(&extents) {
+            assert!(!discarded.contains(&block), "trimmed block {block} of {name}");
+        }
+    }
+    assert!(!discarded.contains(&0), "trimmed the superblock");
+    assert!(!discarded.contains(&1023), "trimmed the backup superblock");
+}
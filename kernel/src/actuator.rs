@@ -373,6 +373,36 @@ actuators! {
     /// instead of a device. See `drivers/virtio.rs`'s `used_selftest`.
     virtio_used_selftest = "virtio-used-selftest";
 
+    /// Register two synthetic disks with the page cache once the boot's own
+    /// mounts are in, and drive the shared budget with them: one streams reads
+    /// through several budgets' worth of blocks while the other asks for its
+    /// fair share. A test boot's real disks are one busy and the rest idle, so
+    /// without this `reclaim_for` — a device under its share taking slots back
+    /// from one over it — would ship never having run, and nothing would say
+    /// whether a streaming disk can push a quiet one out. The cache, the budget
+    /// and the registry are the shipped ones; only the two devices answer from
+    /// memory. See `page_cache.rs`'s `fairness_selftest`.
+    page_cache_fairness_selftest = "page-cache-fairness-selftest";
+
+    /// Stream a file off a synthetic disk through the file cache's readahead,
+    /// then seek, and check the window and the batching. A test boot's files
+    /// are written in the boot that reads them, so every page is resident and
+    /// readahead never fetches; and neither the window nor how many device
+    /// calls a read cost is visible outside the kernel. The detector, the
+    /// cache and `NvmeBacking` are the shipped ones; only the disk answers
+    /// from memory. See `file_cache.rs`'s `readahead_selftest`.
+    file_cache_readahead_selftest = "file-cache-readahead-selftest";
+
+    /// Log every background write-back as it completes and say what made it
+    /// happen: a queued file that waited out `iod::EXPIRE`, the dirty set
+    /// passing `DirtyPressure::Background`, or a writer past `Throttle`
+    /// flushing its own file. A program can only see that its bytes reached
+    /// the device eventually, and all three triggers end the same way, so
+    /// without this a test could not tell which one fired, or whether any
+    /// did before `close`. Off on every other boot because `logd`'s own file is
+    /// queued too, and each line would queue it again.
+    iod_trace = "iod-trace";
+
     /// Leave every AP holding the CR0 and CR4 that INIT left it, which is what
     /// every boot before `arch/control_regs.rs` was: caching disabled, WP clear,
     /// NE clear. `control_regs_negative` boots it and holds the verdict against
@@ -148,6 +148,17 @@ pub fn kick_cpu(cpu_id: u32) {
     Reg::Icr.write(((apic_id as u64) << 32) | 0x4000 | TIMER_VECTOR as u64);
 }
 
+/// Ask one CPU to park for a sleep state. The asking only, as with
+/// [`tlb_ipi`]: `arch::sleep` owns the protocol, including what a CPU that
+/// takes this vector while holding a lock does instead of parking. Targeted
+/// rather than broadcast because the answer is per CPU — the initiator asks
+/// again of exactly the ones that have not parked.
+pub(super) fn park_ipi(cpu_id: u32) {
+    if !X2APIC_ENABLED.load(Ordering::Relaxed) { return; }
+    let apic_id = crate::arch::smp::apic_id_for(cpu_id);
+    Reg::Icr.write(((apic_id as u64) << 32) | 0x4000 | 0xFC);
+}
+
 /// Send an NMI to one CPU. Same targeted write as [`kick_cpu`] with delivery
 /// mode NMI (0x400) instead of a vector, and it exists for the one question
 /// [`kick_cpu`] cannot answer: a CPU that spins with interrupts disabled never
@@ -477,7 +488,10 @@ pub fn arm_one_shot(nanos: u64) {
 ///
 /// Traces nothing, unlike [`arm_one_shot`]: no scheduler deadline is being set
 /// here, and a `TimerArm` record would make the trace say one was.
-#[cfg(feature = "boot-actuators")]
+///
+/// Two callers: `diag-tick`'s idle wait, and [`crate::clock::awaits`], which
+/// halts a CPU in a running context and needs a fire by its deadline whether
+/// or not the awaited interrupt comes.
 pub fn arm_within(nanos: u64) {
     let want = OneShot::after(nanos);
     // A running count never reaches zero without the fire that reloads it, so
@@ -189,8 +189,9 @@ pub fn init_cr0(cpu_id: u32) {
 /// 63 of every live paging entry, and the tables this kernel writes are the
 /// ones it is a statement about.
 ///
-/// Before `arch::syscall::init` on both paths — `percpu::init_bsp` runs it from
-/// `main`, `percpu::init_ap` from `ap_entry`, and each is ahead of that call —
+/// Before `arch::syscall::init` on every path — `percpu::init_bsp` runs it from
+/// `main`, `percpu::init_ap` from `ap_entry`, `percpu::init_resume` from the
+/// wake entries in `arch::sleep`, and each is ahead of that call —
 /// which is what lets `SCE` live in the declaration instead of in a
 /// read-modify-write there.
 pub fn init(cpu_id: u32) {
@@ -200,8 +201,10 @@ pub fn init(cpu_id: u32) {
         // clearing `PAE`/`LA57` in long mode, and on raising `PCIDE` while
         // `CR3[11:0]` is non-zero. `declaration` has just asked CPUID for every
         // optional bit and asserted `LA57` is clear; `PAE` is in
-        // `CR4_REQUIRED`; and both call sites of this function run on the kernel
-        // address space, whose PCID is 0.
+        // `CR4_REQUIRED`; and every call site of this function runs with PCID 0
+        // in `CR3` — the boot paths on the kernel address space, and
+        // `percpu::init_resume` on `arch::sleep`'s wake tables, whose root the
+        // trampoline loaded from a 32-bit register.
         //
         // `wrmsr` asks its caller to own the MSR and the value. [`EFER`] is this
         // file's declaration and its doc comment argues all three bits in it and
@@ -11,7 +11,7 @@
 //! The split between `pub fn` and `pub unsafe fn` here is that second half. A
 //! function is `unsafe` when the caller can choose a value that breaks the
 //! machine — `write_cr0`, `write_cr4`, `write_cr3`, `lidt`, `ltr`, `wbinvd`,
-//! `wrmsr`, `outb`, `outw`, `wrfsbase` — and safe when it cannot.
+//! `wrmsr`, `outb`, `outw`, `outl`, `wrfsbase` — and safe when it cannot.
 //!
 //! **Two wrappers take a caller-chosen value and are safe anyway, and each
 //! argues it in its own doc comment rather than in a `SAFETY:` block.**
@@ -20,8 +20,8 @@
 //! `invpcid`'s two faults are removed rather than argued away — the `#GP` on a
 //! descriptor type above 3 is unrepresentable ([`Invpcid`]) and the `#UD` on a
 //! CPU without the feature is an argument the caller can only get by asking
-//! ([`PcidActive`]). [`inb`] is safe because a read has no value for a caller to
-//! get wrong.
+//! ([`PcidActive`]). [`inb`], [`inw`] and [`inl`] are safe because a read has
+//! no value for a caller to get wrong.
 //!
 //! **Where an `unsafe fn` here has a closed set of callers, the honest form is
 //! one safe wrapper that discharges the choice, not an `unsafe` block apiece.**
@@ -232,7 +232,10 @@ pub unsafe fn write_cr4(value: u64) {
 /// Writes back and invalidates every cache level. Only correct inside a no-fill
 /// window — `CR0.CD` set and `CR0.NW` clear — which is SDM Vol. 3A §11.5.3 for a
 /// plain cache-mode change and §11.11.8's MTRR procedure for the PAT write
-/// [`pat::init`](super::pat::init) wraps in one.
+/// [`pat::init`](super::pat::init) wraps in one; or on a CPU whose caches are
+/// about to lose power, which is ACPI's flush before a sleep state
+/// (`arch::sleep`), where the point is exactly that nothing the CPU writes
+/// afterwards has to survive.
 #[inline]
 pub unsafe fn wbinvd() {
     asm!("wbinvd", options(nostack, preserves_flags));
@@ -425,6 +428,26 @@ pub fn halt() -> ! {
     }
 }
 
+/// `sti; hlt`: sleep until the next interrupt this CPU takes, and return
+/// having taken it, with `IF` set.
+///
+/// For a caller that has closed interrupts to check a condition an interrupt
+/// will change, and found it not yet true. The `sti` shadow is the whole of it:
+/// an interrupt pending at the `sti` is taken at the `hlt` and ends it, so the
+/// one that arrived between the check and here is not slept through.
+///
+/// Not `Hw::halt`, which is the same pair between `arch::sleep`'s marks and is
+/// only for a CPU with nothing to run: this is a wait inside a running context,
+/// usually one holding locks, and a park IPI must find it busy.
+pub fn wait_for_interrupt() {
+    // SAFETY: two instructions, no memory. Irreducible **by sequence**, for
+    // `halt`'s reason in reverse: a boundary between the two is a window for
+    // the awaited interrupt to land in before the `hlt` that waits for it.
+    unsafe {
+        asm!("sti; hlt", options(nomem, nostack));
+    }
+}
+
 /// # Safety
 /// The instruction cannot fault in Ring 0 — `CR4.UMIP` does not cover port I/O
 /// and there is no I/O permission bitmap, because `Tss::iopb_offset` is past the
@@ -457,6 +480,17 @@ pub fn inb(port: u16) -> u8 {
     value
 }
 
+/// Two bytes from an I/O port, for the same reason [`inb`] is safe.
+#[inline]
+pub fn inw(port: u16) -> u16 {
+    let value: u16;
+    // SAFETY: as `inb`'s, sixteen bits wide.
+    unsafe {
+        asm!("in ax, dx", out("ax") value, in("dx") port);
+    }
+    value
+}
+
 /// # Safety
 /// [`outb`]'s contract, sixteen bits wide.
 #[inline]
@@ -464,6 +498,24 @@ pub unsafe fn outw(port: u16, value: u16) {
     asm!("out dx, ax", in("dx") port, in("ax") value);
 }
 
+/// Four bytes from an I/O port, for the same reason [`inb`] is safe.
+#[inline]
+pub fn inl(port: u16) -> u32 {
+    let value: u32;
+    // SAFETY: as `inb`'s, thirty-two bits wide.
+    unsafe {
+        asm!("in eax, dx", out("eax") value, in("dx") port);
+    }
+    value
+}
+
+/// # Safety
+/// [`outb`]'s contract, thirty-two bits wide.
+#[inline]
+pub unsafe fn outl(port: u16, value: u32) {
+    asm!("out dx, eax", in("dx") port, in("eax") value);
+}
+
 /// One I/O bus cycle of delay, for a device that needs one between two commands.
 #[inline]
 pub fn io_wait() {
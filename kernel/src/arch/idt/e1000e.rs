@@ -0,0 +1,22 @@
+use super::device_irq::device_irq_entry;
+use crate::irq_ring::IrqSource;
+
+/// Rust half of the MSI-X (or MSI) handler. Lock-free and heap-free, and the
+/// same record virtio-net's publishes: the consumer wakes netd, which drains
+/// whichever NIC `net` holds, so nothing downstream of this line knows the
+/// frame came off an 82574.
+///
+/// The interrupt cause is not read or cleared here. `ICR` is acknowledged by
+/// the drain, after it has found the ring empty, which is the one point where
+/// clearing it cannot lose a frame.
+extern "sysv64" fn e1000e_handler() {
+    let timestamp = crate::clock::nanos_since_boot();
+    crate::irq_ring::isr_publish(IrqSource::Net, timestamp);
+    crate::preempt::set_need_resched();
+    crate::arch::apic::eoi();
+}
+
+device_irq_entry! {
+    /// e1000e receive entry (see `device_irq_entry` for the asm contract).
+    pub(super) fn e1000e_entry => e1000e_handler
+}
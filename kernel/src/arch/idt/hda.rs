@@ -9,6 +9,7 @@ extern "sysv64" fn hda_handler() {
 }
 
 device_irq_entry! {
-    /// HDA stream MSI entry (see `device_irq_entry` for the asm contract).
+    /// HDA stream interrupt entry, message or pin (see `device_irq_entry` for
+    /// the asm contract).
     pub(super) fn hda_entry => hda_handler
 }
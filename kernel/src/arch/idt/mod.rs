@@ -1,15 +1,21 @@
 pub(crate) mod exceptions;
 mod device_irq;
 mod dma_fault;
+mod e1000e;
 mod hda;
 mod i8042;
 #[cfg(feature = "boot-actuators")]
 mod log_nest;
 mod nmi;
+mod nvme;
+mod park;
+mod sci;
 mod timer;
 mod tlb;
+mod virtio_input;
 mod virtio_net;
 mod virtio_sound;
+mod virtio_vsock;
 mod xhci;
 
 use core::arch::naked_asm;
@@ -36,15 +42,36 @@ pub const I8042_VECTOR: u8 = Vector::I8042 as u8;
 /// reason: the unit is told which vector to raise, and only one place knows.
 pub const DMA_FAULT_VECTOR: u8 = Vector::DmaFault as u8;
 
-/// The vector the HDA controller's message-signalled interrupt carries. Public
-/// for the same reason: the driver arms whichever of MSI-X and MSI the function
-/// offers, and only one place knows the number.
+/// The vector the HDA controller's interrupt carries. Public for the same
+/// reason: the driver arms whichever of MSI-X, MSI and its INTx pin the
+/// function offers, and only one place knows the number.
 pub const HDA_VECTOR: u8 = Vector::Hda as u8;
 
 /// The vector the virtio-sound device's MSI-X entry carries, for the same
 /// reason.
 pub const VIRTIO_SOUND_VECTOR: u8 = Vector::VirtioSound as u8;
 
+/// The vector every NVMe queue's MSI-X entry carries. One number for all of
+/// them: the entries differ in which CPU they are routed to, and the CPU that
+/// takes the interrupt is how the handler knows which queue raised it.
+pub const NVME_VECTOR: u8 = Vector::Nvme as u8;
+
+/// The vector every virtio-input device's MSI-X entry carries. One for all of
+/// them, for the reason the handler gives.
+pub const VIRTIO_INPUT_VECTOR: u8 = Vector::VirtioInput as u8;
+
+/// The vector the virtio-vsock device's MSI-X entry carries. Its receive and
+/// event queues share it, for the reason the handler gives.
+pub const VIRTIO_VSOCK_VECTOR: u8 = Vector::VirtioVsock as u8;
+
+/// The vector the e1000e's receive interrupt carries, whichever of MSI-X and
+/// MSI the function offers.
+pub const E1000E_VECTOR: u8 = Vector::E1000e as u8;
+
+/// The vector the ACPI SCI's I/O APIC entry carries. Public for the reason
+/// [`I8042_VECTOR`] is.
+pub const SCI_VECTOR: u8 = Vector::Sci as u8;
+
 /// The vector `log-nested-emit` sends itself (§9.2), and the one gate that is
 /// not in the table below.
 ///
@@ -54,7 +81,7 @@ pub const VIRTIO_SOUND_VECTOR: u8 = Vector::VirtioSound as u8;
 /// is `direct` in every sense the table means — its own entry, never
 /// `trap_dispatch` — and it sits one past the last device vector.
 #[cfg(feature = "boot-actuators")]
-pub const LOG_NEST_VECTOR: u8 = 0x27;
+pub const LOG_NEST_VECTOR: u8 = 0x2C;
 
 // Page fault error code bits
 const PF_PRESENT: u64 = 1 << 0;
@@ -212,7 +239,7 @@ macro_rules! exception_stub {
 /// `from_raw` runs on the crash path and that path may not panic.
 ///
 /// A `direct` vector is its own naked entry and never reaches
-/// [`trap_dispatch`]: the device IRQs, the halt IPI, the shootdown IPI, and the
+/// [`trap_dispatch`]: the device IRQs, the halt, shootdown and park IPIs, and the
 /// NMI, whose handler must not touch the preempt count or reschedule.
 ///
 /// Each `direct` row also answers whether its handler can reach another task
@@ -334,6 +361,14 @@ idt_vectors! {
         ring3 I8042        = 0x24, i8042::i8042_entry;
         ring3 DmaFault     = 0x25, dma_fault::dma_fault_entry;
         ring3 Hda          = 0x26, hda::hda_entry;
+        ring3 Nvme         = 0x27, nvme::nvme_entry;
+        ring3 VirtioInput  = 0x28, virtio_input::virtio_input_entry;
+        ring3 VirtioVsock  = 0x29, virtio_vsock::virtio_vsock_entry;
+        ring3 E1000e       = 0x2A, e1000e::e1000e_entry;
+        ring3 Sci          = 0x2B, sci::sci_entry;
+        // Ring 3 like `TlbFlush`: a CPU parks for a sleep state from Ring 3
+        // as often as from anywhere, and leaves through the same epilogue.
+        ring3 Park         = 0xFC, park::park_entry;
         // Ring 0 because it never returns: `cli; hlt` forever.
         ring0 HaltAll      = 0xFD, stub_halt_all;
         ring3 TlbFlush     = 0xFE, tlb::tlb_flush_entry;
@@ -465,6 +500,16 @@ pub(crate) extern "sysv64" fn kernel_exit_to_user_check() {
         // kernel stack is empty here by definition, so this is where the
         // unwind ends.
         crate::scheduler::exit_if_killed();
+        // A thread on its way to Ring 3 while a sleep state is being entered
+        // stops here instead, at a syscall's depth because that is what a
+        // blocking site is entitled to, and is checked for a kill again when
+        // the thaw lets it go.
+        if crate::suspend::freezing() {
+            crate::preempt::disable();
+            crate::suspend::freeze();
+            crate::preempt::enable_no_resched();
+            continue;
+        }
         if !crate::preempt::need_resched() {
             return;
         }
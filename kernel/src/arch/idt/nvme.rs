@@ -0,0 +1,15 @@
+use super::device_irq::device_irq_entry;
+
+/// Rust half of the NVMe queue-completion handler. Lock-free and heap-free: it
+/// may interrupt a CPU that holds its own queue's lock, which disables
+/// preemption and not interrupts.
+extern "sysv64" fn nvme_handler() {
+    crate::drivers::nvme::isr_complete();
+    crate::arch::apic::eoi();
+}
+
+device_irq_entry! {
+    /// NVMe per-CPU queue MSI-X entry (see `device_irq_entry` for the asm
+    /// contract).
+    pub(super) fn nvme_entry => nvme_handler
+}
@@ -0,0 +1,58 @@
+use crate::arch::entry::{restore_user_state, ring3_naked_asm, save_user_state};
+
+/// Park IPI handler, for a sleep state.
+///
+/// The same shape as `tlb_flush_entry`, for the same reasons. The one thing
+/// that differs is how long the call can take: a CPU that parks stays inside
+/// it until the machine has slept and woken, and comes back out of it from a
+/// different stack — `arch::sleep` returns it into the frame it left, so from
+/// here the call is an ordinary one that happened to be long.
+#[unsafe(naked)]
+pub(super) extern "sysv64" fn park_entry() {
+    ring3_naked_asm!(
+        "push rax",
+        "push rcx",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push r8",
+        "push r9",
+        "push r10",
+        "push r11",
+        "push rbp",
+        "lock add dword ptr gs:[240], 1",
+        "mov rbp, rsp",
+        "and rsp, -16",
+        "call {park}",
+        "mov rsp, rbp",
+        "mov ecx, 0x80B",
+        "xor eax, eax",
+        "xor edx, edx",
+        "wrmsr",
+        "lock sub dword ptr gs:[240], 1",
+        "test dword ptr [rsp + 88], 3",
+        "jz 1f",
+        "cli",
+        save_user_state!(),
+        "call {exit_to_user}",
+        restore_user_state!(),
+        "1:",
+        "pop rbp",
+        "pop r11",
+        "pop r10",
+        "pop r9",
+        "pop r8",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop rcx",
+        "pop rax",
+        "iretq",
+        park = sym park,
+        exit_to_user = sym crate::arch::idt::kernel_exit_to_user_check,
+    );
+}
+
+fn park() {
+    crate::arch::sleep::serve_park_ipi();
+}
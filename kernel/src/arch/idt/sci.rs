@@ -0,0 +1,8 @@
+use super::device_irq::device_irq_entry;
+
+device_irq_entry! {
+    /// ACPI SCI pin-interrupt entry (see `device_irq_entry` for the asm
+    /// contract). The line is level-triggered, so the Rust half acknowledges
+    /// every event it finds before the EOI and says why it has to.
+    pub(super) fn sci_entry => crate::drivers::sci::handler
+}
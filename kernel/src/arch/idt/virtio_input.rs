@@ -0,0 +1,23 @@
+use super::device_irq::device_irq_entry;
+use crate::irq_ring::IrqSource;
+
+/// Rust half of the MSI-X handler. Lock-free and heap-free — the event queues
+/// are drained by the record's consumer (`sched::driver::drain_irqs` →
+/// `virtio_input::service`), never here.
+///
+/// Every virtio-input device raises this vector, and the record does not say
+/// which: a keyboard and a tablet are two devices and one source, and the
+/// drain asks each of them.
+extern "sysv64" fn virtio_input_handler() {
+    let timestamp = crate::clock::nanos_since_boot();
+    crate::irq_ring::isr_publish(IrqSource::Input, timestamp);
+    // Force a scheduler entry on IRQ return so the keystroke reaches its
+    // reader now, not at the next 10ms quantum tick.
+    crate::preempt::set_need_resched();
+    crate::arch::apic::eoi();
+}
+
+device_irq_entry! {
+    /// Virtio-input MSI-X entry (see `device_irq_entry` for the asm contract).
+    pub(super) fn virtio_input_entry => virtio_input_handler
+}
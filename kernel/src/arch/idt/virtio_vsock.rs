@@ -0,0 +1,21 @@
+use super::device_irq::device_irq_entry;
+use crate::irq_ring::IrqSource;
+
+/// Rust half of the MSI-X handler. Lock-free and heap-free — the queues are
+/// drained by the record's consumer (`sched::driver::drain_irqs` →
+/// `virtio_vsock::service`), never here.
+///
+/// The receive, transmit and event queues all raise this vector and the record
+/// does not say which: the drain checks all three, which costs two empty
+/// used-ring reads on the common interrupt.
+extern "sysv64" fn virtio_vsock_handler() {
+    let timestamp = crate::clock::nanos_since_boot();
+    crate::irq_ring::isr_publish(IrqSource::Vsock, timestamp);
+    crate::preempt::set_need_resched();
+    crate::arch::apic::eoi();
+}
+
+device_irq_entry! {
+    /// Virtio-vsock MSI-X entry (see `device_irq_entry` for the asm contract).
+    pub(super) fn virtio_vsock_entry => virtio_vsock_handler
+}
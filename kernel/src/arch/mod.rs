@@ -26,6 +26,7 @@ pub mod idt;
 pub mod mtrr;
 pub mod pat;
 pub mod percpu;
+pub mod sleep;
 pub mod smp;
 pub mod syscall;
 pub mod tlb;
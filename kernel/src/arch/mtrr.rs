@@ -1,8 +1,13 @@
 //! What memory type firmware gave a physical range.
 //!
-//! Read-only: these registers are firmware's, and the kernel programs none of
-//! them. It reads them because they are half of the answer to what a mapping
-//! costs. The other half is the PAT entry the page selects
+//! Read-only while the machine is up: these registers are firmware's, and the
+//! kernel programs none of them. The one write is [`restore`], putting back on
+//! a CPU that has been through S3 exactly what [`save`] read from it before —
+//! firmware re-programs the CPU that runs its wake path and nothing else, so an
+//! AP would otherwise come back with every range UC.
+//!
+//! It reads them because they are half of the answer to what a mapping costs.
+//! The other half is the PAT entry the page selects
 //! ([`crate::arch::pat`]), and every mapping made with
 //! [`CachePolicy::DeferToMtrr`](crate::mm::paging::CachePolicy) selects entry
 //! 0 — WB, the entry that takes whatever the MTRR says (SDM Vol. 3A
@@ -11,11 +16,20 @@
 //! The exception is the one range that asks for [`effective_under_wc`], where
 //! the MTRR is *outvoted* rather than consulted.
 
+use alloc::vec::Vec;
+
 use crate::arch::cpu;
 
 const IA32_MTRRCAP: u32 = 0xFE;
 const IA32_MTRR_DEF_TYPE: u32 = 0x2FF;
 const IA32_MTRR_PHYSBASE0: u32 = 0x200;
+/// The eleven fixed-range MTRRs that describe the first 1 MiB: one 64 KiB, two
+/// 16 KiB and eight 4 KiB registers (SDM Vol. 3A §11.11.2.2).
+const FIXED: [u32; 11] = [0x250, 0x258, 0x259, 0x268, 0x269, 0x26A, 0x26B, 0x26C, 0x26D, 0x26E, 0x26F];
+/// Bit 8 of `IA32_MTRRCAP`: the fixed-range registers exist.
+const CAP_FIXED: u64 = 1 << 8;
+/// Bit 10 of `IA32_MTRR_DEF_TYPE`: fixed-range MTRRs are consulted.
+const DEF_TYPE_FIXED_ENABLE: u64 = 1 << 10;
 /// Bit 11 of `IA32_MTRR_DEF_TYPE`: variable and fixed MTRRs are consulted at
 /// all. Cleared means the whole address space is UC, whatever the ranges say.
 const DEF_TYPE_ENABLE: u64 = 1 << 11;
@@ -91,6 +105,74 @@ impl Effective {
     }
 }
 
+/// One CPU's MTRRs, as firmware left them, for [`restore`] to put back.
+pub struct Saved {
+    def_type: u64,
+    fixed: Option<[u64; 11]>,
+    /// `(PHYSBASE, PHYSMASK)` per variable range, in register order.
+    variable: Vec<(u64, u64)>,
+}
+
+/// Read this CPU's MTRRs.
+pub fn save() -> Saved {
+    let cap = cpu::rdmsr(IA32_MTRRCAP);
+    Saved {
+        def_type: cpu::rdmsr(IA32_MTRR_DEF_TYPE),
+        fixed: (cap & CAP_FIXED != 0).then(|| FIXED.map(cpu::rdmsr)),
+        variable: (0..(cap & 0xFF) as u32)
+            .map(|i| {
+                (cpu::rdmsr(IA32_MTRR_PHYSBASE0 + i * 2), cpu::rdmsr(IA32_MTRR_PHYSBASE0 + i * 2 + 1))
+            })
+            .collect(),
+    }
+}
+
+/// Write `saved` back into this CPU's MTRRs, with interrupts off.
+///
+/// SDM Vol. 3A §11.11.7.2's procedure, which is [`pat::init`](super::pat::init)'s
+/// window with the MTRRs switched off inside it: a range register rewritten
+/// while `MTRRdefType.E` is set is briefly half one type and half another.
+/// §11.11.8 wants every CPU inside the window at once, and it does not get it
+/// here — it gets something stronger. The only caller is a wake entry, run
+/// before this CPU has touched anything another CPU could be caching, and the
+/// values are the ones every CPU held before the sleep.
+pub fn restore(saved: &Saved) {
+    // SAFETY: **one block, for `pat::init`'s reason** — everything between the
+    // two `write_cr0`s is inside the no-fill window the first one opens, which
+    // is `wbinvd`'s requirement and the procedure itself. `write_cr0` gets the
+    // live `CR0` with `CD`/`NW` moved, then that live value back. `flush_tlb`
+    // gets the live `CR4`. Every `wrmsr` targets an MSR `save` just read on a
+    // CPU of the same model — the ones `IA32_MTRRCAP` says exist — with the
+    // value it read there, and `MTRRdefType` is written cleared first so no
+    // half-written range is ever consulted.
+    unsafe {
+        let cr0 = cpu::read_cr0();
+        let cr4 = cpu::read_cr4();
+        cpu::write_cr0((cr0 | CR0_CD) & !CR0_NW);
+        cpu::wbinvd();
+        super::pat::flush_tlb(cr4);
+        cpu::wrmsr(IA32_MTRR_DEF_TYPE, saved.def_type & !(DEF_TYPE_ENABLE | DEF_TYPE_FIXED_ENABLE));
+
+        if let Some(fixed) = &saved.fixed {
+            for (&msr, &value) in FIXED.iter().zip(fixed) {
+                cpu::wrmsr(msr, value);
+            }
+        }
+        for (i, &(base, mask)) in (0u32..).zip(&saved.variable) {
+            cpu::wrmsr(IA32_MTRR_PHYSBASE0 + i * 2, base);
+            cpu::wrmsr(IA32_MTRR_PHYSBASE0 + i * 2 + 1, mask);
+        }
+
+        cpu::wbinvd();
+        super::pat::flush_tlb(cr4);
+        cpu::wrmsr(IA32_MTRR_DEF_TYPE, saved.def_type);
+        cpu::write_cr0(cr0);
+    }
+}
+
+const CR0_NW: u64 = 1 << 29;
+const CR0_CD: u64 = 1 << 30;
+
 /// The memory type of a page whose PAT entry holds WC, over a range whose MTRR
 /// state is `mtrr`.
 ///
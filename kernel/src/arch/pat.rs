@@ -128,7 +128,7 @@ const RFLAGS_IF: u64 = 1 << 9;
 /// `cr4` must be this CPU's live `CR4`: both arms put it back verbatim, so a
 /// value from anywhere else would silently become the machine configuration —
 /// which is [`cpu::write_cr4`]'s requirement and not a new one.
-unsafe fn flush_tlb(cr4: u64) {
+pub(super) unsafe fn flush_tlb(cr4: u64) {
     if cr4 & CR4_PGE != 0 {
         cpu::write_cr4(cr4 & !CR4_PGE);
         cpu::write_cr4(cr4);
@@ -172,7 +172,10 @@ impl PerCpu {
     /// Load this CPU's GDT, reload segment registers, and load TSS.
     ///
     /// # Safety
-    /// Must be called exactly once per CPU during init.
+    /// Must be called exactly once per CPU each time it comes out of reset —
+    /// during init, and again by [`init_resume`] after a sleep state — and only
+    /// after [`init_tss_descriptor`](Self::init_tss_descriptor): `ltr` marks
+    /// the descriptor busy, and is `#GP` on one that already is.
     unsafe fn load_gdt(&self) {
         let ptr = GdtPointer {
             limit: (size_of::<[u64; 7]>() - 1) as u16,
@@ -888,8 +891,9 @@ pub fn init_bsp(lapic_id: u32) {
     alloc_idle_stack(percpu);
     alloc_ist_stacks(percpu);
 
-    // SAFETY: `load_gdt` asks to be called exactly once per CPU during init, and
-    // this is the BSP's one call — `init_ap` is every AP's. The GDT and TSS it
+    // SAFETY: `load_gdt` asks to be called exactly once per CPU out of reset,
+    // after the TSS descriptor is built, and this is the BSP's boot call —
+    // `init_ap` is every AP's, and `alloc_percpu` built the descriptor. The GDT and TSS it
     // loads are this `PerCpu`'s own, filled by `alloc_percpu` and
     // `alloc_ist_stacks` above.
     unsafe { percpu.load_gdt(); }
@@ -938,14 +942,39 @@ pub fn init_ap(percpu_ptr: *mut PerCpu) {
     // `IA32_GS_BASE`; the BSP built it in `alloc_ap` and dropped its `&mut`
     // before sending the SIPI, so this is again the only reference to it.
     let percpu = unsafe { &mut *percpu_ptr };
-    // SAFETY: `load_gdt` asks to be called exactly once per CPU during init, and
-    // this is that call for this AP — `init_bsp` is the BSP's.
+    // SAFETY: `load_gdt` asks to be called exactly once per CPU out of reset,
+    // after the TSS descriptor is built, and this is that call for this AP —
+    // `init_bsp` is the BSP's, and `alloc_percpu` built the descriptor.
     unsafe { percpu.load_gdt(); }
     super::control_regs::init(percpu.cpu_id);
     super::fpu::init();
     super::fpu::log_state();
 }
 
+/// Put this CPU's GDT, TSS, `CR4`/`EFER` and FPU back after S3, from the
+/// `PerCpu` that stayed in RAM across it. `gs:` is already this CPU's: the wake
+/// trampoline wrote `IA32_GS_BASE` before `lidt`, as it does for `ap_entry`.
+///
+/// The TSS descriptor is rebuilt first because the copy in the GDT still says
+/// busy from the `ltr` at boot. Nothing here prints — `arch::sleep` logs one
+/// line for the whole wake, once the serial port is back.
+pub fn init_resume() {
+    // SAFETY: `init_ap`'s argument, made on the way back: the pointer is this
+    // CPU's own `PerCpu` out of `gs:[0]`, and the only other code that ever
+    // took `&mut` to it ran before this CPU was parked and has long returned.
+    // The wake entry that calls this runs with `IF` clear on a stack that is
+    // not the one the parked context lives on, so nothing else holds a
+    // reference while this one does.
+    let percpu = unsafe { &mut *percpu_ptr() };
+    percpu.init_tss_descriptor();
+    // SAFETY: `load_gdt` asks to be called once per CPU out of reset with the
+    // TSS descriptor rebuilt, and S3 was the reset: this is that call, after
+    // the rebuild on the line above.
+    unsafe { percpu.load_gdt(); }
+    super::control_regs::init(percpu.cpu_id);
+    super::fpu::init();
+}
+
 /// Update both the percpu kernel_rsp (for syscall entry) and tss.rsp0 (for interrupts).
 /// Called during context switch when switching to a new process.
 ///
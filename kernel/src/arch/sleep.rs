@@ -0,0 +1,488 @@
+//! S3 as the CPUs see it: park every CPU but one, put the last one to sleep,
+//! and on the way back bring each of them up from reset and into exactly the
+//! instruction it left.
+//!
+//! **A CPU's whole state is a return from a call.** [`suspend_call`] saves the
+//! callee-saved registers, the stack, the return address and `RFLAGS` — what a
+//! function call promises to give back — and [`resume_from`] gives them back
+//! with a value in `rax`, from whichever stack it was called on. So a CPU that
+//! never slept and a CPU that firmware powered off and this module brought back
+//! through the trampoline both return from the same call: [`STAYED`] or
+//! [`WOKE`]. Everything else a CPU held is either in RAM already (its stacks,
+//! its `PerCpu`, the page tables) or is put back by the wake entry from the
+//! same place boot sets it from: GDT and TSS, `CR0`/`CR4`/`EFER`, PAT, the
+//! `SYSCALL` MSRs and the LAPIC. The MTRRs and the FPU image are the two that
+//! have no boot-time source to repeat, so they are saved.
+//!
+//! **Parking waits for a CPU that holds nothing.** The park IPI is answered
+//! only at depth one — the gate's own increment over a context holding no
+//! [`Lock`](crate::sync::Lock), which is Ring 3, a kernel thread between locks,
+//! or the idle halt (whose depth is the pass's, and which [`halting`] marks).
+//! Anywhere else the handler returns and the initiator asks again a
+//! millisecond later: a CPU parked holding the heap lock, or a driver's, would
+//! hang the suspending CPU at the first allocation on its way down. Syscalls
+//! run with `IF` clear, so a CPU inside one answers at its next block or its
+//! return, which after the freezer is soon.
+//!
+//! **The wake runs on tables of its own.** Firmware enters the waking vector in
+//! real mode on the boot CPU, and the trampoline's 32-bit half loads `CR3` from
+//! a 32-bit word, so the root has to sit low and identity-map the trampoline:
+//! three pages at `0x9000..0xC000`, reserved next to the trampoline's own, with
+//! the kernel half copied from the kernel's root when the machine goes down.
+
+use core::arch::naked_asm;
+use core::hint::spin_loop;
+use core::mem::offset_of;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, Ordering};
+
+use super::{apic, control_regs, cpu, mtrr, pat, percpu, smp, syscall, tlb};
+use crate::clock;
+use crate::log;
+use crate::scheduler::MAX_CPUS;
+use crate::time::{Budget, Cadence, Duration};
+
+/// Where firmware jumps on the way back: the trampoline page, whose real-mode
+/// entry a SIPI and a waking vector reach alike.
+pub const WAKE_VECTOR: u32 = 0x8000;
+/// The wake tables' PML4, PDPT and PD, one page each.
+const WAKE_ROOT: u64 = 0x9000;
+const WAKE_PDPT: u64 = 0xA000;
+const WAKE_PD: u64 = 0xB000;
+
+/// What [`suspend_call`] returns to a CPU that never lost power.
+const STAYED: u64 = 0;
+/// What it returns to one brought back through the trampoline.
+const WOKE: u64 = 1;
+
+/// What a call promises to give back, in the order the assembly below names.
+/// The FPU image first, because `fxsave64` wants it 16-byte aligned.
+#[repr(C, align(16))]
+struct Regs {
+    fx: [u8; 512],
+    rbx: u64,
+    rbp: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rsp: u64,
+    rip: u64,
+    rflags: u64,
+}
+
+const _: () = {
+    assert!(offset_of!(Regs, fx) == 0x000);
+    assert!(offset_of!(Regs, rbx) == 0x200);
+    assert!(offset_of!(Regs, rbp) == 0x208);
+    assert!(offset_of!(Regs, r12) == 0x210);
+    assert!(offset_of!(Regs, r13) == 0x218);
+    assert!(offset_of!(Regs, r14) == 0x220);
+    assert!(offset_of!(Regs, r15) == 0x228);
+    assert!(offset_of!(Regs, rsp) == 0x230);
+    assert!(offset_of!(Regs, rip) == 0x238);
+    assert!(offset_of!(Regs, rflags) == 0x240);
+};
+
+/// One CPU's way back: the registers, and what the wake entry puts back before
+/// it returns into them.
+struct Context {
+    regs: Regs,
+    cr3: u64,
+    fs_base: u64,
+    /// This CPU's `PerCpu`, for the trampoline to put in `GS` when the waking
+    /// CPU restarts it.
+    percpu: u64,
+    mtrr: mtrr::Saved,
+}
+
+impl Context {
+    fn capture() -> Self {
+        Self {
+            regs: Regs {
+                fx: [0; 512],
+                rbx: 0,
+                rbp: 0,
+                r12: 0,
+                r13: 0,
+                r14: 0,
+                r15: 0,
+                rsp: 0,
+                rip: 0,
+                rflags: 0,
+            },
+            cr3: cpu::read_cr3(),
+            fs_base: cpu::rdfsbase(),
+            percpu: percpu::percpu_ptr() as u64,
+            mtrr: mtrr::save(),
+        }
+    }
+}
+
+/// Each CPU's [`Context`] while it is parked or asleep, on its own stack.
+/// Null otherwise, and null is what the wake reads as "this CPU was not down".
+static SAVED: [AtomicPtr<Context>; MAX_CPUS] = [const { AtomicPtr::new(null_mut()) }; MAX_CPUS];
+/// Each CPU that has answered [`park_others`], caches flushed.
+static PARKED: [AtomicBool; MAX_CPUS] = [const { AtomicBool::new(false) }; MAX_CPUS];
+/// Each CPU sitting in [`Hw::halt`](crate::hw)'s `sti; hlt`, which is where an
+/// idle CPU takes the park IPI — at the depth of the pass around it, holding
+/// nothing.
+static HALTED: [AtomicBool; MAX_CPUS] = [const { AtomicBool::new(false) }; MAX_CPUS];
+/// A park IPI is wanted. Cleared by [`release_others`], so a retry that lands
+/// after the release is ignored.
+static PARKING: AtomicBool = AtomicBool::new(false);
+/// Parked CPUs may go.
+static RELEASED: AtomicBool = AtomicBool::new(false);
+/// CPUs inside [`serve_park_ipi`], counted before `PARKING` is read so that
+/// [`release_others`] cannot miss one that read it just before the release.
+static INSIDE: AtomicU32 = AtomicU32::new(0);
+/// The waking CPU's stack, and the one restarted APs take turns on. Allocated
+/// on the first sleep and kept: both are needed again at every wake.
+static WAKE_STACK: AtomicU64 = AtomicU64::new(0);
+static AP_WAKE_STACK: AtomicU64 = AtomicU64::new(0);
+
+/// How long [`park_others`] asks before it names a CPU that will not park.
+///
+/// **A [`Budget`]: expiry is a refusal that says which CPU.** The sleep is
+/// abandoned before any driver has been touched, every parked CPU is released,
+/// and the machine carries on. What it measures is a CPU that stayed inside
+/// one syscall or one lock for this long after userland was frozen, which is
+/// not a thing to sleep through.
+const PARK: Budget =
+    Budget::of(Duration::from_secs(2), "the sleep is refused, naming the CPU that did not park");
+
+/// How often a CPU that has not parked is asked again. Fast against a CPU on
+/// its way out of a syscall; slow against the IPI itself.
+const ASK_AGAIN: Cadence =
+    Cadence::every(Duration::from_millis(1), "a park IPI to each CPU that has not parked");
+
+/// How long the suspending CPU waits after `SLP_EN` for the power to go.
+///
+/// **A [`Budget`] because a chipset can decline without a word.** QEMU with S3
+/// disabled ignores the write, and so does a board whose `\_S3_` is stale; the
+/// CPU simply carries on. Expiry returns the CPU into its own context as
+/// [`STAYED`], and the caller puts the drivers back the way a wake would.
+const ENTRY: Budget = Budget::of(
+    Duration::from_secs(1),
+    "the sleep is reported as not entered and the machine resumes awake",
+);
+
+/// Mark this CPU as inside the idle halt, or out of it. `Hw::halt` brackets its
+/// `sti; hlt` with the two, and holds nothing between them.
+pub fn halting(on: bool) {
+    HALTED[percpu::cpu_id() as usize].store(on, Ordering::Relaxed);
+}
+
+/// The park IPI's Rust half. Parks this CPU if a sleep is being prepared and
+/// the interrupted context holds nothing; returns at once otherwise.
+pub fn serve_park_ipi() {
+    INSIDE.fetch_add(1, Ordering::SeqCst);
+    let me = percpu::cpu_id() as usize;
+    let holds_nothing = crate::preempt::count() == 1 || HALTED[me].load(Ordering::Relaxed);
+    if PARKING.load(Ordering::SeqCst) && holds_nothing {
+        park_here(me);
+    }
+    INSIDE.fetch_sub(1, Ordering::Release);
+}
+
+fn park_here(me: usize) {
+    let mut context = Context::capture();
+    let ctx: *mut Context = &mut context;
+    SAVED[me].store(ctx, Ordering::Release);
+    // SAFETY: `suspend_call` asks for a live `Regs` to save into and a `then`
+    // that never returns into this frame, and gets both: `ctx` is the local
+    // above, which outlives the call because the call returns here; `park_spin`
+    // leaves only through `resume_from` on that same `Regs`.
+    let how = unsafe { suspend_call(&raw mut (*ctx).regs, park_spin, ctx as u64) };
+    if how == WOKE {
+        wait_for_release();
+    }
+    SAVED[me].store(null_mut(), Ordering::Release);
+    PARKED[me].store(false, Ordering::Release);
+    if how == WOKE {
+        // The timer the scheduler had armed went with the power. Any fire
+        // restarts the pass, which arms the real deadline.
+        apic::arm_one_shot(toyos_sched::fair::QUANTUM_NS);
+    }
+}
+
+extern "C" fn park_spin(ctx: u64) -> ! {
+    let ctx = ctx as *const Context;
+    // SAFETY: `wbinvd`'s second case — this CPU's caches may lose power at any
+    // instruction from here on, and what matters is that every line it dirtied
+    // before parking is in RAM when they do. The spin below writes nothing of
+    // its own; a line it pulls for `RELEASED` or a shootdown is one coherence
+    // already moves back out to the CPU that reads it.
+    unsafe { cpu::wbinvd() };
+    PARKED[percpu::cpu_id() as usize].store(true, Ordering::Release);
+    wait_for_release();
+    // SAFETY: `ctx` is `park_here`'s `Context`, live in the frame this call
+    // returns into; `park_here` made it and `suspend_call` filled its `Regs`.
+    unsafe { resume_from(&raw const (*ctx).regs, STAYED, core::ptr::null()) }
+}
+
+fn wait_for_release() {
+    while !RELEASED.load(Ordering::Acquire) {
+        tlb::poll();
+        spin_loop();
+    }
+}
+
+/// Park every CPU but this one, or name the first that would not.
+///
+/// The caller runs with `IF` clear, and so does every CPU that parks: from
+/// here until [`release_others`] nothing but this CPU runs an instruction of
+/// the kernel's, and shootdowns are still answered from both sides' spins.
+pub fn park_others() -> Result<(), u32> {
+    let me = percpu::cpu_id();
+    RELEASED.store(false, Ordering::Release);
+    PARKING.store(true, Ordering::SeqCst);
+    let others = || (0..smp::cpu_count()).filter(move |&cpu| cpu != me);
+    let deadline = clock::nanos_since_boot() + PARK.nanos();
+    loop {
+        let Some(missing) = others().find(|&cpu| !PARKED[cpu as usize].load(Ordering::Acquire))
+        else {
+            return Ok(());
+        };
+        if clock::nanos_since_boot() >= deadline {
+            release_others();
+            return Err(missing);
+        }
+        for cpu in others().filter(|&cpu| !PARKED[cpu as usize].load(Ordering::Acquire)) {
+            apic::park_ipi(cpu);
+        }
+        let retry = clock::nanos_since_boot() + ASK_AGAIN.nanos();
+        while clock::nanos_since_boot() < retry {
+            tlb::poll();
+            spin_loop();
+        }
+    }
+}
+
+/// Let every parked CPU go, and return once none is left inside the handler.
+pub fn release_others() {
+    PARKING.store(false, Ordering::SeqCst);
+    RELEASED.store(true, Ordering::Release);
+    while INSIDE.load(Ordering::SeqCst) != 0 {
+        tlb::poll();
+        spin_loop();
+    }
+}
+
+/// What [`enter_sleep`] needs: where to return to, and the write that sleeps.
+struct Sleeping {
+    regs: *const Regs,
+    enter: fn(),
+}
+
+/// Put this CPU to sleep with `enter`, every other CPU parked, and return
+/// whether it woke — `false` is a chipset that let [`ENTRY`] pass without
+/// taking the power, and the machine is then exactly as it was.
+///
+/// The caller has written the waking vector and brought every device down;
+/// `enter` is the `PM1_CNT` write and nothing else. On `true` the machine has
+/// been through a reset: every CPU is back in its own context with its
+/// registers as boot set them, the clock re-based, and the devices as the
+/// sleep left them — which is to say not yet re-initialised.
+pub fn sleep(enter: fn()) -> bool {
+    let me = percpu::cpu_id() as usize;
+    let mut context = Context::capture();
+    SAVED[me].store(&raw mut context, Ordering::Release);
+
+    build_wake_tables();
+    let waker = SAVED[0].load(Ordering::Acquire);
+    assert!(!waker.is_null(), "S3: the boot CPU is neither parked nor the one sleeping");
+    // SAFETY: non-null entries of `SAVED` point at a live `Context` on a
+    // parked CPU's stack, or at this CPU's own above; none is freed before
+    // `release_others`, which runs after this returns.
+    let waker_percpu = unsafe { (*waker).percpu };
+    smp::prepare_wake(WAKE_ROOT, stack(&WAKE_STACK), wake_entry, waker_percpu);
+
+    let sleeping = Sleeping { regs: &raw const context.regs, enter };
+    // SAFETY: `park_here`'s argument: `context` outlives the call, and
+    // `enter_sleep` leaves only through `resume_from` on its `Regs` — or, if
+    // the power goes, `wake_entry` does.
+    let how =
+        unsafe { suspend_call(&raw mut context.regs, enter_sleep, &raw const sleeping as u64) };
+    SAVED[me].store(null_mut(), Ordering::Release);
+    if how == WOKE {
+        apic::arm_one_shot(toyos_sched::fair::QUANTUM_NS);
+    }
+    how == WOKE
+}
+
+extern "C" fn enter_sleep(sleeping: u64) -> ! {
+    // SAFETY: `sleep`'s local, live in the frame `resume_from` returns into.
+    let sleeping = unsafe { &*(sleeping as *const Sleeping) };
+    (sleeping.enter)();
+    let until = clock::nanos_since_boot() + ENTRY.nanos();
+    while clock::nanos_since_boot() < until {
+        spin_loop();
+    }
+    // SAFETY: `sleeping.regs` is `sleep`'s `Context`, filled by `suspend_call`.
+    unsafe { resume_from(sleeping.regs, STAYED, core::ptr::null()) }
+}
+
+/// A wake stack, allocated the first time and the same one every time after.
+fn stack(slot: &AtomicU64) -> u64 {
+    match slot.load(Ordering::Relaxed) {
+        0 => {
+            let top = smp::alloc_stack();
+            slot.store(top, Ordering::Relaxed);
+            top
+        }
+        top => top,
+    }
+}
+
+/// Write the wake tables: the low 2 MiB identity-mapped with one large page,
+/// so the trampoline survives turning paging on, and the kernel half exactly as
+/// the kernel's own root has it, so everything after the trampoline's far jump
+/// reaches what it reached before the sleep.
+fn build_wake_tables() {
+    let table = |phys: u64| crate::DirectMap::from_phys(phys).as_mut_ptr::<u64>();
+    let (pml4, pdpt, pd) = (table(WAKE_ROOT), table(WAKE_PDPT), table(WAKE_PD));
+    let kernel = table(crate::mm::paging::kernel_cr3().phys());
+    // SAFETY: the three pages are `0x9000..0xC000`, reserved in `main` for
+    // exactly this and handed out by no allocator; each is written through its
+    // direct-map address, 512 entries and no further. Nothing reads them until
+    // the trampoline loads the root on the way back, and every other CPU is
+    // parked. `kernel` is the kernel's live root, only read, and its upper half
+    // is the one part of it every address space shares and nobody rewrites.
+    unsafe {
+        for i in 0..512 {
+            pml4.add(i).write_volatile(if i >= 256 { kernel.add(i).read_volatile() } else { 0 });
+            pdpt.add(i).write_volatile(0);
+            pd.add(i).write_volatile(0);
+        }
+        const PRESENT_WRITABLE: u64 = 0x3;
+        const LARGE: u64 = 0x80;
+        pml4.write_volatile(WAKE_PDPT | PRESENT_WRITABLE);
+        pdpt.write_volatile(WAKE_PD | PRESENT_WRITABLE);
+        pd.write_volatile(PRESENT_WRITABLE | LARGE);
+    }
+}
+
+/// This CPU's context on the way back, which must exist: the trampoline only
+/// ever carries a CPU that went down with one.
+fn saved_here() -> &'static Context {
+    let ctx = SAVED[percpu::cpu_id() as usize].load(Ordering::Acquire);
+    assert!(!ctx.is_null(), "S3: cpu{} woke with nothing to return to", percpu::cpu_id());
+    // SAFETY: `SAVED`'s entries point at a `Context` on the stack of the CPU
+    // that parked with it, which stays in RAM across S3 and is not freed until
+    // that CPU returns from `suspend_call` — which is what this wake is for.
+    unsafe { &*ctx }
+}
+
+/// What every CPU owes itself out of reset before it can run kernel code that
+/// was not written for the trampoline: the same steps as `ap_entry`, without
+/// the page-table switch, which the context's own `CR3` does last.
+fn bring_up(ctx: &Context) {
+    mtrr::restore(&ctx.mtrr);
+    control_regs::init_cr0(percpu::cpu_id());
+    pat::init();
+    percpu::init_resume();
+    syscall::init();
+    apic::init_ap();
+}
+
+/// Back into the context, off the wake stack; `left` is told once it is.
+fn finish(ctx: &Context, left: *const AtomicBool) -> ! {
+    // SAFETY: `ctx.cr3` is the value this CPU ran on when it went down — the
+    // kernel's root or a live process's, which nothing could free while every
+    // CPU was parked — and its upper half is the wake tables', so this code and
+    // this stack stay mapped across the write. `ctx.fs_base` is what the
+    // context's thread had, put back where the switch would. `resume_from`
+    // gets the `Regs` `suspend_call` filled.
+    unsafe {
+        cpu::write_cr3(ctx.cr3);
+        cpu::wrfsbase(ctx.fs_base);
+        resume_from(&ctx.regs, WOKE, left)
+    }
+}
+
+/// Where firmware's jump lands, through the trampoline, on the boot CPU.
+extern "C" fn wake_entry() -> ! {
+    let ctx = saved_here();
+    bring_up(ctx);
+    clock::resume();
+    let me = percpu::cpu_id();
+    for cpu in (0..smp::cpu_count()).filter(|&cpu| cpu != me) {
+        let other = SAVED[cpu as usize].load(Ordering::Acquire);
+        if other.is_null() {
+            continue;
+        }
+        // SAFETY: `saved_here`'s argument, for another CPU's entry.
+        let percpu = unsafe { (*other).percpu };
+        if !smp::restart(cpu, WAKE_ROOT, stack(&AP_WAKE_STACK), ap_wake_entry, percpu) {
+            // Its tasks stay where they are, on a CPU that is not running.
+            // Nothing can take them from it; saying so is what is left.
+            log!("S3: cpu{cpu} did not come back from the sleep");
+        }
+    }
+    finish(ctx, core::ptr::null())
+}
+
+/// Where each AP restarted by [`wake_entry`] lands.
+extern "C" fn ap_wake_entry() -> ! {
+    let ctx = saved_here();
+    bring_up(ctx);
+    finish(ctx, &smp::AP_STARTED)
+}
+
+/// Save what a call gives back into `regs`, then jump to `then(arg)` on the
+/// same stack. Returns when something calls [`resume_from`] on `regs`.
+///
+/// # Safety
+/// `regs` must stay live until that `resume_from`, and `then` must leave
+/// only through it.
+#[unsafe(naked)]
+unsafe extern "C" fn suspend_call(regs: *mut Regs, then: extern "C" fn(u64) -> !, arg: u64) -> u64 {
+    naked_asm!(
+        "fxsave64 [rdi]",
+        "mov [rdi + 0x200], rbx",
+        "mov [rdi + 0x208], rbp",
+        "mov [rdi + 0x210], r12",
+        "mov [rdi + 0x218], r13",
+        "mov [rdi + 0x220], r14",
+        "mov [rdi + 0x228], r15",
+        // The caller's stack as it will be once this returns, and where to.
+        "lea rax, [rsp + 8]",
+        "mov [rdi + 0x230], rax",
+        "mov rax, [rsp]",
+        "mov [rdi + 0x238], rax",
+        "pushfq",
+        "pop qword ptr [rdi + 0x240]",
+        "mov rdi, rdx",
+        "jmp rsi",
+    );
+}
+
+/// Return from the [`suspend_call`] that filled `regs`, with `value`, and
+/// store `true` to `left` (unless it is null) once off the current stack.
+///
+/// # Safety
+/// `regs` must have been filled by a `suspend_call` whose frame is still live,
+/// and `left`, if not null, must be a live `AtomicBool`.
+#[unsafe(naked)]
+unsafe extern "C" fn resume_from(regs: *const Regs, value: u64, left: *const AtomicBool) -> ! {
+    naked_asm!(
+        "fxrstor64 [rdi]",
+        "mov rbx, [rdi + 0x200]",
+        "mov rbp, [rdi + 0x208]",
+        "mov r12, [rdi + 0x210]",
+        "mov r13, [rdi + 0x218]",
+        "mov r14, [rdi + 0x220]",
+        "mov r15, [rdi + 0x228]",
+        "mov rsp, [rdi + 0x230]",
+        "mov rcx, [rdi + 0x238]",
+        "push qword ptr [rdi + 0x240]",
+        "mov rax, rsi",
+        "test rdx, rdx",
+        "jz 2f",
+        "mov byte ptr [rdx], 1",
+        "2:",
+        "popfq",
+        "jmp rcx",
+    );
+}
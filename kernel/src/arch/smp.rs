@@ -3,6 +3,7 @@ use core::mem::size_of;
 use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use alloc::alloc::{alloc_zeroed, Layout};
+use alloc::vec::Vec;
 
 use crate::arch::{apic, percpu, syscall};
 use crate::clock;
@@ -15,7 +16,10 @@ const TRAMPOLINE_VECTOR: u8 = 0x08;
 const AP_STACK_SIZE: usize = 64 * 1024;
 const DATA_OFFSET: usize = 0xF00;
 
-static AP_STARTED: AtomicBool = AtomicBool::new(false);
+/// Set by a starting AP once it no longer needs what the trampoline gave it —
+/// at the end of `ap_entry` on boot, and by `arch::sleep` once a waking AP has
+/// left the stack it shares with the next one.
+pub(super) static AP_STARTED: AtomicBool = AtomicBool::new(false);
 static SMP_READY: AtomicBool = AtomicBool::new(false);
 static CPU_COUNT: AtomicU32 = AtomicU32::new(1); // BSP counts as 1
 
@@ -214,6 +218,23 @@ fn build_trampoline_data() -> TrampolineData {
     }
 }
 
+/// The LAPIC id each cpu id will carry, in cpu-id order: the boot CPU's first,
+/// then every other MADT entry in the order the table lists them, which is the
+/// order [`boot_aps`] hands cpu ids out in.
+///
+/// For a driver brought up before the APs are: NVMe plans one queue per CPU
+/// and routes each queue's interrupt to its CPU while [`cpu_count`] is still
+/// one. An AP that then fails to start keeps its cpu id and its slot here —
+/// `boot_aps` does not reuse an id — so the plan never names the wrong CPU,
+/// only one that never arrived.
+pub fn planned_apic_ids(madt: &MadtInfo) -> Vec<u32> {
+    let bsp_id = apic::id();
+    core::iter::once(bsp_id)
+        .chain(madt.apic_ids.iter().copied().filter(|&id| id != bsp_id))
+        .take(crate::scheduler::MAX_CPUS)
+        .collect()
+}
+
 /// Boot all Application Processors found in the MADT.
 /// `boot_cr3` is the physical address of the bootloader's PML4 (has both
 /// identity map and high-half). APs use this during their transition to
@@ -225,77 +246,20 @@ pub fn boot_aps(madt: &MadtInfo, boot_cr3: u64) {
 
     let mut data = build_trampoline_data();
     data.cr3 = boot_cr3;
-    let target = crate::DirectMap::from_phys(TRAMPOLINE_PAGE + DATA_OFFSET as u64).as_mut_ptr::<TrampolineData>();
 
     let mut next_cpu_id = 1u32; // BSP is 0
     for &ap_id in &madt.apic_ids {
         if ap_id == bsp_id { continue; }
 
-        let stack_layout = Layout::from_size_align(AP_STACK_SIZE, 4096).unwrap();
-        // SAFETY: `AP_STACK_SIZE` is non-zero and 4096 is a power of two, which
-        // is `alloc_zeroed`'s whole contract. Irreducible for the reason
-        // `percpu::alloc_percpu`'s is: the block is never freed and becomes an
-        // AP's `rsp`, so no owning handle can hold it — a `Box` dropped here
-        // would free the stack a CPU is running on, and no `Vec<u8>` expresses
-        // the page alignment the trampoline's stack needs.
-        let stack_base = unsafe { alloc_zeroed(stack_layout) };
-        assert!(!stack_base.is_null(), "SMP: failed to allocate AP stack");
-
         let ap_cpu_id = next_cpu_id;
         next_cpu_id += 1;
         CPU_APIC_IDS[ap_cpu_id as usize].store(ap_id, Ordering::Relaxed);
         let ap_percpu = percpu::alloc_ap(ap_cpu_id, ap_id);
 
-        data.stack_top = stack_base as u64 + AP_STACK_SIZE as u64;
+        data.stack_top = alloc_stack();
         data.entry = ap_entry as *const () as u64;
         data.percpu_ptr = ap_percpu as u64;
-        // SAFETY: `target` is the direct-map address of physical 0x8F00, the
-        // 0x80-byte `TrampolineData` block `copy_trampoline`'s assertion keeps
-        // clear of the blob — reserved low memory no allocator hands out, and
-        // written only here. Unaligned because `TrampolineData` is `repr(C,
-        // packed)`. The AP this describes has not been sent its INIT-SIPI yet,
-        // and `boot_aps` waits for `AP_STARTED` before writing the block again,
-        // so no CPU is reading it while this lands.
-        unsafe { core::ptr::write_unaligned(target, data); }
-
-        AP_STARTED.store(false, Ordering::Release);
-
-        // INIT-SIPI-SIPI sequence. Both delays are spent rather than waited
-        // on: nothing is polled across either and neither can fail.
-        const AFTER_INIT: Delay =
-            Delay::from_spec(Duration::from_millis(10), "SDM §8.4.4.1, after the INIT IPI");
-        /// SDM §8.4.4.1 asks 200us here; a millisecond is the same shape with
-        /// room, and it is paid once per AP at boot.
-        const BETWEEN_SIPIS: Delay =
-            Delay::from_spec(Duration::from_millis(1), "SDM §8.4.4.1, between the two SIPIs");
-        apic::send_init(ap_id);
-        delay(AFTER_INIT);
-
-        apic::send_sipi(ap_id, TRAMPOLINE_VECTOR);
-        delay(BETWEEN_SIPIS);
-
-        if !AP_STARTED.load(Ordering::Acquire) {
-            apic::send_sipi(ap_id, TRAMPOLINE_VECTOR);
-        }
-
-        // How long an AP gets to reach `ap_entry` before it is declared
-        // absent by name and the machine boots without it.
-        //
-        // **A [`Budget`] and not the `Tripwire` offered as the alternative to
-        // finding a source.** Its expiry is already a degraded
-        // answer that says so — "failed to start!", one fewer CPU, a machine
-        // that boots — and making it a panic would be a behaviour change,
-        // which C1's own gate forbids. The number itself still has no source:
-        // SDM §8.4.4.1's numbers are the two delays above, not this.
-        const AP_START: Budget = Budget::of(
-            Duration::from_millis(100),
-            "the AP is named as failed to start and the machine boots one CPU short",
-        );
-        let deadline = clock::nanos_since_boot() + AP_START.nanos();
-        while !AP_STARTED.load(Ordering::Acquire) {
-            if clock::nanos_since_boot() >= deadline { break; }
-            core::hint::spin_loop();
-        }
+        let started = start(ap_id, &data);
 
         // One line per AP, carrying both halves of the identity: the cpu_id is
         // assigned here and appears in every later log prefix, the lapic_id is
@@ -303,7 +267,7 @@ pub fn boot_aps(madt: &MadtInfo, boot_cr3: u64) {
         // It is what lets the three lines this replaced go — `starting AP`,
         // `percpu: AP`, and the AP's own `Hello from CPU` each restated a
         // subset of it, four lines per core on a machine with eight.
-        if AP_STARTED.load(Ordering::Acquire) {
+        if started {
             CPU_COUNT.fetch_add(1, Ordering::Relaxed);
             log!("SMP: AP cpu{} lapic={} online", ap_cpu_id, ap_id);
         } else {
@@ -312,6 +276,108 @@ pub fn boot_aps(madt: &MadtInfo, boot_cr3: u64) {
     }
 }
 
+/// A fresh AP stack, by its top. Never freed: a CPU runs on it.
+pub(super) fn alloc_stack() -> u64 {
+    let stack_layout = Layout::from_size_align(AP_STACK_SIZE, 4096).unwrap();
+    // SAFETY: `AP_STACK_SIZE` is non-zero and 4096 is a power of two, which
+    // is `alloc_zeroed`'s whole contract. Irreducible for the reason
+    // `percpu::alloc_percpu`'s is: the block is never freed and becomes an
+    // AP's `rsp`, so no owning handle can hold it — a `Box` dropped here
+    // would free the stack a CPU is running on, and no `Vec<u8>` expresses
+    // the page alignment the trampoline's stack needs.
+    let stack_base = unsafe { alloc_zeroed(stack_layout) };
+    assert!(!stack_base.is_null(), "SMP: failed to allocate AP stack");
+    stack_base as u64 + AP_STACK_SIZE as u64
+}
+
+/// Publish `data` at 0x8F00 for the next CPU through the trampoline.
+fn write_data(data: &TrampolineData) {
+    let target = crate::DirectMap::from_phys(TRAMPOLINE_PAGE + DATA_OFFSET as u64).as_mut_ptr::<TrampolineData>();
+    // SAFETY: `target` is the direct-map address of physical 0x8F00, the
+    // 0x80-byte `TrampolineData` block `copy_trampoline`'s assertion keeps
+    // clear of the blob — reserved low memory no allocator hands out, and
+    // written only here. Unaligned because `TrampolineData` is `repr(C,
+    // packed)`. Both callers write it with no CPU in the trampoline: `start`
+    // before the INIT-SIPI and after the previous AP said it was done with the
+    // block, `prepare_wake` while every other CPU is parked.
+    unsafe { core::ptr::write_unaligned(target, *data); }
+}
+
+/// Send one AP through the trampoline with `data` and wait for it to say so.
+/// Whether it did within the `AP_START` budget is the answer.
+fn start(ap_id: u32, data: &TrampolineData) -> bool {
+    write_data(data);
+    AP_STARTED.store(false, Ordering::Release);
+
+    // INIT-SIPI-SIPI sequence. Both delays are spent rather than waited
+    // on: nothing is polled across either and neither can fail.
+    const AFTER_INIT: Delay =
+        Delay::from_spec(Duration::from_millis(10), "SDM §8.4.4.1, after the INIT IPI");
+    /// SDM §8.4.4.1 asks 200us here; a millisecond is the same shape with
+    /// room, and it is paid once per AP at boot.
+    const BETWEEN_SIPIS: Delay =
+        Delay::from_spec(Duration::from_millis(1), "SDM §8.4.4.1, between the two SIPIs");
+    apic::send_init(ap_id);
+    delay(AFTER_INIT);
+
+    apic::send_sipi(ap_id, TRAMPOLINE_VECTOR);
+    delay(BETWEEN_SIPIS);
+
+    if !AP_STARTED.load(Ordering::Acquire) {
+        apic::send_sipi(ap_id, TRAMPOLINE_VECTOR);
+    }
+
+    // How long an AP gets to reach `ap_entry` before it is declared
+    // absent by name and the machine boots without it.
+    //
+    // **A [`Budget`] and not the `Tripwire` offered as the alternative to
+    // finding a source.** Its expiry is already a degraded
+    // answer that says so — "failed to start!", one fewer CPU, a machine
+    // that boots — and making it a panic would be a behaviour change,
+    // which C1's own gate forbids. The number itself still has no source:
+    // SDM §8.4.4.1's numbers are the two delays above, not this.
+    const AP_START: Budget = Budget::of(
+        Duration::from_millis(100),
+        "the AP is named as failed to start and the machine boots one CPU short",
+    );
+    let deadline = clock::nanos_since_boot() + AP_START.nanos();
+    while !AP_STARTED.load(Ordering::Acquire) {
+        if clock::nanos_since_boot() >= deadline { break; }
+        core::hint::spin_loop();
+    }
+    AP_STARTED.load(Ordering::Acquire)
+}
+
+/// Lay the trampoline down again for the way back from S3, with the fields
+/// the waking CPU will read: firmware jumps to 0x8000 in real mode, which is
+/// the same entry a SIPI's vector 0x08 gives, so the blob serves both.
+///
+/// `cr3` must be below 4 GiB — the 32-bit half loads it from a 32-bit
+/// register — and must identity-map this page, which is why `arch::sleep`
+/// builds its own tables rather than passing the kernel's.
+pub(super) fn prepare_wake(cr3: u64, stack_top: u64, entry: extern "C" fn() -> !, percpu: u64) {
+    copy_trampoline();
+    let mut data = build_trampoline_data();
+    data.cr3 = cr3;
+    data.stack_top = stack_top;
+    data.entry = entry as *const () as u64;
+    data.percpu_ptr = percpu;
+    write_data(&data);
+}
+
+/// Bring one AP that S3 powered off back through the trampoline, into
+/// `entry` with its own `PerCpu` in `GS`. The CPU keeps its cpu id, so this
+/// changes no count; `false` is an AP that did not answer within the boot
+/// budget.
+pub(super) fn restart(cpu_id: u32, cr3: u64, stack_top: u64, entry: extern "C" fn() -> !, percpu: u64) -> bool {
+    let mut data = build_trampoline_data();
+    data.cr3 = cr3;
+    data.stack_top = stack_top;
+    data.entry = entry as *const () as u64;
+    data.percpu_ptr = percpu;
+    start(apic_id_for(cpu_id), &data)
+}
+
 extern "C" fn ap_entry() -> ! {
     // The trampoline reaches long mode by OR-ing two bits into whatever INIT
     // left in CR0, so this is the first instruction that gives this CPU the
@@ -354,8 +420,9 @@ extern "C" fn ap_entry() -> ! {
     process::ap_idle();
 }
 
-/// Spend a [`Delay`]. Boot only: there is no scheduler to give the CPU back
-/// to, which is what the enclosing function's own presence in §4.4's
+/// Spend a [`Delay`]. Boot and the wake from S3 only: there is no scheduler
+/// to give the CPU back to — on the wake every other CPU is still parked or
+/// powered off — which is what the enclosing function's own presence in §4.4's
 /// allow-list records.
 fn delay(span: Delay) {
     let start = clock::nanos_since_boot();
@@ -409,10 +476,12 @@ global_asm!(
     "mov eax, [0x8F00]",
     "mov cr3, eax",
 
-    // Enable Long Mode (IA32_EFER.LME)
+    // Enable Long Mode (IA32_EFER.LME) and No-Execute (IA32_EFER.NXE). NXE
+    // because the wake from S3 reaches kernel mappings before
+    // `control_regs::init`, and with it clear an NX bit is a reserved one.
     "mov ecx, 0xC0000080",
     "rdmsr",
-    "or eax, 0x100",
+    "or eax, 0x900",
     "wrmsr",
 
     // Enable Paging (CR0.PG)
@@ -8,6 +8,7 @@ use toyos_sched::task::WaitClass;
 
 use crate::completion;
 use crate::object::{ops, port, KObjectRef};
+use crate::suspend::Refusal;
 use crate::time::{Cadence, Deadline, Duration};
 use crate::{device, log, pipe, process, vfs};
 use crate::UserAddr;
@@ -411,7 +412,12 @@ fn syscall_dispatch(num: u64, a1: u64, a2: u64, a3: u64, a4: u64) -> u64 {
             let path = match ctx.user_str(UserAddr::new(a1), a2) { Ok(s) => s, Err(e) => return e.to_u64() };
             sys_delete(&path)
         }
+        SYS_FSTRIM => {
+            let path = match ctx.user_str(UserAddr::new(a1), a2) { Ok(s) => s, Err(e) => return e.to_u64() };
+            sys_fstrim(&path)
+        }
         SYS_SHUTDOWN => sys_shutdown(RawHandle(a1 as u32)),
+        SYS_SUSPEND => sys_suspend(RawHandle(a1 as u32)),
         SYS_CHDIR => {
             let path = match ctx.user_str(UserAddr::new(a1), a2) { Ok(s) => s, Err(e) => return e.to_u64() };
             sys_chdir(&path)
@@ -650,8 +656,9 @@ fn syscall_dispatch(num: u64, a1: u64, a2: u64, a3: u64, a4: u64) -> u64 {
                 return e.refuse();
             }
             match crate::net::poll_rx() {
-                Some((buf_idx, frame_len)) => ((buf_idx as u64) << 16) | (frame_len as u64),
-                None => 0,
+                Ok(Some((buf_idx, frame_len))) => ((buf_idx as u64) << 16) | (frame_len as u64),
+                Ok(None) => 0,
+                Err(e) => e.to_u64(),
             }
         }
         SYS_NIC_RX_DONE => {
@@ -669,6 +676,41 @@ fn syscall_dispatch(num: u64, a1: u64, a2: u64, a3: u64, a4: u64) -> u64 {
                 Err(e) => e.to_u64(),
             }
         }
+        // Behind the claim like the three above, though it drives nothing:
+        // asking clears the news that made the claim readable, and a process
+        // that could clear it could hide an unplugged adapter from netd.
+        SYS_NIC_LINK => {
+            if let Err(e) = holds_claim(RawHandle(a1 as u32), device::DeviceType::Nic) {
+                return e.refuse();
+            }
+            crate::net::link().map_or_else(|e| e.to_u64(), u64::from)
+        }
+        // The same reasoning for soundd's: clearing the news is hiding the
+        // DAC's arrival or departure from whoever holds the claim.
+        SYS_USB_AUDIO_DAC => {
+            if let Err(e) = holds_claim(RawHandle(a1 as u32), device::DeviceType::UsbAudio) {
+                return e.refuse();
+            }
+            toyos_abi::usb_audio::UsbAudioDac::to_raw(crate::drivers::xhci::audio::dac())
+        }
+        // No claim: a battery's charge is a fact about the machine, as its CPU
+        // count is, and reading the copy `acpid` keeps changes nothing. The
+        // count is userland's, so the byte length is checked before anything
+        // is mapped, for `SYS_LOG_READ`'s reason.
+        SYS_POWER_READINGS => {
+            const SIZE: usize = core::mem::size_of::<toyos_abi::power::PowerReading>();
+            let Some(bytes) = (a2 as usize).checked_mul(SIZE) else {
+                return SyscallError::InvalidArgument.to_u64();
+            };
+            let Some(mut out) = ctx.user_bytes_mut(UserAddr::new(a1), bytes as u64) else {
+                return bad_addr;
+            };
+            let readings = crate::drivers::aml::power_readings();
+            for (i, reading) in readings.iter().take(a2 as usize).enumerate() {
+                out.write_at(i * SIZE, reading.as_bytes());
+            }
+            readings.len() as u64
+        }
         SYS_SYMLINK => {
             let target = match ctx.user_str(UserAddr::new(a1), a2) { Ok(s) => s, Err(e) => return e.to_u64() };
             let link = match ctx.user_str(UserAddr::new(a3), a4) { Ok(s) => s, Err(e) => return e.to_u64() };
@@ -697,6 +739,51 @@ fn syscall_dispatch(num: u64, a1: u64, a2: u64, a3: u64, a4: u64) -> u64 {
                 Err(e) => e.to_u64(),
             }
         }
+        SYS_GPU_ARRANGE => {
+            // The claim first, for `SYS_GPU_SET_RESOLUTION`'s reason: an
+            // arrangement is a framebuffer allocation sized by userland.
+            let claim_h = RawHandle(a1 as u32);
+            if let Err(e) = holds_claim(claim_h, device::DeviceType::Framebuffer) {
+                return e.refuse();
+            }
+            let Some(info_out) = UserAddr::checked(a4) else { return bad_addr };
+            if a3 == 0 || a3 > toyos_abi::MAX_OUTPUTS as u64 {
+                return SyscallError::InvalidArgument.to_u64();
+            }
+            // Copied in once, entry by entry, and checked on the copy: the
+            // array is userland's to rewrite while the kernel reads it.
+            let mut outputs = Vec::with_capacity(a3 as usize);
+            for i in 0..a3 {
+                let stride = core::mem::size_of::<toyos_abi::Output>() as u64;
+                let at = UserAddr::new(a2.wrapping_add(i * stride));
+                match ctx.copy_in::<toyos_abi::Output>(at) {
+                    Ok(output) => outputs.push(output),
+                    Err(e) => return e.to_u64(),
+                }
+            }
+            match crate::gpu::arrange(&outputs) {
+                Ok(gpu_info) => sys_gpu_reset_scanout(&ctx, claim_h, gpu_info, info_out),
+                Err(e) => e.to_u64(),
+            }
+        }
+        SYS_GPU_MODES => {
+            // Behind the claim too: nothing here allocates, but a mode list
+            // is only any use to whoever can set one.
+            let claim_h = RawHandle(a1 as u32);
+            if let Err(e) = holds_claim(claim_h, device::DeviceType::Framebuffer) {
+                return e.refuse();
+            }
+            let modes = match crate::gpu::modes(a2 as usize) {
+                Ok(modes) => modes,
+                Err(e) => return e.to_u64(),
+            };
+            let bytes = modes.as_bytes();
+            let Some(mut out) = ctx.user_bytes_mut(UserAddr::new(a3), bytes.len() as u64) else {
+                return bad_addr;
+            };
+            out.write_at(0, bytes);
+            0
+        }
         SYS_ENDOWMENTS => {
             let Some(mut buf) = ctx.user_bytes_mut(UserAddr::new(a1), a2) else { return bad_addr };
             sys_endowments(&mut buf)
@@ -922,6 +1009,22 @@ fn syscall_dispatch(num: u64, a1: u64, a2: u64, a3: u64, a4: u64) -> u64 {
                     None => SyscallError::InvalidArgument.to_u64(),
                 }
             }
+            // A game controller plugged in, reporting and pulled out, with the
+            // report in `a2`. QEMU has no USB pad, so this is the only way a
+            // pad state reaches a reader on a booted machine; see
+            // `xhci::synthetic_pad` for which half is real.
+            DA::PAD_PLUG => match crate::drivers::xhci::synthetic_pad::plug() {
+                Ok(pad) => u64::from(pad),
+                Err(e) => e.to_u64(),
+            },
+            DA::PAD_REPORT => match crate::drivers::xhci::synthetic_pad::report(a2.to_le_bytes()) {
+                Ok(queued) => queued as u64,
+                Err(e) => e.to_u64(),
+            },
+            DA::PAD_UNPLUG => match crate::drivers::xhci::synthetic_pad::unplug() {
+                Ok(()) => 0,
+                Err(e) => e.to_u64(),
+            },
             _ => SyscallError::InvalidArgument.to_u64(),
         },
         SYS_SCHED_INFO => match ctx.copy_out(UserAddr::new(a1), &sys_sched_info()) {
@@ -1187,9 +1290,13 @@ fn sys_device_reg(handle: RawHandle, offset: u64, width: u64, value: Option<u64>
 
 /// Where a read that came back empty parks, decided from the object itself.
 ///
-/// Only these four wait. A mouse, a NIC and a framebuffer answer `NotFound` to
-/// a blocking read that has nothing — which is what they did as descriptors,
-/// and what their holders already build around.
+/// Only these four wait. A mouse, a gamepad, a NIC and a framebuffer answer
+/// `NotFound` to a blocking read that has nothing — which is what they did as
+/// descriptors, and what their holders already build around. A vsock answers the same,
+/// because its one holder parks on a poller and never in a read, and so does a
+/// USB DAC, whose holder reads it non-blocking from a mix loop that parks on
+/// its own inbox, and the power buttons, whose holder is init and whose loop
+/// is already a poller.
 fn read_block_device(claim: &crate::object::device::DeviceClaim) -> ReadBlock {
     match claim.class() {
         device::DeviceType::Keyboard => ReadBlock::Keyboard(Deadline::never()),
@@ -1446,6 +1553,22 @@ fn sys_delete(path: &str) -> u64 {
     }
 }
 
+/// The blocks discarded, or an error. A count can never be mistaken for one:
+/// errors are the top eleven values of a `u64`, and no volume has that many
+/// blocks.
+fn sys_fstrim(path: &str) -> u64 {
+    let cwd = process::with_process_data(|d| d.cwd.clone());
+    let mut vfs = vfs::lock();
+    let resolved = vfs.resolve_absolute(&cwd, path);
+    if !vfs.user_may_modify(&resolved) {
+        return SyscallError::PermissionDenied.to_u64();
+    }
+    match vfs.trim_for_path(&resolved) {
+        Ok(blocks) => blocks,
+        Err(e) => e.to_u64(),
+    }
+}
+
 fn sys_chdir(path: &str) -> u64 {
     let cwd = process::with_process_data(|d| d.cwd.clone());
     match vfs::lock().cd(&cwd, path) {
@@ -1721,7 +1844,7 @@ fn sys_process_open(syscap: RawHandle, pid: process::Pid) -> u64 {
 }
 
 /// Mint the claim for a device class, presenting a `SysCap` that carries
-/// [`Rights::DEVICE`].
+/// [`Rights::DEVICE`] — and, for the power buttons, [`Rights::POWER`] as well.
 ///
 /// The kernel makes one such cap, at boot, for `/bin/init`, so the set of
 /// processes that can reach this at all is exactly what init endowed. What
@@ -1732,8 +1855,15 @@ fn sys_device_claim(syscap: RawHandle, class: u64) -> u64 {
     let Some(class) = device::DeviceType::from_raw(class) else {
         return SyscallError::InvalidArgument.to_u64();
     };
+    // A press of the power button is a request to turn the machine off, so
+    // hearing it is held to the authority that can: a cap that may claim a
+    // mouse is not thereby one that decides what the button means.
+    let needed = match class {
+        device::DeviceType::Power => Rights::DEVICE.union(Rights::POWER),
+        _ => Rights::DEVICE,
+    };
     if let Err(e) = process::with_process_data(|data| {
-        data.handles.get::<crate::object::syscap::SysCap>(syscap, Rights::DEVICE)
+        data.handles.get::<crate::object::syscap::SysCap>(syscap, needed)
     }) {
         return e.refuse();
     }
@@ -1850,6 +1980,34 @@ fn sys_shutdown(syscap: RawHandle) -> u64 {
     acpi::shutdown();
 }
 
+/// Suspend the machine to RAM, presenting a `SysCap` that carries
+/// [`Rights::POWER`], and answer once it is back.
+///
+/// Checked exactly as [`sys_shutdown`] is. Everything after the check is
+/// `suspend::enter`'s, which wants to be called from here: `IF` is clear, so
+/// this CPU can park the others, and the entry is a park point for the two
+/// AML methods that have to wait on `acpid`.
+fn sys_suspend(syscap: RawHandle) -> u64 {
+    if let Err(e) = process::with_process_data(|data| {
+        data.handles.get::<crate::object::syscap::SysCap>(syscap, Rights::POWER)
+    }) {
+        return e.refuse();
+    }
+    let parkable = crate::scheduler::Parkable::at_entry();
+    match crate::suspend::enter(&parkable) {
+        Ok(()) => 0,
+        Err(refusal) => {
+            log!("S3: refused: {refusal}");
+            let e = match refusal {
+                Refusal::NoS3 | Refusal::NoWake | Refusal::Driver { .. } => SyscallError::NotSupported,
+                Refusal::Busy | Refusal::Cpu(_) => SyscallError::WouldBlock,
+                Refusal::NotEntered => SyscallError::Io,
+            };
+            e.to_u64()
+        }
+    }
+}
+
 /// Answer this process's endowment table.
 ///
 /// An empty buffer asks how many bytes the answer needs, so a caller sizes once
@@ -2095,20 +2253,10 @@ fn sys_gpu_reset_scanout(
     gpu_info: crate::gpu::GpuInfo,
     info_out: UserAddr,
 ) -> u64 {
-    let crate::gpu::GpuInfo { scanout, cursor, width, height, stride, pixel_format, flags } =
-        gpu_info;
     let screen = device::Screen {
-        info: toyos_abi::FramebufferInfo {
-            scanout: [toyos_abi::HANDLE_INVALID; 2],
-            cursor: toyos_abi::HANDLE_INVALID,
-            width,
-            height,
-            stride,
-            pixel_format,
-            flags,
-        },
-        scanout,
-        cursor,
+        info: gpu_info.describe(),
+        scanout: gpu_info.scanout,
+        cursor: gpu_info.cursor,
     };
     device::set_framebuffer_info(screen.clone());
     let minted = process::with_process_data(|data| {
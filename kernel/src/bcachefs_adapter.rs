@@ -4,6 +4,7 @@ use alloc::vec::Vec;
 use hashbrown::HashMap;
 
 use bcachefs::{BlockIO, BlockBuf, BlockNum, DeviceError, FsError, Mounted, ReadWrite, ReadOnly, Formatted, SliceBlockIO, Extent};
+use crate::block::DeviceId;
 use crate::file_backing::{FileBacking, FileBlocks, NvmeBacking, InitrdBacking};
 use crate::file_cache::{self, FileId, Residency};
 use crate::page_cache;
@@ -11,8 +12,21 @@ use toyos_abi::syscall::SyscallError;
 
 use crate::vfs::FileSystem;
 
-/// BlockIO implementation that wraps the kernel's global PageCache.
-pub struct PageCacheBlockIO;
+/// BlockIO implementation over one registered device's page cache.
+#[derive(Clone, Copy)]
+pub struct PageCacheBlockIO {
+    device: DeviceId,
+}
+
+impl PageCacheBlockIO {
+    pub fn new(device: DeviceId) -> Self {
+        Self { device }
+    }
+
+    pub fn device(&self) -> DeviceId {
+        self.device
+    }
+}
 
 /// The device error channel now runs the whole way: `BlockDevice` reports a
 /// refused transfer, the page cache propagates it, and `bcachefs::BlockIO`
@@ -23,7 +37,7 @@ pub struct PageCacheBlockIO;
 /// the btree looking like corruption and a *write* error looked like a write.
 impl BlockIO for PageCacheBlockIO {
     fn read_block(&self, block: BlockNum, buf: &mut BlockBuf) -> Result<(), DeviceError> {
-        let mut guard = page_cache::lock();
+        let mut guard = page_cache::lock(self.device);
         let (cache, dev) = guard.cache_and_dev();
         let page = cache.read(dev, block.raw()).map_err(|_| DeviceError)?;
         buf.as_bytes_mut().copy_from_slice(page);
@@ -31,7 +45,7 @@ impl BlockIO for PageCacheBlockIO {
     }
 
     fn write_block(&self, block: BlockNum, buf: &BlockBuf) -> Result<(), DeviceError> {
-        let mut guard = page_cache::lock();
+        let mut guard = page_cache::lock(self.device);
         let (cache, dev) = guard.cache_and_dev();
         let page = cache.write_new(dev, block.raw()).map_err(|_| DeviceError)?;
         page.copy_from_slice(buf.as_bytes());
@@ -39,15 +53,21 @@ impl BlockIO for PageCacheBlockIO {
     }
 
     fn block_count(&self) -> u64 {
-        let guard = page_cache::lock();
+        let guard = page_cache::lock(self.device);
         guard.block_count()
     }
 
     fn sync(&self) -> Result<(), DeviceError> {
-        let mut guard = page_cache::lock();
+        let mut guard = page_cache::lock(self.device);
         let (cache, dev) = guard.cache_and_dev();
         cache.sync(dev).map_err(|_| DeviceError)
     }
+
+    /// Straight to the device through the raw path, as file data goes: the
+    /// cache has nothing to add to a hint about blocks nobody will read.
+    fn discard(&self, start: BlockNum, count: u32) -> Result<(), DeviceError> {
+        page_cache::discard(self.device, start.raw(), count).map_err(|_| DeviceError)
+    }
 }
 
 /// What an `FsError` means to the [`FileSystem`] trait's caller.
@@ -62,7 +82,8 @@ fn as_syscall_error(err: &FsError) -> SyscallError {
         FsError::NotFound => SyscallError::NotFound,
         FsError::NoSpace { .. } | FsError::EntryTooLarge { .. } => SyscallError::ResourceExhausted,
         FsError::NameTooLong { .. } => SyscallError::InvalidArgument,
-        FsError::DeviceRead(_) | FsError::DeviceWrite(_) | FsError::DeviceSync => SyscallError::Io,
+        FsError::DeviceRead(_) | FsError::DeviceWrite(_) | FsError::DeviceSync
+        | FsError::DeviceDiscard(_) => SyscallError::Io,
         FsError::BadMagic { .. }
         | FsError::UnsupportedVersion(_)
         | FsError::ChecksumMismatch { .. }
@@ -183,6 +204,7 @@ impl FileSystem for BcacheFsAdapter {
             file_cache::open(file_id);
             let info = self.open_files.get(&file_id).ok_or(SyscallError::NotFound)?;
             let backing = Arc::new(NvmeBacking::new(
+                self.fs.io().device(),
                 Arc::clone(&info.blocks),
                 file_cache::size(file_id),
             ));
@@ -200,7 +222,7 @@ impl FileSystem for BcacheFsAdapter {
             blocks: Arc::clone(&blocks),
         });
 
-        Ok((file_id, Some(Arc::new(NvmeBacking::new(blocks, size)))))
+        Ok((file_id, Some(Arc::new(NvmeBacking::new(self.fs.io().device(), blocks, size)))))
     }
 
     fn create(&mut self, name: &str, mtime: u64) -> Result<FileId, SyscallError> {
@@ -279,7 +301,7 @@ impl FileSystem for BcacheFsAdapter {
             .with(|extents| self.fs.resolve_or_alloc_block(extents, page_idx))
             .ok_or(SyscallError::NotFound)?;
         let block = mapped("block allocation", &name, block)?;
-        page_cache::raw_block_write(block, data).map_err(|_| {
+        page_cache::raw_block_write(self.fs.io().device(), block, data).map_err(|_| {
             log!("bcachefs: write of block {block} for '{name}' failed");
             SyscallError::Io
         })
@@ -305,10 +327,16 @@ impl FileSystem for BcacheFsAdapter {
         mapped("sync", "/", self.fs.sync())
     }
 
+    fn trim(&mut self) -> Result<u64, SyscallError> {
+        let blocks = mapped("trim", "/", self.fs.trim())?;
+        log!("bcachefs: trimmed {blocks} free blocks ({} MiB)", blocks * 4096 / (1024 * 1024));
+        Ok(blocks)
+    }
+
     fn open_backing(&mut self, name: &str) -> Result<Arc<dyn FileBacking>, SyscallError> {
         let (extents, size) = present("open_backing", name, self.fs.file_extents(name))?;
         let blocks = self.blocks_for(name, extents);
-        Ok(Arc::new(NvmeBacking::new(blocks, size)))
+        Ok(Arc::new(NvmeBacking::new(self.fs.io().device(), blocks, size)))
     }
 }
 
@@ -423,8 +451,8 @@ impl FileSystem for ReadOnlyBcacheFsAdapter {
 ///
 /// Destroys everything on the device. [`probe`] is the only caller that is
 /// entitled to reach it, and only on [`Storage::Designated`].
-fn format() -> Option<Mounted<PageCacheBlockIO, ReadWrite>> {
-    match Formatted::format(PageCacheBlockIO) {
+fn format(io: PageCacheBlockIO) -> Option<Mounted<PageCacheBlockIO, ReadWrite>> {
+    match Formatted::format(io) {
         Ok(fs) => Some(fs.mount()),
         Err(err) => {
             // The disk said we may destroy what is on it and then would not
@@ -438,8 +466,7 @@ fn format() -> Option<Mounted<PageCacheBlockIO, ReadWrite>> {
 }
 
 /// Try to mount an existing bcachefs filesystem from NVMe.
-fn mount() -> Option<Mounted<PageCacheBlockIO, ReadWrite>> {
-    let io = PageCacheBlockIO;
+fn mount(io: PageCacheBlockIO) -> Option<Mounted<PageCacheBlockIO, ReadWrite>> {
     Mounted::<PageCacheBlockIO, ReadWrite>::open(io).ok()
 }
 
@@ -475,12 +502,12 @@ pub enum Storage {
 /// One read decides all three because bcachefs puts its superblock at block 0
 /// too, so a disk cannot be both ours and awaiting designation. Reading is
 /// safe on any disk whatsoever; nothing below writes.
-pub fn probe() -> Storage {
-    if let Some(fs) = mount() {
+pub fn probe(io: PageCacheBlockIO) -> Storage {
+    if let Some(fs) = mount(io) {
         log!("storage: mounted the ToyOS volume at block 0");
         return Storage::Ours(fs);
     }
-    if designated() {
+    if designated(io) {
         log!("storage: block 0 designates this device for ToyOS — formatting it");
         return Storage::Designated;
     }
@@ -496,8 +523,8 @@ pub fn probe() -> Storage {
 /// The size is half the stamp and not decoration: without it, a designated
 /// image copied or restored onto a different disk would designate that disk
 /// too. With it, designation does not survive being moved.
-fn designated() -> bool {
-    let mut guard = page_cache::lock();
+fn designated(io: PageCacheBlockIO) -> bool {
+    let mut guard = page_cache::lock(io.device());
     let blocks = guard.block_count();
     let (cache, dev) = guard.cache_and_dev();
     // A disk whose block 0 cannot be read has not said this kernel may format
@@ -534,10 +561,11 @@ fn designated() -> bool {
 /// `None` means the device is not ours: the caller mounts a tmpfs instead, so
 /// a machine whose disk we may not touch still boots to a working system with
 /// a volatile `/home` rather than panicking or, far worse, helping itself.
-pub fn open_home() -> Option<Mounted<PageCacheBlockIO, ReadWrite>> {
-    match probe() {
+pub fn open_home(device: DeviceId) -> Option<Mounted<PageCacheBlockIO, ReadWrite>> {
+    let io = PageCacheBlockIO::new(device);
+    match probe(io) {
         Storage::Ours(fs) => Some(fs),
-        Storage::Designated => format(),
+        Storage::Designated => format(io),
         Storage::Foreign => None,
     }
 }
@@ -1,3 +1,5 @@
+use alloc::boxed::Box;
+
 use crate::mm::PAGE_SIZE;
 use crate::scheduler::Operation;
 use crate::time::{Budget, Deadline, Duration};
@@ -146,6 +148,33 @@ pub trait BlockDevice: Send {
     /// Flush any hardware write caches to persistent storage.
     #[must_use = "a failed flush means the writes before it are not durable"]
     fn flush(&mut self) -> BlockResult;
+
+    /// Tell the device that `count` blocks from `lba` hold nothing anybody
+    /// will read again, so flash can erase them ahead of the next write.
+    ///
+    /// **A hint, not a write.** What a later read of those blocks answers is
+    /// the device's business — zeroes, the old bytes, or either — and nothing
+    /// above this trait may depend on it; a filesystem that wants zeroes
+    /// writes them. `Ok(())` — the default — is a device that has no such
+    /// command, or one that has it and was not asked because the discard
+    /// would say too much (`crypt_device` says what). `Err` is a device that
+    /// was asked and failed, which a caller logs and goes on from: the blocks
+    /// are still free, only not erased early.
+    fn discard(&mut self, _lba: u64, _count: u32) -> BlockResult {
+        Ok(())
+    }
+
+    /// Another handle onto the same device, which a caller on another CPU may
+    /// drive at the same time as this one.
+    ///
+    /// `None` — the default — for a device whose handles would share one
+    /// command path anyway: a second handle onto one lock buys a caller
+    /// nothing but a second place to wait. NVMe answers `Some`, because each
+    /// CPU has a queue pair of its own and the page cache's raw path takes one
+    /// handle per CPU to reach them.
+    fn share(&self) -> Option<Box<dyn BlockDevice>> {
+        None
+    }
 }
 
 // How much of RAM the two caches above this trait may hold, in 4 KiB pages.
@@ -162,7 +191,9 @@ pub trait BlockDevice: Send {
 // minutes proving what 256 KiB proves in a second. The eviction code they
 // drive is the shipped code — only the bound moves.
 
-/// Blocks the filesystem metadata cache may hold.
+/// Blocks the filesystem metadata cache may hold, across every device it
+/// serves: `page_cache` shares this one number between its registrations
+/// rather than giving each disk its own.
 ///
 /// Metadata residency is a property of the filesystem, not of the machine:
 /// formatting the T14's 244 GB namespace leaves ~1900 blocks resident (the
@@ -17,8 +17,15 @@
 //! boot with no wall clock, [`local_secs`] and [`utc_secs`] say so in their
 //! return type, and every consumer decides what to do about it. Nothing here
 //! invents 1970.
+//!
+//! **S3 is the exception to both halves.** The TSC starts again from zero when
+//! the machine wakes, so [`resume`] re-bases the monotonic clock on the reading
+//! [`suspend`] took — it stands still across the sleep, which is the
+//! `CLOCK_MONOTONIC` every deadline in the kernel was computed against — and
+//! the wall clock, whose anchor would then be behind by however long the
+//! machine slept, is read from the RTC a second time by [`resync_wall`].
 
-use core::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering::{Acquire, Relaxed, Release}};
+use core::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicU8, Ordering::{Acquire, Relaxed, Release}};
 
 use crate::mm::paging::CachePolicy;
 use crate::arch::cpu;
@@ -31,6 +38,11 @@ const HPET_COUNTER: u64 = 0x0F0;
 
 static TSC_BOOT: AtomicU64 = AtomicU64::new(0);
 static TSC_PERIOD_FS: AtomicU64 = AtomicU64::new(0);
+/// What [`nanos_since_boot`] read when [`TSC_BOOT`] was taken: zero for a
+/// boot that has never slept, and the reading [`suspend`] took after each S3.
+static NANOS_AT_TSC_BOOT: AtomicU64 = AtomicU64::new(0);
+/// The reading [`suspend`] took, for [`resume`] to continue from.
+static SUSPENDED_AT: AtomicU64 = AtomicU64::new(0);
 
 pub fn init(hpet_base: u64) {
     let hpet = crate::mm::paging::map_mmio(hpet_base, 0x1000, CachePolicy::DeferToMtrr);
@@ -100,10 +112,27 @@ pub fn calibrated() -> bool {
 /// is on the 2020+ x86-64 machines this kernel targets, and
 /// `issues/kernel/ap-tsc-trail-is-assumed-and-never-checked.md` is the
 /// entry for the fact that nothing measures it.
+///
+/// Since boot *awake*: time spent in S3 is not counted (see the module doc).
 pub fn nanos_since_boot() -> u64 {
     let delta = cpu::rdtsc().saturating_sub(TSC_BOOT.load(Relaxed));
     let period_fs = TSC_PERIOD_FS.load(Relaxed);
-    ((delta as u128 * period_fs as u128) / 1_000_000) as u64
+    NANOS_AT_TSC_BOOT.load(Relaxed) + ((delta as u128 * period_fs as u128) / 1_000_000) as u64
+}
+
+/// Take the reading the clock continues from after S3. The last thing before
+/// the sleep, on the one CPU still running.
+pub fn suspend() {
+    SUSPENDED_AT.store(nanos_since_boot(), Relaxed);
+}
+
+/// Re-base the clock on this CPU's TSC, which the sleep reset, so that it reads
+/// on from where [`suspend`] left it. The first thing the wake does after the
+/// CPU's own registers, before any other CPU is running to read the clock
+/// half-updated.
+pub fn resume() {
+    TSC_BOOT.store(cpu::rdtsc(), Relaxed);
+    NANOS_AT_TSC_BOOT.store(SUSPENDED_AT.load(Relaxed), Relaxed);
 }
 
 /// The same reading as an [`Instant`], which is the type arithmetic on it is
@@ -161,6 +190,41 @@ pub fn settles(nanos: u64, ready: impl Fn() -> bool) -> bool {
     true
 }
 
+/// [`settles`] for a condition an interrupt routed to *this* CPU makes true:
+/// the CPU halts between checks instead of spinning. `false` is the deadline,
+/// as there.
+///
+/// **Checked with interrupts closed, and halted on with them opened in the
+/// same breath** — [`cpu::wait_for_interrupt`]'s shadow — so the interrupt
+/// that makes `ready` true is either seen by the check or ends the halt. The
+/// local timer is shortened to the deadline before each halt, so a device that
+/// never interrupts costs the deadline and not forever; the scheduler sees that
+/// as one extra pass. The nanosecond clock is read once per wake, which is
+/// [`settles`]' objection to it only in a loop that never sleeps.
+///
+/// A caller whose interrupts were already closed gets [`settles`] instead:
+/// the halt would open them, and whatever closed them did so for a reason this
+/// wait does not know.
+pub fn awaits(nanos: u64, ready: impl Fn() -> bool) -> bool {
+    let until = nanos_since_boot().saturating_add(nanos);
+    loop {
+        let irq = crate::hw::IrqGuard::close();
+        if !irq.was_open() {
+            drop(irq);
+            return settles(until.saturating_sub(nanos_since_boot()), ready);
+        }
+        if ready() {
+            return true;
+        }
+        let now = nanos_since_boot();
+        if now >= until {
+            return false;
+        }
+        crate::arch::apic::arm_within(until - now);
+        cpu::wait_for_interrupt();
+    }
+}
+
 /// Unix seconds, in the machine's own zone, at `nanos_since_boot() == 0`.
 static BOOT_LOCAL_SECS: AtomicU64 = AtomicU64::new(0);
 /// Seconds to add to the machine's own zone to get UTC — firmware's
@@ -169,6 +233,9 @@ static UTC_OFFSET_SECS: AtomicI64 = AtomicI64::new(0);
 /// Whether the two above mean anything. Not a sentinel in either: zero is a
 /// real instant and a real offset.
 static WALL_KNOWN: AtomicBool = AtomicBool::new(false);
+/// The century register [`init_wall`] was given, for [`resync_wall`]. Zero is
+/// "none", which is also how the FADT spells it.
+static CENTURY_REG: AtomicU8 = AtomicU8::new(0);
 
 /// Read the RTC, once, and anchor the wall clock to the monotonic one.
 ///
@@ -186,6 +253,7 @@ pub fn init_wall(century_reg: Option<u8>, utc_offset_minutes: Option<i32>) {
     let utc_offset_minutes =
         if crate::actuator::rtc_zone_east() { Some(-120) } else { utc_offset_minutes };
 
+    CENTURY_REG.store(century_reg.unwrap_or(0), Relaxed);
     let civil = match crate::rtc::read(century_reg) {
         Ok(civil) => civil,
         Err(fault) => {
@@ -206,6 +274,27 @@ pub fn init_wall(century_reg: Option<u8>, utc_offset_minutes: Option<i32>) {
     }
 }
 
+/// Read the RTC again after S3 and move the wall clock's anchor by however
+/// long the machine slept. The zone is firmware's and did not change.
+///
+/// A machine whose RTC did not answer at boot has no wall clock to move, and
+/// one that stops answering now keeps the anchor it had — behind by the
+/// length of the sleep, which is what the log line says.
+pub fn resync_wall() {
+    if !WALL_KNOWN.load(Acquire) {
+        return;
+    }
+    let century_reg = Some(CENTURY_REG.load(Relaxed)).filter(|&reg| reg != 0);
+    match crate::rtc::read(century_reg) {
+        Ok(civil) => {
+            let local = civil.to_unix_secs();
+            BOOT_LOCAL_SECS.store(local.saturating_sub(nanos_since_boot() / 1_000_000_000), Relaxed);
+            log!("clock: the RTC reads {civil} after the sleep");
+        }
+        Err(fault) => log!("clock: the RTC did not answer after the sleep — {fault}; the wall clock is behind"),
+    }
+}
+
 /// What time it is in the zone the machine keeps its clock in — what FAT
 /// entries are stamped with, since FAT stores local time by specification, and
 /// what this boot's log file is named for.
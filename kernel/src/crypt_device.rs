@@ -0,0 +1,284 @@
+//! An encrypted `/home`: the NVMe device behind an AES-XTS [`BlockDevice`],
+//! unlocked by a passphrase typed on the panel before anything mounts.
+//!
+//! `toyos-crypt` owns the format, the cipher and the key derivation, and has
+//! the tests. What is here is what that crate deliberately does not know about
+//! — the kernel's 4 KiB [`BlockDevice`], the i8042 it reads the passphrase
+//! from, and the boot phase that has to wait for it.
+//!
+//! # Where it sits
+//!
+//! Between the driver and the page cache, so every layer above — the cache,
+//! `bcachefs_adapter`'s designation probe, the format, the mount — sees the
+//! plaintext volume and is unchanged. Block 0 of the device is the header and
+//! the first [`toyos_crypt::DEFAULT_DATA_OFFSET`] blocks are reserved for it;
+//! block `n` of what the page cache sees is block `data_offset + n` of the
+//! disk, encrypted with `n` as the tweak. That is the same mapping
+//! `toyos_crypt::Encrypted` makes over a `BlockIO`, which is what the host
+//! formats through, and the two have to agree for a volume made on one side to
+//! open on the other.
+//!
+//! A disk whose block 0 is not a header is passed through untouched: a plain
+//! disk boots exactly as it did before this file existed, designation,
+//! refusal and all.
+//!
+//! # The prompt
+//!
+//! Phase 3 runs on the BSP alone, before `i8042::init`, so there is no ISR to
+//! race for port 0x60 and [`i8042::poll_byte`] is legal here for the same
+//! reason it is legal in the panic console's pager. The scancodes are decoded
+//! by a [`KeyDecoder`] of this file's own into [`Prompt`], and the decoder is
+//! set 1: this relies on firmware having left the controller translating,
+//! which every machine this has booted on does. One that did not would decode
+//! noise, fail three times and boot with `/home` on a tmpfs — not wedge.
+//!
+//! Nothing is echoed, not even a count. A line per key would put the
+//! passphrase's length in the log ring, and the log ring is a file on `/log`.
+//!
+//! The passphrase is the keys pressed rather than the characters they type,
+//! and `toyos_crypt::passphrase` says why: there is no layout in the kernel
+//! and the person at the prompt may not have chosen one yet.
+//!
+//! # What it does not do
+//!
+//! - **No USB keyboard.** xHCI comes up in phase 4, after storage. A machine
+//!   whose only keyboard is USB cannot answer the prompt, waits out [`IDLE`]
+//!   and boots with a tmpfs `/home`. Moving the prompt after xHCI means moving
+//!   the whole storage phase, and that is its own change.
+//! - **No way to change a passphrase from inside the guest.** The header has
+//!   key slots so that one can be changed without re-encrypting the disk, and
+//!   `toyos_crypt::Header::change_passphrase` is the operation; nothing in
+//!   userland reaches block 0 to call it yet.
+//! - **No AES-NI.** The cipher is `toyos-crypt`'s table AES, and the crate
+//!   docs say what that costs.
+//! - **No discard.** The filesystem's hints stop here; [`CryptDevice`]'s
+//!   `discard` says why.
+
+use alloc::boxed::Box;
+use alloc::vec;
+
+use toyos_crypt::{Cipher, Error, Header, MasterKey, Prompt, Step, DATA_UNIT, HEADER_BYTES};
+use toyos_ps2::{KeyDecoder, KeyOutcome};
+
+use crate::block::{BlockDevice, BlockError, BlockResult, DeviceId};
+use crate::drivers::{i8042, panic_console};
+use crate::time::{Budget, Duration};
+
+/// Passphrases the prompt takes before it gives up. Each wrong one costs a key
+/// derivation, which is the point of the derivation, so three is what a
+/// person mistyping needs and not what somebody guessing would want.
+const ATTEMPTS: u32 = 3;
+
+/// How long the prompt waits for a key before it stops asking.
+///
+/// A machine with nobody at it — a headless box, a USB-only keyboard, a
+/// controller that decodes nothing — still has to finish booting. The clock
+/// restarts at every key, so this is a bound on an absent person and not on a
+/// slow typist.
+const IDLE: Budget = Budget::of(
+    Duration::from_secs(120),
+    "the volume stays locked and /home is a tmpfs for this boot",
+);
+
+/// Blocks encrypted per call to the device on a write. The caller's buffer is
+/// plaintext it goes on using, so ciphertext needs somewhere else to live;
+/// a write longer than this is several device calls.
+const BOUNCE_BLOCKS: usize = 16;
+
+/// The plaintext volume over an encrypted device.
+pub struct CryptDevice {
+    inner: Box<dyn BlockDevice>,
+    cipher: Cipher,
+    data_offset: u64,
+    bounce: Box<[u8]>,
+}
+
+impl CryptDevice {
+    fn new(inner: Box<dyn BlockDevice>, master: &MasterKey, data_offset: u64) -> Self {
+        Self {
+            inner,
+            cipher: Cipher::new(master),
+            data_offset,
+            bounce: vec![0u8; BOUNCE_BLOCKS * DATA_UNIT].into_boxed_slice(),
+        }
+    }
+
+    /// Where `count` blocks from `lba` live on the device, or [`BlockError`]
+    /// if any of them is past the volume's end. Every transfer goes through
+    /// here, so nothing above can reach the header.
+    fn locate(&self, lba: u64, count: u32) -> Result<u64, BlockError> {
+        let end = lba.checked_add(count as u64).ok_or(BlockError)?;
+        if end > self.block_count() {
+            return Err(BlockError);
+        }
+        Ok(self.data_offset + lba)
+    }
+}
+
+impl BlockDevice for CryptDevice {
+    fn device_id(&self) -> DeviceId {
+        self.inner.device_id()
+    }
+
+    fn block_count(&self) -> u64 {
+        self.inner.block_count().saturating_sub(self.data_offset)
+    }
+
+    /// Decrypted in place, and only after the device said the read happened:
+    /// on `Err` the buffer is the device's to have left as it was.
+    fn read_blocks(&mut self, lba: u64, count: u32, buf: &mut [u8]) -> BlockResult {
+        let at = self.locate(lba, count)?;
+        self.inner.read_blocks(at, count, buf)?;
+        let (units, _) = buf.as_chunks_mut::<DATA_UNIT>();
+        for (i, unit) in units.iter_mut().enumerate() {
+            self.cipher.decrypt(lba + i as u64, unit);
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, count: u32, buf: &[u8]) -> BlockResult {
+        let at = self.locate(lba, count)?;
+        let mut done = 0u64;
+        for piece in buf.chunks(BOUNCE_BLOCKS * DATA_UNIT) {
+            let sealed = &mut self.bounce[..piece.len()];
+            sealed.copy_from_slice(piece);
+            let (units, _) = sealed.as_chunks_mut::<DATA_UNIT>();
+            for (i, unit) in units.iter_mut().enumerate() {
+                self.cipher.encrypt(lba + done + i as u64, unit);
+            }
+            let blocks = units.len() as u64;
+            self.inner.write_blocks(at + done, blocks as u32, sealed)?;
+            done += blocks;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> BlockResult {
+        self.inner.flush()
+    }
+
+    /// Not passed on, which is `dm-crypt`'s default and for its reason: a
+    /// discarded block reads back as zeroes or as the flash's erased pattern,
+    /// not as ciphertext, so anybody holding the disk could map which of the
+    /// volume's blocks are in use — how full it is, where its btree lives,
+    /// how big the files are. That is a fact the encryption exists to keep.
+    /// The filesystem's hint is answered `Ok` and dropped, and an encrypted
+    /// disk wears as it did before discard existed.
+    fn discard(&mut self, lba: u64, count: u32) -> BlockResult {
+        self.locate(lba, count).map(|_| ())
+    }
+
+    /// Whatever the device under it answers, with a copy of the key schedule
+    /// and a bounce buffer of its own: the buffer is per-call scratch, and a
+    /// shared one would have two CPUs' writes encrypt into the same bytes.
+    fn share(&self) -> Option<Box<dyn BlockDevice>> {
+        let inner = self.inner.share()?;
+        Some(Box::new(Self {
+            inner,
+            cipher: self.cipher.clone(),
+            data_offset: self.data_offset,
+            bounce: vec![0u8; BOUNCE_BLOCKS * DATA_UNIT].into_boxed_slice(),
+        }))
+    }
+}
+
+/// The device `/home` is to be probed on: `dev` itself if it is not
+/// encrypted, the unlocked volume if it is and the passphrase was given, and
+/// `None` if it stays locked — which the caller treats as no disk at all.
+///
+/// A header that does not parse is `None` rather than the plain device.
+/// Handing it on would be safe, since `bcachefs_adapter::probe` would call a
+/// disk whose block 0 says `TOYOS-ENCRYPTED` foreign, but the log line would
+/// then be "this disk is not ours", which is the wrong thing to tell the person
+/// whose disk it is.
+pub fn open(mut dev: Box<dyn BlockDevice>) -> Option<Box<dyn BlockDevice>> {
+    let mut block = vec![0u8; HEADER_BYTES];
+    // A read that fails is the plain path's to report: the designation probe
+    // reads the same block and says so in words that already exist.
+    if dev.read_blocks(0, 1, &mut block).is_err() {
+        return Some(dev);
+    }
+    let Some(bytes) = block.first_chunk::<HEADER_BYTES>() else {
+        return Some(dev);
+    };
+    let header = match Header::parse(bytes, dev.block_count()) {
+        Ok(header) => header,
+        Err(Error::NotEncrypted) => return Some(dev),
+        Err(e) => {
+            log!("crypt: block 0 is an encrypted volume whose header is refused ({}); /home stays locked", e);
+            return None;
+        }
+    };
+    log!(
+        "crypt: /home is encrypted ({} key slots, {} blocks)",
+        header.active_slots().count(),
+        dev.block_count().saturating_sub(header.data_offset())
+    );
+
+    let mut keys = KeyDecoder::new();
+    let mut prompt = Prompt::new();
+    for attempt in 1..=ATTEMPTS {
+        log!("crypt: type the passphrase for /home and press Enter, or Esc to boot without it");
+        panic_console::boot_checkpoint();
+        match read_passphrase(&mut keys, &mut prompt) {
+            Step::Submit => {}
+            Step::Cancel => {
+                log!("crypt: passphrase declined; /home stays locked");
+                return None;
+            }
+            Step::Typing => {
+                log!("crypt: no key for {}; /home stays locked", IDLE.duration());
+                return None;
+            }
+        }
+        // The derivation runs here, on the one CPU there is, and takes as
+        // long as it was made to.
+        let unlocked = header.unlock(prompt.passphrase());
+        prompt.clear();
+        match unlocked {
+            Ok((master, slot)) => {
+                log!("crypt: unlocked with key slot {}", slot);
+                return Some(Box::new(CryptDevice::new(dev, &master, header.data_offset())));
+            }
+            Err(Error::WrongPassphrase) => {
+                log!("crypt: wrong passphrase ({} of {})", attempt, ATTEMPTS);
+            }
+            Err(e) => {
+                log!("crypt: cannot unlock ({}); /home stays locked", e);
+                return None;
+            }
+        }
+    }
+    log!("crypt: {} wrong passphrases; /home stays locked", ATTEMPTS);
+    None
+}
+
+/// Feed key transitions to `prompt` until it submits or cancels, or until
+/// [`IDLE`] passes with no byte from the keyboard — which is `Step::Typing`
+/// back.
+fn read_passphrase(keys: &mut KeyDecoder, prompt: &mut Prompt) -> Step {
+    let mut until = crate::clock::nanos_since_boot().saturating_add(IDLE.nanos());
+    while crate::clock::nanos_since_boot() < until {
+        // The pointer shares the port, as in the panic console's pager.
+        if let Some((byte, false)) = i8042::poll_byte() {
+            until = crate::clock::nanos_since_boot().saturating_add(IDLE.nanos());
+            match keys.feed(byte) {
+                KeyOutcome::Key { usage, pressed } => match prompt.key(usage, pressed) {
+                    Step::Typing => {}
+                    done => return done,
+                },
+                // A lost break code is a lost Shift release as often as not,
+                // and a lost make code is a missing key: either way what is
+                // in the buffer is not what was typed.
+                KeyOutcome::Lost => {
+                    *prompt = Prompt::new();
+                    log!("crypt: the keyboard lost bytes; start the passphrase again");
+                    panic_console::boot_checkpoint();
+                }
+                _ => {}
+            }
+        }
+        core::hint::spin_loop();
+    }
+    Step::Typing
+}
@@ -117,6 +117,18 @@ pub fn try_claim(class: DeviceType) -> Result<Arc<DeviceClaim>, ClaimError> {
             mouse::discard_queued();
             Ok(DeviceClaim::new(class, DeviceInfo::Events, claim))
         }
+        DeviceType::Gamepad => {
+            let claim = Claim::acquire(class)?;
+            crate::gamepad::discard_queued();
+            Ok(DeviceClaim::new(class, DeviceInfo::Events, claim))
+        }
+        // Present whether or not the SCI is: a machine whose firmware never
+        // handed it over still has a holder, which simply never hears a press.
+        DeviceType::Power => {
+            let claim = Claim::acquire(class)?;
+            crate::power::discard_queued();
+            Ok(DeviceClaim::new(class, DeviceInfo::Events, claim))
+        }
         DeviceType::Framebuffer => {
             let screen = (*FB_INFO.lock()).clone().ok_or(ClaimError::Absent)?;
             let claim = Claim::acquire(class)?;
@@ -138,6 +150,20 @@ pub fn try_claim(class: DeviceType) -> Result<Arc<DeviceClaim>, ClaimError> {
             let claim = Claim::acquire(class)?;
             Ok(DeviceClaim::new(class, DeviceInfo::VirtioSound(info, shm(dma)), claim))
         }
+        DeviceType::UsbAudio => {
+            let (info, pcm) = crate::drivers::xhci::audio::info().ok_or(ClaimError::Absent)?;
+            let claim = Claim::acquire(class)?;
+            Ok(DeviceClaim::new(class, DeviceInfo::UsbAudio(info, shm(pcm)), claim))
+        }
+        DeviceType::Vsock => {
+            let info = crate::drivers::virtio_vsock::info().ok_or(ClaimError::Absent)?;
+            let claim = Claim::acquire(class)?;
+            // Packets that arrived while nobody held the device were for
+            // connections nobody had, and the next holder would read them as
+            // its own.
+            crate::drivers::virtio_vsock::discard_queued();
+            Ok(DeviceClaim::new(class, DeviceInfo::Vsock(info), claim))
+        }
     }
 }
 
@@ -20,9 +20,11 @@
 
 use alloc::vec::Vec;
 use core::mem::{offset_of, size_of};
-use core::ptr::{read_unaligned, read_volatile};
-use core::sync::atomic::{AtomicU16, Ordering};
+use core::ptr::{read_unaligned, read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+use toyos_acpi::sleep::{FACS_WAKING_VECTOR, FACS_X_WAKING_VECTOR};
 use crate::log;
+use crate::sync::Lock;
 use crate::DirectMap;
 
 pub struct MadtInfo {
@@ -312,6 +314,19 @@ const SLP_EN: u16 = 1 << 13;
 static PM1A_CNT_PORT: AtomicU16 = AtomicU16::new(0);
 static SLP_TYPA: AtomicU16 = AtomicU16::new(0);
 
+/// The PM1a and PM1b control ports, for [`sleep`], zero for a half the machine
+/// does not have. Separate from [`PM1A_CNT_PORT`] because that one's being
+/// non-zero is also [`shutdown`]'s evidence that `\_S5_` was found, and a
+/// sleep state's type comes from AML rather than from this file.
+static PM1_CNT: [AtomicU16; 2] = [const { AtomicU16::new(0) }; 2];
+
+/// The FACS's physical address, zero when firmware gave none that a waking
+/// vector can be written into.
+static FACS: AtomicU64 = AtomicU64::new(0);
+
+/// The DSDT [`init_power`] validated, for whatever else reads AML out of it.
+static DSDT: Lock<Option<Table>> = Lock::new(None);
+
 /// A firmware table whose declared length has been checked to cover the bytes
 /// the caller is about to read, and whose declared bytes sum to zero.
 ///
@@ -377,9 +392,17 @@ impl Table {
         Some(self.base.field(offset))
     }
 
+    /// The whole table, header included, copied out. For a reader that walks
+    /// AML, whose offsets come from the bytes themselves and are bounded by
+    /// the slice rather than checked one field at a time.
+    pub fn to_vec(&self) -> Vec<u8> {
+        (0..self.len).map(|i| self.base.byte(i)).collect()
+    }
+
     /// Like [`Table::field`], but for a caller that has already proved the
-    /// length covers `offset`. Used only inside the MADT walk, whose bound is
-    /// re-established per entry.
+    /// length covers `offset`. Used inside the MADT walk, whose bound is
+    /// re-established per entry, and by [`sci_config`], whose every field ends
+    /// before the length it had [`find_table`] demand.
     fn field_unchecked<T: Copy>(&self, offset: usize) -> T {
         self.base.field(offset)
     }
@@ -467,6 +490,33 @@ pub fn find_table(rsdp_addr: u64, signature: &[u8; 4], needed: usize) -> Result<
     Err(TableError::Absent)
 }
 
+/// Every SSDT in the XSDT, in the order it lists them, which is the order
+/// their definitions are to be loaded in.
+///
+/// Unlike [`find_table`], every match is the answer: an SSDT is one of
+/// several definition blocks and not a second opinion on one. A table that
+/// fails to validate is logged and left out, and the rest still load — a
+/// namespace without one CPU's power states is a machine that boots.
+pub fn ssdts(rsdp_addr: u64) -> Vec<Table> {
+    let xsdt = match xsdt(rsdp_addr) {
+        Ok(table) => table,
+        Err(e) => return refuse("XSDT", e).unwrap_or_default(),
+    };
+    let entry_count = (xsdt.len - size_of::<SdtHeader>()) / size_of::<u64>();
+    let mut tables = Vec::new();
+    for i in 0..entry_count {
+        let offset = size_of::<SdtHeader>() + i * size_of::<u64>();
+        let Some(phys) = xsdt.field::<u64>(offset) else { break };
+        let Some(base) = table_at(phys) else { continue };
+        match Table::open(base, b"SSDT", size_of::<SdtHeader>()) {
+            Ok(table) => tables.push(table),
+            Err(TableError::Absent) => {}
+            Err(e) => log!("ACPI: SSDT at {phys:#x} unusable: {e:?}"),
+        }
+    }
+    tables
+}
+
 /// Log a refusal with the reason firmware earned, and hand the caller a
 /// `None`. Every one of these lines is a line the owner of a machine that
 /// will not boot needs to see; none of them may be a panic.
@@ -515,6 +565,9 @@ pub fn init_power(rsdp_addr: u64) {
         return;
     };
     let pm1a = pm1a as u16;
+    PM1_CNT[0].store(pm1a, Ordering::Relaxed);
+    let pm1b = fadt.field::<u32>(offset_of!(Fadt, pm1b_control_block)).unwrap_or(0);
+    PM1_CNT[1].store(u16::try_from(pm1b).unwrap_or(0), Ordering::Relaxed);
 
     // Prefer X_DSDT (64-bit, ACPI 2.0+) over DSDT (32-bit). A revision that
     // claims 2.0 does not prove the table is long enough to hold the field,
@@ -524,6 +577,14 @@ pub fn init_power(rsdp_addr: u64) {
         Some(r) if r >= 2 => fadt.field::<u64>(offset_of!(Fadt, x_dsdt)).filter(|a| *a != 0),
         _ => None,
     };
+    match facs(&fadt, revision) {
+        Some(addr) => {
+            FACS.store(addr, Ordering::Relaxed);
+            log!("ACPI: FACS at {addr:#x}");
+        }
+        None => log!("ACPI: no usable FACS — no waking vector, so no S3"),
+    }
+
     let dsdt_addr = match x_dsdt {
         Some(addr) => addr,
         None => fadt.field::<u32>(offset_of!(Fadt, dsdt)).unwrap_or(0) as u64,
@@ -548,6 +609,8 @@ pub fn init_power(rsdp_addr: u64) {
         }
     };
 
+    *DSDT.lock() = Some(dsdt);
+
     let Some(slp_typ) = find_s5_slp_typ(&dsdt) else {
         log!("ACPI: no \\_S5_ package in the DSDT — no soft-off");
         return;
@@ -558,6 +621,93 @@ pub fn init_power(rsdp_addr: u64) {
     log!("ACPI: PM1a={pm1a:#x} SLP_TYPa={slp_typ}");
 }
 
+/// The FACS the FADT names, if it is one a waking vector can be written into.
+///
+/// Not through [`Table::open`]: the FACS has no SDT header and no checksum,
+/// because firmware and the OS both write it. What is checked instead is
+/// `toyos_acpi`'s — the signature and a length that covers the X vector — and
+/// the 64-byte alignment the specification requires, which is also what makes
+/// both vectors naturally aligned for [`set_waking_vector`]'s volatile writes.
+fn facs(fadt: &Table, revision: Option<u8>) -> Option<u64> {
+    let legacy = fadt.field::<u32>(offset_of!(Fadt, firmware_ctrl)).unwrap_or(0);
+    let wide = match revision {
+        Some(r) if r >= 2 => fadt.field::<u64>(offset_of!(Fadt, x_firmware_ctrl)).unwrap_or(0),
+        _ => 0,
+    };
+    let addr = toyos_acpi::sleep::facs_address(legacy, wide)?;
+    let base = table_at(addr).filter(|_| addr.is_multiple_of(64))?;
+    let header: [u8; 8] = base.field(0);
+    toyos_acpi::sleep::is_facs(&header).then_some(addr)
+}
+
+/// Point firmware's S3 resume at `phys`, entered in real mode, and clear the
+/// 64-bit vector so a value some earlier boot left there cannot win. False on
+/// a machine with no FACS, which is a machine that cannot be woken.
+pub fn set_waking_vector(phys: u32) -> bool {
+    let facs = FACS.load(Ordering::Relaxed);
+    if facs == 0 {
+        return false;
+    }
+    let base = DirectMap::from_phys(facs).as_mut_ptr::<u8>();
+    // SAFETY: `write_volatile` asks for a valid, aligned destination. `facs`
+    // passed `facs()` above: below `MAX_PHYS`, so direct-mapped, 64-byte
+    // aligned, and at least 64 bytes of FACS by its own length — so offset 12
+    // is an aligned `u32` and offset 24 an aligned `u64` inside it. These two
+    // fields are the ones ACPI gives the OS to write; volatile because firmware,
+    // not this kernel, is the reader.
+    unsafe {
+        write_volatile(base.add(FACS_WAKING_VECTOR).cast::<u32>(), phys);
+        write_volatile(base.add(FACS_X_WAKING_VECTOR).cast::<u64>(), 0);
+    }
+    true
+}
+
+/// Whether [`sleep`] has ports to write. False also for a hardware-reduced
+/// machine, whose FADT names no PM1 block at all.
+pub fn can_sleep() -> bool {
+    PM1_CNT[0].load(Ordering::Relaxed) != 0 && FACS.load(Ordering::Relaxed) != 0
+}
+
+/// Write `SLP_TYPa`/`SLP_TYPb` and then `SLP_EN` into the PM1 control ports.
+///
+/// On a machine that honours it this does not return until the wake, and
+/// returns into whatever `arch::sleep` put at the waking vector — never here.
+/// The caller owns what happens if it does return here: the write was refused
+/// or firmware is slow, and only the caller knows how long to give it.
+pub fn sleep(slp_typ: (u8, u8)) {
+    let ports = PM1_CNT.each_ref().map(|port| port.load(Ordering::Relaxed));
+    let mut enables = [0u16; 2];
+    for (i, (&port, typ)) in ports.iter().zip([slp_typ.0, slp_typ.1]).enumerate() {
+        if port == 0 {
+            continue;
+        }
+        let [typed, enable] = toyos_acpi::sleep::control_writes(crate::arch::cpu::inw(port), typ);
+        enables[i] = enable;
+        // SAFETY: `outw` asks its caller to own the port and the word. The port
+        // is `PM1x_CNT_BLK` as the FADT named it — `shutdown`'s argument — and
+        // the word is what the register read a moment ago with only `SLP_TYP`
+        // replaced, so `SCI_EN` and every other bit firmware owns is kept.
+        unsafe { crate::arch::cpu::outw(port, typed) };
+    }
+    // SAFETY: `wbinvd` is sound on a CPU whose caches are about to lose power,
+    // and the write below is what removes it: nothing this CPU writes after
+    // the flush has to survive, because the wake resumes from the context
+    // `arch::sleep` saved before it called here.
+    unsafe { crate::arch::cpu::wbinvd() };
+    for (&port, enable) in ports.iter().zip(enables) {
+        if port != 0 {
+            // SAFETY: the same port, and the same word with `SLP_EN` added —
+            // ACPI's definition of entering the state.
+            unsafe { crate::arch::cpu::outw(port, enable) };
+        }
+    }
+}
+
+/// The DSDT, once [`init_power`] has found and validated it.
+pub fn dsdt() -> Option<Table> {
+    *DSDT.lock()
+}
+
 /// FADT revision and the IA-PC boot architecture flags.
 ///
 /// Bit 1 of the flags is "the motherboard has a port 60/64 keyboard
@@ -631,6 +781,53 @@ pub fn rtc_century_register(rsdp_addr: u64) -> Result<Option<u8>, TableError> {
     Ok(Some(index))
 }
 
+/// What the FADT says about the System Control Interrupt and the fixed
+/// hardware behind it, raw: which of these fields describe a register that
+/// exists is `toyos-acpi`'s to decide, and `drivers::sci` asks it.
+#[derive(Clone, Copy, Debug)]
+pub struct SciConfig {
+    /// The ISA IRQ the SCI is wired to, before any MADT override.
+    pub irq: u16,
+    pub smi_cmd: u32,
+    pub acpi_enable: u8,
+    pub pm1a_event: u32,
+    pub pm1b_event: u32,
+    pub pm1_event_len: u8,
+    pub pm1a_control: u32,
+    pub gpe0: u32,
+    pub gpe0_len: u8,
+    pub gpe1: u32,
+    pub gpe1_len: u8,
+    pub gpe1_base: u8,
+    pub flags: u32,
+}
+
+/// Read [`SciConfig`] out of the FADT.
+///
+/// Everything through `Flags` is in the ACPI 1.0 table, so there is no
+/// revision to consult and no field that may be missing: a FADT too short to
+/// hold them all is refused whole, as `find_table` refuses any table shorter
+/// than its caller needs.
+pub fn sci_config(rsdp_addr: u64) -> Result<SciConfig, TableError> {
+    const NEEDED: usize = offset_of!(Fadt, flags) + size_of::<u32>();
+    let fadt = find_table(rsdp_addr, b"FACP", NEEDED)?;
+    Ok(SciConfig {
+        irq: fadt.field_unchecked(offset_of!(Fadt, sci_interrupt)),
+        smi_cmd: fadt.field_unchecked(offset_of!(Fadt, smi_command_port)),
+        acpi_enable: fadt.field_unchecked(offset_of!(Fadt, acpi_enable)),
+        pm1a_event: fadt.field_unchecked(offset_of!(Fadt, pm1a_event_block)),
+        pm1b_event: fadt.field_unchecked(offset_of!(Fadt, pm1b_event_block)),
+        pm1_event_len: fadt.field_unchecked(offset_of!(Fadt, pm1_event_length)),
+        pm1a_control: fadt.field_unchecked(offset_of!(Fadt, pm1a_control_block)),
+        gpe0: fadt.field_unchecked(offset_of!(Fadt, gpe0_block)),
+        gpe0_len: fadt.field_unchecked(offset_of!(Fadt, gpe0_block_length)),
+        gpe1: fadt.field_unchecked(offset_of!(Fadt, gpe1_block)),
+        gpe1_len: fadt.field_unchecked(offset_of!(Fadt, gpe1_block_length)),
+        gpe1_base: fadt.field_unchecked(offset_of!(Fadt, gpe1_base)),
+        flags: fadt.field_unchecked(offset_of!(Fadt, flags)),
+    })
+}
+
 /// Trigger ACPI S5 (soft-off) shutdown.
 pub fn shutdown() -> ! {
     // Last chance: nothing drains the log ring after this point.
@@ -0,0 +1,995 @@
+//! AHCI, as a [`BlockDevice`] per SATA drive, read-write for a disk and
+//! read-only for an optical drive.
+//!
+//! The controller QEMU's q35 machine has built in, and the one most desktops
+//! and older laptops put their disk behind: a guest that drives only NVMe and
+//! virtio boots them with `/home` on a tmpfs. Every number this driver takes
+//! from the controller or the drive — which ports to walk, what is on one, how
+//! many slots to queue on, how big a sector is, whether a command failed — is
+//! decided by `toyos-ahci` and tested on the host; what is here is the MMIO and
+//! the DMA that act on those decisions.
+//!
+//! **Several commands outstanding per drive, and completion out of order.**
+//! Unlike `nvme` and `virtio_blk`, a transfer here is split across up to
+//! [`MAX_SLOTS`] command slots and issued as one batch of READ/WRITE FPDMA
+//! QUEUED, which the drive is free to finish in any order; [`toyos_ahci::Slots`]
+//! is what turns a pair of PxCI/PxSACT reads into "these slots are done". The
+//! batch is still waited for whole before the call returns, so a caller sees one
+//! synchronous operation exactly as it does on the other two drivers. A drive or
+//! a controller without NCQ gets the same path one READ/WRITE DMA EXT at a time.
+//!
+//! **An optical drive is the same port and a different command.** ATAPI is
+//! SCSI inside the ATA PACKET command: the port sends the FIS and then the
+//! command table's ATAPI area, and everything from the slot to the PRD entry
+//! to the recovery is what a disk has. So a drive whose signature says ATAPI
+//! is bound here too, on the same machinery, and is read with READ (10) one
+//! command at a time. It is never written: the handle refuses writes before
+//! the drive hears of them. What it is for is `iso9660_adapter`, which mounts
+//! it; that is how a machine booted off QEMU's `-cdrom` sees its disc.
+//!
+//! # Disks, not controllers
+//!
+//! What is numbered is the drive, in PCI order and then port order, and the
+//! shape a caller holds is `virtio_blk`'s: [`count`] and [`open`], a handle per
+//! caller, the state behind one lock per drive. A machine with two controllers
+//! has one list of disks.
+//!
+//! Discs are a second list, [`disc_count`] and [`open_disc`], so that disk 0
+//! is the first *disk* whatever port a CD drive sits on before it — `/home`
+//! goes on AHCI disk 0, and a disc is not somewhere to put it. Device ids and
+//! DMA windows are still handed out in port order across both lists.
+//!
+//! # Bounds
+//!
+//! [`COMMAND`] is `nvme`'s budget for `nvme`'s reason, with one difference its
+//! consequence states: a port can be *stopped*, and a stopped port has given
+//! every command it held back to the driver (AHCI 1.3.1 §10.3.2). So a command
+//! nobody answered costs this driver a port restart, not the disk — unless the
+//! port will not stop, which is a controller no longer following its own
+//! specification, and that does end the disk.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use toyos_ahci::atapi::{self, Capacity, Cdb, PacketIdentify, Sense};
+use toyos_ahci::fis::{self, Fis};
+use toyos_ahci::port::{self as px, Attached, Fault};
+use toyos_ahci::{hba, trim, Capabilities, Identify, Slots};
+
+use super::pci::PciDevice;
+use super::DmaPool;
+use crate::block::{self, BlockDevice, BlockError, BlockResult, DeviceId};
+use crate::log;
+use crate::mm::paging::CachePolicy;
+use crate::mm::{Dma, Mmio};
+use crate::scheduler::Operation;
+use crate::sync::Lock;
+use crate::time::{Bound, Budget, Deadline, Duration};
+
+/// Drives this driver binds, disks and discs together: the six ports of the
+/// ICH9 that q35 builds in. virtio's ids start six above this driver's, so
+/// this is a ceiling on ids as well as on DMA — `virtio_blk`'s argument for
+/// its own.
+const MAX_DRIVES: usize = 6;
+
+/// Where AHCI drives start in the [`DeviceId`] space: after NVMe's 1 and before
+/// virtio's 8.
+const AHCI_DEVICE_ID_BASE: DeviceId = 2;
+
+/// Command slots this driver queues on per drive, whatever more the controller
+/// and the drive would take: eight slots of [`SLOT_PAGES`] are one
+/// `MAX_DATA_PAGES` batch of the other two drivers.
+const MAX_SLOTS: u32 = 8;
+/// Pages of data one command moves. One PRD entry per command, over a
+/// contiguous run of the slot's own window.
+const SLOT_PAGES: usize = 4;
+const SLOT_BYTES: usize = SLOT_PAGES * 0x1000;
+
+// Per-disk DMA window (byte offsets). The alignments are §4.2's: the command
+// list 1 KiB, the received-FIS area 256 bytes, each command table 128.
+const OFF_COMMAND_LIST: usize = 0x0000;
+const OFF_RECEIVED_FIS: usize = 0x0400;
+/// The IDENTIFY page at bind, TRIM's range entries after it, and a disc's
+/// READ CAPACITY answer.
+const OFF_SCRATCH: usize = 0x1000;
+const SCRATCH_BYTES: usize = 0x1000;
+const OFF_TABLES: usize = 0x2000;
+/// One command table: the 64-byte CFIS, the ATAPI command, the reserved bytes,
+/// and one PRD entry at 0x80 — rounded to the next 128.
+const TABLE_BYTES: usize = 0x100;
+const TABLE_ACMD: usize = 0x40;
+const TABLE_PRD: usize = 0x80;
+const OFF_DATA: usize = 0x3000;
+const WINDOW: usize = OFF_DATA + MAX_SLOTS as usize * SLOT_BYTES;
+
+/// How long one batch of commands may spend in the drive before this driver
+/// stops believing the completions are coming.
+///
+/// `nvme::COMMAND`'s number and derivation: the term
+/// [`crate::block::OPERATION`] already spends on the command in flight.
+const COMMAND: Budget = Budget::of(
+    Duration::from_secs(2),
+    "the commands are taken back by stopping the port, the port is restarted, \
+     and the operation fails; a port that will not stop ends the disk",
+);
+
+/// PxCMD.CR and PxCMD.FR after ST and FRE are cleared.
+const PORT_STOP: Bound = Bound::from_spec(
+    Duration::from_millis(500),
+    "AHCI 1.3.1 §10.1.2: the HBA clears PxCMD.CR and PxCMD.FR within 500 milliseconds",
+);
+
+/// Firmware letting go of the controller once asked.
+const HANDOFF: Bound = Bound::from_spec(
+    Duration::from_secs(2),
+    "AHCI 1.3.1 §10.6.3: firmware that sets BOHC.BB has two seconds to finish",
+);
+
+/// A drive settling to not-busy before its port is started. A spinning disk
+/// out of a link reset can take seconds; QEMU's takes none.
+const DRIVE_READY: Budget = Budget::of(
+    Duration::from_secs(2),
+    "the port is not started and the drive behind it is not bound",
+);
+
+/// UNIT ATTENTIONs [`AhciDisk::ready`] asks past before it takes the drive at
+/// its word. One is what a reset leaves; a drive that says it every time is
+/// not going to stop.
+const UNIT_ATTENTIONS: u32 = 3;
+
+/// Why a batch produced no result this driver may use.
+enum Failure {
+    /// PxIS said so, and [`Fault`] says which kind.
+    Fault(Fault),
+    /// Nothing happened inside [`COMMAND`].
+    Silent,
+}
+
+impl core::fmt::Display for Failure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Fault(fault) => write!(f, "{fault}"),
+            Self::Silent => write!(f, "no completion in {}", COMMAND.duration()),
+        }
+    }
+}
+
+/// Which way a [`AhciDisk::transfer`] moves data, and the caller's end of it.
+enum Transfer<'a> {
+    Read(&'a mut [u8]),
+    Write(&'a [u8]),
+}
+
+/// One bound drive: its port's registers and DMA window, and what it reported
+/// about itself.
+struct AhciDisk {
+    /// Where it is in [`DISKS`] or [`DISCS`], whichever holds it.
+    index: usize,
+    id: DeviceId,
+    /// An optical drive: every command is a PACKET, reads are READ (10), and
+    /// nothing is written.
+    atapi: bool,
+    port: u8,
+    regs: Mmio,
+    dma: Dma<'static>,
+    slots: Slots,
+    /// READ/WRITE FPDMA QUEUED rather than READ/WRITE DMA EXT: both the
+    /// controller and the drive have NCQ.
+    ncq: bool,
+    /// Capacity in 4 KiB blocks; a trailing partial block is not served.
+    /// A disc is the exception: see `disc_sectors`.
+    blocks: u64,
+    /// A disc's size in its own sectors, from READ CAPACITY at bind, which
+    /// [`resume`] holds the drive to. Zero on a disk.
+    ///
+    /// A disc's trailing half block *is* served — its last [`blocks`](Self::blocks)
+    /// entry is read to here and zero past it — because a CD with an odd number
+    /// of 2048-byte sectors is ordinary, and ISO 9660 refuses a volume longer
+    /// than the device it is on.
+    disc_sectors: u64,
+    lba_bytes: u32,
+    trim: bool,
+    /// Blocks of range entries one DATA SET MANAGEMENT carries: the drive's
+    /// number, and no more than the scratch page holds.
+    trim_blocks: usize,
+    write_cache: bool,
+    /// Whether the port did not come back after a failed command. `nvme`'s
+    /// latch, reached by a different road.
+    failed: bool,
+}
+
+impl AhciDisk {
+    fn sectors_per_block(&self) -> u64 {
+        4096 / self.lba_bytes as u64
+    }
+
+    /// What the log calls it, so a line about a disc does not read as one
+    /// about the disk with the same index.
+    fn noun(&self) -> &'static str {
+        if self.atapi { "disc" } else { "disk" }
+    }
+
+    /// Whether a batch may be issued at all. Read between batches, never inside
+    /// one — see `NvmeController::may_issue`.
+    fn may_issue(&self, until: Deadline, op: &str, at: u64, count: u32) -> bool {
+        if self.failed {
+            return false;
+        }
+        if until.reached(crate::clock::now()) {
+            log!(
+                "AHCI: {op} of {count} blocks at {at} on {} {} not issued: {}",
+                self.noun(),
+                self.index,
+                block::OPERATION
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Write slot `slot`'s command header, command table and PRD entry.
+    /// `data` is the slot's transfer, as a window offset and a byte count; a
+    /// command with none has no PRD entry. `cdb` is a PACKET's SCSI command,
+    /// which goes in the table's ATAPI area and sets the header's A bit.
+    fn prepare(&self, slot: u8, command: Fis, cdb: Option<Cdb>, write: bool, data: Option<(usize, u32)>) {
+        let dma = self.dma;
+        let table = OFF_TABLES + slot as usize * TABLE_BYTES;
+        let table_phys = dma.phys() + table as u64;
+        dma.write(table, command.0);
+        if let Some(cdb) = cdb {
+            dma.write(table + TABLE_ACMD, cdb.0);
+        }
+        let prdt = match data {
+            Some((off, bytes)) => {
+                let at = dma.phys() + off as u64;
+                dma.write(table + TABLE_PRD, [at as u32, (at >> 32) as u32, 0, fis::prd_dw3(bytes)]);
+                1
+            }
+            None => 0,
+        };
+        let atapi = if cdb.is_some() { fis::HEADER_ATAPI } else { 0 };
+        let header: [u32; 8] = [
+            fis::header_dw0(write, prdt) | atapi,
+            0,
+            table_phys as u32,
+            (table_phys >> 32) as u32,
+            0,
+            0,
+            0,
+            0,
+        ];
+        dma.write(OFF_COMMAND_LIST + slot as usize * 32, header);
+    }
+
+    /// Hand the slots in `mask` to the port, and wait for every one of them.
+    ///
+    /// **PxSACT before PxCI, for the queued commands.** §5.3.10: the drive
+    /// reports a queued command's completion by clearing its PxSACT bit, so the
+    /// bit has to be set before the command can reach the drive.
+    fn run(&mut self, mask: u32, queued: bool) -> Result<(), Failure> {
+        let regs = self.regs;
+        if queued {
+            regs.write_u32(px::PX_SACT, mask);
+        }
+        regs.write_u32(px::PX_CI, mask);
+
+        // Done is every slot in `mask` clear in both registers, or a fault;
+        // decided again after the spin, which only says when to stop looking.
+        let answered = crate::clock::settles(COMMAND.nanos(), || {
+            let busy = regs.read_u32(px::PX_CI) | regs.read_u32(px::PX_SACT);
+            busy & mask == 0 || Fault::decide(regs.read_u32(px::PX_IS), regs.read_u32(px::PX_TFD)).is_some()
+        });
+        let is = regs.read_u32(px::PX_IS);
+        if let Some(fault) = Fault::decide(is, regs.read_u32(px::PX_TFD)) {
+            return Err(Failure::Fault(fault));
+        }
+        if !answered {
+            return Err(Failure::Silent);
+        }
+        // W1C: the completion bits every command leaves, so the next batch's
+        // fault check reads only its own.
+        regs.write_u32(px::PX_IS, is);
+        let settled = self.slots.settle(regs.read_u32(px::PX_CI), regs.read_u32(px::PX_SACT));
+        if settled.strangers != 0 {
+            log!(
+                "AHCI: port {} reports slots {:#010x} busy that this driver never issued",
+                self.port,
+                settled.strangers
+            );
+        }
+        Ok(())
+    }
+
+    /// One command that is not queued, in slot 0 of an idle port.
+    fn command(&mut self, command: Fis, write: bool, data: Option<(usize, u32)>) -> Result<(), Failure> {
+        let Some(slot) = self.slots.take() else {
+            return Err(Failure::Silent);
+        };
+        self.prepare(slot, command, None, write, data);
+        self.run(1 << slot, false)
+    }
+
+    /// One PACKET carrying `cdb`, in slot 0 of an idle port; a disc's
+    /// [`command`](Self::command). Every one reads, if it moves data at all.
+    fn packet(&mut self, cdb: Cdb, data: Option<(usize, u32)>) -> Result<(), Failure> {
+        let Some(slot) = self.slots.take() else {
+            return Err(Failure::Silent);
+        };
+        self.prepare(slot, Fis::packet(), Some(cdb), false, data);
+        self.run(1 << slot, false)
+    }
+
+    /// TEST UNIT READY until the drive takes it, with [`recover`](Self::recover)
+    /// after each refusal for the reason any failed command gets one.
+    ///
+    /// UNIT ATTENTION is asked past: a drive says it once after a reset, which
+    /// is every bind and every resume, and then answers. NOT READY is the
+    /// answer — no disc, or one still spinning up, and this driver does not
+    /// wait for a drive to spin or watch for a disc put in later.
+    fn ready(&mut self) -> Result<(), Failure> {
+        let mut attentions = 0;
+        loop {
+            match self.packet(Cdb::test_unit_ready(), None) {
+                Ok(()) => return Ok(()),
+                Err(Failure::Fault(Fault::TaskFile { error, .. }))
+                    if Sense::from_error(error) == Sense::UnitAttention && attentions < UNIT_ATTENTIONS =>
+                {
+                    self.recover();
+                    attentions += 1;
+                }
+                Err(why) => {
+                    self.recover();
+                    return Err(why);
+                }
+            }
+        }
+    }
+
+    /// READ CAPACITY (10), decided by `toyos-ahci`.
+    fn capacity(&mut self) -> Result<Result<Capacity, atapi::CapacityError>, Failure> {
+        if let Err(why) = self.packet(Cdb::read_capacity(), Some((OFF_SCRATCH, atapi::CAPACITY_BYTES as u32))) {
+            self.recover();
+            return Err(why);
+        }
+        let mut answer = [0u8; atapi::CAPACITY_BYTES];
+        self.dma.copy_to(OFF_SCRATCH, &mut answer);
+        Ok(Capacity::parse(&answer))
+    }
+
+    /// Stop the port: ST and then FRE, each waited out. `false` is a port still
+    /// running after [`PORT_STOP`], which is still processing a command list
+    /// this driver can no longer reason about.
+    fn stop(&self) -> bool {
+        let regs = self.regs;
+        let cmd = regs.read_u32(px::PX_CMD);
+        regs.write_u32(px::PX_CMD, cmd & !px::CMD_ST);
+        if !crate::clock::settles(PORT_STOP.nanos(), || regs.read_u32(px::PX_CMD) & px::CMD_CR == 0) {
+            return false;
+        }
+        let cmd = regs.read_u32(px::PX_CMD);
+        regs.write_u32(px::PX_CMD, cmd & !px::CMD_FRE);
+        crate::clock::settles(PORT_STOP.nanos(), || regs.read_u32(px::PX_CMD) & px::CMD_FR == 0)
+    }
+
+    /// Start a stopped port: FIS receive, clear errors, wait for the drive to
+    /// settle, then the command list (§10.3.1's order).
+    fn start(&self) -> bool {
+        let regs = self.regs;
+        regs.write_u32(px::PX_CMD, regs.read_u32(px::PX_CMD) | px::CMD_FRE);
+        regs.write_u32(px::PX_SERR, u32::MAX);
+        regs.write_u32(px::PX_IS, u32::MAX);
+        if !crate::clock::settles(DRIVE_READY.nanos(), || px::idle(regs.read_u32(px::PX_TFD))) {
+            return false;
+        }
+        regs.write_u32(px::PX_CMD, regs.read_u32(px::PX_CMD) | px::CMD_ST);
+        true
+    }
+
+    /// Point a stopped port at this disk's command list and received-FIS area,
+    /// both zeroed, and start it. Shared by [`bind_port`] and [`resume`]: what
+    /// a power loss takes from a port is exactly these four registers and the
+    /// run bits.
+    fn attach(&self) -> bool {
+        let (regs, dma) = (self.regs, self.dma);
+        dma.zero();
+        let list = dma.phys() + OFF_COMMAND_LIST as u64;
+        let received = dma.phys() + OFF_RECEIVED_FIS as u64;
+        regs.write_u32(px::PX_CLB, list as u32);
+        regs.write_u32(px::PX_CLBU, (list >> 32) as u32);
+        regs.write_u32(px::PX_FB, received as u32);
+        regs.write_u32(px::PX_FBU, (received >> 32) as u32);
+        regs.write_u32(px::PX_IE, 0);
+        self.start()
+    }
+
+    /// Take back whatever the port holds and bring it up again (§6.2.2.1).
+    ///
+    /// A drive still busy after the stop would need a COMRESET, which this
+    /// driver does not send; that and a port that will not stop both end the
+    /// disk, once and loudly.
+    fn recover(&mut self) {
+        self.slots.abandon();
+        if self.stop() && self.start() {
+            return;
+        }
+        if !self.failed {
+            self.failed = true;
+            log!(
+                "AHCI: {} {} is offline: port {} did not come back after a failed command, and \
+                 this driver sends no COMRESET",
+                self.noun(),
+                self.index,
+                self.port
+            );
+        }
+    }
+
+    /// Move `count` blocks from `block` through the slots, as many commands
+    /// at once as there are slots. `count` is at most one batch: the depth
+    /// times [`SLOT_PAGES`].
+    fn transfer(&mut self, block: u64, count: u32, mut buf: Transfer<'_>, until: Deadline) -> BlockResult {
+        let write = matches!(buf, Transfer::Write(_));
+        assert!(!(write && self.atapi), "a write reached a disc past the handle's refusal");
+        let op = if write { "write" } else { "read" };
+        if !self.may_issue(until, op, block, count) {
+            return Err(BlockError);
+        }
+        let per = self.sectors_per_block();
+        let mut chunks = [(0u8, 0usize, 0usize, 0usize); MAX_SLOTS as usize];
+        let mut issued = 0usize;
+        let mut mask = 0u32;
+        let mut at = 0u32;
+        while at < count {
+            let Some(slot) = self.slots.take() else { break };
+            let pages = (count - at).min(SLOT_PAGES as u32);
+            let bytes = pages as usize * 4096;
+            let off = OFF_DATA + slot as usize * SLOT_BYTES;
+            let from = at as usize * 4096;
+            if let Transfer::Write(data) = buf {
+                // Exclusive: the command naming this window has not been issued.
+                self.dma.copy_from(off, &data[from..from + bytes]);
+            }
+            let lba = (block + at as u64) * per;
+            // Exact: at most `SLOT_PAGES * 8` sectors.
+            let mut sectors = (pages as u64 * per) as u16;
+            let (command, cdb) = if self.atapi {
+                // Cut at the disc's end, whose half block is zeros below.
+                sectors = (sectors as u64).min(self.disc_sectors.saturating_sub(lba)) as u16;
+                // Exact: a disc's capacity came from READ CAPACITY (10),
+                // whose last LBA is 32 bits.
+                (Fis::packet(), Some(Cdb::read(lba as u32, sectors)))
+            } else if self.ncq {
+                (Fis::queued(write, lba, sectors, slot), None)
+            } else {
+                (Fis::dma(write, lba, sectors), None)
+            };
+            self.prepare(slot, command, cdb, write, Some((off, bytes as u32)));
+            chunks[issued] = (slot, from, bytes, sectors as usize * self.lba_bytes as usize);
+            issued += 1;
+            mask |= 1 << slot;
+            at += pages;
+        }
+        assert_eq!(at, count, "a transfer is at most one batch of slots");
+
+        if let Err(why) = self.run(mask, self.ncq) {
+            log!("AHCI: {op} of {count} blocks at {block} on {} {}: {why}", self.noun(), self.index);
+            self.recover();
+            return Err(BlockError);
+        }
+        if let Transfer::Read(data) = &mut buf {
+            // A copy out, after the drive has answered, so nothing holds a
+            // reference into a window it may write again.
+            for &(slot, from, bytes, moved) in &chunks[..issued] {
+                self.dma.copy_to(OFF_DATA + slot as usize * SLOT_BYTES, &mut data[from..from + moved]);
+                data[from + moved..from + bytes].fill(0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks one [`transfer`](Self::transfer) moves.
+    fn batch(&self) -> u32 {
+        self.slots.depth() * SLOT_PAGES as u32
+    }
+
+    /// TRIM `count` blocks from `block`, one DATA SET MANAGEMENT per scratch
+    /// page of range entries.
+    fn trim(&mut self, block: u64, count: u32, until: Deadline) -> BlockResult {
+        let per = self.sectors_per_block();
+        let mut sector = block * per;
+        let end = (block + count as u64) * per;
+        let mut entries = [0u64; SCRATCH_BYTES / 8];
+        let entries = &mut entries[..self.trim_blocks * trim::ENTRIES_PER_BLOCK];
+        while sector < end {
+            if !self.may_issue(until, "trim", sector / per, count) {
+                return Err(BlockError);
+            }
+            let covered = trim::fill(sector, end - sector, entries);
+            for (i, entry) in entries.iter().enumerate() {
+                self.dma.write(OFF_SCRATCH + i * 8, entry.to_le());
+            }
+            let bytes = (entries.len() * 8) as u32;
+            // Exact: `trim_blocks` is at most eight.
+            let command = Fis::trim(self.trim_blocks as u16);
+            if let Err(why) = self.command(command, true, Some((OFF_SCRATCH, bytes))) {
+                log!("AHCI: trim of {covered} sectors at {sector} on disk {}: {why}", self.index);
+                self.recover();
+                return Err(BlockError);
+            }
+            sector += covered;
+        }
+        Ok(())
+    }
+}
+
+/// Every disk [`init`] bound, in bind order. An entry is leaked and never
+/// removed, so an index names the same disk for the whole boot.
+static DISKS: Lock<Vec<&'static Lock<AhciDisk>>> = Lock::new(Vec::new());
+/// Every optical drive [`init`] bound with a disc in it, [`DISKS`]' shape.
+static DISCS: Lock<Vec<&'static Lock<AhciDisk>>> = Lock::new(Vec::new());
+/// The register window of every controller [`init`] put in AHCI mode, for
+/// [`resume`]: a power loss takes `GHC.AE` with it, and the ports behind a
+/// controller that is not in AHCI mode do not answer.
+static CONTROLLERS: Lock<Vec<Mmio>> = Lock::new(Vec::new());
+
+/// Disks bound this boot, so `0..count()` names every one.
+pub fn count() -> usize {
+    DISKS.lock().len()
+}
+
+/// A handle to the `index`-th disk, or `None` if there is no such disk.
+pub fn open(index: usize) -> Option<AhciBlockDevice> {
+    Some(handle(*DISKS.lock().get(index)?))
+}
+
+/// Discs bound this boot, so `0..disc_count()` names every one.
+pub fn disc_count() -> usize {
+    DISCS.lock().len()
+}
+
+/// A read-only handle to the `index`-th disc, or `None` if there is no such
+/// disc.
+pub fn open_disc(index: usize) -> Option<AhciBlockDevice> {
+    Some(handle(*DISCS.lock().get(index)?))
+}
+
+fn handle(disk: &'static Lock<AhciDisk>) -> AhciBlockDevice {
+    // Copied out once, for `virtio_blk::open`'s reason.
+    let (index, id, blocks, lba_bytes) = {
+        let d = disk.lock();
+        (d.index, d.id, d.blocks, d.lba_bytes)
+    };
+    AhciBlockDevice { disk, index, id, blocks, lba_bytes }
+}
+
+pub struct AhciBlockDevice {
+    disk: &'static Lock<AhciDisk>,
+    index: usize,
+    id: DeviceId,
+    blocks: u64,
+    lba_bytes: u32,
+}
+
+impl AhciBlockDevice {
+    /// The drive's own logical sector size, which is what a GPT on it is laid
+    /// out in.
+    pub fn logical_block_bytes(&self) -> u32 {
+        self.lba_bytes
+    }
+}
+
+impl BlockDevice for AhciBlockDevice {
+    fn device_id(&self) -> DeviceId {
+        self.id
+    }
+
+    fn block_count(&self) -> u64 {
+        self.blocks
+    }
+
+    /// The guard is a `let _op` for the reason `NvmeBlockDevice::read_blocks`
+    /// gives.
+    fn read_blocks(&mut self, lba: u64, count: u32, buf: &mut [u8]) -> BlockResult {
+        assert_eq!(buf.len(), count as usize * 4096);
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        let mut done = 0u32;
+        while done < count {
+            let batch = (count - done).min(disk.batch());
+            let at = done as usize * 4096;
+            let out = Transfer::Read(&mut buf[at..at + batch as usize * 4096]);
+            disk.transfer(lba + done as u64, batch, out, until)?;
+            done += batch;
+        }
+        Ok(())
+    }
+
+    /// Refused on a disc before anything is issued: nothing mounts one
+    /// writable, so a write here is a caller's mistake and not a question
+    /// for the drive.
+    fn write_blocks(&mut self, lba: u64, count: u32, buf: &[u8]) -> BlockResult {
+        assert_eq!(buf.len(), count as usize * 4096);
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        if disk.atapi {
+            log!("AHCI: write of {count} blocks at {lba} on disc {} refused: a disc is read-only", self.index);
+            return Err(BlockError);
+        }
+        let mut done = 0u32;
+        while done < count {
+            let batch = (count - done).min(disk.batch());
+            let at = done as usize * 4096;
+            let data = Transfer::Write(&buf[at..at + batch as usize * 4096]);
+            disk.transfer(lba + done as u64, batch, data, until)?;
+            done += batch;
+        }
+        Ok(())
+    }
+
+    /// A drive without a volatile write cache has nothing to flush, so a
+    /// completed write is already durable; `virtio_blk`'s reasoning, from
+    /// IDENTIFY words 82 and 85 rather than a feature bit. A failed disk is
+    /// refused for `nvme`'s reason.
+    fn flush(&mut self) -> BlockResult {
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        if disk.failed {
+            return Err(BlockError);
+        }
+        if !disk.write_cache {
+            return Ok(());
+        }
+        if !disk.may_issue(until, "flush", 0, 0) {
+            return Err(BlockError);
+        }
+        if let Err(why) = disk.command(Fis::flush(), false, None) {
+            log!("AHCI: cache flush failed on disk {}: {why}", self.index);
+            disk.recover();
+            return Err(BlockError);
+        }
+        Ok(())
+    }
+
+    /// DATA SET MANAGEMENT with the TRIM bit, on a drive whose IDENTIFY word
+    /// 169 says it has it; a drive without TRIM answers `Ok` having been told
+    /// nothing, as for `virtio_blk`.
+    fn discard(&mut self, lba: u64, count: u32) -> BlockResult {
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        if !disk.trim {
+            return Ok(());
+        }
+        disk.trim(lba, count, until)
+    }
+}
+
+/// Bind every SATA drive behind every AHCI controller on the machine, up to
+/// [`MAX_DRIVES`], and answer how many disks are now served; the discs are
+/// [`disc_count`].
+///
+/// No controller is a configuration and not a failure, as for NVMe. A port
+/// with something this driver does not serve on it — a port multiplier, an
+/// enclosure bridge — is named in the log and passed over.
+pub fn init(devices: &[PciDevice]) -> usize {
+    let found: Vec<PciDevice> =
+        devices.iter().filter(|d| d.matches_class(0x01, 0x06, Some(0x01))).copied().collect();
+    if found.is_empty() {
+        return 0;
+    }
+    // One pool for every drive, carved into windows of 140 KiB. Leaked for the
+    // reason `nvme`'s is.
+    let pool = DmaPool::alloc(MAX_DRIVES * WINDOW).leak();
+    let mut disks = DISKS.lock();
+    let mut discs = DISCS.lock();
+    let mut drives = 0;
+    for pci_dev in &found {
+        bind_controller(pci_dev, pool, &mut drives, &mut disks, &mut discs);
+    }
+    disks.len()
+}
+
+/// Bind the drives on one controller. `drives` counts every port claimed so
+/// far on every controller, bound or not, and is what a drive's device id and
+/// DMA window come from.
+fn bind_controller(
+    pci_dev: &PciDevice,
+    pool: Dma<'static>,
+    drives: &mut usize,
+    disks: &mut Vec<&'static Lock<AhciDisk>>,
+    discs: &mut Vec<&'static Lock<AhciDisk>>,
+) {
+    log!("AHCI: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+    // §2.1.11: the ABAR is BAR 5, and memory. Refused by name for the reason
+    // `nvme::init` refuses a BAR 0 that is not.
+    let abar = match pci_dev.memory_bar(5) {
+        Ok(memory) => memory.address(),
+        Err(why) => {
+            log!("AHCI: NOT BOUND at PCI {:02x}:{:02x}.{} — its registers are in BAR 5 and {}",
+                pci_dev.bus, pci_dev.dev, pci_dev.func, why);
+            return;
+        }
+    };
+    pci_dev.enable_bus_master();
+    let hba_regs = crate::mm::paging::map_mmio(abar, hba::ABAR_BYTES, CachePolicy::DeferToMtrr);
+
+    // §10.6.3: ask firmware for the controller before touching a port it may
+    // still be using. One that does not let go inside the bound is taken
+    // anyway — the alternative is no disk — and the log says so.
+    if hba_regs.read_u32(hba::REG_CAP2) & hba::CAP2_BOH != 0 {
+        let bohc = hba_regs.read_u32(hba::REG_BOHC);
+        hba_regs.write_u32(hba::REG_BOHC, bohc | hba::BOHC_OOS);
+        let released = crate::clock::settles(HANDOFF.nanos(), || {
+            hba_regs.read_u32(hba::REG_BOHC) & (hba::BOHC_BOS | hba::BOHC_BB) == 0
+        });
+        if !released {
+            log!("AHCI: firmware did not hand the controller over in {HANDOFF}; taking it anyway");
+        }
+    }
+    // AHCI mode, interrupts off: this driver polls.
+    let ghc = hba_regs.read_u32(hba::REG_GHC);
+    hba_regs.write_u32(hba::REG_GHC, (ghc | hba::GHC_AE) & !hba::GHC_IE);
+
+    let cap = Capabilities::from_raw(hba_regs.read_u32(hba::REG_CAP));
+    let pi = hba_regs.read_u32(hba::REG_PI);
+    let vs = hba_regs.read_u32(hba::REG_VS);
+    log!("AHCI: version {}.{} {cap:?}, ports implemented {pi:#010x}", vs >> 16, (vs >> 8) & 0xFF);
+    if !cap.addresses_64() && pool.phys() + pool.size() as u64 > 1 << 32 {
+        log!("AHCI: NOT BOUND — the controller takes 32-bit addresses and this driver's DMA is above 4 GiB");
+        return;
+    }
+    CONTROLLERS.lock().push(hba_regs);
+
+    for port in hba::implemented(pi) {
+        if *drives == MAX_DRIVES {
+            log!("AHCI: this driver serves {MAX_DRIVES} drives; port {port} and the rest are not bound");
+            return;
+        }
+        let regs = hba_regs.subregion(hba::port_base(port), 0x80);
+        let atapi = match Attached::decide(regs.read_u32(px::PX_SSTS), regs.read_u32(px::PX_SIG)) {
+            Attached::Ata => false,
+            Attached::Atapi => true,
+            Attached::Nothing => continue,
+            other => {
+                log!("AHCI: port {port} has {other} on it, which this driver does not serve");
+                continue;
+            }
+        };
+        // Spent whether or not the drive binds, so an id names one port for
+        // the whole boot.
+        let window = pool.subview(*drives * WINDOW, WINDOW);
+        let device = AHCI_DEVICE_ID_BASE + *drives as DeviceId;
+        *drives += 1;
+        if atapi {
+            if let Some(disc) = bind_disc(port, regs, discs.len(), device, window) {
+                discs.push(Box::leak(Box::new(Lock::new(disc))));
+            }
+        } else if let Some(disk) = bind_port(cap, port, regs, disks.len(), device, window) {
+            disks.push(Box::leak(Box::new(Lock::new(disk))));
+        }
+    }
+}
+
+/// A port stopped, pointed at `dma` and started again, with a drive on it
+/// that has not been asked anything yet; one slot until the drive says it
+/// queues.
+fn claim(port: u8, regs: Mmio, index: usize, id: DeviceId, atapi: bool, dma: Dma<'static>) -> Option<AhciDisk> {
+    let disk = AhciDisk {
+        index,
+        id,
+        atapi,
+        port,
+        regs,
+        dma,
+        slots: Slots::new(1, 1),
+        ncq: false,
+        blocks: 0,
+        disc_sectors: 0,
+        lba_bytes: 512,
+        trim: false,
+        trim_blocks: 1,
+        write_cache: false,
+        failed: false,
+    };
+    // Firmware may have left the port running on a command list of its own.
+    if !disk.stop() {
+        log!("AHCI: port {port} NOT BOUND — it did not stop in {PORT_STOP}");
+        return None;
+    }
+    if !disk.attach() {
+        log!("AHCI: port {port} NOT BOUND — the drive was still busy after {DRIVE_READY}");
+        return None;
+    }
+    Some(disk)
+}
+
+fn bind_port(
+    cap: Capabilities,
+    port: u8,
+    regs: Mmio,
+    index: usize,
+    device: DeviceId,
+    dma: Dma<'static>,
+) -> Option<AhciDisk> {
+    let mut disk = claim(port, regs, index, device, false, dma)?;
+    if let Err(why) = disk.command(Fis::identify(), false, Some((OFF_SCRATCH, 512))) {
+        log!("AHCI: port {port} NOT BOUND — IDENTIFY DEVICE failed: {why}");
+        return None;
+    }
+    let mut page = [0u8; 512];
+    dma.copy_to(OFF_SCRATCH, &mut page);
+    let id = match Identify::parse(&page) {
+        Ok(id) => id,
+        Err(why) => {
+            log!("AHCI: port {port} NOT BOUND — the drive reports {why}");
+            return None;
+        }
+    };
+
+    disk.ncq = cap.ncq() && id.queue_depth > 1;
+    disk.slots = if disk.ncq {
+        Slots::new(cap.command_slots().min(MAX_SLOTS), id.queue_depth)
+    } else {
+        Slots::new(1, 1)
+    };
+    disk.lba_bytes = id.sector_bytes;
+    disk.blocks = id.sectors / disk.sectors_per_block();
+    disk.trim = id.trim;
+    disk.trim_blocks = (id.trim_blocks as usize).min(SCRATCH_BYTES / 512);
+    disk.write_cache = id.write_cache;
+
+    log!(
+        "AHCI: disk {index} id={device} on port {port}: {} (serial {}, firmware {}) blocks={} ({}MB), {}{}{}",
+        id.model(),
+        id.serial(),
+        id.firmware(),
+        disk.blocks,
+        disk.blocks * 4096 / (1024 * 1024),
+        if disk.ncq { "NCQ" } else { "no NCQ" },
+        if disk.write_cache { ", write cache" } else { "" },
+        if disk.trim { ", TRIM" } else { "" },
+    );
+    if disk.ncq {
+        log!("AHCI: disk {index} queues {} commands", disk.slots.depth());
+    }
+    Some(disk)
+}
+
+/// Bind an optical drive with a disc in it: IDENTIFY PACKET DEVICE for what
+/// the drive is, TEST UNIT READY for whether there is a disc, and READ
+/// CAPACITY for how big it is. An empty drive is not bound — this driver
+/// reads the disc that was there at boot and watches for no other.
+fn bind_disc(port: u8, regs: Mmio, index: usize, device: DeviceId, dma: Dma<'static>) -> Option<AhciDisk> {
+    let mut disc = claim(port, regs, index, device, true, dma)?;
+    // An ATAPI drive refuses IDENTIFY DEVICE, with its signature in the task
+    // file, and answers this one instead.
+    if let Err(why) = disc.command(Fis::identify_packet(), false, Some((OFF_SCRATCH, 512))) {
+        log!("AHCI: port {port} NOT BOUND — IDENTIFY PACKET DEVICE failed: {why}");
+        return None;
+    }
+    let mut page = [0u8; 512];
+    dma.copy_to(OFF_SCRATCH, &mut page);
+    let id = match PacketIdentify::parse(&page) {
+        Ok(id) => id,
+        Err(why) => {
+            log!("AHCI: port {port} NOT BOUND — the optical drive reports {why}");
+            return None;
+        }
+    };
+    if let Err(why) = disc.ready() {
+        log!("AHCI: optical drive {} on port {port} not bound: no disc it will read ({why})", id.model());
+        return None;
+    }
+    let capacity = match disc.capacity() {
+        Ok(Ok(capacity)) => capacity,
+        Ok(Err(why)) => {
+            log!("AHCI: port {port} NOT BOUND — the disc reports {why}");
+            return None;
+        }
+        Err(why) => {
+            log!("AHCI: port {port} NOT BOUND — READ CAPACITY failed: {why}");
+            return None;
+        }
+    };
+    disc.lba_bytes = capacity.sector_bytes;
+    disc.disc_sectors = capacity.sectors;
+    disc.blocks = capacity.sectors.div_ceil(disc.sectors_per_block());
+
+    log!(
+        "AHCI: disc {index} id={device} on port {port}: {} (serial {}, firmware {}) blocks={} ({}MB) \
+         of {}-byte sectors, read-only",
+        id.model(),
+        id.serial(),
+        id.firmware(),
+        disc.blocks,
+        disc.blocks * 4096 / (1024 * 1024),
+        disc.lba_bytes,
+    );
+    Some(disc)
+}
+
+/// Every drive's write cache flushed and every port stopped, ahead of S3.
+///
+/// The flush for `virtio_blk::suspend`'s reason. The stop is what keeps a
+/// port from fetching a command list or posting a FIS after the bus goes
+/// down; one that will not stop is logged and left, and its drive is refused
+/// by the resume rather than trusted with a list it may still be walking. A
+/// disc has no cache to flush and is only stopped.
+pub fn suspend() {
+    let (disks, discs) = (DISKS.lock(), DISCS.lock());
+    for disk in disks.iter().chain(discs.iter()) {
+        let mut disk = disk.lock();
+        if disk.failed {
+            continue;
+        }
+        if disk.write_cache {
+            if let Err(why) = disk.command(Fis::flush(), false, None) {
+                log!("AHCI: cache flush before the sleep failed on disk {}: {why}", disk.index);
+            }
+        }
+        if !disk.stop() {
+            disk.failed = true;
+            log!("AHCI: {} {} is offline: port {} did not stop for the sleep", disk.noun(), disk.index, disk.port);
+        }
+    }
+}
+
+/// Every controller back in AHCI mode and every port on its command list
+/// again, so the `BlockDevice` handles the page cache holds go on naming the
+/// same disks.
+///
+/// What the drive reported at bind — its size, NCQ depth, cache and TRIM —
+/// is kept: it is the same drive, and IDENTIFY again would be a question
+/// whose answer nothing here could act on. The slots are abandoned because a
+/// power loss completes nothing. A port whose drive is still busy after
+/// [`DRIVE_READY`] ends its disk, as a failed command's recovery does.
+///
+/// A disc is asked once more whether it is there, which also takes the UNIT
+/// ATTENTION the power loss left off the first read, and whether it is the
+/// size it was. A disc swapped for one of the same size during the sleep is
+/// not told apart; one of another size, or none, ends the drive, because
+/// the mount above it is still reading the old one's directories.
+pub fn resume() {
+    for hba_regs in CONTROLLERS.lock().iter() {
+        let ghc = hba_regs.read_u32(hba::REG_GHC);
+        hba_regs.write_u32(hba::REG_GHC, (ghc | hba::GHC_AE) & !hba::GHC_IE);
+    }
+    let (disks, discs) = (DISKS.lock(), DISCS.lock());
+    for disk in disks.iter().chain(discs.iter()) {
+        let mut disk = disk.lock();
+        if disk.failed {
+            continue;
+        }
+        disk.slots.abandon();
+        if !(disk.stop() && disk.attach()) {
+            disk.failed = true;
+            log!(
+                "AHCI: {} {} is offline: port {} did not come back from the sleep",
+                disk.noun(),
+                disk.index,
+                disk.port
+            );
+            continue;
+        }
+        if disk.atapi && !same_disc(&mut disk) {
+            disk.failed = true;
+            log!("AHCI: disc {} is offline: the drive on port {} no longer holds the disc it was mounted with",
+                disk.index, disk.port);
+        }
+    }
+}
+
+/// Whether a resumed optical drive still answers with a disc of the size it
+/// was bound with.
+fn same_disc(disc: &mut AhciDisk) -> bool {
+    if disc.ready().is_err() {
+        return false;
+    }
+    match disc.capacity() {
+        Ok(Ok(capacity)) => capacity.sector_bytes == disc.lba_bytes && capacity.sectors == disc.disc_sectors,
+        _ => false,
+    }
+}
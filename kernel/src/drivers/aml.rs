@@ -0,0 +1,1209 @@
+//! The AML interpreter's host, and `acpid`, the thread its methods run on.
+//!
+//! `toyos_aml` runs firmware's methods and asks this file for every byte of
+//! hardware they touch. What it may touch is decided here, per access:
+//!
+//! - **System memory, except RAM.** An address in a usable entry of the
+//!   firmware memory map is refused (`mm::pmm::is_ram`), so a region that
+//!   lands on a kernel stack or a page-table page is an error in the method
+//!   and not a write. Everything else — the NVS firmware keeps its own state
+//!   in, a chipset's register block — is mapped a 2 MiB page at a time, once,
+//!   and the window is kept.
+//! - **I/O ports, except the legacy devices this kernel programs itself**:
+//!   the 8259s it masked, the PIT, the RTC `rtc.rs` reads, the ELCR and the
+//!   `0xCF8` configuration mechanism. The list is ACPICA's, less the DMA
+//!   controllers nothing here uses.
+//! - **PCI configuration space on segment 0**, through the ECAM window
+//!   `kernel_main` already mapped for enumeration.
+//! - **The embedded controller's space**, by running the EC protocol on the
+//!   two ports the EC device's `_CRS` names. No register *is* that space, so
+//!   an access to it is a transaction with a timeout and not a load.
+//!
+//! **Two lives, one interpreter.** [`init`] loads the DSDT and every SSDT at
+//! boot, tells firmware the spaces are connected, runs every device's `_INI`
+//! and logs what it found, with a host whose `Sleep` spins: there is no task
+//! yet to park. Then the interpreter is stashed, and `acpid` takes it out at
+//! its entry and owns it from then on. It is never evaluated under a lock: a
+//! `Lock` is a spinlock that holds preemption off, and a method is a program
+//! of firmware's whose `Sleep` is supposed to give the CPU back.
+//!
+//! **What `acpid` runs is what the SCI hands it.** `drivers::sci` masks a
+//! GPE in its ISR and, for one this file [`handles`], passes its number here
+//! from the scheduler pass; `acpid` runs `\_GPE._Lxx` or `_Exx` — or, for the
+//! EC's GPE, queries the EC and runs its `_Qxx` — and hands the number back
+//! through [`super::sci::gpe_done`] to be acknowledged and re-enabled on the
+//! CPU that owns the GPE registers. The `Notify`s a method executed are then
+//! turned into what they mean: a lid, a power button or a sleep button
+//! wired as a control-method device becomes a [`crate::power`] event.
+//!
+//! **Sleep states are AML's too.** [`init`] reads `\_S3_` once, while the
+//! interpreter is still its own, and keeps the two `SLP_TYP` values for
+//! `crate::suspend`. `_PTS` and `_WAK` are methods like any other, so they run
+//! on `acpid` as well: [`sleep_method`] hands one over and parks until it has
+//! finished, which is what keeps the interpreter out of every lock even on the
+//! one path where the caller needs the answer before it can go on.
+//!
+//! **And between them, a third life.** A device's `_PS3` has to run after its
+//! driver has stopped it and `_PS0` before its driver starts it again, and
+//! `crate::suspend` stops the drivers with every other CPU parked — `acpid`
+//! among them. So once `_PTS` has run, `acpid` puts the interpreter back in
+//! [`STATE`] and the suspending CPU takes it for [`set_device_power`], with a
+//! host whose `Sleep` spins as it did at boot; `_WAK` is `acpid`'s cue to take
+//! it back. `acpid` is the interpreter's only other user, and in between it
+//! has nothing to run the interpreter with: a GPE raised meanwhile waits.
+//!
+//! **`_PRT` is obeyed where a pin is taken.** [`init`] asks every PCI root for
+//! its routing table and resolves each entry to the GSI it lands on — a link
+//! device's through the interrupt its `_CRS` names now, with the trigger and
+//! polarity it gives — and `PciDevice::enable_intx` asks [`pci_route`] before
+//! it falls back to the MADT. Nearly every PCI device here is MSI or MSI-X and
+//! never asserts a pin, so the table is usually consulted by nobody. A link
+//! firmware left disabled is not enabled through `_SRS`: choosing an interrupt
+//! for it is a policy with a `_PRS` to read, and q35 hands every link over
+//! enabled. A bridge's own `_PRT` is not walked either, so a
+//! pin behind a bridge takes the MADT's answer.
+//!
+//! **Batteries and thermal zones are read, and kept.** [`init`] reads each
+//! once and logs it; `acpid` reads them all again every [`READING_PERIOD`] and
+//! whenever firmware notifies one, and [`power_readings`] is the copy
+//! `SYS_POWER_READINGS` answers from — so a reader never waits on a method,
+//! and a method that polls an EC is never run on a reader's behalf.
+//!
+//! **The fourth kernel thread**, beside `klogd`, `usbd` and `iod`, for the
+//! reason each of those has one: a method that polls an EC for a second must
+//! not hold up the log, USB or write-back. Spawned on every machine, as
+//! `usbd` is, including one whose tables would not load; that one parks
+//! forever. Its panic is recoverable: a dead `acpid` leaves every GPE it was
+//! running masked, and the lid stops reporting — visible, and nothing else
+//! depends on it.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use toyos_abi::power::{
+    PowerReading, BATTERY, BATTERY_CHARGING, BATTERY_CRITICAL, BATTERY_DISCHARGING,
+    BATTERY_MILLIWATTS, BATTERY_PRESENT, THERMAL_ZONE, UNKNOWN,
+};
+use toyos_acpi::gpe::{Method, Trigger};
+use toyos_aml::{
+    Error, Host, Interpreter, NameSeg, NodeId, Object, Resource, RouteSource, Space, Value,
+};
+use toyos_sched::task::WaitClass;
+
+use super::acpi;
+use super::ioapic::{self, Gsi, Line, Polarity};
+use crate::arch::cpu::{inb, inl, inw, outb, outl, outw};
+use crate::completion::{self, Armed, Outcome, Subject, Token, Watch};
+use crate::log;
+use crate::mm::paging::{self, CachePolicy};
+use crate::mm::{pmm, Mmio, PAGE_2M};
+use crate::sched::kthread::{self, OnPanic};
+use crate::scheduler::{self, Parkable};
+use crate::sync::Lock;
+use crate::time::{Budget, Deadline, Duration};
+
+/// The name `sched::dump`, `ps` and a crash report use.
+const NAME: &str = "acpid";
+
+/// The highest physical address a region may name: the architectural limit,
+/// and the one `acpi.rs` holds table pointers to.
+const MAX_PHYS: u64 = 1 << 52;
+
+/// How much one run of a method may `Sleep` in all, in milliseconds. The step
+/// budget bounds what a method computes and not how long it waits, and
+/// `While (One) { Sleep (1000) }` is a million steps of a second each. Past
+/// this a `Sleep` returns at once, and the loop spends its budget instead.
+const SLEEP_BUDGET_MS: u64 = 10_000;
+
+/// How long the EC gets to take a byte or offer one. Linux waits 500 ms; an
+/// EC that needs more than a tenth of that is one a method should fail on
+/// rather than keep `acpid` spinning for.
+const EC_TIMEOUT_NS: u64 = 50_000_000;
+
+/// How many queries one EC GPE drains. An EC raises `SCI_EVT` until its queue
+/// is empty, and one that never empties it is broken, not busy.
+const MAX_EC_QUERIES: usize = 32;
+
+/// The EC's status register, and the commands this file sends it (ACPI 6.5
+/// §12.2 and §12.3).
+const EC_OBF: u8 = 1 << 0;
+const EC_IBF: u8 = 1 << 1;
+const EC_SCI_EVT: u8 = 1 << 5;
+const EC_READ: u8 = 0x80;
+const EC_WRITE: u8 = 0x81;
+const EC_QUERY: u8 = 0x84;
+
+/// `Notify` values that mean "look again": status change on a button, a lid
+/// or a battery.
+const NOTIFY_STATUS: u8 = 0x80;
+
+/// How often `acpid` reads every battery and thermal zone again, beside the
+/// reads a notification asks for. A gauge moves a percent in minutes, and
+/// firmware that notifies nothing still gets readings this fresh.
+const READING_PERIOD: Duration = Duration::from_secs(30);
+
+/// Ports AML may not touch, first to last inclusive. Each belongs to
+/// something this kernel programs and would not notice firmware changing.
+const KERNEL_PORTS: &[(u16, u16)] = &[
+    // The 8259s, masked at boot and kept masked.
+    (0x20, 0x21),
+    (0xA0, 0xA1),
+    // The PIT.
+    (0x40, 0x43),
+    (0x48, 0x4B),
+    // The RTC and CMOS, which `rtc.rs` reads through an index register a
+    // method could move under it.
+    (0x70, 0x71),
+    // The ELCR, whose trigger modes the I/O APIC routing assumes.
+    (0x4D0, 0x4D1),
+    // Configuration mechanism #1: its index register is shared state, and
+    // configuration space is reached through ECAM here.
+    (0xCF8, 0xCFF),
+];
+
+/// A GPE `acpid` handles, and what it runs for one.
+#[derive(Clone, Copy)]
+pub enum Gpe {
+    /// `\_GPE._Lxx` or `_Exx`.
+    Method(Method),
+    /// The embedded controller's: a query, then its `_Qxx`.
+    Ec(u8),
+}
+
+impl Gpe {
+    pub fn number(self) -> u8 {
+        match self {
+            Gpe::Method(method) => method.number,
+            Gpe::Ec(number) => number,
+        }
+    }
+}
+
+/// One bit per GPE number [`init`] found something to run for, and one per
+/// number among those that is level-triggered. Written once before the SCI is
+/// unmasked and read, without a lock, from `sci::service`.
+static HANDLED: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+static LEVEL: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+
+/// GPEs the SCI has handed over and `acpid` has not yet run.
+static PENDING: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+
+/// What `acpid` arms on. A static rather than the thread's own watch because
+/// [`raise`] runs on whichever CPU took the SCI.
+static EVENTS: Watch = Watch::new();
+
+/// What [`init`] built, until `acpid` takes it.
+static STATE: Lock<Option<State>> = Lock::new(None);
+
+/// `\_S3_`'s `SLP_TYPa` and `SLP_TYPb`, or `None` on a machine whose
+/// namespace has no S3. Written once by [`init`].
+static S3: Lock<Option<(u8, u8)>> = Lock::new(None);
+
+/// One `_PRT` entry resolved to where it lands: pin `pin` (0 for INTA) of
+/// every function of device `device` on bus `bus`.
+struct PinRoute {
+    bus: u8,
+    device: u8,
+    pin: u8,
+    line: Line,
+}
+
+/// Every `_PRT` entry [`init`] resolved. Written once, before the first driver
+/// binds.
+static PCI_ROUTES: Lock<Vec<PinRoute>> = Lock::new(Vec::new());
+
+/// Every battery and then every thermal zone, as they were last read.
+static READINGS: Lock<Vec<PowerReading>> = Lock::new(Vec::new());
+
+/// The sleep-state method [`sleep_method`] has handed `acpid`, cleared by
+/// `acpid` once it has run, and what the caller parks on meanwhile.
+static SLEEP_CALL: Lock<Option<SleepCall>> = Lock::new(None);
+static SLEEP_DONE: Watch = Watch::new();
+
+/// How long a caller of [`sleep_method`] waits for `acpid`. Longer than
+/// [`SLEEP_BUDGET_MS`], which one `Sleep` in the method may already spend.
+const SLEEP_METHOD: Budget = Budget::of(
+    Duration::from_secs(15),
+    "the transition goes on without the method's answer, and says so",
+);
+
+/// One of the two methods that bracket a sleep state, with the state's number.
+#[derive(Clone, Copy, Debug)]
+pub enum SleepCall {
+    /// `\_PTS`: firmware's last word before the OS enters the state.
+    Prepare(u8),
+    /// `\_WAK`: firmware's first word after the OS is back.
+    Wake(u8),
+}
+
+fn bit(map: &[AtomicU64; 4], number: u8) -> (&AtomicU64, u64) {
+    (&map[usize::from(number / 64)], 1 << (number % 64))
+}
+
+/// How the GPE `number` is signalled, or `None` if `acpid` has nothing to run
+/// for it.
+pub fn handles(number: u16) -> Option<Trigger> {
+    let number = u8::try_from(number).ok()?;
+    let (word, mask) = bit(&HANDLED, number);
+    if word.load(Ordering::Relaxed) & mask == 0 {
+        return None;
+    }
+    let (level, _) = bit(&LEVEL, number);
+    Some(if level.load(Ordering::Relaxed) & mask != 0 { Trigger::Level } else { Trigger::Edge })
+}
+
+/// Every GPE [`init`] found something to run for, for `sci::init` to enable.
+pub fn gpes() -> Vec<Gpe> {
+    STATE.lock().as_ref().map(|state| state.gpes.clone()).unwrap_or_default()
+}
+
+/// Hand GPE `number` to `acpid`. The SCI has masked it, and has acknowledged
+/// it already if it is an edge. Thread context; never blocks.
+pub fn raise(number: u16) {
+    let Ok(number) = u8::try_from(number) else { return };
+    let (word, mask) = bit(&PENDING, number);
+    word.fetch_or(mask, Ordering::Relaxed);
+    completion::post(Subject::of(&EVENTS), Outcome::Ready);
+}
+
+/// `\_S3_`'s `SLP_TYPa` and `SLP_TYPb`, or `None` on a machine that has no
+/// S3 or no interpreter.
+pub fn s3() -> Option<(u8, u8)> {
+    *S3.lock()
+}
+
+/// Where pin `pin` (0 for INTA) of PCI device `device` on bus `bus` lands, as
+/// `_PRT` says — or `None` where it says nothing, which is the caller's cue to
+/// ask the MADT.
+pub fn pci_route(bus: u8, device: u8, pin: u8) -> Option<Line> {
+    PCI_ROUTES
+        .lock()
+        .iter()
+        .find(|r| r.bus == bus && r.device == device && r.pin == pin)
+        .map(|r| r.line)
+}
+
+/// Every battery, then every thermal zone, in namespace order, as `acpid` last
+/// read them. Empty on a machine with neither, or with no interpreter.
+pub fn power_readings() -> Vec<PowerReading> {
+    READINGS.lock().clone()
+}
+
+/// Run `_PS3` (`d3`) or `_PS0` on every device whose power firmware manages,
+/// here and now.
+///
+/// Only for `crate::suspend`, between `\_PTS` and `\_WAK`, where the
+/// interpreter is lent to the suspending CPU (see the module doc). A machine
+/// whose `acpid` did not finish `_PTS` in time has not lent it, and its devices
+/// stay in D0 — logged, and no reason to stay awake.
+pub fn set_device_power(d3: bool) {
+    let Some(mut state) = STATE.lock().take() else {
+        if S3.lock().is_some() {
+            log!("AML: acpid has not lent the interpreter — no _PS0/_PS3 this sleep");
+        }
+        return;
+    };
+    state.set_device_power(d3);
+    *STATE.lock() = Some(state);
+}
+
+/// Run `call` on `acpid` and park until it has. False when `acpid` did not
+/// answer inside [`SLEEP_METHOD`], or the caller's wait was cancelled — the
+/// method's own failure is logged by `acpid` and is not the caller's to act on:
+/// a `_PTS` that errors is not a reason to keep a machine awake.
+pub fn sleep_method(p: &Parkable, call: SleepCall) -> bool {
+    *SLEEP_CALL.lock() = Some(call);
+    completion::post(Subject::of(&EVENTS), Outcome::Ready);
+    let deadline = Deadline::at(crate::clock::now() + SLEEP_METHOD.duration());
+    let ran = completion::wait_until(
+        p,
+        Subject::of(&SLEEP_DONE),
+        Token::new(0),
+        WaitClass::Io,
+        deadline,
+        || SLEEP_CALL.lock().is_none(),
+    );
+    if ran.is_err() || SLEEP_CALL.lock().take().is_some() {
+        log!("AML: {call:?} did not finish within {SLEEP_METHOD}");
+        return false;
+    }
+    true
+}
+
+/// The embedded controller, as its device in the namespace describes it.
+#[derive(Clone, Copy)]
+struct Ec {
+    node: NodeId,
+    data: u16,
+    command: u16,
+    gpe: Option<u8>,
+}
+
+impl Ec {
+    /// Poll the status register until `ready`, or refuse the access.
+    fn wait(&self, ready: impl Fn(u8) -> bool) -> Result<(), Error> {
+        if crate::clock::settles(EC_TIMEOUT_NS, || ready(inb(self.command))) {
+            Ok(())
+        } else {
+            Err(Error::Refused)
+        }
+    }
+
+    fn send(&self, port: u16, byte: u8) -> Result<(), Error> {
+        self.wait(|status| status & EC_IBF == 0)?;
+        // SAFETY: `outb` asks its caller to own the port and the byte. The
+        // port is the EC's data or command register, as the EC device's own
+        // `_CRS` names them, and this file is the only one that writes either;
+        // the byte is a command ACPI defines for that register or the address
+        // and data one of them takes. Once `_REG` has run for space 3 the OS
+        // owns the EC's host interface, which is the state this is called in.
+        unsafe { outb(port, byte) };
+        Ok(())
+    }
+
+    fn receive(&self) -> Result<u8, Error> {
+        self.wait(|status| status & EC_OBF != 0)?;
+        Ok(inb(self.data))
+    }
+
+    /// Throw away a byte a timed-out transaction left in the output buffer,
+    /// so the next one does not read it as its answer.
+    fn flush(&self) {
+        for _ in 0..8 {
+            if inb(self.command) & EC_OBF == 0 {
+                return;
+            }
+            inb(self.data);
+        }
+    }
+
+    fn read(&self, address: u8) -> Result<u8, Error> {
+        self.flush();
+        self.send(self.command, EC_READ)?;
+        self.send(self.data, address)?;
+        self.receive()
+    }
+
+    fn write(&self, address: u8, value: u8) -> Result<(), Error> {
+        self.flush();
+        self.send(self.command, EC_WRITE)?;
+        self.send(self.data, address)?;
+        self.send(self.data, value)?;
+        self.wait(|status| status & EC_IBF == 0)
+    }
+
+    /// The next event the EC has queued, or `None` when it has none.
+    fn query(&self) -> Result<Option<u8>, Error> {
+        if inb(self.command) & EC_SCI_EVT == 0 {
+            return Ok(None);
+        }
+        self.flush();
+        self.send(self.command, EC_QUERY)?;
+        let event = self.receive()?;
+        Ok((event != 0).then_some(event))
+    }
+}
+
+/// Everything an access reaches the machine through.
+struct Platform {
+    ecam: Mmio,
+    ec: Option<Ec>,
+    /// One window per 2 MiB page a region has touched, by page. `map_mmio`
+    /// shoots down every CPU's TLB, which a field read once a second must not
+    /// do each time.
+    windows: BTreeMap<u64, Mmio>,
+}
+
+impl Platform {
+    /// The window over `bytes` of system memory at `phys`, and the offset of
+    /// `phys` in it. Only for an access inside one 2 MiB page.
+    fn memory(&mut self, phys: u64, bytes: u64) -> Result<(Mmio, u64), Error> {
+        let end = phys.checked_add(bytes).filter(|&end| end <= MAX_PHYS).ok_or(Error::Refused)?;
+        if pmm::is_ram(phys, bytes) {
+            return Err(Error::Refused);
+        }
+        let page = phys & !(PAGE_2M - 1);
+        if (end - 1) & !(PAGE_2M - 1) != page {
+            return Err(Error::Unsupported);
+        }
+        // The PMM owns only pages that are RAM from end to end, and these
+        // bytes are not, so no guard page ever split this one: `map_2m` takes
+        // it whether the direct map already covers it or not.
+        let window = *self
+            .windows
+            .entry(page)
+            .or_insert_with(|| paging::map_mmio(page, PAGE_2M, CachePolicy::DeferToMtrr));
+        Ok((window, phys - page))
+    }
+
+    /// The ECAM window and the offset of register `reg` of function `pci`.
+    fn config(&self, pci: toyos_aml::PciAddress, reg: u64, bytes: u64) -> Result<u64, Error> {
+        if pci.segment != 0 || pci.device >= 32 || pci.function >= 8 {
+            return Err(Error::Refused);
+        }
+        if reg.checked_add(bytes).is_none_or(|end| end > 4096) {
+            return Err(Error::OutOfRange);
+        }
+        let function =
+            u64::from(pci.bus) << 20 | u64::from(pci.device) << 15 | u64::from(pci.function) << 12;
+        Ok(function | reg)
+    }
+
+    fn ec(&self, address: u64, bytes: u64) -> Result<Ec, Error> {
+        let ec = self.ec.ok_or(Error::Refused)?;
+        if address.checked_add(bytes).is_none_or(|end| end > 256) {
+            return Err(Error::OutOfRange);
+        }
+        Ok(ec)
+    }
+}
+
+/// `bytes` at `offset` in `window`: one access where it is naturally aligned,
+/// as a register wants, and a byte at a time where it is not, which only
+/// memory tolerates and only a field split oddly asks for.
+fn window_read(window: Mmio, offset: u64, bytes: u64) -> u64 {
+    if offset % bytes != 0 {
+        return (0..bytes).fold(0, |v, i| v | u64::from(window.read_u8(offset + i)) << (8 * i));
+    }
+    match bytes {
+        1 => u64::from(window.read_u8(offset)),
+        2 => u64::from(window.read_u16(offset)),
+        4 => u64::from(window.read_u32(offset)),
+        _ => window.read_u64(offset),
+    }
+}
+
+fn window_write(window: Mmio, offset: u64, bytes: u64, value: u64) {
+    if offset % bytes != 0 {
+        for i in 0..bytes {
+            window.write_u8(offset + i, (value >> (8 * i)) as u8);
+        }
+        return;
+    }
+    match bytes {
+        1 => window.write_u8(offset, value as u8),
+        2 => window.write_u16(offset, value as u16),
+        4 => window.write_u32(offset, value as u32),
+        _ => window.write_u64(offset, value),
+    }
+}
+
+/// The port an I/O access of `bytes` at `address` is, if AML may make it.
+fn port(address: u64, bytes: u64) -> Result<u16, Error> {
+    let first = u16::try_from(address).map_err(|_| Error::OutOfRange)?;
+    let last = u16::try_from(address + bytes - 1).map_err(|_| Error::OutOfRange)?;
+    if KERNEL_PORTS.iter().any(|&(lo, hi)| first <= hi && last >= lo) {
+        return Err(Error::Refused);
+    }
+    Ok(first)
+}
+
+/// The bytes in an access `width` bits wide, or `Unsupported`.
+fn bytes(width: u8) -> Result<u64, Error> {
+    match width {
+        8 | 16 | 32 | 64 => Ok(u64::from(width / 8)),
+        _ => Err(Error::Unsupported),
+    }
+}
+
+struct KernelHost<'a> {
+    platform: &'a mut Platform,
+    /// `acpid`'s park, which a `Sleep` gives the CPU back through; `None` at
+    /// boot, where a `Sleep` spins.
+    park: Option<(&'a Parkable, &'a Armed<'static>)>,
+    /// What is left of [`SLEEP_BUDGET_MS`].
+    sleep_left: u64,
+}
+
+impl<'a> KernelHost<'a> {
+    fn new(platform: &'a mut Platform, park: Option<(&'a Parkable, &'a Armed<'static>)>) -> Self {
+        KernelHost { platform, park, sleep_left: SLEEP_BUDGET_MS }
+    }
+}
+
+impl Host for KernelHost<'_> {
+    fn read(&mut self, space: Space, address: u64, width: u8) -> Result<u64, Error> {
+        let bytes = bytes(width)?;
+        match space {
+            Space::SystemMemory if address % bytes == 0 => {
+                let (window, offset) = self.platform.memory(address, bytes)?;
+                Ok(window_read(window, offset, bytes))
+            }
+            Space::SystemMemory => (0..bytes).try_fold(0, |v, i| {
+                let (window, offset) = self.platform.memory(address + i, 1)?;
+                Ok(v | window_read(window, offset, 1) << (8 * i))
+            }),
+            Space::SystemIo => {
+                let port = port(address, bytes)?;
+                match width {
+                    8 => Ok(u64::from(inb(port))),
+                    16 => Ok(u64::from(inw(port))),
+                    32 => Ok(u64::from(inl(port))),
+                    _ => Err(Error::Unsupported),
+                }
+            }
+            Space::PciConfig(pci) => {
+                let offset = self.platform.config(pci, address, bytes)?;
+                Ok(window_read(self.platform.ecam, offset, bytes))
+            }
+            Space::EmbeddedControl => {
+                let ec = self.platform.ec(address, bytes)?;
+                (0..bytes)
+                    .try_fold(0, |v, i| Ok(v | u64::from(ec.read((address + i) as u8)?) << (8 * i)))
+            }
+            Space::Other(_) => Err(Error::Unsupported),
+        }
+    }
+
+    fn write(&mut self, space: Space, address: u64, width: u8, value: u64) -> Result<(), Error> {
+        let bytes = bytes(width)?;
+        match space {
+            Space::SystemMemory if address % bytes == 0 => {
+                let (window, offset) = self.platform.memory(address, bytes)?;
+                window_write(window, offset, bytes, value);
+                Ok(())
+            }
+            Space::SystemMemory => {
+                // Every byte is checked before any is written, so a refusal
+                // leaves none of them changed.
+                let windows = (0..bytes)
+                    .map(|i| self.platform.memory(address + i, 1))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (i, (window, offset)) in windows.into_iter().enumerate() {
+                    window.write_u8(offset, (value >> (8 * i)) as u8);
+                }
+                Ok(())
+            }
+            Space::SystemIo => {
+                let port = port(address, bytes)?;
+                // SAFETY: `outb`, `outw` and `outl` ask their caller to own the
+                // port and the value, and here neither is this kernel's choice:
+                // both come from an operation region firmware defined, which is
+                // the one way ACPI has of saying "the OS writes this". What the
+                // kernel owns it keeps — `port` refused every register in
+                // `KERNEL_PORTS` — and what is left is firmware's hardware
+                // described by firmware, exactly as Linux lets it be written.
+                unsafe {
+                    match width {
+                        8 => outb(port, value as u8),
+                        16 => outw(port, value as u16),
+                        32 => outl(port, value as u32),
+                        _ => return Err(Error::Unsupported),
+                    }
+                }
+                Ok(())
+            }
+            Space::PciConfig(pci) => {
+                let offset = self.platform.config(pci, address, bytes)?;
+                window_write(self.platform.ecam, offset, bytes, value);
+                Ok(())
+            }
+            Space::EmbeddedControl => {
+                let ec = self.platform.ec(address, bytes)?;
+                (0..bytes).try_for_each(|i| ec.write((address + i) as u8, (value >> (8 * i)) as u8))
+            }
+            Space::Other(_) => Err(Error::Unsupported),
+        }
+    }
+
+    fn sleep(&mut self, ms: u64) {
+        let ms = ms.min(self.sleep_left);
+        self.sleep_left -= ms;
+        let nanos = ms * 1_000_000;
+        let Some((parkable, armed)) = self.park else {
+            let _ = crate::clock::settles(nanos, || false);
+            return;
+        };
+        // On the watch `acpid` already holds: a second arm on one task's inbox
+        // is a bug, and a post that lands during the sleep is found again by
+        // the loop, which re-reads `PENDING` rather than the record.
+        let until = Deadline::at(crate::clock::now() + Duration::from_nanos(nanos));
+        while !until.reached(crate::clock::now()) {
+            let _ = completion::wait(parkable, armed, until);
+        }
+    }
+
+    fn stall(&mut self, us: u64) {
+        let _ = crate::clock::settles(us * 1000, || false);
+    }
+
+    fn timer(&mut self) -> u64 {
+        crate::clock::nanos_since_boot() / 100
+    }
+
+    fn debug(&mut self, value: &Value) {
+        log!("AML: Debug = {value:?}");
+    }
+}
+
+/// The devices whose notifications mean something here.
+#[derive(Default)]
+struct Buttons {
+    lids: Vec<NodeId>,
+    power: Vec<NodeId>,
+    sleep: Vec<NodeId>,
+}
+
+struct State {
+    aml: Interpreter,
+    platform: Platform,
+    gpes: Vec<Gpe>,
+    buttons: Buttons,
+    batteries: Vec<NodeId>,
+    zones: Vec<NodeId>,
+    /// Every device [`set_device_power`] runs `_PS3` and `_PS0` on, parents
+    /// before children.
+    powered: Vec<NodeId>,
+}
+
+impl State {
+    /// Run `_PTS` or `_WAK`, then act on what it notified.
+    fn run_sleep(&mut self, park: (&Parkable, &Armed<'static>), call: SleepCall) {
+        let mut host = KernelHost::new(&mut self.platform, Some(park));
+        let (name, ran) = match call {
+            SleepCall::Prepare(state) => ("_PTS", self.aml.prepare_to_sleep(&mut host, state)),
+            SleepCall::Wake(state) => ("_WAK", self.aml.wake(&mut host, state)),
+        };
+        if let Err(e) = ran {
+            log!("AML: \\{name} failed: {e}");
+        }
+        drop(host);
+        self.notify(park);
+    }
+
+    /// Run what GPE `number` stands for, then act on what it notified.
+    fn run_gpe(&mut self, park: (&Parkable, &Armed<'static>), number: u8) {
+        let Some(gpe) = self.gpes.iter().copied().find(|g| g.number() == number) else {
+            return;
+        };
+        let mut host = KernelHost::new(&mut self.platform, Some(park));
+        match gpe {
+            Gpe::Method(method) => {
+                let name = method.name();
+                let name = core::str::from_utf8(&name).unwrap_or("?");
+                if let Err(e) = self.aml.evaluate(&mut host, &format!("\\_GPE.{name}"), Vec::new())
+                {
+                    log!("AML: \\_GPE.{name} failed: {e}");
+                }
+            }
+            Gpe::Ec(_) => {
+                let Some(ec) = host.platform.ec else { return };
+                for _ in 0..MAX_EC_QUERIES {
+                    let event = match ec.query() {
+                        Ok(Some(event)) => event,
+                        Ok(None) => break,
+                        Err(e) => {
+                            log!("AML: EC query failed: {e}");
+                            break;
+                        }
+                    };
+                    let name = format!("_Q{event:02X}");
+                    match self.aml.evaluate_child(&mut host, ec.node, &name, Vec::new()) {
+                        Ok(Some(_)) => {}
+                        Ok(None) => log!("AML: EC event {event:#04x} has no {name}"),
+                        Err(e) => log!("AML: EC {name} failed: {e}"),
+                    }
+                }
+            }
+        }
+        drop(host);
+        self.notify(park);
+    }
+
+    /// Read every battery and thermal zone again, for [`power_readings`].
+    fn refresh_readings(&mut self, park: (&Parkable, &Armed<'static>)) {
+        let mut host = KernelHost::new(&mut self.platform, Some(park));
+        let readings = read_power(&mut self.aml, &mut host, &self.batteries, &self.zones, false);
+        *READINGS.lock() = readings;
+    }
+
+    fn reads_power(&self) -> bool {
+        !self.batteries.is_empty() || !self.zones.is_empty()
+    }
+
+    /// `_PS3` children first, or `_PS0` parents first: a bus goes down after
+    /// what is on it, and comes up before.
+    fn set_device_power(&mut self, d3: bool) {
+        let method = if d3 { "_PS3" } else { "_PS0" };
+        let mut order = self.powered.clone();
+        if d3 {
+            order.reverse();
+        }
+        let mut host = KernelHost::new(&mut self.platform, None);
+        for &node in &order {
+            if let Err(e) = self.aml.set_power(&mut host, node, d3) {
+                log!("AML: {}.{method} failed: {e}", self.aml.namespace().path(node));
+            }
+        }
+        if !order.is_empty() {
+            log!("AML: {method} ran on {} devices", order.len());
+        }
+    }
+
+    /// Turn the `Notify`s methods executed into events.
+    fn notify(&mut self, park: (&Parkable, &Armed<'static>)) {
+        let mut stale = false;
+        for note in self.aml.take_notifications() {
+            let path = self.aml.namespace().path(note.node);
+            if self.batteries.contains(&note.node) || self.zones.contains(&note.node) {
+                // Status, information or trip points: any of them is a
+                // reading that may have moved.
+                stale = true;
+            } else if note.value != NOTIFY_STATUS {
+                log!("AML: Notify({path}, {:#04x}) has no consumer", note.value);
+            } else if self.buttons.lids.contains(&note.node) {
+                let mut host = KernelHost::new(&mut self.platform, Some(park));
+                match self.aml.evaluate_child(&mut host, note.node, "_LID", Vec::new()) {
+                    Ok(Some(Value::Integer(open))) => {
+                        log!("AML: lid {}", if open != 0 { "open" } else { "closed" });
+                        let state = if open != 0 {
+                            crate::power::LID_OPEN
+                        } else {
+                            crate::power::LID_CLOSED
+                        };
+                        crate::power::publish(crate::power::LID, state);
+                    }
+                    other => log!("AML: {path}._LID answered {other:?}"),
+                }
+            } else if self.buttons.power.contains(&note.node) {
+                log!("AML: power button");
+                crate::power::publish(crate::power::POWER_BUTTON, 0);
+            } else if self.buttons.sleep.contains(&note.node) {
+                log!("AML: sleep button");
+                crate::power::publish(crate::power::SLEEP_BUTTON, 0);
+            } else {
+                log!("AML: Notify({path}, {:#04x}) has no consumer", note.value);
+            }
+        }
+        if stale {
+            self.refresh_readings(park);
+        }
+    }
+}
+
+/// The EC device, if the namespace has one whose `_CRS` names its two ports.
+fn find_ec(aml: &mut Interpreter, host: &mut KernelHost<'_>) -> Option<Ec> {
+    let node = *aml.find_devices(host, "PNP0C09").first()?;
+    let path = aml.namespace().path(node);
+    let ports: Vec<u16> = match aml.resources(host, node) {
+        Ok(Some(resources)) => resources
+            .into_iter()
+            .filter_map(|r| match r {
+                Resource::Io { min, .. } => Some(min),
+                _ => None,
+            })
+            .collect(),
+        other => {
+            log!("AML: EC {path} has no usable _CRS ({other:?}) — its space stays refused");
+            return None;
+        }
+    };
+    let &[data, command, ..] = ports.as_slice() else {
+        log!("AML: EC {path} names {} ports, not two — its space stays refused", ports.len());
+        return None;
+    };
+    let gpe = match aml.evaluate_child(host, node, "_GPE", Vec::new()) {
+        Ok(Some(Value::Integer(n))) => u8::try_from(n).ok(),
+        _ => None,
+    };
+    Some(Ec { node, data, command, gpe })
+}
+
+/// The GPE methods under `\_GPE`, by their names.
+fn gpe_methods(aml: &Interpreter) -> Vec<Method> {
+    let ns = aml.namespace();
+    let Some(scope) = ns.lookup("\\_GPE") else { return Vec::new() };
+    ns.children(scope)
+        .filter(|&node| matches!(ns.object(node), Some(Object::Method(_))))
+        .filter_map(|node| Method::from_name(ns.get(node)?.name.0))
+        .collect()
+}
+
+/// Every PCI root's `_PRT`, resolved to where each pin lands. Two lines per
+/// root: how many pins the table names and through how many link devices, and
+/// how many of them resolved — the account of whether the interpreter can
+/// answer, on the machine in front of it.
+///
+/// Only roots, on the bus their `_BBN` names. An entry whose link has no
+/// interrupt is left out, and its pin falls back to the MADT.
+fn pci_routes(aml: &mut Interpreter, host: &mut KernelHost<'_>) -> Vec<PinRoute> {
+    let mut roots = aml.find_devices(host, "PNP0A08");
+    for node in aml.find_devices(host, "PNP0A03") {
+        if !roots.contains(&node) {
+            roots.push(node);
+        }
+    }
+    let mut resolved = Vec::new();
+    // A link serves a pin of every fourth slot, so each is asked once.
+    let mut links: Vec<(NodeId, Option<Line>)> = Vec::new();
+    for root in roots {
+        let path = aml.namespace().path(root);
+        let routes = match aml.routing(host, root) {
+            Ok(routes) => routes,
+            Err(e) => {
+                log!("AML: {path} _PRT failed: {e}");
+                continue;
+            }
+        };
+        let through_links =
+            routes.iter().filter(|r| matches!(r.source, RouteSource::Link { .. })).count();
+        log!("AML: {path} _PRT routes {} pins, {through_links} through link devices", routes.len());
+        if routes.is_empty() {
+            continue;
+        }
+        let bus = match aml.evaluate_child(host, root, "_BBN", Vec::new()) {
+            Ok(Some(Value::Integer(n))) => n as u8,
+            _ => 0,
+        };
+        let before = resolved.len();
+        for route in &routes {
+            let line = match route.source {
+                // Hard-wired to the GSI: a PCI pin, so level-triggered and
+                // active-low, as the bus drives it.
+                RouteSource::Gsi(gsi) => Some(Line {
+                    gsi: Gsi(gsi),
+                    trigger: ioapic::Trigger::Level,
+                    polarity: Polarity::Low,
+                }),
+                RouteSource::Link { node, .. } => match links.iter().find(|(l, _)| *l == node) {
+                    Some(&(_, line)) => line,
+                    None => {
+                        let line = link_line(aml, host, node);
+                        links.push((node, line));
+                        line
+                    }
+                },
+            };
+            if let (Some(line), Ok(device)) = (line, u8::try_from(route.device)) {
+                resolved.push(PinRoute { bus, device, pin: route.pin, line });
+            }
+        }
+        log!(
+            "AML: {path} _PRT resolves {} of {} pins on bus {bus}",
+            resolved.len() - before,
+            routes.len()
+        );
+    }
+    resolved
+}
+
+/// The interrupt a PCI interrupt link device routes to now and how its `_CRS`
+/// says it is driven, or `None` for a link firmware left disabled.
+fn link_line(aml: &mut Interpreter, host: &mut KernelHost<'_>, link: NodeId) -> Option<Line> {
+    let resources = match aml.resources(host, link) {
+        Ok(resources) => resources?,
+        Err(e) => {
+            log!("AML: link {} _CRS failed: {e}", aml.namespace().path(link));
+            return None;
+        }
+    };
+    resources.into_iter().find_map(|r| match r {
+        Resource::Irq { interrupts, edge, active_low, .. } => Some(Line {
+            gsi: Gsi(interrupts.into_iter().find(|&i| i != 0)?),
+            trigger: if edge { ioapic::Trigger::Edge } else { ioapic::Trigger::Level },
+            polarity: if active_low { Polarity::Low } else { Polarity::High },
+        }),
+        _ => None,
+    })
+}
+
+/// Every thermal zone in the namespace.
+fn thermal_zones(aml: &Interpreter) -> Vec<NodeId> {
+    let ns = aml.namespace();
+    ns.descendants(ns.root())
+        .into_iter()
+        .filter(|&n| matches!(ns.object(n), Some(Object::ThermalZone)))
+        .collect()
+}
+
+/// Every present device whose power firmware manages, parents before children.
+///
+/// A bus address and a `_PS3`: an addressed device is one a driver binds and
+/// stops before the sleep. A `_HID` device with power methods — an EC, a
+/// button — is one firmware's own `_PTS` may still talk to, and is left alone.
+fn powered_devices(aml: &mut Interpreter, host: &mut KernelHost<'_>) -> Vec<NodeId> {
+    let ns = aml.namespace();
+    let candidates: Vec<NodeId> = ns
+        .descendants(ns.root())
+        .into_iter()
+        .filter(|&n| {
+            matches!(ns.object(n), Some(Object::Device))
+                && ns.child(n, NameSeg(*b"_ADR")).is_some()
+                && ns.child(n, NameSeg(*b"_PS3")).is_some()
+        })
+        .collect();
+    candidates.into_iter().filter(|&n| aml.status(host, n).is_ok_and(|s| s.present())).collect()
+}
+
+/// One battery, or why it could not be read. An empty slot is a reading.
+fn battery_reading(
+    aml: &mut Interpreter,
+    host: &mut KernelHost<'_>,
+    battery: NodeId,
+) -> Result<PowerReading, Error> {
+    if !aml.status(host, battery)?.battery_present() {
+        return Ok(PowerReading::unknown(BATTERY));
+    }
+    let info = aml.battery(host, battery)?;
+    let state = aml.battery_state(host, battery)?;
+    let flag = |set: bool, bit: u32| if set { bit } else { 0 };
+    Ok(PowerReading {
+        kind: BATTERY,
+        flags: BATTERY_PRESENT
+            | flag(state.charging, BATTERY_CHARGING)
+            | flag(state.discharging, BATTERY_DISCHARGING)
+            | flag(state.critical, BATTERY_CRITICAL)
+            | flag(info.milliwatts, BATTERY_MILLIWATTS),
+        value: state.remaining.unwrap_or(UNKNOWN),
+        limit: info.last_full_capacity.unwrap_or(UNKNOWN),
+        rate: state.rate.unwrap_or(UNKNOWN),
+    })
+}
+
+fn zone_reading(
+    aml: &mut Interpreter,
+    host: &mut KernelHost<'_>,
+    zone: NodeId,
+) -> Result<PowerReading, Error> {
+    let value = aml.temperature(host, zone)?;
+    let limit = aml.thermal_point(host, zone, "_CRT")?.unwrap_or(UNKNOWN);
+    Ok(PowerReading { value, limit, ..PowerReading::unknown(THERMAL_ZONE) })
+}
+
+/// Every battery, then every thermal zone. One that cannot be read is a
+/// reading of unknowns, so a reader's indices stay the machine's; `say` logs
+/// a line for each, which [`init`] wants once and a refresh never does.
+fn read_power(
+    aml: &mut Interpreter,
+    host: &mut KernelHost<'_>,
+    batteries: &[NodeId],
+    zones: &[NodeId],
+    say: bool,
+) -> Vec<PowerReading> {
+    let shown = |v: u32| if v == UNKNOWN { String::from("?") } else { format!("{v}") };
+    let mut readings = Vec::with_capacity(batteries.len() + zones.len());
+    for &battery in batteries {
+        let path = aml.namespace().path(battery);
+        let reading = match battery_reading(aml, host, battery) {
+            Ok(r) if r.flags & BATTERY_PRESENT == 0 => {
+                if say {
+                    log!("AML: battery {path} slot empty");
+                }
+                r
+            }
+            Ok(r) => {
+                if say {
+                    log!(
+                        "AML: battery {path} {} of {} {}{}",
+                        shown(r.value),
+                        shown(r.limit),
+                        if r.flags & BATTERY_MILLIWATTS != 0 { "mWh" } else { "mAh" },
+                        if r.flags & BATTERY_CHARGING != 0 { ", charging" } else { "" }
+                    );
+                }
+                r
+            }
+            Err(e) => {
+                if say {
+                    log!("AML: battery {path} unreadable: {e}");
+                }
+                PowerReading::unknown(BATTERY)
+            }
+        };
+        readings.push(reading);
+    }
+    for &zone in zones {
+        let path = aml.namespace().path(zone);
+        let reading = match zone_reading(aml, host, zone) {
+            Ok(r) => {
+                if say {
+                    let celsius = i64::from(r.value) - 2732;
+                    log!(
+                        "AML: thermal zone {path} at {}.{} C",
+                        celsius / 10,
+                        celsius.rem_euclid(10)
+                    );
+                }
+                r
+            }
+            Err(e) => {
+                if say {
+                    log!("AML: thermal zone {path} _TMP failed: {e}");
+                }
+                PowerReading::unknown(THERMAL_ZONE)
+            }
+        };
+        readings.push(reading);
+    }
+    readings
+}
+
+/// Build the namespace from the DSDT and every SSDT, connect the address
+/// spaces, run `_INI`, and keep the interpreter for `acpid` — or log why not
+/// and leave the machine without one, as it was before this file.
+///
+/// After [`acpi::init_power`], which finds the DSDT, and before `sci::init`,
+/// which enables the GPEs this found methods for. `ecam` is the window
+/// `kernel_main` mapped for PCI enumeration.
+pub fn init(rsdp_addr: u64, ecam: Mmio) {
+    let Some(dsdt) = acpi::dsdt() else {
+        log!("AML: no DSDT — no interpreter");
+        return;
+    };
+    let mut platform = Platform { ecam, ec: None, windows: BTreeMap::new() };
+    let mut host = KernelHost::new(&mut platform, None);
+    let mut aml = Interpreter::new();
+    if let Err(e) = aml.load_table(&mut host, &dsdt.to_vec()) {
+        // What loaded before the failure stays: it is still the best account
+        // of the machine there is, and the SSDTs may define the rest.
+        log!("AML: DSDT stopped loading: {e}");
+    }
+    let ssdts = acpi::ssdts(rsdp_addr);
+    for (i, ssdt) in ssdts.iter().enumerate() {
+        if let Err(e) = aml.load_table(&mut host, &ssdt.to_vec()) {
+            log!("AML: SSDT {i} stopped loading: {e}");
+        }
+    }
+    if let Err(e) = aml.set_apic_mode(&mut host) {
+        log!("AML: \\_PIC(1) failed: {e}");
+    }
+
+    host.platform.ec = find_ec(&mut aml, &mut host);
+    // SystemMemory, SystemIO and PCI_Config are there from the start and the
+    // EC's space is once its ports are known; `_REG` tells firmware so before
+    // `_INI` reads through any of them.
+    let mut spaces: Vec<u8> = Vec::from([0, 1, 2]);
+    if host.platform.ec.is_some() {
+        spaces.push(3);
+    }
+    for space in spaces {
+        for (node, e) in aml.connect_space(&mut host, space) {
+            log!("AML: {}._REG({space}) failed: {e}", aml.namespace().path(node));
+        }
+    }
+    for (node, e) in aml.initialize(&mut host) {
+        log!("AML: {} failed to initialise: {e}", aml.namespace().path(node));
+    }
+    match aml.sleep_type(&mut host, 3) {
+        Ok(Some((a, b))) => {
+            log!("AML: \\_S3_ SLP_TYPa={a} SLP_TYPb={b}");
+            *S3.lock() = Some((a, b));
+        }
+        Ok(None) => log!("AML: no \\_S3_ — this machine does not suspend to RAM"),
+        Err(e) => log!("AML: \\_S3_ unreadable: {e} — no suspend to RAM"),
+    }
+    // Nothing is listening for what boot-time methods notified.
+    let _ = aml.take_notifications();
+
+    let mut gpes: Vec<Gpe> = gpe_methods(&aml).into_iter().map(Gpe::Method).collect();
+    if let Some(ec) = host.platform.ec {
+        let gpe = ec.gpe.map_or(String::from("none"), |n| format!("{n:#04x}"));
+        log!(
+            "AML: EC {} on ports {:#x}/{:#x}, GPE {gpe}",
+            aml.namespace().path(ec.node),
+            ec.data,
+            ec.command
+        );
+        if let Some(number) = ec.gpe {
+            gpes.retain(|g| g.number() != number);
+            gpes.push(Gpe::Ec(number));
+        }
+    }
+    for gpe in &gpes {
+        let (word, mask) = bit(&HANDLED, gpe.number());
+        word.fetch_or(mask, Ordering::Relaxed);
+        if let Gpe::Method(Method { trigger: Trigger::Level, number }) = gpe {
+            let (word, mask) = bit(&LEVEL, *number);
+            word.fetch_or(mask, Ordering::Relaxed);
+        }
+    }
+
+    let buttons = Buttons {
+        lids: aml.find_devices(&mut host, "PNP0C0D"),
+        power: aml.find_devices(&mut host, "PNP0C0C"),
+        sleep: aml.find_devices(&mut host, "PNP0C0E"),
+    };
+    let routes = pci_routes(&mut aml, &mut host);
+    let batteries = aml.find_devices(&mut host, "PNP0C0A");
+    let zones = thermal_zones(&aml);
+    let readings = read_power(&mut aml, &mut host, &batteries, &zones, true);
+    let powered = powered_devices(&mut aml, &mut host);
+    drop(host);
+
+    log!(
+        "AML: {} objects from the DSDT and {} SSDTs, {} GPE handlers, {} lids, {} power and {} sleep buttons, {} devices with _PS3",
+        aml.namespace().len(),
+        ssdts.len(),
+        gpes.len(),
+        buttons.lids.len(),
+        buttons.power.len(),
+        buttons.sleep.len(),
+        powered.len()
+    );
+    *PCI_ROUTES.lock() = routes;
+    *READINGS.lock() = readings;
+    *STATE.lock() = Some(State { aml, platform, gpes, buttons, batteries, zones, powered });
+}
+
+/// Start the thread. Called once, from `kernel_main`, beside `iod`'s.
+pub fn start() {
+    let _ = kthread::spawn(NAME, body, 0, OnPanic::Recover);
+}
+
+extern "C" fn body(_arg: u64) -> ! {
+    let parkable = scheduler::Parkable::at_entry();
+    // Out of the lock for good: see the module doc for why no method runs
+    // under one.
+    let mut state = STATE.lock().take();
+    // Armed once and held across the loop — §5.3a's edge contract: a GPE
+    // raised while a method is running must find the watch still armed.
+    let armed = completion::arm(Subject::of(&EVENTS), Token::new(0), WaitClass::Io)
+        .expect("a kernel thread is a task and can arm");
+    // `init` has just read them.
+    let mut next_reading = crate::clock::now() + READING_PERIOD;
+    loop {
+        // Ahead of any GPE: the caller is parked on it, and on the way down
+        // every other CPU is waiting for it to be done.
+        let call = *SLEEP_CALL.lock();
+        if let Some(call) = call {
+            if let SleepCall::Wake(_) = call {
+                // Back from the suspending CPU, which has had it since `_PTS`.
+                state = state.or_else(|| STATE.lock().take());
+            }
+            if let Some(state) = state.as_mut() {
+                state.run_sleep((&parkable, &armed), call);
+            }
+            if let SleepCall::Prepare(_) = call {
+                // Lent until `_WAK`, for `set_device_power`. A GPE raised
+                // meanwhile stays in `PENDING` and runs once it is back.
+                *STATE.lock() = state.take();
+            }
+            *SLEEP_CALL.lock() = None;
+            completion::post(Subject::of(&SLEEP_DONE), Outcome::Ready);
+        }
+        if let Some(state) = state.as_mut() {
+            for (word, pending) in PENDING.iter().enumerate() {
+                let mut bits = pending.swap(0, Ordering::Relaxed);
+                while bits != 0 {
+                    let number = (word * 64) as u8 + bits.trailing_zeros() as u8;
+                    bits &= bits - 1;
+                    state.run_gpe((&parkable, &armed), number);
+                    super::sci::gpe_done(u16::from(number));
+                }
+            }
+            if state.reads_power() && Deadline::at(next_reading).reached(crate::clock::now()) {
+                state.refresh_readings((&parkable, &armed));
+                next_reading = crate::clock::now() + READING_PERIOD;
+            }
+        }
+        let deadline = match &state {
+            Some(state) if state.reads_power() => Deadline::at(next_reading),
+            _ => Deadline::never(),
+        };
+        // The cancel arm is unreachable: nothing retires a kernel thread.
+        let _ = completion::wait(&parkable, &armed, deadline);
+    }
+}
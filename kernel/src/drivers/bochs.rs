@@ -0,0 +1,277 @@
+//! QEMU's standard VGA and `bochs-display`, driven through the Bochs DISPI
+//! registers so the resolution can change after boot.
+//!
+//! Firmware leaves both devices scanning out a GOP mode, and the GOP driver
+//! can draw into it but never change it: boot services are gone. The DISPI
+//! registers are what firmware itself used to set that mode, and nothing about
+//! them needs boot services, so this driver takes the same device over from
+//! GOP and answers `set_resolution` by programming them again.
+//!
+//! The framebuffer never moves. It is BAR 0, the device's whole video memory,
+//! and every mode is scanned out from its first byte — so unlike virtio-gpu a
+//! mode change allocates nothing, frees nothing, and the panic console, which
+//! was already drawing into this memory under GOP, follows the new geometry
+//! rather than going dark (see [`panic_console::follow`]).
+//!
+//! Only the MMIO register window in BAR 2 is used. `bochs-display` has no I/O
+//! ports, and a standard VGA with `mmio=off` has no BAR 2 — that device is left
+//! to GOP, which still shows a picture, rather than driven through a second
+//! register interface nothing else here would exercise.
+//!
+//! Which mode, whether it fits and whether the device took it are
+//! `toyos-dispi`'s; this file reads and writes the registers it names.
+
+use alloc::boxed::Box;
+
+use toyos_abi::syscall::SyscallError;
+use toyos_dispi::{regs, Caps, Mode, State};
+use toyos_edid::Edid;
+
+use super::panic_console;
+use super::pci::PciDevice;
+use crate::gpu::{Gpu, GpuInfo};
+use crate::log;
+use crate::mm::paging::CachePolicy;
+use crate::mm::{DirectMap, Mmio, PAGE_2M};
+use crate::object::shm::{Pages, Region};
+use crate::sync::Lock;
+
+const BOCHS_VENDOR: u16 = 0x1234;
+/// Both the standard VGA and `bochs-display`: the latter is the former with
+/// the legacy VGA taken out, and keeps its ID.
+const BOCHS_DEVICE: u16 = 0x1111;
+/// BAR 2 as QEMU sizes it.
+const MMIO_BYTES: u64 = 0x1000;
+
+/// The mode set when firmware left none this driver can draw into, and the
+/// monitor's EDID names none the device can show.
+const DEFAULT_MODE: (u32, u32) = (1280, 720);
+
+/// The registers and the mode on screen, for [`resume`]: the `Gpu` itself is
+/// the compositor's, behind `gpu`'s lock, and the sleep path takes no lock a
+/// parked CPU might have held.
+static LIT: Lock<Option<(Mmio, Mode)>> = Lock::new(None);
+
+struct BochsGpu {
+    mmio: Mmio,
+    caps: Caps,
+    mode: Mode,
+    vram: Region,
+    cursor: Region,
+    edid: Option<Edid>,
+}
+
+impl BochsGpu {
+    fn build_gpu_info(&self) -> GpuInfo {
+        let width = u32::from(self.mode.width);
+        let height = u32::from(self.mode.height);
+        GpuInfo {
+            // One buffer under both names, as with GOP: the device scans out
+            // of the start of video memory and has no second one to flip to.
+            scanout: core::array::from_fn(|_| self.vram.clone()),
+            cursor: self.cursor.clone(),
+            width,
+            height,
+            stride: self.mode.stride / 4,
+            pixel_format: 1, // BGR: a 32bpp DISPI pixel is little-endian XRGB
+            flags: 0,
+            outputs: alloc::vec![toyos_abi::Output { x: 0, y: 0, width, height }],
+        }
+    }
+}
+
+impl Gpu for BochsGpu {
+    fn present_rect(&mut self, _x: u32, _y: u32, _w: u32, _h: u32) {
+        // Video memory is what the device scans out: writes are visible as
+        // they land.
+    }
+
+    // No hardware cursor: `flags` says so and the compositor draws its own.
+    fn set_cursor(&mut self, _hot_x: u32, _hot_y: u32) {}
+    fn move_cursor(&mut self, _x: u32, _y: u32) {}
+
+    fn set_resolution(&mut self, width: u32, height: u32) -> Result<GpuInfo, SyscallError> {
+        if width == u32::from(self.mode.width) && height == u32::from(self.mode.height) {
+            // `gpu::remode` detached the console for the window and re-arms it
+            // only on failure; nothing changed, so it comes straight back.
+            panic_console::rearm();
+            return Ok(self.build_gpu_info());
+        }
+        let mode = toyos_dispi::mode::plan(self.caps, width, height).map_err(|why| {
+            log!("Bochs VGA: {}x{} refused: {:?}", width, height, why);
+            SyscallError::InvalidArgument
+        })?;
+
+        log!("Bochs VGA: changing resolution {}x{} -> {}x{}",
+            self.mode.width, self.mode.height, width, height);
+        if !program(self.mmio, mode) {
+            // The device clamped something `plan` passed. Put back the mode the
+            // compositor still thinks it has rather than leave a third one.
+            log!("Bochs VGA: {}x{} did not take (read back {:?})", width, height, state(self.mmio));
+            if !program(self.mmio, self.mode) {
+                log!("Bochs VGA: and {}x{} did not come back", self.mode.width, self.mode.height);
+            }
+            return Err(SyscallError::InvalidArgument);
+        }
+        self.mode = mode;
+        *LIT.lock() = Some((self.mmio, mode));
+        panic_console::follow(self.vram.phys.phys(), self.vram.size, width, height, mode.stride / 4);
+        log!("Bochs VGA: resolution set to {}x{}", width, height);
+        Ok(self.build_gpu_info())
+    }
+
+    fn edid(&self, output: usize) -> Option<Edid> {
+        if output == 0 { self.edid } else { None }
+    }
+}
+
+/// The mode registers as they read now.
+fn state(mmio: Mmio) -> State {
+    let read = |index| mmio.read_u16(regs::offset(index));
+    State {
+        xres: read(regs::INDEX_XRES),
+        yres: read(regs::INDEX_YRES),
+        bpp: read(regs::INDEX_BPP),
+        virt_width: read(regs::INDEX_VIRT_WIDTH),
+        enable: read(regs::INDEX_ENABLE),
+    }
+}
+
+/// Program `mode` and say whether the device took it.
+fn program(mmio: Mmio, mode: Mode) -> bool {
+    for (index, value) in toyos_dispi::mode::writes(mode) {
+        mmio.write_u16(regs::offset(index), value);
+    }
+    toyos_dispi::mode::took(mode, state(mmio))
+}
+
+/// The EDID QEMU keeps at the start of BAR 2, parsed, or `None` when the
+/// device was given none (`edid=off`) or what is there is not one.
+fn read_edid(mmio: Mmio) -> Option<Edid> {
+    let mut bytes = [0u8; regs::EDID_BYTES];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = mmio.read_u8(regs::EDID_BASE + i as u64);
+    }
+    match Edid::parse(&bytes) {
+        Ok(edid) => Some(edid),
+        Err(e) => {
+            log!("Bochs VGA: EDID refused: {}", e);
+            None
+        }
+    }
+}
+
+/// The device's maxima. `GETCAPS` turns `XRES`/`YRES`/`BPP` into the maxima
+/// for as long as it is set, and is written together with whatever `ENABLE`
+/// already held: written alone it would also clear `ENABLED` and blank the
+/// mode firmware left on screen.
+fn read_caps(mmio: Mmio) -> Option<Caps> {
+    let reg = |index| regs::offset(index);
+    let enable = mmio.read_u16(reg(regs::INDEX_ENABLE));
+    mmio.write_u16(reg(regs::INDEX_ENABLE), enable | regs::GETCAPS);
+    let max_x = mmio.read_u16(reg(regs::INDEX_XRES));
+    let max_y = mmio.read_u16(reg(regs::INDEX_YRES));
+    let max_bpp = mmio.read_u16(reg(regs::INDEX_BPP));
+    mmio.write_u16(reg(regs::INDEX_ENABLE), enable);
+    let vram_64k = mmio.read_u16(reg(regs::INDEX_VIDEO_MEMORY_64K));
+    Caps::from_regs(max_x, max_y, max_bpp, vram_64k)
+}
+
+/// Take over a Bochs display from GOP, or `None` to leave it — or the lack
+/// of one — to the next driver in line.
+pub fn init(devices: &[PciDevice]) -> Option<(Box<dyn Gpu>, GpuInfo)> {
+    let pci_dev = *devices.iter().find(|d| d.is_id(BOCHS_VENDOR, BOCHS_DEVICE))?;
+    log!("Bochs VGA: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+    let (lfb, bar2) = match (pci_dev.memory_bar(0), pci_dev.memory_bar(2)) {
+        (Ok(lfb), Ok(bar2)) => (lfb.address(), bar2.address()),
+        (Err(why), _) => {
+            log!("Bochs VGA: left to GOP — its framebuffer is BAR 0 and {}", why);
+            return None;
+        }
+        (_, Err(why)) => {
+            log!("Bochs VGA: left to GOP — its DISPI registers are BAR 2 and {}", why);
+            return None;
+        }
+    };
+    let mmio = crate::mm::paging::map_mmio(bar2, MMIO_BYTES, CachePolicy::DeferToMtrr);
+
+    let id = mmio.read_u16(regs::offset(regs::INDEX_ID));
+    if !regs::supported(id) {
+        log!("Bochs VGA: left to GOP — DISPI interface {:#06x} does not report its memory", id);
+        return None;
+    }
+    let Some(caps) = read_caps(mmio) else {
+        log!("Bochs VGA: left to GOP — no 32bpp modes or no video memory");
+        return None;
+    };
+    // The whole of video memory is the scanout region, and regions are made
+    // of 2 MiB pages: rounding a smaller size up would hand the compositor a
+    // mapping past the end of the BAR.
+    if caps.vram_bytes % PAGE_2M != 0 {
+        log!("Bochs VGA: left to GOP — {} KiB of video memory is not whole 2 MiB pages",
+            caps.vram_bytes >> 10);
+        return None;
+    }
+    crate::mm::paging::map_mmio(lfb, caps.vram_bytes, CachePolicy::WriteCombining);
+
+    let edid = read_edid(mmio);
+
+    // Keep what firmware lit when it can be drawn into: the picture, and the
+    // panic console armed on it, carry on unchanged. Otherwise the monitor's
+    // preference, and the default when that does not fit either.
+    let firmware = toyos_dispi::mode::current(state(mmio)).filter(|m| m.bytes() <= caps.vram_bytes);
+    let mode = match firmware {
+        Some(mode) => mode,
+        None => {
+            let wanted = edid.and_then(|e| e.preferred).map_or(DEFAULT_MODE, |m| (m.width, m.height));
+            let Ok(mode) = toyos_dispi::mode::plan(caps, wanted.0, wanted.1)
+                .or_else(|_| toyos_dispi::mode::plan(caps, DEFAULT_MODE.0, DEFAULT_MODE.1))
+            else {
+                log!("Bochs VGA: left to GOP — no mode to set, up to {}x{} in {} KiB",
+                    caps.max_width, caps.max_height, caps.vram_bytes >> 10);
+                return None;
+            };
+            if !program(mmio, mode) {
+                log!("Bochs VGA: left to GOP — {}x{} did not take", mode.width, mode.height);
+                return None;
+            }
+            mode
+        }
+    };
+    log!("Bochs VGA: {}x{} stride={} at {:#x}, {} MiB video memory, up to {}x{}",
+        mode.width, mode.height, mode.stride / 4, lfb, caps.vram_bytes >> 20,
+        caps.max_width, caps.max_height);
+    // A no-op when firmware's mode was kept; the new geometry when it was not.
+    panic_console::follow(lfb, caps.vram_bytes, u32::from(mode.width), u32::from(mode.height), mode.stride / 4);
+
+    let cursor_pages = crate::mm::pmm::alloc_contiguous(1, crate::mm::pmm::Category::Framebuffer)
+        .expect("Bochs VGA: cursor alloc failed");
+    // System RAM the compositor composes the cursor in, as GOP's is.
+    let cursor = Region {
+        phys: DirectMap::from_phys(cursor_pages[0].direct_map().phys()),
+        size: PAGE_2M,
+        cache: CachePolicy::DeferToMtrr,
+        pages: Some(alloc::sync::Arc::new(Pages::new(cursor_pages))),
+    };
+    let vram = Region {
+        phys: DirectMap::from_phys(lfb),
+        size: caps.vram_bytes,
+        cache: CachePolicy::WriteCombining,
+        pages: None,
+    };
+    *LIT.lock() = Some((mmio, mode));
+    let gpu = BochsGpu { mmio, caps, mode, vram, cursor, edid };
+    let info = gpu.build_gpu_info();
+    Some((Box::new(gpu), info))
+}
+
+/// Light the mode again after S3, which powered the device down to the
+/// disabled state it starts in. The enable clears video memory, as every mode
+/// change here does, so the screen is black until the compositor next draws.
+pub fn resume() {
+    let Some((mmio, mode)) = *LIT.lock() else { return };
+    if !program(mmio, mode) {
+        log!("Bochs VGA: {}x{} did not come back after the sleep (read back {:?})",
+            mode.width, mode.height, state(mmio));
+    }
+}
@@ -0,0 +1,457 @@
+//! Intel 82574 (e1000e) Gigabit Ethernet, behind the same [`crate::net::Nic`]
+//! netd already drives virtio-net through.
+//!
+//! **netd does not know which NIC it has.** The claim carries the same
+//! [`NicInfo`] and the same one 2 MiB DMA page: receive buffers at
+//! `rx_buf_offset`, the transmit buffer at `tx_buf_offset`, and a
+//! `net_hdr_size` of zero because this device puts the Ethernet frame at the
+//! first byte of a buffer where virtio-net puts its 12-byte header. Everything
+//! the device writes into a descriptor is judged by `toyos_e1000e::ring`, which
+//! is tested on the host; this file moves quadwords and registers.
+//!
+//! One receive queue, one transmit queue, legacy descriptors, no offloads.
+//! Interrupts are receive-only: a frame sent is waited for on its descriptor,
+//! exactly as virtio-net's `submit_and_wait` waits on the used ring, because
+//! there is one transmit buffer and nothing to do until it is free.
+
+use alloc::boxed::Box;
+
+use toyos_e1000e::regs::{self, Link};
+use toyos_e1000e::{mac, Discard, Reaped, RxDesc, RxRing, TxDesc, TxRing};
+
+use super::pci::{PciDevice, MSIX_ENTRY};
+use super::DmaPool;
+use crate::arch::idt::E1000E_VECTOR;
+use crate::log;
+use crate::mm::paging::CachePolicy;
+use crate::mm::{Dma, Mmio};
+use crate::net::NicInfo;
+use crate::object::shm::Region;
+use crate::time::{Budget, Duration};
+use toyos_abi::syscall::SyscallError;
+
+const INTEL_VENDOR: u16 = 0x8086;
+/// The 82574L, which is the part QEMU's `e1000e` device models.
+const E1000E_DEVICE: u16 = 0x10D3;
+
+const DESC_BYTES: usize = 16;
+/// One more descriptor than there are buffers, and then some: a ring of `n`
+/// holds `n - 1`, and `RDLEN` has to be a multiple of eight descriptors.
+const RX_RING_LEN: u16 = 512;
+const TX_RING_LEN: u16 = 8;
+const RX_BUF_COUNT: usize = 256;
+/// `RCTL.BSIZE` 00: what [`regs::rctl`] programs.
+const RX_BUF_SIZE: u16 = 2048;
+
+// DMA layout (byte offsets; the rings 128-byte aligned as `RDBAL`/`TDBAL` ask,
+// the buffers 2 KiB aligned so none crosses a page):
+const OFF_RX_RING: usize = 0x0000; // 512 × 16 bytes
+const OFF_TX_RING: usize = 0x2000; // 8 × 16 bytes
+const OFF_RX_BUFS: usize = 0x3000; // 256 × 2 KiB
+const OFF_TX_BUF: usize = OFF_RX_BUFS + RX_BUF_COUNT * RX_BUF_SIZE as usize;
+const TX_BUF_LEN: usize = 0x800;
+const DMA_SIZE: usize = OFF_TX_BUF + TX_BUF_LEN;
+
+const _: () = assert!(RX_BUF_COUNT <= RxRing::new(RX_RING_LEN, RX_BUF_SIZE).capacity() as usize);
+const _: () = assert!(DMA_SIZE <= crate::mm::PAGE_2M as usize);
+
+/// `CTRL.RST` clearing itself. The datasheet asks for a microsecond before the
+/// first access; QEMU's takes none and a real part takes a few milliseconds.
+const RESET: Budget = Budget::of(
+    Duration::from_millis(100),
+    "the device is not bound and the machine has no NIC",
+);
+
+/// The NVM auto-read that loads the station address after a reset.
+const NVM_READ: Budget = Budget::of(
+    Duration::from_millis(10),
+    "the receive address is read as it stands, and refused if it is not valid",
+);
+
+/// One frame on the wire. A 1514-byte frame is 1.2 ms at 10 Mb/s, and QEMU
+/// completes it inside the `TDT` write.
+const TRANSMIT: Budget = Budget::of(
+    Duration::from_millis(50),
+    "the frame is left with the device, and frames netd sends before it \
+     says it is done are dropped",
+);
+
+/// The ring's bookkeeping is `toyos_e1000e`'s; what is here is which buffer is
+/// in which slot and which buffers netd is holding.
+///
+/// `'static` for the reason `virtio_net::VirtioNic` gives: the pool is leaked
+/// at `init`, and the receive buffers are mapped into netd for the boot.
+struct E1000e {
+    mmio: Mmio,
+    rx_ring: Dma<'static>,
+    tx_ring: Dma<'static>,
+    rx_bufs: [Dma<'static>; RX_BUF_COUNT],
+    tx_phys: u64,
+    rx: RxRing,
+    tx: TxRing,
+    /// Receive descriptor slot -> `rx_bufs` index, written when a buffer is
+    /// posted into the slot.
+    slot_buf: [u16; RX_RING_LEN as usize],
+    /// Buffers `poll_rx` handed to netd and `refill_rx_buf` has not had back.
+    /// The only buffers `refill_rx_buf` accepts, so an index netd did not get
+    /// from `poll_rx` cannot post a buffer twice.
+    lent: [bool; RX_BUF_COUNT],
+    /// Write-backs dropped, and the count as of the last line about them.
+    dropped: u32,
+    reported_drops: u32,
+    last_drop: Option<Discard>,
+    /// How `arm_interrupt` reached a CPU, which a reset makes this driver
+    /// say again: `IVAR` is the device's and goes with it.
+    delivery: Delivery,
+    /// Set when the device did not come out of reset after a sleep, for the
+    /// reason `virtio_net::VirtioNic` carries one.
+    failed: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Delivery {
+    MsiX,
+    Msi,
+}
+
+impl E1000e {
+    fn rx_desc(&self, slot: u16) -> RxDesc {
+        RxDesc::from_word(self.rx_ring.read::<u64>(slot as usize * DESC_BYTES + 8))
+    }
+
+    fn tx_desc(&self, slot: u16) -> TxDesc {
+        TxDesc::from_word(self.tx_ring.read::<u64>(slot as usize * DESC_BYTES + 8))
+    }
+
+    /// Put `buf` in the next slot and move `RDT` past it.
+    ///
+    /// The slot is there by construction: every buffer is either posted or
+    /// lent, and the ring holds more than there are buffers, so `None` here is
+    /// this driver losing count.
+    fn post_rx(&mut self, buf: usize) {
+        let slot = self.rx.post().expect("e1000e: a receive buffer with no slot to go into");
+        let [addr, word] = RxDesc::post(self.rx_bufs[buf].phys());
+        let at = slot as usize * DESC_BYTES;
+        self.rx_ring.write(at, addr);
+        self.rx_ring.write(at + 8, word);
+        self.slot_buf[slot as usize] = buf as u16;
+        // Both volatile, so the descriptor is written before the tail moves
+        // over it, and x86 does not reorder a store past an earlier one.
+        self.mmio.write_u32(regs::RDT, self.rx.tail() as u32);
+    }
+
+    /// Both rings empty and programmed, every buffer netd is not holding
+    /// posted, and the receiver, transmitter, link and interrupt on. Shared
+    /// by [`init`] and the resume, each of which has just reset the device:
+    /// a buffer `lent` across a sleep stays netd's and is posted by its
+    /// `refill_rx_buf`, into a ring that holds more than there are buffers.
+    fn start(&mut self) {
+        let mmio = self.mmio;
+        self.rx = RxRing::new(RX_RING_LEN, RX_BUF_SIZE);
+        self.tx = TxRing::new(TX_RING_LEN);
+        program_rings(mmio, self.rx_ring, self.tx_ring);
+        if let Delivery::MsiX = self.delivery {
+            route_msix(mmio);
+        }
+        for buf in 0..RX_BUF_COUNT {
+            if !self.lent[buf] {
+                self.post_rx(buf);
+            }
+        }
+        mmio.write_u32(regs::RCTL, regs::rctl());
+        mmio.write_u32(regs::TCTL, regs::tctl());
+        mmio.write_u32(regs::CTRL, mmio.read_u32(regs::CTRL) | regs::CTRL_SLU);
+        mmio.write_u32(regs::IMS, regs::ICR_RX);
+    }
+
+    /// What the descriptor at the head of the ring says, or `Idle` when the
+    /// device holds no buffer.
+    fn reap(&mut self) -> Reaped {
+        match self.rx.next() {
+            Some(slot) => {
+                let desc = self.rx_desc(slot);
+                self.rx.reap(desc)
+            }
+            None => Reaped::Idle,
+        }
+    }
+}
+
+impl crate::net::Nic for E1000e {
+    fn has_packet(&self) -> bool {
+        !self.failed && self.rx.next().is_some_and(|slot| self.rx_desc(slot).done())
+    }
+
+    fn poll_rx(&mut self) -> Option<(usize, usize)> {
+        if self.failed {
+            return None;
+        }
+        let mut acked = false;
+        let frame = loop {
+            match self.reap() {
+                Reaped::Frame { slot, len } => {
+                    let buf = self.slot_buf[slot as usize] as usize;
+                    self.lent[buf] = true;
+                    break Some((buf, len as usize));
+                }
+                Reaped::Dropped { slot, why } => {
+                    let buf = self.slot_buf[slot as usize] as usize;
+                    self.post_rx(buf);
+                    self.dropped = self.dropped.wrapping_add(1);
+                    self.last_drop = Some(why);
+                }
+                Reaped::Idle if acked => break None,
+                // Acknowledge, then look once more: a frame written back
+                // between the read that found the ring empty and the write
+                // that cleared the cause would otherwise wait for the next
+                // one to raise the interrupt again.
+                Reaped::Idle => {
+                    self.mmio.write_u32(regs::ICR, regs::ICR_RX);
+                    acked = true;
+                }
+            }
+        };
+        // Reported on change rather than per frame, as `virtio_net` reports
+        // its refusals: a peer sending garbage costs one line per drain.
+        if self.dropped != self.reported_drops {
+            if let Some(why) = self.last_drop {
+                log!("e1000e: dropped {} received frame(s), the last because {}",
+                    self.dropped.wrapping_sub(self.reported_drops), why);
+            }
+            self.reported_drops = self.dropped;
+        }
+        frame
+    }
+
+    fn refill_rx_buf(&mut self, buf_index: usize) -> Result<(), SyscallError> {
+        // `lent` is exactly `RX_BUF_COUNT` long, so `get` is the array bound
+        // and a `false` is a buffer netd was never given.
+        if !self.lent.get(buf_index).is_some_and(|&lent| lent) {
+            return Err(SyscallError::InvalidArgument);
+        }
+        self.lent[buf_index] = false;
+        if !self.failed {
+            self.post_rx(buf_index);
+        }
+        Ok(())
+    }
+
+    fn tx_buf_len(&self) -> usize { TX_BUF_LEN }
+
+    fn submit_tx(&mut self, total_len: usize) {
+        if self.failed {
+            return;
+        }
+        if let Some(slot) = self.tx.in_flight() {
+            let desc = self.tx_desc(slot);
+            if !self.tx.settle(desc) {
+                log!("e1000e: frame dropped, the one before it is still not sent");
+                return;
+            }
+        }
+        let slot = self.tx.submit().expect("e1000e: a frame in flight after settling");
+        // Exact: `net::submit_tx` bounded it by `TX_BUF_LEN`.
+        let [addr, word] = TxDesc::frame(self.tx_phys, total_len as u16);
+        let at = slot as usize * DESC_BYTES;
+        self.tx_ring.write(at, addr);
+        self.tx_ring.write(at + 8, word);
+        self.mmio.write_u32(regs::TDT, self.tx.tail() as u32);
+
+        let ring = self.tx_ring;
+        let sent = crate::clock::settles(TRANSMIT.nanos(), || {
+            TxDesc::from_word(ring.read::<u64>(at + 8)).done()
+        });
+        if sent {
+            self.tx.settle(self.tx_desc(slot));
+        } else {
+            log!("e1000e: a frame was not sent in {TRANSMIT}");
+        }
+    }
+
+    /// Receiver, transmitter and interrupt off, so nothing is fetched or
+    /// written back once the bus goes down. The reset is the resume's, which
+    /// needs one whether or not the power went.
+    fn suspend(&mut self) {
+        self.mmio.write_u32(regs::IMC, !0);
+        self.mmio.write_u32(regs::RCTL, 0);
+        self.mmio.write_u32(regs::TCTL, 0);
+    }
+
+    /// The station address is not read again: it is the NVM's, and the
+    /// auto-read after the reset loads the same one netd was given.
+    fn resume(&mut self) {
+        if self.failed {
+            return;
+        }
+        if !reset(self.mmio) {
+            self.failed = true;
+            log!("e1000e: did not come out of reset after the sleep; every frame is dropped");
+            return;
+        }
+        self.start();
+    }
+}
+
+/// Quiet, then reset: whatever was running stops before the rings it was
+/// using are replaced. `false` is a device still in reset after [`RESET`].
+fn reset(mmio: Mmio) -> bool {
+    mmio.write_u32(regs::IMC, !0);
+    mmio.write_u32(regs::RCTL, 0);
+    mmio.write_u32(regs::TCTL, 0);
+    mmio.write_u32(regs::CTRL, mmio.read_u32(regs::CTRL) | regs::CTRL_RST);
+    if !crate::clock::settles(RESET.nanos(), || mmio.read_u32(regs::CTRL) & regs::CTRL_RST == 0) {
+        return false;
+    }
+    if !crate::clock::settles(NVM_READ.nanos(), || mmio.read_u32(regs::EECD) & regs::EECD_AUTO_RD != 0) {
+        log!("e1000e: the NVM auto-read did not finish in {NVM_READ}");
+    }
+    mmio.write_u32(regs::IMC, !0);
+    mmio.write_u32(regs::ICR, !0);
+    true
+}
+
+/// Both rings zeroed and handed to the device, empty.
+fn program_rings(mmio: Mmio, rx_ring: Dma<'static>, tx_ring: Dma<'static>) {
+    rx_ring.zero();
+    tx_ring.zero();
+    mmio.write_u32(regs::RDBAL, rx_ring.phys() as u32);
+    mmio.write_u32(regs::RDBAH, (rx_ring.phys() >> 32) as u32);
+    mmio.write_u32(regs::RDLEN, rx_ring.size() as u32);
+    mmio.write_u32(regs::RDH, 0);
+    mmio.write_u32(regs::RDT, 0);
+    mmio.write_u32(regs::TDBAL, tx_ring.phys() as u32);
+    mmio.write_u32(regs::TDBAH, (tx_ring.phys() >> 32) as u32);
+    mmio.write_u32(regs::TDLEN, tx_ring.size() as u32);
+    mmio.write_u32(regs::TDH, 0);
+    mmio.write_u32(regs::TDT, 0);
+    mmio.write_u32(regs::TIPG, regs::TIPG_COPPER);
+    // No multicast group is joined, so no hash bit may let one through.
+    for i in 0..regs::MTA_DWORDS {
+        mmio.write_u32(regs::MTA + i * 4, 0);
+    }
+}
+
+/// Receive queue 0 to the MSI-X entry [`PciDevice::enable_msix`] programs.
+fn route_msix(mmio: Mmio) {
+    mmio.write_u32(regs::CTRL_EXT, mmio.read_u32(regs::CTRL_EXT) | regs::CTRL_EXT_PBA_CLR);
+    mmio.write_u32(regs::IVAR, regs::ivar_rx_only(MSIX_ENTRY));
+}
+
+/// Arm the receive interrupt, or say why the machine has no NIC.
+///
+/// MSI-X first, with `IVAR` routing receive queue 0 to the entry
+/// [`PciDevice::enable_msix`] programs, and MSI when the function offers no
+/// usable table — the 82574 has both, and MSI is one message for every cause,
+/// which is all this driver unmasks. Neither is a refusal, for the reason
+/// `virtio_net::arm_interrupt` gives: nothing here polls for a frame.
+fn arm_interrupt(pci_dev: &PciDevice) -> Option<Delivery> {
+    if pci_dev.enable_msix(E1000E_VECTOR) {
+        log!("e1000e: MSI-X vector {:#x} on table entry {}", E1000E_VECTOR, MSIX_ENTRY);
+        return Some(Delivery::MsiX);
+    }
+    if pci_dev.enable_msi(E1000E_VECTOR) {
+        log!("e1000e: MSI vector {:#x}", E1000E_VECTOR);
+        return Some(Delivery::Msi);
+    }
+    log!("e1000e: NOT INITIALISED at PCI {:02x}:{:02x}.{} — neither MSI-X nor MSI could be \
+         armed and this driver has no other way to be told a frame arrived",
+        pci_dev.bus, pci_dev.dev, pci_dev.func);
+    None
+}
+
+pub fn init(devices: &[PciDevice]) {
+    let Some(pci_dev) = devices.iter().find(|d| d.is_id(INTEL_VENDOR, E1000E_DEVICE)).copied() else {
+        log!("e1000e: no device found");
+        return;
+    };
+    log!("e1000e: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+    // One NIC per machine is what `net` holds and what netd drives. A second
+    // would replace the first under a netd already running on its DMA page.
+    if crate::net::nic_info().is_some() {
+        log!("e1000e: NOT INITIALISED at PCI {:02x}:{:02x}.{} — this machine's NIC is \
+             already bound, and netd drives one", pci_dev.bus, pci_dev.dev, pci_dev.func);
+        return;
+    }
+    // Refused by name for the reason `nvme::init` refuses a BAR 0 that is not
+    // memory: the 82574's registers are memory-mapped in BAR 0.
+    let bar = match pci_dev.memory_bar(0) {
+        Ok(memory) => memory.address(),
+        Err(why) => {
+            log!("e1000e: NOT INITIALISED at PCI {:02x}:{:02x}.{} — its registers are in BAR 0 \
+                 and {}", pci_dev.bus, pci_dev.dev, pci_dev.func, why);
+            return;
+        }
+    };
+    pci_dev.enable_bus_master();
+    let mmio = crate::mm::paging::map_mmio(bar, regs::BAR0_BYTES, CachePolicy::DeferToMtrr);
+
+    // Whatever firmware left running stops before the rings it was using are
+    // replaced.
+    if !reset(mmio) {
+        log!("e1000e: NOT INITIALISED at PCI {:02x}:{:02x}.{} — it did not come out of reset \
+             in {RESET}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+        return;
+    }
+
+    let mac = match mac::from_receive_address(mmio.read_u32(regs::RAL0), mmio.read_u32(regs::RAH0)) {
+        Ok(mac) => mac,
+        Err(why) => {
+            log!("e1000e: NOT INITIALISED at PCI {:02x}:{:02x}.{} — {}",
+                pci_dev.bus, pci_dev.dev, pci_dev.func, why);
+            return;
+        }
+    };
+
+    // Leaked for the reason `virtio_net::init` gives.
+    let dma = DmaPool::alloc(DMA_SIZE).leak();
+    let rx_ring = dma.subview(OFF_RX_RING, RX_RING_LEN as usize * DESC_BYTES);
+    let tx_ring = dma.subview(OFF_TX_RING, TX_RING_LEN as usize * DESC_BYTES);
+
+    let Some(delivery) = arm_interrupt(&pci_dev) else {
+        return;
+    };
+
+    let rx_bufs: [Dma<'static>; RX_BUF_COUNT] = core::array::from_fn(|i| {
+        dma.subview(OFF_RX_BUFS + i * RX_BUF_SIZE as usize, RX_BUF_SIZE as usize)
+    });
+    let mut nic = E1000e {
+        mmio,
+        rx_ring,
+        tx_ring,
+        rx_bufs,
+        tx_phys: dma.phys() + OFF_TX_BUF as u64,
+        rx: RxRing::new(RX_RING_LEN, RX_BUF_SIZE),
+        tx: TxRing::new(TX_RING_LEN),
+        slot_buf: [0; RX_RING_LEN as usize],
+        lent: [false; RX_BUF_COUNT],
+        dropped: 0,
+        reported_drops: 0,
+        last_drop: None,
+        delivery,
+        failed: false,
+    };
+    nic.start();
+
+    log!("e1000e: MAC {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}, {}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5],
+        Link::from_status(mmio.read_u32(regs::STATUS)));
+
+    let dma_base_phys = dma.phys() & !(crate::mm::PAGE_2M - 1);
+    let dma_region = Region {
+        phys: crate::DirectMap::from_phys(dma_base_phys),
+        size: crate::mm::PAGE_2M,
+        cache: CachePolicy::DeferToMtrr,
+        pages: None,
+    };
+    crate::net::set_nic_info(NicInfo {
+        dma: toyos_abi::HANDLE_INVALID,
+        rx_buf_offset: OFF_RX_BUFS as u32,
+        tx_buf_offset: OFF_TX_BUF as u32,
+        mac,
+        rx_buf_count: RX_BUF_COUNT as u16,
+        rx_buf_size: RX_BUF_SIZE,
+        net_hdr_size: 0,
+    }, dma_region);
+    crate::net::register(Box::new(nic));
+    log!("e1000e: {} RX buffers, ring of {}", RX_BUF_COUNT, RX_RING_LEN);
+}
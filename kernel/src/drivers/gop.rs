@@ -110,6 +110,10 @@ pub fn init(
         stride,
         pixel_format,
         flags: 0,
+        // Whatever firmware lit is one panel as far as the kernel can tell:
+        // GOP describes a single mode and never says how many connectors it
+        // mirrors it to.
+        outputs: alloc::vec![toyos_abi::Output { x: 0, y: 0, width, height }],
     };
 
     (Box::new(GopGpu), info)
@@ -13,6 +13,18 @@
 //! Nothing here decides. The moment this file has to know which codec or which
 //! pin, the line has moved and this stub has become a driver.
 //!
+//! **A sleep forgets the codec, and the kernel remembers it for soundd.** S3
+//! cuts the link's power, and a codec comes back from that with every widget
+//! at its default. So every verb soundd sends through `ICW` that sets
+//! something is kept, in the order it was sent, and [`resume`] sends the list
+//! again before it programs the stream. This is a recording and not a
+//! decision: the one thing read out of a verb is the bit the specification
+//! uses to mark a get, and a get is left out because its answer is only
+//! soundd's to read. The stream's format and tag are read back off the
+//! descriptor at [`suspend`]. The stream then stays stopped, and soundd
+//! learns it was reset from its completions
+//! ([`AudioCompletionRecord::RESET`]), because when to run is soundd's choice.
+//!
 //! Register offsets, bit positions and the descriptor layout come from the
 //! Intel High Definition Audio specification.
 
@@ -62,6 +74,12 @@ const IMMEDIATE_STATUS: u64 = 0x68;
 
 const GCTL_CRST: u32 = 1 << 0;
 const INTCTL_GIE: u32 = 1 << 31;
+const ICS_BUSY: u16 = 1 << 0;
+const ICS_VALID: u16 = 1 << 1;
+
+/// The bit of a verb that makes it a get. The 12-bit gets are `0xF00` and up,
+/// the 4-bit ones `0xA` to `0xD`, and each is its set with this bit added.
+const VERB_GET: u32 = 1 << 19;
 
 /// The first stream descriptor's offset, and the stride between them. Input
 /// descriptors come first, then output, then bidirectional — so which one is
@@ -134,6 +152,15 @@ const CODEC_DETECT: Delay = Delay::from_spec(
 /// call still fails — only the line stops.
 const MAX_NAMED_REFUSALS: usize = 16;
 
+/// How many codec settings are kept to send again after a sleep.
+///
+/// Policy. soundd configures a path in a few dozen verbs, and it sends them at
+/// claim time and not after. A driver that kept setting things would grow this
+/// list for the whole boot. Past the limit the list is no longer what the
+/// codec was told, so the machine stops offering S3 rather than wake up with
+/// half of it ([`suspend_refusal`]).
+const MAX_KEPT_VERBS: usize = 256;
+
 // --- what the interrupt handler may touch ---
 
 /// The handler's whole view of the device.
@@ -149,7 +176,8 @@ struct StreamIsr {
     last: AtomicUsize,
     /// Periods completed and not yet handed to the driver. Accumulating rather
     /// than a ring: an interrupt carries nothing a later one does not, so there
-    /// is no queue for a slow reader to overflow.
+    /// is no queue for a slow reader to overflow. [`resume`] replaces it with
+    /// [`AudioCompletionRecord::RESET`].
     mask: AtomicU32,
     /// `nanos_since_boot` at the newest interrupt folded into `mask`. The DLL
     /// measures a batch against its *last* grid point, which is why the newest
@@ -257,7 +285,7 @@ fn take_completions() -> Option<AudioCompletionRecord> {
     }
     Some(AudioCompletionRecord {
         mask,
-        _pad: 0,
+        feedback_rate: 0,
         timestamp_nanos: ISR.timestamp.load(Ordering::Acquire),
     })
 }
@@ -280,15 +308,48 @@ pub fn drain_completed(buf: &mut crate::user_ptr::UserBytesMut) -> usize {
 // --- the controller ---
 
 struct HdaController {
+    pci: PciDevice,
     regs: Mmio,
     stream: Mmio,
+    stream_index: u8,
+    /// The codecs `STATESTS` named at bind. A wake that finds fewer has lost one
+    /// soundd's verbs were for.
+    statests: u16,
     /// Kept so the pages outlive the mappings that name them — and so a
     /// controller this driver refuses below gives them back, which is why these
     /// are pools rather than leaked views. Nothing here holds a [`super::Dma`]
     /// past `init`: the descriptor list is written once and the PCM ring is
-    /// soundd's, so the borrows all end inside that function.
-    _bdl: super::DmaPool,
+    /// soundd's, so the borrows all end inside that function. The descriptor
+    /// list's address is read back out of its pool for [`resume`].
+    bdl: super::DmaPool,
     _pcm: super::DmaPool,
+    /// Every setting soundd sent a codec, oldest first, with no repeats.
+    verbs: alloc::vec::Vec<u32>,
+    /// Set when a setting did not fit in `verbs`.
+    verbs_lost: bool,
+    /// `SDnFMT` and the tag byte as [`suspend`] found them.
+    saved: Option<(u16, u8)>,
+}
+
+impl HdaController {
+    /// Keep `verb` to send again after a sleep, unless it is a get or is
+    /// already kept.
+    fn keep(&mut self, verb: u32) {
+        if verb & VERB_GET != 0 || self.verbs.contains(&verb) {
+            return;
+        }
+        if self.verbs.len() == MAX_KEPT_VERBS {
+            if !self.verbs_lost {
+                self.verbs_lost = true;
+                log!(
+                    "hda: soundd has set more than {MAX_KEPT_VERBS} codec verbs and the rest \
+                     are not kept — this machine will not sleep"
+                );
+            }
+            return;
+        }
+        self.verbs.push(verb);
+    }
 }
 
 static CONTROLLER: Lock<Option<HdaController>> = Lock::new(None);
@@ -367,12 +428,15 @@ pub fn reg_write(offset: u64, width: RegWidth, value: u32) -> Result<(), Syscall
         return Err(SyscallError::InvalidArgument);
     }
 
-    let guard = CONTROLLER.lock();
-    let controller = guard.as_ref().ok_or(SyscallError::NotFound)?;
+    let mut guard = CONTROLLER.lock();
+    let controller = guard.as_mut().ok_or(SyscallError::NotFound)?;
 
     if offset == stream_offset + SD_CTL {
         return start_stop(controller, value as u8);
     }
+    if offset == IMMEDIATE_COMMAND {
+        controller.keep(value);
+    }
     match width {
         RegWidth::U8 => controller.regs.write_u8(offset, value as u8),
         RegWidth::U16 => controller.regs.write_u16(offset, value as u16),
@@ -488,11 +552,11 @@ pub fn init(devices: &[PciDevice]) {
     let bdl = super::DmaPool::alloc(PERIODS * 16);
     let pcm = super::DmaPool::alloc(PERIODS * PERIOD_BYTES);
     // The unaligned discipline for the descriptor list: it is written here,
-    // once, and the controller is not told where it is until `SD_BDPL` thirty
-    // lines below — so nothing races these stores, and what is written is a
-    // layout the HDA specification chose (§3.6.2) rather than a Rust one. The
-    // PCM ring is only ever cleared and handed to soundd, so it takes the
-    // pool's own discipline and never reads or writes a `T` at all.
+    // once, and the controller is not told where it is until `program_stream`
+    // writes `SD_BDPL` below — so nothing races these stores, and what is
+    // written is a layout the HDA specification chose (§3.6.2) rather than a
+    // Rust one. The PCM ring is only ever cleared and handed to soundd, so it
+    // takes the pool's own discipline and never reads or writes a `T` at all.
     let bdl_view = bdl.view().unaligned();
     let pcm_view = pcm.view();
     // Exclusive: both pools were allocated on the two lines above and no address
@@ -521,16 +585,7 @@ pub fn init(devices: &[PciDevice]) {
         return;
     }
 
-    stream.write_u32(SD_BDPL, bdl_view.phys() as u32);
-    stream.write_u32(SD_BDPU, (bdl_view.phys() >> 32) as u32);
-    stream.write_u32(
-        SD_CBL,
-        stream::cyclic_length(PERIOD_BYTES as u32, PERIODS).expect("fits a u32"),
-    );
-    stream.write_u16(SD_LVI, stream::last_valid_index(PERIODS).expect("a ring of eight") as u16);
-    stream.write_u8(SD_CTL_TAG, STREAM_TAG << 4);
-    stream.write_u8(SD_CTL, SD_CTL_IOCE | SD_CTL_FEIE | SD_CTL_DEIE);
-    stream.write_u8(SD_STS, SD_STS_WRITE_CLEAR);
+    program_stream(stream, bdl_view.phys(), STREAM_TAG << 4);
 
     // SAFETY: no vector is armed yet, so nothing can observe a half-written
     // Option.
@@ -551,7 +606,18 @@ pub fn init(devices: &[PciDevice]) {
         pages: None,
     };
 
-    *CONTROLLER.lock() = Some(HdaController { regs, stream, _bdl: bdl, _pcm: pcm });
+    *CONTROLLER.lock() = Some(HdaController {
+        pci: *pci,
+        regs,
+        stream,
+        stream_index,
+        statests,
+        bdl,
+        _pcm: pcm,
+        verbs: alloc::vec::Vec::new(),
+        verbs_lost: false,
+        saved: None,
+    });
     *INFO.lock() = Some((HdaInfo {
         pcm: toyos_abi::HANDLE_INVALID,
         period_bytes: PERIOD_BYTES as u32,
@@ -575,6 +641,135 @@ pub fn init(devices: &[PciDevice]) {
     }
 }
 
+// --- the sleep ---
+
+/// Why this machine's HDA cannot be brought back from S3, or `None` when it
+/// can.
+pub fn suspend_refusal() -> Option<&'static str> {
+    CONTROLLER
+        .lock()
+        .as_ref()
+        .is_some_and(|c| c.verbs_lost)
+        .then_some("soundd set more codec verbs than the kernel keeps to send again")
+}
+
+/// Stop the stream ahead of S3 and read back what soundd programmed into it.
+///
+/// The engine is stopped whether or not soundd was running it, so it fetches
+/// no descriptor after the bus goes down. An engine that does not stop is
+/// logged and left. The reset in [`resume`] takes the stream down either way.
+pub fn suspend() {
+    let mut guard = CONTROLLER.lock();
+    let Some(controller) = guard.as_mut() else { return };
+    let stream = controller.stream;
+    controller.saved = Some((stream.read_u16(SD_FMT), stream.read_u8(SD_CTL_TAG)));
+    stream.write_u8(SD_CTL, 0);
+    if !crate::clock::settles(SETTLE_NS, || stream.read_u8(SD_CTL) & SD_CTL_RUN == 0) {
+        log!("hda: the output stream did not stop for the sleep");
+    }
+    controller.regs.write_u32(INTCTL, 0);
+    stream.write_u8(SD_STS, SD_STS_WRITE_CLEAR);
+}
+
+/// Bring the controller, its codecs and the stream back after S3, and tell
+/// soundd.
+///
+/// Bring-up over again, from the controller reset, with what bring-up did not
+/// have: soundd's codec settings, sent in the order soundd sent them, and the
+/// format and tag [`suspend`] read. The stream is left stopped. soundd's
+/// next completion read is [`AudioCompletionRecord::RESET`], which is how it
+/// knows its engine is no longer running and every period is its own again.
+/// A controller that does not come back is named and left silent.
+pub fn resume() {
+    let mut guard = CONTROLLER.lock();
+    let Some(controller) = guard.as_mut() else { return };
+    let pci = controller.pci;
+    match controller.restore() {
+        Ok(sent) => log!(
+            "hda: {:02x}:{:02x}.{} back after the sleep, {sent} codec verbs sent again",
+            pci.bus,
+            pci.dev,
+            pci.func
+        ),
+        Err(why) => log!(
+            "hda: {:02x}:{:02x}.{} did not come back from the sleep and is silent: {why}",
+            pci.bus,
+            pci.dev,
+            pci.func
+        ),
+    }
+    // Whatever was pending completed on a stream that no longer exists.
+    ISR.mask.store(AudioCompletionRecord::RESET, Ordering::Release);
+    let now = crate::clock::nanos_since_boot();
+    ISR.timestamp.store(now, Ordering::Relaxed);
+    // This runs with interrupts off on the one CPU left running, which is all
+    // `isr_publish` asks. The next drain on this CPU wakes soundd.
+    crate::irq_ring::isr_publish(crate::irq_ring::IrqSource::Audio, now);
+}
+
+impl HdaController {
+    /// Everything [`resume`] puts back, or the first thing that did not come
+    /// back. Answers how many codec verbs were sent.
+    fn restore(&mut self) -> Result<usize, &'static str> {
+        power_up(&self.pci);
+        if !reset_controller(self.regs) {
+            return Err("the controller never left reset");
+        }
+        let statests = self.regs.read_u16(STATESTS);
+        if statests & self.statests != self.statests {
+            return Err("a codec soundd configured no longer answers on the link");
+        }
+        for &verb in &self.verbs {
+            if !send_verb(self.regs, verb) {
+                return Err("a codec verb was not answered");
+            }
+        }
+        if !reset_stream(self.stream) {
+            return Err("the stream never left reset");
+        }
+        let (format, tag) = self.saved.unwrap_or((0, STREAM_TAG << 4));
+        program_stream(self.stream, self.bdl.view().phys(), tag);
+        self.stream.write_u16(SD_FMT, format);
+        self.regs.write_u32(INTCTL, INTCTL_GIE | (1 << self.stream_index));
+        Ok(self.verbs.len())
+    }
+}
+
+/// One verb over the immediate-command registers, soundd's own handshake: wait
+/// for the interface to be free, clear the last result, send, and wait for the
+/// answer. The answer is then cleared and not read. Only sets are sent from
+/// here, and what a set answers is nothing.
+fn send_verb(regs: Mmio, verb: u32) -> bool {
+    if !crate::clock::settles(SETTLE_NS, || regs.read_u16(IMMEDIATE_STATUS) & ICS_BUSY == 0) {
+        return false;
+    }
+    regs.write_u16(IMMEDIATE_STATUS, ICS_VALID);
+    regs.write_u32(IMMEDIATE_COMMAND, verb);
+    regs.write_u16(IMMEDIATE_STATUS, ICS_BUSY);
+    let answered = crate::clock::settles(SETTLE_NS, || {
+        let status = regs.read_u16(IMMEDIATE_STATUS);
+        status & ICS_BUSY == 0 && status & ICS_VALID != 0
+    });
+    regs.write_u16(IMMEDIATE_STATUS, ICS_VALID);
+    answered
+}
+
+/// Every register of a stream that is reset that holds an address or indexes
+/// the descriptor list, plus its tag and interrupt enables. The stream is left
+/// stopped.
+fn program_stream(stream: Mmio, bdl: u64, tag: u8) {
+    stream.write_u32(SD_BDPL, bdl as u32);
+    stream.write_u32(SD_BDPU, (bdl >> 32) as u32);
+    stream.write_u32(
+        SD_CBL,
+        stream::cyclic_length(PERIOD_BYTES as u32, PERIODS).expect("fits a u32"),
+    );
+    stream.write_u16(SD_LVI, stream::last_valid_index(PERIODS).expect("a ring of eight") as u16);
+    stream.write_u8(SD_CTL_TAG, tag);
+    stream.write_u8(SD_CTL, SD_CTL_IOCE | SD_CTL_FEIE | SD_CTL_DEIE);
+    stream.write_u8(SD_STS, SD_STS_WRITE_CLEAR);
+}
+
 /// Every arm of [`write_permit`] and [`read_permit`], run against the bound
 /// controller and reported by name.
 ///
@@ -756,17 +951,23 @@ fn reset_stream(stream: Mmio) -> bool {
 
 /// Arm the completion interrupt, or say why this machine has no HDA audio.
 ///
+/// Message-signalled where the controller offers it, and its INTx pin where it
+/// does not — an HDA controller is a PCI function firmware routes like any
+/// other, and older PCH parts ship with MSI off. The pin is level-triggered,
+/// which [`isr_complete`] already answers for: the stream's status bit is the
+/// only cause `INTCTL` enables, and it is cleared before the EOI.
+///
 /// A refusal rather than a panic: a controller that cannot be told a period
 /// completed is one whose every period stays in flight forever, and a machine
 /// that boots and plays nothing is better than one that dies over a peripheral.
 fn arm_interrupt(pci: &PciDevice) -> bool {
     let vector = crate::arch::idt::HDA_VECTOR;
-    if pci.enable_msix(vector) || pci.enable_msi(vector) {
+    if pci.enable_msix(vector) || pci.enable_msi(vector) || pci.enable_intx(vector) {
         return true;
     }
     log!(
-        "hda: {:02x}:{:02x}.{} offers neither MSI-X nor MSI, and this driver has no other way to \
-         be told a period completed — NOT INITIALISED",
+        "hda: {:02x}:{:02x}.{} offers neither MSI-X nor MSI and its pin is routed nowhere, and \
+         this driver has no other way to be told a period completed — NOT INITIALISED",
         pci.bus,
         pci.dev,
         pci.func
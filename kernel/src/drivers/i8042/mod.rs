@@ -111,6 +111,8 @@ const ISA_IRQ_AUX: u8 = 12;
 const ISR_BURST: usize = 16;
 
 static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// The config byte `init` armed the pins with, for [`resume`] to write back.
+static ARMED_CONFIG: AtomicU8 = AtomicU8::new(0);
 static QUARANTINE: AtomicBool = AtomicBool::new(false);
 static KBD_EVENTS: AtomicU32 = AtomicU32::new(0);
 static AUX_EVENTS: AtomicU32 = AtomicU32::new(0);
@@ -1710,6 +1712,7 @@ pub fn init(rsdp_addr: u64) {
     if let Some(l) = aux_line {
         let _ = ioapic::set_masked(l.gsi, false);
     }
+    ARMED_CONFIG.store(config, Ordering::Relaxed);
     ACTIVE.store(true, Ordering::Relaxed);
     ARMED_NS.store(crate::clock::nanos_since_boot(), Ordering::Relaxed);
     HEALTH.store(HEALTH_ARMED, Ordering::Relaxed);
@@ -1736,15 +1739,61 @@ pub fn init(rsdp_addr: u64) {
     }
 }
 
+/// Put an armed controller and its devices back after S3.
+///
+/// Not the probe again: which wire format the keyboard speaks and which GSIs
+/// carry the two ports were settled at boot and the I/O APIC has its routes
+/// back already. What the power loss took is the controller's config byte —
+/// firmware's resume path leaves its own — and each device's enable, which a
+/// powered-up PS/2 device comes out of reset without. The aux device also
+/// loses its rate and resolution, so it gets `init`'s three commands.
+///
+/// Run with `IF` clear on the CPU the vector is pinned to, like `aux_reenable`,
+/// which is what makes this the sole reader of the output buffer; the config
+/// byte that arms the pins is written last, with the buffer drained first.
+pub fn resume() {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let config = ARMED_CONFIG.load(Ordering::Relaxed);
+    let budget = deadline(init_budget_ms());
+    command(CMD_DISABLE_PORT1, budget);
+    command(CMD_DISABLE_AUX, budget);
+    flush();
+    command(CMD_ENABLE_PORT1, budget);
+    let kbd = stage(ms(KEYBOARD), budget, "keyboard")
+        .is_some_and(|kbd| device_command(&[0xF4], kbd));
+    let aux = AUX_GSI.load(Ordering::Relaxed) != u32::MAX && {
+        command(CMD_ENABLE_AUX, budget);
+        stage(ms(AUX_RESET), budget, "aux reset").is_some_and(|reset| {
+            aux_command(&[0xF3, 0x64], reset)
+                && aux_command(&[0xE8, 0x03], reset)
+                && aux_command(&[0xF4], reset)
+        })
+    };
+    flush();
+    let armed = write_config(config, budget) && read_config(budget) == Some(config);
+    handler_poll();
+    log!(
+        "i8042: back after the sleep, kbd {} aux {} cfg {:#04x} {}",
+        if kbd { "scanning" } else { "SILENT" },
+        if AUX_GSI.load(Ordering::Relaxed) == u32::MAX { "absent" } else if aux { "reporting" } else { "SILENT" },
+        config,
+        if armed { "armed" } else { "DID NOT TAKE" }
+    );
+}
+
 /// One byte from the controller if it has one, and whether the aux port sent
 /// it. Never waits: a machine whose keyboard is dead, disabled or absent costs
 /// the caller one `inb` and answers `None` forever.
 ///
-/// **Only legal once every CPU is halted.** It reads port 0x60, which this
-/// module's whole design makes the ISR the sole reader of, and the halt is what
-/// stands in for that: there is no ISR left to race. It exists for the panic
-/// console's pager, which may take no lock and so cannot reach [`PS2`]'s
-/// decoders — it feeds a [`KeyDecoder`] of its own instead.
+/// **Only legal once every CPU is halted, or before [`init`] on the BSP
+/// alone.** It reads port 0x60, which this module's whole design makes the ISR
+/// the sole reader of, and either state stands in for that: there is no ISR
+/// left to race, or none yet. It exists for the panic console's pager, which
+/// may take no lock and so cannot reach [`PS2`]'s decoders, and for
+/// `crypt_device`'s passphrase prompt in the storage phase, which runs before
+/// this module is initialised — each feeds a [`KeyDecoder`] of its own.
 pub fn poll_byte() -> Option<(u8, bool)> {
     let status = inb(STATUS);
     if status & OBF == 0 {
@@ -1,8 +1,10 @@
 //! I/O APIC — the only path a pin interrupt has into this kernel.
 //!
-//! Every device here is MSI-X and the 8259 is masked first thing in
-//! `idt::init`, so before this module an ISA line had nowhere to land. Two
-//! properties follow, and both are the point of the module rather than
+//! Almost every device here is MSI-X and the 8259 is masked first thing in
+//! `idt::init`, so before this module an ISA line had nowhere to land. The
+//! exception is a PCI function that offers no message-signalled interrupt at
+//! all: `PciDevice::enable_intx` routes its pin here, to the GSI `_PRT` names.
+//! Two properties follow, and both are the point of the module rather than
 //! incidental to it:
 //!
 //! - Nothing this module reads back is trusted to be a chip. An MMIO window
@@ -17,8 +19,9 @@
 //!   obvious candidate — turns the first assertion of that pin into #GP and
 //!   panics the boot. That is why `init` runs between `lidt` and the first
 //!   `sti`: exception handlers are live throughout, and the window in which a
-//!   stray entry could fire never opens. Accepted consequence: no ACPI SCI,
-//!   so no power-button or lid events. They were a panic before.
+//!   stray entry could fire never opens. The SCI is routed again later, by
+//!   `drivers::sci`, once its handler exists and every event source firmware
+//!   left enabled has been turned off.
 //! - A register access is an index write followed by a data access, so it is
 //!   never atomic. `TOPOLOGY` serializes it and is taken from thread context
 //!   only. No ISR touches this module.
@@ -69,11 +72,12 @@ pub enum Polarity {
     Low,
 }
 
-/// An ISA line resolved against the override table: where it actually lands
-/// and how it is actually driven. Returned as one value so a caller cannot
-/// take the GSI and forget the electrical properties that came with it.
+/// A line resolved against firmware's tables — an ISA IRQ against the
+/// override table, a PCI pin against `_PRT`: where it actually lands and how it
+/// is actually driven. Returned as one value so a caller cannot take the GSI
+/// and forget the electrical properties that came with it.
 #[derive(Clone, Copy, Debug)]
-pub struct IsaLine {
+pub struct Line {
     pub gsi: Gsi,
     pub trigger: Trigger,
     pub polarity: Polarity,
@@ -113,6 +117,10 @@ struct Unit {
     mmio: Mmio,
     gsi_base: u32,
     entries: u32,
+    /// Every redirection entry as `suspend` found it, high word first. S3
+    /// powers the chip off and it wakes with every entry masked and pointing
+    /// at vector 0, so the routes drivers made would otherwise be gone.
+    saved: Vec<u64>,
 }
 
 impl Unit {
@@ -127,11 +135,15 @@ impl Unit {
     }
 }
 
+/// `None` in either field is the MPS "conforms to the bus" encoding, kept as
+/// such rather than resolved at parse: the ISA default is edge/high, but the
+/// SCI is an ISA IRQ whose default ACPI defines as level/low, so which default
+/// applies depends on who asks.
 struct Override {
     source_irq: u8,
     gsi: u32,
-    trigger: Trigger,
-    polarity: Polarity,
+    trigger: Option<Trigger>,
+    polarity: Option<Polarity>,
 }
 
 struct Topology {
@@ -151,7 +163,7 @@ pub fn init(madt: &MadtInfo) {
         // 0x20 covers IOREGSEL and IOWIN; every redirection entry is reached
         // through those two, so the window never needs to be larger.
         let mmio = crate::mm::paging::map_mmio(entry.address as u64, 0x20, CachePolicy::DeferToMtrr);
-        let mut unit = Unit { mmio, gsi_base: entry.gsi_base, entries: 0 };
+        let mut unit = Unit { mmio, gsi_base: entry.gsi_base, entries: 0, saved: Vec::new() };
         let ver = unit.read(REG_VER);
         let version = ver & 0xFF;
         unit.entries = ((ver >> 16) & 0xFF) + 1;
@@ -201,12 +213,14 @@ pub fn init(madt: &MadtInfo) {
         // MPS INTI flags: 00 in either field means "conforms to the bus", and
         // the ISA bus default is edge-triggered active-high.
         let polarity = match iso.flags & 0x3 {
-            3 => Polarity::Low,
-            _ => Polarity::High,
+            0 => None,
+            3 => Some(Polarity::Low),
+            _ => Some(Polarity::High),
         };
         let trigger = match (iso.flags >> 2) & 0x3 {
-            3 => Trigger::Level,
-            _ => Trigger::Edge,
+            0 => None,
+            3 => Some(Trigger::Level),
+            _ => Some(Trigger::Edge),
         };
         let _ = write!(
             table,
@@ -215,7 +229,7 @@ pub fn init(madt: &MadtInfo) {
             iso.bus,
             iso.source_irq,
             iso.gsi,
-            describe(trigger, polarity)
+            describe(trigger.unwrap_or(Trigger::Edge), polarity.unwrap_or(Polarity::High))
         );
         topology.overrides.push(Override {
             source_irq: iso.source_irq,
@@ -231,7 +245,7 @@ pub fn init(madt: &MadtInfo) {
     }
 }
 
-fn describe(trigger: Trigger, polarity: Polarity) -> &'static str {
+pub fn describe(trigger: Trigger, polarity: Polarity) -> &'static str {
     match (trigger, polarity) {
         (Trigger::Edge, Polarity::High) => "edge/high",
         (Trigger::Edge, Polarity::Low) => "edge/low",
@@ -242,25 +256,39 @@ fn describe(trigger: Trigger, polarity: Polarity) -> &'static str {
 
 /// Where ISA `irq` lands and how it is driven, or `None` when no I/O APIC
 /// exists at all.
-pub fn gsi_for_isa_irq(irq: u8) -> Option<IsaLine> {
+pub fn gsi_for_isa_irq(irq: u8) -> Option<Line> {
+    resolve(irq, Trigger::Edge, Polarity::High)
+}
+
+/// [`gsi_for_isa_irq`] for the ACPI SCI, the one ISA line whose default is not
+/// the bus's: ACPI defines it as shared, level-triggered and active-low unless
+/// an override says otherwise. Taking the ISA default would route a level line
+/// as an edge, and the first press would be the only one.
+pub fn gsi_for_sci(irq: u8) -> Option<Line> {
+    resolve(irq, Trigger::Level, Polarity::Low)
+}
+
+/// [`gsi_for_isa_irq`] for the ISA number BIOS wrote into a PCI function's
+/// Interrupt Line register, which is how the MADT alone says where a pin
+/// landed. The bus is PCI's, so the default is PCI's: level-triggered and
+/// active-low, unless an override for the number says otherwise.
+pub fn gsi_for_pci_irq(irq: u8) -> Option<Line> {
+    resolve(irq, Trigger::Level, Polarity::Low)
+}
+
+fn resolve(irq: u8, trigger: Trigger, polarity: Polarity) -> Option<Line> {
     let topology = TOPOLOGY.lock();
     if topology.units.is_empty() {
         return None;
     }
-    Some(
-        topology
-            .overrides
-            .iter()
-            .find(|o| o.source_irq == irq)
-            .map_or(
-                IsaLine {
-                    gsi: Gsi(irq as u32),
-                    trigger: Trigger::Edge,
-                    polarity: Polarity::High,
-                },
-                |o| IsaLine { gsi: Gsi(o.gsi), trigger: o.trigger, polarity: o.polarity },
-            ),
-    )
+    Some(topology.overrides.iter().find(|o| o.source_irq == irq).map_or(
+        Line { gsi: Gsi(irq as u32), trigger, polarity },
+        |o| Line {
+            gsi: Gsi(o.gsi),
+            trigger: o.trigger.unwrap_or(trigger),
+            polarity: o.polarity.unwrap_or(polarity),
+        },
+    ))
 }
 
 fn locate(topology: &Topology, gsi: Gsi) -> Result<(&Unit, u32), RouteError> {
@@ -334,3 +362,34 @@ pub fn set_masked(gsi: Gsi, masked: bool) -> Result<(), RouteError> {
     unit.write(index, if masked { low | RTE_MASKED } else { low & !RTE_MASKED });
     Ok(())
 }
+
+/// Snapshot every redirection entry ahead of S3. Delivery status and remote
+/// IRR are kept in the snapshot only because they are in the same word;
+/// `resume` strips them, since both are read-only and describe an interrupt
+/// the sleep has long since lost.
+pub fn suspend() {
+    let mut topology = TOPOLOGY.lock();
+    for unit in &mut topology.units {
+        unit.saved = (0..unit.entries)
+            .map(|n| {
+                let low = unit.read(REG_REDTBL + 2 * n);
+                let high = unit.read(REG_REDTBL + 2 * n + 1);
+                u64::from(high) << 32 | u64::from(low)
+            })
+            .collect();
+    }
+}
+
+/// Put back what `suspend` saved. Same order as `route`: the chip comes out
+/// of reset with every entry masked, so writing the destination before the
+/// low word means no entry is ever live at a destination it did not have.
+pub fn resume() {
+    let topology = TOPOLOGY.lock();
+    for unit in &topology.units {
+        for (n, &entry) in (0..).zip(&unit.saved) {
+            let low = entry as u32 & !(RTE_DELIVERY_STATUS | RTE_REMOTE_IRR);
+            unit.write(REG_REDTBL + 2 * n + 1, (entry >> 32) as u32);
+            unit.write(REG_REDTBL + 2 * n, low);
+        }
+    }
+}
@@ -19,17 +19,26 @@
 
 pub mod serial;
 pub mod acpi;
+pub mod ahci;
+pub mod e1000e;
 pub mod i8042;
 pub mod ioapic;
 pub mod pci;
+pub mod aml;
+pub mod sci;
 pub mod nvme;
 pub mod xhci;
 pub mod usb_storage;
 pub mod virtio;
+pub mod virtio_9p;
+pub mod virtio_blk;
 pub mod virtio_console;
 pub mod virtio_gpu;
+pub mod virtio_input;
 pub mod virtio_net;
 pub mod virtio_sound;
+pub mod virtio_vsock;
+pub mod bochs;
 pub mod gop;
 pub mod hda;
 pub mod panic_console;
@@ -1,48 +1,78 @@
 //! NVMe, as a [`BlockDevice`].
 //!
-//! **One command outstanding at a time**, which is a property of this driver
-//! and not of the protocol: every submission goes through `submit_and_wait`, so
-//! the completion at the head of a queue is always the one the caller asked
-//! for. `wait_completion`'s `cid` comparison is what checks that rather than
-//! assuming it.
+//! **One I/O queue pair per CPU, and several commands in flight on each.** The
+//! controller is asked for as many queue pairs as the machine has CPUs (Set
+//! Features, Number of Queues), and the CPU a caller runs on picks the pair it
+//! submits to, so two CPUs reading two files never meet on a lock this driver
+//! owns. Each pair is as deep as `CAP.MQES` allows up to [`MAX_DEPTH`], and one
+//! call keeps up to [`IN_FLIGHT`] commands outstanding on its pair, each with
+//! a data region of its own.
+//!
+//! **Completions are matched by command id, in whatever order they come.** A
+//! controller with several commands from one queue finishes them as its media
+//! allows, so the entry at the head of the completion queue is *a* command's
+//! and not necessarily the oldest. The `cid` in each entry is the one number
+//! this driver chose and the device must echo back unchanged (NVMe 2.0
+//! §3.3.3.2.1); it is looked up among the commands in flight and refused when
+//! it names none of them.
+//!
+//! **Each queue's interrupt is routed to its CPU, and that CPU halts until it
+//! comes.** Queue `q`'s MSI-X entry targets cpu `q`, which is the CPU whose
+//! callers submit to it, so a caller waiting for a completion is on the CPU the
+//! completion will interrupt: it halts in [`crate::clock::awaits`] and the
+//! interrupt is what wakes it. Not a sleep in the scheduler's sense — every
+//! caller holds preemption-disabling locks, so there is nothing to switch to —
+//! but no longer a spin either. A queue without an entry of its own, or a
+//! caller on a CPU that shares another's queue, polls as before.
+//!
+//! **Discard is Dataset Management with the deallocate attribute**, on a
+//! controller whose Identify Controller lists the command in ONCS. One command
+//! per call, with its range list where a read's data would go.
 //!
 //! # Two bounds, and only one of them is this driver's
 //!
 //! [`COMMAND`] bounds *one* command, and it is reached only by a controller
 //! that has stopped answering. What a caller actually spends is the composition
-//! above it — one `read_blocks` of N blocks is `ceil(N / 32)` commands — and
-//! nothing in this driver has an opinion about how long that may be.
-//! [`crate::block::OPERATION`] is that opinion, and it belongs to the layer
-//! that knows one call is one operation.
+//! above it — one `read_blocks` of N blocks is `ceil(N / 8)` commands, up to
+//! [`IN_FLIGHT`] of them at once — and nothing in this driver has an opinion
+//! about how long that may be. [`crate::block::OPERATION`] is that opinion,
+//! and it belongs to the layer that knows one call is one operation.
 //!
 //! **It arrives ambiently and is threaded from there.** Owner ruling 1B: the
 //! deadline is established on the running context by
 //! [`crate::block::begin_operation`] in [`NvmeBlockDevice`]'s trait methods —
 //! this file is both the establisher and the driver, where the USB path needs
 //! two files for it — recovered by `read_blocks` and `write_blocks`, and from
-//! there an ordinary argument down to `read_sectors` and `write_sectors`, which
-//! are the two sites that read it. `admin` is deliberately outside that: it is
+//! there an ordinary argument down to `IoQueue::transfer`, which is the one
+//! site that reads it. The admin queue is deliberately outside that: it is
 //! reached only from [`init`], bringing a controller up is not a block-device
 //! operation and has no establishment above it, so it takes no deadline
-//! argument and asking for one would panic the boot by name. What bounds it is
+//! argument and asking for one would panic the boot by name. [`resume`] is
+//! the same bring-up again, after S3, and reaches it the same way. What bounds it is
 //! [`COMMAND`], like every other command here.
 //!
 //! **The refusal is taken between commands and never inside one**, for the
 //! reason `XhciController::scsi` states at length: ending a wait at the
 //! caller's deadline abandons a command the device is still going to answer.
 //! Here that costs more than it does there, because there is no reset in this
-//! driver to take it back — see [`COMMAND`].
+//! driver to take it back — see [`COMMAND`]. With several commands in flight
+//! "between commands" means before the next submission: the ones already
+//! issued are waited for whatever the deadline says.
 
-use core::sync::atomic::{fence, Ordering};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, AtomicBool, AtomicU64, Ordering};
 use toyos_untrusted::{Refused, Untrusted};
 use crate::mm::Mmio;
 use super::pci::PciDevice;
 use super::DmaPool;
+use crate::arch::percpu;
 use crate::block::{self, BlockDevice, BlockError, BlockResult, DeviceId};
 use crate::mm::paging::CachePolicy;
 use crate::log;
-use crate::mm::{Dma, Unaligned};
-use crate::scheduler::Operation;
+use crate::mm::Dma;
+use crate::scheduler::{Operation, MAX_CPUS};
+use crate::sync::Lock;
 use crate::time::{Budget, Deadline, Duration};
 
 // NVMe register offsets (BAR0 MMIO)
@@ -53,7 +83,39 @@ const REG_AQA: u64 = 0x24;
 const REG_ASQ: u64 = 0x28;
 const REG_ACQ: u64 = 0x30;
 
-const QUEUE_DEPTH: usize = 16;
+/// `CC.SHN`, bits 15:14, and the normal shutdown notification written there.
+const CC_SHN_MASK: u32 = 0b11 << 14;
+const CC_SHN_NORMAL: u32 = 0b01 << 14;
+/// `CSTS.SHST`, bits 3:2, and the value that says the shutdown is done.
+const CSTS_SHST_MASK: u32 = 0b11 << 2;
+const CSTS_SHST_COMPLETE: u32 = 0b10 << 2;
+
+/// The deepest queue this driver creates, whatever `CAP.MQES` allows.
+///
+/// **Not the limit on what is outstanding; [`IN_FLIGHT`] is.** 64 submission
+/// entries are the one page the window gives the ring anyway, so a shallower
+/// ring would save nothing; a deeper one would be entries the controller is
+/// never handed anything in. The floor is two, which is the shallowest queue
+/// the specification lets a controller report.
+const MAX_DEPTH: u32 = 64;
+
+/// Commands one call keeps outstanding on its queue, each with its own data
+/// region and PRP list — and, one call at a time per queue, the most the
+/// queue ever has outstanding.
+///
+/// **The real limit, and it is memory, not the ring.** Each command owns a
+/// [`COMMAND_BYTES`] data region in its queue's window, so matching the ring's
+/// 63 would be two megabytes of contiguous DMA per CPU. Sixteen is 512 KiB,
+/// `file_cache`'s largest readahead window, so the largest read the kernel
+/// issues goes out in one round and nothing waits on a slot; a larger call
+/// reuses slots as their completions come back.
+const IN_FLIGHT: usize = 16;
+
+/// Pages one command moves. Paired with [`IN_FLIGHT`]: a large transfer is
+/// split into commands this size, and more of them in parallel is what the
+/// controller can reorder; one enormous command is what it cannot.
+const PAGES_PER_COMMAND: usize = 8;
+const COMMAND_BYTES: usize = PAGES_PER_COMMAND * 4096;
 
 /// How long one command may spend in the controller before this driver stops
 /// believing a completion is coming.
@@ -67,7 +129,9 @@ const QUEUE_DEPTH: usize = 16;
 /// is it on this one, and it is the same number because the arithmetic above it
 /// is the same arithmetic. It is generous by construction: an I/O command
 /// completes in microseconds even under TCG, so nothing but a controller that
-/// has stopped answering reaches it.
+/// has stopped answering reaches it. With several commands in flight it bounds
+/// the wait for the *next* completion, not for all of them: a controller still
+/// answering resets it with every entry it posts.
 ///
 /// **A [`Budget`] and not a [`crate::time::Bound`].** NVMe 2.0 states no
 /// completion timeout for an I/O command; `CAP.TO` is the one number the device
@@ -78,10 +142,12 @@ const QUEUE_DEPTH: usize = 16;
 ///
 /// **Its expiry ends this controller, which is why it may be generous.** A
 /// command this driver stops waiting for is a command the device still owns:
-/// its PRP list still names the shared DMA window and its completion still owes
-/// the entry at `cq_head`, so a command issued after it would race a stranger's
-/// DMA and read a stranger's status. There is no controller reset here to take
-/// either back, so the queue is abandoned with the command.
+/// its PRP list still names its queue's DMA window and its completion still
+/// owes an entry to that queue, so a command issued after it would race a
+/// stranger's DMA and read a stranger's status. There is no controller reset
+/// here to take either back, so every queue is abandoned with the command —
+/// the others are sound, but a controller that has stopped answering one
+/// queue is not a controller to go on trusting with the rest.
 const COMMAND: Budget = Budget::of(
     Duration::from_secs(2),
     "the command is abandoned, the controller is marked failed, and every later \
@@ -109,8 +175,36 @@ struct IdentifyNamespace {
 const ADMIN_CREATE_IO_SQ: u8 = 0x01;
 const ADMIN_CREATE_IO_CQ: u8 = 0x05;
 const ADMIN_IDENTIFY: u8 = 0x06;
+const ADMIN_SET_FEATURES: u8 = 0x09;
 const IO_WRITE: u8 = 0x01;
 const IO_READ: u8 = 0x02;
+const IO_DATASET_MANAGEMENT: u8 = 0x09;
+
+/// Dataset Management's CDW11 attribute for deallocate: the ranges hold
+/// nothing the host will read, which is the NVM command set's TRIM.
+const DSM_DEALLOCATE: u32 = 1 << 2;
+/// Ranges one Dataset Management carries: NR is 0-based in eight bits, and
+/// 256 sixteen-byte ranges are one page.
+const DSM_MAX_RANGES: usize = 256;
+const DSM_RANGE_BYTES: usize = 16;
+/// Identify Controller's Optional NVM Command Support word, and the bit in it
+/// that says Dataset Management is implemented.
+const ID_ONCS: usize = 520;
+const ONCS_DSM: u16 = 1 << 2;
+
+/// Set Features' identifier for Number of Queues (NVMe 2.0 §5.27.1.5).
+const FEATURE_NUMBER_OF_QUEUES: u32 = 0x07;
+
+/// Create I/O Completion Queue's CDW11 bits: physically contiguous, and
+/// interrupts enabled.
+const CQ_PC: u32 = 1 << 0;
+const CQ_IEN: u32 = 1 << 1;
+/// Create I/O Submission Queue's CDW11: physically contiguous.
+const SQ_PC: u32 = 1 << 0;
+
+/// The one command id a command may not carry: the error log uses it for "no
+/// particular command" (NVMe 2.0 §5.16.1.2).
+const CID_RESERVED: u16 = 0xFFFF;
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -136,6 +230,12 @@ impl SqEntry {
         mptr: 0, prp1: 0, prp2: 0,
         cdw10: 0, cdw11: 0, cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0,
     };
+
+    /// `opcode` with `cid` in the dword's upper half, where the controller
+    /// looks for it.
+    fn new(opcode: u8, cid: u16) -> Self {
+        Self { cdw0: (cid as u32) << 16 | opcode as u32, ..Self::ZERO }
+    }
 }
 
 #[repr(C)]
@@ -156,12 +256,13 @@ struct CqEntry {
 /// submission queue and writes the completion queue concurrently with this CPU,
 /// which is what the volatile discipline names — and a view carries the length,
 /// so an entry is bounded against the page the queue actually occupies rather
-/// than against `% QUEUE_DEPTH` being right. It is also what deleted
+/// than against `% depth` being right. It is also what deleted
 /// `unsafe impl Send for NvmeBlockDevice`: every field here is `Send` on its own
 /// now, so the auto trait applies.
-struct NvmeQueue {
+struct Ring {
     sq: Dma<'static>,
     cq: Dma<'static>,
+    depth: u16,
     sq_tail: u16,
     cq_head: u16,
     phase: bool,
@@ -169,109 +270,109 @@ struct NvmeQueue {
     cq_doorbell: u64,
 }
 
-impl NvmeQueue {
-    fn new(sq: Dma<'static>, cq: Dma<'static>, qid: u16, stride: u32) -> Self {
+impl Ring {
+    /// `sq` and `cq` are exactly `depth` entries each, which is what bounds
+    /// every access below.
+    fn new(sq: Dma<'static>, cq: Dma<'static>, depth: u16, qid: u16, stride: u32) -> Self {
         let doorbell_stride = 4u64 << stride;
         Self {
-            sq, cq,
+            sq, cq, depth,
             sq_tail: 0, cq_head: 0, phase: true,
             sq_doorbell: 0x1000 + (2 * qid as u64) * doorbell_stride,
             cq_doorbell: 0x1000 + (2 * qid as u64 + 1) * doorbell_stride,
         }
     }
 
-    fn submit(&mut self, bar: &Mmio, cmd: SqEntry) {
-        // Bounded by the write itself: `init` gave each queue a whole 4096-byte
-        // page, which is `QUEUE_DEPTH * size_of::<SqEntry>()` (16 * 64) exactly,
-        // and `sq_tail` is kept `% QUEUE_DEPTH` on the next line. Volatile
-        // because the controller reads this queue; not racing it for *this*
-        // entry, since this driver keeps one command outstanding and the `fence`
-        // and doorbell below are what tell the device about it.
+    /// Back to the state [`Self::new`] made, for a queue the controller is
+    /// about to be told about again.
+    fn restart(&mut self) {
+        self.sq_tail = 0;
+        self.cq_head = 0;
+        self.phase = true;
+    }
+
+    /// Place `cmd` at the tail without telling the controller: [`Self::ring`]
+    /// does that once for however many were placed.
+    ///
+    /// Never past an entry the controller has not fetched, because no caller
+    /// keeps more than `depth - 1` commands outstanding and a completion is
+    /// only posted for a command already fetched. Volatile because the
+    /// controller reads this queue; not racing it for *this* entry, which it
+    /// does not look at until the doorbell moves past it.
+    fn push(&mut self, cmd: SqEntry) {
         self.sq.write(self.sq_tail as usize * core::mem::size_of::<SqEntry>(), cmd);
-        self.sq_tail = (self.sq_tail + 1) % QUEUE_DEPTH as u16;
+        self.sq_tail = (self.sq_tail + 1) % self.depth;
+    }
+
+    fn ring(&self, bar: &Mmio) {
         fence(Ordering::Release);
         bar.write_u32(self.sq_doorbell, self.sq_tail as u32);
     }
 
-    /// Wait for the completion at the head of the queue, and refuse it unless
-    /// its `cid` is `expected`.
+    /// Wait for the next completion entry and consume it, whichever command
+    /// it answers; `None` when none came inside [`COMMAND`].
     ///
-    /// `cid` is the one number this driver chose for the command it
-    /// submitted and the device must echo back unchanged (NVMe 2.0
-    /// §3.3.3.2.1); nothing compared it against anything until now. Sound
-    /// today only because every submission on this queue is synchronous —
-    /// one command outstanding at a time is a property of the caller, not of
-    /// this parse, which is exactly why the comparison belongs here rather
-    /// than staying an invariant nobody checks.
+    /// `interrupted` says this ring's completion interrupt is routed to the
+    /// CPU running this, and then the wait halts between looks at the phase
+    /// bit rather than spinning on it; see [`IoQueue::interrupted`].
     ///
     /// **Bounded by [`COMMAND`], and by nothing the caller chose.** This loop
     /// used to have no deadline in it at all, which mattered more here than
-    /// anywhere else in the kernel: every real caller reaches it holding
-    /// `page_cache::BLOCK_CACHE` *and* `page_cache::BLOCK_DEV`, both
-    /// `sync::Lock`s that disable preemption for their whole life, so a
-    /// controller that stopped answering wedged a CPU holding two of the
-    /// machine's statics and the only thing that ever said so was some other
-    /// CPU's `DEADLOCK` panic naming the victim.
+    /// anywhere else in the kernel: every real caller reaches it holding a
+    /// `sync::Lock` that disables preemption for its whole life — a page-cache
+    /// lane or the device lock, and the queue's own — so a controller that
+    /// stopped answering wedged a CPU holding them and the only thing that
+    /// ever said so was some other CPU's `DEADLOCK` panic naming the victim.
     ///
     /// **Two reads of the entry and not one.** [`crate::clock::settles`] is the
-    /// kernel's one bounded driver spin and it takes a predicate, so the read
-    /// that decides is not the read that is consumed. Sound because one command
-    /// is outstanding at a time: once the phase bit at `cq_head` has flipped,
-    /// nothing writes that entry again until the head has been the whole way
-    /// round the queue. Spelling the loop out to read once instead would be a
-    /// fourth copy of `settles`' body, and that function's own doc records why
-    /// the body may not read `nanos_since_boot` per iteration.
-    fn wait_completion(&mut self, bar: &Mmio, expected: u16) -> Result<u16, Unanswered> {
+    /// kernel's one bounded driver spin and it takes a predicate, and so does
+    /// [`crate::clock::awaits`], so the read that decides is not the read that
+    /// is consumed. Sound whatever is in
+    /// flight: the controller posts entries at its own tail, and once the phase
+    /// bit at `cq_head` has flipped nothing writes that entry again until the
+    /// head has been the whole way round the queue. Spelling the loop out to
+    /// read once instead would be a fourth copy of `settles`' body, and that
+    /// function's own doc records why the body may not read `nanos_since_boot`
+    /// per iteration.
+    fn wait(&mut self, bar: &Mmio, interrupted: bool) -> Option<CqEntry> {
         let (cq, head, phase) = (self.cq, self.cq_head, self.phase);
         let at = |i: u16| i as usize * core::mem::size_of::<CqEntry>();
-        let answered = crate::clock::settles(COMMAND.nanos(), || {
+        let posted = || {
             // Volatile is exactly what makes this spin observe the phase bit
             // flipping rather than reading it once. In range for the same reason
-            // as `submit`: `cq_head` is kept `% QUEUE_DEPTH` and the queue is a
-            // whole page. Racing the controller by design — that is what a
+            // as `push`: `cq_head` is kept `% depth` and the view is `depth`
+            // entries. Racing the controller by design — that is what a
             // completion queue is — and the phase bit is the protocol's own
             // answer to whether the entry is complete (NVMe 2.0 §3.3.3.2).
             let entry: CqEntry = cq.read(at(head));
             ((entry.status & 1) != 0) == phase
-        });
+        };
+        let answered = if interrupted {
+            crate::clock::awaits(COMMAND.nanos(), posted)
+        } else {
+            crate::clock::settles(COMMAND.nanos(), posted)
+        };
         if !answered {
-            return Err(Unanswered::Silent);
+            return None;
         }
-        // The second read the doc comment argues for: `settles` takes a
-        // predicate, so the read that decided is not the read that is consumed,
-        // and it is sound because one command is outstanding at a time — once
-        // the phase bit at `cq_head` has flipped, nothing writes that entry
-        // again until the head has been the whole way round the queue.
-        let cq: CqEntry = self.cq.read(at(self.cq_head));
-        let status = cq.status >> 1;
-        let cid = Untrusted::new(cq.cid);
-        self.cq_head = (self.cq_head + 1) % QUEUE_DEPTH as u16;
+        let entry: CqEntry = self.cq.read(at(self.cq_head));
+        self.cq_head = (self.cq_head + 1) % self.depth;
         if self.cq_head == 0 {
             self.phase = !self.phase;
         }
         bar.write_u32(self.cq_doorbell, self.cq_head as u32);
-        cid.exactly(expected).map(|_| status).map_err(Unanswered::Wrong)
-    }
-
-    fn submit_and_wait(&mut self, bar: &Mmio, cmd: SqEntry) -> Result<u16, Unanswered> {
-        // The cid this driver chose for `cmd` is already packed into its own
-        // dword, which is why `wait_completion` needs no argument beyond it:
-        // reading it back out is not trusting `cmd` again, it is naming what
-        // this call itself just wrote.
-        let expected = (cmd.cdw0 >> 16) as u16;
-        self.submit(bar, cmd);
-        self.wait_completion(bar, expected)
+        Some(entry)
     }
 }
 
-/// Why a submitted command produced no status this driver may use.
+/// Why an admin command produced no status this driver may use.
 ///
-/// Two arms and not one, because what they leave behind differs and the
-/// controller's fate is decided on that difference. A completion carrying the
-/// wrong `cid` leaves the queue *consistent* — the entry was consumed, the head
-/// advanced, the doorbell rang — so the next command starts from a known place.
-/// A command that was never answered leaves the queue owed an entry and the DMA
-/// window owed a write, and nothing in this driver can take either back.
+/// Two arms and not one, because what they leave behind differs. A completion
+/// carrying the wrong `cid` leaves the queue *consistent* — the entry was
+/// consumed, the head advanced, the doorbell rang — so the next command starts
+/// from a known place. A command that was never answered leaves the queue owed
+/// an entry and the DMA window owed a write, and nothing in this driver can
+/// take either back.
 enum Unanswered {
     /// The completion queue answered a different command.
     Wrong(Refused),
@@ -290,207 +391,167 @@ impl core::fmt::Display for Unanswered {
     }
 }
 
-// DMA layout (byte offsets)
+// The controller's DMA window (byte offsets): the admin pair and the one page
+// its commands return data in, then one `QUEUE_WINDOW` per I/O queue pair.
 const OFF_ADMIN_SQ: usize   = 0x0000;
 const OFF_ADMIN_CQ: usize   = 0x1000;
-const OFF_IO_SQ: usize      = 0x2000;
-const OFF_IO_CQ: usize      = 0x3000;
-const OFF_IDENTIFY: usize   = 0x4000;
-const OFF_PRP_LIST: usize   = 0x5000;
-const OFF_DATA: usize       = 0x6000;
-const MAX_DATA_PAGES: usize  = 32;
-const DMA_SIZE: usize        = OFF_DATA + MAX_DATA_PAGES * 0x1000;
-
-/// Fill the PRP list with the physical address of every data page after the
-/// first, and answer with the list's own physical address for `prp2`.
-///
-/// A transfer of more than two pages names its pages through a list rather
-/// than through `prp1`/`prp2` (NVMe 2.0 §4.1.2). `read_sectors` and
-/// `write_sectors` had a byte-identical copy of this loop each.
-/// The unaligned discipline, because the list is written before the command
-/// naming it is submitted: the controller is not reading it while this runs.
-fn fill_prp_list(dma: Dma<'static, Unaligned>, pages: usize, data_phys: u64) -> u64 {
-    // The list holds `pages - 1` entries and is one page, so `pages` is
-    // bounded by `MAX_DATA_PAGES` — which both callers assert before getting
-    // here — and `subview` is what turns that into a check.
-    let list = dma.subview(OFF_PRP_LIST, (pages - 1) * core::mem::size_of::<u64>());
-    for i in 1..pages {
-        list.write::<u64>((i - 1) * core::mem::size_of::<u64>(), data_phys + i as u64 * 0x1000);
-    }
-    dma.phys() + OFF_PRP_LIST as u64
-}
-
-struct NvmeController {
+const OFF_IDENTIFY: usize   = 0x2000;
+const OFF_QUEUES: usize     = 0x3000;
+
+// One I/O queue pair's window, from its own base: the submission queue's page,
+// the completion queue and the PRP lists sharing the next, then one data region
+// per command in flight.
+const Q_SQ: usize           = 0x0000;
+const Q_CQ: usize           = 0x1000;
+const Q_PRP_LISTS: usize    = 0x1800;
+/// One command's PRP list: `PAGES_PER_COMMAND - 1` entries, rounded up.
+const PRP_LIST_BYTES: usize = 0x40;
+const Q_DATA: usize         = 0x2000;
+const QUEUE_WINDOW: usize   = Q_DATA + IN_FLIGHT * COMMAND_BYTES;
+
+const _: () = assert!((PAGES_PER_COMMAND - 1) * 8 <= PRP_LIST_BYTES);
+const _: () = assert!(Q_PRP_LISTS + IN_FLIGHT * PRP_LIST_BYTES <= Q_DATA);
+const _: () = assert!(Q_CQ + MAX_DEPTH as usize * core::mem::size_of::<CqEntry>() <= Q_PRP_LISTS);
+const _: () = assert!(IN_FLIGHT < MAX_DEPTH as usize);
+
+/// The admin queue, and what bringing the controller up needs beside it.
+/// Used by [`init`] and dropped there: nothing after boot issues an admin
+/// command.
+struct Admin {
     bar: Mmio,
     /// This controller's DMA window, leaked at `init` and therefore `'static`.
     /// It used to be a `static Lock<Option<DmaPool>>` that was written once and
     /// never read for anything but `slice()`; a leaked view says the same thing
     /// in the type and puts it where the controller is.
     dma: Dma<'static>,
-    admin: NvmeQueue,
-    io: NvmeQueue,
+    ring: Ring,
     next_cid: u16,
-    sector_size: u32,
-    ns_size: u64,
-    /// Whether a command has been abandoned on this controller. Once it has,
-    /// the queues and the DMA window are the device's and this driver issues
-    /// nothing more on them — see [`COMMAND`].
-    failed: bool,
 }
 
-impl NvmeController {
+impl Admin {
     /// Clear `len` bytes of the DMA window at `off`.
     ///
-    /// **One clearer instead of four.** `create_io_cq`, `create_io_sq`,
-    /// `identify_namespace` and `init` each spelled `write_bytes(<a raw pointer
-    /// derived from the pool>, 0, <a length>)` in an `unsafe` block of its own,
-    /// with the bound stated nowhere. Exclusive at every call site: each is
-    /// preparing a queue, a scratch page or a descriptor buffer before the
-    /// command that hands it to the controller is submitted, and this driver
-    /// keeps exactly one command outstanding.
+    /// **One clearer instead of four.** Queue creation, `identify_namespace`
+    /// and `init` each spelled `write_bytes(<a raw pointer derived from the
+    /// pool>, 0, <a length>)` in an `unsafe` block of its own, with the bound
+    /// stated nowhere. Exclusive at every call site: each is preparing a queue
+    /// or a scratch page before the command that hands it to the controller
+    /// is submitted, and the admin queue keeps one command outstanding.
     fn zero_dma(&self, off: usize, len: usize) {
         self.dma.subview(off, len).zero();
     }
 
-    fn alloc_cid(&mut self) -> u16 {
-        let cid = self.next_cid;
-        self.next_cid = self.next_cid.wrapping_add(1);
-        cid
-    }
-
-    /// One command on each queue, with the one verdict that outlives the
-    /// command folded into the controller's own state.
-    fn admin_command(&mut self, cmd: SqEntry) -> Result<u16, Unanswered> {
-        let out = self.admin.submit_and_wait(&self.bar, cmd);
-        self.note(&out);
-        out
-    }
-
-    fn io_command(&mut self, cmd: SqEntry) -> Result<u16, Unanswered> {
-        let out = self.io.submit_and_wait(&self.bar, cmd);
-        self.note(&out);
-        out
-    }
-
-    /// A command nobody answered ends this controller, once and loudly.
+    /// One admin command, with the status the controller returned actually
+    /// looked at, answering the completion's DW0. Six calls here discarded the
+    /// status, so a controller that refused to identify itself or to create a
+    /// queue produced a driver that went on to read whatever the DMA buffer
+    /// held and derive a geometry from it.
     ///
-    /// Once, because the line is about the abandonment and not about the caller
-    /// that noticed: the page cache retries, and a line per refused operation
-    /// would bury the one that says what happened. Every later refusal is
-    /// silent by design and carries the caller's own log line above it.
-    fn note(&mut self, out: &Result<u16, Unanswered>) {
-        if matches!(out, Err(Unanswered::Silent)) && !self.failed {
-            self.failed = true;
-            log!("NVMe: this controller is offline: the command it did not answer still owns \
-                 its PRP list and is still owed a completion entry, and this driver has no \
-                 reset to take either back");
-        }
-    }
-
-    /// An admin command, with the status the controller returned actually
-    /// looked at. Six calls here discarded it, so a controller that refused to
-    /// identify itself or to create a queue produced a driver that went on to
-    /// read whatever the DMA buffer held and derive a geometry from it.
+    /// One outstanding at a time, so the entry at the head is this command's
+    /// or a stranger's, and the `cid` comparison says which.
     ///
     /// No deadline argument, and no establishment above it: bringing a
     /// controller up is not a block-device operation, so what bounds these is
     /// [`COMMAND`] alone.
-    fn admin(&mut self, cmd: SqEntry, what: &str) -> bool {
-        let status = match self.admin_command(cmd) {
-            Ok(status) => status,
+    ///
+    /// Polled, never halted on: the first of these run before [`init`] has
+    /// routed any interrupt, and the rest are a handful at boot and at wake.
+    fn command(&mut self, opcode: u8, fill: impl FnOnce(&mut SqEntry), what: &str) -> Option<u32> {
+        let cid = self.next_cid;
+        self.next_cid = self.next_cid.wrapping_add(1);
+        let mut cmd = SqEntry::new(opcode, cid);
+        fill(&mut cmd);
+        self.ring.push(cmd);
+        self.ring.ring(&self.bar);
+        let answer = self.ring.wait(&self.bar, false).ok_or(Unanswered::Silent).and_then(|entry| {
+            Untrusted::new(entry.cid).exactly(cid).map(|_| entry).map_err(Unanswered::Wrong)
+        });
+        let entry = match answer {
+            Ok(entry) => entry,
             Err(why) => {
                 log!("NVMe: {what}: {why}");
-                return false;
+                return None;
             }
         };
+        let status = entry.status >> 1;
         if status != 0 {
             log!("NVMe: {what} failed, status={status:#x}");
-            return false;
-        }
-        true
-    }
-
-    /// Whether this command may be issued at all: the controller still has its
-    /// queues, and the caller's budget has something left in it.
-    ///
-    /// **Read between commands and never inside one**, which is the whole of
-    /// why a refusal here is free. Nothing has been submitted, no completion is
-    /// owed and the DMA window is nobody's, so this is a decision about the
-    /// *caller's* time and never a verdict about the disk: the controller is
-    /// left exactly as the previous operation left it, and the next caller
-    /// finds it that way. [`crate::block::OPERATION`] carries the rest of the
-    /// argument, and `XhciController::scsi` is the same decision on the USB
-    /// path.
-    ///
-    /// An offline controller refuses silently: the line that says what happened
-    /// was written once by [`Self::note`], and one per refused command after it
-    /// would bury that line under the page cache's retries.
-    fn may_issue(&self, until: Deadline, op: &str, lba: u64, sector_count: u32) -> bool {
-        if self.failed {
-            return false;
-        }
-        if until.reached(crate::clock::now()) {
-            log!("NVMe: {op} of {sector_count} sectors at {lba} not issued: {}", block::OPERATION);
-            return false;
+            return None;
         }
-        true
+        Some(entry.dw0)
     }
 
-    fn identify_controller(&mut self) -> bool {
-        let dma = self.dma;
-        let cid = self.alloc_cid();
-        let mut cmd = SqEntry::ZERO;
-        cmd.cdw0 = (cid as u32) << 16 | ADMIN_IDENTIFY as u32;
-        cmd.prp1 = dma.phys() + OFF_IDENTIFY as u64;
-        cmd.cdw10 = 1;
-        self.admin(cmd, "Identify Controller")
+    /// Identify the controller and answer its ONCS word, which is the one
+    /// thing in the structure this driver acts on.
+    fn identify_controller(&mut self) -> Option<u16> {
+        self.zero_dma(OFF_IDENTIFY, 4096);
+        let page = self.dma.phys() + OFF_IDENTIFY as u64;
+        self.command(ADMIN_IDENTIFY, |cmd| {
+            cmd.prp1 = page;
+            cmd.cdw10 = 1;
+        }, "Identify Controller")?;
+        // Completed, so nothing is writing the page; `identify_namespace`'s
+        // discipline for the same window.
+        Some(self.dma.unaligned().read(OFF_IDENTIFY + ID_ONCS))
     }
 
-    fn create_io_cq(&mut self) -> bool {
-        self.zero_dma(OFF_IO_CQ, QUEUE_DEPTH * core::mem::size_of::<CqEntry>());
-        let dma = self.dma;
-        let cid = self.alloc_cid();
-        let mut cmd = SqEntry::ZERO;
-        cmd.cdw0 = (cid as u32) << 16 | ADMIN_CREATE_IO_CQ as u32;
-        cmd.prp1 = dma.phys() + OFF_IO_CQ as u64;
-        cmd.cdw10 = ((QUEUE_DEPTH as u32 - 1) << 16) | 1;
-        cmd.cdw11 = 1;
-        self.admin(cmd, "Create I/O Completion Queue")
+    /// Ask for `wanted` I/O queue pairs and answer how many the controller
+    /// granted, at least one and at most `wanted`.
+    ///
+    /// Both counts are 0-based on the wire, and the controller may grant more
+    /// or fewer than asked; more is the controller's business, fewer is a
+    /// machine whose CPUs share queues. A controller that refuses the command
+    /// still has the one pair every controller supports.
+    fn number_of_queues(&mut self, wanted: u16) -> u16 {
+        let ask = (wanted - 1) as u32;
+        let Some(dw0) = self.command(ADMIN_SET_FEATURES, |cmd| {
+            cmd.cdw10 = FEATURE_NUMBER_OF_QUEUES;
+            cmd.cdw11 = ask << 16 | ask;
+        }, "Set Features (Number of Queues)") else {
+            return 1;
+        };
+        let submission = (dw0 & 0xFFFF) as u16;
+        let completion = (dw0 >> 16) as u16;
+        submission.min(completion).saturating_add(1).min(wanted)
     }
 
-    fn create_io_sq(&mut self) -> bool {
-        self.zero_dma(OFF_IO_SQ, QUEUE_DEPTH * core::mem::size_of::<SqEntry>());
-        let dma = self.dma;
-        let cid = self.alloc_cid();
-        let mut cmd = SqEntry::ZERO;
-        cmd.cdw0 = (cid as u32) << 16 | ADMIN_CREATE_IO_SQ as u32;
-        cmd.prp1 = dma.phys() + OFF_IO_SQ as u64;
-        cmd.cdw10 = ((QUEUE_DEPTH as u32 - 1) << 16) | 1;
-        cmd.cdw11 = (1 << 16) | 1;
-        self.admin(cmd, "Create I/O Submission Queue")
+    /// Create I/O queue pair `qid` over `sq` and `cq`, its completions raising
+    /// MSI-X entry `vector` when there is one.
+    fn create_pair(&mut self, qid: u16, depth: u16, sq: u64, cq: u64, vector: Option<u16>) -> bool {
+        let size = (depth as u32 - 1) << 16 | qid as u32;
+        let interrupts = match vector {
+            Some(entry) => (entry as u32) << 16 | CQ_IEN,
+            None => 0,
+        };
+        self.command(ADMIN_CREATE_IO_CQ, |cmd| {
+            cmd.prp1 = cq;
+            cmd.cdw10 = size;
+            cmd.cdw11 = interrupts | CQ_PC;
+        }, "Create I/O Completion Queue").is_some()
+            && self.command(ADMIN_CREATE_IO_SQ, |cmd| {
+                cmd.prp1 = sq;
+                cmd.cdw10 = size;
+                cmd.cdw11 = (qid as u32) << 16 | SQ_PC;
+            }, "Create I/O Submission Queue").is_some()
     }
 
-    fn identify_namespace(&mut self) -> bool {
-        let dma = self.dma;
+    /// The namespace's sector size and its size in sectors.
+    fn identify_namespace(&mut self) -> Option<(u32, u64)> {
         self.zero_dma(OFF_IDENTIFY, 4096);
-        let cid = self.alloc_cid();
-        let mut cmd = SqEntry::ZERO;
-        cmd.cdw0 = (cid as u32) << 16 | ADMIN_IDENTIFY as u32;
-        cmd.nsid = 1;
-        cmd.prp1 = dma.phys() + OFF_IDENTIFY as u64;
-        cmd.cdw10 = 0;
-        if !self.admin(cmd, "Identify Namespace") {
-            return false;
-        }
+        let page = self.dma.phys() + OFF_IDENTIFY as u64;
+        self.command(ADMIN_IDENTIFY, |cmd| {
+            cmd.nsid = 1;
+            cmd.prp1 = page;
+        }, "Identify Namespace")?;
 
         // A copy rather than the `&*(ptr as *const IdentifyNamespace)` that was
         // here, so nothing holds a reference into a window the device may write
         // again. Bounded for the whole structure, which is 384 bytes of the 4096
         // the command was given. The unaligned discipline: the transfer has
-        // completed — `admin` returned `true`, which means `wait_completion` saw
-        // the phase bit flip — so nothing is writing these bytes, and what is
-        // read is a layout NVMe 2.0 §5.17.2.1 chose.
-        let ns: IdentifyNamespace = dma.unaligned().read(OFF_IDENTIFY);
+        // completed — `command` answered, which means `wait` saw the phase bit
+        // flip — so nothing is writing these bytes, and what is read is a layout
+        // NVMe 2.0 §5.17.2.1 chose.
+        let ns: IdentifyNamespace = self.dma.unaligned().read(OFF_IDENTIFY);
         let fmt_idx = (ns.flbas & 0x0F) as usize;
         let lba_ds = (ns.lba_formats[fmt_idx] >> 16) & 0xFF;
         // `lba_ds` is an 8-bit device-reported shift, and it reaches both a
@@ -510,145 +571,391 @@ impl NvmeController {
              this driver serves 4096-byte blocks and needs 512..=4096",
             ns.flbas,
         );
-        self.sector_size = 1 << lba_ds;
-        self.ns_size = ns.nsze;
-        log!("NVMe: NS1 size={} sectors, sector_size={}", ns.nsze, self.sector_size);
-        true
+        let sector_size = 1 << lba_ds;
+        log!("NVMe: NS1 size={} sectors, sector_size={}", ns.nsze, sector_size);
+        Some((sector_size, ns.nsze))
     }
+}
 
-    /// Read `sector_count` contiguous sectors starting at `lba` into `buf`.
-    /// Handles PRP list setup for multi-page transfers.
-    ///
-    /// `until` is the whole operation's deadline and not this command's; see
-    /// [`Self::may_issue`] and the module header.
-    fn read_sectors(
-        &mut self,
-        lba: u64,
-        sector_count: u32,
-        buf: &mut [u8],
-        until: Deadline,
-    ) -> BlockResult {
-        let total_bytes = sector_count as usize * self.sector_size as usize;
-        assert!(buf.len() >= total_bytes);
-        assert!(total_bytes <= MAX_DATA_PAGES * 4096);
+/// Completion interrupts each CPU has taken from its queue.
+///
+/// **Counted here, waited on by the halt they end.** The waiter needs nothing
+/// from the handler but its arrival: it is halted on this CPU, and taking the
+/// interrupt is what returns it from the `hlt` to look at the phase bit. The
+/// count is what the line declaring a controller offline reports, because it
+/// separates the two ways a queue goes quiet: a controller that stopped
+/// posting interrupts too, and one whose interrupts arrive for entries the
+/// waiter never sees.
+static INTERRUPTS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+/// Acknowledge one queue's completion interrupt on the CPU it was routed to.
+/// Lock-free: it arrives while this CPU holds its queue's lock, halted in
+/// [`Ring::wait`], whenever a caller is waiting for it. MSI-X needs
+/// no acknowledgement at the device; the completion queue's head doorbell is
+/// what tells the controller an entry was consumed, and the waiter rings it.
+pub fn isr_complete() {
+    if let Some(count) = INTERRUPTS.get(percpu::cpu_id() as usize) {
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+}
 
-        if !self.may_issue(until, "read", lba, sector_count) {
-            return Err(BlockError);
+/// One transfer's direction, with the caller's buffer in it.
+enum Transfer<'a> {
+    Read(&'a mut [u8]),
+    Write(&'a [u8]),
+}
+
+impl Transfer<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Read(buf) => buf.len(),
+            Self::Write(buf) => buf.len(),
         }
+    }
 
-        let dma = self.dma;
-        let pages = total_bytes.div_ceil(4096);
-        let data_phys = dma.phys() + OFF_DATA as u64;
+    fn opcode(&self) -> u8 {
+        match self {
+            Self::Read(_) => IO_READ,
+            Self::Write(_) => IO_WRITE,
+        }
+    }
 
-        let cid = self.alloc_cid();
-        let mut cmd = SqEntry::ZERO;
-        cmd.cdw0 = (cid as u32) << 16 | IO_READ as u32;
-        cmd.nsid = 1;
-        cmd.prp1 = data_phys;
-        cmd.cdw10 = lba as u32;
-        cmd.cdw11 = (lba >> 32) as u32;
-        cmd.cdw12 = sector_count - 1;
-
-        if pages == 2 {
-            cmd.prp2 = data_phys + 0x1000;
-        } else if pages > 2 {
-            cmd.prp2 = fill_prp_list(dma.unaligned(), pages, data_phys);
+    fn op(&self) -> &'static str {
+        match self {
+            Self::Read(_) => "read",
+            Self::Write(_) => "write",
         }
+    }
+}
 
-        let status = match self.io_command(cmd) {
-            Ok(status) => status,
-            Err(why) => {
-                log!("NVMe: read of {sector_count} sectors at {lba}: {why}");
-                return Err(BlockError);
-            }
+/// One CPU's I/O queue pair and the window its commands' data moves through.
+struct IoQueue {
+    /// Which pair this is, 0-based; its queue id is one more.
+    index: usize,
+    ring: Ring,
+    window: Dma<'static>,
+    /// How many commands one call keeps outstanding: [`IN_FLIGHT`], or fewer
+    /// on a queue too shallow to hold them.
+    slots: usize,
+    /// Whether this pair's completion queue has an MSI-X entry of its own,
+    /// routed to cpu `index`.
+    routed: bool,
+    next_cid: u16,
+}
+
+/// Where piece `n` of a `total`-byte transfer starts, and how long it is.
+fn piece(n: usize, total: usize) -> (usize, usize) {
+    let off = n * COMMAND_BYTES;
+    (off, COMMAND_BYTES.min(total - off))
+}
+
+impl IoQueue {
+    /// Whether a completion on this pair interrupts the CPU waiting for it,
+    /// which is what lets that CPU halt instead of spin. The caller holds the
+    /// lock, so it stays on this CPU for the whole wait.
+    ///
+    /// Only on cpu `index`: a CPU past the count the controller granted shares
+    /// a pair whose interrupt goes to another CPU, and halting there would
+    /// wait for the timer rather than for the completion.
+    fn interrupted(&self) -> bool {
+        self.routed && percpu::cpu_id() as usize == self.index
+    }
+
+    fn alloc_cid(&mut self) -> u16 {
+        let cid = self.next_cid;
+        self.next_cid = match self.next_cid.wrapping_add(1) {
+            CID_RESERVED => 0,
+            next => next,
         };
-        if status != 0 {
-            log!("NVMe: read of {sector_count} sectors at {lba} failed, status={status:#x}");
-            return Err(BlockError);
-        }
+        cid
+    }
 
-        // A copy out rather than a `&[u8]` into the window, so no reference into
-        // DMA memory outlives the instant the driver knows the controller is
-        // done with it. Bounded on both sides: `copy_to` refuses
-        // `OFF_DATA + total_bytes` past the pool (`total_bytes <= MAX_DATA_PAGES
-        // * 4096` was asserted on entry), and `buf.len() >= total_bytes` was
-        // asserted with it.
-        dma.copy_to(OFF_DATA, &mut buf[..total_bytes]);
-        Ok(())
+    /// The data region of command slot `slot`, as an offset into the window.
+    fn data(slot: usize) -> usize {
+        Q_DATA + slot * COMMAND_BYTES
+    }
+
+    /// `prp2` for `pages` pages at slot `slot`'s data region: nothing for one
+    /// page, the second page for two, and past that the slot's PRP list, filled
+    /// here with every page after the first (NVMe 2.0 §4.1.2).
+    ///
+    /// The unaligned discipline for the list, because it is written before the
+    /// command naming it is submitted: the controller is not reading it while
+    /// this runs, and no other command names this slot's list.
+    fn prp2(&self, slot: usize, pages: usize) -> u64 {
+        let data = self.window.phys() + Self::data(slot) as u64;
+        match pages {
+            0 | 1 => 0,
+            2 => data + 0x1000,
+            _ => {
+                let at = Q_PRP_LISTS + slot * PRP_LIST_BYTES;
+                // The list holds `pages - 1` entries and `pages` is at most
+                // `PAGES_PER_COMMAND`; `subview` is what turns that into a check.
+                let list = self.window.unaligned().subview(at, (pages - 1) * 8);
+                for i in 1..pages {
+                    list.write::<u64>((i - 1) * 8, data + i as u64 * 0x1000);
+                }
+                self.window.phys() + at as u64
+            }
+        }
     }
 
-    fn write_sectors(
+    /// Move `io` to or from the namespace from sector `lba`, as commands of up
+    /// to [`COMMAND_BYTES`] with up to `slots` of them outstanding, completing
+    /// in whatever order the controller finishes them.
+    ///
+    /// **Returns only with nothing in flight**, whatever it returns: a command
+    /// this call issued owns its slot's data region until its completion has
+    /// been consumed, and the next call reuses the regions from the first. So
+    /// a refusal or a failed status stops further submissions and the loop
+    /// goes on reaping what was already issued. The one exception is a
+    /// controller that stops answering, which is abandoned with everything on
+    /// it — see [`COMMAND`].
+    ///
+    /// `until` is the whole operation's deadline and not this command's; see
+    /// [`NvmeController::may_issue`] and the module header.
+    fn transfer(
         &mut self,
+        ctrl: &NvmeController,
         lba: u64,
-        sector_count: u32,
-        buf: &[u8],
+        mut io: Transfer<'_>,
         until: Deadline,
     ) -> BlockResult {
-        let total_bytes = sector_count as usize * self.sector_size as usize;
-        assert!(buf.len() >= total_bytes);
-        assert!(total_bytes <= MAX_DATA_PAGES * 4096);
+        let total = io.len();
+        let sector = ctrl.sector_size as usize;
+        let sectors = |off: usize, len: usize| (lba + (off / sector) as u64, (len / sector) as u32);
+        let pieces = total.div_ceil(COMMAND_BYTES);
+        // Each slot's command id and the piece it carries, while it is in flight.
+        let mut flight: [Option<(u16, usize)>; IN_FLIGHT] = [None; IN_FLIGHT];
+        let mut next = 0;
+        let mut outcome = Ok(());
+
+        loop {
+            let mut pushed = false;
+            while outcome.is_ok() && next < pieces {
+                let Some(slot) = (0..self.slots).find(|&s| flight[s].is_none()) else { break };
+                let (off, len) = piece(next, total);
+                let (at, count) = sectors(off, len);
+                if !ctrl.may_issue(until, io.op(), at, count) {
+                    outcome = Err(BlockError);
+                    break;
+                }
+                // Bounded by `copy_from`, which refuses a region past the
+                // window; `len` is at most `COMMAND_BYTES`, which is the
+                // region. Exclusive: no command naming this slot is in flight.
+                if let Transfer::Write(buf) = &io {
+                    self.window.copy_from(Self::data(slot), &buf[off..off + len]);
+                }
+                let cid = self.alloc_cid();
+                let mut cmd = SqEntry::new(io.opcode(), cid);
+                cmd.nsid = 1;
+                cmd.prp1 = self.window.phys() + Self::data(slot) as u64;
+                cmd.prp2 = self.prp2(slot, len.div_ceil(4096));
+                cmd.cdw10 = at as u32;
+                cmd.cdw11 = (at >> 32) as u32;
+                cmd.cdw12 = count - 1;
+                self.ring.push(cmd);
+                flight[slot] = Some((cid, next));
+                next += 1;
+                pushed = true;
+            }
+            if pushed {
+                self.ring.ring(&ctrl.bar);
+            }
+            if flight.iter().all(Option::is_none) {
+                return outcome;
+            }
 
-        if !self.may_issue(until, "write", lba, sector_count) {
+            let interrupted = self.interrupted();
+            let Some(entry) = self.ring.wait(&ctrl.bar, interrupted) else {
+                let (at, count) = sectors(0, total);
+                log!("NVMe: {} of {count} sectors at {at}: {}", io.op(), Unanswered::Silent);
+                ctrl.abandon(self.index);
+                return Err(BlockError);
+            };
+            let cid = Untrusted::new(entry.cid);
+            let Some(slot) = (0..self.slots).find(|&s| flight[s].is_some_and(|(c, _)| cid.is(c)))
+            else {
+                // Consumed, so the queue is consistent; but a command of ours
+                // this entry should have answered may never be, and then the
+                // wait above is what says so.
+                log!("NVMe: queue {}: a completion for command {:#x}, which is not in flight",
+                    self.index, entry.cid);
+                outcome = Err(BlockError);
+                continue;
+            };
+            let Some((_, n)) = flight[slot].take() else { continue };
+            let (off, len) = piece(n, total);
+            let status = entry.status >> 1;
+            if status != 0 {
+                let (at, count) = sectors(off, len);
+                log!("NVMe: {} of {count} sectors at {at} failed, status={status:#x}", io.op());
+                outcome = Err(BlockError);
+            } else if let Transfer::Read(buf) = &mut io {
+                // A copy out rather than a `&[u8]` into the window, so no
+                // reference into DMA memory outlives the instant the driver
+                // knows the controller is done with it. Bounded on both sides
+                // by `copy_to` and by `piece`.
+                self.window.copy_to(Self::data(slot), &mut buf[off..off + len]);
+            }
+        }
+    }
+
+    /// Deallocate `count` sectors from `lba`, as one Dataset Management whose
+    /// range list is in slot 0's data region.
+    ///
+    /// One command and never several: a range's length is 32 bits of sectors,
+    /// so the most a `BlockDevice::discard` can ask for — `u32::MAX` blocks of
+    /// eight sectors — is nine ranges, and a command carries
+    /// [`DSM_MAX_RANGES`]. Slot 0's region is free because [`Self::transfer`]
+    /// returns only with nothing in flight, and this holds the queue's lock
+    /// from the list's first byte to the completion.
+    fn deallocate(&mut self, ctrl: &NvmeController, lba: u64, count: u64, until: Deadline) -> BlockResult {
+        let span = count.min(u32::MAX as u64) as u32;
+        if !ctrl.may_issue(until, "deallocate", lba, span) {
+            return Err(BlockError);
+        }
+        // Each range whole blocks' worth of sectors, so none ends mid-block.
+        let widest = (u32::MAX / (4096 / ctrl.sector_size) * (4096 / ctrl.sector_size)) as u64;
+        let ranges = count.div_ceil(widest) as usize;
+        if ranges == 0 || ranges > DSM_MAX_RANGES {
             return Err(BlockError);
         }
+        // The unaligned discipline, for `prp2`'s reason: written before the
+        // command naming it is submitted.
+        let list = self.window.unaligned().subview(Self::data(0), ranges * DSM_RANGE_BYTES);
+        for r in 0..ranges {
+            let start = r as u64 * widest;
+            let at = r * DSM_RANGE_BYTES;
+            list.write::<u32>(at, 0);
+            list.write::<u32>(at + 4, (count - start).min(widest) as u32);
+            list.write::<u64>(at + 8, lba + start);
+        }
+        let cid = self.alloc_cid();
+        let mut cmd = SqEntry::new(IO_DATASET_MANAGEMENT, cid);
+        cmd.nsid = 1;
+        cmd.prp1 = self.window.phys() + Self::data(0) as u64;
+        cmd.cdw10 = ranges as u32 - 1;
+        cmd.cdw11 = DSM_DEALLOCATE;
+        self.ring.push(cmd);
+        self.ring.ring(&ctrl.bar);
+        loop {
+            let interrupted = self.interrupted();
+            let Some(entry) = self.ring.wait(&ctrl.bar, interrupted) else {
+                log!("NVMe: deallocate of {count} sectors at {lba}: {}", Unanswered::Silent);
+                ctrl.abandon(self.index);
+                return Err(BlockError);
+            };
+            if !Untrusted::new(entry.cid).is(cid) {
+                log!("NVMe: queue {}: a completion for command {:#x}, which is not in flight",
+                    self.index, entry.cid);
+                continue;
+            }
+            let status = entry.status >> 1;
+            if status != 0 {
+                log!("NVMe: deallocate of {count} sectors at {lba} failed, status={status:#x}");
+                return Err(BlockError);
+            }
+            return Ok(());
+        }
+    }
+}
 
-        let dma = self.dma;
-        let pages = total_bytes.div_ceil(4096);
-        let data_phys = dma.phys() + OFF_DATA as u64;
+struct NvmeController {
+    bar: Mmio,
+    queues: Vec<Lock<IoQueue>>,
+    sector_size: u32,
+    ns_size: u64,
+    /// Whether Identify Controller's ONCS says Dataset Management is there.
+    /// Without it [`BlockDevice::discard`] tells this controller nothing.
+    deallocate: bool,
+    /// Whether a command has been abandoned on this controller. Once it has,
+    /// the queues and the DMA window are the device's and this driver issues
+    /// nothing more on any of them — see [`COMMAND`].
+    failed: AtomicBool,
+    /// The admin queue, kept for [`resume`]: S3 takes every queue the
+    /// controller had, and this is the one that recreates the others.
+    admin: Lock<Admin>,
+    /// How many of the I/O queues have an MSI-X entry of their own.
+    routed: usize,
+    /// `CAP.TO`, in nanoseconds: how long the controller may take to become
+    /// ready, or to stop being.
+    ready_ns: u64,
+}
 
-        // Bounded by `copy_from`, which refuses `OFF_DATA + total_bytes` past
-        // the pool; `total_bytes <= MAX_DATA_PAGES * 4096` and
-        // `buf.len() >= total_bytes` were both asserted on entry. Exclusive: the
-        // write command naming this window has not been submitted yet.
-        dma.copy_from(OFF_DATA, &buf[..total_bytes]);
+impl NvmeController {
+    /// The pair this CPU submits to. CPUs past the count the controller
+    /// granted share, round-robin.
+    fn queue(&self) -> &Lock<IoQueue> {
+        &self.queues[percpu::cpu_id() as usize % self.queues.len()]
+    }
 
-        let cid = self.alloc_cid();
-        let mut cmd = SqEntry::ZERO;
-        cmd.cdw0 = (cid as u32) << 16 | IO_WRITE as u32;
-        cmd.nsid = 1;
-        cmd.prp1 = data_phys;
-        cmd.cdw10 = lba as u32;
-        cmd.cdw11 = (lba >> 32) as u32;
-        cmd.cdw12 = sector_count - 1;
-
-        if pages == 2 {
-            cmd.prp2 = data_phys + 0x1000;
-        } else if pages > 2 {
-            cmd.prp2 = fill_prp_list(dma.unaligned(), pages, data_phys);
+    /// A command nobody answered ends this controller, once and loudly.
+    ///
+    /// Once, because the line is about the abandonment and not about the caller
+    /// that noticed: the page cache retries, and a line per refused operation
+    /// would bury the one that says what happened. Every later refusal is
+    /// silent by design and carries the caller's own log line above it.
+    fn abandon(&self, queue: usize) {
+        if self.failed.swap(true, Ordering::Relaxed) {
+            return;
         }
+        // Queue `q`'s interrupts are routed to cpu `q`, when they are routed.
+        let interrupts = INTERRUPTS.get(queue).map_or(0, |n| n.load(Ordering::Relaxed));
+        log!("NVMe: this controller is offline: a command on queue {queue} went unanswered, and \
+             it still owns its PRP list and is still owed a completion entry, and this driver \
+             has no reset to take either back (cpu{queue} has taken {interrupts} NVMe interrupts)");
+    }
 
-        let status = match self.io_command(cmd) {
-            Ok(status) => status,
-            Err(why) => {
-                log!("NVMe: write of {sector_count} sectors at {lba}: {why}");
-                return Err(BlockError);
-            }
-        };
-        if status != 0 {
-            log!("NVMe: write of {sector_count} sectors at {lba} failed, status={status:#x}");
-            return Err(BlockError);
+    /// Whether this command may be issued at all: the controller still has its
+    /// queues, and the caller's budget has something left in it.
+    ///
+    /// **Read between commands and never inside one**, which is the whole of
+    /// why a refusal here is free. Nothing has been submitted for it, no
+    /// completion is owed and its data region is nobody's, so this is a
+    /// decision about the *caller's* time and never a verdict about the disk:
+    /// the controller is left exactly as the previous command left it, and the
+    /// next caller finds it that way. [`crate::block::OPERATION`] carries the
+    /// rest of the argument, and `XhciController::scsi` is the same decision on
+    /// the USB path.
+    ///
+    /// An offline controller refuses silently: the line that says what happened
+    /// was written once by [`Self::abandon`], and one per refused command after
+    /// it would bury that line under the page cache's retries.
+    fn may_issue(&self, until: Deadline, op: &str, lba: u64, sector_count: u32) -> bool {
+        if self.failed.load(Ordering::Relaxed) {
+            return false;
         }
-        Ok(())
+        if until.reached(crate::clock::now()) {
+            log!("NVMe: {op} of {sector_count} sectors at {lba} not issued: {}", block::OPERATION);
+            return false;
+        }
+        true
+    }
+
+    fn transfer(&self, lba: u64, io: Transfer<'_>) -> BlockResult {
+        let until = Operation::deadline();
+        self.queue().lock().transfer(self, lba, io, until)
     }
 }
 
 /// NVMe block device exposing 4KB block I/O through the BlockDevice trait.
 ///
-/// **`Send` is derived, not asserted.** `block::BlockDevice` requires it, and
-/// the `unsafe impl` that stood here existed because `NvmeController` held
-/// `*mut SqEntry`/`*mut CqEntry` into the DMA pool. They are [`Dma`] views now —
-/// which carry the length as well as the address, and have the typed volatile
-/// accessor the queues need — so every field is `Send` on its own and the auto
-/// trait applies.
+/// **A handle, not the controller.** The controller is leaked at [`init`] and
+/// lives the boot; this names it, so [`BlockDevice::share`] can hand the page
+/// cache one handle per CPU and each drives the queue of the CPU it runs on.
+/// `Send` is derived: the controller is reached through a shared reference, and
+/// every field of it is `Sync` on its own — the queues behind their locks, the
+/// verdict an atomic.
 pub struct NvmeBlockDevice {
-    ctrl: NvmeController,
+    ctrl: &'static NvmeController,
     id: DeviceId,
     sectors_per_block: u32,
     block_count: u64,
 }
 
 impl NvmeBlockDevice {
-    fn new(ctrl: NvmeController, id: DeviceId) -> Self {
+    fn new(ctrl: &'static NvmeController, id: DeviceId) -> Self {
         let sectors_per_block = 4096 / ctrl.sector_size;
         let block_count = ctrl.ns_size / sectors_per_block as u64;
         log!("NVMe: block device id={} blocks={} ({}MB)",
@@ -670,83 +977,77 @@ impl BlockDevice for NvmeBlockDevice {
     fn block_count(&self) -> u64 { self.block_count }
 
     /// The guard is a `let _op` and not a `let _`: `let _` drops at the end of
-    /// its statement, which would end the operation before the loop it bounds.
-    /// [`Operation::deadline`] is read *after* the establishment because an
-    /// inner establishment may only narrow — a caller that arrived with less
-    /// than two seconds left keeps its own deadline, and that is the value the
-    /// batching loop below spends.
+    /// its statement, which would end the operation before the transfer it
+    /// bounds. [`Operation::deadline`] is read *after* the establishment
+    /// because an inner establishment may only narrow — a caller that arrived
+    /// with less than two seconds left keeps its own deadline, and that is the
+    /// value the transfer spends.
     fn read_blocks(&mut self, lba: u64, count: u32, buf: &mut [u8]) -> BlockResult {
         assert_eq!(buf.len(), count as usize * 4096);
         let _op = block::begin_operation();
-        let until = Operation::deadline();
-        let mut remaining = count;
-        let mut block = lba;
-        let mut offset = 0usize;
-
-        while remaining > 0 {
-            let batch = remaining.min(MAX_DATA_PAGES as u32);
-            let sector_lba = block * self.sectors_per_block as u64;
-            let sector_count = batch * self.sectors_per_block;
-            let bytes = batch as usize * 4096;
-
-            self.ctrl
-                .read_sectors(sector_lba, sector_count, &mut buf[offset..offset + bytes], until)?;
-
-            block += batch as u64;
-            offset += bytes;
-            remaining -= batch;
-        }
-        Ok(())
+        self.ctrl.transfer(lba * self.sectors_per_block as u64, Transfer::Read(buf))
     }
 
     fn write_blocks(&mut self, lba: u64, count: u32, buf: &[u8]) -> BlockResult {
         assert_eq!(buf.len(), count as usize * 4096);
         let _op = block::begin_operation();
-        let until = Operation::deadline();
-        let mut remaining = count;
-        let mut block = lba;
-        let mut offset = 0usize;
-
-        while remaining > 0 {
-            let batch = remaining.min(MAX_DATA_PAGES as u32);
-            let sector_lba = block * self.sectors_per_block as u64;
-            let sector_count = batch * self.sectors_per_block;
-            let bytes = batch as usize * 4096;
-
-            self.ctrl
-                .write_sectors(sector_lba, sector_count, &buf[offset..offset + bytes], until)?;
-
-            block += batch as u64;
-            offset += bytes;
-            remaining -= batch;
-        }
-        Ok(())
+        self.ctrl.transfer(lba * self.sectors_per_block as u64, Transfer::Write(buf))
     }
 
-    /// NVMe writes are synchronous (`submit_and_wait`), so data is on disk
-    /// after `write_blocks` returns. Nothing to flush — and so nothing to
-    /// bound, which is why this is the one trait method here that establishes
-    /// no operation: it issues no command and cannot spend a caller's time.
+    /// NVMe writes are synchronous — `transfer` returns only once every
+    /// command it issued has completed — so data is on disk after
+    /// `write_blocks` returns. Nothing to flush, and so nothing to bound, which
+    /// is why this is the one trait method here that establishes no operation:
+    /// it issues no command and cannot spend a caller's time.
     ///
     /// A controller that has been abandoned is the exception, and it is not a
     /// flush that failed. The writes this would have made durable are the ones
     /// that never completed, and answering `Ok` would tell `page_cache::sync`
     /// they had.
     fn flush(&mut self) -> BlockResult {
-        if self.ctrl.failed {
+        if self.ctrl.failed.load(Ordering::Relaxed) {
             return Err(BlockError);
         }
         Ok(())
     }
+
+    /// Dataset Management with the deallocate attribute, on the queue of the
+    /// CPU the caller runs on. A controller whose ONCS does not list the
+    /// command answers `Ok` having been told nothing.
+    fn discard(&mut self, lba: u64, count: u32) -> BlockResult {
+        if !self.ctrl.deallocate || count == 0 {
+            return Ok(());
+        }
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let per = self.sectors_per_block as u64;
+        self.ctrl.queue().lock().deallocate(self.ctrl, lba * per, count as u64 * per, until)
+    }
+
+    fn share(&self) -> Option<Box<dyn BlockDevice>> {
+        Some(Box::new(Self {
+            ctrl: self.ctrl,
+            id: self.id,
+            sectors_per_block: self.sectors_per_block,
+            block_count: self.block_count,
+        }))
+    }
 }
 
-/// Bring up the machine's NVMe controller.
+/// Bring up the machine's NVMe controller, with one I/O queue pair for each
+/// CPU in `cpus` — LAPIC ids in cpu-id order, from
+/// [`crate::arch::smp::planned_apic_ids`], because this runs before the APs
+/// are started.
 ///
-/// The first one, and a machine with two loses the second: unlike xHCI, where
-/// the second controller is where a Tiger Lake laptop's keyboard actually is,
-/// nothing above here can hold more than one disk yet — `page_cache::init`
-/// takes a single `BlockDevice`. Filed rather than papered over.
-pub fn init(devices: &[PciDevice]) -> Option<NvmeBlockDevice> {
+/// The first controller, and a machine with two loses the second: unlike xHCI,
+/// where the second controller is where a Tiger Lake laptop's keyboard actually
+/// is, nothing here binds it, so nothing reads it — through the page cache or
+/// around it. Every disk that *is* read is registered with the cache: this
+/// one for `/home`, and a USB, virtio or AHCI disk by the first mount that
+/// asks `fat32_adapter::device_carrying` for it. A second NVMe disk would join
+/// them the same way once it has a driver instance and a [`DeviceId`] of its
+/// own; today NVMe owns exactly one id.
+pub fn init(devices: &[PciDevice], cpus: &[u32]) -> Option<NvmeBlockDevice> {
     let pci_dev = *devices.iter().find(|d| d.matches_class(0x01, 0x08, None))?;
     log!("NVMe: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
 
@@ -770,6 +1071,12 @@ pub fn init(devices: &[PciDevice]) -> Option<NvmeBlockDevice> {
 
     let cap = bar.read_u64(REG_CAP);
     let stride = ((cap >> 32) & 0xF) as u32;
+    // CAP.MQES is 0-based, and a controller reporting zero — a one-entry queue,
+    // which the specification forbids — is given the floor rather than a ring
+    // that is full when empty.
+    let mqes = (cap & 0xFFFF) as u32 + 1;
+    // Exact: at most `MAX_DEPTH`.
+    let depth = mqes.clamp(2, MAX_DEPTH) as u16;
 
     let cc = bar.read_u32(REG_CC);
     if cc & 1 != 0 {
@@ -783,19 +1090,18 @@ pub fn init(devices: &[PciDevice]) -> Option<NvmeBlockDevice> {
     // root filesystem for the life of the boot, and the `Lock<Option<DmaPool>>`
     // that used to hold the pages alive was never cleared either. It is
     // allocated here, after every refusal above, so a machine whose NVMe
-    // function this driver declines still costs no physical memory.
-    let dma = DmaPool::alloc(DMA_SIZE).leak();
-    const SQ_PAGE: usize = QUEUE_DEPTH * core::mem::size_of::<SqEntry>();
-    const CQ_PAGE: usize = QUEUE_DEPTH * core::mem::size_of::<CqEntry>();
-    let admin_sq = dma.subview(OFF_ADMIN_SQ, SQ_PAGE);
-    let admin_cq = dma.subview(OFF_ADMIN_CQ, CQ_PAGE);
-    let io_sq = dma.subview(OFF_IO_SQ, SQ_PAGE);
-    let io_cq = dma.subview(OFF_IO_CQ, CQ_PAGE);
+    // function this driver declines still costs no physical memory — and sized
+    // for every CPU asking, since how many the controller grants is an admin
+    // command away and that needs the admin queue in this window.
+    let wanted = cpus.len().clamp(1, MAX_CPUS);
+    let dma = DmaPool::alloc(OFF_QUEUES + wanted * QUEUE_WINDOW).leak();
+    let sq_bytes = depth as usize * core::mem::size_of::<SqEntry>();
+    let cq_bytes = depth as usize * core::mem::size_of::<CqEntry>();
 
     dma.subview(OFF_ADMIN_SQ, 4096).zero();
     dma.subview(OFF_ADMIN_CQ, 4096).zero();
 
-    let aqa = ((QUEUE_DEPTH as u32 - 1) << 16) | (QUEUE_DEPTH as u32 - 1);
+    let aqa = ((depth as u32 - 1) << 16) | (depth as u32 - 1);
     bar.write_u32(REG_AQA, aqa);
     bar.write_u64(REG_ASQ, dma.phys() + OFF_ADMIN_SQ as u64);
     bar.write_u64(REG_ACQ, dma.phys() + OFF_ADMIN_CQ as u64);
@@ -808,29 +1114,174 @@ pub fn init(devices: &[PciDevice]) -> Option<NvmeBlockDevice> {
     }
     log!("NVMe: controller enabled");
 
-    let mut ctrl = NvmeController {
+    let mut admin = Admin {
         bar,
         dma,
-        admin: NvmeQueue::new(admin_sq, admin_cq, 0, stride),
-        io: NvmeQueue::new(io_sq, io_cq, 1, stride),
+        ring: Ring::new(
+            dma.subview(OFF_ADMIN_SQ, sq_bytes),
+            dma.subview(OFF_ADMIN_CQ, cq_bytes),
+            depth, 0, stride,
+        ),
         next_cid: 0,
-        sector_size: 512,
-        ns_size: 0,
-        failed: false,
     };
 
     // A controller that refuses any of these has not given the driver a
     // namespace to serve. Going on regardless is what discarding the statuses
     // amounted to: `identify_namespace` would read a zeroed DMA buffer and
     // derive its geometry from it.
-    if !ctrl.identify_controller()
-        || !ctrl.create_io_cq()
-        || !ctrl.create_io_sq()
-        || !ctrl.identify_namespace()
-    {
+    let Some(oncs) = admin.identify_controller() else {
         log!("NVMe: controller did not come up; this machine has no NVMe storage");
         return None;
+    };
+    let deallocate = oncs & ONCS_DSM != 0;
+    // Exact: `wanted` is at most `MAX_CPUS`.
+    let count = admin.number_of_queues(wanted as u16) as usize;
+
+    // Entry 0 is the admin queue's, which always raises it (§3.1.3.5 gives the
+    // admin completion queue vector 0), and goes to the boot CPU, which is the
+    // only one that issues admin commands. Entry `q + 1` is queue `q`'s and
+    // goes to cpu `q`. A table shorter than that leaves the rest polled.
+    let targets: Vec<u32> = cpus.first().into_iter().chain(cpus.iter().take(count)).copied().collect();
+    let routed = match pci_dev.enable_msix_routed(crate::arch::idt::NVME_VECTOR, &targets) {
+        Some(armed) => (armed as usize).saturating_sub(1),
+        None => 0,
+    };
+
+    let mut queues = Vec::with_capacity(count);
+    for index in 0..count {
+        let base = OFF_QUEUES + index * QUEUE_WINDOW;
+        let window = dma.subview(base, QUEUE_WINDOW);
+        window.subview(Q_SQ, 4096).zero();
+        window.subview(Q_CQ, cq_bytes).zero();
+        // Exact: `index < count <= MAX_CPUS`.
+        let qid = index as u16 + 1;
+        let vector = (index < routed).then_some(qid);
+        let (sq, cq) = (window.phys() + Q_SQ as u64, window.phys() + Q_CQ as u64);
+        if !admin.create_pair(qid, depth, sq, cq, vector) {
+            log!("NVMe: controller did not come up; this machine has no NVMe storage");
+            return None;
+        }
+        queues.push(Lock::new(IoQueue {
+            index,
+            ring: Ring::new(window.subview(Q_SQ, sq_bytes), window.subview(Q_CQ, cq_bytes),
+                depth, qid, stride),
+            window,
+            slots: IN_FLIGHT.min(depth as usize - 1),
+            routed: vector.is_some(),
+            next_cid: 0,
+        }));
     }
+    log!("NVMe: {count} I/O queue pairs for {} CPUs, {depth} deep (CAP.MQES allows {mqes}), \
+         {} with an interrupt routed to its CPU{}", cpus.len(), routed.min(count),
+         if deallocate { ", deallocate" } else { "" });
+
+    let Some((sector_size, ns_size)) = admin.identify_namespace() else {
+        log!("NVMe: controller did not come up; this machine has no NVMe storage");
+        return None;
+    };
 
+    let ctrl: &'static NvmeController = Box::leak(Box::new(NvmeController {
+        bar,
+        queues,
+        sector_size,
+        ns_size,
+        deallocate,
+        failed: AtomicBool::new(false),
+        admin: Lock::new(admin),
+        routed,
+        // CAP.TO is in 500 ms units, and zero would be a controller that is
+        // never given a moment; one unit is the floor.
+        ready_ns: ((cap >> 24) & 0xFF).max(1) * 500_000_000,
+    }));
+    *CONTROLLER.lock() = Some(ctrl);
     Some(NvmeBlockDevice::new(ctrl, 1))
 }
+
+/// The controller [`init`] brought up, for [`suspend`] and [`resume`].
+static CONTROLLER: Lock<Option<&'static NvmeController>> = Lock::new(None);
+
+/// Tell the controller the power is going, then disable it.
+///
+/// The shutdown notification is what lets it put its own caches and metadata
+/// on the media (NVMe 2.0 §3.6.2); the disable is what [`resume`] starts from
+/// whether or not the sleep happens, since `CC.EN` going 0 to 1 is the only
+/// bring-up this driver knows.
+pub fn suspend() {
+    let Some(ctrl) = *CONTROLLER.lock() else { return };
+    let bar = ctrl.bar;
+    let cc = bar.read_u32(REG_CC);
+    bar.write_u32(REG_CC, (cc & !CC_SHN_MASK) | CC_SHN_NORMAL);
+    let shut = crate::clock::settles(ctrl.ready_ns, || {
+        bar.read_u32(REG_CSTS) & CSTS_SHST_MASK == CSTS_SHST_COMPLETE
+    });
+    if !shut {
+        log!("NVMe: shutdown not reported complete within CAP.TO; disabling anyway");
+    }
+    bar.write_u32(REG_CC, cc & !(CC_SHN_MASK | 1));
+    if !crate::clock::settles(ctrl.ready_ns, || bar.read_u32(REG_CSTS) & 1 == 0) {
+        log!("NVMe: still ready {} ms after being disabled", ctrl.ready_ns / 1_000_000);
+    }
+}
+
+/// Enable the controller again and recreate every I/O queue pair it had, in
+/// the same DMA window and with the same interrupts, so the handles the page
+/// cache holds go on naming the same queues.
+///
+/// A controller that does not come back is offline the way an abandoned one
+/// is: every later transfer is refused, and the volume on it answers with I/O
+/// errors rather than with a hang.
+pub fn resume() {
+    let Some(ctrl) = *CONTROLLER.lock() else { return };
+    if !restart(ctrl) {
+        ctrl.failed.store(true, Ordering::Relaxed);
+        log!("NVMe: this controller is offline: it did not come back from the sleep");
+        return;
+    }
+    log!("NVMe: {} I/O queue pairs back after the sleep", ctrl.queues.len());
+}
+
+fn restart(ctrl: &NvmeController) -> bool {
+    let bar = ctrl.bar;
+    let mut admin = ctrl.admin.lock();
+    let depth = admin.ring.depth;
+    let cc = bar.read_u32(REG_CC);
+    if cc & 1 != 0 {
+        bar.write_u32(REG_CC, cc & !1);
+        if !crate::clock::settles(ctrl.ready_ns, || bar.read_u32(REG_CSTS) & 1 == 0) {
+            log!("NVMe: would not disable after the sleep");
+            return false;
+        }
+    }
+    admin.zero_dma(OFF_ADMIN_SQ, 4096);
+    admin.zero_dma(OFF_ADMIN_CQ, 4096);
+    admin.ring.restart();
+    let aqa = ((depth as u32 - 1) << 16) | (depth as u32 - 1);
+    bar.write_u32(REG_AQA, aqa);
+    bar.write_u64(REG_ASQ, admin.dma.phys() + OFF_ADMIN_SQ as u64);
+    bar.write_u64(REG_ACQ, admin.dma.phys() + OFF_ADMIN_CQ as u64);
+    bar.write_u32(REG_CC, 1 | (6 << 16) | (4 << 20));
+    if !crate::clock::settles(ctrl.ready_ns, || bar.read_u32(REG_CSTS) & 1 != 0) {
+        log!("NVMe: not ready {} ms after being enabled again", ctrl.ready_ns / 1_000_000);
+        return false;
+    }
+
+    // Exact: there are at most `MAX_CPUS` queues.
+    let count = ctrl.queues.len() as u16;
+    if admin.number_of_queues(count) < count {
+        log!("NVMe: granted fewer than the {count} queue pairs it had before the sleep");
+        return false;
+    }
+    for queue in &ctrl.queues {
+        let mut queue = queue.lock();
+        queue.window.subview(Q_SQ, 4096).zero();
+        queue.window.subview(Q_CQ, depth as usize * core::mem::size_of::<CqEntry>()).zero();
+        queue.ring.restart();
+        let qid = queue.index as u16 + 1;
+        let vector = (queue.index < ctrl.routed).then_some(qid);
+        let (sq, cq) = (queue.window.phys() + Q_SQ as u64, queue.window.phys() + Q_CQ as u64);
+        if !admin.create_pair(qid, depth, sq, cq, vector) {
+            return false;
+        }
+    }
+    true
+}
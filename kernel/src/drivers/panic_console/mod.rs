@@ -459,7 +459,8 @@ pub fn screen_claimed_by_userland() {
 /// The descriptor [`arm`] validated, held so [`remap`] and [`rearm`] can
 /// re-publish it without re-deriving anything. A null pointer here means
 /// there is no usable GOP framebuffer at all, and no later call can invent
-/// one. Written only during single-threaded boot.
+/// one. Written only during single-threaded boot, and by [`follow`] inside
+/// `set_resolution`'s window.
 static PENDING: FbCell = FbCell(UnsafeCell::new(Fb::DETACHED));
 static RAW_PHYS: AtomicU64 = AtomicU64::new(0);
 static RAW_SIZE: AtomicU64 = AtomicU64::new(0);
@@ -497,10 +498,10 @@ pub fn detach() {
 pub fn rearm() {
     // SAFETY: irreducible — `PENDING` is an `UnsafeCell` for the reason
     // `FbCell` states. Sound because `PENDING` is written only during
-    // single-threaded boot (`arm`) and by `disable`, and `rearm` is called from
-    // the same single-threaded sequence and from `set_resolution`'s window,
-    // which is the one place two of these can be in flight — and that window is
-    // itself entered by one CPU. `Fb` is `Copy`, so this takes a copy and holds
+    // single-threaded boot (`arm`), by `disable` and by `follow`, and `rearm`
+    // is called from the same single-threaded sequence and from
+    // `set_resolution`'s window, which is the one place two of these can be in
+    // flight — and that window is itself entered by one CPU. `Fb` is `Copy`, so this takes a copy and holds
     // no reference into the cell.
     let fb = unsafe { *PENDING.0.get() };
     if validate(&fb) {
@@ -521,6 +522,36 @@ pub fn disable() {
     detach();
 }
 
+/// Carry on drawing into the framebuffer [`arm`] found after a driver has
+/// changed its mode in place. For a device whose scanout is the firmware one
+/// at a new geometry — the Bochs display — so the console need not go dark
+/// the way it must for a scanout that was reallocated.
+///
+/// `phys` must be the framebuffer the console was armed on, and `bytes` what
+/// the driver has mapped of it; anything else is a different framebuffer, and
+/// the console stays as it is. Called where [`rearm`] would be: during
+/// single-threaded boot, or inside `set_resolution`'s window after a
+/// successful change.
+pub fn follow(phys: u64, bytes: u64, width: u32, height: u32, stride_px: u32) {
+    if phys == 0 || phys != RAW_PHYS.load(Ordering::Relaxed) {
+        return;
+    }
+    // SAFETY: irreducible and sound for the same reasons as `rearm`'s read:
+    // `PENDING` has one writer at a time, and this runs from the boot sequence
+    // or from `set_resolution`'s window, which one CPU enters.
+    let armed = unsafe { *PENDING.0.get() };
+    let fb = Fb { bytes, stride_px, width, height, ..armed };
+    if !validate(&fb) {
+        log!("panic console: detached, cannot follow to {}x{} stride={}", width, height, stride_px);
+        disable();
+        return;
+    }
+    // SAFETY: as above.
+    unsafe { *PENDING.0.get() = fb };
+    RAW_SIZE.store(bytes, Ordering::Relaxed);
+    rearm();
+}
+
 /// Torn means unavailable, never a wild pointer -- and that has to hold on
 /// the ordering, not on the values. Today only two descriptors are ever
 /// published, one `validate`d and one all-zero, so every torn mixture is
@@ -2,9 +2,11 @@ use alloc::vec::Vec;
 
 use toyos_pci::{bar, msi, msix};
 
+use super::{aml, ioapic};
 use crate::mm::Mmio;
 use crate::mm::paging::CachePolicy;
 use crate::log;
+use crate::sync::Lock;
 
 const VENDOR_ID: u64 = 0x00;
 const DEVICE_ID: u64 = 0x02;
@@ -14,6 +16,17 @@ const SUBCLASS: u64 = 0x0A;
 const CLASS: u64 = 0x0B;
 const HEADER_TYPE: u64 = 0x0E;
 const CAPABILITIES_PTR: u64 = 0x34;
+const INTERRUPT_LINE: u64 = 0x3C;
+const INTERRUPT_PIN: u64 = 0x3D;
+
+/// Command register bit 10: the function may not assert its INTx pin. Set by
+/// the device itself whenever MSI or MSI-X is enabled, and by some firmware on
+/// every function it hands over.
+const COMMAND_INTX_DISABLE: u16 = 1 << 10;
+
+/// What an Interrupt Line register holds for "unknown, or not connected"
+/// (PCI Local Bus 3.0 §6.2.4).
+const LINE_UNKNOWN: u8 = 0xFF;
 
 const MULTI_FUNCTION: u8 = 0x80;
 const INVALID_VENDOR: u16 = 0xFFFF;
@@ -27,12 +40,20 @@ pub const MSIX_ENTRY: u16 = 0;
 
 /// The address a message-signalled interrupt is DMA'd to, in either form:
 /// the LAPIC window, physical destination 0, fixed delivery, edge. Every
-/// device in this kernel already targets it, so every device interrupt lands
-/// on the boot CPU and is spread from there by `irq_ring` plus the scheduler
-/// rather than by the interrupt controller. Written once because MSI and
+/// device in this kernel but NVMe's per-CPU queues targets it, so a device
+/// interrupt lands on the boot CPU and is spread from there by `irq_ring` plus
+/// the scheduler rather than by the interrupt controller. Written once because MSI and
 /// MSI-X differ in where the address is configured and not in what it is.
 const MSG_ADDR: u32 = 0xFEE0_0000;
 
+/// Write one MSI-X table entry, mapped at `entry`, and unmask it.
+fn program_msix_entry(entry: Mmio, address: u32, vector: u8) {
+    entry.write_u32(msix::ENTRY_ADDRESS_LO, address);
+    entry.write_u32(msix::ENTRY_ADDRESS_HI, 0);
+    entry.write_u32(msix::ENTRY_DATA, vector as u32);
+    entry.write_u32(msix::ENTRY_VECTOR_CONTROL, msix::ENTRY_UNMASKED);
+}
+
 pub struct Capability<'a> {
     device: &'a PciDevice,
     offset: u64,
@@ -143,16 +164,57 @@ impl PciDevice {
     /// decision and differs: an xHC falls back to [`Self::enable_msi`], a
     /// virtio device has nothing to fall back to and refuses itself.
     pub fn enable_msix(&self, vector: u8) -> bool {
-        let Some(cap) = self.capabilities().find(|c| c.id() == msix::CAP_ID) else {
+        let Some((cap, control, address, _)) = self.msix_table() else {
             return false;
         };
+        let entry = address + MSIX_ENTRY as u64 * msix::ENTRY_BYTES;
+        let table = crate::mm::paging::map_mmio(entry, 0x1000, CachePolicy::DeferToMtrr);
+        program_msix_entry(table, MSG_ADDR, vector);
+        cap.write_u16(msix::MESSAGE_CONTROL, msix::Msix::enabled(control));
+        true
+    }
+
+    /// Point entry `i` of this function's MSI-X table at `vector` on the CPU
+    /// whose LAPIC id is `targets[i]`, for as many entries as the table has,
+    /// and enable it. Answers how many were armed, from entry 0 up; `None` is
+    /// [`Self::enable_msix`]'s `false`.
+    ///
+    /// **For a device with one queue per CPU**, whose completions are the
+    /// business of the CPU that submitted them: delivering them all to the
+    /// boot CPU, which is what `MSG_ADDR` does, would make one CPU take every
+    /// other CPU's interrupts. A target the address format cannot name ends
+    /// the run there, and the caller is told by the count rather than by an
+    /// entry quietly delivered to the wrong CPU.
+    pub fn enable_msix_routed(&self, vector: u8, targets: &[u32]) -> Option<u16> {
+        let (cap, control, address, entries) = self.msix_table()?;
+        let mut armed = 0u16;
+        for &apic_id in targets.iter().take(entries as usize) {
+            let Some(message) = msix::message_address(apic_id) else {
+                log!("PCI {:02x}:{:02x}.{}: MSI-X entry {armed} not armed, LAPIC {apic_id} is \
+                     past what a message address can name", self.bus, self.dev, self.func);
+                break;
+            };
+            let entry = address + armed as u64 * msix::ENTRY_BYTES;
+            let table = crate::mm::paging::map_mmio(entry, 0x1000, CachePolicy::DeferToMtrr);
+            program_msix_entry(table, message, vector);
+            armed += 1;
+        }
+        cap.write_u16(msix::MESSAGE_CONTROL, msix::Msix::enabled(control));
+        Some(armed)
+    }
+
+    /// This function's MSI-X capability, its Message Control as read, where
+    /// its table is and how many entries it has — or `None`, logged, when the
+    /// table is one this kernel declines to believe.
+    fn msix_table(&self) -> Option<(Capability<'_>, u16, u64, u16)> {
+        let cap = self.capabilities().find(|c| c.id() == msix::CAP_ID)?;
         let control = cap.read_u16(msix::MESSAGE_CONTROL);
         let table = match msix::Msix::decode(control, cap.read_u32(msix::TABLE)) {
             Ok(table) => table,
             Err(why) => {
                 log!("PCI {:02x}:{:02x}.{}: MSI-X not armed, {}",
                     self.bus, self.dev, self.func, why);
-                return false;
+                return None;
             }
         };
         // The BAR the capability named, decoded rather than assumed to be
@@ -165,7 +227,7 @@ impl PciDevice {
             Err(why) => {
                 log!("PCI {:02x}:{:02x}.{}: MSI-X not armed, its table names BAR {} and {}",
                     self.bus, self.dev, self.func, table.bir(), why);
-                return false;
+                return None;
             }
         };
         let address = match table.table_address(base) {
@@ -173,20 +235,10 @@ impl PciDevice {
             Err(why) => {
                 log!("PCI {:02x}:{:02x}.{}: MSI-X not armed, {}",
                     self.bus, self.dev, self.func, why);
-                return false;
+                return None;
             }
         };
-
-        let entry = address + MSIX_ENTRY as u64 * msix::ENTRY_BYTES;
-        let table = crate::mm::paging::map_mmio(entry, 0x1000, CachePolicy::DeferToMtrr);
-
-        table.write_u32(msix::ENTRY_ADDRESS_LO, MSG_ADDR);
-        table.write_u32(msix::ENTRY_ADDRESS_HI, 0);
-        table.write_u32(msix::ENTRY_DATA, vector as u32);
-        table.write_u32(msix::ENTRY_VECTOR_CONTROL, msix::ENTRY_UNMASKED);
-
-        cap.write_u16(msix::MESSAGE_CONTROL, msix::Msix::enabled(control));
-        true
+        Some((cap, control, address, table.entries()))
     }
 
     /// Point this function's single MSI message at `vector` and enable it.
@@ -215,6 +267,65 @@ impl PciDevice {
         true
     }
 
+    /// Route this function's INTx pin to `vector` on this CPU and let the
+    /// function assert it.
+    ///
+    /// The fallback after [`Self::enable_msix`] and [`Self::enable_msi`], for a
+    /// function that offers neither. Where the pin lands is firmware's to say,
+    /// and it says it twice: `_PRT`, which `drivers::aml` resolved per root bus
+    /// at boot — link devices included — and the MADT, through the ISA number
+    /// BIOS left in the Interrupt Line register. `_PRT` is asked first because
+    /// it is the one written for an I/O APIC; the register is what an 8259
+    /// machine was told, and is right on one only where an override or the
+    /// identity mapping happens to agree.
+    ///
+    /// The line is level-triggered on every PCI machine, so the handler behind
+    /// `vector` must clear the function's interrupt cause before its EOI, or
+    /// the pin is still asserted when the I/O APIC looks again. And a line can
+    /// be shared: a function this kernel does not drive that asserts the same
+    /// pin is one no handler here will ever clear. Every other function this
+    /// kernel binds is message-signalled and keeps its pin quiet.
+    pub fn enable_intx(&self, vector: u8) -> bool {
+        let pin = self.read_config_u8(INTERRUPT_PIN);
+        let at = || alloc::format!("PCI {:02x}:{:02x}.{}", self.bus, self.dev, self.func);
+        if !(1..=4).contains(&pin) {
+            log!("{}: INTx not armed, Interrupt Pin reads {pin}", at());
+            return false;
+        }
+        let name = char::from(b'A' + pin - 1);
+        let (line, source) = match aml::pci_route(self.bus, self.dev, pin - 1) {
+            Some(line) => (line, "_PRT"),
+            None => {
+                let irq = self.read_config_u8(INTERRUPT_LINE);
+                let Some(line) = (irq != LINE_UNKNOWN).then(|| ioapic::gsi_for_pci_irq(irq)).flatten()
+                else {
+                    log!("{}: INTx not armed, no _PRT route for INT{name} and Interrupt Line \
+                         reads {irq:#04x}", at());
+                    return false;
+                };
+                (line, "Interrupt Line")
+            }
+        };
+        let apic_id = crate::arch::apic::id();
+        if let Err(e) = ioapic::route(line.gsi, vector, apic_id, line.trigger, line.polarity) {
+            log!("{}: INT{name} not armed, GSI {}: {e:?}", at(), line.gsi.0);
+            return false;
+        }
+        let cmd = self.mmio.read_u16(COMMAND);
+        self.mmio.write_u16(COMMAND, cmd & !COMMAND_INTX_DISABLE);
+        if let Err(e) = ioapic::set_masked(line.gsi, false) {
+            log!("{}: INT{name} not armed, GSI {} would not unmask: {e:?}", at(), line.gsi.0);
+            return false;
+        }
+        log!(
+            "{}: INT{name} -> GSI {} {} vec {vector:#04x} apic {apic_id}, from {source}",
+            at(),
+            line.gsi.0,
+            ioapic::describe(line.trigger, line.polarity)
+        );
+        true
+    }
+
     pub fn capabilities(&self) -> CapabilityIter<'_> {
         let first = self.mmio.read_u8(CAPABILITIES_PTR);
         CapabilityIter { device: self, next: first }
@@ -294,9 +405,129 @@ pub fn enumerate(ecam: &crate::mm::Mmio) -> Vec<PciDevice> {
     }
 
     log!("PCI: Enumeration complete, {} functions.", found.len());
+    *FUNCTIONS.lock() = found.clone();
     found
 }
 
+/// What [`enumerate`] found, kept for [`suspend`]: config space is the one
+/// piece of every device's state that no driver owns, and a sleep loses it for
+/// all of them at once.
+static FUNCTIONS: Lock<Vec<PciDevice>> = Lock::new(Vec::new());
+static SAVED: Lock<Vec<Saved>> = Lock::new(Vec::new());
+
+/// The header dwords [`suspend`] keeps: everything after the IDs, which are
+/// read-only, up to the capability area. A half-open `u32` range because
+/// [`resume`] walks it backwards in step with the saved array, and only that
+/// shape of range knows its own length.
+const HEADER_DWORDS: core::ops::Range<u32> = 1..16;
+
+/// One function's config space as it was before the sleep.
+struct Saved {
+    device: PciDevice,
+    header: [u32; 15],
+    /// The MSI capability's offset, Message Control, and its address, data
+    /// and mask registers in [`MsiSaved`]'s order.
+    msi: Option<(u64, u16, MsiSaved)>,
+    /// The MSI-X capability's offset and Message Control, and every table
+    /// entry with the table's physical address — the table lives in a BAR, so
+    /// the reset that cleared the header cleared it too.
+    msix: Option<(u64, u16, u64, Vec<[u32; 4]>)>,
+}
+
+#[derive(Clone, Copy)]
+struct MsiSaved {
+    address_lo: u32,
+    address_hi: Option<u32>,
+    data: u16,
+    mask: Option<u32>,
+}
+
+/// Read every function's header, MSI and MSI-X state, ahead of S3. Every
+/// driver has already stopped its device; this is what lets [`resume`] give
+/// each one back its BARs and its interrupts before the driver touches it.
+pub fn suspend() {
+    let functions = FUNCTIONS.lock();
+    let mut saved = SAVED.lock();
+    saved.clear();
+    for &device in functions.iter() {
+        let mut header = [0u32; 15];
+        for (slot, dword) in header.iter_mut().zip(HEADER_DWORDS) {
+            *slot = device.read_config_u32(u64::from(dword) * 4);
+        }
+        let msi = device.capabilities().find(|c| c.id() == msi::CAP_ID).map(|cap| {
+            let control = cap.read_u16(msi::MESSAGE_CONTROL);
+            let layout = msi::Msi::decode(control);
+            let state = MsiSaved {
+                address_lo: cap.read_u32(layout.address_lo()),
+                address_hi: layout.address_hi().map(|at| cap.read_u32(at)),
+                data: cap.read_u16(layout.data()),
+                mask: layout.mask().map(|at| cap.read_u32(at)),
+            };
+            (cap.offset, control, state)
+        });
+        let msix = device.msix_table().map(|(cap, control, address, entries)| {
+            let bytes = u64::from(entries) * msix::ENTRY_BYTES;
+            let table = crate::mm::paging::map_mmio(address, bytes, CachePolicy::DeferToMtrr);
+            let entries = (0..u64::from(entries))
+                .map(|i| {
+                    let at = i * msix::ENTRY_BYTES;
+                    [msix::ENTRY_ADDRESS_LO, msix::ENTRY_ADDRESS_HI, msix::ENTRY_DATA,
+                        msix::ENTRY_VECTOR_CONTROL].map(|field| table.read_u32(at + field))
+                })
+                .collect();
+            (cap.offset, control, address, entries)
+        });
+        saved.push(Saved { device, header, msi, msix });
+    }
+}
+
+/// Write back what [`suspend`] read, for every function still there.
+///
+/// **The command register last among the header**, and the interrupt state
+/// after it: the BARs have to be in place before memory decoding is turned on,
+/// and an MSI-X table is in a BAR, so it cannot be written until decoding is.
+/// Enables go last of all, so no function signals through a half-written
+/// message.
+pub fn resume() {
+    for saved in SAVED.lock().iter() {
+        let device = saved.device;
+        if device.vendor_id() == INVALID_VENDOR {
+            log!("PCI {:02x}:{:02x}.{}: gone after the sleep", device.bus, device.dev, device.func);
+            continue;
+        }
+        for (&value, dword) in saved.header.iter().zip(HEADER_DWORDS).rev() {
+            device.write_config_u32(u64::from(dword) * 4, value);
+        }
+        if let Some((offset, control, state)) = saved.msi {
+            let cap = Capability { device: &device, offset };
+            let layout = msi::Msi::decode(control);
+            cap.write_u32(layout.address_lo(), state.address_lo);
+            if let (Some(at), Some(value)) = (layout.address_hi(), state.address_hi) {
+                cap.write_u32(at, value);
+            }
+            cap.write_u16(layout.data(), state.data);
+            if let (Some(at), Some(value)) = (layout.mask(), state.mask) {
+                cap.write_u32(at, value);
+            }
+            cap.write_u16(msi::MESSAGE_CONTROL, control);
+        }
+        if let Some((offset, control, address, entries)) = &saved.msix {
+            let cap = Capability { device: &device, offset: *offset };
+            let bytes = entries.len() as u64 * msix::ENTRY_BYTES;
+            let table = crate::mm::paging::map_mmio(*address, bytes, CachePolicy::DeferToMtrr);
+            for (i, entry) in (0..).zip(entries) {
+                let at = i * msix::ENTRY_BYTES;
+                let fields = [msix::ENTRY_ADDRESS_LO, msix::ENTRY_ADDRESS_HI, msix::ENTRY_DATA,
+                    msix::ENTRY_VECTOR_CONTROL];
+                for (field, &value) in fields.into_iter().zip(entry) {
+                    table.write_u32(at + field, value);
+                }
+            }
+            cap.write_u16(msix::MESSAGE_CONTROL, *control);
+        }
+    }
+}
+
 fn print_device(pci: &PciDevice) {
     log!(
         "  PCI {:02x}:{:02x}.{} [{:02x}{:02x}] vendor={:04x} device={:04x} prog_if={:02x}",
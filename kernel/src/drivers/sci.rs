@@ -0,0 +1,509 @@
+//! The ACPI System Control Interrupt: the power button, the sleep button, and
+//! the general-purpose events behind them.
+//!
+//! Until this module the SCI was masked at boot and never routed, so pressing
+//! the power button — or `system_powerdown` in QEMU's monitor — did nothing.
+//! Now a fixed-feature press becomes a [`crate::power`] event for whoever holds
+//! the `power` claim, and the machine turns off only when that holder asks.
+//!
+//! **The line is level-triggered, so the ISR acknowledges everything it finds
+//! before the EOI.** A level SCI stays asserted for as long as any enabled
+//! status bit is set, and an ISR that left one for thread context would take
+//! the interrupt again the instant it returned, forever. So the handler reads
+//! the PM1 status, writes back the bits that fired, and for each pending GPE
+//! turns its enable off, recording what it did in atomics; [`service`] turns
+//! the record into events at the next scheduler pass on the same CPU. Port I/O
+//! is all the ISR does — no lock, no allocation.
+//!
+//! **GPEs are enabled only where `drivers::aml` has something to run for
+//! them**: a `\_GPE._Lxx` or `_Exx` method, or the embedded controller's
+//! query. What a GPE means — a lid, a sleep button wired as a control-method
+//! device, an EC event — is said only by running that method, and a method is
+//! no work for an ISR or a scheduler pass. So a GPE that fires is masked in
+//! the ISR and handed to `acpid` by [`service`]: acknowledged first if it is
+//! an edge, since the method may raise it again. `acpid` runs the method and
+//! gives the number back through [`gpe_done`], and the next pass on this CPU
+//! acknowledges a level GPE — whose source the method has now quieted — and
+//! turns it back on.
+//!
+//! Which register is where and what a bit means is `toyos-acpi`'s; this file
+//! reads and writes the ports it names.
+//!
+//! **Delivery is pinned to the CPU that ran [`init`]**, as the i8042's is, and
+//! for the same reason: `irq_ring` records live on the CPU that took the
+//! interrupt, so only that CPU's [`service`] finds one — which also makes it
+//! the only CPU whose thread context ever writes a GPE register, with
+//! interrupts off so the ISR cannot interleave with it. `acpid` runs wherever
+//! the scheduler put it, so it writes none: [`gpe_done`] records what it
+//! finished and kicks this CPU, whose next pass does the writes.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering};
+
+use toyos_acpi::gpe::{self, Block, Trigger};
+use toyos_acpi::pm1::{self, EventBlock, Fixed};
+use toyos_acpi::Handover;
+
+use super::aml::{self, Gpe};
+use super::{acpi, ioapic};
+use crate::arch::cpu::{inb, inw, outb, outw};
+use crate::arch::idt::SCI_VECTOR;
+use crate::irq_ring::IrqSource;
+use crate::log;
+use crate::sync::Lock;
+
+/// How long firmware gets to hand the machine over after `ACPI_ENABLE` is
+/// written. The spec names no bound; Linux waits three seconds, and a BIOS
+/// that takes longer is not going to.
+const HANDOVER_TIMEOUT_NS: u64 = 3_000_000_000;
+
+/// The PM1 status and enable ports of each half of the event block, zero for
+/// a half that does not exist. Written once by [`init`] before the line is
+/// unmasked, and only read after.
+static PM1_STATUS: [AtomicU16; 2] = [const { AtomicU16::new(0) }; 2];
+static PM1_ENABLE: [AtomicU16; 2] = [const { AtomicU16::new(0) }; 2];
+/// The `PM1_EN` bits [`init`] turned on.
+static PM1_ENABLED: AtomicU16 = AtomicU16::new(0);
+
+/// The two GPE blocks, as `(base, len, first)` — a zero length for a block
+/// the machine does not have. Atomics rather than a `Block` behind a lock,
+/// because the ISR reads them and takes no lock.
+static GPE_BASE: [AtomicU16; 2] = [const { AtomicU16::new(0) }; 2];
+static GPE_LEN: [AtomicU8; 2] = [const { AtomicU8::new(0) }; 2];
+static GPE_FIRST: [AtomicU16; 2] = [const { AtomicU16::new(0) }; 2];
+
+/// What the ISR found and [`service`] has not yet handled: the PM1 bits it
+/// acknowledged, and one bit per GPE number it masked.
+static FIRED_PM1: AtomicU16 = AtomicU16::new(0);
+static FIRED_GPE: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+
+/// What `acpid` has run and [`service`] has not yet turned back on, one bit
+/// per GPE number, and whether any bit is set — the one load a pass on any
+/// CPU pays for it.
+static DONE_GPE: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+static DONE: AtomicBool = AtomicBool::new(false);
+
+/// The CPU the SCI is routed to, which is the one that writes GPE registers.
+/// `u32::MAX` until [`init`] routes it.
+static SCI_CPU: AtomicU32 = AtomicU32::new(u32::MAX);
+
+/// What [`init`] asked of [`take_from_firmware`] — `PM1a_CNT`, `SMI_CMD` and
+/// `ACPI_ENABLE` — for [`resume`] to ask again.
+static HANDOVER: Lock<Option<(u32, u32, u8)>> = Lock::new(None);
+
+/// Every GPE enable register as [`suspend`] found it, as `(port, value)`.
+static GPE_SAVED: Lock<Vec<(u16, u8)>> = Lock::new(Vec::new());
+
+fn blocks() -> impl Iterator<Item = Block> {
+    (0..2).filter_map(|i| {
+        Block::new(
+            u32::from(GPE_BASE[i].load(Ordering::Relaxed)),
+            GPE_LEN[i].load(Ordering::Relaxed),
+            GPE_FIRST[i].load(Ordering::Relaxed),
+        )
+    })
+}
+
+fn pm1_status() -> u16 {
+    PM1_STATUS
+        .iter()
+        .map(|port| port.load(Ordering::Relaxed))
+        .filter(|&port| port != 0)
+        .fold(0, |status, port| status | inw(port))
+}
+
+/// Write `value` to every half's register in `ports`.
+///
+/// Both halves, always: a status bit is set in whichever half the chipset
+/// wired it to, writing a one to a bit the other half does not implement is
+/// ignored by it, and a zero to a write-one-to-clear bit changes nothing.
+fn pm1_write(ports: &[AtomicU16; 2], value: u16) {
+    for port in ports.iter().map(|p| p.load(Ordering::Relaxed)).filter(|&p| p != 0) {
+        // SAFETY: `outw` asks its caller to own the port and the word. The port
+        // is a PM1 status or enable register the FADT named, checked into one
+        // by `toyos_acpi::pm1::EventBlock`, and published by `init` alone. The
+        // word is PM1 register bits as `toyos_acpi::pm1` defines them: ones
+        // that acknowledge events that fired, or the enable bits for the fixed
+        // buttons this machine has. Neither commands anything but the ACPI
+        // event logic.
+        unsafe { outw(port, value) };
+    }
+}
+
+/// # Safety
+/// `port` is a GPE status or enable register of a [`Block`] this module
+/// published, and `value` is a mask of GPE bits: ones to acknowledge in a
+/// status register, the events to leave on in an enable register.
+unsafe fn gpe_write(port: u16, value: u8) {
+    // SAFETY: the caller's contract is `outb`'s, narrowed to a register whose
+    // every bit is one GPE's status or enable and commands nothing else.
+    unsafe { outb(port, value) };
+}
+
+/// Rust half of the pin-interrupt handler. Read the module doc before adding
+/// anything to it.
+pub extern "sysv64" fn handler() {
+    let timestamp = crate::clock::nanos_since_boot();
+    let mut found = false;
+
+    let fired = pm1::fired(pm1_status(), PM1_ENABLED.load(Ordering::Relaxed));
+    if fired.clear != 0 {
+        pm1_write(&PM1_STATUS, fired.clear);
+        FIRED_PM1.fetch_or(fired.clear, Ordering::Relaxed);
+        found = true;
+    }
+
+    for block in blocks() {
+        for i in 0..block.registers() {
+            let enable = inb(block.enable_port(i));
+            let pending = gpe::pending(inb(block.status_port(i)), enable);
+            if pending == 0 {
+                continue;
+            }
+            // Off, not acknowledged: a level GPE's status comes straight back
+            // while its source is asserted, and only its method can make the
+            // source stop. `service` acknowledges it once that has happened.
+            // SAFETY: an enable register of a published block, written with the
+            // bits it already had minus the ones that fired.
+            unsafe { gpe_write(block.enable_port(i), enable & !pending) };
+            for bit in gpe::bits(pending) {
+                let number = block.number(i, bit);
+                FIRED_GPE[usize::from(number / 64) % 4]
+                    .fetch_or(1 << (number % 64), Ordering::Relaxed);
+            }
+            found = true;
+        }
+    }
+
+    if found {
+        crate::irq_ring::isr_publish(IrqSource::Sci, timestamp);
+        crate::preempt::set_need_resched();
+    }
+    crate::arch::apic::eoi();
+}
+
+/// Turn what the ISR recorded into power events, hand fired GPEs to `acpid`,
+/// and turn back on the ones it has finished. Runs at the top of every
+/// scheduler pass on every CPU, so the idle cost is two atomic loads.
+pub fn service() {
+    if crate::irq_ring::take(IrqSource::Sci).is_some() {
+        fired();
+    }
+    if DONE.load(Ordering::Acquire)
+        && crate::arch::percpu::cpu_id() == SCI_CPU.load(Ordering::Relaxed)
+    {
+        finished();
+    }
+}
+
+fn fired() {
+    let pm1_bits = FIRED_PM1.swap(0, Ordering::Relaxed);
+    if pm1_bits & pm1::PWRBTN != 0 {
+        log!("SCI: power button");
+        crate::power::publish(crate::power::POWER_BUTTON, 0);
+    }
+    if pm1_bits & pm1::SLPBTN != 0 {
+        log!("SCI: sleep button");
+        crate::power::publish(crate::power::SLEEP_BUTTON, 0);
+    }
+
+    for (word, fired) in FIRED_GPE.iter().enumerate() {
+        let mut bits = fired.swap(0, Ordering::Relaxed);
+        while bits != 0 {
+            let number = (word * 64) as u16 + bits.trailing_zeros() as u16;
+            bits &= bits - 1;
+            match aml::handles(number) {
+                Some(trigger) => {
+                    if trigger == Trigger::Edge {
+                        acknowledge(number);
+                    }
+                    aml::raise(number);
+                }
+                None => unhandled_gpe(number),
+            }
+        }
+    }
+}
+
+/// Acknowledge a level GPE `acpid` has run the method of, and turn every one
+/// it has finished back on.
+fn finished() {
+    DONE.store(false, Ordering::Relaxed);
+    for (word, done) in DONE_GPE.iter().enumerate() {
+        let mut bits = done.swap(0, Ordering::Acquire);
+        while bits != 0 {
+            let number = (word * 64) as u16 + bits.trailing_zeros() as u16;
+            bits &= bits - 1;
+            let Some((block, (i, bit))) = blocks().find_map(|b| Some((b, b.locate(number)?)))
+            else {
+                continue;
+            };
+            if aml::handles(number) == Some(Trigger::Level) {
+                acknowledge(number);
+            }
+            let _irq = crate::hw::IrqGuard::close();
+            let port = block.enable_port(i);
+            // SAFETY: an enable register of a published block, with the one
+            // bit the ISR cleared set again.
+            unsafe { gpe_write(port, inb(port) | 1 << bit) };
+        }
+    }
+}
+
+/// `acpid` has run what GPE `number` stands for. Any thread, any CPU: the
+/// register writes wait for the SCI's CPU, which this wakes.
+pub fn gpe_done(number: u16) {
+    let Some(word) = DONE_GPE.get(usize::from(number / 64)) else { return };
+    word.fetch_or(1 << (number % 64), Ordering::Release);
+    DONE.store(true, Ordering::Release);
+    let cpu = SCI_CPU.load(Ordering::Relaxed);
+    if cpu == crate::arch::percpu::cpu_id() {
+        crate::preempt::set_need_resched();
+    } else {
+        crate::arch::apic::kick_cpu(cpu);
+    }
+}
+
+/// Write GPE `number`'s status bit, to acknowledge it.
+fn acknowledge(number: u16) {
+    let Some((block, (i, bit))) = blocks().find_map(|b| Some((b, b.locate(number)?))) else {
+        return;
+    };
+    let _irq = crate::hw::IrqGuard::close();
+    // SAFETY: the status register of a published block, written with the one
+    // bit of the event the ISR masked.
+    unsafe { gpe_write(block.status_port(i), 1 << bit) };
+}
+
+/// A GPE fired that `acpid` has nothing to run for. The ISR has already
+/// turned it off; acknowledge it so a re-enable later starts clean, and say so.
+fn unhandled_gpe(number: u16) {
+    acknowledge(number);
+    log!("SCI: GPE {:#04x} fired and has no method, so it stays masked", number);
+}
+
+/// Take the SCI from firmware and route it, or log why not and leave the
+/// machine as it was — without a power button, which is what it had before.
+///
+/// After `aml::init`, which is what finds the GPE methods the namespace
+/// defines.
+pub fn init(rsdp_addr: u64) {
+    let config = match acpi::sci_config(rsdp_addr) {
+        Ok(config) => config,
+        Err(e) => {
+            log!("SCI: FADT unusable: {e:?} — no power button");
+            return;
+        }
+    };
+    if config.flags & pm1::FADT_HW_REDUCED != 0 {
+        log!("SCI: hardware-reduced ACPI — its buttons are AML devices; no power button");
+        return;
+    }
+    let Some(pm1a) = EventBlock::new(config.pm1a_event, config.pm1_event_len) else {
+        log!(
+            "SCI: no PM1a event block ({:#x}, {} bytes) — no power button",
+            config.pm1a_event,
+            config.pm1_event_len
+        );
+        return;
+    };
+    let pm1b = EventBlock::new(config.pm1b_event, config.pm1_event_len);
+    let Ok(irq) = u8::try_from(config.irq) else {
+        log!("SCI: on interrupt {}, past the ISA range — no power button", config.irq);
+        return;
+    };
+    let fixed = Fixed::from_fadt_flags(config.flags);
+    let gpe_blocks = [
+        (config.gpe0, config.gpe0_len, 0),
+        (config.gpe1, config.gpe1_len, u16::from(config.gpe1_base)),
+    ];
+
+    // Publish the registers first, then quiet them: `pm1_write` and `blocks`
+    // read what is published.
+    for (i, half) in [Some(pm1a), pm1b].into_iter().enumerate() {
+        if let Some(half) = half {
+            PM1_STATUS[i].store(half.status, Ordering::Relaxed);
+            PM1_ENABLE[i].store(half.enable, Ordering::Relaxed);
+        }
+    }
+    for (i, (base, len, first)) in gpe_blocks.into_iter().enumerate() {
+        // `Block::new` refuses a base past the port space, so the narrowing
+        // below only ever happens to one that fits.
+        if Block::new(base, len, first).is_some() {
+            GPE_BASE[i].store(base as u16, Ordering::Relaxed);
+            GPE_LEN[i].store(len, Ordering::Relaxed);
+            GPE_FIRST[i].store(first, Ordering::Relaxed);
+        }
+    }
+
+    // Everything off and acknowledged before the handover: firmware may have
+    // left events enabled for its own SMM handler, and the moment `SCI_EN`
+    // goes to one each of them raises an interrupt nothing is routed for yet.
+    pm1_write(&PM1_ENABLE, 0);
+    pm1_write(&PM1_STATUS, 0xFFFF);
+    for block in blocks() {
+        for i in 0..block.registers() {
+            // SAFETY: registers of a block published above; every event off,
+            // then every status acknowledged.
+            unsafe {
+                gpe_write(block.enable_port(i), 0);
+                gpe_write(block.status_port(i), 0xFF);
+            }
+        }
+    }
+
+    if !take_from_firmware(config.pm1a_control, config.smi_cmd, config.acpi_enable) {
+        return;
+    }
+    *HANDOVER.lock() = Some((config.pm1a_control, config.smi_cmd, config.acpi_enable));
+
+    let apic_id = crate::arch::apic::id();
+    let Some(line) = ioapic::gsi_for_sci(irq) else {
+        log!("SCI: no I/O APIC covers IRQ {irq} — no power button");
+        return;
+    };
+    if let Err(e) = ioapic::route(line.gsi, SCI_VECTOR, apic_id, line.trigger, line.polarity) {
+        log!("SCI: GSI {} not routable to apic {}: {:?}", line.gsi.0, apic_id, e);
+        return;
+    }
+
+    // Before the first GPE can fire: `gpe_done` kicks this CPU by name.
+    SCI_CPU.store(crate::arch::percpu::cpu_id(), Ordering::Relaxed);
+
+    // The GPEs `acpid` has something to run for.
+    let mut enabled = String::new();
+    for gpe in aml::gpes() {
+        let Some((block, (i, bit))) =
+            blocks().find_map(|b| Some((b, b.locate(u16::from(gpe.number()))?)))
+        else {
+            continue;
+        };
+        let port = block.enable_port(i);
+        // SAFETY: an enable register of a published block, with one more of
+        // its own bits set.
+        unsafe { gpe_write(port, inb(port) | 1 << bit) };
+        match gpe {
+            Gpe::Method(method) => {
+                let name = method.name();
+                let _ = write!(enabled, " {}", core::str::from_utf8(&name).unwrap_or("?"));
+            }
+            Gpe::Ec(number) => {
+                let _ = write!(enabled, " EC:{number:#04x}");
+            }
+        }
+    }
+
+    PM1_ENABLED.store(fixed.enable_bits(), Ordering::Relaxed);
+    pm1_write(&PM1_ENABLE, fixed.enable_bits());
+    if ioapic::set_masked(line.gsi, false).is_err() {
+        log!("SCI: GSI {} would not unmask — no power button", line.gsi.0);
+        return;
+    }
+
+    log!(
+        "SCI: IRQ {} -> GSI {} vec {:#04x} apic {}, power button {}, sleep button {}, GPEs [{}]",
+        irq,
+        line.gsi.0,
+        SCI_VECTOR,
+        apic_id,
+        if fixed.power_button { "fixed" } else { "AML" },
+        if fixed.sleep_button { "fixed" } else { "AML" },
+        enabled.trim_start()
+    );
+}
+
+/// Quiet the event logic ahead of S3, with interrupts off and every other CPU
+/// parked.
+///
+/// Every GPE enable is saved and then cleared, and of the fixed events only the
+/// power button is left on: in a sleep state an enabled event is a wake source,
+/// and the only one this kernel means to have is the button the user presses to
+/// get the machine back. Every status bit is acknowledged last — `WAK_STS`
+/// included, so the one [`resume`] finds is this sleep's.
+pub fn suspend() {
+    if SCI_CPU.load(Ordering::Relaxed) == u32::MAX {
+        return;
+    }
+    let mut saved = GPE_SAVED.lock();
+    saved.clear();
+    for block in blocks() {
+        for i in 0..block.registers() {
+            let port = block.enable_port(i);
+            saved.push((port, inb(port)));
+            // SAFETY: an enable register of a published block, every event off.
+            unsafe { gpe_write(port, 0) };
+            // SAFETY: its status register, every event acknowledged.
+            unsafe { gpe_write(block.status_port(i), 0xFF) };
+        }
+    }
+    pm1_write(&PM1_ENABLE, PM1_ENABLED.load(Ordering::Relaxed) & pm1::PWRBTN);
+    pm1_write(&PM1_STATUS, 0xFFFF);
+}
+
+/// Put the event logic back after S3: the machine in ACPI mode again if
+/// firmware's resume path left it in legacy mode, every status — the wake, and
+/// the button press that may have caused it — acknowledged rather than
+/// delivered, and the enables [`suspend`] saved turned back on.
+///
+/// A press that woke the machine is not a press to act on: the power button's
+/// holder would take it as a request to turn off a machine the user has just
+/// turned back on.
+pub fn resume() {
+    if SCI_CPU.load(Ordering::Relaxed) == u32::MAX {
+        return;
+    }
+    if let Some((control, smi_cmd, acpi_enable)) = *HANDOVER.lock() {
+        take_from_firmware(control, smi_cmd, acpi_enable);
+    }
+    pm1_write(&PM1_STATUS, 0xFFFF);
+    for block in blocks() {
+        for i in 0..block.registers() {
+            // SAFETY: a status register of a published block, every event
+            // acknowledged before any is turned back on.
+            unsafe { gpe_write(block.status_port(i), 0xFF) };
+        }
+    }
+    for &(port, value) in GPE_SAVED.lock().iter() {
+        // SAFETY: an enable register `suspend` read from a published block,
+        // written back with the value it held.
+        unsafe { gpe_write(port, value) };
+    }
+    pm1_write(&PM1_ENABLE, PM1_ENABLED.load(Ordering::Relaxed));
+}
+
+/// Leave legacy mode if the machine is in it. False, logged, when firmware
+/// never let go — an SCI that is never delivered is a power button that is
+/// quietly a BIOS feature, and routing it would only hide that.
+fn take_from_firmware(pm1a_control: u32, smi_cmd: u32, acpi_enable: u8) -> bool {
+    let Ok(control) = u16::try_from(pm1a_control) else {
+        log!("SCI: PM1a control block {pm1a_control:#x} is not a port — no power button");
+        return false;
+    };
+    if control == 0 {
+        log!("SCI: no PM1a control block — no power button");
+        return false;
+    }
+    let Handover::Ask { port, value } =
+        toyos_acpi::handover::handover(inw(control), smi_cmd, acpi_enable)
+    else {
+        return true;
+    };
+    log!("SCI: machine is in legacy mode; writing {value:#04x} to SMI_CMD {port:#x}");
+    // SAFETY: `outb` asks its caller to own the port and the byte. Both come
+    // from the FADT, whose whole purpose for these two fields is this write:
+    // `SMI_CMD` is the port firmware listens on and `ACPI_ENABLE` the command
+    // that hands the fixed hardware to the OS. `handover` returned them only
+    // because `SCI_EN` is clear, which is the one state the write is for.
+    unsafe { outb(port, value) };
+    let deadline = crate::clock::nanos_since_boot() + HANDOVER_TIMEOUT_NS;
+    while inw(control) & pm1::SCI_EN == 0 {
+        if crate::clock::nanos_since_boot() >= deadline {
+            log!("SCI: firmware never set SCI_EN — no power button");
+            return false;
+        }
+        core::hint::spin_loop();
+    }
+    true
+}
@@ -1,4 +1,5 @@
-//! The 16550 and the virtio-console, and the one lock that serialises them.
+//! The 16550, the virtio-console and a USB serial adapter, and the one lock
+//! that serialises them.
 //!
 //! **There is one thing here now where there were two.** `SerialWriter` was a
 //! per-invocation stack buffer that every `log!` formatted into and committed
@@ -83,10 +84,33 @@ pub fn init() {
     console_changed();
 }
 
+/// Program the UART again after S3, which took its divisor and line control
+/// with the power. No probe: whether a 16550 is there was settled at boot,
+/// and the loopback byte would be one more thing to explain on the wire.
+#[allow(clippy::identity_op)]
+pub fn resume() {
+    if !uart_present() {
+        return;
+    }
+    // SAFETY: the same ports and the same bytes as `init`'s block, minus the
+    // probe, and in the same order — DLAB set before the two divisor writes
+    // and cleared by the line-control write after them.
+    unsafe {
+        outb(PORT + 1, 0x00); // Disable all interrupts
+        outb(PORT + 3, 0x80); // Enable DLAB
+        outb(PORT + 0, 0x03); // Divisor 3, 38400 baud
+        outb(PORT + 1, 0x00);
+        outb(PORT + 3, 0x03); // 8N1
+        outb(PORT + 2, 0xC7); // FIFOs on and cleared
+        outb(PORT + 4, 0x0F); // Normal operation mode
+    }
+}
+
 /// A backend has arrived, or the machine has switched to a better one.
 ///
-/// Called from the two places [`backend`] can change its answer — this module's
-/// probe, and virtio-console coming up in phase 6. What it does is
+/// Called from the places [`backend`] can change its answer — this module's
+/// probe, virtio-console coming up in phase 6, and a USB serial adapter being
+/// bound or pulled, which can happen at any time. What it does is
 /// `log::console`'s and the argument lives there: everything said so far went
 /// to whichever backend existed then, and the new one has heard none of it.
 pub fn console_changed() {
@@ -113,8 +137,10 @@ pub fn has_console() -> bool {
 /// **One answer, and [`BackendGuard::write_raw`] is written in terms of it**, so
 /// the drain's "which backend has already heard this" question cannot disagree
 /// with where the bytes actually went. The order is the preference: a
-/// virtio-console is the host's own channel and a 16550 is what is left when
-/// there is none.
+/// virtio-console is the host's own channel, a USB serial adapter is a cable
+/// somebody plugged in to read this machine — on one that also has a 16550,
+/// that is a statement about which one they are watching — and a 16550 is
+/// what is left when there is neither.
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Backend {
@@ -123,11 +149,14 @@ pub enum Backend {
     None = 0,
     Uart = 1,
     Virtio = 2,
+    Usb = 3,
 }
 
 pub fn backend() -> Backend {
     if super::virtio_console::is_ready() {
         Backend::Virtio
+    } else if super::xhci::serial::is_ready() {
+        Backend::Usb
     } else if uart_present() {
         Backend::Uart
     } else {
@@ -139,8 +168,8 @@ pub fn backend() -> Backend {
 
 static BACKEND_LOCKED: AtomicBool = AtomicBool::new(false);
 
-/// RAII handle for exclusive access to the serial backend (virtio-console
-/// or UART). Disables interrupts because reads and writes touch device
+/// RAII handle for exclusive access to the serial backend (virtio-console,
+/// USB serial or UART). Disables interrupts because reads and writes touch device
 /// state shared with poll callers; same-CPU re-entry from an IRQ handler
 /// would otherwise deadlock the spin.
 pub struct BackendGuard {
@@ -221,26 +250,29 @@ impl BackendGuard {
     pub fn write_raw(&mut self, bytes: &[u8]) {
         match backend() {
             Backend::Virtio => super::virtio_console::write_bytes_locked(bytes),
+            Backend::Usb => super::xhci::serial::write_bytes_locked(bytes),
             Backend::Uart => uart_write_bytes(bytes),
             Backend::None => {}
         }
     }
 
+    /// Input comes from the same backend output goes to: a shell answering on
+    /// one channel what was typed on another is a shell nobody can use.
     pub fn has_data(&self) -> bool {
-        if super::virtio_console::is_ready() {
-            super::virtio_console::has_data_locked()
-        } else {
-            uart_present() && inb(PORT + 5) & 0x01 != 0
+        match backend() {
+            Backend::Virtio => super::virtio_console::has_data_locked(),
+            Backend::Usb => super::xhci::serial::has_data_locked(),
+            Backend::Uart => inb(PORT + 5) & 0x01 != 0,
+            Backend::None => false,
         }
     }
 
     pub fn try_read_byte(&mut self) -> Option<u8> {
-        if super::virtio_console::is_ready() {
-            super::virtio_console::try_read_byte_locked()
-        } else if uart_present() && inb(PORT + 5) & 0x01 != 0 {
-            Some(inb(PORT))
-        } else {
-            None
+        match backend() {
+            Backend::Virtio => super::virtio_console::try_read_byte_locked(),
+            Backend::Usb => super::xhci::serial::try_read_byte_locked(),
+            Backend::Uart if inb(PORT + 5) & 0x01 != 0 => Some(inb(PORT)),
+            Backend::Uart | Backend::None => None,
         }
     }
 }
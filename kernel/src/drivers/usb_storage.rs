@@ -7,6 +7,11 @@
 //! geometry the device reported, so the controller lock is taken per operation
 //! and never held across one.
 //!
+//! The handle does not know which transport its disk speaks. Bulk-Only and
+//! USB Attached SCSI both end in the same SCSI commands against the same
+//! geometry; what differs is how many of them are in flight at once, and that
+//! is decided where the pipes are, in `xhci::wait/uas.rs`.
+//!
 //! **This is where an operation's device-time budget is established**
 //! ([`crate::block::begin_operation`]), because this is the layer at which one
 //! call is one operation: below here the driver batches, retries and recovers,
@@ -16,7 +21,7 @@
 //!
 //! **Established on the running context and not passed down**, which is owner
 //! ruling 1B: the deadline crosses `BlockAccess` and `BlockDevice`, two frames
-//! that cannot carry it, so `xhci::wait/msc.rs`'s three operation entry points
+//! that cannot carry it, so `xhci::wait/msc.rs`'s four operation entry points
 //! recover it instead. The guard is a `let _op` and not a `let _`: `let _`
 //! drops at the end of the statement, which would end the operation before the
 //! call it bounds.
@@ -25,9 +30,9 @@ use crate::block::{self, BlockDevice, BlockError, BlockResult, DeviceId};
 use crate::log;
 use super::xhci;
 
-/// Where USB disks start in the [`DeviceId`] space. NVMe takes 1; the page
-/// cache keys itself on this, so two devices sharing a number would serve each
-/// other's blocks.
+/// Where USB disks start in the [`DeviceId`] space. NVMe takes 1, AHCI disks 2
+/// to 7 and virtio disks 8 to 15; the page cache keys itself on this, so two
+/// devices sharing a number would serve each other's blocks.
 const USB_DEVICE_ID_BASE: DeviceId = 16;
 
 /// Disk numbers issued this boot, so `0..count()` names every disk this machine
@@ -40,8 +45,8 @@ const USB_DEVICE_ID_BASE: DeviceId = 16;
 /// driver's job.
 ///
 /// A number never moves and is never reissued, which is what everything above
-/// here depends on: [`open`] hands out a handle keyed on one and a mount holds
-/// that handle for its whole life. An unplugged disk leaves its number behind
+/// here depends on: [`open`] hands out a handle keyed on one, and the page
+/// cache holds the handle a mount reads through for the rest of the boot. An unplugged disk leaves its number behind
 /// naming nothing, rather than passing it to whatever is plugged in next.
 pub fn count() -> usize {
     xhci::storage_count()
@@ -127,4 +132,16 @@ impl BlockDevice for UsbBlockDevice {
         log!("usb-storage: cache flush failed on disk {}", self.index);
         Err(BlockError)
     }
+
+    /// UNMAP, on a disk whose bring-up found it both provisioning and willing
+    /// to say how much one command may carry; any other disk answers `Ok`
+    /// having been told nothing.
+    fn discard(&mut self, lba: u64, count: u32) -> BlockResult {
+        let _op = block::begin_operation();
+        if xhci::storage_discard(self.index, lba, count) {
+            return Ok(());
+        }
+        log!("usb-storage: unmap of {count} blocks at {lba} failed on disk {}", self.index);
+        Err(BlockError)
+    }
 }
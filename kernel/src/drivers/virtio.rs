@@ -308,6 +308,13 @@ impl UsedRingConsumer<'_> {
     pub fn refused(&self) -> u32 {
         self.refused
     }
+
+    /// Start again at the head of a used ring that
+    /// [`Virtqueue::restart_rings`] has emptied. The refusal count is kept,
+    /// because it is counted for the boot.
+    pub fn restart(&mut self) {
+        self.last_used_idx = 0;
+    }
 }
 
 /// A VirtIO split virtqueue.
@@ -377,6 +384,40 @@ impl<'pool> Virtqueue<'pool> {
         }
     }
 
+    /// Put this queue back as `from_regions` left it, for a device that has
+    /// been reset and is about to be given the same rings again.
+    ///
+    /// Answers the whole slot pool, which replaces every slot the caller
+    /// held: a reset device completes nothing it was given, so whatever was in
+    /// flight is not coming back and its slot would otherwise be lost for the
+    /// rest of the boot. A queue whose used ring was split off cannot be
+    /// restarted here, because the consumer's index lives where this cannot
+    /// reach it.
+    pub fn restart(&mut self) -> alloc::vec::Vec<DescSlot> {
+        assert!(!self.used_split, "virtqueue: restarting a queue whose used ring was split");
+        self.desc.zero();
+        self.avail.zero();
+        self.used.zero();
+        self.last_used_idx = 0;
+        self.chain_bytes.fill(0);
+        self.initial_slots()
+    }
+
+    /// Empty the avail and used rings for a device that has been reset, and
+    /// keep the descriptor table.
+    ///
+    /// [`restart`](Self::restart) is for a queue whose chains are submitted
+    /// and retired. This one is for a queue whose chains were written once
+    /// with [`write_chain`](Self::write_chain) and are published by index, so
+    /// the chains are still right after the reset and only the two indices
+    /// the device forgot have to start again from zero. A split-off consumer
+    /// is restarted by its holder.
+    pub fn restart_rings(&mut self) {
+        self.avail.zero();
+        self.used.zero();
+        self.last_used_idx = 0;
+    }
+
     /// Hand the used ring to a dedicated (interrupt-context) consumer.
     /// Afterwards this queue is submit-only: `poll_used`/`has_used` panic,
     /// enforcing the single-consumer invariant at runtime.
@@ -741,6 +782,10 @@ impl core::fmt::Display for NoVector {
 /// A fully initialized VirtIO device.
 pub struct VirtioDevice {
     config: VirtioPciConfig,
+    /// What negotiation settled on: the device's offer masked by the driver's.
+    /// Kept because a feature the driver *accepts* is not one it may use —
+    /// virtio-blk's flush and discard exist only on a device that offered them.
+    features: u64,
 }
 
 impl VirtioDevice {
@@ -751,47 +796,55 @@ impl VirtioDevice {
         let config = VirtioPciConfig::parse(pci_dev);
         let common = config.common;
 
-        // 1. Reset
-        common.write_u32(COMMON_DEVICE_STATUS, 0);
-        while common.read_u32(COMMON_DEVICE_STATUS) != 0 {
-            core::hint::spin_loop();
-        }
-
-        // 2. ACKNOWLEDGE
-        common.write_u32(COMMON_DEVICE_STATUS, STATUS_ACKNOWLEDGE as u32);
-
-        // 3. DRIVER
-        common.write_u32(COMMON_DEVICE_STATUS,
-            STATUS_ACKNOWLEDGE as u32 | STATUS_DRIVER as u32);
+        // 1-3. Reset, ACKNOWLEDGE, DRIVER
+        let device_features = handshake(common);
 
         // 4. Negotiate features
-        // Read device features (low 32 bits)
-        common.write_u32(COMMON_DEVICE_FEATURE_SELECT, 0);
-        let device_features_lo = common.read_u32(COMMON_DEVICE_FEATURE);
-        // Read device features (high 32 bits)
-        common.write_u32(COMMON_DEVICE_FEATURE_SELECT, 1);
-        let device_features_hi = common.read_u32(COMMON_DEVICE_FEATURE);
-        let device_features = (device_features_hi as u64) << 32 | device_features_lo as u64;
-
         let features = device_features & accepted_features;
         log!("VirtIO: device features={:#x} negotiated={:#x}", device_features, features);
 
-        common.write_u32(COMMON_DRIVER_FEATURE_SELECT, 0);
-        common.write_u32(COMMON_DRIVER_FEATURE, features as u32);
-        common.write_u32(COMMON_DRIVER_FEATURE_SELECT, 1);
-        common.write_u32(COMMON_DRIVER_FEATURE, (features >> 32) as u32);
+        // 5-6. FEATURES_OK, and verify it stuck
+        assert!(negotiate(common, features), "VirtIO: device rejected features");
 
-        // 5. FEATURES_OK
-        let status = STATUS_ACKNOWLEDGE as u32 | STATUS_DRIVER as u32 | STATUS_FEATURES_OK as u32;
-        common.write_u32(COMMON_DEVICE_STATUS, status);
+        Self { config, features }
+    }
 
-        // 6. Verify FEATURES_OK stuck
-        assert!(
-            common.read_u32(COMMON_DEVICE_STATUS) & STATUS_FEATURES_OK as u32 != 0,
-            "VirtIO: device rejected features"
-        );
+    /// Reset the device, which stops every queue and forgets every address,
+    /// vector and feature it was given.
+    ///
+    /// What a driver calls on its way into S3: a reset device does no DMA, so
+    /// nothing it does can land in memory the sleep is about to stop
+    /// refreshing — or, on a machine where the IOMMU is on, fault against a
+    /// translation `iommu::suspend` has just turned off.
+    pub fn reset(&self) {
+        let common = self.config.common;
+        common.write_u32(COMMON_DEVICE_STATUS, 0);
+        while common.read_u32(COMMON_DEVICE_STATUS) != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Take a reset device back to where `init` left it: features negotiated,
+    /// no queue configured and `DRIVER_OK` not yet set.
+    ///
+    /// Renegotiates exactly what was agreed at bind rather than what the
+    /// driver would accept, because every decision the driver made since —
+    /// buffer layouts, which requests it builds — was made against those
+    /// features. False if the device no longer offers all of them or refuses
+    /// `FEATURES_OK`, which leaves it reset; the caller treats that as the
+    /// device having gone away.
+    pub fn restart(&self) -> bool {
+        let common = self.config.common;
+        let offered = handshake(common);
+        if offered & self.features != self.features {
+            return false;
+        }
+        negotiate(common, self.features)
+    }
 
-        Self { config }
+    /// The features both sides agreed to, as written to the device in step 4.
+    pub fn features(&self) -> u64 {
+        self.features
     }
 
     /// Configure a virtqueue's addresses and size. Does NOT enable the queue —
@@ -862,3 +915,33 @@ impl VirtioDevice {
         self.config.device
     }
 }
+
+/// Steps 1 to 3 of initialization: reset, `ACKNOWLEDGE`, `DRIVER`. Answers
+/// what the device offers.
+fn handshake(common: Mmio) -> u64 {
+    common.write_u32(COMMON_DEVICE_STATUS, 0);
+    while common.read_u32(COMMON_DEVICE_STATUS) != 0 {
+        core::hint::spin_loop();
+    }
+    common.write_u32(COMMON_DEVICE_STATUS, STATUS_ACKNOWLEDGE as u32);
+    common.write_u32(COMMON_DEVICE_STATUS, STATUS_ACKNOWLEDGE as u32 | STATUS_DRIVER as u32);
+
+    common.write_u32(COMMON_DEVICE_FEATURE_SELECT, 0);
+    let lo = common.read_u32(COMMON_DEVICE_FEATURE);
+    common.write_u32(COMMON_DEVICE_FEATURE_SELECT, 1);
+    let hi = common.read_u32(COMMON_DEVICE_FEATURE);
+    (hi as u64) << 32 | lo as u64
+}
+
+/// Steps 4 to 6: write `features`, set `FEATURES_OK`, and answer whether the
+/// device kept it set.
+fn negotiate(common: Mmio, features: u64) -> bool {
+    common.write_u32(COMMON_DRIVER_FEATURE_SELECT, 0);
+    common.write_u32(COMMON_DRIVER_FEATURE, features as u32);
+    common.write_u32(COMMON_DRIVER_FEATURE_SELECT, 1);
+    common.write_u32(COMMON_DRIVER_FEATURE, (features >> 32) as u32);
+
+    let status = STATUS_ACKNOWLEDGE as u32 | STATUS_DRIVER as u32 | STATUS_FEATURES_OK as u32;
+    common.write_u32(COMMON_DEVICE_STATUS, status);
+    common.read_u32(COMMON_DEVICE_STATUS) & STATUS_FEATURES_OK as u32 != 0
+}
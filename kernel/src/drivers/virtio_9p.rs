@@ -0,0 +1,282 @@
+//! virtio-9p: a channel to a 9P server on the host, one per `-virtfs`.
+//!
+//! The transport and nothing above it. A channel carries one encoded request
+//! to the device and one reply back, as a two-descriptor chain: the request
+//! readable, the reply writable. What the bytes say is `toyos_9p`'s, and the
+//! session that speaks it — fids, the attach, the paths — is
+//! `ninep_adapter`'s, which is where a channel becomes a mount.
+//!
+//! **One request outstanding per channel**, `virtio_blk`'s choice for
+//! `virtio_blk`'s reason: the completion at the head of the used ring is then
+//! always the request the caller is waiting on, and the protocol's own tags —
+//! which exist so a server can answer out of order — never have to be matched
+//! against a table. A channel is served under its own lock, so two shares still
+//! overlap with each other.
+//!
+//! **No interrupt.** A request is answered by a host syscall, so the wait is
+//! `virtio_blk`'s spin on the used ring under [`COMMAND`], and nothing here
+//! needs a vector.
+//!
+//! Structure layouts come from the VirtIO 1.2 specification §5.11.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use toyos_untrusted::Untrusted;
+
+use super::pci::PciDevice;
+use super::virtio::{BufDir, DescSlot, Virtqueue, VirtioDevice, VIRTIO_F_VERSION_1};
+use super::DmaPool;
+use crate::log;
+use crate::mm::{Dma, Mmio};
+use crate::sync::Lock;
+use crate::time::{Budget, Duration};
+
+const VIRTIO_VENDOR: u16 = 0x1AF4;
+/// The modern function: 0x1040 + device type 9.
+const VIRTIO_9P_DEVICE: u16 = 0x1049;
+/// The transitional function, which is what `-virtfs` builds. It carries the
+/// modern capabilities as well as the legacy BAR, so it is the same device
+/// here — see `virtio_blk`.
+const VIRTIO_9P_TRANSITIONAL: u16 = 0x1009;
+
+/// The device names itself with a mount tag. Without one a share has nothing
+/// to be called, and is not bound.
+const VIRTIO_9P_F_MOUNT_TAG: u64 = 1 << 0;
+
+// Device configuration (virtio 1.2 §5.11.4): `le16 tag_len`, then the tag.
+const CFG_TAG_LEN: u64 = 0;
+const CFG_TAG: u64 = 2;
+/// The longest tag this driver reads. QEMU refuses a `mount_tag` past 255
+/// bytes; a tag becomes a directory name, and 32 is more than any of those
+/// wants.
+const MAX_TAG: u64 = 32;
+
+/// The largest message either way, and so the size of each half of the DMA
+/// window: 64 KiB, which moves sixteen pages in one `Tread` — a batch of
+/// `file_backing::READ_BATCH_PAGES` is four round trips, and a page is one.
+pub const MSIZE: u32 = 0x1_0000;
+
+/// Shares this driver binds. A machine with more than one `-virtfs` is a
+/// developer's, and four is more than one.
+const MAX_CHANNELS: usize = 4;
+
+const QUEUE: u16 = 0;
+const QUEUE_SIZE: u16 = 16;
+
+// Per-channel DMA window (byte offsets). The queue has a page to itself because
+// `Virtqueue::new` zeroes the whole view it is given.
+const OFF_QUEUE: usize = 0x0000;
+const OFF_REQUEST: usize = 0x1000;
+const OFF_REPLY: usize = OFF_REQUEST + MSIZE as usize;
+const WINDOW: usize = OFF_REPLY + MSIZE as usize;
+
+/// How long one request may spend with the server before this driver stops
+/// believing a reply is coming.
+///
+/// Longer than `virtio_blk::COMMAND`'s two seconds because the other side is a
+/// host filesystem rather than a disk image: a `Tread` of a file the host has
+/// not cached waits on the host's own disk, and a `Tfsync` waits on all of it.
+/// Nothing that completes reaches five.
+///
+/// **Its expiry ends this channel**, for `virtio_blk`'s reason: the request it
+/// stopped waiting for still names the window and owns its descriptors.
+const COMMAND: Budget = Budget::of(
+    Duration::from_secs(5),
+    "the request is abandoned, the share is marked failed, and every later \
+     request on it is refused",
+);
+
+/// Why a request came back with no reply this layer may hand up. What a reply
+/// that did arrive says is the session's to judge, not this.
+pub enum Unanswered {
+    /// The device did not answer inside [`COMMAND`], or the share was already
+    /// offline.
+    Silent,
+    /// The device said it wrote more than the reply buffer holds. The used
+    /// ring's length is held to the whole chain, and the request half is
+    /// readable — so this is the one count `poll_used` cannot refuse for us.
+    Overlong(u32),
+}
+
+impl core::fmt::Display for Unanswered {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Silent => write!(f, "no reply in {}", COMMAND.duration()),
+            Self::Overlong(written) => write!(f, "reply of {written} bytes into a {MSIZE}-byte buffer"),
+        }
+    }
+}
+
+/// One bound share: its queue, its DMA window, and the tag the host gave it.
+pub struct Channel {
+    index: usize,
+    tag: String,
+    #[allow(dead_code)] // kept for the life of the binding, like every driver's
+    device: VirtioDevice,
+    vq: Virtqueue<'static>,
+    notify: Mmio,
+    notify_mult: u32,
+    dma: Dma<'static>,
+    /// The one descriptor slot this channel's requests are built at. `None`
+    /// once a request has been abandoned: the device still owns the chain.
+    slot: Option<DescSlot>,
+    /// Whether a request has been abandoned, which refuses every later one —
+    /// `virtio_blk`'s latch.
+    failed: bool,
+}
+
+impl Channel {
+    /// What the host called this share: `mount_tag=` on its command line.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Send one encoded message and copy the reply into `reply`, answering how
+    /// many bytes the device wrote.
+    ///
+    /// Both are at most [`MSIZE`]. The count is the used ring's, which
+    /// `poll_used` has already held to the reply buffer's length; what the
+    /// bytes say is not looked at here.
+    pub fn transact(&mut self, request: &[u8], reply: &mut [u8]) -> Result<usize, Unanswered> {
+        assert!(request.len() <= MSIZE as usize && reply.len() >= MSIZE as usize);
+        if self.failed {
+            return Err(Unanswered::Silent);
+        }
+        let Some(slot) = self.slot.take() else {
+            return Err(Unanswered::Silent);
+        };
+        let dma = self.dma;
+        // Exclusive: no descriptor names the window between one answered
+        // request and the next.
+        dma.copy_from(OFF_REQUEST, request);
+        let out = (dma.phys() + OFF_REQUEST as u64, request.len() as u32, BufDir::Readable);
+        let back = (dma.phys() + OFF_REPLY as u64, MSIZE, BufDir::Writable);
+        self.vq.submit(slot, &[out, back], self.notify, self.notify_mult, QUEUE);
+
+        let vq = &self.vq;
+        let answered = crate::clock::settles(COMMAND.nanos(), || vq.has_used());
+        let returned = if answered { self.vq.poll_used() } else { None };
+        let Some((slot, written)) = returned else {
+            self.abandon();
+            return Err(Unanswered::Silent);
+        };
+        self.slot = Some(slot);
+        let written = Untrusted::new(written)
+            .at_most(MSIZE as u64)
+            .map_err(|_| Unanswered::Overlong(written))? as usize;
+        dma.copy_to(OFF_REPLY, &mut reply[..written]);
+        Ok(written)
+    }
+
+    /// A request nobody answered ends this share, once and loudly.
+    fn abandon(&mut self) {
+        if !self.failed {
+            self.failed = true;
+            log!(
+                "virtio-9p: share {} ({}) is offline: the request it did not answer still owns \
+                 its descriptors and its DMA window, and this driver has no queue reset to take \
+                 either back",
+                self.index,
+                self.tag
+            );
+        }
+    }
+}
+
+/// Every share [`init`] bound, in bind order. Leaked and never removed, so an
+/// index names the same share for the whole boot.
+static CHANNELS: Lock<Vec<&'static Lock<Channel>>> = Lock::new(Vec::new());
+
+/// The `index`-th share, or `None` if there is no such share.
+pub fn open(index: usize) -> Option<&'static Lock<Channel>> {
+    CHANNELS.lock().get(index).copied()
+}
+
+/// The mount tag from configuration space, or `None` for a device that gives
+/// none this driver can use as a name.
+fn tag_of(config: Mmio, pci_dev: &PciDevice) -> Option<String> {
+    let len = Untrusted::new(config.read_u16(CFG_TAG_LEN));
+    let len = match len.at_most(MAX_TAG) {
+        Ok(0) | Err(_) => {
+            log!("virtio-9p: NOT BOUND at PCI {:02x}:{:02x}.{} — its mount tag is {len} bytes, \
+                  and a share is named by a tag of 1 to {MAX_TAG}",
+                pci_dev.bus, pci_dev.dev, pci_dev.func);
+            return None;
+        }
+        Ok(len) => len,
+    };
+    let bytes: Vec<u8> = (0..len).map(|i| config.read_u8(CFG_TAG + i)).collect();
+    match String::from_utf8(bytes) {
+        Ok(tag) => Some(tag),
+        Err(_) => {
+            log!("virtio-9p: NOT BOUND at PCI {:02x}:{:02x}.{} — its mount tag is not UTF-8",
+                pci_dev.bus, pci_dev.dev, pci_dev.func);
+            None
+        }
+    }
+}
+
+fn bind(pci_dev: &PciDevice, index: usize, dma: Dma<'static>) -> Option<Channel> {
+    log!("virtio-9p: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+    let device = VirtioDevice::init(pci_dev, VIRTIO_F_VERSION_1 | VIRTIO_9P_F_MOUNT_TAG);
+    if device.features() & VIRTIO_9P_F_MOUNT_TAG == 0 {
+        log!("virtio-9p: NOT BOUND at PCI {:02x}:{:02x}.{} — it offers no mount tag, and a share \
+              with no name has nowhere to be mounted",
+            pci_dev.bus, pci_dev.dev, pci_dev.func);
+        return None;
+    }
+    let tag = tag_of(device.device_config(), pci_dev)?;
+
+    let mut vq = Virtqueue::new(dma.subview(OFF_QUEUE, 0x1000), QUEUE_SIZE);
+    device.setup_queue(QUEUE, &mut vq);
+    device.enable_queue(QUEUE);
+    device.activate();
+    let slot = vq.initial_slots().into_iter().next();
+
+    log!("virtio-9p: share {index} tagged {tag:?}, msize {MSIZE}");
+    Some(Channel {
+        index,
+        tag,
+        notify: device.notify_mmio(),
+        notify_mult: device.notify_off_multiplier(),
+        device,
+        vq,
+        dma,
+        slot,
+        failed: false,
+    })
+}
+
+/// Bind every virtio-9p function, and answer how many shares are now served.
+///
+/// No function is a configuration and not a failure: a machine booted without
+/// `-virtfs` — every machine that is not a developer's — says nothing here.
+pub fn init(devices: &[PciDevice]) -> usize {
+    let found: Vec<PciDevice> = devices
+        .iter()
+        .filter(|d| d.is_id(VIRTIO_VENDOR, VIRTIO_9P_DEVICE) || d.is_id(VIRTIO_VENDOR, VIRTIO_9P_TRANSITIONAL))
+        .copied()
+        .collect();
+    if found.is_empty() {
+        return 0;
+    }
+    if found.len() > MAX_CHANNELS {
+        log!("virtio-9p: {} shares on this machine and this driver serves {MAX_CHANNELS}; the \
+              rest are not bound", found.len());
+    }
+    // One pool for every share, carved into windows of 132 KiB; leaked for the
+    // reason `virtio_blk`'s is.
+    let pool = DmaPool::alloc(MAX_CHANNELS * WINDOW).leak();
+    let mut channels = CHANNELS.lock();
+    for pci_dev in found.iter().take(MAX_CHANNELS) {
+        let index = channels.len();
+        let window = pool.subview(index * WINDOW, WINDOW);
+        if let Some(channel) = bind(pci_dev, index, window) {
+            channels.push(Box::leak(Box::new(Lock::new(channel))));
+            crate::suspend::forbid("virtio-9p", "a reset forgets every fid the mounted share holds");
+        }
+    }
+    channels.len()
+}
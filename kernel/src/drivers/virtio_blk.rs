@@ -0,0 +1,614 @@
+//! virtio-blk, as a [`BlockDevice`].
+//!
+//! The disk QEMU gives a guest for `-drive if=virtio`, and the one every cloud
+//! hypervisor gives it by default: a guest that can only drive NVMe boots those
+//! machines with `/home` on a tmpfs. Built on the same [`Virtqueue`] the GPU,
+//! the NIC, sound and the console use, over one request queue per disk.
+//!
+//! **One request outstanding per disk**, which is a property of this driver and
+//! not of the protocol — the same choice `nvme` makes, for the same reason: the
+//! completion at the head of the used ring is then always the request the caller
+//! is waiting on, and a disk is served under its own lock, so two disks still
+//! overlap with each other.
+//!
+//! # Many disks, one shape
+//!
+//! Disks are numbered in PCI order as [`init`] binds them, and a number names
+//! the same disk for the whole boot. What a caller holds is a handle —
+//! [`open`] — and not the disk: the queue, the DMA window and the failed latch
+//! live here, behind one lock per disk, so the page cache can own one handle
+//! while a mount probes another without two copies of a queue's state. That is
+//! the shape `usb_storage` already has over `xhci`, and the callers that walk
+//! disks walk both the same way.
+//!
+//! # Bounds
+//!
+//! The two bounds are `nvme`'s, and its module header is the argument for both.
+//! [`COMMAND`] bounds one request and is reached only by a device that stopped
+//! answering; [`crate::block::OPERATION`] bounds the call, is established in the
+//! trait methods below, and is read between requests and never inside one.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use super::pci::PciDevice;
+use super::virtio::{BufDir, DescSlot, Virtqueue, VirtioDevice, VIRTIO_F_VERSION_1};
+use super::DmaPool;
+use crate::block::{self, BlockDevice, BlockError, BlockResult, DeviceId};
+use crate::log;
+use crate::mm::{Dma, Mmio};
+use crate::scheduler::Operation;
+use crate::sync::Lock;
+use crate::time::{Budget, Deadline, Duration};
+
+const VIRTIO_VENDOR: u16 = 0x1AF4;
+/// The modern function: 0x1040 + device type 2.
+const VIRTIO_BLK_DEVICE: u16 = 0x1042;
+/// The transitional function, which is what `-drive if=virtio` builds on a q35
+/// root bus. It carries the modern capabilities as well as the legacy BAR, and
+/// [`VirtioDevice::init`] speaks only the former, so it is the same device here.
+const VIRTIO_BLK_TRANSITIONAL: u16 = 0x1001;
+
+const VIRTIO_BLK_F_RO: u64 = 1 << 5;
+const VIRTIO_BLK_F_BLK_SIZE: u64 = 1 << 6;
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+const VIRTIO_BLK_F_DISCARD: u64 = 1 << 13;
+const VIRTIO_BLK_F_WRITE_ZEROES: u64 = 1 << 14;
+
+// Device configuration (virtio 1.2 §5.2.4), byte offsets.
+const CFG_CAPACITY: u64 = 0;
+const CFG_BLK_SIZE: u64 = 20;
+const CFG_MAX_DISCARD_SECTORS: u64 = 36;
+const CFG_MAX_WRITE_ZEROES_SECTORS: u64 = 48;
+
+const REQ_IN: u32 = 0;
+const REQ_OUT: u32 = 1;
+const REQ_FLUSH: u32 = 4;
+const REQ_DISCARD: u32 = 11;
+const REQ_WRITE_ZEROES: u32 = 13;
+
+const STATUS_OK: u8 = 0;
+/// Written into the status byte before every request, and a value the device
+/// never writes (0, 1 and 2 are the protocol's). A completion that leaves it
+/// in place is a device that answered without saying how it went.
+const STATUS_UNWRITTEN: u8 = 0xFF;
+
+/// The unit every request addresses in, whatever the disk's logical block size
+/// (virtio 1.2 §5.2.6): `sector` fields and `capacity` both count 512 bytes.
+const SECTOR: u64 = 512;
+const SECTORS_PER_BLOCK: u64 = 4096 / SECTOR;
+
+/// Disks this driver binds. USB's ids start eight above this driver's, so
+/// this is a ceiling on ids as well as on DMA: a ninth disk is refused by name
+/// rather than given a number that names a USB stick.
+const MAX_DISKS: usize = 8;
+
+/// Where virtio disks start in the [`DeviceId`] space: after NVMe's 1 and
+/// AHCI's 2 to 7, and before USB's 16.
+const VIRTIO_BLK_DEVICE_ID_BASE: DeviceId = 8;
+
+const QUEUE: u16 = 0;
+const QUEUE_SIZE: u16 = 16;
+
+// Per-disk DMA window (byte offsets). The queue has a page to itself because
+// `Virtqueue::new` zeroes the whole view it is given.
+const OFF_QUEUE: usize = 0x0000;
+const OFF_HEADER: usize = 0x1000;
+const OFF_STATUS: usize = 0x1010;
+const OFF_SEGMENT: usize = 0x1020;
+const OFF_DATA: usize = 0x2000;
+const MAX_DATA_PAGES: usize = 32;
+const WINDOW: usize = OFF_DATA + MAX_DATA_PAGES * 0x1000;
+
+/// How long one request may spend in the device before this driver stops
+/// believing a completion is coming.
+///
+/// `nvme::COMMAND`'s number and `nvme::COMMAND`'s derivation: it is the term
+/// [`crate::block::OPERATION`] already spends on the command in flight, and a
+/// request to a host-backed disk completes in microseconds, so nothing but a
+/// device that stopped answering reaches it.
+///
+/// **Its expiry ends this disk.** A request this driver stops waiting for still
+/// names the disk's DMA window and still owns its descriptors; there is no
+/// queue reset here to take either back, so the disk is abandoned with it.
+const COMMAND: Budget = Budget::of(
+    Duration::from_secs(2),
+    "the request is abandoned, the disk is marked failed, and every later \
+     operation on it is refused",
+);
+
+/// The request header every chain starts with (virtio 1.2 §5.2.6).
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RequestHeader {
+    kind: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+/// One range of a discard or write-zeroes request (virtio 1.2 §5.2.6.2).
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Segment {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
+/// Why a request produced no status this driver may use.
+enum Unanswered {
+    /// The device answered with a status other than OK.
+    Status(u8),
+    /// The used ring held an element this queue refused, or the device did not
+    /// answer inside [`COMMAND`]. Both leave the request's slot with the
+    /// device, which is what ends the disk.
+    Silent,
+}
+
+impl core::fmt::Display for Unanswered {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Status(status) => write!(f, "status {status}"),
+            Self::Silent => write!(f, "no completion in {}", COMMAND.duration()),
+        }
+    }
+}
+
+/// One bound disk: its queue and DMA window, and what it reported about itself.
+struct VirtioDisk {
+    index: usize,
+    device: VirtioDevice,
+    vq: Virtqueue<'static>,
+    notify: Mmio,
+    notify_mult: u32,
+    dma: Dma<'static>,
+    /// The one descriptor slot this disk's requests are built at. `None` once a
+    /// request has been abandoned: the device still owns the chain.
+    slot: Option<DescSlot>,
+    /// Capacity in 4 KiB blocks; a trailing partial block is not served.
+    blocks: u64,
+    lba_bytes: u32,
+    max_discard_sectors: u32,
+    max_write_zeroes_sectors: u32,
+    /// Whether a request has been abandoned on this disk, which turns every
+    /// later one into a silent refusal — `nvme`'s latch, for `nvme`'s reason.
+    failed: bool,
+}
+
+impl VirtioDisk {
+    fn has(&self, feature: u64) -> bool {
+        self.device.features() & feature != 0
+    }
+
+    /// Whether a request may be issued at all: the disk still has its queue and
+    /// the caller's budget has something left. Read between requests, never
+    /// inside one — see `NvmeController::may_issue`.
+    fn may_issue(&self, until: Deadline, op: &str, sector: u64, sectors: u64) -> bool {
+        if self.failed {
+            return false;
+        }
+        if until.reached(crate::clock::now()) {
+            log!(
+                "virtio-blk: {op} of {sectors} sectors at {sector} on disk {} not issued: {}",
+                self.index,
+                block::OPERATION
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Build one request, hand it to the device, and wait for its status.
+    ///
+    /// `payload` is the middle descriptor — the data window or the segment —
+    /// and is absent for a flush. Chains are built at the one slot this disk
+    /// has, so the element the used ring returns can only be this request's.
+    fn request(&mut self, kind: u32, sector: u64, payload: Option<(usize, u32, BufDir)>)
+        -> Result<(), Unanswered>
+    {
+        let Some(slot) = self.slot.take() else {
+            return Err(Unanswered::Silent);
+        };
+        let dma = self.dma;
+        dma.write(OFF_HEADER, RequestHeader { kind, reserved: 0, sector });
+        dma.write(OFF_STATUS, STATUS_UNWRITTEN);
+
+        let header = (dma.phys() + OFF_HEADER as u64, 16, BufDir::Readable);
+        let status = (dma.phys() + OFF_STATUS as u64, 1, BufDir::Writable);
+        match payload {
+            Some((off, len, dir)) => {
+                let data = (dma.phys() + off as u64, len, dir);
+                self.vq.submit(slot, &[header, data, status], self.notify, self.notify_mult, QUEUE);
+            }
+            None => {
+                self.vq.submit(slot, &[header, status], self.notify, self.notify_mult, QUEUE);
+            }
+        }
+
+        // `has_used` and not `poll_used` inside the spin: `settles` takes a
+        // predicate, and the element is consumed once, after it has decided.
+        let vq = &self.vq;
+        let answered = crate::clock::settles(COMMAND.nanos(), || vq.has_used());
+        let returned = if answered { self.vq.poll_used() } else { None };
+        let Some((slot, _)) = returned else {
+            self.abandon();
+            return Err(Unanswered::Silent);
+        };
+        self.slot = Some(slot);
+        match dma.read::<u8>(OFF_STATUS) {
+            STATUS_OK => Ok(()),
+            other => Err(Unanswered::Status(other)),
+        }
+    }
+
+    /// Give a negotiated device its queue and take the one slot back. Shared by
+    /// [`bind`] and [`resume`]: after a sleep the queue is the same page and
+    /// the slot the same descriptor, and the device remembers neither.
+    fn start(&mut self) {
+        let slots = self.vq.restart();
+        self.device.setup_queue(QUEUE, &mut self.vq);
+        self.device.enable_queue(QUEUE);
+        self.device.activate();
+        self.slot = slots.into_iter().next();
+    }
+
+    /// A request nobody answered ends this disk, once and loudly.
+    fn abandon(&mut self) {
+        if !self.failed {
+            self.failed = true;
+            log!(
+                "virtio-blk: disk {} is offline: the request it did not answer still owns its \
+                 descriptors and its DMA window, and this driver has no queue reset to take \
+                 either back",
+                self.index
+            );
+        }
+    }
+
+    fn read(&mut self, block: u64, count: u32, buf: &mut [u8], until: Deadline) -> BlockResult {
+        let bytes = count as usize * 4096;
+        assert!(buf.len() >= bytes && count as usize <= MAX_DATA_PAGES);
+        let sector = block * SECTORS_PER_BLOCK;
+        let sectors = count as u64 * SECTORS_PER_BLOCK;
+        if !self.may_issue(until, "read", sector, sectors) {
+            return Err(BlockError);
+        }
+        if let Err(why) = self.request(REQ_IN, sector, Some((OFF_DATA, bytes as u32, BufDir::Writable))) {
+            log!("virtio-blk: read of {sectors} sectors at {sector} on disk {}: {why}", self.index);
+            return Err(BlockError);
+        }
+        // A copy out, after the device has answered, so nothing holds a
+        // reference into a window it may write again.
+        self.dma.copy_to(OFF_DATA, &mut buf[..bytes]);
+        Ok(())
+    }
+
+    fn write(&mut self, block: u64, count: u32, buf: &[u8], until: Deadline) -> BlockResult {
+        let bytes = count as usize * 4096;
+        assert!(buf.len() >= bytes && count as usize <= MAX_DATA_PAGES);
+        let sector = block * SECTORS_PER_BLOCK;
+        let sectors = count as u64 * SECTORS_PER_BLOCK;
+        if !self.may_issue(until, "write", sector, sectors) {
+            return Err(BlockError);
+        }
+        // Exclusive: the request naming this window has not been submitted.
+        self.dma.copy_from(OFF_DATA, &buf[..bytes]);
+        if let Err(why) = self.request(REQ_OUT, sector, Some((OFF_DATA, bytes as u32, BufDir::Readable))) {
+            log!("virtio-blk: write of {sectors} sectors at {sector} on disk {}: {why}", self.index);
+            return Err(BlockError);
+        }
+        Ok(())
+    }
+
+    /// One discard or write-zeroes range, already clipped to what the device
+    /// takes in one segment.
+    fn ranged(&mut self, kind: u32, op: &str, sector: u64, sectors: u32, until: Deadline) -> BlockResult {
+        if !self.may_issue(until, op, sector, sectors as u64) {
+            return Err(BlockError);
+        }
+        self.dma.write(OFF_SEGMENT, Segment { sector, num_sectors: sectors, flags: 0 });
+        let segment = (OFF_SEGMENT, core::mem::size_of::<Segment>() as u32, BufDir::Readable);
+        if let Err(why) = self.request(kind, 0, Some(segment)) {
+            log!("virtio-blk: {op} of {sectors} sectors at {sector} on disk {}: {why}", self.index);
+            return Err(BlockError);
+        }
+        Ok(())
+    }
+
+    /// Walk `count` blocks from `block` in ranges of at most `max` sectors,
+    /// rounded down to whole blocks so no range splits one.
+    fn ranges(&mut self, kind: u32, op: &str, block: u64, count: u32, max: u32, until: Deadline) -> BlockResult {
+        let per = (max as u64 / SECTORS_PER_BLOCK).max(1);
+        let mut at = block;
+        let end = block + count as u64;
+        while at < end {
+            let n = per.min(end - at);
+            // Exact: `n <= max / 8`, and `max` is a `u32`, so `n * 8` fits.
+            self.ranged(kind, op, at * SECTORS_PER_BLOCK, (n * SECTORS_PER_BLOCK) as u32, until)?;
+            at += n;
+        }
+        Ok(())
+    }
+}
+
+/// Every disk [`init`] bound, in bind order. An entry is leaked and never
+/// removed, so an index names the same disk for the whole boot.
+static DISKS: Lock<Vec<&'static Lock<VirtioDisk>>> = Lock::new(Vec::new());
+
+/// Disks bound this boot, so `0..count()` names every one.
+pub fn count() -> usize {
+    DISKS.lock().len()
+}
+
+/// A handle to the `index`-th disk, or `None` if there is no such disk.
+pub fn open(index: usize) -> Option<VirtioBlockDevice> {
+    let disk = *DISKS.lock().get(index)?;
+    // Copied out once, so a handle answers its geometry without the disk's
+    // lock — the page cache asks for it holding its own.
+    let (blocks, lba_bytes) = {
+        let d = disk.lock();
+        (d.blocks, d.lba_bytes)
+    };
+    Some(VirtioBlockDevice {
+        disk,
+        index,
+        id: VIRTIO_BLK_DEVICE_ID_BASE + index as DeviceId,
+        blocks,
+        lba_bytes,
+    })
+}
+
+pub struct VirtioBlockDevice {
+    disk: &'static Lock<VirtioDisk>,
+    index: usize,
+    id: DeviceId,
+    blocks: u64,
+    lba_bytes: u32,
+}
+
+impl VirtioBlockDevice {
+    /// The disk's own logical block size, for the one caller that speaks in
+    /// it: a GPT is laid out in these and not in 4 KiB blocks.
+    pub fn logical_block_bytes(&self) -> u32 {
+        self.lba_bytes
+    }
+
+    /// Make `count` blocks from `lba` read back as zeroes. Not a hint, so a
+    /// disk without the request gets the zeroes written the long way.
+    #[allow(dead_code)] // nothing above the driver frees blocks yet
+    pub fn write_zeroes(&mut self, lba: u64, count: u32) -> BlockResult {
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        if disk.has(VIRTIO_BLK_F_WRITE_ZEROES) {
+            let max = disk.max_write_zeroes_sectors;
+            return disk.ranges(REQ_WRITE_ZEROES, "write-zeroes", lba, count, max, until);
+        }
+        let zeroes = [0u8; 4096];
+        for block in lba..lba + count as u64 {
+            disk.write(block, 1, &zeroes, until)?;
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for VirtioBlockDevice {
+    fn device_id(&self) -> DeviceId {
+        self.id
+    }
+
+    fn block_count(&self) -> u64 {
+        self.blocks
+    }
+
+    /// The guard is a `let _op` for the reason `NvmeBlockDevice::read_blocks`
+    /// gives, and the deadline is read after it so an inner establishment only
+    /// narrows.
+    fn read_blocks(&mut self, lba: u64, count: u32, buf: &mut [u8]) -> BlockResult {
+        assert_eq!(buf.len(), count as usize * 4096);
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        let mut done = 0u32;
+        while done < count {
+            let batch = (count - done).min(MAX_DATA_PAGES as u32);
+            let at = done as usize * 4096;
+            disk.read(lba + done as u64, batch, &mut buf[at..at + batch as usize * 4096], until)?;
+            done += batch;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, count: u32, buf: &[u8]) -> BlockResult {
+        assert_eq!(buf.len(), count as usize * 4096);
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        if disk.has(VIRTIO_BLK_F_RO) {
+            log!("virtio-blk: write of {count} blocks at {lba} refused: disk {} is read-only", self.index);
+            return Err(BlockError);
+        }
+        let mut done = 0u32;
+        while done < count {
+            let batch = (count - done).min(MAX_DATA_PAGES as u32);
+            let at = done as usize * 4096;
+            disk.write(lba + done as u64, batch, &buf[at..at + batch as usize * 4096], until)?;
+            done += batch;
+        }
+        Ok(())
+    }
+
+    /// A disk that did not offer `VIRTIO_BLK_F_FLUSH` has no volatile cache to
+    /// flush (virtio 1.2 §5.2.6.2: it is write-through), so a completed write
+    /// is already durable and there is nothing to issue. An abandoned disk is
+    /// the exception for `nvme`'s reason: the writes this would have made
+    /// durable are the ones that never completed.
+    fn flush(&mut self) -> BlockResult {
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        if disk.failed {
+            return Err(BlockError);
+        }
+        if !disk.has(VIRTIO_BLK_F_FLUSH) {
+            return Ok(());
+        }
+        if !disk.may_issue(until, "flush", 0, 0) {
+            return Err(BlockError);
+        }
+        if let Err(why) = disk.request(REQ_FLUSH, 0, None) {
+            log!("virtio-blk: cache flush failed on disk {}: {why}", self.index);
+            return Err(BlockError);
+        }
+        Ok(())
+    }
+
+    /// A disk that did not offer discard answers `Ok` having been told
+    /// nothing, which is what it would have done with the hint anyway.
+    fn discard(&mut self, lba: u64, count: u32) -> BlockResult {
+        let _op = block::begin_operation();
+        let until = Operation::deadline();
+        let mut disk = self.disk.lock();
+        if !disk.has(VIRTIO_BLK_F_DISCARD) {
+            return Ok(());
+        }
+        let max = disk.max_discard_sectors;
+        disk.ranges(REQ_DISCARD, "discard", lba, count, max, until)
+    }
+}
+
+/// Bind every virtio-blk function on the machine, up to [`MAX_DISKS`], and
+/// answer how many are now served.
+///
+/// No function is a configuration and not a failure, exactly as for NVMe. A
+/// function this driver cannot serve is named in the log and skipped; the
+/// numbers are handed out to the disks that *were* bound, so they stay dense.
+pub fn init(devices: &[PciDevice]) -> usize {
+    let found: Vec<PciDevice> = devices
+        .iter()
+        .filter(|d| d.is_id(VIRTIO_VENDOR, VIRTIO_BLK_DEVICE) || d.is_id(VIRTIO_VENDOR, VIRTIO_BLK_TRANSITIONAL))
+        .copied()
+        .collect();
+    if found.is_empty() {
+        return 0;
+    }
+    if found.len() > MAX_DISKS {
+        log!("virtio-blk: {} disks on this machine and this driver serves {MAX_DISKS}; the rest \
+              are not bound", found.len());
+    }
+    // One pool for every disk, carved into windows: each is 136 KiB, and
+    // `DmaPool` hands out 2 MiB at a time. Leaked for the reason `nvme`'s is.
+    let pool = DmaPool::alloc(MAX_DISKS * WINDOW).leak();
+    let mut disks = DISKS.lock();
+    for pci_dev in found.iter().take(MAX_DISKS) {
+        let index = disks.len();
+        let window = pool.subview(index * WINDOW, WINDOW);
+        if let Some(disk) = bind(pci_dev, index, window) {
+            disks.push(Box::leak(Box::new(Lock::new(disk))));
+        }
+    }
+    disks.len()
+}
+
+fn bind(pci_dev: &PciDevice, index: usize, dma: Dma<'static>) -> Option<VirtioDisk> {
+    log!("virtio-blk: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+    let device = VirtioDevice::init(
+        pci_dev,
+        VIRTIO_F_VERSION_1
+            | VIRTIO_BLK_F_RO
+            | VIRTIO_BLK_F_BLK_SIZE
+            | VIRTIO_BLK_F_FLUSH
+            | VIRTIO_BLK_F_DISCARD
+            | VIRTIO_BLK_F_WRITE_ZEROES,
+    );
+    let features = device.features();
+    let config = device.device_config();
+
+    // The same window `nvme` refuses outside of, for the same reason: every
+    // path above here is written in 4096-byte blocks and needs the disk's own
+    // block to divide one. Refused rather than asserted — this is one disk of
+    // possibly several, and the others are still worth binding.
+    let lba_bytes = if features & VIRTIO_BLK_F_BLK_SIZE != 0 {
+        config.read_u32(CFG_BLK_SIZE)
+    } else {
+        SECTOR as u32
+    };
+    if !lba_bytes.is_power_of_two() || !(512..=4096).contains(&lba_bytes) {
+        log!("virtio-blk: NOT BOUND at PCI {:02x}:{:02x}.{} — it reports {lba_bytes}-byte blocks, \
+              and this driver serves 4096-byte blocks and needs 512..=4096",
+            pci_dev.bus, pci_dev.dev, pci_dev.func);
+        return None;
+    }
+    let capacity = config.read_u64(CFG_CAPACITY);
+    let max_discard_sectors = config.read_u32(CFG_MAX_DISCARD_SECTORS);
+    let max_write_zeroes_sectors = config.read_u32(CFG_MAX_WRITE_ZEROES_SECTORS);
+
+    let vq = Virtqueue::new(dma.subview(OFF_QUEUE, 0x1000), QUEUE_SIZE);
+
+    let blocks = capacity / SECTORS_PER_BLOCK;
+    log!(
+        "virtio-blk: disk {index} id={} blocks={} ({}MB){}{}{}",
+        VIRTIO_BLK_DEVICE_ID_BASE + index as DeviceId,
+        blocks,
+        blocks * 4096 / (1024 * 1024),
+        if features & VIRTIO_BLK_F_RO != 0 { ", read-only" } else { "" },
+        if features & VIRTIO_BLK_F_FLUSH != 0 { ", write cache" } else { "" },
+        if features & VIRTIO_BLK_F_DISCARD != 0 { ", discard" } else { "" },
+    );
+    let mut disk = VirtioDisk {
+        index,
+        notify: device.notify_mmio(),
+        notify_mult: device.notify_off_multiplier(),
+        device,
+        vq,
+        dma,
+        slot: None,
+        blocks,
+        lba_bytes,
+        max_discard_sectors: if max_discard_sectors == 0 { u32::MAX } else { max_discard_sectors },
+        max_write_zeroes_sectors: if max_write_zeroes_sectors == 0 { u32::MAX } else { max_write_zeroes_sectors },
+        failed: false,
+    };
+    disk.start();
+    Some(disk)
+}
+
+/// Every disk's write cache flushed and its device reset, ahead of S3.
+///
+/// The flush is the one request that has to be made here and not left to
+/// `suspend::go_down`'s sync: the sync returns once each write completed, and
+/// a disk with `VIRTIO_BLK_F_FLUSH` may hold a completed write in a cache the
+/// power loss empties. A flush that fails is logged and the disk is reset all
+/// the same — there is no staying awake for one disk.
+pub fn suspend() {
+    for disk in DISKS.lock().iter() {
+        let mut disk = disk.lock();
+        if !disk.failed && disk.has(VIRTIO_BLK_F_FLUSH) {
+            if let Err(why) = disk.request(REQ_FLUSH, 0, None) {
+                log!("virtio-blk: cache flush before the sleep failed on disk {}: {why}", disk.index);
+            }
+        }
+        disk.device.reset();
+    }
+}
+
+/// Every disk back on the queue it had, so the `BlockDevice` handles the page
+/// cache holds go on naming the same disks.
+///
+/// A disk that was abandoned before the sleep stays abandoned: the failure
+/// said its contents could not be vouched for, and a reset does not change
+/// that. One that does not take its features back is abandoned here.
+pub fn resume() {
+    for disk in DISKS.lock().iter() {
+        let mut disk = disk.lock();
+        if disk.failed {
+            continue;
+        }
+        if !disk.device.restart() {
+            disk.failed = true;
+            log!("virtio-blk: disk {} is offline: it did not come back from the sleep", disk.index);
+            continue;
+        }
+        disk.start();
+    }
+}
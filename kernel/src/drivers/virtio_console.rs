@@ -16,6 +16,7 @@ use core::mem::MaybeUninit;
 use core::sync::atomic::{AtomicBool, Ordering};
 
 use super::pci::PciDevice;
+use super::serial::BackendGuard;
 use super::virtio::{BufDir, DescSlot, Virtqueue, VirtioDevice, VIRTIO_F_VERSION_1};
 use super::DmaPool;
 use crate::log;
@@ -88,6 +89,7 @@ unsafe impl Sync for ConsoleCell {}
 /// `try_read_byte_locked`, and `has_data_locked`, all of which require the
 /// caller to be holding `serial::BackendGuard` with interrupts disabled — that
 /// outer lock is what serializes concurrent access to the VConsole state.
+/// `suspend` and `resume` take the same guard themselves.
 static CONSOLE: ConsoleCell = ConsoleCell(UnsafeCell::new(MaybeUninit::uninit()));
 static READY: AtomicBool = AtomicBool::new(false);
 
@@ -202,6 +204,63 @@ pub fn has_data_locked() -> bool {
     with_console(|c| c.rx_pending.is_some() || c.rx.has_used()).unwrap_or(false)
 }
 
+/// Give a negotiated device both queues, take the TX slot and post every RX
+/// buffer. Shared by [`init`] and [`resume`], which is why the queues are
+/// restarted rather than assumed fresh: the resume has rings the device
+/// forgot and slots the driver still thinks are out.
+fn start(c: &mut VConsole) {
+    let mut rx_slots = c.rx.restart();
+    let mut tx_slots = c.tx.restart();
+    c.device.setup_queue(0, &mut c.rx);
+    c.device.setup_queue(1, &mut c.tx);
+    c.device.enable_queue(0);
+    c.device.enable_queue(1);
+    c.device.activate();
+
+    c.tx_slot = Some(tx_slots.pop().expect("vconsole: no tx slots"));
+    c.rx_pending = None;
+    for i in 0..RX_BUF_COUNT {
+        let slot = rx_slots.pop().expect("vconsole: not enough rx slots");
+        refill_rx(c, i, slot);
+    }
+}
+
+/// Reset the device on the way into S3.
+///
+/// Not in `suspend::quiesce` with the others, and after the last line the
+/// sleep logs: on a machine where this is the log backend, "S3: suspending"
+/// is written through it, and a reset console would have that write spin on a
+/// completion that never comes.
+pub fn suspend() {
+    let _guard = BackendGuard::lock();
+    with_console(|c| c.device.reset());
+}
+
+/// Bring the console back after S3, first of the devices behind the IOMMU:
+/// every log line from here on may be drained through it.
+///
+/// A byte typed while the machine slept, or one half-read out of a buffer,
+/// is gone with the queue it sat in. A device that does not take its
+/// features back is dropped as the backend, and the log goes back to the
+/// UART it used before this driver bound.
+pub fn resume() {
+    let back = {
+        let _guard = BackendGuard::lock();
+        with_console(|c| {
+            let back = c.device.restart();
+            if back {
+                start(c);
+            }
+            back
+        })
+    };
+    if back == Some(false) {
+        disable();
+        crate::drivers::serial::console_changed();
+        log!("virtio-console: did not come back from the sleep; the log is on the UART");
+    }
+}
+
 pub fn init(devices: &[PciDevice]) -> bool {
     let pci_dev = match devices.iter().find(|d| d.is_id(VIRTIO_VENDOR, VIRTIO_CONSOLE_DEVICE)) {
         Some(d) => *d,
@@ -219,14 +278,8 @@ pub fn init(devices: &[PciDevice]) -> bool {
 
     let device = VirtioDevice::init(&pci_dev, VIRTIO_F_VERSION_1);
 
-    let mut rx = Virtqueue::new(dma.subview(OFF_RXVQ, 0x1000), QUEUE_SIZE);
-    let mut tx = Virtqueue::new(dma.subview(OFF_TXVQ, 0x1000), QUEUE_SIZE);
-
-    device.setup_queue(0, &mut rx);
-    device.setup_queue(1, &mut tx);
-    device.enable_queue(0);
-    device.enable_queue(1);
-    device.activate();
+    let rx = Virtqueue::new(dma.subview(OFF_RXVQ, 0x1000), QUEUE_SIZE);
+    let tx = Virtqueue::new(dma.subview(OFF_TXVQ, 0x1000), QUEUE_SIZE);
 
     let tx_buf = dma.subview(OFF_TX_BUF, TX_BUF_SIZE);
 
@@ -234,26 +287,15 @@ pub fn init(devices: &[PciDevice]) -> bool {
         dma.subview(OFF_RX_BUFS + i * RX_BUF_SIZE as usize, RX_BUF_SIZE as usize).unaligned()
     });
 
-    let mut tx_slots = tx.initial_slots();
-    let tx_slot = tx_slots.pop().expect("vconsole: no tx slots");
-    drop(tx_slots);
-
-    let mut rx_slots = rx.initial_slots();
-
     let mut console = VConsole {
         device, rx, tx,
         tx_buf,
-        tx_slot: Some(tx_slot),
+        tx_slot: None,
         rx_bufs,
         desc_to_rx: [0; QUEUE_SIZE as usize],
         rx_pending: None,
     };
-
-    for i in 0..RX_BUF_COUNT {
-        let slot = rx_slots.pop().expect("vconsole: not enough rx slots");
-        refill_rx(&mut console, i, slot);
-    }
-    drop(rx_slots);
+    start(&mut console);
 
     // SAFETY: irreducible — this is the write that initialises the
     // `MaybeUninit` payload of a `static`, and there is no safe form of it
@@ -1,18 +1,22 @@
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 
 use super::pci::PciDevice;
 use super::virtio::{BufDir, DescSlot, Virtqueue, VirtioDevice, VIRTIO_F_VERSION_1};
 use super::DmaPool;
 use toyos_abi::syscall::SyscallError;
+use toyos_abi::{Output, MAX_OUTPUTS};
 use crate::mm::{Dma, Unaligned, PAGE_2M};
 use crate::gpu::{FLAG_HARDWARE_CURSOR, Gpu, GpuInfo};
 use crate::log;
 use crate::mm::paging::CachePolicy;
 use crate::object::shm::{Pages, Region};
+use toyos_edid::Edid;
 
 const VIRTIO_VENDOR: u16 = 0x1AF4;
 const VIRTIO_GPU_DEVICE: u16 = 0x1050; // 0x1040 + device_id 16
 
+const CMD_GET_DISPLAY_INFO: u32 = 0x0100;
 const CMD_RESOURCE_CREATE_2D: u32 = 0x0101;
 const CMD_RESOURCE_UNREF: u32 = 0x0102;
 const CMD_SET_SCANOUT: u32 = 0x0103;
@@ -24,6 +28,7 @@ const CMD_UPDATE_CURSOR: u32 = 0x0300;
 const CMD_MOVE_CURSOR: u32 = 0x0301;
 
 const RESP_OK_NODATA: u32 = 0x1100;
+const RESP_OK_DISPLAY_INFO: u32 = 0x1101;
 const RESP_OK_EDID: u32 = 0x1104;
 
 const VIRTIO_GPU_F_EDID: u64 = 1 << 1;
@@ -41,6 +46,9 @@ const DMA_SIZE: usize           = 0x4000;
 const CURSOR_SIZE: u32 = 64;
 const CURSOR_RESOURCE_ID: u32 = 3;
 
+/// What an output runs at when its EDID offers nothing better.
+const DEFAULT_MODE: (u32, u32) = (1280, 720);
+
 const REQ_OFFSET: usize = 0x000;
 const RESP_OFFSET: usize = 0x800;
 
@@ -70,6 +78,23 @@ struct Rect {
     height: u32,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DisplayOne {
+    r: Rect,
+    enabled: u32,
+    flags: u32,
+}
+
+/// GET_DISPLAY_INFO's answer: one entry per scanout the specification allows,
+/// whether or not the device has that many.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RespDisplayInfo {
+    hdr: CtrlHeader,
+    pmodes: [DisplayOne; MAX_OUTPUTS],
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 struct ResourceCreate2d {
@@ -182,6 +207,24 @@ struct FbAlloc {
     phys_addrs: [u64; 2],
 }
 
+/// One monitor: the scanout the device numbers it by, the rectangle of the
+/// framebuffer resource it shows, and what its EDID said about it.
+#[derive(Clone, Copy)]
+struct Head {
+    scanout: u32,
+    place: Output,
+    /// Read once at `init`: QEMU's EDID for a scanout is fixed by its command
+    /// line, and a monitor that would not parse stays `None` for good.
+    edid: Option<Edid>,
+}
+
+impl Head {
+    fn contains(&self, x: u32, y: u32) -> bool {
+        let p = self.place;
+        x >= p.x && x - p.x < p.width && y >= p.y && y - p.y < p.height
+    }
+}
+
 struct GpuController {
     device: VirtioDevice,
     controlq: Virtqueue<'static>,
@@ -209,11 +252,22 @@ struct GpuController {
     cursor_req: Dma<'static, Unaligned>,
     #[allow(dead_code)] // the cursor queue's answers are not read
     cursor_resp: Dma<'static>,
+    /// The framebuffer resource, which is the bounding box of `heads`.
     width: u32,
     height: u32,
+    /// The resource every head scans out of; 0 before the first is made.
     resource: u32,
     fb: FbAlloc,
+    /// Every enabled scanout, in scanout order. Never empty once `init` has
+    /// returned.
+    heads: Vec<Head>,
     cursor: Region,
+    /// Which head shows the hardware cursor, and where on it. The device draws
+    /// a cursor per scanout, so crossing from one monitor to the next is a
+    /// hide on one and a show on the other rather than a move.
+    cursor_head: usize,
+    cursor_at: (u32, u32),
+    cursor_hot: (u32, u32),
 }
 
 impl GpuController {
@@ -266,6 +320,12 @@ impl GpuController {
         hdr.cmd_type
     }
 
+    fn display_info(&mut self) -> Option<RespDisplayInfo> {
+        let cmd = CtrlHeader::new(CMD_GET_DISPLAY_INFO);
+        let resp: RespDisplayInfo = self.command_of(&cmd);
+        (resp.hdr.cmd_type == RESP_OK_DISPLAY_INFO).then_some(resp)
+    }
+
     fn get_edid(&mut self, scanout: u32) -> RespEdid {
         let cmd = GetEdid {
             hdr: CtrlHeader::new(CMD_GET_EDID),
@@ -377,11 +437,13 @@ impl GpuController {
         ));
     }
 
-    fn update_cursor(&mut self, x: u32, y: u32, hot_x: u32, hot_y: u32) {
+    /// Show the cursor image on `scanout` at `(x, y)`, or hide it there when
+    /// `resource` is 0.
+    fn update_cursor(&mut self, scanout: u32, resource: u32, x: u32, y: u32, hot_x: u32, hot_y: u32) {
         let cmd = UpdateCursor {
             hdr: CtrlHeader::new(CMD_UPDATE_CURSOR),
-            pos: CursorPos { scanout_id: 0, x, y, padding: 0 },
-            resource_id: CURSOR_RESOURCE_ID,
+            pos: CursorPos { scanout_id: scanout, x, y, padding: 0 },
+            resource_id: resource,
             hot_x,
             hot_y,
             padding: 0,
@@ -389,10 +451,10 @@ impl GpuController {
         self.cursor_command(&cmd);
     }
 
-    fn move_cursor(&mut self, x: u32, y: u32) {
+    fn move_cursor(&mut self, scanout: u32, x: u32, y: u32) {
         let cmd = UpdateCursor {
             hdr: CtrlHeader::new(CMD_MOVE_CURSOR),
-            pos: CursorPos { scanout_id: 0, x, y, padding: 0 },
+            pos: CursorPos { scanout_id: scanout, x, y, padding: 0 },
             resource_id: CURSOR_RESOURCE_ID,
             hot_x: 0,
             hot_y: 0,
@@ -401,6 +463,28 @@ impl GpuController {
         self.cursor_command(&cmd);
     }
 
+    /// Give a negotiated device both queues and take one slot on each. Shared
+    /// by [`init`] and the resume, where the slots the controller held name
+    /// descriptors the reset device has forgotten.
+    fn start(&mut self) {
+        let mut control_slots = self.controlq.restart();
+        let mut cursor_slots = self.cursorq.restart();
+        self.device.setup_queue(0, &mut self.controlq);
+        self.device.setup_queue(1, &mut self.cursorq);
+        self.device.enable_queue(0);
+        self.device.enable_queue(1);
+        self.device.activate();
+        self.control_slot = Some(control_slots.pop().expect("GPU: no control slots"));
+        self.cursor_slot = Some(cursor_slots.pop().expect("GPU: no cursor slots"));
+    }
+
+    /// The cursor resource, backed by the page the compositor draws it into.
+    fn create_cursor(&mut self) {
+        let bytes = CURSOR_SIZE * CURSOR_SIZE * 4;
+        self.create_resource(CURSOR_RESOURCE_ID, FORMAT_B8G8R8A8_UNORM, CURSOR_SIZE, CURSOR_SIZE);
+        self.attach_backing(CURSOR_RESOURCE_ID, self.cursor.phys.phys(), bytes);
+    }
+
     /// Allocate framebuffer backing stores and register as shared memory.
     /// `None` when the physical memory is not there — the caller decides
     /// whether that is fatal.
@@ -440,7 +524,112 @@ impl GpuController {
             stride: self.width,
             pixel_format: 1, // BGR (B8G8R8X8_UNORM)
             flags: FLAG_HARDWARE_CURSOR,
+            outputs: self.heads.iter().map(|h| h.place).collect(),
+        }
+    }
+
+    /// A scanout's EDID, parsed, or `None` when the device has none to give
+    /// or the one it gave is not an EDID.
+    fn read_edid(&mut self, scanout: u32) -> Option<Edid> {
+        let resp = self.get_edid(scanout);
+        if resp.hdr.cmd_type != RESP_OK_EDID {
+            return None;
+        }
+        let len = (resp.size as usize).min(resp.edid.len());
+        match Edid::parse(&resp.edid[..len]) {
+            Ok(edid) => Some(edid),
+            Err(e) => {
+                log!("VirtIO GPU: scanout {} EDID refused: {}", scanout, e);
+                None
+            }
+        }
+    }
+
+    /// Scan `heads` out of one new resource the size of their bounding box,
+    /// and retire the one they showed before.
+    ///
+    /// **One resource for every monitor** is what makes a window that spans
+    /// two of them one blit: each scanout is pointed at its own rectangle of
+    /// the same memory, and a flush of the resource updates every scanout
+    /// showing the part of it that changed. The part of the bounding box no
+    /// head covers is allocated and never seen, which costs a diagonal
+    /// arrangement memory and nothing else.
+    ///
+    /// `InvalidArgument` for a layout the device could never back and
+    /// `ResourceExhausted` when the memory is not there; either way the
+    /// previous resource is still on every panel.
+    fn scan_out(&mut self, heads: Vec<Head>) -> Result<GpuInfo, SyscallError> {
+        let width = heads.iter().map(|h| h.place.x + h.place.width).max().unwrap_or(0);
+        let height = heads.iter().map(|h| h.place.y + h.place.height).max().unwrap_or(0);
+        let fb_size = fb_size_bytes(width, height).ok_or(SyscallError::InvalidArgument)?;
+
+        // Allocate new framebuffer backing. The old pair stays live until the
+        // swap below, so a refusal here leaves the display exactly as it was.
+        let new_fb = self.alloc_framebuffer(fb_size).ok_or(SyscallError::ResourceExhausted)?;
+
+        let old_resource = self.resource;
+        self.resource = next_resource(self.resource);
+        self.create_resource(self.resource, FORMAT_B8G8R8X8_UNORM, width, height);
+        self.attach_backing(self.resource, new_fb.phys_addrs[0], fb_size);
+
+        for head in &heads {
+            let p = head.place;
+            let rect = Rect { x: p.x, y: p.y, width: p.width, height: p.height };
+            self.set_scanout(head.scanout, self.resource, rect);
+            log!(
+                "VirtIO GPU: scanout {} shows {}x{} at ({}, {})",
+                head.scanout, p.width, p.height, p.x, p.y
+            );
         }
+
+        if old_resource != 0 {
+            self.destroy_resource(old_resource);
+        }
+        // The old pages go when the last holder lets go, which may be a
+        // compositor that has not yet mapped the new ones. Nothing is revoked.
+        drop(core::mem::replace(&mut self.fb, new_fb));
+
+        self.width = width;
+        self.height = height;
+        self.heads = heads;
+        self.cursor_head = self.cursor_head.min(self.heads.len() - 1);
+
+        Ok(self.build_gpu_info())
+    }
+}
+
+/// The next framebuffer resource id after `current`, stepping over the
+/// cursor's: ids are never reused while the old resource may still be on a
+/// panel, and the second mode set used to land on `CURSOR_RESOURCE_ID` and
+/// have RESOURCE_CREATE_2D refused.
+fn next_resource(current: u32) -> u32 {
+    match current + 1 {
+        CURSOR_RESOURCE_ID => CURSOR_RESOURCE_ID + 1,
+        id => id,
+    }
+}
+
+/// The mode a monitor starts in: the one its EDID prefers.
+///
+/// QEMU's EDID reports the firmware-set resolution (often 640x480 from OVMF)
+/// rather than the host-configured one when the guest has not been given a
+/// size, so a preferred mode smaller than [`DEFAULT_MODE`] is taken as stale
+/// and the default used instead. The rest of the mode list is still offered.
+fn preferred_mode(edid: Option<&Edid>) -> (u32, u32) {
+    match edid.and_then(|e| e.preferred) {
+        Some(m) if m.width >= DEFAULT_MODE.0 && m.height >= DEFAULT_MODE.1 => (m.width, m.height),
+        _ => DEFAULT_MODE,
+    }
+}
+
+/// `heads` side by side, left to right in scanout order and top-aligned —
+/// where every monitor starts before anything says otherwise.
+fn side_by_side(heads: &mut [Head]) {
+    let mut x = 0;
+    for head in heads {
+        head.place.x = x;
+        head.place.y = 0;
+        x += head.place.width;
     }
 }
 
@@ -464,45 +653,100 @@ impl Gpu for GpuController {
     fn set_cursor(&mut self, hot_x: u32, hot_y: u32) {
         let rect = Rect { x: 0, y: 0, width: CURSOR_SIZE, height: CURSOR_SIZE };
         self.transfer_to_host(CURSOR_RESOURCE_ID, rect, 0);
-        self.update_cursor(0, 0, hot_x, hot_y);
+        self.cursor_hot = (hot_x, hot_y);
+        let scanout = self.heads[self.cursor_head].scanout;
+        let (x, y) = self.cursor_at;
+        self.update_cursor(scanout, CURSOR_RESOURCE_ID, x, y, hot_x, hot_y);
     }
 
     fn move_cursor(&mut self, x: u32, y: u32) {
-        GpuController::move_cursor(self, x, y);
+        // A point in the gap between two monitors stays on the one it was on:
+        // the compositor confines its pointer to the outputs, so this is a
+        // position it never sends and not one worth a hide.
+        let Some(head) = self.heads.iter().position(|h| h.contains(x, y)) else { return };
+        let place = self.heads[head].place;
+        let at = (x - place.x, y - place.y);
+        let scanout = self.heads[head].scanout;
+        if head != self.cursor_head {
+            let old = self.heads[self.cursor_head].scanout;
+            self.update_cursor(old, 0, 0, 0, 0, 0);
+            let (hot_x, hot_y) = self.cursor_hot;
+            self.update_cursor(scanout, CURSOR_RESOURCE_ID, at.0, at.1, hot_x, hot_y);
+            self.cursor_head = head;
+        } else {
+            GpuController::move_cursor(self, scanout, at.0, at.1);
+        }
+        self.cursor_at = at;
     }
 
+    /// The first output's mode. Every other output keeps its own, and the row
+    /// is laid out again side by side: a mode set does not know where the
+    /// user put the monitors, and [`arrange`](Gpu::arrange) is the call that
+    /// does.
     fn set_resolution(&mut self, width: u32, height: u32) -> Result<GpuInfo, SyscallError> {
-        if width == self.width && height == self.height {
+        if self.heads.len() == 1 && width == self.width && height == self.height {
             return Ok(self.build_gpu_info());
         }
-
-        let fb_size = fb_size_bytes(width, height).ok_or(SyscallError::InvalidArgument)?;
+        fb_size_bytes(width, height).ok_or(SyscallError::InvalidArgument)?;
 
         log!("VirtIO GPU: changing resolution {}x{} -> {}x{}", self.width, self.height, width, height);
+        let mut heads = self.heads.clone();
+        heads[0].place.width = width;
+        heads[0].place.height = height;
+        side_by_side(&mut heads);
+        let info = self.scan_out(heads)?;
+        log!("VirtIO GPU: resolution set to {}x{}", width, height);
+        Ok(info)
+    }
 
-        // Allocate new framebuffer backing. The old pair stays live until the
-        // swap below, so a refusal here leaves the display exactly as it was.
-        let new_fb = self.alloc_framebuffer(fb_size).ok_or(SyscallError::ResourceExhausted)?;
-
-        let old_resource = self.resource;
-        self.resource += 1;
-        self.create_resource(self.resource, FORMAT_B8G8R8X8_UNORM, width, height);
-        self.attach_backing(self.resource, new_fb.phys_addrs[0], fb_size);
-
-        let rect = Rect { x: 0, y: 0, width, height };
-        self.set_scanout(0, self.resource, rect);
-
-        self.destroy_resource(old_resource);
-        // The old pages go when the last holder lets go, which may be a
-        // compositor that has not yet mapped the new ones. Nothing is revoked.
-        drop(core::mem::replace(&mut self.fb, new_fb));
+    fn arrange(&mut self, outputs: &[Output]) -> Result<GpuInfo, SyscallError> {
+        let heads: Vec<Head> = self
+            .heads
+            .iter()
+            .zip(outputs)
+            .map(|(head, place)| Head { place: *place, ..*head })
+            .collect();
+        self.scan_out(heads)
+    }
 
-        self.width = width;
-        self.height = height;
+    fn edid(&self, output: usize) -> Option<Edid> {
+        self.heads.get(output)?.edid
+    }
 
-        log!("VirtIO GPU: resolution set to {}x{}", width, height);
+    fn suspend(&mut self) {
+        self.device.reset();
+    }
 
-        Ok(self.build_gpu_info())
+    /// Replay what the compositor's display is made of, into a device that
+    /// remembers none of it: the framebuffer resource over the same pages,
+    /// every head's scanout of it, and the cursor. Then the whole framebuffer
+    /// is transferred and flushed, because what is in those pages now is what
+    /// the compositor last drew and the host has not seen any of it.
+    ///
+    /// The ids are the ones the compositor's display had, so nothing that
+    /// names them has to change. A device that will not take its features
+    /// back leaves the panels dark and says so; there is no other display to
+    /// fall back to by then.
+    fn resume(&mut self) {
+        if !self.device.restart() {
+            log!("VirtIO GPU: did not come back from the sleep; the panels stay dark");
+            return;
+        }
+        self.start();
+        let fb_size = fb_size_bytes(self.width, self.height)
+            .expect("VirtIO GPU: a framebuffer this driver backed before the sleep");
+        self.create_resource(self.resource, FORMAT_B8G8R8X8_UNORM, self.width, self.height);
+        self.attach_backing(self.resource, self.fb.phys_addrs[0], fb_size);
+        for i in 0..self.heads.len() {
+            let (scanout, p) = (self.heads[i].scanout, self.heads[i].place);
+            self.set_scanout(scanout, self.resource, Rect { x: p.x, y: p.y, width: p.width, height: p.height });
+        }
+        self.create_cursor();
+        let (hot_x, hot_y) = self.cursor_hot;
+        self.set_cursor(hot_x, hot_y);
+        self.present_rect(0, 0, 0, 0);
+        log!("VirtIO GPU: {}x{} on {} scanout(s) again after the sleep",
+            self.width, self.height, self.heads.len());
     }
 }
 
@@ -527,21 +771,8 @@ pub fn init(devices: &[PciDevice]) -> Option<(Box<dyn Gpu>, GpuInfo)> {
 
     let device = VirtioDevice::init(&pci_dev, VIRTIO_F_VERSION_1 | VIRTIO_GPU_F_EDID);
 
-    let mut controlq = Virtqueue::new(dma.subview(OFF_CONTROLQ, 0x1000), 16);
-    let mut cursorq = Virtqueue::new(dma.subview(OFF_CURSORQ, 0x1000), 16);
-
-    device.setup_queue(0, &mut controlq);
-    device.setup_queue(1, &mut cursorq);
-    device.enable_queue(0);
-    device.enable_queue(1);
-    device.activate();
-
-    let mut control_slots = controlq.initial_slots();
-    let mut cursor_slots = cursorq.initial_slots();
-    let control_slot = control_slots.pop().expect("GPU: no control slots");
-    let cursor_slot = cursor_slots.pop().expect("GPU: no cursor slots");
-    drop(control_slots);
-    drop(cursor_slots);
+    let controlq = Virtqueue::new(dma.subview(OFF_CONTROLQ, 0x1000), 16);
+    let cursorq = Virtqueue::new(dma.subview(OFF_CURSORQ, 0x1000), 16);
 
     let ctrl_bufs = dma.subview(OFF_CONTROLQ_BUFS, 0x1000);
     let cursor_bufs = dma.subview(OFF_CURSORQ_BUFS, 0x1000);
@@ -553,54 +784,54 @@ pub fn init(devices: &[PciDevice]) -> Option<(Box<dyn Gpu>, GpuInfo)> {
         device,
         controlq,
         cursorq,
-        control_slot: Some(control_slot),
-        cursor_slot: Some(cursor_slot),
+        control_slot: None,
+        cursor_slot: None,
         req: ctrl_bufs.subview(REQ_OFFSET, HALF).unaligned(),
         resp: ctrl_bufs.subview(RESP_OFFSET, HALF),
         cursor_req: cursor_bufs.subview(REQ_OFFSET, HALF).unaligned(),
         cursor_resp: cursor_bufs.subview(RESP_OFFSET, HALF),
         width: 0,
         height: 0,
-        resource: 1,
+        resource: 0,
         fb: FbAlloc {
             regions: core::array::from_fn(|_| Region::empty()),
             phys_addrs: [0; 2],
         },
+        heads: Vec::new(),
         cursor: Region::empty(),
+        cursor_head: 0,
+        cursor_at: (0, 0),
+        cursor_hot: (0, 0),
     };
-
-    // EDID reports firmware-set resolution (often 640x480 from OVMF), not the
-    // host-configured preferred resolution. Query EDID for the preferred mode
-    // from the first Detailed Timing Descriptor.
-    let edid = gpu.get_edid(0);
-    let (width, height) = if edid.hdr.cmd_type == RESP_OK_EDID {
-        let dtd = &edid.edid[54..72];
-        let w = dtd[2] as u32 | ((dtd[4] as u32 >> 4) << 8);
-        let h = dtd[5] as u32 | ((dtd[7] as u32 >> 4) << 8);
-        if w >= 1280 && h >= 720 {
-            (w, h)
-        } else {
-            (1280, 720) // EDID reports stale firmware resolution, use default
-        }
-    } else {
-        (1280, 720)
+    gpu.start();
+
+    // Every scanout the host enabled is a monitor, and QEMU enables the first
+    // `max_outputs` of them. A device that will not say is one monitor on
+    // scanout 0, which is what this driver assumed before it asked.
+    let enabled: Vec<u32> = match gpu.display_info() {
+        Some(info) => (0..MAX_OUTPUTS as u32)
+            .filter(|&i| info.pmodes[i as usize].enabled != 0)
+            .collect(),
+        None => Vec::new(),
     };
-    log!("VirtIO GPU: display {}x{}", width, height);
+    let scanouts = if enabled.is_empty() { alloc::vec![0] } else { enabled };
+    let mut heads: Vec<Head> = scanouts
+        .into_iter()
+        .map(|scanout| {
+            let edid = gpu.read_edid(scanout);
+            let (width, height) = preferred_mode(edid.as_ref());
+            log!("VirtIO GPU: scanout {} display {}x{}", scanout, width, height);
+            Head { scanout, place: Output { x: 0, y: 0, width, height }, edid }
+        })
+        .collect();
+    side_by_side(&mut heads);
 
     // Allocate framebuffer backing stores (2MB-aligned). Boot-time, and the
     // dimensions come from EDID or the default above — a failure here is a
     // machine that cannot run, so it dies loudly.
-    let fb_size = fb_size_bytes(width, height).expect("VirtIO GPU: nonsense display dimensions");
-    gpu.fb = gpu.alloc_framebuffer(fb_size).expect("VirtIO GPU: framebuffer alloc failed");
-
-    gpu.create_resource(gpu.resource, FORMAT_B8G8R8X8_UNORM, width, height);
-    gpu.attach_backing(gpu.resource, gpu.fb.phys_addrs[0], fb_size);
-
-    let rect = Rect { x: 0, y: 0, width, height };
-    gpu.set_scanout(0, gpu.resource, rect);
+    gpu.scan_out(heads).expect("VirtIO GPU: cannot back the displays it reported");
 
     // Create cursor resource (64x64, BGRA with alpha)
-    let cursor_bytes = (CURSOR_SIZE * CURSOR_SIZE * 4) as usize;
     let cursor_pages = crate::mm::pmm::alloc_contiguous(1, crate::mm::pmm::Category::Framebuffer).expect("VirtIO GPU: cursor alloc failed");
     let cursor_ptr = cursor_pages[0].direct_map().as_mut_ptr::<u8>();
     let cursor_phys = cursor_pages[0].direct_map().phys();
@@ -610,13 +841,9 @@ pub fn init(devices: &[PciDevice]) -> Option<(Box<dyn Gpu>, GpuInfo)> {
         cache: CachePolicy::DeferToMtrr,
         pages: Some(alloc::sync::Arc::new(Pages::new(cursor_pages))),
     };
-    gpu.create_resource(CURSOR_RESOURCE_ID, FORMAT_B8G8R8A8_UNORM, CURSOR_SIZE, CURSOR_SIZE);
-    gpu.attach_backing(CURSOR_RESOURCE_ID, cursor_phys, cursor_bytes as u32);
+    gpu.create_cursor();
     log!("VirtIO GPU: cursor resource at {:?} phys={:#x}", cursor_ptr, cursor_phys);
 
-    gpu.width = width;
-    gpu.height = height;
-
     let info = gpu.build_gpu_info();
 
     Some((Box::new(gpu), info))
@@ -0,0 +1,400 @@
+//! virtio-input: the hypervisor's keyboard, mouse and tablet, decoded into the
+//! same key transitions and pointer samples the i8042 and USB HID produce.
+//!
+//! **Every device is the same driver and nothing here knows which kind it
+//! has.** A virtio-input device forwards a Linux input device, so what comes
+//! back on its event queue is evdev whatever it is; what differs is which
+//! codes its configuration space says it can send, and that decides only
+//! whether it needs a [`PointerSource`] and what range its absolute axes are
+//! scaled from. The decoding is `toyos_evdev`'s, where it is tested.
+//!
+//! One event queue per device, of 8-byte writable buffers, and no status
+//! queue: that carries LED state *to* the device, and this kernel keeps none.
+//! Every device's MSI-X entry carries one vector, and the record it publishes
+//! says only that some device has events — [`service`] drains them all, which
+//! costs an empty used-ring check per device that had none.
+//!
+//! Structure layouts come from the VirtIO 1.2 specification §5.8.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use toyos_evdev::config::{self as cfg, ABS_INFO_BYTES, UNION_BYTES};
+use toyos_evdev::event::{ABS_X, ABS_Y, EV_ABS, EV_KEY, EV_REL};
+use toyos_evdev::{Axis, Capabilities, Decoder, Event, Outcome};
+
+use super::pci::{PciDevice, MSIX_ENTRY};
+use super::virtio::{BufDir, DescSlot, Virtqueue, VirtioDevice, VIRTIO_F_VERSION_1};
+use super::DmaPool;
+use crate::arch::idt::VIRTIO_INPUT_VECTOR;
+use crate::irq_ring::IrqSource;
+use crate::log;
+use crate::mm::{Dma, Mmio};
+use crate::mouse::{Motion, PointerSource};
+use crate::sync::Lock;
+
+const VIRTIO_VENDOR: u16 = 0x1AF4;
+const VIRTIO_INPUT_DEVICE: u16 = 0x1052; // 0x1040 + device_id 18
+
+const EVENT_QUEUE: u16 = 0;
+/// Events the device can hold for a driver that has not drained. A frame is a
+/// handful of events and a pass drains every one, so 64 is several frames of
+/// slack behind a scheduler pass that ran late — and a device that runs out
+/// drops events and says so with `SYN_DROPPED`, which the decoder handles.
+const QUEUE_SIZE: u16 = 64;
+
+// DMA layout, 4 KiB-aligned regions in an 8 KiB pool per device:
+const OFF_EVENTQ: usize = 0x0000;
+const OFF_EVENTS: usize = 0x1000; // QUEUE_SIZE × 8 B
+const DMA_SIZE: usize = 0x2000;
+
+const _: () = assert!(QUEUE_SIZE as usize * Event::BYTES <= DMA_SIZE - OFF_EVENTS);
+
+/// One bound device.
+///
+/// `'static` because the pool is leaked at `init`: a virtio-input device
+/// cannot be unplugged from under a running guest on any hypervisor this
+/// targets, so a device bound is bound for the boot.
+struct InputDevice {
+    device: VirtioDevice,
+    eventq: Virtqueue<'static>,
+    /// One 8-byte buffer per descriptor, in descriptor order: the chain a
+    /// buffer is posted on is one descriptor long, so the id `poll_used` hands
+    /// back is the buffer's index and no side table is needed.
+    events: Dma<'static>,
+    decoder: Decoder,
+    caps: Capabilities,
+    /// `Some` for a device that moves the cursor.
+    pointer: Option<PointerSource>,
+    /// For log lines: `QEMU Virtio Tablet` says more than a BDF does.
+    name: String,
+    /// The event queue's refusal count as of the last line written about it,
+    /// for the reason `virtio_net` carries one.
+    reported_refusals: u32,
+}
+
+/// What one drain produced, for the wakes.
+#[derive(Default)]
+struct Drained {
+    keys: usize,
+    motion: usize,
+}
+
+impl InputDevice {
+    fn post(&mut self, slot: DescSlot) {
+        let at = slot.id() as usize * Event::BYTES;
+        self.eventq.submit(
+            slot,
+            &[(self.events.phys() + at as u64, Event::BYTES as u32, BufDir::Writable)],
+            self.device.notify_mmio(),
+            self.device.notify_off_multiplier(),
+            EVENT_QUEUE,
+        );
+    }
+
+    /// Give a negotiated device its queue and its vector, and post every
+    /// buffer. Shared by [`bind`] and [`resume`]; after a sleep the queue's
+    /// slots all come back, since nothing the reset device held will be
+    /// returned.
+    fn start(&mut self) -> bool {
+        let slots = self.eventq.restart();
+        self.device.setup_queue(EVENT_QUEUE, &mut self.eventq);
+        if let Err(refused) = self.device.bind_msix(EVENT_QUEUE) {
+            log!("virtio-input: {} refused a vector for {refused}", self.name);
+            return false;
+        }
+        self.device.enable_queue(EVENT_QUEUE);
+        self.device.activate();
+        for slot in slots {
+            self.post(slot);
+        }
+        true
+    }
+
+    /// Decode every event the device has returned, and give each buffer back.
+    fn drain(&mut self, out: &mut Drained) {
+        while let Some((slot, len)) = self.eventq.poll_used() {
+            // In range by construction: `poll_used` refuses a head past the
+            // queue, and the events region is `QUEUE_SIZE` buffers long.
+            let mut bytes = [0u8; Event::BYTES];
+            let complete = len as usize == Event::BYTES;
+            if complete {
+                self.events.copy_to(slot.id() as usize * Event::BYTES, &mut bytes);
+            }
+            self.post(slot);
+            if !complete {
+                // Not an event, so whatever frame it was part of is not one.
+                self.decoder.reset();
+                continue;
+            }
+            self.deliver(Event::parse(&bytes), out);
+        }
+        let refused = self.eventq.refused();
+        if refused != self.reported_refusals {
+            log!(
+                "virtio-input: {} refused {} used-ring element(s) — the device named a \
+                 descriptor this queue never published or claimed more bytes than it was given",
+                self.name,
+                refused - self.reported_refusals
+            );
+            self.reported_refusals = refused;
+        }
+    }
+
+    fn deliver(&mut self, event: Event, out: &mut Drained) {
+        match self.decoder.feed(event) {
+            Outcome::Pending | Outcome::None => {}
+            Outcome::Key { usage, pressed } => {
+                if crate::keyboard::handle_key(usage, pressed) {
+                    out.keys += 1;
+                }
+            }
+            Outcome::Pointer(sample) => {
+                // A keyboard that sent a button has no entry to publish it
+                // into; taking somebody else's is the aliasing `PointerSource`
+                // exists to prevent.
+                let Some(source) = self.pointer else { return };
+                let motion = match sample.motion {
+                    toyos_evdev::Motion::Relative { dx, dy } => Motion::Relative { dx, dy },
+                    toyos_evdev::Motion::Absolute { x, y } => Motion::Absolute { x, y },
+                };
+                if crate::mouse::handle_motion(source, sample.buttons, motion, sample.scroll) {
+                    out.motion += 1;
+                }
+            }
+            // The held-set is shared, so this releases what the other
+            // keyboards hold too — which is what the i8042 does on an overrun,
+            // and a key that stays down because its release was dropped is the
+            // worse of the two.
+            Outcome::Lost => {
+                log!("virtio-input: {} dropped events; releasing what it held", self.name);
+                if self.caps.keys {
+                    out.keys += crate::keyboard::release_all();
+                }
+                if let Some(source) = self.pointer {
+                    if crate::mouse::release_buttons(source) {
+                        out.motion += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+static DEVICES: Lock<Vec<InputDevice>> = Lock::new(Vec::new());
+/// Set once a device is bound, so a machine with none pays one load per pass.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Turn whatever the devices returned into events and wakes. Runs at the top
+/// of every scheduler pass on every CPU.
+pub fn service() {
+    // Unconditionally first, for the reason `i8042::service` gives: a record
+    // nobody consumes keeps the idle loop from halting.
+    let recorded = crate::irq_ring::take(IrqSource::Input).is_some();
+    if !recorded || !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut out = Drained::default();
+    for device in DEVICES.lock().iter_mut() {
+        device.drain(&mut out);
+    }
+    // Wake only when the decode queued something, for the reason
+    // `i8042::service_bytes` gives.
+    if out.keys > 0 {
+        crate::keyboard::wake_waiters();
+        let watchers = crate::keyboard::inbox_watchers();
+        if !watchers.is_empty() {
+            crate::inbox::complete_pending_for_event(&watchers, crate::inbox::Source::Keyboard);
+        }
+    }
+    if out.motion > 0 {
+        crate::mouse::wake_waiters();
+        let watchers = crate::mouse::inbox_watchers();
+        if !watchers.is_empty() {
+            crate::inbox::complete_pending_for_event(&watchers, crate::inbox::Source::Mouse);
+        }
+    }
+}
+
+/// Ask the configuration space one question: `select` and `subsel` in, up to
+/// `out.len()` bytes of the union back, and how many the device said there
+/// were. Zero is the device's own "no such thing".
+fn query(config: Mmio, select: u8, subsel: u8, out: &mut [u8]) -> usize {
+    config.write_u8(cfg::SELECT, select);
+    config.write_u8(cfg::SUBSEL, subsel);
+    let size = (config.read_u8(cfg::SIZE) as usize).min(out.len());
+    for (i, byte) in out[..size].iter_mut().enumerate() {
+        *byte = config.read_u8(cfg::UNION + i as u64);
+    }
+    size
+}
+
+fn name_of(config: Mmio) -> String {
+    let mut name = [0u8; UNION_BYTES];
+    let len = query(config, cfg::ID_NAME, 0, &mut name);
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+fn capabilities(config: Mmio) -> Capabilities {
+    let mut bits = [[0u8; UNION_BYTES]; 3];
+    for (bitmap, kind) in bits.iter_mut().zip([EV_KEY, EV_REL, EV_ABS]) {
+        query(config, cfg::EV_BITS, kind as u8, bitmap);
+    }
+    Capabilities::read(&bits[0], &bits[1], &bits[2])
+}
+
+/// The range of absolute axis `code`, or the compositor's own range for a
+/// device that gives none.
+fn axis(config: Mmio, code: u16, name: &str) -> Axis {
+    let mut info = [0u8; ABS_INFO_BYTES];
+    if query(config, cfg::ABS_INFO, code as u8, &mut info) < 8 {
+        return Axis::UNSPECIFIED;
+    }
+    Axis::parse(&info).unwrap_or_else(|| {
+        log!("virtio-input: {name} declares an empty range for axis {code}; reading it as 0..32767");
+        Axis::UNSPECIFIED
+    })
+}
+
+/// Arm this device's queue interrupt, or say why it is not bound. Nothing here
+/// polls — the drain runs behind the record the vector's handler publishes —
+/// so a device whose messages cannot reach a CPU would be a device that never
+/// types, for the reason `virtio_net::arm_interrupt` gives.
+fn arm_interrupt(pci_dev: &PciDevice, device: &VirtioDevice, name: &str) -> bool {
+    if !pci_dev.enable_msix(VIRTIO_INPUT_VECTOR) {
+        log!("virtio-input: {name} NOT BOUND at PCI {:02x}:{:02x}.{} — its MSI-X could not be \
+             armed and this driver has no other way to be told an event arrived",
+            pci_dev.bus, pci_dev.dev, pci_dev.func);
+        return false;
+    }
+    if let Err(refused) = device.bind_msix(EVENT_QUEUE) {
+        log!("virtio-input: {name} NOT BOUND at PCI {:02x}:{:02x}.{} — the device refused a \
+             vector for {}", pci_dev.bus, pci_dev.dev, pci_dev.func, refused);
+        return false;
+    }
+    true
+}
+
+fn bind(pci_dev: &PciDevice) -> Option<InputDevice> {
+    let device = VirtioDevice::init(pci_dev, VIRTIO_F_VERSION_1);
+    let config = device.device_config();
+    let name = name_of(config);
+    let caps = capabilities(config);
+    if caps.is_empty() {
+        log!("virtio-input: {name} at PCI {:02x}:{:02x}.{} reports no keys and no pointer; \
+             not bound", pci_dev.bus, pci_dev.dev, pci_dev.func);
+        return None;
+    }
+    let (x, y) = if caps.absolute {
+        (axis(config, ABS_X, &name), axis(config, ABS_Y, &name))
+    } else {
+        (Axis::UNSPECIFIED, Axis::UNSPECIFIED)
+    };
+
+    // Claimed before the queue is armed, so a refusal leaves nothing to undo.
+    let pointer = if caps.is_pointer() {
+        let Some(source) = PointerSource::claim() else {
+            log!("virtio-input: {name} NOT BOUND — every pointer entry is held");
+            return None;
+        };
+        Some(source)
+    } else {
+        None
+    };
+
+    // Leaked rather than held in a `static`: see `InputDevice`.
+    let dma = DmaPool::alloc(DMA_SIZE).leak();
+    let eventq = Virtqueue::new(dma.subview(OFF_EVENTQ, 0x1000), QUEUE_SIZE);
+    let unbind = || {
+        if let Some(source) = pointer {
+            crate::mouse::unbind(source);
+        }
+    };
+    if !arm_interrupt(pci_dev, &device, &name) {
+        unbind();
+        return None;
+    }
+
+    let mut input = InputDevice {
+        device,
+        eventq,
+        events: dma.subview(OFF_EVENTS, QUEUE_SIZE as usize * Event::BYTES),
+        decoder: Decoder::new(x, y),
+        caps,
+        pointer,
+        name,
+        reported_refusals: 0,
+    };
+    if !input.start() {
+        unbind();
+        return None;
+    }
+
+    match pointer {
+        Some(source) if caps.absolute => log!(
+            "virtio-input: {} ready as a {} (pointer {}, x {}..{}, y {}..{})",
+            input.name, caps.name(), source.id(), x.min, x.max, y.min, y.max
+        ),
+        Some(source) => log!(
+            "virtio-input: {} ready as a {} (pointer {})",
+            input.name, caps.name(), source.id()
+        ),
+        None => log!("virtio-input: {} ready as a {}", input.name, caps.name()),
+    }
+    Some(input)
+}
+
+pub fn init(devices: &[PciDevice]) {
+    let found: Vec<PciDevice> = devices
+        .iter()
+        .filter(|d| d.is_id(VIRTIO_VENDOR, VIRTIO_INPUT_DEVICE))
+        .copied()
+        .collect();
+    if found.is_empty() {
+        log!("virtio-input: no device found");
+        return;
+    }
+    let mut bound = DEVICES.lock();
+    for pci_dev in &found {
+        log!("virtio-input: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+        if let Some(input) = bind(pci_dev) {
+            bound.push(input);
+        }
+    }
+    if !bound.is_empty() {
+        ACTIVE.store(true, Ordering::Relaxed);
+        log!("virtio-input: {} of {} device(s) bound, MSI-X vector {:#x} on table entry {}",
+            bound.len(), found.len(), VIRTIO_INPUT_VECTOR, MSIX_ENTRY);
+    }
+}
+
+/// Every device reset ahead of S3, so none writes an event into a buffer
+/// after the bus has gone down.
+pub fn suspend() {
+    for input in DEVICES.lock().iter_mut() {
+        input.device.reset();
+    }
+}
+
+/// Every device back on its queue after S3, with its decoder emptied.
+///
+/// A release typed while the machine slept, or still in the queue the reset
+/// emptied, is never coming, so what each device held is released here — the
+/// same self-heal `Outcome::Lost` runs, and for the same reason. The readers
+/// hear of it with the next event. A device that does not take its features
+/// or its vector back goes silent and says so; the others are unaffected.
+pub fn resume() {
+    for input in DEVICES.lock().iter_mut() {
+        input.decoder.reset();
+        if input.caps.keys {
+            crate::keyboard::release_all();
+        }
+        if let Some(source) = input.pointer {
+            crate::mouse::release_buttons(source);
+        }
+        if !input.device.restart() || !input.start() {
+            log!("virtio-input: {} did not come back from the sleep and is silent", input.name);
+        }
+    }
+}
@@ -62,9 +62,47 @@ struct VirtioNic {
     /// Stash area: slot returned by poll_used, indexed by buf_idx, consumed by refill_rx_buf.
     pending_rx_slots: [Option<DescSlot>; RX_BUF_COUNT],
     tx_slot: Option<DescSlot>,
+    /// Set when the device did not take its features back after a sleep.
+    /// Every frame is dropped from then on rather than waited for, because a
+    /// TX submission spins on a completion a dead device never writes.
+    failed: bool,
 }
 
 impl VirtioNic {
+    /// Configure both queues on a negotiated device, bind the RX interrupt
+    /// and post every buffer netd is not holding. Shared by `init` and the
+    /// resume, which is why the queues are restarted first: the slots a resume
+    /// inherits name descriptors the reset device has forgotten.
+    ///
+    /// The table entry itself is not armed here — `enable_msix` at bind, and
+    /// `pci::resume`'s restore of the table after a sleep — only the device's
+    /// own pointer at it, which a reset clears.
+    fn start(&mut self) -> bool {
+        let mut rx_slots = self.rxq.restart();
+        let mut tx_slots = self.txq.restart();
+        self.device.setup_queue(RX_QUEUE, &mut self.rxq);
+        self.device.setup_queue(TX_QUEUE, &mut self.txq);
+        if let Err(refused) = self.device.bind_msix(RX_QUEUE) {
+            log!("VirtIO net: the device refused a vector for {refused}");
+            return false;
+        }
+        self.device.enable_queue(RX_QUEUE);
+        self.device.enable_queue(TX_QUEUE);
+        self.device.activate();
+
+        self.tx_slot = tx_slots.pop();
+        for i in 0..RX_BUF_COUNT {
+            let slot = rx_slots.pop().expect("virtio-net: not enough rx slots");
+            // A buffer netd was given and has not handed back is still being
+            // read: it takes a fresh slot and is posted by `refill_rx_buf`.
+            match &mut self.pending_rx_slots[i] {
+                Some(held) => *held = slot,
+                None => self.refill_rx(i, slot),
+            }
+        }
+        true
+    }
+
     fn refill_rx(&mut self, buf_idx: usize, slot: DescSlot) {
         // Bounded by construction: the subview is exactly `NET_HDR_SIZE` bytes
         // at the front of a `RX_BUF_SIZE` buffer. This runs before the
@@ -85,10 +123,13 @@ impl VirtioNic {
 
 impl crate::net::Nic for VirtioNic {
     fn has_packet(&self) -> bool {
-        self.rxq.has_used()
+        !self.failed && self.rxq.has_used()
     }
 
     fn poll_rx(&mut self) -> Option<(usize, usize)> {
+        if self.failed {
+            return None;
+        }
         let polled = self.rxq.poll_used();
         // Named here and not in `poll_used`: this runs on the `irq_ring` drain,
         // where a log line is ordinary, and it is the used ring an untrusted
@@ -127,13 +168,18 @@ impl crate::net::Nic for VirtioNic {
         let Some(slot) = self.pending_rx_slots[buf_index].take() else {
             return Err(SyscallError::InvalidArgument);
         };
-        self.refill_rx(buf_index, slot);
+        if !self.failed {
+            self.refill_rx(buf_index, slot);
+        }
         Ok(())
     }
 
     fn tx_buf_len(&self) -> usize { TX_BUF_LEN }
 
     fn submit_tx(&mut self, total_len: usize) {
+        if self.failed {
+            return;
+        }
         let slot = self.tx_slot.take().expect("virtio-net: no tx slot");
         self.tx_slot = Some(self.txq.submit_and_wait(
             slot,
@@ -143,6 +189,20 @@ impl crate::net::Nic for VirtioNic {
             TX_QUEUE,
         ));
     }
+
+    fn suspend(&mut self) {
+        self.device.reset();
+    }
+
+    fn resume(&mut self) {
+        if self.failed {
+            return;
+        }
+        if !self.device.restart() || !self.start() {
+            self.failed = true;
+            log!("VirtIO net: did not come back from the sleep; every frame is dropped");
+        }
+    }
 }
 
 /// Arm this NIC's RX interrupt, or say why the machine has no NIC.
@@ -192,19 +252,14 @@ pub fn init(devices: &[PciDevice]) {
         dma.subview(OFF_RXQ_USED, 0x1000),
         RX_QUEUE_SIZE,
     );
-    let mut rxq = Virtqueue::from_regions(&rxq_regions, RX_QUEUE_SIZE);
+    let rxq = Virtqueue::from_regions(&rxq_regions, RX_QUEUE_SIZE);
 
     // TX queue: 16 entries, fits in one page
-    let mut txq = Virtqueue::new(dma.subview(OFF_TXQ, 0x1000), 16);
+    let txq = Virtqueue::new(dma.subview(OFF_TXQ, 0x1000), 16);
 
-    device.setup_queue(RX_QUEUE, &mut rxq);
-    device.setup_queue(TX_QUEUE, &mut txq);
     if !arm_interrupt(&pci_dev, &device) {
         return;
     }
-    device.enable_queue(RX_QUEUE);
-    device.enable_queue(TX_QUEUE);
-    device.activate();
 
     let cfg = device.device_config();
     let mac = [
@@ -218,6 +273,19 @@ pub fn init(devices: &[PciDevice]) {
         core::array::from_fn(|i| dma.subview(OFF_RX_BUFS + i * 0x1000, RX_BUF_SIZE as usize));
     let tx_phys = dma.phys() + OFF_TX_BUF as u64;
 
+    const NONE_SLOT: Option<DescSlot> = None;
+    let mut nic = VirtioNic {
+        device, rxq, txq, tx_phys, rx_bufs,
+        desc_to_buf: [0; RX_QUEUE_SIZE as usize],
+        reported_refusals: 0,
+        pending_rx_slots: [NONE_SLOT; RX_BUF_COUNT],
+        tx_slot: None,
+        failed: false,
+    };
+    if !nic.start() {
+        return;
+    }
+
     let dma_base_phys = dma.phys() & !(crate::mm::PAGE_2M - 1);
     let dma_region = Region {
         phys: crate::DirectMap::from_phys(dma_base_phys),
@@ -236,25 +304,6 @@ pub fn init(devices: &[PciDevice]) {
         net_hdr_size: NET_HDR_SIZE as u16,
     }, dma_region);
 
-    let mut rx_slots = rxq.initial_slots();
-    let mut tx_slots = txq.initial_slots();
-    let tx_slot = tx_slots.pop().expect("virtio-net: no tx slots");
-    drop(tx_slots);
-
-    const NONE_SLOT: Option<DescSlot> = None;
-    let mut nic = VirtioNic {
-        device, rxq, txq, tx_phys, rx_bufs,
-        desc_to_buf: [0; RX_QUEUE_SIZE as usize],
-        reported_refusals: 0,
-        pending_rx_slots: [NONE_SLOT; RX_BUF_COUNT],
-        tx_slot: Some(tx_slot),
-    };
-
-    for i in 0..RX_BUF_COUNT {
-        let slot = rx_slots.pop().expect("virtio-net: not enough rx slots");
-        nic.refill_rx(i, slot);
-    }
-
     crate::net::register(Box::new(nic));
     log!("VirtIO net: {} RX buffers, queue size {}", RX_BUF_COUNT, RX_QUEUE_SIZE);
 }
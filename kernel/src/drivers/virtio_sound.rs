@@ -13,6 +13,12 @@
 //! period is published and when the stream runs are soundd's, and every one of
 //! them is a message the driver writes into a buffer of its own.
 //!
+//! **A sleep resets the device, and soundd configures it again.** The chains
+//! are the kernel's and survive in memory, so [`resume`] gives the device its
+//! queues back with the rings emptied, which is all the device forgot that
+//! the kernel owns. The stream's parameters were a message soundd wrote, so
+//! soundd is told with [`AudioCompletionRecord::RESET`] and sends them again.
+//!
 //! Structure layouts and command codes come from the VirtIO 1.2 specification
 //! §5.14; the ones the kernel needs are the transfer header's size and nothing
 //! else, because the kernel never reads a response.
@@ -25,8 +31,8 @@ use toyos_abi::syscall::{RegWidth, SyscallError};
 use toyos_abi::virtio_sound as abi;
 
 use super::pci::{PciDevice, MSIX_ENTRY};
-use super::virtio::{BufDir, UsedRingConsumer, VirtioDevice, Virtqueue, VirtqueueRegions,
-                    VIRTIO_F_VERSION_1};
+use super::virtio::{BufDir, NoVector, UsedRingConsumer, VirtioDevice, Virtqueue,
+                    VirtqueueRegions, VIRTIO_F_VERSION_1};
 use super::DmaPool;
 use crate::log;
 use crate::mm::paging::CachePolicy;
@@ -179,7 +185,7 @@ unsafe impl Sync for RecordRing {}
 
 static RECORDS: RecordRing = RecordRing {
     slots: [const {
-        UnsafeCell::new(AudioCompletionRecord { mask: 0, _pad: 0, timestamp_nanos: 0 })
+        UnsafeCell::new(AudioCompletionRecord { mask: 0, feedback_rate: 0, timestamp_nanos: 0 })
     }; RECORD_RING_CAP as usize],
     head: AtomicU32::new(0),
     tail: AtomicU32::new(0),
@@ -210,7 +216,7 @@ fn isr_push_completion(mask: u32, timestamp_nanos: u64) {
     let slot = (head % RECORD_RING_CAP) as usize;
     // SAFETY: slot is outside [tail, head) — not visible to the consumer.
     unsafe {
-        *ring.slots[slot].get() = AudioCompletionRecord { mask, _pad: 0, timestamp_nanos };
+        *ring.slots[slot].get() = AudioCompletionRecord { mask, feedback_rate: 0, timestamp_nanos };
     }
     // Release: publish the record contents before the consumer can observe the
     // new head.
@@ -235,7 +241,7 @@ fn pop_completion() -> Option<AudioCompletionRecord> {
         }
         return Some(AudioCompletionRecord {
             mask,
-            _pad: 0,
+            feedback_rate: 0,
             timestamp_nanos: SPILL.timestamp.load(Ordering::Relaxed),
         });
     }
@@ -306,13 +312,13 @@ pub fn inbox_watchers() -> alloc::vec::Vec<crate::inbox::InboxId> {
 
 // --- the controller ---
 
-/// What the bring-up leaves behind: the notification region the driver's three
-/// doorbells are in, and the pages every descriptor points into.
+/// What the bring-up leaves behind: the device, the notification region the
+/// driver's three doorbells are in, and the three virtqueues.
 ///
-/// The virtqueues are not here because after [`build_chains`] there is nothing
-/// left to do with one — their descriptors are written, their addresses are in
-/// the device, and their used rings are consumed by the handler and by the
-/// driver.
+/// After [`build_chains`] nothing is submitted on a virtqueue here — its
+/// descriptors are written, and its used ring is consumed by the handler or by
+/// the driver. They are kept for [`resume`], which gives a reset device their
+/// addresses again and empties their rings.
 /// The pools are not here, and that is the change of 2026-08-22: they are leaked
 /// at bring-up and what the driver holds is `Dma<'static>`.
 ///
@@ -326,7 +332,32 @@ pub fn inbox_watchers() -> alloc::vec::Vec<crate::inbox::InboxId> {
 /// device whose MSI-X cannot be armed keeps its DMA for the boot, which is a
 /// machine with no audio either way.
 struct Bound {
+    device: VirtioDevice,
     notify: Mmio,
+    controlq: Virtqueue<'static>,
+    eventq: Virtqueue<'static>,
+    txq: Virtqueue<'static>,
+}
+
+impl Bound {
+    /// Give a restarted device its three queues back, empty, and set it live.
+    /// The MSI-X table entry is already armed: `pci::resume` wrote it back.
+    fn start(&mut self) -> Result<(), NoVector> {
+        for (index, queue) in [
+            (abi::CONTROL_QUEUE, &mut self.controlq),
+            (abi::EVENT_QUEUE, &mut self.eventq),
+            (abi::TX_QUEUE, &mut self.txq),
+        ] {
+            queue.restart_rings();
+            self.device.setup_queue(index, queue);
+        }
+        self.device.bind_msix(abi::TX_QUEUE)?;
+        for index in [abi::CONTROL_QUEUE, abi::EVENT_QUEUE, abi::TX_QUEUE] {
+            self.device.enable_queue(index);
+        }
+        self.device.activate();
+        Ok(())
+    }
 }
 
 static CONTROLLER: Lock<Option<Bound>> = Lock::new(None);
@@ -477,7 +508,8 @@ pub fn init(devices: &[PciDevice]) {
         chmaps,
     };
 
-    *CONTROLLER.lock() = Some(Bound { notify: device.notify_mmio() });
+    *CONTROLLER.lock() =
+        Some(Bound { notify: device.notify_mmio(), device, controlq, eventq, txq });
     *INFO.lock() = Some((info, dma_region));
 
     log!(
@@ -574,3 +606,54 @@ fn arm_interrupt(pci: &PciDevice, device: &VirtioDevice) -> bool {
     log!("virtio-sound: MSI-X vector {vector:#x} on table entry {MSIX_ENTRY}");
     true
 }
+
+/// Reset the device ahead of S3, so it fetches no descriptor and writes no
+/// used ring after the bus goes down.
+pub fn suspend() {
+    if let Some(bound) = CONTROLLER.lock().as_ref() {
+        bound.device.reset();
+    }
+}
+
+/// Bring the device back after S3 with its queues empty, and tell soundd.
+///
+/// What was pending is dropped: it completed on queues the device has
+/// forgotten, and a mask for one of those periods would reach soundd after it
+/// had already taken every period back. In its place soundd reads one
+/// [`AudioCompletionRecord::RESET`]. It answers that with its event buffers,
+/// `SET_PARAMS` and `PREPARE`, as at claim. A device that does not take its
+/// features or its vector back is named and left silent.
+pub fn resume() {
+    let mut guard = CONTROLLER.lock();
+    let Some(bound) = guard.as_mut() else { return };
+    // The consumer side of the record ring, which `CONTROLLER` serialises.
+    RECORDS.tail.store(RECORDS.head.load(Ordering::Acquire), Ordering::Release);
+    SPILL.mask.store(0, Ordering::Relaxed);
+    // SAFETY: the device was reset by `suspend` and has not been set live
+    // again, so no interrupt can reach the handler that is this consumer's
+    // only other user.
+    if let Some(consumer) = unsafe { &mut *TX_ISR.consumer.get() }.as_mut() {
+        consumer.restart();
+    }
+    if !bound.device.restart() {
+        log!(
+            "virtio-sound: did not come back from the sleep and is silent: it no longer offers \
+             the features it was bound with"
+        );
+        return;
+    }
+    if let Err(refused) = bound.start() {
+        log!(
+            "virtio-sound: did not come back from the sleep and is silent: it refused a vector \
+             for {refused}"
+        );
+        return;
+    }
+    let now = crate::clock::nanos_since_boot();
+    // The handler's own two calls, made here with interrupts off on the one CPU
+    // left running: the device is live again but has been given nothing to
+    // complete, so the handler cannot be pushing beside this.
+    isr_push_completion(AudioCompletionRecord::RESET, now);
+    crate::irq_ring::isr_publish(crate::irq_ring::IrqSource::Audio, now);
+    log!("virtio-sound: back after the sleep, queues empty");
+}
@@ -0,0 +1,445 @@
+//! virtio-vsock: sockets to the host with no network in between.
+//!
+//! The queues and nothing above them. A packet that arrives is checked, copied
+//! off its receive buffer into a short queue, and handed whole to whoever holds
+//! the `vsock` claim on its next read; a packet the holder writes is checked
+//! and copied into a transmit buffer. What a packet *means* — which connection,
+//! whose credit — is vsockd's, through `toyos_vsock`, and the kernel keeps no
+//! connection state at all.
+//!
+//! **What is checked is what the kernel is answerable for.** Every packet in
+//! either direction parses as a stream packet whose length field is the bytes
+//! that carry it, so the holder is never handed a header that lies about its
+//! own size; one that arrives addressed to some other context id is dropped,
+//! and one the holder writes must come *from* this guest's — the claim is the
+//! authority to speak for this machine on the host's vsock, not for another.
+//! Whether a stream's credit is honoured is the protocol's business and
+//! vsockd's check.
+//!
+//! **Backpressure is the receive ring.** At most [`QUEUED`] packets wait for
+//! the holder; past that the used ring is left undrained, the device runs out
+//! of buffers and stops, and the host's own credit accounting stalls the
+//! sender. Nothing here drops a packet for want of room.
+//!
+//! Structure layouts come from the VirtIO 1.2 specification §5.10.
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use toyos_abi::syscall::SyscallError;
+use toyos_abi::vsock::{VsockInfo, MAX_PACKET, TRANSPORT_RESET};
+use toyos_vsock::Header;
+
+use super::pci::{PciDevice, MSIX_ENTRY};
+use super::virtio::{BufDir, DescSlot, Virtqueue, VirtioDevice, VIRTIO_F_VERSION_1};
+use super::DmaPool;
+use crate::arch::idt::VIRTIO_VSOCK_VECTOR;
+use crate::inbox::InboxId;
+use crate::irq_ring::IrqSource;
+use crate::log;
+use crate::mm::Dma;
+use crate::sync::Lock;
+use crate::user_ptr::{UserBytes, UserBytesMut};
+
+const VIRTIO_VENDOR: u16 = 0x1AF4;
+const VIRTIO_VSOCK_DEVICE: u16 = 0x1053; // 0x1040 + device_id 19
+
+const RX_QUEUE: u16 = 0;
+const TX_QUEUE: u16 = 1;
+const EVENT_QUEUE: u16 = 2;
+
+/// Receive buffers, one page each and one descriptor each, so a used element's
+/// id is its buffer's index.
+const RX_BUFS: u16 = 64;
+/// Transmit buffers, on the same terms.
+const TX_BUFS: u16 = 32;
+/// Event buffers. The only event the specification defines is a transport
+/// reset, so these are slack for a host that sends two before a drain.
+const EVENT_BUFS: u16 = 4;
+/// `struct virtio_vsock_event`: one `le32 id`, in an 8-byte stride.
+const EVENT_STRIDE: usize = 8;
+const EVENT_TRANSPORT_RESET: u32 = 0;
+
+/// Packets copied off the ring and not yet read. One receive ring's worth, so
+/// a holder that stops reading costs the kernel what the device was already
+/// holding and no more.
+const QUEUED: usize = RX_BUFS as usize;
+
+// DMA layout, 4 KiB-aligned regions:
+const OFF_RXQ: usize = 0x0000;
+const OFF_TXQ: usize = 0x1000;
+const OFF_EVQ: usize = 0x2000;
+const OFF_EVENTS: usize = 0x3000;
+const OFF_RX: usize = 0x4000;
+const OFF_TX: usize = OFF_RX + RX_BUFS as usize * MAX_PACKET;
+const DMA_SIZE: usize = OFF_TX + TX_BUFS as usize * MAX_PACKET;
+
+// Device configuration (virtio 1.2 §5.10.4): `le64 guest_cid`.
+const CFG_GUEST_CID: u64 = 0;
+
+struct Vsock {
+    device: VirtioDevice,
+    rxq: Virtqueue<'static>,
+    txq: Virtqueue<'static>,
+    eventq: Virtqueue<'static>,
+    rx: Dma<'static>,
+    tx: Dma<'static>,
+    events: Dma<'static>,
+    tx_free: Vec<DescSlot>,
+    queued: VecDeque<Vec<u8>>,
+    guest_cid: u64,
+    /// A transport reset the holder has not been told about.
+    reset_owed: bool,
+    /// Packets dropped on receive, and the count as of the last line about it.
+    dropped: u32,
+    reported_dropped: u32,
+    reported_refusals: u32,
+}
+
+impl Vsock {
+    fn post_rx(&mut self, slot: DescSlot) {
+        let at = slot.id() as usize * MAX_PACKET;
+        self.rxq.submit(
+            slot,
+            &[(self.rx.phys() + at as u64, MAX_PACKET as u32, BufDir::Writable)],
+            self.device.notify_mmio(),
+            self.device.notify_off_multiplier(),
+            RX_QUEUE,
+        );
+    }
+
+    fn post_event(&mut self, slot: DescSlot) {
+        let at = slot.id() as usize * EVENT_STRIDE;
+        self.eventq.submit(
+            slot,
+            &[(self.events.phys() + at as u64, 4, BufDir::Writable)],
+            self.device.notify_mmio(),
+            self.device.notify_off_multiplier(),
+            EVENT_QUEUE,
+        );
+    }
+
+    /// Give a negotiated device its three queues and their vectors, post
+    /// every receive and event buffer, and take every TX slot back. Shared by
+    /// [`init`] and [`resume`]: a reset device returns nothing it was given,
+    /// so a slot the resume did not reclaim here would be gone for the boot.
+    fn start(&mut self) -> bool {
+        let rx_slots = self.rxq.restart();
+        let event_slots = self.eventq.restart();
+        self.tx_free = self.txq.restart();
+        self.device.setup_queue(RX_QUEUE, &mut self.rxq);
+        self.device.setup_queue(TX_QUEUE, &mut self.txq);
+        self.device.setup_queue(EVENT_QUEUE, &mut self.eventq);
+        for queue in [RX_QUEUE, TX_QUEUE, EVENT_QUEUE] {
+            if let Err(refused) = self.device.bind_msix(queue) {
+                log!("virtio-vsock: the device refused a vector for {refused}");
+                return false;
+            }
+        }
+        for queue in [RX_QUEUE, TX_QUEUE, EVENT_QUEUE] {
+            self.device.enable_queue(queue);
+        }
+        self.device.activate();
+        for slot in rx_slots {
+            self.post_rx(slot);
+        }
+        for slot in event_slots {
+            self.post_event(slot);
+        }
+        true
+    }
+
+    fn reclaim_tx(&mut self) {
+        while let Some((slot, _)) = self.txq.poll_used() {
+            self.tx_free.push(slot);
+        }
+    }
+
+    /// Move what the device returned onto the queue the holder reads. Whether
+    /// anything arrived that the holder should be woken for.
+    fn drain(&mut self) -> bool {
+        let mut woke = false;
+        while let Some((slot, _)) = self.eventq.poll_used() {
+            let id = {
+                let mut raw = [0u8; 4];
+                self.events.copy_to(slot.id() as usize * EVENT_STRIDE, &mut raw);
+                u32::from_le_bytes(raw)
+            };
+            self.post_event(slot);
+            if id == EVENT_TRANSPORT_RESET {
+                // Every connection the host had is gone, so every packet still
+                // queued is for one of them.
+                self.queued.clear();
+                self.guest_cid = self.device.device_config().read_u64(CFG_GUEST_CID);
+                self.reset_owed = true;
+                woke = true;
+                log!("virtio-vsock: transport reset, guest cid {}", self.guest_cid);
+            }
+        }
+        while self.queued.len() < QUEUED {
+            let Some((slot, len)) = self.rxq.poll_used() else { break };
+            // In range by construction: `poll_used` refuses a length past the
+            // buffer the chain was published with.
+            let mut packet = vec![0u8; len as usize];
+            self.rx.copy_to(slot.id() as usize * MAX_PACKET, &mut packet);
+            self.post_rx(slot);
+            match Header::parse(&packet) {
+                Ok((header, _)) if header.dst.cid == self.guest_cid => {
+                    self.queued.push_back(packet);
+                    woke = true;
+                }
+                Ok(_) | Err(_) => self.dropped += 1,
+            }
+        }
+        self.reclaim_tx();
+        self.report();
+        woke
+    }
+
+    /// Say what was refused since the last line about it, for the reason
+    /// `virtio_net` carries its own refusal count.
+    fn report(&mut self) {
+        if self.dropped != self.reported_dropped {
+            log!(
+                "virtio-vsock: dropped {} packet(s) that were not a stream packet for this guest",
+                self.dropped - self.reported_dropped
+            );
+            self.reported_dropped = self.dropped;
+        }
+        let refused = self.rxq.refused() + self.txq.refused() + self.eventq.refused();
+        if refused != self.reported_refusals {
+            log!(
+                "virtio-vsock: refused {} used-ring element(s) — the device named a descriptor \
+                 this queue never published or claimed more bytes than it was given",
+                refused - self.reported_refusals
+            );
+            self.reported_refusals = refused;
+        }
+    }
+}
+
+static VSOCK: Lock<Option<Vsock>> = Lock::new(None);
+/// Set once the device is bound, so a machine with none pays one load per pass.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static INBOX_WATCHERS: Lock<Vec<InboxId>> = Lock::new(Vec::new());
+/// Set by [`resume`], which owes the holder a reset notice and runs where no
+/// one can be woken; the first [`service`] pass after the thaw does the wake.
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+pub fn add_inbox_watcher(id: InboxId) {
+    let mut watchers = INBOX_WATCHERS.lock();
+    if !watchers.contains(&id) {
+        watchers.push(id);
+    }
+}
+
+pub fn remove_inbox_watcher(id: InboxId) {
+    INBOX_WATCHERS.lock().retain(|&x| x != id);
+}
+
+pub fn inbox_watchers() -> Vec<InboxId> {
+    INBOX_WATCHERS.lock().clone()
+}
+
+/// The description a claim answers with, or `None` on a machine with no
+/// device.
+pub fn info() -> Option<VsockInfo> {
+    VSOCK.lock().as_ref().map(|v| VsockInfo { guest_cid: v.guest_cid })
+}
+
+pub fn has_pending() -> bool {
+    VSOCK.lock().as_ref().is_some_and(|v| v.reset_owed || !v.queued.is_empty())
+}
+
+/// Forget what arrived for the previous holder.
+pub fn discard_queued() {
+    if let Some(v) = VSOCK.lock().as_mut() {
+        v.queued.clear();
+        v.reset_owed = false;
+    }
+}
+
+/// Answer a read with the oldest packet, or with the reset notice if one is
+/// owed. `None` when there is neither.
+///
+/// A buffer too small for the packet is `InvalidArgument` and the packet
+/// stays: a short read would hand back a header whose length is a lie, which
+/// is the one thing the checks on receive exist to rule out.
+pub fn receive(buf: &mut UserBytesMut) -> Option<u64> {
+    let mut guard = VSOCK.lock();
+    let v = guard.as_mut()?;
+    if v.reset_owed {
+        if buf.len() < TRANSPORT_RESET {
+            return Some(SyscallError::InvalidArgument.to_u64());
+        }
+        let mut notice = [0u8; TRANSPORT_RESET];
+        notice[8..16].copy_from_slice(&v.guest_cid.to_le_bytes());
+        buf.write_at(0, &notice);
+        v.reset_owed = false;
+        return Some(TRANSPORT_RESET as u64);
+    }
+    let len = v.queued.front()?.len();
+    if buf.len() < len {
+        return Some(SyscallError::InvalidArgument.to_u64());
+    }
+    let packet = v.queued.pop_front().expect("just looked");
+    buf.write_at(0, &packet);
+    Some(len as u64)
+}
+
+/// Put one packet on the wire.
+pub fn send(buf: &UserBytes) -> Result<(), SyscallError> {
+    if buf.len() > MAX_PACKET {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let mut packet = vec![0u8; buf.len()];
+    buf.read_at(0, &mut packet);
+    let mut guard = VSOCK.lock();
+    let v = guard.as_mut().ok_or(SyscallError::NotFound)?;
+    let Ok((header, _)) = Header::parse(&packet) else {
+        return Err(SyscallError::InvalidArgument);
+    };
+    if header.src.cid != v.guest_cid {
+        return Err(SyscallError::PermissionDenied);
+    }
+    v.reclaim_tx();
+    let slot = v.tx_free.pop().ok_or(SyscallError::WouldBlock)?;
+    let at = slot.id() as usize * MAX_PACKET;
+    v.tx.copy_from(at, &packet);
+    let notify = v.device.notify_mmio();
+    let multiplier = v.device.notify_off_multiplier();
+    v.txq.submit(
+        slot,
+        &[(v.tx.phys() + at as u64, packet.len() as u32, BufDir::Readable)],
+        notify,
+        multiplier,
+        TX_QUEUE,
+    );
+    Ok(())
+}
+
+/// Turn what the device returned into packets and wakes. Runs at the top of
+/// every scheduler pass on every CPU.
+pub fn service() {
+    // Unconditionally first, for the reason `i8042::service` gives.
+    let recorded = crate::irq_ring::take(IrqSource::Vsock).is_some();
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    // Without a record there may still be packets left on the used ring by a
+    // drain that stopped at `QUEUED`, and the holder's reads are what make
+    // room for them. One used-index load per pass.
+    let resumed = RESUMED.swap(false, Ordering::Relaxed);
+    let woke = {
+        let mut guard = VSOCK.lock();
+        let Some(v) = guard.as_mut() else { return };
+        if !recorded && !resumed && (v.queued.len() >= QUEUED || !v.rxq.has_used()) {
+            return;
+        }
+        v.drain() || resumed
+    };
+    if woke {
+        let watchers = inbox_watchers();
+        if !watchers.is_empty() {
+            crate::inbox::complete_pending_for_event(&watchers, crate::inbox::Source::Vsock);
+        }
+    }
+}
+
+/// Arm the device's interrupt, or say why it is not bound, for the reason
+/// `virtio_input::arm_interrupt` gives.
+fn arm_interrupt(pci_dev: &PciDevice, device: &VirtioDevice) -> bool {
+    if !pci_dev.enable_msix(VIRTIO_VSOCK_VECTOR) {
+        log!("virtio-vsock: NOT BOUND at PCI {:02x}:{:02x}.{} — its MSI-X could not be armed \
+             and this driver has no other way to be told a packet arrived",
+            pci_dev.bus, pci_dev.dev, pci_dev.func);
+        return false;
+    }
+    for queue in [RX_QUEUE, TX_QUEUE, EVENT_QUEUE] {
+        if let Err(refused) = device.bind_msix(queue) {
+            log!("virtio-vsock: NOT BOUND at PCI {:02x}:{:02x}.{} — the device refused a \
+                 vector for {}", pci_dev.bus, pci_dev.dev, pci_dev.func, refused);
+            return false;
+        }
+    }
+    true
+}
+
+pub fn init(devices: &[PciDevice]) {
+    let Some(pci_dev) = devices.iter().find(|d| d.is_id(VIRTIO_VENDOR, VIRTIO_VSOCK_DEVICE)) else {
+        log!("virtio-vsock: no device found");
+        return;
+    };
+    log!("virtio-vsock: found at PCI {:02x}:{:02x}.{}", pci_dev.bus, pci_dev.dev, pci_dev.func);
+    let device = VirtioDevice::init(pci_dev, VIRTIO_F_VERSION_1);
+    let guest_cid = device.device_config().read_u64(CFG_GUEST_CID);
+
+    // Leaked: see `virtio_input::InputDevice`.
+    let dma = DmaPool::alloc(DMA_SIZE).leak();
+    let rxq = Virtqueue::new(dma.subview(OFF_RXQ, 0x1000), RX_BUFS);
+    let txq = Virtqueue::new(dma.subview(OFF_TXQ, 0x1000), TX_BUFS);
+    let eventq = Virtqueue::new(dma.subview(OFF_EVQ, 0x1000), EVENT_BUFS);
+    if !arm_interrupt(pci_dev, &device) {
+        return;
+    }
+
+    let mut vsock = Vsock {
+        device,
+        rxq,
+        txq,
+        eventq,
+        rx: dma.subview(OFF_RX, RX_BUFS as usize * MAX_PACKET),
+        tx: dma.subview(OFF_TX, TX_BUFS as usize * MAX_PACKET),
+        events: dma.subview(OFF_EVENTS, EVENT_BUFS as usize * EVENT_STRIDE),
+        tx_free: Vec::new(),
+        queued: VecDeque::with_capacity(QUEUED),
+        guest_cid,
+        reset_owed: false,
+        dropped: 0,
+        reported_dropped: 0,
+        reported_refusals: 0,
+    };
+    if !vsock.start() {
+        return;
+    }
+    *VSOCK.lock() = Some(vsock);
+    ACTIVE.store(true, Ordering::Relaxed);
+    log!("virtio-vsock: ready, guest cid {guest_cid}, MSI-X vector {:#x} on table entry {}",
+        VIRTIO_VSOCK_VECTOR, MSIX_ENTRY);
+}
+
+/// Reset the device ahead of S3.
+pub fn suspend() {
+    if let Some(v) = VSOCK.lock().as_mut() {
+        v.device.reset();
+    }
+}
+
+/// Bring the device back after S3 and tell the holder every connection is
+/// gone.
+///
+/// A reset is a transport reset whichever side made it: the device forgot
+/// every stream, and a packet still queued belongs to one of them. So the
+/// holder gets the notice it gets when the host sends `TRANSPORT_RESET`, and
+/// `vsockd` tears down and reconnects exactly as it does for that. The CID is
+/// read again for the same reason the event handler reads it — a host that
+/// restored this guest somewhere else may have given it a new one. A device
+/// that does not come back is unbound: every later call answers as if there
+/// had never been one.
+pub fn resume() {
+    let mut guard = VSOCK.lock();
+    let Some(v) = guard.as_mut() else { return };
+    v.queued.clear();
+    v.reset_owed = true;
+    if !v.device.restart() || !v.start() {
+        *guard = None;
+        ACTIVE.store(false, Ordering::Relaxed);
+        log!("virtio-vsock: did not come back from the sleep; unbound");
+        return;
+    }
+    v.guest_cid = v.device.device_config().read_u64(CFG_GUEST_CID);
+    RESUMED.store(true, Ordering::Relaxed);
+    log!("virtio-vsock: back after the sleep, guest cid {}", v.guest_cid);
+}
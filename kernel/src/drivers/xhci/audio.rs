@@ -0,0 +1,634 @@
+//! USB Audio Class playback — UAC1 and UAC2 — as the third sound card soundd
+//! can claim, beside [`crate::drivers::hda`] and virtio-sound.
+//!
+//! **The kernel keeps the clock and soundd keeps the samples.** An isochronous
+//! OUT endpoint wants a packet every service interval whether or not soundd
+//! has run, and how many frames a packet carries is the device's rate rather
+//! than anything a process should compute per millisecond. So this file owns
+//! the transfer ring and sizes every packet with `toyos_xhci::uac::Pacer`,
+//! copying it out of a PCM ring laid out as the HDA stub's is: `PERIODS`
+//! periods of `PERIOD_BYTES`, cycled for as long as the stream runs. soundd
+//! fills periods and is told which have played, through the record HDA uses,
+//! so to its mix loop the device is a ring exactly as HDA's engine is, and
+//! nothing there changes.
+//!
+//! **A packet is copied when it is queued, not when it is played.** Each
+//! isochronous TD is one TRB over a staging slot in this file's own memory, so
+//! a packet never straddles the PCM ring's wrap or the transfer ring's link,
+//! and the PCM ring is the claimant's to map without a transfer ring beside
+//! it. `AHEAD` packets are queued at a time, which is the latency this adds
+//! over HDA's FIFO and the number of events one device can owe an event ring
+//! shared with every other device on the controller.
+//!
+//! **An asynchronous DAC runs on its own crystal** and says how fast on a
+//! feedback endpoint. Its reading steers the pacer, so the packets follow the
+//! device, and rides on every completion record as `feedback_rate` so
+//! soundd's DLL follows it too: a mixer timed off a nominal 48 kHz against a
+//! device playing 47.99 would drift a period every few minutes.
+//!
+//! What the descriptors say and which request sets the rate is
+//! `toyos_xhci::uac`'s, tested on the host. One device per machine, as there
+//! is one soundd; the memory outlives it, as `serial`'s does, so a DAC pulled
+//! and plugged back is the same PCM ring and a soundd that held the claim
+//! carries on into it.
+//!
+//! **The claim is the class and not the device**, as the NIC's is for a USB
+//! adapter. Any machine with an xHCI controller offers it from boot, DAC or
+//! none, because a DAC is something a person plugs in after the desktop is up
+//! and soundd can only be told about it through a claim it already holds. A
+//! bind or an unplug is news on that claim: it wakes the holder, and [`dac`]
+//! is the answer it woke for.
+
+use core::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use toyos_abi::audio::AudioCompletionRecord;
+use toyos_abi::usb_audio::{UsbAudioDac, UsbAudioInfo};
+use toyos_xhci::uac::{self as class, Pacer, Version};
+
+use super::device::Endpoint;
+use super::{Completion, Mmio, Trb, TrbRing, XhciController, PAGE, TRB_ISOCH};
+use super::{CC_MISSED_SERVICE, CC_RING_UNDERRUN, CC_SHORT_PACKET, CC_STALL, CC_SUCCESS};
+use super::CC_TRB_ERROR;
+use super::OFF_INPUT_CTX;
+use crate::drivers::DmaPool;
+use crate::log;
+use crate::mm::paging::CachePolicy;
+use crate::mm::Dma;
+use crate::object::shm::Region;
+use crate::sync::Lock;
+
+/// What the configuration descriptor offered: the streaming setting that
+/// plays, and where its rate request goes.
+#[derive(Clone, Copy)]
+pub(super) struct AudioInterface {
+    pub(super) version: Version,
+    pub(super) control_iface: u8,
+    pub(super) stream_iface: u8,
+    pub(super) alternate: u8,
+    pub(super) out_ep: Endpoint,
+    /// The isochronous IN endpoint an asynchronous OUT reports its rate on;
+    /// `None` for an adaptive or synchronous one, which follows the host.
+    pub(super) feedback: Option<Endpoint>,
+    /// Whether the rate can be set at all: the endpoint's control for UAC1,
+    /// the clock being programmable for UAC2.
+    pub(super) rate_control: bool,
+    /// The UAC2 Clock Source the rate request is addressed to; 0 for UAC1.
+    pub(super) clock: u8,
+}
+
+/// The rings Configure Endpoint named, carried to the bind.
+#[derive(Clone, Copy)]
+pub(super) struct AudioRings {
+    out_ring: TrbRing,
+    feedback_ring: Option<TrbRing>,
+}
+
+/// Which device on a controller is the DAC: what a transfer event and a
+/// teardown are matched against.
+#[derive(Clone, Copy)]
+pub(super) struct AudioDevice {
+    pub(super) slot_id: u8,
+    pub(super) port_idx: u8,
+}
+
+/// The pipeline, in periods and bytes: HDA's shape, for HDA's reason — soundd's
+/// mix loop, its client ring depth and gate A's counters are sized against it.
+const PERIODS: usize = 8;
+const PERIOD_BYTES: usize = 512;
+
+/// Packets queued at once, each in its own staging slot. Eight is 8 ms at full
+/// speed: deep enough that a scheduler pass late by a few milliseconds costs
+/// nothing, and shallow enough that a stop is heard within a period or two.
+const AHEAD: usize = 8;
+/// A staging slot: the largest full-speed isochronous packet, rounded up. A
+/// high-speed endpoint with more room than this is only ever given this much,
+/// which at 48 kHz stereo is still five times what one microframe carries.
+const SLOT: usize = 1024;
+/// Feedback transfers the controller holds at once, and how large each is.
+const FEEDBACK_TRANSFERS: usize = 2;
+const FEEDBACK_BUF: usize = 64;
+
+const OFF_OUT_RING: usize = 0;
+const OFF_FEEDBACK_RING: usize = PAGE;
+const OFF_FEEDBACK_BUFS: usize = 2 * PAGE;
+const OFF_STAGING: usize = 3 * PAGE;
+const PRIVATE_SIZE: usize = OFF_STAGING + AHEAD * SLOT;
+
+const _: () = assert!(FEEDBACK_TRANSFERS * FEEDBACK_BUF <= PAGE);
+// No staging slot crosses the 64 KiB boundary a TRB's buffer may not.
+const _: () = assert!(PRIVATE_SIZE <= 64 * 1024);
+const _: () = assert!(PERIODS * PERIOD_BYTES <= crate::mm::PAGE_2M as usize);
+
+/// The rings, the feedback buffers and the staging slots, which no process
+/// maps.
+static MEMORY: Lock<Option<Dma<'static>>> = Lock::new(None);
+/// The PCM ring, which soundd maps. Apart from [`MEMORY`] because a mapping is
+/// 2 MiB at a time and the transfer rings must not be in it.
+static PCM: Lock<Option<Dma<'static>>> = Lock::new(None);
+
+/// The bound DAC. Reached from the controller's event ring, under `XHCI`, and
+/// from the claim's writes; it takes nothing else while it is held.
+static DAC: Lock<Option<Dac>> = Lock::new(None);
+
+/// Whether the claimant last asked for the stream to run. Outside [`DAC`] so
+/// it outlives a replug: a DAC bound while soundd is playing starts playing.
+static WANTED: AtomicBool = AtomicBool::new(false);
+
+/// The claim's description, and the PCM ring it maps. Set by [`offer`] once a
+/// controller is up and restated by every bind, so a claim is `Absent` only on
+/// a machine with no xHCI to plug a DAC into.
+static INFO: Lock<Option<(UsbAudioInfo, Region)>> = Lock::new(None);
+
+/// What the completions left for the claimant, as `hda`'s ISR leaves it:
+/// written under [`DAC`] and read without it.
+struct Pending {
+    mask: AtomicU32,
+    timestamp: AtomicU64,
+    /// The rate the device reports, millihertz; 0 until it has said.
+    rate: AtomicU32,
+    /// Packets that came back with anything but success, and whether the
+    /// claimant has been told.
+    errors: AtomicU32,
+    named_error: AtomicBool,
+    /// A DAC bound or went away since the claimant last asked [`dac`].
+    news: AtomicBool,
+}
+
+static PENDING: Pending = Pending {
+    mask: AtomicU32::new(0),
+    timestamp: AtomicU64::new(0),
+    rate: AtomicU32::new(0),
+    errors: AtomicU32::new(0),
+    named_error: AtomicBool::new(false),
+    news: AtomicBool::new(false),
+};
+
+static INBOX_WATCHERS: Lock<alloc::vec::Vec<crate::inbox::InboxId>> =
+    Lock::new(alloc::vec::Vec::new());
+
+fn memory() -> Dma<'static> {
+    *MEMORY.lock().get_or_insert_with(|| DmaPool::alloc(PRIVATE_SIZE).leak())
+}
+
+fn pcm() -> Dma<'static> {
+    *PCM.lock().get_or_insert_with(|| {
+        let pcm = DmaPool::alloc(crate::mm::PAGE_2M as usize).leak();
+        pcm.zero();
+        pcm
+    })
+}
+
+/// What a completion changed that has to be said once the lock is dropped.
+enum News {
+    Nothing,
+    /// Periods finished while the stream runs: the claimant is owed a wake.
+    Played,
+    /// The endpoint stopped, and with it the stream.
+    Halted(u32),
+}
+
+/// One queued packet: its TRB, its length, and whether it is part of the
+/// stream the claimant is being told about.
+#[derive(Clone, Copy)]
+struct Packet {
+    trb: u64,
+    bytes: u32,
+    counted: bool,
+}
+
+struct Dac {
+    slot_id: u8,
+    db: Mmio,
+    out_dci: u8,
+    out_ring: TrbRing,
+    feedback: Option<(u8, TrbRing)>,
+    memory: Dma<'static>,
+    pcm: Dma<'static>,
+    pacer: Pacer,
+    high_speed: bool,
+    running: bool,
+    halted: bool,
+    /// Bytes of the PCM ring ever queued, played, and played as of the last
+    /// record. Each modulo the ring is where; `reported` is where a restart
+    /// primes from.
+    queued: u64,
+    played: u64,
+    reported: u64,
+    /// Packets in flight, oldest at `head`, each in staging slot `seq %
+    /// AHEAD` for the `seq` it was queued as.
+    flight: [Packet; AHEAD],
+    head: usize,
+    in_flight: usize,
+    /// The feedback transfers outstanding, by TRB.
+    feedback_trbs: [Option<u64>; FEEDBACK_TRANSFERS],
+}
+
+impl Dac {
+    fn doorbell(&self, dci: u8) {
+        fence(Ordering::Release);
+        self.db.write_u32(self.slot_id as u64 * 4, dci as u32);
+    }
+
+    /// Queue packets until `AHEAD` are in flight.
+    fn fill(&mut self) {
+        let ring = (PERIODS * PERIOD_BYTES) as u64;
+        let mut queued_any = false;
+        while self.running && self.in_flight < AHEAD {
+            let bytes = self.pacer.frames() as usize * class::FRAME_BYTES as usize;
+            let slot = (self.head + self.in_flight) % AHEAD;
+            let mut packet = [0u8; SLOT];
+            let at = (self.queued % ring) as usize;
+            let first = bytes.min(ring as usize - at);
+            self.pcm.copy_to(at, &mut packet[..first]);
+            self.pcm.copy_to(0, &mut packet[first..bytes]);
+            self.memory.copy_from(OFF_STAGING + slot * SLOT, &packet[..bytes]);
+
+            let mut trb = Trb::ZERO;
+            trb.param = self.memory.phys() + (OFF_STAGING + slot * SLOT) as u64;
+            trb.status = bytes as u32;
+            // SIA: each TD plays in the interval after the one before it,
+            // which is what keeps the stream a stream. IOC on every one,
+            // because every one is a completion soundd may be owed.
+            trb.control = TRB_ISOCH | (1 << 31) | (1 << 5);
+            let trb = self.out_ring.enqueue(trb);
+            self.flight[slot] = Packet { trb, bytes: bytes as u32, counted: true };
+            self.in_flight += 1;
+            self.queued += bytes as u64;
+            queued_any = true;
+        }
+        if queued_any {
+            self.doorbell(self.out_dci);
+        }
+    }
+
+    /// Give the controller feedback buffer `buf` to fill.
+    fn post_feedback(&mut self, buf: usize) {
+        let Some((dci, ref mut ring)) = self.feedback else { return };
+        let mut trb = Trb::ZERO;
+        trb.param = self.memory.phys() + (OFF_FEEDBACK_BUFS + buf * FEEDBACK_BUF) as u64;
+        trb.status = FEEDBACK_BUF as u32;
+        trb.control = TRB_ISOCH | (1 << 31) | (1 << 5) | (1 << 2); // SIA + IOC + ISP
+        self.feedback_trbs[buf] = Some(ring.enqueue(trb));
+        self.doorbell(dci);
+    }
+
+    /// Start playing at the beginning of the period after the last one
+    /// reported, which is where soundd's next prime starts filling.
+    fn start(&mut self) {
+        if self.running || self.halted {
+            return;
+        }
+        let at = self.reported / PERIOD_BYTES as u64 * PERIOD_BYTES as u64;
+        self.queued = at;
+        self.played = at;
+        self.reported = at;
+        PENDING.mask.store(0, Ordering::Relaxed);
+        self.running = true;
+        self.fill();
+        for buf in 0..FEEDBACK_TRANSFERS {
+            if self.feedback_trbs[buf].is_none() {
+                self.post_feedback(buf);
+            }
+        }
+    }
+
+    /// Stop queueing and stop reporting. What is in flight plays out as
+    /// it would on HDA's FIFO, and none of it is a completion: soundd takes the
+    /// whole ring back the moment it stops, so a record after this would be
+    /// for a period it already holds.
+    fn stop(&mut self) {
+        self.running = false;
+        for packet in &mut self.flight {
+            packet.counted = false;
+        }
+        PENDING.mask.store(0, Ordering::Relaxed);
+    }
+
+    fn completed(&mut self, dci: u8, trb: u64, code: u32) -> News {
+        if dci == self.out_dci {
+            // An underrun names no TRB: the ring ran dry, which is what a
+            // stopped stream does, and nothing is owed for it.
+            if code == CC_RING_UNDERRUN || self.in_flight == 0 || self.flight[self.head].trb != trb
+            {
+                return News::Nothing;
+            }
+            let packet = self.flight[self.head];
+            self.head = (self.head + 1) % AHEAD;
+            self.in_flight -= 1;
+            if code == CC_STALL || code == CC_TRB_ERROR {
+                // A TRB the controller refused or an endpoint it halted: an
+                // isochronous endpoint has no retry and halts for nothing
+                // else, so the stream is over until a replug.
+                self.halted = true;
+                self.stop();
+                return News::Halted(code);
+            }
+            // A packet that came back played, whatever it says. Missed Service
+            // is the controller having been late, and the audio it carried
+            // is as gone as if it had gone out.
+            if code != CC_SUCCESS && code != CC_SHORT_PACKET && code != CC_MISSED_SERVICE {
+                PENDING.errors.fetch_add(1, Ordering::Relaxed);
+            }
+            let mut news = News::Nothing;
+            if packet.counted {
+                let before = self.played;
+                self.played += packet.bytes as u64;
+                let mask = class::periods_done(
+                    before, self.played, PERIOD_BYTES as u32, PERIODS as u32,
+                );
+                if mask != 0 && self.running {
+                    self.reported = self.played;
+                    PENDING.timestamp.store(crate::clock::nanos_since_boot(), Ordering::Relaxed);
+                    PENDING.mask.fetch_or(mask, Ordering::Release);
+                    news = News::Played;
+                }
+            }
+            self.fill();
+            news
+        } else if self.feedback.is_some_and(|(fb, _)| fb == dci) {
+            let Some(buf) = self.feedback_trbs.iter().position(|&t| t == Some(trb)) else {
+                return News::Nothing;
+            };
+            self.feedback_trbs[buf] = None;
+            if code == CC_SUCCESS || code == CC_SHORT_PACKET {
+                let mut bytes = [0u8; 4];
+                self.memory.copy_to(OFF_FEEDBACK_BUFS + buf * FEEDBACK_BUF, &mut bytes);
+                let nominal = class::per_ms(class::RATE);
+                if let Some(per_ms) = class::feedback(&bytes, self.high_speed, nominal) {
+                    self.pacer.steer(per_ms);
+                    PENDING.rate.store(self.pacer.rate_millihz(), Ordering::Relaxed);
+                }
+            }
+            // Only while the stream runs: a feedback endpoint answers every
+            // interval, and an idle DAC would be a thousand events a second
+            // saying its crystal has not changed.
+            if self.running {
+                self.post_feedback(buf);
+            }
+            News::Nothing
+        } else {
+            News::Nothing
+        }
+    }
+}
+
+/// Are completions or news pending? Lock-free — handle readiness, an inbox
+/// watch and the scheduler's park-time recheck all ask this.
+pub fn has_pending() -> bool {
+    PENDING.mask.load(Ordering::Acquire) != 0 || PENDING.news.load(Ordering::Acquire)
+}
+
+/// The bound DAC, for the claim's `SYS_USB_AUDIO_DAC`. Clears the news that
+/// made the claim readable.
+pub fn dac() -> Option<UsbAudioDac> {
+    PENDING.news.store(false, Ordering::Release);
+    if DAC.lock().is_none() {
+        return None;
+    }
+    INFO.lock().as_ref().map(|(info, _)| UsbAudioDac {
+        version: info.version,
+        feedback: info.feedback != 0,
+    })
+}
+
+pub fn add_inbox_watcher(id: crate::inbox::InboxId) {
+    let mut watchers = INBOX_WATCHERS.lock();
+    if !watchers.contains(&id) {
+        watchers.push(id);
+    }
+}
+
+pub fn remove_inbox_watcher(id: crate::inbox::InboxId) {
+    INBOX_WATCHERS.lock().retain(|&x| x != id);
+}
+
+pub fn inbox_watchers() -> alloc::vec::Vec<crate::inbox::InboxId> {
+    INBOX_WATCHERS.lock().clone()
+}
+
+/// Copy one completion record into `buf`, with the device's measured rate,
+/// and name a transfer error the first time one has been counted.
+pub fn drain_completed(buf: &mut crate::user_ptr::UserBytesMut) -> usize {
+    let errors = PENDING.errors.load(Ordering::Relaxed);
+    if errors != 0 && !PENDING.named_error.swap(true, Ordering::Relaxed) {
+        log!("usb-audio: a packet came back with an error ({errors} so far)");
+    }
+    let mask = PENDING.mask.swap(0, Ordering::AcqRel);
+    if mask == 0 {
+        return 0;
+    }
+    let record = AudioCompletionRecord {
+        mask,
+        feedback_rate: PENDING.rate.load(Ordering::Relaxed),
+        timestamp_nanos: PENDING.timestamp.load(Ordering::Acquire),
+    };
+    let mut bytes = [0u8; AudioCompletionRecord::SIZE];
+    bytes[0..4].copy_from_slice(&record.mask.to_le_bytes());
+    bytes[4..8].copy_from_slice(&record.feedback_rate.to_le_bytes());
+    bytes[8..16].copy_from_slice(&record.timestamp_nanos.to_le_bytes());
+    buf.write_at(0, &bytes);
+    AudioCompletionRecord::SIZE
+}
+
+pub fn info() -> Option<(UsbAudioInfo, Region)> {
+    INFO.lock().clone()
+}
+
+/// The claim's description with `version` and `feedback` as a DAC states them,
+/// or as 0 where there is none yet. Everything else is this file's constant.
+fn describe(version: u8, feedback: bool) -> (UsbAudioInfo, Region) {
+    let region = Region {
+        phys: crate::DirectMap::from_phys(pcm().phys()),
+        size: crate::mm::PAGE_2M,
+        cache: CachePolicy::DeferToMtrr,
+        pages: None,
+    };
+    (UsbAudioInfo {
+        pcm: toyos_abi::HANDLE_INVALID,
+        period_bytes: PERIOD_BYTES as u32,
+        rate: class::RATE,
+        channels: class::CHANNELS,
+        periods: PERIODS as u8,
+        version,
+        feedback: u8::from(feedback),
+    }, region)
+}
+
+/// Make the claim mintable on a machine with a controller to plug a DAC into,
+/// whether or not one is plugged in. From the boot scan, after any DAC on it
+/// has bound, so a DAC present at boot is described as itself.
+pub(super) fn offer() {
+    let mut info = INFO.lock();
+    if info.is_none() {
+        *info = Some(describe(0, false));
+    }
+}
+
+/// Tell the claimant a DAC came or went, through the wake its completions use.
+fn announce() {
+    PENDING.news.store(true, Ordering::Release);
+    publish();
+}
+
+/// Wake whoever waits on the claim. The xHCI poll runs with interrupts on, and
+/// a publish is an ISR's operation on this CPU's slot.
+fn publish() {
+    let _irq = crate::hw::IrqGuard::close();
+    let now = crate::clock::nanos_since_boot();
+    crate::irq_ring::isr_publish(crate::irq_ring::IrqSource::Audio, now);
+    crate::preempt::set_need_resched();
+}
+
+/// Start or stop the stream, as the claimant's write asks. Remembered when
+/// there is no DAC, so one plugged back in picks up where soundd is.
+pub fn run(start: bool) {
+    WANTED.store(start, Ordering::Relaxed);
+    if let Some(dac) = DAC.lock().as_mut() {
+        if start { dac.start() } else { dac.stop() }
+    }
+}
+
+/// Build the input context for the streaming endpoints and the rings they run
+/// on, or `None` where the machine has its DAC already.
+pub(super) fn prepare(
+    ctrl: &mut XhciController,
+    slot_id: u8,
+    speed: u8,
+    port_idx: u8,
+    info: &AudioInterface,
+) -> Option<AudioRings> {
+    if DAC.lock().is_some() {
+        log!("usb-audio: slot {slot_id} is a DAC and the machine has one; port {} is not bound",
+            port_idx + 1);
+        return None;
+    }
+    let memory = memory();
+    let rings = AudioRings {
+        out_ring: TrbRing::init(memory.subview(OFF_OUT_RING, PAGE)),
+        feedback_ring: info
+            .feedback
+            .map(|_| TrbRing::init(memory.subview(OFF_FEEDBACK_RING, PAGE))),
+    };
+
+    let dma = ctrl.dma();
+    let input_ctx = super::zero_dma(dma, OFF_INPUT_CTX, PAGE);
+    // EP Type 1 is Isoch Out and 5 Isoch In. CErr is 0: an isochronous
+    // endpoint has no retries to count.
+    let mut endpoints = [(info.out_ep, 1u32, rings.out_ring.dequeue()); 2];
+    let mut count = 1;
+    if let (Some(ep), Some(ring)) = (info.feedback, rings.feedback_ring) {
+        endpoints[1] = (ep, 5, ring.dequeue());
+        count = 2;
+    }
+    let endpoints = &endpoints[..count];
+    let added = endpoints.iter().fold(1u32, |mask, (ep, ..)| mask | (1u32 << ep.dci()));
+    ctrl.write_ctx32(input_ctx, 0, 1, added);
+    let max_dci = endpoints.iter().map(|(ep, ..)| ep.dci()).max().unwrap_or(1);
+    ctrl.write_slot_context(input_ctx, port_idx, speed, max_dci, None);
+
+    for &(ep, ep_type, dequeue) in endpoints {
+        let ctx = ep.dci() as usize + 1;
+        ctrl.write_ctx32(input_ctx, ctx, 0, isochronous_interval(speed, &ep) << 16);
+        ctrl.write_ctx32(
+            input_ctx,
+            ctx,
+            1,
+            (ep_type << 3) | ((ep.max_burst as u32) << 8) | ((ep.max_packet as u32) << 16),
+        );
+        ctrl.write_ctx32(input_ctx, ctx, 2, dequeue as u32);
+        ctrl.write_ctx32(input_ctx, ctx, 3, (dequeue >> 32) as u32);
+        // Max ESIT Payload over Average TRB Length: one packet per interval,
+        // as `interrupt_input_context` reasons for an interrupt endpoint.
+        let esit = ep.max_packet as u32 * (ep.max_burst as u32 + 1);
+        ctrl.write_ctx32(input_ctx, ctx, 4, (esit << 16) | ep.max_packet as u32);
+    }
+    Some(rings)
+}
+
+/// An isochronous endpoint's Interval field, as a power of two of 125 µs. The
+/// descriptor's bInterval is an exponent plus one at every speed, of frames
+/// below High Speed and of microframes at it (USB 2.0 §9.6.6).
+fn isochronous_interval(speed: u8, ep: &Endpoint) -> u32 {
+    let exp = ep.interval.clamp(1, 16) as u32 - 1;
+    if speed <= 2 { exp + 3 } else { exp }
+}
+
+/// Make the DAC soundd's: describe it to the claim, and start it if soundd is
+/// already playing.
+pub(super) fn bind(
+    ctrl: &mut XhciController,
+    slot_id: u8,
+    port_idx: u8,
+    speed: u8,
+    info: &AudioInterface,
+    rings: AudioRings,
+) {
+    let high_speed = speed > 2;
+    let interval_us = class::interval_us(high_speed, info.out_ep.interval);
+    let room = (info.out_ep.max_packet as usize).min(SLOT) as u32 / class::FRAME_BYTES;
+    let mut dac = Dac {
+        slot_id,
+        db: ctrl.db_base,
+        out_dci: info.out_ep.dci(),
+        out_ring: rings.out_ring,
+        feedback: info.feedback.zip(rings.feedback_ring).map(|(ep, ring)| (ep.dci(), ring)),
+        memory: memory(),
+        pcm: pcm(),
+        pacer: Pacer::new(class::RATE, interval_us, room),
+        high_speed,
+        running: false,
+        halted: false,
+        queued: 0,
+        played: 0,
+        reported: 0,
+        flight: [Packet { trb: 0, bytes: 0, counted: false }; AHEAD],
+        head: 0,
+        in_flight: 0,
+        feedback_trbs: [None; FEEDBACK_TRANSFERS],
+    };
+    PENDING.rate.store(0, Ordering::Relaxed);
+    if WANTED.load(Ordering::Relaxed) {
+        dac.start();
+    }
+    *DAC.lock() = Some(dac);
+    ctrl.audio = Some(AudioDevice { slot_id, port_idx });
+
+    let version = match info.version { Version::Uac1 => 1, Version::Uac2 => 2 };
+    *INFO.lock() = Some(describe(version, info.feedback.is_some()));
+    // A soundd already running holds the claim and is playing through
+    // whatever else the machine has; this is what tells it to move.
+    announce();
+
+    log!("usb-audio: UAC{} DAC on slot {slot_id}, {} Hz {}ch, {} us packets of up to {room} \
+         frames{}", if info.version == Version::Uac1 { 1 } else { 2 }, class::RATE,
+        class::CHANNELS, interval_us,
+        if info.feedback.is_some() { ", asynchronous with feedback" } else { "" });
+}
+
+/// A transfer event on the DAC's slot.
+pub(super) fn completed(dci: u8, trb: u64, code: u32) {
+    let news = match DAC.lock().as_mut() {
+        Some(dac) => dac.completed(dci, trb, code),
+        None => return,
+    };
+    match news {
+        News::Nothing => {}
+        News::Played => publish(),
+        News::Halted(code) => log!("usb-audio: the stream stopped ({}); the DAC is silent until \
+             it is replugged", Completion(code)),
+    }
+}
+
+impl XhciController {
+    /// The device on `port_idx` is being torn down; if it is the DAC, the
+    /// claim stays and says so the next time it is asked.
+    pub(super) fn unbind_audio(&mut self, port_idx: u8) {
+        let Some(audio) = self.audio.filter(|a| a.port_idx == port_idx) else { return };
+        self.audio = None;
+        drop(DAC.lock().take());
+        *INFO.lock() = Some(describe(0, false));
+        PENDING.mask.store(0, Ordering::Relaxed);
+        log!("usb-audio: DAC on slot {} unplugged from port {}", audio.slot_id, port_idx + 1);
+        announce();
+    }
+}
@@ -0,0 +1,614 @@
+//! USB Ethernet adapters — CDC ECM and NCM — behind the same
+//! [`crate::net::Nic`] netd drives virtio-net and the e1000e through.
+//!
+//! **netd does not know it has a USB adapter.** The claim carries the same
+//! [`NicInfo`] and one 2 MiB page: receive buffers at `rx_buf_offset`, the
+//! transmit buffer at `tx_buf_offset`, and a `net_hdr_size` of zero. What a
+//! descriptor said, what the interrupt endpoint reported and how a frame is
+//! framed on the bulk pipes is `toyos_xhci::cdc`'s, tested on the host; this
+//! file moves frames between pages and puts TRBs on rings.
+//!
+//! **netd's page is not the controller's.** The controller reads and writes a
+//! second page only the kernel has, and every frame is copied between the two.
+//! A virtio or e1000e descriptor names one buffer of one length; a TRB names any
+//! physical address at all, and the rings holding them would be in memory a
+//! process can write. A copy per frame at USB speeds costs nothing measurable.
+//!
+//! **An adapter can leave.** Pulling it tears its port down like any device's,
+//! and [`crate::net::unregister`] then answers netd `NotFound` on every call,
+//! which is how netd learns to take the interface down. Both pages stay: the
+//! first adapter allocates them and every later one is handed the same two, so
+//! the mapping netd was given once, at claim time, is still the right one when
+//! a dongle is plugged back in.
+//!
+//! One adapter per machine, as there is one NIC: a second, or one on a machine
+//! that already has a PCI NIC, is refused before its endpoints are configured.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::sync::atomic::{fence, Ordering};
+
+use toyos_abi::syscall::SyscallError;
+use toyos_xhci::cdc::{self as class, ntb, BadFrame, Notification};
+
+use super::device::{interrupt_interval, Endpoint};
+use super::{Completion, Mmio, Trb, TrbRing, XhciController, PAGE, TRB_NORMAL};
+use super::{CC_SHORT_PACKET, CC_SUCCESS, OFF_INPUT_CTX};
+use crate::drivers::DmaPool;
+use crate::log;
+use crate::mm::paging::CachePolicy;
+use crate::mm::Dma;
+use crate::net::NicInfo;
+use crate::object::shm::Region;
+use crate::sync::Lock;
+
+/// What the configuration descriptor offered: the communication interface, its
+/// notification endpoint, and the data interface's setting with the bulk pair.
+#[derive(Clone, Copy)]
+pub(super) struct NetInterface {
+    /// Where class requests are addressed.
+    pub(super) comm_iface: u8,
+    pub(super) data_iface: u8,
+    /// The data interface's setting with endpoints, which SET_INTERFACE selects.
+    pub(super) alternate: u8,
+    pub(super) ncm: bool,
+    /// The string descriptor index of the station address.
+    pub(super) mac_string: u8,
+    pub(super) notify: Endpoint,
+    pub(super) in_ep: Endpoint,
+    pub(super) out_ep: Endpoint,
+}
+
+/// The three transfer rings Configure Endpoint named, carried to the bind.
+#[derive(Clone, Copy)]
+pub(super) struct NetRings {
+    in_ring: TrbRing,
+    out_ring: TrbRing,
+    notify_ring: TrbRing,
+}
+
+/// Which device on a controller is the adapter: what a transfer event and a
+/// teardown are matched against.
+#[derive(Clone, Copy)]
+pub(super) struct NetDevice {
+    pub(super) slot_id: u8,
+    pub(super) port_idx: u8,
+}
+
+const RX_BUF_COUNT: usize = 64;
+const RX_BUF_SIZE: u16 = 2048;
+const TX_BUF_LEN: usize = 2048;
+
+// netd's page (byte offsets):
+const OFF_RX_BUFS: usize = 0; // 64 × 2 KiB
+const OFF_TX_BUF: usize = RX_BUF_COUNT * RX_BUF_SIZE as usize;
+const SHARED_SIZE: usize = OFF_TX_BUF + TX_BUF_LEN;
+
+/// Receive transfers the controller holds at once. Each is a whole NCM block,
+/// which is what SET_NTB_INPUT_SIZE told the device it may send; an ECM
+/// transfer ends short at the end of its one frame.
+const IN_TRANSFERS: usize = 4;
+const IN_BUF: usize = ntb::IN_SIZE as usize;
+/// Frames handed to the controller and not yet reported sent.
+const TX_SLOTS: usize = 8;
+const TX_SLOT: usize = 2048;
+
+// The kernel's page:
+const OFF_IN_RING: usize = 0;
+const OFF_OUT_RING: usize = PAGE;
+const OFF_NOTIFY_RING: usize = 2 * PAGE;
+const OFF_NOTIFY: usize = 3 * PAGE; // 16 B, one notification
+const OFF_TX_STAGE: usize = 4 * PAGE; // 8 × 2 KiB
+const OFF_IN_BUFS: usize = OFF_TX_STAGE + TX_SLOTS * TX_SLOT; // 4 × 8 KiB
+const PRIVATE_SIZE: usize = OFF_IN_BUFS + IN_TRANSFERS * IN_BUF;
+
+const _: () = assert!(ntb::TX_HEADER + class::MAX_FRAME <= TX_SLOT);
+const _: () = assert!(class::MAX_FRAME <= RX_BUF_SIZE as usize);
+const _: () = assert!(RX_BUF_COUNT <= u8::MAX as usize + 1);
+const _: () = assert!(PRIVATE_SIZE <= crate::mm::PAGE_2M as usize);
+const _: () = assert!(SHARED_SIZE <= crate::mm::PAGE_2M as usize);
+
+/// The two pages, once allocated. See the module doc for why they outlive the
+/// adapter that allocated them.
+#[derive(Clone, Copy)]
+struct Pages {
+    private: Dma<'static>,
+    shared: Dma<'static>,
+}
+
+static PAGES: Lock<Option<Pages>> = Lock::new(None);
+
+/// The bound adapter. Reached from two sides — the controller's event ring,
+/// under `XHCI`, and netd's syscalls, under `net::NIC` — and taking nothing
+/// else while it is held, so neither order can meet the other.
+static ADAPTER: Lock<Option<Adapter>> = Lock::new(None);
+
+fn pages() -> Pages {
+    *PAGES.lock().get_or_insert_with(|| Pages {
+        private: DmaPool::alloc(PRIVATE_SIZE).leak(),
+        shared: DmaPool::alloc(SHARED_SIZE).leak(),
+    })
+}
+
+/// Why a received frame did not reach netd.
+#[derive(Clone, Copy)]
+enum Dropped {
+    /// Every buffer was with netd or already holding a frame for it.
+    Full,
+    Frame(BadFrame),
+    Block(ntb::Malformed),
+}
+
+impl core::fmt::Display for Dropped {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Full => write!(f, "netd held every receive buffer"),
+            Self::Frame(why) => write!(f, "{why}"),
+            Self::Block(why) => write!(f, "a transfer block was refused, as {why}"),
+        }
+    }
+}
+
+/// What a completion changed, and so who has to be told.
+enum News {
+    Nothing,
+    Frames,
+    Link,
+}
+
+struct Adapter {
+    slot_id: u8,
+    db: Mmio,
+    ncm: bool,
+    in_dci: u8,
+    out_dci: u8,
+    notify_dci: u8,
+    /// The bulk OUT endpoint's packet size, which decides when a transfer
+    /// needs a zero-length packet to end it.
+    out_packet: u16,
+    in_ring: TrbRing,
+    out_ring: TrbRing,
+    notify_ring: TrbRing,
+    private: Dma<'static>,
+    shared: Dma<'static>,
+    /// Where each receive buffer's TRB landed. The endpoint completes them in
+    /// the order they were posted, and `in_next` is the one owed next; an
+    /// event naming another is not an answer to anything outstanding.
+    in_trbs: [u64; IN_TRANSFERS],
+    in_next: usize,
+    /// One received transfer, copied out of the kernel's page so the block
+    /// walk reads a slice and not the device's memory.
+    scratch: Box<[u8]>,
+    /// Transmit slots in flight, oldest at `tx_head`.
+    tx_head: usize,
+    tx_busy: usize,
+    /// NCM's block sequence number, which the device may use to spot a lost one.
+    tx_seq: u16,
+    /// netd's receive buffers: free ones, ones holding a frame netd has not
+    /// polled (oldest first), and ones netd is holding.
+    free: [u8; RX_BUF_COUNT],
+    free_n: usize,
+    ready: [(u8, u16); RX_BUF_COUNT],
+    ready_head: usize,
+    ready_n: usize,
+    lent: [bool; RX_BUF_COUNT],
+    /// The last connection notification. Up until the adapter says otherwise:
+    /// QEMU's `usb-net` says nothing at all, and a link taken as down until
+    /// told would be one that never came up.
+    link: bool,
+    /// The bulk OUT endpoint stopped. Nothing is put on a halted ring, so a
+    /// frame submitted after this is dropped.
+    out_halted: bool,
+    dropped: u32,
+    reported_drops: u32,
+    last_drop: Option<Dropped>,
+    tx_dropped: u32,
+}
+
+impl Adapter {
+    fn doorbell(&self, dci: u8) {
+        fence(Ordering::Release);
+        self.db.write_u32(self.slot_id as u64 * 4, dci as u32);
+    }
+
+    /// Give the controller receive buffer `buf` to fill.
+    fn post_in(&mut self, buf: usize) {
+        let mut trb = Trb::ZERO;
+        trb.param = self.private.phys() + (OFF_IN_BUFS + buf * IN_BUF) as u64;
+        trb.status = IN_BUF as u32;
+        trb.control = TRB_NORMAL | (1 << 2) | (1 << 5); // ISP + IOC
+        self.in_trbs[buf] = self.in_ring.enqueue(trb);
+        self.doorbell(self.in_dci);
+    }
+
+    fn post_notify(&mut self) {
+        let mut trb = Trb::ZERO;
+        trb.param = self.private.phys() + OFF_NOTIFY as u64;
+        trb.status = class::NOTIFICATION_BYTES as u32;
+        trb.control = TRB_NORMAL | (1 << 5); // IOC
+        self.notify_ring.enqueue(trb);
+        self.doorbell(self.notify_dci);
+    }
+
+    fn completed(&mut self, dci: u8, trb: u64, code: u32, residue: u32) -> News {
+        let ok = code == CC_SUCCESS || code == CC_SHORT_PACKET;
+        if dci == self.in_dci {
+            if trb != self.in_trbs[self.in_next] {
+                return News::Nothing;
+            }
+            let buf = self.in_next;
+            self.in_next = (buf + 1) % IN_TRANSFERS;
+            if !ok {
+                log!("usb-net: receiving stopped ({}); no frame arrives until the adapter is \
+                     replugged", Completion(code));
+                return News::Nothing;
+            }
+            let len = IN_BUF.saturating_sub(residue as usize);
+            let delivered = self.received(buf, len);
+            self.post_in(buf);
+            if delivered { News::Frames } else { News::Nothing }
+        } else if dci == self.notify_dci {
+            if !ok {
+                log!("usb-net: the notification endpoint stopped ({}); the link is taken as it \
+                     last stood", Completion(code));
+                return News::Nothing;
+            }
+            let len = class::NOTIFICATION_BYTES.saturating_sub(residue as usize);
+            let changed = self.notified(len);
+            self.post_notify();
+            if changed { News::Link } else { News::Nothing }
+        } else if dci == self.out_dci {
+            // A frame's last TRB is the only one that interrupts, so one event
+            // is one frame; an error ends the frame wherever it happened.
+            if self.tx_busy > 0 {
+                self.tx_head = (self.tx_head + 1) % TX_SLOTS;
+                self.tx_busy -= 1;
+            }
+            if !ok && !self.out_halted {
+                self.out_halted = true;
+                log!("usb-net: sending stopped ({}); no frame leaves until the adapter is \
+                     replugged", Completion(code));
+            }
+            News::Nothing
+        } else {
+            News::Nothing
+        }
+    }
+
+    /// Split one received transfer into frames for netd. `true` if any arrived.
+    fn received(&mut self, buf: usize, len: usize) -> bool {
+        // A zero-length packet is how a device ends a transfer that filled its
+        // last packet exactly; one on its own carries nothing.
+        if len == 0 {
+            return false;
+        }
+        let mut scratch = core::mem::take(&mut self.scratch);
+        self.private.copy_to(OFF_IN_BUFS + buf * IN_BUF, &mut scratch[..len]);
+        let mut delivered = false;
+        if self.ncm {
+            for datagram in ntb::datagrams(&scratch[..len]) {
+                match datagram {
+                    Ok(frame) => delivered |= self.deliver(&scratch[frame]),
+                    Err(why) => self.drop_frame(Dropped::Block(why)),
+                }
+            }
+        } else {
+            match class::ecm_frame(len) {
+                Ok(n) => delivered = self.deliver(&scratch[..n]),
+                Err(why) => self.drop_frame(Dropped::Frame(why)),
+            }
+        }
+        self.scratch = scratch;
+        delivered
+    }
+
+    fn deliver(&mut self, frame: &[u8]) -> bool {
+        if self.free_n == 0 {
+            self.drop_frame(Dropped::Full);
+            return false;
+        }
+        self.free_n -= 1;
+        let buf = self.free[self.free_n];
+        self.shared.copy_from(OFF_RX_BUFS + buf as usize * RX_BUF_SIZE as usize, frame);
+        let at = (self.ready_head + self.ready_n) % RX_BUF_COUNT;
+        self.ready[at] = (buf, frame.len() as u16);
+        self.ready_n += 1;
+        true
+    }
+
+    fn drop_frame(&mut self, why: Dropped) {
+        self.dropped = self.dropped.wrapping_add(1);
+        self.last_drop = Some(why);
+    }
+
+    /// `true` if the link changed.
+    fn notified(&mut self, len: usize) -> bool {
+        let mut bytes = [0u8; class::NOTIFICATION_BYTES];
+        self.private.copy_to(OFF_NOTIFY, &mut bytes);
+        match Notification::parse(&bytes[..len]) {
+            Ok(Notification::Connection(up)) if up != self.link => {
+                self.link = up;
+                log!("usb-net: link {}", if up { "up" } else { "down" });
+                true
+            }
+            Ok(Notification::Speed { down, up }) => {
+                log!("usb-net: {} Mb/s down, {} Mb/s up", down / 1_000_000, up / 1_000_000);
+                false
+            }
+            Ok(_) => false,
+            Err(why) => {
+                log!("usb-net: a notification was refused: {why}");
+                false
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Option<(usize, usize)> {
+        // Reported on change rather than per frame, as the e1000e reports its
+        // drops: a peer flooding the adapter costs one line per poll.
+        if self.dropped != self.reported_drops {
+            if let Some(why) = self.last_drop {
+                log!("usb-net: dropped {} received frame(s), the last because {}",
+                    self.dropped.wrapping_sub(self.reported_drops), why);
+            }
+            self.reported_drops = self.dropped;
+        }
+        if self.ready_n == 0 {
+            return None;
+        }
+        let (buf, len) = self.ready[self.ready_head];
+        self.ready_head = (self.ready_head + 1) % RX_BUF_COUNT;
+        self.ready_n -= 1;
+        self.lent[buf as usize] = true;
+        Some((buf as usize, len as usize))
+    }
+
+    fn give_back(&mut self, buf: usize) -> Result<(), SyscallError> {
+        // `lent` is exactly `RX_BUF_COUNT` long, so `get` is the array bound
+        // and a `false` is a buffer netd was never given — or was given by the
+        // adapter before this one.
+        if !self.lent.get(buf).is_some_and(|&lent| lent) {
+            return Err(SyscallError::InvalidArgument);
+        }
+        self.lent[buf] = false;
+        self.free[self.free_n] = buf as u8;
+        self.free_n += 1;
+        Ok(())
+    }
+
+    /// Copy netd's frame into the next transmit slot and hand it to the
+    /// controller. NCM wraps it in a block of one datagram.
+    fn transmit(&mut self, len: usize) {
+        if self.out_halted {
+            return;
+        }
+        if self.tx_busy == TX_SLOTS {
+            self.tx_dropped = self.tx_dropped.wrapping_add(1);
+            // Once and then at every power of two, so a stalled adapter says
+            // so without one line per frame netd keeps sending.
+            if self.tx_dropped.is_power_of_two() {
+                log!("usb-net: {} frame(s) dropped, all {TX_SLOTS} transmit slots in flight",
+                    self.tx_dropped);
+            }
+            return;
+        }
+        let slot = (self.tx_head + self.tx_busy) % TX_SLOTS;
+        let at = OFF_TX_STAGE + slot * TX_SLOT;
+        let mut frame = [0u8; class::MAX_FRAME];
+        self.shared.copy_to(OFF_TX_BUF, &mut frame[..len]);
+        let total = if self.ncm {
+            let header = ntb::header(self.tx_seq, len as u16);
+            self.tx_seq = self.tx_seq.wrapping_add(1);
+            self.private.copy_from(at, &header);
+            self.private.copy_from(at + ntb::TX_HEADER, &frame[..len]);
+            ntb::TX_HEADER + len
+        } else {
+            self.private.copy_from(at, &frame[..len]);
+            len
+        };
+        let zero_length = class::needs_zero_length(total, self.out_packet);
+        let mut trb = Trb::ZERO;
+        trb.param = self.private.phys() + at as u64;
+        trb.status = total as u32;
+        trb.control = TRB_NORMAL | if zero_length { 0 } else { 1 << 5 }; // IOC
+        self.out_ring.enqueue(trb);
+        if zero_length {
+            let mut end = Trb::ZERO;
+            end.control = TRB_NORMAL | (1 << 5); // IOC
+            self.out_ring.enqueue(end);
+        }
+        self.tx_busy += 1;
+        self.doorbell(self.out_dci);
+    }
+}
+
+/// What netd's syscalls reach. Stateless: everything is in [`ADAPTER`], which
+/// the transfer events reach too.
+struct UsbNic;
+
+impl crate::net::Nic for UsbNic {
+    fn has_packet(&self) -> bool {
+        ADAPTER.lock().as_ref().is_some_and(|a| a.ready_n > 0)
+    }
+
+    fn poll_rx(&mut self) -> Option<(usize, usize)> {
+        ADAPTER.lock().as_mut()?.poll()
+    }
+
+    fn refill_rx_buf(&mut self, buf_index: usize) -> Result<(), SyscallError> {
+        ADAPTER.lock().as_mut().ok_or(SyscallError::NotFound)?.give_back(buf_index)
+    }
+
+    /// The bound is the longest frame and not the buffer: a longer one is
+    /// nothing either subclass carries, so `net::submit_tx` refuses it.
+    fn tx_buf_len(&self) -> usize { class::MAX_FRAME }
+
+    fn submit_tx(&mut self, total_len: usize) {
+        if let Some(adapter) = ADAPTER.lock().as_mut() {
+            adapter.transmit(total_len);
+        }
+    }
+
+    fn link_up(&self) -> bool {
+        ADAPTER.lock().as_ref().is_some_and(|a| a.link)
+    }
+}
+
+/// Build the input context for the adapter's three endpoints and the rings they
+/// run on, or `None` where the machine has its NIC already.
+pub(super) fn prepare(
+    ctrl: &mut XhciController,
+    slot_id: u8,
+    speed: u8,
+    port_idx: u8,
+    info: &NetInterface,
+) -> Option<NetRings> {
+    if ADAPTER.lock().is_some() || crate::net::nic_info().is_some() {
+        log!("usb-net: slot {slot_id} is a network adapter and the machine has its NIC; \
+             port {} is not bound", port_idx + 1);
+        return None;
+    }
+    let pages = pages();
+    let rings = NetRings {
+        in_ring: TrbRing::init(pages.private.subview(OFF_IN_RING, PAGE)),
+        out_ring: TrbRing::init(pages.private.subview(OFF_OUT_RING, PAGE)),
+        notify_ring: TrbRing::init(pages.private.subview(OFF_NOTIFY_RING, PAGE)),
+    };
+
+    let dma = ctrl.dma();
+    let input_ctx = super::zero_dma(dma, OFF_INPUT_CTX, PAGE);
+    // EP Type 2 is Bulk Out, 6 Bulk In and 7 Interrupt In, as in `msc::prepare`
+    // and `interrupt_input_context`.
+    let endpoints = [
+        (&info.out_ep, 2u32, rings.out_ring.dequeue()),
+        (&info.in_ep, 6, rings.in_ring.dequeue()),
+        (&info.notify, 7, rings.notify_ring.dequeue()),
+    ];
+    let added = endpoints.iter().fold(1u32, |mask, (ep, ..)| mask | (1u32 << ep.dci()));
+    ctrl.write_ctx32(input_ctx, 0, 1, added);
+    let max_dci = endpoints.iter().map(|(ep, ..)| ep.dci()).max().unwrap_or(0);
+    ctrl.write_slot_context(input_ctx, port_idx, speed, max_dci, None);
+
+    for (ep, ep_type, dequeue) in endpoints {
+        let ctx = ep.dci() as usize + 1;
+        let periodic = ep_type == 7;
+        let interval = if periodic { interrupt_interval(speed, ep) } else { 0 };
+        ctrl.write_ctx32(input_ctx, ctx, 0, interval << 16);
+        ctrl.write_ctx32(
+            input_ctx,
+            ctx,
+            1,
+            (3 << 1) | (ep_type << 3) | ((ep.max_burst as u32) << 8) | ((ep.max_packet as u32) << 16),
+        );
+        ctrl.write_ctx32(input_ctx, ctx, 2, dequeue as u32);
+        ctrl.write_ctx32(input_ctx, ctx, 3, (dequeue >> 32) as u32);
+        // Max ESIT Payload only for the periodic endpoint, for the reason
+        // `interrupt_input_context` gives; a bulk endpoint has none.
+        let esit = if periodic { (ep.max_packet as u32) << 16 } else { 0 };
+        ctrl.write_ctx32(input_ctx, ctx, 4, esit | ep.max_packet as u32);
+    }
+    Some(rings)
+}
+
+/// Start receiving, and put the adapter behind the NIC claim.
+pub(super) fn bind(
+    ctrl: &mut XhciController,
+    slot_id: u8,
+    port_idx: u8,
+    info: &NetInterface,
+    rings: NetRings,
+    mac: [u8; 6],
+) {
+    let pages = pages();
+    let mut adapter = Adapter {
+        slot_id,
+        db: ctrl.db_base,
+        ncm: info.ncm,
+        in_dci: info.in_ep.dci(),
+        out_dci: info.out_ep.dci(),
+        notify_dci: info.notify.dci(),
+        out_packet: info.out_ep.max_packet,
+        in_ring: rings.in_ring,
+        out_ring: rings.out_ring,
+        notify_ring: rings.notify_ring,
+        private: pages.private,
+        shared: pages.shared,
+        in_trbs: [0; IN_TRANSFERS],
+        in_next: 0,
+        scratch: vec![0u8; IN_BUF].into_boxed_slice(),
+        tx_head: 0,
+        tx_busy: 0,
+        tx_seq: 0,
+        free: core::array::from_fn(|i| i as u8),
+        free_n: RX_BUF_COUNT,
+        ready: [(0, 0); RX_BUF_COUNT],
+        ready_head: 0,
+        ready_n: 0,
+        lent: [false; RX_BUF_COUNT],
+        link: true,
+        out_halted: false,
+        dropped: 0,
+        reported_drops: 0,
+        last_drop: None,
+        tx_dropped: 0,
+    };
+    for buf in 0..IN_TRANSFERS {
+        adapter.post_in(buf);
+    }
+    adapter.post_notify();
+    *ADAPTER.lock() = Some(adapter);
+    ctrl.net = Some(NetDevice { slot_id, port_idx });
+
+    log!("usb-net: {} adapter on slot {slot_id}, MAC {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        if info.ncm { "CDC-NCM" } else { "CDC-ECM" },
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]);
+
+    let dma_base_phys = pages.shared.phys() & !(crate::mm::PAGE_2M - 1);
+    let dma_region = Region {
+        phys: crate::DirectMap::from_phys(dma_base_phys),
+        size: crate::mm::PAGE_2M,
+        cache: CachePolicy::DeferToMtrr,
+        pages: None,
+    };
+    crate::net::set_nic_info(NicInfo {
+        dma: toyos_abi::HANDLE_INVALID,
+        rx_buf_offset: OFF_RX_BUFS as u32,
+        tx_buf_offset: OFF_TX_BUF as u32,
+        mac,
+        rx_buf_count: RX_BUF_COUNT as u16,
+        rx_buf_size: RX_BUF_SIZE,
+        net_hdr_size: 0,
+    }, dma_region);
+    crate::net::register(Box::new(UsbNic));
+    // A netd already running is parked on a claim whose NIC had gone, or never
+    // came; this is what tells it there is one.
+    crate::net::announce();
+}
+
+/// A transfer event on the adapter's slot.
+pub(super) fn completed(dci: u8, trb: u64, code: u32, residue: u32) {
+    let news = match ADAPTER.lock().as_mut() {
+        Some(adapter) => adapter.completed(dci, trb, code, residue),
+        None => return,
+    };
+    match news {
+        News::Nothing => {}
+        News::Frames => crate::net::wake(),
+        News::Link => crate::net::announce(),
+    }
+}
+
+impl XhciController {
+    /// The device on `port_idx` is being torn down; if it is the adapter, the
+    /// machine's NIC goes with it.
+    pub(super) fn unbind_net(&mut self, port_idx: u8) {
+        let Some(net) = self.net.filter(|n| n.port_idx == port_idx) else { return };
+        self.net = None;
+        // Out of `ADAPTER` before `net::unregister` takes `NIC`, which is the
+        // order netd's syscalls take them in.
+        drop(ADAPTER.lock().take());
+        log!("usb-net: adapter on slot {} unplugged from port {}; the machine has no NIC",
+            net.slot_id, port_idx + 1);
+        crate::net::unregister();
+    }
+}
@@ -1,19 +1,28 @@
+use alloc::boxed::Box;
 use core::num::NonZeroU8;
 
 use crate::log;
 use toyos_xhci::enumerate::{
     self, ep0_packet_from_descriptor, initial_ep0_packet, Act, Enumeration, Learnt, Next, Request,
 };
+use toyos_xhci::cdc::{self as ethernet, ntb};
+use toyos_xhci::hub::{self as class, HubSlot, Setup};
 use toyos_xhci::job::{Await, Outcome, Stages};
 use toyos_xhci::port::{self, Reset};
+use toyos_xhci::serial::{self as uart, Kind as SerialKind};
+use toyos_xhci::uac::{self, Clock, Stream, Version};
+use toyos_xhci::uas;
 use super::{deadline, Answer, Trb, TrbRing, What, XhciController, PAGE};
 use super::{OFF_INPUT_CTX, OFF_DATA_BUF};
 use super::{DEV_INT_RING, DEV_EP0_RING, DEV_OUT_CTX, DEV_REPORT};
 use super::{TRB_ENABLE_SLOT, TRB_ADDRESS_DEVICE, TRB_CONFIGURE_EP, TRB_EVALUATE_CONTEXT};
 use super::{enqueue_control, CC_SUCCESS};
 
+use super::audio::{AudioInterface, AudioRings};
+use super::cdc::{NetInterface, NetRings};
 use super::hid::{HidType, HidRole, HidDevice};
-use super::msc::{MscInterface, MscRings};
+use super::msc::{MscInterface, MscRings, UasPipes};
+use super::serial::{SerialInterface, SerialRings};
 
 /// How much of a configuration descriptor the driver reads and parses.
 ///
@@ -22,6 +31,16 @@ use super::msc::{MscInterface, MscRings};
 /// asked for, and the scratch page is four times this.
 const MAX_CONFIG_DESC: usize = 256;
 
+/// How much of a report descriptor the driver reads, which is also the most a
+/// GET_DESCRIPTOR(Report) asks for.
+///
+/// The HID descriptor states the length and the request asks for exactly that,
+/// so this is a cap on the device's number rather than a guess at it. A
+/// keyboard's is 60 bytes or so and a game controller's a few hundred; one past
+/// a kilobyte is describing more than [`toyos_hid::MAX_FIELDS`] would keep. A
+/// quarter of the scratch page.
+const MAX_REPORT_DESC: usize = 1024;
+
 /// One endpoint from a configuration descriptor, which this driver has decided
 /// it can configure.
 ///
@@ -50,7 +69,10 @@ pub(super) struct Endpoint {
     /// The SuperSpeed companion's burst size. Zero is legal and means one
     /// packet per burst, which is what a device that omits the companion means.
     pub(super) max_burst: u8,
-    /// bInterval. Only an interrupt endpoint uses it.
+    /// The companion's MaxStreams for a bulk endpoint: the endpoint has
+    /// `2^max_streams` streams, and zero is none. Only a UAS pipe uses them.
+    pub(super) max_streams: u8,
+    /// bInterval. Only an interrupt or an isochronous endpoint uses it.
     pub(super) interval: u8,
 }
 
@@ -73,6 +95,7 @@ impl Endpoint {
             dci: num * 2 + u8::from(addr & 0x80 != 0),
             max_packet,
             max_burst: 0,
+            max_streams: 0,
             interval,
         })
     }
@@ -84,30 +107,56 @@ struct HidInterfaceInfo {
     protocol: HidType,
     iface_num: u8,
     ep: Endpoint,
+    /// The report descriptor's length, as the HID descriptor states it, and 0
+    /// where the interface had none.
+    report_len: u16,
+}
+
+/// A hub's interface: its one interrupt IN endpoint, which delivers the
+/// status-change bitmap.
+#[derive(Clone, Copy)]
+struct HubInterface {
+    iface_num: u8,
+    ep: Endpoint,
 }
 
 /// What one configuration descriptor offered that this driver can drive.
 ///
-/// Both variants are *complete*: there is no value of this type describing an
+/// Every variant is *complete*: there is no value of this type describing an
 /// interface whose endpoints the driver has not resolved, which is why the
 /// walk below accumulates into [`Walk`] and converts once.
 #[derive(Clone, Copy)]
 enum Function {
     Hid(HidInterfaceInfo),
     Msc(MscInterface),
+    Hub(HubInterface),
+    Net(NetInterface),
+    Serial(SerialInterface),
+    Audio(AudioInterface),
 }
 
 impl Function {
     /// The same interface as far as *the order of what is left* depends on it,
     /// which is all [`Enumeration`] is given: whether there is a boot protocol
     /// to select, and nothing about which endpoints or which interface number.
-    fn shape(self) -> enumerate::Function {
+    /// A hub's shape is its speed's, since only a SuperSpeed hub has a depth to
+    /// be told.
+    fn shape(self, speed: u8) -> enumerate::Function {
         match self {
-            // A tablet reports in its own format, so there is no boot protocol
-            // to ask for and asking is a request it may stall for.
-            Self::Hid(info) if info.protocol == HidType::Tablet => enumerate::Function::Hid,
+            // A tablet or a pad reports in its own format, so there is no boot
+            // protocol to ask for and asking is a request it may stall for.
+            Self::Hid(info) if info.protocol == HidType::Report => enumerate::Function::Hid,
             Self::Hid(_) => enumerate::Function::BootHid,
+            Self::Msc(msc) if msc.alternate != 0 => enumerate::Function::MscAlternate,
             Self::Msc(_) => enumerate::Function::Msc,
+            Self::Hub(_) if speed >= class::SPEED_SUPER => enumerate::Function::SuperSpeedHub,
+            Self::Hub(_) => enumerate::Function::Hub,
+            Self::Net(net) if net.ncm => enumerate::Function::NetNcm,
+            Self::Net(_) => enumerate::Function::Net,
+            Self::Serial(serial) => {
+                enumerate::Function::Serial { steps: uart::line_steps(serial.kind) }
+            }
+            Self::Audio(audio) => enumerate::Function::Audio { rate: audio.rate_control },
         }
     }
 }
@@ -119,30 +168,219 @@ impl Function {
 /// [`Walk::finish`] *is* the completeness test, and it is the only one — two
 /// tests on `!= 0` further down deleted themselves when this landed.
 enum Walk {
-    Hid { protocol: HidType, iface_num: u8, ep: Option<Endpoint> },
-    Msc { iface_num: u8, in_ep: Option<Endpoint>, out_ep: Option<Endpoint> },
+    Hid { protocol: HidType, iface_num: u8, ep: Option<Endpoint>, report_len: u16 },
+    Msc { iface_num: u8, alternate: u8, in_ep: Option<Endpoint>, out_ep: Option<Endpoint> },
+    /// A UAS setting. Its four bulk endpoints are told apart by the Pipe Usage
+    /// descriptor behind each rather than by direction, so an endpoint waits in
+    /// `pending` until its descriptor names it; `pipes` is indexed by Pipe ID
+    /// less one.
+    Uas { iface_num: u8, alternate: u8, pending: Option<Endpoint>, pipes: [Option<Endpoint>; 4] },
+    Hub { iface_num: u8, ep: Option<Endpoint> },
+    /// A CDC communication interface: the interrupt endpoint it notifies on,
+    /// and what its functional descriptors say about the address and about
+    /// which data interface is its own.
+    NetComm {
+        iface_num: u8,
+        ncm: bool,
+        mac_string: Option<u8>,
+        data: Option<u8>,
+        notify: Option<Endpoint>,
+    },
+    /// The setting of that data interface that has the bulk pair. Only ever
+    /// begun for the interface a finished [`Walk::NetComm`] named, so a data
+    /// interface is never paired with a communication interface it is not.
+    NetData { iface_num: u8, alternate: u8, in_ep: Option<Endpoint>, out_ep: Option<Endpoint> },
+    /// A CDC-ACM communication interface, which is read only for the data
+    /// interface its Union names: its notification endpoint says nothing a
+    /// console needs, so it is never configured.
+    AcmComm { iface_num: u8, data: Option<u8> },
+    /// A serial adapter's bulk pair: the data interface an [`Walk::AcmComm`]
+    /// named, or the vendor interface of a device its IDs said was one.
+    /// `control` is where its line setup requests go.
+    Serial {
+        kind: SerialKind,
+        control: u8,
+        iface_num: u8,
+        in_ep: Option<Endpoint>,
+        out_ep: Option<Endpoint>,
+    },
+    /// An audio function's control interface: which version of the class it
+    /// speaks, and for UAC2 the clock its rate is set on.
+    AudioControl { iface_num: u8, version: Version, clock: Option<Clock> },
+    /// A setting of a streaming interface, which is only ever begun once a
+    /// control interface has said which version its descriptors are in: what
+    /// its class-specific descriptors say it plays, its isochronous OUT
+    /// endpoint with the bmAttributes that say how it is clocked, and the
+    /// feedback endpoint beside it.
+    Audio {
+        iface_num: u8,
+        alternate: u8,
+        version: Version,
+        stream: Stream,
+        out_ep: Option<Endpoint>,
+        out_attrs: u8,
+        feedback: Option<Endpoint>,
+        rate_control: bool,
+    },
+}
+
+/// A communication interface the walk finished, waiting for its data
+/// interface, which comes after it.
+#[derive(Clone, Copy)]
+struct Comm {
+    iface_num: u8,
+    ncm: bool,
+    mac_string: u8,
+    data: u8,
+    notify: Endpoint,
+}
+
+/// The interfaces a walk found, one of each transport, before the choice
+/// between them.
+#[derive(Default)]
+struct Found {
+    hid: Option<HidInterfaceInfo>,
+    bot: Option<MscInterface>,
+    uas: Option<MscInterface>,
+    hub: Option<HubInterface>,
+    comm: Option<Comm>,
+    net: Option<NetInterface>,
+    /// A finished ACM communication interface and the data interface it named.
+    acm: Option<(u8, u8)>,
+    serial: Option<SerialInterface>,
+    /// The last finished audio control interface, its version and its clock.
+    audio_control: Option<(u8, Version, Option<Clock>)>,
+    audio: Option<AudioInterface>,
 }
 
 impl Walk {
     /// Move a finished interface into the running answer, if it is one this
     /// driver can bind.
-    fn finish(self, hid: &mut Option<HidInterfaceInfo>, msc: &mut Option<MscInterface>) {
+    fn finish(self, found: &mut Found) {
+        let Found { hid, bot: msc, uas, hub, comm, net, acm, serial, audio_control, audio } = found;
         match self {
-            Self::Hid { protocol, iface_num, ep: Some(ep) } => {
+            Self::Hid { protocol, iface_num, ep: Some(ep), report_len } => {
                 if hid.is_none() {
-                    *hid = Some(HidInterfaceInfo { protocol, iface_num, ep });
+                    *hid = Some(HidInterfaceInfo { protocol, iface_num, ep, report_len });
                 }
             }
             Self::Hid { .. } => {}
-            Self::Msc { iface_num, in_ep: Some(in_ep), out_ep: Some(out_ep) } => {
+            Self::Msc { iface_num, alternate, in_ep: Some(in_ep), out_ep: Some(out_ep) } => {
                 if msc.is_none() {
-                    *msc = Some(MscInterface { iface_num, in_ep, out_ep });
+                    *msc = Some(MscInterface { iface_num, alternate, in_ep, out_ep, uas: None });
                 }
             }
             Self::Msc { iface_num, .. } => {
                 log!("xHCI: mass-storage interface {iface_num} has no pair of bulk endpoints \
                      this driver can configure, skipping");
             }
+            Self::Uas { iface_num, alternate, pipes: [Some(command), Some(status), Some(in_ep), Some(out_ep)], .. } => {
+                if uas.is_none() {
+                    let pipes = Some(UasPipes { command, status });
+                    *uas = Some(MscInterface { iface_num, alternate, in_ep, out_ep, uas: pipes });
+                }
+            }
+            Self::Uas { iface_num, alternate, .. } => {
+                log!("xHCI: UAS setting {iface_num}.{alternate} does not name all four of its \
+                     pipes, skipping it");
+            }
+            Self::Hub { iface_num, ep: Some(ep) } => {
+                if hub.is_none() {
+                    *hub = Some(HubInterface { iface_num, ep });
+                }
+            }
+            Self::Hub { iface_num, .. } => {
+                log!("xHCI: hub interface {iface_num} has no interrupt IN endpoint to report \
+                     its ports on, skipping it");
+            }
+            Self::NetComm {
+                iface_num,
+                ncm,
+                mac_string: Some(mac_string),
+                data: Some(data),
+                notify: Some(notify),
+            } => {
+                if comm.is_none() {
+                    *comm = Some(Comm { iface_num, ncm, mac_string, data, notify });
+                }
+            }
+            Self::NetComm { iface_num, .. } => {
+                log!("xHCI: network interface {iface_num} does not state its address, its data \
+                     interface and its notification endpoint, skipping it");
+            }
+            Self::NetData { iface_num, alternate, in_ep: Some(in_ep), out_ep: Some(out_ep) } => {
+                if let (Some(c), None) = (*comm, &net) {
+                    *net = Some(NetInterface {
+                        comm_iface: c.iface_num,
+                        data_iface: iface_num,
+                        alternate,
+                        ncm: c.ncm,
+                        mac_string: c.mac_string,
+                        notify: c.notify,
+                        in_ep,
+                        out_ep,
+                    });
+                }
+            }
+            Self::NetData { iface_num, alternate, .. } => {
+                log!("xHCI: network data setting {iface_num}.{alternate} has no pair of bulk \
+                     endpoints this driver can configure, skipping it");
+            }
+            Self::AcmComm { iface_num, data: Some(data) } => {
+                acm.get_or_insert((iface_num, data));
+            }
+            Self::AcmComm { iface_num, .. } => {
+                log!("xHCI: serial interface {iface_num} does not name its data interface, \
+                     skipping it");
+            }
+            Self::Serial { kind, control, in_ep: Some(in_ep), out_ep: Some(out_ep), .. } => {
+                serial.get_or_insert(SerialInterface {
+                    kind,
+                    control_iface: control,
+                    in_ep,
+                    out_ep,
+                });
+            }
+            Self::Serial { iface_num, .. } => {
+                log!("xHCI: serial data interface {iface_num} has no pair of bulk endpoints \
+                     this driver can configure, skipping it");
+            }
+            Self::AudioControl { iface_num, version, clock } => {
+                *audio_control = Some((iface_num, version, clock));
+            }
+            Self::Audio {
+                iface_num,
+                alternate,
+                version,
+                stream,
+                out_ep: Some(out_ep),
+                out_attrs,
+                feedback,
+                rate_control,
+            } if stream.plays() => {
+                let Some((control_iface, _, clock)) = *audio_control else { return };
+                // Only an asynchronous endpoint's feedback is read: an
+                // adaptive one follows the host, and has none.
+                let feedback = feedback.filter(|_| uac::asynchronous(out_attrs));
+                let rate_control = match version {
+                    Version::Uac1 => rate_control,
+                    Version::Uac2 => clock.is_some_and(|c| c.programmable),
+                };
+                audio.get_or_insert(AudioInterface {
+                    version,
+                    control_iface,
+                    stream_iface: iface_num,
+                    alternate,
+                    out_ep,
+                    feedback,
+                    rate_control,
+                    clock: clock.map_or(0, |c| c.id),
+                });
+            }
+            Self::Audio { iface_num, alternate, .. } => {
+                log!("xHCI: audio setting {iface_num}.{alternate} does not play 16-bit stereo \
+                     PCM at {} Hz on an isochronous OUT endpoint, skipping it", uac::RATE);
+            }
         }
     }
 }
@@ -183,12 +421,20 @@ fn le16(buf: &[u8], at: usize) -> u16 {
 /// zero length terminates it rather than looping forever, and every field is
 /// read through `get`. Mass storage wins a tie with HID because a device
 /// offering a disk is a disk; nothing in this tree offers both.
-fn parse_config(buf: &[u8]) -> Option<(u8, Function)> {
+///
+/// **UAS wins a tie with Bulk-Only** on the same disk — the two are alternate
+/// settings of one interface on nearly every stick that has UAS — unless its
+/// pipes have streams and `streams` says this controller has none, which at
+/// SuperSpeed is every UAS device: the Bulk-Only setting is then the disk.
+///
+/// `vendor` is the serial family the device's IDs named, if any, and is what
+/// lets an interface of class 0xFF be read at all: a vendor interface says
+/// nothing about itself, and only a device known by its IDs is bound on one.
+fn parse_config(buf: &[u8], streams: bool, vendor: Option<SerialKind>) -> Option<(u8, Function)> {
     let total_len = (le16(buf, 2) as usize).min(buf.len());
     let config_val = *buf.get(5)?;
 
-    let mut hid: Option<HidInterfaceInfo> = None;
-    let mut msc: Option<MscInterface> = None;
+    let mut found = Found::default();
     // Which interface the endpoint descriptors that follow belong to.
     let mut current: Option<Walk> = None;
     // A SuperSpeed companion describes the endpoint immediately before it.
@@ -210,19 +456,69 @@ fn parse_config(buf: &[u8]) -> Option<(u8, Function)> {
             // Interface
             4 if desc.len() >= 9 => {
                 if let Some(done) = current.take() {
-                    done.finish(&mut hid, &mut msc);
+                    done.finish(&mut found);
                 }
                 let (class, sub, proto) = (desc[5], desc[6], desc[7]);
+                let (iface_num, alternate) = (desc[2], desc[3]);
                 current = if class == 0x08 && sub == 0x06 && proto == 0x50 {
-                    Some(Walk::Msc { iface_num: desc[2], in_ep: None, out_ep: None })
+                    Some(Walk::Msc { iface_num, alternate, in_ep: None, out_ep: None })
+                } else if class == 0x08 && sub == 0x06 && proto == 0x62 {
+                    Some(Walk::Uas { iface_num, alternate, pending: None, pipes: [None; 4] })
+                } else if class == ethernet::CLASS_DATA
+                    && alternate != 0
+                    && found.comm.is_some_and(|c| c.data == iface_num)
+                {
+                    Some(Walk::NetData { iface_num, alternate, in_ep: None, out_ep: None })
+                } else if class == uac::CLASS_AUDIO && sub == uac::SUBCLASS_STREAMING && alternate != 0 {
+                    found.audio_control.map(|(_, version, _)| Walk::Audio {
+                        iface_num,
+                        alternate,
+                        version,
+                        stream: Stream::default(),
+                        out_ep: None,
+                        out_attrs: 0,
+                        feedback: None,
+                        rate_control: false,
+                    })
+                } else if alternate != 0 {
+                    // Only a disk, a network adapter's data interface and an
+                    // audio function's streaming interface are bound on a
+                    // setting other than 0, which is the only setting
+                    // SET_CONFIGURATION leaves a HID in.
+                    None
+                } else if class == ethernet::CLASS_DATA
+                    && found.acm.is_some_and(|(_, data)| data == iface_num)
+                {
+                    let control = found.acm.map_or(iface_num, |(comm, _)| comm);
+                    let kind = SerialKind::Acm;
+                    Some(Walk::Serial { kind, control, iface_num, in_ep: None, out_ep: None })
+                } else if let (uart::CLASS_VENDOR, Some(kind)) = (class, vendor) {
+                    let control = iface_num;
+                    Some(Walk::Serial { kind, control, iface_num, in_ep: None, out_ep: None })
                 } else if class == 3 {
                     let protocol = match (sub, proto) {
                         (1, 1) => Some(HidType::Keyboard),
                         (1, 2) => Some(HidType::Mouse),
-                        (0, _) => Some(HidType::Tablet),
+                        (0, _) => Some(HidType::Report),
                         _ => None,
                     };
-                    protocol.map(|protocol| Walk::Hid { protocol, iface_num: desc[2], ep: None })
+                    protocol.map(|protocol| Walk::Hid {
+                        protocol,
+                        iface_num: desc[2],
+                        ep: None,
+                        report_len: 0,
+                    })
+                } else if class == class::CLASS {
+                    Some(Walk::Hub { iface_num, ep: None })
+                } else if class == ethernet::CLASS_COMM
+                    && matches!(sub, ethernet::SUBCLASS_ECM | ethernet::SUBCLASS_NCM)
+                {
+                    let ncm = sub == ethernet::SUBCLASS_NCM;
+                    Some(Walk::NetComm { iface_num, ncm, mac_string: None, data: None, notify: None })
+                } else if class == ethernet::CLASS_COMM && sub == uart::SUBCLASS_ACM {
+                    Some(Walk::AcmComm { iface_num, data: None })
+                } else if class == uac::CLASS_AUDIO && sub == uac::SUBCLASS_CONTROL {
+                    Some(Walk::AudioControl { iface_num, version: Version::of(proto), clock: None })
                 } else {
                     None
                 };
@@ -242,10 +538,24 @@ fn parse_config(buf: &[u8]) -> Option<(u8, Function)> {
                         Some(Walk::Hid { ep: slot, .. }) if is_in && slot.is_none() => {
                             *slot = Some(ep);
                         }
+                        Some(Walk::Hub { ep: slot, .. })
+                            if is_in && transfer == 3 && slot.is_none() =>
+                        {
+                            *slot = Some(ep);
+                        }
+                        Some(Walk::NetComm { notify, .. })
+                            if is_in && transfer == 3 && notify.is_none() =>
+                        {
+                            *notify = Some(ep);
+                        }
                         // Bulk only: a mass-storage interface's interrupt
                         // endpoint belongs to CBI, which this driver does not
                         // speak.
-                        Some(Walk::Msc { in_ep, out_ep, .. }) if transfer == 2 => {
+                        Some(
+                            Walk::Msc { in_ep, out_ep, .. }
+                            | Walk::NetData { in_ep, out_ep, .. }
+                            | Walk::Serial { in_ep, out_ep, .. },
+                        ) if transfer == 2 => {
                             if is_in && in_ep.is_none() {
                                 *in_ep = Some(ep);
                                 last_ep_in = Some(true);
@@ -254,36 +564,141 @@ fn parse_config(buf: &[u8]) -> Option<(u8, Function)> {
                                 last_ep_in = Some(false);
                             }
                         }
+                        Some(Walk::Uas { pending, .. }) => {
+                            *pending = (transfer == 2).then_some(ep);
+                        }
+                        // Isochronous only, and the packet size without the
+                        // high-speed transactions-per-microframe bits above
+                        // it: one packet per interval is all this driver sends.
+                        // An IN endpoint here is the OUT's feedback when its
+                        // usage type (bits 5:4) says so.
+                        Some(Walk::Audio { out_ep, out_attrs, feedback, .. }) if transfer == 1 => {
+                            let mut ep = ep;
+                            ep.max_packet &= 0x7FF;
+                            if !is_in && out_ep.is_none() {
+                                *out_ep = Some(ep);
+                                *out_attrs = desc[3];
+                            } else if is_in && (desc[3] >> 4) & 0b11 == 1 && feedback.is_none() {
+                                *feedback = Some(ep);
+                            }
+                        }
                         _ => {}
                     }
+                } else if let Some(Walk::Uas { pending, .. }) = &mut current {
+                    *pending = None;
                 }
             }
             // SuperSpeed Endpoint Companion, which is where a SuperSpeed
             // device states the burst size of the endpoint just above it.
-            0x30 if desc.len() >= 3 => {
-                if let (Some(Walk::Msc { in_ep, out_ep, .. }), Some(is_in)) =
-                    (&mut current, last_ep_in)
-                {
+            // MaxStreams is bits 4:0 of bmAttributes for a bulk endpoint.
+            0x30 if desc.len() >= 4 => match (&mut current, last_ep_in) {
+                (
+                    Some(
+                        Walk::Msc { in_ep, out_ep, .. }
+                        | Walk::NetData { in_ep, out_ep, .. }
+                        | Walk::Serial { in_ep, out_ep, .. },
+                    ),
+                    Some(is_in),
+                ) => {
                     if let Some(ep) = if is_in { in_ep } else { out_ep } {
                         ep.max_burst = desc[2];
                     }
                 }
+                (Some(Walk::Uas { pending: Some(ep), .. }), _) => {
+                    ep.max_burst = desc[2];
+                    ep.max_streams = desc[3] & 0x1F;
+                }
+                _ => {}
+            },
+            // UAS Pipe Usage, which says which pipe the endpoint just above it
+            // is. A pipe whose direction is not its ID's is not that pipe.
+            // Inside a CDC communication interface the same descriptor type is
+            // a functional descriptor, and which one is its subtype.
+            0x24 if desc.len() >= 3 => match &mut current {
+                Some(Walk::Uas { pending, pipes, .. }) => {
+                    let id = desc[2];
+                    if let (Some(ep), 1..=4) = (pending.take(), id) {
+                        let wants_in = matches!(id, uas::PIPE_STATUS | uas::PIPE_DATA_IN);
+                        let slot = &mut pipes[id as usize - 1];
+                        if (ep.addr & 0x80 != 0) == wants_in && slot.is_none() {
+                            *slot = Some(ep);
+                        }
+                    }
+                }
+                Some(Walk::NetComm { mac_string, data, .. }) => {
+                    if let Some(eth) = ethernet::Ethernet::parse(desc) {
+                        mac_string.get_or_insert(eth.mac_string);
+                    }
+                    if let Some(iface) = ethernet::union_data(desc) {
+                        data.get_or_insert(iface);
+                    }
+                }
+                Some(Walk::AcmComm { data, .. }) => {
+                    if let Some(iface) = ethernet::union_data(desc) {
+                        data.get_or_insert(iface);
+                    }
+                }
+                // An audio streaming setting's general and format descriptors,
+                // and a UAC2 control interface's clock.
+                Some(Walk::Audio { stream, version, .. }) => stream.read(desc, *version),
+                Some(Walk::AudioControl { clock, .. }) => {
+                    if let Some(source) = Clock::parse(desc) {
+                        clock.get_or_insert(source);
+                    }
+                }
+                _ => {}
+            },
+            // A HID descriptor, which is where an interface says how long its
+            // report descriptor is: the first class descriptor it lists, by
+            // the rule every HID since 1.11 keeps (HID 1.11 §6.2.1).
+            0x21 if desc.len() >= 9 && desc[6] == 0x22 => {
+                if let Some(Walk::Hid { report_len, .. }) = &mut current {
+                    *report_len = le16(desc, 7);
+                }
+            }
+            // A class-specific endpoint descriptor, which for a UAC1 OUT
+            // endpoint says whether its rate can be set.
+            uac::CS_ENDPOINT => {
+                if let Some(Walk::Audio { out_ep: Some(_), rate_control, .. }) = &mut current {
+                    *rate_control |= uac::endpoint_rate_control(desc);
+                }
             }
             _ => {}
         }
         offset += desc_len;
     }
     if let Some(done) = current {
-        done.finish(&mut hid, &mut msc);
+        done.finish(&mut found);
     }
 
     // Mass storage wins a tie with HID because a device offering a disk is a
-    // disk. No completeness test here: an interface that reached `hid` or `msc`
-    // has one, because `Walk::finish` could not have built it otherwise.
-    if let Some(m) = msc {
+    // disk. No completeness test here: an interface that reached `found` has
+    // one, because `Walk::finish` could not have built it otherwise.
+    let uas = found.uas.filter(|m| {
+        let needs = m.uas.is_some_and(|p| p.status.max_streams != 0);
+        streams || !needs
+    });
+    if let Some(m) = uas.or(found.bot) {
         return Some((config_val, Function::Msc(m)));
     }
-    Some((config_val, Function::Hid(hid?)))
+    if let Some(h) = found.hub {
+        return Some((config_val, Function::Hub(h)));
+    }
+    if let Some(n) = found.net {
+        return Some((config_val, Function::Net(n)));
+    }
+    // A serial adapter wins a tie with HID for the reason a disk does: a
+    // keyboard interface beside a UART is a debug board's, and the UART is
+    // why it was plugged in.
+    if let Some(s) = found.serial {
+        return Some((config_val, Function::Serial(s)));
+    }
+    // And a DAC for the same reason: the HID interface beside one is its
+    // volume buttons, and what it was plugged in for is the sound.
+    if let Some(a) = found.audio {
+        return Some((config_val, Function::Audio(a)));
+    }
+    Some((config_val, Function::Hid(found.hid?)))
 }
 
 /// Ask a port to reset.
@@ -330,6 +745,18 @@ pub(super) struct Enumerating {
     /// rather than rebuilt: a second `TrbRing::init` would zero memory the
     /// controller is by then reading.
     rings: Option<Rings>,
+    /// A hub's own descriptor, which its slot context and its bind are built
+    /// from.
+    hub: Option<class::Descriptor>,
+    /// A network adapter's station address, read between SET_CONFIGURATION and
+    /// the bind that hands it to netd.
+    mac: Option<[u8; 6]>,
+    /// The serial family the device descriptor's IDs named, which the
+    /// configuration walk needs before it can read a vendor interface.
+    vendor: Option<SerialKind>,
+    /// A report-protocol HID's report descriptor, read and parsed between
+    /// SET_CONFIGURATION and the bind that decodes through it.
+    layout: Option<Box<toyos_hid::Layout>>,
 }
 
 /// The transfer rings one device's Configure Endpoint put into the Running
@@ -338,6 +765,10 @@ pub(super) struct Enumerating {
 enum Rings {
     Hid(TrbRing),
     Msc(MscRings),
+    Hub(TrbRing),
+    Net(NetRings),
+    Serial(SerialRings),
+    Audio(AudioRings),
 }
 
 
@@ -358,6 +789,11 @@ pub(super) fn begin(ctrl: &mut XhciController, port_idx: u8) {
     }
     let speed = portsc.speed();
     log!("xHCI: port {} enabled, speed={}", port_idx + 1, speed);
+    let place = ctrl.place(port_idx);
+    if place.tiers() > 0 {
+        log!("xHCI: port {} is route {:#07x} below root port {}", port_idx + 1, place.route(),
+            place.root_port());
+    }
     let Some(packet) = initial_ep0_packet(speed) else {
         log!("xHCI: port {} came up at speed {speed}, which is not a speed this driver has a \
              control-endpoint packet size for; skipping it", port_idx + 1);
@@ -411,6 +847,10 @@ pub(super) fn slot_answered(
         issued: Act::Command(enumerate::Command::EnableSlot),
         parsed: None,
         rings: None,
+        hub: None,
+        mac: None,
+        vendor: None,
+        layout: None,
     };
     advance(ctrl, state, Learnt::Nothing);
 }
@@ -442,11 +882,31 @@ pub(super) fn stepped(ctrl: &mut XhciController, mut state: Enumerating, outcome
             }
             Learnt::Nothing
         }
+        // The same for a serial adapter's line setup: plenty of ACM firmware
+        // stalls SET_LINE_CODING because a USB pipe has no baud rate, and its
+        // bulk pair carries bytes just the same.
+        // And for a DAC's rate: one that stalls it is running at a rate its
+        // format already said includes 48 kHz, and most run at nothing else.
+        Act::Request(request @ (Request::LineSetup { .. } | Request::SampleRate)) => {
+            if !outcome.succeeded() {
+                log!("xHCI: {} on port {port}: {}", request_name(request), Answer(outcome));
+            }
+            Learnt::Nothing
+        }
         Act::Request(request) => {
             let want = match request {
                 Request::DeviceDescriptor { want } => want,
-                Request::ConfigDescriptor => MAX_CONFIG_DESC as u16,
-                Request::SetConfiguration | Request::SetProtocol => 0,
+                Request::ConfigDescriptor { .. } => MAX_CONFIG_DESC as u16,
+                Request::HubDescriptor => class::DESCRIPTOR_BYTES,
+                Request::MacAddress => ethernet::MAC_STRING_BYTES,
+                Request::ReportDescriptor => report_descriptor_len(&state),
+                Request::SetConfiguration
+                | Request::SetProtocol
+                | Request::SetInterface
+                | Request::SetHubDepth
+                | Request::NtbInputSize
+                | Request::LineSetup { .. }
+                | Request::SampleRate => 0,
             };
             let Some(delivered) = delivered(outcome, want) else {
                 log!("xHCI: {} on port {port}: {}", request_name(request), Answer(outcome));
@@ -522,8 +982,8 @@ fn control(
     let scratch = dma.phys() + OFF_DATA_BUF as u64;
     let (bm_request_type, b_request, w_value, w_index, data, len) = match request {
         Request::DeviceDescriptor { want } => (0x80, 0x06, 0x0100, 0, Some(scratch), want),
-        Request::ConfigDescriptor => {
-            (0x80, 0x06, 0x0200, 0, Some(scratch), MAX_CONFIG_DESC as u16)
+        Request::ConfigDescriptor { index } => {
+            (0x80, 0x06, 0x0200 | index as u16, 0, Some(scratch), MAX_CONFIG_DESC as u16)
         }
         Request::SetConfiguration => {
             let (config_val, _) = state.parsed.expect("a configuration named a function");
@@ -536,13 +996,92 @@ fn control(
             };
             (0x21, 0x0B, 0, iface as u16, None, 0)
         }
+        // A standard request addressed to the interface, since the descriptor
+        // is the interface's (HID 1.11 §7.1.1).
+        Request::ReportDescriptor => {
+            let iface = match state.parsed {
+                Some((_, Function::Hid(info))) => info.iface_num,
+                _ => unreachable!("only a HID interface has a report descriptor"),
+            };
+            (0x81, 0x06, 0x2200, iface as u16, Some(scratch), report_descriptor_len(state))
+        }
+        Request::SetInterface => {
+            let (iface, alternate) = match state.parsed {
+                Some((_, Function::Msc(info))) => (info.iface_num, info.alternate),
+                Some((_, Function::Net(info))) => (info.data_iface, info.alternate),
+                Some((_, Function::Audio(info))) => (info.stream_iface, info.alternate),
+                _ => unreachable!("only a disk, a network adapter and a DAC are bound on an \
+                    alternate setting"),
+            };
+            (0x01, 0x0B, alternate as u16, iface as u16, None, 0)
+        }
+        Request::MacAddress | Request::NtbInputSize => {
+            let Some((_, Function::Net(info))) = state.parsed else {
+                unreachable!("only a network adapter has an address to read")
+            };
+            let setup = match request {
+                Request::MacAddress => ethernet::Setup::mac_string(info.mac_string),
+                _ => ethernet::Setup::ntb_input_size(info.comm_iface),
+            };
+            let ethernet::Setup { request_type, request, value, index, length } = setup;
+            (request_type, request, value, index, Some(scratch), length)
+        }
+        Request::HubDescriptor => {
+            let setup = Setup::descriptor(state.speed >= class::SPEED_SUPER);
+            let Setup { request_type, request, value, index, length } = setup;
+            (request_type, request, value, index, Some(scratch), length)
+        }
+        Request::LineSetup { step } => {
+            let Some((_, Function::Serial(info))) = state.parsed else {
+                unreachable!("only a serial adapter has a line to set up")
+            };
+            let (setup, _) = uart::line_step(info.kind, info.control_iface, step)
+                .expect("the sequence asks for no step past the family's count");
+            let ethernet::Setup { request_type, request, value, index, length } = setup;
+            (request_type, request, value, index, (length > 0).then_some(scratch), length)
+        }
+        Request::SampleRate => {
+            let Some((_, Function::Audio(info))) = state.parsed else {
+                unreachable!("only an audio function has a rate to set")
+            };
+            let (setup, _) =
+                uac::sample_rate(info.version, info.out_ep.addr, info.clock, info.control_iface);
+            let ethernet::Setup { request_type, request, value, index, length } = setup;
+            (request_type, request, value, index, Some(scratch), length)
+        }
+        // The depth is how many hubs are above this one, which is the place
+        // its own port gave it.
+        Request::SetHubDepth => {
+            let setup = Setup::hub_depth(ctrl.place(state.port_idx).tiers());
+            (setup.request_type, setup.request, setup.value, setup.index, None, 0)
+        }
     };
     // The scratch is one page shared by every enumeration, and enumeration is
     // serial: the slot holds one operation and a port inside an effect is not
     // decided about. Zeroed before each read so a short answer leaves zeroes
     // behind it rather than the last device's descriptor.
     if data.is_some() {
-        super::zero_dma(dma, OFF_DATA_BUF, MAX_CONFIG_DESC);
+        let asked =
+            if request == Request::ReportDescriptor { MAX_REPORT_DESC } else { MAX_CONFIG_DESC };
+        super::zero_dma(dma, OFF_DATA_BUF, asked);
+    }
+    // The one request here whose data stage is the host's to fill.
+    if request == Request::NtbInputSize {
+        dma.write::<u32>(OFF_DATA_BUF, ntb::IN_SIZE.to_le());
+    }
+    // As is a line setup's, where it has one: the rate, for ACM and CP210x.
+    if let (Request::LineSetup { step }, Some((_, Function::Serial(info)))) =
+        (request, state.parsed)
+    {
+        if let Some((setup, bytes)) = uart::line_step(info.kind, info.control_iface, step) {
+            dma.copy_from(OFF_DATA_BUF, &bytes[..setup.length as usize]);
+        }
+    }
+    // And a rate request's, which is the rate.
+    if let (Request::SampleRate, Some((_, Function::Audio(info)))) = (request, state.parsed) {
+        let (setup, bytes) =
+            uac::sample_rate(info.version, info.out_ep.addr, info.clock, info.control_iface);
+        dma.copy_from(OFF_DATA_BUF, &bytes[..setup.length as usize]);
     }
     let trbs = enqueue_control(
         &mut state.ep0_ring, bm_request_type, b_request, w_value, w_index, data, len,
@@ -602,13 +1141,14 @@ fn read_back(
             let descriptor = &scratch[..18];
             log!("xHCI: device class={:#x} vendor={:04x} product={:04x}",
                 descriptor[4], le16(descriptor, 8), le16(descriptor, 10));
-            Ok(Learnt::Nothing)
+            state.vendor = uart::vendor_kind(le16(descriptor, 8), le16(descriptor, 10));
+            Ok(Learnt::Configurations(descriptor[17]))
         }
         // Nine bytes is a configuration descriptor's own header, which is where
         // `wTotalLength` lives; fewer than that is not one. The parser is then
         // bounded by what *arrived* rather than by what was asked for, so the
         // zeroes behind a short answer are never walked as descriptors.
-        Request::ConfigDescriptor => {
+        Request::ConfigDescriptor { index } => {
             if delivered < 9 {
                 log!("xHCI: port {port} answered {delivered} B to GET_DESCRIPTOR(Config); a \
                      configuration descriptor is at least 9", );
@@ -618,25 +1158,112 @@ fn read_back(
             // slice bounds it in turn — a device claiming more than the page
             // holds is refused by the `min` rather than read past.
             let config = &scratch[..(delivered as usize).min(scratch.len())];
-            let Some((config_val, function)) = parse_config(config) else {
+            let Some((config_val, function)) = parse_config(config, ctrl.streams(), state.vendor) else {
+                log!("xHCI: configuration {index} on port {port} offers nothing this driver binds");
                 return Ok(Learnt::Nothing);
             };
             match function {
+                Function::Msc(msc @ MscInterface { uas: Some(pipes), .. }) => log!(
+                    "xHCI: UAS iface={}.{} cmd={:#x} status={:#x} in={:#x}/{} out={:#x}/{} \
+                     streams={}", msc.iface_num, msc.alternate, pipes.command.addr,
+                    pipes.status.addr, msc.in_ep.addr, msc.in_ep.max_packet, msc.out_ep.addr,
+                    msc.out_ep.max_packet, pipes.status.max_streams),
                 Function::Msc(msc) => log!("xHCI: mass storage iface={} in={:#x}/{} out={:#x}/{}",
                     msc.iface_num, msc.in_ep.addr, msc.in_ep.max_packet,
                     msc.out_ep.addr, msc.out_ep.max_packet),
                 Function::Hid(info) => log!("xHCI: HID {} iface={} ep={:#x} max_pkt={} \
                      interval={} dci={}", hid_kind(info.protocol), info.iface_num, info.ep.addr,
                     info.ep.max_packet, info.ep.interval, info.ep.dci()),
+                Function::Hub(info) => log!("xHCI: hub iface={} ep={:#x} interval={} dci={}",
+                    info.iface_num, info.ep.addr, info.ep.interval, info.ep.dci()),
+                Function::Net(net) => log!("xHCI: {} iface={} data={}.{} in={:#x}/{} \
+                     out={:#x}/{} notify={:#x}", if net.ncm { "CDC-NCM" } else { "CDC-ECM" },
+                    net.comm_iface, net.data_iface, net.alternate, net.in_ep.addr,
+                    net.in_ep.max_packet, net.out_ep.addr, net.out_ep.max_packet,
+                    net.notify.addr),
+                Function::Serial(serial) => log!("xHCI: {} serial iface={} in={:#x}/{} \
+                     out={:#x}/{}", serial_kind(serial.kind), serial.control_iface,
+                    serial.in_ep.addr, serial.in_ep.max_packet, serial.out_ep.addr,
+                    serial.out_ep.max_packet),
+                Function::Audio(audio) => log!("xHCI: UAC{} audio iface={} stream={}.{} \
+                     out={:#x}/{} interval={} feedback={:#x} rate control={}",
+                    if audio.version == Version::Uac1 { 1 } else { 2 }, audio.control_iface,
+                    audio.stream_iface, audio.alternate, audio.out_ep.addr,
+                    audio.out_ep.max_packet, audio.out_ep.interval,
+                    audio.feedback.map_or(0, |ep| ep.addr), audio.rate_control),
             }
             state.parsed = Some((config_val, function));
-            Ok(Learnt::Function(function.shape()))
+            Ok(Learnt::Function(function.shape(state.speed)))
         }
         Request::SetConfiguration => {
             log!("xHCI: configuration set");
             Ok(Learnt::Nothing)
         }
         Request::SetProtocol => Ok(Learnt::Nothing),
+        // Its own copy, and off the heap: a report descriptor may be four times
+        // the scratch above, and it is read once per device. A descriptor this
+        // driver cannot read refuses the device rather than binding one whose
+        // reports would be decoded as something they are not.
+        Request::ReportDescriptor => {
+            let mut descriptor = alloc::vec![0u8; (delivered as usize).min(MAX_REPORT_DESC)];
+            ctrl.dma().copy_to(OFF_DATA_BUF, &mut descriptor);
+            match toyos_hid::Layout::parse(&descriptor) {
+                Ok(layout) => {
+                    log!("xHCI: report descriptor of {delivered} B: a {} with {} B reports, \
+                         report ID {}", layout.kind().name(), layout.report_len(),
+                        layout.report_id());
+                    state.layout = Some(Box::new(layout));
+                    Ok(Learnt::Nothing)
+                }
+                Err(e) => {
+                    log!("xHCI: port {port} has a report descriptor this driver cannot read \
+                         ({e}); skipping it");
+                    Err(())
+                }
+            }
+        }
+        Request::SetInterface => {
+            log!("xHCI: alternate setting selected");
+            Ok(Learnt::Nothing)
+        }
+        Request::HubDescriptor => {
+            let descriptor = &scratch[..(delivered as usize).min(scratch.len())];
+            let superspeed = state.speed >= class::SPEED_SUPER;
+            match class::Descriptor::parse(descriptor, superspeed) {
+                Ok(hub) => {
+                    log!("xHCI: hub with {} ports, {} ms to power good", hub.ports,
+                        hub.power_good_ms);
+                    state.hub = Some(hub);
+                    Ok(Learnt::Nothing)
+                }
+                Err(refused) => {
+                    log!("xHCI: port {port} is a hub whose descriptor is not one ({refused:?}); \
+                         skipping it");
+                    Err(())
+                }
+            }
+        }
+        Request::SetHubDepth => Ok(Learnt::Nothing),
+        // A device whose address is no address is refused here rather than
+        // handed to netd, which would put it on the wire as the source of
+        // every frame.
+        Request::MacAddress => {
+            let descriptor = &scratch[..(delivered as usize).min(scratch.len())];
+            match ethernet::mac_from_string(descriptor) {
+                Ok(mac) => {
+                    state.mac = Some(mac);
+                    Ok(Learnt::Nothing)
+                }
+                Err(refused) => {
+                    log!("xHCI: port {port} is a network adapter whose address is not one \
+                         ({refused}); skipping it");
+                    Err(())
+                }
+            }
+        }
+        Request::NtbInputSize | Request::LineSetup { .. } | Request::SampleRate => {
+            Ok(Learnt::Nothing)
+        }
     }
 }
 
@@ -666,9 +1293,26 @@ fn request_name(request: Request) -> &'static str {
     match request {
         Request::DeviceDescriptor { want: 8 } => "GET_DESCRIPTOR(Device, 8)",
         Request::DeviceDescriptor { .. } => "GET_DESCRIPTOR(Device)",
-        Request::ConfigDescriptor => "GET_DESCRIPTOR(Config)",
+        Request::ConfigDescriptor { .. } => "GET_DESCRIPTOR(Config)",
         Request::SetConfiguration => "SET_CONFIGURATION",
         Request::SetProtocol => "SET_PROTOCOL",
+        Request::ReportDescriptor => "GET_DESCRIPTOR(Report)",
+        Request::SetInterface => "SET_INTERFACE",
+        Request::HubDescriptor => "GET_DESCRIPTOR(Hub)",
+        Request::SetHubDepth => "SET_HUB_DEPTH",
+        Request::MacAddress => "GET_DESCRIPTOR(String, iMACAddress)",
+        Request::NtbInputSize => "SET_NTB_INPUT_SIZE",
+        Request::LineSetup { .. } => "serial line setup",
+        Request::SampleRate => "SET_CUR(sampling frequency)",
+    }
+}
+
+pub(super) fn serial_kind(kind: SerialKind) -> &'static str {
+    match kind {
+        SerialKind::Acm => "CDC-ACM",
+        SerialKind::Ftdi => "FTDI",
+        SerialKind::Cp210x => "CP210x",
+        SerialKind::Ch34x => "CH34x",
     }
 }
 
@@ -676,10 +1320,20 @@ fn hid_kind(protocol: HidType) -> &'static str {
     match protocol {
         HidType::Keyboard => "keyboard",
         HidType::Mouse => "mouse",
-        HidType::Tablet => "tablet",
+        HidType::Report => "report-protocol",
     }
 }
 
+/// How much of the report descriptor is asked for: what the HID descriptor
+/// stated, and the cap where it stated nothing or more than the driver reads.
+fn report_descriptor_len(state: &Enumerating) -> u16 {
+    let stated = match state.parsed {
+        Some((_, Function::Hid(info))) => info.report_len as usize,
+        _ => 0,
+    };
+    if stated == 0 { MAX_REPORT_DESC as u16 } else { stated.min(MAX_REPORT_DESC) as u16 }
+}
+
 /// The Address Device command, with the slot and control endpoint this device
 /// is about to be known by.
 fn address_device_trb(ctrl: &mut XhciController, state: &Enumerating) -> Trb {
@@ -687,9 +1341,7 @@ fn address_device_trb(ctrl: &mut XhciController, state: &Enumerating) -> Trb {
     let input_ctx = super::zero_dma(dma, OFF_INPUT_CTX, PAGE);
 
     ctrl.write_ctx32(input_ctx, 0, 1, 0x3); // Add Slot + EP0
-    let slot_dw0 = ((state.speed as u32) << 20) | (1u32 << 27);
-    ctrl.write_ctx32(input_ctx, 1, 0, slot_dw0);
-    ctrl.write_ctx32(input_ctx, 1, 1, (state.port_idx as u32 + 1) << 16);
+    ctrl.write_slot_context(input_ctx, state.port_idx, state.speed, 1, None);
 
     let ep0_dw1 = (3u32 << 1) | (4u32 << 3) | ((state.packet as u32) << 16);
     ctrl.write_ctx32(input_ctx, 2, 1, ep0_dw1);
@@ -715,8 +1367,22 @@ fn address_device_trb(ctrl: &mut XhciController, state: &Enumerating) -> Trb {
 fn configure_endpoint_trb(ctrl: &mut XhciController, state: &mut Enumerating) -> Option<Trb> {
     let (_, function) = state.parsed.expect("a configuration named a function");
     let rings = match function {
-        Function::Hid(info) => Rings::Hid(hid_input_context(ctrl, state, &info)),
+        Function::Hid(info) => Rings::Hid(interrupt_input_context(ctrl, state, &info.ep, None)),
         Function::Msc(info) => Rings::Msc(super::msc::prepare(
+            ctrl, state.slot_id, state.block, state.speed, state.port_idx, &info,
+        )?),
+        Function::Hub(info) => {
+            let hub = state.hub.expect("the hub descriptor came before its endpoints");
+            let slot = HubSlot { ports: hub.ports, think: hub.think };
+            Rings::Hub(interrupt_input_context(ctrl, state, &info.ep, Some(slot)))
+        }
+        Function::Net(info) => Rings::Net(super::cdc::prepare(
+            ctrl, state.slot_id, state.speed, state.port_idx, &info,
+        )?),
+        Function::Serial(info) => Rings::Serial(super::serial::prepare(
+            ctrl, state.slot_id, state.speed, state.port_idx, &info,
+        )?),
+        Function::Audio(info) => Rings::Audio(super::audio::prepare(
             ctrl, state.slot_id, state.speed, state.port_idx, &info,
         )?),
     };
@@ -728,37 +1394,28 @@ fn configure_endpoint_trb(ctrl: &mut XhciController, state: &mut Enumerating) ->
     Some(configure)
 }
 
-/// The input context for one HID interrupt endpoint, and the ring it runs on.
-fn hid_input_context(
+/// The input context for one interrupt IN endpoint — a HID's, or a hub's with
+/// `hub` saying so in the slot context — and the ring it runs on.
+fn interrupt_input_context(
     ctrl: &mut XhciController,
     state: &Enumerating,
-    info: &HidInterfaceInfo,
+    ep: &Endpoint,
+    hub: Option<HubSlot>,
 ) -> TrbRing {
     let dma = ctrl.dma();
-    let int_ep_dci = info.ep.dci();
+    let int_ep_dci = ep.dci();
     let int_ring = TrbRing::init(dma.subview(state.block + DEV_INT_RING, PAGE));
 
     let input_ctx = super::zero_dma(dma, OFF_INPUT_CTX, PAGE);
 
     ctrl.write_ctx32(input_ctx, 0, 1, (1u32 << (int_ep_dci as u32)) | 1);
 
-    let slot_dw0 = ((state.speed as u32) << 20) | ((int_ep_dci as u32) << 27);
-    ctrl.write_ctx32(input_ctx, 1, 0, slot_dw0);
-    ctrl.write_ctx32(input_ctx, 1, 1, (state.port_idx as u32 + 1) << 16);
+    ctrl.write_slot_context(input_ctx, state.port_idx, state.speed, int_ep_dci, hub);
 
     let ep_ctx_index = int_ep_dci as usize + 1;
-    let interval_val = if info.ep.interval == 0 { 0u32 } else if state.speed <= 2 {
-        let frames = (info.ep.interval as u32) * 8;
-        let mut exp = 0u32;
-        let mut v = frames;
-        while v > 1 { v >>= 1; exp += 1; }
-        exp
-    } else {
-        (info.ep.interval - 1) as u32
-    };
-    ctrl.write_ctx32(input_ctx, ep_ctx_index, 0, interval_val << 16);
+    ctrl.write_ctx32(input_ctx, ep_ctx_index, 0, interrupt_interval(state.speed, ep) << 16);
 
-    let ep_dw1 = (3u32 << 1) | (7u32 << 3) | ((info.ep.max_packet as u32) << 16);
+    let ep_dw1 = (3u32 << 1) | (7u32 << 3) | ((ep.max_packet as u32) << 16);
     ctrl.write_ctx32(input_ctx, ep_ctx_index, 1, ep_dw1);
 
     let int_dequeue = int_ring.dequeue();
@@ -773,11 +1430,27 @@ fn hid_input_context(
     // was declaring that it moves nothing. For a low- or full-speed interrupt
     // endpoint there is one burst of one packet, so both halves are the max
     // packet size, which is what Linux's `xhci_endpoint_init` writes.
-    let esit = info.ep.max_packet as u32;
+    let esit = ep.max_packet as u32;
     ctrl.write_ctx32(input_ctx, ep_ctx_index, 4, (esit << 16) | esit);
     int_ring
 }
 
+/// An interrupt endpoint's Interval field: the service period as a power of
+/// two of 125 µs frames. Below High Speed `bInterval` is in milliseconds, which
+/// is eight of those; at and above it the descriptor already holds the
+/// exponent, plus one.
+pub(super) fn interrupt_interval(speed: u8, ep: &Endpoint) -> u32 {
+    if ep.interval == 0 { 0u32 } else if speed <= 2 {
+        let frames = (ep.interval as u32) * 8;
+        let mut exp = 0u32;
+        let mut v = frames;
+        while v > 1 { v >>= 1; exp += 1; }
+        exp
+    } else {
+        (ep.interval - 1) as u32
+    }
+}
+
 /// Everything class-specific, which is where this sequence stops and the
 /// device's own driver starts.
 fn bind(ctrl: &mut XhciController, state: Enumerating) {
@@ -790,6 +1463,23 @@ fn bind(ctrl: &mut XhciController, state: Enumerating) {
         (Function::Hid(info), Rings::Hid(int_ring)) => {
             bind_hid(ctrl, &state, &info, int_ring);
         }
+        (Function::Hub(info), Rings::Hub(int_ring)) => {
+            let hub = state.hub.expect("the hub descriptor came before its endpoints");
+            ctrl.bind_hub(
+                state.slot_id, state.port_idx, state.block, state.speed, state.ep0_ring,
+                int_ring, info.ep.dci(), hub,
+            );
+        }
+        (Function::Net(info), Rings::Net(rings)) => {
+            let mac = state.mac.expect("the address was read before the endpoints");
+            super::cdc::bind(ctrl, state.slot_id, state.port_idx, &info, rings, mac);
+        }
+        (Function::Serial(info), Rings::Serial(rings)) => {
+            super::serial::bind(ctrl, state.slot_id, state.port_idx, &info, rings);
+        }
+        (Function::Audio(info), Rings::Audio(rings)) => {
+            super::audio::bind(ctrl, state.slot_id, state.port_idx, state.speed, &info, rings);
+        }
         // The rings are built from the function two acts earlier and nothing
         // between the two can change it, so a mismatch is a driver that lost
         // track of which device it is enumerating.
@@ -804,25 +1494,44 @@ fn bind_hid(
     info: &HidInterfaceInfo,
     int_ring: TrbRing,
 ) {
-    let report = ctrl.dma().subview(state.block + DEV_REPORT, 8);
-    let report_size = match info.protocol {
-        HidType::Keyboard => 8,
-        HidType::Mouse => 4,
-        HidType::Tablet => 6,
+    let layout = state.layout.clone();
+    // A device with a layout is asked for a whole packet, up to the largest
+    // report the layout reads: a device with report IDs sends reports of more
+    // than one length, and a transfer shorter than the one that arrives is a
+    // babble the endpoint halts for.
+    let report_size = match (info.protocol, &layout) {
+        (HidType::Keyboard, _) => 8,
+        (HidType::Mouse, _) => 4,
+        (HidType::Report, Some(layout)) => (info.ep.max_packet as usize)
+            .max(layout.report_len())
+            .min(toyos_hid::MAX_REPORT) as u32,
+        (HidType::Report, None) => unreachable!("a report-protocol HID was read for its layout"),
+    };
+    let report = ctrl.dma().subview(state.block + DEV_REPORT, report_size as usize);
+    // A pointer with no entry in the button table cannot be bound: it would
+    // have to share another device's, and then each report of one publishes
+    // the other's buttons as released. A pad with no number is refused for the
+    // same reason.
+    let pointer = || crate::mouse::PointerSource::claim().map(HidRole::Pointer);
+    let kind = layout.as_deref().map(toyos_hid::Layout::kind);
+    let role = match (info.protocol, kind) {
+        (HidType::Keyboard, _) | (_, Some(toyos_hid::Kind::Keyboard)) => Some(HidRole::Keyboard),
+        (HidType::Mouse, _) | (_, Some(toyos_hid::Kind::Mouse | toyos_hid::Kind::Tablet)) => {
+            pointer()
+        }
+        (_, Some(pad @ (toyos_hid::Kind::Gamepad | toyos_hid::Kind::Joystick))) => {
+            let class = match pad {
+                toyos_hid::Kind::Joystick => toyos_abi::input::PAD_JOYSTICK,
+                _ => toyos_abi::input::PAD_GAMEPAD,
+            };
+            crate::gamepad::PadId::claim().map(|id| HidRole::Pad(id, class))
+        }
+        (HidType::Report, None) => unreachable!("a report-protocol HID was read for its layout"),
     };
-    let role = match info.protocol {
-        HidType::Keyboard => HidRole::Keyboard,
-        // A pointer with no entry in the button table cannot be bound: it
-        // would have to share another device's, and then each report of one
-        // publishes the other's buttons as released.
-        HidType::Mouse | HidType::Tablet => match crate::mouse::PointerSource::claim() {
-            Some(source) => HidRole::Pointer(source),
-            None => {
-                log!("xHCI: slot {} is past the pointers this machine can number, dropping it",
-                    state.slot_id);
-                return;
-            }
-        },
+    let Some(role) = role else {
+        log!("xHCI: slot {} is past the {}s this machine can number, dropping it",
+            state.slot_id, kind.map_or("pointer", toyos_hid::Kind::name));
+        return;
     };
     let mut dev = HidDevice {
         slot_id: state.slot_id,
@@ -835,6 +1544,7 @@ fn bind_hid(
         report,
         report_size,
         role,
+        layout,
         prev_report: [0; 8],
         broke_with: None,
         failures: 0,
@@ -845,14 +1555,21 @@ fn bind_hid(
     // The ring offset is in the line because two devices of one class landing
     // on one ring is invisible from every other angle: both still enumerate,
     // both still bind, and both still deliver until their TRBs interleave.
+    // A report-protocol device is named by its descriptor, which is the only
+    // thing that knows whether it is a tablet or a pad.
     log!("xHCI: USB {} ready on slot {}, int_ring +{:#x}",
-        hid_kind(info.protocol), state.slot_id, state.block + DEV_INT_RING);
+        kind.map_or(hid_kind(info.protocol), toyos_hid::Kind::name), state.slot_id,
+        state.block + DEV_INT_RING);
     // The same argument one level up, and the only place the merge is visible:
     // two pointers on two controllers both have a slot 1, so a source derived
     // from the slot id would be one entry and each report would publish the
     // other device's buttons as released.
-    if let HidRole::Pointer(source) = dev.role {
-        log!("xHCI: pointer on slot {} merges as source {}", state.slot_id, source.id());
+    match dev.role {
+        HidRole::Pointer(source) => {
+            log!("xHCI: pointer on slot {} merges as source {}", state.slot_id, source.id());
+        }
+        HidRole::Pad(pad, _) => log!("xHCI: gamepad on slot {} is pad {}", state.slot_id, pad.id()),
+        HidRole::Keyboard => {}
     }
     ctrl.devices.push(dev);
 }
@@ -908,20 +1625,31 @@ pub(super) fn cancel_on(ctrl: &mut XhciController, port_idx: u8) {
 #[cfg(feature = "boot-actuators")]
 pub fn selftest() {
     /// (kind, config value, first DCI, second DCI); kind 1 is HID, 2 is mass
-    /// storage. A tuple rather than the enum, because what is under test is the
-    /// numbers the parser resolved and `Function` has no equality.
+    /// storage over Bulk-Only, 3 over UAS, 4 a hub, 5 a network adapter, 6 a
+    /// serial adapter and 7 a DAC, whose second DCI is its feedback endpoint's
+    /// or 0. A tuple rather than the enum, because what is under test
+    /// is the numbers the parser resolved and `Function` has no equality.
     type Verdict = Option<(u8, u8, u8, u8)>;
 
     fn summarise(got: Option<(u8, Function)>) -> Verdict {
         match got? {
             (cfg, Function::Hid(h)) => Some((1, cfg, h.ep.dci(), 0)),
-            (cfg, Function::Msc(m)) => Some((2, cfg, m.in_ep.dci(), m.out_ep.dci())),
+            (cfg, Function::Msc(m)) => {
+                let kind = if m.uas.is_some() { 3 } else { 2 };
+                Some((kind, cfg, m.in_ep.dci(), m.out_ep.dci()))
+            }
+            (cfg, Function::Hub(h)) => Some((4, cfg, h.ep.dci(), 0)),
+            (cfg, Function::Net(n)) => Some((5, cfg, n.in_ep.dci(), n.out_ep.dci())),
+            (cfg, Function::Serial(s)) => Some((6, cfg, s.in_ep.dci(), s.out_ep.dci())),
+            (cfg, Function::Audio(a)) => {
+                Some((7, cfg, a.out_ep.dci(), a.feedback.map_or(0, |ep| ep.dci())))
+            }
         }
     }
 
     /// A config descriptor whose `wTotalLength` is `total` and whose body is
     /// one interface followed by `eps`, each `(address, transfer type)`.
-    fn build(buf: &mut [u8; 64], class: (u8, u8, u8), eps: &[(u8, u8)], total: u16) -> usize {
+    fn build(buf: &mut [u8; 128], class: (u8, u8, u8), eps: &[(u8, u8)], total: u16) -> usize {
         buf.fill(0);
         buf[..9].copy_from_slice(&[9, 2, total as u8, (total >> 8) as u8, 1, 0x42, 0, 0, 0]);
         buf[9..18].copy_from_slice(&[9, 4, 0, 0, eps.len() as u8, class.0, class.1, class.2, 0]);
@@ -933,20 +1661,116 @@ pub fn selftest() {
         at
     }
 
+    /// A stick the way they ship: Bulk-Only as setting 0 on bulk 0x81/0x02,
+    /// UAS as setting 1 with its four pipes, each a SuperSpeed endpoint with a
+    /// companion offering `streams` and the Pipe Usage that names it.
+    fn build_uas(buf: &mut [u8; 128], streams: u8) -> usize {
+        buf.fill(0);
+        buf[9..18].copy_from_slice(&[9, 4, 0, 0, 2, 0x08, 0x06, 0x50, 0]);
+        buf[18..25].copy_from_slice(&[7, 5, 0x81, 2, 0, 4, 0]);
+        buf[25..32].copy_from_slice(&[7, 5, 0x02, 2, 0, 4, 0]);
+        buf[32..41].copy_from_slice(&[9, 4, 0, 1, 4, 0x08, 0x06, 0x62, 0]);
+        let mut at = 41;
+        // Command out, status in, data in, data out: DCIs 8, 7, 5, 6.
+        for (addr, pipe) in [(0x04u8, 1u8), (0x83, 2), (0x82, 3), (0x03, 4)] {
+            buf[at..at + 7].copy_from_slice(&[7, 5, addr, 2, 0, 4, 0]);
+            let streams = if pipe == 1 { 0 } else { streams };
+            buf[at + 7..at + 13].copy_from_slice(&[6, 0x30, 0, streams, 0, 0]);
+            buf[at + 13..at + 17].copy_from_slice(&[4, 0x24, pipe, 0]);
+            at += 17;
+        }
+        let total = at as u16;
+        buf[..9].copy_from_slice(&[9, 2, total as u8, (total >> 8) as u8, 1, 0x42, 0, 0, 0]);
+        at
+    }
+
+    /// An ECM adapter the way QEMU's `usb-net` describes its second
+    /// configuration: the communication interface with its Header, Union and
+    /// Ethernet functional descriptors and interrupt 0x81, then data interface
+    /// `data` with no endpoints at setting 0 and bulk 0x82/0x03 at setting 1.
+    /// The Union names `union`, which is `data` unless a case says otherwise.
+    fn build_ecm(buf: &mut [u8; 128], union: u8, data: u8) -> usize {
+        buf.fill(0);
+        buf[9..18].copy_from_slice(&[9, 4, 0, 0, 1, 0x02, 0x06, 0, 0]);
+        buf[18..23].copy_from_slice(&[5, 0x24, 0x00, 0x10, 0x01]);
+        buf[23..28].copy_from_slice(&[5, 0x24, 0x06, 0, union]);
+        buf[28..41].copy_from_slice(&[13, 0x24, 0x0F, 3, 0, 0, 0, 0, 0xEA, 0x05, 0, 0, 0]);
+        buf[41..48].copy_from_slice(&[7, 5, 0x81, 3, 16, 0, 0x40]);
+        buf[48..57].copy_from_slice(&[9, 4, data, 0, 0, 0x0A, 0, 0, 0]);
+        buf[57..66].copy_from_slice(&[9, 4, data, 1, 2, 0x0A, 0, 0, 0]);
+        buf[66..73].copy_from_slice(&[7, 5, 0x82, 2, 64, 0, 0]);
+        buf[73..80].copy_from_slice(&[7, 5, 0x03, 2, 64, 0, 0]);
+        buf[..9].copy_from_slice(&[9, 2, 80, 0, 2, 0x42, 0, 0, 0]);
+        80
+    }
+
+    /// An ACM adapter the way a microcontroller's USB stack describes one: the
+    /// communication interface with its Header, Call Management, ACM and Union
+    /// functional descriptors and interrupt 0x83, then data interface 1 with
+    /// bulk 0x81/0x02 at setting 0. The Union names `union`.
+    fn build_acm(buf: &mut [u8; 128], union: u8) -> usize {
+        buf.fill(0);
+        buf[9..18].copy_from_slice(&[9, 4, 0, 0, 1, 0x02, 0x02, 0x01, 0]);
+        buf[18..23].copy_from_slice(&[5, 0x24, 0x00, 0x10, 0x01]);
+        buf[23..28].copy_from_slice(&[5, 0x24, 0x01, 0, 1]);
+        buf[28..32].copy_from_slice(&[4, 0x24, 0x02, 0x02]);
+        buf[32..37].copy_from_slice(&[5, 0x24, 0x06, 0, union]);
+        buf[37..44].copy_from_slice(&[7, 5, 0x83, 3, 8, 0, 0x10]);
+        buf[44..53].copy_from_slice(&[9, 4, 1, 0, 2, 0x0A, 0, 0, 0]);
+        buf[53..60].copy_from_slice(&[7, 5, 0x81, 2, 64, 0, 0]);
+        buf[60..67].copy_from_slice(&[7, 5, 0x02, 2, 64, 0, 0]);
+        buf[..9].copy_from_slice(&[9, 2, 67, 0, 2, 0x42, 0, 0, 0]);
+        67
+    }
+
+    /// A UAC1 DAC the way QEMU's `usb-audio` describes itself: the control
+    /// interface with its header and terminals, then streaming interface 1
+    /// with no endpoints at setting 0 and, at setting 1, PCM in two 16-bit
+    /// channels at the one rate `rate`, on isochronous OUT 0x01 of 192 bytes
+    /// with a rate control. `feedback` makes the endpoint asynchronous, with
+    /// its feedback on isochronous IN 0x81.
+    fn build_uac1(buf: &mut [u8; 128], rate: u32, feedback: bool) -> usize {
+        buf.fill(0);
+        buf[9..18].copy_from_slice(&[9, 4, 0, 0, 0, 0x01, 0x01, 0, 0]);
+        buf[18..27].copy_from_slice(&[9, 0x24, 0x01, 0x00, 0x01, 30, 0, 1, 1]);
+        buf[27..39].copy_from_slice(&[12, 0x24, 0x02, 1, 0x01, 0x01, 0, 2, 0x03, 0, 0, 0]);
+        buf[39..48].copy_from_slice(&[9, 0x24, 0x03, 3, 0x01, 0x03, 0, 1, 0]);
+        buf[48..57].copy_from_slice(&[9, 4, 1, 0, 0, 0x01, 0x02, 0, 0]);
+        buf[57..66].copy_from_slice(&[9, 4, 1, 1, 1 + u8::from(feedback), 0x01, 0x02, 0, 0]);
+        buf[66..73].copy_from_slice(&[7, 0x24, 0x01, 1, 1, 1, 0]);
+        let [r0, r1, r2, _] = rate.to_le_bytes();
+        buf[73..84].copy_from_slice(&[11, 0x24, 0x02, 1, 2, 2, 16, 1, r0, r1, r2]);
+        let attrs = if feedback { 0x05 } else { 0x0D };
+        buf[84..93].copy_from_slice(&[9, 5, 0x01, attrs, 192, 0, 1, 0, 0x81 * u8::from(feedback)]);
+        buf[93..100].copy_from_slice(&[7, 0x25, 0x01, 0x01, 0, 0, 0]);
+        let mut at = 100;
+        if feedback {
+            buf[100..109].copy_from_slice(&[9, 5, 0x81, 0x11, 3, 0, 1, 2, 0]);
+            at = 109;
+        }
+        buf[..9].copy_from_slice(&[9, 2, at as u8, 0, 2, 0x42, 0, 0, 0]);
+        at
+    }
+
     const MSC: (u8, u8, u8) = (0x08, 0x06, 0x50);
     const KBD: (u8, u8, u8) = (3, 1, 1);
-    const CASES: usize = 9;
-
-    let mut passed = 0usize;
-    let mut buf = [0u8; 64];
-    let mut check = |name: &str, desc: &[u8], want: Verdict| {
-        let got = summarise(parse_config(desc));
+    const HUB: (u8, u8, u8) = (9, 0, 0);
+    const VENDOR: (u8, u8, u8) = (0xFF, 0xFF, 0xFF);
+    const CASES: usize = 23;
+
+    // A `Cell` so both closures are `Fn`: `check` borrows `check_on`, and the
+    // cases that call `check_on` directly sit between cases that call `check`.
+    let passed = core::cell::Cell::new(0usize);
+    let mut buf = [0u8; 128];
+    let check_on = |name: &str, desc: &[u8], streams: bool, vendor, want: Verdict| {
+        let got = summarise(parse_config(desc, streams, vendor));
         if got == want {
-            passed += 1;
+            passed.set(passed.get() + 1);
         } else {
             log!("xHCI: descriptor selftest FAILED on {name}: got {got:?}, want {want:?}");
         }
     };
+    let check = |name: &str, desc: &[u8], want: Verdict| check_on(name, desc, true, None, want);
 
     // Bulk IN 0x81 is DCI 3, bulk OUT 0x02 is DCI 4.
     let len = build(&mut buf, MSC, &[(0x81, 2), (0x02, 2)], 32);
@@ -987,5 +1811,68 @@ pub fn selftest() {
     let len = build(&mut buf, KBD, &[(0x81, 3)], 25);
     check("a truncated final descriptor", &buf[..len - 3], None);
 
-    log!("xHCI: descriptor selftest {passed}/{CASES} configurations parsed as required");
+    // The UAS setting wins, and its data pipes are the disk's endpoints: data
+    // in 0x82 is DCI 5, data out 0x03 is DCI 6.
+    let len = build_uas(&mut buf, 4);
+    check("a disk offering UAS beside Bulk-Only", &buf[..len], Some((3, 0x42, 5, 6)));
+
+    // A data-in pipe that is an OUT endpoint is not one; UAS is incomplete
+    // and Bulk-Only is what is left.
+    let len = build_uas(&mut buf, 4);
+    buf[41 + 2 * 17 + 2] = 0x02 | 0x05;
+    check("a UAS data-in pipe pointing out", &buf[..len], Some((2, 0x42, 3, 4)));
+
+    // The same stick on a controller without streams is a Bulk-Only disk.
+    let len = build_uas(&mut buf, 4);
+    check_on("a UAS disk on a controller without streams", &buf[..len], false, None,
+        Some((2, 0x42, 3, 4)));
+
+    // A hub's status-change endpoint is interrupt IN 0x81, DCI 3.
+    let len = build(&mut buf, HUB, &[(0x81, 3)], 25);
+    check("an ordinary hub", &buf[..len], Some((4, 0x42, 3, 0)));
+
+    // Bulk IN 0x82 is DCI 5 and bulk OUT 0x03 is DCI 6, found at setting 1 of
+    // the data interface the Union named.
+    let len = build_ecm(&mut buf, 1, 1);
+    check("an ordinary ECM adapter", &buf[..len], Some((5, 0x42, 5, 6)));
+
+    // A data interface the communication interface does not name is some
+    // other function's pipes, and a network adapter with none is not one.
+    let len = build_ecm(&mut buf, 2, 1);
+    check("an ECM adapter whose Union names another interface", &buf[..len], None);
+
+    // Bulk IN 0x81 is DCI 3 and bulk OUT 0x02 is DCI 4, found at setting 0 of
+    // the data interface the Union named; the notification endpoint is not.
+    let len = build_acm(&mut buf, 1);
+    check("an ordinary ACM adapter", &buf[..len], Some((6, 0x42, 3, 4)));
+
+    let len = build_acm(&mut buf, 2);
+    check("an ACM adapter whose Union names another interface", &buf[..len], None);
+
+    // QEMU's `usb-serial`: one vendor interface with a bulk pair, which is a
+    // serial port only because the device descriptor's IDs said so.
+    let len = build(&mut buf, VENDOR, &[(0x81, 2), (0x02, 2)], 32);
+    check_on("an FTDI adapter", &buf[..len], true, Some(SerialKind::Ftdi), Some((6, 0x42, 3, 4)));
+    check("a vendor interface nobody's IDs named", &buf[..len], None);
+
+    // A CH340: its bulk IN is 0x82, DCI 5, and the interrupt IN 0x81 beside
+    // the pair carries modem status this driver does not read.
+    let len = build(&mut buf, VENDOR, &[(0x82, 2), (0x02, 2), (0x81, 3)], 39);
+    check_on("a CH340 adapter", &buf[..len], true, Some(SerialKind::Ch34x), Some((6, 0x42, 5, 4)));
+
+    // Isochronous OUT 0x01 is DCI 2, found at setting 1 of the streaming
+    // interface; an adaptive endpoint has no feedback to read.
+    let len = build_uac1(&mut buf, 48_000, false);
+    check("an ordinary UAC1 DAC", &buf[..len], Some((7, 0x42, 2, 0)));
+
+    // The same DAC offering only 44.1 kHz plays nothing soundd sends, and
+    // nothing is converted in the kernel to make it.
+    let len = build_uac1(&mut buf, 44_100, false);
+    check("a DAC without 48 kHz", &buf[..len], None);
+
+    // An asynchronous one's feedback is isochronous IN 0x81, DCI 3.
+    let len = build_uac1(&mut buf, 48_000, true);
+    check("an asynchronous DAC", &buf[..len], Some((7, 0x42, 2, 3)));
+
+    log!("xHCI: descriptor selftest {}/{CASES} configurations parsed as required", passed.get());
 }
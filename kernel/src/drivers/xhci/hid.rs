@@ -1,26 +1,35 @@
+use alloc::boxed::Box;
 use core::sync::atomic::{fence, Ordering};
 
-use crate::{keyboard, mouse};
+use toyos_hid::{Layout, Report};
+
+use crate::{gamepad, keyboard, mouse};
 use super::{Mmio, Trb, TrbRing, TRB_NORMAL};
 
 /// What a configuration descriptor's HID interface said it was. Parse-time
-/// only: the three differ in report size and in whether SET_PROTOCOL applies,
-/// and in nothing a bound device does.
+/// only: the three differ in report size, in whether SET_PROTOCOL applies and
+/// in whether a report descriptor is read, and in nothing a bound device does.
 #[derive(Clone, Copy, PartialEq)]
 pub enum HidType {
     Keyboard,
     Mouse,
-    Tablet,
+    /// No boot protocol: a tablet, a game controller, a keyboard that never
+    /// had a boot interface. What it is and how it reports are both its
+    /// report descriptor's to say.
+    Report,
 }
 
 /// What a *bound* device is, which is a coarser question — the two pointer
 /// kinds dispatch identically, and `mouse::handle_report` tells them apart by
 /// report length. The source is carried rather than derived from the slot id,
 /// which is per controller and therefore not a machine-wide name for a device.
-#[derive(Clone, Copy)]
+/// A pad carries its number for the same reason, and whether its descriptor
+/// called it a gamepad or a joystick, which every state it publishes restates.
+#[derive(Clone, Copy, Debug)]
 pub enum HidRole {
     Keyboard,
     Pointer(mouse::PointerSource),
+    Pad(gamepad::PadId, u8),
 }
 
 pub struct HidDevice {
@@ -44,13 +53,19 @@ pub struct HidDevice {
     /// mass-storage device keeps its: clearing a halt is a control transfer, so
     /// a bound HID is something the driver may still have to talk to.
     pub ep0_ring: TrbRing,
-    /// The eight-byte DMA slot the interrupt endpoint delivers reports into.
+    /// The DMA slot the interrupt endpoint delivers reports into, eight bytes
+    /// for a boot device and [`toyos_hid::MAX_REPORT`] for one with a layout.
     /// A [`crate::mm::Dma`] view and not a `*mut u8` beside its own physical
     /// address: it carries the length, so the two accesses below are bounded
     /// against the slot rather than against `report_size`'s own honesty.
     pub report: crate::mm::Dma<'static>,
     pub report_size: u32,
     pub role: HidRole,
+    /// Where in a report each key, button and axis is, out of the device's
+    /// report descriptor — and `None` for a boot device, whose format is the
+    /// specification's and is read by hand as it always was. Boxed because it
+    /// is a kilobyte and most devices on a bus have none.
+    pub layout: Option<Box<Layout>>,
     /// This keyboard's last report. Per device, because a report is a snapshot
     /// of one keyboard and diffing it against another's synthesizes releases
     /// for keys that are still physically down.
@@ -79,22 +94,25 @@ pub struct HidDevice {
 impl HidDevice {
     /// What this device is called in every line about it.
     ///
-    /// Two names and not the descriptor's three: a mouse and a tablet bind
+    /// Three names and not the descriptor's five: a mouse and a tablet bind
     /// identically and `mouse::handle_report` tells them apart by report
     /// length, so a line saying "tablet" would be naming what the descriptor
-    /// claimed rather than what the driver has.
+    /// claimed rather than what the driver has. A joystick and a gamepad are
+    /// one pad by the same argument.
     pub fn kind(&self) -> &'static str {
         match self.role {
             HidRole::Keyboard => "keyboard",
             HidRole::Pointer(_) => "pointer",
+            HidRole::Pad(..) => "gamepad",
         }
     }
 
-    pub fn dispatch_report(&mut self) {
-        let mut buf = [0u8; 8];
+    /// One completed report, `residue` bytes short of the transfer asked for.
+    pub fn dispatch_report(&mut self, residue: u32) {
+        let mut buf = [0u8; toyos_hid::MAX_REPORT];
         let size = self.report_size as usize;
-        // Bounded twice: `copy_to` refuses `size > 8`, which is the slot
-        // `bind_hid` allocated, and `report_size` is 4, 6 or 8 by the `match`
+        // Bounded twice: `copy_to` refuses `size` past the slot `bind_hid`
+        // allocated, and `report_size` is at most `MAX_REPORT` by the `match`
         // that set it. A copy and not a borrow into DMA memory; the transfer has
         // completed, since `dispatch_report` runs off a Transfer Event, and the
         // endpoint is not requeued until `requeue` below.
@@ -103,12 +121,26 @@ impl HidDevice {
         // identical to the last one produces no event, and waking watchers
         // for it made readiness disagree with `has_data()` — which froze the
         // compositor for as long as a key was held.
-        let queued = match self.role {
-            HidRole::Keyboard => keyboard::handle_report(&mut self.prev_report, &buf[..size]) != 0,
-            HidRole::Pointer(source) => mouse::handle_report(source, &buf[..size]) != 0,
+        let queued = match self.layout.as_deref() {
+            // A device with a layout asks for the largest report it may send,
+            // so how much of it arrived is the residue's to say: a report of
+            // another ID may be shorter than this one, and the bytes behind it
+            // are the last report's.
+            Some(layout) => {
+                let arrived = size - (residue as usize).min(size);
+                let report = layout.decode(&buf[..arrived]);
+                report.is_some_and(|report| self.role.deliver(&mut self.prev_report, report))
+            }
+            None => match self.role {
+                HidRole::Keyboard => {
+                    keyboard::handle_report(&mut self.prev_report, &buf[..size]) != 0
+                }
+                HidRole::Pointer(source) => mouse::handle_report(source, &buf[..size]) != 0,
+                HidRole::Pad(..) => unreachable!("a pad is bound only with a layout"),
+            },
         };
         if queued {
-            self.wake();
+            self.role.wake();
         }
     }
 
@@ -122,23 +154,69 @@ impl HidDevice {
     /// beside it keeps the keys it is holding.
     ///
     /// The pointer half gives the button-table entry back as well, which is
-    /// what makes a device that is plugged in again cost the machine nothing.
+    /// what makes a device that is plugged in again cost the machine nothing,
+    /// and a pad gives back its number.
     pub fn unbind(&mut self) {
-        let queued = match self.role {
-            HidRole::Keyboard => keyboard::handle_report(&mut self.prev_report, &[0u8; 8]) != 0,
+        if self.role.release(&mut self.prev_report) {
+            self.role.wake();
+        }
+    }
+
+    pub fn requeue(&mut self, db_base: &Mmio) {
+        let mut trb = Trb::ZERO;
+        trb.param = self.report.phys();
+        trb.status = self.report_size;
+        trb.control = TRB_NORMAL | (1 << 5); // IOC
+        self.int_ring.enqueue(trb);
+        fence(Ordering::Release);
+        db_base.write_u32(self.slot_id as u64 * 4, self.int_ep_dci as u32);
+    }
+}
+
+/// The half of a bound device that is about what it publishes and not about
+/// the endpoint it arrived on — which is all a report needs once it has been
+/// decoded, and all `synthetic_pad` below has.
+impl HidRole {
+    /// Hand one decoded report to the path this role publishes through, which
+    /// is the path a boot device of the same role takes. `prev_report` is the
+    /// device's own, for a keyboard's diff. True iff something was queued.
+    fn deliver(self, prev_report: &mut [u8; 8], report: Report) -> bool {
+        match (self, report) {
+            (HidRole::Keyboard, Report::Keyboard(boot)) => {
+                keyboard::handle_report(prev_report, &boot) != 0
+            }
+            (HidRole::Pointer(source), Report::Pointer(pointer)) => {
+                let motion = match pointer.motion {
+                    toyos_hid::Motion::Relative { dx, dy } => mouse::Motion::Relative { dx, dy },
+                    toyos_hid::Motion::Absolute { x, y } => mouse::Motion::Absolute { x, y },
+                };
+                mouse::handle_motion(source, pointer.buttons, motion, pointer.wheel)
+            }
+            (HidRole::Pad(pad, kind), Report::Pad(state)) => {
+                gamepad::publish(pad, kind, state.buttons, state.axes, state.hat)
+            }
+            // The role was chosen from this layout's kind and the layout
+            // decodes only into that kind's report.
+            (role, report) => unreachable!("a {role:?} decoded {report:?}"),
+        }
+    }
+
+    /// What [`HidDevice::unbind`] publishes for this role. True iff something
+    /// was queued.
+    fn release(self, prev_report: &mut [u8; 8]) -> bool {
+        match self {
+            HidRole::Keyboard => keyboard::handle_report(prev_report, &[0u8; 8]) != 0,
             HidRole::Pointer(source) => mouse::unbind(source),
-        };
-        if queued {
-            self.wake();
+            HidRole::Pad(pad, kind) => gamepad::unbind(pad, kind),
         }
     }
 
-    /// Wake whoever is waiting on this device's kind of event. Both halves of
+    /// Wake whoever is waiting on this role's kind of event. Both halves of
     /// the pair, always: the queue a blocked `sys_read` parks on and the ring
     /// watchers `process_poll_add` registered, which nothing in the type
     /// system pairs.
-    fn wake(&self) {
-        let (watchers, source) = match self.role {
+    fn wake(self) {
+        let (watchers, source) = match self {
             HidRole::Keyboard => {
                 keyboard::wake_waiters();
                 (keyboard::inbox_watchers(), crate::inbox::Source::Keyboard)
@@ -147,21 +225,15 @@ impl HidDevice {
                 mouse::wake_waiters();
                 (mouse::inbox_watchers(), crate::inbox::Source::Mouse)
             }
+            HidRole::Pad(..) => {
+                gamepad::wake_waiters();
+                (gamepad::inbox_watchers(), crate::inbox::Source::Gamepad)
+            }
         };
         if !watchers.is_empty() {
             crate::inbox::complete_pending_for_event(&watchers, source);
         }
     }
-
-    pub fn requeue(&mut self, db_base: &Mmio) {
-        let mut trb = Trb::ZERO;
-        trb.param = self.report.phys();
-        trb.status = self.report_size;
-        trb.control = TRB_NORMAL | (1 << 5); // IOC
-        self.int_ring.enqueue(trb);
-        fence(Ordering::Release);
-        db_base.write_u32(self.slot_id as u64 * 4, self.int_ep_dci as u32);
-    }
 }
 
 /// Take one completion away from the device that earned it and hand the driver
@@ -211,10 +283,99 @@ impl HidDevice {
             return code;
         }
         // This actuator's whole job is to leave the slot as a stalled endpoint
-        // would have. Bounded against the 8-byte slot. Exclusive for the same
+        // would have. Bounded against the report slot. Exclusive for the same
         // reason as `dispatch_report`: this runs on the completion, before the
         // endpoint is requeued.
         self.report.subview(0, self.report_size as usize).zero();
         super::CC_STALL
     }
 }
+
+/// A game controller with no bus under it, for `SYS_DEBUG`'s pad actions.
+///
+/// **The only pad a test can plug in.** QEMU has no USB game controller:
+/// `usb-hid` is a keyboard, a mouse or a tablet, each with a report descriptor
+/// fixed in `hw/usb/dev-hid.c`, and no `-device` property gives one another.
+/// `usb-host` passthrough needs a pad on the runner. So everything from
+/// [`toyos_hid::Layout::decode`] onward — the pad number, the diff against the
+/// pad's last state, the queue, the wake, the read, and the release an unplug
+/// publishes — had never run on a booted machine.
+///
+/// What is real is everything after the transfer: the descriptor is a
+/// DirectInput controller's, parsed by the parse enumeration runs; each report
+/// is decoded by its layout and handed to [`HidRole::deliver`]; the wake is
+/// [`HidRole::wake`]; and the unplug is [`HidRole::release`]. What is replaced
+/// is the endpoint — the bytes arrive in a syscall argument rather than in the
+/// report slot — which is why the report is capped at eight bytes and the
+/// descriptor's is eight.
+#[cfg(feature = "test-actuators")]
+pub mod synthetic_pad {
+    use alloc::boxed::Box;
+
+    use toyos_abi::syscall::SyscallError;
+    use toyos_hid::Layout;
+
+    use super::HidRole;
+    use crate::log;
+    use crate::sync::Lock;
+
+    /// Report ID 1, then four 8-bit sticks (X, Y, Z, Rz), an eight-way hat
+    /// with a null state and its padding, and twelve buttons padded to sixteen:
+    /// the descriptor `toyos-hid`'s own tests read as their gamepad.
+    const DESCRIPTOR: &[u8] = &[
+        0x05, 0x01, 0x09, 0x05, 0xA1, 0x01, 0x85, 0x01, 0xA1, 0x00, 0x09, 0x30, 0x09, 0x31, 0x09,
+        0x32, 0x09, 0x35, 0x15, 0x00, 0x26, 0xFF, 0x00, 0x75, 0x08, 0x95, 0x04, 0x81, 0x02, 0xC0,
+        0x09, 0x39, 0x15, 0x00, 0x25, 0x07, 0x35, 0x00, 0x46, 0x3B, 0x01, 0x65, 0x14, 0x75, 0x04,
+        0x95, 0x01, 0x81, 0x42, 0x65, 0x00, 0x75, 0x04, 0x95, 0x01, 0x81, 0x01, 0x05, 0x09, 0x19,
+        0x01, 0x29, 0x0C, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x0C, 0x81, 0x02, 0x75, 0x01,
+        0x95, 0x04, 0x81, 0x01, 0xC0,
+    ];
+
+    /// The plugged-in pad, as a bound device holds it: its layout and its role.
+    static PAD: Lock<Option<(Box<Layout>, HidRole)>> = Lock::new(None);
+
+    /// Plug the pad in, and answer the number it was given. Refused while it is
+    /// already plugged in, and when [`crate::gamepad::MAX_PADS`] are.
+    pub fn plug() -> Result<u8, SyscallError> {
+        let mut pad = PAD.lock();
+        if pad.is_some() {
+            return Err(SyscallError::InvalidArgument);
+        }
+        let layout = Layout::parse(DESCRIPTOR).expect("the synthetic pad's own descriptor parses");
+        let id = crate::gamepad::PadId::claim().ok_or(SyscallError::ResourceExhausted)?;
+        *pad = Some((Box::new(layout), HidRole::Pad(id, toyos_abi::input::PAD_GAMEPAD)));
+        log!("xHCI: synthetic gamepad is pad {}", id.id());
+        Ok(id.id())
+    }
+
+    /// One report, as the interrupt endpoint would have delivered it. True iff
+    /// it queued a state: a report of another ID decodes to nothing, and one
+    /// that restates the last state queues nothing, exactly as from a device.
+    pub fn report(bytes: [u8; 8]) -> Result<bool, SyscallError> {
+        let role = {
+            let pad = PAD.lock();
+            let Some((layout, role)) = pad.as_ref() else {
+                return Err(SyscallError::InvalidArgument);
+            };
+            // A pad has no keyboard diff to keep, so the slot is a scratch one.
+            let queued = layout
+                .decode(&bytes)
+                .is_some_and(|report| role.deliver(&mut [0; 8], report));
+            queued.then_some(*role)
+        };
+        // Outside the lock, since a wake completes other processes' inboxes.
+        if let Some(role) = role {
+            role.wake();
+        }
+        Ok(role.is_some())
+    }
+
+    /// Pull the pad out: its last, released state, and its number back.
+    pub fn unplug() -> Result<(), SyscallError> {
+        let (_, role) = PAD.lock().take().ok_or(SyscallError::InvalidArgument)?;
+        if role.release(&mut [0; 8]) {
+            role.wake();
+        }
+        Ok(())
+    }
+}
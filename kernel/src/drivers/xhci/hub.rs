@@ -0,0 +1,171 @@
+//! A bound hub, as the poll sees it: the ports it was given in
+//! [`XhciController::ports`], the status-change bitmap its interrupt endpoint
+//! delivers, and what becomes of both when the hub itself leaves.
+//!
+//! Nothing here asks the hub anything. A question to a hub is a control
+//! transfer and waits, so that half is `wait::hub`'s; this half only records
+//! that a question is owed, which is what a bitmap bit means.
+//!
+//! **A hub's ports are numbered after the root ports, in the same table.**
+//! Everything the driver keys by `port_idx` — the port machine, the slot a
+//! device holds, teardown, the mass-storage block a disk claimed — then works
+//! on a device behind a hub without knowing it is one, and the only places
+//! that do know are the two that touch the register: `read_portsc` and
+//! `write_portsc`. [`Downstream`] is what they consult.
+
+use core::sync::atomic::{fence, Ordering};
+
+use crate::log;
+use crate::mm::Dma;
+use toyos_xhci::hub::{PortStatus, Place};
+use super::{Completion, Mmio, Trb, TrbRing, XhciController, TRB_NORMAL};
+use super::{CC_SHORT_PACKET, CC_SUCCESS};
+
+pub struct HubDevice {
+    pub slot_id: u8,
+    /// The port the hub itself is on. Its teardown names this, and it is how
+    /// [`XhciController::orphan_hub`] finds the hub to let go of.
+    pub port_idx: u8,
+    pub ep0_ring: TrbRing,
+    pub int_ring: TrbRing,
+    pub int_dci: u8,
+    /// Where the interrupt endpoint writes the status-change bitmap: bit 0 is
+    /// the hub, bit N its port N (USB 2.0 §11.12.4).
+    pub bitmap: Dma<'static>,
+    /// Where a GET_STATUS(port) answer lands. One buffer, because the hub is
+    /// asked about one port at a time.
+    pub status: Dma<'static>,
+    /// This hub's port 1 in [`XhciController::ports`], and how many follow it.
+    pub first: u8,
+    pub count: u8,
+    /// Ports the hub has said changed and nobody has asked about since, bit N
+    /// for port N as in the bitmap. Set by the transfer event, cleared by
+    /// `refresh_hubs` when it asks.
+    pub changed: u16,
+    /// Not asked before this instant: the hub's bPwrOn2PwrGood after its ports
+    /// were powered. A port asked earlier reads as nothing connected, which is
+    /// true, and the change that follows is then the only connect it reports.
+    pub not_before: u64,
+}
+
+impl HubDevice {
+    /// The bitmap's length: one bit for the hub and one per port, rounded up
+    /// to a byte. A longer TRB would be a short packet on every completion.
+    fn bitmap_len(&self) -> u32 {
+        (self.count as u32 + 1).div_ceil(8)
+    }
+
+    /// Give the interrupt endpoint its next TRB. The bitmap is zeroed first,
+    /// so a short answer cannot leave the previous one's second byte behind.
+    pub fn requeue(&mut self, db_base: &Mmio) {
+        self.bitmap.write::<u16>(0, 0);
+        let mut trb = Trb::ZERO;
+        trb.param = self.bitmap.phys();
+        trb.status = self.bitmap_len();
+        trb.control = TRB_NORMAL | (1 << 5); // IOC
+        self.int_ring.enqueue(trb);
+        fence(Ordering::Release);
+        db_base.write_u32(self.slot_id as u64 * 4, self.int_dci as u32);
+    }
+}
+
+/// One of a hub's ports, at its index in [`XhciController::ports`] less the
+/// root ports'.
+#[derive(Clone, Copy)]
+pub struct Downstream {
+    pub hub_slot: u8,
+    /// The hub's own number for the port, 1-based, which is what wIndex says.
+    pub number: u8,
+    pub superspeed: bool,
+    /// The hub's last answer about the port, which is what reading it returns.
+    pub status: PortStatus,
+    pub place: Place,
+    /// The hub has gone. The port reads [`PortStatus::GONE`] and its writes
+    /// are applied here instead of being sent, until the machine has torn down
+    /// what was on it and the entry can be given to the next hub.
+    pub orphaned: bool,
+}
+
+impl XhciController {
+    /// The hub port at `port_idx`, or `None` for a root port.
+    pub(super) fn downstream_port(&self, port_idx: u8) -> Option<&Downstream> {
+        self.downstream.get(port_idx.checked_sub(self.max_ports)? as usize)
+    }
+
+    /// The interrupt endpoint of `self.hubs[at]` completed with `code`.
+    ///
+    /// Only records: the ports named are asked about by the next pass's
+    /// `refresh_hubs`, which is allowed to wait and this is not.
+    pub(super) fn hub_changed(&mut self, at: usize, code: u32) {
+        let hub = &mut self.hubs[at];
+        if code != CC_SUCCESS && code != CC_SHORT_PACKET {
+            log!(
+                "xHCI: hub on slot {} stopped reporting its ports ({}); what is plugged into it from here on is not seen until it is replugged",
+                hub.slot_id,
+                Completion(code)
+            );
+            return;
+        }
+        let mut bytes = [0u8; 2];
+        hub.bitmap.copy_to(0, &mut bytes);
+        let ports = ((1u32 << (hub.count + 1)) - 2) as u16;
+        hub.changed |= u16::from_le_bytes(bytes) & ports;
+        hub.requeue(&self.db_base);
+        self.ports_dirty = true;
+    }
+
+    /// The device on `port_idx` is being torn down; if it is a hub, let go of
+    /// it and of its ports.
+    ///
+    /// Its ports stay in the table. Each still holds whatever the machine had
+    /// enumerated behind it, and the way that is torn down is the way a pulled
+    /// device is: the port reads a connect change with nothing connected, on
+    /// the next pass, and the machine does the rest. **The hub is asked
+    /// nothing from here on** — its slot is about to be disabled, and a request
+    /// to it is a transfer that can only time out.
+    pub(super) fn orphan_hub(&mut self, port_idx: u8) {
+        while let Some(at) = self.hubs.iter().position(|h| h.port_idx == port_idx) {
+            let hub = self.hubs.remove(at);
+            let first = (hub.first - self.max_ports) as usize;
+            for port in &mut self.downstream[first..first + hub.count as usize] {
+                port.status = PortStatus::GONE;
+                port.orphaned = true;
+            }
+            log!(
+                "xHCI: hub on slot {} (port {}) gone; tearing down what was behind ports {}..{}",
+                hub.slot_id,
+                port_idx + 1,
+                hub.first + 1,
+                hub.first + hub.count
+            );
+            self.ports_dirty = true;
+        }
+    }
+
+    /// Where a hub with `count` ports puts its port 1 in the table: over a run
+    /// a departed hub left behind whose machines have all come to rest, or
+    /// after the last port. `None` past 255, which is as far as a `port_idx`
+    /// counts.
+    ///
+    /// Reuse is what keeps a hub replugged all afternoon from growing the
+    /// table by four ports each time; the rest condition is what keeps a new
+    /// hub from being handed a port whose old device is still being torn down.
+    pub(super) fn claim_downstream(&self, count: u8) -> Option<u8> {
+        let at_rest = |at: usize| {
+            let port = &self.ports[self.max_ports as usize + at];
+            self.downstream[at].orphaned
+                && !port.attached()
+                && port.working().is_none()
+                && !port.outstanding()
+        };
+        let mut run = 0;
+        for at in 0..self.downstream.len() {
+            run = if at_rest(at) { run + 1 } else { 0 };
+            if run == count as usize {
+                return Some((self.max_ports as usize + at + 1 - run) as u8);
+            }
+        }
+        let first = self.ports.len();
+        (first + count as usize <= u8::MAX as usize).then_some(first as u8)
+    }
+}
@@ -1,6 +1,10 @@
+pub mod audio;
+mod cdc;
 mod device;
 mod hid;
+mod hub;
 mod legacy;
+pub mod serial;
 pub mod usbd;
 mod wait;
 
@@ -9,8 +13,10 @@ use wait::msc;
 /// The driver's whole surface to the rest of the kernel. Everything that waits
 /// lives under [`wait`] — see its own documentation for why that is a module
 /// boundary and not a type.
-pub use wait::boot::{init, PORT_POLL, PORT_SETTLE_CEILING};
-pub use wait::msc::{storage_flush, storage_read, storage_write};
+pub use wait::boot::{init, resume, suspend, PORT_POLL, PORT_SETTLE_CEILING};
+pub use wait::msc::{storage_discard, storage_flush, storage_read, storage_write};
+#[cfg(feature = "test-actuators")]
+pub use hid::synthetic_pad;
 
 use alloc::vec::Vec;
 use core::num::NonZeroU8;
@@ -28,6 +34,8 @@ use toyos_xhci::Protocols;
 use toyos_xhci::Portsc;
 
 use hid::HidDevice;
+use hub::{Downstream, HubDevice};
+use toyos_xhci::hub::{HubSlot, Place};
 
 // xHCI Capability Register offsets (from BAR0)
 const CAP_CAPLENGTH:  u64 = 0x00; // u8
@@ -94,6 +102,7 @@ const TRB_NORMAL:       u32 = trb_type(1);
 const TRB_SETUP_STAGE:  u32 = trb_type(2);
 const TRB_DATA_STAGE:   u32 = trb_type(3);
 const TRB_STATUS_STAGE: u32 = trb_type(4);
+const TRB_ISOCH:        u32 = trb_type(5);
 const TRB_LINK:         u32 = trb_type(6);
 
 const TRB_ENABLE_SLOT:    u32 = trb_type(9);
@@ -115,8 +124,13 @@ const EVENT_PORT_STATUS_CHANGE: u32 = 34;
 // reading it as one is the classic mass-storage bug, since every SCSI command
 // that under-delivers takes that path.
 const CC_SUCCESS: u32 = 1;
+const CC_TRB_ERROR: u32 = 5;
 const CC_STALL: u32 = 6;
 const CC_SHORT_PACKET: u32 = 13;
+// An isochronous endpoint's two: its ring ran dry, which names no TRB, and a
+// TD the controller could not schedule in its interval, which played nothing.
+const CC_RING_UNDERRUN: u32 = 14;
+const CC_MISSED_SERVICE: u32 = 23;
 
 /// A completion code, named where xHCI 1.2 Table 6-90 names it.
 ///
@@ -563,6 +577,11 @@ pub fn recheck_ports() {
 
 const RING_SIZE: usize = 256; // TRBs per ring (one page = 256 * 16)
 
+/// TRBs in one UAS stream ring. A stream carries one command's status or data
+/// transfer at a time, so a page per stream would be 255 slots of which one is
+/// ever used — and a disk has a dozen streams.
+const STREAM_RING_SIZE: usize = 16;
+
 /// Clear `len` bytes of `dma` at `off`, and answer with the region that was
 /// cleared.
 ///
@@ -630,17 +649,35 @@ impl TrbRing {
     /// run, so recovery is this plus a Set TR Dequeue Pointer naming
     /// [`Self::dequeue`] — the two have to agree or the endpoint resumes on
     /// stale TRBs.
+    ///
+    /// **The ring is as long as `buf`.** Every ring but a UAS stream's is one
+    /// page and [`RING_SIZE`] TRBs; a stream's is [`STREAM_RING_SIZE`], and a
+    /// length taken from the region rather than from a constant is what lets
+    /// the two share this type without a second link-TRB rule.
     fn init(buf: Dma<'static>) -> Self {
-        assert!(buf.size() >= RING_SIZE * core::mem::size_of::<Trb>());
+        let trbs = buf.size() / core::mem::size_of::<Trb>();
+        assert!(trbs >= 2 && buf.size() % core::mem::size_of::<Trb>() == 0);
         zero_dma(buf, 0, buf.size());
         let ring = Self { buf, base_phys: buf.phys(), tail: 0, cycle: true };
         let mut link = Trb::ZERO;
         link.param = buf.phys();
         link.control = TRB_LINK | (1 << 1); // TC (Toggle Cycle)
-        ring.put(RING_SIZE - 1, link);
+        ring.put(trbs - 1, link);
         ring
     }
 
+    /// The same memory as a ring the controller has never seen, which is what
+    /// recovery hands Set TR Dequeue. The region travels with the ring, so no
+    /// caller has to remember where its ring lives to rebuild it.
+    fn rebuilt(&self) -> Self {
+        Self::init(self.buf)
+    }
+
+    /// TRBs in the ring, the link included.
+    fn len(&self) -> usize {
+        self.buf.size() / core::mem::size_of::<Trb>()
+    }
+
     /// One TRB at ring index `at`.
     ///
     /// **The ring's one writer.** `init` and both arms of `enqueue` each had
@@ -684,7 +721,7 @@ impl TrbRing {
         self.put(self.tail as usize, trb);
         self.tail += 1;
 
-        if self.tail as usize >= RING_SIZE - 1 {
+        if self.tail as usize >= self.len() - 1 {
             let mut link = Trb::ZERO;
             link.param = self.base_phys;
             link.control = TRB_LINK | (1 << 1); // TC (Toggle Cycle)
@@ -734,7 +771,8 @@ const SHARED_SIZE: usize   = 6 * PAGE;
 const DEV_INT_RING: usize = 0;                 // 256 TRBs, exactly one page
 const DEV_EP0_RING: usize = PAGE;              // likewise
 const DEV_OUT_CTX: usize  = 2 * PAGE;          // 32 contexts, 2 KiB at ctx_size 64
-const DEV_REPORT: usize   = 2 * PAGE + 0x800;  // 8 B, the largest boot report
+const DEV_REPORT: usize   = 2 * PAGE + 0x800;  // 64 B, toyos_hid::MAX_REPORT
+const DEV_HUB_STATUS: usize = 2 * PAGE + 0x840; // 4 B, a hub's GET_STATUS(port)
 const DEV_STRIDE: usize   = 3 * PAGE;
 
 // One of these per mass-storage device, and separate from the device block
@@ -758,6 +796,35 @@ const MSC_DATA: usize      = 8 * PAGE;
 const MSC_DATA_LEN: usize  = 8 * PAGE;
 const MSC_STRIDE: usize    = 16 * PAGE;
 
+// A UAS disk lays the same block out differently, because it has four pipes
+// where Bulk-Only has two and a ring per stream on three of them. Same data
+// window, same scratch — INQUIRY and READ CAPACITY land where they always did —
+// and everything else in the pages Bulk-Only leaves unused.
+const UAS_COMMAND_RING: usize = 0;
+/// Status, data-in and data-out, [`UAS_RINGS`] rings of [`STREAM_RING_SIZE`]
+/// TRBs each: 3840 bytes of the page.
+const UAS_STREAM_RINGS: usize = PAGE;
+/// One IU slot per tag, past the end of `MSC_SCRATCH`.
+const UAS_IU: usize = 2 * PAGE + 0x100;
+const UAS_IU_STRIDE: usize = 0x20;
+/// One status buffer per stream, each holding the longest IU there is.
+const UAS_STATUS: usize = 3 * PAGE;
+const UAS_STATUS_STRIDE: usize = 0x200;
+/// A Primary Stream Context Array per streamed pipe, of [`UAS_MAX_ENTRIES`].
+const UAS_STREAM_CTX: usize = 4 * PAGE;
+const UAS_STREAM_CTX_STRIDE: usize = 0x80;
+
+/// Commands a UAS disk has in flight at once. Four, because the data window is
+/// what they share: each takes a quarter of it, and a quarter is two blocks.
+const UAS_DEPTH: usize = 4;
+/// Rings per streamed pipe: one per tag, and one for the task management tag
+/// that follows them.
+const UAS_RINGS: usize = UAS_DEPTH + 1;
+/// Entries in a Primary Stream Context Array this driver builds, entry 0 — the
+/// reserved one — included. Eight is the smallest power of two past
+/// [`UAS_RINGS`].
+const UAS_MAX_ENTRIES: usize = 8;
+
 /// Mass-storage devices the pool has blocks for. Two, because that is what a
 /// machine booting off a USB stick with a second one plugged in has, and each
 /// costs 64 KiB whether or not it is used. A third stick is refused by name
@@ -902,10 +969,15 @@ struct MscBlock {
     /// refused after its endpoints were configured keeps this `None` and stays
     /// claimed, which is the whole reason `port` is the thing that says taken.
     disk: Option<Disk>,
+    /// The disk this block held when the machine went to sleep, from the
+    /// resume until its port enumerates again. The block stays claimed for
+    /// that port meanwhile, so the disk that comes back can be given back its
+    /// number — see [`XhciController::claim_msc_block`].
+    slept: Option<Disk>,
 }
 
 impl MscBlock {
-    const FREE: Self = Self { port: None, disk: None };
+    const FREE: Self = Self { port: None, disk: None, slept: None };
 }
 
 /// A disk this controller brought up, and the number the machine knows it by.
@@ -971,6 +1043,11 @@ pub struct XhciController {
     context_size: usize, // 32 or 64
     layout: Layout,
 
+    /// HCCPARAMS1's MaxPSASize: the controller takes Primary Stream Context
+    /// Arrays of up to `2^(max_psa+1)` entries, and zero means it has no
+    /// streams at all — which decides whether a UAS disk can be driven as one.
+    max_psa: u8,
+
     /// This controller's DMA, and this controller's only. It used to be one
     /// static for the driver, which was sound only while the machine had one
     /// controller: every offset in `Layout` is relative to a pool base, so two
@@ -1020,6 +1097,33 @@ pub struct XhciController {
     /// array would be 255 entries on a controller with five.
     ports: Vec<PortState>,
 
+    /// The hubs bound on this controller, each with the run of [`Self::ports`]
+    /// its downstream ports were given.
+    hubs: Vec<HubDevice>,
+
+    /// The USB Ethernet adapter, if it is on this controller. Its rings and
+    /// buffers are `cdc`'s, which netd reaches without this controller's lock;
+    /// this is what a transfer event and a teardown are matched against.
+    net: Option<cdc::NetDevice>,
+    /// The USB serial console, if it is on this controller, on the same terms:
+    /// its FIFO and rings are `serial`'s, which every console write reaches
+    /// without this controller's lock.
+    serial: Option<serial::SerialDevice>,
+    /// The USB DAC, if it is on this controller, on the same terms: its rings
+    /// and its PCM ring are `audio`'s, which soundd's writes reach without this
+    /// controller's lock.
+    audio: Option<audio::AudioDevice>,
+
+    /// Every hub port this controller has numbered, at `port_idx - max_ports`:
+    /// the hub it is on, its number there, and the hub's last answer about it.
+    ///
+    /// **A hub's ports are entries in [`Self::ports`] past the root ports**,
+    /// and that is what lets everything keyed by a port index — the machine,
+    /// the slot it records, the blocks a disk claims, the teardown — serve
+    /// a device behind a hub without knowing it is one. Only the register
+    /// read and write tell the two kinds apart.
+    downstream: Vec<Downstream>,
+
     /// What each port register speaks, out of the controller.s own Supported
     /// Protocol capabilities. The boot scan reads it directly; the hot-plug
     /// machine was given its port.s copy at bring-up.
@@ -1087,7 +1191,13 @@ impl XhciController {
 
     /// Every read of a port register in this driver, so that what the connect
     /// settle sees and what `init_device` acts on cannot disagree.
+    ///
+    /// A hub's port reads as the PORTSC word its last GET_STATUS answer
+    /// describes — see [`toyos_xhci::hub`].
     fn read_portsc(&self, port_idx: u8) -> Portsc {
+        if let Some(port) = self.downstream_port(port_idx) {
+            return port.status.portsc(port.superspeed);
+        }
         Portsc::from_raw(self.read_portsc_raw(port_idx))
     }
 
@@ -1122,7 +1232,13 @@ impl XhciController {
     /// type can only be built from a neutral base and offers no way to set PED,
     /// so the two writes that disable a port the driver was enabling are
     /// unreachable rather than asserted against.
+    ///
+    /// A hub's port is written by asking the hub, which waits — see
+    /// [`wait::hub`].
     fn write_portsc(&mut self, port_idx: u8, write: toyos_xhci::portsc::Write) {
+        if port_idx >= self.max_ports {
+            return self.write_downstream(port_idx, write);
+        }
         let value = write.raw();
         if crate::actuator::xhci_portsc_rw1c() {
             let word = port_idx as usize / 64;
@@ -1158,6 +1274,36 @@ impl XhciController {
             .map(|at| at as u8)
     }
 
+    /// Where the device on `port_idx` sits, as its slot context says it: a
+    /// root port, or a hub's port with the Route String and the TT that reach
+    /// it.
+    fn place(&self, port_idx: u8) -> Place {
+        match self.downstream_port(port_idx) {
+            Some(port) => port.place,
+            None => Place::root(port_idx + 1),
+        }
+    }
+
+    /// Slot context dwords 0 to 2 of the input context `ctx`, for the device
+    /// on `port_idx` whose last endpoint context is `entries`. Every command
+    /// that names a slot context writes it here, so Address Device and
+    /// Configure Endpoint cannot disagree about where a device is.
+    fn write_slot_context(
+        &self,
+        ctx: Dma<'static>,
+        port_idx: u8,
+        speed: u8,
+        entries: u8,
+        hub: Option<HubSlot>,
+    ) {
+        let dwords = self.place(port_idx).slot_context(speed, entries, hub);
+        for (dword, val) in dwords.into_iter().enumerate() {
+            self.write_ctx32(ctx, 1, dword, val);
+        }
+    }
+
+    /// The root-hub ports with something in them. Only the root ports: this
+    /// is the boot settle's question, asked before any hub has been bound.
     fn connected_ports(&self) -> PortMask {
         let mut mask = [0u64; 4];
         for p in 0..self.max_ports {
@@ -1176,10 +1322,30 @@ impl XhciController {
     /// exists for, and the one `ctrl.layout.msc(ctrl.storage.len())` did not
     /// have. `None` when the pool is out; nothing is spent then, because
     /// nothing was handed out.
-    fn claim_msc_block(&mut self, port_idx: u8) -> Option<(usize, usize)> {
-        let index = self.msc.iter().position(|block| block.port.is_none())?;
+    ///
+    /// After S3 the block a disk on this port had before the sleep comes first,
+    /// and the disk it held comes with it: that is what `bind` compares the
+    /// new device against before giving it the old number.
+    fn claim_msc_block(&mut self, port_idx: u8) -> Option<(usize, usize, Option<Disk>)> {
+        let slept = |block: &MscBlock| block.port == Some(port_idx) && block.slept.is_some();
+        let index = match self.msc.iter().position(slept) {
+            Some(index) => index,
+            None => self.msc.iter().position(|block| block.port.is_none())?,
+        };
         self.msc[index].port = Some(port_idx);
-        Some((index, self.layout.msc(index)))
+        Some((index, self.layout.msc(index), self.msc[index].slept.take()))
+    }
+
+    /// Give back every block whose port did not enumerate again after S3, and
+    /// say which disks went with them.
+    fn forget_slept(&mut self) {
+        for block in self.msc.iter_mut() {
+            if let Some(disk) = block.slept {
+                log!("usb-storage: disk {} did not come back from the sleep; it is offline",
+                    disk.index);
+                *block = MscBlock::FREE;
+            }
+        }
     }
 
     /// How many blocks are spoken for, for the line that refuses the disk the
@@ -1315,6 +1481,21 @@ impl XhciController {
         {
             return;
         }
+        if let Some(at) = self.hubs.iter().position(|h| h.slot_id == slot) {
+            return self.hub_changed(at, code);
+        }
+        if self.net.is_some_and(|n| n.slot_id == slot) {
+            let dci = ((event.control >> 16) & 0x1F) as u8;
+            return cdc::completed(dci, event.param & !0xF, code, event.status & 0xFF_FFFF);
+        }
+        if self.serial.is_some_and(|s| s.slot_id == slot) {
+            let dci = ((event.control >> 16) & 0x1F) as u8;
+            return serial::completed(dci, event.param & !0xF, code, event.status & 0xFF_FFFF);
+        }
+        if self.audio.is_some_and(|a| a.slot_id == slot) {
+            let dci = ((event.control >> 16) & 0x1F) as u8;
+            return audio::completed(dci, event.param & !0xF, code);
+        }
         let Some(at) = self.devices.iter().position(|d| d.slot_id == slot) else {
             return;
         };
@@ -1323,7 +1504,7 @@ impl XhciController {
         let dev = &mut self.devices[at];
         if code == CC_SUCCESS || code == CC_SHORT_PACKET {
             dev.failures = 0;
-            dev.dispatch_report();
+            dev.dispatch_report(event.status & 0xFF_FFFF);
             dev.requeue(&self.db_base);
             return;
         }
@@ -1432,7 +1613,7 @@ impl XhciController {
         };
         let slot = self.slot(slot_id);
         let dev = &mut self.devices[at];
-        let (dci, ep_addr, ring_at) = (dev.int_ep_dci, dev.ep_addr, dev.block + DEV_INT_RING);
+        let (dci, ep_addr) = (dev.int_ep_dci, dev.ep_addr);
         match act {
             Act::Running => {
                 dev.requeue(&self.db_base);
@@ -1445,7 +1626,7 @@ impl XhciController {
                 // re-enter and find a ring that is neither the old one nor the
                 // new one.
                 let mut ring = dev.int_ring;
-                let trb = self.recovery_trb(cmd, slot_id, dci, &mut ring, ring_at);
+                let trb = self.recovery_trb(cmd, slot_id, dci, 0, &mut ring);
                 self.devices[at].int_ring = ring;
                 let on = Await::Command { trb: self.submit_command(trb) };
                 let what = What::Recovering { slot_id, seq, issued: cmd.name() };
@@ -1533,21 +1714,30 @@ impl XhciController {
     /// The two have to happen together or they disagree: the TRBs behind the
     /// transfer that broke belong to nobody, and Set TR Dequeue is the only
     /// thing that tells the controller so.
+    ///
+    /// `stream` is 0 for an endpoint without streams. On one with them, Reset
+    /// and Stop act on the whole endpoint and Set TR Dequeue on one stream's
+    /// ring, named in the TRB with SCT 1 — a primary ring, which is all this
+    /// driver builds (xHCI 1.2 §6.4.3.9).
     fn recovery_trb(
         &self,
         cmd: recovery::Command,
         slot_id: u8,
         dci: u8,
+        stream: u16,
         ring: &mut TrbRing,
-        ring_at: usize,
     ) -> Trb {
         let mut trb = Trb::ZERO;
         let kind = match cmd {
             recovery::Command::ResetEndpoint => TRB_RESET_ENDPOINT,
             recovery::Command::StopEndpoint => TRB_STOP_ENDPOINT,
             recovery::Command::SetDequeue => {
-                *ring = TrbRing::init(self.dma().subview(ring_at, PAGE));
+                *ring = ring.rebuilt();
                 trb.param = ring.dequeue();
+                if stream != 0 {
+                    trb.param |= 1 << 1;
+                    trb.status = (stream as u32) << 16;
+                }
                 TRB_SET_TR_DEQUEUE
             }
         };
@@ -1599,9 +1789,12 @@ impl XhciController {
     /// `wait::boot::configure` the boot path runs; what this removes from the
     /// blocking part is the debounce and the port reset, which on the T14 are
     /// 100 ms and 55 ms against roughly 14 ms for everything else.
+    ///
+    /// A hub's ports are stepped in the same loop: they are entries of
+    /// [`Self::ports`] like any other.
     fn service_ports(&mut self) -> Option<u64> {
         let now = crate::clock::nanos_since_boot();
-        (0..self.max_ports)
+        (0..self.ports.len() as u8)
             .filter_map(|p| self.service_port(p, now))
             .min()
     }
@@ -1774,8 +1967,16 @@ impl XhciController {
                     "xHCI: USB pointer on slot {} unplugged from port {}, source {} released",
                     dev.slot_id, port_idx + 1, source.id()
                 ),
+                hid::HidRole::Pad(pad, _) => log!(
+                    "xHCI: USB gamepad on slot {} unplugged from port {}, pad {} released",
+                    dev.slot_id, port_idx + 1, pad.id()
+                ),
             }
         }
+        self.orphan_hub(port_idx);
+        self.unbind_net(port_idx);
+        self.unbind_serial(port_idx);
+        self.unbind_audio(port_idx);
         let Some(slot) = self.ports[port_idx as usize].take_slot() else {
             self.release_blocks(port_idx);
             return true;
@@ -1886,17 +2087,23 @@ impl XhciController {
         self.advance_outstanding();
         self.recover_endpoints();
 
-        // Nothing below reads the event ring, which is what makes one advance
-        // enough: every step `service_ports` takes is a submit, so no answer can
-        // arrive inside it and none can be left behind it. The last thing that
-        // drained on its own behalf was the enumeration, and the only one left
-        // is the disk bring-up behind `msc::bind` — which runs above, so a port
-        // change it consumed is already in `ports_dirty` when this reads it.
-        let mut wake_at = None;
+        // A hub's ports read its last answer, so the answers its interrupt
+        // endpoint said are stale are asked for again before anything is
+        // decided from them.
+        let mut wake_at = self.refresh_hubs();
+
+        // **Everything from here may read the event ring**, and so may have
+        // recorded an answer nobody has acted on: asking a hub for its port
+        // status, and writing a hub's port, are control transfers on the hub's
+        // own EP0 and wait for them like `msc::bind` does. Every other step
+        // `service_ports` takes is a submit. Hence the second advance — an
+        // answer drained in here and left recorded is a port inside an effect
+        // until the next interrupt, which for an Enable Slot is never.
         if self.ports_dirty || self.ports.iter().any(PortState::outstanding) {
             self.ports_dirty = false;
-            wake_at = self.service_ports();
+            wake_at = earliest(wake_at, self.service_ports());
         }
+        self.advance_outstanding();
         earliest(wake_at, self.outstanding.wake_at())
     }
 
@@ -1952,9 +2159,31 @@ impl XhciController {
         self.rt_base.write_u32(IR0_IMAN, 3); // clear IP (W1C) + keep IE
     }
 
+    /// Whether this controller takes streams at all, which a UAS disk at
+    /// SuperSpeed cannot be driven without.
+    fn streams(&self) -> bool {
+        self.max_psa != 0
+    }
+
+    /// The largest Primary Stream Context Array this controller takes, in
+    /// entries.
+    fn stream_entries(&self) -> usize {
+        match self.max_psa {
+            0 => 0,
+            psa => 1usize << (psa as u32 + 1).min(15),
+        }
+    }
+
     fn ring_doorbell(&self, slot: u8, dci: u8) {
+        self.ring_stream_doorbell(slot, dci, 0);
+    }
+
+    /// The doorbell for one stream of an endpoint that has them: DB Stream ID
+    /// is the register's high half (xHCI 1.2 §5.6), and 0 there is the
+    /// doorbell every other endpoint rings.
+    fn ring_stream_doorbell(&self, slot: u8, dci: u8, stream: u16) {
         fence(Ordering::Release);
-        self.db_base.write_u32(slot as u64 * 4, dci as u32);
+        self.db_base.write_u32(slot as u64 * 4, dci as u32 | ((stream as u32) << 16));
     }
 
 }
@@ -1967,6 +2196,34 @@ impl XhciController {
 /// driver that kept one controller reported that the T14 had no USB input.
 static XHCI: Lock<Vec<XhciController>> = Lock::new(Vec::new());
 
+/// Why this machine's USB cannot be brought back from S3, or `None` when it
+/// can.
+///
+/// Resume resets every controller and enumerates its ports again, as boot
+/// does. That brings back what the boot scan alone can bring up — keyboards,
+/// pointers, pads and Bulk-Only disks, which keep their numbers — and nothing
+/// whose state lives somewhere this driver does not own.
+pub fn suspend_refusal() -> Option<&'static str> {
+    for ctrl in XHCI.lock().iter() {
+        if !ctrl.hubs.is_empty() {
+            return Some("a USB hub is bound, and its ports are enumerated only by hotplug");
+        }
+        if ctrl.net.is_some() {
+            return Some("a USB network adapter is bound, and netd holds its buffers");
+        }
+        if ctrl.serial.is_some() {
+            return Some("a USB serial console is bound, and the console holds its FIFO");
+        }
+        if ctrl.audio.is_some() {
+            return Some("a USB DAC is bound, and soundd holds its PCM ring");
+        }
+        if ctrl.msc.iter().any(|block| block.disk.is_some_and(|disk| disk.dev.uas())) {
+            return Some("a UAS disk is bound, and only Bulk-Only disks are brought back");
+        }
+    }
+    None
+}
+
 /// Process xHCI events if this CPU has an unserviced interrupt record, or if a
 /// port's state machine is due to be stepped.
 ///
@@ -2039,7 +2296,7 @@ pub fn poll_if_pending() {
 }
 
 /// Everything above the controller needs to know about one bound disk.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct StorageGeometry {
     /// What the device itself addresses in, straight off READ CAPACITY.
     pub logical_block_bytes: u32,
@@ -0,0 +1,446 @@
+//! USB serial adapters — CDC-ACM, FTDI, CP210x and CH34x — as a console
+//! backend beside the 16550 and the virtio-console in [`crate::drivers::serial`].
+//!
+//! **This is for the machine with no serial port**, which is most laptops this
+//! tree runs on: the T14 has no SuperIO, so `uart_present` is false, and a
+//! virtio-console is a hypervisor's. A USB serial cable to another machine is
+//! then the only channel a log line or a shell prompt can leave by. What the
+//! descriptors say and which control requests set the line up is
+//! `toyos_xhci::serial`'s, tested on the host; this file moves bytes between a
+//! FIFO and the bulk pipes.
+//!
+//! **A writer never waits for the controller.** [`write_bytes_locked`] is
+//! called under [`crate::drivers::serial::BackendGuard`] — interrupts off, one
+//! rendered record at a time — and what frees room for more bytes is a transfer
+//! event, which only the xHCI poll reads. So a write copies into a FIFO in the
+//! adapter's own DMA memory and starts a transfer if none is in flight; the
+//! completion starts the next one over whatever accumulated meanwhile. One
+//! transfer in flight is one event outstanding at a time, which is all an event
+//! ring shared with every other device on the controller can be asked to hold
+//! for a console. A unit that does not fit whole is dropped whole and counted,
+//! so a line is never cut, and the count is said once room comes back.
+//!
+//! **Nothing here logs while [`ADAPTER`] is held**, and nothing here takes the
+//! controller's lock: the log path ends in this file, so a line said under the
+//! lock would come back to it. Writers take the lock with a bounded
+//! `try_lock`, because one may be an interrupt on the CPU that holds it.
+//!
+//! One adapter per machine, as there is one console. The memory outlives it,
+//! as `cdc`'s does, so a cable pulled and plugged back costs no allocation.
+
+use core::sync::atomic::{fence, AtomicBool, Ordering};
+
+use toyos_xhci::serial::{self as class, Kind};
+
+use super::device::Endpoint;
+use super::{Completion, Mmio, Trb, TrbRing, XhciController, PAGE, TRB_NORMAL};
+use super::{CC_SHORT_PACKET, CC_SUCCESS, OFF_INPUT_CTX};
+use crate::drivers::DmaPool;
+use crate::log;
+use crate::mm::Dma;
+use crate::sync::Lock;
+
+/// What the configuration descriptor offered: the interface class requests go
+/// to, and the bulk pair.
+#[derive(Clone, Copy)]
+pub(super) struct SerialInterface {
+    pub(super) kind: Kind,
+    /// The ACM communication interface, or the vendor interface itself.
+    pub(super) control_iface: u8,
+    pub(super) in_ep: Endpoint,
+    pub(super) out_ep: Endpoint,
+}
+
+/// The two transfer rings Configure Endpoint named, carried to the bind.
+#[derive(Clone, Copy)]
+pub(super) struct SerialRings {
+    in_ring: TrbRing,
+    out_ring: TrbRing,
+}
+
+/// Which device on a controller is the adapter: what a transfer event and a
+/// teardown are matched against.
+#[derive(Clone, Copy)]
+pub(super) struct SerialDevice {
+    pub(super) slot_id: u8,
+    pub(super) port_idx: u8,
+}
+
+/// Receive transfers the controller holds at once, each one buffer long. 512 is
+/// a high-speed bulk packet, the largest a serial adapter has, so a transfer
+/// ends at every packet and a byte typed is a byte delivered.
+const IN_TRANSFERS: usize = 4;
+const IN_BUF: usize = 512;
+/// Console output accepted and not yet sent. The size is the whole-boot replay
+/// [`crate::log::console::backend_changed`] writes the moment the adapter binds,
+/// in one burst no completion can interrupt.
+const FIFO: usize = 1 << 20;
+/// A TRB's buffer may not cross a 64 KiB boundary (xHCI 1.2 §6.4.1), and its
+/// length field is 17 bits, so this is both limits at once.
+const TRB_SPAN: usize = 64 * 1024;
+/// Bytes received and not yet read by the console's reader.
+const RX_FIFO: usize = 4096;
+
+const OFF_OUT_RING: usize = 0;
+const OFF_IN_RING: usize = PAGE;
+const OFF_IN_BUFS: usize = 2 * PAGE;
+const OFF_FIFO: usize = TRB_SPAN;
+const PRIVATE_SIZE: usize = OFF_FIFO + FIFO;
+
+const _: () = assert!(OFF_IN_BUFS + IN_TRANSFERS * IN_BUF <= OFF_FIFO);
+const _: () = assert!(OFF_FIFO.is_multiple_of(TRB_SPAN) && FIFO.is_multiple_of(TRB_SPAN));
+const _: () = assert!(PRIVATE_SIZE <= crate::mm::PAGE_2M as usize);
+
+/// How long a writer spins for [`ADAPTER`] before dropping its unit. The holder
+/// copies at most one unit or one receive buffer, so a holder on another CPU
+/// is through long before this; a holder on *this* CPU, under an interrupt that
+/// is now logging, never will be.
+const WRITE_SPIN_LIMIT: usize = 100_000;
+
+static MEMORY: Lock<Option<Dma<'static>>> = Lock::new(None);
+
+/// The bound adapter. Reached from the controller's event ring, under `XHCI`,
+/// and from every console write and read, under the backend guard; it takes
+/// nothing else while it is held.
+static ADAPTER: Lock<Option<Adapter>> = Lock::new(None);
+
+/// Whether [`ADAPTER`] holds one, readable without the lock, because
+/// `serial::backend` asks on every write.
+static READY: AtomicBool = AtomicBool::new(false);
+
+fn memory() -> Dma<'static> {
+    *MEMORY.lock().get_or_insert_with(|| DmaPool::alloc(PRIVATE_SIZE).leak())
+}
+
+/// What a completion changed that has to be said once the lock is dropped.
+enum News {
+    Nothing,
+    /// The bulk OUT endpoint stopped, and with it the console.
+    OutStopped(u32),
+    InStopped(u32),
+}
+
+struct Adapter {
+    slot_id: u8,
+    db: Mmio,
+    kind: Kind,
+    in_dci: u8,
+    out_dci: u8,
+    /// The bulk IN packet size, which is where an FTDI chip repeats its status
+    /// prefix.
+    in_packet: u16,
+    in_ring: TrbRing,
+    out_ring: TrbRing,
+    memory: Dma<'static>,
+    /// Where each receive buffer's TRB landed, and which is owed next, as in
+    /// `cdc`.
+    in_trbs: [u64; IN_TRANSFERS],
+    in_next: usize,
+    /// Bytes ever accepted into the FIFO and ever sent out of it. Their
+    /// difference is what is in it; each modulo [`FIFO`] is where.
+    written: u64,
+    sent: u64,
+    /// The transfer in flight: its TRB, and how many bytes it carries.
+    in_flight: Option<(u64, usize)>,
+    out_halted: bool,
+    in_halted: bool,
+    /// Received bytes, oldest at `rx_head`.
+    rx: [u8; RX_FIFO],
+    rx_head: usize,
+    rx_n: usize,
+    /// Output units refused for want of room, and bytes received with nowhere
+    /// to go; each reported on change.
+    dropped: u32,
+    reported_drops: u32,
+    rx_dropped: u32,
+    reported_rx_drops: u32,
+}
+
+impl Adapter {
+    fn doorbell(&self, dci: u8) {
+        fence(Ordering::Release);
+        self.db.write_u32(self.slot_id as u64 * 4, dci as u32);
+    }
+
+    /// Give the controller receive buffer `buf` to fill.
+    fn post_in(&mut self, buf: usize) {
+        let mut trb = Trb::ZERO;
+        trb.param = self.memory.phys() + (OFF_IN_BUFS + buf * IN_BUF) as u64;
+        trb.status = IN_BUF as u32;
+        trb.control = TRB_NORMAL | (1 << 2) | (1 << 5); // ISP + IOC
+        self.in_trbs[buf] = self.in_ring.enqueue(trb);
+        self.doorbell(self.in_dci);
+    }
+
+    /// Accept one unit of output whole, or refuse it whole.
+    fn write(&mut self, bytes: &[u8]) {
+        let queued = (self.written - self.sent) as usize;
+        if self.out_halted || bytes.len() > FIFO - queued {
+            self.dropped = self.dropped.wrapping_add(1);
+            return;
+        }
+        let at = (self.written % FIFO as u64) as usize;
+        let first = bytes.len().min(FIFO - at);
+        self.memory.copy_from(OFF_FIFO + at, &bytes[..first]);
+        self.memory.copy_from(OFF_FIFO, &bytes[first..]);
+        self.written += bytes.len() as u64;
+        self.kick();
+    }
+
+    /// Start a transfer over what the FIFO holds, if none is in flight. One
+    /// TRB, stopping at the FIFO's wrap and at the next 64 KiB boundary; the
+    /// rest goes when it completes.
+    fn kick(&mut self) {
+        if self.in_flight.is_some() || self.out_halted || self.sent == self.written {
+            return;
+        }
+        let at = (self.sent % FIFO as u64) as usize;
+        let len = ((self.written - self.sent) as usize)
+            .min(FIFO - at)
+            .min(TRB_SPAN - at % TRB_SPAN);
+        let mut trb = Trb::ZERO;
+        trb.param = self.memory.phys() + (OFF_FIFO + at) as u64;
+        trb.status = len as u32;
+        trb.control = TRB_NORMAL | (1 << 5); // IOC
+        self.in_flight = Some((self.out_ring.enqueue(trb), len));
+        self.doorbell(self.out_dci);
+    }
+
+    fn completed(&mut self, dci: u8, trb: u64, code: u32, residue: u32) -> News {
+        let ok = code == CC_SUCCESS || code == CC_SHORT_PACKET;
+        if dci == self.out_dci {
+            let Some((_, len)) = self.in_flight.filter(|&(at, _)| at == trb) else {
+                return News::Nothing;
+            };
+            self.in_flight = None;
+            if !ok {
+                // What was in flight is gone with the endpoint; the FIFO is
+                // emptied so `write` refuses from here on rather than queueing
+                // behind a transfer that will never finish.
+                self.out_halted = true;
+                self.sent = self.written;
+                return News::OutStopped(code);
+            }
+            self.sent += len as u64;
+            self.kick();
+            News::Nothing
+        } else if dci == self.in_dci {
+            if trb != self.in_trbs[self.in_next] || self.in_halted {
+                return News::Nothing;
+            }
+            let buf = self.in_next;
+            self.in_next = (buf + 1) % IN_TRANSFERS;
+            if !ok {
+                self.in_halted = true;
+                return News::InStopped(code);
+            }
+            let len = IN_BUF.saturating_sub(residue as usize);
+            self.received(buf, len);
+            self.post_in(buf);
+            News::Nothing
+        } else {
+            News::Nothing
+        }
+    }
+
+    /// Move one receive buffer's bytes into the console's FIFO, without the
+    /// status an FTDI chip puts at the head of every packet.
+    fn received(&mut self, buf: usize, len: usize) {
+        let mut transfer = [0u8; IN_BUF];
+        self.memory.copy_to(OFF_IN_BUFS + buf * IN_BUF, &mut transfer[..len]);
+        let mut line = [0u8; IN_BUF];
+        let n = if self.kind == Kind::Ftdi {
+            class::ftdi_data(&transfer[..len], self.in_packet, &mut line)
+        } else {
+            line[..len].copy_from_slice(&transfer[..len]);
+            len
+        };
+        for &b in &line[..n] {
+            if self.rx_n == RX_FIFO {
+                self.rx_dropped = self.rx_dropped.wrapping_add(1);
+                continue;
+            }
+            self.rx[(self.rx_head + self.rx_n) % RX_FIFO] = b;
+            self.rx_n += 1;
+        }
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        if self.rx_n == 0 {
+            return None;
+        }
+        let b = self.rx[self.rx_head];
+        self.rx_head = (self.rx_head + 1) % RX_FIFO;
+        self.rx_n -= 1;
+        Some(b)
+    }
+
+    /// The drops not yet said, as (units, bytes received), and mark them said.
+    fn unreported(&mut self) -> (u32, u32) {
+        let out = self.dropped.wrapping_sub(self.reported_drops);
+        let rx = self.rx_dropped.wrapping_sub(self.reported_rx_drops);
+        self.reported_drops = self.dropped;
+        self.reported_rx_drops = self.rx_dropped;
+        (out, rx)
+    }
+}
+
+/// Whether an adapter is bound, which is what `serial::backend` asks.
+pub fn is_ready() -> bool {
+    READY.load(Ordering::Acquire)
+}
+
+/// One unit of console output — a rendered record, a piece of a userland
+/// write — to the adapter. The caller holds the backend guard.
+pub fn write_bytes_locked(bytes: &[u8]) {
+    for _ in 0..WRITE_SPIN_LIMIT {
+        if let Some(mut adapter) = ADAPTER.try_lock() {
+            if let Some(adapter) = adapter.as_mut() {
+                adapter.write(bytes);
+            }
+            return;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+pub fn has_data_locked() -> bool {
+    ADAPTER.try_lock().is_some_and(|a| a.as_ref().is_some_and(|a| a.rx_n > 0))
+}
+
+pub fn try_read_byte_locked() -> Option<u8> {
+    ADAPTER.try_lock()?.as_mut()?.read_byte()
+}
+
+/// Build the input context for the adapter's bulk pair and the rings they run
+/// on, or `None` where the machine has its USB console already.
+pub(super) fn prepare(
+    ctrl: &mut XhciController,
+    slot_id: u8,
+    speed: u8,
+    port_idx: u8,
+    info: &SerialInterface,
+) -> Option<SerialRings> {
+    if is_ready() {
+        log!("usb-serial: slot {slot_id} is a serial adapter and the machine has its USB \
+             console; port {} is not bound", port_idx + 1);
+        return None;
+    }
+    let memory = memory();
+    let rings = SerialRings {
+        out_ring: TrbRing::init(memory.subview(OFF_OUT_RING, PAGE)),
+        in_ring: TrbRing::init(memory.subview(OFF_IN_RING, PAGE)),
+    };
+
+    let dma = ctrl.dma();
+    let input_ctx = super::zero_dma(dma, OFF_INPUT_CTX, PAGE);
+    // EP Type 2 is Bulk Out and 6 Bulk In, as in `cdc::prepare`.
+    let endpoints = [
+        (&info.out_ep, 2u32, rings.out_ring.dequeue()),
+        (&info.in_ep, 6, rings.in_ring.dequeue()),
+    ];
+    let added = endpoints.iter().fold(1u32, |mask, (ep, ..)| mask | (1u32 << ep.dci()));
+    ctrl.write_ctx32(input_ctx, 0, 1, added);
+    let max_dci = info.out_ep.dci().max(info.in_ep.dci());
+    ctrl.write_slot_context(input_ctx, port_idx, speed, max_dci, None);
+
+    for (ep, ep_type, dequeue) in endpoints {
+        let ctx = ep.dci() as usize + 1;
+        ctrl.write_ctx32(input_ctx, ctx, 0, 0);
+        ctrl.write_ctx32(
+            input_ctx,
+            ctx,
+            1,
+            (3 << 1) | (ep_type << 3) | ((ep.max_burst as u32) << 8) | ((ep.max_packet as u32) << 16),
+        );
+        ctrl.write_ctx32(input_ctx, ctx, 2, dequeue as u32);
+        ctrl.write_ctx32(input_ctx, ctx, 3, (dequeue >> 32) as u32);
+        ctrl.write_ctx32(input_ctx, ctx, 4, ep.max_packet as u32);
+    }
+    Some(rings)
+}
+
+/// Start receiving, and make the adapter the console.
+pub(super) fn bind(
+    ctrl: &mut XhciController,
+    slot_id: u8,
+    port_idx: u8,
+    info: &SerialInterface,
+    rings: SerialRings,
+) {
+    let mut adapter = Adapter {
+        slot_id,
+        db: ctrl.db_base,
+        kind: info.kind,
+        in_dci: info.in_ep.dci(),
+        out_dci: info.out_ep.dci(),
+        in_packet: info.in_ep.max_packet,
+        in_ring: rings.in_ring,
+        out_ring: rings.out_ring,
+        memory: memory(),
+        in_trbs: [0; IN_TRANSFERS],
+        in_next: 0,
+        written: 0,
+        sent: 0,
+        in_flight: None,
+        out_halted: false,
+        in_halted: false,
+        rx: [0; RX_FIFO],
+        rx_head: 0,
+        rx_n: 0,
+        dropped: 0,
+        reported_drops: 0,
+        rx_dropped: 0,
+        reported_rx_drops: 0,
+    };
+    for buf in 0..IN_TRANSFERS {
+        adapter.post_in(buf);
+    }
+    *ADAPTER.lock() = Some(adapter);
+    ctrl.serial = Some(SerialDevice { slot_id, port_idx });
+
+    log!("usb-serial: {} adapter on slot {slot_id} is the console, {} 8N1",
+        super::device::serial_kind(info.kind), class::BAUD);
+    READY.store(true, Ordering::Release);
+    // Outside `ADAPTER`: this replays the boot into the adapter, through
+    // `write_bytes_locked`.
+    crate::drivers::serial::console_changed();
+}
+
+/// A transfer event on the adapter's slot.
+pub(super) fn completed(dci: u8, trb: u64, code: u32, residue: u32) {
+    let (news, (out, rx)) = match ADAPTER.lock().as_mut() {
+        Some(adapter) => (adapter.completed(dci, trb, code, residue), adapter.unreported()),
+        None => return,
+    };
+    // Reported on change rather than per unit, as `cdc` reports its drops: a
+    // boot replay that overran the FIFO costs one line.
+    if out != 0 {
+        log!("usb-serial: dropped {out} write(s) of console output, {FIFO} B were already \
+             waiting for the adapter");
+    }
+    if rx != 0 {
+        log!("usb-serial: dropped {rx} received byte(s), nothing read the console");
+    }
+    match news {
+        News::Nothing => {}
+        News::OutStopped(code) => log!("usb-serial: sending stopped ({}); the console is silent \
+             until the adapter is replugged", Completion(code)),
+        News::InStopped(code) => log!("usb-serial: receiving stopped ({}); nothing typed arrives \
+             until the adapter is replugged", Completion(code)),
+    }
+}
+
+impl XhciController {
+    /// The device on `port_idx` is being torn down; if it is the adapter, the
+    /// console goes back to whatever the machine had before it.
+    pub(super) fn unbind_serial(&mut self, port_idx: u8) {
+        let Some(serial) = self.serial.filter(|s| s.port_idx == port_idx) else { return };
+        self.serial = None;
+        READY.store(false, Ordering::Release);
+        drop(ADAPTER.lock().take());
+        log!("usb-serial: adapter on slot {} unplugged from port {}", serial.slot_id,
+            port_idx + 1);
+        crate::drivers::serial::console_changed();
+    }
+}
@@ -1,11 +1,12 @@
 //! `usbd`: the context USB work runs in, instead of whichever thread trapped.
 //!
-//! The kernel has three kernel threads and this is the second: `klogd` drains
-//! the console (`log/console.rs`), `usbd` owns the xHCI port machine, and
-//! `iod` (`crate::iod`) owns the write-back queue. Three and not one, because
-//! a stuck USB enumeration must not stop the log — which is exactly what it
-//! does today, where [`super::poll_if_pending`] runs at the top of every
-//! scheduler pass on every CPU and `wait_transfer` spins with `XHCI` held.
+//! The kernel has four kernel threads and this is the second: `klogd` drains
+//! the console (`log/console.rs`), `usbd` owns the xHCI port machine, `iod`
+//! (`crate::iod`) owns the write-back queue and `acpid` (`drivers::aml`) runs
+//! firmware's methods. Several and not one, because a stuck USB enumeration
+//! must not stop the log — which is exactly what it does today, where
+//! [`super::poll_if_pending`] runs at the top of every scheduler pass on every
+//! CPU and `wait_transfer` spins with `XHCI` held.
 //!
 //! **What it will own, and what it owns now.** C7 moves `poll_if_pending` off
 //! `drain_irqs` and onto this thread, C9 hands it the i8042's verdict as a
@@ -12,6 +12,7 @@ use crate::log;
 use crate::time::{Budget, Cadence, Duration};
 use crate::mm::paging::CachePolicy;
 use crate::mm::Mmio;
+use crate::mm::Dma;
 use crate::drivers::pci::PciDevice;
 use crate::drivers::DmaPool;
 use super::super::{device, legacy};
@@ -206,13 +207,116 @@ pub fn init(devices: &[PciDevice]) {
     // Nothing is outstanding out of a boot scan — every port it looked at it
     // acted on — so a machine that is never plugged into pays nothing for
     // hotplug beyond one atomic load per pass.
-    PORT_WORK_AT.store(0, Ordering::Relaxed);
+    //
+    // **Except a hub's ports, which the scan does not walk.** A hub bound here
+    // has powered them and has not yet been asked what is in them, and what is
+    // is enumerated by the first passes exactly as if it had been plugged in
+    // after boot — the one enumeration path that already knows how to wait for
+    // a hub's power to come good without holding the boot CPU across it. So a
+    // boot that bound one leaves the first pass due.
+    let hubs = controllers.iter().any(|c| !c.hubs.is_empty());
+    let due = if hubs { crate::clock::nanos_since_boot().max(1) } else { 0 };
+    PORT_WORK_AT.store(due, Ordering::Relaxed);
     let hid: usize = controllers.iter().map(|c| c.devices.len()).sum();
     log!("xHCI: {} controller(s), {} HID device(s)", controllers.len(), hid);
     log!("usb-storage: {} device(s)", storage_count());
+    super::super::audio::offer();
     *XHCI.lock() = controllers;
 }
 
+/// Stop every controller ahead of S3, so none is writing an event or a
+/// transfer into memory while the machine goes down around it. Halted rather
+/// than reset: [`resume`] starts from a reset either way, and one that finds
+/// the sleep never happened finds a halted controller to reset.
+pub fn suspend() {
+    for ctrl in XHCI.lock().iter() {
+        if !halt(&ctrl.op_base) {
+            log!("xHCI: the controller at {:02x}:{:02x}.{} did not halt for the sleep",
+                ctrl.pci.bus, ctrl.pci.dev, ctrl.pci.func);
+        }
+    }
+}
+
+/// Bring every controller back after S3 and enumerate it again, the boot's
+/// way: reset, the same pool programmed afresh, ports powered, one settle for
+/// the machine, then a scan.
+///
+/// **Every device is new to the controller**, because the power loss — or
+/// QEMU's `system_wakeup` — reset it, and every slot it had is gone. So the
+/// HID devices are unbound as if unplugged and bound again as found, and a
+/// disk's block is kept back for its port ([`MscBlock::slept`]): a disk that
+/// comes back the same is given its number back, and one that does not is
+/// offline, which is what [`XhciController::forget_slept`] says of it.
+///
+/// Run with `IF` clear and every other CPU parked, like the boot scan it
+/// reuses. The controllers are out of [`XHCI`] meanwhile for the same reason
+/// `init` builds them outside it: the scan is the only thing that could reach
+/// them, and it has them by `&mut`.
+pub fn resume() {
+    let mut controllers = core::mem::take(&mut *XHCI.lock());
+    if controllers.is_empty() {
+        return;
+    }
+    let mut back = Vec::with_capacity(controllers.len());
+    for ctrl in controllers.iter_mut() {
+        back.push(restart(ctrl));
+    }
+
+    await_connect_settle(&controllers);
+
+    for (ctrl, &back) in controllers.iter_mut().zip(&back) {
+        if back {
+            scan_ports(ctrl);
+        }
+    }
+    for ctrl in controllers.iter_mut() {
+        ctrl.forget_slept();
+    }
+    PORT_WORK_AT.store(0, Ordering::Relaxed);
+    let hid: usize = controllers.iter().map(|c| c.devices.len()).sum();
+    log!("xHCI: {} controller(s) back after the sleep, {} HID device(s)",
+        back.iter().filter(|&&b| b).count(), hid);
+    *XHCI.lock() = controllers;
+}
+
+/// Reset one controller and start it again over the pool it already owns,
+/// with every piece of driver state that named a slot put back to what
+/// `init_one` built. False for a controller that did not come back, which
+/// keeps its ports unscanned and its disks offline.
+fn restart(ctrl: &mut XhciController) -> bool {
+    for mut dev in ctrl.devices.drain(..) {
+        dev.unbind();
+    }
+    for block in ctrl.msc.iter_mut() {
+        *block = match block.disk.take() {
+            Some(disk) => MscBlock { slept: Some(disk), ..*block },
+            None => MscBlock::FREE,
+        };
+    }
+    ctrl.ports = fresh_ports(ctrl.max_ports, ctrl.protocols);
+    ctrl.outstanding = Outstanding::EMPTY;
+    ctrl.ports_dirty = false;
+    ctrl.software_disabled = [0; 4];
+    ctrl.held_event = None;
+
+    let pci = ctrl.pci;
+    let refuse = |why: core::fmt::Arguments| {
+        log!("xHCI: the controller at {:02x}:{:02x}.{} did not come back from the sleep: {why}",
+            pci.bus, pci.dev, pci.func);
+    };
+    if !reset(&ctrl.op_base, &refuse)
+        || !run(&ctrl.op_base, &ctrl.rt_base, &ctrl.layout, ctrl.pool, &refuse)
+    {
+        return false;
+    }
+    ctrl.cmd_ring = TrbRing::init(ctrl.pool.subview(OFF_CMD_RING, PAGE));
+    ctrl.event_head = 0;
+    ctrl.event_phase = true;
+    power_ports(&ctrl.op_base, ctrl.max_ports);
+    ctrl.powered_at = crate::clock::nanos_since_boot();
+    true
+}
+
 /// What each of this controller's port registers speaks, out of its own
 /// Supported Protocol capabilities (§7.2).
 ///
@@ -377,23 +481,7 @@ fn init_one(pci_dev: &PciDevice) -> Option<XhciController> {
     legacy::take_ownership(&bar, bar_size, hccparams1);
     let protocols = read_protocols(&bar, bar_size, hccparams1, max_ports, pci_dev);
 
-    let usbcmd = op_base.read_u32(OP_USBCMD);
-    if usbcmd & 1 != 0 {
-        op_base.write_u32(OP_USBCMD, usbcmd & !1);
-    }
-    let deadline_ms = USB_TIMEOUT_NS / 1_000_000;
-    if !settles(|| controller_answers() && op_base.read_u32(OP_USBSTS) & 1 != 0) {
-        refuse(format_args!("it never halted, within {deadline_ms} ms of being asked to"));
-        return None;
-    }
-
-    op_base.write_u32(OP_USBCMD, 1 << 1);
-    if !settles(|| controller_answers() && op_base.read_u32(OP_USBCMD) & (1 << 1) == 0) {
-        refuse(format_args!("it held HCRST for {deadline_ms} ms"));
-        return None;
-    }
-    if !settles(|| controller_answers() && op_base.read_u32(OP_USBSTS) & (1 << 11) == 0) {
-        refuse(format_args!("it stayed Controller Not Ready for {deadline_ms} ms after its reset"));
+    if !reset(&op_base, &refuse) {
         return None;
     }
     log!("xHCI: controller reset");
@@ -405,65 +493,7 @@ fn init_one(pci_dev: &PciDevice) -> Option<XhciController> {
     // pages are still the pool's to give back.
     let pool = DmaPool::alloc(layout.pool_size);
 
-    // MaxSlotsEn is what the driver can track, not what the controller can
-    // offer: a conformant xHC then refuses Enable Slot past it rather than
-    // handing back an id with nowhere to put its context.
-    op_base.write_u32(OP_CONFIG, layout.dev_blocks as u32);
-
-    {
-        let dma = pool.view();
-        dma.zero();
-
-        if layout.scratch_count > 0 {
-            for i in 0..layout.scratch_count {
-                let buf = dma.phys() + (layout.scratch_buffers + i * PAGE) as u64;
-                // Volatile because the controller reads this array as soon as
-                // DCBAA[0] points at it. Bounded for the whole entry, where the
-                // form before the sweep
-                // (`(dma.ptr_at(scratch_array) as *mut u64).add(i)`) bounded only
-                // the array's base; `scratch_count` is the controller's own
-                // HCSPARAMS2 figure and `Layout` sized the pool for exactly that
-                // many. Aligned: `scratch_array` is page-aligned and entries are
-                // 8 bytes. Exclusive: DCBAA[0] is written after this loop, so the
-                // controller has not been told the array exists.
-                dma.write::<u64>(layout.scratch_array + i * core::mem::size_of::<u64>(), buf);
-            }
-            // DCBAA slot 0 is the scratchpad array pointer, not a device context.
-            super::super::write_dcbaa(dma, 0, dma.phys() + layout.scratch_array as u64);
-            log!("xHCI: {} scratchpad buffers configured", layout.scratch_count);
-        }
-
-        op_base.write_u64(OP_DCBAAP, dma.phys() + OFF_DCBAA as u64);
-
-        // CRCR bit 0 is RCS, the cycle state the controller starts on, and the
-        // pointer above it is 64-byte aligned — so the OR lands in that bit and
-        // nowhere else (xHCI 1.2 §5.4.5). Parenthesised because `+` binds tighter
-        // than `|`, and this should not need that table to read.
-        op_base.write_u64(OP_CRCR, (dma.phys() + OFF_CMD_RING as u64) | 1);
-
-        // Volatile because the controller reads this table the moment
-        // `IR0_ERSTBA` is written three lines below. Bounded for the whole entry.
-        // Aligned: `OFF_ERST` is page-aligned and `ErstEntry` is 16 bytes with
-        // alignment 8. Exclusive: the controller has not been given the table's
-        // address yet.
-        dma.write::<ErstEntry>(OFF_ERST, ErstEntry {
-            ring_base: dma.phys() + OFF_EVT_RING as u64,
-            ring_size: RING_SIZE as u32,
-            _reserved: 0,
-        });
-        rt_base.write_u32(IR0_ERSTSZ, 1);
-        rt_base.write_u64(IR0_ERDP, dma.phys() + OFF_EVT_RING as u64);
-        rt_base.write_u64(IR0_ERSTBA, dma.phys() + OFF_ERST as u64);
-
-        // Enable interrupter 0
-        rt_base.write_u32(IR0_IMOD, 0);
-        rt_base.write_u32(IR0_IMAN, 3);
-
-        // Start controller (R/S + INTE for interrupt delivery)
-        op_base.write_u32(OP_USBCMD, 1 | (1 << 2));
-    }
-    if !settles(|| controller_answers() && op_base.read_u32(OP_USBSTS) & 1 == 0) {
-        refuse(format_args!("it stayed halted for {deadline_ms} ms after R/S"));
+    if !run(&op_base, &rt_base, &layout, pool.view(), &refuse) {
         return None;
     }
     log!("xHCI: controller started");
@@ -480,22 +510,7 @@ fn init_one(pci_dev: &PciDevice) -> Option<XhciController> {
     let dma = pool.leak();
     let cmd_ring = TrbRing::init(dma.subview(OFF_CMD_RING, PAGE));
 
-    // HCRST returns every root-hub port to the state it has with nothing
-    // attached, and on a controller with Port Power Control that state is
-    // unpowered — a port with no power reports no device, for the life of the
-    // boot. PP is RW there and reads back set on a controller without PPC, so
-    // the write is unconditional and the count is what says which happened.
-    let mut powered = 0;
-    for p in 0..max_ports {
-        let off = OP_PORT_BASE + p as u64 * PORT_REG_SIZE;
-        let portsc = op_base.read_u32(off);
-        if portsc & PORTSC_PP == 0 {
-            op_base.write_u32(off, Portsc::from_raw(portsc).neutral().powered().raw());
-        }
-        if op_base.read_u32(off) & PORTSC_PP != 0 {
-            powered += 1;
-        }
-    }
+    let powered = power_ports(&op_base, max_ports);
     let powered_at = crate::clock::nanos_since_boot();
     log!("xHCI: {powered}/{max_ports} root-hub ports powered (PPC={})",
         u8::from(hccparams1 & HCC_PPC != 0));
@@ -515,6 +530,7 @@ fn init_one(pci_dev: &PciDevice) -> Option<XhciController> {
         powered_at,
         context_size,
         layout,
+        max_psa: ((hccparams1 >> 12) & 0xF) as u8,
         pool: dma,
         protocols,
         cmd_ring,
@@ -523,19 +539,153 @@ fn init_one(pci_dev: &PciDevice) -> Option<XhciController> {
         event_phase: true,
         devices: Vec::new(),
         msc: [MscBlock::FREE; MSC_BLOCKS],
-        ports: (0..max_ports)
-            .map(|p| {
-                let mut port = PortState::EMPTY;
-                port.speaks(protocols.of(p));
-                port
-            })
-            .collect(),
+        ports: fresh_ports(max_ports, protocols),
+        hubs: Vec::new(),
+        net: None,
+        serial: None,
+        audio: None,
+        downstream: Vec::new(),
         ports_dirty: false,
         outstanding: Outstanding::EMPTY,
         software_disabled: [0u64; 4],
         held_event: None,
     })
 }
+/// Halt the controller, if it is running, and wait until it says so.
+fn halt(op_base: &Mmio) -> bool {
+    let usbcmd = op_base.read_u32(OP_USBCMD);
+    if usbcmd & 1 != 0 {
+        op_base.write_u32(OP_USBCMD, usbcmd & !1);
+    }
+    settles(|| controller_answers() && op_base.read_u32(OP_USBSTS) & 1 != 0)
+}
+
+/// Halt and reset the controller, each step waited for in place. False once
+/// `refuse` has been told which step it stalled at.
+fn reset(op_base: &Mmio, refuse: &dyn Fn(core::fmt::Arguments)) -> bool {
+    let deadline_ms = USB_TIMEOUT_NS / 1_000_000;
+    if !halt(op_base) {
+        refuse(format_args!("it never halted, within {deadline_ms} ms of being asked to"));
+        return false;
+    }
+
+    op_base.write_u32(OP_USBCMD, 1 << 1);
+    if !settles(|| controller_answers() && op_base.read_u32(OP_USBCMD) & (1 << 1) == 0) {
+        refuse(format_args!("it held HCRST for {deadline_ms} ms"));
+        return false;
+    }
+    if !settles(|| controller_answers() && op_base.read_u32(OP_USBSTS) & (1 << 11) == 0) {
+        refuse(format_args!("it stayed Controller Not Ready for {deadline_ms} ms after its reset"));
+        return false;
+    }
+    true
+}
+
+/// Program a controller fresh out of [`reset`] over the pool `dma` — zeroed
+/// here, scratchpad, DCBAA, command ring and event ring — and start it.
+fn run(
+    op_base: &Mmio,
+    rt_base: &Mmio,
+    layout: &Layout,
+    dma: Dma<'_>,
+    refuse: &dyn Fn(core::fmt::Arguments),
+) -> bool {
+    // MaxSlotsEn is what the driver can track, not what the controller can
+    // offer: a conformant xHC then refuses Enable Slot past it rather than
+    // handing back an id with nowhere to put its context.
+    op_base.write_u32(OP_CONFIG, layout.dev_blocks as u32);
+
+    dma.zero();
+
+    if layout.scratch_count > 0 {
+        for i in 0..layout.scratch_count {
+            let buf = dma.phys() + (layout.scratch_buffers + i * PAGE) as u64;
+            // Volatile because the controller reads this array as soon as
+            // DCBAA[0] points at it. Bounded for the whole entry, where the
+            // form before the sweep
+            // (`(dma.ptr_at(scratch_array) as *mut u64).add(i)`) bounded only
+            // the array's base; `scratch_count` is the controller's own
+            // HCSPARAMS2 figure and `Layout` sized the pool for exactly that
+            // many. Aligned: `scratch_array` is page-aligned and entries are
+            // 8 bytes. Exclusive: DCBAA[0] is written after this loop, so the
+            // controller has not been told the array exists.
+            dma.write::<u64>(layout.scratch_array + i * core::mem::size_of::<u64>(), buf);
+        }
+        // DCBAA slot 0 is the scratchpad array pointer, not a device context.
+        super::super::write_dcbaa(dma, 0, dma.phys() + layout.scratch_array as u64);
+        log!("xHCI: {} scratchpad buffers configured", layout.scratch_count);
+    }
+
+    op_base.write_u64(OP_DCBAAP, dma.phys() + OFF_DCBAA as u64);
+
+    // CRCR bit 0 is RCS, the cycle state the controller starts on, and the
+    // pointer above it is 64-byte aligned — so the OR lands in that bit and
+    // nowhere else (xHCI 1.2 §5.4.5). Parenthesised because `+` binds tighter
+    // than `|`, and this should not need that table to read.
+    op_base.write_u64(OP_CRCR, (dma.phys() + OFF_CMD_RING as u64) | 1);
+
+    // Volatile because the controller reads this table the moment
+    // `IR0_ERSTBA` is written three lines below. Bounded for the whole entry.
+    // Aligned: `OFF_ERST` is page-aligned and `ErstEntry` is 16 bytes with
+    // alignment 8. Exclusive: the controller has not been given the table's
+    // address yet.
+    dma.write::<ErstEntry>(OFF_ERST, ErstEntry {
+        ring_base: dma.phys() + OFF_EVT_RING as u64,
+        ring_size: RING_SIZE as u32,
+        _reserved: 0,
+    });
+    rt_base.write_u32(IR0_ERSTSZ, 1);
+    rt_base.write_u64(IR0_ERDP, dma.phys() + OFF_EVT_RING as u64);
+    rt_base.write_u64(IR0_ERSTBA, dma.phys() + OFF_ERST as u64);
+
+    // Enable interrupter 0
+    rt_base.write_u32(IR0_IMOD, 0);
+    rt_base.write_u32(IR0_IMAN, 3);
+
+    // Start controller (R/S + INTE for interrupt delivery)
+    op_base.write_u32(OP_USBCMD, 1 | (1 << 2));
+    if !settles(|| controller_answers() && op_base.read_u32(OP_USBSTS) & 1 == 0) {
+        let deadline_ms = USB_TIMEOUT_NS / 1_000_000;
+        refuse(format_args!("it stayed halted for {deadline_ms} ms after R/S"));
+        return false;
+    }
+    true
+}
+
+/// Power every root-hub port that is not, and answer how many are.
+///
+/// HCRST returns every root-hub port to the state it has with nothing
+/// attached, and on a controller with Port Power Control that state is
+/// unpowered — a port with no power reports no device, for the life of the
+/// boot. PP is RW there and reads back set on a controller without PPC, so
+/// the write is unconditional and the count is what says which happened.
+fn power_ports(op_base: &Mmio, max_ports: u8) -> u8 {
+    let mut powered = 0;
+    for p in 0..max_ports {
+        let off = OP_PORT_BASE + p as u64 * PORT_REG_SIZE;
+        let portsc = op_base.read_u32(off);
+        if portsc & PORTSC_PP == 0 {
+            op_base.write_u32(off, Portsc::from_raw(portsc).neutral().powered().raw());
+        }
+        if op_base.read_u32(off) & PORTSC_PP != 0 {
+            powered += 1;
+        }
+    }
+    powered
+}
+
+/// Every port as the machine has it before anything was seen on it, each
+/// told what it speaks.
+fn fresh_ports(max_ports: u8, protocols: Protocols) -> Vec<PortState> {
+    (0..max_ports)
+        .map(|p| {
+            let mut port = PortState::EMPTY;
+            port.speaks(protocols.of(p));
+            port
+        })
+        .collect()
+}
+
 /// Initialize and configure one USB device on a port, waiting for each step.
 ///
 /// **Which reset this port needs is [`port::reset_needed`]'s answer and never a
@@ -0,0 +1,232 @@
+//! A hub's ports, read and written by asking the hub — the second door out of
+//! a scheduler pass, see [`super`].
+//!
+//! Three things come through here and nothing else: bringing a hub up once its
+//! endpoints are configured ([`XhciController::bind_hub`]), asking it about
+//! the ports its bitmap named ([`XhciController::refresh_hubs`]), and carrying
+//! a port-machine write to it ([`XhciController::write_downstream`]). Each is
+//! one control transfer per request on the hub's EP0.
+
+use crate::log;
+use toyos_xhci::hub::{self as class, Descriptor, Feature, PortStatus, Setup};
+use toyos_xhci::port::PortState;
+use toyos_xhci::portsc::Write;
+use toyos_xhci::Protocol;
+use super::Control;
+use super::super::hub::{Downstream, HubDevice};
+use super::super::{earliest, TrbRing, XhciController, DEV_HUB_STATUS, DEV_REPORT};
+
+impl XhciController {
+    /// Bring up the hub on `port_idx` whose endpoints are configured: number
+    /// its ports, power them, and arm the interrupt endpoint that reports
+    /// their changes.
+    ///
+    /// Every port is marked changed, and asked about once the descriptor's
+    /// power-good time has passed. A device already plugged in when the hub
+    /// arrived then enumerates by the hot-plug path like one plugged in a
+    /// second later, and whether the hub also reports its connect change is
+    /// the hub's business.
+    ///
+    /// A hub this driver cannot number ports for is left bound and unpowered,
+    /// by name: at the sixth tier, where a Route String has no nibble left,
+    /// or past the 255th port on this controller.
+    #[allow(clippy::too_many_arguments)]
+    pub(in crate::drivers::xhci) fn bind_hub(
+        &mut self,
+        slot_id: u8,
+        port_idx: u8,
+        block: usize,
+        speed: u8,
+        ep0_ring: TrbRing,
+        int_ring: TrbRing,
+        int_dci: u8,
+        descriptor: Descriptor,
+    ) {
+        let place = self.place(port_idx);
+        let count = descriptor.served();
+        if place.below(slot_id, speed, 1).is_none() {
+            log!(
+                "xHCI: hub on slot {slot_id} (port {}) is below five others, where no Route String reaches; leaving its ports unpowered",
+                port_idx + 1
+            );
+            return;
+        }
+        if descriptor.ports > count {
+            log!(
+                "xHCI: hub on slot {slot_id} has {} ports and a Route String can name {count}; the rest stay unpowered",
+                descriptor.ports
+            );
+        }
+        let Some(first) = self.claim_downstream(count) else {
+            log!(
+                "xHCI: hub on slot {slot_id} (port {}) would take this controller past 255 ports; leaving its ports unpowered",
+                port_idx + 1
+            );
+            return;
+        };
+        let superspeed = speed >= class::SPEED_SUPER;
+        let protocol = if superspeed { Protocol::Usb3 } else { Protocol::Usb2 };
+        for number in 1..=count {
+            let entry = Downstream {
+                hub_slot: slot_id,
+                number,
+                superspeed,
+                status: PortStatus::default(),
+                place: place.below(slot_id, speed, number).expect("port 1 had a place"),
+                orphaned: false,
+            };
+            let mut machine = PortState::EMPTY;
+            machine.speaks(Some(protocol));
+            let at = (first + number - 1) as usize;
+            let i = at - self.max_ports as usize;
+            if i == self.downstream.len() {
+                self.downstream.push(entry);
+                self.ports.push(machine);
+            } else {
+                self.downstream[i] = entry;
+                self.ports[at] = machine;
+            }
+        }
+
+        let dma = self.dma();
+        let now = crate::clock::nanos_since_boot();
+        self.hubs.push(HubDevice {
+            slot_id,
+            port_idx,
+            ep0_ring,
+            int_ring,
+            int_dci,
+            bitmap: dma.subview(block + DEV_REPORT, 2),
+            status: dma.subview(block + DEV_HUB_STATUS, 4),
+            first,
+            count,
+            changed: ((1u32 << (count + 1)) - 2) as u16,
+            not_before: now + descriptor.power_good_ms as u64 * 1_000_000,
+        });
+        let at = self.hubs.len() - 1;
+        for number in 1..=count {
+            let power = self.hub_request(at, Setup::feature(class::POWER, number), None);
+            if !power.done() {
+                log!("xHCI: hub on slot {slot_id} would not power its port {number}: {power}");
+            }
+        }
+        let db_base = self.db_base;
+        self.hubs[at].requeue(&db_base);
+        self.ports_dirty = true;
+        log!(
+            "xHCI: hub on slot {slot_id} (port {}) has {count} ports, numbered {}..{} here",
+            port_idx + 1,
+            first + 1,
+            first + count
+        );
+    }
+
+    /// Ask every hub about the ports its bitmap named, once each is past its
+    /// power-good time. Returns the earliest such time still ahead, for the
+    /// poll's wake-up.
+    ///
+    /// Runs before the port machines look, so what they read is this pass's
+    /// answer and not the last one's.
+    pub(in crate::drivers::xhci) fn refresh_hubs(&mut self) -> Option<u64> {
+        let now = crate::clock::nanos_since_boot();
+        let mut wake_at = None;
+        for at in 0..self.hubs.len() {
+            let HubDevice { changed, not_before, count, .. } = self.hubs[at];
+            if changed == 0 {
+                continue;
+            }
+            if now < not_before {
+                wake_at = earliest(wake_at, Some(not_before));
+                continue;
+            }
+            self.hubs[at].changed = 0;
+            for number in (1..=count).filter(|n| changed & (1 << n) != 0) {
+                self.refresh_port(at, number);
+            }
+            self.ports_dirty = true;
+        }
+        wake_at
+    }
+
+    /// Carry a port-machine write to the hub port at `port_idx`.
+    ///
+    /// An orphan's write is applied to its last status instead: the change
+    /// flags it acknowledges are cleared here, which is what lets the port
+    /// come to rest after its device is torn down, and nothing is sent.
+    pub(in crate::drivers::xhci) fn write_downstream(&mut self, port_idx: u8, write: Write) {
+        let i = (port_idx - self.max_ports) as usize;
+        let port = self.downstream[i];
+        let features = class::features(write, port.status, port.superspeed);
+        let hub = self.hubs.iter().position(|h| h.slot_id == port.hub_slot);
+        let Some(at) = hub.filter(|_| !port.orphaned) else {
+            for feature in features.as_slice() {
+                if let Feature::Clear(selector) = *feature {
+                    let bit = class::cleared_by(selector, port.superspeed).unwrap_or(0);
+                    self.downstream[i].status.change &= !bit;
+                }
+            }
+            return;
+        };
+        for &feature in features.as_slice() {
+            let answer = self.hub_request(at, Setup::feature(feature, port.number), None);
+            if !answer.done() {
+                log!(
+                    "xHCI: hub on slot {} refused {feature:?} on its port {}: {answer}",
+                    port.hub_slot,
+                    port.number
+                );
+            }
+        }
+        // What the machine reads next has to be what the hub now says, or a
+        // cleared change reads as still set and is acknowledged again.
+        if !features.as_slice().is_empty() {
+            self.refresh_port(at, port.number);
+        }
+    }
+
+    /// GET_STATUS on port `number` of `self.hubs[at]`, into its entry.
+    ///
+    /// An answer that did not come leaves the last one in place. The port is
+    /// then decided on what it last said, and the next change the hub reports
+    /// asks again.
+    fn refresh_port(&mut self, at: usize, number: u8) {
+        let (slot_id, first, buffer) = (self.hubs[at].slot_id, self.hubs[at].first, self.hubs[at].status);
+        buffer.write::<u32>(0, 0);
+        let answer = self.hub_request(at, Setup::port_status(number), Some(buffer.phys()));
+        match answer {
+            Control::Done { delivered: 4 } => {
+                let mut bytes = [0u8; 4];
+                buffer.copy_to(0, &mut bytes);
+                let i = (first + number - 1 - self.max_ports) as usize;
+                self.downstream[i].status = PortStatus::decode(bytes);
+            }
+            Control::Done { delivered } => log!(
+                "xHCI: hub on slot {slot_id} answered GET_STATUS for its port {number} with {delivered} B of 4"
+            ),
+            _ => log!("xHCI: hub on slot {slot_id} would not report its port {number}: {answer}"),
+        }
+    }
+
+    /// One request on the EP0 of `self.hubs[at]`.
+    ///
+    /// The ring is copied out and back because `control_transfer` drains the
+    /// event ring while it waits, and a drain may reach the hub's interrupt
+    /// endpoint through `hub_changed` — which touches the same entry, though
+    /// never this field.
+    fn hub_request(&mut self, at: usize, setup: Setup, data: Option<u64>) -> Control {
+        let slot_id = self.hubs[at].slot_id;
+        let mut ring = self.hubs[at].ep0_ring;
+        let answer = self.control_transfer(
+            slot_id,
+            &mut ring,
+            setup.request_type,
+            setup.request,
+            setup.value,
+            setup.index,
+            data,
+            setup.length,
+        );
+        self.hubs[at].ep0_ring = ring;
+        answer
+    }
+}
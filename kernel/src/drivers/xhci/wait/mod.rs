@@ -22,7 +22,8 @@
 //! - [`boot`] — the scan. There is no scheduler yet, so the pass this would
 //!   give itself back to does not exist.
 //! - [`msc`] — a disk. `storage_read` and `storage_write` run on the thread
-//!   that faulted, which is spending its own time.
+//!   that faulted, which is spending its own time. A UAS disk's transport is
+//!   [`uas`], which `msc` calls into and which waits in the same context.
 //! - [`msc::bind`] — **the one door**, and the only blocking thing a scheduler
 //!   pass can still reach. A disk plugged in after boot has to be brought up by
 //!   somebody, its bring-up is Bulk-Only Transport, and that is a machine of
@@ -30,6 +31,14 @@
 //!   (`issues/hardware/the-bot-scsi-machine-is-still-hand-written-in-the-kernel.md`).
 //!   Until then the claim above holds of everything except a disk arriving
 //!   after boot.
+//! - [`hub`] — **the second door**, and a narrower one. A hub's port is not a
+//!   register: reading it is GET_STATUS and writing it is SET_FEATURE or
+//!   CLEAR_FEATURE, each a control transfer on the hub's EP0, and the port
+//!   machine decides from a read and acts by a write inside one pass. What
+//!   passes through is one transfer per request to a hub that is on the bus
+//!   and answering, which is a millisecond; a hub that has gone is asked
+//!   nothing at all, because its ports stop being the hub's the moment its own
+//!   port tears it down.
 //!
 //! # Two bounds, and only one of them is this driver's
 //!
@@ -43,9 +52,9 @@
 //!
 //! **It arrives ambiently and is threaded from there.** Owner ruling 1B: the
 //! deadline is established on the running context above `BlockDevice` and
-//! recovered by [`msc`]'s three operation entry points — `msc_read`,
-//! `msc_write`, `msc_flush` — because the two frames in between cannot carry
-//! it. From those three down it is an ordinary argument, ending at
+//! recovered by [`msc`]'s four operation entry points — `msc_read`,
+//! `msc_write`, `msc_flush`, `msc_discard` — because the two frames in between
+//! cannot carry it. From those four down it is an ordinary argument, ending at
 //! `XhciController::scsi`, which is the one site that reads it; that is what
 //! leaves `scsi` usable by `msc::bind`'s bring-up, which is not a block-device
 //! operation, has no establishment above it, and passes [`Deadline::never`] by
@@ -55,7 +64,9 @@
 //! [`Deadline::never`]: crate::time::Deadline::never
 
 pub mod boot;
+mod hub;
 pub mod msc;
+mod uas;
 
 /// How much of the kernel is holding a spinlock at the moment a device is
 /// waited for.
@@ -152,9 +163,8 @@ impl core::fmt::Display for Control {
 /// A struct because the two callers hold their endpoints in different shapes —
 /// a disk's bulk pair live in a mass-storage pool block and a HID's interrupt
 /// endpoint in a device block — and the recovery needs both the block the
-/// controller writes the *output context* into and the place the ring's memory
-/// is. Passing them positionally is six numbers whose order is the whole
-/// contract.
+/// controller writes the *output context* into and the ring it rebuilds.
+/// Passing them positionally is six numbers whose order is the whole contract.
 struct Restart<'a> {
     slot_id: u8,
     /// The device block whose output context carries this endpoint's state.
@@ -163,10 +173,11 @@ struct Restart<'a> {
     /// The address the *device* knows this endpoint by, which is what a
     /// CLEAR_FEATURE names.
     ep_addr: u8,
-    /// Where in the pool the transfer ring lives, because recovery rebuilds it
-    /// rather than resuming a ring the controller has a stale dequeue pointer
-    /// into.
-    ring_at: usize,
+    /// Which of the endpoint's streams `ring` is, and 0 for an endpoint
+    /// without them — see [`uas`] for the one that has them.
+    stream: u16,
+    /// Rebuilt rather than resumed, because the controller has a stale dequeue
+    /// pointer into it.
     ring: &'a mut TrbRing,
     ep0_ring: &'a mut TrbRing,
 }
@@ -246,7 +257,7 @@ impl XhciController {
                 Act::ClearHalt => return Owed::ClearHalt { ep_addr: ep.ep_addr },
                 Act::Command(cmd) => cmd,
             };
-            let trb = self.recovery_trb(cmd, ep.slot_id, ep.dci, ep.ring, ep.ring_at);
+            let trb = self.recovery_trb(cmd, ep.slot_id, ep.dci, ep.stream, ep.ring);
             if !self.run_command(trb, cmd.name()) {
                 return Owed::Failed;
             }
@@ -361,11 +372,23 @@ impl XhciController {
     /// many of the caller's bytes are real — to whatever asked next on the same
     /// endpoint.
     fn wait_transfer(&mut self, slot: u8, dci: u8, trb: u64) -> Option<(u32, u32)> {
+        self.wait_any_transfer(slot, &[(dci, trb)]).map(|(_, code, residue)| (code, residue))
+    }
+
+    /// The first of several transfers on `slot` to complete, as its index in
+    /// `on` — each a (dci, TRB address) — and [`Self::wait_transfer`]'s pair.
+    ///
+    /// A UAS disk has a command, a status receive and a data transfer
+    /// outstanding per tag and several tags at once, and which of them the
+    /// device answers first is its own business; waiting for any one of them
+    /// in particular would hold every other completion in the event ring behind
+    /// it. The matching is by TRB all the same, so nothing here can take an
+    /// answer that is not one of `on`'s.
+    fn wait_any_transfer(&mut self, slot: u8, on: &[(u8, u64)]) -> Option<(usize, u32, u32)> {
         #[cfg(feature = "boot-actuators")]
         if crate::actuator::io_depth_probe() {
             depth_probe::report();
         }
-        let on = Await::Transfer { slot, dci, trb };
         let deadline = deadline();
         let port = self.port_of_slot(slot);
         loop {
@@ -393,8 +416,9 @@ impl XhciController {
                 dci: ((event.control >> 16) & 0x1F) as u8,
                 trb: event.param & !0xF,
             };
-            if trb_type == EVENT_TRANSFER && answers == on {
-                return Some(((event.status >> 24) & 0xFF, event.status & 0x00FF_FFFF));
+            let which = on.iter().position(|&(dci, trb)| answers == Await::Transfer { slot, dci, trb });
+            if let (EVENT_TRANSFER, Some(which)) = (trb_type, which) {
+                return Some((which, (event.status >> 24) & 0xFF, event.status & 0x00FF_FFFF));
             }
             self.dispatch_event(event);
         }
@@ -1,5 +1,6 @@
 //! USB Mass Storage, Bulk-Only Transport with a transparent SCSI command set
-//! (interface class 0x08, subclass 0x06, protocol 0x50).
+//! (interface class 0x08, subclass 0x06, protocol 0x50), and the SCSI half of
+//! a USB Attached SCSI disk (protocol 0x62), whose transport is [`super::uas`].
 //!
 //! Everything that arrives here came off a wire, so nothing in this file may
 //! panic on it: a capacity, a block size, a CSW tag and a residue are all
@@ -25,12 +26,13 @@ use crate::log;
 use crate::scheduler::Operation;
 use crate::time::{Budget, Deadline, Duration};
 use super::super::device::Endpoint;
+use super::uas::{self, UasDevice};
 use super::{Owed, Restart};
 use super::super::{with_disk, Disk, StorageGeometry, Trb, TrbRing, XhciController, PAGE};
 use super::super::{CC_SUCCESS, CC_STALL, CC_SHORT_PACKET, TRB_NORMAL, OFF_INPUT_CTX};
 use super::super::USB_TIMEOUT_NS;
 use super::super::{MSC_IN_RING, MSC_OUT_RING, MSC_CBW, MSC_CSW, MSC_SCRATCH, MSC_SCRATCH_LEN};
-use super::super::{MSC_DATA, MSC_DATA_LEN, MSC_MAX_BLOCKS};
+use super::super::{MSC_DATA, MSC_DATA_LEN, MSC_MAX_BLOCKS, UAS_DEPTH};
 
 /// The block size the layer above this one is written in. A device that
 /// addresses in anything this does not divide by is unimplemented, not
@@ -82,11 +84,36 @@ const CSW_LEN: u32 = 13;
 /// nothing left to check. The private *field* is what buys that; a private
 /// constructor beside public fields would leave `bind`'s own struct literal
 /// able to name any `dci` at all.
+///
+/// A UAS setting is the same shape with its other two pipes in `uas`: its
+/// data-in and data-out pipes are `in_ep` and `out_ep`, because those are what
+/// the data moves through whichever transport names them.
 #[derive(Clone, Copy)]
 pub struct MscInterface {
     pub iface_num: u8,
+    /// The alternate setting this is, which is SET_INTERFACE'd before the
+    /// endpoints are configured when it is not 0.
+    pub alternate: u8,
     pub in_ep: Endpoint,
     pub out_ep: Endpoint,
+    pub uas: Option<UasPipes>,
+}
+
+/// A UAS setting's command and status pipes, which its Pipe Usage descriptors
+/// named.
+#[derive(Clone, Copy)]
+pub struct UasPipes {
+    pub command: Endpoint,
+    pub status: Endpoint,
+}
+
+/// How SCSI reaches one disk.
+#[derive(Clone, Copy)]
+enum Transport {
+    /// One command at a time over a bulk pair.
+    Bot { in_ring: TrbRing, out_ring: TrbRing },
+    /// Several at once over four pipes.
+    Uas(UasDevice),
 }
 
 /// One bound disk. `Copy` because every operation takes it out of the
@@ -108,8 +135,8 @@ pub struct MscDevice {
     /// in, and those states are what decides which recovery command is legal.
     dev_block: usize,
     ep0_ring: TrbRing,
-    in_ring: TrbRing,
-    out_ring: TrbRing,
+    transport: Transport,
+    /// The last CBW tag, which Bulk-Only alone uses.
     tag: u32,
     logical_block_bytes: u32,
     sectors_per_block: u32,
@@ -126,6 +153,14 @@ pub struct MscDevice {
     /// it is the same self-sustaining write loop reading the refusal as a
     /// failure produced.
     no_write_cache: bool,
+    /// Set for an MMC device — a CD or DVD drive — which this driver reads and
+    /// never writes. Refused here rather than left to the drive: a writer
+    /// answers WRITE(10) on a blank disc, and nothing above this driver burns
+    /// media.
+    read_only: bool,
+    /// Sectors one UNMAP may name, or zero for a disk that did not say it
+    /// takes the command — see [`probe_unmap`].
+    unmap_sectors: u32,
 }
 
 impl MscDevice {
@@ -145,6 +180,11 @@ impl MscDevice {
         self.slot_id
     }
 
+    /// Whether this disk speaks UAS rather than Bulk-Only.
+    pub fn uas(&self) -> bool {
+        matches!(self.transport, Transport::Uas(_))
+    }
+
     pub fn geometry(&self) -> StorageGeometry {
         StorageGeometry {
             logical_block_bytes: self.logical_block_bytes,
@@ -152,6 +192,14 @@ impl MscDevice {
         }
     }
 
+    /// Commands the transport carries at once.
+    fn depth(&self) -> u8 {
+        match &self.transport {
+            Transport::Bot { .. } => 1,
+            Transport::Uas(uas) => uas.depth(),
+        }
+    }
+
     fn next_tag(&mut self) -> u32 {
         self.tag = self.tag.wrapping_add(1);
         self.tag
@@ -206,6 +254,8 @@ enum Broke {
     /// would underflow the byte count every caller then uses to decide how much
     /// of the buffer is real.
     Residue { unmoved: u32, of: u32 },
+    /// A UAS disk's transport, which has no phases to name.
+    Uas(uas::Broke),
 }
 
 impl core::fmt::Display for Broke {
@@ -228,6 +278,7 @@ impl core::fmt::Display for Broke {
             Self::Residue { unmoved, of } => {
                 write!(f, "CSW claims {unmoved} B unmoved of {of}")
             }
+            Self::Uas(broke) => write!(f, "{broke}"),
         }
     }
 }
@@ -342,7 +393,8 @@ pub(in crate::drivers::xhci) mod short_read {
 }
 
 /// The completion of one SCSI command, after the transport's own recovery.
-enum Scsi {
+#[derive(Clone, Copy)]
+pub(super) enum Scsi {
     Ok { delivered: u32 },
     /// The device understood the command and declined it, carrying the sense
     /// key, ASC and ASCQ it gave for declining. Carried rather than logged and
@@ -355,6 +407,22 @@ enum Scsi {
     Broken,
 }
 
+/// One command of a batch [`XhciController::scsi_all`] runs, and how it ended
+/// once it has.
+pub(super) struct Command<'a> {
+    pub(super) cdb: &'a [u8],
+    pub(super) data_phys: u64,
+    pub(super) data_len: u32,
+    pub(super) data_in: bool,
+    pub(super) outcome: Option<Scsi>,
+}
+
+impl<'a> Command<'a> {
+    pub(super) fn new(cdb: &'a [u8], data_phys: u64, data_len: u32, data_in: bool) -> Self {
+        Self { cdb, data_phys, data_len, data_in, outcome: None }
+    }
+}
+
 impl Scsi {
     /// SBC's ILLEGAL REQUEST / INVALID COMMAND OPERATION CODE: the device does
     /// not have this opcode. For a command SBC makes optional that is an
@@ -378,6 +446,14 @@ fn log_refusal(cdb: &[u8], key: u8, asc: u8, ascq: u8) {
     );
 }
 
+/// Every command of a batch still without an outcome, ended as broken: the
+/// retry has given up on the transport, so nothing is known about any of them.
+fn broken(cmds: &mut [Command<'_>]) {
+    for cmd in cmds.iter_mut().filter(|c| c.outcome.is_none()) {
+        cmd.outcome = Some(Scsi::Broken);
+    }
+}
+
 /// The sense a test makes SYNCHRONIZE CACHE answer with, in place of the
 /// device's own answer, or `None` on a shipped kernel.
 ///
@@ -495,7 +571,7 @@ impl XhciController {
         Some(out)
     }
 
-    /// The three below are this driver's **operation entry points**, and the
+    /// The four below are this driver's **operation entry points**, and the
     /// one place in it that recovers the caller's budget.
     ///
     /// Owner ruling 1B: the deadline is established by
@@ -530,6 +606,10 @@ impl XhciController {
             if dev.failed {
                 return false;
             }
+            // Nothing was ever written, so there is nothing to make durable.
+            if dev.read_only {
+                return true;
+            }
             // LBA 0, block count 0: the whole medium, which is the only thing
             // a cache flush above a block device can mean.
             let cdb = [0x35u8, 0, 0, 0, 0, 0, 0, 0, 0, 0];
@@ -564,6 +644,64 @@ impl XhciController {
         .unwrap_or(false)
     }
 
+    /// UNMAP `count` 4 KiB blocks from `lba`, one descriptor per command and
+    /// as many commands as the disk's Block Limits page makes it take.
+    ///
+    /// A disk that never said it takes UNMAP answers `true` having been told
+    /// nothing, which is the `BlockDevice::discard` contract. Every UNMAP names
+    /// the same blocks whether it is the first attempt or the third, so
+    /// [`Self::scsi`]'s re-issue is as sound for it as for a WRITE.
+    pub(super) fn msc_discard(&mut self, at: usize, lba: u64, count: u32) -> bool {
+        let until = Operation::deadline();
+        self.with_storage(at, |ctrl, disk| {
+            let dev = &mut disk.dev;
+            if dev.failed {
+                return false;
+            }
+            if dev.unmap_sectors == 0 || count == 0 {
+                return true;
+            }
+            match lba.checked_add(count as u64) {
+                Some(end) if end <= dev.blocks => {}
+                _ => {
+                    log!("usb-storage: {lba}+{count} is past the {} blocks this disk has", dev.blocks);
+                    return false;
+                }
+            }
+            let dma = ctrl.dma();
+            let data_phys = dma.phys() + (dev.block + MSC_DATA) as u64;
+            let per = dev.sectors_per_block as u64;
+            // Whole blocks' worth of sectors per command, so none ends inside
+            // a block the next one starts.
+            let widest = (dev.unmap_sectors as u64 / per * per).max(per);
+            let mut sector = lba * per;
+            let end = (lba + count as u64) * per;
+            while sector < end {
+                let sectors = (end - sector).min(widest) as u32;
+                // The parameter list (SBC-4 §5.32.2): an 8-byte header, then
+                // one 16-byte descriptor.
+                let mut list = [0u8; UNMAP_LIST_BYTES];
+                list[0..2].copy_from_slice(&(UNMAP_LIST_BYTES as u16 - 2).to_be_bytes());
+                list[2..4].copy_from_slice(&16u16.to_be_bytes());
+                list[8..16].copy_from_slice(&sector.to_be_bytes());
+                list[16..20].copy_from_slice(&sectors.to_be_bytes());
+                dma.copy_from(dev.block + MSC_DATA, &list);
+                let cdb = [0x42u8, 0, 0, 0, 0, 0, 0, 0, UNMAP_LIST_BYTES as u8, 0];
+                match ctrl.scsi(dev, &cdb, 10, data_phys, UNMAP_LIST_BYTES as u32, false, until) {
+                    Scsi::Ok { .. } => {}
+                    Scsi::Refused { key, asc, ascq } => {
+                        log_refusal(&cdb, key, asc, ascq);
+                        return false;
+                    }
+                    Scsi::Broken => return false,
+                }
+                sector += sectors as u64;
+            }
+            true
+        })
+        .unwrap_or(false)
+    }
+
     /// Move `count` 4 KiB blocks between the caller's buffer and the disk.
     ///
     /// `until` bounds the whole of it and not one command: the loop below is
@@ -585,6 +723,10 @@ impl XhciController {
         if dev.failed {
             return false;
         }
+        if write && dev.read_only {
+            log!("usb-storage: refusing a write of {count} blocks at {lba} to a read-only drive");
+            return false;
+        }
         if count == 0 {
             return true;
         }
@@ -596,64 +738,90 @@ impl XhciController {
             }
         }
 
+        // One command per slice of the data window, and as many slices as the
+        // transport carries commands at once: the whole window for Bulk-Only,
+        // a quarter of it per tag for UAS.
         let dma = self.dma();
+        let depth = dev.depth() as usize;
+        let per = MSC_MAX_BLOCKS / depth as u32;
+        let slice = per as usize * HOST_BLOCK as usize;
         let data_phys = dma.phys() + (dev.block + MSC_DATA) as u64;
         let mut done = 0u32;
         while done < count {
-            let batch = (count - done).min(MSC_MAX_BLOCKS);
-            let bytes = batch as usize * HOST_BLOCK as usize;
-            let offset = done as usize * HOST_BLOCK as usize;
-            let sector_lba = (lba + done as u64) * dev.sectors_per_block as u64;
-            let sectors = batch * dev.sectors_per_block;
-
-            // `bring_up` refused any disk whose last sector does not fit a
-            // 32-bit LBA, so this driver's READ(10)/WRITE(10) can address
-            // every block it reported.
-            let lba32 = sector_lba as u32;
-            let cdb = [
-                if write { 0x2Au8 } else { 0x28 },
-                0,
-                (lba32 >> 24) as u8,
-                (lba32 >> 16) as u8,
-                (lba32 >> 8) as u8,
-                lba32 as u8,
-                0,
-                (sectors >> 8) as u8,
-                sectors as u8,
-                0,
-            ];
-
-            if let Host::From(src) = &host {
-                dma.copy_from(dev.block + MSC_DATA, &src[offset..offset + bytes]);
+            let mut cdbs = [[0u8; 10]; UAS_DEPTH];
+            let mut batches = [(0u32, 0u32); UAS_DEPTH];
+            let mut n = 0;
+            let mut next = done;
+            while n < depth && next < count {
+                let batch = (count - next).min(per);
+                let sector_lba = (lba + next as u64) * dev.sectors_per_block as u64;
+                let sectors = batch * dev.sectors_per_block;
+                // `bring_up` refused any disk whose last sector does not fit a
+                // 32-bit LBA, so this driver's READ(10)/WRITE(10) can address
+                // every block it reported.
+                let lba32 = sector_lba as u32;
+                cdbs[n] = [
+                    if write { 0x2Au8 } else { 0x28 },
+                    0,
+                    (lba32 >> 24) as u8,
+                    (lba32 >> 16) as u8,
+                    (lba32 >> 8) as u8,
+                    lba32 as u8,
+                    0,
+                    (sectors >> 8) as u8,
+                    sectors as u8,
+                    0,
+                ];
+                if let Host::From(src) = &host {
+                    let offset = next as usize * HOST_BLOCK as usize;
+                    let bytes = batch as usize * HOST_BLOCK as usize;
+                    dma.copy_from(dev.block + MSC_DATA + n * slice, &src[offset..offset + bytes]);
+                }
+                batches[n] = (next, batch);
+                next += batch;
+                n += 1;
             }
 
-            match self.scsi(dev, &cdb, 10, data_phys, bytes as u32, !write, until) {
-                Scsi::Ok { delivered } if delivered as usize == bytes => {}
-                // Short of what was asked, and reported as success. Nothing
-                // above here has a way to say "these blocks arrived and those
-                // did not", so a partial transfer is a failed one.
-                Scsi::Ok { delivered } => {
-                    log!("usb-storage: {delivered} of {bytes} B at block {}", lba + done as u64);
-                    return false;
+            let mut cmds: [Command<'_>; UAS_DEPTH] = core::array::from_fn(|i| {
+                let bytes = batches[i].1 * HOST_BLOCK;
+                Command::new(&cdbs[i], data_phys + (i * slice) as u64, bytes, !write)
+            });
+            self.scsi_all(dev, &mut cmds[..n], until);
+
+            for (i, cmd) in cmds[..n].iter().enumerate() {
+                let (first, batch) = batches[i];
+                let bytes = batch as usize * HOST_BLOCK as usize;
+                match cmd.outcome.unwrap_or(Scsi::Broken) {
+                    Scsi::Ok { delivered } if delivered as usize == bytes => {}
+                    // Short of what was asked, and reported as success. Nothing
+                    // above here has a way to say "these blocks arrived and
+                    // those did not", so a partial transfer is a failed one.
+                    Scsi::Ok { delivered } => {
+                        log!("usb-storage: {delivered} of {bytes} B at block {}",
+                            lba + first as u64);
+                        return false;
+                    }
+                    Scsi::Refused { key, asc, ascq } => {
+                        log_refusal(cmd.cdb, key, asc, ascq);
+                        return false;
+                    }
+                    Scsi::Broken => return false,
                 }
-                Scsi::Refused { key, asc, ascq } => {
-                    log_refusal(&cdb, key, asc, ascq);
-                    return false;
+                if let Host::Into(dst) = &mut host {
+                    let offset = first as usize * HOST_BLOCK as usize;
+                    dma.copy_to(dev.block + MSC_DATA + i * slice, &mut dst[offset..offset + bytes]);
                 }
-                Scsi::Broken => return false,
-            }
-
-            if let Host::Into(dst) = &mut host {
-                dma.copy_to(dev.block + MSC_DATA, &mut dst[offset..offset + bytes]);
             }
-            done += batch;
+            done = next;
         }
         true
     }
 
-    /// One SCSI command, with the transport's recovery applied and the command
-    /// re-issued over the transport that recovery gave back. `Scsi::Ok` means
-    /// the device reported success and moved `delivered` bytes.
+    /// SCSI commands, with the transport's recovery applied and the commands
+    /// re-issued over the transport that recovery gave back; each leaves with
+    /// an outcome, and `Scsi::Ok` means the device reported success and moved
+    /// `delivered` bytes. Bulk-Only runs them one after another and UAS all at
+    /// once, and the retry around both is this one.
     ///
     /// **Reset Recovery restores the transport and says nothing about the
     /// command**, so a driver that recovers and then reports failure has thrown
@@ -685,18 +853,13 @@ impl XhciController {
     /// batching, the retries and the recoveries take. The overshoot is the
     /// command in flight when the deadline passes, which the transfer bound
     /// covers.
-    #[allow(clippy::too_many_arguments)]
-    fn scsi(
-        &mut self,
-        dev: &mut MscDevice,
-        cdb: &[u8],
-        cdb_len: u8,
-        data_phys: u64,
-        data_len: u32,
-        data_in: bool,
-        until: Deadline,
-    ) -> Scsi {
-        let opcode = cdb.first().copied().unwrap_or(0);
+    ///
+    /// **A command that finished keeps its outcome across a re-issue**, which
+    /// only matters to UAS: a batch that broke on one tag still has the others'
+    /// answers, and sending those again would be a second WRITE of blocks the
+    /// device has already said it wrote.
+    fn scsi_all(&mut self, dev: &mut MscDevice, cmds: &mut [Command<'_>], until: Deadline) {
+        let opcode = cmds.first().and_then(|c| c.cdb.first()).copied().unwrap_or(0);
         // Which disk this command is on, in every line the retry writes. A
         // machine that boots off USB carries at least two — the stick it
         // started from and whatever else is plugged in — and an unnamed
@@ -708,33 +871,70 @@ impl XhciController {
             if until.reached(crate::clock::now()) {
                 log!("usb-storage: {slot} SCSI {opcode:#04x} not issued: {}",
                     crate::block::OPERATION);
-                return Scsi::Broken;
+                return broken(cmds);
             }
-            match self.bot(dev, cdb, cdb_len, data_phys, data_len, data_in) {
-                Ok(Bot::Done { delivered }) => {
+            match self.attempt(dev, cmds) {
+                Ok(()) => {
                     if attempt > 1 {
                         log!("usb-storage: {slot} SCSI {opcode:#04x} completed on attempt \
                              {attempt}");
                     }
-                    return Scsi::Ok { delivered };
-                }
-                Ok(Bot::Failed) => {
-                    let (key, asc, ascq) = self.request_sense(dev);
-                    return Scsi::Refused { key, asc, ascq };
+                    return;
                 }
                 Err(broke) => {
                     log!("usb-storage: {slot} transport broke on SCSI {opcode:#04x}: {broke}");
                     if !self.reset_recovery(dev) {
                         log!("usb-storage: {slot} reset recovery failed; disk is offline");
                         dev.failed = true;
-                        return Scsi::Broken;
+                        return broken(cmds);
                     }
                 }
             }
         }
         log!("usb-storage: {slot} SCSI {opcode:#04x} broke {MAX_TRANSPORT_ATTEMPTS} times \
              running; the transport is not coming back on its own");
-        Scsi::Broken
+        broken(cmds)
+    }
+
+    /// One SCSI command of [`Self::scsi_all`]'s.
+    #[allow(clippy::too_many_arguments)]
+    fn scsi(
+        &mut self,
+        dev: &mut MscDevice,
+        cdb: &[u8],
+        cdb_len: u8,
+        data_phys: u64,
+        data_len: u32,
+        data_in: bool,
+        until: Deadline,
+    ) -> Scsi {
+        let mut cmd = [Command::new(&cdb[..cdb_len as usize], data_phys, data_len, data_in)];
+        self.scsi_all(dev, &mut cmd, until);
+        cmd[0].outcome.unwrap_or(Scsi::Broken)
+    }
+
+    /// One pass of the transport over every command without an outcome, with
+    /// no recovery: `Err` is the transport breaking under whichever was in
+    /// flight. A Bulk-Only refusal fetches its sense here, because the next
+    /// command would clear it.
+    fn attempt(&mut self, dev: &mut MscDevice, cmds: &mut [Command<'_>]) -> Result<(), Broke> {
+        if let Transport::Uas(mut uas) = dev.transport {
+            let ran = self.uas_run(&mut uas, &mut dev.ep0_ring, cmds);
+            dev.transport = Transport::Uas(uas);
+            return ran.map_err(Broke::Uas);
+        }
+        for cmd in cmds.iter_mut().filter(|c| c.outcome.is_none()) {
+            let (cdb_len, data_phys, data_len, data_in) =
+                (cmd.cdb.len() as u8, cmd.data_phys, cmd.data_len, cmd.data_in);
+            cmd.outcome = Some(match self.bot(dev, cmd.cdb, cdb_len, data_phys, data_len, data_in)? {
+                Bot::Done { delivered } => Scsi::Ok { delivered },
+                Bot::Failed => {
+                    let (key, asc, ascq) = self.request_sense(dev);
+                    Scsi::Refused { key, asc, ascq }
+                }
+            });
+        }
+        Ok(())
     }
 
     /// REQUEST SENSE, as (sense key, ASC, ASCQ) and zeroed if the device would
@@ -924,11 +1124,10 @@ impl XhciController {
         phys: u64,
         len: u32,
     ) -> Option<(u32, u32)> {
-        let (dci, ring) = if in_dir {
-            (dev.in_dci, &mut dev.in_ring)
-        } else {
-            (dev.out_dci, &mut dev.out_ring)
+        let Transport::Bot { in_ring, out_ring } = &mut dev.transport else {
+            unreachable!("only a Bulk-Only disk moves data through `bulk`");
         };
+        let (dci, ring) = if in_dir { (dev.in_dci, in_ring) } else { (dev.out_dci, out_ring) };
         let mut trb = Trb::ZERO;
         trb.param = phys;
         trb.status = len;
@@ -951,18 +1150,21 @@ impl XhciController {
     /// which command is legal is a property of the endpoint's state and nothing
     /// about that is per class. All this decides is which of the pair is meant.
     fn bulk_endpoint<'a>(dev: &'a mut MscDevice, in_dir: bool) -> Restart<'a> {
-        let (dci, ep_addr, ring_off) = if in_dir {
-            (dev.in_dci, dev.in_ep, MSC_IN_RING)
+        let Transport::Bot { in_ring, out_ring } = &mut dev.transport else {
+            unreachable!("only a Bulk-Only disk has a bulk pair to restart");
+        };
+        let (dci, ep_addr, ring) = if in_dir {
+            (dev.in_dci, dev.in_ep, in_ring)
         } else {
-            (dev.out_dci, dev.out_ep, MSC_OUT_RING)
+            (dev.out_dci, dev.out_ep, out_ring)
         };
         Restart {
             slot_id: dev.slot_id,
             ctx_block: dev.dev_block,
             dci,
             ep_addr,
-            ring_at: dev.block + ring_off,
-            ring: if in_dir { &mut dev.in_ring } else { &mut dev.out_ring },
+            stream: 0,
+            ring,
             ep0_ring: &mut dev.ep0_ring,
         }
     }
@@ -992,7 +1194,15 @@ impl XhciController {
     /// Everything this says to the device is [`Self::reset_the_device`], whose
     /// arguments are the two endpoints' quiesces — so there is no order of
     /// these three lines that speaks first.
+    ///
+    /// A UAS disk has its own, in [`Self::uas_recover`], for the same reason
+    /// and in the same order.
     fn reset_recovery(&mut self, dev: &mut MscDevice) -> bool {
+        if let Transport::Uas(mut uas) = dev.transport {
+            let recovered = self.uas_recover(&mut uas, &mut dev.ep0_ring);
+            dev.transport = Transport::Uas(uas);
+            return recovered;
+        }
         let owed_in = self.quiesce_endpoint(&mut Self::bulk_endpoint(dev, true));
         let owed_out = self.quiesce_endpoint(&mut Self::bulk_endpoint(dev, false));
         self.reset_the_device(dev, owed_in, owed_out)
@@ -1023,8 +1233,9 @@ impl XhciController {
     }
 }
 
-/// One mass-storage device's pool block and the two bulk rings its endpoint
-/// contexts name, as [`prepare`] left them.
+/// One mass-storage device's pool block and the rings its endpoint contexts
+/// name — a bulk pair, or a UAS interface's four pipes — as [`prepare`] left
+/// them.
 ///
 /// Carried from the Configure Endpoint act to the bind behind it rather than
 /// rebuilt there: `TrbRing::init` zeroes, and by then the memory is the
@@ -1035,12 +1246,15 @@ pub(in crate::drivers::xhci) struct MscRings {
     /// back.
     at: usize,
     block: usize,
-    in_ring: TrbRing,
-    out_ring: TrbRing,
+    transport: Transport,
+    /// The disk this block held before S3, for `bind` to give its number back
+    /// to if this is the same disk.
+    slept: Option<Disk>,
 }
 
-/// Claim a pool block for this device and write its two bulk endpoints into the
-/// input context, ready for the Configure Endpoint the sequence issues.
+/// Claim a pool block for this device and write its bulk endpoints into the
+/// input context, ready for the Configure Endpoint the sequence issues: two for
+/// Bulk-Only, four for UAS with a stream array behind three of them.
 ///
 /// **Claimed before the endpoints are configured** and released only by the
 /// teardown that disables this slot, so the block cannot be reissued while the
@@ -1048,54 +1262,75 @@ pub(in crate::drivers::xhci) struct MscRings {
 /// and is a different question: it counts the disks that *finished*.
 ///
 /// `None` when the pool is out, which is a refusal and not a failure — nothing
-/// is spent, because nothing was handed out.
+/// is spent, because nothing was handed out. Also for a UAS disk whose streams
+/// are too few to drive, whose block stays claimed until the unplug as every
+/// refused disk's does.
 pub(in crate::drivers::xhci) fn prepare(
     ctrl: &mut XhciController,
     slot_id: u8,
+    dev_block: usize,
     speed: u8,
     port_idx: u8,
     info: &MscInterface,
 ) -> Option<MscRings> {
-    let Some((at, block)) = ctrl.claim_msc_block(port_idx) else {
+    let Some((at, block, slept)) = ctrl.claim_msc_block(port_idx) else {
         log!("usb-storage: slot {slot_id} is the {}th disk; this driver serves {}",
             ctrl.msc_blocks_taken() + 1, super::super::MSC_BLOCKS);
         return None;
     };
 
-    let (in_dci, out_dci) = (info.in_ep.dci(), info.out_ep.dci());
     let dma = ctrl.dma();
-    let in_ring = TrbRing::init(dma.subview(block + MSC_IN_RING, PAGE));
-    let out_ring = TrbRing::init(dma.subview(block + MSC_OUT_RING, PAGE));
+    let (transport, contexts, n) = match info.uas {
+        None => {
+            let in_ring = TrbRing::init(dma.subview(block + MSC_IN_RING, PAGE));
+            let out_ring = TrbRing::init(dma.subview(block + MSC_OUT_RING, PAGE));
+            let bulk = |ep: &Endpoint, ep_type, ring: &TrbRing| uas::Context {
+                dci: ep.dci(),
+                ep_type,
+                max_packet: ep.max_packet,
+                max_burst: ep.max_burst,
+                streams: 0,
+                dequeue: ring.dequeue(),
+            };
+            let out = bulk(&info.out_ep, 2, &out_ring);
+            let contexts = [out, bulk(&info.in_ep, 6, &in_ring), out, out];
+            (Transport::Bot { in_ring, out_ring }, contexts, 2)
+        }
+        Some(pipes) => {
+            let (device, contexts) =
+                UasDevice::build(ctrl, slot_id, dev_block, block, info, pipes)?;
+            (Transport::Uas(device), contexts, 4)
+        }
+    };
+    let contexts = &contexts[..n];
 
     let input_ctx = super::super::zero_dma(dma, OFF_INPUT_CTX, PAGE);
-    ctrl.write_ctx32(input_ctx, 0, 1, 1 | (1u32 << in_dci) | (1u32 << out_dci));
-    let max_dci = in_dci.max(out_dci) as u32;
-    ctrl.write_ctx32(input_ctx, 1, 0, ((speed as u32) << 20) | (max_dci << 27));
-    ctrl.write_ctx32(input_ctx, 1, 1, (port_idx as u32 + 1) << 16);
+    let added = contexts.iter().fold(1u32, |mask, c| mask | (1u32 << c.dci));
+    ctrl.write_ctx32(input_ctx, 0, 1, added);
+    let max_dci = contexts.iter().map(|c| c.dci).max().unwrap_or(0);
+    ctrl.write_slot_context(input_ctx, port_idx, speed, max_dci, None);
 
     // EP Type 2 is Bulk Out and 6 is Bulk In; CErr 3 is the retry count the
     // controller applies before reporting a transaction error. Average TRB
     // Length is advisory — the controller uses it for bandwidth bookkeeping —
     // and the endpoint's own maximum packet size is the honest answer for a
-    // driver that issues one TRB per transfer.
-    for (dci, ep_type, mps, burst, ring) in [
-        (out_dci, 2u32, info.out_ep.max_packet, info.out_ep.max_burst, &out_ring),
-        (in_dci, 6u32, info.in_ep.max_packet, info.in_ep.max_burst, &in_ring),
-    ] {
-        let ctx = dci as usize + 1;
-        ctrl.write_ctx32(input_ctx, ctx, 0, 0);
+    // driver that issues one TRB per transfer. A UAS pipe with streams has
+    // MaxPStreams and LSA in dword 0 and its stream array where the ring would
+    // be.
+    for c in contexts {
+        let ctx = c.dci as usize + 1;
+        ctrl.write_ctx32(input_ctx, ctx, 0, c.streams);
         ctrl.write_ctx32(
             input_ctx,
             ctx,
             1,
-            (3 << 1) | (ep_type << 3) | ((burst as u32) << 8) | ((mps as u32) << 16),
+            (3 << 1) | (c.ep_type << 3) | ((c.max_burst as u32) << 8) | ((c.max_packet as u32) << 16),
         );
-        let dequeue = ring.dequeue();
-        ctrl.write_ctx32(input_ctx, ctx, 2, dequeue as u32);
-        ctrl.write_ctx32(input_ctx, ctx, 3, (dequeue >> 32) as u32);
-        ctrl.write_ctx32(input_ctx, ctx, 4, mps as u32);
+        ctrl.write_ctx32(input_ctx, ctx, 2, c.dequeue as u32);
+        ctrl.write_ctx32(input_ctx, ctx, 3, (c.dequeue >> 32) as u32);
+        ctrl.write_ctx32(input_ctx, ctx, 4, c.max_packet as u32);
     }
-    Some(MscRings { at, block, in_ring, out_ring })
+    Some(MscRings { at, block, transport, slept })
 }
 
 /// Ask the disk what it is, and register it if the answer is one this driver
@@ -1122,7 +1357,7 @@ pub(in crate::drivers::xhci) fn bind(
     rings: MscRings,
     info: &MscInterface,
 ) {
-    let MscRings { at, block, in_ring, out_ring } = rings;
+    let MscRings { at, block, transport, slept } = rings;
     let mut dev = MscDevice {
         slot_id,
         iface: info.iface_num,
@@ -1133,19 +1368,36 @@ pub(in crate::drivers::xhci) fn bind(
         block,
         dev_block,
         ep0_ring,
-        in_ring,
-        out_ring,
+        transport,
         tag: 0,
         logical_block_bytes: 0,
         sectors_per_block: 0,
         blocks: 0,
         failed: false,
         no_write_cache: false,
+        read_only: false,
+        unmap_sectors: 0,
     };
 
     if !bring_up(ctrl, &mut dev) {
+        if let Some(old) = slept {
+            log!("usb-storage: disk {} did not come back from the sleep; it is offline", old.index);
+        }
         return;
     }
+    // The same disk on the same port after S3 is the disk a mount is still
+    // holding, and gets its number back. Anything else on that port is a
+    // different disk whatever it is, and the old number goes offline.
+    match slept {
+        Some(old) if old.dev.geometry() == dev.geometry() => {
+            log!("usb-storage: disk {} back on slot {slot_id} after the sleep", old.index);
+            ctrl.msc[at].disk = Some(Disk { index: old.index, dev });
+            return;
+        }
+        Some(old) => log!("usb-storage: disk {} came back as a different disk after the sleep; \
+            it is offline and this one gets a number of its own", old.index),
+        None => {}
+    }
     // The machine-wide number, taken here because here is where there is a disk
     // to give one to: it is what `usb_storage::open` indexes by and what a mount
     // holds for its whole life, so it must not move when some other controller
@@ -1153,32 +1405,44 @@ pub(in crate::drivers::xhci) fn bind(
     let index = super::super::DISKS_BOUND.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
     log!(
         "usb-storage: disk {index} ready on slot {slot_id}, {} blocks of {} B \
-         ({} MiB), msc_block +{:#x}",
+         ({} MiB), msc_block +{:#x}{}{}",
         dev.blocks,
         dev.logical_block_bytes,
         dev.blocks * HOST_BLOCK as u64 / (1024 * 1024),
-        block
+        block,
+        if dev.unmap_sectors != 0 { ", UNMAP" } else { "" },
+        match &dev.transport {
+            Transport::Bot { .. } => "",
+            Transport::Uas(uas) if uas.streams() => ", UAS with streams",
+            Transport::Uas(_) => ", UAS",
+        }
     );
     ctrl.msc[at].disk = Some(Disk { index, dev });
 }
 
 /// TEST UNIT READY, INQUIRY and READ CAPACITY: everything between a configured
-/// interface and a disk with a size.
+/// interface and a disk with a size, and whether it takes UNMAP.
 fn bring_up(ctrl: &mut XhciController, dev: &mut MscDevice) -> bool {
     // Driving the transport rather than `scsi` here, for two reasons: a device
     // that answers NOT READY is expected rather than an error, so it must not
     // produce a log line per attempt, and the sense fetch that reports it is
-    // also what clears the condition on a stick still spinning up.
+    // also what clears the condition on a stick still spinning up. A UAS disk
+    // brings its sense in the status IU, and answers the first command after
+    // power-on with UNIT ATTENTION, which this loop absorbs the same way.
     let give_up = crate::clock::nanos_since_boot() + READY_BUDGET.nanos();
     let mut sense = (0u8, 0u8, 0u8);
     let mut ready = false;
     loop {
-        match ctrl.bot(dev, &[0x00u8; 6], 6, 0, 0, false) {
-            Ok(Bot::Done { .. }) => {
-                ready = true;
-                break;
-            }
-            Ok(Bot::Failed) => sense = ctrl.request_sense(dev),
+        let mut tur = [Command::new(&[0x00u8; 6], 0, 0, false)];
+        match ctrl.attempt(dev, &mut tur) {
+            Ok(()) => match tur[0].outcome {
+                Some(Scsi::Ok { .. }) => {
+                    ready = true;
+                    break;
+                }
+                Some(Scsi::Refused { key, asc, ascq }) => sense = (key, asc, ascq),
+                _ => {}
+            },
             Err(broke) => {
                 log!("usb-storage: slot {} broke on TEST UNIT READY: {broke}", dev.slot_id);
                 if !ctrl.reset_recovery(dev) {
@@ -1232,11 +1496,18 @@ fn bring_up(ctrl: &mut XhciController, dev: &mut MscDevice) -> bool {
         log!("usb-storage: slot {} would not answer INQUIRY", dev.slot_id);
         return false;
     }
+    // Type 0 is a disk. Type 5 is an MMC drive, a CD or DVD, which speaks the
+    // same READ CAPACITY(10) and READ(10) in 2048-byte blocks and is served
+    // read-only; every other type is a tape, a printer, a scanner.
     let peripheral = inquiry[0] & 0x1F;
-    if peripheral != 0 {
-        log!("usb-storage: slot {} is SCSI peripheral type {peripheral:#04x}, not a disk",
-            dev.slot_id);
-        return false;
+    match peripheral {
+        0x00 => {}
+        0x05 => dev.read_only = true,
+        _ => {
+            log!("usb-storage: slot {} is SCSI peripheral type {peripheral:#04x}, not a disk",
+                dev.slot_id);
+            return false;
+        }
     }
     log!("usb-storage: slot {} vendor {} product {}", dev.slot_id,
         Printable(&inquiry[8..16]), Printable(&inquiry[16..32]));
@@ -1292,6 +1563,10 @@ fn bring_up(ctrl: &mut XhciController, dev: &mut MscDevice) -> bool {
     }
     let sectors = last_lba + 1;
     let sectors_per_block = HOST_BLOCK / block_bytes;
+    // A disc with an odd number of 2048-byte sectors loses its last one here:
+    // this driver serves whole 4 KiB blocks, and a volume that ends in that
+    // sector is refused as truncated by whatever mounts it rather than read
+    // short.
     let blocks = sectors / sectors_per_block as u64;
     if blocks == 0 {
         log!("usb-storage: slot {} holds {sectors} sectors of {block_bytes} B, less \
@@ -1302,9 +1577,71 @@ fn bring_up(ctrl: &mut XhciController, dev: &mut MscDevice) -> bool {
     dev.logical_block_bytes = block_bytes;
     dev.sectors_per_block = sectors_per_block;
     dev.blocks = blocks;
+    dev.unmap_sectors = probe_unmap(ctrl, dev, inquiry[2]);
     true
 }
 
+/// Bytes of an UNMAP parameter list with one block descriptor.
+const UNMAP_LIST_BYTES: usize = 24;
+
+/// Sectors one UNMAP may name on this disk, or zero if it is not to be sent
+/// one.
+///
+/// **Asked only of a disk that claims logical block provisioning**, which is
+/// two questions and not one: READ CAPACITY(16)'s LBPME bit says the disk
+/// provisions at all, and the Block Limits VPD page says how much one UNMAP may
+/// carry. An older stick is never asked either, because SPC-3 is where both
+/// arrive and a BOT device handed a command it has never heard of is as likely
+/// to wedge its transport as to refuse it. A refusal of either question is the
+/// disk saying no, so it is not logged as one; the bring-up line says `UNMAP`
+/// for the disks that said yes.
+///
+/// `version` is standard INQUIRY's byte 2, which `bring_up` already has.
+fn probe_unmap(ctrl: &mut XhciController, dev: &mut MscDevice, version: u8) -> u32 {
+    if dev.read_only || version < 5 {
+        return 0;
+    }
+    let mut cap16 = [0u8; 16];
+    let cdb = [0x9Eu8, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0, 0];
+    if !quiet_read(ctrl, dev, &cdb, 16, 32, &mut cap16) || cap16[14] & 0x80 == 0 {
+        return 0;
+    }
+    let mut limits = [0u8; 28];
+    if !quiet_read(ctrl, dev, &[0x12u8, 0x01, 0xB0, 0, 64, 0], 6, 64, &mut limits)
+        || limits[1] != 0xB0
+    {
+        return 0;
+    }
+    let sectors = u32::from_be_bytes([limits[20], limits[21], limits[22], limits[23]]);
+    let descriptors = u32::from_be_bytes([limits[24], limits[25], limits[26], limits[27]]);
+    if descriptors == 0 {
+        return 0;
+    }
+    sectors
+}
+
+/// One data-in command into the scratch block, with no caller's budget for
+/// `bring_up`'s reason and no line for a refusal.
+fn quiet_read(
+    ctrl: &mut XhciController,
+    dev: &mut MscDevice,
+    cdb: &[u8],
+    cdb_len: u8,
+    want: u32,
+    out: &mut [u8],
+) -> bool {
+    let dma = ctrl.dma();
+    let scratch_phys = dma.phys() + (dev.block + MSC_SCRATCH) as u64;
+    super::super::zero_dma(dma, dev.block + MSC_SCRATCH, MSC_SCRATCH_LEN);
+    match ctrl.scsi(dev, cdb, cdb_len, scratch_phys, want, true, Deadline::never()) {
+        Scsi::Ok { delivered } if delivered as usize >= out.len() => {
+            dma.copy_to(dev.block + MSC_SCRATCH, out);
+            true
+        }
+        _ => false,
+    }
+}
+
 /// A device-supplied ASCII field, rendered without letting it choose what the
 /// log looks like.
 struct Printable<'a>(&'a [u8]);
@@ -1341,3 +1678,7 @@ pub fn storage_flush(index: usize) -> bool {
     with_disk(index, |ctrl, local| ctrl.msc_flush(local)).unwrap_or(false)
 }
 
+pub fn storage_discard(index: usize, lba: u64, count: u32) -> bool {
+    with_disk(index, |ctrl, local| ctrl.msc_discard(local, lba, count)).unwrap_or(false)
+}
+
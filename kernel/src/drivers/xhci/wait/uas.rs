@@ -0,0 +1,597 @@
+//! USB Attached SCSI's transport: the four pipes, a ring per stream on three
+//! of them, and the effects of what [`toyos_xhci::uas`] decides.
+//!
+//! **The split is the recovery machine's.** Which tag is free, which transfer
+//! a tag still owes and whether an IU on the status pipe is one this driver
+//! can believe are all decided on the host side, where the tests are; what is
+//! here writes IUs, enqueues TRBs, rings doorbells and reads completions back
+//! into that machine. A UAS disk is otherwise an [`msc`](super::msc) disk: the
+//! same pool block, the same SCSI above it, and the same retry policy around
+//! every command — this file is one of the two things that policy retries.
+//!
+//! **Everything here blocks**, for `msc`'s reason: a disk is spoken to from
+//! `storage_read` and `storage_write`, on the thread that faulted, which is
+//! spending its own time. What UAS changes is how many commands that thread has
+//! on the wire while it waits, not whether it waits.
+
+use toyos_xhci::recovery;
+use toyos_xhci::uas::{self as tagged, Act, Data, Uas, Verdict};
+
+use crate::log;
+use super::msc::{Command, MscInterface, Scsi, UasPipes};
+use super::{Owed, Restart};
+use super::super::{zero_dma, Trb, TrbRing, XhciController, PAGE};
+use super::super::{CC_SHORT_PACKET, CC_SUCCESS, TRB_NORMAL};
+use super::super::{STREAM_RING_SIZE, UAS_COMMAND_RING, UAS_DEPTH, UAS_IU, UAS_IU_STRIDE};
+use super::super::{UAS_MAX_ENTRIES, UAS_RINGS, UAS_STATUS, UAS_STATUS_STRIDE};
+use super::super::{UAS_STREAM_CTX, UAS_STREAM_CTX_STRIDE, UAS_STREAM_RINGS};
+
+/// Transfers one disk can have outstanding: a command, a status receive and a
+/// data transfer per tag, which bounds every other count here.
+const OWING: usize = 3 * UAS_RINGS;
+
+/// Why a UAS attempt could not be completed, for the line
+/// [`msc`](super::msc)'s retry writes before recovering.
+#[derive(Clone, Copy)]
+pub(super) enum Broke {
+    /// The IUs contradicted what is in flight — see [`tagged::Broke`].
+    Transport(tagged::Broke),
+    /// The controller reported this completion code on the named pipe.
+    Code { pipe: &'static str, code: u32 },
+    /// Nothing in flight completed inside the transfer budget.
+    Silence,
+    /// A data transfer the device is never going to move could not be taken
+    /// back off its stream.
+    Reclaim { tag: u16 },
+}
+
+impl core::fmt::Display for Broke {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(broke) => write!(f, "UAS {broke:?}"),
+            Self::Code { pipe, code } => write!(f, "{pipe} pipe completion code {code}"),
+            Self::Silence => write!(
+                f,
+                "nothing in flight answered in {} ms",
+                super::super::USB_TIMEOUT_NS / 1_000_000
+            ),
+            Self::Reclaim { tag } => write!(f, "tag {tag}'s data could not be reclaimed"),
+        }
+    }
+}
+
+impl From<tagged::Broke> for Broke {
+    fn from(broke: tagged::Broke) -> Self {
+        Self::Transport(broke)
+    }
+}
+
+/// One of the three pipes with a ring per stream. Without streams only
+/// `rings[0]` is used, and it is the endpoint's one ring.
+#[derive(Clone, Copy)]
+struct Pipe {
+    addr: u8,
+    dci: u8,
+    rings: [TrbRing; UAS_RINGS],
+}
+
+/// Which transfer an outstanding TRB is.
+#[derive(Clone, Copy)]
+enum Leg {
+    Command { tag: u16 },
+    Status { stream: u16 },
+    Data { tag: u16 },
+}
+
+#[derive(Clone, Copy)]
+struct Owing {
+    dci: u8,
+    trb: u64,
+    leg: Leg,
+}
+
+/// What one tag was begun for: the command of the caller's batch it carries —
+/// `None` for the task management tag — and that command's data window.
+#[derive(Clone, Copy)]
+struct Issued {
+    index: Option<usize>,
+    data_phys: u64,
+    iu_len: u32,
+}
+
+/// What one endpoint context of a UAS interface is written with, for
+/// [`super::msc::prepare`], which writes all four.
+#[derive(Clone, Copy)]
+pub(super) struct Context {
+    pub(super) dci: u8,
+    pub(super) ep_type: u32,
+    pub(super) max_packet: u16,
+    pub(super) max_burst: u8,
+    /// MaxPStreams and LSA, already in place for dword 0.
+    pub(super) streams: u32,
+    pub(super) dequeue: u64,
+}
+
+/// One UAS interface's transport. `Copy` for [`super::msc::MscDevice`]'s
+/// reason, which it is a field of.
+#[derive(Clone, Copy)]
+pub(super) struct UasDevice {
+    slot_id: u8,
+    /// The device block, whose output context carries the pipes' states.
+    dev_block: usize,
+    /// This disk's mass-storage pool block.
+    block: usize,
+    command_addr: u8,
+    command_dci: u8,
+    command_ring: TrbRing,
+    status: Pipe,
+    data_in: Pipe,
+    data_out: Pipe,
+    machine: Uas,
+    owing: [Option<Owing>; OWING],
+    /// Index `tag - 1`.
+    issued: [Option<Issued>; UAS_RINGS],
+    /// Tags whose data transfer [`Act::Reclaim`] asked to take back, done
+    /// once nothing else is on the pipes.
+    reclaim: [bool; UAS_RINGS],
+}
+
+impl UasDevice {
+    /// Lay the interface's rings and stream arrays out in `block` and say how
+    /// its endpoint contexts are to be written. `None` for a disk whose pipes
+    /// have too few streams for a command and a task at once.
+    ///
+    /// **Entries are the smallest of three numbers**: the streams the pipes
+    /// offer, the array the controller takes, and [`UAS_MAX_ENTRIES`]. Entry 0
+    /// is reserved and the task management tag needs a stream of its own, so
+    /// the depth is the entries less two.
+    pub(super) fn build(
+        ctrl: &XhciController,
+        slot_id: u8,
+        dev_block: usize,
+        block: usize,
+        info: &MscInterface,
+        pipes: UasPipes,
+    ) -> Option<(Self, [Context; 4])> {
+        let offered = pipes.status.max_streams.min(info.in_ep.max_streams).min(info.out_ep.max_streams);
+        let entries = match offered {
+            0 => 0,
+            n => (1usize << n.min(15)).min(ctrl.stream_entries()).min(UAS_MAX_ENTRIES),
+        };
+        if pipes.status.max_streams != 0 && entries < 4 {
+            log!("usb-storage: slot {slot_id}'s UAS pipes offer {entries} stream context \
+                 entries on this controller; a command and a task need 4");
+            return None;
+        }
+        let streams = entries != 0;
+        let depth = if streams { (entries - 2).min(UAS_DEPTH) } else { UAS_DEPTH };
+
+        let dma = ctrl.dma();
+        let ring_bytes = STREAM_RING_SIZE * core::mem::size_of::<Trb>();
+        let pipe = |n: usize, ep: &super::super::device::Endpoint| Pipe {
+            addr: ep.addr,
+            dci: ep.dci(),
+            rings: core::array::from_fn(|i| {
+                let at = block + UAS_STREAM_RINGS + (n * UAS_RINGS + i) * ring_bytes;
+                TrbRing::init(dma.subview(at, ring_bytes))
+            }),
+        };
+        let dev = Self {
+            slot_id,
+            dev_block,
+            block,
+            command_addr: pipes.command.addr,
+            command_dci: pipes.command.dci(),
+            command_ring: TrbRing::init(dma.subview(block + UAS_COMMAND_RING, PAGE)),
+            status: pipe(0, &pipes.status),
+            data_in: pipe(1, &info.in_ep),
+            data_out: pipe(2, &info.out_ep),
+            machine: Uas::new(streams, depth as u8),
+            owing: [None; OWING],
+            issued: [None; UAS_RINGS],
+            reclaim: [false; UAS_RINGS],
+        };
+
+        // A Primary Stream Context Array per streamed pipe, entry `stream`
+        // naming ring `stream - 1` as a primary ring (SCT 1). Entries past the
+        // rings stay zero and are never a tag's.
+        let (max_pstreams, lsa) = match entries {
+            0 => (0, 0),
+            n => (n.trailing_zeros() - 1, 1),
+        };
+        let mut dequeue = [0u64; 3];
+        for (n, p) in [&dev.status, &dev.data_in, &dev.data_out].into_iter().enumerate() {
+            if !streams {
+                dequeue[n] = p.rings[0].dequeue();
+                continue;
+            }
+            let array = zero_dma(dma, block + UAS_STREAM_CTX + n * UAS_STREAM_CTX_STRIDE,
+                UAS_STREAM_CTX_STRIDE);
+            for (i, ring) in p.rings.iter().enumerate().take(entries - 1) {
+                array.write::<u64>((i + 1) * 16, ring.dequeue() | (1 << 1));
+            }
+            dequeue[n] = array.phys();
+        }
+        let streamed = (max_pstreams << 10) | (lsa << 15);
+        let context = |ep: &super::super::device::Endpoint, ep_type, streams, dequeue| Context {
+            dci: ep.dci(),
+            ep_type,
+            max_packet: ep.max_packet,
+            max_burst: ep.max_burst,
+            streams,
+            dequeue,
+        };
+        // EP Type 2 is Bulk Out and 6 is Bulk In, as for Bulk-Only.
+        let contexts = [
+            context(&pipes.command, 2, 0, dev.command_ring.dequeue()),
+            context(&pipes.status, 6, streamed, dequeue[0]),
+            context(&info.in_ep, 6, streamed, dequeue[1]),
+            context(&info.out_ep, 2, streamed, dequeue[2]),
+        ];
+        Some((dev, contexts))
+    }
+
+    /// Commands this interface has in flight at once.
+    pub(super) fn depth(&self) -> u8 {
+        self.machine.depth()
+    }
+
+    pub(super) fn streams(&self) -> bool {
+        self.machine.streams()
+    }
+
+    /// The ring a stream's transfers go on: stream `n` is ring `n - 1`, and a
+    /// pipe without streams has one.
+    fn ring_index(stream: u16) -> usize {
+        (stream as usize).saturating_sub(1)
+    }
+
+    fn data_pipe(&mut self, tag: u16) -> &mut Pipe {
+        match self.machine.data(tag) {
+            Some(Data::Out(_)) => &mut self.data_out,
+            _ => &mut self.data_in,
+        }
+    }
+
+    fn owe(&mut self, dci: u8, trb: u64, leg: Leg) {
+        let free = self.owing.iter().position(Option::is_none);
+        // Bounded by construction: a tag owes at most one of each leg.
+        self.owing[free.expect("a UAS disk owes at most three transfers per tag")] =
+            Some(Owing { dci, trb, leg });
+    }
+
+    fn carrying(&self, index: usize) -> bool {
+        self.issued.iter().flatten().any(|i| i.index == Some(index))
+    }
+
+    /// Nothing on any pipe but the data transfers waiting to be reclaimed,
+    /// which is the one state a pipe may be stopped in without an answer for
+    /// somebody else's tag arriving during the stop and being dropped.
+    fn quiet_but_reclaims(&self) -> bool {
+        self.reclaim.iter().any(|&r| r)
+            && self.owing.iter().flatten().all(|o| match o.leg {
+                Leg::Data { tag } => self.reclaim[tag as usize - 1],
+                _ => false,
+            })
+    }
+
+    /// Forget everything in flight, with the rings it was on.
+    fn abandon(&mut self) {
+        self.machine.abandon();
+        self.owing = [None; OWING];
+        self.issued = [None; UAS_RINGS];
+        self.reclaim = [false; UAS_RINGS];
+    }
+}
+
+/// One Normal TRB, with ISP so a short transfer reports as one and IOC so it
+/// reports at all.
+fn normal(phys: u64, len: u32) -> Trb {
+    let mut trb = Trb::ZERO;
+    trb.param = phys;
+    trb.status = len;
+    trb.control = TRB_NORMAL | (1 << 5) | (1 << 2);
+    trb
+}
+
+impl XhciController {
+    /// Run every command of `cmds` that has no outcome yet, up to the disk's
+    /// depth at a time, until each has one.
+    ///
+    /// `Err` is the transport and says nothing about the commands that had
+    /// finished by then, which keep their outcomes: [`super::msc`]'s retry
+    /// recovers and calls this again, and only the rest go back out.
+    pub(super) fn uas_run(
+        &mut self,
+        uas: &mut UasDevice,
+        ep0_ring: &mut TrbRing,
+        cmds: &mut [Command<'_>],
+    ) -> Result<(), Broke> {
+        loop {
+            // Start whatever there is a tag for, unless a reclaim is waiting
+            // for the pipes to go quiet — a new command would keep them busy.
+            while !uas.reclaim.iter().any(|&r| r) {
+                let Some(index) =
+                    (0..cmds.len()).find(|&i| cmds[i].outcome.is_none() && !uas.carrying(i))
+                else {
+                    break;
+                };
+                let cmd = &cmds[index];
+                let data = match (cmd.data_len, cmd.data_in) {
+                    (0, _) => Data::None,
+                    (n, true) => Data::In(n),
+                    (n, false) => Data::Out(n),
+                };
+                let Some((tag, acts)) = uas.machine.begin(data) else { break };
+                let iu = tagged::command_iu(tag, cmd.cdb);
+                self.dma().copy_from(uas.block + UAS_IU + (tag as usize - 1) * UAS_IU_STRIDE, &iu);
+                uas.issued[tag as usize - 1] = Some(Issued {
+                    index: Some(index),
+                    data_phys: cmd.data_phys,
+                    iu_len: tagged::COMMAND_IU_BYTES as u32,
+                });
+                for act in acts {
+                    self.uas_act(uas, act);
+                }
+            }
+            while let Some((tag, verdict)) = uas.machine.finished() {
+                let issued = uas.issued[tag as usize - 1].take();
+                let Some(index) = issued.and_then(|i| i.index) else {
+                    return Err(tagged::Broke::Stray { tag }.into());
+                };
+                cmds[index].outcome = Some(match verdict {
+                    Verdict::Good { moved } => Scsi::Ok { delivered: moved },
+                    Verdict::Refused { sense: Some(s), .. } => {
+                        Scsi::Refused { key: s.key, asc: s.asc, ascq: s.ascq }
+                    }
+                    // BUSY, TASK SET FULL, or CHECK CONDITION with no sense:
+                    // a refusal with nothing to say why, which falls on the
+                    // failing side of every decision sense bytes make.
+                    Verdict::Refused { status, sense: None } => {
+                        log!("usb-storage: {} answered SCSI status {status:#04x} on tag {tag}",
+                            self.slot(uas.slot_id));
+                        Scsi::Refused { key: 0, asc: 0, ascq: 0 }
+                    }
+                    Verdict::Task { .. } => return Err(tagged::Broke::Stray { tag }.into()),
+                });
+            }
+            if uas.machine.idle() && cmds.iter().all(|c| c.outcome.is_some()) {
+                return Ok(());
+            }
+            self.uas_step(uas, ep0_ring)?;
+        }
+    }
+
+    /// One act of the machine's, on the rings.
+    fn uas_act(&mut self, uas: &mut UasDevice, act: Act) {
+        let dma = self.dma();
+        let slot = uas.slot_id;
+        match act {
+            Act::ReceiveStatus { stream } => {
+                let at = uas.block + UAS_STATUS + UasDevice::ring_index(stream) * UAS_STATUS_STRIDE;
+                zero_dma(dma, at, tagged::STATUS_IU_BYTES);
+                let ring = &mut uas.status.rings[UasDevice::ring_index(stream)];
+                let trb = ring.enqueue(normal(dma.phys() + at as u64, tagged::STATUS_IU_BYTES as u32));
+                let dci = uas.status.dci;
+                uas.owe(dci, trb, Leg::Status { stream });
+                self.ring_stream_doorbell(slot, dci, stream);
+            }
+            Act::QueueData { tag, stream } => {
+                let len = match uas.machine.data(tag) {
+                    Some(Data::In(n) | Data::Out(n)) => n,
+                    _ => return,
+                };
+                let phys = uas.issued[tag as usize - 1].map_or(0, |i| i.data_phys);
+                let pipe = uas.data_pipe(tag);
+                let dci = pipe.dci;
+                let trb = pipe.rings[UasDevice::ring_index(stream)].enqueue(normal(phys, len));
+                uas.owe(dci, trb, Leg::Data { tag });
+                self.ring_stream_doorbell(slot, dci, stream);
+            }
+            Act::SendCommand { tag } => {
+                let at = uas.block + UAS_IU + (tag as usize - 1) * UAS_IU_STRIDE;
+                let len = uas.issued[tag as usize - 1].map_or(0, |i| i.iu_len);
+                let trb = uas.command_ring.enqueue(normal(dma.phys() + at as u64, len));
+                let dci = uas.command_dci;
+                uas.owe(dci, trb, Leg::Command { tag });
+                self.ring_doorbell(slot, dci);
+            }
+            Act::Reclaim { tag } => uas.reclaim[tag as usize - 1] = true,
+        }
+    }
+
+    /// Wait for one completion and tell the machine, or take back the data
+    /// transfers it asked to if that is all that is left on the pipes.
+    fn uas_step(&mut self, uas: &mut UasDevice, ep0_ring: &mut TrbRing) -> Result<(), Broke> {
+        if uas.quiet_but_reclaims() {
+            return self.uas_reclaim(uas, ep0_ring);
+        }
+        let mut on = [(0u8, 0u64); OWING];
+        let mut at = [0usize; OWING];
+        let mut n = 0;
+        for (i, owing) in uas.owing.iter().enumerate() {
+            if let Some(o) = owing {
+                on[n] = (o.dci, o.trb);
+                at[n] = i;
+                n += 1;
+            }
+        }
+        let (which, code, residue) =
+            self.wait_any_transfer(uas.slot_id, &on[..n]).ok_or(Broke::Silence)?;
+        let owing = uas.owing[at[which]].take().expect("the wait matched an outstanding TRB");
+        match owing.leg {
+            Leg::Command { tag } => match code {
+                CC_SUCCESS => uas.machine.sent(tag)?,
+                code => return Err(Broke::Code { pipe: "command", code }),
+            },
+            Leg::Status { stream } => match code {
+                // Short Packet is the ordinary case: the receive is sized for
+                // the longest IU and most are sixteen bytes.
+                CC_SUCCESS | CC_SHORT_PACKET => {
+                    let got = tagged::STATUS_IU_BYTES.saturating_sub(residue as usize);
+                    let mut bytes = [0u8; tagged::STATUS_IU_BYTES];
+                    let buf = uas.block + UAS_STATUS + UasDevice::ring_index(stream) * UAS_STATUS_STRIDE;
+                    self.dma().copy_to(buf, &mut bytes[..got]);
+                    let acts = uas.machine.status(stream, &bytes[..got])?;
+                    for act in acts {
+                        self.uas_act(uas, act);
+                    }
+                }
+                code => return Err(Broke::Code { pipe: "status", code }),
+            },
+            Leg::Data { tag } => match code {
+                CC_SUCCESS | CC_SHORT_PACKET => {
+                    let len = match uas.machine.data(tag) {
+                        Some(Data::In(n) | Data::Out(n)) => n,
+                        _ => 0,
+                    };
+                    uas.machine.moved(tag, len.saturating_sub(residue))?;
+                }
+                code => return Err(Broke::Code { pipe: "data", code }),
+            },
+        }
+        Ok(())
+    }
+
+    /// Take every data transfer the machine gave up on back off its stream.
+    ///
+    /// Only ever called with nothing else on the pipes (see
+    /// [`UasDevice::quiet_but_reclaims`]), because stopping an endpoint waits
+    /// for a command and the wait drops every transfer event it does not own —
+    /// including the next tag's answer, had one been outstanding.
+    fn uas_reclaim(&mut self, uas: &mut UasDevice, ep0_ring: &mut TrbRing) -> Result<(), Broke> {
+        for tag in 1..=UAS_RINGS as u16 {
+            if !uas.reclaim[tag as usize - 1] {
+                continue;
+            }
+            let stream = if uas.streams() { tag } else { 0 };
+            let (slot_id, ctx_block) = (uas.slot_id, uas.dev_block);
+            let pipe = uas.data_pipe(tag);
+            let restart = Restart {
+                slot_id,
+                ctx_block,
+                dci: pipe.dci,
+                ep_addr: pipe.addr,
+                stream,
+                ring: &mut pipe.rings[UasDevice::ring_index(stream)],
+                ep0_ring: &mut *ep0_ring,
+            };
+            if !self.restart_endpoint(restart) {
+                return Err(Broke::Reclaim { tag });
+            }
+            uas.owing.iter_mut().for_each(|o| {
+                if matches!(o, Some(Owing { leg: Leg::Data { tag: t }, .. }) if *t == tag) {
+                    *o = None;
+                }
+            });
+            uas.reclaim[tag as usize - 1] = false;
+            uas.machine.reclaimed(tag)?;
+        }
+        Ok(())
+    }
+
+    /// The transport back to a state that runs commands, after an attempt
+    /// broke: every pipe off its transfers with every ring rebuilt, every tag
+    /// forgotten, the halts cleared, and a LOGICAL UNIT RESET for whatever the
+    /// device still holds.
+    ///
+    /// **The pipes go first for Bulk-Only's reason** (see
+    /// `XhciController::reset_recovery`): a transfer the driver stopped
+    /// waiting for is still the device's to answer, and a task management IU
+    /// sent into that window is answered on a stream the driver has already
+    /// given to somebody else. [`Owed`] keeps the order here as it does there.
+    ///
+    /// The reset's own UNIT ATTENTION is taken by a TEST UNIT READY before
+    /// this returns, so the command the caller re-issues is answered about
+    /// itself rather than about the recovery.
+    pub(super) fn uas_recover(&mut self, uas: &mut UasDevice, ep0_ring: &mut TrbRing) -> bool {
+        let (slot_id, ctx_block) = (uas.slot_id, uas.dev_block);
+        let mut owed = [Owed::Nothing; 4];
+        owed[0] = self.quiesce_endpoint(&mut Restart {
+            slot_id,
+            ctx_block,
+            dci: uas.command_dci,
+            ep_addr: uas.command_addr,
+            stream: 0,
+            ring: &mut uas.command_ring,
+            ep0_ring: &mut *ep0_ring,
+        });
+        let streams = uas.streams();
+        for (n, pipe) in [&mut uas.status, &mut uas.data_in, &mut uas.data_out].into_iter().enumerate() {
+            let [first, rest @ ..] = &mut pipe.rings;
+            owed[n + 1] = self.quiesce_endpoint(&mut Restart {
+                slot_id,
+                ctx_block,
+                dci: pipe.dci,
+                ep_addr: pipe.addr,
+                stream: u16::from(streams),
+                ring: first,
+                ep0_ring: &mut *ep0_ring,
+            });
+            // The quiesce rebuilt one stream's ring and left the endpoint
+            // Stopped, which is the state Set TR Dequeue is legal in for the
+            // rest of them.
+            if !streams || matches!(owed[n + 1], Owed::Failed) {
+                continue;
+            }
+            for (i, ring) in rest.iter_mut().enumerate() {
+                let cmd = recovery::Command::SetDequeue;
+                let trb = self.recovery_trb(cmd, slot_id, pipe.dci, i as u16 + 2, ring);
+                if !self.run_command(trb, cmd.name()) {
+                    owed[n + 1] = Owed::Failed;
+                    break;
+                }
+            }
+        }
+        uas.abandon();
+        let mut cleared = true;
+        for owed in owed {
+            cleared &= self.clear_endpoint_halt(slot_id, ep0_ring, owed);
+        }
+        if !cleared {
+            return false;
+        }
+        self.uas_reset_unit(uas, ep0_ring)
+    }
+
+    /// LOGICAL UNIT RESET on the task management tag, and the TEST UNIT READY
+    /// that collects its UNIT ATTENTION.
+    fn uas_reset_unit(&mut self, uas: &mut UasDevice, ep0_ring: &mut TrbRing) -> bool {
+        let slot = self.slot(uas.slot_id);
+        let Some((tag, acts)) = uas.machine.begin_task() else { return false };
+        let iu = tagged::task_iu(tag, tagged::LOGICAL_UNIT_RESET, 0);
+        self.dma().copy_from(uas.block + UAS_IU + (tag as usize - 1) * UAS_IU_STRIDE, &iu);
+        uas.issued[tag as usize - 1] =
+            Some(Issued { index: None, data_phys: 0, iu_len: tagged::TASK_IU_BYTES as u32 });
+        for act in acts {
+            self.uas_act(uas, act);
+        }
+        let code = loop {
+            if let Some((_, verdict)) = uas.machine.finished() {
+                uas.issued[tag as usize - 1] = None;
+                break match verdict {
+                    Verdict::Task { code } => code,
+                    _ => return false,
+                };
+            }
+            if let Err(broke) = self.uas_step(uas, ep0_ring) {
+                log!("usb-storage: {slot} LOGICAL UNIT RESET broke: {broke}");
+                uas.abandon();
+                return false;
+            }
+        };
+        if !matches!(code, tagged::RESPONSE_COMPLETE | tagged::RESPONSE_SUCCEEDED) {
+            log!("usb-storage: {slot} answered LOGICAL UNIT RESET with response {code:#04x}");
+            return false;
+        }
+        let mut tur = [Command::new(&[0u8; 6], 0, 0, false)];
+        match self.uas_run(uas, ep0_ring, &mut tur) {
+            Ok(()) => true,
+            Err(broke) => {
+                log!("usb-storage: {slot} broke on the TEST UNIT READY after a reset: {broke}");
+                uas.abandon();
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! Linux partitions on disks this machine did not boot from, mounted read-only.
+//!
+//! `toyos-ext4` reads ext2, ext3 and ext4; this file is what that crate
+//! deliberately does not know about — the kernel's 4 KiB [`BlockDevice`], the
+//! partition a volume lives in, and [`vfs::FileSystem`].
+//!
+//! # Why a mount found by type is acceptable here
+//!
+//! Everything `fat32_adapter` says about choosing a partition is about a mount
+//! that writes. This one cannot, at three layers, and the claim is meant to be
+//! checkable by reading rather than by trusting:
+//!
+//! 1. **The crate.** `toyos_ext4::BlockAccess` has no write method, so there is
+//!    no function in `toyos-ext4` that could be reached to change a volume.
+//! 2. **This adapter.** [`Ext4Device`] reaches the device through
+//!    [`page_cache::read_bytes`] and nothing else, and has no `write_at`;
+//!    every [`FileSystem`] method that would change something answers
+//!    [`SyscallError::PermissionDenied`] without looking at its arguments.
+//! 3. **The VFS.** Each mount is `UserAccess::KernelOnly`, so a syscall that
+//!    would create, rename, delete or open for write is refused before it gets
+//!    here — which also keeps `mkdir`, a VFS-only operation no adapter is told
+//!    about, from inventing directories on the mount.
+//!
+//! With no write path, selecting a partition by its GPT type is a question of
+//! what is shown rather than of what is damaged: the worst a wrong pick does is
+//! list somebody's files under a name that says which disk they came from. The
+//! device carrying the boot partition is still skipped, because the two FAT32
+//! volumes on it are the handoff's, and a stick's own partitions are not
+//! "a Linux disk somebody plugged in".
+//!
+//! # Which bytes
+//!
+//! As in `fat32_adapter`: [`Ext4Device`] clamps every read to the volume
+//! before it reaches the device, and the volume is tightened from the partition
+//! to what the superblock describes before anything past the superblock is
+//! read. A crafted extent pointing a terabyte out is [`IoError`] here, not a
+//! read of a neighbour's partition.
+//!
+//! # What these mounts are not
+//!
+//! Not a way to write to a Linux disk. `toyos-ext4`'s own documentation says
+//! why a half-honest ext4 writer is worse than none, and nothing here works
+//! around it.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+
+use toyos_abi::syscall::SyscallError;
+use toyos_ext4::{BlockAccess, Error, Ext4, IoError};
+
+use crate::block::{BlockDevice, DeviceId};
+use crate::drivers::{ahci, usb_storage, virtio_blk};
+use crate::fat32_adapter;
+use crate::file_backing::FileBacking;
+use crate::file_cache::{self, FileId};
+use crate::gpt;
+use crate::page_cache;
+use crate::sync::Lock;
+use crate::vfs::FileSystem;
+
+/// The only transfer unit [`BlockDevice`] has.
+const BLOCK: u64 = 4096;
+
+/// Linux volumes mounted at once, across every disk.
+///
+/// A slot per mount rather than per device, since a disk with a root and a
+/// `/home` partition is the common case. Past this, further volumes are named
+/// in the log and not mounted.
+const MAX_MOUNTS: usize = 4;
+
+/// One partition as a byte range over a device that only does whole 4 KiB
+/// blocks, and only reads them.
+///
+/// Offsets are relative to the partition and nothing above this struct can
+/// name a byte outside it. There is no write method to clamp.
+///
+/// What stays resident is the page cache's: an ext4 volume's 1 KiB metadata
+/// blocks and the inode table the crate re-reads per path component sit four
+/// to a device block, and re-fetching the block for each is a USB round trip
+/// per 256 bytes of inode, which [`page_cache::read_bytes`] serves from the
+/// device's cache instead. Keeping them is sound because nothing writes these
+/// blocks while the mount exists — not this kernel, which cannot, and not the
+/// disk's owner, whose machine is somewhere else.
+struct Ext4Device {
+    device: DeviceId,
+    /// Where the partition starts, in bytes from the start of the device.
+    start: u64,
+    /// How many bytes the volume may address.
+    len: u64,
+}
+
+impl Ext4Device {
+    /// The device byte offset `offset` names, or [`IoError`] if the request
+    /// leaves the volume. Every read goes through here.
+    fn locate(&self, offset: u64, len: usize) -> Result<u64, IoError> {
+        let end = offset.checked_add(len as u64).ok_or(IoError)?;
+        if end > self.len {
+            return Err(IoError);
+        }
+        Ok(self.start + offset)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError> {
+        let at = self.locate(offset, buf.len())?;
+        page_cache::read_bytes(self.device, at, buf).map_err(|_| IoError)
+    }
+}
+
+/// Each mounted volume's device, reachable without the VFS lock.
+///
+/// Statics for `fat32_adapter::VOLUMES`'s reason: a [`FileBacking`] serves a
+/// page-fault miss with no filesystem in hand. Lock order is VFS → a mount's
+/// [`Shared`] → here → the device's page cache → `XHCI`, and no path holds two
+/// slots at once.
+static VOLUMES: [Lock<Option<Ext4Device>>; MAX_MOUNTS] =
+    [Lock::new(None), Lock::new(None), Lock::new(None), Lock::new(None)];
+
+/// One [`VOLUMES`] entry in the shape `toyos-ext4` asks for. Zero state beyond
+/// the slot and the capacity, as `FatVolume` is.
+pub struct Ext4Volume {
+    slot: usize,
+    bytes: u64,
+}
+
+impl BlockAccess for Ext4Volume {
+    fn capacity(&self) -> u64 {
+        self.bytes
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError> {
+        let mut guard = VOLUMES[self.slot].lock();
+        guard.as_mut().ok_or(IoError)?.read_at(offset, buf)
+    }
+}
+
+/// The mounted filesystem, shared between the VFS's adapter and every backing
+/// it hands out.
+///
+/// Shared rather than re-mounted per page: `Ext4::read` needs the parsed
+/// superblock and a scratch node buffer, and mounting again for each page
+/// fault is a superblock and a group descriptor read per 4 KiB of file.
+type Shared = Arc<Lock<Ext4<Ext4Volume>>>;
+
+/// A file on one of these volumes, read through the filesystem a page at a
+/// time.
+///
+/// Holds a copy of the inode rather than a list of byte ranges, unlike
+/// `FatBacking`: an ext4 file's map is a tree the crate walks, and flattening
+/// it at open is an allocation sized by the file's fragmentation for nothing
+/// the tree walk does not already give. The copy cannot go stale — see
+/// `toyos-ext4`'s `Shape`.
+struct Ext4Backing {
+    mount: String,
+    fs: Shared,
+    file: toyos_ext4::File,
+}
+
+impl FileBacking for Ext4Backing {
+    fn read_page(&self, file_offset: u64, buf: &mut [u8; 4096]) -> crate::block::BlockResult {
+        buf.fill(0);
+        match self.fs.lock().read(&self.file, file_offset, buf) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                buf.fill(0);
+                log!("{}: read of inode {} at {file_offset} failed: {e}; serving zeros",
+                    self.mount, self.file.inode());
+                Err(crate::block::BlockError)
+            }
+        }
+    }
+
+    fn file_size(&self) -> u64 {
+        self.file.len()
+    }
+}
+
+/// What one of `toyos-ext4`'s errors means to the [`FileSystem`] trait's
+/// caller.
+///
+/// Exhaustive, as `fat32_adapter`'s is, and for the same reason: the crate's
+/// [`Error`] is exhaustive so that this stops compiling when a variant appears.
+/// Structural variants answer [`SyscallError::Io`] — a corrupt extent tree is a
+/// volume that cannot say what is in the file, which is the opposite of a name
+/// that is not there.
+fn as_syscall_error(e: Error) -> SyscallError {
+    match e {
+        Error::NotFound => SyscallError::NotFound,
+        Error::Io
+        | Error::NotExt
+        | Error::Truncated
+        | Error::NeedsRecovery
+        | Error::BadChecksum
+        | Error::CorruptInode
+        | Error::CorruptMap
+        | Error::CorruptDirectory => SyscallError::Io,
+        // Inline data, encryption, an unknown incompat flag: the bytes are
+        // there and this reader does not decode them.
+        Error::Unsupported => SyscallError::NotSupported,
+        // The name resolves; it is not the thing the operation is for.
+        Error::NotADirectory | Error::IsADirectory | Error::NotASymlink => {
+            SyscallError::InvalidArgument
+        }
+        Error::LimitExceeded => SyscallError::ResourceExhausted,
+    }
+}
+
+/// Log what the volume said, and hand the caller the code for it. A name that
+/// is not there is not logged, for `fat32_adapter::refused`'s reason.
+fn refused(mount: &str, op: &str, name: &str, e: Error) -> SyscallError {
+    if e != Error::NotFound {
+        log!("{mount}: {op} of {name}: {e}");
+    }
+    as_syscall_error(e)
+}
+
+/// VFS adapter for one Linux volume.
+///
+/// A file's identity is its path, as on the FAT32 mounts — and here nothing can
+/// rename or delete, so the path is as stable as the inode number and needs
+/// none of the re-keying `FatFs` does.
+pub struct Ext4Fs {
+    mount: String,
+    fs: Shared,
+    by_name: HashMap<String, FileId>,
+}
+
+impl Ext4Fs {
+    /// The VFS mount name, which is also the top-level directory.
+    pub fn mount_name(&self) -> &str {
+        &self.mount
+    }
+
+    fn backing(&mut self, name: &str) -> Result<Arc<dyn FileBacking>, SyscallError> {
+        let file = self.fs.lock().open(name).map_err(|e| refused(&self.mount, "open", name, e))?;
+        Ok(Arc::new(Ext4Backing { mount: self.mount.clone(), fs: self.fs.clone(), file }))
+    }
+}
+
+impl FileSystem for Ext4Fs {
+    /// Bounded before the allocation: `Ext4::walk` refuses at `limit` rather
+    /// than truncating, as `Fat32::walk` does.
+    fn list(&mut self, limit: usize) -> Result<Vec<(String, u64)>, SyscallError> {
+        self.fs.lock().walk(limit).map_err(|e| refused(&self.mount, "list", "/", e))
+    }
+
+    fn file_mtime(&mut self, name: &str) -> Result<u64, SyscallError> {
+        self.fs
+            .lock()
+            .metadata(name)
+            .map(|m| m.modified_unix)
+            .map_err(|e| refused(&self.mount, "metadata", name, e))
+    }
+
+    /// The target as a path from the volume's root, which is the only shape
+    /// the VFS can follow: it resolves every target from the mount's root and
+    /// has no notion of the link's own directory.
+    fn read_link(&mut self, name: &str) -> Result<Option<String>, SyscallError> {
+        self.fs
+            .lock()
+            .read_link_from_root(name)
+            .map_err(|e| refused(&self.mount, "read_link", name, e))
+    }
+
+    fn open_file(&mut self, name: &str) -> Result<(FileId, Option<Arc<dyn FileBacking>>), SyscallError> {
+        let backing = self.backing(name)?;
+        if let Some(&file_id) = self.by_name.get(name) {
+            file_cache::open(file_id);
+            return Ok((file_id, Some(backing)));
+        }
+        let file_id = file_cache::create_file(true);
+        file_cache::set_size(file_id, backing.file_size());
+        self.by_name.insert(String::from(name), file_id);
+        Ok((file_id, Some(backing)))
+    }
+
+    fn create(&mut self, _name: &str, _mtime: u64) -> Result<FileId, SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn close_file(&mut self, file_id: FileId) {
+        self.by_name.retain(|_, &mut id| id != file_id);
+    }
+
+    /// Every write path answers `PermissionDenied`, as the initrd's does: the
+    /// mount is read-only by construction, and a caller retrying a refused
+    /// write would be right about a device and wrong about this.
+    fn delete(&mut self, _name: &str) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn rename(&mut self, _old: &str, _new: &str) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn write_page(&mut self, _file_id: FileId, _page_idx: u32, _data: &[u8; 4096]) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn update_metadata(&mut self, _file_id: FileId, _size: u64, _mtime: u64) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn create_symlink(&mut self, _name: &str, _target: &str) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn sync(&mut self) -> Result<(), SyscallError> {
+        Ok(())
+    }
+
+    fn open_backing(&mut self, name: &str) -> Result<Arc<dyn FileBacking>, SyscallError> {
+        self.backing(name)
+    }
+}
+
+/// Mount every Linux-typed partition on every USB, virtio and AHCI disk except
+/// the one carrying the boot partition and the one behind the page cache, as
+/// `linux0`, `linux1`, … in discovery order — USB disks first.
+///
+/// Called once at boot, after `fat32_adapter::probe_boot_disks` has settled
+/// which disk that is. A partition that does not hold an ext2/3/4 volume this
+/// crate can read — swap, LVM, a journal that needs replaying — is named in the
+/// log and skipped; nothing is ever written to find out.
+///
+/// Not NVMe, which nothing but `/home` is read from, and not the disk behind
+/// `/home` either, whose blocks are its bcachefs's.
+pub fn mount_all() -> Vec<Ext4Fs> {
+    let boot_device = gpt::boot_volume().map(|v| v.device);
+    let mut mounted = Vec::new();
+    let usb = (0..usb_storage::count()).map(|index| {
+        usb_storage::open(index).map(|disk| {
+            let lba_bytes = disk.logical_block_bytes();
+            (Box::new(disk) as Box<dyn BlockDevice>, lba_bytes)
+        })
+    });
+    let virtio = (0..virtio_blk::count()).map(|index| {
+        virtio_blk::open(index).map(|disk| {
+            let lba_bytes = disk.logical_block_bytes();
+            (Box::new(disk) as Box<dyn BlockDevice>, lba_bytes)
+        })
+    });
+    let ahci = (0..ahci::count()).map(|index| {
+        ahci::open(index).map(|disk| {
+            let lba_bytes = disk.logical_block_bytes();
+            (Box::new(disk) as Box<dyn BlockDevice>, lba_bytes)
+        })
+    });
+    for (mut disk, lba_bytes) in usb.chain(virtio).chain(ahci).flatten() {
+        let id = disk.device_id();
+        if Some(id) == boot_device || page_cache::is_registered(id) {
+            continue;
+        }
+        for volume in gpt::linux_volumes(disk.as_mut(), lba_bytes) {
+            if mounted.len() == MAX_MOUNTS {
+                log!("linux: device {} has more Linux partitions than there are mount slots; \
+                      not mounting the rest", volume.device);
+                return mounted;
+            }
+            // Registered on the first volume and the same id for the rest, so
+            // every mount on this disk shares one cache of it.
+            let Some(device) = fat32_adapter::device_carrying(id) else { break };
+            if let Some(fs) = mount(mounted.len(), device, volume) {
+                mounted.push(fs);
+            }
+        }
+    }
+    mounted
+}
+
+/// Open one partition as slot `slot`, if it holds a volume `toyos-ext4` reads.
+fn mount(slot: usize, device: DeviceId, volume: gpt::Volume) -> Option<Ext4Fs> {
+    let name = format!("linux{slot}");
+    let lba = volume.lba_bytes as u64;
+    let start = volume.start_lba.checked_mul(lba)?;
+    let len = volume.blocks.checked_mul(lba)?;
+    let device_bytes = page_cache::lock(device).block_count().checked_mul(BLOCK)?;
+    if start.checked_add(len)? > device_bytes {
+        log!(
+            "{name}: the table puts the partition at {start}+{len} on a device of \
+             {device_bytes} bytes — not mounting past the end of it"
+        );
+        return None;
+    }
+
+    *VOLUMES[slot].lock() = Some(Ext4Device { device, start, len });
+
+    // Tightened to the volume before anything but the superblock is read, as
+    // `fat32_adapter::mount` does; `probe` has already refused a superblock
+    // describing more than the partition holds.
+    let mut access = Ext4Volume { slot, bytes: len };
+    let sb = match Ext4::probe(&mut access) {
+        Ok(sb) => sb,
+        Err(e) => {
+            log!("{name}: partition at device offset {start} holds nothing this kernel can mount: {e}");
+            *VOLUMES[slot].lock() = None;
+            return None;
+        }
+    };
+    access.bytes = sb.bytes();
+    if let Some(device) = VOLUMES[slot].lock().as_mut() {
+        device.len = sb.bytes();
+    }
+
+    match Ext4::mount(access) {
+        Ok(fs) => {
+            log!(
+                "{name}: mounted read-only, {} bytes of a {len}-byte partition at device offset \
+                 {start}, {}-byte blocks, {} groups, label {:?}",
+                sb.bytes(),
+                sb.block_size,
+                sb.group_count,
+                sb.label_str().unwrap_or("")
+            );
+            Some(Ext4Fs { mount: name, fs: Arc::new(Lock::new(fs)), by_name: HashMap::new() })
+        }
+        Err(e) => {
+            log!("{name}: partition at device offset {start} holds nothing this kernel can mount: {e}");
+            *VOLUMES[slot].lock() = None;
+            None
+        }
+    }
+}
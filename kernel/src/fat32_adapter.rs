@@ -55,7 +55,6 @@
 use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::sync::Arc;
-use alloc::vec;
 use alloc::vec::Vec;
 use core::sync::atomic::{AtomicBool, Ordering};
 use hashbrown::HashMap;
@@ -63,11 +62,12 @@ use hashbrown::HashMap;
 use toyos_abi::syscall::SyscallError;
 use toyos_fat32::{BlockAccess, Error, Extent, Fat32, FatTime, IoError};
 
-use crate::block::BlockDevice;
-use crate::drivers::{usb_storage, xhci};
+use crate::block::{BlockDevice, DeviceId};
+use crate::drivers::{ahci, usb_storage, virtio_blk, xhci};
 use crate::file_backing::FileBacking;
 use crate::file_cache::{self, FileId};
 use crate::gpt;
+use crate::page_cache;
 use crate::sync::Lock;
 use crate::vfs::FileSystem;
 
@@ -150,53 +150,36 @@ const _: () = assert!(core::mem::size_of::<Extent>() == 16);
 /// builds have 512-byte clusters. One device block covers 1024 FAT entries;
 /// re-reading it per entry is the whole cost.
 ///
+/// The blocks are the page cache's, not a handful kept here: the device is
+/// registered there by [`device_carrying`], and [`page_cache::read_bytes`]
+/// serves the partial blocks a FAT entry or a directory entry sits in from
+/// the device's cache, under the one budget every disk shares.
+///
 /// # Why keeping copies is sound here
 ///
-/// Nothing is ever held back: a write reaches the device before this returns,
-/// and the copy is updated (partial block) or dropped (whole blocks) as part
-/// of issuing it. So the resident set can be stale only if something else
-/// writes these blocks, and after [`mount`] nothing can. There is one of these
-/// per [`Role`] and no other handle to either partition: `probe_boot_disks`
-/// opens its own and only reads, before the mounts; `usb_gate` writes only a
-/// disk whose block 0 carries the designation stamp, which this one does not.
+/// Nothing is ever held back: [`page_cache::write_bytes`] writes through, so a
+/// write reaches the device before this returns and any cached copy of the
+/// block is brought up to date as part of issuing it. The copies can be stale
+/// only if something writes these blocks around the cache, and nothing can:
+/// every handle this kernel reads the device through after [`mount`] is the
+/// cache's. `probe_boot_disks` opens its own and only reads, before the
+/// mounts; `usb_gate` writes only a disk whose block 0 carries the designation
+/// stamp, which this one does not.
 ///
-/// The two roles' resident sets cannot alias each other either, and that is a
+/// The two roles' blocks cannot alias each other either, and that is a
 /// property of the *image* rather than of this code: `create_gpt_disk` aligns
 /// both partitions to 1 MiB and asserts that no 4 KiB device block belongs to
 /// both. Unaligned, the ESP ended a quarter of the way into a block the log
-/// partition began in, and each mount's copy of it would have gone stale on the
-/// other's write with nothing here able to see it.
+/// partition began in, and a partial write to it from one role would have been
+/// a read-modify-write of bytes the other was writing.
 struct FatDevice {
-    dev: Box<dyn BlockDevice>,
+    device: DeviceId,
     /// Where the partition starts, in bytes from the start of the device.
     start: u64,
     /// How many bytes it has.
     len: u64,
-    /// One device block, for the partial-block ends of a request. On the heap
-    /// because the deepest caller of this is the idle loop, whose stack is
-    /// 16 KiB and has no guard page.
-    scratch: Vec<u8>,
-    /// [`RESIDENT_BLOCKS`] blocks, each tagged with the block it holds.
-    resident: Vec<u8>,
-    tags: [Option<u64>; RESIDENT_BLOCKS],
-    /// Round-robin, because the access pattern this exists for touches every
-    /// resident block on every append. Recency cannot rank blocks that are all
-    /// used once per operation, so anything cleverer would cost a counter to
-    /// arrive at the same eviction.
-    next_victim: usize,
 }
 
-/// Device blocks [`FatDevice`] keeps a copy of.
-///
-/// Sized to hold what one append touches at once, which is what makes the
-/// difference between one device read per FAT entry and one per operation:
-/// the active FAT's block for the file's clusters, the mirror FAT's block at
-/// the same index (`set_fat_entry` writes every FAT and a 4-byte write is a
-/// read-modify-write), the directory block carrying the entry, the FSInfo
-/// block, and the data block being appended to. That is five, or seven when
-/// the file's chain straddles a FAT block boundary.
-const RESIDENT_BLOCKS: usize = 8;
-
 impl FatDevice {
     /// The device byte offset `offset` names, or [`IoError`] if the request
     /// leaves the partition. Every read and write goes through here.
@@ -208,111 +191,25 @@ impl FatDevice {
         Ok(self.start + offset)
     }
 
-    fn slot_of(&self, block: u64) -> Option<usize> {
-        self.tags.iter().position(|&t| t == Some(block))
-    }
-
-    /// Leave `block` in `scratch`, reading it only if it is not already here.
-    fn load(&mut self, block: u64) -> Result<(), IoError> {
-        if let Some(slot) = self.slot_of(block) {
-            let at = slot * BLOCK as usize;
-            self.scratch.copy_from_slice(&self.resident[at..at + BLOCK as usize]);
-            return Ok(());
-        }
-        let Self { dev, scratch, .. } = self;
-        dev.read_blocks(block, 1, scratch).map_err(|_| IoError)?;
-        self.retain(block);
-        Ok(())
-    }
-
-    /// Record `scratch` as this device's `block`. Only ever called where the
-    /// device holds those bytes already — after reading them, or after writing
-    /// them — so nothing here is a copy the disk is waiting for.
-    fn retain(&mut self, block: u64) {
-        let slot = self.slot_of(block).unwrap_or_else(|| {
-            let s = self.next_victim;
-            self.next_victim = (s + 1) % RESIDENT_BLOCKS;
-            s
-        });
-        let at = slot * BLOCK as usize;
-        self.resident[at..at + BLOCK as usize].copy_from_slice(&self.scratch);
-        self.tags[slot] = Some(block);
-    }
-
-    fn forget(&mut self, first: u64, count: u64) {
-        for tag in &mut self.tags {
-            if tag.is_some_and(|b| b >= first && b < first + count) {
-                *tag = None;
-            }
-        }
-    }
-
     fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError> {
-        let base = self.locate(offset, buf.len())?;
-        let mut done = 0usize;
-        while done < buf.len() {
-            let at = base + done as u64;
-            let block = at / BLOCK;
-            let within = (at % BLOCK) as usize;
-            let left = buf.len() - done;
-            if within == 0 && left >= BLOCK as usize {
-                let count = left / BLOCK as usize;
-                let end = done + count * BLOCK as usize;
-                self.dev
-                    .read_blocks(block, count as u32, &mut buf[done..end])
-                    .map_err(|_| IoError)?;
-                done = end;
-            } else {
-                let n = (BLOCK as usize - within).min(left);
-                self.load(block)?;
-                buf[done..done + n].copy_from_slice(&self.scratch[within..within + n]);
-                done += n;
-            }
-        }
-        Ok(())
+        let at = self.locate(offset, buf.len())?;
+        page_cache::read_bytes(self.device, at, buf).map_err(|_| IoError)
     }
 
     fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), IoError> {
-        let base = self.locate(offset, buf.len())?;
-        let mut done = 0usize;
-        while done < buf.len() {
-            let at = base + done as u64;
-            let block = at / BLOCK;
-            let within = (at % BLOCK) as usize;
-            let left = buf.len() - done;
-            if within == 0 && left >= BLOCK as usize {
-                let count = left / BLOCK as usize;
-                let end = done + count * BLOCK as usize;
-                self.forget(block, count as u64);
-                self.dev
-                    .write_blocks(block, count as u32, &buf[done..end])
-                    .map_err(|_| IoError)?;
-                done = end;
-            } else {
-                // The bytes this request does not cover belong to whoever wrote
-                // them — another file, or the partition table itself when the
-                // partition does not start on a 4 KiB boundary.
-                let n = (BLOCK as usize - within).min(left);
-                self.load(block)?;
-                self.scratch[within..within + n].copy_from_slice(&buf[done..done + n]);
-                let Self { dev, scratch, .. } = self;
-                dev.write_blocks(block, 1, scratch).map_err(|_| IoError)?;
-                self.retain(block);
-                done += n;
-            }
-        }
-        Ok(())
+        let at = self.locate(offset, buf.len())?;
+        page_cache::write_bytes(self.device, at, buf).map_err(|_| IoError)
     }
 }
 
 /// Each mounted partition's device, reachable without the VFS lock.
 ///
-/// Statics for the same reason `page_cache`'s device is one: a [`FileBacking`]
+/// Statics for the same reason `page_cache`'s registry is one: a [`FileBacking`]
 /// serves a page-fault miss with `&self` and no filesystem in hand, so the
 /// device cannot live inside the `Box<dyn FileSystem>` the VFS owns. Lock
-/// order is VFS → here → `XHCI`; nothing takes them the other way, and no path
-/// holds two of these at once — [`Role`] indexes one of them and every caller
-/// has exactly one role in hand.
+/// order is VFS → here → the device's page cache → `XHCI`; nothing takes them
+/// the other way, and no path holds two of these at once — [`Role`] indexes
+/// one of them and every caller has exactly one role in hand.
 static VOLUMES: [Lock<Option<FatDevice>>; 2] = [Lock::new(None), Lock::new(None)];
 
 fn device(role: Role) -> &'static Lock<Option<FatDevice>> {
@@ -355,8 +252,9 @@ impl BlockAccess for FatVolume {
     }
 
     fn flush(&mut self) -> Result<(), IoError> {
-        let mut guard = device(self.role).lock();
-        guard.as_mut().ok_or(IoError)?.dev.flush().map_err(|_| IoError)
+        let guard = device(self.role).lock();
+        let cached = guard.as_ref().ok_or(IoError)?.device;
+        page_cache::flush(cached).map_err(|_| IoError)
     }
 }
 
@@ -426,11 +324,29 @@ struct FatBacking {
 
 impl FileBacking for FatBacking {
     fn read_page(&self, file_offset: u64, buf: &mut [u8; 4096]) -> crate::block::BlockResult {
+        self.read_range(file_offset, buf)
+    }
+
+    /// One `read_at` per extent the batch crosses. `FatDevice::read_at` sends
+    /// the whole-block middle of each one to the stick as a single transfer.
+    fn read_pages(&self, file_offset: u64, buf: &mut [u8]) -> crate::block::BlockResult {
+        self.read_range(file_offset, buf)
+    }
+
+    fn file_size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl FatBacking {
+    /// `buf.len()` bytes of the file from `file_offset`, with zeros past its
+    /// end. One page and a batch of pages differ only in that length.
+    fn read_range(&self, file_offset: u64, buf: &mut [u8]) -> crate::block::BlockResult {
         buf.fill(0);
         if file_offset >= self.size {
             return Ok(());
         }
-        let valid = (4096u64).min(self.size - file_offset) as usize;
+        let valid = (buf.len() as u64).min(self.size - file_offset) as usize;
         let mut done = 0usize;
         // Where the extent under consideration starts, in file bytes. A page
         // can span two of them whenever the volume's cluster is smaller than
@@ -477,10 +393,6 @@ impl FileBacking for FatBacking {
         }
         Ok(())
     }
-
-    fn file_size(&self) -> u64 {
-        self.size
-    }
 }
 
 /// Per-open-file state: the path it was opened by, and the crate's own handle,
@@ -839,8 +751,9 @@ impl FileSystem for FatFs {
 ///
 /// The asymmetry is the same one `xhci::EMPTY_BUS` is written around, and it
 /// is what keeps this free: a machine whose boot volume has already been
-/// resolved — every QEMU boot, every machine that boots off NVMe, and the T14 on
-/// a good boot — leaves after one pass, because `gpt::boot_volume()` answers.
+/// resolved — every QEMU boot, every machine that boots off NVMe or a virtio
+/// disk, and the T14 on a good boot — leaves after one pass, because
+/// `gpt::boot_volume()` answers.
 /// Only a machine that would otherwise report no boot volume at all pays
 /// anything, and that is the outcome this exists to prevent.
 ///
@@ -908,18 +821,43 @@ fn probe_announced(mut probed: usize) -> usize {
     probed
 }
 
-/// The bound disk carrying `id`, or `None` when no driver here serves it.
-///
-/// Only USB today. A machine that boots off an internal disk lands in the
-/// `None` arm and gets neither mount, because the NVMe device is owned by the
-/// page cache from the moment storage comes up and there is no second handle
-/// to it — see the report in `issues/build/page-cache-owns-one-device.md`
-/// rather than a workaround here.
-fn device_carrying(id: crate::block::DeviceId) -> Option<Box<dyn BlockDevice>> {
-    (0..usb_storage::count())
+/// The bound disk carrying `id`, behind the page cache, or `None` when no
+/// driver here serves it.
+///
+/// USB, virtio and AHCI, an AHCI disc as well as a disk, registered with the
+/// cache on the first ask and named by the same id on every later one — so
+/// `/boot` and `/log` off one stick, or a stick's `/boot` and the ext4 volumes
+/// beside it, are one registration and one set of cached blocks, and no mount
+/// reads around another's cache. A disk
+/// the cache already holds is answered as it stands, which is also how a
+/// machine that boots off the NVMe disk carrying `/home` gets its `/boot`:
+/// that disk was registered before anything here ran.
+///
+/// Check-then-register is not atomic, and needs not be: every caller is a
+/// mount at boot, on the BSP, one after another.
+pub fn device_carrying(id: DeviceId) -> Option<DeviceId> {
+    if page_cache::is_registered(id) {
+        return Some(id);
+    }
+    let usb = (0..usb_storage::count())
         .filter_map(usb_storage::open)
         .find(|disk| disk.device_id() == id)
-        .map(|disk| Box::new(disk) as Box<dyn BlockDevice>)
+        .map(|disk| Box::new(disk) as Box<dyn BlockDevice>);
+    let disk = usb
+        .or_else(|| {
+            (0..virtio_blk::count())
+                .filter_map(virtio_blk::open)
+                .find(|disk| disk.device_id() == id)
+                .map(|disk| Box::new(disk) as Box<dyn BlockDevice>)
+        })
+        .or_else(|| {
+            (0..ahci::count())
+                .filter_map(ahci::open)
+                .chain((0..ahci::disc_count()).filter_map(ahci::open_disc))
+                .find(|disk| disk.device_id() == id)
+                .map(|disk| Box::new(disk) as Box<dyn BlockDevice>)
+        })?;
+    Some(page_cache::register(disk))
 }
 
 /// Open the partition `role` names, if it can be found and if it carries a
@@ -932,7 +870,7 @@ fn device_carrying(id: crate::block::DeviceId) -> Option<Box<dyn BlockDevice>> {
 pub fn mount(role: Role) -> Option<FatFs> {
     let volume = role.volume()?;
 
-    let Some(dev) = device_carrying(volume.device) else {
+    let Some(cached) = device_carrying(volume.device) else {
         log!(
             "{role}-volume: the partition is on device {} and no driver here can open it",
             volume.device
@@ -943,7 +881,7 @@ pub fn mount(role: Role) -> Option<FatFs> {
     let lba = volume.lba_bytes as u64;
     let start = volume.start_lba.checked_mul(lba)?;
     let len = volume.blocks.checked_mul(lba)?;
-    let device_bytes = dev.block_count().checked_mul(BLOCK)?;
+    let device_bytes = page_cache::lock(cached).block_count().checked_mul(BLOCK)?;
     if start.checked_add(len)? > device_bytes {
         log!(
             "{role}-volume: the table puts the partition at {start}+{len} on a device of \
@@ -953,13 +891,9 @@ pub fn mount(role: Role) -> Option<FatFs> {
     }
 
     *device(role).lock() = Some(FatDevice {
-        dev,
+        device: cached,
         start,
         len,
-        scratch: vec![0u8; BLOCK as usize],
-        resident: vec![0u8; RESIDENT_BLOCKS * BLOCK as usize],
-        tags: [None; RESIDENT_BLOCKS],
-        next_victim: 0,
     });
 
     // `probe` is a total read and takes no ownership, which is what lets the
@@ -2,7 +2,7 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 use bcachefs::{BlockIO, BlockNum, Extent, SliceBlockIO};
-use crate::block::{BlockError, BlockResult};
+use crate::block::{BlockError, BlockResult, DeviceId};
 use crate::page_cache;
 use crate::sync::Lock;
 
@@ -29,10 +29,45 @@ pub trait FileBacking: Send + Sync {
     #[must_use = "a failed read left the buffer zeroed; it does not hold the file's bytes"]
     fn read_page(&self, file_offset: u64, buf: &mut [u8; BLOCK_SIZE]) -> BlockResult;
 
+    /// Read `buf.len() / 4096` consecutive pages starting at `file_offset`
+    /// into `buf`. Each page gets [`read_page`]'s contract.
+    ///
+    /// The default goes a page at a time. A backing whose pages lie in runs of
+    /// contiguous device blocks overrides it to give the device one transfer per
+    /// run. Loading a large shared object off the boot stick used to cost one
+    /// USB round trip per 4 KiB, and those round trips were nearly all of the
+    /// time.
+    ///
+    /// `Err` means some page could not be read, and nothing in `buf` can be
+    /// trusted. There is no partial answer, because a caller cannot tell which
+    /// pages did arrive. The callers that want the pages that did go back to
+    /// [`read_page`] one at a time. That call names the page that failed and
+    /// logs it.
+    ///
+    /// [`read_page`]: FileBacking::read_page
+    #[must_use = "a failed read left the buffer zeroed; it does not hold the file's bytes"]
+    fn read_pages(&self, file_offset: u64, buf: &mut [u8]) -> BlockResult {
+        debug_assert!(buf.len().is_multiple_of(BLOCK_SIZE));
+        let (pages, _) = buf.as_chunks_mut::<BLOCK_SIZE>();
+        for (i, page) in pages.iter_mut().enumerate() {
+            self.read_page(file_offset + (i * BLOCK_SIZE) as u64, page)?;
+        }
+        Ok(())
+    }
+
     /// Total file size in bytes.
     fn file_size(&self) -> u64;
 }
 
+/// The most pages one [`FileBacking::read_pages`] call is asked for: 256 KiB.
+///
+/// This bounds the heap buffer each batched caller allocates, not the device
+/// transfer. Each driver already splits a long `read_blocks` at its own limit.
+/// A 2 MiB fault fill is eight of these, which amortises the round trip as well
+/// as one call would. It also does not make a 2 MiB allocation out of the heap
+/// for every fault.
+pub const READ_BATCH_PAGES: usize = 64;
+
 /// Which blocks a `/home` file's data lives in, and whether they are still
 /// that file's.
 ///
@@ -92,13 +127,15 @@ fn offset_to_block(extents: &[Extent], file_offset: u64) -> Option<u64> {
 
 /// File backed by NVMe blocks via the kernel PageCache.
 pub struct NvmeBacking {
+    /// The page-cache registration the blocks below are numbered on.
+    device: DeviceId,
     blocks: Arc<FileBlocks>,
     size: u64,
 }
 
 impl NvmeBacking {
-    pub fn new(blocks: Arc<FileBlocks>, size: u64) -> Self {
-        Self { blocks, size }
+    pub fn new(device: DeviceId, blocks: Arc<FileBlocks>, size: u64) -> Self {
+        Self { device, blocks, size }
     }
 }
 
@@ -123,7 +160,7 @@ impl FileBacking for NvmeBacking {
             // hole rather than another file's data — and now says so in the
             // return as well as in the log, so a caller that is about to merge
             // a partial write into this page can decline instead.
-            if page_cache::raw_block_read(block, &mut raw).is_err() {
+            if page_cache::raw_block_read(self.device, block, &mut raw).is_err() {
                 log!("file: read of block {block} failed; serving zeros");
                 return Err(BlockError);
             }
@@ -133,6 +170,50 @@ impl FileBacking for NvmeBacking {
         Ok(())
     }
 
+    /// One `read_blocks` per run of contiguous blocks. bcachefs allocates a
+    /// file written in one go as a few long extents, so a 256 KiB batch is
+    /// usually a single transfer.
+    fn read_pages(&self, file_offset: u64, buf: &mut [u8]) -> BlockResult {
+        buf.fill(0);
+        let pages = buf.len() / BLOCK_SIZE;
+        // Resolved under one acquisition, so a revoke cannot split the batch
+        // into pages read from this file's blocks and pages read from whoever
+        // got them next.
+        let Some(blocks) = self.blocks.with(|extents| {
+            (0..pages)
+                .map(|i| file_offset + (i * BLOCK_SIZE) as u64)
+                .map(|at| if at < self.size { offset_to_block(extents, at) } else { None })
+                .collect::<Vec<_>>()
+        }) else {
+            log!("file: read through a backing whose file was deleted");
+            return Err(BlockError);
+        };
+        let mut i = 0;
+        while i < pages {
+            // A hole past the extent list stays the zeros it already is.
+            let Some(first) = blocks[i] else {
+                i += 1;
+                continue;
+            };
+            let mut n = 1;
+            while i + n < pages && blocks[i + n] == Some(first + n as u64) {
+                n += 1;
+            }
+            let run = &mut buf[i * BLOCK_SIZE..(i + n) * BLOCK_SIZE];
+            if page_cache::raw_blocks_read(self.device, first, n as u32, run).is_err() {
+                buf.fill(0);
+                log!("file: read of {n} blocks at {first} failed; serving zeros");
+                return Err(BlockError);
+            }
+            i += n;
+        }
+        // The tail of the last page is the block's bytes past the end of the
+        // file, which belong to nobody and read as zeros through `read_page`.
+        let valid = self.size.saturating_sub(file_offset).min(buf.len() as u64) as usize;
+        buf[valid..].fill(0);
+        Ok(())
+    }
+
     fn file_size(&self) -> u64 {
         self.size
     }
@@ -3,9 +3,10 @@ use alloc::collections::btree_map::Entry;
 use alloc::collections::BTreeMap;
 use alloc::collections::BTreeSet;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use crate::block;
-use crate::file_backing::FileBacking;
+use crate::file_backing::{FileBacking, READ_BATCH_PAGES};
 use crate::sync::Lock;
 use crate::user_ptr::{ByteSource, UserBytesMut};
 
@@ -13,6 +14,14 @@ pub type FileId = u64;
 
 const PAGE_SIZE: usize = 4096;
 
+/// The first readahead window a sequential reader gets, in pages.
+const READAHEAD_MIN: u32 = 4;
+
+/// The largest window, 512 KiB. Enough to keep a reader streaming a shared
+/// object ahead of the device without one reader taking over the cache. The
+/// budget bounds it too, in [`populate`].
+const READAHEAD_MAX: u32 = 2 * READ_BATCH_PAGES as u32;
+
 struct CachedPage {
     data: Box<[u8; PAGE_SIZE]>,
     dirty: bool,
@@ -50,6 +59,10 @@ struct FileCache {
     cached_pages: usize,
     max_pages: usize,
     evictions: u64,
+    /// Dirty pages of files on a disk filesystem (`evictable` ones), whether or
+    /// not they have a backing yet. Only these can be written back to free
+    /// memory. A tmpfs page is always dirty and has nowhere else to go.
+    dirty_pages: usize,
     /// CLOCK hand, in (file, page) key order. Kept across calls so the sweep
     /// costs one step per eviction rather than a scan of the whole cache.
     hand: (FileId, u32),
@@ -64,6 +77,7 @@ static FILE_CACHE: Lock<FileCache> = Lock::new(FileCache {
     // ceiling nothing could reach.
     max_pages: 0,
     evictions: 0,
+    dirty_pages: 0,
     hand: (0, 0),
 });
 
@@ -106,6 +120,13 @@ pub fn set_backing(file_id: FileId, backing: Arc<dyn FileBacking>) {
     evict_if_needed(&mut cache);
 }
 
+/// Whether this file is on a disk filesystem, so that its dirty pages count
+/// against [`dirty_pressure`] and writing it back frees memory. False for
+/// tmpfs, whose pages are the file.
+pub fn is_on_disk(file_id: FileId) -> bool {
+    FILE_CACHE.lock().files.get(&file_id).is_some_and(|f| f.evictable)
+}
+
 /// Whether an evicted page of this file could be read back. False for tmpfs,
 /// and for a disk file created in this boot until its blocks exist.
 pub fn has_backing(file_id: FileId) -> bool {
@@ -206,6 +227,129 @@ pub fn read_page(
     Ok(())
 }
 
+/// Where one open handle's reads have been going, and how far ahead of them
+/// the cache has been filled.
+///
+/// Kept per handle and not per file. Two processes streaming one file from
+/// different offsets each read sequentially. A per-file detector would see
+/// their reads interleaved and decide the file was being read at random.
+#[derive(Default)]
+pub struct Readahead {
+    /// The page after the last one the handle read.
+    next: u32,
+    /// The current window, in pages. Zero until a sequential read is seen.
+    window: u32,
+    /// The page after the last one readahead fetched.
+    ahead: u32,
+}
+
+impl Readahead {
+    pub const fn new() -> Self {
+        Self { next: 0, window: 0, ahead: 0 }
+    }
+}
+
+/// Fill the cache for a read of pages `first..=last` through the handle `ra`
+/// belongs to, before the read looks at the pages one at a time.
+///
+/// A read that starts where the handle's last read ended is sequential. It
+/// fetches a window past its own end, and the window doubles from
+/// [`READAHEAD_MIN`] to [`READAHEAD_MAX`] each time the reader gets within half
+/// a window of the edge. Any other read resets the window and fetches only
+/// the pages it asked for. Either way, the pages that are missing go to the
+/// backing as runs, in one [`FileBacking::read_pages`] call each.
+///
+/// This is an optimisation and never the answer. A failed batch is dropped
+/// without a word. [`read_page`] then fetches each page that is still missing
+/// by itself, and that is where the error is reported against the page the
+/// caller was promised.
+pub fn read_ahead(file_id: FileId, ra: &mut Readahead, first: u32, last: u32) {
+    // Starting inside the page the last read ended in also counts: a reader
+    // with a buffer that is not a multiple of a page does it every time.
+    let sequential = first == ra.next || first.checked_add(1) == Some(ra.next);
+    ra.next = last.saturating_add(1);
+    if !sequential {
+        ra.window = 0;
+        ra.ahead = ra.next;
+        populate(file_id, first, ra.next);
+        return;
+    }
+    if ra.ahead.saturating_sub(ra.next) > ra.window / 2 {
+        return;
+    }
+    ra.window = ra.window.saturating_mul(2).clamp(READAHEAD_MIN, READAHEAD_MAX);
+    ra.ahead = ra.next.saturating_add(ra.window);
+    populate(file_id, first, ra.ahead);
+}
+
+/// Fetch the pages of `first..end` that are not resident, in runs of at most
+/// [`READ_BATCH_PAGES`], and insert them unreferenced. A page that CLOCK finds
+/// before a reader does is taken first.
+///
+/// Clipped to the file and to an eighth of the budget. Without the budget
+/// limit, a window on a small machine could push out the pages it had just
+/// fetched before the reader got to them.
+fn populate(file_id: FileId, first: u32, end: u32) {
+    let backing;
+    let mut runs = Vec::new();
+    {
+        let cache = FILE_CACHE.lock();
+        let Some(file) = cache.files.get(&file_id) else { return };
+        let Some(b) = file.backing.clone() else { return };
+        backing = b;
+        let pages = file.size.div_ceil(PAGE_SIZE as u64).min(u32::MAX as u64) as u32;
+        let share = (cache.max_pages / 8).max(1).min(u32::MAX as usize) as u32;
+        let end = end.min(pages).min(first.saturating_add(share));
+        let mut idx = first;
+        while idx < end {
+            if file.pages.contains_key(&idx) {
+                idx += 1;
+                continue;
+            }
+            let start = idx;
+            while idx < end && idx - start < READ_BATCH_PAGES as u32 && !file.pages.contains_key(&idx) {
+                idx += 1;
+            }
+            runs.push((start, idx - start));
+        }
+    }
+    if runs.is_empty() {
+        return;
+    }
+
+    let mut batch = alloc::vec![0u8; READ_BATCH_PAGES * PAGE_SIZE];
+    for (start, count) in runs {
+        let buf = &mut batch[..count as usize * PAGE_SIZE];
+        if backing.read_pages(start as u64 * PAGE_SIZE as u64, buf).is_err() {
+            return;
+        }
+        let mut cache = FILE_CACHE.lock();
+        let mut added = 0;
+        {
+            let Some(file) = cache.files.get_mut(&file_id) else { return };
+            let is_cache = file.is_cache();
+            let size = file.size;
+            for (i, data) in buf.as_chunks::<PAGE_SIZE>().0.iter().enumerate() {
+                let idx = start + i as u32;
+                // A truncate while the lock was dropped: what came back is
+                // past the end now, and a later extension must find a hole
+                // there and not these bytes.
+                if idx as u64 * PAGE_SIZE as u64 >= size {
+                    break;
+                }
+                if let Entry::Vacant(slot) = file.pages.entry(idx) {
+                    let mut page = blank_page();
+                    page.copy_from_slice(data);
+                    slot.insert(CachedPage::new(page));
+                    added += is_cache as usize;
+                }
+            }
+        }
+        cache.cached_pages += added;
+        evict_if_needed(&mut cache);
+    }
+}
+
 /// Write data into a file page. Handles cache miss via the file's backing.
 /// Lock is NOT held during disk I/O for cache misses.
 ///
@@ -228,15 +372,17 @@ pub fn write_page<S: ByteSource + ?Sized>(
     let backing;
     {
         let mut cache = FILE_CACHE.lock();
+        let mut dirtied = 0;
         {
             let Some(file) = cache.files.get_mut(&file_id) else { return Ok(()) };
             if file.pages.contains_key(&page_idx) {
-                apply_write(file, page_idx, offset, data);
+                dirtied = apply_write(file, page_idx, offset, data);
                 backing = None;
             } else {
                 backing = Some(file.backing.clone());
             }
         }
+        cache.dirty_pages += dirtied;
         if backing.is_none() {
             evict_if_needed(&mut cache);
             return Ok(());
@@ -259,6 +405,7 @@ pub fn write_page<S: ByteSource + ?Sized>(
     // backing returns.
     let mut cache = FILE_CACHE.lock();
     let mut added = 0;
+    let dirtied;
     {
         let Some(file) = cache.files.get_mut(&file_id) else { return Ok(()) };
         let is_cache = file.is_cache();
@@ -266,22 +413,26 @@ pub fn write_page<S: ByteSource + ?Sized>(
             slot.insert(CachedPage::new(fetched));
             added = is_cache as usize;
         }
-        apply_write(file, page_idx, offset, data);
+        dirtied = apply_write(file, page_idx, offset, data);
     }
     cache.cached_pages += added;
+    cache.dirty_pages += dirtied;
     evict_if_needed(&mut cache);
     Ok(())
 }
 
+/// Answers how many pages this made dirty that [`FileCache::dirty_pages`]
+/// counts: one or zero.
 fn apply_write<S: ByteSource + ?Sized>(
     file: &mut CachedFile,
     page_idx: u32,
     offset: usize,
     data: &S,
-) {
+) -> usize {
     let page = file.pages.get_mut(&page_idx).expect("write_page: page not resident");
     let end = (offset + data.len()).min(PAGE_SIZE);
     data.read_at(0, &mut page.data[offset..end]);
+    let dirtied = (!page.dirty && file.evictable) as usize;
     page.dirty = true;
     page.referenced = true;
 
@@ -289,6 +440,7 @@ fn apply_write<S: ByteSource + ?Sized>(
     if write_end > file.size {
         file.size = write_end;
     }
+    dirtied
 }
 
 /// Copy a resident page out. `false`, and `buf` untouched, when the page is not
@@ -324,13 +476,56 @@ pub fn clone_dirty(file_id: FileId) -> BTreeSet<u32> {
 /// is free to drop — which turns a lost write into a silent one.
 pub fn clear_dirty(file_id: FileId, flushed: &BTreeSet<u32>) {
     let mut cache = FILE_CACHE.lock();
+    let mut cleaned = 0;
     if let Some(file) = cache.files.get_mut(&file_id) {
         for page_idx in flushed {
             if let Some(page) = file.pages.get_mut(page_idx) {
+                cleaned += (page.dirty && file.evictable) as usize;
                 page.dirty = false;
             }
         }
     }
+    cache.dirty_pages -= cleaned;
+}
+
+/// How far dirty pages have got into the budget. Write-back is what brings
+/// them down: eviction cannot, because it only takes clean pages.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DirtyPressure {
+    /// Below a tenth of the budget. Dirty pages wait for `fsync`, for close,
+    /// or for `iod` to find them old enough.
+    Low,
+    /// A tenth or more. `iod` writes back every file it has queued.
+    Background,
+    /// A fifth or more. The writer writes back its own file before its
+    /// `write` returns, so the rate it dirties pages is limited by how fast
+    /// the device takes them.
+    Throttle,
+}
+
+/// Where the dirty set stands against the budget. The thresholds are
+/// fractions of the budget so that they scale with the machine's memory.
+pub fn dirty_pressure() -> DirtyPressure {
+    let cache = FILE_CACHE.lock();
+    if cache.dirty_pages >= cache.max_pages / 5 {
+        DirtyPressure::Throttle
+    } else if cache.dirty_pages >= cache.max_pages / 10 {
+        DirtyPressure::Background
+    } else {
+        DirtyPressure::Low
+    }
+}
+
+/// Whether a write-back of this file would do anything that has to be done
+/// now. That means the file is on a disk filesystem, is still open, is not
+/// unlinked, and has dirty pages.
+///
+/// `iod` asks this with the VFS lock held. Close and unlink both run under
+/// that lock, so the answer still holds when the flush runs.
+pub fn wants_writeback(file_id: FileId) -> bool {
+    let cache = FILE_CACHE.lock();
+    cache.files.get(&file_id)
+        .is_some_and(|f| f.evictable && !f.deleted && f.ref_count > 0 && f.pages.values().any(|p| p.dirty))
 }
 
 /// Get the authoritative file size.
@@ -341,24 +536,28 @@ pub fn size(file_id: FileId) -> u64 {
 /// Set file size. Removes pages past the new size on truncation.
 pub fn set_size(file_id: FileId, new_size: u64) {
     let mut cache = FILE_CACHE.lock();
-    let dropped;
+    let (dropped, undirtied);
     {
         let Some(file) = cache.files.get_mut(&file_id) else { return };
-        dropped = if new_size < file.size {
+        (dropped, undirtied) = if new_size < file.size {
             let is_cache = file.is_cache();
             let first_removed = (new_size as usize).div_ceil(PAGE_SIZE) as u32;
-            let removed: alloc::vec::Vec<u32> = file.pages.range(first_removed..)
+            let removed: Vec<u32> = file.pages.range(first_removed..)
                 .map(|(&k, _)| k).collect();
+            let mut dirty = 0;
             for k in &removed {
-                file.pages.remove(k);
+                if file.pages.remove(k).is_some_and(|p| p.dirty) {
+                    dirty += file.evictable as usize;
+                }
             }
-            if is_cache { removed.len() } else { 0 }
+            (if is_cache { removed.len() } else { 0 }, dirty)
         } else {
-            0
+            (0, 0)
         };
         file.size = new_size;
     }
     cache.cached_pages -= dropped;
+    cache.dirty_pages -= undirtied;
 }
 
 /// What the cache holds for a file after an operation that may have freed it.
@@ -417,6 +616,9 @@ fn drop_file(cache: &mut FileCache, file_id: FileId) {
     if removed.is_cache() {
         cache.cached_pages -= removed.pages.len();
     }
+    if removed.evictable {
+        cache.dirty_pages -= removed.pages.values().filter(|p| p.dirty).count();
+    }
 }
 
 fn valid_bytes_in_page(page_idx: u32, file_size: u64) -> usize {
@@ -449,9 +651,9 @@ fn evict_if_needed(cache: &mut FileCache) {
     let before = cache.evictions;
     while cache.cached_pages > cache.max_pages {
         if !evict_one(cache) {
-            // Everything resident is dirty. Write-back is the handle layer's job
-            // (`vfs::flush_file` on fsync and on close), so the only bound on
-            // dirty pages is the writer's un-flushed working set.
+            // Everything resident is dirty. Eviction cannot help, and write-back
+            // is what can: `iod` past `DirtyPressure::Background`, and the
+            // writer itself past `DirtyPressure::Throttle`.
             break;
         }
     }
@@ -522,3 +724,134 @@ fn page_at_or_after(cache: &FileCache, from: (FileId, u32)) -> Option<(FileId, u
     }
     None
 }
+
+/// Stream a file through [`read_ahead`] the way `object/ops.rs` does, a page
+/// per read, then seek, and say whether the window and the batching behave as
+/// the function's doc says.
+///
+/// A test boot cannot say it from a real file. `/home` is freshly formatted and
+/// its files are written in the same boot, so every page a test reads back is
+/// already resident and readahead has nothing to fetch; and nothing outside the
+/// kernel can count device calls or see a [`Readahead`]. Here the file's pages
+/// are on a synthetic disk registered with the page cache, read through an
+/// [`NvmeBacking`](crate::file_backing::NvmeBacking) like any `/home` file's,
+/// and the disk counts its `read_blocks` calls. Its two extents are far apart,
+/// so a run that crossed the gap as one transfer would read the wrong blocks,
+/// and every page is checked for the block number the disk stamped on it.
+///
+/// The detector, the window, [`populate`]'s runs and the backing's coalescing
+/// are the shipped ones; only the disk answers from memory. The disk's
+/// registration lasts the boot, as `page_cache::fairness_selftest`'s do.
+#[cfg(feature = "boot-actuators")]
+pub fn readahead_selftest() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use bcachefs::Extent;
+
+    use crate::block::DeviceId;
+    use crate::file_backing::{FileBlocks, NvmeBacking};
+    use crate::page_cache::{self, Synthetic};
+
+    const CASES: usize = 5;
+    /// Streamed in full, then sought into the half that was never read.
+    const STREAMED: u32 = 512;
+    const PAGES: u32 = 2 * STREAMED;
+    /// Where each half of the file lives on the disk.
+    const EXTENTS: [u64; 2] = [1 << 20, 1 << 24];
+    const SEEK_TO: u32 = STREAMED + STREAMED / 2;
+
+    let reads: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+    // Below the two ids the fairness self-test takes.
+    let device = page_cache::register(Box::new(Synthetic { id: DeviceId::MAX - 2, reads }));
+    let extents = EXTENTS
+        .iter()
+        .map(|&start_block| Extent { start_block, block_count: PAGES / 2, _reserved: 0 })
+        .collect();
+    let size = PAGES as u64 * PAGE_SIZE as u64;
+    let file = create_file(true);
+    set_size(file, size);
+    set_backing(file, Arc::new(NvmeBacking::new(device, FileBlocks::new(extents), size)));
+    let block_of = |page: u32| EXTENTS[(page / (PAGES / 2)) as usize] + (page % (PAGES / 2)) as u64;
+    // Whether `page` is resident and holds the block it was read from. Looked
+    // at directly, never through `read_page`, which would fetch a page that
+    // readahead had failed to and hide the failure.
+    let resident = |page: u32| {
+        let mut data = [0u8; PAGE_SIZE];
+        copy_page_out(file, page, &mut data) && data[..8] == block_of(page).to_le_bytes()
+    };
+
+    let mut passed = 0usize;
+    let mut ra = Readahead::new();
+
+    // The stream. Every window the detector opens is recorded, and every page
+    // must be resident with the right bytes before the read for it would look.
+    let mut windows = Vec::new();
+    let mut missed = 0usize;
+    for page in 0..STREAMED {
+        read_ahead(file, &mut ra, page, page);
+        if windows.last() != Some(&ra.window) {
+            windows.push(ra.window);
+        }
+        missed += !resident(page) as usize;
+    }
+    let calls = reads.load(Ordering::Relaxed);
+
+    // The window doubles from the least to the most and then stays there.
+    let mut want = Vec::new();
+    let mut window = READAHEAD_MIN;
+    while window < READAHEAD_MAX {
+        want.push(window);
+        window *= 2;
+    }
+    want.push(READAHEAD_MAX);
+    if windows == want {
+        passed += 1;
+    } else {
+        log!("file cache: readahead selftest FAILED on the window: {windows:?} over a \
+              sequential stream, want {want:?}");
+    }
+
+    if missed == 0 {
+        passed += 1;
+    } else {
+        log!("file cache: readahead selftest FAILED on the stream: {missed} of {STREAMED} \
+              pages absent or holding another block when the read reached them");
+    }
+
+    // Batched: a device call brings in a window's worth, not a page. The first
+    // window is the smallest run there is, so fewer pages than that per call
+    // on average is a populate that went back to the device a page at a time.
+    if calls > 0 && calls * READAHEAD_MIN as usize <= STREAMED as usize {
+        passed += 1;
+    } else {
+        log!("file cache: readahead selftest FAILED on batching: {calls} device reads for \
+              {STREAMED} pages");
+    }
+
+    // A seek into pages nobody has read resets the window and fetches the one
+    // page asked for, in one call, and nothing past it.
+    let before = reads.load(Ordering::Relaxed);
+    read_ahead(file, &mut ra, SEEK_TO, SEEK_TO);
+    let seek_calls = reads.load(Ordering::Relaxed) - before;
+    if ra.window == 0 && seek_calls == 1 && resident(SEEK_TO) && !resident(SEEK_TO + 1) {
+        passed += 1;
+    } else {
+        log!("file cache: readahead selftest FAILED on the seek: window {} and {seek_calls} \
+              device reads after it, the page past it resident={}",
+            ra.window, resident(SEEK_TO + 1));
+    }
+
+    // And the read after the seek is sequential again, from the least window.
+    read_ahead(file, &mut ra, SEEK_TO + 1, SEEK_TO + 1);
+    if ra.window == READAHEAD_MIN && resident(SEEK_TO + 1) {
+        passed += 1;
+    } else {
+        log!("file cache: readahead selftest FAILED on the restart: window {} after the \
+              read that follows a seek, want {READAHEAD_MIN}", ra.window);
+    }
+
+    log!("file cache: readahead selftest: {STREAMED} pages streamed in {calls} device reads, \
+          windows {windows:?}");
+    log!("file cache: readahead selftest {passed}/{CASES}");
+    release(file);
+}
@@ -0,0 +1,161 @@
+//! Every game controller the machine has, as one queue of pad states.
+//!
+//! The keyboard and the mouse merge their devices into one keyboard and one
+//! cursor, because the machine has one of each as far as a program can tell.
+//! Pads are the opposite: two players are two pads, and a game has to be able
+//! to tell them apart. So nothing is merged here — each event is one pad's
+//! whole state, named by its [`PadId`], and the queue carries them in the order
+//! the reports arrived.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::inbox::InboxId;
+use crate::sync::Lock;
+pub use toyos_abi::input::{GamepadEvent, GAMEPAD_AXES, HAT_CENTERED, PAD_DISCONNECTED};
+
+static PAD_BUF: Lock<VecDeque<GamepadEvent>> = Lock::new(VecDeque::new());
+static INBOX_WATCHERS: Lock<Vec<InboxId>> = Lock::new(Vec::new());
+
+/// How many pad states the kernel holds for a reader that is not reading.
+///
+/// The mouse's bound and the mouse's argument ([`crate::mouse::MAX_QUEUED_EVENTS`]):
+/// every event restates its pad, so the newest is where the sticks *are* and
+/// dropping the oldest loses nothing a reader needs. 12 KiB at 24 bytes an
+/// event.
+pub const MAX_QUEUED_EVENTS: usize = 512;
+
+/// How many pads can be attached at once. More than any game in reach asks
+/// for, and a ninth is refused at bind rather than given a number a game
+/// would confuse with another.
+pub const MAX_PADS: usize = 8;
+
+/// One bit per pad number, set while a device holds it.
+static IN_USE: AtomicU8 = AtomicU8::new(0);
+
+/// Each pad's last published state, so a report that changes nothing — a pad
+/// repeats its state on every interval whether or not a thumb moved — queues
+/// nothing and wakes nobody.
+static LAST: Lock<[Option<GamepadEvent>; MAX_PADS]> = Lock::new([None; MAX_PADS]);
+
+/// Which pad a report came from.
+///
+/// Numbered as devices bind, lowest free first, for the reason
+/// [`crate::mouse::PointerSource`] is: a number derived from an xHCI slot is
+/// per controller, and a number that is never given back runs out on a
+/// machine with one pad plugged in and out all afternoon. Here the number is
+/// also what a game sees, so lowest-free is what keeps player one player one
+/// while player two swaps batteries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PadId(u8);
+
+impl PadId {
+    /// A number for a pad that is binding, or `None` when [`MAX_PADS`] are
+    /// already attached.
+    pub fn claim() -> Option<Self> {
+        let mut seen = IN_USE.load(Ordering::Relaxed);
+        loop {
+            let bit = seen.trailing_ones();
+            if bit as usize >= MAX_PADS {
+                return None;
+            }
+            match IN_USE.compare_exchange_weak(
+                seen,
+                seen | (1 << bit),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(Self(bit as u8)),
+                Err(now) => seen = now,
+            }
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        self.0
+    }
+}
+
+pub fn add_inbox_watcher(id: InboxId) {
+    let mut w = INBOX_WATCHERS.lock();
+    if !w.contains(&id) { w.push(id); }
+}
+
+pub fn remove_inbox_watcher(id: InboxId) {
+    INBOX_WATCHERS.lock().retain(|&x| x != id);
+}
+
+/// Wake every thread blocked on gamepad input.
+pub fn wake_waiters() {
+    crate::sched::waitqs::wake_device(&crate::sched::waitqs::GAMEPAD_WATCH);
+}
+
+pub fn inbox_watchers() -> Vec<InboxId> {
+    INBOX_WATCHERS.lock().clone()
+}
+
+/// Queue one pad's state. Returns true iff it differed from the last one this
+/// pad published, which is the only case anything was queued.
+pub fn publish(
+    pad: PadId,
+    kind: u8,
+    buttons: u32,
+    axes: [i16; GAMEPAD_AXES],
+    hat: u8,
+) -> bool {
+    let event = GamepadEvent { buttons, axes, pad: pad.0, hat, kind, flags: 0 };
+    let mut last = LAST.lock();
+    if last[pad.0 as usize] == Some(event) {
+        return false;
+    }
+    last[pad.0 as usize] = Some(event);
+    queue(event);
+    true
+}
+
+fn queue(event: GamepadEvent) {
+    let mut buf = PAD_BUF.lock();
+    if buf.len() >= MAX_QUEUED_EVENTS {
+        buf.pop_front();
+    }
+    buf.push_back(event);
+}
+
+/// A pad that is gone: publish its last event, with everything released, and
+/// give its number back. Always queues.
+///
+/// The event is what makes the number safe to reuse. A reader that held
+/// button 1 down for pad 0 would otherwise see the next pad 0's first report
+/// as a continuation of the old one's, and a game with a player walking right
+/// keeps walking until the new pad says otherwise.
+pub fn unbind(pad: PadId, kind: u8) -> bool {
+    LAST.lock()[pad.0 as usize] = None;
+    queue(GamepadEvent {
+        buttons: 0,
+        axes: [0; GAMEPAD_AXES],
+        pad: pad.0,
+        hat: HAT_CENTERED,
+        kind,
+        flags: PAD_DISCONNECTED,
+    });
+    IN_USE.fetch_and(!(1 << pad.0), Ordering::Relaxed);
+    true
+}
+
+/// Throw away everything queued, for the reason
+/// [`crate::keyboard::discard_queued`] gives — and forget what each pad last
+/// said, so its next report is published whole to the new reader rather than
+/// compared against a state only the old one saw.
+pub fn discard_queued() {
+    PAD_BUF.lock().clear();
+    *LAST.lock() = [None; MAX_PADS];
+}
+
+pub fn has_data() -> bool {
+    !PAD_BUF.lock().is_empty()
+}
+
+pub fn try_read_event() -> Option<GamepadEvent> {
+    PAD_BUF.lock().pop_front()
+}
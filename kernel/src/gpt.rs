@@ -25,6 +25,12 @@
 //! kernel's 4 KiB `BlockDevice` down to the device's own logical block, plus
 //! the two things only the kernel can decide: whether firmware and the table
 //! agree, and what to do when two devices claim the same partition.
+//!
+//! One exception, and it is narrow on purpose: [`linux_volumes`] lists
+//! partitions by type, for a read-only mount on a disk that is not the boot
+//! disk. See its documentation for why that caller, and only that caller, may.
+
+use alloc::vec::Vec;
 
 use crate::block::{BlockDevice, DeviceId};
 use crate::sync::Lock;
@@ -259,6 +265,68 @@ fn locate_log(sectors: &mut DeviceSectors<'_>, id: DeviceId, lba_bytes: u32) ->
     }
 }
 
+/// Linux-filesystem partitions a mount may carry, at most, per device.
+///
+/// A desktop install has one or two; a table with more is listed up to here
+/// and the rest named in the log rather than silently dropped.
+pub const MAX_LINUX_VOLUMES: usize = 4;
+
+/// The partitions on `dev` whose *type* says they hold a Linux filesystem.
+///
+/// The one place this kernel selects by type, and it is allowed here because
+/// of who asks: `ext4_adapter` has no write path, so the worst a wrong answer
+/// can do is show somebody's files read-only under a name that says where they
+/// came from. Everything the module documentation says about identity is about
+/// a caller that writes next, which this one cannot.
+///
+/// Never the device carrying the boot partition — the caller skips it, and the
+/// two FAT32 volumes on it are the handoff's business. Read-only like
+/// everything else here.
+pub fn linux_volumes(dev: &mut dyn BlockDevice, lba_bytes: u32) -> Vec<Volume> {
+    let id = dev.device_id();
+    let blank = toyos_gpt::Partition {
+        index: 0,
+        type_guid: Guid::ZERO,
+        unique_guid: Guid::ZERO,
+        first_lba: 0,
+        last_lba: 0,
+    };
+    let mut parts = [blank; MAX_LINUX_VOLUMES];
+    let mut sectors = DeviceSectors::new(dev, lba_bytes);
+    let listed = match toyos_gpt::partitions_of_type(&mut sectors, Guid::LINUX_FILESYSTEM, &mut parts) {
+        Ok(listed) => listed,
+        Err(e) => {
+            log!("gpt: device {id} has no partition table we can list: {e:?}");
+            return Vec::new();
+        }
+    };
+    if listed.matching as usize > listed.count {
+        log!(
+            "gpt: device {id} has {} Linux partitions and only the first {} are looked at",
+            listed.matching,
+            listed.count
+        );
+    }
+    let mut found = Vec::with_capacity(listed.count);
+    for part in &parts[..listed.count] {
+        log!(
+            "gpt: device {id} has Linux partition {} at LBA {}+{}, entry {} of {}",
+            part.unique_guid,
+            part.first_lba,
+            part.lba_count(),
+            part.index,
+            listed.used_entries
+        );
+        found.push(Volume {
+            device: id,
+            lba_bytes,
+            start_lba: part.first_lba,
+            blocks: part.lba_count(),
+        });
+    }
+    found
+}
+
 /// The kernel's 4 KiB `BlockDevice`, seen in the device's own logical blocks.
 ///
 /// A GPT is laid out in the device's blocks, so the parser reads 512-byte LBAs
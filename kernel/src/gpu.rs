@@ -1,5 +1,11 @@
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use toyos_abi::syscall::SyscallError;
+use toyos_abi::{
+    DisplayMode, DisplayModes, FramebufferInfo, Output, HANDLE_INVALID, MAX_OUTPUTS, MODE_CURRENT,
+    MODE_PREFERRED,
+};
+use toyos_edid::{Edid, Mode};
 use crate::object::shm::Region;
 use crate::sync::Lock;
 
@@ -21,6 +27,31 @@ pub struct GpuInfo {
     pub stride: u32,
     pub pixel_format: u32,
     pub flags: u32,
+    /// Where each monitor reads the scanout from, in the order the driver
+    /// numbers them. Never empty and never more than [`MAX_OUTPUTS`].
+    pub outputs: Vec<Output>,
+}
+
+impl GpuInfo {
+    /// The wire description, with every handle still invalid: a description
+    /// carries handles into whichever process reads it, and the claim's own
+    /// read is what mints them.
+    pub fn describe(&self) -> FramebufferInfo {
+        let mut outputs = [Output::default(); MAX_OUTPUTS];
+        let count = self.outputs.len().min(MAX_OUTPUTS);
+        outputs[..count].copy_from_slice(&self.outputs[..count]);
+        FramebufferInfo {
+            scanout: [HANDLE_INVALID; 2],
+            cursor: HANDLE_INVALID,
+            width: self.width,
+            height: self.height,
+            stride: self.stride,
+            pixel_format: self.pixel_format,
+            flags: self.flags,
+            output_count: count as u32,
+            outputs,
+        }
+    }
 }
 
 /// Hardware-agnostic GPU interface. Implement this for any display driver
@@ -32,6 +63,28 @@ pub trait Gpu: Send {
     /// Width and height come straight from userspace. Implementations must
     /// refuse a resolution they cannot back rather than panic on the way.
     fn set_resolution(&mut self, width: u32, height: u32) -> Result<GpuInfo, SyscallError>;
+    /// Place every output. `outputs` has been checked to name as many as the
+    /// last [`GpuInfo`] did, none of them empty and no two overlapping, and is
+    /// already moved so its bounding box starts at the origin. A display with
+    /// one output has nothing to arrange, which is the default.
+    fn arrange(&mut self, _outputs: &[Output]) -> Result<GpuInfo, SyscallError> {
+        Err(SyscallError::NotSupported)
+    }
+    /// What output `output`'s monitor said about itself. A display that
+    /// cannot ask — GOP's one framebuffer, a monitor whose EDID did not parse
+    /// — has nothing to say, which is the default.
+    fn edid(&self, _output: usize) -> Option<Edid> {
+        None
+    }
+    /// Stop the device ahead of S3. A display that is memory the CPU writes
+    /// and nothing else — GOP's, Bochs' — has nothing to stop, which is the
+    /// default.
+    fn suspend(&mut self) {}
+    /// Put back after S3 what the device forgot, so the compositor finds the
+    /// same framebuffer on the same panels. The default has nothing to put
+    /// back; Bochs' mode registers are `bochs::resume`'s, because that
+    /// display is lit before anything registers it here.
+    fn resume(&mut self) {}
 }
 
 static GPU: Lock<Option<Box<dyn Gpu>>> = Lock::new(None);
@@ -42,6 +95,20 @@ pub fn register(gpu: Box<dyn Gpu>, info: GpuInfo) {
     *GPU.lock() = Some(gpu);
 }
 
+/// The registered display down, from `suspend::quiesce`.
+pub fn suspend() {
+    if let Some(gpu) = GPU.lock().as_mut() {
+        gpu.suspend();
+    }
+}
+
+/// The registered display back, from `suspend::restore`.
+pub fn resume() {
+    if let Some(gpu) = GPU.lock().as_mut() {
+        gpu.resume();
+    }
+}
+
 pub fn present_rect(x: u32, y: u32, w: u32, h: u32) {
     let (x, y, w, h) = {
         let info = INFO.lock();
@@ -65,6 +132,94 @@ pub fn set_cursor(hot_x: u32, hot_y: u32) {
 }
 
 pub fn set_resolution(width: u32, height: u32) -> Result<GpuInfo, SyscallError> {
+    remode(|gpu| gpu.set_resolution(width, height))
+}
+
+/// Put the outputs where `outputs` says, after checking what every driver
+/// would otherwise have to: one entry per output, none empty, none overlapping.
+/// The arrangement is translated so its bounding box starts at the origin,
+/// because a position is only ever relative to the other monitors.
+pub fn arrange(outputs: &[Output]) -> Result<GpuInfo, SyscallError> {
+    let count = INFO.lock().as_ref().map_or(0, |info| info.outputs.len());
+    if outputs.is_empty() || outputs.len() != count {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let right = |o: &Output| o.x.checked_add(o.width);
+    let bottom = |o: &Output| o.y.checked_add(o.height);
+    for (i, a) in outputs.iter().enumerate() {
+        if a.width == 0 || a.height == 0 || right(a).is_none() || bottom(a).is_none() {
+            return Err(SyscallError::InvalidArgument);
+        }
+        for b in &outputs[..i] {
+            let apart = right(a) <= Some(b.x)
+                || right(b) <= Some(a.x)
+                || bottom(a) <= Some(b.y)
+                || bottom(b) <= Some(a.y);
+            if !apart {
+                return Err(SyscallError::InvalidArgument);
+            }
+        }
+    }
+    let x0 = outputs.iter().map(|o| o.x).min().unwrap_or(0);
+    let y0 = outputs.iter().map(|o| o.y).min().unwrap_or(0);
+    let placed: Vec<Output> =
+        outputs.iter().map(|o| Output { x: o.x - x0, y: o.y - y0, ..*o }).collect();
+    remode(|gpu| gpu.arrange(&placed))
+}
+
+/// The modes output `output` can be set to, with the one it is showing and
+/// the one its monitor prefers flagged.
+///
+/// An output with no EDID still answers, with the one mode it is in: a picker
+/// that shows an empty list has nothing to offer and no way to say where it
+/// is. So does an output running a size its monitor does not list — one set
+/// by hand, or the driver's fallback for a stale EDID — which is added to the
+/// end of the list, over the last entry if the list is full.
+pub fn modes(output: usize) -> Result<DisplayModes, SyscallError> {
+    let current = INFO
+        .lock()
+        .as_ref()
+        .and_then(|info| info.outputs.get(output).copied())
+        .ok_or(SyscallError::InvalidArgument)?;
+    let edid = GPU.lock().as_ref().and_then(|gpu| gpu.edid(output));
+
+    let mut list = DisplayModes::EMPTY;
+    let mut shown = false;
+    if let Some(edid) = &edid {
+        if let Some((w, h)) = edid.size_mm {
+            (list.width_mm, list.height_mm) = (w, h);
+        }
+        for &m in edid.modes.as_slice() {
+            let mut flags = 0;
+            if edid.preferred.is_some_and(|p| p.same_as(m)) {
+                flags |= MODE_PREFERRED;
+            }
+            // The fastest rate of the size is the first listed, and the one a
+            // virtual display reports: it does not have a rate to tell apart.
+            if !shown && m.width == current.width && m.height == current.height {
+                flags |= MODE_CURRENT;
+                shown = true;
+            }
+            let Mode { width, height, refresh_mhz } = m;
+            list.push(DisplayMode { width, height, refresh_mhz, flags });
+        }
+    }
+    if !shown {
+        let flags = if edid.is_some() { MODE_CURRENT } else { MODE_CURRENT | MODE_PREFERRED };
+        let Output { width, height, .. } = current;
+        let mode = DisplayMode { width, height, refresh_mhz: 0, flags };
+        if !list.push(mode) {
+            list.modes[list.count as usize - 1] = mode;
+        }
+    }
+    Ok(list)
+}
+
+/// Run a driver call that reallocates the scanout, and publish what it
+/// answered.
+fn remode(
+    change: impl FnOnce(&mut dyn Gpu) -> Result<GpuInfo, SyscallError>,
+) -> Result<GpuInfo, SyscallError> {
     let new_info = {
         let mut gpu = GPU.lock();
         let gpu = gpu.as_mut().ok_or(SyscallError::NotSupported)?;
@@ -73,7 +228,7 @@ pub fn set_resolution(width: u32, height: u32) -> Result<GpuInfo, SyscallError>
         // reallocated physical memory. Blind the panic console for the window;
         // worst case it has no screen, never a wild write.
         crate::drivers::panic_console::detach();
-        let result = gpu.set_resolution(width, height);
+        let result = change(gpu.as_mut());
         if result.is_err() {
             crate::drivers::panic_console::rearm();
         }
@@ -88,6 +243,7 @@ pub fn set_resolution(width: u32, height: u32) -> Result<GpuInfo, SyscallError>
         stride: new_info.stride,
         pixel_format: new_info.pixel_format,
         flags: new_info.flags,
+        outputs: new_info.outputs.clone(),
     });
     Ok(new_info)
 }
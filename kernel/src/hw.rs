@@ -64,6 +64,12 @@ impl IrqGuard {
         }
         Self { rflags }
     }
+
+    /// Whether `IF` was set when this guard closed it: whether the caller was
+    /// a context an interrupt could reach at all.
+    pub fn was_open(&self) -> bool {
+        self.rflags & (1 << 9) != 0
+    }
 }
 
 impl Drop for IrqGuard {
@@ -120,6 +126,11 @@ impl Machine for KernelHw {
     }
 
     fn halt(&self) {
+        // The marks on either side are for `arch::sleep`, whose park IPI is
+        // answered here at the pass's depth: nothing is held between them, so
+        // an interrupt that finds the mark set — on the `hlt`, or on the way
+        // out of it — lands on a CPU with nothing to give up.
+        crate::arch::sleep::halting(true);
         // SAFETY: two instructions that touch no memory and no stack. `sti`
         // and `hlt` are Ring 0 instructions this kernel always runs in, and
         // `hlt` is only ever a wait — the scheduler core calls this having
@@ -132,6 +143,7 @@ impl Machine for KernelHw {
         // rather than slept through. Two safe calls would put a sequence point
         // where the architecture guarantees there is none.
         unsafe { asm!("sti; hlt", options(nomem, nostack)); }
+        crate::arch::sleep::halting(false);
     }
 
     /// A remote CPU's `need_resched` byte is not writable from here: `PerCpu`
@@ -628,7 +640,7 @@ unsafe fn switch_witness_mutate(restore: *const KernelCtx) {
     /// `compositor: ready` with fewer than three hundred context switches behind
     /// it — a `MUTATE_AT` of 300 produced a clean boot, which is what a control
     /// that never fires looks like whether or not the instrument works. Eight is
-    /// past the three kernel threads and inside the first dispatches, and it is
+    /// past the four kernel threads and inside the first dispatches, and it is
     /// reached by every boot there is.
     const MUTATE_AT: u64 = 8;
     static SWITCHES: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
@@ -176,6 +176,10 @@ pub enum Source {
     PipeWritable(PipeId),
     VirtioSound,
     Hda,
+    Vsock,
+    UsbAudio,
+    Gamepad,
+    Power,
     /// The machine's kernel log, named by a `SysCap` that carries
     /// `Rights::LOG`.
     ///
@@ -230,7 +234,7 @@ impl Source {
     /// [`Source::watchers`], and a source added to this enum has to answer it.
     ///
     /// Every other source really is its object's: a pipe end, a connection, a
-    /// port and the four remaining device classes each go away with their last
+    /// port and the eight remaining device classes each go away with their last
     /// handle, and nothing else in the kernel names any of them.
     pub fn ended_by_its_last_handle(self) -> Option<EndedSource> {
         // The negative controls restore the prior behaviour for one source
@@ -246,6 +250,10 @@ impl Source {
             | Self::Network
             | Self::VirtioSound
             | Self::Hda
+            | Self::Vsock
+            | Self::UsbAudio
+            | Self::Gamepad
+            | Self::Power
             | Self::Port(_)
             | Self::PipeReadable(_)
             | Self::PipeWritable(_) => true,
@@ -262,7 +270,11 @@ impl PartialEq for Source {
             | (Self::Network, Self::Network)
             | (Self::VirtioSound, Self::VirtioSound)
             | (Self::Log, Self::Log)
-            | (Self::Hda, Self::Hda) => true,
+            | (Self::Hda, Self::Hda)
+            | (Self::Vsock, Self::Vsock)
+            | (Self::UsbAudio, Self::UsbAudio)
+            | (Self::Gamepad, Self::Gamepad)
+            | (Self::Power, Self::Power) => true,
             (Self::Port(a), Self::Port(b)) => Arc::ptr_eq(a, b),
             (Self::PipeReadable(a), Self::PipeReadable(b)) => a == b,
             (Self::PipeWritable(a), Self::PipeWritable(b)) => a == b,
@@ -1105,9 +1117,13 @@ impl Source {
             Self::Port(p) => p.has_pending(),
             Self::Keyboard => crate::keyboard::has_data(),
             Self::Mouse => crate::mouse::has_data(),
-            Self::Network => crate::net::has_packet(),
+            Self::Network => crate::net::readable(),
             Self::VirtioSound => crate::drivers::virtio_sound::has_pending(),
             Self::Hda => crate::drivers::hda::has_pending(),
+            Self::Vsock => crate::drivers::virtio_vsock::has_pending(),
+            Self::UsbAudio => crate::drivers::xhci::audio::has_pending(),
+            Self::Gamepad => crate::gamepad::has_data(),
+            Self::Power => crate::power::has_data(),
             // Never, and the variant's own doc is the argument: this recheck
             // asks "is the object ready", and for the log that question is
             // about a cursor the kernel does not hold. Answering `true` would
@@ -1127,6 +1143,10 @@ impl Source {
             Self::Network => crate::net::add_inbox_watcher(inbox_id),
             Self::VirtioSound => crate::drivers::virtio_sound::add_inbox_watcher(inbox_id),
             Self::Hda => crate::drivers::hda::add_inbox_watcher(inbox_id),
+            Self::Vsock => crate::drivers::virtio_vsock::add_inbox_watcher(inbox_id),
+            Self::UsbAudio => crate::drivers::xhci::audio::add_inbox_watcher(inbox_id),
+            Self::Gamepad => crate::gamepad::add_inbox_watcher(inbox_id),
+            Self::Power => crate::power::add_inbox_watcher(inbox_id),
             Self::Log => crate::log::user::add_inbox_watcher(inbox_id),
             Self::Port(p) => p.add_watcher(inbox_id),
         }
@@ -1142,6 +1162,10 @@ impl Source {
             Self::Network => crate::net::remove_inbox_watcher(inbox_id),
             Self::VirtioSound => crate::drivers::virtio_sound::remove_inbox_watcher(inbox_id),
             Self::Hda => crate::drivers::hda::remove_inbox_watcher(inbox_id),
+            Self::Vsock => crate::drivers::virtio_vsock::remove_inbox_watcher(inbox_id),
+            Self::UsbAudio => crate::drivers::xhci::audio::remove_inbox_watcher(inbox_id),
+            Self::Gamepad => crate::gamepad::remove_inbox_watcher(inbox_id),
+            Self::Power => crate::power::remove_inbox_watcher(inbox_id),
             Self::Log => crate::log::user::remove_inbox_watcher(inbox_id),
             Self::Port(p) => p.remove_watcher(inbox_id),
         }
@@ -1157,6 +1181,10 @@ impl Source {
             Self::Network => crate::net::inbox_watchers(),
             Self::VirtioSound => crate::drivers::virtio_sound::inbox_watchers(),
             Self::Hda => crate::drivers::hda::inbox_watchers(),
+            Self::Vsock => crate::drivers::virtio_vsock::inbox_watchers(),
+            Self::UsbAudio => crate::drivers::xhci::audio::inbox_watchers(),
+            Self::Gamepad => crate::gamepad::inbox_watchers(),
+            Self::Power => crate::power::inbox_watchers(),
             Self::Log => crate::log::user::inbox_watchers(),
             Self::Port(p) => p.watchers(),
         }
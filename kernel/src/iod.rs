@@ -1,10 +1,11 @@
 //! `iod`: the context deferred filesystem work runs in, because `Drop` has no
 //! `Parkable`.
 //!
-//! The third of the kernel's three threads — `klogd` drains the console
+//! The third of the kernel's four threads — `klogd` drains the console
 //! (`log/console.rs`), `usbd` owns the xHCI port machine
-//! (`drivers::xhci::usbd`), and this one owns the write-back queue: the flush
-//! a closed file's dirty pages owe, and page-cache eviction's.
+//! (`drivers::xhci::usbd`), `acpid` runs firmware's methods (`drivers::aml`),
+//! and this one owns the write-back queue: the flush a closed file's dirty
+//! pages owe, and page-cache eviction's.
 //!
 //! **Why the write-back cannot stay where it is.** `OpenFileState::drop`
 //! (`object/file.rs`) takes the VFS lock and flushes. Once that lock is a
@@ -21,15 +22,25 @@
 //! that gives the CPU back, and a panic row. The loop below is where C12's
 //! drain goes.
 //!
+//! **Background write-back is the first thing to push.** Every `write` to a file
+//! on a disk filesystem queues that file here with [`submit`]. This thread
+//! writes a queued file back once it has waited [`EXPIRE`], or at once when
+//! the dirty set passes `file_cache::DirtyPressure::Background`. Past
+//! `Throttle` the writer does not wait for this thread and writes back its own
+//! file. Before this, dirty pages were bounded by nothing except fsync and
+//! close, and eviction cannot free a dirty page. Closed files still flush in
+//! `OpenFileState::drop`. Moving them here is the `SleepLock` change, not this
+//! one.
+//!
 //! **One `iod`, machine-wide, and that is a decision with a measurement owed.**
 //! At the 128-core target the root `CLAUDE.md` sets, one thread draining the
 //! write-back of 128 cores' closed files is a serialisation point nobody has
 //! sized — §10 says so in terms and leaves per-CPU as the obvious escape. It
-//! costs nothing to leave open because the producers do not exist yet: at C6
-//! nothing pushes, so there is nothing to serialise and no measurement a
-//! honest number could come from. **C12 is where it is measured**, against real
-//! producers, and this paragraph is the record that the question was asked
-//! rather than missed.
+//! is still cheap to leave open. The only producer so far is background
+//! write-back, and a writer under real pressure does not wait for this thread:
+//! past `Throttle` it flushes its own file. **C12 is where it is measured**,
+//! against close's producers, and this paragraph is the record that the
+//! question was asked rather than missed.
 //!
 //! **Its panic is recoverable.** A killed `iod` costs the machine its deferred
 //! write-back — dirty pages stop reaching the device — and that is a loss
@@ -37,16 +48,116 @@
 //! `klogd`'s is not recoverable for the opposite reason: its loss is the one
 //! nothing left alive can report.
 
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
 use toyos_sched::task::WaitClass;
 
-use crate::completion::{self, Subject, Token};
+use crate::completion::{self, Outcome, Subject, Token, Watch};
+use crate::file_cache::{self, DirtyPressure, FileId};
 use crate::sched::kthread::{self, OnPanic};
 use crate::scheduler;
-use crate::time::Deadline;
+use crate::sync::Lock;
+use crate::time::{Deadline, Duration, Instant};
 
 /// The name `sched::dump`, `ps` and a crash report use.
 const NAME: &str = "iod";
 
+/// How long a queued file waits before it is written back while the dirty
+/// set is below the background threshold. This bounds what a power cut loses
+/// from a writer that never calls `fsync`. It is also long enough that a file
+/// written in a burst is written back once and not once per `write`.
+const EXPIRE: Duration = Duration::from_secs(5);
+
+/// A file that has dirty pages and the handle details a flush needs. There is
+/// one entry per file, and a newer write updates the `mtime`.
+struct Pending {
+    path: String,
+    mtime: u64,
+    since: Instant,
+}
+
+static QUEUE: Lock<BTreeMap<FileId, Pending>> = Lock::new(BTreeMap::new());
+
+/// What this thread arms on. It is a static rather than the thread's own watch
+/// because [`submit`] runs in whichever process wrote, and that process has no
+/// handle on this thread.
+static WRITEBACK: Watch = Watch::new();
+
+/// Queue `file_id` for write-back after a `write` through a handle opened as
+/// `path`. `pressure` is the dirty set's level, read after the write.
+///
+/// Called with the process's handle table held, so it allocates once per file
+/// and never blocks. A post with nobody armed costs a load, which is all a call
+/// costs before [`start`] runs.
+pub fn submit(path: &str, file_id: FileId, mtime: u64, pressure: DirtyPressure) {
+    let queued = {
+        let mut queue = QUEUE.lock();
+        match queue.get_mut(&file_id) {
+            Some(pending) => {
+                pending.mtime = mtime;
+                false
+            }
+            None => {
+                let since = crate::clock::now();
+                queue.insert(file_id, Pending { path: String::from(path), mtime, since });
+                true
+            }
+        }
+    };
+    // A new entry means there is a new expiry to wait for. Pressure means the
+    // queue is to be drained now.
+    if queued || pressure >= DirtyPressure::Background {
+        completion::post(Subject::of(&WRITEBACK), Outcome::Ready);
+    }
+}
+
+/// Write back every queued file that is due, and answer when the next one
+/// will be.
+fn drain() -> Deadline {
+    let now = crate::clock::now();
+    let pressed = file_cache::dirty_pressure() >= DirtyPressure::Background;
+    let due: BTreeMap<FileId, Pending> = {
+        let mut queue = QUEUE.lock();
+        let (due, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = core::mem::take(&mut *queue)
+            .into_iter()
+            .partition(|(_, pending)| pressed || now - pending.since >= EXPIRE);
+        *queue = kept;
+        due
+    };
+    for (file_id, pending) in &due {
+        let mut vfs = crate::vfs::lock();
+        // Asked under the VFS lock: close and unlink both take it, so a file
+        // that is still open here is still open during the flush. A flush of a
+        // closed file would record the size the cache no longer holds, which
+        // is zero.
+        if !file_cache::wants_writeback(*file_id) {
+            continue;
+        }
+        // The pages stay dirty, so the next write queues the file again and
+        // the handle's own close reports the failure to the handle's owner.
+        let flushed = vfs.flush_file(&pending.path, *file_id, pending.mtime);
+        if let Err(e) = &flushed {
+            log!("iod: write-back of {} failed: {}", pending.path, e);
+        }
+        // Which of the two triggers fired is the only thing a test can hold
+        // this thread to, and a line per write-back on every boot would be a
+        // line every `EXPIRE` for `logd`'s own file.
+        #[cfg(feature = "boot-actuators")]
+        if flushed.is_ok() && crate::actuator::iod_trace() {
+            let queued = now - pending.since;
+            let why = if queued >= EXPIRE { "expired" } else { "background" };
+            log!("iod: wrote back {} ({why}, queued {} ms)", pending.path, queued.millis());
+        }
+    }
+    QUEUE
+        .lock()
+        .values()
+        .map(|pending| pending.since)
+        .min()
+        .map_or(Deadline::never(), |since| Deadline::at(since + EXPIRE))
+}
+
 /// Start the thread. Called once, from `kernel_main`, beside `klogd`'s.
 pub fn start() {
     let _ = kthread::spawn(NAME, body, 0, OnPanic::Recover);
@@ -54,7 +165,6 @@ pub fn start() {
 
 extern "C" fn body(_arg: u64) -> ! {
     let parkable = scheduler::Parkable::at_entry();
-    let handle = crate::sched::driver::current_handle().expect("iod runs as a task");
     // The task half of the operation-nesting gate, and this thread is where it
     // lives because of the sentence above: `iod` is the one context in this
     // kernel that is a task, exists on every boot, and reaches its loop with
@@ -71,21 +181,21 @@ extern "C" fn body(_arg: u64) -> ! {
     // that pushes while this thread is draining must find the watch still
     // armed.
     let armed = completion::arm(
-        Subject::of(handle.watch()),
+        Subject::of(&WRITEBACK),
         Token::new(0),
         WaitClass::Io,
     )
     .expect("a kernel thread is a task and can arm");
     loop {
-        // C12's drain goes here: take the queue's head, flush it under the VFS
-        // sleep lock with this thread's own `Parkable`, and post the completion
-        // whoever called `SYS_FSYNC` is parked on. Nothing pushes yet.
+        // The rest of C12's drain goes here too: close's flush, and the
+        // completion a `SYS_FSYNC` caller parks on.
         //
-        // No deadline: what ends this wait is a push, and a periodic wake on a
-        // machine with nothing to write back is an audio change (root
-        // `CLAUDE.md`).
+        // The deadline is the oldest entry's expiry, or never when the queue
+        // is empty: a periodic wake on a machine with nothing to write back is
+        // an audio change (root `CLAUDE.md`).
         //
         // The cancel arm is unreachable: nothing retires a kernel thread.
-        let _ = completion::wait(&parkable, &armed, Deadline::never());
+        let next = drain();
+        let _ = completion::wait(&parkable, &armed, next);
     }
 }
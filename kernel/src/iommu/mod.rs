@@ -149,6 +149,16 @@ pub fn init(rsdp_addr: u64, devices: &[crate::drivers::pci::PciDevice]) {
     vtd::init(rsdp_addr, devices);
 }
 
+/// Stop every unit translating ahead of S3, once no device is mastering.
+pub fn suspend() {
+    vtd::suspend();
+}
+
+/// Turn every unit back on after S3, before any device masters again.
+pub fn resume() {
+    vtd::resume();
+}
+
 /// The unit blocked a transaction and raised its fault event.
 ///
 /// Reached from the IDT gate the unit's own `FEDATA` names. Not a device's
@@ -26,6 +26,8 @@ pub mod fault;
 mod queue;
 mod table;
 
+use alloc::vec::Vec;
+
 use crate::drivers::acpi::TableError;
 use crate::drivers::pci::PciDevice;
 use crate::iommu::{AddressWidth, StreamId};
@@ -221,6 +223,21 @@ impl Unit {
             core::hint::spin_loop();
         }
     }
+
+    /// Clear one persistent `GCMD` bit and wait for `GSTS` to drop it.
+    fn revoke(&mut self, bit: u32, what: &str) {
+        self.gcmd &= !bit;
+        self.regs.write_u32(GCMD_REG, self.gcmd);
+        let deadline = crate::clock::nanos_since_boot() + COMMAND_TIMEOUT.nanos();
+        while self.regs.read_u32(GSTS_REG) & bit != 0 {
+            assert!(
+                crate::clock::nanos_since_boot() < deadline,
+                "iommu: unit{} never reported {what}",
+                self.index
+            );
+            core::hint::spin_loop();
+        }
+    }
 }
 
 /// Firmware's register base, mapped only if it is one.
@@ -417,26 +434,10 @@ fn enable(mut unit: Unit, devices: &[PciDevice], domains: &mut [Option<Table>; 2
             table::bind(&mut tables, root, stream, domain, width);
         }
 
-        let mut queue = Queue::new(&mut tables, unit.regs);
-        drop(tables);
-
-        // Before `TE`, so the first transaction this unit blocks is one it can
-        // report rather than one that is merely counted.
-        fault::arm(index, unit.regs, records, crate::arch::idt::DMA_FAULT_VECTOR);
-
-        unit.command(
-            QUEUED_INVALIDATION_ENABLE,
-            true,
-            QUEUED_INVALIDATION_ENABLE,
-            "queued invalidation",
-        );
-        unit.regs.write_u64(RTADDR_REG, root.phys());
-        unit.command(SET_ROOT_TABLE_POINTER, false, ROOT_TABLE_SET, "the root table pointer");
-        queue.invalidate_all(unit.regs);
         root
     };
-
-    unit.command(TRANSLATION_ENABLE, true, TRANSLATION_ENABLE, "translation");
+    let mut queue = Queue::new(&mut TABLES.lock(), unit.regs);
+    switch_on(&mut unit, root, &mut queue, records);
 
     let gsts = unit.regs.read_u32(GSTS_REG);
     log!(
@@ -450,6 +451,59 @@ fn enable(mut unit: Unit, devices: &[PciDevice], domains: &mut [Option<Table>; 2
         width.bits(),
         devices.len(),
     );
+    ENABLED.lock().push(Enabled { unit, root, queue, records });
+}
+
+/// Turn a unit on over tables that are already built: the fault event, the
+/// queue, the root table, a full invalidation, and translation last.
+///
+/// Boot's order, and the resume's — `queue` has been pointed at the unit and
+/// `QIE` is off in both.
+fn switch_on(unit: &mut Unit, root: Table, queue: &mut Queue, records: fault::Records) {
+    // Before `TE`, so the first transaction this unit blocks is one it can
+    // report rather than one that is merely counted.
+    fault::arm(unit.index, unit.regs, records, crate::arch::idt::DMA_FAULT_VECTOR);
+
+    unit.command(QUEUED_INVALIDATION_ENABLE, true, QUEUED_INVALIDATION_ENABLE, "queued invalidation");
+    unit.regs.write_u64(RTADDR_REG, root.phys());
+    unit.command(SET_ROOT_TABLE_POINTER, false, ROOT_TABLE_SET, "the root table pointer");
+    queue.invalidate_all(unit.regs);
+    unit.command(TRANSLATION_ENABLE, true, TRANSLATION_ENABLE, "translation");
+}
+
+/// A unit [`enable`] turned on, with what it takes to turn it on again.
+struct Enabled {
+    unit: Unit,
+    root: Table,
+    queue: Queue,
+    records: fault::Records,
+}
+
+/// Every unit translating, for [`suspend`] and [`resume`]. The tables
+/// themselves are in [`TABLES`] and RAM keeps them through S3; what the sleep
+/// loses is the unit's registers, which is all this holds.
+static ENABLED: Lock<Vec<Enabled>> = Lock::new(Vec::new());
+
+/// Translation and queued invalidation off on every unit, with every bus
+/// master already stopped. A unit left translating would be one [`resume`]
+/// could not repoint: `IQA` is not writable while `QIE` is set.
+pub fn suspend() {
+    for enabled in ENABLED.lock().iter_mut() {
+        enabled.unit.revoke(TRANSLATION_ENABLE, "translation off");
+        enabled.unit.revoke(QUEUED_INVALIDATION_ENABLE, "queued invalidation off");
+    }
+}
+
+/// Every unit back on over the tables it had. Whether the sleep reset it or
+/// not, [`suspend`] left it with both enables clear, which is where boot
+/// found it too.
+pub fn resume() {
+    for enabled in ENABLED.lock().iter_mut() {
+        let Enabled { unit, root, queue, records } = enabled;
+        queue.repoint(unit.regs);
+        switch_on(unit, *root, queue, *records);
+        log!("iommu: unit{} translating again", unit.index);
+    }
 }
 
 /// The class both actuators name their victim by. Chosen rather than a
@@ -81,6 +81,14 @@ impl Queue {
         queue
     }
 
+    /// Point the unit at this queue again, from its start, after S3 reset
+    /// the registers [`Self::new`] wrote. `QIE` is off, as it is there.
+    pub fn repoint(&mut self, regs: Mmio) {
+        self.tail = 0;
+        regs.write_u64(IQA_REG, self.descriptors.phys());
+        regs.write_u64(IQT_REG, 0);
+    }
+
     /// Every cached translation and every cached context entry, gone, and the
     /// unit has said so before this returns.
     ///
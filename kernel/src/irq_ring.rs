@@ -38,10 +38,13 @@ pub enum IrqSource {
     Net,
     Xhci,
     I8042,
+    Input,
+    Vsock,
+    Sci,
 }
 
 impl IrqSource {
-    pub const COUNT: usize = 4;
+    pub const COUNT: usize = 7;
 }
 
 /// 64-byte aligned so two CPUs' slots never share a cache line — the array
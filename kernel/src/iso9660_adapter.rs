@@ -0,0 +1,330 @@
+//! Discs in a drive this machine did not boot from, mounted read-only.
+//!
+//! `toyos-iso9660` reads ISO 9660 with its Joliet and Rock Ridge names; this
+//! file is the kernel's side of it — the 4 KiB
+//! [`BlockDevice`](crate::block::BlockDevice) a drive is served as, and
+//! [`vfs::FileSystem`]. It is `ext4_adapter` with the partition taken
+//! away: a disc is one volume from byte 0 of the device, so there is no table
+//! to consult and nothing to clamp to but the volume itself.
+//!
+//! # Why this cannot write
+//!
+//! The same three layers `ext4_adapter` lists, and a fourth below them:
+//! `toyos_iso9660::BlockAccess` has no write method, [`IsoDevice`] has no
+//! `write_at` and every [`FileSystem`] write answers
+//! [`SyscallError::PermissionDenied`], each mount is `UserAccess::KernelOnly`,
+//! and both drivers a disc arrives through refuse a write to it outright: the
+//! USB one WRITE(10) to an MMC drive, and `ahci` any write to a disc handle.
+//!
+//! # Which devices
+//!
+//! Every USB disk but the boot device whose sector 16 starts an ISO 9660
+//! descriptor set. That is a CD or DVD drive, and it is also a USB stick a
+//! hybrid image was written to — which is why the boot device is skipped: a
+//! stick ToyOS booted from is already `/boot`, and mounting its ISO 9660 face
+//! as well would show one set of files under two names. A stick that holds a
+//! GPT disk and no ISO is `ext4_adapter`'s and answers [`Error::NotIso`] here,
+//! which is not logged.
+//!
+//! Then every disc in an AHCI optical drive. QEMU's `-cdrom` is one: an IDE
+//! drive on the q35 AHCI controller, which `ahci` reads over ATAPI. A machine
+//! booted from one still has no `/boot` — the boot partition GUID is on the
+//! disc's GPT face, in 512-byte sectors a 2048-byte drive does not have, and
+//! nothing asks it — but it has `cdrom0`, the bytes firmware booted from seen
+//! as ISO 9660.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+
+use toyos_abi::syscall::SyscallError;
+use toyos_iso9660::{BlockAccess, Error, IoError, Iso9660};
+
+use crate::block::{BlockDevice, DeviceId};
+use crate::drivers::{ahci, usb_storage};
+use crate::fat32_adapter;
+use crate::file_backing::FileBacking;
+use crate::file_cache::{self, FileId};
+use crate::gpt;
+use crate::page_cache;
+use crate::sync::Lock;
+use crate::vfs::FileSystem;
+
+/// The only transfer unit [`BlockDevice`](crate::block::BlockDevice) has.
+const BLOCK: u64 = 4096;
+
+/// Discs mounted at once. One per drive, and a machine with more than two
+/// drives is not one anybody runs this on.
+const MAX_MOUNTS: usize = 2;
+
+/// A whole device as a byte range, read in whole 4 KiB blocks.
+///
+/// `Ext4Device` without the partition offset, and through the page cache for
+/// the same reason: a directory sector is 2048 bytes, half a device block, and
+/// a Rock Ridge continuation area is a few hundred bytes of some other sector,
+/// so the block just read is very often the next one asked for. Nothing writes
+/// a disc while it is mounted.
+struct IsoDevice {
+    device: DeviceId,
+    /// How many bytes the volume may address.
+    len: u64,
+}
+
+impl IsoDevice {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError> {
+        let end = offset.checked_add(buf.len() as u64).ok_or(IoError)?;
+        if end > self.len {
+            return Err(IoError);
+        }
+        page_cache::read_bytes(self.device, offset, buf).map_err(|_| IoError)
+    }
+}
+
+/// Each mounted disc's device, reachable without the VFS lock, for
+/// `ext4_adapter::VOLUMES`'s reason and under its lock order.
+static VOLUMES: [Lock<Option<IsoDevice>>; MAX_MOUNTS] = [Lock::new(None), Lock::new(None)];
+
+/// One [`VOLUMES`] entry in the shape `toyos-iso9660` asks for.
+pub struct IsoVolume {
+    slot: usize,
+    bytes: u64,
+}
+
+impl BlockAccess for IsoVolume {
+    fn capacity(&self) -> u64 {
+        self.bytes
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError> {
+        let mut guard = VOLUMES[self.slot].lock();
+        guard.as_mut().ok_or(IoError)?.read_at(offset, buf)
+    }
+}
+
+/// Shared between the adapter and its backings, as `ext4_adapter::Shared` is.
+type Shared = Arc<Lock<Iso9660<IsoVolume>>>;
+
+/// A file on a disc: an extent and a length, so a page fault resolves nothing.
+struct IsoBacking {
+    mount: String,
+    fs: Shared,
+    file: toyos_iso9660::File,
+}
+
+impl FileBacking for IsoBacking {
+    fn read_page(&self, file_offset: u64, buf: &mut [u8; 4096]) -> crate::block::BlockResult {
+        self.read_range(file_offset, buf)
+    }
+
+    /// One device read for the whole batch, since a file is one extent.
+    fn read_pages(&self, file_offset: u64, buf: &mut [u8]) -> crate::block::BlockResult {
+        self.read_range(file_offset, buf)
+    }
+
+    fn file_size(&self) -> u64 {
+        self.file.len()
+    }
+}
+
+impl IsoBacking {
+    fn read_range(&self, file_offset: u64, buf: &mut [u8]) -> crate::block::BlockResult {
+        buf.fill(0);
+        match self.fs.lock().read(&self.file, file_offset, buf) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                buf.fill(0);
+                log!("{}: read of extent {} at {file_offset} failed: {e}; serving zeros",
+                    self.mount, self.file.extent());
+                Err(crate::block::BlockError)
+            }
+        }
+    }
+}
+
+/// What one of `toyos-iso9660`'s errors means to the [`FileSystem`] trait's
+/// caller. Exhaustive, for `ext4_adapter::as_syscall_error`'s reason.
+fn as_syscall_error(e: Error) -> SyscallError {
+    match e {
+        Error::NotFound => SyscallError::NotFound,
+        Error::Io
+        | Error::NotIso
+        | Error::Truncated
+        | Error::CorruptDirectory
+        | Error::CorruptExtension
+        | Error::CorruptCatalog => SyscallError::Io,
+        // Multi-extent, interleaved and zisofs files: there, and not decoded.
+        Error::Unsupported => SyscallError::NotSupported,
+        Error::NotADirectory | Error::IsADirectory | Error::NotASymlink => {
+            SyscallError::InvalidArgument
+        }
+        Error::LimitExceeded => SyscallError::ResourceExhausted,
+    }
+}
+
+/// Log what the disc said, and hand the caller the code for it.
+fn refused(mount: &str, op: &str, name: &str, e: Error) -> SyscallError {
+    if e != Error::NotFound {
+        log!("{mount}: {op} of {name}: {e}");
+    }
+    as_syscall_error(e)
+}
+
+/// VFS adapter for one disc. Identity by path, as on the ext4 mounts.
+pub struct IsoFs {
+    mount: String,
+    fs: Shared,
+    by_name: HashMap<String, FileId>,
+}
+
+impl IsoFs {
+    /// The VFS mount name, which is also the top-level directory.
+    pub fn mount_name(&self) -> &str {
+        &self.mount
+    }
+
+    fn backing(&mut self, name: &str) -> Result<Arc<dyn FileBacking>, SyscallError> {
+        let file = self.fs.lock().open(name).map_err(|e| refused(&self.mount, "open", name, e))?;
+        Ok(Arc::new(IsoBacking { mount: self.mount.clone(), fs: self.fs.clone(), file }))
+    }
+}
+
+impl FileSystem for IsoFs {
+    fn list(&mut self, limit: usize) -> Result<Vec<(String, u64)>, SyscallError> {
+        self.fs.lock().walk(limit).map_err(|e| refused(&self.mount, "list", "/", e))
+    }
+
+    fn file_mtime(&mut self, name: &str) -> Result<u64, SyscallError> {
+        self.fs
+            .lock()
+            .metadata(name)
+            .map(|m| m.modified_unix)
+            .map_err(|e| refused(&self.mount, "metadata", name, e))
+    }
+
+    /// Rock Ridge links only; on a disc without it, nothing is a link.
+    fn read_link(&mut self, name: &str) -> Result<Option<String>, SyscallError> {
+        self.fs
+            .lock()
+            .read_link_from_root(name)
+            .map_err(|e| refused(&self.mount, "read_link", name, e))
+    }
+
+    fn open_file(&mut self, name: &str) -> Result<(FileId, Option<Arc<dyn FileBacking>>), SyscallError> {
+        let backing = self.backing(name)?;
+        if let Some(&file_id) = self.by_name.get(name) {
+            file_cache::open(file_id);
+            return Ok((file_id, Some(backing)));
+        }
+        let file_id = file_cache::create_file(true);
+        file_cache::set_size(file_id, backing.file_size());
+        self.by_name.insert(String::from(name), file_id);
+        Ok((file_id, Some(backing)))
+    }
+
+    fn create(&mut self, _name: &str, _mtime: u64) -> Result<FileId, SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn close_file(&mut self, file_id: FileId) {
+        self.by_name.retain(|_, &mut id| id != file_id);
+    }
+
+    fn delete(&mut self, _name: &str) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn rename(&mut self, _old: &str, _new: &str) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn write_page(&mut self, _file_id: FileId, _page_idx: u32, _data: &[u8; 4096]) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn update_metadata(&mut self, _file_id: FileId, _size: u64, _mtime: u64) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn create_symlink(&mut self, _name: &str, _target: &str) -> Result<(), SyscallError> {
+        Err(SyscallError::PermissionDenied)
+    }
+
+    fn sync(&mut self) -> Result<(), SyscallError> {
+        Ok(())
+    }
+
+    fn open_backing(&mut self, name: &str) -> Result<Arc<dyn FileBacking>, SyscallError> {
+        self.backing(name)
+    }
+}
+
+/// Mount every ISO 9660 volume on a USB disk other than the boot device, and
+/// on every AHCI disc, as `cdrom0`, `cdrom1`, … in that order.
+///
+/// Called once at boot, beside `ext4_adapter::mount_all`. Nothing is written
+/// to find out, and a disk that is not a disc is passed over in silence.
+pub fn mount_all() -> Vec<IsoFs> {
+    let boot_device = gpt::boot_volume().map(|v| v.device);
+    let mut mounted = Vec::new();
+    let usb = (0..usb_storage::count()).filter_map(|index| usb_storage::open(index).map(|d| d.device_id()));
+    let ahci = (0..ahci::disc_count()).filter_map(|index| ahci::open_disc(index).map(|d| d.device_id()));
+    for id in usb.chain(ahci) {
+        if Some(id) == boot_device {
+            continue;
+        }
+        if mounted.len() == MAX_MOUNTS {
+            log!("cdrom: more discs than mount slots; not mounting device {id}");
+            break;
+        }
+        let Some(device) = fat32_adapter::device_carrying(id) else { continue };
+        if let Some(fs) = mount(mounted.len(), device) {
+            mounted.push(fs);
+        }
+    }
+    mounted
+}
+
+/// Open one whole device as slot `slot`, if it holds an ISO 9660 volume.
+fn mount(slot: usize, device: DeviceId) -> Option<IsoFs> {
+    let name = format!("cdrom{slot}");
+    let device_bytes = page_cache::lock(device).block_count().checked_mul(BLOCK)?;
+    *VOLUMES[slot].lock() = Some(IsoDevice { device, len: device_bytes });
+
+    // Tightened to the volume before anything past the descriptors is read:
+    // `probe` has already refused a volume larger than the device, and the
+    // rest of a hybrid stick past its ISO 9660 volume is its GPT disk's.
+    let mut access = IsoVolume { slot, bytes: device_bytes };
+    let volume = match Iso9660::probe(&mut access) {
+        Ok(volume) => volume,
+        Err(e) => {
+            if e != Error::NotIso {
+                log!("{name}: device of {device_bytes} bytes holds nothing this kernel can mount: {e}");
+            }
+            *VOLUMES[slot].lock() = None;
+            return None;
+        }
+    };
+    access.bytes = volume.bytes();
+    if let Some(device) = VOLUMES[slot].lock().as_mut() {
+        device.len = volume.bytes();
+    }
+
+    match Iso9660::mount(access) {
+        Ok(fs) => {
+            log!(
+                "{name}: mounted read-only, {} bytes, {:?} names, label {:?}",
+                volume.bytes(),
+                fs.namespace(),
+                volume.label_str().unwrap_or("")
+            );
+            Some(IsoFs { mount: name, fs: Arc::new(Lock::new(fs)), by_name: HashMap::new() })
+        }
+        Err(e) => {
+            log!("{name}: device of {device_bytes} bytes holds nothing this kernel can mount: {e}");
+            *VOLUMES[slot].lock() = None;
+            None
+        }
+    }
+}
@@ -48,8 +48,11 @@ mod actuator;
 mod mm;
 mod panic;
 
+mod gamepad;
 mod keyboard;
 mod mouse;
+mod power;
+mod suspend;
 #[cfg(feature = "boot-actuators")]
 mod input_merge_test;
 #[cfg(feature = "boot-actuators")]
@@ -62,12 +65,16 @@ mod sched_gate;
 mod nmi_gate;
 mod block;
 mod gpt;
+mod crypt_device;
 mod page_cache;
 mod file_cache;
 mod tmpfs;
 mod file_backing;
 mod bcachefs_adapter;
 mod fat32_adapter;
+mod ext4_adapter;
+mod iso9660_adapter;
+mod ninep_adapter;
 #[cfg(feature = "boot-actuators")]
 mod heartbeat;
 mod vfs;
@@ -123,7 +130,7 @@ mod late_panic {
 use crate::mm::paging::CachePolicy;
 use alloc::boxed::Box;
 use arch::{apic, cpu, idt, pat, percpu, smp, syscall};
-use drivers::{acpi, gop, i8042, ioapic, nvme, pci, serial, virtio_console, virtio_gpu, virtio_net, virtio_sound, xhci};
+use drivers::{acpi, ahci, aml, bochs, e1000e, gop, i8042, ioapic, nvme, pci, sci, serial, virtio_9p, virtio_blk, virtio_console, virtio_gpu, virtio_input, virtio_net, virtio_sound, virtio_vsock, xhci};
 use toyos_abi::boot::{KernelArgs, MemoryMapEntry};
 
 #[panic_handler]
@@ -294,15 +301,7 @@ fn register_gpu(driver: Box<dyn gpu::Gpu>, info: gpu::GpuInfo) {
     crate::device::set_framebuffer_info(crate::device::Screen {
         // A description carries handles into whichever process reads it, and
         // that process does not exist yet: `try_claim` mints them.
-        info: toyos_abi::FramebufferInfo {
-            scanout: [toyos_abi::HANDLE_INVALID; 2],
-            cursor: toyos_abi::HANDLE_INVALID,
-            width: info.width,
-            height: info.height,
-            stride: info.stride,
-            pixel_format: info.pixel_format,
-            flags: info.flags,
-        },
+        info: info.describe(),
         scanout: info.scanout.clone(),
         cursor: info.cursor.clone(),
     });
@@ -467,6 +466,7 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
         mm::Region { start: kernel_args.kernel_elf_addr, end: kernel_args.kernel_elf_addr + kernel_args.kernel_elf_size },
         mm::Region { start: kernel_args.kernel_stack_addr, end: kernel_args.kernel_stack_addr + kernel_args.kernel_stack_size },
         mm::Region { start: 0x8000, end: 0x9000 }, // AP trampoline page
+        mm::Region { start: 0x9000, end: 0xC000 }, // S3 wake page tables
     ];
 
     mm::init(maps, &reserved);
@@ -535,7 +535,31 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
     //
     // `None` from `open_home` is the other half and means something different:
     // there *is* a disk and it is not ours to write to. Both land on a tmpfs.
-    let home_volume = match nvme::init(&pci_devices) {
+    //
+    // Every virtio and AHCI disk is probed, whichever one ends up carrying
+    // `/home`: a machine that boots off one has its boot partition there, and
+    // the question has to be asked before the page cache owns the device.
+    for index in 0..virtio_blk::init(&pci_devices) {
+        if let Some(mut disk) = virtio_blk::open(index) {
+            let lba_bytes = disk.logical_block_bytes();
+            gpt::probe(&mut disk, lba_bytes);
+        }
+    }
+    for index in 0..ahci::init(&pci_devices) {
+        if let Some(mut disk) = ahci::open(index) {
+            let lba_bytes = disk.logical_block_bytes();
+            gpt::probe(&mut disk, lba_bytes);
+        }
+    }
+    // NVMe first, then virtio disk 0, then AHCI disk 0: a machine with NVMe
+    // and virtio is a laptop under a hypervisor's passthrough, and its own disk
+    // is the one it meant; a VM given a virtio disk on a q35 machine, whose
+    // AHCI controller is always there, was given it to use.
+    //
+    // NVMe wants one queue per CPU and the APs are not up yet, so it is told
+    // which CPUs there will be.
+    let cpus = smp::planned_apic_ids(&madt);
+    let home_disk: Option<Box<dyn block::BlockDevice>> = match nvme::init(&pci_devices, &cpus) {
         Some(mut nvme_dev) => {
             // Before the page cache takes the device: this is the one place
             // that has it in the device's own logical blocks, and asking a
@@ -543,19 +567,39 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
             // anything on it turns out to be ours.
             let sector_size = nvme_dev.sector_size();
             gpt::probe(&mut nvme_dev, sector_size);
-            page_cache::init(Box::new(nvme_dev));
-            // Before anything has mounted the device, so the one block the gate
-            // asks for is one nothing else is reading yet.
+            Some(Box::new(nvme_dev))
+        }
+        None => match (virtio_blk::open(0), ahci::open(0)) {
+            (Some(disk), _) => {
+                log!("NVMe: no controller on this machine; /home goes on virtio disk 0");
+                Some(Box::new(disk))
+            }
+            (None, Some(disk)) => {
+                log!("NVMe: no controller on this machine; /home goes on AHCI disk 0");
+                Some(Box::new(disk))
+            }
+            (None, None) => {
+                log!("NVMe: no controller on this machine, storage unavailable");
+                None
+            }
+        },
+    };
+    // Between the driver and the cache: an encrypted disk is asked for its
+    // passphrase here, and everything from the cache up sees the plaintext
+    // volume. A disk left locked is no disk, so `/home` is a tmpfs exactly as
+    // it is on a machine without one.
+    let home_volume = match home_disk.and_then(crypt_device::open) {
+        Some(dev) => {
+            let home = page_cache::register(dev);
+            // Before anything has mounted the device, so the one block the
+            // gate asks for is one nothing else is reading yet.
             #[cfg(feature = "boot-actuators")]
             if actuator::nvme_spent_budget() {
-                nvme_gate::run();
+                nvme_gate::run(home);
             }
-            bcachefs_adapter::open_home()
-        }
-        None => {
-            log!("NVMe: no controller on this machine, storage unavailable");
-            None
+            bcachefs_adapter::open_home(home)
         }
+        None => None,
     };
 
     boot_phase!("storage ready", t_storage);
@@ -583,6 +627,11 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
     fat32_adapter::probe_boot_disks();
     i8042::init(kernel_args.rsdp_addr);
     acpi::init_power(kernel_args.rsdp_addr);
+    // After `init_power`, which keeps the DSDT this builds the namespace from,
+    // and with the ECAM window PCI_Config regions are reached through.
+    aml::init(kernel_args.rsdp_addr, ecam);
+    // After `aml::init`, which says which GPEs have something to run.
+    sci::init(kernel_args.rsdp_addr);
 
     boot_phase!("peripherals ready", t_periph);
 
@@ -666,6 +715,31 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
         None => log!("log-volume: not mounted; this boot's kernel log stays in memory"),
     }
 
+    // Linux partitions on any other disk, read-only and under names that say
+    // nothing about their contents. `KernelOnly` is the third of the three
+    // layers `ext4_adapter` documents: the adapter refuses every write, and
+    // this refuses it — and `mkdir`, which the adapter never hears about —
+    // before the adapter is asked.
+    for fs in ext4_adapter::mount_all() {
+        let name = alloc::string::String::from(fs.mount_name());
+        vfs::lock().mount(&name, Box::new(fs), UserAccess::KernelOnly);
+    }
+    // Discs, under the same three layers and for the same reason.
+    for fs in iso9660_adapter::mount_all() {
+        let name = alloc::string::String::from(fs.mount_name());
+        vfs::lock().mount(&name, Box::new(fs), UserAccess::KernelOnly);
+    }
+    // After every mount, so the share it measures is divided among the disks
+    // this machine really registered, plus its own two.
+    #[cfg(feature = "boot-actuators")]
+    if actuator::page_cache_fairness_selftest() {
+        page_cache::fairness_selftest();
+    }
+    #[cfg(feature = "boot-actuators")]
+    if actuator::file_cache_readahead_selftest() {
+        file_cache::readahead_selftest();
+    }
+
     // Kernel string literals, not untrusted input: these are orders of
     // magnitude under `MAX_PATH`, so a refusal here is a kernel bug and gets
     // fail-fast rather than the error return `sys_mkdir` hands userland.
@@ -687,6 +761,22 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
 
     virtio_console::init(&pci_devices);
     virtio_net::init(&pci_devices);
+    // After virtio-net, so a machine with both keeps the NIC it always had and
+    // the 82574 is refused by name rather than bound over it.
+    e1000e::init(&pci_devices);
+    virtio_input::init(&pci_devices);
+    virtio_vsock::init(&pci_devices);
+
+    // Host directories, for the dev loop. Here rather than beside the other
+    // mounts because the channel they arrive on is a device, and `ReadWrite`
+    // because writing back to the host is half of what the share is for. A
+    // machine booted without `-virtfs` binds nothing and mounts nothing.
+    if virtio_9p::init(&pci_devices) > 0 {
+        for fs in ninep_adapter::mount_all() {
+            let name = alloc::string::String::from(fs.mount_name());
+            vfs::lock().mount(&name, Box::new(fs), UserAccess::ReadWrite);
+        }
+    }
 
     virtio_sound::init(&pci_devices);
     drivers::hda::init(&pci_devices);
@@ -697,6 +787,11 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
         // behind GPU.lock(), which the panic path may not take.
         drivers::panic_console::disable();
         register_gpu(gpu_driver, gpu_info);
+    } else if let Some((gpu_driver, gpu_info)) = bochs::init(&pci_devices) {
+        // Ahead of GOP because it is the same device with the one thing GOP
+        // lacks after boot services: a way to change the mode.
+        log!("GPU: using Bochs DISPI");
+        register_gpu(gpu_driver, gpu_info);
     } else if kernel_args.gop_framebuffer != 0 {
         log!("GPU: using UEFI GOP");
         let (gpu_driver, gpu_info) = gop::init(
@@ -776,9 +871,11 @@ unsafe fn kernel_main(kernel_args: &KernelArgs) -> ! {
     // a device's work needs a context of its own rather than whichever thread
     // happened to trap. Here rather than earlier because nothing can run before
     // `enter_idle_loop` anyway, and after `klogd` because a kernel thread that
-    // logs its own spawn wants a drainer to exist.
+    // logs its own spawn wants a drainer to exist. `acpid` is the fourth, for
+    // firmware's methods, and is here for the same two reasons.
     drivers::xhci::usbd::start();
     iod::start();
+    aml::start();
 
     smp::set_ready();
     crate::scheduler::enter_idle_loop();
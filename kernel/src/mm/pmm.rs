@@ -184,8 +184,25 @@ impl Bitmap {
 
 static BITMAP: Lock<Bitmap> = Lock::new(Bitmap::new());
 
+/// How many disjoint runs of RAM [`is_ram`] remembers. A UEFI map has a few
+/// hundred entries, but adjacent usable ones are merged first and what is
+/// left on a real machine is a few dozen; past this many the last run is
+/// stretched over the rest, which only ever answers "RAM" for more.
+const MAX_RAM_RUNS: usize = 128;
+
+/// The firmware memory map's usable entries, byte-exact and merged: what
+/// [`is_ram`] answers from. Kept apart from the bitmap because that is 2 MiB
+/// pages rounded inward, and the question is asked of the bytes in between.
+struct RamRuns {
+    runs: [(u64, u64); MAX_RAM_RUNS],
+    len: usize,
+}
+
+static RAM: Lock<RamRuns> = Lock::new(RamRuns { runs: [(0, 0); MAX_RAM_RUNS], len: 0 });
+
 /// Initialize the bitmap from the UEFI memory map.
 pub(super) fn init(entries: &[MemoryMapEntry], reserved: &[Region]) {
+    record_ram(entries);
     let mut bm = BITMAP.lock();
 
     let mut lo = u64::MAX;
@@ -220,6 +237,40 @@ pub(super) fn init(entries: &[MemoryMapEntry], reserved: &[Region]) {
     }
 }
 
+/// Remember every usable entry's exact extent, merging the ones that touch.
+fn record_ram(entries: &[MemoryMapEntry]) {
+    let mut ram = RAM.lock();
+    let mut usable: [(u64, u64); MAX_RAM_RUNS] = [(0, 0); MAX_RAM_RUNS];
+    let mut n = 0;
+    for entry in entries.iter().filter(|e| is_usable(e) && e.start < e.end) {
+        let (start, end) = (entry.start, entry.end);
+        if let Some(run) = usable[..n].iter_mut().find(|r| start <= r.1 && end >= r.0) {
+            *run = (run.0.min(start), run.1.max(end));
+        } else if n < MAX_RAM_RUNS {
+            usable[n] = (start, end);
+            n += 1;
+        } else {
+            let last = &mut usable[MAX_RAM_RUNS - 1];
+            *last = (last.0.min(start), last.1.max(end));
+        }
+    }
+    ram.runs = usable;
+    ram.len = n;
+}
+
+/// Whether any of `len` bytes at `phys` is RAM the firmware handed this
+/// kernel — the PMM's pages, the kernel image, the boot allocations.
+///
+/// For a caller that reaches physical memory on someone else's word: AML's
+/// `SystemMemory` regions name addresses firmware chose, and the answer to one
+/// that lands on a kernel stack is a refusal, not a write. Byte-exact, because
+/// the firmware's own NVS often shares a 2 MiB page with the RAM below it.
+pub fn is_ram(phys: u64, len: u64) -> bool {
+    let end = phys.saturating_add(len);
+    let ram = RAM.lock();
+    ram.runs[..ram.len].iter().any(|&(start, stop)| phys < stop && end > start)
+}
+
 /// Allocate one 2MB physical page. Does not heap-allocate (safe to call from the allocator).
 pub fn alloc_page(cat: Category) -> Option<PhysPage> {
     let mut bm = BITMAP.lock();
@@ -1,5 +1,6 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
 use crate::inbox::InboxId;
 use crate::sync::Lock;
 use toyos_abi::syscall::SyscallError;
@@ -33,12 +34,33 @@ pub trait Nic: Send {
     /// only thing standing between a `u64` from userland and the device
     /// reading adjacent kernel memory onto the wire.
     fn tx_buf_len(&self) -> usize { 0 }
+
+    /// Whether there is a link. A NIC that cannot tell answers up, which is
+    /// what netd assumed of every NIC before it asked.
+    fn link_up(&self) -> bool { true }
+
+    /// Stop the device ahead of S3, so nothing it does lands after the IOMMU
+    /// and the bus go down. The default does nothing, which is right for a NIC
+    /// whose bus stops it: the USB adapter goes down with its xHCI.
+    fn suspend(&mut self) {}
+
+    /// Bring the device back after S3, or after a sleep that was never
+    /// entered. The rings are the kernel's, so they are posted again here; a
+    /// buffer netd was reading when the machine went down stays netd's to
+    /// hand back.
+    fn resume(&mut self) {}
 }
 
 static NIC: Lock<Option<Box<dyn Nic>>> = Lock::new(None);
 static NIC_INFO: Lock<Option<(NicInfo, crate::object::shm::Region)>> = Lock::new(None);
 static INBOX_WATCHERS: Lock<Vec<InboxId>> = Lock::new(Vec::new());
 
+/// The NIC has news that is not a frame: its link went up or down, or it came
+/// or went. Makes the claim readable until netd asks [`link`], so a netd
+/// parked on its poll wakes for a cable it would otherwise learn about only
+/// from the next frame — and for a removal, after which there is no next frame.
+static NEWS: AtomicBool = AtomicBool::new(false);
+
 pub fn add_inbox_watcher(id: InboxId) {
     let mut w = INBOX_WATCHERS.lock();
     if !w.contains(&id) { w.push(id); }
@@ -53,14 +75,75 @@ pub fn wake_waiters() {
     crate::sched::waitqs::wake_device(&crate::sched::waitqs::NETWORK_WATCH);
 }
 
+/// Wake the NIC's readers, parked and polling both. For a driver that learns
+/// of frames somewhere other than its own interrupt — the USB adapter hears of
+/// them through the xHCI event ring, under that controller's lock, the way a
+/// USB keyboard wakes its readers.
+pub fn wake() {
+    wake_waiters();
+    let watchers = inbox_watchers();
+    if !watchers.is_empty() {
+        crate::inbox::complete_pending_for_event(&watchers, crate::inbox::Source::Network);
+    }
+}
+
 pub fn inbox_watchers() -> Vec<InboxId> {
     INBOX_WATCHERS.lock().clone()
 }
 
+/// The registered NIC down, from `suspend::quiesce`.
+pub fn suspend() {
+    if let Some(nic) = NIC.lock().as_mut() {
+        nic.suspend();
+    }
+}
+
+/// The registered NIC back, from `suspend::restore`. netd is not told: it
+/// finds the same claim, the same buffers and the same link it had, and a
+/// frame that arrived while the machine slept was never received.
+pub fn resume() {
+    if let Some(nic) = NIC.lock().as_mut() {
+        nic.resume();
+    }
+}
+
 pub fn register(nic: Box<dyn Nic>) {
     *NIC.lock() = Some(nic);
 }
 
+/// The NIC is gone — a USB adapter pulled out of its port. Every call on the
+/// claim answers `NotFound` until another registers, and netd is woken to
+/// find that out.
+///
+/// The claim itself survives: it names the class, not the device, and the
+/// adapter that registers next hands netd the same DMA page it already has
+/// mapped. See `drivers::xhci::cdc`.
+pub fn unregister() {
+    *NIC.lock() = None;
+    *NIC_INFO.lock() = None;
+    announce();
+}
+
+/// The NIC has news for netd: its link went up or down, or it arrived after
+/// boot. For the driver that hears of it; the boot-time NICs register before
+/// there is anyone to tell.
+pub fn announce() {
+    NEWS.store(true, Ordering::Release);
+    wake();
+}
+
+/// Whether the link is up, and `NotFound` if there is no NIC. Clears the news
+/// that made the claim readable.
+pub fn link() -> Result<bool, SyscallError> {
+    NEWS.store(false, Ordering::Release);
+    NIC.lock().as_ref().map(|nic| nic.link_up()).ok_or(SyscallError::NotFound)
+}
+
+/// Whether a poll on the claim should complete: a frame waiting, or news.
+pub fn readable() -> bool {
+    NEWS.load(Ordering::Acquire) || has_packet()
+}
+
 pub fn set_nic_info(info: NicInfo, dma: crate::object::shm::Region) {
     *NIC_INFO.lock() = Some((info, dma));
 }
@@ -73,8 +156,13 @@ pub fn has_packet() -> bool {
     NIC.lock().as_ref().is_some_and(|nic| nic.has_packet())
 }
 
-pub fn poll_rx() -> Option<(usize, usize)> {
-    NIC.lock().as_mut().and_then(|nic| nic.poll_rx())
+/// The next received frame, `Ok(None)` if there is none, and `NotFound` if
+/// there is no NIC to have one — which netd has to be able to tell apart,
+/// because one is a quiet wire and the other is an unplugged adapter.
+pub fn poll_rx() -> Result<Option<(usize, usize)>, SyscallError> {
+    let mut guard = NIC.lock();
+    let Some(nic) = guard.as_mut() else { return Err(SyscallError::NotFound) };
+    Ok(nic.poll_rx())
 }
 
 pub fn refill_rx_buf(buf_index: usize) -> Result<(), SyscallError> {
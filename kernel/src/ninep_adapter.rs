@@ -0,0 +1,885 @@
+//! Host directories shared over virtio-9p, as read-write mounts.
+//!
+//! The dev loop's mount: `cargo run` shares the userland build directory with
+//! `-virtfs`, and a binary rebuilt on the host is the next `exec` of its path
+//! in the guest, with no initrd to repack and no reboot. `toyos-9p` writes the
+//! requests and refuses the replies, `drivers::virtio_9p` carries them; this
+//! file is the session between the two — the fids, the attach, and the walk
+//! from a VFS path to a file on the host — and [`vfs::FileSystem`] over it.
+//!
+//! # A file is a path, and a fid is borrowed
+//!
+//! Identity is the path, as on every other mount here: [`by_name`] keys on it,
+//! `delete` drops it and `rename` re-keys it. A fid is not identity. It is the
+//! server's handle on one walk, and every operation that needs one walks from
+//! the root, uses it and clunks it, except for two that live as long as what
+//! holds them: an open file's write fid, clunked by `close_file`, and a
+//! backing's read fid, clunked after the backing is dropped.
+//!
+//! *After*, and not by the drop: a backing is dropped wherever its last `Arc`
+//! goes, which may be under `file_cache`'s lock or the session's own, and a
+//! round trip to the host from there is a lock-order question nobody should
+//! have to answer. So a dropped backing's fid goes onto a list, and the next
+//! request on the session clunks it first.
+//!
+//! # What the host says is what the file is
+//!
+//! The host may change any file under the guest at any time — that is the
+//! point of the mount. Nothing here caches a size, a listing or a page beyond
+//! what `file_cache` already does for an open file, so a program rebuilt on
+//! the host is seen by the next `open` of its name. A file already open in the
+//! guest keeps the pages it has, and reads the rest from whatever the host has
+//! now; a binary replaced under a running process is that process's problem,
+//! as it is on Linux with the same share.
+//!
+//! # What this mount is not
+//!
+//! Not a production filesystem. It is only reached when the machine was booted
+//! with `-virtfs`, which no machine but a developer's is. There are no
+//! permissions: `security_model=none` makes every file the host user's, and the
+//! mount is `ReadWrite` because the use it exists for is writing back.
+//!
+//! [`by_name`]: NinePFs::by_name
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+
+use toyos_9p::{errno, Attr, DirEntry, Error, Reply, Request, SetAttr, IOHDR, MAX_WELEM, NOTAG};
+use toyos_abi::syscall::SyscallError;
+
+use crate::drivers::virtio_9p::{self, Channel, Unanswered, MSIZE};
+use crate::file_backing::FileBacking;
+use crate::file_cache::{self, FileId};
+use crate::sync::Lock;
+use crate::vfs::FileSystem;
+
+/// The fid the attach gives the share's root. Every walk starts from it, and
+/// it is never clunked.
+const ROOT: u32 = 0;
+
+// Linux `open(2)` flags, which is what `Tlopen` and `Tlcreate` carry.
+const O_RDONLY: u32 = 0;
+const O_WRONLY: u32 = 0o1;
+const O_CREAT: u32 = 0o100;
+const O_DIRECTORY: u32 = 0o200_000;
+
+/// What a file or a directory this mount creates is given. The host user owns
+/// both either way; these are only what another host program sees.
+const FILE_MODE: u32 = 0o644;
+const DIR_MODE: u32 = 0o755;
+
+/// How deep [`NinePFs::list`] descends. A host tree is not on-disk data a
+/// crafted image controls, but a symlinked directory loop on the host is one
+/// `ln -s` away, and `Treaddir` reports a symlink as a symlink — so this is
+/// for a server that follows them, not for the ones QEMU ships.
+const MAX_DEPTH: usize = 32;
+
+/// Why a request did not get the answer it asked for.
+enum Fault {
+    /// The request was sent and the reply was refused, or was `Rlerror` —
+    /// which is [`Error::Server`] and the only one of these that is the server
+    /// working.
+    Protocol(Error),
+    /// The request was not answered at all.
+    Transport(Unanswered),
+    /// A VFS path with a `.` or `..` component, or an empty one where a name
+    /// is needed. The VFS normalises both away before a path gets here, so
+    /// this is the check that keeps a walk inside the share if it ever stops.
+    Path,
+}
+
+impl Fault {
+    fn errno(&self) -> Option<u32> {
+        match self {
+            Self::Protocol(Error::Server(errno)) => Some(*errno),
+            _ => None,
+        }
+    }
+
+    fn is(&self, errno: u32) -> bool {
+        self.errno() == Some(errno)
+    }
+}
+
+impl core::fmt::Display for Fault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Protocol(e) => write!(f, "{e}"),
+            Self::Transport(e) => write!(f, "{e}"),
+            Self::Path => f.write_str("not a path inside the share"),
+        }
+    }
+}
+
+/// What one [`Fault`] means to the [`FileSystem`] trait's caller.
+///
+/// The errno is the host's own, so it maps the way Linux's meaning of it does.
+/// Everything that is not an errno — a reply refused, a request unanswered —
+/// is [`SyscallError::Io`], because to a caller it is a device that would not
+/// say, and never `NotFound`.
+fn as_syscall_error(fault: &Fault) -> SyscallError {
+    let Some(errno) = fault.errno() else {
+        return match fault {
+            Fault::Path => SyscallError::InvalidArgument,
+            _ => SyscallError::Io,
+        };
+    };
+    match errno {
+        errno::ENOENT => SyscallError::NotFound,
+        errno::EEXIST => SyscallError::AlreadyExists,
+        errno::EPERM | errno::EACCES | errno::EROFS => SyscallError::PermissionDenied,
+        errno::ENOSPC | errno::EDQUOT | errno::EFBIG => SyscallError::ResourceExhausted,
+        // The name resolves and is not the thing the operation is for, or is
+        // not a name the host will take.
+        errno::ENOTDIR
+        | errno::EISDIR
+        | errno::ENOTEMPTY
+        | errno::EINVAL
+        | errno::ENAMETOOLONG
+        | errno::ELOOP => SyscallError::InvalidArgument,
+        errno::ENOSYS | errno::EOPNOTSUPP => SyscallError::NotSupported,
+        _ => SyscallError::Io,
+    }
+}
+
+/// Log what the host said, and hand the caller the code for it — `FatFs`'s
+/// `refused`, and silent for a name that is not there for the same reason.
+fn refused(mount: &str, op: &str, name: &str, fault: Fault) -> SyscallError {
+    if !fault.is(errno::ENOENT) {
+        log!("{mount}: {op} of {name}: {fault}");
+    }
+    as_syscall_error(&fault)
+}
+
+/// The components of a VFS path, refusing any that would walk somewhere the
+/// path does not name.
+fn components(path: &str) -> Result<Vec<&str>, Fault> {
+    let names: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    if names.iter().any(|&c| c == "." || c == "..") {
+        return Err(Fault::Path);
+    }
+    Ok(names)
+}
+
+/// `a/b/c` as `("a/b", "c")`, and `c` as `("", "c")`.
+fn split_parent(path: &str) -> Result<(&str, &str), Fault> {
+    let (dir, leaf) = path.rsplit_once('/').unwrap_or(("", path));
+    if leaf.is_empty() || leaf == "." || leaf == ".." {
+        return Err(Fault::Path);
+    }
+    Ok((dir, leaf))
+}
+
+/// `target`, read from the link at `link`, as a path from the share's root —
+/// or `None` if it leaves the share.
+fn resolve_link(link: &str, target: &str) -> Option<String> {
+    if target.starts_with('/') {
+        return None;
+    }
+    let mut path: Vec<&str> = link.split('/').filter(|c| !c.is_empty()).collect();
+    path.pop();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                path.pop()?;
+            }
+            part => path.push(part),
+        }
+    }
+    Some(path.join("/"))
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        String::from(name)
+    } else {
+        alloc::format!("{dir}/{name}")
+    }
+}
+
+/// One attached share: the channel it talks over and the fids it has handed
+/// out.
+///
+/// Behind an `Arc<Lock<..>>` rather than inside [`NinePFs`], for the reason
+/// `fat32_adapter`'s devices are statics: a [`NineBacking`] serves a page-fault
+/// miss with `&self` and no filesystem in hand. Lock order is VFS → here →
+/// the channel; nothing takes them the other way.
+struct Session {
+    mount: String,
+    channel: &'static Lock<Channel>,
+    /// What the version exchange settled on; at most [`MSIZE`].
+    msize: u32,
+    tag: u16,
+    next_fid: u32,
+    free: Vec<u32>,
+    /// Backings' fids waiting to be clunked — see the module documentation.
+    retired: Arc<Lock<Vec<u32>>>,
+    /// [`MSIZE`] each, on the heap: a request and its reply are each up to
+    /// 64 KiB, and the deepest caller of this is a page fault.
+    out: Vec<u8>,
+    reply: Vec<u8>,
+}
+
+impl Session {
+    /// Any tag but [`NOTAG`], which is the version exchange's. One request is
+    /// outstanding at a time, so a tag only has to differ from the last one.
+    fn next_tag(&mut self) -> u16 {
+        self.tag = self.tag.wrapping_add(1);
+        if self.tag == NOTAG {
+            self.tag = 0;
+        }
+        self.tag
+    }
+
+    fn fid(&mut self) -> u32 {
+        self.free.pop().unwrap_or_else(|| {
+            self.next_fid += 1;
+            self.next_fid
+        })
+    }
+
+    /// One round trip, with the reply parsed against the request.
+    fn send(&mut self, tag: u16, request: &Request<'_>) -> Result<Reply<'_>, Fault> {
+        let len = request.encode(tag, &mut self.out[..self.msize as usize]).map_err(Fault::Protocol)?;
+        let got = self.channel.lock().transact(&self.out[..len], &mut self.reply).map_err(Fault::Transport)?;
+        toyos_9p::parse(request, tag, &self.reply[..got]).map_err(Fault::Protocol)
+    }
+
+    /// [`Session::send`] with a fresh tag, after clunking whatever the dropped
+    /// backings left behind.
+    fn rpc(&mut self, request: &Request<'_>) -> Result<Reply<'_>, Fault> {
+        let retired = core::mem::take(&mut *self.retired.lock());
+        for fid in retired {
+            self.clunk(fid);
+        }
+        let tag = self.next_tag();
+        self.send(tag, request)
+    }
+
+    /// Give `fid` back to the server and to the free list.
+    ///
+    /// A clunk the server refused still frees the fid — that is the protocol's
+    /// rule, so the server no longer knows it either way. One that was never
+    /// answered does not, and the fid is simply not reused: there are four
+    /// billion of them, and a channel that dropped a request is offline.
+    fn clunk(&mut self, fid: u32) {
+        let tag = self.next_tag();
+        match self.send(tag, &Request::Clunk { fid }) {
+            Ok(_) | Err(Fault::Protocol(_)) => self.free.push(fid),
+            Err(_) => {}
+        }
+    }
+
+    /// A new fid for `path`, walked from the root `MAX_WELEM` names at a time.
+    ///
+    /// A walk that stops short is `ENOENT`, which is what the host would have
+    /// said about the name it stopped at. The fid is then unassigned if the
+    /// first step stopped and still names the last directory reached if a
+    /// later one did, so it is freed or clunked to match.
+    fn walk(&mut self, path: &str) -> Result<u32, Fault> {
+        let names = components(path)?;
+        let fid = self.fid();
+        let mut from = ROOT;
+        // An empty path is one walk of no names: a clone of the root.
+        let chunks: Vec<&[&str]> = if names.is_empty() { vec![&[]] } else { names.chunks(MAX_WELEM).collect() };
+        for chunk in chunks {
+            let walked = match self.rpc(&Request::Walk { fid: from, newfid: fid, names: chunk }) {
+                Ok(Reply::Walk { walked, .. }) => walked,
+                Ok(_) => unreachable!("parse answers the reply to the request it was given"),
+                Err(e) => {
+                    self.forget(fid, from == fid);
+                    return Err(e);
+                }
+            };
+            if walked < chunk.len() {
+                self.forget(fid, from == fid);
+                return Err(Fault::Protocol(Error::Server(errno::ENOENT)));
+            }
+            from = fid;
+        }
+        Ok(fid)
+    }
+
+    /// A fid a walk was abandoned with: clunked if the server assigned it,
+    /// back on the free list if it never did.
+    fn forget(&mut self, fid: u32, assigned: bool) {
+        if assigned {
+            self.clunk(fid);
+        } else {
+            self.free.push(fid);
+        }
+    }
+
+    fn getattr(&mut self, fid: u32) -> Result<Attr, Fault> {
+        match self.rpc(&Request::Getattr { fid, mask: Attr::BASIC })? {
+            Reply::Getattr(attr) => Ok(attr),
+            _ => unreachable!("parse answers the reply to the request it was given"),
+        }
+    }
+
+    /// `f` on a fid walked to `path`, which is clunked whatever `f` answers.
+    fn with_fid<T>(&mut self, path: &str, f: impl FnOnce(&mut Self, u32) -> Result<T, Fault>) -> Result<T, Fault> {
+        let fid = self.walk(path)?;
+        let out = f(self, fid);
+        self.clunk(fid);
+        out
+    }
+
+    fn stat(&mut self, path: &str) -> Result<Attr, Fault> {
+        self.with_fid(path, |s, fid| s.getattr(fid))
+    }
+
+    /// A fid walked to `path` and opened with `flags`, which must be a regular
+    /// file — a directory opened for reading is `EISDIR` here rather than a
+    /// stream of `Treaddir` bytes served as a file's.
+    fn open(&mut self, path: &str, flags: u32) -> Result<(u32, u64), Fault> {
+        let fid = self.walk(path)?;
+        let opened = self.getattr(fid).and_then(|attr| {
+            if !attr.is_file() {
+                return Err(Fault::Protocol(Error::Server(errno::EISDIR)));
+            }
+            self.rpc(&Request::Lopen { fid, flags })?;
+            Ok(attr.size)
+        });
+        match opened {
+            Ok(size) => Ok((fid, size)),
+            Err(e) => {
+                self.clunk(fid);
+                Err(e)
+            }
+        }
+    }
+
+    /// Up to `buf.len()` bytes at `offset`, in as many `Tread`s as `msize`
+    /// needs. Fewer is the end of the file.
+    fn read(&mut self, fid: u32, offset: u64, buf: &mut [u8]) -> Result<usize, Fault> {
+        let most = self.msize as usize - IOHDR;
+        let mut done = 0;
+        while done < buf.len() {
+            let count = (buf.len() - done).min(most) as u32;
+            let request = Request::Read { fid, offset: offset + done as u64, count };
+            let data = match self.rpc(&request)? {
+                Reply::Read(data) => data,
+                _ => unreachable!("parse answers the reply to the request it was given"),
+            };
+            // At most `count`: `parse` held it to the request.
+            buf[done..done + data.len()].copy_from_slice(data);
+            if data.is_empty() {
+                break;
+            }
+            done += data.len();
+        }
+        Ok(done)
+    }
+
+    /// All of `data` at `offset`, in as many `Twrite`s as it takes.
+    ///
+    /// A write the host took none of is `EIO` rather than another try: it is
+    /// what a full disk without the errno looks like, and looping on it would
+    /// be a page flush that never ends.
+    fn write(&mut self, fid: u32, offset: u64, data: &[u8]) -> Result<(), Fault> {
+        let most = self.msize as usize - IOHDR;
+        let mut done = 0;
+        while done < data.len() {
+            let chunk = &data[done..(done + most).min(data.len())];
+            let request = Request::Write { fid, offset: offset + done as u64, data: chunk };
+            let written = match self.rpc(&request)? {
+                Reply::Write(n) => n as usize,
+                _ => unreachable!("parse answers the reply to the request it was given"),
+            };
+            if written == 0 {
+                return Err(Fault::Protocol(Error::Server(errno::EIO)));
+            }
+            done += written;
+        }
+        Ok(())
+    }
+
+    /// Every entry of the directory at `path`, `.` and `..` aside, as names and
+    /// kinds — no more than `budget` of them.
+    ///
+    /// Copied out of each reply because the reply is the next request's buffer.
+    /// The budget is checked before each push, so a directory of a million
+    /// entries costs one over the limit and not a million.
+    fn read_dir(&mut self, path: &str, budget: usize) -> Result<Option<Vec<(String, u8)>>, Fault> {
+        let count = self.msize - IOHDR as u32;
+        self.with_fid(path, |s, fid| {
+            s.rpc(&Request::Lopen { fid, flags: O_RDONLY | O_DIRECTORY })?;
+            let mut out = Vec::new();
+            let mut offset = 0;
+            loop {
+                let entries = match s.rpc(&Request::Readdir { fid, offset, count })? {
+                    Reply::Readdir(entries) => entries,
+                    _ => unreachable!("parse answers the reply to the request it was given"),
+                };
+                let mut next = offset;
+                let mut any = false;
+                for entry in entries {
+                    let entry: DirEntry<'_> = entry.map_err(Fault::Protocol)?;
+                    if out.len() == budget {
+                        return Ok(None);
+                    }
+                    out.push((String::from(entry.name), entry.kind));
+                    next = entry.offset;
+                    any = true;
+                }
+                // An empty reply is the end, and so is one that held only `.`
+                // and `..` — Linux returns those first, in the same reply as
+                // whatever follows them. A server that hands back the same
+                // cookie forever runs into `budget`.
+                if !any {
+                    return Ok(Some(out));
+                }
+                offset = next;
+            }
+        })
+    }
+
+    /// `mkdir -p` of `dir`, for `FatFs::ensure_parent`'s reason: the VFS never
+    /// tells a mount about a directory until something is created inside it.
+    fn make_dirs(&mut self, dir: &str) -> Result<(), Fault> {
+        if dir.is_empty() {
+            return Ok(());
+        }
+        // Almost always there already, and then one walk is the whole cost.
+        if let Ok(fid) = self.walk(dir) {
+            self.clunk(fid);
+            return Ok(());
+        }
+        let names = components(dir)?;
+        for depth in 1..=names.len() {
+            let parent = names[..depth - 1].join("/");
+            let name = names[depth - 1];
+            let made = self.with_fid(&parent, |s, dirfid| {
+                s.rpc(&Request::Mkdir { dirfid, name, mode: DIR_MODE, gid: 0 }).map(drop)
+            });
+            match made {
+                Ok(()) => {}
+                Err(e) if e.is(errno::EEXIST) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A shared file as the page-fault path reads it: a read fid of its own, and
+/// the size the host gave when it was opened.
+struct NineBacking {
+    session: Arc<Lock<Session>>,
+    retired: Arc<Lock<Vec<u32>>>,
+    fid: u32,
+    size: u64,
+}
+
+impl FileBacking for NineBacking {
+    fn read_page(&self, file_offset: u64, buf: &mut [u8; 4096]) -> crate::block::BlockResult {
+        self.read_range(file_offset, buf)
+    }
+
+    /// As many `Tread`s as the batch is `msize`s long — four for a full one,
+    /// against one per page for the default.
+    fn read_pages(&self, file_offset: u64, buf: &mut [u8]) -> crate::block::BlockResult {
+        self.read_range(file_offset, buf)
+    }
+
+    fn file_size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl NineBacking {
+    /// `buf.len()` bytes from `file_offset`, zeros past the end.
+    ///
+    /// A read that comes back short of the size is not an error: the host
+    /// truncated the file after it was opened, and the rest of it is a hole
+    /// until the next `open` sees the new size.
+    fn read_range(&self, file_offset: u64, buf: &mut [u8]) -> crate::block::BlockResult {
+        buf.fill(0);
+        if file_offset >= self.size {
+            return Ok(());
+        }
+        let valid = (buf.len() as u64).min(self.size - file_offset) as usize;
+        let mut session = self.session.lock();
+        if let Err(fault) = session.read(self.fid, file_offset, &mut buf[..valid]) {
+            buf.fill(0);
+            log!("{}: read of {valid} B at file offset {file_offset} failed: {fault}; serving zeros",
+                session.mount);
+            return Err(crate::block::BlockError);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NineBacking {
+    fn drop(&mut self) {
+        self.retired.lock().push(self.fid);
+    }
+}
+
+/// Per-open-file state: the path, and the fid writes go through once one has
+/// been opened for writing.
+struct OpenFile {
+    name: String,
+    write: Option<u32>,
+}
+
+/// VFS adapter for one share.
+pub struct NinePFs {
+    mount: String,
+    session: Arc<Lock<Session>>,
+    open: HashMap<FileId, OpenFile>,
+    by_name: HashMap<String, FileId>,
+}
+
+impl NinePFs {
+    /// The VFS mount name, which is also the top-level directory: the share's
+    /// mount tag.
+    pub fn mount_name(&self) -> &str {
+        &self.mount
+    }
+
+    fn backing(&mut self, name: &str) -> Result<Arc<dyn FileBacking>, SyscallError> {
+        let mut session = self.session.lock();
+        let (fid, size) = session.open(name, O_RDONLY).map_err(|e| refused(&self.mount, "open", name, e))?;
+        let retired = session.retired.clone();
+        Ok(Arc::new(NineBacking { session: self.session.clone(), retired, fid, size }))
+    }
+
+    /// The write fid for `file_id`, opening one if the file was opened for
+    /// reading only.
+    fn write_fid(&mut self, file_id: FileId) -> Result<u32, SyscallError> {
+        let info = self.open.get_mut(&file_id).ok_or(SyscallError::NotFound)?;
+        if let Some(fid) = info.write {
+            return Ok(fid);
+        }
+        let opened = self.session.lock().open(&info.name, O_WRONLY);
+        let (fid, _) = opened.map_err(|e| refused(&self.mount, "open for writing", &info.name, e))?;
+        info.write = Some(fid);
+        Ok(fid)
+    }
+
+    fn register(&mut self, name: &str, write: Option<u32>, size: u64) -> FileId {
+        let file_id = file_cache::create_file(true);
+        file_cache::set_size(file_id, size);
+        self.by_name.insert(String::from(name), file_id);
+        self.open.insert(file_id, OpenFile { name: String::from(name), write });
+        file_id
+    }
+}
+
+impl FileSystem for NinePFs {
+    /// Every file under the share's root, walked one directory at a time.
+    ///
+    /// Refused before it allocates, like `FatFs`'s: `limit` counts files and
+    /// directories alike, and is checked before each entry is kept.
+    ///
+    /// **Not cheap.** A directory is a walk, an open, a `Treaddir` per 64 KiB
+    /// of entries and a clunk; a file's size is a walk, a `Tgetattr` and a
+    /// clunk, because `Rreaddir` carries no size. A build directory of a few
+    /// hundred files is a few hundred milliseconds of round trips, which is
+    /// the price of a listing the host can change between any two of them.
+    fn list(&mut self, limit: usize) -> Result<Vec<(String, u64)>, SyscallError> {
+        let mount = self.mount.as_str();
+        let mut session = self.session.lock();
+        let mut listed = Vec::new();
+        let mut seen = 0usize;
+        let mut pending = vec![(String::new(), 0usize)];
+        while let Some((dir, depth)) = pending.pop() {
+            let entries = session.read_dir(&dir, limit - seen).map_err(|e| refused(mount, "list", &dir, e))?;
+            let Some(entries) = entries else {
+                return Err(SyscallError::ResourceExhausted);
+            };
+            seen += entries.len();
+            for (name, kind) in entries {
+                let path = join(&dir, &name);
+                match kind {
+                    DirEntry::DT_DIR if depth + 1 < MAX_DEPTH => pending.push((path, depth + 1)),
+                    DirEntry::DT_DIR => log!("{mount}: {path} is more than {MAX_DEPTH} deep; not listed"),
+                    DirEntry::DT_LNK => listed.push((path, 0)),
+                    DirEntry::DT_REG => {
+                        // Gone between the `Treaddir` and here is the host at
+                        // work, and not a listing that failed.
+                        match session.stat(&path) {
+                            Ok(attr) => listed.push((path, attr.size)),
+                            Err(e) if e.is(errno::ENOENT) => {}
+                            Err(e) => return Err(refused(mount, "stat", &path, e)),
+                        }
+                    }
+                    // Sockets, FIFOs and devices, which nothing in the guest
+                    // could open as a file.
+                    _ => {}
+                }
+            }
+        }
+        Ok(listed)
+    }
+
+    /// Seconds since the Unix epoch, which is what the FAT32 and ext4 mounts
+    /// answer too.
+    fn file_mtime(&mut self, name: &str) -> Result<u64, SyscallError> {
+        let attr = self.session.lock().stat(name).map_err(|e| refused(&self.mount, "stat", name, e))?;
+        Ok(attr.mtime.0)
+    }
+
+    /// The target as a path from the share's root, which is the only shape
+    /// the VFS can follow — it resolves every target from the mount's root.
+    ///
+    /// A relative target is joined to the link's own directory. An absolute
+    /// one, or one whose `..`s climb out of the share, names a file on the
+    /// host that is not shared, and is refused rather than re-rooted into a
+    /// path that happens to exist here.
+    fn read_link(&mut self, name: &str) -> Result<Option<String>, SyscallError> {
+        let mount = self.mount.as_str();
+        let mut session = self.session.lock();
+        let target = session.with_fid(name, |s, fid| {
+            if !s.getattr(fid)?.is_symlink() {
+                return Ok(None);
+            }
+            match s.rpc(&Request::Readlink { fid })? {
+                Reply::Readlink(target) => Ok(Some(String::from(target))),
+                _ => unreachable!("parse answers the reply to the request it was given"),
+            }
+        });
+        let target = match target {
+            Ok(Some(target)) => target,
+            Ok(None) => return Ok(None),
+            Err(e) if e.is(errno::ENOENT) => return Ok(None),
+            Err(e) => return Err(refused(mount, "read_link", name, e)),
+        };
+        match resolve_link(name, &target) {
+            Some(path) => Ok(Some(path)),
+            None => {
+                log!("{mount}: {name} points at {target:?}, which is outside the share");
+                Err(SyscallError::InvalidArgument)
+            }
+        }
+    }
+
+    fn open_file(&mut self, name: &str) -> Result<(FileId, Option<Arc<dyn FileBacking>>), SyscallError> {
+        let backing = self.backing(name)?;
+        if let Some(&file_id) = self.by_name.get(name) {
+            file_cache::open(file_id);
+            return Ok((file_id, Some(backing)));
+        }
+        let file_id = self.register(name, None, backing.file_size());
+        Ok((file_id, Some(backing)))
+    }
+
+    /// Create and open for writing, or open an existing file without
+    /// truncating it — `vfs::create_file` is also how a file is reopened for
+    /// writing, as on FAT.
+    fn create(&mut self, name: &str, _mtime: u64) -> Result<FileId, SyscallError> {
+        if let Some(&file_id) = self.by_name.get(name) {
+            return Ok(file_id);
+        }
+        let mount = self.mount.as_str();
+        let mut session = self.session.lock();
+        let (dir, leaf) = split_parent(name).map_err(|e| refused(mount, "create", name, e))?;
+        session.make_dirs(dir).map_err(|e| refused(mount, "mkdir -p", dir, e))?;
+        // `Tlcreate` turns the directory's fid into the new file's, so on
+        // success this walk is the write fid and is not clunked.
+        let dirfid = session.walk(dir).map_err(|e| refused(mount, "create", name, e))?;
+        let request = Request::Lcreate { fid: dirfid, name: leaf, flags: O_WRONLY | O_CREAT, mode: FILE_MODE, gid: 0 };
+        let (fid, size) = match session.rpc(&request) {
+            Ok(_) => (dirfid, 0),
+            Err(e) if e.is(errno::EEXIST) => {
+                session.clunk(dirfid);
+                session.open(name, O_WRONLY).map_err(|e| refused(mount, "open", name, e))?
+            }
+            Err(e) => {
+                session.clunk(dirfid);
+                return Err(refused(mount, "create", name, e));
+            }
+        };
+        drop(session);
+        Ok(self.register(name, Some(fid), size))
+    }
+
+    fn close_file(&mut self, file_id: FileId) {
+        let Some(info) = self.open.remove(&file_id) else { return };
+        self.by_name.remove(&info.name);
+        if let Some(fid) = info.write {
+            self.session.lock().clunk(fid);
+        }
+    }
+
+    /// Unlink, and drop the write fid with the name — a later `write_page`
+    /// through a handle held across the unlink is `NotFound`, as on FAT. The
+    /// host keeps an unlinked file's bytes for as long as the server has it
+    /// open, so unlike FAT's there is nothing here a stale fid could reach.
+    fn delete(&mut self, name: &str) -> Result<(), SyscallError> {
+        let write = self.by_name.remove(name).and_then(|file_id| {
+            let _ = file_cache::mark_deleted(file_id);
+            self.open.remove(&file_id).and_then(|info| info.write)
+        });
+        let mount = self.mount.as_str();
+        let mut session = self.session.lock();
+        if let Some(fid) = write {
+            session.clunk(fid);
+        }
+        let (dir, leaf) = split_parent(name).map_err(|e| refused(mount, "delete", name, e))?;
+        session
+            .with_fid(dir, |s, dirfid| s.rpc(&Request::Unlinkat { dirfid, name: leaf, flags: 0 }).map(drop))
+            .map_err(|e| refused(mount, "delete", name, e))
+    }
+
+    /// One `Trenameat`, which replaces a destination that exists the way
+    /// `rename(2)` does — no window in which neither name resolves, unlike
+    /// FAT's.
+    fn rename(&mut self, old: &str, new: &str) -> Result<(), SyscallError> {
+        let mount = self.mount.as_str();
+        let mut session = self.session.lock();
+        let (old_dir, oldname) = split_parent(old).map_err(|e| refused(mount, "rename", old, e))?;
+        let (new_dir, newname) = split_parent(new).map_err(|e| refused(mount, "rename", new, e))?;
+        session.make_dirs(new_dir).map_err(|e| refused(mount, "mkdir -p", new_dir, e))?;
+        session
+            .with_fid(old_dir, |s, olddirfid| {
+                s.with_fid(new_dir, |s, newdirfid| {
+                    s.rpc(&Request::Renameat { olddirfid, oldname, newdirfid, newname }).map(drop)
+                })
+            })
+            .map_err(|e| refused(mount, "rename", old, e))?;
+        drop(session);
+        if let Some(file_id) = self.by_name.remove(old) {
+            self.by_name.insert(String::from(new), file_id);
+            if let Some(info) = self.open.get_mut(&file_id) {
+                info.name = String::from(new);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_page(&mut self, file_id: FileId, page_idx: u32, data: &[u8; 4096]) -> Result<(), SyscallError> {
+        let fid = self.write_fid(file_id)?;
+        let written = self.session.lock().write(fid, page_idx as u64 * 4096, data);
+        written.map_err(|e| {
+            let name = self.open.get(&file_id).map_or("?", |info| info.name.as_str());
+            refused(&self.mount, "write", name, e)
+        })
+    }
+
+    /// Truncate to the real length — `write_page` writes whole pages, so the
+    /// last one carries the cache's padding — and re-derive the backing, whose
+    /// size was the host's when the file was opened.
+    ///
+    /// The time is the host's: `SetAttr::size` asks the server to stamp its
+    /// own clock, and `_mtime` is nanoseconds since the guest booted.
+    fn update_metadata(&mut self, file_id: FileId, size: u64, _mtime: u64) -> Result<(), SyscallError> {
+        let fid = self.write_fid(file_id)?;
+        let name = self.open.get(&file_id).map(|info| info.name.clone()).ok_or(SyscallError::NotFound)?;
+        self.session
+            .lock()
+            .rpc(&Request::Setattr { fid, attr: SetAttr::size(size) })
+            .map_err(|e| refused(&self.mount, "truncate", &name, e))?;
+        // The bytes are on the host by here; a backing that will not open
+        // costs this file its evictability, as on FAT, and not the write.
+        match self.backing(&name) {
+            Ok(backing) => file_cache::set_backing(file_id, backing),
+            Err(_) => log!("{}: {name} was written but cannot be reopened for reading", self.mount),
+        }
+        Ok(())
+    }
+
+    fn create_symlink(&mut self, name: &str, target: &str) -> Result<(), SyscallError> {
+        let mount = self.mount.as_str();
+        let mut session = self.session.lock();
+        let (dir, leaf) = split_parent(name).map_err(|e| refused(mount, "symlink", name, e))?;
+        session.make_dirs(dir).map_err(|e| refused(mount, "mkdir -p", dir, e))?;
+        session
+            .with_fid(dir, |s, dirfid| s.rpc(&Request::Symlink { dirfid, name: leaf, target, gid: 0 }).map(drop))
+            .map_err(|e| refused(mount, "symlink", name, e))
+    }
+
+    /// `Tfsync` of every file open for writing: the host's page cache is the
+    /// device's write cache, as far as this mount can see.
+    ///
+    /// Not logged, for `FatFs::sync`'s reason — the caller logs a failure, and
+    /// a line per refusal here would be two.
+    fn sync(&mut self) -> Result<(), SyscallError> {
+        let mut session = self.session.lock();
+        for info in self.open.values() {
+            if let Some(fid) = info.write {
+                session.rpc(&Request::Fsync { fid }).map_err(|e| as_syscall_error(&e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn open_backing(&mut self, name: &str) -> Result<Arc<dyn FileBacking>, SyscallError> {
+        self.backing(name)
+    }
+}
+
+/// Whether a mount tag can be a mount name: one directory name, of the
+/// characters a shell will not make the developer quote.
+fn usable_name(tag: &str) -> bool {
+    !tag.is_empty() && tag.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Attach every share `virtio_9p` bound, each under its mount tag.
+///
+/// A share whose tag is not a usable name, or is already a mount's, is not
+/// mounted: re-rooting it under a name the developer did not choose would
+/// leave them looking for it.
+pub fn mount_all() -> Vec<NinePFs> {
+    let mut mounted = Vec::new();
+    for index in 0.. {
+        let Some(channel) = virtio_9p::open(index) else { break };
+        let tag = String::from(channel.lock().tag());
+        if !usable_name(&tag) {
+            log!("9p: share {index} is tagged {tag:?}, which is not a mount name; not mounted");
+            continue;
+        }
+        if crate::vfs::lock().has_mount(&tag) || mounted.iter().any(|fs: &NinePFs| fs.mount == tag) {
+            log!("9p: share {index} is tagged {tag:?}, which is already mounted; not mounted");
+            continue;
+        }
+        if let Some(fs) = attach(tag, channel) {
+            mounted.push(fs);
+        }
+    }
+    mounted
+}
+
+/// The version exchange and the attach, which together make a channel a
+/// session.
+fn attach(mount: String, channel: &'static Lock<Channel>) -> Option<NinePFs> {
+    let mut session = Session {
+        mount,
+        channel,
+        msize: MSIZE,
+        tag: 0,
+        next_fid: ROOT,
+        free: Vec::new(),
+        retired: Arc::new(Lock::new(Vec::new())),
+        out: vec![0u8; MSIZE as usize],
+        reply: vec![0u8; MSIZE as usize],
+    };
+    let msize = match session.send(NOTAG, &Request::Version { msize: MSIZE }) {
+        Ok(Reply::Version { msize }) => msize,
+        Ok(_) => unreachable!("parse answers the reply to the request it was given"),
+        Err(e) => {
+            log!("{}: version exchange refused: {e}; not mounted", session.mount);
+            return None;
+        }
+    };
+    session.msize = msize;
+    let attached = session.rpc(&Request::Attach { fid: ROOT, uname: "root", aname: "", uid: 0 });
+    if let Err(e) = attached {
+        log!("{}: attach refused: {e}; not mounted", session.mount);
+        return None;
+    }
+    log!("{}: mounted read-write, msize {msize}", session.mount);
+    let mount = session.mount.clone();
+    Some(NinePFs {
+        mount,
+        session: Arc::new(Lock::new(session)),
+        open: HashMap::new(),
+        by_name: HashMap::new(),
+    })
+}
@@ -12,11 +12,11 @@
 //! more length.
 //!
 //! **Under both page-cache locks, because that is the state the refusal has to
-//! be reachable from.** Every real caller holds `page_cache::BLOCK_CACHE` and
-//! `page_cache::BLOCK_DEV` across the whole of `read_blocks`, and both are
-//! `sync::Lock`s that disable preemption for their whole life — so a wait that
-//! could not end was a CPU wedged holding two of the machine's statics. The
-//! read below goes through those two guards and not around them.
+//! be reachable from.** Every real caller holds the device's cache lock and its
+//! device lock across the whole of `read_blocks`, and both are `sync::Lock`s
+//! that disable preemption for their whole life — so a wait that could not end
+//! was a CPU wedged holding two of the machine's locks. The read below goes
+//! through those two guards and not around them.
 //!
 //! **What it proves is the plumbing and the verdict together**: the deadline is
 //! established above `BlockDevice`, `NvmeBlockDevice::read_blocks` narrows its
@@ -29,6 +29,7 @@
 
 use alloc::vec;
 
+use crate::block::DeviceId;
 use crate::page_cache;
 use crate::scheduler::Operation;
 use crate::time::Deadline;
@@ -38,7 +39,7 @@ use crate::time::Deadline;
 /// asks for anyway, so a device disturbed by this would show it immediately.
 const AT: u64 = 0;
 
-pub fn run() {
+pub fn run(device: DeviceId) {
     let mut buf = vec![0u8; 4096];
 
     // The establishment is the *caller's*, above the trait, which is what makes
@@ -47,7 +48,7 @@ pub fn run() {
     // narrow, so what the driver recovers is this deadline.
     let refused = {
         let _op = Operation::begin(Deadline::passed());
-        let mut guard = page_cache::lock();
+        let mut guard = page_cache::lock(device);
         let (_cache, dev) = guard.cache_and_dev();
         dev.read_blocks(AT, 1, &mut buf).is_err()
     };
@@ -57,7 +58,7 @@ pub fn run() {
     // taken inside a command instead of between two would have abandoned one:
     // the queue would still be owed a completion entry and the DMA window still
     // owed a write, and this read is the first thing that would find out.
-    let mut guard = page_cache::lock();
+    let mut guard = page_cache::lock(device);
     let (_cache, dev) = guard.cache_and_dev();
     let served = dev.read_blocks(AT, 1, &mut buf).is_ok();
     drop(guard);
@@ -30,6 +30,9 @@ pub enum DeviceInfo {
     Nic(crate::net::NicInfo, Arc<SharedMemObject>),
     Hda(toyos_abi::hda::HdaInfo, Arc<SharedMemObject>),
     VirtioSound(toyos_abi::virtio_sound::VirtioSoundInfo, Arc<SharedMemObject>),
+    /// Names no buffer: every packet crosses the claim by copy.
+    Vsock(toyos_abi::vsock::VsockInfo),
+    UsbAudio(toyos_abi::usb_audio::UsbAudioInfo, Arc<SharedMemObject>),
 }
 
 /// The two scanout buffers and the cursor plane.
@@ -85,6 +88,12 @@ impl DeviceInfo {
                 info.dma = install_buffer(table, dma)?;
                 info.as_bytes().into()
             }
+            Self::Vsock(info) => info.as_bytes().into(),
+            Self::UsbAudio(info, pcm) => {
+                let mut info = *info;
+                info.pcm = install_buffer(table, pcm)?;
+                info.as_bytes().into()
+            }
         })
     }
 }
@@ -183,8 +192,15 @@ impl DeviceClaim {
 /// does: a daemon killed while parked on its device — soundd's steady state —
 /// strands an `Arc` on a freed kernel stack, and a claim released by Arc count
 /// would then never come back for the process that replaces it.
+///
+/// A USB DAC is stopped on the way: its stream is the kernel's to run, so a
+/// soundd killed mid-stream would otherwise leave it cycling the last lap of
+/// the ring until the next soundd started it again.
 impl ZeroHandles for DeviceClaim {
     fn on_zero_handles(&self) {
+        if self.class == DeviceType::UsbAudio {
+            crate::drivers::xhci::audio::run(false);
+        }
         self.reference.release();
     }
 }
@@ -19,6 +19,9 @@ pub struct OpenFileState {
     pub position: usize,
     pub modified: bool,
     pub mtime: u64,
+    /// This handle's sequential-read state. It is kept per handle because the
+    /// handle has the cursor.
+    pub readahead: file_cache::Readahead,
 }
 
 /// **This takes the VFS lock** — flushing needs it — which is why nothing in
@@ -148,6 +148,7 @@ pub fn open(table: &mut HandleTable, path: &str, flags: OpenFlags) -> u64 {
         position,
         modified: false,
         mtime,
+        readahead: file_cache::Readahead::new(),
     }));
     // **`writable` is a right, not a field.** A write to a read-only file
     // answers `PermissionDenied` because the handle does not carry `WRITE`,
@@ -255,9 +256,13 @@ pub fn read_source(object: &KObjectRef) -> Option<Source> {
         KObjectRef::Device(d) => match d.class() {
             device_registry::DeviceType::Keyboard => Some(Source::Keyboard),
             device_registry::DeviceType::Mouse => Some(Source::Mouse),
+            device_registry::DeviceType::Gamepad => Some(Source::Gamepad),
+            device_registry::DeviceType::Power => Some(Source::Power),
             device_registry::DeviceType::Nic => Some(Source::Network),
             device_registry::DeviceType::HdaAudio => Some(Source::Hda),
             device_registry::DeviceType::VirtioSound => Some(Source::VirtioSound),
+            device_registry::DeviceType::Vsock => Some(Source::Vsock),
+            device_registry::DeviceType::UsbAudio => Some(Source::UsbAudio),
             device_registry::DeviceType::Framebuffer => None,
         },
         // **The `SysCap` is what a log reader parks on, and the rights on the
@@ -297,6 +302,9 @@ fn read_file(file: &FileObject, buf: &mut UserBytesMut) -> Option<u64> {
         if count == 0 {
             return Some(0);
         }
+        let first = (state.position / 4096) as u32;
+        let last = ((state.position + count - 1) / 4096) as u32;
+        file_cache::read_ahead(state.file_id, &mut state.readahead, first, last);
         let mut read = 0;
         let mut refused = false;
         while read < count {
@@ -351,7 +359,10 @@ pub fn read_device(
         // kernel and nothing dropped. `drain_irqs` calls the same function at
         // the top of every scheduler pass, so a reader gives up at most one
         // pass of latency.
-        device_registry::DeviceType::Keyboard | device_registry::DeviceType::Mouse => {
+        device_registry::DeviceType::Keyboard
+        | device_registry::DeviceType::Mouse
+        | device_registry::DeviceType::Gamepad
+        | device_registry::DeviceType::Power => {
             match claim.class() {
             device_registry::DeviceType::Keyboard => {
                 let event_size = core::mem::size_of::<keyboard::RawKeyEvent>();
@@ -373,6 +384,26 @@ pub fn read_device(
                 }
                 if count > 0 { Some(count as u64) } else { None }
             }
+            device_registry::DeviceType::Gamepad => {
+                let event_size = core::mem::size_of::<crate::gamepad::GamepadEvent>();
+                let mut count = 0;
+                while count + event_size <= buf.len() {
+                    let Some(event) = crate::gamepad::try_read_event() else { break };
+                    buf.write_at(count, event.as_bytes());
+                    count += event_size;
+                }
+                if count > 0 { Some(count as u64) } else { None }
+            }
+            device_registry::DeviceType::Power => {
+                let event_size = core::mem::size_of::<crate::power::PowerEvent>();
+                let mut count = 0;
+                while count + event_size <= buf.len() {
+                    let Some(event) = crate::power::try_read_event() else { break };
+                    buf.write_at(count, event.as_bytes());
+                    count += event_size;
+                }
+                if count > 0 { Some(count as u64) } else { None }
+            }
             other => panic!("a {other:?} claim answers with events"),
             }
         }
@@ -401,6 +432,25 @@ pub fn read_device(
             let n = crate::drivers::virtio_sound::drain_completed(buf);
             if n == 0 { None } else { Some(n as u64) }
         }
+        device_registry::DeviceType::UsbAudio => {
+            if !claim.info_read() {
+                return Some(claim.describe(table, buf));
+            }
+            if buf.len() < toyos_abi::audio::AudioCompletionRecord::SIZE {
+                return Some(SyscallError::InvalidArgument.to_u64());
+            }
+            let n = crate::drivers::xhci::audio::drain_completed(buf);
+            if n == 0 { None } else { Some(n as u64) }
+        }
+        device_registry::DeviceType::Vsock => {
+            if !claim.info_read() {
+                return Some(claim.describe(table, buf));
+            }
+            // One packet, oldest first. Empty → None, which answers a blocking
+            // read `NotFound` as it does the NIC's: vsockd parks on its poller,
+            // never in a read.
+            crate::drivers::virtio_vsock::receive(buf)
+        }
     }
 }
 
@@ -448,42 +498,75 @@ fn write_pipe(id: PipeId, buf: &UserBytes) -> Option<u64> {
     }
 }
 
+/// The file half of [`try_write`]. Answers the count or error code, and the
+/// flush the writer owes when the dirty set is past the throttle threshold.
+fn write_file(f: &FileObject, buf: &UserBytes) -> (u64, Option<(String, file_cache::FileId, u64)>) {
+    f.with(|state| {
+        let mut written = 0;
+        let mut refused = false;
+        while written < buf.len() {
+            let abs_pos = state.position + written;
+            let page_idx = (abs_pos / 4096) as u32;
+            let offset_in_page = abs_pos % 4096;
+            let remaining_in_page = 4096 - offset_in_page;
+            let to_write = remaining_in_page.min(buf.len() - written);
+            // A partial write whose page could not be re-read off the
+            // device is refused rather than merged into zeros, so this
+            // stops short instead of claiming bytes that are not in the
+            // file.
+            if file_cache::write_page(
+                state.file_id,
+                page_idx,
+                offset_in_page,
+                &buf.sub(written, to_write),
+            )
+            .is_err()
+            {
+                refused = true;
+                break;
+            }
+            written += to_write;
+        }
+        if written == 0 && refused {
+            return (SyscallError::Io.to_u64(), None);
+        }
+        state.position += written;
+        state.modified = true;
+        state.mtime = crate::clock::nanos_since_boot();
+        if !file_cache::is_on_disk(state.file_id) {
+            return (written as u64, None);
+        }
+        let pressure = file_cache::dirty_pressure();
+        crate::iod::submit(&state.path, state.file_id, state.mtime, pressure);
+        let owed = (pressure == file_cache::DirtyPressure::Throttle)
+            .then(|| (state.path.clone(), state.file_id, state.mtime));
+        (written as u64, owed)
+    })
+}
+
 pub fn try_write(object: &KObjectRef, buf: &UserBytes) -> Option<u64> {
     match object {
-        KObjectRef::File(f) => f.with(|state| {
-            let mut written = 0;
-            let mut refused = false;
-            while written < buf.len() {
-                let abs_pos = state.position + written;
-                let page_idx = (abs_pos / 4096) as u32;
-                let offset_in_page = abs_pos % 4096;
-                let remaining_in_page = 4096 - offset_in_page;
-                let to_write = remaining_in_page.min(buf.len() - written);
-                // A partial write whose page could not be re-read off the
-                // device is refused rather than merged into zeros, so this
-                // stops short instead of claiming bytes that are not in the
-                // file.
-                if file_cache::write_page(
-                    state.file_id,
-                    page_idx,
-                    offset_in_page,
-                    &buf.sub(written, to_write),
-                )
-                .is_err()
-                {
-                    refused = true;
-                    break;
+        KObjectRef::File(f) => {
+            let (written, owed) = write_file(f, buf);
+            // Past `DirtyPressure::Throttle` the writer writes back its own
+            // file, outside `FileObject`'s lock for the ordering `fsync`
+            // keeps. The bytes are already written, so a failed flush is not
+            // this call's error: the pages stay dirty, and close reports it.
+            if let Some((path, file_id, mtime)) = owed {
+                let mut vfs = crate::vfs::lock();
+                let flushed = vfs.flush_file(&path, file_id, mtime);
+                if let Err(e) = &flushed {
+                    crate::log!("warning: throttled write-back failed: {}: {}", path, e);
+                }
+                // `iod`'s two triggers say when they fire under the same
+                // actuator; this is the third.
+                #[cfg(feature = "boot-actuators")]
+                if flushed.is_ok() && crate::actuator::iod_trace() {
+                    crate::log!("write-back: throttled {}", path);
                 }
-                written += to_write;
-            }
-            if written == 0 && refused {
-                return Some(SyscallError::Io.to_u64());
             }
-            state.position += written;
-            state.modified = true;
-            state.mtime = crate::clock::nanos_since_boot();
-            Some(written as u64)
-        }),
+            Some(written)
+        }
         KObjectRef::PipeWrite(w) => write_pipe(w.id(), buf),
         KObjectRef::Connection(c) => write_pipe(c.tx(), buf),
         KObjectRef::Console(c) => {
@@ -500,6 +583,34 @@ pub fn try_write(object: &KObjectRef, buf: &UserBytes) -> Option<u64> {
             c.write(buf);
             Some(buf.len() as u64)
         }
+        // **A vsock claim is the one device a write reaches**, and a write is
+        // one whole packet: the driver checks it and copies it into a transmit
+        // buffer, and a full queue answers `WouldBlock` rather than parking —
+        // the transmit ring drains on the device's time, and vsockd has a loop
+        // to get back to.
+        KObjectRef::Device(d) if d.class() == device_registry::DeviceType::Vsock => {
+            Some(match crate::drivers::virtio_vsock::send(buf) {
+                Ok(()) => buf.len() as u64,
+                Err(e) => e.to_u64(),
+            })
+        }
+        // A USB audio claim takes one byte, start or stop; the stream is the
+        // kernel's to run, so that is the whole of what soundd decides.
+        KObjectRef::Device(d) if d.class() == device_registry::DeviceType::UsbAudio => {
+            Some(match buf.len() {
+                1 => {
+                    let mut byte = [0u8];
+                    buf.read_at(0, &mut byte);
+                    match byte[0] {
+                        toyos_abi::usb_audio::START => crate::drivers::xhci::audio::run(true),
+                        toyos_abi::usb_audio::STOP => crate::drivers::xhci::audio::run(false),
+                        _ => return Some(SyscallError::InvalidArgument.to_u64()),
+                    }
+                    1
+                }
+                _ => SyscallError::InvalidArgument.to_u64(),
+            })
+        }
         KObjectRef::PipeRead(_) | KObjectRef::Device(_) | KObjectRef::Acceptor(_)
         | KObjectRef::Inbox(_) | KObjectRef::SharedMem(_) | KObjectRef::SysCap(_)
         | KObjectRef::Connector(_) | KObjectRef::Namespace(_)
@@ -572,7 +683,11 @@ pub fn fstat(object: &KObjectRef) -> Stat {
             device_registry::DeviceType::Framebuffer => FileType::Framebuffer,
             device_registry::DeviceType::Nic => FileType::Nic,
             device_registry::DeviceType::HdaAudio
-            | device_registry::DeviceType::VirtioSound => FileType::Unknown,
+            | device_registry::DeviceType::VirtioSound
+            | device_registry::DeviceType::Vsock
+            | device_registry::DeviceType::UsbAudio
+            | device_registry::DeviceType::Gamepad
+            | device_registry::DeviceType::Power => FileType::Unknown,
         }),
     }
 }
@@ -657,7 +772,9 @@ pub fn has_data(object: &KObjectRef) -> bool {
         KObjectRef::Device(d) => match d.class() {
             device_registry::DeviceType::Keyboard => keyboard::has_data(),
             device_registry::DeviceType::Mouse => mouse::has_data(),
-            device_registry::DeviceType::Nic => crate::net::has_packet(),
+            device_registry::DeviceType::Gamepad => crate::gamepad::has_data(),
+            device_registry::DeviceType::Power => crate::power::has_data(),
+            device_registry::DeviceType::Nic => crate::net::readable(),
             device_registry::DeviceType::Framebuffer => true,
             device_registry::DeviceType::HdaAudio => {
                 !d.info_read() || crate::drivers::hda::has_pending()
@@ -665,6 +782,12 @@ pub fn has_data(object: &KObjectRef) -> bool {
             device_registry::DeviceType::VirtioSound => {
                 !d.info_read() || crate::drivers::virtio_sound::has_pending()
             }
+            device_registry::DeviceType::Vsock => {
+                !d.info_read() || crate::drivers::virtio_vsock::has_pending()
+            }
+            device_registry::DeviceType::UsbAudio => {
+                !d.info_read() || crate::drivers::xhci::audio::has_pending()
+            }
         },
         KObjectRef::PipeWrite(_) | KObjectRef::Inbox(_) | KObjectRef::SysCap(_)
         | KObjectRef::Connector(_) | KObjectRef::Namespace(_)
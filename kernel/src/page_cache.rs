@@ -1,78 +1,352 @@
+//! The block-metadata cache, one per registered device, under one budget.
+//!
+//! A device is [`register`]ed once and named by its [`DeviceId`] from then on,
+//! so what a cached block is keyed by is `(DeviceId, lba)`: the id picks the
+//! device's own [`PageCache`], and the block number is the key inside it. Each
+//! device has its own two locks, so a btree walk on one disk never waits for a
+//! USB round trip on another — one global pair would have made the slowest
+//! device on the machine the speed of every one.
+//!
+//! Every disk something is mounted from is registered: the one carrying
+//! `/home` at boot, and each disk a FAT32, ext4 or ISO 9660 mount reads, on the
+//! first mount that asks for it (`fat32_adapter::device_carrying`). Those
+//! filesystems address their volumes in bytes and reach the cache through
+//! [`read_bytes`] and [`write_bytes`]; bcachefs reaches it a block at a time
+//! through [`lock`].
+//!
+//! # One budget, shared
+//!
+//! [`block::metadata_cache_blocks`] is the ceiling for the machine and not per
+//! device, for the reason `block.rs` gives for having ceilings at all: there is
+//! no pressure signal, so the number has to be one the machine can lose
+//! outright, and a second disk must not double it. (`file_cache_pages` is the
+//! file cache's number; that cache is keyed by file and already serves every
+//! device without knowing there is more than one.) [`RESIDENT`] counts slots
+//! across every device, and a slot is only ever *added* by winning the
+//! increment that keeps it under the ceiling.
+//!
+//! # Fairness
+//!
+//! A device may grow into room nobody is using, so a lone disk gets the whole
+//! budget exactly as it did when it was the only one the cache could hold.
+//! Once the budget is full the answer depends on who is asking:
+//!
+//! - **A device at or over its fair share** — the budget over the number of
+//!   devices — evicts from itself. A busy disk cannot grow by pushing a quiet
+//!   one out of memory it is entitled to.
+//! - **A device under its fair share** takes slots back before it takes its
+//!   own locks: [`reclaim_for`] shrinks the devices over their share, one at a
+//!   time, each under its own cache lock and with nothing else held. So the
+//!   lock order stays "one device's cache, then that device's device" and no
+//!   path ever holds two devices at once.
+//!
+//! Only clean slots are taken back. A device whose surplus is all dirty keeps
+//! it until its own write-back, and the claimant evicts from itself meanwhile —
+//! degraded, not wedged.
+
 use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use hashbrown::HashMap;
 
 use crate::block::{self, BlockDevice, BlockError, BlockResult, DeviceId};
-use crate::sync::Lock;
-
-// Separate locks: device I/O and cache data structures.
-// Lock ordering: BLOCK_CACHE → BLOCK_DEV (never reversed).
-static BLOCK_CACHE: Lock<Option<PageCache>> = Lock::new(None);
-static BLOCK_DEV: Lock<Option<Box<dyn BlockDevice>>> = Lock::new(None);
+use crate::sync::{Lock, LockGuard};
+
+/// Every registered device. Taken only to find one, and never while a
+/// device's own locks are held.
+static DEVICES: Lock<Vec<&'static CachedDevice>> = Lock::new(Vec::new());
+
+/// Slots resident across every device's cache.
+static RESIDENT: AtomicUsize = AtomicUsize::new(0);
+
+/// [`block::metadata_cache_blocks`], stored by every registration — it is the
+/// same number each time — so [`reclaim_for`], which runs on every [`lock`],
+/// does not ask the physical allocator for it.
+static BUDGET: AtomicUsize = AtomicUsize::new(0);
+
+/// Slots a device may hold whatever the budget says. Without a floor, a device
+/// that registers into a full cache and loses the race for the room
+/// [`reclaim_for`] freed would have no slot to evict and no slot to grow into,
+/// and every read on it would fail. The overshoot is this times the number of
+/// devices, which is a handful of pages.
+const FLOOR_SLOTS: usize = 8;
+
+/// Slots taken back from other devices per claim. Enough that a device
+/// filling its share does not walk the registry on every miss.
+const RECLAIM_BATCH: usize = 32;
+
+/// One registered device: its cache and the device itself, each behind its own
+/// lock. Lock ordering: `cache` → `dev` (never reversed).
+///
+/// Leaked at registration. A registration lasts the boot — a USB disk pulled
+/// out leaves its id naming a handle whose every transfer fails, exactly as
+/// `usb_storage` leaves the number itself — so the entry is as `'static` as the
+/// two globals it replaced, and a guard can borrow it for as long as it likes.
+struct CachedDevice {
+    id: DeviceId,
+    cache: Lock<PageCache>,
+    dev: Lock<Box<dyn BlockDevice>>,
+    /// One handle per CPU for the raw path, from [`BlockDevice::share`], or
+    /// none when the device has one command path and `dev` is the only way
+    /// in. Never held with `cache` or `dev`, so they sit outside the ordering.
+    lanes: Vec<Lock<Box<dyn BlockDevice>>>,
+    /// `cache`'s slot count, readable without its lock: [`reclaim_for`] asks
+    /// every device how much it holds and must not lock each one to find out.
+    /// The cache keeps it current itself, through the same reference.
+    resident: &'static AtomicUsize,
+}
 
-/// Initialize the page cache, taking ownership of the block device.
-pub fn init(dev: Box<dyn BlockDevice>) {
+/// Put `dev` behind the cache and return the id every later call names it by.
+///
+/// Registering an id twice is a kernel bug — two caches over one disk would
+/// each believe its copy of a block is the one on the device — and is refused
+/// with a panic rather than a second entry.
+pub fn register(dev: Box<dyn BlockDevice>) -> DeviceId {
+    let id = dev.device_id();
     let block_count = dev.block_count();
-    let cache = PageCache::new(block_count, dev.device_id());
-    log!("page cache: {} device blocks, index sized for {} cached blocks, cap {} slots",
-        block_count, cache.index_capacity(), cache.max_slots);
-    *BLOCK_CACHE.lock() = Some(cache);
-    *BLOCK_DEV.lock() = Some(dev);
+    let resident: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+    let cache = PageCache::new(block_count, id, resident);
+    // Sized by the most CPUs there can be and not by `cpu_count`: the home
+    // volume registers before the APs are up, when the count is still one.
+    let lanes: Vec<_> =
+        (0..crate::scheduler::MAX_CPUS).map_while(|_| dev.share()).map(Lock::new).collect();
+    BUDGET.store(cache.max_slots, Ordering::Relaxed);
+    log!("page cache: device {} registered, {} device blocks, index sized for {} cached blocks, \
+          {} slots shared by every device",
+        id, block_count, cache.index_capacity(), cache.max_slots);
+    let entry: &'static CachedDevice = Box::leak(Box::new(CachedDevice {
+        id,
+        cache: Lock::new(cache),
+        dev: Lock::new(dev),
+        lanes,
+        resident,
+    }));
+    let mut devices = DEVICES.lock();
+    assert!(
+        devices.iter().all(|d| d.id != id),
+        "page cache: device {} registered twice",
+        id
+    );
+    devices.push(entry);
+    id
+}
+
+/// Whether `id` is behind the cache. A device that is has exactly one owner,
+/// and every later mount of it names it by this id rather than opening a
+/// handle of its own, which would read around the cache and write behind it.
+pub fn is_registered(id: DeviceId) -> bool {
+    DEVICES.lock().iter().any(|d| d.id == id)
+}
+
+fn device(id: DeviceId) -> &'static CachedDevice {
+    DEVICES
+        .lock()
+        .iter()
+        .copied()
+        .find(|d| d.id == id)
+        .unwrap_or_else(|| panic!("page cache: device {} was never registered", id))
 }
 
-/// Lock both cache and device for metadata operations (bcachefs btree, etc.).
-/// Lock ordering: cache first, then device.
-pub fn lock() -> PageCacheGuard {
-    let cache = BLOCK_CACHE.lock();
-    let dev = BLOCK_DEV.lock();
+/// The slots one device may count on when every device wants its share.
+fn fair_share() -> usize {
+    let devices = DEVICES.lock().len().max(1);
+    (BUDGET.load(Ordering::Relaxed) / devices).max(FLOOR_SLOTS)
+}
+
+/// Lock one device's cache and the device, for metadata operations (bcachefs
+/// btree, etc.). Lock ordering: cache first, then device.
+///
+/// A device under its fair share of a full budget takes slots back from the
+/// others first, before either of its own locks is held — see the module docs.
+pub fn lock(id: DeviceId) -> PageCacheGuard {
+    let entry = device(id);
+    reclaim_for(entry);
+    let cache = entry.cache.lock();
+    let dev = entry.dev.lock();
     PageCacheGuard { cache, dev }
 }
 
+/// Shrink the devices over their fair share until `claimant` has room to grow
+/// into, when it is owed any.
+///
+/// Holds one device's cache lock at a time and never `claimant`'s own, so it
+/// cannot close a cycle with another claimant doing the same thing.
+fn reclaim_for(claimant: &CachedDevice) {
+    if RESIDENT.load(Ordering::Relaxed) < BUDGET.load(Ordering::Relaxed) {
+        return;
+    }
+    let share = fair_share();
+    let held = claimant.resident.load(Ordering::Relaxed);
+    if held >= share {
+        return;
+    }
+    let mut owed = (share - held).min(RECLAIM_BATCH);
+    let others: Vec<&'static CachedDevice> =
+        DEVICES.lock().iter().copied().filter(|d| d.id != claimant.id).collect();
+    for other in others {
+        if owed == 0 {
+            break;
+        }
+        let surplus = other.resident.load(Ordering::Relaxed).saturating_sub(share);
+        if surplus == 0 {
+            continue;
+        }
+        owed -= other.cache.lock().release(surplus.min(owed));
+    }
+}
+
 pub struct PageCacheGuard {
-    cache: crate::sync::LockGuard<'static, Option<PageCache>>,
-    dev: crate::sync::LockGuard<'static, Option<Box<dyn BlockDevice>>>,
+    cache: LockGuard<'static, PageCache>,
+    dev: LockGuard<'static, Box<dyn BlockDevice>>,
 }
 
 impl PageCacheGuard {
     pub fn cache_and_dev(&mut self) -> (&mut PageCache, &mut dyn BlockDevice) {
-        let cache = self.cache.as_mut().expect("page cache not initialized");
-        let dev = self.dev.as_mut().expect("block device not initialized");
-        (cache, dev.as_mut())
+        (&mut *self.cache, &mut **self.dev)
     }
 
     pub fn block_count(&self) -> u64 {
-        self.cache.as_ref().expect("page cache not initialized").block_count()
+        self.cache.block_count()
     }
 }
 
 impl core::ops::Deref for PageCacheGuard {
     type Target = PageCache;
-    fn deref(&self) -> &PageCache { self.cache.as_ref().expect("page cache not initialized") }
+    fn deref(&self) -> &PageCache { &self.cache }
 }
 
 impl core::ops::DerefMut for PageCacheGuard {
-    fn deref_mut(&mut self) -> &mut PageCache { self.cache.as_mut().expect("page cache not initialized") }
+    fn deref_mut(&mut self) -> &mut PageCache { &mut self.cache }
+}
+
+/// The handle the raw path drives from this CPU: its own lane when the device
+/// has them, so file data on two CPUs goes down two queues at once, and `dev`
+/// when it does not.
+///
+/// Which lane is read before its lock is taken, and a task moved in between
+/// drives another CPU's lane. That is contention, not a fault: every lane is
+/// the whole device.
+fn raw(entry: &'static CachedDevice) -> LockGuard<'static, Box<dyn BlockDevice>> {
+    match entry.lanes.len() {
+        0 => entry.dev.lock(),
+        n => entry.lanes[crate::arch::percpu::cpu_id() as usize % n].lock(),
+    }
 }
 
 /// Read a block directly from disk, bypassing the cache.
 /// Locks only the device — no contention with metadata cache operations.
 /// Used by NvmeBacking for file data reads (file cache is the sole data cache).
 #[must_use = "a failed read leaves the buffer holding whatever it held before"]
-pub fn raw_block_read(block: u64, buf: &mut [u8; 4096]) -> BlockResult {
-    let mut dev = BLOCK_DEV.lock();
-    let dev = dev.as_mut().expect("block device not initialized");
-    dev.read_blocks(block, 1, buf)
+pub fn raw_block_read(id: DeviceId, block: u64, buf: &mut [u8; 4096]) -> BlockResult {
+    raw(device(id)).read_blocks(block, 1, buf)
+}
+
+/// [`raw_block_read`] for `count` consecutive blocks, as one device call.
+/// `NvmeBacking::read_pages` uses it to turn a run of a file's blocks into a
+/// single transfer.
+#[must_use = "a failed read leaves the buffer holding whatever it held before"]
+pub fn raw_blocks_read(id: DeviceId, block: u64, count: u32, buf: &mut [u8]) -> BlockResult {
+    raw(device(id)).read_blocks(block, count, buf)
 }
 
 /// Write a block directly to disk, bypassing the cache.
 /// Locks only the device.
 /// Used by filesystem write_page for file data writeback.
 #[must_use = "a failed write did not reach the device"]
-pub fn raw_block_write(block: u64, buf: &[u8; 4096]) -> BlockResult {
-    let mut dev = BLOCK_DEV.lock();
-    let dev = dev.as_mut().expect("block device not initialized");
-    dev.write_blocks(block, 1, buf)
+pub fn raw_block_write(id: DeviceId, block: u64, buf: &[u8; 4096]) -> BlockResult {
+    raw(device(id)).write_blocks(block, 1, buf)
+}
+
+/// Tell the device `count` blocks from `block` are free, bypassing the cache.
+///
+/// A block of the range that is still cached — clean, or dirty from before it
+/// was freed — is left there: the filesystem that freed it reads it again only
+/// after reallocating and rewriting it, and a dirty one written back after the
+/// discard costs the flash an erase and nothing else.
+pub fn discard(id: DeviceId, block: u64, count: u32) -> BlockResult {
+    raw(device(id)).discard(block, count)
+}
+
+/// The only transfer unit [`BlockDevice`] has, for the byte-range calls below.
+const BLOCK: usize = 4096;
+
+/// Read `buf.len()` bytes from byte `at` of the device, for a filesystem that
+/// addresses its volume in bytes rather than in this cache's blocks — FAT32,
+/// ext4 and ISO 9660, whose adapters each kept a private block or eight of
+/// their own before they came behind the cache.
+///
+/// The partial blocks at either end come through the cache, because that is
+/// where such a filesystem's small records are: a device block holds 1024 FAT
+/// entries, four 1 KiB ext4 metadata blocks, two 2 KiB directory sectors of a
+/// disc, and the next record asked for is very often in the block just read.
+/// The whole blocks in between go down the raw path as one transfer, as file
+/// data does. That is coherent with the cache because [`write_bytes`] writes
+/// through it: no block these callers read is ever dirty here.
+#[must_use = "a failed read leaves the buffer holding whatever it held before"]
+pub fn read_bytes(id: DeviceId, at: u64, buf: &mut [u8]) -> BlockResult {
+    let entry = device(id);
+    let mut done = 0usize;
+    while done < buf.len() {
+        let pos = at + done as u64;
+        let block = pos / BLOCK as u64;
+        let within = (pos % BLOCK as u64) as usize;
+        let left = buf.len() - done;
+        if within == 0 && left >= BLOCK {
+            let end = done + left / BLOCK * BLOCK;
+            raw(entry).read_blocks(block, (left / BLOCK) as u32, &mut buf[done..end])?;
+            done = end;
+        } else {
+            let n = (BLOCK - within).min(left);
+            let mut guard = lock(id);
+            let (cache, dev) = guard.cache_and_dev();
+            let page = cache.read(dev, block)?;
+            buf[done..done + n].copy_from_slice(&page[within..within + n]);
+            done += n;
+        }
+    }
+    Ok(())
+}
+
+/// Write `buf` at byte `at` of the device, reaching the device before this
+/// returns.
+///
+/// Write-through rather than the write-back [`PageCache::write_new`] does,
+/// because the filesystems that write this way have no `sync` that commits a
+/// cache: FAT32's own promise is that a write is on the device once the call
+/// returns, and `/log` is read after a crash. A cached copy of any block the
+/// write covers is updated with it, so the next [`read_bytes`] of a partial
+/// block sees the new bytes without a device read.
+#[must_use = "a failed write did not reach the device"]
+pub fn write_bytes(id: DeviceId, at: u64, buf: &[u8]) -> BlockResult {
+    let mut guard = lock(id);
+    let (cache, dev) = guard.cache_and_dev();
+    let mut done = 0usize;
+    while done < buf.len() {
+        let pos = at + done as u64;
+        let block = pos / BLOCK as u64;
+        let within = (pos % BLOCK as u64) as usize;
+        let left = buf.len() - done;
+        if within == 0 && left >= BLOCK {
+            let end = done + left / BLOCK * BLOCK;
+            cache.write_through(dev, block, &buf[done..end])?;
+            done = end;
+        } else {
+            // The bytes this request does not cover belong to whoever wrote
+            // them — another file, or the partition table itself.
+            let n = (BLOCK - within).min(left);
+            cache.patch(dev, block, within, &buf[done..done + n])?;
+            done += n;
+        }
+    }
+    Ok(())
+}
+
+/// Ask the device to make what it has been given durable. Nothing is written
+/// back from the cache first: a caller of [`write_bytes`] has nothing here
+/// that the device does not already have.
+pub fn flush(id: DeviceId) -> BlockResult {
+    device(id).dev.lock().flush()
 }
 
 /// The block number of a slot that names nothing — see [`PageCache::unbind`].
@@ -113,11 +387,16 @@ pub struct PageCache {
     /// The device's size, which the filesystem needs. Nothing in here may
     /// size an allocation by it.
     block_count: u64,
-    _device_id: DeviceId,
+    device_id: DeviceId,
+    /// This cache's share of [`RESIDENT`], mirrored for [`reclaim_for`].
+    resident: &'static AtomicUsize,
 }
 
 impl PageCache {
-    fn new(block_count: u64, device_id: DeviceId) -> Self {
+    fn new(block_count: u64, device_id: DeviceId, resident: &'static AtomicUsize) -> Self {
+        // The whole budget, because one device alone may use all of it. The
+        // reservations below are per device and so cost a second disk its own
+        // index — a few pages, and what `index_capacity` reports.
         let max_slots = block::metadata_cache_blocks();
         Self {
             // Reserved up front so `index_capacity` reports the real ceiling
@@ -132,7 +411,8 @@ impl PageCache {
             max_slots,
             evictions: 0,
             block_count,
-            _device_id: device_id,
+            device_id,
+            resident,
         }
     }
 
@@ -153,7 +433,7 @@ impl PageCache {
     /// write-back that would clean them failed, which is a device error and
     /// not the fail-fast this used to be.
     fn alloc_slot(&mut self, dev: &mut dyn BlockDevice, block: u64) -> Option<u32> {
-        let slot = if self.slot_to_block.len() < self.max_slots {
+        let slot = if self.may_grow() {
             let slot = self.slot_to_block.len() as u32;
             self.slot_to_block.push(block);
             self.dirty.push(false);
@@ -161,6 +441,7 @@ impl PageCache {
             if slot as usize / PAGES_PER_CHUNK >= self.chunks.len() {
                 self.chunks.push(vec![0u8; CHUNK_SIZE].into_boxed_slice());
             }
+            self.resident.store(self.slot_to_block.len(), Ordering::Relaxed);
             slot
         } else {
             let slot = self.take_victim(dev)?;
@@ -172,9 +453,11 @@ impl PageCache {
             // with the bound instead of with a number picked here: it is the
             // only evidence from outside the kernel that residency stays flat
             // while the eviction count climbs.
-            if self.evictions == 1 || self.evictions.is_multiple_of(self.max_slots as u64) {
-                log!("page cache: {} evictions, {}/{} slots resident",
-                    self.evictions, self.slot_to_block.len(), self.max_slots);
+            let turnover = self.slot_to_block.len().max(1) as u64;
+            if self.evictions == 1 || self.evictions.is_multiple_of(turnover) {
+                log!("page cache: {} evictions, {}/{} slots resident across devices, {} on device {}",
+                    self.evictions, RESIDENT.load(Ordering::Relaxed), self.max_slots,
+                    self.slot_to_block.len(), self.device_id);
             }
             slot
         };
@@ -183,6 +466,72 @@ impl PageCache {
         Some(slot)
     }
 
+    /// Whether a new slot may be added rather than one evicted, and if so the
+    /// slot is already counted in [`RESIDENT`].
+    ///
+    /// A slot is added only by winning the increment that keeps the machine
+    /// under its budget, so two devices filling at once cannot both take the
+    /// last one. Below [`FLOOR_SLOTS`] a device grows regardless.
+    fn may_grow(&self) -> bool {
+        let held = self.slot_to_block.len();
+        if held < FLOOR_SLOTS {
+            RESIDENT.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        if held >= self.max_slots {
+            return false;
+        }
+        // A plain CAS loop rather than `fetch_update`, whose name is
+        // deprecated on newer toolchains and not yet replaced on older ones.
+        let mut n = RESIDENT.load(Ordering::Relaxed);
+        while n < self.max_slots {
+            match RESIDENT.compare_exchange_weak(n, n + 1, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(now) => n = now,
+            }
+        }
+        false
+    }
+
+    /// Give up to `n` clean slots back to the budget, and the memory under
+    /// them once a whole chunk is empty. Returns how many went.
+    ///
+    /// A slot is only ever removed from the end, so what is released is the
+    /// *last* slot: the victim CLOCK picks takes the last slot's block and
+    /// bytes, and the last slot goes. Nothing the cache holds is lost but the
+    /// victim, which is the block eviction would have lost anyway.
+    fn release(&mut self, n: usize) -> usize {
+        let mut released = 0;
+        while released < n && self.slot_to_block.len() > FLOOR_SLOTS {
+            let Some(victim) = self.clock_pick() else { break };
+            let last = self.slot_to_block.len() as u32 - 1;
+            self.block_to_slot.remove(&self.slot_to_block[victim as usize]);
+            if victim != last {
+                let moved = self.slot_to_block[last as usize];
+                self.copy_slot(last, victim);
+                self.slot_to_block[victim as usize] = moved;
+                self.dirty[victim as usize] = self.dirty[last as usize];
+                self.referenced[victim as usize] = self.referenced[last as usize];
+                if moved != NO_BLOCK {
+                    self.block_to_slot.insert(moved, victim);
+                }
+            }
+            self.slot_to_block.pop();
+            self.dirty.pop();
+            self.referenced.pop();
+            if self.chunks.len() > self.slot_to_block.len().div_ceil(PAGES_PER_CHUNK) {
+                self.chunks.pop();
+            }
+            if self.hand as usize >= self.slot_to_block.len() {
+                self.hand = 0;
+            }
+            RESIDENT.fetch_sub(1, Ordering::Relaxed);
+            released += 1;
+        }
+        self.resident.store(self.slot_to_block.len(), Ordering::Relaxed);
+        released
+    }
+
     /// Undo a binding whose fill never happened.
     ///
     /// The slot still holds the evicted block's bytes, so the one thing that
@@ -252,14 +601,41 @@ impl PageCache {
         &mut self.chunks[chunk_idx][off..off + 4096]
     }
 
+    /// Copy one slot's bytes over another's, with no page-sized temporary:
+    /// [`release`](Self::release) runs inside [`lock`], and a FAT32 write from
+    /// the idle loop, whose stack is 16 KiB, reaches that.
+    fn copy_slot(&mut self, from: u32, to: u32) {
+        let (from, to) = (from as usize, to as usize);
+        let (from_chunk, from_off) = (from / PAGES_PER_CHUNK, from % PAGES_PER_CHUNK * 4096);
+        let (to_chunk, to_off) = (to / PAGES_PER_CHUNK, to % PAGES_PER_CHUNK * 4096);
+        if from_chunk == to_chunk {
+            self.chunks[to_chunk].copy_within(from_off..from_off + 4096, to_off);
+        } else {
+            let (low, high) = self.chunks.split_at_mut(from_chunk.max(to_chunk));
+            let (src, dst) = if from_chunk < to_chunk {
+                (&low[from_chunk][..], &mut high[0][..])
+            } else {
+                (&high[0][..], &mut low[to_chunk][..])
+            };
+            dst[to_off..to_off + 4096].copy_from_slice(&src[from_off..from_off + 4096]);
+        }
+    }
+
     fn slot_of(&self, block: u64) -> Option<u32> {
         self.block_to_slot.get(&block).copied()
     }
 
     pub fn read(&mut self, dev: &mut dyn BlockDevice, block: u64) -> Result<&[u8], BlockError> {
+        let slot = self.fill(dev, block)?;
+        Ok(self.slot_data(slot))
+    }
+
+    /// The slot holding `block`, read from the device first if it is not
+    /// resident.
+    fn fill(&mut self, dev: &mut dyn BlockDevice, block: u64) -> Result<u32, BlockError> {
         if let Some(slot) = self.slot_of(block) {
             self.referenced[slot as usize] = true;
-            return Ok(self.slot_data(slot));
+            return Ok(slot);
         }
         let slot = self.alloc_slot(dev, block).ok_or(BlockError)?;
         let page = self.slot_data_mut(slot);
@@ -267,7 +643,55 @@ impl PageCache {
             self.unbind(slot, block);
             return Err(e);
         }
-        Ok(self.slot_data(slot))
+        Ok(slot)
+    }
+
+    /// Write whole blocks from `block` to the device now, and bring each copy
+    /// of one the cache holds up to date — see [`write_bytes`].
+    ///
+    /// A copy is only changed once the device has the bytes. On a refusal the
+    /// device holds the old block, the new one or a torn mix, so a clean copy
+    /// is dropped rather than believed, and a dirty one is left to its own
+    /// write-back.
+    fn write_through(&mut self, dev: &mut dyn BlockDevice, block: u64, buf: &[u8]) -> BlockResult {
+        let result = dev.write_blocks(block, (buf.len() / 4096) as u32, buf);
+        for (i, page) in buf.chunks_exact(4096).enumerate() {
+            let at = block + i as u64;
+            let Some(slot) = self.slot_of(at) else { continue };
+            match result {
+                Ok(()) => {
+                    self.slot_data_mut(slot).copy_from_slice(page);
+                    self.dirty[slot as usize] = false;
+                }
+                Err(_) if !self.dirty[slot as usize] => self.unbind(slot, at),
+                Err(_) => {}
+            }
+        }
+        result
+    }
+
+    /// Replace `bytes.len()` bytes at `within` of `block` and write the block
+    /// to the device now. The rest of the block is read first if it is not
+    /// resident, and patched in place: it is on the heap already, and the
+    /// deepest caller of this is the idle loop writing `/log`.
+    fn patch(
+        &mut self,
+        dev: &mut dyn BlockDevice,
+        block: u64,
+        within: usize,
+        bytes: &[u8],
+    ) -> BlockResult {
+        let slot = self.fill(dev, block)?;
+        let was_dirty = self.dirty[slot as usize];
+        self.slot_data_mut(slot)[within..within + bytes.len()].copy_from_slice(bytes);
+        let result = dev.write_blocks(block, 1, self.slot_data(slot));
+        match result {
+            Ok(()) => self.dirty[slot as usize] = false,
+            // The copy now holds bytes the device may not: see `write_through`.
+            Err(_) if !was_dirty => self.unbind(slot, block),
+            Err(_) => {}
+        }
+        result
     }
 
     pub fn write_new(
@@ -350,3 +774,158 @@ impl PageCache {
         if failed { Err(BlockError) } else { Ok(()) }
     }
 }
+
+/// A disk with nothing on it but its block numbers, for [`fairness_selftest`]
+/// and `file_cache::readahead_selftest`: each block it reads starts with its
+/// own number, and every `read_blocks` call is counted, so a self-test can tell
+/// a block served from a cache from one that had to be fetched again, and one
+/// transfer of many blocks from many transfers of one.
+#[cfg(feature = "boot-actuators")]
+pub(crate) struct Synthetic {
+    pub(crate) id: DeviceId,
+    pub(crate) reads: &'static AtomicUsize,
+}
+
+#[cfg(feature = "boot-actuators")]
+impl BlockDevice for Synthetic {
+    fn device_id(&self) -> DeviceId {
+        self.id
+    }
+
+    fn block_count(&self) -> u64 {
+        1 << 32
+    }
+
+    fn read_blocks(&mut self, lba: u64, _count: u32, buf: &mut [u8]) -> BlockResult {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        for (i, page) in buf.chunks_exact_mut(4096).enumerate() {
+            page[..8].copy_from_slice(&(lba + i as u64).to_le_bytes());
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, _lba: u64, _count: u32, _buf: &[u8]) -> BlockResult {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> BlockResult {
+        Ok(())
+    }
+}
+
+/// Drive the shared budget with two registered devices, one streaming and one
+/// quiet, and say whether the module docs' fairness holds.
+///
+/// The test boot's own disks cannot say it: `/home` is the only one that ever
+/// fills the budget, and the boot stick's FAT blocks are a handful, so no boot
+/// has a device under its share asking a full cache for room. Here one device
+/// reads through twice the budget alone — it may, because the room is idle —
+/// then the other asks for its fair share, which only [`reclaim_for`] can give
+/// it, then the first streams on through as many blocks again. The second must
+/// still hold every block of its share, and [`RESIDENT`] must never have passed
+/// the budget by more than [`FLOOR_SLOTS`] per device.
+///
+/// Nothing is simulated but the two disks: the registry, the budget, the
+/// reclaim and both caches are the shipped ones. The registrations last the
+/// boot, so a boot that runs this has two more devices dividing its share.
+#[cfg(feature = "boot-actuators")]
+pub fn fairness_selftest() {
+    const CASES: usize = 5;
+
+    let counter = || -> &'static AtomicUsize { Box::leak(Box::new(AtomicUsize::new(0))) };
+    let (quiet_reads, stream_reads) = (counter(), counter());
+    // Past every id a driver issues — USB counts up from 16 — so neither can
+    // collide with a real disk's registration.
+    let quiet = register(Box::new(Synthetic { id: DeviceId::MAX - 1, reads: quiet_reads }));
+    let stream = register(Box::new(Synthetic { id: DeviceId::MAX, reads: stream_reads }));
+
+    let budget = BUDGET.load(Ordering::Relaxed);
+    let share = fair_share();
+    let devices = DEVICES.lock().len();
+    let ceiling = budget + FLOOR_SLOTS * devices;
+    let held = |id: DeviceId| device(id).resident.load(Ordering::Relaxed);
+
+    let mut peak = 0usize;
+    let mut mislabelled = 0usize;
+    let mut read = |id: DeviceId, block: u64| {
+        let mut guard = lock(id);
+        let (cache, dev) = guard.cache_and_dev();
+        match cache.read(dev, block) {
+            Ok(page) if page[..8] == block.to_le_bytes() => {}
+            _ => mislabelled += 1,
+        }
+        drop(guard);
+        peak = peak.max(RESIDENT.load(Ordering::Relaxed));
+    };
+    let (budget_blocks, share_blocks) = (budget as u64, share as u64);
+
+    let mut passed = 0usize;
+
+    // Alone, the streamer grows into the idle room until the budget is full.
+    // Without that, nobody is ever owed anything below and every later case
+    // passes for the wrong reason.
+    for block in 0..2 * budget_blocks {
+        read(stream, block);
+    }
+    let resident = RESIDENT.load(Ordering::Relaxed);
+    if resident >= budget {
+        passed += 1;
+    } else {
+        log!("page cache: fairness selftest FAILED on filling the budget: {resident} of \
+              {budget} resident after a stream of {}", 2 * budget);
+    }
+
+    // The quiet disk asks a full cache for its share, then reads it again.
+    for block in 0..share_blocks {
+        read(quiet, block);
+    }
+    let before = quiet_reads.load(Ordering::Relaxed);
+    for block in 0..share_blocks {
+        read(quiet, block);
+    }
+    let refetched = quiet_reads.load(Ordering::Relaxed) - before;
+    if refetched == 0 && held(quiet) >= share {
+        passed += 1;
+    } else {
+        log!("page cache: fairness selftest FAILED on taking the share back: holds {} of a \
+              {share}-slot share, {refetched} re-read from the device", held(quiet));
+    }
+
+    // The streamer goes on through as many fresh blocks again. It is over its
+    // share, so every one of them has to come out of its own slots.
+    for block in 2 * budget_blocks..4 * budget_blocks {
+        read(stream, block);
+    }
+    let before = quiet_reads.load(Ordering::Relaxed);
+    for block in 0..share_blocks {
+        read(quiet, block);
+    }
+    let refetched = quiet_reads.load(Ordering::Relaxed) - before;
+    if refetched == 0 {
+        passed += 1;
+    } else {
+        log!("page cache: fairness selftest FAILED on holding the share: {refetched} of \
+              {share} blocks evicted by the streaming disk");
+    }
+
+    if peak <= ceiling {
+        passed += 1;
+    } else {
+        log!("page cache: fairness selftest FAILED on the budget: {peak} resident at the \
+              peak, {ceiling} allowed");
+    }
+
+    // Every block came back as itself, across the slots `release` moved.
+    if mislabelled == 0 {
+        passed += 1;
+    } else {
+        log!("page cache: fairness selftest FAILED on the data: {mislabelled} reads \
+              answered with another block's bytes");
+    }
+
+    log!("page cache: fairness selftest: {budget} slots over {devices} devices, a share of \
+          {share}; the quiet disk holds {}, the streaming disk {} after {} reads, peak {peak} \
+          resident",
+        held(quiet), held(stream), stream_reads.load(Ordering::Relaxed));
+    log!("page cache: fairness selftest {passed}/{CASES}");
+}
@@ -0,0 +1,70 @@
+//! The machine's power controls, as one queue of presses.
+//!
+//! Filled by `drivers::sci` from the SCI's fixed events and by `drivers::aml`
+//! from the lid and the buttons firmware describes as devices, and read by
+//! whoever holds the `power` claim. Nothing here acts on a press: what a power
+//! button means is the holder's decision, for the reason `toyos_abi::power`
+//! gives.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::inbox::InboxId;
+use crate::sync::Lock;
+pub use toyos_abi::power::{PowerEvent, LID, LID_CLOSED, LID_OPEN, POWER_BUTTON, SLEEP_BUTTON};
+
+static EVENTS: Lock<VecDeque<PowerEvent>> = Lock::new(VecDeque::new());
+static INBOX_WATCHERS: Lock<Vec<InboxId>> = Lock::new(Vec::new());
+
+/// How many presses the kernel holds for a reader that is not reading.
+///
+/// Small, and full means the *newest* is dropped, the opposite of the mouse's
+/// bound: the first press of the power button is the request, and the tenth
+/// press of a reader that has not answered the first says nothing new.
+pub const MAX_QUEUED_EVENTS: usize = 16;
+
+pub fn add_inbox_watcher(id: InboxId) {
+    let mut w = INBOX_WATCHERS.lock();
+    if !w.contains(&id) { w.push(id); }
+}
+
+pub fn remove_inbox_watcher(id: InboxId) {
+    INBOX_WATCHERS.lock().retain(|&x| x != id);
+}
+
+pub fn inbox_watchers() -> Vec<InboxId> {
+    INBOX_WATCHERS.lock().clone()
+}
+
+/// Queue one event and complete the holder's poll. Thread context only: the
+/// SCI's ISR records what fired and `drivers::sci::service` calls this, or
+/// `acpid` does for what a method notified.
+pub fn publish(kind: u32, state: u32) {
+    {
+        let mut events = EVENTS.lock();
+        if events.len() >= MAX_QUEUED_EVENTS {
+            return;
+        }
+        events.push_back(PowerEvent { kind, state });
+    }
+    let watchers = inbox_watchers();
+    if !watchers.is_empty() {
+        crate::inbox::complete_pending_for_event(&watchers, crate::inbox::Source::Power);
+    }
+}
+
+/// Throw away everything queued, for the reason
+/// [`crate::keyboard::discard_queued`] gives — with more at stake: a press
+/// nobody read, handed to the next claimant, is a shutdown nobody asked that
+/// claimant for.
+pub fn discard_queued() {
+    EVENTS.lock().clear();
+}
+
+pub fn has_data() -> bool {
+    !EVENTS.lock().is_empty()
+}
+
+pub fn try_read_event() -> Option<PowerEvent> {
+    EVENTS.lock().pop_front()
+}
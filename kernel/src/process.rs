@@ -37,6 +37,7 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::ptr::NonNull;
 use crate::arch::percpu;
+use crate::file_backing::READ_BATCH_PAGES;
 use crate::mm::paging::{CachePolicy, Prot, WindowProt};
 use crate::mm::PAGE_2M;
 use crate::object::{ops, HandleTable};
@@ -1796,6 +1797,10 @@ pub fn handle_page_fault(fault_addr: u64, _error_code: u64) -> bool {
     // Fill the 2MB page from ALL regions that overlap this range.
     // Multiple segments (e.g. .text and .rodata) can share a 2MB range.
     let mut io_reads: u32 = 0;
+    let mut io_bytes: u64 = 0;
+    // On the heap and only once a region has file bytes to read: a batch is
+    // 256 KiB, and an anonymous fault has no use for one.
+    let mut batch: Vec<u8> = Vec::new();
     for region in &regions {
         match &region.kind {
             RegionSnapKind::Anonymous => {
@@ -1805,49 +1810,58 @@ pub fn handle_page_fault(fault_addr: u64, _error_code: u64) -> bool {
                 let fill_start = region_start.max(region.start);
                 let fill_end = region_end_full.min(region.end);
                 let mut vaddr = fill_start & !0xFFF;
-
-                while vaddr < fill_end {
+                // Past the file's end the frame's zeros are the mapping, so
+                // the reads stop there rather than at the region's end.
+                let read_end = fill_end.min(region.start.saturating_add(*file_size));
+
+                // Batches of `READ_BATCH_PAGES` rather than a page at a time.
+                // A 2 MiB fill of a shared object off the boot stick was 512
+                // round trips, and this is what made loading rustc slow.
+                while vaddr < read_end {
+                    if batch.is_empty() {
+                        batch = alloc::vec![0u8; READ_BATCH_PAGES * 4096];
+                    }
                     let vma_offset = vaddr - region.start;
                     let page_offset = (vaddr - region_start) as usize;
-
-                    if vma_offset < *file_size {
-                        let byte_offset = vma_offset + file_offset;
-                        let mut page_buf = [0u8; 4096];
-                        // Unhandled, not filled with zeros. The fault is on a
-                        // file-backed mapping, so zeros here are instructions
-                        // or constants the program never had, and the fault it
-                        // takes later names an address rather than the disk.
-                        // `page_alloc` is a local, so this return gives the
-                        // 2 MiB page back.
-                        if backing.read_page(byte_offset, &mut page_buf).is_err() {
-                            log!("fault: {:#x} is backed by a file byte {byte_offset} that the \
-                                 device would not read; leaving the fault unhandled",
-                                fault_addr);
-                            return false;
-                        }
-                        io_reads += 1;
-                        let valid = if vma_offset + 4096 <= *file_size { 4096 } else { (*file_size - vma_offset) as usize };
-                        // SAFETY: `copy_from` asserts
-                        // `page_offset + valid <= page_2m` against
-                        // `page_alloc`'s own size, so the write lands inside
-                        // the frame — the bound that used to be derived from
-                        // this loop's conditions is now checked. `page_alloc`
-                        // is a local of this function that nothing else can
-                        // see: the frame is not mapped into any address space
-                        // until `map_window` below, so nothing is reading it
-                        // and the `noalias` question the borrow would have
-                        // raised does not arise. `page_buf` is a stack array
-                        // this loop owns, so the ranges cannot overlap.
-                        //
-                        // Irreducible: the safe spelling is a `&mut [u8]`, and
-                        // these pages become a user mapping four statements
-                        // later — the borrow `user_ptr.rs`'s header refuses.
-                        // A bounds-checked window that hands out no reference is
-                        // the reduction, and `PageAlloc::window` is where it is
-                        // taken.
-                        unsafe { page.copy_from(page_offset, &page_buf[..valid]) };
+                    let pages = ((read_end - vaddr).div_ceil(4096) as usize).min(READ_BATCH_PAGES);
+                    let buf = &mut batch[..pages * 4096];
+
+                    let byte_offset = vma_offset + file_offset;
+                    // Unhandled, not filled with zeros. The fault is on a
+                    // file-backed mapping, so zeros here are instructions
+                    // or constants the program never had, and the fault it
+                    // takes later names an address rather than the disk.
+                    // `page_alloc` is a local, so this return gives the
+                    // 2 MiB page back.
+                    if backing.read_pages(byte_offset, buf).is_err() {
+                        log!("fault: {:#x} is backed by file bytes from {byte_offset} that the \
+                             device would not read; leaving the fault unhandled",
+                            fault_addr);
+                        return false;
                     }
-                    vaddr += 4096;
+                    io_reads += 1;
+                    let valid = ((*file_size - vma_offset) as usize).min(buf.len());
+                    io_bytes += valid as u64;
+                    // SAFETY: `copy_from` asserts
+                    // `page_offset + valid <= page_2m` against
+                    // `page_alloc`'s own size, so the write lands inside
+                    // the frame — the bound that used to be derived from
+                    // this loop's conditions is now checked. `page_alloc`
+                    // is a local of this function that nothing else can
+                    // see: the frame is not mapped into any address space
+                    // until `map_window` below, so nothing is reading it
+                    // and the `noalias` question the borrow would have
+                    // raised does not arise. `batch` is a buffer this loop
+                    // owns, so the ranges cannot overlap.
+                    //
+                    // Irreducible: the safe spelling is a `&mut [u8]`, and
+                    // these pages become a user mapping four statements
+                    // later — the borrow `user_ptr.rs`'s header refuses.
+                    // A bounds-checked window that hands out no reference is
+                    // the reduction, and `PageAlloc::window` is where it is
+                    // taken.
+                    unsafe { page.copy_from(page_offset, &buf[..valid]) };
+                    vaddr += pages as u64 * 4096;
                 }
             }
         }
@@ -1899,7 +1913,7 @@ pub fn handle_page_fault(fault_addr: u64, _error_code: u64) -> bool {
     if io_reads > 0 {
         data.accounting.fault_demand_count += 1;
         data.accounting.io_read_ops += io_reads;
-        data.accounting.io_read_bytes += io_reads as u64 * 4096;
+        data.accounting.io_read_bytes += io_bytes;
     } else {
         data.accounting.fault_zero_count += 1;
     }
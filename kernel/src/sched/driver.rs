@@ -979,9 +979,15 @@ fn drain_irqs() {
     // The i8042's bytes are already in kernel memory when the IRQ returns;
     // this turns them into events and wakes.
     crate::drivers::i8042::service();
+    // virtio-input's events are still in its queues; this decodes them the
+    // same way, and wakes the same readers.
+    crate::drivers::virtio_input::service();
+    // The SCI's ISR has acknowledged what fired; a power button press becomes
+    // an event for the `power` claim's holder here.
+    crate::drivers::sci::service();
     // Ctrl+Alt+D. Here rather than at the keystroke, which is decoded under
     // whichever driver's guard produced it: this walks the scheduler and logs
-    // a line per parked thread, and both drivers are done above.
+    // a line per parked thread, and every keyboard's driver is done above.
     if crate::keyboard::take_dump_request() {
         super::dump::request();
     }
@@ -994,17 +1000,13 @@ fn drain_irqs() {
     crate::drivers::panic_console::hold_report();
 
     if crate::irq_ring::take(crate::irq_ring::IrqSource::Net).is_some() {
-        crate::net::wake_waiters();
-        let watchers = crate::net::inbox_watchers();
-        if !watchers.is_empty() {
-            crate::inbox::complete_pending_for_event(
-                &watchers,
-                crate::inbox::Source::Network,
-            );
-        }
+        crate::net::wake();
     }
+    // Packets for the vsock claim's holder: copied off the receive ring and
+    // queued, and the holder's poll completed.
+    crate::drivers::virtio_vsock::service();
     if crate::irq_ring::take(crate::irq_ring::IrqSource::Audio).is_some() {
-        // One wait queue for both backends: an over-wake costs a recheck, and a
+        // One wait queue for every backend: an over-wake costs a recheck, and a
         // second queue would have to be chosen by whichever driver bound —
         // which is a fact the parking side does not have.
         crate::sched::waitqs::wake_device(&crate::sched::waitqs::AUDIO_WATCH);
@@ -1014,6 +1016,7 @@ fn drain_irqs() {
                 crate::inbox::Source::VirtioSound,
             ),
             (crate::drivers::hda::inbox_watchers(), crate::inbox::Source::Hda),
+            (crate::drivers::xhci::audio::inbox_watchers(), crate::inbox::Source::UsbAudio),
         ] {
             if !watchers.is_empty() {
                 crate::inbox::complete_pending_for_event(&watchers, source);
@@ -553,11 +553,11 @@ fn census() -> Census {
             return;
         }
         // **A kernel thread is named whatever it is doing, and it is the one
-        // exception to the rule below.** `klogd`, `usbd` and `iod` are almost
-        // always blocked, so the CPUs' parked lines are where they appear — and
-        // those lines carry a pid and a tid and no name. On a machine that has
-        // gone quiet the question is *which* of the three is stuck, and a pid is
-        // not an answer to it.
+        // exception to the rule below.** `klogd`, `usbd`, `iod` and `acpid` are
+        // almost always blocked, so the CPUs' parked lines are where they appear
+        // — and those lines carry a pid and a tid and no name. On a machine that
+        // has gone quiet the question is *which* of the four is stuck, and a pid
+        // is not an answer to it.
         let kernel = crate::sched::kthread::is_kernel_task(crate::scheduler::TaskId(
             thread.pid,
             thread.tid,
@@ -576,9 +576,9 @@ fn census() -> Census {
         // Blocked and running threads are the CPUs' lines; printing them again
         // would push the ones only this half can see off the page.
         let Some(tag) = tag else { return };
-        // The three kernel threads do not count against the budget and cannot
+        // The four kernel threads do not count against the budget and cannot
         // flood it: `sched::kthread::MAX_KERNEL_TASKS` is the ceiling, and a
-        // shipping kernel's is three. Counting them would let a machine with
+        // shipping kernel's is four. Counting them would let a machine with
         // enough ready threads push the very lines this exception exists for
         // off the page.
         if !kernel {
@@ -1,10 +1,10 @@
 //! Kernel threads: a task with no address space of its own, and the one place
 //! that says what a panic inside one means.
 //!
-//! There are three: `klogd`, the console drainer; `drivers::xhci::usbd`; and
-//! `crate::iod`. [`ROWS`] carries all three, and the machine has one thread per
-//! kind of work that must not borrow whichever thread happened to trap — a
-//! stuck USB enumeration must not stop the log.
+//! There are four: `klogd`, the console drainer; `drivers::xhci::usbd`;
+//! `crate::iod`; and `acpid` (`drivers::aml`). [`ROWS`] carries all four, and
+//! the machine has one thread per kind of work that must not borrow whichever
+//! thread happened to trap — a stuck USB enumeration must not stop the log.
 //!
 //! **A kernel thread is not a special kind of task.** It is an ordinary task
 //! that names `mm::paging::kernel` as its address space — the one every CPU is
@@ -36,24 +36,25 @@ use super::payload::ThreadSched;
 
 /// How many kernel threads the machine may have.
 ///
-/// Three, and all three exist: `klogd` (`log::console`), `usbd`
-/// (`drivers::xhci::usbd`) and `iod` (`crate::iod`). A fourth is a design
-/// decision and gets to notice that it is one — `Claim::take` dies naming the
-/// thread that had nowhere to go, before it takes the process table.
+/// Four, and all four exist: `klogd` (`log::console`), `usbd`
+/// (`drivers::xhci::usbd`), `iod` (`crate::iod`) and `acpid`
+/// (`drivers::aml`). A fifth is a design decision and gets to notice that it
+/// is one — `Claim::take` dies naming the thread that had nowhere to go,
+/// before it takes the process table.
 ///
 /// **The test kernel carries room for one `log-storm` thread per shard on top,
 /// and the shipping kernel carries none of it.** That storm is one ordinary
 /// stealable task per CPU, and it exists only in the build that has the
-/// actuator. A shipping kernel spawning a fourth still dies naming it, so the
+/// actuator. A shipping kernel spawning a fifth still dies naming it, so the
 /// rule above is untouched where it applies. It used to say the storm is what
 /// exercises the migration §2.3a's bracket exists to survive, and that was
 /// never true of any workload here: a task is stealable while it is *Ready*,
 /// and nothing switches a Ring 0 context out between two instructions
 /// (`issues/kernel/a-ring-0-loop-is-never-preempted.md`).
 #[cfg(not(feature = "boot-actuators"))]
-const MAX_KERNEL_TASKS: usize = 3;
+const MAX_KERNEL_TASKS: usize = 4;
 #[cfg(feature = "boot-actuators")]
-const MAX_KERNEL_TASKS: usize = 3 + toyos_abi::log::MAX_LOG_SHARDS;
+const MAX_KERNEL_TASKS: usize = 4 + toyos_abi::log::MAX_LOG_SHARDS;
 
 /// No task. `TaskId::pack` puts a `Pid` in the high word and a `Tid` in the
 /// low one, and neither id map ever issues `u32::MAX`, so this collides with
@@ -29,6 +29,7 @@ static FUTEX_WATCH: [Watch; FUTEX_BUCKETS] = [const { Watch::new() }; FUTEX_BUCK
 /// queue, so a shared list per device had nothing left in it.
 pub static KEYBOARD_WATCH: Watch = Watch::new();
 pub static MOUSE_WATCH: Watch = Watch::new();
+pub static GAMEPAD_WATCH: Watch = Watch::new();
 pub static NETWORK_WATCH: Watch = Watch::new();
 pub static AUDIO_WATCH: Watch = Watch::new();
 
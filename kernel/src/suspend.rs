@@ -0,0 +1,257 @@
+//! Suspend to RAM: the order the machine goes down in and comes back up in.
+//!
+//! Every piece has an owner elsewhere — `arch::sleep` parks the CPUs and
+//! carries them through the power loss, `drivers::acpi` writes `PM1_CNT`,
+//! `drivers::aml` runs `\_PTS`, `\_WAK` and the `_PS3`/`_PS0` of every device
+//! firmware powers, and each driver knows what its own device needs. This file is the sequence, and the one rule that makes the
+//! sequence safe to abandon halfway: **every `resume` here is also correct when
+//! the sleep never happened.** A chipset that ignores `SLP_EN` hands the CPU
+//! back with every device still powered, and the same resume path runs over
+//! it; so each driver's `suspend` leaves its device stopped in a state its own
+//! `resume` reinitialises from, rather than one only a power loss would clear.
+//!
+//! **Userland stops at Ring 3's door.** A thread on its way back to user mode
+//! while [`freezing`] is set parks in [`freeze`] instead, so from the moment
+//! the filesystems are synced nothing new is asked of a driver. A request
+//! already inside the kernel is not waited for beyond the sync — the thread
+//! that made it freezes on its way out, after the resume.
+//!
+//! **A driver that cannot come back says so up front.** Resume is a full
+//! reinitialisation: QEMU's `system_wakeup` resets every device, and on a real
+//! machine S3 cuts their power. The storage, network, input, vsock and display
+//! drivers rebuild what their devices forgot from state the kernel kept — the
+//! rings are the kernel's, so they can be posted again. The two sound drivers
+//! do too, and what their device forgot that only `soundd` knew is handed
+//! back to it. HDA sends the codec verbs again from the kernel's own copy,
+//! and virtio-sound tells `soundd` to configure its stream again. virtio-9p
+//! cannot come back, because its host server forgets every fid the mounted
+//! share holds. [`forbid`] is how it keeps the machine from sleeping rather
+//! than from waking.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+use toyos_sched::task::WaitClass;
+
+use crate::completion::{self, Outcome, Subject, Token, Watch};
+use crate::drivers::aml::{self, SleepCall};
+use crate::drivers::{
+    acpi, ahci, bochs, hda, i8042, ioapic, nvme, pci, sci, serial, virtio_blk, virtio_console,
+    virtio_input, virtio_sound, virtio_vsock, xhci,
+};
+use crate::scheduler::Parkable;
+use crate::sync::Lock;
+use crate::time::Deadline;
+
+/// Set from just after the filesystems are synced until every driver is back.
+static FREEZING: AtomicBool = AtomicBool::new(false);
+/// What a frozen thread is parked on; posted once by [`thaw`].
+static THAW: Watch = Watch::new();
+/// One sleep at a time: a second `suspend` while the first is on its way down
+/// is refused, not queued behind it.
+static ENTERING: AtomicBool = AtomicBool::new(false);
+/// `\_S3_`'s two types, packed for [`enter_state`], which is a bare `fn` and
+/// runs with every other CPU parked — the one place a lock is not worth it.
+static SLP_TYP: AtomicU16 = AtomicU16::new(0);
+/// The drivers that bound a device and cannot bring it back, with why.
+static FORBIDDEN: Lock<Vec<(&'static str, &'static str)>> = Lock::new(Vec::new());
+
+/// Why the machine did not sleep.
+#[derive(Clone, Copy, Debug)]
+pub enum Refusal {
+    /// Firmware describes no `\_S3_`, or there is no interpreter to read it.
+    NoS3,
+    /// The FADT names no PM1 control block, or no FACS to wake through.
+    NoWake,
+    /// A bound driver cannot reinitialise its device after the power loss.
+    Driver { driver: &'static str, why: &'static str },
+    /// Another sleep is already being entered.
+    Busy,
+    /// This CPU never reached a point where it holds nothing.
+    Cpu(u32),
+    /// The chipset let the `PM1_CNT` write pass without taking the power.
+    NotEntered,
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoS3 => write!(f, "firmware describes no S3"),
+            Self::NoWake => write!(f, "the FADT gives no PM1 control block or FACS"),
+            Self::Driver { driver, why } => write!(f, "{driver} cannot resume: {why}"),
+            Self::Busy => write!(f, "a sleep is already being entered"),
+            Self::Cpu(cpu) => write!(f, "CPU {cpu} did not park"),
+            Self::NotEntered => write!(f, "the chipset did not enter S3"),
+        }
+    }
+}
+
+/// Keep the machine out of S3 for as long as it runs: `driver` bound a device
+/// it cannot bring back from a power loss, for the reason `why` gives. Called
+/// once a driver has bound, so a machine without the device pays nothing.
+pub fn forbid(driver: &'static str, why: &'static str) {
+    let mut forbidden = FORBIDDEN.lock();
+    if !forbidden.iter().any(|&(d, _)| d == driver) {
+        forbidden.push((driver, why));
+    }
+}
+
+/// Whether a thread on its way to Ring 3 should stop in [`freeze`].
+pub fn freezing() -> bool {
+    FREEZING.load(Ordering::Acquire)
+}
+
+/// Park this thread until the machine is back. Reached only from the return
+/// to Ring 3, which is a trap entry's own depth and holds nothing.
+///
+/// A cancelled park — the thread was killed — returns, and the caller's
+/// loop runs `exit_if_killed` before it could come back here.
+pub fn freeze() {
+    let p = Parkable::at_entry();
+    let _ = completion::wait_until(
+        &p,
+        Subject::of(&THAW),
+        Token::new(0),
+        WaitClass::Other,
+        Deadline::never(),
+        || !freezing(),
+    );
+}
+
+fn thaw() {
+    FREEZING.store(false, Ordering::Release);
+    completion::post(Subject::of(&THAW), Outcome::Ready);
+}
+
+/// The first reason this machine cannot sleep, checked before anything is
+/// touched.
+fn refusal() -> Result<(u8, u8), Refusal> {
+    let slp_typ = aml::s3().ok_or(Refusal::NoS3)?;
+    if !acpi::can_sleep() {
+        return Err(Refusal::NoWake);
+    }
+    if let Some(&(driver, why)) = FORBIDDEN.lock().first() {
+        return Err(Refusal::Driver { driver, why });
+    }
+    if let Some(why) = xhci::suspend_refusal() {
+        return Err(Refusal::Driver { driver: "xHCI", why });
+    }
+    if let Some(why) = hda::suspend_refusal() {
+        return Err(Refusal::Driver { driver: "hda", why });
+    }
+    Ok(slp_typ)
+}
+
+/// Enter S3 and come back, or say why not.
+///
+/// The caller is a syscall with `IF` clear, which is what `arch::sleep` asks
+/// of whoever parks the other CPUs; it may block only before that and after
+/// they are released, which is where the two AML methods run.
+///
+/// `Ok` is a machine that slept and woke. [`Refusal::NotEntered`] is one that
+/// went all the way down and was put back without sleeping, and every other
+/// refusal is one nothing was done to.
+pub fn enter(p: &Parkable) -> Result<(), Refusal> {
+    let slp_typ = refusal()?;
+    if ENTERING.swap(true, Ordering::AcqRel) {
+        return Err(Refusal::Busy);
+    }
+    let slept = go_down(p, slp_typ);
+    ENTERING.store(false, Ordering::Release);
+    slept
+}
+
+fn go_down(p: &Parkable, slp_typ: (u8, u8)) -> Result<(), Refusal> {
+    log!("S3: syncing filesystems");
+    crate::vfs::lock().sync_all();
+    crate::log::wait_for_durable();
+    FREEZING.store(true, Ordering::Release);
+    // `\_PTS` asks `acpid`, which has to run; after the park nothing but this
+    // CPU does.
+    aml::sleep_method(p, SleepCall::Prepare(3));
+    if let Err(cpu) = crate::arch::sleep::park_others() {
+        log!("S3: CPU {cpu} did not park; staying awake");
+        aml::sleep_method(p, SleepCall::Wake(3));
+        thaw();
+        return Err(Refusal::Cpu(cpu));
+    }
+    SLP_TYP.store(u16::from(slp_typ.0) | u16::from(slp_typ.1) << 8, Ordering::Relaxed);
+
+    quiesce();
+    // After the drivers and after `pci::suspend` has read every header: a
+    // function in D3 may answer its configuration space with nothing.
+    aml::set_device_power(true);
+    log!("S3: suspending");
+    crate::log::console::drain_inline();
+    virtio_console::suspend();
+    crate::clock::suspend();
+    acpi::set_waking_vector(crate::arch::sleep::WAKE_VECTOR);
+    let woke = crate::arch::sleep::sleep(enter_state);
+    // Before `pci::resume` writes the headers back, for the same reason.
+    aml::set_device_power(false);
+    restore();
+    crate::arch::sleep::release_others();
+
+    aml::sleep_method(p, SleepCall::Wake(3));
+    thaw();
+    if woke {
+        log!("S3: resumed");
+        Ok(())
+    } else {
+        log!("S3: the chipset did not take the sleep; every device was restarted awake");
+        Err(Refusal::NotEntered)
+    }
+}
+
+/// The `PM1_CNT` write, as `arch::sleep` wants it.
+fn enter_state() {
+    let [a, b] = SLP_TYP.load(Ordering::Relaxed).to_le_bytes();
+    acpi::sleep((a, b));
+}
+
+/// Every device down, in the reverse of the order [`restore`] brings it back:
+/// the bus masters first, so none is mid-transfer when the IOMMU stops
+/// translating, and the interrupt routing last. The virtio console is the
+/// exception, and goes down in [`go_down`] after the last line it carries.
+fn quiesce() {
+    crate::gpu::suspend();
+    crate::net::suspend();
+    virtio_input::suspend();
+    virtio_vsock::suspend();
+    hda::suspend();
+    virtio_sound::suspend();
+    xhci::suspend();
+    virtio_blk::suspend();
+    ahci::suspend();
+    nvme::suspend();
+    crate::iommu::suspend();
+    pci::suspend();
+    ioapic::suspend();
+    sci::suspend();
+}
+
+/// Every device back: config space before anything touches a BAR, the IOMMU
+/// before anything masters the bus, both consoles before the first line that
+/// might want one, and the clock before the first driver that waits on it.
+fn restore() {
+    pci::resume();
+    crate::iommu::resume();
+    virtio_console::resume();
+    ioapic::resume();
+    sci::resume();
+    serial::resume();
+    crate::clock::resync_wall();
+    nvme::resume();
+    ahci::resume();
+    virtio_blk::resume();
+    xhci::resume();
+    i8042::resume();
+    virtio_input::resume();
+    crate::net::resume();
+    virtio_vsock::resume();
+    virtio_sound::resume();
+    hda::resume();
+    bochs::resume();
+    crate::gpu::resume();
+}
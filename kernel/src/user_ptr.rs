@@ -98,9 +98,13 @@ unsafe impl UserSafe for toyos_abi::syscall::SchedInfo {}
 // `io_read_ops`/`pid`) are what keeps every `u64` 8-aligned, and `pid` was
 // named padding until it was given a meaning.
 unsafe impl UserSafe for toyos_abi::syscall::ProcessStats {}
-// SAFETY: `#[repr(C)] Copy`, `[RawHandle; 2]` then six `u32`s — 32 bytes,
-// align 4, no padding.
+// SAFETY: `#[repr(C)] Copy`, `[RawHandle; 2]`, seven `u32`s and sixteen
+// `Output`s — 292 bytes, align 4, no padding.
 unsafe impl UserSafe for toyos_abi::FramebufferInfo {}
+// SAFETY: `#[repr(C)] Copy`, four `u32`s — 16 bytes, align 4, no padding. An
+// arrangement with overlapping or empty outputs is still a value, refused by
+// `gpu::arrange` and not here.
+unsafe impl UserSafe for toyos_abi::Output {}
 // SAFETY: `#[repr(C)] Copy`, `RawHandle`, an explicit `_pad: u32`, `u64` — 16
 // bytes, no padding.
 unsafe impl UserSafe for toyos_abi::syscall::InboxSetup {}
@@ -218,8 +222,8 @@ impl<'a> SyscallContext<'a> {
     /// A copy rather than a borrow: a `&T` over a page userland can still write
     /// is a claim the compiler enforces and the hardware does not, and the
     /// kernel would be reading a value that can change between two of its own
-    /// reads. Every `UserSafe` type is at most 128 bytes, so the copy costs less
-    /// than the second lock-and-translate the borrow already paid.
+    /// reads. Every `UserSafe` type copied in is at most 128 bytes, so the copy
+    /// costs less than the second lock-and-translate the borrow already paid.
     pub fn copy_in<T: UserSafe>(&self, ptr: UserAddr) -> Result<T, SyscallError> {
         let kptr = object::<T>(ptr)?;
         // SAFETY: `object::<T>` returned, so `is_user_object` accepted
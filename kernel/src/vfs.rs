@@ -120,6 +120,16 @@ pub trait FileSystem: Send {
     /// sync produce the pending bytes that ask for the next one.
     fn sync(&mut self) -> Result<(), SyscallError>;
 
+    /// Make everything durable, then tell the device every block this
+    /// filesystem has free, and answer how many 4 KiB blocks that was.
+    ///
+    /// `NotSupported` by default, which is the honest answer for every mount
+    /// but `/home`: a tmpfs has no device, the initrd and an ISO are read-only,
+    /// and FAT and ext4 are not taught to walk their free space yet.
+    fn trim(&mut self) -> Result<u64, SyscallError> {
+        Err(SyscallError::NotSupported)
+    }
+
     /// Open a file backing for demand-paged ELF loading (separate from handle
     /// I/O).
     ///
@@ -725,6 +735,21 @@ impl Vfs {
         }
     }
 
+    /// Trim whichever filesystem `path` lives on, answering the blocks
+    /// discarded — `SYS_FSTRIM`.
+    ///
+    /// A path and not a mount name, for [`Vfs::sync_for_path`]'s reason: it is
+    /// what the caller has. A machine with no root and no such mount has
+    /// nothing to trim, and that *is* an error here, because the caller asked
+    /// about a volume that is not there.
+    pub fn trim_for_path(&mut self, path: &str) -> Result<u64, SyscallError> {
+        let (mount, _) = self.resolve_path("/", path);
+        if let Some(mount) = self.mounts.get_mut(&mount) {
+            return mount.fs.trim();
+        }
+        self.root.as_mut().ok_or(SyscallError::NotFound)?.trim()
+    }
+
     /// Every mount, on the way down. Failures are logged here and not returned:
     /// the caller is `SYS_SHUTDOWN`, which has nowhere to put a `Result` and
     /// nothing left to try, and one mount refusing must not stop the rest from
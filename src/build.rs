@@ -1141,6 +1141,56 @@ pub fn designate_for_format(path: &Path, len: u64) {
         .unwrap_or_else(|e| panic!("stamp {}: {e}", path.display()));
 }
 
+/// [`create_sparse`] for a disk the kernel has to unlock first: an encrypted
+/// volume header in block 0, one key slot opened by `passphrase` typed on a
+/// US keyboard, and the designation stamp written *through the cipher* at
+/// block 0 of the volume.
+///
+/// So the guest meets the same three-way probe it meets on a plain scratch
+/// disk, one layer up: once the passphrase is in, the volume is designated and
+/// the kernel formats it, and until then the file holds nothing but a header
+/// and noise. The stamp's block count is the volume's and not the file's,
+/// which is what `designated` compares it to.
+///
+/// The master key and every salt come from `/dev/urandom`. The key is not
+/// returned: a test that wants to read the volume back unlocks it the way the
+/// kernel does.
+pub fn create_encrypted(path: &Path, len: u64, passphrase: &str) {
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use toyos_crypt::{Cipher, Header, MasterKey, Params, Passphrase, DEFAULT_DATA_OFFSET};
+
+    let mut random = [0u8; 64 + 32 + 32];
+    fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut random))
+        .unwrap_or_else(|e| panic!("read /dev/urandom: {e}"));
+    let (key, salts) = random.split_at(64);
+    let (digest_salt, slot_salt) = salts.split_at(32);
+    let master = MasterKey::from_bytes(key.try_into().unwrap());
+    let pass = Passphrase::from_us_text(passphrase)
+        .unwrap_or_else(|| panic!("{passphrase:?} cannot be typed on a US keyboard"));
+
+    let mut header = Header::new(&master, digest_salt.try_into().unwrap(), DEFAULT_DATA_OFFSET);
+    header
+        .add_slot(&master, &pass, Params::DEFAULT, slot_salt.try_into().unwrap())
+        .unwrap_or_else(|e| panic!("key slot for {}: {e}", path.display()));
+
+    let mut stamp = [0u8; 4096];
+    stamp[..bcachefs::DESIGNATION_MAGIC.len()].copy_from_slice(&bcachefs::DESIGNATION_MAGIC);
+    let blocks = (len / 4096 - DEFAULT_DATA_OFFSET).to_le_bytes();
+    let at = bcachefs::DESIGNATION_BLOCKS_OFFSET;
+    stamp[at..at + blocks.len()].copy_from_slice(&blocks);
+    Cipher::new(&master).encrypt(0, &mut stamp);
+
+    let mut file = fs::File::create(path)
+        .unwrap_or_else(|e| panic!("create {}: {e}", path.display()));
+    file.set_len(len)
+        .unwrap_or_else(|e| panic!("set_len {} on {}: {e}", len, path.display()));
+    file.write_all(&header.to_block())
+        .and_then(|()| file.seek(SeekFrom::Start(DEFAULT_DATA_OFFSET * 4096)))
+        .and_then(|_| file.write_all(&stamp))
+        .unwrap_or_else(|e| panic!("write the encrypted header to {}: {e}", path.display()));
+}
+
 /// One part of a boot image, built once per key for the life of this process.
 ///
 /// A `cargo test` run boots ~76 machines, and most of those boots ask for an
@@ -2005,9 +2055,11 @@ mod tests {
 
     /// A device class init can mint exactly one claim for, so two programs
     /// naming the same class is a config init cannot satisfy — a runtime
-    /// first-come race today.
+    /// first-come race today. `power` starts out claimed: init holds it itself,
+    /// after `[boot] start`, so a row naming it would win the claim and leave
+    /// the power button deciding nothing.
     fn one_claimant_per_device(cfg: &SystemConfig) -> Result<(), String> {
-        let mut seen: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut seen: BTreeMap<&str, &str> = BTreeMap::from([("power", "init")]);
         for (name, prog) in &cfg.programs {
             for d in &prog.devices {
                 if let Some(prev) = seen.insert(d, name) {
@@ -2031,6 +2083,9 @@ mod tests {
         )
         .unwrap();
         assert!(one_claimant_per_device(&bad).is_err());
+        let power: SystemConfig =
+            toml::from_str("init = []\n[programs.a]\ndevices = [\"power\"]\n").unwrap();
+        assert!(one_claimant_per_device(&power).is_err());
     }
 
     /// A class name the ABI does not know renders fine and leaves init with a
@@ -479,9 +479,176 @@ fn create_gpt_disk(esp_volume: Vec<u8>, log_volume: Vec<u8>, log_guid: uuid::Uui
     final_bytes[esp_start..esp_start + esp_volume.len()].copy_from_slice(&esp_volume);
     final_bytes[log_start..log_start + log_volume.len()].copy_from_slice(&log_volume);
 
+    write_iso9660(&mut final_bytes, esp_start, esp_volume.len());
+
     final_bytes
 }
 
+/// ISO 9660's logical block, which is also the sector a CD-ROM drive reports.
+const ISO_BLOCK: usize = 2048;
+
+/// Where ISO 9660's volume descriptors start: sector 16, byte 32 KiB.
+const ISO_DESCRIPTORS: usize = 16;
+
+/// Where each ISO 9660 structure goes, in 2048-byte blocks: the descriptor
+/// set, then the boot catalog, the root directory and the two path tables.
+const ISO_BOOT_RECORD: usize = ISO_DESCRIPTORS + 1;
+const ISO_TERMINATOR: usize = ISO_DESCRIPTORS + 2;
+const ISO_CATALOG: usize = ISO_DESCRIPTORS + 3;
+const ISO_ROOT: usize = ISO_DESCRIPTORS + 4;
+const ISO_PATH_TABLE_L: usize = ISO_DESCRIPTORS + 5;
+const ISO_PATH_TABLE_M: usize = ISO_DESCRIPTORS + 6;
+
+/// Give a finished GPT disk an ISO 9660 face, so one image boots both from a
+/// USB stick and from a CD-ROM drive.
+///
+/// **The gap is free, and checked to be.** The GPT ends at byte 17,408 — the
+/// protective MBR, the header and 128 entries of 128 bytes — and the ESP starts
+/// at [`PARTITION_ALIGN`]. Everything ISO 9660 needs fits between: a primary
+/// descriptor at 32 KiB, an El Torito boot record, a terminator, the catalog, a
+/// root directory and its path tables, seven blocks in all. A firmware reading
+/// the disk as a disk never looks there, and one reading it as a disc never
+/// looks at the GPT.
+///
+/// **What the disc boots is the ESP itself.** The catalog's one entry is a
+/// no-emulation UEFI image starting at the ESP's first byte, and the root
+/// directory names the same bytes `ESP.IMG`, as `xorriso` does, so the volume
+/// accounts for them. The ESP is larger than the catalog's 16-bit count of
+/// 512-byte sectors can say, so the count is zero; EDK2 and every other UEFI
+/// then take the image to run to the end of the volume, and the FAT32 inside
+/// knows its own size.
+///
+/// The volume is the whole disk. What a machine booted this way does not get is
+/// `/boot` and `/log`: a CD-ROM device path has no GPT partition GUID for the
+/// bootloader to hand over, so the kernel runs from the initrd and keeps its
+/// log in memory, as it does on any boot with no boot partition.
+fn write_iso9660(disk: &mut [u8], esp_start: usize, esp_len: usize) {
+    assert!(
+        (ISO_PATH_TABLE_M + 1) * ISO_BLOCK <= esp_start,
+        "the ISO 9660 structures run to {} and the ESP starts at {esp_start}",
+        (ISO_PATH_TABLE_M + 1) * ISO_BLOCK
+    );
+    assert!(
+        disk[ISO_DESCRIPTORS * ISO_BLOCK..esp_start].iter().all(|&b| b == 0),
+        "something already lives between the GPT and the ESP"
+    );
+    assert_eq!(esp_start % ISO_BLOCK, 0, "the ESP is not on a {ISO_BLOCK}-byte block");
+    assert_eq!(disk.len() % ISO_BLOCK, 0, "the disk is not whole {ISO_BLOCK}-byte blocks");
+    let blocks = u32::try_from(disk.len() / ISO_BLOCK).expect("an image past 8 TiB");
+    let esp_block = (esp_start / ISO_BLOCK) as u32;
+    let esp_len = u32::try_from(esp_len).expect("an ESP past 4 GiB");
+
+    // Primary volume descriptor. Every text field is d-characters padded with
+    // spaces, and every date "not specified": sixteen ASCII zeros and a zero
+    // offset, which is how ECMA-119 spells it.
+    let pvd = &mut disk[iso_block(ISO_DESCRIPTORS)];
+    descriptor_header(pvd, 1);
+    pvd[8..72].fill(b' ');
+    pvd[40..45].copy_from_slice(b"TOYOS");
+    both_endian_u32(&mut pvd[80..88], blocks);
+    both_endian_u16(&mut pvd[120..124], 1);
+    both_endian_u16(&mut pvd[124..128], 1);
+    both_endian_u16(&mut pvd[128..132], ISO_BLOCK as u16);
+    both_endian_u32(&mut pvd[132..140], PATH_TABLE_BYTES as u32);
+    pvd[140..144].copy_from_slice(&(ISO_PATH_TABLE_L as u32).to_le_bytes());
+    pvd[148..152].copy_from_slice(&(ISO_PATH_TABLE_M as u32).to_be_bytes());
+    directory_record(&mut pvd[156..190], ISO_ROOT as u32, ISO_BLOCK as u32, true, &[0]);
+    pvd[190..813].fill(b' ');
+    for date in [813, 830, 847, 864] {
+        pvd[date..date + 16].fill(b'0');
+    }
+    pvd[881] = 1;
+
+    // El Torito boot record: the specification's name and where the catalog is.
+    let record = &mut disk[iso_block(ISO_BOOT_RECORD)];
+    descriptor_header(record, 0);
+    record[7..7 + 23].copy_from_slice(b"EL TORITO SPECIFICATION");
+    record[71..75].copy_from_slice(&(ISO_CATALOG as u32).to_le_bytes());
+
+    descriptor_header(&mut disk[iso_block(ISO_TERMINATOR)], 255);
+
+    // The catalog: a validation entry for UEFI whose sixteen-bit words sum to
+    // zero, then the initial entry, bootable and not emulating anything.
+    let catalog = &mut disk[iso_block(ISO_CATALOG)];
+    catalog[0] = 0x01;
+    catalog[1] = 0xEF;
+    catalog[30] = 0x55;
+    catalog[31] = 0xAA;
+    let sum = catalog[..32]
+        .as_chunks::<2>()
+        .0
+        .iter()
+        .fold(0u16, |s, w| s.wrapping_add(u16::from_le_bytes(*w)));
+    catalog[28..30].copy_from_slice(&0u16.wrapping_sub(sum).to_le_bytes());
+    catalog[32] = 0x88;
+    let sectors = u16::try_from(esp_len / 512).unwrap_or(0);
+    catalog[38..40].copy_from_slice(&sectors.to_le_bytes());
+    catalog[40..44].copy_from_slice(&esp_block.to_le_bytes());
+
+    // The root directory: itself, its parent (itself again), and the two files,
+    // in the sorted order ECMA-119 requires.
+    let root = &mut disk[iso_block(ISO_ROOT)];
+    let mut at = 0;
+    for (extent, len, dir, name) in [
+        (ISO_ROOT as u32, ISO_BLOCK as u32, true, &b"\0"[..]),
+        (ISO_ROOT as u32, ISO_BLOCK as u32, true, &b"\x01"[..]),
+        (ISO_CATALOG as u32, ISO_BLOCK as u32, false, &b"BOOT.CAT;1"[..]),
+        (esp_block, esp_len, false, &b"ESP.IMG;1"[..]),
+    ] {
+        at += directory_record(&mut root[at..], extent, len, dir, name);
+    }
+
+    // Path tables: one entry each, the root's, whose parent is itself.
+    for (n, extent) in [
+        (ISO_PATH_TABLE_L, (ISO_ROOT as u32).to_le_bytes()),
+        (ISO_PATH_TABLE_M, (ISO_ROOT as u32).to_be_bytes()),
+    ] {
+        let table = &mut disk[iso_block(n)];
+        table[0] = 1;
+        table[2..6].copy_from_slice(&extent);
+        let parent = if n == ISO_PATH_TABLE_L { 1u16.to_le_bytes() } else { 1u16.to_be_bytes() };
+        table[6..8].copy_from_slice(&parent);
+    }
+}
+
+/// Block `n` of the volume as a byte range.
+fn iso_block(n: usize) -> std::ops::Range<usize> {
+    n * ISO_BLOCK..(n + 1) * ISO_BLOCK
+}
+
+/// One path table entry with a one-byte name and its pad byte.
+const PATH_TABLE_BYTES: usize = 10;
+
+fn descriptor_header(sector: &mut [u8], kind: u8) {
+    sector[0] = kind;
+    sector[1..6].copy_from_slice(b"CD001");
+    sector[6] = 1;
+}
+
+fn both_endian_u16(field: &mut [u8], v: u16) {
+    field[..2].copy_from_slice(&v.to_le_bytes());
+    field[2..4].copy_from_slice(&v.to_be_bytes());
+}
+
+fn both_endian_u32(field: &mut [u8], v: u32) {
+    field[..4].copy_from_slice(&v.to_le_bytes());
+    field[4..8].copy_from_slice(&v.to_be_bytes());
+}
+
+/// Write one directory record at the start of `out` and return its length.
+/// The recording date is left zero, which every reader takes as unknown.
+fn directory_record(out: &mut [u8], extent: u32, len: u32, dir: bool, name: &[u8]) -> usize {
+    let bytes = 33 + name.len() + (name.len() + 1) % 2;
+    out[0] = bytes as u8;
+    both_endian_u32(&mut out[2..10], extent);
+    both_endian_u32(&mut out[10..18], len);
+    out[25] = if dir { 2 } else { 0 };
+    both_endian_u16(&mut out[28..32], 1);
+    out[32] = name.len() as u8;
+    out[33..33 + name.len()].copy_from_slice(name);
+    bytes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -595,4 +762,84 @@ mod tests {
 
         let _ = std::fs::remove_dir_all(&dir);
     }
+
+    /// Every boot image is also a disc, and the disc boots the same ESP the
+    /// disk does.
+    ///
+    /// Read back with `toyos-iso9660` and `toyos-gpt`, the kernel's own readers,
+    /// over the bytes `dd` or `-cdrom` would see: the catalog's one entry is a
+    /// UEFI image at the first byte of the GPT's ESP, the root directory names
+    /// those bytes, and they mount as the FAT32 volume with the bootloader on it.
+    /// A writer that put the ISO 9660 structures anywhere the GPT or the ESP
+    /// uses would fail one side or the other here.
+    #[test]
+    fn every_boot_image_is_also_a_disc_that_boots_the_esp() {
+        let disk = create_boot_image(b"kernel", b"bootloader", b"initrd", "");
+
+        let mut disc = toyos_iso9660::Iso9660::mount(DiscIo(&disk)).expect("mount the image as a disc");
+        assert_eq!(disc.volume().bytes(), disk.len() as u64, "the disc is not the whole image");
+        assert_eq!(disc.volume().label_str(), Some("TOYOS"));
+        let entries = disc.boot_entries().expect("read the boot catalog");
+        let [entry] = entries.as_slice() else { panic!("one boot entry expected, the catalog has {entries:?}") };
+        assert_eq!(entry.platform, toyos_iso9660::PLATFORM_EFI);
+        assert!(entry.bootable);
+        assert_eq!(entry.media, toyos_iso9660::Media::NoEmulation);
+        let image = disc.metadata("esp.img").expect("the root directory names the ESP");
+        assert_eq!(image.extent, entry.load_rba, "ESP.IMG and the catalog name different bytes");
+
+        let mut esps = [toyos_gpt::Partition {
+            index: 0,
+            type_guid: toyos_gpt::Guid::EFI_SYSTEM,
+            unique_guid: toyos_gpt::Guid::EFI_SYSTEM,
+            first_lba: 0,
+            last_lba: 0,
+        }; 1];
+        let listed = toyos_gpt::partitions_of_type(&mut DiscIo(&disk), toyos_gpt::Guid::EFI_SYSTEM, &mut esps)
+            .expect("the GPT still reads");
+        assert_eq!(listed.matching, 1);
+        let start = esps[0].first_lba as usize * 512;
+        assert_eq!(start, entry.load_rba as usize * 2048, "the disc boots something other than the ESP");
+        assert_eq!(image.len, esps[0].lba_count() * 512);
+
+        let mut esp = disk[start..start + image.len as usize].to_vec();
+        let mut fs = Fat32::mount(VolumeIo(&mut esp)).expect("mount the ESP the disc boots");
+        assert!(fs.exists("EFI/BOOT/BOOTx64.EFI").expect("look up the bootloader"));
+    }
+
+    /// An image in memory as both a disc and a disk.
+    struct DiscIo<'a>(&'a [u8]);
+
+    impl toyos_iso9660::BlockAccess for DiscIo<'_> {
+        fn capacity(&self) -> u64 {
+            self.0.len() as u64
+        }
+
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), toyos_iso9660::IoError> {
+            let at = usize::try_from(offset).map_err(|_| toyos_iso9660::IoError)?;
+            let src = self.0.get(at..at + buf.len()).ok_or(toyos_iso9660::IoError)?;
+            buf.copy_from_slice(src);
+            Ok(())
+        }
+    }
+
+    impl toyos_gpt::Sectors for DiscIo<'_> {
+        fn lba_bytes(&self) -> u32 {
+            512
+        }
+
+        fn lba_count(&self) -> u64 {
+            (self.0.len() / 512) as u64
+        }
+
+        fn read_lba(&mut self, lba: u64, out: &mut [u8]) -> bool {
+            let at = lba as usize * 512;
+            match self.0.get(at..at + out.len()) {
+                Some(src) => {
+                    out.copy_from_slice(src);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
 }
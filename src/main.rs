@@ -257,16 +257,20 @@ fn parse_repeated(args: &[String], flag: &str) -> Vec<String> {
     values
 }
 
-/// `--gop` swaps virtio-gpu for a firmware framebuffer; `--metal-sim` goes
-/// further and removes every virtio device, which is what the target laptop
-/// actually presents. `--metal-sim --mute` additionally takes the 16550 away.
+/// `--gop` swaps virtio-gpu for a firmware framebuffer; `--std-vga` for a
+/// standard VGA whose mode the kernel can change; `--metal-sim` goes further
+/// than `--gop` and removes every virtio device, which is what the target
+/// laptop actually presents. `--metal-sim --mute` additionally takes the 16550
+/// away.
 fn parse_profile(args: &[String]) -> qemu::Profile {
     let gop = args.iter().any(|a| a == "--gop");
+    let std_vga = args.iter().any(|a| a == "--std-vga");
     let metal = args.iter().any(|a| a == "--metal-sim");
-    match (gop, metal) {
-        (_, true) => qemu::Profile::Metal,
-        (true, false) => qemu::Profile::Gop,
-        (false, false) => qemu::Profile::Virtio,
+    match (gop, std_vga, metal) {
+        (_, _, true) => qemu::Profile::Metal,
+        (true, _, false) => qemu::Profile::Gop,
+        (false, true, false) => qemu::Profile::StdVga,
+        (false, false, false) => qemu::Profile::Virtio,
     }
 }
 
@@ -46,24 +46,48 @@
 //! a wrong playback clock are indistinguishable to a listener, and doom's
 //! real-time factor is what separates them — RTF near 1.0 with playback still
 //! slow is the clock, RTF well below 1.0 is synthesis not keeping up.
+//!
+//! # The host share
+//!
+//! Every profile with virtio devices shares [`SHARE`] — the userland build's
+//! output — as a 9P mount tagged `host`, so the guest sees
+//! `/host/toyos/<program>`. A program rebuilt with `cargo build` in
+//! `userland/` on the host is run from there with no reboot: the initrd copy
+//! is what boots, and the share is what a developer iterates on. It is
+//! read-write, and the guest's writes land in the host's tree.
+//! `security_model=none`, because the files are the developer's own and the
+//! guest has no users to map.
 
 use std::fs::File;
 use std::path::PathBuf;
 use std::process::Command;
 
+/// The host directory the guest mounts at `/host`, relative to the repository
+/// root, which `main` has made the working directory. The directory above
+/// [`toyos_build::build::PROFILE`]'s, so a program built under any profile is
+/// reachable.
+const SHARE: &str = "userland/target/x86_64-unknown-toyos";
+
 /// The hardware shape QEMU presents to the guest.
 ///
-/// Not a display setting: each variant is a whole machine. `Virtio` and `Gop`
-/// differ only in how the framebuffer arrives, but `Metal` removes every
-/// virtio device, which changes the console, the network, and audio too.
+/// Not a display setting: each variant is a whole machine. `Virtio`, `Gop`
+/// and `StdVga` differ only in how the framebuffer arrives, but `Metal`
+/// removes every virtio device, which changes the console, the network, and
+/// audio too.
 #[derive(Clone, Copy, PartialEq)]
 pub enum Profile {
     /// virtio-gpu for the display, virtio-console for the console, plus
-    /// virtio-net and virtio-sound. What every boot in this tree used to be.
+    /// virtio-net, virtio-sound and the host share. What every boot in this
+    /// tree used to be.
     Virtio,
-    /// `-vga std`, so firmware publishes a GOP and the kernel takes the
-    /// laptop's display path. Every virtio device is still present.
+    /// A standard VGA with its mode registers hidden, so firmware publishes
+    /// a GOP and the kernel takes the laptop's display path. Every virtio
+    /// device is still present.
     Gop,
+    /// `-vga std` as QEMU builds it: the same framebuffer, but with the DISPI
+    /// registers the kernel's Bochs driver changes the mode through. Every
+    /// virtio device is still present.
+    StdVga,
     /// M1 metal-sim: the shape a ThinkPad T14 presents. Firmware framebuffer,
     /// i8042 (q35 gives it for free), NVMe, xHCI with the boot stick on it,
     /// and nothing else — no virtio device anywhere and no USB HID. The 16550
@@ -78,9 +102,10 @@ pub enum Profile {
 /// Profile::Metal` did the opposite of: it handed anything that was not
 /// literally Metal the whole virtio block and a USB keyboard.
 struct Shape {
-    /// The display device. `Virtio` is the only profile with no firmware GOP.
-    virtio_gpu: bool,
-    /// virtio-net, virtio-sound, and the console on virtio-serial.
+    /// The display device.
+    display: Display,
+    /// virtio-net, virtio-sound, the console on virtio-serial, and the host
+    /// share over virtio-9p.
     virtio: bool,
     /// A USB HID on the xHCI. The T14's keyboard is PS/2 and its touchpad
     /// I2C-HID; it has no USB HID at all.
@@ -94,12 +119,24 @@ struct Shape {
     iommu: bool,
 }
 
+/// How a profile's framebuffer arrives.
+#[derive(Clone, Copy)]
+enum Display {
+    /// virtio-gpu, and no firmware GOP at all.
+    Virtio,
+    /// Firmware's GOP and nothing the kernel could change the mode through.
+    Firmware,
+    /// A standard VGA the kernel drives through its DISPI registers.
+    StdVga,
+}
+
 impl Profile {
     fn shape(self) -> Shape {
         match self {
-            Self::Virtio => Shape { virtio_gpu: true, virtio: true, usb_hid: true, iommu: true },
-            Self::Gop => Shape { virtio_gpu: false, virtio: true, usb_hid: true, iommu: true },
-            Self::Metal => Shape { virtio_gpu: false, virtio: false, usb_hid: false, iommu: true },
+            Self::Virtio => Shape { display: Display::Virtio, virtio: true, usb_hid: true, iommu: true },
+            Self::Gop => Shape { display: Display::Firmware, virtio: true, usb_hid: true, iommu: true },
+            Self::StdVga => Shape { display: Display::StdVga, virtio: true, usb_hid: true, iommu: true },
+            Self::Metal => Shape { display: Display::Firmware, virtio: false, usb_hid: false, iommu: true },
         }
     }
 }
@@ -179,17 +216,25 @@ pub fn launch(opts: &Options) {
             .arg("usb-tablet,bus=xhci.0");
     }
 
-    // `-vga std` is the display path a real laptop takes: firmware publishes a
+    // `Firmware` is the display path a real laptop takes: firmware publishes a
     // linear framebuffer, the kernel maps it, and there is no virtio device to
-    // fall back to. It is also the only config in which the on-screen panic
-    // console renders anything.
-    if shape.virtio_gpu {
-        qemu.arg("-vga")
-            .arg("none")
-            .arg("-device")
-            .arg("virtio-gpu-pci,xres=1280,yres=720");
-    } else {
-        qemu.arg("-vga").arg("std");
+    // fall back to. A standard VGA without its MMIO BAR is that: firmware sets
+    // its mode through the I/O ports, and the kernel's Bochs driver, which
+    // wants the BAR, leaves it to GOP. Those two are also the only configs in
+    // which the on-screen panic console renders anything.
+    match shape.display {
+        Display::Virtio => {
+            qemu.arg("-vga")
+                .arg("none")
+                .arg("-device")
+                .arg("virtio-gpu-pci,xres=1280,yres=720");
+        }
+        Display::Firmware => {
+            qemu.arg("-vga").arg("none").arg("-device").arg("VGA,mmio=off");
+        }
+        Display::StdVga => {
+            qemu.arg("-vga").arg("std");
+        }
     }
 
     if shape.virtio {
@@ -214,6 +259,16 @@ pub fn launch(opts: &Options) {
         qemu.arg("-device")
             .arg("virtio-sound-pci,audiodev=audio0,streams=1");
 
+        // QEMU refuses `-virtfs` on a path that does not exist. The image
+        // build before this has made it on every ordinary run; this is for a
+        // `target/` deleted in between, which should not cost the boot.
+        if let Err(e) = std::fs::create_dir_all(SHARE) {
+            eprintln!("Host share: cannot create {SHARE}: {e}; booting without it");
+        } else {
+            qemu.arg("-virtfs")
+                .arg(format!("local,path={SHARE},mount_tag=host,security_model=none"));
+        }
+
         // Console wiring: virtio-console on stdio is the primary I/O channel
         // (the kernel switches to it once virtio-console init completes —
         // see drivers/virtio_console.rs). UART stays on a file so early-boot
@@ -287,6 +287,68 @@ fn sum_field(serial: &str, key: &str) -> u32 {
         .sum()
 }
 
+/// The controller with MSI off ([`Profile::HdaIntx`]), so every completion
+/// arrives on its INTA pin.
+///
+/// **What a wrong route looks like is silence, not a crash**: the I/O APIC
+/// entry points at a GSI the pin does not drive, no completion ever arrives,
+/// soundd never finishes a stats window and the tone client never exits. So the
+/// verdict is soundd's windows and wakes, with the client exiting 0 — and the
+/// kernel's own line saying the pin came from `_PRT`, because on q35 the
+/// Interrupt Line register BIOS writes names the same GSI, and a kernel that
+/// ignored `_PRT` would pass everything else here.
+///
+/// Parallel and without a capture: whether the tone is clean is `hda_tone`'s
+/// question, and the interrupt mechanism is not an input to it.
+pub fn hda_intx(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions { profile: Profile::HdaIntx, ..Default::default() },
+    );
+    let mut log = Serial::boot(&qemu);
+    log.push(&qemu.drain_serial(Duration::from_millis(500)));
+
+    let result = qemu.run_test("test_rs_audio_tone", Duration::from_secs(30));
+    if let Some(err) = &result.error {
+        return Err(format!("{err}\n{}\n{}", result.stdout, log.text()));
+    }
+    log.push(&result.serial);
+    log.push(&qemu.drain_serial(Duration::from_millis(500)));
+    if result.exit_code != Some(0) {
+        return Err(format!(
+            "the tone client exited {:?} with completions on the pin:\n{}\n{}",
+            result.exit_code,
+            result.stdout,
+            log.text()
+        ));
+    }
+    log.must_not_say("offers neither MSI-X nor MSI")?;
+    let pin = log.must_say(": INTA -> GSI ")?;
+    if !pin.contains("from _PRT") {
+        return Err(format!("the pin was routed without _PRT: {pin:?}"));
+    }
+    eprintln!("  [hda] {}", pin.trim());
+    log.must_say("bound, statests=")?;
+
+    let counters = crate::common::audio::parse_soundd_counters(log.text())?;
+    if counters.windows == 0 || counters.wakes == 0 {
+        return Err(format!(
+            "soundd reported {} stats windows and {} wakes: no completion reached it\n{}",
+            counters.windows,
+            counters.wakes,
+            log.text()
+        ));
+    }
+    eprintln!("  [hda] {} windows, {} wakes on the pin", counters.windows, counters.wakes);
+    log.must_be_clean()
+}
+
 /// Two controllers, both with a codec that answers.
 ///
 /// The kernel binds neither and names both. A first-match bind would go green
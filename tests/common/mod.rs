@@ -33,10 +33,14 @@ pub mod stats;
 #[allow(dead_code)]
 pub mod storage;
 #[allow(dead_code)]
+pub mod suspend;
+#[allow(dead_code)]
 pub mod toybox;
 #[allow(dead_code)]
 pub mod usb;
 #[allow(dead_code)]
+pub mod vsock;
+#[allow(dead_code)]
 pub mod volumes;
 #[allow(dead_code)]
 pub mod wallclock;
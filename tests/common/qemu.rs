@@ -813,6 +813,32 @@ pub enum Profile {
     /// driver does without it — and what it used to do was panic the kernel,
     /// on a machine whose other devices were all fine.
     VirtioNetNoMsix,
+    /// [`Profile::Headless`] with an e1000e as its only NIC.
+    ///
+    /// NIC *model* is a shape dimension the way a disk's sector size is: netd
+    /// reads one `NicInfo` and one DMA page and is meant not to care which
+    /// driver filled them in, and a suite whose every NIC was virtio-net had
+    /// no way to show that it does not. The 82574 is the part QEMU picks when
+    /// nobody asks, and the commonest wired NIC a real machine of this class
+    /// has.
+    E1000e,
+    /// [`Profile::Headless`] whose only NIC is QEMU's `usb-net`.
+    ///
+    /// The NIC shape where the device is not on PCI and not there from reset:
+    /// it is found by USB enumeration, reached through xHCI transfer rings,
+    /// and can be pulled out from under netd. QEMU's model offers RNDIS as
+    /// its first configuration and CDC-ECM as its second, so it also covers a
+    /// device whose first configuration is the wrong one.
+    UsbNet,
+    /// A headless machine whose only console is QEMU's `usb-serial`, an FTDI
+    /// FT232 on the xHCI.
+    ///
+    /// The one console that is not there from reset: the kernel logs to a
+    /// ring nobody drains until enumeration finds the adapter, and then has to
+    /// replay the boot onto it. There is no 16550 and no virtio device, so a
+    /// test that reads its ready marker and its result here has read them off
+    /// the USB path and nothing else.
+    UsbSerial,
     Gop,
     /// M1 metal-sim: GOP, NVMe, xHCI with the boot stick on it, i8042 from
     /// q35, and nothing else -- no virtio device and no USB HID. This is the
@@ -912,12 +938,23 @@ pub enum Profile {
     ///
     /// Two and not three, though the pool would refuse either way.
     /// `nec-usb-xhci` offers four SuperSpeed ports and QEMU puts the fifth
-    /// device behind an auto-created hub, which this driver walks past — so a
-    /// third data disk is not one the guest refuses, it is one the guest never
-    /// sees, and a count that included it would be measuring QEMU's port
-    /// allocation. Measured: `class=0x9 vendor=0409 product=55aa` on port 8 at
-    /// full speed, with `no HID boot interface found, skipping`.
+    /// device behind an auto-created hub — so a third data disk is not one the
+    /// boot scan refuses, it is one the hub's port enumerates after the scan,
+    /// and a count that included it would be measuring QEMU's port allocation
+    /// and the hot-plug path's timing. Measured: `class=0x9 vendor=0409
+    /// product=55aa` on port 8 at full speed.
     UsbDiskCrowd,
+    /// [`Profile::UsbDisk`] with the data disk behind a `usb-uas` bridge
+    /// instead of a `usb-storage` stick.
+    ///
+    /// The same bytes on the same bus, reached through four bulk pipes and
+    /// tagged commands instead of Bulk-Only's three-phase wrapper. QEMU's
+    /// bridge offers UAS as its only interface and advertises streams on its
+    /// SuperSpeed companions, so this is the profile where the driver has to
+    /// build a stream context array and the one where a Bulk-Only fallback
+    /// would show up as a device it did not bind at all. The boot stick stays
+    /// Bulk-Only, so one boot runs both transports side by side.
+    UsbDiskUas,
     /// metal-sim with a device that attaches at **full speed**.
     ///
     /// Speed is a shape dimension and it was one no profile varied: every USB
@@ -1000,6 +1037,24 @@ pub enum Profile {
     /// one is hot-plugged, so a keystroke that arrives can only have come
     /// through the device that was added after the boot.
     MetalHotplug,
+    /// A keyboard behind a `usb-hub` on root port 2, and nothing else that
+    /// types.
+    ///
+    /// Both ports are named so the topology is the argv's and not QEMU's
+    /// allocation: the keyboard is at `2.1`, which is Route String 0x1 below
+    /// root port 2, and a device hot-plugged at `2.3` is 0x3 below the same
+    /// port. QEMU's hub is a full-speed one, so there is no TT on the path —
+    /// a full-speed hub has none, and nothing above it is high-speed. The
+    /// tablet on the root port is what the input client's closing click goes
+    /// through; it is absolute, so a relative move can only have come from a
+    /// mouse plugged in behind the hub. The gate boots it with the i8042 off,
+    /// for the reason [`Profile::MetalHotplug`]'s does.
+    UsbHub,
+    /// A virtio keyboard, mouse and tablet, and no other input device: no USB
+    /// HID and no virtio block, so every keystroke and every pointer sample the
+    /// guest sees came through a virtio-input event queue. The gate boots it
+    /// with the i8042 off, for the reason [`Profile::MetalHotplug`]'s does.
+    VirtioInput,
     /// metal-sim with no IOMMU at all, so firmware publishes no `DMAR`.
     ///
     /// Presence of the unit is the shape dimension, and it is the one QEMU
@@ -1041,6 +1096,18 @@ pub enum Profile {
     /// that is the driver's work. The negative control on the whole bind path
     /// — a first-match kernel would go green on every other HDA test.
     HdaTwoLive,
+    /// [`Profile::Hda`] with the controller's MSI turned off, and it has no
+    /// MSI-X: the one way it can say a period completed is its INTA pin, on
+    /// the GSI q35's `_PRT` routes it to through a link device.
+    HdaIntx,
+    /// [`Profile::Hda`]'s machine with QEMU's `usb-audio` where the HDA
+    /// controller was: a UAC1 DAC on the xHCI, and no other sound card.
+    ///
+    /// The same reasoning as [`Profile::Hda`]'s: the console, the NIC and the
+    /// disks are [`Profile::Headless`]'s, so a difference in the capture is a
+    /// difference in the audio path. The DAC writes into the same wav backend,
+    /// so the tone is read back off the device by the same instrument.
+    UsbAudio,
 }
 
 /// The vIOMMU a profile puts on the machine.
@@ -1074,6 +1141,9 @@ pub const IOMMU_DEFAULT: Iommu = Iommu { aw_bits: 48, intremap: true };
 /// of them — so the default `p2=4,p3=4` takes **four** devices, two short of the
 /// crowded set rather than one.
 const XHCI_DEFAULT: &str = "nec-usb-xhci,id=xhci";
+/// The FTDI adapter [`Profile::UsbSerial`] talks through, on the chardev the
+/// console arm of `qemu_command` puts on stdio.
+const USB_CONSOLE: &str = "usb-serial,chardev=usbcon,bus=xhci.0";
 /// Eight attachable ports, which is `MAX(p2=8, p3=4)`, over twelve port
 /// registers: 1-4 the SuperSpeed view, 5-12 the USB2 view. Measured on QEMU
 /// 11.0.2 against the kernel's own lines — `max_ports=12`, and the six devices
@@ -1110,6 +1180,12 @@ const XHCI_NO_IRQ_SECOND: &str = "nec-usb-xhci,id=xhci1,msix=off,msi=off";
 /// walks.
 const HDA_ONE: &[&str] = &["intel-hda,id=hda0", "hda-output,bus=hda0.0,cad=0,audiodev=hdaaud"];
 
+/// [`HDA_ONE`] with no message-signalled interrupt. `msi=off` drops the
+/// capability from config space rather than leaving it disabled, so the driver
+/// finds no MSI to fall back to, as on a part that never had one.
+const HDA_INTX: &[&str] =
+    &["intel-hda,id=hda0,msi=off", "hda-output,bus=hda0.0,cad=0,audiodev=hdaaud"];
+
 /// Two controllers, each with a codec that answers.
 ///
 /// The state the kernel refuses: it can tell which links are alive and cannot
@@ -1121,6 +1197,10 @@ const HDA_TWO_LIVE: &[&str] = &[
     "hda-output,bus=hda1.0,cad=0,audiodev=hdaaud",
 ];
 
+/// The DAC [`Profile::UsbAudio`] plays through, on the wav backend the HDA
+/// profiles use. Full speed only, as QEMU models it: one packet a millisecond.
+const USB_DAC: &str = "usb-audio,audiodev=hdaaud,bus=xhci.0";
+
 /// Whether a machine has the virtio block, and whether its NIC can raise an
 /// interrupt.
 ///
@@ -1149,6 +1229,15 @@ enum Virtio {
     /// and it keeps the console, the NIC and the timing of the recorded audio
     /// configs so the two arms differ in the sound card and not in the machine.
     WithoutSound,
+    /// The whole block with the NIC an Intel 82574 instead of virtio-net: the
+    /// `e1000e` QEMU models, on the same user-mode backend. Everything else is
+    /// [`Virtio::Present`]'s, so the machine differs in the NIC and nothing
+    /// else.
+    NicE1000e,
+    /// The whole block with no PCI NIC at all: the user-mode backend is there
+    /// and the only thing on it is the `usb-net` in the profile's USB list, so
+    /// the machine's NIC is a CDC-ECM adapter on the xHCI.
+    NicUsb,
 }
 
 impl Virtio {
@@ -1157,7 +1246,7 @@ impl Virtio {
     }
 
     fn sound(self) -> bool {
-        matches!(self, Self::Present | Self::NicWithoutMsix)
+        matches!(self, Self::Present | Self::NicWithoutMsix | Self::NicE1000e | Self::NicUsb)
     }
 }
 
@@ -1166,7 +1255,8 @@ impl Virtio {
 /// Profile::Metal` did the opposite of: it handed anything that was not
 /// literally Metal the whole virtio block, a USB keyboard and a console.
 struct Shape {
-    /// `-vga` mode. "none" leaves firmware with no GOP to publish.
+    /// `-vga` mode. "none" leaves firmware with no GOP to publish; "std" is
+    /// a framebuffer only GOP can drive, its mode registers hidden.
     vga: &'static str,
     /// Video memory, which is what decides the panel: OVMF offers every mode
     /// that fits in it and the bootloader takes the one with the most pixels.
@@ -1215,8 +1305,7 @@ struct Shape {
     usb_disks: &'static [UsbDisk],
     /// Every Intel HDA controller on the machine and the codecs behind each,
     /// as `-device` arguments in the order QEMU is to create them. Empty is
-    /// what every profile but [`Profile::Hda`] and [`Profile::HdaTwoLive`]
-    /// declares, and it is the machine this kernel has always booted: audio
+    /// what every profile but the three HDA ones declares, and it is the machine this kernel has always booted: audio
     /// through virtio-sound or through nothing at all.
     ///
     /// Presence of a class-0403 *function* is the shape dimension, and it is
@@ -1224,6 +1313,11 @@ struct Shape {
     /// H0's question (b), and what the codec
     /// arguments in this list decide per controller.
     hda: &'static [&'static str],
+    /// Every virtio-input device, as `-device` arguments. Empty everywhere but
+    /// [`Profile::VirtioInput`], and for the reason `usb` has to be stated: QEMU
+    /// routes injected input to one handler per event class, so a tablet here
+    /// is a tablet the USB one stops receiving from.
+    input: &'static [&'static str],
     /// The unit that decodes this machine's DMA, or its absence. Stated per
     /// profile because absence is a shape and because the unit's own
     /// capabilities are what the kernel reads at boot.
@@ -1250,6 +1344,10 @@ pub struct UsbDisk {
     /// pool block of a failed bind to the next disk is only observable when the
     /// failure is first.
     before_boot_stick: bool,
+    /// Put it behind a `usb-uas` bridge with a `scsi-hd` as LUN 0, rather than
+    /// a `usb-storage` stick. The disk is the same; the transport the driver
+    /// has to speak to reach it is not.
+    uas: bool,
 }
 
 impl UsbDisk {
@@ -1260,6 +1358,7 @@ impl UsbDisk {
         lba_bytes: 512,
         readonly: false,
         before_boot_stick: false,
+        uas: false,
     };
     /// A 3 TB external disk, which this driver has to refuse by name rather
     /// than serve the first 2 TiB of.
@@ -1330,6 +1429,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::VirtioNetNoMsix => Shape {
@@ -1343,6 +1443,49 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
+                iommu: Some(IOMMU_DEFAULT),
+            },
+            Self::E1000e => Shape {
+                vga: "none",
+                vgamem_mb: None,
+                virtio: Virtio::NicE1000e,
+                xhci: &[XHCI_DEFAULT],
+                storage_bus: "xhci.0",
+                usb: &["usb-kbd,bus=xhci.0"],
+                nvme_bytes: NVME_SMALL,
+                nvme_lba_bytes: NVME_LBA_DEFAULT,
+                usb_disks: &[],
+                hda: &[],
+                input: &[],
+                iommu: Some(IOMMU_DEFAULT),
+            },
+            Self::UsbNet => Shape {
+                vga: "none",
+                vgamem_mb: None,
+                virtio: Virtio::NicUsb,
+                xhci: &[XHCI_DEFAULT],
+                storage_bus: "xhci.0",
+                usb: &["usb-kbd,bus=xhci.0", "usb-net,netdev=net0,bus=xhci.0,id=usbnet0"],
+                nvme_bytes: NVME_SMALL,
+                nvme_lba_bytes: NVME_LBA_DEFAULT,
+                usb_disks: &[],
+                hda: &[],
+                input: &[],
+                iommu: Some(IOMMU_DEFAULT),
+            },
+            Self::UsbSerial => Shape {
+                vga: "none",
+                vgamem_mb: None,
+                virtio: Virtio::Absent,
+                xhci: &[XHCI_DEFAULT],
+                storage_bus: "xhci.0",
+                usb: &[USB_CONSOLE],
+                nvme_bytes: NVME_SMALL,
+                nvme_lba_bytes: NVME_LBA_DEFAULT,
+                usb_disks: &[],
+                hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::Gop => Shape {
@@ -1356,6 +1499,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::Diskless => Shape {
@@ -1371,6 +1515,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::Metal => Shape {
@@ -1391,6 +1536,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             // Two keyboards and two pointers, because the collision this
@@ -1414,6 +1560,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::MetalDisk => Shape {
@@ -1427,6 +1574,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::NvmeWideSector => Shape {
@@ -1440,6 +1588,7 @@ impl Profile {
                 nvme_lba_bytes: 8192,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::UsbDisk => Shape {
@@ -1453,6 +1602,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[UsbDisk::DATA],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::UsbDisk4k => Shape {
@@ -1466,6 +1616,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[UsbDisk { lba_bytes: 4096, ..UsbDisk::DATA }],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::UsbDiskHuge => Shape {
@@ -1479,6 +1630,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[UsbDisk::HUGE],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::UsbDiskRefusedFirst => Shape {
@@ -1492,6 +1644,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[UsbDisk { before_boot_stick: true, ..UsbDisk::HUGE }],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::UsbDiskReadOnly => Shape {
@@ -1505,6 +1658,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[UsbDisk { readonly: true, ..UsbDisk::DATA }],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::UsbDiskCrowd => Shape {
@@ -1518,6 +1672,21 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[UsbDisk::DATA, UsbDisk::DATA],
                 hda: &[],
+                input: &[],
+                iommu: Some(IOMMU_DEFAULT),
+            },
+            Self::UsbDiskUas => Shape {
+                vga: "std",
+                vgamem_mb: None,
+                virtio: Virtio::Absent,
+                xhci: &[XHCI_DEFAULT],
+                storage_bus: "xhci.0",
+                usb: &[],
+                nvme_bytes: NVME_SMALL,
+                nvme_lba_bytes: NVME_LBA_DEFAULT,
+                usb_disks: &[UsbDisk { uas: true, ..UsbDisk::DATA }],
+                hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             // The first controller carries nothing at all — not even the boot
@@ -1537,6 +1706,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::MetalXhciSecond => Shape {
@@ -1550,6 +1720,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             // A hub ahead of the second controller's HID, so that controller's
@@ -1575,6 +1746,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             // The boot stick's controller is the one with no interrupt
@@ -1597,6 +1769,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             // Boot stick on the good controller, HID on the crippled one. A
@@ -1613,6 +1786,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(IOMMU_DEFAULT),
             },
             Self::MetalHotplug => Shape {
@@ -1626,6 +1800,39 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
+                iommu: Some(IOMMU_DEFAULT),
+            },
+            Self::UsbHub => Shape {
+                vga: "std",
+                vgamem_mb: None,
+                virtio: Virtio::Absent,
+                xhci: &[XHCI_DEFAULT],
+                storage_bus: "xhci.0",
+                usb: &[
+                    "usb-tablet,bus=xhci.0",
+                    "usb-hub,bus=xhci.0,port=2",
+                    "usb-kbd,bus=xhci.0,port=2.1",
+                ],
+                nvme_bytes: NVME_SMALL,
+                nvme_lba_bytes: NVME_LBA_DEFAULT,
+                usb_disks: &[],
+                hda: &[],
+                input: &[],
+                iommu: Some(IOMMU_DEFAULT),
+            },
+            Self::VirtioInput => Shape {
+                vga: "std",
+                vgamem_mb: None,
+                virtio: Virtio::Absent,
+                xhci: &[XHCI_DEFAULT],
+                storage_bus: "xhci.0",
+                usb: &[],
+                nvme_bytes: NVME_SMALL,
+                nvme_lba_bytes: NVME_LBA_DEFAULT,
+                usb_disks: &[],
+                hda: &[],
+                input: &["virtio-keyboard-pci", "virtio-mouse-pci", "virtio-tablet-pci"],
                 iommu: Some(IOMMU_DEFAULT),
             },
             // The three below are metal-sim with one field of the unit moved,
@@ -1642,6 +1849,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: None,
             },
             Self::IommuNarrow => Shape {
@@ -1655,6 +1863,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(Iommu { aw_bits: 39, ..IOMMU_DEFAULT }),
             },
             Self::IommuNoIntremap => Shape {
@@ -1668,6 +1877,7 @@ impl Profile {
                 nvme_lba_bytes: NVME_LBA_DEFAULT,
                 usb_disks: &[],
                 hda: &[],
+                input: &[],
                 iommu: Some(Iommu { intremap: false, ..IOMMU_DEFAULT }),
             },
             Self::Hda => Shape {
@@ -1680,6 +1890,16 @@ impl Profile {
                 hda: HDA_TWO_LIVE,
                 ..Self::Headless.shape()
             },
+            Self::HdaIntx => Shape {
+                virtio: Virtio::WithoutSound,
+                hda: HDA_INTX,
+                ..Self::Headless.shape()
+            },
+            Self::UsbAudio => Shape {
+                virtio: Virtio::WithoutSound,
+                usb: &["usb-kbd,bus=xhci.0", USB_DAC],
+                ..Self::Headless.shape()
+            },
         }
     }
 
@@ -1781,6 +2001,54 @@ pub struct BootOptions {
     /// command line entirely, so every existing profile assertion sees the argv
     /// it always saw.
     pub rtc_base: Option<&'static str>,
+    /// Present the boot image as a CD-ROM instead of a USB stick: an `ide-cd`
+    /// on q35's own AHCI controller, read-only, with the image's ISO 9660 face
+    /// and El Torito catalog as the thing firmware boots.
+    ///
+    /// The same bytes a stick gets, because every image `src/image.rs` writes
+    /// is both. What changes is which face firmware reads — and that the guest
+    /// has no USB stick at all, so it boots from the initrd with no `/boot`
+    /// and no `/log` and says so. The kernel reads the disc itself over ATAPI,
+    /// and mounts its ISO 9660 face as `cdrom0`.
+    pub cdrom: bool,
+    /// Which controller the profile's scratch disk sits behind. The same
+    /// backing file and the same size whichever it is — see [`HomeBus`].
+    pub home_bus: HomeBus,
+    /// Share this host directory with the guest over virtio-9p, tagged `host`
+    /// — the share `cargo run` gives the userland build directory, which the
+    /// guest mounts at `/host`. `None` leaves the argument off, so every
+    /// existing profile assertion sees the argv it always saw.
+    pub share: Option<PathBuf>,
+    /// Attach a `vhost-vsock-pci` with this guest context id, so a host
+    /// process can reach the guest over `AF_VSOCK`. The id is the host's
+    /// namespace, not the guest's: two machines on one host need two.
+    pub vsock: Option<u32>,
+    /// Let the guest suspend to RAM. q35's ICH9 LPC advertises no `\_S3_` by
+    /// default, so without this the guest's `suspend` is refused before it
+    /// touches anything — which is the answer every other boot should give.
+    /// Off leaves the argument off, as `vsock` does.
+    pub s3: bool,
+}
+
+/// The controller a profile's scratch disk is attached through.
+///
+/// A shape and not a profile because nothing else about the machine moves:
+/// what is under test is that `/home` comes up on whichever one the machine
+/// has. Ignored by a profile with no scratch disk at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HomeBus {
+    /// An NVMe controller with one namespace, whose logical block size the
+    /// profile states.
+    #[default]
+    Nvme,
+    /// virtio-blk, on the device `-drive if=virtio` builds — transitional, on
+    /// the root bus. It is also the cheaper disk — one virtqueue, no admin
+    /// queue and no namespace — for a test that only needs somewhere to write.
+    Virtio,
+    /// An `ide-hd` on the second port of the ich9-ahci q35 builds in. The
+    /// second and not the first because the first is where
+    /// [`BootOptions::cdrom`] puts its disc.
+    Ahci,
 }
 
 /// The in-guest test runner's startup marker.
@@ -1809,6 +2077,11 @@ impl Default for BootOptions {
             boot_image: None,
             usb_images: Vec::new(),
             rtc_base: None,
+            cdrom: false,
+            home_bus: HomeBus::Nvme,
+            share: None,
+            vsock: None,
+            s3: false,
         }
     }
 }
@@ -2907,6 +3180,19 @@ impl QmpInput {
         self.send(&body);
     }
 
+    /// One absolute packet: where a tablet says the pointer is, in QEMU's
+    /// 0..=32767 on both axes. Only a device that takes absolute input receives
+    /// it, so on a machine with one tablet this is that tablet speaking.
+    pub fn tablet(&mut self, x: i32, y: i32) {
+        let body: Vec<String> = [("x", x), ("y", y)]
+            .iter()
+            .map(|(axis, value)| {
+                format!("{{\"type\":\"abs\",\"data\":{{\"axis\":\"{axis}\",\"value\":{value}}}}}")
+            })
+            .collect();
+        self.send(&body);
+    }
+
     /// One pointer packet: relative motion and/or a button transition.
     pub fn mouse(&mut self, dx: i32, dy: i32, button: Option<(&str, bool)>) {
         let mut body: Vec<String> = Vec::new();
@@ -3062,6 +3348,18 @@ fn qemu_command(
     if let Some(base) = options.rtc_base {
         qemu.arg("-rtc").arg(format!("base={base}"));
     }
+    if let Some(share) = &options.share {
+        qemu.arg("-virtfs").arg(format!(
+            "local,path={},mount_tag=host,security_model=none",
+            share.display()
+        ));
+    }
+    if let Some(cid) = options.vsock {
+        qemu.arg("-device").arg(format!("vhost-vsock-pci,guest-cid={cid}"));
+    }
+    if options.s3 {
+        qemu.arg("-global").arg("ICH9-LPC.disable_s3=0");
+    }
 
     qemu.arg("-machine")
         .arg(&machine)
@@ -3083,8 +3381,9 @@ fn qemu_command(
         ))
         .arg("-drive")
         .arg(format!(
-            "if=none,id=stick,format=raw,file={}",
-            boot_image.display()
+            "if=none,id=stick,format=raw,file={}{}",
+            boot_image.display(),
+            if options.cdrom { ",media=cdrom,readonly=on" } else { "" }
         ));
 
     // Ahead of every other `-device`: QEMU gives a PCI function the bypassing
@@ -3113,7 +3412,7 @@ fn qemu_command(
         .iter()
         .enumerate()
         .map(|(i, disk)| {
-            vec![
+            let mut args = vec![
                 "-drive".to_string(),
                 format!(
                     "if=none,id={},format=raw,file={}{}",
@@ -3122,15 +3421,34 @@ fn qemu_command(
                     if disk.readonly { ",readonly=on" } else { "" }
                 ),
                 "-device".to_string(),
-                format!(
+            ];
+            // A UAS bridge is a USB device with a SCSI bus behind it, and the
+            // disk is a second device on that bus rather than a property of
+            // the first. The bridge takes the device id, so a QMP unplug still
+            // names the thing on the USB bus.
+            if disk.uas {
+                args.extend([
+                    format!("usb-uas,bus={},id={}", shape.storage_bus, usb_device_id(i)),
+                    "-device".to_string(),
+                    format!(
+                        "scsi-hd,bus={2}.0,scsi-id=0,lun=0,drive={1},logical_block_size={0},\
+                         physical_block_size={0}",
+                        disk.lba_bytes,
+                        usb_drive_id(i),
+                        usb_device_id(i),
+                    ),
+                ]);
+            } else {
+                args.push(format!(
                     "usb-storage,bus={1},drive={2},id={3},logical_block_size={0},\
                      physical_block_size={0}",
                     disk.lba_bytes,
                     shape.storage_bus,
                     usb_drive_id(i),
                     usb_device_id(i),
-                ),
-            ]
+                ));
+            }
+            args
         })
         .collect();
     for (disk, args) in shape.usb_disks.iter().zip(&data_sticks) {
@@ -3139,11 +3457,16 @@ fn qemu_command(
         }
     }
 
+    // `ide.0` is the first port of the ich9-ahci q35 builds in, which
+    // `-nodefaults` leaves in place: it is part of the machine, not a default
+    // device.
+    let boot_device = if options.cdrom {
+        format!("ide-cd,bus=ide.0,drive=stick,id={BOOT_STICK_ID},bootindex=0")
+    } else {
+        format!("usb-storage,bus={},drive=stick,id={BOOT_STICK_ID},bootindex=0", shape.storage_bus)
+    };
     qemu.arg("-device")
-        .arg(format!(
-            "usb-storage,bus={},drive=stick,id={BOOT_STICK_ID},bootindex=0",
-            shape.storage_bus
-        ))
+        .arg(boot_device)
         .arg("-vga")
         .arg(shape.vga)
         .arg("-display")
@@ -3152,6 +3475,14 @@ fn qemu_command(
     if let Some(mb) = shape.vgamem_mb {
         qemu.arg("-global").arg(format!("VGA.vgamem_mb={mb}"));
     }
+    // Without the DISPI register BAR the kernel's Bochs driver declines the
+    // device and GOP takes it, which is the path every `std` profile is here
+    // to exercise: the laptop has no Bochs display, only firmware's
+    // framebuffer, and the panic-console and memory-type tests read GOP's
+    // lines. Firmware sets its mode through the I/O ports instead.
+    if shape.vga == "std" {
+        qemu.arg("-global").arg("VGA.mmio=off");
+    }
 
     // Controller and namespace as two devices rather than QEMU's implicit
     // one, so the logical block size is something a profile states instead of
@@ -3159,7 +3490,20 @@ fn qemu_command(
     // the guest no controller at all, rather than an empty one. A machine
     // with no NVMe is a shape, and the argv is the only place it is visible:
     // no console line and no screendump can see a device that is absent.
-    if shape.nvme_bytes != 0 {
+    if shape.nvme_bytes != 0 && options.home_bus != HomeBus::Nvme {
+        let device = match options.home_bus {
+            HomeBus::Virtio => "virtio-blk-pci,drive=nvme0,id=home0",
+            HomeBus::Ahci => "ide-hd,bus=ide.1,drive=nvme0,id=home0",
+            HomeBus::Nvme => unreachable!(),
+        };
+        qemu.arg("-drive")
+            .arg(format!(
+                "if=none,id=nvme0,format=raw,file={}",
+                nvme_image.display()
+            ))
+            .arg("-device")
+            .arg(device);
+    } else if shape.nvme_bytes != 0 {
         qemu.arg("-drive")
             .arg(format!(
                 "if=none,id=nvme0,format=raw,file={}",
@@ -3187,8 +3531,11 @@ fn qemu_command(
     for dev in shape.usb {
         qemu.arg("-device").arg(*dev);
     }
+    for dev in shape.input {
+        qemu.arg("-device").arg(*dev);
+    }
 
-    if !shape.hda.is_empty() {
+    if !shape.hda.is_empty() || shape.usb.contains(&USB_DAC) {
         // The same wav backend virtio-sound gets, so gate A's ground truth —
         // what the *device* received — transfers with no new instrument. A boot
         // that plays nothing leaves an empty file and costs nothing.
@@ -3215,13 +3562,17 @@ fn qemu_command(
     }
 
     if shape.virtio.present() {
-        qemu.arg("-netdev")
-            .arg("user,id=net0")
-            .arg("-device")
-            .arg(match shape.virtio {
-                Virtio::NicWithoutMsix => "virtio-net-pci-non-transitional,netdev=net0,vectors=0",
-                _ => "virtio-net-pci-non-transitional,netdev=net0",
-            });
+        qemu.arg("-netdev").arg("user,id=net0");
+        let nic = match shape.virtio {
+            Virtio::NicWithoutMsix => Some("virtio-net-pci-non-transitional,netdev=net0,vectors=0"),
+            Virtio::NicE1000e => Some("e1000e,netdev=net0"),
+            // The adapter is in `shape.usb`, on the xHCI.
+            Virtio::NicUsb => None,
+            _ => Some("virtio-net-pci-non-transitional,netdev=net0"),
+        };
+        if let Some(nic) = nic {
+            qemu.arg("-device").arg(nic);
+        }
         if shape.virtio.sound() {
             // virtio-sound records everything the guest plays into a per-boot
             // wav for glitch analysis; timer-period matches the interactive
@@ -3246,6 +3597,14 @@ fn qemu_command(
             .arg("virtio-serial-pci-non-transitional,id=virtio-serial0,max_ports=1")
             .arg("-device")
             .arg("virtconsole,chardev=cs0,id=console0");
+    } else if shape.usb.contains(&USB_CONSOLE) {
+        // The adapter is the console: stdio goes to it, and the 16550 is
+        // taken away so that nothing the harness reads could have come out
+        // of the UART instead.
+        qemu.arg("-chardev")
+            .arg("stdio,id=usbcon,signal=off")
+            .arg("-serial")
+            .arg("none");
     } else if options.mute {
         qemu.arg("-serial").arg("none");
     } else {
@@ -175,3 +175,333 @@ pub fn first_difference(before: &[u8], after: &[u8]) -> Option<String> {
         None => format!("the region changed length: {} -> {}", before.len(), after.len()),
     })
 }
+
+/// Boot against an encrypted disk, answer the passphrase prompt wrong and
+/// then right, and prove from the host that what reached the file is
+/// ciphertext.
+///
+/// The prompt is the ready marker, so the keys are typed into a kernel that is
+/// provably polling for them and not into firmware. The wrong passphrase is
+/// the negative half: a kernel that took any passphrase, or none, would format
+/// a volume the host then fails to open with the right one.
+///
+/// `Profile::Metal` for the reason `keyboard_claim_close_spares_stdin` gives:
+/// its i8042 is the only keyboard, so an injected key cannot land on a USB
+/// HID device the storage phase has not brought up.
+pub fn encrypted_home(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    const BYTES: u64 = 128 * 1024 * 1024;
+    const PASSPHRASE: &str = "Correct horse 42";
+    const PROMPT: &str = "crypt: type the passphrase for /home";
+    let image = super::lane::dir().join("encrypted-disk.img");
+    toyos_build::build::create_encrypted(&image, BYTES, PASSPHRASE);
+
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions {
+            profile: qemu::Profile::Metal,
+            qmp: true,
+            ready_marker: PROMPT,
+            nvme_image: Some(image.clone()),
+            ..Default::default()
+        },
+    );
+    let mut log = qemu.boot_log().to_string();
+    let mut keys = qemu::QmpInput::open(qemu.qmp_socket());
+
+    // The derivation runs between the two attempts, so the second is typed
+    // only once the first has been judged: keys typed into a kernel that is
+    // not polling sit in the controller and would be read as one passphrase.
+    keys.type_text("correct horse 42\n");
+    log.push_str(&qemu.drain_until(Duration::from_secs(60), |l| l.contains(PROMPT)));
+    if !log.contains("crypt: wrong passphrase (1 of 3)") {
+        return Err(format!("a passphrase differing only in Shift was not refused\n{log}"));
+    }
+    keys.type_text(&format!("{PASSPHRASE}\n"));
+    log.push_str(&qemu.drain_until(Duration::from_secs(180), |l| l.contains(qemu::DEFAULT_READY)));
+
+    for bad in ["PANIC:", "panicked at"] {
+        if log.contains(bad) {
+            return Err(format!("{bad:?} on an encrypted disk\n{log}"));
+        }
+    }
+    for want in ["crypt: unlocked with key slot 0", "formatting it", qemu::DEFAULT_READY] {
+        if !log.contains(want) {
+            return Err(format!("the boot never said {want:?}\n{log}"));
+        }
+    }
+    if log.contains("/home is a tmpfs") {
+        return Err(format!("the volume unlocked and /home is still a tmpfs\n{log}"));
+    }
+
+    // Shut down for the same reason `foreign_disk_untouched` does: the format
+    // is in the page cache until the shutdown sync moves it to the device.
+    writeln!(qemu.stdin_mut(), "run shutdown").expect("write to QEMU stdin");
+    qemu.flush_stdin();
+    let tail = qemu.drain_serial(Duration::from_secs(20));
+    for bad in ["PANIC:", "panicked at"] {
+        if tail.contains(bad) {
+            return Err(format!("{bad:?} during shutdown\n{tail}"));
+        }
+    }
+    drop(qemu);
+
+    let disk = std::fs::read(&image).map_err(|e| format!("read {}: {e}", image.display()))?;
+    let _ = std::fs::remove_file(&image);
+    encrypted_disk_holds_the_volume(&disk, PASSPHRASE)
+}
+
+/// The host's half of `encrypted_home`: no bcachefs superblock anywhere on the
+/// raw disk, and one at block 0 of the volume once the passphrase is applied.
+fn encrypted_disk_holds_the_volume(disk: &[u8], passphrase: &str) -> Result<(), String> {
+    use toyos_crypt::{Cipher, Header, Passphrase, DATA_UNIT, HEADER_BYTES};
+
+    let (blocks, _) = disk.as_chunks::<DATA_UNIT>();
+    if let Some(at) = blocks.iter().position(|b| b.starts_with(b"BCFS")) {
+        return Err(format!("device block {at} is a plaintext bcachefs superblock"));
+    }
+    let head: &[u8; HEADER_BYTES] = disk.first_chunk().ok_or("the disk is empty")?;
+    let header = Header::parse(head, blocks.len() as u64)
+        .map_err(|e| format!("the header the guest left does not parse: {e}"))?;
+    let pass = Passphrase::from_us_text(passphrase).ok_or("untypeable passphrase")?;
+    let (master, _) = header.unlock(&pass).map_err(|e| format!("unlock on the host: {e}"))?;
+    let mut first = blocks[header.data_offset() as usize];
+    Cipher::new(&master).decrypt(0, &mut first);
+    if !first.starts_with(b"BCFS") {
+        return Err("block 0 of the volume does not decrypt to a bcachefs superblock".to_string());
+    }
+    Ok(())
+}
+
+/// Boot with the scratch disk on virtio-blk and no NVMe controller, and prove
+/// `/home` comes up on it.
+///
+/// The disk is the one every ordinary boot formats, attached the way
+/// `-drive if=virtio` attaches it, so the verdict is the same one an NVMe boot
+/// would earn: the volume mounts or is formatted, `/home` is not a tmpfs, and
+/// after a clean shutdown the host finds a bcachefs superblock at block 0 of
+/// the backing file. That last half is what sees the writes — a driver whose
+/// writes or flushes went nowhere boots just as far.
+pub fn virtio_blk_home(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions {
+            profile: qemu::Profile::Metal,
+            home_bus: qemu::HomeBus::Virtio,
+            ..Default::default()
+        },
+    );
+    let log = qemu.boot_log().to_string();
+    for bad in ["PANIC:", "panicked at"] {
+        if log.contains(bad) {
+            return Err(format!("{bad:?} on a virtio disk\n{log}"));
+        }
+    }
+    for want in ["virtio-blk: disk 0 id=8", "/home goes on virtio disk 0", qemu::DEFAULT_READY] {
+        if !log.contains(want) {
+            return Err(format!("the boot never said {want:?}\n{log}"));
+        }
+    }
+    if !log.contains("formatting it") && !log.contains("mounted the ToyOS volume") {
+        return Err(format!("the virtio disk was neither mounted nor formatted\n{log}"));
+    }
+    if log.contains("/home is a tmpfs") {
+        return Err(format!("a virtio disk was found and /home is still a tmpfs\n{log}"));
+    }
+
+    // Shut down for the reason `foreign_disk_untouched` gives: only the
+    // shutdown sync moves a format from the page cache to the device.
+    writeln!(qemu.stdin_mut(), "run shutdown").expect("write to QEMU stdin");
+    qemu.flush_stdin();
+    let tail = qemu.drain_serial(Duration::from_secs(20));
+    for bad in ["PANIC:", "panicked at", "virtio-blk: disk 0 is offline"] {
+        if tail.contains(bad) {
+            return Err(format!("{bad:?} during shutdown\n{tail}"));
+        }
+    }
+    let image = qemu.nvme_image().to_path_buf();
+    drop(qemu);
+
+    let mut head = [0u8; 4];
+    std::io::Read::read_exact(
+        &mut std::fs::File::open(&image).map_err(|e| format!("open {}: {e}", image.display()))?,
+        &mut head,
+    )
+    .map_err(|e| format!("read {}: {e}", image.display()))?;
+    if &head != b"BCFS" {
+        return Err(format!("block 0 of {} is not a bcachefs superblock", image.display()));
+    }
+    Ok(())
+}
+
+/// `/home` on NVMe with four CPUs: the driver asks for a queue pair per CPU,
+/// routes each pair's interrupt to its CPU, and a format plus a shutdown sync
+/// on that disk go through without a completion the driver did not expect.
+pub fn nvme_queues(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions { profile: qemu::Profile::Metal, smp: 4, ..Default::default() },
+    );
+    let log = qemu.boot_log().to_string();
+    for bad in ["PANIC:", "panicked at"] {
+        if log.contains(bad) {
+            return Err(format!("{bad:?} on a four-CPU NVMe machine\n{log}"));
+        }
+    }
+    for want in [
+        "NVMe: 4 I/O queue pairs for 4 CPUs",
+        "4 with an interrupt routed to its CPU",
+        "NVMe: block device id=1",
+        qemu::DEFAULT_READY,
+    ] {
+        if !log.contains(want) {
+            return Err(format!("the boot never said {want:?}\n{log}"));
+        }
+    }
+
+    writeln!(qemu.stdin_mut(), "run shutdown").expect("write to QEMU stdin");
+    qemu.flush_stdin();
+    let tail = qemu.drain_serial(Duration::from_secs(20));
+    for bad in ["PANIC:", "panicked at", "controller is offline", "which is not in flight"] {
+        if tail.contains(bad) || log.contains(bad) {
+            return Err(format!("{bad:?} with a queue per CPU\n{log}{tail}"));
+        }
+    }
+    Ok(())
+}
+
+/// `fstrim /home` on an NVMe `/home`, which QEMU's controller says can
+/// deallocate: the command has to reach the disk as Dataset Management and
+/// come back answered, or the applet's count is a number the disk never saw.
+pub fn nvme_discard(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions { profile: qemu::Profile::Metal, ..Default::default() },
+    );
+    let log = qemu.boot_log().to_string();
+    for want in ["NVMe: block device id=1", ", deallocate", qemu::DEFAULT_READY] {
+        if !log.contains(want) {
+            return Err(format!("the boot never said {want:?}\n{log}"));
+        }
+    }
+
+    let result = qemu.run_test("fstrim /home", Duration::from_secs(60));
+    if let Some(err) = &result.error {
+        return Err(format!("`fstrim /home` never finished: {err}\n{}", result.serial));
+    }
+    if result.exit_code != Some(0) {
+        return Err(format!(
+            "`fstrim /home` exited {:?}\n{}{}",
+            result.exit_code, result.stdout, result.serial
+        ));
+    }
+    if !result.stdout.contains("/home: ") || !result.stdout.contains("trimmed") {
+        return Err(format!("`fstrim /home` did not say what it trimmed:\n{}", result.stdout));
+    }
+    if !result.serial.contains("bcachefs: trimmed") {
+        return Err(format!("the kernel never logged the trim\n{}", result.serial));
+    }
+
+    writeln!(qemu.stdin_mut(), "run shutdown").expect("write to QEMU stdin");
+    qemu.flush_stdin();
+    let tail = qemu.drain_serial(Duration::from_secs(20));
+    for bad in ["PANIC:", "panicked at", "controller is offline", "deallocate of"] {
+        if tail.contains(bad) || log.contains(bad) || result.serial.contains(bad) {
+            return Err(format!("{bad:?} around a trim\n{log}{}{tail}", result.serial));
+        }
+    }
+    Ok(())
+}
+
+/// A host directory shared with `-virtfs`, read, rewritten on the host, read
+/// again, and written back — the dev loop, on one boot.
+///
+/// The second read is the claim the share exists for: a file the host changed
+/// after the guest first read it is seen by the next `open`, with no reboot and
+/// nothing cached in the way. The copy is asserted on the host's file, because
+/// the guest's account of what reached the host is the thing in question.
+pub fn virtio_9p_share(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    const FIRST: &str = "9p-first-4d1c";
+    const SECOND: &str = "9p-second-a07e";
+    let share = super::lane::dir().join("share");
+    let _ = std::fs::remove_dir_all(&share);
+    std::fs::create_dir_all(&share).map_err(|e| format!("create {}: {e}", share.display()))?;
+    let hello = share.join("hello.txt");
+    std::fs::write(&hello, FIRST).map_err(|e| format!("write {}: {e}", hello.display()))?;
+
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions { share: Some(share.clone()), ..Default::default() },
+    );
+    let log = qemu.boot_log().to_string();
+    for want in ["virtio-9p: share 0 tagged \"host\"", "host: mounted read-write", qemu::DEFAULT_READY] {
+        if !log.contains(want) {
+            return Err(format!("the boot never said {want:?}\n{log}"));
+        }
+    }
+
+    let cat = |qemu: &mut QemuInstance, want: &str| {
+        let result = qemu.run_test("cat /host/hello.txt", Duration::from_secs(30));
+        if result.exit_code != Some(0) || !result.stdout.contains(want) {
+            return Err(format!(
+                "`cat /host/hello.txt` exited {:?} without printing {want:?}\n{}{}",
+                result.exit_code, result.stdout, result.serial
+            ));
+        }
+        Ok(result.serial)
+    };
+    let mut serial = cat(&mut qemu, FIRST)?;
+    std::fs::write(&hello, SECOND).map_err(|e| format!("rewrite {}: {e}", hello.display()))?;
+    serial += &cat(&mut qemu, SECOND)?;
+
+    let result = qemu.run_test("cp /host/hello.txt /host/out/copy.txt", Duration::from_secs(30));
+    serial += &result.serial;
+    if result.exit_code != Some(0) {
+        return Err(format!("`cp` onto the share exited {:?}\n{}{serial}", result.exit_code, result.stdout));
+    }
+    let copy = share.join("out/copy.txt");
+    match std::fs::read_to_string(&copy) {
+        Ok(text) if text == SECOND => {}
+        Ok(text) => return Err(format!("{} holds {text:?}, want {SECOND:?}", copy.display())),
+        Err(e) => return Err(format!("the guest's copy never reached the host: {}: {e}", copy.display())),
+    }
+
+    writeln!(qemu.stdin_mut(), "run shutdown").expect("write to QEMU stdin");
+    qemu.flush_stdin();
+    let tail = qemu.drain_serial(Duration::from_secs(20));
+    for bad in ["PANIC:", "panicked at", "NOT BOUND", "is offline", "serving zeros"] {
+        if tail.contains(bad) || log.contains(bad) || serial.contains(bad) {
+            return Err(format!("{bad:?} around the host share\n{log}{serial}{tail}"));
+        }
+    }
+    Ok(())
+}
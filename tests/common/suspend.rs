@@ -0,0 +1,192 @@
+//! Suspend to RAM and back, on the machine the laptop is: metal-sim with S3
+//! switched on in the ICH9 LPC, woken by the monitor's `system_wakeup`.
+//!
+//! **The wake is a reset of every device**, which is what makes QEMU a fair
+//! stand-in for the power loss: `system_wakeup` resets the machine's devices
+//! and re-enters firmware, which jumps through the FACS waking vector instead
+//! of booting. So each verdict below is a driver that reinitialised its device
+//! from nothing, not one that found it as it left it.
+//!
+//! The boot stick is the disk asserted on, because it is the one whose loss
+//! would be silent: `/boot` is read after the wake, and a stick that came back
+//! under another number would leave that read failing with no line about USB.
+//!
+//! The two sound cards get a machine each, and the verdict is a tone played
+//! after the wake and read back off the device. A codec or a stream that was
+//! not put back plays nothing, and nothing in the guest would say so.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::qemu::{self, BootOptions, QemuInstance};
+use super::serial::Serial;
+
+/// How long the monitor is watched for the guest to report itself suspended,
+/// from the kernel's last line before the `PM1_CNT` write. Liveness: the write
+/// is a few instructions after that line.
+const ENTER: Duration = Duration::from_secs(10);
+
+pub fn s3_resume(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let options =
+        BootOptions { profile: qemu::Profile::Metal, qmp: true, s3: true, ..Default::default() };
+    let argv = qemu::profile_argv(&options);
+    if !argv.iter().any(|a| a == "ICH9-LPC.disable_s3=0") {
+        return Err(format!("the S3 boot did not switch S3 on in the LPC: {argv:?}"));
+    }
+    let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+    let boot = Serial::boot(&qemu);
+    boot.must_be_clean()?;
+
+    let mut log = sleep_and_wake(&mut qemu)?;
+    // Each of these is a driver that found its device reset and rebuilt it.
+    log.must_say("I/O queue pairs back after the sleep")?;
+    log.must_say("iommu: unit0 translating again")?;
+    log.must_say("back on slot")?;
+    log.must_not_say("is offline")?;
+
+    // Userland was thawed — the runner answered — and the stick reads.
+    let listing = qemu.run_test("ls /boot", Duration::from_secs(60));
+    log.push(&listing.serial);
+    if listing.exit_code != Some(0) {
+        return Err(format!(
+            "`ls /boot` after the wake exited {:?}:\n{}\nserial:\n{}",
+            listing.exit_code, listing.stdout, listing.serial
+        ));
+    }
+    log.must_be_clean()?;
+    eprintln!("  [s3] suspended, woken, and the boot stick read back after the wake");
+    Ok(())
+}
+
+/// Run `suspend`, wake the guest once the monitor reports it asleep, and
+/// answer what it printed on the way down and back up.
+///
+/// What every S3 test asserts is checked here: the guest slept, `suspend`
+/// reported the wake, the log is clean, and no driver said it lost its device.
+fn sleep_and_wake(qemu: &mut QemuInstance) -> Result<Serial, String> {
+    // Woken from the line before the write, once the monitor agrees the guest
+    // is asleep: `system_wakeup` on a running guest is refused, so waking on
+    // the line alone would race the last few instructions of the way down.
+    let mut asleep = None;
+    let result =
+        qemu.run_test_hooked("suspend", Duration::from_secs(120), "S3: suspending", |socket| {
+            let mut monitor = qemu::QmpMonitor::open(socket);
+            let deadline = Instant::now() + ENTER;
+            while Instant::now() < deadline {
+                let status = monitor.human("info status");
+                if status.contains("suspended") {
+                    asleep = Some(status);
+                    monitor.human("system_wakeup");
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        });
+    let mut log = Serial::named("the suspend and the resume", result.serial.as_str());
+    if let Some(err) = &result.error {
+        return Err(format!("`suspend` never finished: {err}\nserial:\n{}", result.serial));
+    }
+    let Some(status) = asleep else {
+        return Err(format!(
+            "the guest said `S3: suspending` and the monitor never reported it suspended \
+             within {} s\nserial:\n{}",
+            ENTER.as_secs(),
+            result.serial
+        ));
+    };
+    eprintln!("  [s3] monitor: {}", status.trim());
+    if result.exit_code != Some(0) || !result.stdout.contains("suspend: resumed") {
+        return Err(format!(
+            "`suspend` exited {:?}:\n{}\nserial:\n{}",
+            result.exit_code, result.stdout, result.serial
+        ));
+    }
+    log.must_be_clean()?;
+    log.must_say("S3: resumed")?;
+    log.must_not_say("did not come back from the sleep")?;
+    Ok(log)
+}
+
+/// The default machine through a sleep: the whole virtio block, its sound card
+/// included, with S3 switched on in the LPC exactly as [`s3_resume`] has it.
+///
+/// The device comes back with empty queues and soundd configures its stream
+/// again. So the verdict is soundd reading the reset, then a tone it plays
+/// after the wake.
+pub fn s3_resume_virtio_sound(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let back = "virtio-sound: back after the sleep";
+    tone_after_wake(test_config, c_bins, rust_bins, qemu::Profile::Headless, "virtio-sound", back)
+}
+
+/// [`s3_resume_virtio_sound`] on the machine with an Intel HDA controller in
+/// the sound card's place.
+///
+/// The codec comes back at its defaults, with no stream routed to the pin. A
+/// tone that reaches the capture is therefore soundd's path sent again by the
+/// kernel, and not a path configured at boot that survived.
+pub fn s3_resume_hda(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    tone_after_wake(test_config, c_bins, rust_bins, qemu::Profile::Hda, "hda", "codec verbs sent again")
+}
+
+/// Sleep and wake `profile`, require `card`'s own line about the wake, `back`,
+/// and play a tone.
+fn tone_after_wake(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+    profile: qemu::Profile,
+    card: &str,
+    back: &str,
+) -> Result<(), String> {
+    let options = BootOptions { profile, qmp: true, s3: true, ..Default::default() };
+    let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+    let boot = Serial::boot(&qemu);
+    boot.must_be_clean()?;
+
+    let mut log = sleep_and_wake(&mut qemu)?;
+    log.must_say(back)?;
+
+    let result = qemu.run_test("test_rs_audio_tone", Duration::from_secs(30));
+    log.push(&result.serial);
+    log.push(&qemu.drain_serial(Duration::from_millis(500)));
+    if result.error.is_some() || result.exit_code != Some(0) {
+        return Err(format!(
+            "the tone after the wake exited {:?}:\n{}\nserial:\n{}",
+            result.exit_code,
+            result.stdout,
+            log.text()
+        ));
+    }
+    log.must_say("soundd: the device was reset across a sleep")?;
+    log.must_not_say("presenting a null sink")?;
+    let counters = super::audio::parse_soundd_counters(log.text())?;
+    if counters.windows == 0 {
+        return Err(format!("soundd reported no stats window after the wake\n{}", log.text()));
+    }
+    let wav = super::audio::parse_wav(qemu.audio_wav_path())?;
+    let peak = super::audio::analyze(&wav).peak;
+    if peak < 8000 {
+        return Err(format!(
+            "the capture peaks at {peak} — the tone plays at 16000 and nothing reached the \
+             device after the wake"
+        ));
+    }
+    if let Some(complaint) = super::audio::wrong_pitch(&wav) {
+        return Err(complaint);
+    }
+    log.must_be_clean()?;
+    eprintln!("  [s3] {card}: woken, and a tone played after the wake peaks at {peak}");
+    Ok(())
+}
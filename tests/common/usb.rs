@@ -791,6 +791,56 @@ pub fn usb_storage_write_error(
     Ok(())
 }
 
+/// The gate's sweep over a disk behind a `usb-uas` bridge: the same host
+/// bytes read, the same guest bytes verified host-side, through UAS's four
+/// pipes instead of Bulk-Only's two.
+///
+/// The bridge offers nothing but UAS, so a driver that only spoke Bulk-Only
+/// would leave it unbound and the gate would count one disk, not two — which
+/// is why `gate_ran` is the first assertion and not the transport line. That
+/// line is the second: QEMU advertises streams on the bridge's SuperSpeed
+/// companions, and a driver that bound it without them would have run every
+/// command one at a time on a single ring and still passed the sweep.
+pub fn usb_storage_uas(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let (bytes, lba) = Profile::UsbDiskUas.usb_disk().expect("the profile declares a disk");
+    let image = test_dir().join("usb-gate-uas.img");
+    let nonce = stage(&image, bytes);
+
+    let options = BootOptions {
+        profile: Profile::UsbDiskUas,
+        kernel_params: GATE,
+        usb_images: vec![image.clone()],
+        ..Default::default()
+    };
+    // Whether the disk is behind a bridge at all is a property of argv: a
+    // `usb-storage` stick with a working driver prints the same sweep.
+    let argv = qemu::profile_argv(&options);
+    if !argv.iter().any(|a| a.starts_with("usb-uas,")) {
+        return Err(format!("the data disk is not behind a usb-uas bridge: {argv:?}"));
+    }
+
+    let log = boot_and_shutdown(test_config, c_bins, rust_bins, options)?;
+    gate_ran(&log, 2)?;
+    check_geometry(&log, bytes, lba)?;
+    if !log.contains(", UAS with streams") {
+        return Err(format!("the bridge did not bind over UAS with streams\n{log}"));
+    }
+    if !log.contains("usb-gate: disk done reads=ok writes=ok refusal=true wr_err=0 healthy=true") {
+        return Err(format!("the guest did not report a clean pass over UAS\n{log}"));
+    }
+    verify(&image, bytes, nonce)?;
+    serial::Serial::named("boot console", log.as_str()).must_be_clean()?;
+    let _ = std::fs::remove_file(&image);
+
+    eprintln!("  [usb] UAS bridge: bound with streams, host bytes read, guest bytes verified \
+               host-side, boot stick still Bulk-Only");
+    Ok(())
+}
+
 /// The geometry the guest derived, against what the profile handed it. This is
 /// where a driver that believed the wrong sector size shows up: at 4 KiB
 /// sectors and at 512 the block count is the same number, and it is the
@@ -2621,6 +2671,159 @@ fn hotplug_unbound(log: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// A keyboard behind a hub, and a mouse plugged into the same hub after the
+/// boot.
+///
+/// Everything the driver knows about a hub's port it learned by asking the hub,
+/// so each assertion here is a request that was answered: the descriptor that
+/// numbered the ports, the GET_STATUS that saw the keyboard, the resets that
+/// enabled it, and the interrupt endpoint whose bitmap said the mouse arrived
+/// and left. The Route String is in the log because the controller routes by
+/// it and nothing else — a keyboard that types under the wrong one is a
+/// keyboard that was addressed somewhere else.
+///
+/// The hub's own unplug is not staged: QEMU gives a device on the command line
+/// no id for `device_del` to name. What a departing hub does to its ports is
+/// `toyos-xhci/sim/tests/hub.rs`'s.
+pub fn xhci_hub(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    const DX: i32 = 40;
+    const DY: i32 = -30;
+
+    let options = BootOptions {
+        profile: Profile::UsbHub,
+        qmp: true,
+        // Without it QEMU delivers the injected keystrokes over PS/2, and the
+        // keyboard behind the hub is never what typed.
+        i8042: false,
+        ..Default::default()
+    };
+    let argv = qemu::profile_argv(&options);
+    let usb = crate::usb_argv(&argv);
+    if !usb.iter().any(|d| d.starts_with("usb-kbd") && d.contains("port=2.1")) {
+        return Err(format!("this gate needs the keyboard behind the hub, argv has {usb:?}"));
+    }
+    if usb.iter().any(|d| d.starts_with("usb-kbd") && !d.contains("port=2.")) {
+        return Err(format!("a keyboard is on a root port, argv has {usb:?}"));
+    }
+
+    let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+    let boot = qemu.boot_log().to_string();
+    let Some((scale_x, scale_y)) = crate::parse_rel_scale(&boot) else {
+        return Err(format!("the kernel never said what pointer scale it used:\n{boot}"));
+    };
+
+    let result = qemu.run_test_hooked(
+        "test_rs_input_events",
+        Duration::from_secs(60),
+        "===INPUT_READY===",
+        move |socket| {
+            // The hub's ports are powered at its bind and enumerated by the
+            // hot-plug path once their power is good; this is that with room.
+            thread::sleep(Duration::from_millis(800));
+            let mut devices = qemu::QmpDevices::open(socket);
+            devices.add("usb-mouse", "xhci.0", "hubmouse", &[("port", "2.3")]);
+            drop(devices);
+            thread::sleep(Duration::from_millis(800));
+
+            let mut input = qemu::QmpInput::open(socket);
+            input.mouse(100, 100, None);
+            thread::sleep(Duration::from_millis(100));
+            input.mouse(DX, DY, None);
+            thread::sleep(Duration::from_millis(100));
+            for key in ["h", "e", "l", "l", "o"] {
+                input.keys(&[(key, true), (key, false)]);
+                thread::sleep(Duration::from_millis(20));
+            }
+            drop(input);
+            thread::sleep(Duration::from_millis(200));
+
+            let mut devices = qemu::QmpDevices::open(socket);
+            devices.del("hubmouse");
+            drop(devices);
+            thread::sleep(Duration::from_millis(800));
+
+            // The keyboard on the next port of the same hub is untouched.
+            let mut input = qemu::QmpInput::open(socket);
+            for key in ["w", "o", "r", "l", "d"] {
+                input.keys(&[(key, true), (key, false)]);
+                thread::sleep(Duration::from_millis(20));
+            }
+            drop(input);
+            crate::input_events_end(&mut qemu::QmpInput::open(socket));
+        },
+    );
+    if let Some(err) = &result.error {
+        return Err(format!("{err}\n{}\n{}", result.serial, result.stdout));
+    }
+    let log = format!("{boot}{}", result.serial);
+    for bad in ["PANIC:", "panicked at"] {
+        if log.contains(bad) {
+            return Err(format!("{bad:?} behind a hub\n{log}"));
+        }
+    }
+
+    // QEMU's hub has eight ports, numbered after the controller's own.
+    if !log.contains("(port 2) has 8 ports, numbered") {
+        return Err(format!("the hub on root port 2 was not bound with its 8 ports\n{log}"));
+    }
+    for want in [
+        "is route 0x00001 below root port 2",
+        "is route 0x00003 below root port 2",
+        "xHCI: USB keyboard ready on slot",
+        "xHCI: USB mouse ready on slot",
+    ] {
+        if !log.contains(want) {
+            return Err(format!("nothing behind the hub said {want:?}\n{log}"));
+        }
+    }
+    let typed: String = crate::parse_key_events(&result.stdout)
+        .iter()
+        .filter(|e| e.modifiers & 0x10 == 0)
+        .map(|e| e.translated.as_str())
+        .collect();
+    for word in ["hello", "world"] {
+        if !typed.contains(word) {
+            return Err(format!(
+                "typed {typed:?}, want {word:?} — the only keyboard is behind the hub\n{}",
+                result.stdout
+            ));
+        }
+    }
+    let want = (DX * scale_x, DY * scale_y);
+    let pointer = crate::parse_mouse_events(&result.stdout);
+    let moved = pointer
+        .windows(2)
+        .any(|w| (w[1].x as i32 - w[0].x as i32, w[1].y as i32 - w[0].y as i32) == want);
+    if !moved {
+        return Err(format!(
+            "no pointer event moved by {want:?} — the tablet is absolute, so only the mouse \
+             behind the hub could have\n{}",
+            result.stdout
+        ));
+    }
+    for want in ["unplugged from port", "disabled"] {
+        if !log.contains(want) {
+            return Err(format!("the mouse pulled from the hub left {want:?} unsaid\n{log}"));
+        }
+    }
+    for illegal in ["Disable Slot failed", "Disable Slot timed out", "would not report its port"] {
+        if log.contains(illegal) {
+            return Err(format!("{illegal:?} behind the hub\n{log}"));
+        }
+    }
+    serial::Serial::named("boot console", boot.as_str()).must_be_clean()?;
+
+    eprintln!(
+        "  [xhci] a hub of 8 ports on root port 2: the keyboard at route 0x1 typed before and \
+         after its neighbour at 0x3 came and went, and the mouse moved by {want:?}"
+    );
+    Ok(())
+}
+
 /// A disk the driver refuses, on the port the controller enumerates *first*.
 ///
 /// `bind` claims a 64 KiB DMA pool block, issues Configure Endpoint — which
@@ -2969,3 +3172,213 @@ pub fn usb_boot_stick_pulled(
     );
     Ok(())
 }
+
+/// A machine whose **only console is a USB serial adapter**: the ready marker,
+/// the command that runs a test and the test's output all go over QEMU's
+/// `usb-serial`, which is an FTDI FT232 on the xHCI.
+///
+/// The adapter is not there at reset. Everything the kernel logged before
+/// enumeration found it sat in the ring, so the first thing this reads is the
+/// replay, and the 16550 probe's line in it is the proof: that line was written
+/// long before a USB device existed. Then the harness's `===TEST_START===` has
+/// to arrive through the adapter's bulk IN pipe with the FTDI status bytes
+/// stripped, or the runner never sees a command it can parse.
+///
+/// There is no 16550 in this profile and no virtio device, so none of it can
+/// have come out of another backend.
+pub fn usb_serial_console(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let options = BootOptions { profile: Profile::UsbSerial, ..Default::default() };
+    let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+    let boot = qemu.boot_log().to_string();
+
+    let Some(bound) = boot.lines().find(|l| l.contains("usb-serial: FTDI adapter on slot ")) else {
+        return Err(format!("no USB serial adapter became the console\n{boot}"));
+    };
+    if !boot.contains("serial: 16550 loopback") {
+        return Err(format!(
+            "the boot from before the adapter was found was not replayed onto it\n{boot}"
+        ));
+    }
+
+    let result = qemu.run_test("test_rs_std_io", Duration::from_secs(30));
+    if let Some(e) = &result.error {
+        return Err(format!("test_rs_std_io over the USB console: {e}\n{}", result.serial));
+    }
+    if result.exit_code != Some(0) || !result.stdout.contains("hello from ToyOS") {
+        return Err(format!(
+            "test_rs_std_io over the USB console exited {:?} with stdout {:?}\n{}",
+            result.exit_code, result.stdout, result.serial
+        ));
+    }
+    if let Some(line) = result.serial.lines().find(|l| l.contains("usb-serial: dropped")) {
+        return Err(format!("{line:?} on an idle console\n{}", result.serial));
+    }
+    serial::Serial::named("boot console", boot.as_str()).must_be_clean()?;
+
+    eprintln!(
+        "  [usb] the boot replayed onto an FTDI adapter ({}) and a test ran over it",
+        bound.trim()
+    );
+    Ok(())
+}
+
+/// A 440 Hz tone out of a USB Audio Class DAC, read back off the device.
+///
+/// The kernel bound the streaming interface and sends one packet a service
+/// interval; soundd fills the PCM ring the kernel packetizes from. The verdict
+/// is `hda_tone`'s on the same wav backend, because the two drivers share
+/// soundd's ring discipline and differ in who moves the samples: a tone that is
+/// present, continuous and at the right pitch. A DAC that plays 44.1 kHz data
+/// at 48 kHz passes every other check here and fails the last.
+///
+/// QEMU's `usb-audio` is adaptive, with no feedback endpoint, so the
+/// asynchronous half of the driver is the descriptor selftest's to cover and
+/// not this boot's.
+pub fn usb_audio_tone(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let options = BootOptions { profile: Profile::UsbAudio, ..Default::default() };
+    let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+    // soundd claims the DAC the instant it starts, after the ready marker and
+    // before any test command, exactly as it claims an HDA controller.
+    let mut log = serial::Serial::boot(&qemu);
+    log.push(&qemu.drain_serial(Duration::from_millis(500)));
+
+    let result = qemu.run_test("test_rs_audio_tone", Duration::from_secs(30));
+    if let Some(err) = &result.error {
+        return Err(err.to_string());
+    }
+    if result.exit_code != Some(0) {
+        return Err(format!("the tone did not play: {:?}\n{}", result.exit_code, result.stdout));
+    }
+    log.push(&result.serial);
+    log.push(&qemu.drain_serial(Duration::from_millis(500)));
+    let text = log.text().to_string();
+    log.must_say("usb-audio: UAC1 DAC on slot ")?;
+    log.must_say("soundd: usb audio UAC1 DAC, 48000 Hz 2 ch")?;
+    if text.contains("presenting a null sink") {
+        return Err(format!("soundd fell back to the null sink:\n{text}"));
+    }
+    if let Some(line) = text.lines().find(|l| l.contains("usb-audio: the stream stopped")) {
+        return Err(format!("{line:?} while the tone played\n{text}"));
+    }
+
+    let wav = super::audio::parse_wav(qemu.audio_wav_path())?;
+    let analysis = super::audio::analyze(&wav);
+    if analysis.peak < 8000 {
+        return Err(format!(
+            "the capture peaks at {} — the tone plays at 16000 and nothing reached the device",
+            analysis.peak
+        ));
+    }
+    let gaps = super::audio::gap_histogram(&analysis, wav.sample_rate);
+    let dropouts: u32 = gaps.values().sum();
+    eprintln!(
+        "  [usb] {} frames at {} Hz {} ch, peak {} pitch {:.1}Hz gaps {} phase-breaks {}",
+        wav.mono.len(),
+        wav.sample_rate,
+        wav.channels,
+        analysis.peak,
+        super::audio::dominant_hz(&wav).unwrap_or(0.0),
+        super::audio::format_histogram(&gaps),
+        super::audio::phase_breaks(&wav).len(),
+    );
+    if dropouts > 0 {
+        return Err(format!(
+            "{dropouts} mid-tone silences in the capture: {}",
+            super::audio::format_histogram(&gaps)
+        ));
+    }
+    if let Some(complaint) = super::audio::wrong_pitch(&wav) {
+        return Err(complaint);
+    }
+    log.must_be_clean()
+}
+
+/// A USB DAC plugged into a machine already playing through its HDA card, then
+/// pulled out again, with soundd following it both ways.
+///
+/// **The actuator is QEMU's own `device_add`, as `xhci_hotplug`'s is.** The DAC
+/// is on no bus at boot, so soundd starts on the card with nothing but the USB
+/// audio claim to watch — the claim minted for the class rather than the
+/// device, and the only way a DAC that arrives later can be heard about. The
+/// tone is then played twice, once with the DAC in and once after it has gone,
+/// and each time the console has to say which output soundd chose: the DAC's
+/// 48 kHz session and then the card's 44.1 kHz one, each after the line that
+/// says what moved it.
+///
+/// Both outputs write into one wav backend, so the capture cannot say which of
+/// them played; the verdict is the tone program's exit and soundd's own account
+/// of where it routed, and the pitch is `usb_audio_tone`'s and `hda_tone`'s.
+pub fn usb_audio_hotplug(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let options = BootOptions { profile: Profile::Hda, qmp: true, ..Default::default() };
+    let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+    let mut log = serial::Serial::boot(&qemu);
+    log.push(&qemu.drain_serial(Duration::from_millis(500)));
+    log.must_say("buffers, 44100Hz")?;
+    if log.text().contains("soundd: usb audio") {
+        return Err(format!("soundd found a DAC before one was plugged in:\n{}", log.text()));
+    }
+
+    let mut devices = qemu::QmpDevices::open(qemu.qmp_socket());
+    devices.add("usb-audio", "xhci.0", "hotdac", &[("audiodev", "hdaaud")]);
+    drop(devices);
+    // The driver's debounce, the enumeration and soundd's change of session,
+    // with room: nothing below is timed off this sleep, only ordered after it.
+    log.push(&qemu.drain_serial(Duration::from_millis(1500)));
+    log.must_say_after("usb-audio: UAC1 DAC on slot ", "soundd: usb audio UAC1 DAC, 48000 Hz 2 ch")?;
+    log.must_say_after("soundd: usb audio UAC1 DAC", "buffers, 48000Hz")?;
+
+    let result = qemu.run_test("test_rs_audio_tone", Duration::from_secs(30));
+    if let Some(err) = &result.error {
+        return Err(err.to_string());
+    }
+    if result.exit_code != Some(0) {
+        return Err(format!(
+            "the tone did not play through the DAC: {:?}\n{}",
+            result.exit_code, result.stdout
+        ));
+    }
+    log.push(&result.serial);
+
+    let mut devices = qemu::QmpDevices::open(qemu.qmp_socket());
+    devices.del("hotdac");
+    drop(devices);
+    log.push(&qemu.drain_serial(Duration::from_millis(1500)));
+    log.must_say_after(" unplugged from port ", "buffers, 44100Hz")?;
+
+    let result = qemu.run_test("test_rs_audio_tone", Duration::from_secs(30));
+    if let Some(err) = &result.error {
+        return Err(err.to_string());
+    }
+    if result.exit_code != Some(0) {
+        return Err(format!(
+            "the tone did not play through the card once the DAC had gone: {:?}\n{}",
+            result.exit_code, result.stdout
+        ));
+    }
+    log.push(&result.serial);
+    log.push(&qemu.drain_serial(Duration::from_millis(500)));
+    let text = log.text().to_string();
+    for bad in ["presenting a null sink", "usb-audio: the stream stopped"] {
+        if let Some(line) = text.lines().find(|l| l.contains(bad)) {
+            return Err(format!("{line:?} while the DAC came and went\n{text}"));
+        }
+    }
+    let wav = super::audio::parse_wav(qemu.audio_wav_path())?;
+    if let Some(complaint) = super::audio::wrong_pitch(&wav) {
+        return Err(complaint);
+    }
+    eprintln!("  [usb] soundd moved to a hot-plugged DAC and back to its card, and the tone played on both");
+    log.must_be_clean()
+}
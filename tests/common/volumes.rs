@@ -440,6 +440,211 @@ pub fn esp_filesystem(
     Ok(())
 }
 
+/// The same image, booted as a CD-ROM: firmware reads its ISO 9660 face, finds
+/// the El Torito catalog, and starts the bootloader from the ESP the catalog
+/// names — the path every virtualisation host's install media takes.
+///
+/// Reaching the ready marker is most of the verdict, because there is no other
+/// way to reach it: the machine has no USB stick, so the kernel and the initrd
+/// it runs from came off the disc. What is asserted besides is that the kernel
+/// knew it had no boot partition rather than mounting something else as one —
+/// a CD-ROM's device path carries no GPT GUID for the bootloader to hand over.
+///
+/// The image is checked as a disc on the host first, by `toyos-iso9660`, so a
+/// boot that fails here is about firmware or the guest rather than about the
+/// writer.
+pub fn cdrom_boot(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let image_path = test_dir().join("cdrom-boot.iso");
+    let image = qemu::build_boot_image(test_config, c_bins, rust_bins, &[]);
+    std::fs::write(&image_path, &image).map_err(|e| format!("write the boot image: {e}"))?;
+
+    let (esp_start, _) = esp_extent(&image, &image_path)?;
+    let mut disc = toyos_iso9660::Iso9660::mount(DiscBytes(&image))
+        .map_err(|e| format!("the boot image is not a disc: {e}"))?;
+    let entries = disc.boot_entries().map_err(|e| format!("the boot catalog does not read: {e}"))?;
+    match entries.as_slice() {
+        [entry] if entry.platform == toyos_iso9660::PLATFORM_EFI
+            && entry.load_rba as usize * 2048 == esp_start => {}
+        other => return Err(format!("the catalog does not boot the ESP at {esp_start}: {other:?}")),
+    }
+
+    let qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions { boot_image: Some(image_path.clone()), cdrom: true, ..Default::default() },
+    );
+    let boot = qemu.boot_log().to_string();
+    for want in ["boot-volume: not mounted", "log-volume: not mounted"] {
+        if !boot.contains(want) {
+            return Err(format!("a CD-ROM boot did not say {want:?}; {}", volume_lines(&boot)));
+        }
+    }
+    eprintln!("  [cdrom] booted from the El Torito catalog to {}", qemu::DEFAULT_READY);
+    Ok(())
+}
+
+/// Boot from the CD-ROM again and read the disc back through the kernel's own
+/// AHCI driver, as ISO 9660, after the boot.
+///
+/// `cdrom_boot` proves firmware reads the disc; nothing there touches the
+/// drive once ExitBootServices has run. This is the kernel's half: the
+/// `ide-cd` is bound as an ATAPI disc, its READ CAPACITY is the image's size,
+/// `cdrom0` mounts over it, and a process lists its root and reads bytes out
+/// of a file on it. The bytes are checked against the image on the host, at
+/// an offset into the ESP's extent the directory reads never touch — so a
+/// listing that came from a cached descriptor and a READ (10) that moved
+/// nothing would not pass.
+pub fn ahci_cdrom(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let image_path = test_dir().join("ahci-cdrom.iso");
+    let image = qemu::build_boot_image(test_config, c_bins, rust_bins, &[]);
+    std::fs::write(&image_path, &image).map_err(|e| format!("write the boot image: {e}"))?;
+    let (esp_start, _) = esp_extent(&image, &image_path)?;
+
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions { boot_image: Some(image_path.clone()), cdrom: true, ..Default::default() },
+    );
+    let log = qemu.boot_log().to_string();
+    for bad in ["PANIC:", "panicked at", "AHCI: disc 0 is offline"] {
+        if log.contains(bad) {
+            return Err(format!("{bad:?} with a disc in the AHCI drive\n{log}"));
+        }
+    }
+    // The disc's size in the kernel's 4 KiB blocks, which is what the bind
+    // line carries: a trailing half block counts, as the driver serves it.
+    let bind = "AHCI: disc 0 id=2 on port 0: QEMU DVD-ROM";
+    let blocks = format!("blocks={} ", image.len().div_ceil(4096));
+    let mounted = format!("cdrom0: mounted read-only, {} bytes", image.len());
+    if !log.lines().any(|l| l.contains(bind) && l.contains(&blocks)) {
+        return Err(format!("the boot never said {bind:?} with {blocks:?}\n{log}"));
+    }
+    if !log.contains(&mounted) {
+        return Err(format!("the boot never said {mounted:?}\n{log}"));
+    }
+
+    let listing = qemu.run_test("ls /cdrom0", Duration::from_secs(60));
+    let names = ["esp.img", "boot.cat"];
+    if listing.exit_code != Some(0) || !names.iter().all(|n| listing.stdout.contains(n)) {
+        return Err(format!(
+            "`ls /cdrom0` exited {:?} without both of the disc's files:\n{}\nserial:\n{}",
+            listing.exit_code, listing.stdout, listing.serial
+        ));
+    }
+
+    // The ESP's boot-sector signature, as the xxd-style groups `hexdump`
+    // prints: 0x1F0 is the last line of its first sector.
+    let at = 0x1F0;
+    let want: Vec<String> = image[esp_start + at..esp_start + at + 16]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect();
+    let want = want.join(" ");
+    let dump = qemu.run_test(&format!("hexdump -s {at:#x} -l 16 /cdrom0/esp.img"), Duration::from_secs(60));
+    if dump.exit_code != Some(0) || !dump.stdout.contains(&want) {
+        return Err(format!(
+            "`hexdump` of esp.img off the disc exited {:?} without {want:?}:\n{}\nserial:\n{}",
+            dump.exit_code, dump.stdout, dump.serial
+        ));
+    }
+    eprintln!("  [cdrom] the boot disc read back as ISO 9660 through AHCI's ATAPI path");
+    Ok(())
+}
+
+/// Boot with the scratch disk on the q35 machine's own AHCI controller and no
+/// NVMe, and prove `/home` comes up on it.
+///
+/// `storage::virtio_blk_home`'s verdict on a SATA drive: the volume mounts or
+/// is formatted, `/home` is not a tmpfs, and after a clean shutdown the host
+/// finds a bcachefs superblock at block 0 of the backing file. The drive is
+/// QEMU's `ide-hd`, which offers NCQ, so the boot log is also asked for the
+/// queue depth — a format written one command at a time would pass the rest.
+pub fn ahci_home(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions {
+            profile: qemu::Profile::Metal,
+            home_bus: qemu::HomeBus::Ahci,
+            ..Default::default()
+        },
+    );
+    let log = qemu.boot_log().to_string();
+    for bad in ["PANIC:", "panicked at"] {
+        if log.contains(bad) {
+            return Err(format!("{bad:?} on an AHCI disk\n{log}"));
+        }
+    }
+    for want in [
+        "AHCI: disk 0 id=2 on port 1",
+        "AHCI: disk 0 queues",
+        "/home goes on AHCI disk 0",
+        qemu::DEFAULT_READY,
+    ] {
+        if !log.contains(want) {
+            return Err(format!("the boot never said {want:?}\n{log}"));
+        }
+    }
+    if !log.contains("formatting it") && !log.contains("mounted the ToyOS volume") {
+        return Err(format!("the AHCI disk was neither mounted nor formatted\n{log}"));
+    }
+    if log.contains("/home is a tmpfs") {
+        return Err(format!("an AHCI disk was found and /home is still a tmpfs\n{log}"));
+    }
+
+    writeln!(qemu.stdin_mut(), "run shutdown").expect("write to QEMU stdin");
+    qemu.flush_stdin();
+    let tail = qemu.drain_serial(Duration::from_secs(20));
+    for bad in ["PANIC:", "panicked at", "AHCI: disk 0 is offline"] {
+        if tail.contains(bad) {
+            return Err(format!("{bad:?} during shutdown\n{tail}"));
+        }
+    }
+    let image = qemu.nvme_image().to_path_buf();
+    drop(qemu);
+
+    let mut head = [0u8; 4];
+    std::fs::File::open(&image)
+        .and_then(|mut f| f.read_exact(&mut head))
+        .map_err(|e| format!("read {}: {e}", image.display()))?;
+    if &head != b"BCFS" {
+        return Err(format!("block 0 of {} is not a bcachefs superblock", image.display()));
+    }
+    eprintln!("  [ahci] /home formatted and flushed through NCQ on ich9-ahci");
+    Ok(())
+}
+
+/// An image in memory, read as a disc.
+struct DiscBytes<'a>(&'a [u8]);
+
+impl toyos_iso9660::BlockAccess for DiscBytes<'_> {
+    fn capacity(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), toyos_iso9660::IoError> {
+        let at = usize::try_from(offset).map_err(|_| toyos_iso9660::IoError)?;
+        let src = self.0.get(at..at + buf.len()).ok_or(toyos_iso9660::IoError)?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+}
+
 /// Everything the guest said about identifying and mounting its volumes.
 ///
 /// Wider than the mount's own lines on purpose. A mount that does not happen is
@@ -0,0 +1,182 @@
+//! Host↔guest streams over virtio-vsock, driven from the host's own
+//! `AF_VSOCK` sockets.
+//!
+//! The host side is `libc` and nothing else, so what is tested is the guest
+//! against the host kernel's vsock — the peer every real use of this has — and
+//! not against a second implementation of the protocol written here.
+
+use std::io::{Read, Write};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::qemu::{BootOptions, QemuInstance};
+
+/// An `AF_VSOCK` stream socket, not yet connected or bound.
+fn vsock_socket() -> Result<OwnedFd, String> {
+    let fd = unsafe { libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(format!("socket(AF_VSOCK): {}", std::io::Error::last_os_error()));
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn sockaddr(cid: u32, port: u32) -> libc::sockaddr_vm {
+    let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+    addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+    addr.svm_cid = cid;
+    addr.svm_port = port;
+    addr
+}
+
+const ADDR_LEN: libc::socklen_t = std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+
+/// A stream to `(cid, port)`, wrapped in a `UnixStream` only for its `Read`
+/// and `Write`: both are a plain `read(2)`/`write(2)` on the descriptor, which
+/// is all a stream socket of any family needs.
+fn connect(cid: u32, port: u32) -> Result<UnixStream, String> {
+    use std::os::fd::{AsRawFd, IntoRawFd};
+    let fd = vsock_socket()?;
+    let addr = sockaddr(cid, port);
+    let rc = unsafe { libc::connect(fd.as_raw_fd(), &addr as *const _ as *const libc::sockaddr, ADDR_LEN) };
+    if rc < 0 {
+        return Err(format!("connect({cid}:{port}): {}", std::io::Error::last_os_error()));
+    }
+    Ok(unsafe { UnixStream::from_raw_fd(fd.into_raw_fd()) })
+}
+
+fn listen(port: u32) -> Result<OwnedFd, String> {
+    use std::os::fd::AsRawFd;
+    let fd = vsock_socket()?;
+    let addr = sockaddr(libc::VMADDR_CID_ANY, port);
+    if unsafe { libc::bind(fd.as_raw_fd(), &addr as *const _ as *const libc::sockaddr, ADDR_LEN) } < 0 {
+        return Err(format!("bind(vsock port {port}): {}", std::io::Error::last_os_error()));
+    }
+    if unsafe { libc::listen(fd.as_raw_fd(), 1) } < 0 {
+        return Err(format!("listen(vsock port {port}): {}", std::io::Error::last_os_error()));
+    }
+    Ok(fd)
+}
+
+/// Both directions on one boot: the host connects to a port the guest listens
+/// on, and the guest connects to a port the host listens on. Each side sends,
+/// shuts its sending half, and reads the other's answer to the end — so a
+/// shutdown that never reached the peer is a hang here rather than a pass.
+///
+/// **A host without `/dev/vhost-vsock` passes with a line saying it could not
+/// run.** The device is a host kernel module (`vhost_vsock`) that a container
+/// or a non-Linux host may not have, and a red there would be about the lane,
+/// not the guest.
+pub fn virtio_vsock_host(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    if !Path::new("/dev/vhost-vsock").exists() {
+        eprintln!("virtio_vsock_host: this host has no /dev/vhost-vsock; nothing was run");
+        return Ok(());
+    }
+    // A context id is the host's namespace, so two lanes on one host must not
+    // share one. The process id is unique among the live ones, and 3 is the
+    // first id a guest may have. The host's listening port is in the same
+    // namespace, so it is the process id too, above the 16-bit range anything
+    // else on the host would pick.
+    let cid = 3 + std::process::id();
+    let host_port = 0x1_0000 + std::process::id();
+    const GUEST_PORT: u32 = 5000;
+    const TO_GUEST: &str = "hello from the host 71c2";
+    // One argument on the guest's command line, so no spaces.
+    const TO_HOST: &str = "hello-from-the-guest-0b9e";
+
+    let mut qemu = QemuInstance::boot_with_options(
+        test_config,
+        c_bins,
+        rust_bins,
+        BootOptions { vsock: Some(cid), ..Default::default() },
+    );
+    let log = qemu.boot_log().to_string();
+    for want in [format!("virtio-vsock: ready, guest cid {cid}"), format!("vsockd: ready, guest cid {cid}")] {
+        if !log.contains(&want) {
+            return Err(format!("the boot never said {want:?}\n{log}"));
+        }
+    }
+
+    // Host → guest. The guest's `listen` is what this thread races, so it
+    // retries a refusal until the port is there rather than guessing a delay.
+    let host = std::thread::spawn(move || -> Result<String, String> {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let mut stream = loop {
+            match connect(cid, GUEST_PORT) {
+                Ok(s) => break s,
+                Err(e) if Instant::now() >= deadline => return Err(e),
+                Err(_) => std::thread::sleep(Duration::from_millis(100)),
+            }
+        };
+        stream.set_read_timeout(Some(Duration::from_secs(30))).ok();
+        stream.write_all(TO_GUEST.as_bytes()).map_err(|e| format!("write to the guest: {e}"))?;
+        stream.shutdown(std::net::Shutdown::Write).map_err(|e| format!("shutdown: {e}"))?;
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).map_err(|e| format!("read the guest's answer: {e}"))?;
+        Ok(answer)
+    });
+    let result = qemu.run_test(&format!("vsock listen {GUEST_PORT}"), Duration::from_secs(60));
+    let mut serial = result.serial.clone();
+    if let Some(err) = &result.error {
+        return Err(format!("`vsock listen` never finished: {err}\n{serial}"));
+    }
+    let answer = host.join().expect("the host thread panicked")?;
+    if result.exit_code != Some(0) || !result.stdout.contains(TO_GUEST) {
+        return Err(format!(
+            "`vsock listen` exited {:?} without printing {TO_GUEST:?}\n{}{serial}",
+            result.exit_code, result.stdout
+        ));
+    }
+    let want = format!("{} bytes\n", TO_GUEST.len());
+    if answer != want {
+        return Err(format!("the guest answered {answer:?}, want {want:?}\n{serial}"));
+    }
+
+    // Guest → host. The listening socket exists before the guest is asked to
+    // connect, so nothing races.
+    let listener = listen(host_port)?;
+    let host = std::thread::spawn(move || -> Result<String, String> {
+        use std::os::fd::AsRawFd;
+        let fd = unsafe { libc::accept(listener.as_raw_fd(), std::ptr::null_mut(), std::ptr::null_mut()) };
+        if fd < 0 {
+            return Err(format!("accept: {}", std::io::Error::last_os_error()));
+        }
+        let mut stream = unsafe { UnixStream::from_raw_fd(fd) };
+        stream.set_read_timeout(Some(Duration::from_secs(30))).ok();
+        let mut got = String::new();
+        stream.read_to_string(&mut got).map_err(|e| format!("read the guest's message: {e}"))?;
+        stream.write_all(format!("host got {}", got.len()).as_bytes()).map_err(|e| format!("answer: {e}"))?;
+        Ok(got)
+    });
+    let result = qemu.run_test(&format!("vsock connect 2 {host_port} {TO_HOST}"), Duration::from_secs(60));
+    serial += &result.serial;
+    if let Some(err) = &result.error {
+        return Err(format!("`vsock connect` never finished: {err}\n{serial}"));
+    }
+    let got = host.join().expect("the host thread panicked")?;
+    if got != TO_HOST {
+        return Err(format!("the host read {got:?}, want {TO_HOST:?}\n{serial}"));
+    }
+    let want = format!("host got {}", TO_HOST.len());
+    if result.exit_code != Some(0) || !result.stdout.contains(&want) {
+        return Err(format!(
+            "`vsock connect` exited {:?} without printing {want:?}\n{}{serial}",
+            result.exit_code, result.stdout
+        ));
+    }
+
+    writeln!(qemu.stdin_mut(), "run shutdown").expect("write to QEMU stdin");
+    qemu.flush_stdin();
+    let tail = qemu.drain_serial(Duration::from_secs(20));
+    for bad in ["PANIC:", "panicked at", "vsockd: resetting", "does not parse"] {
+        if tail.contains(bad) || log.contains(bad) || serial.contains(bad) {
+            return Err(format!("{bad:?} around the vsock streams\n{log}{serial}{tail}"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! A game controller's report reaches the gamepad handle as the state it says.
+//!
+//! QEMU has no USB game controller, so the pad is `SYS_DEBUG`'s: actions 21 to
+//! 23 plug in a controller with a real DirectInput report descriptor, hand the
+//! kernel one report of it at a time, and pull it out. Everything between the
+//! report and this process is the shipped path — the descriptor's layout
+//! decodes it, the pad's number and last state are the gamepad class's, the
+//! watcher completion is the one a transfer event makes, and the read is the
+//! handle's. See `xhci::synthetic_pad` for the half that is not.
+//!
+//! The reports are eight bytes: report ID 1, X, Y, Z and Rz as 0..=255 with
+//! 0x80 at rest, the hat in the low nibble (8 is its null state), then twelve
+//! buttons from bit 48.
+
+use toyos::device::Gamepad;
+use toyos::endow::Endowments;
+use toyos::poller::{Poller, READABLE};
+use toyos::syscap::SysCap;
+use toyos_abi::input::{GamepadEvent, HAT_CENTERED, PAD_DISCONNECTED, PAD_GAMEPAD};
+use toyos_abi::syscall::{self, debug_action, DeviceType, SyscallError, SYSCAP_LABEL};
+
+const EVENT_SIZE: usize = std::mem::size_of::<GamepadEvent>();
+
+const TOKEN: u64 = 1;
+
+/// How long a report may take to complete the watch. It is completed inside
+/// the syscall that hands the report over, so this is a hang and not a slow
+/// guest.
+const PATIENCE_NANOS: u64 = 5_000_000_000;
+
+/// X hard left, Y hard down, Z and Rz at rest, the hat pointing right (2),
+/// buttons 1, 3 and 12.
+const PRESS: [u8; 8] = [1, 0x00, 0xFF, 0x80, 0x80, 0x02, 0x05, 0x08];
+/// What `PRESS` decodes to. An 8-bit axis at 0x80 is one step past centre, so
+/// a stick at rest reads 128 and not 0.
+const PRESS_AXES: [i16; 8] = [-32767, 32767, 128, 0, 0, 128, 0, 0];
+const PRESS_BUTTONS: u32 = 1 | 1 << 2 | 1 << 11;
+
+/// Every stick at rest, the hat in its null state, no buttons.
+const RELEASE: [u8; 8] = [1, 0x80, 0x80, 0x80, 0x80, 0x08, 0x00, 0x00];
+const RELEASE_AXES: [i16; 8] = [128, 128, 128, 0, 0, 128, 0, 0];
+
+fn main() {
+    let cap: SysCap = Endowments::get()
+        .take(SYSCAP_LABEL)
+        .expect("the test estate is endowed a device-minting capability");
+    let pad_handle: Gamepad =
+        cap.claim(DeviceType::Gamepad).expect("gamepad_report: no gamepad device");
+    let poller = Poller::new(1);
+
+    let pad = plug();
+    println!("gamepad_report: plugged in as pad {pad}");
+
+    // The watch is in the kernel before the report is, so what completes it is
+    // the report's own wake and not the recheck a registration makes.
+    poller.watch(&pad_handle, READABLE, TOKEN);
+    poller.wait(0, 0, |token| panic!("gamepad_report: token {token} before any report"));
+    assert_eq!(report(PRESS), 1, "gamepad_report: a new state queued nothing");
+    let mut woken = false;
+    poller.wait(1, PATIENCE_NANOS, |token| woken |= token == TOKEN);
+    assert!(woken, "gamepad_report: the report queued a state and completed no watch");
+    expect(&read(&pad_handle), pad, PRESS_BUTTONS, PRESS_AXES, 2, 0, "the press");
+
+    // A pad restates its state on every interval; only a change is an event.
+    assert_eq!(report(PRESS), 0, "gamepad_report: an unchanged report queued a state");
+    // And a report of an ID the layout does not describe is not this pad's.
+    let mut other = PRESS;
+    other[0] = 2;
+    assert_eq!(report(other), 0, "gamepad_report: a report of another ID queued a state");
+    assert!(read(&pad_handle).is_empty(), "gamepad_report: a state was queued for nothing");
+
+    assert_eq!(report(RELEASE), 1, "gamepad_report: the release queued nothing");
+    expect(&read(&pad_handle), pad, 0, RELEASE_AXES, HAT_CENTERED, 0, "the release");
+
+    // Pulled out holding something, so the last state is what lets go of it.
+    assert_eq!(report(PRESS), 1, "gamepad_report: the second press queued nothing");
+    expect(&read(&pad_handle), pad, PRESS_BUTTONS, PRESS_AXES, 2, 0, "the second press");
+    unplug();
+    expect(&read(&pad_handle), pad, 0, [0; 8], HAT_CENTERED, PAD_DISCONNECTED, "the unplug");
+
+    // The number went back, and the next pad takes it.
+    assert_eq!(plug(), pad, "gamepad_report: a pad plugged back in was given a fresh number");
+    unplug();
+    assert_eq!(
+        read(&pad_handle).len(),
+        1,
+        "gamepad_report: an unplug should publish its last state and nothing else"
+    );
+
+    println!("gamepad_report: ok");
+}
+
+fn plug() -> u8 {
+    let raw = syscall::debug(debug_action::PAD_PLUG);
+    if let Some(e) = SyscallError::from_u64(raw) {
+        panic!(
+            "gamepad_report: PAD_PLUG answered {e:?} — this kernel carries no actuators, or a pad \
+             from an earlier run is still plugged in"
+        );
+    }
+    raw as u8
+}
+
+fn report(bytes: [u8; 8]) -> u64 {
+    let raw = syscall::debug_with(debug_action::PAD_REPORT, u64::from_le_bytes(bytes));
+    if let Some(e) = SyscallError::from_u64(raw) {
+        panic!("gamepad_report: PAD_REPORT answered {e:?}");
+    }
+    raw
+}
+
+fn unplug() {
+    let raw = syscall::debug(debug_action::PAD_UNPLUG);
+    if let Some(e) = SyscallError::from_u64(raw) {
+        panic!("gamepad_report: PAD_UNPLUG answered {e:?}");
+    }
+}
+
+/// Every state queued so far. Empty is `WouldBlock`, which is what the handle
+/// answers with nothing queued.
+fn read(handle: &Gamepad) -> Vec<GamepadEvent> {
+    let mut buf = [0u8; EVENT_SIZE * 8];
+    let n = match handle.read_nonblock(&mut buf) {
+        Ok(n) => n,
+        Err(SyscallError::WouldBlock) => 0,
+        Err(e) => panic!("gamepad_report: reading the gamepad answered {e:?}"),
+    };
+    assert_eq!(n % EVENT_SIZE, 0, "gamepad_report: a read of {n} bytes split an event");
+    buf[..n]
+        .chunks_exact(EVENT_SIZE)
+        // SAFETY: each chunk is `size_of::<GamepadEvent>()` bytes the kernel
+        // wrote with `GamepadEvent::as_bytes`, every bit pattern of its integer
+        // fields is valid, and the read is unaligned because the buffer is.
+        .map(|chunk| unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const GamepadEvent) })
+        .collect()
+}
+
+fn expect(
+    events: &[GamepadEvent],
+    pad: u8,
+    buttons: u32,
+    axes: [i16; 8],
+    hat: u8,
+    flags: u8,
+    what: &str,
+) {
+    let want = GamepadEvent { buttons, axes, pad, hat, kind: PAD_GAMEPAD, flags };
+    assert_eq!(events, [want], "gamepad_report: {what} should be exactly one state");
+    println!("gamepad_report: {what}: {:?}", events[0]);
+}
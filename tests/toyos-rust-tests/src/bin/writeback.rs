@@ -0,0 +1,132 @@
+//! Background write-back's three triggers, one file each, and `fsync` with
+//! `iod` writing the same file back underneath it.
+//!
+//! Sized against the `test-small-caches` budget of 64 pages, where the dirty
+//! set reaches `DirtyPressure::Background` at 6 pages and `Throttle` at 12.
+//! Each trigger's file is written with a single `write`, because the pressure
+//! is read once per `write` and a loop of small ones would let `iod` drain
+//! between them and decide which threshold the next one saw. Every file but
+//! the last is fsynced before the next begins, so each starts from a dirty
+//! set that is `logd`'s and nobody else's.
+//!
+//! The verdict is the kernel's, not this program's: under `iod-trace` every
+//! background write-back logs what triggered it and how long the file had
+//! been queued, and the harness reads those lines. `iod` only writes back a
+//! file that is still open, so a line for one of these files is a write-back
+//! that happened before its close. What this program checks itself is the
+//! bytes, which must read back the same however they reached the device.
+//! The last file's bytes are checked again by the harness, in the NVMe image,
+//! while this program still holds the file open.
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::Duration;
+
+const PAGE: usize = 4096;
+/// Twice `Throttle`'s 12 pages: one `write` of these is past it on any dirty
+/// set `logd` leaves.
+const THROTTLE_PAGES: usize = 16;
+/// Past `Background`'s 6 and short of `Throttle`'s 12.
+const BACKGROUND_PAGES: usize = 8;
+/// Well under `Background`, so only the expiry can write it back.
+const EXPIRE_PAGES: usize = 1;
+/// `iod::EXPIRE` and two seconds for the thread to be scheduled.
+const EXPIRE_WAIT: Duration = Duration::from_secs(7);
+/// Pages of the file `fsync` is tested on, and how many of them are rewritten
+/// just before it.
+const FSYNC_PAGES: usize = 32;
+const FSYNC_REWRITTEN: usize = BACKGROUND_PAGES;
+
+/// Distinct per (tag, page, byte), so the harness can find each page in the
+/// image by its contents. The NVMe image holds no other copy of them.
+fn byte_at(tag: usize, page: usize, i: usize) -> u8 {
+    let mixed = (tag.wrapping_mul(0x9E37_79B9))
+        ^ (page.wrapping_mul(0x85EB_CA6B))
+        ^ (i.wrapping_mul(0xC2B2_AE35));
+    (mixed >> 13) as u8
+}
+
+fn pages(tag: usize, range: std::ops::Range<usize>) -> Vec<u8> {
+    range
+        .flat_map(|page| (0..PAGE).map(move |i| byte_at(tag, page, i)))
+        .collect()
+}
+
+fn check(path: &str, want: &[u8]) {
+    let mut got = Vec::new();
+    fs::File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut got))
+        .unwrap_or_else(|e| panic!("read back {path}: {e}"));
+    assert_eq!(got.len(), want.len(), "{path}: length after write-back");
+    if let Some(i) = got.iter().zip(want).position(|(a, b)| a != b) {
+        panic!(
+            "{path}: byte {i} (page {}) differs after write-back",
+            i / PAGE
+        );
+    }
+}
+
+fn create(path: &str) -> fs::File {
+    fs::File::create(path).unwrap_or_else(|e| panic!("create {path}: {e}"))
+}
+
+fn main() {
+    // Throttle: the writer writes its own file back before `write` returns.
+    let path = "/home/wb_throttle.bin";
+    let data = pages(1, 0..THROTTLE_PAGES);
+    let mut f = create(path);
+    f.write_all(&data).expect("write the throttle file");
+    f.sync_all().expect("fsync the throttle file");
+    drop(f);
+    check(path, &data);
+
+    // Background: `iod` is woken at once and writes it back with the file
+    // still open. A second is all it is given, well short of the expiry.
+    let path = "/home/wb_background.bin";
+    let data = pages(2, 0..BACKGROUND_PAGES);
+    let mut f = create(path);
+    f.write_all(&data).expect("write the background file");
+    thread::sleep(Duration::from_secs(1));
+    f.sync_all().expect("fsync the background file");
+    drop(f);
+    check(path, &data);
+
+    // Expiry: nothing presses, so `iod` waits out `EXPIRE` and then writes it
+    // back, still with the file open.
+    let path = "/home/wb_expire.bin";
+    let data = pages(3, 0..EXPIRE_PAGES);
+    let mut f = create(path);
+    f.write_all(&data).expect("write the expire file");
+    thread::sleep(EXPIRE_WAIT);
+    drop(f);
+    check(path, &data);
+
+    // fsync with `iod` running. The file is written a page per `write`, so it
+    // crosses both thresholds on the way and `iod` and the throttled writer
+    // both write it back as it grows. Then the head is rewritten in one
+    // `write` that puts the dirty set past `Background` — which wakes `iod`
+    // for this file — and `fsync` races it.
+    let path = "/home/wb_fsync.bin";
+    let mut f = create(path);
+    for page in 0..FSYNC_PAGES {
+        f.write_all(&pages(4, page..page + 1))
+            .expect("write a page of the fsync file");
+    }
+    f.sync_all().expect("the first fsync");
+    f.seek(SeekFrom::Start(0)).expect("rewind the fsync file");
+    f.write_all(&pages(5, 0..FSYNC_REWRITTEN))
+        .expect("rewrite the head");
+    f.sync_all().expect("the fsync under test");
+    // The harness reads the image on this line, so the file is held open
+    // until it has had time to: what it finds must have been put there by
+    // the time `fsync` returned, not by the close.
+    println!("writeback: fsynced");
+    thread::sleep(Duration::from_secs(3));
+    drop(f);
+    let mut want = pages(5, 0..FSYNC_REWRITTEN);
+    want.extend(pages(4, FSYNC_REWRITTEN..FSYNC_PAGES));
+    check(path, &want);
+
+    println!("writeback: PASS");
+}
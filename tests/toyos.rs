@@ -11,7 +11,7 @@ use common::qemu::{
     self, await_guest, await_marker, await_marker_new, BootOptions, QemuInstance, TestResult,
     STALLED,
 };
-use common::{audio, compile, faults, hostload, screen, serial, stats, storage, usb};
+use common::{audio, compile, faults, hostload, screen, serial, stats, storage, suspend, usb, vsock};
 use toyos_build::testargs::Shard;
 use toyos_build::tiers::{self, Tier};
 
@@ -168,6 +168,10 @@ const ACTUATOR_TESTS: &[&str] = &[
     "handle_basic",
     "handle_kill_policy",
     "handle_transfer",
+    // Actions 21, 22 and 23: a USB game controller plugged in, reporting and
+    // pulled out. QEMU has no such device, so without them no pad state has
+    // ever reached a reader.
+    "gamepad_report",
 ];
 
 /// What [`ACTUATOR_TESTS`] boots: the one kernel that carries `SYS_DEBUG`, with
@@ -224,7 +228,9 @@ const RUST_SKIP: &[&str] = &[
     // one — and a second boot on the kernel that saves nothing, which is the
     // only thing that proves the arms have teeth.
     "fpu_isolation",
-    // Needs netd with a NIC. `netd_connection_caps` runs it on tests/netcase.
+    // Needs netd with a NIC. `netd_connection_caps` runs it on tests/netcase,
+    // and `e1000e_netcase` and `usbnet_netcase` run it there again over the
+    // other NIC drivers.
     "netd_caps",
     // Same reason, same config: `netd_hostile_peer` runs it there.
     "netd_hostile_peer",
@@ -289,6 +295,9 @@ const RUST_SKIP: &[&str] = &[
     //
     // `cache_eviction` needs the small NVMe that makes the cache evict at all.
     "cache_eviction",
+    // `writeback` needs the small caches, so that one `write` lands between
+    // the dirty thresholds, and the trace that says which one it crossed.
+    "writeback",
     // Needs an HDA controller, which `tests/testcases` has none of.
     "hda_client_stall",
     // Gate A's two, whose verdict is the wav the device captured — which the
@@ -434,6 +443,8 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     // not compute-bound: timer-anchored, and Nightly for that reason.
     ("doom_music", Sched::Parallel, Tier::Nightly),
     ("netd_connection_caps", Sched::Parallel, Tier::Fast),
+    ("e1000e_netcase", Sched::Parallel, Tier::Fast),
+    ("usbnet_netcase", Sched::Parallel, Tier::Fast),
     // Its own boot with a NIC under it, because sshd leaves at the bind on
     // every other config. Every verdict is a line of text; no clock in any.
     ("sshd_fail_closed", Sched::Parallel, Tier::Fast),
@@ -444,6 +455,27 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     ("netd_hostile_peer", Sched::Serial, Tier::Nightly),
     ("launcher_refusals", Sched::Parallel, Tier::Fast),
     ("foreign_disk_untouched", Sched::Parallel, Tier::Fast),
+    // Its own boot on its own disk: a passphrase typed at the storage phase
+    // and the ciphertext read back on the host. Every verdict is a line of
+    // text or a byte on the disk; the waits are liveness ceilings.
+    ("encrypted_home", Sched::Parallel, Tier::Fast),
+    ("virtio_blk_home", Sched::Parallel, Tier::Fast),
+    ("nvme_queues", Sched::Parallel, Tier::Fast),
+    ("nvme_discard", Sched::Parallel, Tier::Fast),
+    // Its own boot, with S3 switched on in the LPC. The host waits on the
+    // guest's line and then on the monitor's status, so no verdict is a margin.
+    ("s3_resume", Sched::Parallel, Tier::Fast),
+    // The default virtio machine and the HDA one with S3 on: a `suspend`, the
+    // wake, and a tone read back off the sound card.
+    ("s3_resume_virtio_sound", Sched::Parallel, Tier::Fast),
+    ("s3_resume_hda", Sched::Parallel, Tier::Fast),
+    // One boot, and every verdict is a line the kernel printed about QEMU's own
+    // tables before userland started.
+    ("aml_q35_tables", Sched::Parallel, Tier::Fast),
+    ("virtio_9p_share", Sched::Parallel, Tier::Fast),
+    // Parallel: the two host threads wait on the guest's own lines and on
+    // sockets, with 30 s ceilings that are liveness, not margins.
+    ("virtio_vsock_host", Sched::Parallel, Tier::Fast),
     ("boot_partition_identity", Sched::Parallel, Tier::Fast),
     ("double_fault_stack", Sched::Parallel, Tier::Fast),
     // One boot of its own, ten seconds of Ring 3 spinning, and every verdict is
@@ -490,6 +522,12 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     // One boot, and its verdict is a line the kernel printed before any device
     // was brought up. No clock and no device in it.
     ("virtio_used_ring", Sched::Parallel, Tier::Fast),
+    // One boot, and its verdict is a line the kernel printed after the mounts,
+    // about two disks that answer from memory. No clock in it.
+    ("page_cache_fairness", Sched::Parallel, Tier::Fast),
+    // Same shape: a line the kernel printed after the mounts, about a file on
+    // a disk that answers from memory.
+    ("file_cache_readahead", Sched::Parallel, Tier::Fast),
     ("xhci_many_devices", Sched::Parallel, Tier::Fast),
     // Its whole assertion is that a keystroke injected from the host crossed a
     // USB keyboard on the *second* controller, and `input_events_run` sends
@@ -736,6 +774,7 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     // margin looks under TCG.
     ("usb_disk_index_stable", Sched::Parallel, Tier::Nightly),
     ("usb_storage_write_error", Sched::Parallel, Tier::Fast),
+    ("usb_storage_uas", Sched::Parallel, Tier::Fast),
     ("usb_flush_optional", Sched::Parallel, Tier::Nightly),
     ("xhci_deaf_registers", Sched::Parallel, Tier::Nightly),
     // Mirrors the kernel's `SLOW_CONNECT_NS` as a constant of its own and
@@ -760,12 +799,15 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     ("usb_transport_break", Sched::Serial, Tier::Nightly),
     ("xhci_full_speed_device", Sched::Parallel, Tier::Fast),
     ("xhci_superspeed_ports", Sched::Parallel, Tier::Fast),
+    ("usb_serial_console", Sched::Parallel, Tier::Fast),
     // Two of the three below stage plug and unplug with fixed waits, 600-800 ms
     // against a 100 ms debounce, plus 20-200 ms sleeps pacing the input pokes
     // that follow — staged latency windows gating the verdict, so timer-
     // anchored even though every individual check is a count of what the
     // guest logged.
     ("xhci_hotplug", Sched::Parallel, Tier::Nightly),
+    ("xhci_hub", Sched::Parallel, Tier::Nightly),
+    ("virtio_input", Sched::Parallel, Tier::Nightly),
     // `xhci_flap` is the one that genuinely races the host against the guest:
     // its two QMP writes have to land inside *one* 100 ms debounce or the state
     // under test never happens, and it says so — `no replug collapsed inside a
@@ -776,6 +818,9 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     ("xhci_hid_break", Sched::Parallel, Tier::Nightly),
     ("xhci_descriptor_walk", Sched::Parallel, Tier::Fast),
     ("esp_filesystem", Sched::Parallel, Tier::Fast),
+    ("cdrom_boot", Sched::Parallel, Tier::Fast),
+    ("ahci_cdrom", Sched::Parallel, Tier::Fast),
+    ("ahci_home", Sched::Parallel, Tier::Fast),
     ("toybox_cp_volume", Sched::Parallel, Tier::Nightly),
     ("kernel_log_file", Sched::Parallel, Tier::Nightly),
     // Serial: its verdict is a cadence — heartbeats against a 250 ms period —
@@ -800,6 +845,8 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     ("log_partition_layout", Sched::Parallel, Tier::Fast),
     ("log_partition_identity", Sched::Parallel, Tier::Fast),
     ("cache_eviction", Sched::Parallel, Tier::Fast),
+    // Seven of its seconds are the expiry it waits out.
+    ("writeback", Sched::Parallel, Tier::Fast),
     ("va_exhaustion", Sched::Parallel, Tier::Fast),
     ("heap_ceiling_recovery", Sched::Parallel, Tier::Fast),
     ("iommu_context_absent", Sched::Parallel, Tier::Fast),
@@ -813,6 +860,15 @@ const MACHINE_TESTS: &[(&str, Sched, Tier)] = &[
     // and its counters rather than a capture, so it runs wide.
     ("hda_client_stall", Sched::Parallel, Tier::Nightly),
     ("hda_two_live_refused", Sched::Parallel, Tier::Fast),
+    // The same controller with MSI off, so its completions arrive on the pin
+    // `_PRT` routes. Its verdict is soundd's counters, not a capture.
+    ("hda_intx", Sched::Parallel, Tier::Fast),
+    // A USB DAC, read back off the same wav backend. Serial for `hda_tone`'s
+    // reason.
+    ("usb_audio_tone", Sched::Serial, Tier::Nightly),
+    // The same DAC plugged in under a running soundd and pulled out again.
+    // Serial for the same reason.
+    ("usb_audio_hotplug", Sched::Serial, Tier::Nightly),
     ("serial_vocabulary", Sched::Parallel, Tier::Fast),
     // Host-side, no guest: the harness asking whether it can still tell a
     // suspended machine from a slow one, and whether it reports one as a
@@ -4649,6 +4705,94 @@ fn boot_locale(
     QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options)
 }
 
+/// `netd_caps` on `tests/netcase`, booted on `profile`: the cap netd
+/// announced, checked against where the guest measured the refusals start,
+/// and the boot console.
+///
+/// A function of the profile because the NIC model is one: netd is meant to
+/// behave the same over any driver that fills in its `NicInfo`, and this is
+/// the test that would notice if it did not.
+fn netd_caps_on(
+    profile: qemu::Profile,
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(usize, String), String> {
+    netd_caps_then(BootOptions { profile, ..Default::default() }, rust_bins, |_, _| Ok(()))
+}
+
+/// [`netd_caps_on`], and then `after` on the same boot while netd is still up.
+fn netd_caps_then(
+    options: BootOptions,
+    rust_bins: &[(String, Vec<u8>)],
+    after: impl FnOnce(&mut QemuInstance, &mut String) -> Result<(), String>,
+) -> Result<(usize, String), String> {
+    let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/netcase");
+    let bins: Vec<(String, Vec<u8>)> = rust_bins
+        .iter()
+        .filter(|(name, _)| name == "netd_caps")
+        .cloned()
+        .collect();
+    if bins.is_empty() {
+        return Err("netd_caps was not built".to_string());
+    }
+    // Without a NIC netd exits before reaching anything this is about.
+    if !qemu::profile_argv(&options).iter().any(|a| a.contains("netdev=net0")) {
+        return Err("this test needs a NIC and the profile has none".to_string());
+    }
+
+    let mut qemu = QemuInstance::boot_with_options(&config, &[], &bins, options);
+
+    let mut console = qemu.boot_log().to_string();
+    let _ = await_marker(&mut qemu, &mut console, "netd: ready, at most ", "netd to come up");
+    let Some(declared) = console
+        .lines()
+        .find_map(|l| l.split("netd: ready, at most ").nth(1))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|n| n.parse::<usize>().ok())
+    else {
+        return Err(format!(
+            "netd never said how many piped connections it would hold:\n{console}"
+        ));
+    };
+    if declared == 0 {
+        return Err("netd derived a cap of zero connections".to_string());
+    }
+
+    // The cap is passed as the burst size, not as the answer: the
+    // guest still measures the boundary itself.
+    let result = qemu.run_test(
+        &format!("test_rs_netd_caps {declared}"),
+        Duration::from_secs(120),
+    );
+    if let Some(err) = &result.error {
+        return Err(format!("{err}\n{}", result.stdout));
+    }
+    if result.exit_code != Some(0) {
+        return Err(format!(
+            "netd_caps exited {:?}:\n{}",
+            result.exit_code, result.stdout
+        ));
+    }
+
+    let Some(granted) = result
+        .stdout
+        .split("netd caps: ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|n| n.parse::<usize>().ok())
+    else {
+        return Err(format!("netd_caps printed no count:\n{}", result.stdout));
+    };
+    if granted != declared {
+        return Err(format!(
+            "netd declared a cap of {declared} piped connections and accepted \
+             {granted} — the derivation and the enforcement disagree:\n{}",
+            result.stdout
+        ));
+    }
+    after(&mut qemu, &mut console)?;
+    Ok((declared, console))
+}
+
 /// Every negative claim `Profile::Metal` makes, read off the argv QEMU is
 /// launched with. A claim about which devices do *not* exist is a claim about
 /// this list and nothing else — no console line and no screendump can see a
@@ -6538,20 +6682,21 @@ fn blocked_dump() -> Result<(), String> {
         return Err(format!("no parked task was named by pid and tid:\n{report}"));
     }
 
-    // **All three kernel threads, by name.** They are almost always blocked, so
+    // **All four kernel threads, by name.** They are almost always blocked, so
     // the parked lines above carry them as a pid and a tid and nothing else —
-    // and on a machine that has gone quiet the question is *which* of the three
+    // and on a machine that has gone quiet the question is *which* of the four
     // is stuck. `sched::dump`'s census tags a kernel thread whatever it is
-    // doing, which is C6's gate: three kernel threads split the work —
-    // `klogd` the console drain, `usbd` the xHCI port machine, `iod` the
-    // write-back queue — precisely so that one of them wedging does not stop
-    // the other two. A report that cannot tell them apart cannot say which did.
+    // doing, which is C6's gate: the kernel threads split the work — `klogd`
+    // the console drain, `usbd` the xHCI port machine, `iod` the write-back
+    // queue, `acpid` firmware's methods — precisely so that one of them wedging
+    // does not stop the others. A report that cannot tell them apart cannot
+    // say which did.
     //
     // Matched with the ` cpu=` that follows the name on the census line, because
     // a bare name appears in every one of these programs' own log lines and
     // `/bin/init` speaks in a program's name before that program runs
     // (`tests/CLAUDE.md`).
-    let unnamed: Vec<&str> = ["klogd", "usbd", "iod"]
+    let unnamed: Vec<&str> = ["klogd", "usbd", "iod", "acpid"]
         .into_iter()
         .filter(|name| !report.contains(&format!(" {name} cpu=")))
         .collect();
@@ -7350,6 +7495,86 @@ fn run_machine_test(
         // Body in `tests/common/storage.rs`, so the hunk in this shared file
         // stays one line.
         "foreign_disk_untouched" => storage::foreign_disk_untouched(test_config, c_bins, rust_bins),
+        "encrypted_home" => storage::encrypted_home(test_config, c_bins, rust_bins),
+        "virtio_blk_home" => storage::virtio_blk_home(test_config, c_bins, rust_bins),
+        "nvme_queues" => storage::nvme_queues(test_config, c_bins, rust_bins),
+        "nvme_discard" => storage::nvme_discard(test_config, c_bins, rust_bins),
+        "aml_q35_tables" => {
+            // The interpreter against the tables QEMU generates for q35, read
+            // off the running machine rather than out of a fixture: there is
+            // no `acpidump -b` of them in the tree, and `toyos-aml`'s own
+            // tests have only Firecracker's DSDT. QEMU writes them fresh on
+            // every boot, and this boot is where the kernel meets them.
+            //
+            // With S3 on in the LPC, because that is the only q35 that has an
+            // `\_S3_` to read.
+            let qemu = QemuInstance::boot_with_options(
+                test_config,
+                c_bins,
+                rust_bins,
+                BootOptions {
+                    s3: true,
+                    ..Default::default()
+                },
+            );
+            let boot = serial::Serial::boot(&qemu);
+            boot.must_be_clean()?;
+            // The namespace loaded whole and every `_INI` ran.
+            boot.must_not_say("stopped loading")?;
+            boot.must_not_say("failed to initialise")?;
+            let objects = boot.must_say(" objects from the DSDT")?;
+            eprintln!("  [aml] {}", objects.trim());
+
+            let s3 = boot.must_say("AML: \\_S3_ SLP_TYPa=")?;
+            eprintln!("  [aml] {}", s3.trim());
+
+            // q35's `_PRT` in APIC mode names a GSI link device for every pin,
+            // four pins a slot. Counted and not matched, so a QEMU that adds
+            // slots does not turn this red — but every pin through a link,
+            // because a table returned before `\_PIC(1)` took is the 8259's.
+            let prt = boot.must_say("AML: \\_SB_.PCI0 _PRT routes ")?;
+            let counts: Vec<usize> = prt
+                .split(|c: char| !c.is_ascii_digit())
+                .filter_map(|n| n.parse().ok())
+                .collect();
+            // `PCI0` contributes the 0 in the first place, then the two counts.
+            let &[.., pins, links] = counts.as_slice() else {
+                return Err(format!("no counts in the _PRT line: {prt:?}"));
+            };
+            if pins == 0 || pins % 4 != 0 || links != pins {
+                return Err(format!(
+                    "q35's _PRT routes {pins} pins, {links} of them through link devices; \
+                     want a non-zero multiple of four, all of them through links: {prt:?}"
+                ));
+            }
+            eprintln!("  [aml] {}", prt.trim());
+
+            // And every one of them resolved: a link whose `_CRS` the kernel
+            // could not read is a pin an INTx driver would find routed nowhere.
+            let resolved = boot.must_say("AML: \\_SB_.PCI0 _PRT resolves ")?;
+            let counts: Vec<usize> = resolved
+                .split(|c: char| !c.is_ascii_digit())
+                .filter_map(|n| n.parse().ok())
+                .collect();
+            // `PCI0`'s 0, the two counts, then the bus.
+            let &[.., got, of, _bus] = counts.as_slice() else {
+                return Err(format!("no counts in the resolve line: {resolved:?}"));
+            };
+            if got != of || of != pins {
+                return Err(format!(
+                    "{got} of {of} pins resolved to a GSI, of the {pins} _PRT routes: {resolved:?}"
+                ));
+            }
+            eprintln!("  [aml] {}", resolved.trim());
+            Ok(())
+        }
+        // Body in `tests/common/suspend.rs`.
+        "s3_resume" => suspend::s3_resume(test_config, c_bins, rust_bins),
+        "s3_resume_virtio_sound" => suspend::s3_resume_virtio_sound(test_config, c_bins, rust_bins),
+        "s3_resume_hda" => suspend::s3_resume_hda(test_config, c_bins, rust_bins),
+        "virtio_9p_share" => storage::virtio_9p_share(test_config, c_bins, rust_bins),
+        // Body in `tests/common/vsock.rs`.
+        "virtio_vsock_host" => vsock::virtio_vsock_host(test_config, c_bins, rust_bins),
         // Body in `tests/common/gpt.rs`, same reason.
         "boot_partition_identity" => common::gpt::boot_partition_identity(test_config, c_bins, rust_bins),
         // Bodies in `tests/common/usb.rs`, for the same reason.
@@ -7364,6 +7589,9 @@ fn run_machine_test(
         "usb_disk_index_stable" => usb::usb_disk_index_stable(test_config, c_bins, rust_bins),
         // Body in `tests/common/volumes.rs`, same reason.
         "esp_filesystem" => common::volumes::esp_filesystem(test_config, c_bins, rust_bins),
+        "cdrom_boot" => common::volumes::cdrom_boot(test_config, c_bins, rust_bins),
+        "ahci_cdrom" => common::volumes::ahci_cdrom(test_config, c_bins, rust_bins),
+        "ahci_home" => common::volumes::ahci_home(test_config, c_bins, rust_bins),
         // Body in `tests/common/toybox.rs`, same reason.
         "toybox_cp_volume" => common::toybox::cp_volume(test_config, c_bins, rust_bins),
         "kernel_log_file" => common::volumes::kernel_log_file(test_config, c_bins, rust_bins),
@@ -7692,6 +7920,7 @@ fn run_machine_test(
             common::volumes::boot_volume_metadata_error(test_config, c_bins, rust_bins)
         }
         "usb_storage_write_error" => usb::usb_storage_write_error(test_config, c_bins, rust_bins),
+        "usb_storage_uas" => usb::usb_storage_uas(test_config, c_bins, rust_bins),
         "usb_flush_optional" => usb::usb_flush_optional(test_config, c_bins, rust_bins),
         "xhci_deaf_registers" => usb::xhci_deaf_registers(test_config, c_bins, rust_bins),
         "xhci_slow_connect" => usb::xhci_slow_connect(test_config, c_bins, rust_bins),
@@ -7701,7 +7930,12 @@ fn run_machine_test(
             usb::xhci_full_speed_device(test_config, c_bins, rust_bins)
         }
         "xhci_superspeed_ports" => usb::xhci_superspeed_ports(test_config, c_bins, rust_bins),
+        "usb_serial_console" => usb::usb_serial_console(test_config, c_bins, rust_bins),
+        "usb_audio_tone" => usb::usb_audio_tone(test_config, c_bins, rust_bins),
+        "usb_audio_hotplug" => usb::usb_audio_hotplug(test_config, c_bins, rust_bins),
         "xhci_hotplug" => usb::xhci_hotplug(test_config, c_bins, rust_bins),
+        "xhci_hub" => usb::xhci_hub(test_config, c_bins, rust_bins),
+        "virtio_input" => virtio_input(test_config, c_bins, rust_bins),
         "xhci_flap" => usb::xhci_flap(test_config, c_bins, rust_bins),
         "xhci_hid_break" => usb::xhci_hid_break(test_config, c_bins, rust_bins),
         // Body in `tests/common/iommu.rs`, same reason.
@@ -7744,6 +7978,7 @@ fn run_machine_test(
         "hda_two_live_refused" => {
             common::hda::hda_two_live_refused(test_config, c_bins, rust_bins)
         }
+        "hda_intx" => common::hda::hda_intx(test_config, c_bins, rust_bins),
         "double_fault_stack" => faults::double_fault_stack(test_config, c_bins, rust_bins),
         "syscall_window_nmi" => faults::syscall_window_nmi(test_config, c_bins, rust_bins),
         "syscall_window_nmi_controls" => {
@@ -7912,18 +8147,24 @@ fn run_machine_test(
                 ));
             }
             // And every device on the bus is accounted for exactly once: the
-            // HIDs bound above, the boot stick bound as a disk, the hub walked
-            // past. An inequality here would let a driver that bound the stick
-            // *and* skipped it, or that stopped enumerating early, pass.
+            // HIDs bound above, the boot stick bound as a disk, the hub bound
+            // as a hub with nothing behind it. An inequality here would let a
+            // driver that bound the stick *and* skipped it, or that stopped
+            // enumerating early, pass.
             let disks = log.matches("usb-storage: disk ").count();
+            let hubs = log.matches("ports, numbered").count();
             let skipped = log.matches("no HID boot interface found").count();
-            if binds.len() + disks + skipped != usb.len() {
+            if binds.len() + disks + hubs + skipped != usb.len() {
                 return Err(format!(
-                    "{} HID + {disks} disk + {skipped} skipped is not the {} devices on the bus:\n{log}",
+                    "{} HID + {disks} disk + {hubs} hub + {skipped} skipped is not the {} devices \
+                     on the bus:\n{log}",
                     binds.len(),
                     usb.len()
                 ));
             }
+            if hubs != 1 {
+                return Err(format!("{hubs} hubs bound, want the one on the bus:\n{log}"));
+            }
             if disks != 1 {
                 return Err(format!("{disks} disks bound, want the boot stick:\n{log}"));
             }
@@ -8893,14 +9134,14 @@ fn run_machine_test(
             }
             eprintln!("  [klogd] {}", line.trim());
 
-            // **The other two threads, and the opposite row.** `usbd` owns
-            // the xHCI port machine and `iod` the write-back queue, so a
-            // stuck USB enumeration cannot stop the log. Their panics are
-            // *recoverable* and `klogd`'s deliberately is not — a killed
-            // drainer is the one loss nothing left alive can report — and
-            // this is the one boot in the suite where all three rows are on
-            // the wire together.
-            for name in ["usbd", "iod"] {
+            // **The other three threads, and the opposite row.** `usbd` owns
+            // the xHCI port machine, `iod` the write-back queue and `acpid`
+            // firmware's methods, so a stuck USB enumeration cannot stop the
+            // log. Their panics are *recoverable* and `klogd`'s deliberately
+            // is not — a killed drainer is the one loss nothing left alive can
+            // report — and this is the one boot in the suite where all four
+            // rows are on the wire together.
+            for name in ["usbd", "iod", "acpid"] {
                 let line = boot.must_say(&format!("kthread: {name}"))?;
                 if !line.contains("kills the thread") {
                     return Err(format!(
@@ -8910,6 +9151,15 @@ fn run_machine_test(
                 eprintln!("  [kthread] {}", line.trim());
             }
 
+            // `acpid` with something to run: q35's DSDT built into a
+            // namespace at boot, and its `\_GPE` methods handed to the
+            // thread. Zero objects is an interpreter that refused the table.
+            let aml = boot.must_say(" objects from the DSDT")?;
+            if aml.contains("AML: 0 objects") || !aml.contains("GPE handlers") {
+                return Err(format!("the interpreter built no namespace: {aml:?}"));
+            }
+            eprintln!("  [aml] {}", aml.trim());
+
             drop(qemu);
 
             // The marker is a line of the crash *report* rather than `PANIC:`
@@ -9397,6 +9647,96 @@ fn run_machine_test(
             );
             Ok(())
         }
+        "writeback" => {
+            // Background write-back has three triggers — a queued file waiting
+            // out `iod::EXPIRE`, the dirty set passing a tenth of the budget,
+            // and a writer past a fifth flushing its own file — and all three
+            // end with the same bytes on the same device. A program can see
+            // the bytes and not which trigger put them there, so `iod-trace`
+            // has the kernel log each one, and the guest program below writes
+            // one file per trigger at the `test-small-caches` budget, where
+            // the thresholds are 6 and 12 pages and one `write` can land
+            // between them.
+            let options = BootOptions {
+                kernel_params: &["test-small-caches", "iod-trace"],
+                qmp: true,
+                ..Default::default()
+            };
+            let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+            let image = qemu.nvme_image().to_path_buf();
+
+            // And `fsync` with `iod` waking for the same file. The image is
+            // read on the line the guest prints after `fsync` returns, with
+            // the file still open, so the close's own flush cannot be what
+            // put the pages there.
+            let mut missing: Option<Vec<String>> = None;
+            let result = qemu.run_test_hooked(
+                "test_rs_writeback",
+                Duration::from_secs(60),
+                "writeback: fsynced",
+                |_| missing = Some(writeback_pages_missing(&image)),
+            );
+            if !check_rust_result(&result) {
+                return Err(format!(
+                    "a file did not read back after write-back:\n{}\n{}",
+                    result.stdout, result.serial
+                ));
+            }
+            let log = &result.serial;
+            let Some(missing) = missing else {
+                return Err(format!("the guest never said it had fsynced:\n{log}"));
+            };
+            if !missing.is_empty() {
+                return Err(format!(
+                    "fsync returned and {} of the file's pages were not on the device: {}\n{log}",
+                    missing.len(),
+                    missing.join(", ")
+                ));
+            }
+
+            let said = |line: &str| log.lines().any(|l| l.contains(line));
+            if !said("write-back: throttled /home/wb_throttle.bin") {
+                return Err(format!(
+                    "16 dirty pages against a 64-page budget, and the writer did not write \
+                     its own file back:\n{log}"
+                ));
+            }
+            // Eight pages is past the background threshold and short of this
+            // one. Without the check, a throttle at the background threshold
+            // would pass every assertion here.
+            if said("write-back: throttled /home/wb_background.bin") {
+                return Err(format!("8 dirty pages throttled the writer:\n{log}"));
+            }
+            if !said("iod: wrote back /home/wb_background.bin (background,") {
+                return Err(format!(
+                    "8 dirty pages against a 64-page budget, and iod did not write the file \
+                     back before it was closed a second later:\n{log}"
+                ));
+            }
+            // The expiry is the one trigger with a time in it, so the time is
+            // what is held: a page written back early is the bug a background
+            // trigger firing on a quiet dirty set would be.
+            const EXPIRED: &str = "iod: wrote back /home/wb_expire.bin (expired, queued ";
+            let Some(queued) = log.lines().find_map(|l| {
+                let rest = &l[l.find(EXPIRED)? + EXPIRED.len()..];
+                rest.split(' ').next()?.parse::<u64>().ok()
+            }) else {
+                return Err(format!(
+                    "one dirty page, left open for 7 seconds, and iod never wrote it back on \
+                     expiry:\n{log}"
+                ));
+            };
+            if queued < 5000 || said("iod: wrote back /home/wb_expire.bin (background") {
+                return Err(format!(
+                    "one dirty page was written back after {queued} ms, before it expired:\n{log}"
+                ));
+            }
+            eprintln!(
+                "  [writeback] throttle, background and expiry (after {queued} ms) each fired; \
+                 an fsync racing iod left every page on the device"
+            );
+            Ok(())
+        }
         "xhci_slot_exhaustion" => {
             // A device count is untrusted input: more devices than the driver
             // has room for must cost those devices and nothing else. QEMU
@@ -10002,6 +10342,83 @@ fn run_machine_test(
             eprintln!("  [virtio] {}", verdict.trim());
             Ok(())
         }
+        "file_cache_readahead" => {
+            // Readahead has nothing to fetch on a test boot: `/home` is
+            // formatted fresh, so every page a program reads back it wrote in
+            // the same boot and is still resident. And what is under test — the
+            // window, and how many device calls a stream costs — is not visible
+            // from userland at all. The kernel streams a file off a synthetic
+            // disk under this parameter and reports both.
+            let qemu = QemuInstance::boot_with_options(
+                test_config,
+                c_bins,
+                rust_bins,
+                BootOptions {
+                    kernel_params: &["file-cache-readahead-selftest"],
+                    ..Default::default()
+                },
+            );
+            let log = qemu.boot_log().to_string();
+            if let Some(bad) = log.lines().find(|l| l.contains("readahead selftest FAILED")) {
+                return Err(format!("{bad}\n{log}"));
+            }
+            let Some(verdict) = log.lines().find(|l| l.contains("readahead selftest 5/5")) else {
+                return Err(format!("the readahead self-test never reported 5/5:\n{log}"));
+            };
+            let Some(shape) = log.lines().find(|l| l.contains("readahead selftest:")) else {
+                return Err(format!("the self-test gave its verdict without its numbers:\n{log}"));
+            };
+            eprintln!("  [file cache] {}", shape.trim());
+            eprintln!("  [file cache] {}", verdict.trim());
+            Ok(())
+        }
+        "page_cache_fairness" => {
+            // The page cache shares one budget between every disk it holds,
+            // and the reclaim that hands a quiet disk its share back runs only
+            // when a full cache is asked for room by a disk under its share.
+            // A test boot has one disk that ever fills the budget, so nothing
+            // reaches that path on its own. The kernel registers two synthetic
+            // disks after its mounts under this parameter, streams one through
+            // several budgets' worth of blocks around the other's share, and
+            // says whether the quiet one kept every block of it.
+            let qemu = QemuInstance::boot_with_options(
+                test_config,
+                c_bins,
+                rust_bins,
+                BootOptions {
+                    kernel_params: &["page-cache-fairness-selftest"],
+                    ..Default::default()
+                },
+            );
+            let log = qemu.boot_log().to_string();
+            if let Some(bad) = log.lines().find(|l| l.contains("fairness selftest FAILED")) {
+                return Err(format!("{bad}\n{log}"));
+            }
+            let Some(verdict) = log.lines().find(|l| l.contains("fairness selftest 5/5")) else {
+                return Err(format!("the fairness self-test never reported 5/5:\n{log}"));
+            };
+            // The numbers behind the verdict, so a run that passed on a share
+            // of zero or a budget nobody filled is visible from the output.
+            let Some(shape) = log.lines().find(|l| l.contains("fairness selftest:")) else {
+                return Err(format!("the self-test gave its verdict without its numbers:\n{log}"));
+            };
+            // The disks the boot itself mounted went behind the cache too:
+            // the NVMe disk for `/home`, and the boot stick on the `/boot`
+            // mount — two, before the self-test's own two.
+            let registered = log
+                .lines()
+                .filter(|l| l.contains("page cache: device") && l.contains("registered"))
+                .count();
+            if registered < 4 {
+                return Err(format!(
+                    "{registered} devices registered with the page cache, wanted the two \
+                     disks this boot mounts and the self-test's two:\n{log}"
+                ));
+            }
+            eprintln!("  [page cache] {}", shape.trim());
+            eprintln!("  [page cache] {}", verdict.trim());
+            Ok(())
+        }
         "xhci_descriptor_walk" => {
             // A configuration descriptor is the device's, and a device is not
             // kernel code. Every device QEMU can attach describes itself
@@ -10009,7 +10426,7 @@ fn run_machine_test(
             // descriptor and nothing else — while the interesting inputs are
             // the wrong ones, and one of them is an endpoint address naming
             // endpoint 0, whose device context index is the slot context or
-            // EP0's. The parser is pure, so the driver runs it over nine
+            // EP0's. The parser is pure, so the driver runs it over its
             // crafted descriptors at init under this feature.
             let qemu = QemuInstance::boot_with_options(
                 test_config,
@@ -10028,13 +10445,13 @@ fn run_machine_test(
             let Some(verdict) = log.lines().find(|l| l.contains("descriptor selftest")) else {
                 return Err(format!("the parser's self-test never ran:\n{log}"));
             };
-            // `9/9`, not "no failures": a self-test that ran zero cases would
+            // `23/23`, not "no failures": a self-test that ran zero cases would
             // satisfy the absence of a FAILED line.
-            if !verdict.contains("9/9") {
+            if !verdict.contains("23/23") {
                 return Err(format!("not every descriptor was parsed as required: {verdict}"));
             }
             // Once for the machine. It reads no register, so a per-controller
-            // run would be two verdicts about the same nine byte arrays.
+            // run would be two verdicts about the same byte arrays.
             let ran = log.matches("descriptor selftest").count();
             if ran != 1 {
                 return Err(format!("the self-test ran {ran} times, wanted once\n{log}"));
@@ -10716,76 +11133,60 @@ fn run_machine_test(
             // Same assertion design as `metal_sim_window_caps`: netd announces
             // the cap it derived, the guest measures where the refusals start,
             // and these must be the same number.
-            let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/netcase");
-            let bins: Vec<(String, Vec<u8>)> = rust_bins
-                .iter()
-                .filter(|(name, _)| name == "netd_caps")
-                .cloned()
-                .collect();
-            if bins.is_empty() {
-                return Err("netd_caps was not built".to_string());
+            let (declared, _) = netd_caps_on(qemu::Profile::Headless, rust_bins)?;
+            eprintln!("  [netcase] netd cap {declared} piped connections, {declared} accepted then refused");
+            Ok(())
+        }
+        "e1000e_netcase" => {
+            // `netd_connection_caps` again, on a machine whose NIC is an 82574.
+            // netd is the same binary reading the same `NicInfo`; what differs
+            // is the driver under it, so every frame the burst sends and every
+            // ARP reply it waits on crosses the e1000e's rings instead.
+            let (declared, console) = netd_caps_on(qemu::Profile::E1000e, rust_bins)?;
+            if !console.contains("e1000e: MAC ") {
+                return Err(format!("the e1000e was never bound:\n{console}"));
+            }
+            if console.contains("VirtIO net: found") {
+                return Err(format!("the e1000e profile has a virtio NIC too:\n{console}"));
             }
-            // Headless is the profile with virtio-net; without a NIC netd
-            // exits before reaching anything this test is about.
+            serial::Serial::named("boot console", console.as_str()).must_be_clean()?;
+            eprintln!("  [netcase] netd on the e1000e: cap {declared}, accepted then refused");
+            Ok(())
+        }
+        "usbnet_netcase" => {
+            // `netd_connection_caps` over a USB adapter, and then the thing no
+            // PCI NIC does: the adapter is pulled out from under netd and put
+            // back. Pulled, netd has to say the interface went down rather
+            // than crash on a claim whose every call now answers `NotFound`;
+            // put back, it has to bring the interface up on the same mapping.
             let options = BootOptions {
-                profile: qemu::Profile::Headless,
+                profile: qemu::Profile::UsbNet,
+                qmp: true,
                 ..Default::default()
             };
-            if !qemu::profile_argv(&options).iter().any(|a| a.contains("virtio-net")) {
-                return Err("this test needs a NIC and the profile has none".to_string());
-            }
-
-            let mut qemu = QemuInstance::boot_with_options(&config, &[], &bins, options);
-
-            let mut console = qemu.boot_log().to_string();
-            let _ = await_marker(&mut qemu, &mut console, "netd: ready, at most ", "netd to come up");
-            let Some(declared) = console
-                .lines()
-                .find_map(|l| l.split("netd: ready, at most ").nth(1))
-                .and_then(|rest| rest.split_whitespace().next())
-                .and_then(|n| n.parse::<usize>().ok())
-            else {
-                return Err(format!(
-                    "netd never said how many piped connections it would hold:\n{console}"
-                ));
-            };
-            if declared == 0 {
-                return Err("netd derived a cap of zero connections".to_string());
+            let (declared, console) = netd_caps_then(options, rust_bins, |qemu, console| {
+                let from = console.len();
+                let mut devices = qemu::QmpDevices::open(qemu.qmp_socket());
+                devices.del("usbnet0");
+                drop(devices);
+                await_marker_new(qemu, console, "netd: the NIC has gone", from, "netd to see the adapter go")?;
+                let from = console.len();
+                let mut devices = qemu::QmpDevices::open(qemu.qmp_socket());
+                devices.add("usb-net", "xhci.0", "usbnet0", &[("netdev", "net0")]);
+                drop(devices);
+                await_marker_new(qemu, console, "netd: the NIC is back", from, "netd to see the adapter return")
+            })?;
+            if !console.contains("usb-net: CDC-ECM adapter on slot ") {
+                return Err(format!("the USB adapter was never bound:\n{console}"));
             }
-
-            // The cap is passed as the burst size, not as the answer: the
-            // guest still measures the boundary itself.
-            let result = qemu.run_test(
-                &format!("test_rs_netd_caps {declared}"),
-                Duration::from_secs(120),
-            );
-            if let Some(err) = &result.error {
-                return Err(format!("{err}\n{}", result.stdout));
+            if !console.contains("unplugged from port") {
+                return Err(format!("the adapter's removal was never logged:\n{console}"));
             }
-            if result.exit_code != Some(0) {
-                return Err(format!(
-                    "netd_caps exited {:?}:\n{}",
-                    result.exit_code, result.stdout
-                ));
+            if console.contains("VirtIO net: found") {
+                return Err(format!("the usb-net profile has a virtio NIC too:\n{console}"));
             }
-
-            let Some(granted) = result
-                .stdout
-                .split("netd caps: ")
-                .nth(1)
-                .and_then(|rest| rest.split_whitespace().next())
-                .and_then(|n| n.parse::<usize>().ok())
-            else {
-                return Err(format!("netd_caps printed no count:\n{}", result.stdout));
-            };
-            if granted != declared {
-                return Err(format!(
-                    "netd declared a cap of {declared} piped connections and accepted \
-                     {granted} — the derivation and the enforcement disagree:\n{}",
-                    result.stdout
-                ));
-            }
-            eprintln!("  [netcase] netd cap {declared} piped connections, {granted} accepted then refused");
+            serial::Serial::named("boot console", console.as_str()).must_be_clean()?;
+            eprintln!("  [netcase] netd on a USB adapter: cap {declared}, then unplugged and replugged");
             Ok(())
         }
         "netd_hostile_peer" => {
@@ -11130,7 +11531,8 @@ fn parse_cache_series(log: &str, prefix: &str, unit: &str) -> Vec<(u64, u64)> {
 }
 
 /// How many blocks the page cache's index has room for, out of
-/// `page cache: N device blocks, index sized for C cached blocks`.
+/// `page cache: device D registered, N device blocks, index sized for C cached
+/// blocks`.
 fn parse_page_cache_index(log: &str) -> Option<u64> {
     log.lines()
         .find_map(|l| l.split("index sized for ").nth(1))?
@@ -11142,6 +11544,44 @@ fn parse_page_cache_index(log: &str) -> Option<u64> {
 
 /// Decode one bcachefs superblock straight out of a disk image, with the
 /// same parser the kernel uses — magic, version and CRC all checked.
+/// The pages of `/home/wb_fsync.bin` that `test_rs_writeback` fsynced and the
+/// NVMe image does not hold, by name. The contents are the guest program's own
+/// `byte_at`, and no other file in the image carries them, so a block holding
+/// one of them is that page wherever bcachefs put it.
+fn writeback_pages_missing(image: &Path) -> Vec<String> {
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    const PAGE: usize = 4096;
+    let byte_at = |tag: usize, page: usize, i: usize| {
+        let mixed = (tag.wrapping_mul(0x9E37_79B9))
+            ^ (page.wrapping_mul(0x85EB_CA6B))
+            ^ (i.wrapping_mul(0xC2B2_AE35));
+        (mixed >> 13) as u8
+    };
+    // The head rewritten just before the `fsync` under test, then the rest of
+    // the file as the first `fsync` left it.
+    let mut wanted: HashMap<Vec<u8>, String> = (0..8)
+        .map(|page| (5, page))
+        .chain((8..32).map(|page| (4, page)))
+        .map(|(tag, page)| {
+            let bytes = (0..PAGE).map(|i| byte_at(tag, page, i)).collect();
+            (bytes, format!("page {page}"))
+        })
+        .collect();
+
+    let Ok(mut f) = fs::File::open(image) else {
+        return vec![format!("the image {} could not be opened", image.display())];
+    };
+    let mut block = vec![0u8; PAGE];
+    while !wanted.is_empty() && f.read_exact(&mut block).is_ok() {
+        wanted.remove(&block);
+    }
+    let mut missing: Vec<String> = wanted.into_values().collect();
+    missing.sort();
+    missing
+}
+
 fn read_superblock(image: &Path, block: u64) -> Result<bcachefs::Superblock, String> {
     use std::io::{Read, Seek, SeekFrom};
     let mut f = fs::File::open(image).map_err(|e| format!("open {}: {e}", image.display()))?;
@@ -11703,6 +12143,119 @@ fn input_events_run(
     (result, sent.get())
 }
 
+/// A virtio keyboard, mouse and tablet, and nothing else that types or points.
+///
+/// Each device is checked through the one thing only it can produce: a word
+/// typed on the only keyboard, a relative move on the only relative pointer,
+/// and an absolute position on the only tablet — landed on exactly, because
+/// QEMU's tablet declares the compositor's own 0..32767 and anything but the
+/// identity scale would move it. The click that closes the client goes through
+/// whichever of the two pointers QEMU gives buttons to, and either is right.
+fn virtio_input(
+    test_config: &Path,
+    c_bins: &[(String, Vec<u8>)],
+    rust_bins: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    const DX: i32 = 40;
+    const DY: i32 = -30;
+    const AT: (u16, u16) = (20000, 9000);
+
+    let options = BootOptions {
+        profile: qemu::Profile::VirtioInput,
+        qmp: true,
+        // Without it QEMU may hand the injected keystrokes to the PS/2
+        // keyboard, and the virtio one is never what typed.
+        i8042: false,
+        ..Default::default()
+    };
+    let argv = qemu::profile_argv(&options);
+    if let Some(hid) = usb_argv(&argv).into_iter().find(|d| !d.starts_with("usb-storage")) {
+        return Err(format!("a USB device that is not the boot stick is on the machine: {hid}"));
+    }
+
+    let mut qemu = QemuInstance::boot_with_options(test_config, c_bins, rust_bins, options);
+    let boot = qemu.boot_log().to_string();
+    let Some((scale_x, scale_y)) = parse_rel_scale(&boot) else {
+        return Err(format!("the kernel never said what pointer scale it used:\n{boot}"));
+    };
+
+    let result = qemu.run_test_hooked(
+        "test_rs_input_events",
+        Duration::from_secs(60),
+        "===INPUT_READY===",
+        move |socket| {
+            let mut input = qemu::QmpInput::open(socket);
+            input.tablet(AT.0 as i32, AT.1 as i32);
+            thread::sleep(Duration::from_millis(100));
+            input.mouse(DX, DY, None);
+            thread::sleep(Duration::from_millis(100));
+            for key in ["h", "e", "l", "l", "o"] {
+                input.keys(&[(key, true), (key, false)]);
+                thread::sleep(Duration::from_millis(20));
+            }
+            thread::sleep(Duration::from_millis(200));
+            input_events_end(&mut input);
+        },
+    );
+    if let Some(err) = &result.error {
+        return Err(format!("{err}\n{}\n{}", result.serial, result.stdout));
+    }
+    let log = format!("{boot}{}", result.serial);
+    for bad in ["PANIC:", "panicked at", "NOT BOUND", "dropped events"] {
+        if log.contains(bad) {
+            return Err(format!("{bad:?} on the virtio-input machine\n{log}"));
+        }
+    }
+    for want in [
+        "ready as a keyboard",
+        "ready as a mouse (pointer",
+        "ready as a tablet (pointer",
+        "x 0..32767, y 0..32767",
+        "virtio-input: 3 of 3 device(s) bound",
+    ] {
+        if !log.contains(want) {
+            return Err(format!("no virtio-input line said {want:?}\n{log}"));
+        }
+    }
+
+    let typed: String = parse_key_events(&result.stdout)
+        .iter()
+        .filter(|e| e.modifiers & 0x10 == 0)
+        .map(|e| e.translated.as_str())
+        .collect();
+    if !typed.contains("hello") {
+        return Err(format!(
+            "typed {typed:?}, want \"hello\" — the only keyboard is virtio's\n{}",
+            result.stdout
+        ));
+    }
+    let pointer = parse_mouse_events(&result.stdout);
+    if !pointer.iter().any(|e| (e.x, e.y) == AT) {
+        return Err(format!(
+            "no pointer event at {AT:?} — the tablet's position was scaled, or never arrived\n{}",
+            result.stdout
+        ));
+    }
+    let want = (DX * scale_x, DY * scale_y);
+    let moved = pointer
+        .windows(2)
+        .any(|w| (w[1].x as i32 - w[0].x as i32, w[1].y as i32 - w[0].y as i32) == want);
+    if !moved {
+        return Err(format!(
+            "no pointer event moved by {want:?} — the tablet is absolute, so only the virtio \
+             mouse could have\n{}",
+            result.stdout
+        ));
+    }
+    serial::Serial::named("boot console", boot.as_str()).must_be_clean()?;
+
+    eprintln!(
+        "  [virtio-input] typed {typed:?}, the tablet landed on {AT:?} and the mouse moved by \
+         {want:?}"
+    );
+    Ok(())
+}
+
 /// The per-axis relative-pointer scale out of `mouse: rel scale x=64 y=64`.
 ///
 /// Read from the kernel rather than restated here: `kernel/src/mouse.rs`
@@ -0,0 +1,24 @@
+//! The Linux errnos an `Rlerror` carries that a caller answers differently.
+//!
+//! 9P2000.L's errors are the host's `errno` values, passed through. These are
+//! x86-64 Linux's, which is what QEMU on the hosts this project runs on sends;
+//! anything not named here is an error the caller has no better word for than
+//! "the host could not".
+
+pub const EPERM: u32 = 1;
+pub const ENOENT: u32 = 2;
+pub const EIO: u32 = 5;
+pub const EACCES: u32 = 13;
+pub const EEXIST: u32 = 17;
+pub const ENOTDIR: u32 = 20;
+pub const EISDIR: u32 = 21;
+pub const EINVAL: u32 = 22;
+pub const EFBIG: u32 = 27;
+pub const ENOSPC: u32 = 28;
+pub const EROFS: u32 = 30;
+pub const ENAMETOOLONG: u32 = 36;
+pub const ENOSYS: u32 = 38;
+pub const ENOTEMPTY: u32 = 39;
+pub const ELOOP: u32 = 40;
+pub const EOPNOTSUPP: u32 = 95;
+pub const EDQUOT: u32 = 122;
@@ -0,0 +1,57 @@
+use core::fmt;
+
+/// Everything that can go wrong, as data rather than a panic.
+///
+/// Exhaustive on purpose, as `toyos_iso9660::Error` is: an adapter mapping
+/// these to `SyscallError` should stop compiling when a new one appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The server answered with `Rlerror`, carrying this Linux errno. The one
+    /// variant that is the server working rather than failing.
+    Server(u32),
+    /// The request does not fit the buffer it was to be written into, which
+    /// is `msize` for every request the kernel sends.
+    TooLong,
+    /// A `Twalk` of more than [`MAX_WELEM`](crate::MAX_WELEM) names.
+    TooManyNames,
+    /// The reply is shorter than its own fields: a size past the bytes that
+    /// arrived, or a string or a count that runs past the end.
+    Truncated,
+    /// Bytes past the last field of a reply whose length the protocol fixes.
+    Trailing,
+    /// A reply of a type that answers some other request.
+    UnexpectedType { wanted: u8, got: u8 },
+    /// A reply carrying a tag other than the request's.
+    WrongTag { wanted: u16, got: u16 },
+    /// A count past what the request asked for: bytes read or written, names
+    /// walked, or directory bytes returned.
+    CountPastRequest,
+    /// A string that is not UTF-8.
+    BadString,
+    /// A directory entry whose name is not one name: empty, `.`, `..`, or
+    /// holding a `/` or a NUL.
+    BadName,
+    /// `Rversion` named a protocol other than 9P2000.L, or an `msize` larger
+    /// than the one offered or too small for a page.
+    Version,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Server(errno) => write!(f, "server error {errno}"),
+            Self::TooLong => f.write_str("request larger than msize"),
+            Self::TooManyNames => f.write_str("walk of more names than one Twalk carries"),
+            Self::Truncated => f.write_str("reply shorter than its fields"),
+            Self::Trailing => f.write_str("bytes past the end of the reply"),
+            Self::UnexpectedType { wanted, got } => {
+                write!(f, "reply type {got} where {wanted} or Rlerror was expected")
+            }
+            Self::WrongTag { wanted, got } => write!(f, "reply tag {got} for request tag {wanted}"),
+            Self::CountPastRequest => f.write_str("reply counts more than was asked for"),
+            Self::BadString => f.write_str("string is not UTF-8"),
+            Self::BadName => f.write_str("directory entry name is not a single name"),
+            Self::Version => f.write_str("server does not speak 9P2000.L at a usable msize"),
+        }
+    }
+}
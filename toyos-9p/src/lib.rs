@@ -0,0 +1,91 @@
+//! 9P2000.L messages: requests encoded, replies parsed and refused.
+//!
+//! The protocol QEMU's `-virtfs` speaks over a virtio transport, and the one
+//! the dev loop shares `target/x86_64-unknown-toyos/` into the guest with. A
+//! request is a size, a type, a tag and fields; a reply is the same shape going
+//! the other way. This crate writes the first and reads the second, and that is
+//! all it does — fids, the session and the transport are the kernel's
+//! (`kernel/src/ninep_adapter.rs` and `kernel/src/drivers/virtio_9p.rs`).
+//!
+//! # Every reply is untrusted
+//!
+//! The server is a host process that reads a directory the guest does not
+//! control, and a reply is bytes it wrote into guest memory. So a reply is
+//! parsed the way `toyos-iso9660` parses a disc: no path that touches its bytes
+//! may panic, and every failure is an [`Error`]. The hazards particular to this
+//! format, and what closes each:
+//!
+//! - **Lengths are fields.** The message size, every string's length and
+//!   `Rread`'s and `Rreaddir`'s counts are the server's numbers. Each is held
+//!   as an [`Untrusted`](toyos_untrusted::Untrusted) until it has been compared
+//!   with the bytes that actually arrived, and one that claims more is
+//!   [`Error::Truncated`].
+//! - **Counts answer a question.** `Rread`, `Rwrite` and `Rreaddir` say how
+//!   many bytes they moved and `Rwalk` how many names it walked, and none may
+//!   say more than the request asked for — a write acknowledged past the bytes
+//!   sent would make the caller believe data reached the host that never left
+//!   the guest. [`parse`] takes the request so it can hold each to it.
+//! - **Names are paths.** A directory entry named `a/b`, or one with a NUL in
+//!   it, would be a path into somewhere else once the kernel joins it to its
+//!   parent. [`Entries`] refuses each with [`Error::BadName`] rather than
+//!   skipping it, because a server writing such names is not one whose other
+//!   entries are worth believing. `.` and `..` are skipped: every Linux
+//!   directory has them, and they are never a child.
+//! - **A reply must be the one asked for.** The type has to be the request's
+//!   plus one, or `Rlerror`, and the tag has to be the request's. Anything else
+//!   is a server answering some other question.
+//!
+//! What is *not* checked is data: a file's size, mode and times are what the
+//! host says they are, and a wrong one is a wrong file rather than a hazard to
+//! this side. A size the file does not have is met by a read that comes back
+//! short, which is the ordinary end of a file.
+//!
+//! # What this crate does not do
+//!
+//! - **No allocation.** Requests are written into a caller's buffer and
+//!   replies are borrowed out of one, so the kernel decides where both live —
+//!   DMA memory, for a virtio transport.
+//! - **No legacy 9P2000 or 9P2000.u.** A server that will not speak 9P2000.L
+//!   is refused at [`Reply::Version`].
+//! - **Not the whole of 9P2000.L.** Locks, extended attributes, hard links,
+//!   `mknod` and `statfs` have no request here, because nothing in the kernel
+//!   would send one.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+mod error;
+mod message;
+mod stat;
+mod wire;
+
+pub mod errno;
+
+pub use error::Error;
+pub use message::{parse, Reply, Request};
+pub use stat::{Attr, DirEntry, Entries, Qid, SetAttr};
+
+/// The only protocol this crate speaks.
+pub const VERSION: &str = "9P2000.L";
+
+/// The tag `Tversion` is sent with, which is the one tag no other request may
+/// use.
+pub const NOTAG: u16 = 0xFFFF;
+
+/// "No fid": the `afid` of an attach that does not authenticate.
+pub const NOFID: u32 = 0xFFFF_FFFF;
+
+/// The most names one `Twalk` may carry. A longer path is walked in steps.
+pub const MAX_WELEM: usize = 16;
+
+/// `size[4] type[1] tag[2]`, in front of every message.
+pub const HEADER: usize = 7;
+
+/// How much of a message an `Rread` or a `Twrite` spends on everything but
+/// data: the header, a fid, an offset and a count. What is left of `msize` is
+/// the most one of them moves.
+pub const IOHDR: usize = 24;
+
+/// The smallest `msize` a client may accept: one page of data in one message,
+/// which is the unit every caller of the kernel's adapter works in.
+pub const MIN_MSIZE: u32 = 4096 + IOHDR as u32;
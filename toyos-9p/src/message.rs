@@ -0,0 +1,293 @@
+//! The requests the kernel sends and the replies it accepts.
+
+use toyos_untrusted::Untrusted;
+
+use crate::stat::{Attr, Entries, Qid, SetAttr};
+use crate::wire::{Reader, Writer};
+use crate::{Error, MAX_WELEM, MIN_MSIZE, VERSION};
+
+const RLERROR: u8 = 7;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TSYMLINK: u8 = 16;
+const TREADLINK: u8 = 22;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+/// One request, borrowing whatever it names.
+///
+/// Fids are the caller's: this crate neither hands them out nor remembers
+/// which are in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request<'a> {
+    Version { msize: u32 },
+    Attach { fid: u32, uname: &'a str, aname: &'a str, uid: u32 },
+    /// `fid` to `newfid` by way of `names`, which may be empty — a clone.
+    Walk { fid: u32, newfid: u32, names: &'a [&'a str] },
+    /// `flags` are Linux `open(2)` flags.
+    Lopen { fid: u32, flags: u32 },
+    /// Create `name` in directory `fid` and open it; `fid` becomes the file.
+    Lcreate { fid: u32, name: &'a str, flags: u32, mode: u32, gid: u32 },
+    Read { fid: u32, offset: u64, count: u32 },
+    Write { fid: u32, offset: u64, data: &'a [u8] },
+    Clunk { fid: u32 },
+    Readdir { fid: u32, offset: u64, count: u32 },
+    Getattr { fid: u32, mask: u64 },
+    Setattr { fid: u32, attr: SetAttr },
+    /// `flags` is `AT_REMOVEDIR` (0x200) for a directory, else 0.
+    Unlinkat { dirfid: u32, name: &'a str, flags: u32 },
+    Renameat { olddirfid: u32, oldname: &'a str, newdirfid: u32, newname: &'a str },
+    Mkdir { dirfid: u32, name: &'a str, mode: u32, gid: u32 },
+    Symlink { dirfid: u32, name: &'a str, target: &'a str, gid: u32 },
+    Readlink { fid: u32 },
+    Fsync { fid: u32 },
+}
+
+impl Request<'_> {
+    /// The `T` type byte. Its reply's is one more.
+    pub fn kind(&self) -> u8 {
+        match self {
+            Self::Version { .. } => TVERSION,
+            Self::Attach { .. } => TATTACH,
+            Self::Walk { .. } => TWALK,
+            Self::Lopen { .. } => TLOPEN,
+            Self::Lcreate { .. } => TLCREATE,
+            Self::Read { .. } => TREAD,
+            Self::Write { .. } => TWRITE,
+            Self::Clunk { .. } => TCLUNK,
+            Self::Readdir { .. } => TREADDIR,
+            Self::Getattr { .. } => TGETATTR,
+            Self::Setattr { .. } => TSETATTR,
+            Self::Unlinkat { .. } => TUNLINKAT,
+            Self::Renameat { .. } => TRENAMEAT,
+            Self::Mkdir { .. } => TMKDIR,
+            Self::Symlink { .. } => TSYMLINK,
+            Self::Readlink { .. } => TREADLINK,
+            Self::Fsync { .. } => TFSYNC,
+        }
+    }
+
+    /// Write the whole message, header included, into `out`, and answer how
+    /// many bytes it is. `out` is `msize` long for every request the kernel
+    /// sends, so [`Error::TooLong`] is a request the server would refuse.
+    pub fn encode(&self, tag: u16, out: &mut [u8]) -> Result<usize, Error> {
+        let mut w = Writer::new(out);
+        w.u32(0)?;
+        w.u8(self.kind())?;
+        w.u16(tag)?;
+        match *self {
+            Self::Version { msize } => {
+                w.u32(msize)?;
+                w.str(VERSION)?;
+            }
+            Self::Attach { fid, uname, aname, uid } => {
+                w.u32(fid)?;
+                w.u32(crate::NOFID)?;
+                w.str(uname)?;
+                w.str(aname)?;
+                w.u32(uid)?;
+            }
+            Self::Walk { fid, newfid, names } => {
+                if names.len() > MAX_WELEM {
+                    return Err(Error::TooManyNames);
+                }
+                w.u32(fid)?;
+                w.u32(newfid)?;
+                // Exact: at most `MAX_WELEM`.
+                w.u16(names.len() as u16)?;
+                for name in names {
+                    w.str(name)?;
+                }
+            }
+            Self::Lopen { fid, flags } => {
+                w.u32(fid)?;
+                w.u32(flags)?;
+            }
+            Self::Lcreate { fid, name, flags, mode, gid } => {
+                w.u32(fid)?;
+                w.str(name)?;
+                w.u32(flags)?;
+                w.u32(mode)?;
+                w.u32(gid)?;
+            }
+            Self::Read { fid, offset, count } | Self::Readdir { fid, offset, count } => {
+                w.u32(fid)?;
+                w.u64(offset)?;
+                w.u32(count)?;
+            }
+            Self::Write { fid, offset, data } => {
+                let count = u32::try_from(data.len()).map_err(|_| Error::TooLong)?;
+                w.u32(fid)?;
+                w.u64(offset)?;
+                w.u32(count)?;
+                w.bytes(data)?;
+            }
+            Self::Clunk { fid } | Self::Readlink { fid } => w.u32(fid)?,
+            Self::Getattr { fid, mask } => {
+                w.u32(fid)?;
+                w.u64(mask)?;
+            }
+            Self::Setattr { fid, attr } => {
+                w.u32(fid)?;
+                attr.write(&mut w)?;
+            }
+            Self::Unlinkat { dirfid, name, flags } => {
+                w.u32(dirfid)?;
+                w.str(name)?;
+                w.u32(flags)?;
+            }
+            Self::Renameat { olddirfid, oldname, newdirfid, newname } => {
+                w.u32(olddirfid)?;
+                w.str(oldname)?;
+                w.u32(newdirfid)?;
+                w.str(newname)?;
+            }
+            Self::Mkdir { dirfid, name, mode, gid } => {
+                w.u32(dirfid)?;
+                w.str(name)?;
+                w.u32(mode)?;
+                w.u32(gid)?;
+            }
+            Self::Symlink { dirfid, name, target, gid } => {
+                w.u32(dirfid)?;
+                w.str(name)?;
+                w.str(target)?;
+                w.u32(gid)?;
+            }
+            Self::Fsync { fid } => {
+                // `datasync`: 0, so the metadata goes to the host's disk too.
+                w.u32(fid)?;
+                w.u32(0)?;
+            }
+        }
+        let len = w.len();
+        let size = u32::try_from(len).map_err(|_| Error::TooLong)?;
+        w.patch_u32(0, size)?;
+        Ok(len)
+    }
+}
+
+/// A reply, borrowing out of the buffer it was parsed from.
+#[derive(Debug, Clone)]
+pub enum Reply<'a> {
+    /// The `msize` both sides will use: never more than the one offered, and
+    /// never less than [`MIN_MSIZE`].
+    Version { msize: u32 },
+    Attach(Qid),
+    /// How many names of the request were walked, and the qid of the last. A
+    /// walk that stopped short leaves `newfid` unassigned.
+    Walk { walked: usize, last: Option<Qid> },
+    Lopen { qid: Qid, iounit: u32 },
+    Lcreate { qid: Qid, iounit: u32 },
+    /// No more than the request asked for; fewer at the end of the file.
+    Read(&'a [u8]),
+    /// Bytes the server took, no more than were sent.
+    Write(u32),
+    Clunk,
+    Readdir(Entries<'a>),
+    Getattr(Attr),
+    Setattr,
+    Unlinkat,
+    Renameat,
+    Mkdir(Qid),
+    Symlink(Qid),
+    Readlink(&'a str),
+    Fsync,
+}
+
+/// Parse the reply to `request`, sent with `tag`, out of `bytes` — everything
+/// the transport received, which may be more than the message.
+///
+/// `Rlerror` is [`Error::Server`], so `Ok` is always the reply the request
+/// asked for.
+pub fn parse<'a>(request: &Request<'_>, tag: u16, bytes: &'a [u8]) -> Result<Reply<'a>, Error> {
+    let mut head = Reader::new(bytes);
+    let size = Untrusted::new(head.u32()?);
+    // Held to what arrived before anything past the header is read: the
+    // message is the first `size` bytes, and whatever follows is the rest of a
+    // buffer the transport was given, not part of the reply.
+    let size = size.at_most(bytes.len() as u64).map_err(|_| Error::Truncated)?;
+    let message = bytes.get(..size as usize).ok_or(Error::Truncated)?;
+    let mut r = Reader::new(message);
+    r.take(4)?;
+    let kind = r.u8()?;
+    let got = r.u16()?;
+    if got != tag {
+        return Err(Error::WrongTag { wanted: tag, got });
+    }
+    if kind == RLERROR {
+        let errno = r.u32()?;
+        r.finish()?;
+        return Err(Error::Server(errno));
+    }
+    let wanted = request.kind() + 1;
+    if kind != wanted {
+        return Err(Error::UnexpectedType { wanted, got: kind });
+    }
+    let reply = body(request, &mut r)?;
+    r.finish()?;
+    Ok(reply)
+}
+
+fn body<'a>(request: &Request<'_>, r: &mut Reader<'a>) -> Result<Reply<'a>, Error> {
+    Ok(match *request {
+        Request::Version { msize } => {
+            let offered = r.u32()?;
+            let version = r.str()?;
+            // A server may lower `msize` and may not raise it: the buffers
+            // this side posted are the size it offered.
+            if version != VERSION || offered > msize || offered < MIN_MSIZE {
+                return Err(Error::Version);
+            }
+            Reply::Version { msize: offered }
+        }
+        Request::Attach { .. } => Reply::Attach(Qid::read(r)?),
+        Request::Walk { names, .. } => {
+            let walked = Untrusted::new(r.u16()?);
+            let walked = walked.at_most(names.len() as u64).map_err(|_| Error::CountPastRequest)? as usize;
+            let mut last = None;
+            for _ in 0..walked {
+                last = Some(Qid::read(r)?);
+            }
+            Reply::Walk { walked, last }
+        }
+        Request::Lopen { .. } => Reply::Lopen { qid: Qid::read(r)?, iounit: r.u32()? },
+        Request::Lcreate { .. } => Reply::Lcreate { qid: Qid::read(r)?, iounit: r.u32()? },
+        Request::Read { count, .. } => Reply::Read(counted(r, count)?),
+        Request::Readdir { count, .. } => Reply::Readdir(Entries::new(counted(r, count)?)),
+        Request::Write { data, .. } => {
+            let written = Untrusted::new(r.u32()?);
+            let written = written.at_most(data.len() as u64).map_err(|_| Error::CountPastRequest)?;
+            // Exact: no more than `data.len()`, which `encode` proved fits.
+            Reply::Write(written as u32)
+        }
+        Request::Clunk { .. } => Reply::Clunk,
+        Request::Getattr { .. } => Reply::Getattr(Attr::read(r)?),
+        Request::Setattr { .. } => Reply::Setattr,
+        Request::Unlinkat { .. } => Reply::Unlinkat,
+        Request::Renameat { .. } => Reply::Renameat,
+        Request::Mkdir { .. } => Reply::Mkdir(Qid::read(r)?),
+        Request::Symlink { .. } => Reply::Symlink(Qid::read(r)?),
+        Request::Readlink { .. } => Reply::Readlink(r.str()?),
+        Request::Fsync { .. } => Reply::Fsync,
+    })
+}
+
+/// `count[4]` and that many bytes, held first to what was asked for and then
+/// to what arrived.
+fn counted<'a>(r: &mut Reader<'a>, asked: u32) -> Result<&'a [u8], Error> {
+    let count = Untrusted::new(r.u32()?);
+    count.at_most(asked as u64).map_err(|_| Error::CountPastRequest)?;
+    r.counted(count)
+}
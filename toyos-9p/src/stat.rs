@@ -0,0 +1,242 @@
+//! What a server says about a file: its qid, its attributes, and the entries
+//! of a directory.
+
+use toyos_untrusted::Untrusted;
+
+use crate::wire::{Reader, Writer};
+use crate::Error;
+
+/// The server's name for a file: what it is, and a number that stays the same
+/// for as long as the file does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Qid {
+    pub kind: u8,
+    pub version: u32,
+    pub path: u64,
+}
+
+impl Qid {
+    pub const BYTES: usize = 13;
+    pub const DIR: u8 = 0x80;
+    pub const SYMLINK: u8 = 0x02;
+
+    pub(crate) fn read(r: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(Self { kind: r.u8()?, version: r.u32()?, path: r.u64()? })
+    }
+
+    pub fn is_dir(self) -> bool {
+        self.kind & Self::DIR != 0
+    }
+
+    pub fn is_symlink(self) -> bool {
+        self.kind & Self::SYMLINK != 0
+    }
+}
+
+/// `Rgetattr`'s body, less the fields nothing here reads: birth time,
+/// generation and data version are parsed past and dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attr {
+    /// Which of the fields below the server filled in — a subset of the mask
+    /// asked for. A field whose bit is clear holds whatever the server wrote.
+    pub valid: u64,
+    pub qid: Qid,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u64,
+    pub size: u64,
+    pub blocks: u64,
+    pub atime: (u64, u64),
+    pub mtime: (u64, u64),
+    pub ctime: (u64, u64),
+}
+
+impl Attr {
+    /// The fields a `Tgetattr` of [`Attr::BASIC`] asks for.
+    pub const MODE: u64 = 0x0001;
+    pub const NLINK: u64 = 0x0002;
+    pub const UID: u64 = 0x0004;
+    pub const GID: u64 = 0x0008;
+    pub const RDEV: u64 = 0x0010;
+    pub const ATIME: u64 = 0x0020;
+    pub const MTIME: u64 = 0x0040;
+    pub const CTIME: u64 = 0x0080;
+    pub const INO: u64 = 0x0100;
+    pub const SIZE: u64 = 0x0200;
+    pub const BLOCKS: u64 = 0x0400;
+    /// Everything `stat(2)` returns.
+    pub const BASIC: u64 = 0x07FF;
+
+    /// `S_IFMT` and the three kinds of file the kernel tells apart.
+    pub const S_IFMT: u32 = 0o170_000;
+    pub const S_IFDIR: u32 = 0o040_000;
+    pub const S_IFREG: u32 = 0o100_000;
+    pub const S_IFLNK: u32 = 0o120_000;
+
+    /// The fixed length of the body, and so of every `Rgetattr` but its header.
+    pub const BYTES: usize = 8 + Qid::BYTES + 4 * 3 + 8 * 5 + 8 * 8 + 8 * 2;
+
+    pub(crate) fn read(r: &mut Reader<'_>) -> Result<Self, Error> {
+        let valid = r.u64()?;
+        let qid = Qid::read(r)?;
+        let (mode, uid, gid) = (r.u32()?, r.u32()?, r.u32()?);
+        let nlink = r.u64()?;
+        let _rdev = r.u64()?;
+        let size = r.u64()?;
+        let _blksize = r.u64()?;
+        let blocks = r.u64()?;
+        let atime = (r.u64()?, r.u64()?);
+        let mtime = (r.u64()?, r.u64()?);
+        let ctime = (r.u64()?, r.u64()?);
+        let _btime = (r.u64()?, r.u64()?);
+        let _gen = r.u64()?;
+        let _data_version = r.u64()?;
+        Ok(Self { valid, qid, mode, uid, gid, nlink, size, blocks, atime, mtime, ctime })
+    }
+
+    /// Whether the server answered for every field in `mask`.
+    pub fn has(&self, mask: u64) -> bool {
+        self.valid & mask == mask
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & Self::S_IFMT == Self::S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.mode & Self::S_IFMT == Self::S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & Self::S_IFMT == Self::S_IFLNK
+    }
+
+    /// Modification time in nanoseconds since the Unix epoch, held at the edge
+    /// rather than wrapped for a date a `u64` of nanoseconds cannot reach.
+    pub fn mtime_nanos(&self) -> u64 {
+        self.mtime.0.saturating_mul(1_000_000_000).saturating_add(self.mtime.1)
+    }
+}
+
+/// `Tsetattr`'s body: which fields to change, and what to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SetAttr {
+    pub valid: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: (u64, u64),
+    pub mtime: (u64, u64),
+}
+
+impl SetAttr {
+    pub const MODE: u32 = 0x0001;
+    pub const UID: u32 = 0x0002;
+    pub const GID: u32 = 0x0004;
+    pub const SIZE: u32 = 0x0008;
+    /// Set the time to the server's own clock.
+    pub const ATIME: u32 = 0x0010;
+    pub const MTIME: u32 = 0x0020;
+    pub const CTIME: u32 = 0x0040;
+    /// Set the time to the one given rather than the server's clock.
+    pub const ATIME_SET: u32 = 0x0080;
+    pub const MTIME_SET: u32 = 0x0100;
+
+    /// Truncate or extend to `size`, and stamp the modification time with the
+    /// host's clock — the guest's is seconds since boot, and a file in the
+    /// host's tree dated 1970 would be older than the source it was built from.
+    pub fn size(size: u64) -> Self {
+        Self { valid: Self::SIZE | Self::MTIME | Self::CTIME, size, ..Self::default() }
+    }
+
+    pub(crate) fn write(&self, w: &mut Writer<'_>) -> Result<(), Error> {
+        w.u32(self.valid)?;
+        w.u32(self.mode)?;
+        w.u32(self.uid)?;
+        w.u32(self.gid)?;
+        w.u64(self.size)?;
+        w.u64(self.atime.0)?;
+        w.u64(self.atime.1)?;
+        w.u64(self.mtime.0)?;
+        w.u64(self.mtime.1)
+    }
+}
+
+/// One entry of an `Rreaddir`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirEntry<'a> {
+    pub qid: Qid,
+    /// Where the next `Treaddir` of this directory starts if this is the last
+    /// entry of the reply. The server's cookie, meaningful only to it.
+    pub offset: u64,
+    /// `d_type`: 4 a directory, 8 a regular file, 10 a symbolic link.
+    pub kind: u8,
+    pub name: &'a str,
+}
+
+impl DirEntry<'_> {
+    pub const DT_DIR: u8 = 4;
+    pub const DT_REG: u8 = 8;
+    pub const DT_LNK: u8 = 10;
+}
+
+/// The entries of one `Rreaddir`, in the order the server wrote them, less
+/// `.` and `..`.
+///
+/// Those two are skipped rather than refused: a server reading a Linux
+/// directory passes them through, and they are the directory and its parent —
+/// names the kernel already has, and the two a caller joining an entry to its
+/// parent's path must never see.
+///
+/// An iterator of `Result` rather than a slice of entries, because the crate
+/// does not allocate and an entry is variable-length. It stops at the first
+/// error, which it yields once: an entry that runs past the reply leaves no
+/// way to find where the next one starts, and one with a name that is not a
+/// name says the server is not to be believed about the rest.
+#[derive(Debug, Clone)]
+pub struct Entries<'a> {
+    r: Option<Reader<'a>>,
+}
+
+impl<'a> Entries<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { r: Some(Reader::new(data)) }
+    }
+
+    fn next_entry(r: &mut Reader<'a>) -> Result<DirEntry<'a>, Error> {
+        let qid = Qid::read(r)?;
+        let offset = r.u64()?;
+        let kind = r.u8()?;
+        let len = Untrusted::new(r.u16()?);
+        let name = r.counted(len.map(u32::from))?;
+        let name = core::str::from_utf8(name).map_err(|_| Error::BadName)?;
+        if name.is_empty() || name.bytes().any(|b| b == b'/' || b == 0) {
+            return Err(Error::BadName);
+        }
+        Ok(DirEntry { qid, offset, kind, name })
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<DirEntry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let r = self.r.as_mut()?;
+        loop {
+            if r.remaining() == 0 {
+                self.r = None;
+                return None;
+            }
+            match Self::next_entry(r) {
+                Ok(entry) if entry.name == "." || entry.name == ".." => continue,
+                Ok(entry) => return Some(Ok(entry)),
+                Err(e) => {
+                    self.r = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Little-endian fields into and out of a message, without a path that panics.
+//!
+//! A [`Writer`] that runs out of room answers [`Error::TooLong`] and a
+//! [`Reader`] that runs out of bytes answers [`Error::Truncated`]; neither
+//! indexes past its slice. Every length a [`Reader`] hands back went through
+//! [`Untrusted::at_most`] against the bytes left, which is the one comparison
+//! between a server's number and a slice.
+
+use toyos_untrusted::Untrusted;
+
+use crate::Error;
+
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    at: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, at: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.at
+    }
+
+    pub fn bytes(&mut self, src: &[u8]) -> Result<(), Error> {
+        let end = self.at.checked_add(src.len()).ok_or(Error::TooLong)?;
+        self.buf.get_mut(self.at..end).ok_or(Error::TooLong)?.copy_from_slice(src);
+        self.at = end;
+        Ok(())
+    }
+
+    pub fn u8(&mut self, v: u8) -> Result<(), Error> {
+        self.bytes(&[v])
+    }
+
+    pub fn u16(&mut self, v: u16) -> Result<(), Error> {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, v: u32) -> Result<(), Error> {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    pub fn u64(&mut self, v: u64) -> Result<(), Error> {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    /// `len[2]` and the bytes. A string longer than a `u16` can count is a
+    /// request that does not fit any message.
+    pub fn str(&mut self, s: &str) -> Result<(), Error> {
+        let len = u16::try_from(s.len()).map_err(|_| Error::TooLong)?;
+        self.u16(len)?;
+        self.bytes(s.as_bytes())
+    }
+
+    /// Write `v` over four bytes already written at `at` — the size field,
+    /// which is known only once everything after it is.
+    pub fn patch_u32(&mut self, at: usize, v: u32) -> Result<(), Error> {
+        let end = at.checked_add(4).ok_or(Error::TooLong)?;
+        self.buf.get_mut(at..end).ok_or(Error::TooLong)?.copy_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, at: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.at
+    }
+
+    /// `n` bytes, where `n` is this side's own number — a field width.
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let end = self.at.checked_add(n).ok_or(Error::Truncated)?;
+        let out = self.buf.get(self.at..end).ok_or(Error::Truncated)?;
+        self.at = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Error> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    /// Bytes whose count is the server's: held to what is left of the reply.
+    pub fn counted(&mut self, count: Untrusted<u32>) -> Result<&'a [u8], Error> {
+        let n = count.at_most(self.remaining() as u64).map_err(|_| Error::Truncated)?;
+        // Exact: `at_most` proved it is no more than a `usize`'s remaining.
+        self.take(n as usize)
+    }
+
+    /// `len[2]` and that many bytes of UTF-8.
+    pub fn str(&mut self) -> Result<&'a str, Error> {
+        let len = Untrusted::new(self.u16()?);
+        let n = len.at_most(self.remaining() as u64).map_err(|_| Error::Truncated)?;
+        let bytes = self.take(n as usize)?;
+        core::str::from_utf8(bytes).map_err(|_| Error::BadString)
+    }
+
+    /// The end of a reply whose every field has been read. A byte left over is
+    /// a reply of some other shape.
+    pub fn finish(&self) -> Result<(), Error> {
+        if self.remaining() == 0 {
+            Ok(())
+        } else {
+            Err(Error::Trailing)
+        }
+    }
+}
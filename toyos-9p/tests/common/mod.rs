@@ -0,0 +1,64 @@
+//! Replies built by hand, the way a server writes them.
+
+#![allow(dead_code)]
+
+pub const TAG: u16 = 7;
+
+/// A whole message: size, type, tag and `body`.
+pub fn message(kind: u8, tag: u16, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(7 + body.len() as u32).to_le_bytes());
+    out.push(kind);
+    out.extend_from_slice(&tag.to_le_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+pub fn s(text: &str) -> Vec<u8> {
+    let mut out = (text.len() as u16).to_le_bytes().to_vec();
+    out.extend_from_slice(text.as_bytes());
+    out
+}
+
+pub fn qid(kind: u8, path: u64) -> Vec<u8> {
+    let mut out = vec![kind];
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&path.to_le_bytes());
+    out
+}
+
+/// One `Rreaddir` entry.
+pub fn dirent(path: u64, offset: u64, kind: u8, name: &[u8]) -> Vec<u8> {
+    let mut out = qid(if kind == 4 { 0x80 } else { 0 }, path);
+    out.extend_from_slice(&offset.to_le_bytes());
+    out.push(kind);
+    out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    out.extend_from_slice(name);
+    out
+}
+
+/// `count[4]` and `data`.
+pub fn counted(data: &[u8]) -> Vec<u8> {
+    let mut out = (data.len() as u32).to_le_bytes().to_vec();
+    out.extend_from_slice(data);
+    out
+}
+
+/// An `Rgetattr` body for a regular file of `size` bytes, modified at
+/// `mtime` seconds.
+pub fn attr(mode: u32, size: u64, mtime: u64) -> Vec<u8> {
+    let mut out = 0x7FFu64.to_le_bytes().to_vec();
+    out.extend(qid(0, 42));
+    for v in [mode, 1000, 1000] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    // nlink, rdev, size, blksize, blocks
+    for v in [1u64, 0, size, 4096, size.div_ceil(512)] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    // atime, mtime, ctime, btime, gen, data_version
+    for v in [0u64, 0, mtime, 500, mtime, 0, 0, 0, 0, 0] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out
+}
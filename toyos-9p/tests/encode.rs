@@ -0,0 +1,117 @@
+//! Requests, byte for byte as Linux's `net/9p` writes the same messages.
+
+mod common;
+
+use common::s;
+use toyos_9p::{Error, Request, SetAttr, MAX_WELEM, NOFID, NOTAG};
+
+fn encode(request: Request<'_>, tag: u16) -> Vec<u8> {
+    let mut buf = vec![0u8; 8192];
+    let n = request.encode(tag, &mut buf).expect("fits");
+    buf.truncate(n);
+    buf
+}
+
+fn header(size: u32, kind: u8, tag: u16) -> Vec<u8> {
+    let mut out = size.to_le_bytes().to_vec();
+    out.push(kind);
+    out.extend_from_slice(&tag.to_le_bytes());
+    out
+}
+
+#[test]
+fn version_is_sent_without_a_tag() {
+    let bytes = encode(Request::Version { msize: 0x10000 }, NOTAG);
+    let mut want = header(21, 100, 0xFFFF);
+    want.extend_from_slice(&0x10000u32.to_le_bytes());
+    want.extend(s("9P2000.L"));
+    assert_eq!(bytes, want);
+}
+
+#[test]
+fn attach_carries_no_auth_fid() {
+    let bytes = encode(Request::Attach { fid: 0, uname: "root", aname: "", uid: 0 }, 1);
+    let mut want = header(27, 104, 1);
+    want.extend_from_slice(&0u32.to_le_bytes());
+    want.extend_from_slice(&NOFID.to_le_bytes());
+    want.extend(s("root"));
+    want.extend(s(""));
+    want.extend_from_slice(&0u32.to_le_bytes());
+    assert_eq!(bytes, want);
+}
+
+#[test]
+fn walk_names_each_component() {
+    let bytes = encode(Request::Walk { fid: 0, newfid: 3, names: &["debug", "hello"] }, 2);
+    let mut want = header(31, 110, 2);
+    want.extend_from_slice(&0u32.to_le_bytes());
+    want.extend_from_slice(&3u32.to_le_bytes());
+    want.extend_from_slice(&2u16.to_le_bytes());
+    want.extend(s("debug"));
+    want.extend(s("hello"));
+    assert_eq!(bytes, want);
+}
+
+#[test]
+fn walk_refuses_more_names_than_one_message_carries() {
+    let names = ["a"; MAX_WELEM + 1];
+    let mut buf = [0u8; 512];
+    assert_eq!(
+        Request::Walk { fid: 0, newfid: 1, names: &names }.encode(1, &mut buf),
+        Err(Error::TooManyNames)
+    );
+    assert!(Request::Walk { fid: 0, newfid: 1, names: &names[..MAX_WELEM] }.encode(1, &mut buf).is_ok());
+}
+
+#[test]
+fn write_counts_its_data() {
+    let bytes = encode(Request::Write { fid: 4, offset: 8192, data: b"page" }, 3);
+    let mut want = header(27, 118, 3);
+    want.extend_from_slice(&4u32.to_le_bytes());
+    want.extend_from_slice(&8192u64.to_le_bytes());
+    want.extend_from_slice(&4u32.to_le_bytes());
+    want.extend_from_slice(b"page");
+    assert_eq!(bytes, want);
+}
+
+#[test]
+fn setattr_size_stamps_with_the_hosts_clock() {
+    let bytes = encode(Request::Setattr { fid: 5, attr: SetAttr::size(100) }, 4);
+    assert_eq!(bytes.len(), 7 + 4 + 4 * 4 + 8 * 5);
+    assert_eq!(bytes[4], 26);
+    let valid = u32::from_le_bytes(bytes[11..15].try_into().unwrap());
+    assert_eq!(valid, SetAttr::SIZE | SetAttr::MTIME | SetAttr::CTIME);
+    assert_eq!(valid & (SetAttr::MTIME_SET | SetAttr::ATIME_SET), 0);
+    assert_eq!(u64::from_le_bytes(bytes[27..35].try_into().unwrap()), 100);
+}
+
+#[test]
+fn fsync_syncs_metadata_too() {
+    let bytes = encode(Request::Fsync { fid: 9 }, 5);
+    let mut want = header(15, 50, 5);
+    want.extend_from_slice(&9u32.to_le_bytes());
+    want.extend_from_slice(&0u32.to_le_bytes());
+    assert_eq!(bytes, want);
+}
+
+#[test]
+fn a_request_larger_than_the_buffer_is_refused() {
+    let data = [0u8; 4096];
+    let mut buf = [0u8; 4096 + 22];
+    assert_eq!(
+        Request::Write { fid: 1, offset: 0, data: &data }.encode(1, &mut buf),
+        Err(Error::TooLong)
+    );
+    let mut buf = [0u8; 4096 + 23];
+    assert_eq!(Request::Write { fid: 1, offset: 0, data: &data }.encode(1, &mut buf), Ok(4096 + 23));
+}
+
+#[test]
+fn a_name_longer_than_a_string_can_count_is_refused() {
+    let long = "x".repeat(70_000);
+    let mut buf = vec![0u8; 100_000];
+    assert_eq!(
+        Request::Mkdir { dirfid: 0, name: &long, mode: 0o755, gid: 0 }.encode(1, &mut buf),
+        Err(Error::TooLong)
+    );
+}
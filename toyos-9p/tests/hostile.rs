@@ -0,0 +1,240 @@
+//! A server is untrusted input, and this is where that is proved rather than
+//! claimed.
+//!
+//! Every reply here is one a server could write and QEMU never does. The
+//! assertion is always a typed error and never a panic, a slice past the
+//! buffer, or a count the caller would believe.
+
+mod common;
+
+use common::{attr, counted, dirent, message, qid, s, TAG};
+use toyos_9p::{parse, Attr, DirEntry, Error, Reply, Request, MIN_MSIZE, NOTAG};
+
+fn version_body(msize: u32, version: &str) -> Vec<u8> {
+    let mut body = msize.to_le_bytes().to_vec();
+    body.extend(s(version));
+    body
+}
+
+#[test]
+fn a_size_past_what_arrived_is_truncated() {
+    let mut bytes = message(121, TAG, &[]);
+    bytes[..4].copy_from_slice(&1000u32.to_le_bytes());
+    assert_eq!(parse(&Request::Clunk { fid: 1 }, TAG, &bytes).unwrap_err(), Error::Truncated);
+}
+
+#[test]
+fn a_size_shorter_than_the_header_is_truncated() {
+    for size in 0..7u32 {
+        let mut bytes = message(121, TAG, &[]);
+        bytes[..4].copy_from_slice(&size.to_le_bytes());
+        assert_eq!(parse(&Request::Clunk { fid: 1 }, TAG, &bytes).unwrap_err(), Error::Truncated, "{size}");
+    }
+}
+
+#[test]
+fn every_prefix_of_every_reply_is_refused() {
+    let read = Request::Read { fid: 1, offset: 0, count: 64 };
+    let readdir = Request::Readdir { fid: 1, offset: 0, count: 4096 };
+    let walk = Request::Walk { fid: 0, newfid: 1, names: &["a", "b"] };
+    let getattr = Request::Getattr { fid: 1, mask: Attr::BASIC };
+    let readlink = Request::Readlink { fid: 1 };
+    let mut walked = 2u16.to_le_bytes().to_vec();
+    walked.extend(qid(0x80, 1));
+    walked.extend(qid(0, 2));
+    let cases: [(&Request<'_>, Vec<u8>); 5] = [
+        (&read, message(117, TAG, &counted(b"hello"))),
+        (&readdir, message(41, TAG, &counted(&dirent(1, 1, 8, b"file")))),
+        (&walk, message(111, TAG, &walked)),
+        (&getattr, message(25, TAG, &attr(Attr::S_IFREG, 1, 1))),
+        (&readlink, message(23, TAG, &s("target"))),
+    ];
+    for (request, whole) in &cases {
+        assert!(parse(request, TAG, whole).is_ok());
+        for len in 0..whole.len() {
+            let mut cut = whole[..len].to_vec();
+            // Both ways a reply can come up short: a size that says so, and a
+            // size that claims the bytes that did not come.
+            if len >= 4 {
+                cut[..4].copy_from_slice(&(len as u32).to_le_bytes());
+            }
+            let reply = parse(request, TAG, &cut);
+            if let Ok(Reply::Readdir(entries)) = reply {
+                // A cut inside `count` is caught there; one inside the entry is
+                // caught by the entry.
+                assert!(entries.clone().any(|e| e.is_err()), "readdir cut at {len}");
+                continue;
+            }
+            assert!(reply.is_err(), "cut at {len} of {}", whole.len());
+            assert!(parse(request, TAG, &whole[..len]).is_err());
+        }
+    }
+}
+
+#[test]
+fn a_reply_for_another_tag_is_refused() {
+    let bytes = message(121, TAG + 1, &[]);
+    assert_eq!(
+        parse(&Request::Clunk { fid: 1 }, TAG, &bytes).unwrap_err(),
+        Error::WrongTag { wanted: TAG, got: TAG + 1 }
+    );
+}
+
+#[test]
+fn a_reply_to_another_request_is_refused() {
+    // An Rclunk where the caller sent a Tremove-shaped unlink.
+    let request = Request::Unlinkat { dirfid: 0, name: "x", flags: 0 };
+    assert_eq!(
+        parse(&request, TAG, &message(121, TAG, &[])).unwrap_err(),
+        Error::UnexpectedType { wanted: 77, got: 121 }
+    );
+}
+
+#[test]
+fn a_read_of_more_than_was_asked_is_refused() {
+    let request = Request::Read { fid: 1, offset: 0, count: 4 };
+    let bytes = message(117, TAG, &counted(b"hello"));
+    assert_eq!(parse(&request, TAG, &bytes).unwrap_err(), Error::CountPastRequest);
+}
+
+#[test]
+fn a_read_count_past_the_message_is_refused() {
+    let request = Request::Read { fid: 1, offset: 0, count: 4096 };
+    let mut body = 4096u32.to_le_bytes().to_vec();
+    body.extend_from_slice(b"short");
+    assert_eq!(parse(&request, TAG, &message(117, TAG, &body)).unwrap_err(), Error::Truncated);
+}
+
+#[test]
+fn a_write_acknowledged_past_what_was_sent_is_refused() {
+    let request = Request::Write { fid: 1, offset: 0, data: b"four" };
+    let bytes = message(119, TAG, &5u32.to_le_bytes());
+    assert_eq!(parse(&request, TAG, &bytes).unwrap_err(), Error::CountPastRequest);
+    let bytes = message(119, TAG, &4u32.to_le_bytes());
+    assert!(matches!(parse(&request, TAG, &bytes), Ok(Reply::Write(4))));
+}
+
+#[test]
+fn a_walk_of_more_names_than_were_sent_is_refused() {
+    let request = Request::Walk { fid: 0, newfid: 1, names: &["a"] };
+    let mut body = 2u16.to_le_bytes().to_vec();
+    body.extend(qid(0x80, 1));
+    body.extend(qid(0, 2));
+    assert_eq!(parse(&request, TAG, &message(111, TAG, &body)).unwrap_err(), Error::CountPastRequest);
+}
+
+#[test]
+fn a_readdir_of_more_than_was_asked_is_refused() {
+    let request = Request::Readdir { fid: 1, offset: 0, count: 8 };
+    let data = dirent(1, 1, 8, b"file");
+    assert_eq!(
+        parse(&request, TAG, &message(41, TAG, &counted(&data))).unwrap_err(),
+        Error::CountPastRequest
+    );
+}
+
+#[test]
+fn an_entry_name_that_is_a_path_is_refused() {
+    let request = Request::Readdir { fid: 1, offset: 0, count: 4096 };
+    for bad in [&b""[..], b"../etc", b"a/b", b"nul\0", b"\xff\xfe"] {
+        let mut data = dirent(1, 1, DirEntry::DT_REG, b"fine");
+        data.extend(dirent(2, 2, DirEntry::DT_REG, bad));
+        data.extend(dirent(3, 3, DirEntry::DT_REG, b"after"));
+        let bytes = message(41, TAG, &counted(&data));
+        let Ok(Reply::Readdir(mut entries)) = parse(&request, TAG, &bytes) else {
+            panic!("the reply itself is well-formed");
+        };
+        assert_eq!(entries.next().map(|e| e.map(|e| e.name)), Some(Ok("fine")));
+        assert_eq!(entries.next().map(|e| e.map(|e| e.name)), Some(Err(Error::BadName)), "{bad:?}");
+        // Nothing after: the server is not believed about the rest.
+        assert!(entries.next().is_none());
+    }
+}
+
+#[test]
+fn an_entry_name_past_its_reply_is_truncated() {
+    let request = Request::Readdir { fid: 1, offset: 0, count: 4096 };
+    let mut data = dirent(1, 1, DirEntry::DT_REG, b"file");
+    let len_at = data.len() - 4 - 2;
+    data[len_at..len_at + 2].copy_from_slice(&0xFFFFu16.to_le_bytes());
+    let bytes = message(41, TAG, &counted(&data));
+    let Ok(Reply::Readdir(mut entries)) = parse(&request, TAG, &bytes) else {
+        panic!("the reply itself is well-formed");
+    };
+    assert_eq!(entries.next(), Some(Err(Error::Truncated)));
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn a_string_that_is_not_utf8_is_refused() {
+    let mut body = 2u16.to_le_bytes().to_vec();
+    body.extend_from_slice(b"\xc3\x28");
+    assert_eq!(
+        parse(&Request::Readlink { fid: 1 }, TAG, &message(23, TAG, &body)).unwrap_err(),
+        Error::BadString
+    );
+}
+
+#[test]
+fn trailing_bytes_inside_the_message_are_refused() {
+    let bytes = message(121, TAG, &[0]);
+    assert_eq!(parse(&Request::Clunk { fid: 1 }, TAG, &bytes).unwrap_err(), Error::Trailing);
+    let mut body = 2u32.to_le_bytes().to_vec();
+    body.push(0);
+    assert_eq!(
+        parse(&Request::Clunk { fid: 1 }, TAG, &message(7, TAG, &body)).unwrap_err(),
+        Error::Trailing
+    );
+}
+
+#[test]
+fn a_version_this_side_cannot_use_is_refused() {
+    let request = Request::Version { msize: 0x10000 };
+    for (msize, version) in [
+        (0x10001, "9P2000.L"),
+        (MIN_MSIZE - 1, "9P2000.L"),
+        (0x10000, "9P2000"),
+        (0x10000, "9P2000.u"),
+        (0x10000, "unknown"),
+    ] {
+        let bytes = message(101, NOTAG, &version_body(msize, version));
+        assert_eq!(parse(&request, NOTAG, &bytes).unwrap_err(), Error::Version, "{msize} {version}");
+    }
+}
+
+#[test]
+fn noise_never_panics() {
+    // A fixed xorshift, so a failure names the bytes that caused it.
+    let mut x = 0x9E37_79B9_7F4A_7C15u64;
+    let mut next = || {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        x
+    };
+    let requests = [
+        Request::Version { msize: 0x10000 },
+        Request::Walk { fid: 0, newfid: 1, names: &["a", "b", "c"] },
+        Request::Read { fid: 1, offset: 0, count: 4096 },
+        Request::Readdir { fid: 1, offset: 0, count: 4096 },
+        Request::Getattr { fid: 1, mask: Attr::BASIC },
+        Request::Readlink { fid: 1 },
+        Request::Lopen { fid: 1, flags: 0 },
+    ];
+    for _ in 0..20_000 {
+        let len = (next() % 200) as usize;
+        let mut bytes: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+        let request = &requests[(next() % requests.len() as u64) as usize];
+        if len >= 7 {
+            // Mostly well-framed, so the parse gets past the header.
+            bytes[..4].copy_from_slice(&(len as u32).to_le_bytes());
+            bytes[4] = request.kind() + 1;
+            bytes[5..7].copy_from_slice(&TAG.to_le_bytes());
+        }
+        if let Ok(Reply::Readdir(entries)) = parse(request, TAG, &bytes) {
+            for entry in entries.flatten() {
+                assert!(!entry.name.contains('/') && entry.name != "." && entry.name != "..");
+            }
+        }
+    }
+}
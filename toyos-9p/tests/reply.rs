@@ -0,0 +1,128 @@
+//! Replies a working server writes, parsed into what they say.
+
+mod common;
+
+use common::{attr, counted, dirent, message, qid, s, TAG};
+use toyos_9p::{errno, parse, Attr, DirEntry, Error, Qid, Reply, Request, MIN_MSIZE, NOTAG};
+
+#[test]
+fn version_may_lower_msize() {
+    let request = Request::Version { msize: 0x10000 };
+    let mut body = 0x2000u32.to_le_bytes().to_vec();
+    body.extend(s("9P2000.L"));
+    let bytes = message(101, NOTAG, &body);
+    let reply = parse(&request, NOTAG, &bytes).unwrap();
+    assert!(matches!(reply, Reply::Version { msize: 0x2000 }));
+}
+
+#[test]
+fn attach_answers_the_roots_qid() {
+    let request = Request::Attach { fid: 0, uname: "root", aname: "", uid: 0 };
+    let bytes = message(105, TAG, &qid(0x80, 1));
+    let reply = parse(&request, TAG, &bytes).unwrap();
+    let Reply::Attach(q) = reply else { panic!("{reply:?}") };
+    assert!(q.is_dir());
+    assert_eq!(q.path, 1);
+}
+
+#[test]
+fn a_short_walk_names_how_far_it_got() {
+    let request = Request::Walk { fid: 0, newfid: 1, names: &["a", "b", "c"] };
+    let mut body = 1u16.to_le_bytes().to_vec();
+    body.extend(qid(0x80, 9));
+    let bytes = message(111, TAG, &body);
+    let reply = parse(&request, TAG, &bytes).unwrap();
+    let Reply::Walk { walked, last } = reply else { panic!("{reply:?}") };
+    assert_eq!(walked, 1);
+    assert_eq!(last.map(|q| q.path), Some(9));
+}
+
+#[test]
+fn read_borrows_the_data() {
+    let request = Request::Read { fid: 1, offset: 0, count: 4096 };
+    let bytes = message(117, TAG, &counted(b"\x7fELF"));
+    let reply = parse(&request, TAG, &bytes).unwrap();
+    let Reply::Read(data) = reply else { panic!("{reply:?}") };
+    assert_eq!(data, b"\x7fELF");
+}
+
+#[test]
+fn getattr_reads_every_field_it_keeps() {
+    let request = Request::Getattr { fid: 1, mask: Attr::BASIC };
+    let body = attr(Attr::S_IFREG | 0o755, 12345, 1_700_000_000);
+    assert_eq!(body.len(), Attr::BYTES);
+    let bytes = message(25, TAG, &body);
+    let reply = parse(&request, TAG, &bytes).unwrap();
+    let Reply::Getattr(a) = reply else { panic!("{reply:?}") };
+    assert!(a.is_file() && !a.is_dir() && !a.is_symlink());
+    assert!(a.has(Attr::SIZE | Attr::MODE));
+    assert_eq!(a.size, 12345);
+    assert_eq!(a.mtime, (1_700_000_000, 500));
+    assert_eq!(a.mtime_nanos(), 1_700_000_000_000_000_500);
+}
+
+#[test]
+fn readdir_skips_the_directory_and_its_parent() {
+    let request = Request::Readdir { fid: 1, offset: 0, count: 8192 };
+    let mut data = dirent(1, 1, DirEntry::DT_DIR, b".");
+    data.extend(dirent(2, 2, DirEntry::DT_DIR, b".."));
+    data.extend(dirent(3, 3, DirEntry::DT_REG, b"hello"));
+    data.extend(dirent(4, 4, DirEntry::DT_DIR, b"deps"));
+    let bytes = message(41, TAG, &counted(&data));
+    let reply = parse(&request, TAG, &bytes).unwrap();
+    let Reply::Readdir(entries) = reply else { panic!("{reply:?}") };
+    let entries: Vec<_> = entries.map(Result::unwrap).collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[0].name, entries[0].kind, entries[0].offset), ("hello", DirEntry::DT_REG, 3));
+    assert_eq!((entries[1].name, entries[1].kind), ("deps", DirEntry::DT_DIR));
+    assert!(entries[1].qid.is_dir());
+}
+
+#[test]
+fn an_empty_readdir_is_the_end_of_the_directory() {
+    let request = Request::Readdir { fid: 1, offset: 7, count: 8192 };
+    let bytes = message(41, TAG, &counted(&[]));
+    let reply = parse(&request, TAG, &bytes).unwrap();
+    let Reply::Readdir(mut entries) = reply else { panic!("{reply:?}") };
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn rlerror_is_the_servers_errno() {
+    let request = Request::Walk { fid: 0, newfid: 1, names: &["missing"] };
+    let bytes = message(7, TAG, &errno::ENOENT.to_le_bytes());
+    let reply = parse(&request, TAG, &bytes);
+    assert_eq!(reply.unwrap_err(), Error::Server(errno::ENOENT));
+}
+
+#[test]
+fn bytes_past_the_message_are_the_transports() {
+    let request = Request::Clunk { fid: 3 };
+    let mut bytes = message(121, TAG, &[]);
+    bytes.extend_from_slice(&[0xAA; 64]);
+    assert!(matches!(parse(&request, TAG, &bytes), Ok(Reply::Clunk)));
+}
+
+#[test]
+fn readlink_mkdir_and_symlink() {
+    let bytes = message(23, TAG, &s("../lib"));
+    let reply = parse(&Request::Readlink { fid: 1 }, TAG, &bytes).unwrap();
+    assert!(matches!(reply, Reply::Readlink("../lib")));
+    let mkdir = Request::Mkdir { dirfid: 0, name: "d", mode: 0o755, gid: 0 };
+    let bytes = message(73, TAG, &qid(0x80, 5));
+    let reply = parse(&mkdir, TAG, &bytes).unwrap();
+    assert!(matches!(reply, Reply::Mkdir(Qid { path: 5, .. })));
+    let symlink = Request::Symlink { dirfid: 0, name: "l", target: "t", gid: 0 };
+    let bytes = message(17, TAG, &qid(0x02, 6));
+    let reply = parse(&symlink, TAG, &bytes).unwrap();
+    let Reply::Symlink(q) = reply else { panic!("{reply:?}") };
+    assert!(q.is_symlink());
+}
+
+#[test]
+fn the_floor_is_a_page_of_data() {
+    let request = Request::Version { msize: 0x10000 };
+    let mut body = MIN_MSIZE.to_le_bytes().to_vec();
+    body.extend(s("9P2000.L"));
+    assert!(parse(&request, NOTAG, &message(101, NOTAG, &body)).is_ok());
+}
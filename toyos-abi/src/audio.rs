@@ -12,21 +12,46 @@ use core::sync::atomic::AtomicU32;
 /// mask is derived there rather than by the driver at wake time. Records are
 /// returned oldest-first.
 ///
-/// Both stubs produce it, so the two backends differ in nothing a mixer sees.
+/// Every stub produces it, so the backends differ in nothing a mixer sees
+/// except `feedback_rate`, which only a device that measures its own rate has.
+///
+/// **One record is not a completion:** a `mask` of exactly [`Self::RESET`]
+/// says the device was reset across a sleep. It comes after every record the
+/// device produced before the sleep, and nothing follows it until the driver
+/// starts the stream again.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct AudioCompletionRecord {
     pub mask: u32,
-    pub _pad: u32,
+    /// The rate the device says it is consuming frames at, in millihertz, or 0
+    /// where it says nothing. A USB DAC on its own crystal reports this on its
+    /// feedback endpoint, and it is a better period than any timestamp the
+    /// xHCI poll takes; HDA and virtio-sound run on the host's clock and
+    /// leave it 0.
+    pub feedback_rate: u32,
     pub timestamp_nanos: u64,
 }
 
 impl AudioCompletionRecord {
     pub const SIZE: usize = core::mem::size_of::<Self>();
+
+    /// The mask of the record that says the device lost its state.
+    ///
+    /// The kernel has put back what it owns. For HDA that is the codec verbs
+    /// and the stream's registers, and for virtio-sound the queues. The stream
+    /// is stopped and every period is the driver's again: a reset device
+    /// plays nothing it was given. It is a bit no period mask can have, because
+    /// every device's pipeline is eight periods deep.
+    pub const RESET: u32 = 1 << 31;
+
+    /// Whether this is the [`Self::RESET`] record rather than a completion.
+    pub fn is_reset(&self) -> bool {
+        self.mask == Self::RESET
+    }
 }
 
-/// Every byte belongs to a field. This crosses the boundary, and `_pad` is why
-/// there is no gap for whatever the kernel stack held to travel in.
+/// Every byte belongs to a field. This crosses the boundary, and `feedback_rate`
+/// sits where a gap would otherwise carry whatever the kernel stack held.
 const _: () = assert!(AudioCompletionRecord::SIZE == 4 + 4 + 8);
 
 /// Shared memory header for the client↔soundd slot-ring protocol.
@@ -96,7 +96,7 @@ impl Rights {
     ///
     /// [`SYS_LOG_READ`]: crate::syscall::SYS_LOG_READ
     pub const LOG: Rights = Rights(1 << 9);
-    /// On a `SysCap`: power the machine off.
+    /// On a `SysCap`: power the machine off, or suspend it to RAM.
     ///
     /// [`SYS_SHUTDOWN`] ends every process there is and does not come back, so
     /// it is the largest authority this capability carries — and it was the one
@@ -112,7 +112,12 @@ impl Rights {
     /// `test-runner` holds one because `run shutdown` is how the suite ends a
     /// guest and reads what reached the volume.
     ///
+    /// [`SYS_SUSPEND`] rides the same bit rather than one of its own: a
+    /// sleeping machine has stopped every process as surely as an unpowered
+    /// one, and comes back only when someone presses a key.
+    ///
     /// [`SYS_SHUTDOWN`]: crate::syscall::SYS_SHUTDOWN
+    /// [`SYS_SUSPEND`]: crate::syscall::SYS_SUSPEND
     pub const POWER: Rights = Rights(1 << 10);
     /// On a `SysCap`: read the roster of every process in the machine.
     ///
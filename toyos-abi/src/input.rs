@@ -1,4 +1,4 @@
-//! Wire-format types for HID input devices (keyboard, mouse).
+//! Wire-format types for HID input devices (keyboard, mouse, gamepad).
 //!
 //! These cross the kernel→userland boundary as reads on a device handle.
 
@@ -76,3 +76,64 @@ impl MouseEvent {
         }
     }
 }
+
+/// Axes a [`GamepadEvent`] carries: X, Y, Z, Rx, Ry, Rz, Slider and Dial, in
+/// the order HID's Generic Desktop page numbers them.
+pub const GAMEPAD_AXES: usize = 8;
+
+/// `GamepadEvent::hat` when the hat is not pressed. Pressed positions are 0
+/// (up) through 7, clockwise.
+pub const HAT_CENTERED: u8 = 8;
+
+/// `GamepadEvent::kind`: what the device's report descriptor called itself.
+/// The two report identically, and a game that binds a flight stick
+/// differently from a pad has this to go on.
+pub const PAD_GAMEPAD: u8 = 0;
+pub const PAD_JOYSTICK: u8 = 1;
+
+/// `GamepadEvent::flags`: this pad has gone, and this event is its last. Every
+/// control in it reads released, so a reader that only tracks state lets go of
+/// whatever the pad was holding.
+pub const PAD_DISCONNECTED: u8 = 1;
+
+/// One game controller's whole state, as the kernel delivers it through the
+/// gamepad handle every time a report changes it.
+///
+/// **A state and not a transition**, unlike [`RawKeyEvent`]: a stick moves on
+/// nearly every report, and a reader that missed an event to the queue's bound
+/// should still hold the pad's position after the next one.
+///
+/// `pad` numbers the controller among those attached, lowest free first, and
+/// is given back when it is unplugged — so two players are 0 and 1 for as long
+/// as both stay plugged in.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GamepadEvent {
+    /// Bit n is button n + 1 of the device's button page.
+    pub buttons: u32,
+    /// Each axis scaled to -32767..=32767, centre 0; one the device does not
+    /// have reads 0.
+    pub axes: [i16; GAMEPAD_AXES],
+    pub pad: u8,
+    pub hat: u8,
+    pub kind: u8,
+    pub flags: u8,
+}
+
+/// Every byte belongs to a field: this crosses the boundary through
+/// `as_bytes`, so a gap would publish whatever the kernel stack held.
+const _: () = assert!(core::mem::size_of::<GamepadEvent>() == 4 + 2 * GAMEPAD_AXES + 1 + 1 + 1 + 1);
+
+impl GamepadEvent {
+    pub fn disconnected(&self) -> bool { self.flags & PAD_DISCONNECTED != 0 }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `self` is a valid `&Self` (non-null, aligned, readable for
+        // `size_of::<Self>()` bytes), and the const assert above proves the
+        // `repr(C)` layout has no padding, so every byte the slice exposes is
+        // an initialized field, not a gap.
+        unsafe {
+            core::slice::from_raw_parts(self as *const Self as *const u8, core::mem::size_of::<Self>())
+        }
+    }
+}
@@ -11,9 +11,12 @@ pub mod inbox;
 pub mod input;
 pub mod log;
 pub mod net;
+pub mod power;
 pub mod ring;
 pub mod syscall;
+pub mod usb_audio;
 pub mod virtio_sound;
+pub mod vsock;
 
 pub use handle::{RawHandle, Rights, HANDLE_INVALID};
 
@@ -60,6 +63,30 @@ impl core::ops::Add for Tid {
     fn add(self, rhs: Self) -> Self { Tid(self.0 + rhs.0) }
 }
 
+/// Most monitors one display device drives. virtio-gpu's `max_outputs` is
+/// capped at 16 by its specification, and nothing else this kernel drives has
+/// more than one.
+pub const MAX_OUTPUTS: usize = 16;
+
+/// One monitor, as the rectangle of the framebuffer it scans out.
+///
+/// **Every output reads the same buffer**, so an arrangement is a set of
+/// rectangles in one coordinate space and a window straddling two monitors is
+/// one blit. The framebuffer is the bounding box of its outputs; whatever of it
+/// no output covers is memory nobody sees.
+///
+/// Also the shape of an arrangement going the other way: `SYS_GPU_ARRANGE`
+/// takes one of these per output, in the order [`FramebufferInfo::outputs`]
+/// lists them.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Output {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
 /// GPU framebuffer info passed between kernel and userland.
 /// Shared definition so both sides agree on the layout.
 ///
@@ -67,6 +94,10 @@ impl core::ops::Add for Tid {
 /// description is a set of buffers, and the process being told about them is
 /// the one that must be able to map them — which is never the process that
 /// minted the claim, because `/bin/init` mints every claim and holds none.
+///
+/// `width` and `height` are the whole framebuffer, and the first
+/// `output_count` entries of `outputs` say which parts of it are on a panel.
+/// A one-monitor display has one output covering all of it.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct FramebufferInfo {
@@ -77,13 +108,21 @@ pub struct FramebufferInfo {
     pub stride: u32,
     pub pixel_format: u32,
     pub flags: u32,
+    pub output_count: u32,
+    pub outputs: [Output; MAX_OUTPUTS],
 }
 
 /// Every byte belongs to a field: this crosses the boundary through
 /// `as_bytes`, so a gap would publish whatever the kernel stack held. Every
-/// field here is a `u32` or a `repr(transparent)` wrapper over one, so the
-/// `repr(C)` layout has no padding without needing a separate size check.
+/// field here is a `u32`, a `repr(transparent)` wrapper over one or an array of
+/// four-`u32` [`Output`]s, so the `repr(C)` layout has no padding without
+/// needing a separate size check.
 impl FramebufferInfo {
+    /// The outputs that exist, without the unused tail of the array.
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs[..(self.output_count as usize).min(MAX_OUTPUTS)]
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         // SAFETY: `self` is a valid `&Self` (non-null, aligned, readable for
         // `size_of::<Self>()` bytes) and, per the doc comment above, every
@@ -94,6 +133,97 @@ impl FramebufferInfo {
     }
 }
 
+/// Most modes one output's [`DisplayModes`] lists — the cap the kernel's EDID
+/// parser keeps, so a list is never cut short between the two.
+pub const MAX_MODES: usize = 32;
+
+/// [`DisplayMode::flags`]: the mode the monitor asks for, its native one.
+pub const MODE_PREFERRED: u32 = 1 << 0;
+/// [`DisplayMode::flags`]: the mode the output is showing now.
+pub const MODE_CURRENT: u32 = 1 << 1;
+
+/// One mode a monitor can show. `refresh_mhz` is in millihertz, because a
+/// timing's rate is a ratio and 59.94 Hz is not 60.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_mhz: u32,
+    pub flags: u32,
+}
+
+/// What one output's monitor says it can show, as `SYS_GPU_MODES` answers it:
+/// the modes from its EDID, largest first, and its physical size for the DPI.
+///
+/// A display with no EDID to read — UEFI GOP, a monitor that sends none — is
+/// described by the one mode it is showing, flagged both preferred and
+/// current, and a size of zero.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DisplayModes {
+    pub count: u32,
+    pub width_mm: u32,
+    pub height_mm: u32,
+    pub modes: [DisplayMode; MAX_MODES],
+}
+
+impl DisplayModes {
+    pub const EMPTY: Self = Self {
+        count: 0,
+        width_mm: 0,
+        height_mm: 0,
+        modes: [DisplayMode { width: 0, height: 0, refresh_mhz: 0, flags: 0 }; MAX_MODES],
+    };
+
+    /// The modes that exist, without the unused tail of the array.
+    pub fn modes(&self) -> &[DisplayMode] {
+        &self.modes[..(self.count as usize).min(MAX_MODES)]
+    }
+
+    /// Add `mode` to the end. `false`, and nothing added, when full.
+    pub fn push(&mut self, mode: DisplayMode) -> bool {
+        let Some(slot) = self.modes.get_mut(self.count as usize) else {
+            return false;
+        };
+        *slot = mode;
+        self.count += 1;
+        true
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: as for `FramebufferInfo::as_bytes` — `self` is a valid
+        // `&Self`, and every field is a `u32` or an array of all-`u32`
+        // `DisplayMode`s, so the `repr(C)` layout has no padding to expose.
+        unsafe {
+            core::slice::from_raw_parts(self as *const Self as *const u8, core::mem::size_of::<Self>())
+        }
+    }
+
+    /// The inverse of [`as_bytes`](Self::as_bytes), for a list that crossed
+    /// a pipe rather than the syscall boundary. `None` when `bytes` is short;
+    /// a `count` past [`MAX_MODES`] is not refused here, because
+    /// [`modes`](Self::modes) already reads no further than the array.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..core::mem::size_of::<Self>())?;
+        let (words, _) = bytes.as_chunks::<4>();
+        let word = |i: usize| u32::from_ne_bytes(words[i]);
+        let mut list = Self { count: word(0), width_mm: word(1), height_mm: word(2), ..Self::EMPTY };
+        for (i, mode) in list.modes.iter_mut().enumerate() {
+            let at = 3 + i * 4;
+            *mode = DisplayMode {
+                width: word(at),
+                height: word(at + 1),
+                refresh_mhz: word(at + 2),
+                flags: word(at + 3),
+            };
+        }
+        Some(list)
+    }
+}
+
+const _: () = assert!(core::mem::size_of::<DisplayModes>() == 12 + 16 * MAX_MODES);
+
 // SAFETY: FramebufferInfo is #[repr(C)] and every field is a u32 or a
 // `repr(transparent)` wrapper over one — no padding, no pointers.
 unsafe impl Sync for FramebufferInfo {}
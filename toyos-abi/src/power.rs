@@ -0,0 +1,211 @@
+//! What the machine's power controls say, as the holder of the `power` device
+//! class reads it.
+//!
+//! **Requests and not actions.** Pressing the power button used to do nothing
+//! at all; it does not now cut the power either. The kernel turns the ACPI
+//! event into a [`PowerEvent`] and queues it, and whoever holds the claim —
+//! `/bin/init` today — decides what a press means and then asks for the
+//! shutdown through `SYS_SHUTDOWN` like any other holder of
+//! [`Rights::POWER`](crate::handle::Rights::POWER). The kernel never powers off
+//! on a button, because an orderly shutdown is the one that lets every process
+//! finish first, and only userland knows which processes those are.
+//!
+//! **Readings are not events.** A battery's charge and a thermal zone's
+//! temperature are state, not presses, and are asked for with
+//! [`power_readings`](crate::syscall::power_readings) by anyone — a fact about
+//! the machine, like its CPU count, and no claim stands behind it. What the
+//! call answers is what the kernel's `acpid` last read from firmware, on a
+//! timer and whenever firmware notifies a change, so asking never waits on an
+//! embedded controller.
+
+/// The power button was pressed: a request to turn the machine off.
+pub const POWER_BUTTON: u32 = 1;
+/// The sleep button was pressed: a request to suspend.
+pub const SLEEP_BUTTON: u32 = 2;
+/// The lid moved; `state` says which way ([`LID_CLOSED`] or [`LID_OPEN`]).
+///
+/// A lid is always described in AML — its GPE has a `_Lxx` method that
+/// notifies the lid device, and its position is that device's `_LID` — so it
+/// is delivered by the kernel's `acpid` thread, after the method has run, and
+/// only on a machine whose tables the kernel's interpreter loaded.
+pub const LID: u32 = 3;
+
+/// [`PowerEvent::state`] for [`LID`].
+pub const LID_CLOSED: u32 = 0;
+pub const LID_OPEN: u32 = 1;
+
+/// One press, or one lid movement, in the order they happened.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PowerEvent {
+    /// [`POWER_BUTTON`], [`SLEEP_BUTTON`] or [`LID`]. A kind a reader does not
+    /// know is one a later kernel added, and is skipped rather than refused.
+    pub kind: u32,
+    /// Zero for a button, which has no state a press does not already say.
+    pub state: u32,
+}
+
+/// Every byte belongs to a field, for the reason
+/// [`GamepadEvent`](crate::input::GamepadEvent) gives.
+const _: () = assert!(core::mem::size_of::<PowerEvent>() == 4 + 4);
+
+impl PowerEvent {
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `self` is a valid `&Self` (non-null, aligned, readable for
+        // `size_of::<Self>()` bytes), and the const assert above proves the
+        // `repr(C)` layout has no padding, so every byte the slice exposes is
+        // an initialized field, not a gap.
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+
+    /// The event at the start of `bytes`, or `None` when fewer than a whole
+    /// event's worth are there.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let word = |at: usize| -> Option<u32> {
+            Some(u32::from_ne_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+        };
+        Some(PowerEvent { kind: word(0)?, state: word(4)? })
+    }
+}
+
+/// [`PowerReading::kind`]: a battery slot firmware describes, full or empty.
+pub const BATTERY: u32 = 1;
+/// [`PowerReading::kind`]: an ACPI thermal zone.
+pub const THERMAL_ZONE: u32 = 2;
+
+/// A field firmware did not report, reported as unknown, or could not be read.
+pub const UNKNOWN: u32 = u32::MAX;
+
+/// [`PowerReading::flags`] for a [`BATTERY`]. Without `BATTERY_PRESENT` the
+/// slot is empty and every other field is [`UNKNOWN`].
+pub const BATTERY_PRESENT: u32 = 1 << 0;
+pub const BATTERY_CHARGING: u32 = 1 << 1;
+pub const BATTERY_DISCHARGING: u32 = 1 << 2;
+/// Firmware says the charge is critically low.
+pub const BATTERY_CRITICAL: u32 = 1 << 3;
+/// Capacities are in mWh and the rate in mW; otherwise mAh and mA.
+pub const BATTERY_MILLIWATTS: u32 = 1 << 4;
+
+/// One battery or thermal zone, as firmware last described it.
+///
+/// One layout for both kinds rather than one per kind: a reader asks once and
+/// gets the machine in namespace order, and the three numbers mean the same
+/// thing in each — a level, the level that is its limit, and how fast it moves.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PowerReading {
+    /// [`BATTERY`] or [`THERMAL_ZONE`]. A kind a reader does not know is one a
+    /// later kernel added, and is skipped rather than refused.
+    pub kind: u32,
+    /// `BATTERY_*` bits for a battery; zero for a thermal zone.
+    pub flags: u32,
+    /// A battery's remaining capacity (`_BST`); a zone's temperature in tenths
+    /// of a kelvin (`_TMP`).
+    pub value: u32,
+    /// A battery's last full capacity (`_BIF` or `_BIX`); a zone's critical
+    /// trip point in tenths of a kelvin (`_CRT`).
+    pub limit: u32,
+    /// A battery's present charge or discharge rate (`_BST`); [`UNKNOWN`] for
+    /// a zone.
+    pub rate: u32,
+}
+
+const _: () = assert!(core::mem::size_of::<PowerReading>() == 5 * 4);
+
+impl PowerReading {
+    /// A reading of `kind` that says nothing but that the device is there.
+    pub const fn unknown(kind: u32) -> Self {
+        PowerReading { kind, flags: 0, value: UNKNOWN, limit: UNKNOWN, rate: UNKNOWN }
+    }
+
+    /// A battery's charge as a percentage of its last full capacity, or `None`
+    /// when either is unknown or this is not a battery. Capped at 100: a
+    /// battery whose remaining capacity passes the last full one is a gauge
+    /// that has not caught up, not a battery more than full.
+    pub fn percent(&self) -> Option<u32> {
+        if self.kind != BATTERY || self.value == UNKNOWN || self.limit == UNKNOWN || self.limit == 0 {
+            return None;
+        }
+        Some((u64::from(self.value) * 100 / u64::from(self.limit)).min(100) as u32)
+    }
+
+    /// A thermal zone's temperature in tenths of a degree Celsius, or `None`
+    /// when it is unknown or this is not a zone.
+    pub fn decicelsius(&self) -> Option<i32> {
+        if self.kind != THERMAL_ZONE || self.value == UNKNOWN {
+            return None;
+        }
+        Some(self.value as i32 - 2732)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `PowerEvent::as_bytes`'s argument: `self` is a valid `&Self`,
+        // and the const assert above proves the `repr(C)` layout is five `u32`s
+        // with no padding between or after them.
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+
+    /// The reading at the start of `bytes`, or `None` when fewer than a whole
+    /// reading's worth are there.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let word = |at: usize| -> Option<u32> {
+            Some(u32::from_ne_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+        };
+        Some(PowerReading {
+            kind: word(0)?,
+            flags: word(4)?,
+            value: word(8)?,
+            limit: word(12)?,
+            rate: word(16)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_event_round_trips_through_its_bytes() {
+        let event = PowerEvent { kind: LID, state: LID_OPEN };
+        assert_eq!(PowerEvent::from_bytes(event.as_bytes()), Some(event));
+        assert_eq!(PowerEvent::from_bytes(&event.as_bytes()[..7]), None);
+    }
+
+    #[test]
+    fn a_reading_round_trips_through_its_bytes() {
+        let reading =
+            PowerReading { kind: BATTERY, flags: BATTERY_PRESENT, value: 1, limit: 2, rate: 3 };
+        assert_eq!(PowerReading::from_bytes(reading.as_bytes()), Some(reading));
+        assert_eq!(PowerReading::from_bytes(&reading.as_bytes()[..19]), None);
+    }
+
+    #[test]
+    fn a_battery_reads_as_a_capped_percentage() {
+        let battery = |value, limit| PowerReading { value, limit, ..PowerReading::unknown(BATTERY) };
+        assert_eq!(battery(2_500, 5_000).percent(), Some(50));
+        assert_eq!(battery(5_100, 5_000).percent(), Some(100));
+        assert_eq!(battery(UNKNOWN, 5_000).percent(), None);
+        assert_eq!(battery(2_500, 0).percent(), None);
+        let zone = PowerReading { value: 50, limit: 100, ..PowerReading::unknown(THERMAL_ZONE) };
+        assert_eq!(zone.percent(), None);
+    }
+
+    #[test]
+    fn a_zone_reads_in_tenths_of_a_degree() {
+        let zone = |value| PowerReading { value, ..PowerReading::unknown(THERMAL_ZONE) };
+        assert_eq!(zone(3_182).decicelsius(), Some(450));
+        assert_eq!(zone(2_700).decicelsius(), Some(-32));
+        assert_eq!(zone(UNKNOWN).decicelsius(), None);
+    }
+}
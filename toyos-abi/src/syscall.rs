@@ -285,6 +285,50 @@ pub const SYS_RT_ENTER: u64 = 112;
 /// [`Rights::LOG`]: crate::handle::Rights::LOG
 pub const SYS_LOG_READ: u64 = 114;
 
+/// Discard every free block of the volume a path is on, answering how many
+/// 4 KiB blocks that was. See [`fstrim`].
+///
+/// Gated like [`SYS_DELETE`] and not by a right: a process that may delete
+/// files on a mount has already shown it may change what is free there, and
+/// telling the device what is free changes nothing any reader can see.
+pub const SYS_FSTRIM: u64 = 116;
+
+/// Whether the NIC behind a claim has a link, and whether it is there at all.
+/// See [`nic_link`].
+///
+/// A virtio or PCIe NIC never leaves and answers "up" for as long as the
+/// machine runs. A USB adapter can be unplugged under netd, and netd is the
+/// one that has to take the interface down when it is.
+pub const SYS_NIC_LINK: u64 = 117;
+
+/// Place every output of the display behind a framebuffer claim. See
+/// [`gpu_arrange`].
+///
+/// A mode set and not a move: where the monitors stand decides how big the
+/// framebuffer that spans them is, so this reallocates the scanout exactly as
+/// [`SYS_GPU_SET_RESOLUTION`] does and answers with the description that names
+/// the new buffers.
+pub const SYS_GPU_ARRANGE: u64 = 118;
+
+/// The modes one output's monitor offers. See [`gpu_modes`].
+pub const SYS_GPU_MODES: u64 = 119;
+
+/// Suspend the machine to RAM and come back, presenting a `SysCap` that
+/// carries [`Rights::POWER`](crate::handle::Rights::POWER). See [`suspend`].
+pub const SYS_SUSPEND: u64 = 120;
+
+/// The USB DAC behind a USB audio claim, if one is plugged in. See
+/// [`usb_audio_dac`].
+///
+/// The claim names the class and outlives the device, exactly as a NIC claim
+/// does for a USB adapter: soundd holds it from boot on any machine with an
+/// xHCI controller, and this is how it hears that a DAC arrived or left.
+pub const SYS_USB_AUDIO_DAC: u64 = 121;
+
+/// Every battery and thermal zone firmware describes, as `acpid` last read
+/// them. See [`power_readings`].
+pub const SYS_POWER_READINGS: u64 = 122;
+
 /// Bins in the per-process syscall profile — one for every number this ABI
 /// issues, and one at the end for every number it does not.
 ///
@@ -298,7 +342,7 @@ pub const SYSCALL_PROFILE_BINS: usize = 128;
 /// a reader can see in the line; dropping is one nobody can.
 pub const SYSCALL_PROFILE_OTHER: usize = SYSCALL_PROFILE_BINS - 1;
 
-const _: () = assert!(SYS_LOG_READ < SYSCALL_PROFILE_OTHER as u64);
+const _: () = assert!(SYS_POWER_READINGS < SYSCALL_PROFILE_OTHER as u64);
 
 pub const WNOHANG: u64 = 1;
 
@@ -788,6 +832,20 @@ pub mod debug_action {
     /// shipped field, and the install and the close that follow are the shipped
     /// paths making the shipped decision (`kernel::object::handle`).
     pub const SLOT_TO_LAST_GENERATION: u64 = 20;
+    /// Plug in a USB game controller that has no bus under it, and answer the
+    /// pad number it was given. One at a time: a second plug before an unplug
+    /// answers `InvalidArgument`.
+    ///
+    /// QEMU has no game controller to add, so a pad state reaches a reader on
+    /// a booted machine this way or not at all. The descriptor, the decode and
+    /// everything published after it are the shipped paths; only the transfer
+    /// is not.
+    pub const PAD_PLUG: u64 = 21;
+    /// One report from that controller, its eight bytes little-endian in the
+    /// argument. Answers 1 if it queued a pad state and 0 if it did not.
+    pub const PAD_REPORT: u64 = 22;
+    /// Pull it out again, which publishes its last, released state.
+    pub const PAD_UNPLUG: u64 = 23;
 }
 
 /// Every kind of kernel object, in the order the kernel's own `kobject!`
@@ -1002,6 +1060,17 @@ pub fn delete(path: &[u8]) -> Result<(), SyscallError> {
     check_unit(syscall(SYS_DELETE, path.as_ptr() as u64, path.len() as u64, 0, 0))
 }
 
+/// Discard every free block of the filesystem `path` is on, answering how
+/// many 4 KiB blocks were discarded. `NotSupported` is a filesystem that does
+/// not implement it, which is every one but `/home`'s.
+///
+/// Slow in proportion to the volume, not to what is free: the whole
+/// allocation bitmap is walked, under the VFS lock, so every other file
+/// operation in the machine waits for it.
+pub fn fstrim(path: &[u8]) -> Result<u64, SyscallError> {
+    check(syscall(SYS_FSTRIM, path.as_ptr() as u64, path.len() as u64, 0, 0))
+}
+
 /// Change current working directory.
 pub fn chdir(path: &[u8]) -> Result<(), SyscallError> {
     check_unit(syscall(SYS_CHDIR, path.as_ptr() as u64, path.len() as u64, 0, 0))
@@ -1105,6 +1174,50 @@ pub unsafe fn gpu_set_resolution(
     ))
 }
 
+/// Put the display's outputs where `outputs` says, one entry per output in the
+/// order [`FramebufferInfo::outputs`](crate::FramebufferInfo::outputs) listed
+/// them. Each entry's size is the mode that output is to run at, and the
+/// positions are relative to each other: the kernel moves the whole
+/// arrangement so that its top-left corner is the framebuffer's.
+///
+/// Refused with `InvalidArgument` if the count is not the display's, if any
+/// output is empty, or if two of them overlap. On success writes the new
+/// [`FramebufferInfo`](crate::FramebufferInfo) to `info_out`, as
+/// [`gpu_set_resolution`] does.
+///
+/// # Safety
+/// `info_out` must point to a writable buffer of at least
+/// `size_of::<FramebufferInfo>()` bytes.
+pub unsafe fn gpu_arrange(
+    claim: RawHandle,
+    outputs: &[crate::Output],
+    info_out: *mut u8,
+) -> Result<(), SyscallError> {
+    check_unit(syscall(
+        SYS_GPU_ARRANGE,
+        claim.0 as u64,
+        outputs.as_ptr() as u64,
+        outputs.len() as u64,
+        info_out as u64,
+    ))
+}
+
+/// Write the [`DisplayModes`](crate::DisplayModes) of output `output` — its
+/// index in [`FramebufferInfo::outputs`](crate::FramebufferInfo::outputs) —
+/// to `out`.
+///
+/// Through the claim, like every other question about the display: the list
+/// is what a mode set may be asked for, and only the claim's holder sets
+/// modes. Refused with `InvalidArgument` for an output the display does not
+/// have.
+///
+/// # Safety
+/// `out` must point to a writable buffer of at least
+/// `size_of::<DisplayModes>()` bytes.
+pub unsafe fn gpu_modes(claim: RawHandle, output: u32, out: *mut u8) -> Result<(), SyscallError> {
+    check_unit(syscall(SYS_GPU_MODES, claim.0 as u64, output as u64, out as u64, 0))
+}
+
 /// Power the machine off, presenting a `SysCap` that carries
 /// [`Rights::POWER`](crate::handle::Rights::POWER).
 ///
@@ -1121,6 +1234,21 @@ pub fn shutdown(syscap: RawHandle) -> SyscallError {
     }
 }
 
+/// Suspend the machine to RAM (ACPI S3), presenting a `SysCap` that carries
+/// [`Rights::POWER`](crate::handle::Rights::POWER), and return once it is back.
+///
+/// Unlike [`shutdown`] this has a success to report, and it is reported only
+/// after the wake: the caller is frozen with every other thread for the whole
+/// of the sleep. `NotSupported` is a machine that cannot sleep — no `\_S3_`, or
+/// a bound device whose driver cannot bring it back — and nothing was touched.
+/// `WouldBlock` is one that could and did not this time: another sleep was
+/// being entered, or a CPU would not park. `Io` is a machine that went all the
+/// way down and was handed back by a chipset that ignored the sleep; the kernel
+/// log names which.
+pub fn suspend(syscap: RawHandle) -> Result<(), SyscallError> {
+    check_unit(syscall(SYS_SUSPEND, syscap.0 as u64, 0, 0, 0))
+}
+
 /// The device classes, their wire numbers, and the name a `system.toml`
 /// `devices` entry and a `dev:` endowment label spell each one with.
 ///
@@ -1178,6 +1306,23 @@ device_classes! {
     /// decision above that — the stream, the rate, the format, when a period is
     /// published — belongs to whoever holds this.
     VirtioSound = 6 => "virtio-sound",
+    /// A virtio-vsock device. The kernel owns its queues and the claimant
+    /// reads and writes whole packets — see [`crate::vsock`].
+    Vsock = 7 => "vsock",
+    /// A USB Audio Class DAC behind the xHCI driver. The kernel owns its
+    /// isochronous ring and copies each packet out of the PCM ring the
+    /// claimant fills — see [`crate::usb_audio`].
+    UsbAudio = 8 => "usb-audio",
+    /// Every game controller the machine has, as one queue of pad states —
+    /// see [`crate::input::GamepadEvent`]. Like the keyboard and the mouse, a
+    /// claim exists whether or not anything is plugged in.
+    Gamepad = 9 => "gamepad",
+    /// The power button, the sleep button and the lid, as one queue of
+    /// [`crate::power::PowerEvent`]s. The one class whose claim asks for more
+    /// than [`Rights::DEVICE`](crate::handle::Rights::DEVICE): reading the
+    /// power button is deciding when the machine turns off, so it also takes
+    /// [`Rights::POWER`](crate::handle::Rights::POWER).
+    Power = 10 => "power",
 }
 
 /// Mint a device claim for `class`, presenting a `SysCap` handle that carries
@@ -1732,6 +1877,43 @@ pub fn nic_tx(claim: RawHandle, total_len: u64) -> Result<(), SyscallError> {
     check_unit(syscall(SYS_NIC_TX, claim.0 as u64, total_len, 0, 0))
 }
 
+/// Ask whether the claimed NIC has a link. `Ok(true)` is up, `Ok(false)` is
+/// down — cable out, or the far end gone — and `NotFound` is the NIC itself
+/// gone, which for a USB adapter is the dongle pulled out of its port.
+///
+/// **Asking clears the news.** A link change or a removal makes the claim
+/// readable even with no frame waiting, so netd parked in its poll wakes for
+/// it; the answer here is what it woke for, and the claim stays readable only
+/// while frames remain.
+pub fn nic_link(claim: RawHandle) -> Result<bool, SyscallError> {
+    check(syscall(SYS_NIC_LINK, claim.0 as u64, 0, 0, 0)).map(|v| v != 0)
+}
+
+/// The DAC plugged in behind the claimed USB audio class, or `None`.
+///
+/// **Asking clears the news**, as [`nic_link`] does: a DAC binding or going
+/// away makes the claim readable with no period played, so soundd parked in its
+/// poll wakes for it, and this is the answer it woke for.
+pub fn usb_audio_dac(
+    claim: RawHandle,
+) -> Result<Option<crate::usb_audio::UsbAudioDac>, SyscallError> {
+    check(syscall(SYS_USB_AUDIO_DAC, claim.0 as u64, 0, 0, 0))
+        .map(crate::usb_audio::UsbAudioDac::from_raw)
+}
+
+/// Copy the machine's battery and thermal zone readings into `out`, in
+/// namespace order, and answer how many the machine has — which may be more
+/// than `out` holds, so an empty `out` asks for the count.
+///
+/// **It never blocks**, for [`log_read`]'s reason: the readings are the ones
+/// the kernel keeps, refreshed by `acpid` on its own schedule, so a caller that
+/// asks twice a second costs a copy and not two embedded-controller
+/// transactions. A machine without ACPI tables the kernel could load has none
+/// and answers 0.
+pub fn power_readings(out: &mut [crate::power::PowerReading]) -> usize {
+    syscall(SYS_POWER_READINGS, out.as_mut_ptr() as u64, out.len() as u64, 0, 0) as usize
+}
+
 /// Allocate a TLS block for a dlopen'd module on the current thread.
 ///
 /// The block's *virtual* address, which is what the kernel writes into the DTV.
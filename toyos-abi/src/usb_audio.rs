@@ -0,0 +1,92 @@
+//! What the kernel's USB audio driver hands the process that claims it.
+//!
+//! **The kernel keeps the clock and the claimant keeps the samples.** An
+//! isochronous endpoint wants a packet every service interval whether or not
+//! anyone has written one, and how many frames each packet carries is the
+//! device's rate and not a number soundd should be computing per packet. So
+//! the kernel owns the transfer ring, sizes every packet and copies it out of
+//! a PCM ring laid out the way the HDA stub's is; the claimant fills periods
+//! and is told which ones have played, exactly as it is for HDA.
+//!
+//! Completions come back as [`AudioCompletionRecord`](crate::audio::AudioCompletionRecord),
+//! with `feedback_rate` set once an asynchronous device has reported how fast
+//! it really runs. A write is one byte, [`START`] or [`STOP`], and
+//! [`usb_audio_dac`](crate::syscall::usb_audio_dac) asks whether a DAC is
+//! plugged in at all; nothing else crosses the claim, and nothing names an
+//! address.
+//!
+//! The claim is the class and not the device. Any machine with an xHCI
+//! controller offers it, DAC or none, so soundd holds it from boot and is woken
+//! on it when a DAC arrives or leaves.
+
+/// The byte a write carries to start playing from the period after the last
+/// one reported, and the byte that stops it.
+pub const START: u8 = 1;
+pub const STOP: u8 = 0;
+
+/// The description a USB audio claim answers its first read with.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UsbAudioInfo {
+    /// The PCM ring, mapped writable: `periods` buffers of `period_bytes` laid
+    /// end to end from the start of the region.
+    pub pcm: crate::RawHandle,
+    pub period_bytes: u32,
+    /// Always 48000 and always two channels of 16-bit samples: the kernel
+    /// binds no setting that plays anything else. Stated rather than assumed
+    /// so the claimant's arithmetic has one source.
+    pub rate: u32,
+    pub channels: u8,
+    pub periods: u8,
+    /// The Audio Class version the device speaks, 1 or 2, and 0 where no DAC
+    /// was plugged in when the claim was read.
+    pub version: u8,
+    /// Whether the device is asynchronous and has a feedback endpoint, so
+    /// completions will carry its measured rate. Like `version`, as of the
+    /// read: a DAC plugged in later says so through its completion records.
+    pub feedback: u8,
+}
+
+/// Every byte belongs to a field: this crosses the boundary through
+/// `as_bytes`, so a gap would publish whatever the kernel stack held.
+const _: () = {
+    let named = 4 + 4 + 4 + 1 + 1 + 1 + 1;
+    assert!(core::mem::size_of::<UsbAudioInfo>() == named);
+};
+
+/// The DAC behind a claim, as [`usb_audio_dac`](crate::syscall::usb_audio_dac)
+/// answers: the two fields of [`UsbAudioInfo`] that belong to the device
+/// rather than to the ring.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UsbAudioDac {
+    /// The Audio Class version, 1 or 2.
+    pub version: u8,
+    pub feedback: bool,
+}
+
+impl UsbAudioDac {
+    /// One syscall return: the version in the low byte and the feedback flag
+    /// above it, and 0 for no DAC — which no version is.
+    pub fn to_raw(dac: Option<Self>) -> u64 {
+        dac.map_or(0, |d| d.version as u64 | (d.feedback as u64) << 8)
+    }
+
+    pub fn from_raw(raw: u64) -> Option<Self> {
+        (raw != 0).then_some(Self { version: raw as u8, feedback: raw & (1 << 8) != 0 })
+    }
+}
+
+impl UsbAudioInfo {
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `self` is a valid `&Self` (non-null, aligned, readable for
+        // `size_of::<Self>()` bytes), and the const assert above proves the
+        // `repr(C)` layout has no padding, so every byte the slice exposes is
+        // an initialized field, not a gap.
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! What the kernel's virtio-vsock driver hands the process that claims it.
+//!
+//! **A read is one packet and a write is one packet.** The kernel owns the
+//! three virtqueues and every descriptor; what crosses the claim is the packet
+//! itself, header and payload, in the wire format `toyos-vsock` reads and
+//! writes. So the claimant never learns an address and never names a buffer,
+//! and the whole of what it can do to the device is put a packet on the wire
+//! from this guest's own context id — the kernel refuses any other source.
+//!
+//! The first read answers with [`VsockInfo`] instead, as every claim's first
+//! read answers with its description.
+
+/// The longest packet a read can answer with or a write may carry: one page,
+/// header included, which is the receive buffer the kernel posts. A peer's
+/// credit is what keeps it from sending more than this side can buffer; this
+/// is what keeps one packet inside one buffer.
+pub const MAX_PACKET: usize = 4096;
+
+/// What a read answers with, between packets, when the device reported a
+/// transport reset: exactly this many bytes, zero except the destination
+/// context id, which is this guest's from then on.
+///
+/// A reset means the host forgot every connection — a migration, or vhost
+/// restarted — and the guest's context id may have changed with it. No packet
+/// carries that news, so the kernel says it this way: a header of operation 0,
+/// which no peer can send, because the kernel refuses it on receive like any
+/// other packet that does not parse.
+pub const TRANSPORT_RESET: usize = 44;
+
+/// The description a vsock claim answers its first read with.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VsockInfo {
+    /// This guest's context id, from the device's configuration space. What
+    /// every packet the claimant writes must carry as its source.
+    pub guest_cid: u64,
+}
+
+/// Every byte belongs to a field: this crosses the boundary through
+/// `as_bytes`, so a gap would publish whatever the kernel stack held.
+const _: () = assert!(core::mem::size_of::<VsockInfo>() == 8);
+
+impl VsockInfo {
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `self` is a valid `&Self` (non-null, aligned, readable for
+        // `size_of::<Self>()` bytes), and the const assert above proves the
+        // `repr(C)` layout has no padding, so every byte the slice exposes is
+        // an initialized field, not a gap.
+        unsafe {
+            core::slice::from_raw_parts(self as *const Self as *const u8, core::mem::size_of::<Self>())
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! General-purpose event blocks.
+//!
+//! A GPE block is a run of byte-wide status registers followed by as many
+//! byte-wide enable registers; bit `n` of register `i` is GPE `first + 8i + n`.
+//! What a GPE *means* — a lid, a hot-plug, an embedded controller — is said
+//! only in AML, by a `_Lxx` or `_Exx` method named after its number, and
+//! running it is `toyos-aml`'s. This module is the part that needs no AML:
+//! where each event's bits are, how a method's name says its number and
+//! trigger ([`Method`]), and which events are pending.
+
+/// One GPE block, as the FADT's `GPEx_BLK`, `GPEx_BLK_LEN` and (for GPE1)
+/// `GPE1_BASE` describe it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Block {
+    base: u16,
+    registers: u8,
+    first: u16,
+}
+
+impl Block {
+    /// The block at I/O port `base`, `len` bytes long, whose first event is
+    /// numbered `first` — or `None` for no block, or for one whose length is
+    /// not two equal halves or whose registers run past the port space.
+    pub fn new(base: u32, len: u8, first: u16) -> Option<Self> {
+        if base == 0 || len == 0 || !len.is_multiple_of(2) || base + u32::from(len) > 0x1_0000 {
+            return None;
+        }
+        Some(Block { base: base as u16, registers: len / 2, first })
+    }
+
+    /// How many status registers, which is also how many enable registers.
+    pub fn registers(&self) -> u8 {
+        self.registers
+    }
+
+    /// The status register `i`.
+    pub fn status_port(&self, i: u8) -> u16 {
+        self.base + u16::from(i)
+    }
+
+    /// The enable register `i`, in the second half.
+    pub fn enable_port(&self, i: u8) -> u16 {
+        self.base + u16::from(self.registers) + u16::from(i)
+    }
+
+    /// The number of bit `bit` of register `i`.
+    pub fn number(&self, i: u8, bit: u8) -> u16 {
+        self.first + u16::from(i) * 8 + u16::from(bit)
+    }
+
+    /// Every event this block carries.
+    pub fn numbers(&self) -> core::ops::Range<u16> {
+        self.first..self.first + u16::from(self.registers) * 8
+    }
+
+    /// Where event `gpe` lives in this block, as its register and bit.
+    pub fn locate(&self, gpe: u16) -> Option<(u8, u8)> {
+        if !self.numbers().contains(&gpe) {
+            return None;
+        }
+        let offset = gpe - self.first;
+        Some(((offset / 8) as u8, (offset % 8) as u8))
+    }
+}
+
+/// How a GPE is signalled, as its method's name says: `_Lxx` for a level, to
+/// be acknowledged after the method has run; `_Exx` for an edge, before.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    Level,
+    Edge,
+}
+
+/// A GPE's handler method, identified by its name alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Method {
+    pub number: u8,
+    pub trigger: Trigger,
+}
+
+impl Method {
+    /// The method a NameSeg names, if it is one: `_L` or `_E` and two
+    /// upper-case hex digits. A NameSeg has no lower case to accept.
+    pub fn from_name(name: [u8; 4]) -> Option<Self> {
+        let trigger = match &name[..2] {
+            b"_L" => Trigger::Level,
+            b"_E" => Trigger::Edge,
+            _ => return None,
+        };
+        let digit = |c: u8| match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        };
+        Some(Method { number: digit(name[2])? << 4 | digit(name[3])?, trigger })
+    }
+
+    /// The NameSeg, for a log line.
+    pub fn name(&self) -> [u8; 4] {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+        let kind = match self.trigger {
+            Trigger::Level => b'L',
+            Trigger::Edge => b'E',
+        };
+        [b'_', kind, HEX[usize::from(self.number >> 4)], HEX[usize::from(self.number & 0xF)]]
+    }
+}
+
+/// The bits of one register pair that are pending: set, and enabled. A status
+/// bit whose enable is off raises nothing, and is not this interrupt's.
+pub fn pending(status: u8, enable: u8) -> u8 {
+    status & enable
+}
+
+/// The bit positions set in `byte`, lowest first.
+pub fn bits(byte: u8) -> impl Iterator<Item = u8> {
+    (0..8).filter(move |bit| byte & (1 << bit) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_then_enable_halves() {
+        // Q35's ICH9: GPE0_BLK at 0x620, sixteen bytes.
+        let b = Block::new(0x620, 16, 0).unwrap();
+        assert_eq!(b.registers(), 8);
+        assert_eq!(b.status_port(0), 0x620);
+        assert_eq!(b.status_port(7), 0x627);
+        assert_eq!(b.enable_port(0), 0x628);
+        assert_eq!(b.enable_port(7), 0x62F);
+        assert_eq!(b.numbers(), 0..64);
+    }
+
+    #[test]
+    fn a_second_block_numbers_from_its_base() {
+        let b = Block::new(0x1000, 4, 0x40).unwrap();
+        assert_eq!(b.number(0, 0), 0x40);
+        assert_eq!(b.number(1, 7), 0x4F);
+        assert_eq!(b.locate(0x4F), Some((1, 7)));
+        assert_eq!(b.locate(0x3F), None);
+        assert_eq!(b.locate(0x50), None);
+    }
+
+    #[test]
+    fn an_odd_or_empty_block_is_none() {
+        assert_eq!(Block::new(0, 4, 0), None);
+        assert_eq!(Block::new(0xAFE0, 0, 0), None);
+        assert_eq!(Block::new(0xAFE0, 3, 0), None);
+        assert_eq!(Block::new(0xFFFE, 4, 0), None);
+    }
+
+    #[test]
+    fn a_gpe_method_is_named_for_its_number() {
+        let lid = Method::from_name(*b"_L0D").unwrap();
+        assert_eq!(lid, Method { number: 0x0D, trigger: Trigger::Level });
+        assert_eq!(&lid.name(), b"_L0D");
+        assert_eq!(Method::from_name(*b"_EFF").unwrap().trigger, Trigger::Edge);
+        assert_eq!(Method::from_name(*b"_LID"), None);
+        assert_eq!(Method::from_name(*b"_e01"), None);
+        assert_eq!(Method::from_name(*b"_Q0D"), None);
+    }
+
+    #[test]
+    fn pending_is_status_under_enable() {
+        assert_eq!(pending(0b1010_0001, 0b0010_0011), 0b0010_0001);
+        let mut seen = [0u8; 8];
+        let mut n = 0;
+        for bit in bits(0b1000_0101) {
+            seen[n] = bit;
+            n += 1;
+        }
+        assert_eq!(&seen[..n], &[0, 2, 7]);
+    }
+}
@@ -0,0 +1,59 @@
+//! Taking the machine from firmware: legacy mode to ACPI mode.
+//!
+//! A PC can boot with its power-management events routed to SMM, where the
+//! BIOS handles the power button itself and the SCI never fires. Writing the
+//! FADT's `ACPI_ENABLE` byte to its `SMI_CMD` port asks firmware to let go;
+//! `SCI_EN` in `PM1_CNT` going to one is firmware saying it has. UEFI firmware
+//! usually hands over already in ACPI mode, and QEMU's OVMF does — but the
+//! handshake is the spec's and a machine that needs it otherwise gets a power
+//! button that is quietly a BIOS feature.
+
+use crate::pm1::SCI_EN;
+
+/// What has to happen before the SCI can be relied on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handover {
+    /// `SCI_EN` is already set, or the FADT says there is no legacy mode to
+    /// leave (no `SMI_CMD`, or no `ACPI_ENABLE` byte to write to it).
+    Done,
+    /// Write `value` to port `port`, then wait for `SCI_EN`.
+    Ask { port: u16, value: u8 },
+}
+
+/// Decide from `PM1_CNT` as read now and the FADT's `SMI_CMD` and
+/// `ACPI_ENABLE`.
+///
+/// An `SMI_CMD` past the port space is treated as absent rather than
+/// truncated into a write to some other device.
+pub fn handover(pm1_cnt: u16, smi_cmd: u32, acpi_enable: u8) -> Handover {
+    if pm1_cnt & SCI_EN != 0 || smi_cmd == 0 || acpi_enable == 0 {
+        return Handover::Done;
+    }
+    match u16::try_from(smi_cmd) {
+        Ok(port) => Handover::Ask { port, value: acpi_enable },
+        Err(_) => Handover::Done,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_machine_already_in_acpi_mode_is_left_alone() {
+        assert_eq!(handover(SCI_EN, 0xB2, 0xF1), Handover::Done);
+    }
+
+    #[test]
+    fn legacy_mode_asks_through_smi_cmd() {
+        // QEMU's PIIX4: SMI_CMD 0xB2, ACPI_ENABLE 0xF1.
+        assert_eq!(handover(0, 0xB2, 0xF1), Handover::Ask { port: 0xB2, value: 0xF1 });
+    }
+
+    #[test]
+    fn no_smi_cmd_means_no_legacy_mode() {
+        assert_eq!(handover(0, 0, 0xF1), Handover::Done);
+        assert_eq!(handover(0, 0xB2, 0), Handover::Done);
+        assert_eq!(handover(0, 0x1_0000, 0xF1), Handover::Done);
+    }
+}
@@ -0,0 +1,29 @@
+//! ACPI's fixed hardware, as decisions separated from their effects.
+//!
+//! The kernel reads the FADT and the event registers it names, asks this crate
+//! what they mean, and writes what this crate says to write; nothing here
+//! touches a port. What is decided here is everything between the System
+//! Control Interrupt firing and a press reaching userland, and the writes that
+//! put the machine to sleep:
+//!
+//! - [`handover`]: whether the machine is already in ACPI mode, and if not,
+//!   which port and byte ask firmware to hand it over.
+//! - [`pm1`]: the fixed-event registers — where they are, which buttons this
+//!   machine wires to them, and which status bits a given read is a press of.
+//! - [`gpe`]: the general-purpose event blocks, the method a GPE's number
+//!   names, and which events in a block are pending.
+//! - [`sleep`]: the `PM1_CNT` writes that enter a sleep state, and where in
+//!   the FACS firmware looks for the way back.
+//!
+//! `no_std`, no allocation, no `unsafe`.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+pub mod gpe;
+pub mod handover;
+pub mod pm1;
+pub mod sleep;
+
+pub use handover::Handover;
+pub use pm1::{EventBlock, Fired, Fixed};
@@ -0,0 +1,142 @@
+//! The PM1 fixed-event registers: the power button, the sleep button, and the
+//! rest of what ACPI wires to the SCI without any AML.
+//!
+//! An event block is a status register followed by an enable register, each
+//! half the block's declared length. A machine may split the block in two —
+//! PM1a and PM1b — and then a bit is set in whichever half the chipset put it
+//! in, so the two are read and OR'd and every write goes to both. Status bits
+//! are write-one-to-clear: writing back exactly the bits that were handled
+//! acknowledges those and leaves any that arrived since for the next pass.
+
+/// `PM1_STS`/`PM1_EN`: the power button.
+pub const PWRBTN: u16 = 1 << 8;
+/// `PM1_STS`/`PM1_EN`: the sleep button.
+pub const SLPBTN: u16 = 1 << 9;
+/// `PM1_STS`: the machine has just woken from a sleep state. Not an event with
+/// an enable bit; S3 resume reads it.
+pub const WAK_STS: u16 = 1 << 15;
+
+/// `PM1_CNT`: the SCI is delivered and the machine is in ACPI mode.
+pub const SCI_EN: u16 = 1 << 0;
+
+/// FADT `Flags`: the power button is a control-method device, or there is
+/// none. Clear means it is the fixed-feature button in `PM1`.
+pub const FADT_PWR_BUTTON: u32 = 1 << 4;
+/// FADT `Flags`: the same, for the sleep button.
+pub const FADT_SLP_BUTTON: u32 = 1 << 5;
+/// FADT `Flags`: a hardware-reduced machine, which has no fixed hardware at
+/// all — no PM1 registers, no SCI_EN — and reports buttons through AML.
+pub const FADT_HW_REDUCED: u32 = 1 << 20;
+
+/// Where one half of a PM1 event block's registers are, as I/O ports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventBlock {
+    pub status: u16,
+    pub enable: u16,
+}
+
+impl EventBlock {
+    /// The registers of a block at I/O port `base` that the FADT says is `len`
+    /// bytes long, or `None` for no block.
+    ///
+    /// `None` is also the answer for a block that cannot be one: each register
+    /// is at least 16 bits, so a length under four or an odd one is firmware
+    /// describing something else, and a base whose registers run past the
+    /// 64 KiB port space is not an I/O address.
+    pub fn new(base: u32, len: u8) -> Option<Self> {
+        if base == 0 || len < 4 || !len.is_multiple_of(2) {
+            return None;
+        }
+        let half = u32::from(len / 2);
+        if base + u32::from(len) > 0x1_0000 {
+            return None;
+        }
+        Some(EventBlock { status: base as u16, enable: (base + half) as u16 })
+    }
+}
+
+/// Which buttons this machine wires to the PM1 registers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixed {
+    pub power_button: bool,
+    pub sleep_button: bool,
+}
+
+impl Fixed {
+    /// What the FADT's `Flags` say. A button whose flag is set is either
+    /// absent or a control-method device, and its fixed bits are left off: a
+    /// status bit the chipset never sets costs nothing, but one firmware also
+    /// reports through AML would be a second press.
+    pub fn from_fadt_flags(flags: u32) -> Self {
+        Fixed {
+            power_button: flags & FADT_PWR_BUTTON == 0,
+            sleep_button: flags & FADT_SLP_BUTTON == 0,
+        }
+    }
+
+    /// The `PM1_EN` bits that turn these on.
+    pub fn enable_bits(self) -> u16 {
+        (if self.power_button { PWRBTN } else { 0 }) | (if self.sleep_button { SLPBTN } else { 0 })
+    }
+}
+
+/// What one read of the status registers says happened.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fired {
+    pub power_button: bool,
+    pub sleep_button: bool,
+    /// The bits to write back to acknowledge it: the ones that are both set
+    /// and enabled. A status bit whose enable is off did not raise this
+    /// interrupt and is left for whoever turns it on.
+    pub clear: u16,
+}
+
+/// Decode `status`, the OR of the PM1a and PM1b status registers, against the
+/// bits this kernel enabled.
+pub fn fired(status: u16, enabled: u16) -> Fired {
+    let ours = status & enabled;
+    Fired { power_button: ours & PWRBTN != 0, sleep_button: ours & SLPBTN != 0, clear: ours }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_enable_register_is_the_second_half() {
+        // QEMU's PIIX4 and Q35 both: PM1a_EVT_BLK at the PM base, four bytes.
+        assert_eq!(EventBlock::new(0x600, 4), Some(EventBlock { status: 0x600, enable: 0x602 }));
+        assert_eq!(EventBlock::new(0x400, 8), Some(EventBlock { status: 0x400, enable: 0x404 }));
+    }
+
+    #[test]
+    fn a_block_that_cannot_hold_two_registers_is_none() {
+        assert_eq!(EventBlock::new(0, 4), None);
+        assert_eq!(EventBlock::new(0x600, 0), None);
+        assert_eq!(EventBlock::new(0x600, 2), None);
+        assert_eq!(EventBlock::new(0x600, 5), None);
+        assert_eq!(EventBlock::new(0xFFFE, 4), None);
+        assert!(EventBlock::new(0xFFFC, 4).is_some());
+    }
+
+    #[test]
+    fn a_control_method_button_is_not_enabled_as_a_fixed_one() {
+        assert_eq!(Fixed::from_fadt_flags(0).enable_bits(), PWRBTN | SLPBTN);
+        // QEMU: the power button is fixed, there is no sleep button.
+        let qemu = Fixed::from_fadt_flags(FADT_SLP_BUTTON);
+        assert_eq!(qemu, Fixed { power_button: true, sleep_button: false });
+        assert_eq!(qemu.enable_bits(), PWRBTN);
+        assert_eq!(Fixed::from_fadt_flags(FADT_PWR_BUTTON | FADT_SLP_BUTTON).enable_bits(), 0);
+    }
+
+    #[test]
+    fn only_enabled_bits_fire_and_only_they_are_cleared() {
+        let f = fired(PWRBTN | WAK_STS | 1, PWRBTN);
+        assert_eq!(f, Fired { power_button: true, sleep_button: false, clear: PWRBTN });
+        // A sleep button the kernel did not enable is not a press.
+        assert_eq!(fired(SLPBTN, PWRBTN), Fired::default());
+        let both = fired(PWRBTN | SLPBTN, PWRBTN | SLPBTN);
+        assert!(both.power_button && both.sleep_button);
+        assert_eq!(both.clear, PWRBTN | SLPBTN);
+    }
+}
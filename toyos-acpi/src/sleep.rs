@@ -0,0 +1,112 @@
+//! Entering a sleep state: the `PM1_CNT` writes that do it, and the FACS word
+//! firmware jumps through on the way back.
+//!
+//! Which `SLP_TYP` a state is belongs to AML (`\_Sx_`), and what the CPUs do
+//! around the write belongs to the kernel; this is the part in between that
+//! only the ACPI specification decides. The FACS is found through the FADT
+//! and is the one table firmware reads *back* — the OS writes its waking
+//! vector, and on S3 resume firmware jumps to it in real mode instead of
+//! booting.
+
+/// `PM1_CNT.SLP_TYP`, bits 10..13.
+pub const SLP_TYP_SHIFT: u16 = 10;
+pub const SLP_TYP_MASK: u16 = 0x7 << SLP_TYP_SHIFT;
+/// `PM1_CNT.SLP_EN`: write-only, and writing it is what enters the state.
+pub const SLP_EN: u16 = 1 << 13;
+
+/// The two `PM1_CNT` writes that enter the sleep state `slp_typ`, given what
+/// the register reads now.
+///
+/// Two rather than one (ACPI 6.5 §4.8.3.2.1, and what every OS does): the type
+/// lands first with `SLP_EN` clear, so no chipset ever sees the enable with a
+/// stale type in the same write. The rest of the register is kept — `SCI_EN`
+/// in particular, which a blind write would clear and take the machine out of
+/// ACPI mode for the resume to find.
+pub fn control_writes(cnt: u16, slp_typ: u8) -> [u16; 2] {
+    let typed = (cnt & !(SLP_TYP_MASK | SLP_EN)) | (u16::from(slp_typ & 0x7) << SLP_TYP_SHIFT);
+    [typed, typed | SLP_EN]
+}
+
+/// FACS `Firmware_Waking_Vector`: a 32-bit physical address firmware enters in
+/// real mode, as `CS = vector >> 4`, `IP = vector & 0xF`.
+pub const FACS_WAKING_VECTOR: usize = 12;
+/// FACS `X_Firmware_Waking_Vector`: the 64-bit form, which firmware prefers
+/// when it is non-zero and then enters in whatever mode the FACS flags ask.
+/// Zeroed by an OS that uses the real-mode one, so a value left by a previous
+/// boot cannot win.
+pub const FACS_X_WAKING_VECTOR: usize = 24;
+/// The FACS is at least this long in every revision that has the X vector.
+const FACS_MIN_LEN: u32 = 64;
+
+/// Where the FACS is, from the FADT's two fields for it: the 64-bit one when
+/// it is set, the 32-bit one otherwise, `None` when firmware gave neither.
+pub fn facs_address(firmware_ctrl: u32, x_firmware_ctrl: u64) -> Option<u64> {
+    match (x_firmware_ctrl, firmware_ctrl) {
+        (0, 0) => None,
+        (0, legacy) => Some(u64::from(legacy)),
+        (x, _) => Some(x),
+    }
+}
+
+/// Whether the first bytes of a table are a FACS this kernel can write a
+/// waking vector into: the signature, and a length that covers the X vector.
+///
+/// The FACS has no checksum — firmware and the OS both write it — so the
+/// signature and the length are all there is to check.
+pub fn is_facs(header: &[u8]) -> bool {
+    let Some(len) = header.get(4..8) else { return false };
+    header.starts_with(b"FACS")
+        && u32::from_le_bytes([len[0], len[1], len[2], len[3]]) >= FACS_MIN_LEN
+}
+
+/// Whether `addr` can be a real-mode waking vector: below 1 MiB, where a
+/// `CS:IP` pair reaches.
+pub fn is_real_mode_vector(addr: u64) -> bool {
+    addr < 0x10_0000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_type_lands_before_the_enable_and_the_rest_is_kept() {
+        // SCI_EN set, a stale S5 type left in the field.
+        let cnt = 1 | (5 << SLP_TYP_SHIFT);
+        assert_eq!(
+            control_writes(cnt, 1),
+            [1 | (1 << SLP_TYP_SHIFT), 1 | (1 << SLP_TYP_SHIFT) | SLP_EN]
+        );
+        // A type wider than three bits cannot reach SLP_EN.
+        assert_eq!(control_writes(0, 0xFF)[0], SLP_TYP_MASK);
+    }
+
+    #[test]
+    fn the_wider_facs_address_wins_when_there_is_one() {
+        assert_eq!(facs_address(0, 0), None);
+        assert_eq!(facs_address(0x7FFE_0000, 0), Some(0x7FFE_0000));
+        assert_eq!(facs_address(0x7FFE_0000, 0x1_0000_0000), Some(0x1_0000_0000));
+        assert_eq!(facs_address(0, 0x7FFE_0000), Some(0x7FFE_0000));
+    }
+
+    #[test]
+    fn a_facs_is_its_signature_and_a_long_enough_length() {
+        let mut facs = [0u8; 64];
+        facs[..4].copy_from_slice(b"FACS");
+        facs[4..8].copy_from_slice(&64u32.to_le_bytes());
+        assert!(is_facs(&facs));
+        facs[4..8].copy_from_slice(&32u32.to_le_bytes());
+        assert!(!is_facs(&facs));
+        assert!(!is_facs(b"FACS"));
+        facs[4..8].copy_from_slice(&64u32.to_le_bytes());
+        facs[..4].copy_from_slice(b"DSDT");
+        assert!(!is_facs(&facs));
+    }
+
+    #[test]
+    fn a_real_mode_vector_is_below_a_megabyte() {
+        assert!(is_real_mode_vector(0x8000));
+        assert!(is_real_mode_vector(0xF_FFF0));
+        assert!(!is_real_mode_vector(0x10_0000));
+    }
+}
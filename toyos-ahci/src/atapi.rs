@@ -0,0 +1,261 @@
+//! An optical drive behind a SATA port: SCSI commands (MMC-6) carried by the
+//! ATA PACKET command, and the few answers the driver reads out of them.
+//!
+//! The port sends a [`Fis::packet`](crate::fis::Fis::packet) and then the
+//! command descriptor block from the command table's ATAPI area (AHCI 1.3.1
+//! §4.2.3); the drive answers in SCSI's terms and big-endian, and a refusal
+//! comes back as an ATA error whose high nibble is the SCSI sense key. Those
+//! three conversions are the ones a driver gets wrong without the drive
+//! saying so, and they are what is here.
+
+use crate::identify::{string, word, Printable};
+
+/// The ATAPI area's size. A CD drive takes 12-byte commands and reads the
+/// first 12; the rest is zero.
+pub const CDB_BYTES: usize = 16;
+
+/// TEST UNIT READY.
+pub const TEST_UNIT_READY: u8 = 0x00;
+/// READ CAPACITY (10).
+pub const READ_CAPACITY: u8 = 0x25;
+/// READ (10).
+pub const READ_10: u8 = 0x28;
+
+/// Bytes READ CAPACITY (10) answers with.
+pub const CAPACITY_BYTES: usize = 8;
+
+/// One command descriptor block, as the port copies it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cdb(pub [u8; CDB_BYTES]);
+
+impl Cdb {
+    pub fn test_unit_ready() -> Self {
+        Self([TEST_UNIT_READY, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+    }
+
+    pub fn read_capacity() -> Self {
+        Self([READ_CAPACITY, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+    }
+
+    /// READ (10) of `sectors` from `lba`, both in the drive's own sectors and
+    /// both big-endian, as every SCSI field is.
+    pub fn read(lba: u32, sectors: u16) -> Self {
+        let [a, b, c, d] = lba.to_be_bytes();
+        let [hi, lo] = sectors.to_be_bytes();
+        Self([READ_10, 0, a, b, c, d, 0, hi, lo, 0, 0, 0, 0, 0, 0, 0])
+    }
+}
+
+/// What READ CAPACITY (10) says about the disc in the drive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Capacity {
+    /// Sectors on the disc — one more than the last LBA the drive reports.
+    pub sectors: u64,
+    /// Bytes per sector: 2048 on every CD and DVD, and a power of two in
+    /// 512..=4096 or the answer is refused.
+    pub sector_bytes: u32,
+}
+
+/// Why a READ CAPACITY answer was refused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CapacityError {
+    /// A sector size this driver cannot serve in 4 KiB blocks.
+    SectorSize(u32),
+    /// A disc too small to hold one whole 4 KiB block.
+    Empty,
+}
+
+impl core::fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::SectorSize(bytes) => {
+                write!(f, "{bytes}-byte sectors, and this driver needs 512..=4096")
+            }
+            Self::Empty => write!(f, "a disc smaller than one 4 KiB block"),
+        }
+    }
+}
+
+impl Capacity {
+    pub fn parse(data: &[u8; CAPACITY_BYTES]) -> Result<Self, CapacityError> {
+        let last = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let sector_bytes = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        if !sector_bytes.is_power_of_two() || !(512..=4096).contains(&sector_bytes) {
+            return Err(CapacityError::SectorSize(sector_bytes));
+        }
+        // Plus one: the drive reports the last LBA, not a count. Widened
+        // first, because a last LBA of `u32::MAX` is a real answer.
+        let sectors = last as u64 + 1;
+        if sectors * (sector_bytes as u64) < 4096 {
+            return Err(CapacityError::Empty);
+        }
+        Ok(Self { sectors, sector_bytes })
+    }
+}
+
+/// The SCSI sense key a refused PACKET carries in the high nibble of the ATA
+/// error register (ACS-3 §7.18.6), for the two the driver acts on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sense {
+    /// NOT READY: no disc, or one not yet spun up.
+    NotReady,
+    /// UNIT ATTENTION: the drive was reset or its disc may have changed. It
+    /// says so once, refusing the command it says it on, and then takes the
+    /// next one.
+    UnitAttention,
+    Other(u8),
+}
+
+impl Sense {
+    pub const fn from_error(error: u8) -> Self {
+        match error >> 4 {
+            0x2 => Self::NotReady,
+            0x6 => Self::UnitAttention,
+            key => Self::Other(key),
+        }
+    }
+}
+
+/// The drive's IDENTIFY PACKET DEVICE page (ACS-3 §7.13), as the facts the
+/// driver takes from it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PacketIdentify {
+    model: [u8; 40],
+    serial: [u8; 20],
+    firmware: [u8; 8],
+}
+
+/// Why a page was refused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PacketIdentifyError {
+    /// The page is not 512 bytes.
+    Length(usize),
+    /// Word 0 does not say ATAPI.
+    NotPacket,
+    /// Word 0 says the drive takes 16-byte commands, which no optical drive
+    /// does and this driver does not build.
+    LongPackets,
+    /// Word 49: no DMA, and this driver moves nothing by PIO.
+    NoDma,
+}
+
+impl core::fmt::Display for PacketIdentifyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Length(len) => write!(f, "an IDENTIFY PACKET page of {len} bytes, not 512"),
+            Self::NotPacket => write!(f, "a page that is not an ATAPI device's"),
+            Self::LongPackets => write!(f, "16-byte commands"),
+            Self::NoDma => write!(f, "no DMA"),
+        }
+    }
+}
+
+impl PacketIdentify {
+    pub fn parse(page: &[u8]) -> Result<Self, PacketIdentifyError> {
+        if page.len() != 512 {
+            return Err(PacketIdentifyError::Length(page.len()));
+        }
+        // Word 0: bits 15:14 are 10b on an ATAPI device, and bits 1:0 are
+        // the command length, 00b for 12 bytes.
+        let w0 = word(page, 0);
+        if w0 >> 14 != 0b10 {
+            return Err(PacketIdentifyError::NotPacket);
+        }
+        if w0 & 0b11 != 0 {
+            return Err(PacketIdentifyError::LongPackets);
+        }
+        if word(page, 49) & (1 << 8) == 0 {
+            return Err(PacketIdentifyError::NoDma);
+        }
+        Ok(Self { model: string(page, 27), serial: string(page, 10), firmware: string(page, 23) })
+    }
+
+    pub fn model(&self) -> Printable<'_> {
+        Printable(&self.model)
+    }
+
+    pub fn serial(&self) -> Printable<'_> {
+        Printable(&self.serial)
+    }
+
+    pub fn firmware(&self) -> Printable<'_> {
+        Printable(&self.firmware)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn a_read_is_big_endian_where_a_fis_is_not() {
+        let Cdb(c) = Cdb::read(0x0102_0304, 0x0506);
+        assert_eq!(&c[..10], &[READ_10, 0, 1, 2, 3, 4, 0, 5, 6, 0]);
+        assert!(c[10..].iter().all(|&b| b == 0));
+        assert_eq!(Cdb::test_unit_ready().0, [0; CDB_BYTES]);
+        assert_eq!(Cdb::read_capacity().0[0], READ_CAPACITY);
+    }
+
+    #[test]
+    fn capacity_counts_from_the_last_lba() {
+        let cd = Capacity::parse(&[0, 0, 0x0F, 0xFF, 0, 0, 0x08, 0]).unwrap();
+        assert_eq!(cd, Capacity { sectors: 0x1000, sector_bytes: 2048 });
+        let huge = Capacity::parse(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0x08, 0]).unwrap();
+        assert_eq!(huge.sectors, 1 << 32, "the last LBA is u32::MAX, not an overflow");
+    }
+
+    #[test]
+    fn capacities_that_are_not_served() {
+        assert_eq!(
+            Capacity::parse(&[0, 0, 0x10, 0, 0, 0, 0x09, 0]),
+            Err(CapacityError::SectorSize(2304)),
+            "a raw-sector length is refused by name"
+        );
+        assert_eq!(Capacity::parse(&[0; 8]), Err(CapacityError::SectorSize(0)));
+        assert_eq!(Capacity::parse(&[0, 0, 0, 0, 0, 0, 0x08, 0]), Err(CapacityError::Empty));
+        assert!(Capacity::parse(&[0, 0, 0, 1, 0, 0, 0x08, 0]).is_ok(), "two sectors are a block");
+    }
+
+    #[test]
+    fn the_sense_key_is_the_high_nibble() {
+        assert_eq!(Sense::from_error(0x24), Sense::NotReady);
+        assert_eq!(Sense::from_error(0x64), Sense::UnitAttention);
+        assert_eq!(Sense::from_error(0x50), Sense::Other(5));
+        assert_eq!(Sense::from_error(0x06), Sense::Other(0), "the low nibble is not the key");
+    }
+
+    fn set(p: &mut [u8; 512], i: usize, w: u16) {
+        p[2 * i..2 * i + 2].copy_from_slice(&w.to_le_bytes());
+    }
+
+    /// Word 0 and word 49 as QEMU's `ide-cd` fills them in.
+    fn page() -> [u8; 512] {
+        let mut p = [0u8; 512];
+        set(&mut p, 0, 2 << 14 | 5 << 8 | 1 << 7 | 2 << 5);
+        set(&mut p, 49, 1 << 9 | 1 << 8);
+        for (i, pair) in b"QEMU DVD-ROM                            ".chunks(2).enumerate() {
+            set(&mut p, 27 + i, u16::from_be_bytes([pair[0], pair[1]]));
+        }
+        p
+    }
+
+    #[test]
+    fn an_optical_drive() {
+        let id = PacketIdentify::parse(&page()).unwrap();
+        assert_eq!(id.model().to_string(), "QEMU DVD-ROM");
+    }
+
+    #[test]
+    fn pages_that_are_not_served() {
+        assert_eq!(PacketIdentify::parse(&[0; 256]), Err(PacketIdentifyError::Length(256)));
+        assert_eq!(PacketIdentify::parse(&[0; 512]), Err(PacketIdentifyError::NotPacket));
+        let mut p = page();
+        set(&mut p, 0, 2 << 14 | 5 << 8 | 1);
+        assert_eq!(PacketIdentify::parse(&p), Err(PacketIdentifyError::LongPackets));
+        let mut p = page();
+        set(&mut p, 49, 1 << 9);
+        assert_eq!(PacketIdentify::parse(&p), Err(PacketIdentifyError::NoDma));
+    }
+}
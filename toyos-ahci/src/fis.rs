@@ -0,0 +1,190 @@
+//! The Register Host-to-Device FIS (SATA 3.x §10.5.5) for every command this
+//! driver issues, and the command header that points the port at it (AHCI
+//! 1.3.1 §4.2.2).
+//!
+//! A FIS is 20 bytes the port sends to the drive verbatim; which byte carries
+//! what depends on the command, and the queued commands move the sector count
+//! into the feature bytes to make room for the tag. That is the one layout
+//! rule a driver gets wrong silently — the drive does not refuse a count in
+//! the wrong field, it transfers a different number of sectors — so it is
+//! written once here and tested.
+
+/// READ DMA EXT.
+pub const READ_DMA_EXT: u8 = 0x25;
+/// WRITE DMA EXT.
+pub const WRITE_DMA_EXT: u8 = 0x35;
+/// READ FPDMA QUEUED.
+pub const READ_FPDMA_QUEUED: u8 = 0x60;
+/// WRITE FPDMA QUEUED.
+pub const WRITE_FPDMA_QUEUED: u8 = 0x61;
+/// DATA SET MANAGEMENT, which with feature bit 0 is TRIM.
+pub const DATA_SET_MANAGEMENT: u8 = 0x06;
+/// FLUSH CACHE EXT.
+pub const FLUSH_CACHE_EXT: u8 = 0xEA;
+/// IDENTIFY DEVICE.
+pub const IDENTIFY_DEVICE: u8 = 0xEC;
+/// PACKET: the SCSI command in the table's ATAPI area ([`crate::atapi`]).
+pub const PACKET: u8 = 0xA0;
+/// IDENTIFY PACKET DEVICE, an optical drive's IDENTIFY.
+pub const IDENTIFY_PACKET_DEVICE: u8 = 0xA1;
+
+/// FIS type: Register, host to device.
+const FIS_TYPE_REG_H2D: u8 = 0x27;
+/// Byte 1 bit 7: this FIS carries a command, not a control update.
+const FIS_C: u8 = 1 << 7;
+/// The device register's LBA bit, which every 48-bit command sets.
+const DEVICE_LBA: u8 = 1 << 6;
+
+/// The FIS's length in dwords, which is what the command header's CFL holds.
+pub const FIS_DWORDS: u32 = 5;
+
+/// Most sectors one non-queued 48-bit command moves: a count of zero means
+/// 65,536, which this driver never asks for.
+pub const MAX_SECTORS: u32 = 0xFFFF;
+
+/// One Register H2D FIS.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Fis(pub [u8; 20]);
+
+impl Fis {
+    fn command(command: u8, lba: u64, count: u16, features: u16) -> Self {
+        let mut f = [0u8; 20];
+        f[0] = FIS_TYPE_REG_H2D;
+        f[1] = FIS_C;
+        f[2] = command;
+        f[3] = features as u8;
+        f[4] = lba as u8;
+        f[5] = (lba >> 8) as u8;
+        f[6] = (lba >> 16) as u8;
+        f[7] = DEVICE_LBA;
+        f[8] = (lba >> 24) as u8;
+        f[9] = (lba >> 32) as u8;
+        f[10] = (lba >> 40) as u8;
+        f[11] = (features >> 8) as u8;
+        f[12] = count as u8;
+        f[13] = (count >> 8) as u8;
+        Self(f)
+    }
+
+    /// A non-queued READ or WRITE DMA EXT of `sectors` (1..=[`MAX_SECTORS`]).
+    pub fn dma(write: bool, lba: u64, sectors: u16) -> Self {
+        let command = if write { WRITE_DMA_EXT } else { READ_DMA_EXT };
+        Self::command(command, lba, sectors, 0)
+    }
+
+    /// A READ or WRITE FPDMA QUEUED in slot `tag`.
+    ///
+    /// **The count is in the features and the tag is in the count**: SATA
+    /// 3.x §13.6.4.1 puts the tag in bits 7:3 of the count register, and the
+    /// sector count, which no longer fits there, in FEATURE(15:0).
+    pub fn queued(write: bool, lba: u64, sectors: u16, tag: u8) -> Self {
+        let command = if write { WRITE_FPDMA_QUEUED } else { READ_FPDMA_QUEUED };
+        Self::command(command, lba, ((tag & 0x1F) as u16) << 3, sectors)
+    }
+
+    /// DATA SET MANAGEMENT with TRIM, whose payload is `blocks` 512-byte blocks
+    /// of range entries ([`crate::trim`]).
+    pub fn trim(blocks: u16) -> Self {
+        Self::command(DATA_SET_MANAGEMENT, 0, blocks, 1)
+    }
+
+    pub fn flush() -> Self {
+        Self::command(FLUSH_CACHE_EXT, 0, 0, 0)
+    }
+
+    pub fn identify() -> Self {
+        Self::unaddressed(IDENTIFY_DEVICE, 0)
+    }
+
+    pub fn identify_packet() -> Self {
+        Self::unaddressed(IDENTIFY_PACKET_DEVICE, 0)
+    }
+
+    /// A PACKET whose data, if any, moves by DMA: FEATURE bit 0 (ACS-3
+    /// §7.18.3). Without it the drive would move the data by PIO, through
+    /// the byte count this FIS leaves at zero, and this driver has no PIO.
+    pub fn packet() -> Self {
+        Self::unaddressed(PACKET, 1)
+    }
+
+    /// The commands that are not LBA commands, whose device register is zero.
+    fn unaddressed(command: u8, features: u16) -> Self {
+        let mut fis = Self::command(command, 0, 0, features);
+        fis.0[7] = 0;
+        fis
+    }
+}
+
+/// Command header DW0 bit 5, A: the table's ATAPI area holds a command the
+/// port sends after the FIS. Set on every [`Fis::packet`] and nothing else.
+pub const HEADER_ATAPI: u32 = 1 << 5;
+
+/// Command header DW0 (§4.2.2): the FIS length, the write bit and the number
+/// of PRD entries. `prdt` is at most 65,535 by the field's width.
+pub const fn header_dw0(write: bool, prdt: u16) -> u32 {
+    FIS_DWORDS | if write { 1 << 6 } else { 0 } | (prdt as u32) << 16
+}
+
+/// One PRD entry's DW3 (§4.2.3.3): the byte count, 0-based, in bits 21:0.
+/// `bytes` must be even and at most 4 MiB, which is the field's width.
+pub const fn prd_dw3(bytes: u32) -> u32 {
+    (bytes - 1) & 0x3F_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_dma_command_carries_its_lba_and_count_where_the_table_puts_them() {
+        let Fis(f) = Fis::dma(false, 0x0000_BEEF_CAFE_F00D, 0x1234);
+        assert_eq!(&f[..4], &[0x27, 0x80, READ_DMA_EXT, 0]);
+        assert_eq!(&f[4..7], &[0x0D, 0xF0, 0xFE]);
+        assert_eq!(f[7], 0x40);
+        assert_eq!(&f[8..11], &[0xCA, 0xEF, 0xBE]);
+        assert_eq!(&f[12..14], &[0x34, 0x12]);
+        assert_eq!(Fis::dma(true, 0, 1).0[2], WRITE_DMA_EXT);
+    }
+
+    /// The queued forms, whose count and tag trade places.
+    #[test]
+    fn a_queued_command_puts_the_count_in_the_features_and_the_tag_in_the_count() {
+        let Fis(f) = Fis::queued(true, 8, 256, 31);
+        assert_eq!(f[2], WRITE_FPDMA_QUEUED);
+        assert_eq!((f[3], f[11]), (0x00, 0x01), "256 sectors in FEATURE(15:0)");
+        assert_eq!((f[12], f[13]), (31 << 3, 0), "the tag in COUNT(7:3)");
+        assert_eq!(Fis::queued(false, 0, 1, 0).0[12], 0);
+        // A tag past 31 names nothing; it is masked rather than carried into
+        // the bits above the field.
+        assert_eq!(Fis::queued(false, 0, 1, 33).0[12], 1 << 3);
+    }
+
+    #[test]
+    fn trim_flush_and_identify() {
+        let Fis(t) = Fis::trim(2);
+        assert_eq!((t[2], t[3], t[12]), (DATA_SET_MANAGEMENT, 1, 2));
+        assert_eq!(Fis::flush().0[2], FLUSH_CACHE_EXT);
+        let Fis(id) = Fis::identify();
+        assert_eq!((id[2], id[7]), (IDENTIFY_DEVICE, 0));
+    }
+
+    /// DMA in the feature byte, and nothing else: the count and the LBA are
+    /// the CDB's, not the FIS's.
+    #[test]
+    fn packet_commands() {
+        let Fis(p) = Fis::packet();
+        assert_eq!(&p[..8], &[0x27, 0x80, PACKET, 1, 0, 0, 0, 0]);
+        assert!(p[8..].iter().all(|&b| b == 0));
+        let Fis(id) = Fis::identify_packet();
+        assert_eq!((id[2], id[3], id[7]), (IDENTIFY_PACKET_DEVICE, 0, 0));
+        assert_eq!(header_dw0(false, 1) | HEADER_ATAPI, 0x0001_0025);
+    }
+
+    #[test]
+    fn header_and_prd_fields() {
+        assert_eq!(header_dw0(false, 1), 0x0001_0005);
+        assert_eq!(header_dw0(true, 1), 0x0001_0045);
+        assert_eq!(prd_dw3(65536), 0xFFFF);
+        assert_eq!(prd_dw3(4 << 20), 0x3F_FFFF);
+    }
+}
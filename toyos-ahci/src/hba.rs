@@ -0,0 +1,133 @@
+//! The HBA's global registers (AHCI 1.3.1 §3.1).
+
+/// CAP — host capabilities, read once at bind.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(u32);
+
+/// S64A — the controller takes 64-bit addresses for the command list, FIS
+/// area, command tables and data.
+const S64A: u32 = 1 << 31;
+/// SNCQ — the controller supports Native Command Queuing.
+const SNCQ: u32 = 1 << 30;
+/// NCS — command slots per port, 0-based, bits 12:8.
+const NCS: u32 = 0x1F << 8;
+/// NP — ports, 0-based, bits 4:0.
+const NP: u32 = 0x1F;
+
+impl Capabilities {
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    pub const fn raw(self) -> u32 {
+        self.0
+    }
+
+    pub const fn addresses_64(self) -> bool {
+        self.0 & S64A != 0
+    }
+
+    pub const fn ncq(self) -> bool {
+        self.0 & SNCQ != 0
+    }
+
+    /// Command slots per port, 1..=32.
+    pub const fn command_slots(self) -> u32 {
+        ((self.0 & NCS) >> 8) + 1
+    }
+
+    /// The port count CAP states, 1..=32. Not the ports to walk: that is
+    /// [`implemented`], which is the register that says which ones exist.
+    pub const fn ports(self) -> u32 {
+        (self.0 & NP) + 1
+    }
+}
+
+impl core::fmt::Debug for Capabilities {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "CAP({:#010x}: {} ports, {} slots{}{})",
+            self.0,
+            self.ports(),
+            self.command_slots(),
+            if self.ncq() { ", NCQ" } else { "" },
+            if self.addresses_64() { ", 64-bit" } else { "" },
+        )
+    }
+}
+
+/// The ports to walk: every bit PI sets.
+///
+/// **PI and not CAP.NP.** §3.1.4 makes PI the authority and lets the two
+/// disagree — a controller may implement port 5 and not port 2, and NP then
+/// counts ports rather than naming them. A PI of zero is a controller with no
+/// ports, which is a controller to report and not one to guess about.
+pub fn implemented(pi: u32) -> impl Iterator<Item = u8> {
+    (0u8..32).filter(move |port| pi & (1 << port) != 0)
+}
+
+/// GHC.AE — AHCI enable.
+pub const GHC_AE: u32 = 1 << 31;
+/// GHC.IE — interrupt enable. This driver polls, and never sets it.
+pub const GHC_IE: u32 = 1 << 1;
+
+/// CAP2.BOH — the controller supports BIOS/OS handoff (§10.6).
+pub const CAP2_BOH: u32 = 1 << 0;
+/// BOHC.BOS — firmware owns the controller.
+pub const BOHC_BOS: u32 = 1 << 0;
+/// BOHC.OOS — the OS asks for it.
+pub const BOHC_OOS: u32 = 1 << 1;
+/// BOHC.BB — firmware is busy cleaning up, and §10.6.3 gives it two seconds.
+pub const BOHC_BB: u32 = 1 << 4;
+
+/// Register offsets in the ABAR.
+pub const REG_CAP: u64 = 0x00;
+pub const REG_GHC: u64 = 0x04;
+pub const REG_PI: u64 = 0x0C;
+pub const REG_VS: u64 = 0x10;
+pub const REG_CAP2: u64 = 0x24;
+pub const REG_BOHC: u64 = 0x28;
+
+/// Where port `port`'s registers start in the ABAR.
+pub const fn port_base(port: u8) -> u64 {
+    0x100 + port as u64 * 0x80
+}
+
+/// How much of the ABAR the thirty-two ports can reach.
+pub const ABAR_BYTES: u64 = 0x1100;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fields against the table, at both ends of each range.
+    #[test]
+    fn fields_are_where_the_table_puts_them() {
+        let none = Capabilities::from_raw(0);
+        assert_eq!((none.ports(), none.command_slots(), none.ncq()), (1, 1, false));
+        let all = Capabilities::from_raw(u32::MAX);
+        assert_eq!((all.ports(), all.command_slots(), all.ncq()), (32, 32, true));
+        assert!(all.addresses_64());
+        // Shaped like QEMU's ich9-ahci: six ports, 32 slots, NCQ, 64-bit.
+        let ich9 = Capabilities::from_raw(0xC734_FF05);
+        assert_eq!((ich9.ports(), ich9.command_slots()), (6, 32));
+        assert!(ich9.ncq() && ich9.addresses_64());
+    }
+
+    /// PI names ports; a hole is a hole.
+    #[test]
+    fn implemented_walks_pi_and_not_a_count() {
+        let mut it = implemented(0b10_0101);
+        assert_eq!((it.next(), it.next(), it.next(), it.next()), (Some(0), Some(2), Some(5), None));
+        assert_eq!(implemented(0).count(), 0);
+        assert_eq!(implemented(u32::MAX).count(), 32);
+        assert_eq!(implemented(1 << 31).next(), Some(31));
+    }
+
+    #[test]
+    fn ports_are_0x80_apart_from_0x100() {
+        assert_eq!(port_base(0), 0x100);
+        assert_eq!(port_base(31) + 0x80, ABAR_BYTES);
+    }
+}
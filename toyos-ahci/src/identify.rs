@@ -0,0 +1,266 @@
+//! The drive's IDENTIFY DEVICE page (ACS-3 §7.12.7), as the handful of facts
+//! the driver takes from it.
+//!
+//! Every field is a number the drive wrote, and several of them become a
+//! divisor, a shift or a loop bound in the driver. So each is decoded here with
+//! its validity bits checked and its range refused rather than clamped: a drive
+//! that reports a 3-byte sector is a drive this driver declines, by name.
+
+/// The decoded page.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Identify {
+    /// Addressable logical sectors.
+    pub sectors: u64,
+    /// Bytes per logical sector: a power of two in 512..=4096.
+    pub sector_bytes: u32,
+    /// 48-bit addressing, which is what READ/WRITE DMA EXT and FLUSH CACHE EXT
+    /// need. A drive without it is addressed in 28 bits and this driver does
+    /// not serve it.
+    pub lba48: bool,
+    /// NCQ, and how many commands the drive will hold at once (1..=32). One
+    /// when the drive has no NCQ.
+    pub queue_depth: u32,
+    /// DATA SET MANAGEMENT with the TRIM bit.
+    pub trim: bool,
+    /// How many 512-byte blocks of range entries one DATA SET MANAGEMENT may
+    /// carry. Zero in the page means "not stated", which ACS-3 reads as one.
+    pub trim_blocks: u16,
+    /// A volatile write cache is present and enabled, so FLUSH CACHE EXT is
+    /// what makes a completed write durable.
+    pub write_cache: bool,
+    model: [u8; 40],
+    serial: [u8; 20],
+    firmware: [u8; 8],
+}
+
+/// Why a page was refused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IdentifyError {
+    /// The page is not 512 bytes.
+    Length(usize),
+    /// The drive cannot be addressed in 48 bits.
+    NoLba48,
+    /// The sector size word is valid and names a size this driver cannot serve.
+    SectorSize(u64),
+    /// The drive reports no sectors.
+    Empty,
+}
+
+impl core::fmt::Display for IdentifyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Length(len) => write!(f, "an IDENTIFY page of {len} bytes, not 512"),
+            Self::NoLba48 => write!(f, "no 48-bit addressing"),
+            Self::SectorSize(bytes) => {
+                write!(f, "{bytes}-byte logical sectors, and this driver needs 512..=4096")
+            }
+            Self::Empty => write!(f, "a capacity of zero sectors"),
+        }
+    }
+}
+
+/// Word `i` of the page, little-endian as ATA transfers it.
+pub(crate) fn word(page: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([page[2 * i], page[2 * i + 1]])
+}
+
+/// Whether word `i` is one the drive filled in: ACS-3 marks the optional
+/// feature words valid with bit 14 set and bit 15 clear, and a drive that
+/// leaves one as 0x0000 or 0xFFFF has not said anything.
+fn valid(w: u16) -> bool {
+    w & 0xC000 == 0x4000
+}
+
+/// An ATA string: two characters per word, the first in the high byte.
+pub(crate) fn string<const N: usize>(page: &[u8], first_word: usize) -> [u8; N] {
+    let mut out = [0u8; N];
+    for (i, pair) in out.as_chunks_mut::<2>().0.iter_mut().enumerate() {
+        *pair = word(page, first_word + i).to_be_bytes();
+    }
+    out
+}
+
+impl Identify {
+    pub fn parse(page: &[u8]) -> Result<Self, IdentifyError> {
+        if page.len() != 512 {
+            return Err(IdentifyError::Length(page.len()));
+        }
+        let w83 = word(page, 83);
+        let lba48 = valid(w83) && w83 & (1 << 10) != 0;
+        if !lba48 {
+            return Err(IdentifyError::NoLba48);
+        }
+        let sectors = (0..4).fold(0u64, |acc, i| acc | (word(page, 100 + i) as u64) << (16 * i));
+        if sectors == 0 {
+            return Err(IdentifyError::Empty);
+        }
+
+        // Word 106 bit 12: the logical sector is longer than 256 words, and
+        // words 117-118 say how long — *in words*, which is the unit every
+        // driver that read it as bytes got wrong.
+        let w106 = word(page, 106);
+        let sector_bytes = if valid(w106) && w106 & (1 << 12) != 0 {
+            let words = word(page, 117) as u64 | (word(page, 118) as u64) << 16;
+            words * 2
+        } else {
+            512
+        };
+        if !sector_bytes.is_power_of_two() || !(512..=4096).contains(&sector_bytes) {
+            return Err(IdentifyError::SectorSize(sector_bytes));
+        }
+
+        // Word 76 bit 8: NCQ. Word 75 bits 4:0 are the depth minus one. Word
+        // 76 has no validity pattern of its own; 0xFFFF is what a drive with no
+        // SATA capabilities word leaves, and it is read as no NCQ.
+        let w76 = word(page, 76);
+        let ncq = w76 != 0xFFFF && w76 & (1 << 8) != 0;
+        let queue_depth = if ncq { (word(page, 75) as u32 & 0x1F) + 1 } else { 1 };
+
+        let trim = word(page, 169) & 1 != 0 && word(page, 169) != 0xFFFF;
+        let trim_blocks = match word(page, 105) {
+            0 | 0xFFFF => 1,
+            n => n,
+        };
+        let w82 = word(page, 82);
+        let w85 = word(page, 85);
+        let write_cache = w82 != 0xFFFF && w82 & (1 << 5) != 0 && w85 & (1 << 5) != 0;
+
+        Ok(Self {
+            sectors,
+            // Exact: refused above unless it is at most 4096.
+            sector_bytes: sector_bytes as u32,
+            lba48,
+            queue_depth,
+            trim,
+            trim_blocks,
+            write_cache,
+            model: string(page, 27),
+            serial: string(page, 10),
+            firmware: string(page, 23),
+        })
+    }
+
+    /// The model, trimmed, with anything outside printable ASCII replaced —
+    /// it goes straight into the log, and the log is a terminal.
+    pub fn model(&self) -> Printable<'_> {
+        Printable(&self.model)
+    }
+
+    pub fn serial(&self) -> Printable<'_> {
+        Printable(&self.serial)
+    }
+
+    pub fn firmware(&self) -> Printable<'_> {
+        Printable(&self.firmware)
+    }
+}
+
+/// A drive string, displayed trimmed and with non-printable bytes as `?`.
+pub struct Printable<'a>(pub(crate) &'a [u8]);
+
+impl core::fmt::Display for Printable<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let blank = |b: &u8| *b == b' ' || *b == 0;
+        let start = self.0.iter().position(|b| !blank(b)).unwrap_or(self.0.len());
+        let end = self.0.iter().rposition(|b| !blank(b)).map_or(start, |i| i + 1);
+        for &b in &self.0[start..end] {
+            let c = if (0x20..0x7F).contains(&b) { b as char } else { '?' };
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::string::ToString;
+
+    /// A page with the words a plain 512-byte, LBA48, NCQ-32 drive fills in.
+    fn page() -> [u8; 512] {
+        let mut p = [0u8; 512];
+        set(&mut p, 75, 31);
+        set(&mut p, 76, 1 << 8);
+        set(&mut p, 82, 1 << 5);
+        set(&mut p, 83, 0x4000 | 1 << 10 | 1 << 13);
+        set(&mut p, 85, 1 << 5);
+        set(&mut p, 101, 0x0100); // 2^24 sectors: 8 GiB
+        set(&mut p, 169, 1);
+        for (i, pair) in b"QEMU HARDDISK                           ".chunks(2).enumerate() {
+            set(&mut p, 27 + i, u16::from_be_bytes([pair[0], pair[1]]));
+        }
+        p
+    }
+
+    fn set(p: &mut [u8; 512], i: usize, w: u16) {
+        p[2 * i..2 * i + 2].copy_from_slice(&w.to_le_bytes());
+    }
+
+    #[test]
+    fn an_ordinary_drive() {
+        let id = Identify::parse(&page()).unwrap();
+        assert_eq!(id.sectors, 1 << 24);
+        assert_eq!(id.sector_bytes, 512);
+        assert_eq!(id.queue_depth, 32);
+        assert!(id.lba48 && id.trim && id.write_cache);
+        assert_eq!(id.trim_blocks, 1);
+        assert_eq!(id.model().to_string(), "QEMU HARDDISK");
+    }
+
+    #[test]
+    fn a_4k_native_drive_states_its_sector_in_words() {
+        let mut p = page();
+        set(&mut p, 106, 0x4000 | 1 << 12);
+        set(&mut p, 117, 2048);
+        assert_eq!(Identify::parse(&p).unwrap().sector_bytes, 4096);
+    }
+
+    /// The sector-size word is believed only with its validity pattern, and a
+    /// size outside the driver's window is refused, not clamped.
+    #[test]
+    fn sector_sizes_are_refused_by_name() {
+        let mut p = page();
+        set(&mut p, 106, 0xFFFF);
+        assert_eq!(Identify::parse(&p).unwrap().sector_bytes, 512, "0xFFFF is not a valid word");
+        for (words, bytes) in [(4096u16, 8192u64), (0, 0), (257, 514), (128, 256)] {
+            set(&mut p, 106, 0x4000 | 1 << 12);
+            set(&mut p, 117, words);
+            assert_eq!(Identify::parse(&p), Err(IdentifyError::SectorSize(bytes)));
+        }
+        set(&mut p, 117, 0);
+        set(&mut p, 118, 1);
+        assert_eq!(Identify::parse(&p), Err(IdentifyError::SectorSize(1 << 17)));
+    }
+
+    #[test]
+    fn pages_that_are_not_a_drive() {
+        assert_eq!(Identify::parse(&[0; 511]), Err(IdentifyError::Length(511)));
+        assert_eq!(Identify::parse(&[0; 512]), Err(IdentifyError::NoLba48));
+        assert_eq!(Identify::parse(&[0xFF; 512]), Err(IdentifyError::NoLba48));
+        let mut p = page();
+        set(&mut p, 101, 0);
+        assert_eq!(Identify::parse(&p), Err(IdentifyError::Empty));
+    }
+
+    #[test]
+    fn no_ncq_is_a_depth_of_one() {
+        let mut p = page();
+        set(&mut p, 76, 0);
+        assert_eq!(Identify::parse(&p).unwrap().queue_depth, 1);
+        set(&mut p, 76, 0xFFFF);
+        assert_eq!(Identify::parse(&p).unwrap().queue_depth, 1);
+        set(&mut p, 76, 1 << 8);
+        set(&mut p, 75, 0xFFFF);
+        assert_eq!(Identify::parse(&p).unwrap().queue_depth, 32, "only bits 4:0 are the depth");
+    }
+
+    #[test]
+    fn strings_are_trimmed_and_made_printable() {
+        let mut p = page();
+        set(&mut p, 10, u16::from_be_bytes(*b" S"));
+        set(&mut p, 11, u16::from_be_bytes([0x1B, b'N']));
+        assert_eq!(Identify::parse(&p).unwrap().serial().to_string(), "S?N");
+        assert_eq!(Identify::parse(&page()).unwrap().firmware().to_string(), "");
+    }
+}
@@ -0,0 +1,38 @@
+//! AHCI 1.3.1 and the ATA commands a SATA disk is driven with, and the SCSI
+//! ones an optical drive is, as decisions separated from their effects.
+//!
+//! The kernel reads a register or an IDENTIFY page, asks this crate what it
+//! means, and acts; nothing here touches MMIO or DMA. What is decided here is
+//! every number the driver takes from the device and turns into a size, an
+//! index or a count:
+//!
+//! - [`hba`]: how many ports and command slots the controller has, and which
+//!   ports it implements.
+//! - [`port`]: whether a port has a drive this driver serves, and whether the
+//!   port's interrupt status means a command failed.
+//! - [`identify`]: the drive's capacity, sector size, queue depth, features and
+//!   strings, out of its 256-word IDENTIFY DEVICE page.
+//! - [`fis`]: the Register H2D FIS for each command the driver issues.
+//! - [`slots`]: which command slots are free, and which of the issued ones a
+//!   pair of `PxCI`/`PxSACT` reads says have completed.
+//! - [`trim`]: a block range as DATA SET MANAGEMENT range entries.
+//! - [`atapi`]: the SCSI commands an optical drive is read with, and its
+//!   capacity, sense key and IDENTIFY PACKET page.
+//!
+//! `no_std`, no allocation, no `unsafe`.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+pub mod atapi;
+pub mod fis;
+pub mod hba;
+pub mod identify;
+pub mod port;
+pub mod slots;
+pub mod trim;
+
+pub use hba::Capabilities;
+pub use identify::{Identify, IdentifyError};
+pub use port::{Attached, Fault};
+pub use slots::Slots;
@@ -0,0 +1,203 @@
+//! One port's registers (AHCI 1.3.1 §3.3): what is attached, and whether what
+//! it is doing has failed.
+
+/// Offsets in a port's 0x80-byte register block.
+pub const PX_CLB: u64 = 0x00;
+pub const PX_CLBU: u64 = 0x04;
+pub const PX_FB: u64 = 0x08;
+pub const PX_FBU: u64 = 0x0C;
+pub const PX_IS: u64 = 0x10;
+pub const PX_IE: u64 = 0x14;
+pub const PX_CMD: u64 = 0x18;
+pub const PX_TFD: u64 = 0x20;
+pub const PX_SIG: u64 = 0x24;
+pub const PX_SSTS: u64 = 0x28;
+pub const PX_SERR: u64 = 0x30;
+pub const PX_SACT: u64 = 0x34;
+pub const PX_CI: u64 = 0x38;
+
+/// PxCMD.ST — the port processes the command list.
+pub const CMD_ST: u32 = 1 << 0;
+/// PxCMD.FRE — the port may post received FISes.
+pub const CMD_FRE: u32 = 1 << 4;
+/// PxCMD.FR — FIS receive is running. Read-only.
+pub const CMD_FR: u32 = 1 << 14;
+/// PxCMD.CR — the command list is running. Read-only.
+pub const CMD_CR: u32 = 1 << 15;
+
+/// PxTFD.STS.ERR — the last command ended in error.
+pub const TFD_ERR: u32 = 1 << 0;
+/// PxTFD.STS.DRQ — the drive wants a data transfer.
+pub const TFD_DRQ: u32 = 1 << 3;
+/// PxTFD.STS.BSY — the drive is busy.
+pub const TFD_BSY: u32 = 1 << 7;
+
+/// What a port's link and signature say is on the other end of it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Attached {
+    /// Nothing, or a link that has not come up: there is no device to talk to.
+    Nothing,
+    /// A SATA drive, which is the one thing this driver serves.
+    Ata,
+    /// A packet device — an optical drive. Named so the log says what it
+    /// passed over rather than that it found nothing.
+    Atapi,
+    /// A port multiplier, which this driver does not walk.
+    PortMultiplier,
+    /// An enclosure management bridge.
+    Enclosure,
+    /// A signature no table names.
+    Unknown(u32),
+}
+
+const SIG_ATA: u32 = 0x0000_0101;
+const SIG_ATAPI: u32 = 0xEB14_0101;
+const SIG_SEMB: u32 = 0xC33C_0101;
+const SIG_PM: u32 = 0x9669_0101;
+
+/// PxSSTS.DET == 3: a device is present and Phy communication is established.
+const DET_ESTABLISHED: u32 = 3;
+/// PxSSTS.IPM == 1: the interface is active, not in a power state.
+const IPM_ACTIVE: u32 = 1;
+
+impl Attached {
+    /// Decide from PxSSTS and PxSIG.
+    ///
+    /// **The link first, and the signature only behind it.** PxSIG holds the
+    /// last signature FIS the port received, which on a port whose device has
+    /// gone is the signature of the device that used to be there; only DET
+    /// says whether anything is there *now*. IPM is the second half: a link in
+    /// Partial or Slumber has a device behind it, but one this driver would
+    /// have to wake first, and it does not.
+    pub const fn decide(ssts: u32, sig: u32) -> Self {
+        let det = ssts & 0xF;
+        let ipm = (ssts >> 8) & 0xF;
+        if det != DET_ESTABLISHED || ipm != IPM_ACTIVE {
+            return Self::Nothing;
+        }
+        match sig {
+            SIG_ATA => Self::Ata,
+            SIG_ATAPI => Self::Atapi,
+            SIG_PM => Self::PortMultiplier,
+            SIG_SEMB => Self::Enclosure,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl core::fmt::Display for Attached {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Nothing => write!(f, "nothing"),
+            Self::Ata => write!(f, "a SATA drive"),
+            Self::Atapi => write!(f, "an ATAPI device"),
+            Self::PortMultiplier => write!(f, "a port multiplier"),
+            Self::Enclosure => write!(f, "an enclosure management bridge"),
+            Self::Unknown(sig) => write!(f, "a device with signature {sig:#010x}"),
+        }
+    }
+}
+
+/// Why a port's interrupt status says the commands in flight have failed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fault {
+    /// TFES: the drive reported an error in PxTFD. The drive is fine and the
+    /// command was refused — a bad sector, an unsupported command.
+    TaskFile { status: u8, error: u8 },
+    /// HBFS, HBDS or IFS: the controller or the link failed, and §6.1.2 calls
+    /// each of them fatal to every command in flight.
+    Host(u32),
+}
+
+/// PxIS.TFES — task file error status.
+const IS_TFES: u32 = 1 << 30;
+/// PxIS.HBFS — host bus fatal error.
+const IS_HBFS: u32 = 1 << 29;
+/// PxIS.HBDS — host bus data error.
+const IS_HBDS: u32 = 1 << 28;
+/// PxIS.IFS — interface fatal error.
+const IS_IFS: u32 = 1 << 27;
+
+impl Fault {
+    /// Decide from PxIS and PxTFD whether the port has failed what it was
+    /// given.
+    ///
+    /// The host errors win over TFES, because they say the controller may have
+    /// stopped mid-transfer and the task file's account of the command is
+    /// therefore not the whole story.
+    pub const fn decide(is: u32, tfd: u32) -> Option<Self> {
+        let host = is & (IS_HBFS | IS_HBDS | IS_IFS);
+        if host != 0 {
+            return Some(Self::Host(host));
+        }
+        if is & IS_TFES != 0 {
+            return Some(Self::TaskFile { status: tfd as u8, error: (tfd >> 8) as u8 });
+        }
+        None
+    }
+}
+
+impl core::fmt::Display for Fault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TaskFile { status, error } => {
+                write!(f, "the drive refused it (status {status:#04x}, error {error:#04x})")
+            }
+            Self::Host(bits) => write!(f, "the controller failed it (PxIS {bits:#010x})"),
+        }
+    }
+}
+
+/// Whether the drive will take a command: neither busy nor asking for data.
+/// §10.3.1 makes this the last condition before PxCMD.ST may be set.
+pub const fn idle(tfd: u32) -> bool {
+    tfd & (TFD_BSY | TFD_DRQ) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: u32 = 0x123; // Gen 2, IPM active, DET established
+
+    #[test]
+    fn a_drive_is_served_only_behind_an_established_active_link() {
+        assert_eq!(Attached::decide(UP, SIG_ATA), Attached::Ata);
+        // DET 1: present, no communication. The signature is stale.
+        assert_eq!(Attached::decide(0x121, SIG_ATA), Attached::Nothing);
+        // IPM 2: Partial. A drive, and one that would have to be woken.
+        assert_eq!(Attached::decide(0x223, SIG_ATA), Attached::Nothing);
+        assert_eq!(Attached::decide(0, SIG_ATA), Attached::Nothing);
+        assert_eq!(Attached::decide(u32::MAX, SIG_ATA), Attached::Nothing);
+    }
+
+    #[test]
+    fn signatures_are_named() {
+        assert_eq!(Attached::decide(UP, SIG_ATAPI), Attached::Atapi);
+        assert_eq!(Attached::decide(UP, SIG_PM), Attached::PortMultiplier);
+        assert_eq!(Attached::decide(UP, SIG_SEMB), Attached::Enclosure);
+        assert_eq!(Attached::decide(UP, 0xFFFF_FFFF), Attached::Unknown(0xFFFF_FFFF));
+    }
+
+    #[test]
+    fn host_errors_outrank_a_task_file_error() {
+        assert_eq!(Fault::decide(0, 0x51), None);
+        assert_eq!(
+            Fault::decide(IS_TFES, 0x0451),
+            Some(Fault::TaskFile { status: 0x51, error: 0x04 })
+        );
+        assert_eq!(Fault::decide(IS_TFES | IS_IFS, 0x51), Some(Fault::Host(IS_IFS)));
+        assert_eq!(Fault::decide(IS_HBFS | IS_HBDS, 0), Some(Fault::Host(IS_HBFS | IS_HBDS)));
+        // The completion bits are not faults.
+        assert_eq!(Fault::decide(0b1111, 0), None);
+    }
+
+    #[test]
+    fn idle_is_neither_busy_nor_data_request() {
+        assert!(idle(0x50));
+        assert!(!idle(0x80));
+        assert!(!idle(0x58));
+        // ERR alone does not hold the port: it is what a failed command leaves.
+        assert!(idle(0x51));
+    }
+}
@@ -0,0 +1,152 @@
+//! A port's command slots: which are free, and which of the issued ones have
+//! completed.
+//!
+//! **Completion is read off two registers, and out of order.** A slot is done
+//! when the port has cleared its bit in PxCI and, for a queued command, the
+//! drive has cleared it in PxSACT (AHCI 1.3.1 §5.3.10). NCQ lets the drive
+//! finish commands in whatever order suits its media, so the slot that
+//! completes first is not the slot issued first, and a driver that waits on
+//! "the oldest" is a driver that spins on a command the drive is still
+//! reordering behind another.
+
+/// The slots one port has issued and not yet seen complete.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Slots {
+    /// Slots this driver may use: the lesser of the controller's and the
+    /// drive's depth, at most 32.
+    depth: u32,
+    issued: u32,
+}
+
+/// What one pair of reads says.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Settled {
+    /// Slots that were issued and have now completed.
+    pub done: u32,
+    /// Bits the port reports busy that this driver never issued: a controller
+    /// describing work nobody gave it. Reported, never acted on.
+    pub strangers: u32,
+}
+
+impl Slots {
+    /// `controller` is CAP.NCS and `drive` the IDENTIFY queue depth; either
+    /// may be anything a device wrote, so both are clamped to 1..=32.
+    pub fn new(controller: u32, drive: u32) -> Self {
+        Self { depth: controller.min(drive).clamp(1, 32), issued: 0 }
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Slots in flight, as the mask PxCI and PxSACT are compared against.
+    pub fn issued(&self) -> u32 {
+        self.issued
+    }
+
+    pub fn idle(&self) -> bool {
+        self.issued == 0
+    }
+
+    /// Take the lowest free slot, or `None` when every one is in flight.
+    pub fn take(&mut self) -> Option<u8> {
+        let free = !self.issued & mask(self.depth);
+        if free == 0 {
+            return None;
+        }
+        let slot = free.trailing_zeros();
+        self.issued |= 1 << slot;
+        // Exact: `trailing_zeros` of a nonzero `u32` is below 32.
+        Some(slot as u8)
+    }
+
+    /// Retire what a read of PxCI and PxSACT says is done.
+    pub fn settle(&mut self, ci: u32, sact: u32) -> Settled {
+        let busy = ci | sact;
+        let done = self.issued & !busy;
+        self.issued &= !done;
+        Settled { done, strangers: busy & !self.issued }
+    }
+
+    /// Give every slot back: the port was stopped, and §6.2.2.1 says stopping
+    /// it clears PxCI and PxSACT along with whatever they named.
+    pub fn abandon(&mut self) -> u32 {
+        core::mem::take(&mut self.issued)
+    }
+}
+
+/// The low `depth` bits.
+fn mask(depth: u32) -> u32 {
+    if depth >= 32 { u32::MAX } else { (1 << depth) - 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_is_the_lesser_and_never_zero_or_past_32() {
+        assert_eq!(Slots::new(32, 32).depth(), 32);
+        assert_eq!(Slots::new(32, 8).depth(), 8);
+        assert_eq!(Slots::new(1, 32).depth(), 1);
+        assert_eq!(Slots::new(0, 0).depth(), 1);
+        assert_eq!(Slots::new(u32::MAX, u32::MAX).depth(), 32);
+    }
+
+    #[test]
+    fn slots_are_taken_lowest_first_and_run_out_at_the_depth() {
+        let mut s = Slots::new(32, 3);
+        assert_eq!((s.take(), s.take(), s.take(), s.take()), (Some(0), Some(1), Some(2), None));
+        let mut full = Slots::new(32, 32);
+        for want in 0..32 {
+            assert_eq!(full.take(), Some(want));
+        }
+        assert_eq!(full.take(), None);
+        assert_eq!(full.issued(), u32::MAX);
+    }
+
+    /// The drive finishes slot 2 before slot 0, and the slot freed is 2.
+    #[test]
+    fn completion_is_out_of_order() {
+        let mut s = Slots::new(32, 32);
+        for _ in 0..3 {
+            s.take();
+        }
+        let settled = s.settle(0b011, 0b011);
+        assert_eq!(settled, Settled { done: 0b100, strangers: 0 });
+        assert_eq!(s.take(), Some(2), "the slot that completed is the one reused");
+        assert_eq!(s.settle(0, 0b100).done, 0b011);
+        assert_eq!(s.issued(), 0b100);
+    }
+
+    /// PxCI clear with PxSACT still set is not done: the port has sent the
+    /// command and the drive has not finished it.
+    #[test]
+    fn a_queued_command_is_done_only_when_both_registers_agree() {
+        let mut s = Slots::new(32, 32);
+        s.take();
+        assert_eq!(s.settle(0, 1).done, 0);
+        assert_eq!(s.settle(1, 0).done, 0);
+        assert_eq!(s.settle(0, 0).done, 1);
+        assert!(s.idle());
+    }
+
+    #[test]
+    fn busy_bits_nobody_issued_are_strangers_and_change_nothing() {
+        let mut s = Slots::new(32, 32);
+        s.take();
+        let settled = s.settle(0b1001_0000, 0);
+        assert_eq!(settled, Settled { done: 1, strangers: 0b1001_0000 });
+        assert!(s.idle());
+    }
+
+    #[test]
+    fn abandon_returns_what_was_in_flight() {
+        let mut s = Slots::new(32, 32);
+        s.take();
+        s.take();
+        assert_eq!(s.abandon(), 0b11);
+        assert!(s.idle());
+        assert_eq!(s.take(), Some(0));
+    }
+}
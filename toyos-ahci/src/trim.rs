@@ -0,0 +1,82 @@
+//! A sector range as DATA SET MANAGEMENT range entries (ACS-3 §7.5.3.2).
+//!
+//! One entry is eight little-endian bytes: the LBA in bits 47:0 and a length
+//! in bits 63:48, so one entry covers at most 65,535 sectors and one 512-byte
+//! block of entries at most 64 of them. A range longer than that is several
+//! entries, and longer than a block's worth is several commands; this is the
+//! arithmetic that splits it, and [`fill`] says how much of the range one
+//! command took so the caller can come back for the rest.
+
+/// Entries in one 512-byte block.
+pub const ENTRIES_PER_BLOCK: usize = 64;
+/// The most sectors one entry names.
+pub const MAX_ENTRY_SECTORS: u64 = 0xFFFF;
+/// The most an LBA field holds.
+const LBA_MASK: u64 = (1 << 48) - 1;
+
+/// One range entry.
+pub const fn entry(lba: u64, sectors: u16) -> u64 {
+    (lba & LBA_MASK) | (sectors as u64) << 48
+}
+
+/// Fill `out` with entries covering as much of `sectors` from `lba` as fits,
+/// zeroing the rest, and answer how many sectors that covered.
+///
+/// Unused entries are zero because ACS-3 gives a zero-length entry no effect,
+/// and the payload is whole blocks whatever the range.
+pub fn fill(lba: u64, sectors: u64, out: &mut [u64]) -> u64 {
+    let mut covered = 0u64;
+    for slot in out.iter_mut() {
+        let left = sectors - covered;
+        if left == 0 {
+            *slot = 0;
+            continue;
+        }
+        let n = left.min(MAX_ENTRY_SECTORS);
+        // Exact: `n` is at most 0xFFFF.
+        *slot = entry(lba + covered, n as u16);
+        covered += n;
+    }
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_entry_is_lba_low_and_length_high() {
+        assert_eq!(entry(0x1234_5678_9ABC, 8), 0x0008_1234_5678_9ABC);
+        // An LBA past 48 bits is masked rather than carried into the length.
+        assert_eq!(entry(1 << 48 | 5, 1), 0x0001_0000_0000_0005);
+    }
+
+    #[test]
+    fn a_short_range_is_one_entry_and_the_rest_are_zero() {
+        let mut out = [u64::MAX; ENTRIES_PER_BLOCK];
+        assert_eq!(fill(100, 8, &mut out), 8);
+        assert_eq!(out[0], entry(100, 8));
+        assert!(out[1..].iter().all(|&e| e == 0));
+    }
+
+    #[test]
+    fn a_long_range_is_split_at_the_entry_length() {
+        let mut out = [0; ENTRIES_PER_BLOCK];
+        assert_eq!(fill(0, 0x1_0000, &mut out), 0x1_0000);
+        assert_eq!(out[0], entry(0, 0xFFFF));
+        assert_eq!(out[1], entry(0xFFFF, 1));
+        assert_eq!(out[2], 0);
+    }
+
+    /// More than a block's worth covers what fits and says so, and the rest is
+    /// the caller's next command.
+    #[test]
+    fn a_range_past_one_block_is_covered_in_part() {
+        let mut out = [0; ENTRIES_PER_BLOCK];
+        let all = MAX_ENTRY_SECTORS * ENTRIES_PER_BLOCK as u64;
+        assert_eq!(fill(7, all + 1, &mut out), all);
+        assert_eq!(out[63], entry(7 + 63 * MAX_ENTRY_SECTORS, 0xFFFF));
+        assert_eq!(fill(0, 0, &mut out), 0);
+        assert!(out.iter().all(|&e| e == 0));
+    }
+}
@@ -0,0 +1,455 @@
+//! What a kernel asks of a device, by what it means rather than by method
+//! name: is it there, what is it, what does it decode, where are its PCI
+//! interrupts routed, how is it powered, and — for a battery or a thermal
+//! zone — what it is reporting. And, for the machine as a whole, what a
+//! sleeping state is written as and what firmware runs on the way into and
+//! out of one.
+//!
+//! Each answer is one or two evaluations and a decode of what came back. The
+//! decode is as strict as the interpreter: a `_PRT` entry that is not a
+//! four-element package, or a `_BST` with a string where a number goes, is
+//! [`Error::TypeMismatch`], not a guess.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::host::Host;
+use crate::interp::Interpreter;
+use crate::namespace::{NodeId, Object};
+use crate::resource::{self, Resource};
+use crate::value::{Reference, Value};
+use crate::Error;
+
+/// `_STA`'s bits. A device with no `_STA` is present, enabled, shown and
+/// functioning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Status(pub u64);
+
+impl Status {
+    pub const ASSUMED: Status = Status(0x0F);
+
+    pub fn present(self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    pub fn enabled(self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    pub fn functioning(self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    /// For a battery's `_STA`: whether a battery is in the slot.
+    pub fn battery_present(self) -> bool {
+        self.0 & 0x10 != 0
+    }
+}
+
+/// One `_PRT` entry: interrupt pin `pin` (0 for INTA) of every function of
+/// PCI device `device` on the bridge's bus is routed to `source`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Route {
+    pub device: u16,
+    pub pin: u8,
+    pub source: RouteSource,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RouteSource {
+    /// Hard-wired to this GSI.
+    Gsi(u32),
+    /// Through a PCI interrupt link device, whose `_CRS` says which interrupt
+    /// it currently routes to, at `index` among that device's resources.
+    Link { node: NodeId, index: u32 },
+}
+
+/// A battery's fixed description, from `_BIF` or `_BIX`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Battery {
+    /// Whether capacities are mWh and rates mW; otherwise mAh and mA.
+    pub milliwatts: bool,
+    pub design_capacity: Option<u32>,
+    pub last_full_capacity: Option<u32>,
+    pub rechargeable: bool,
+    pub design_voltage: Option<u32>,
+    pub model: String,
+    pub serial: String,
+    pub kind: String,
+}
+
+/// A battery's present state, from `_BST`. A field the battery reports as
+/// unknown (`0xFFFFFFFF`) is `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatteryState {
+    pub discharging: bool,
+    pub charging: bool,
+    pub critical: bool,
+    pub rate: Option<u32>,
+    pub remaining: Option<u32>,
+    pub voltage: Option<u32>,
+}
+
+const UNKNOWN: u64 = 0xFFFF_FFFF;
+
+fn known(v: u64) -> Option<u32> {
+    (v != UNKNOWN).then_some(v as u32)
+}
+
+fn int(p: &[Value], i: usize) -> Result<u64, Error> {
+    p.get(i).and_then(Value::as_integer).ok_or(Error::TypeMismatch)
+}
+
+fn text(p: &[Value], i: usize) -> String {
+    match p.get(i) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Buffer(b)) => {
+            String::from_utf8_lossy(b.split(|&c| c == 0).next().unwrap_or(&[])).into_owned()
+        }
+        _ => String::new(),
+    }
+}
+
+impl Battery {
+    /// `_BIF`: unit, design capacity, last full capacity, technology, design
+    /// voltage, two warning levels, two granularities, then model, serial,
+    /// type and OEM strings.
+    pub fn from_bif(v: &Value) -> Result<Self, Error> {
+        let p = v.as_package().filter(|p| p.len() >= 13).ok_or(Error::TypeMismatch)?;
+        Ok(Battery {
+            milliwatts: int(p, 0)? == 0,
+            design_capacity: known(int(p, 1)?),
+            last_full_capacity: known(int(p, 2)?),
+            rechargeable: int(p, 3)? == 1,
+            design_voltage: known(int(p, 4)?),
+            model: text(p, 9),
+            serial: text(p, 10),
+            kind: text(p, 11),
+        })
+    }
+
+    /// `_BIX`: `_BIF`'s fields with a revision in front and eleven more
+    /// between the levels and the strings.
+    pub fn from_bix(v: &Value) -> Result<Self, Error> {
+        let p = v.as_package().filter(|p| p.len() >= 20).ok_or(Error::TypeMismatch)?;
+        Ok(Battery {
+            milliwatts: int(p, 1)? == 0,
+            design_capacity: known(int(p, 2)?),
+            last_full_capacity: known(int(p, 3)?),
+            rechargeable: int(p, 4)? == 1,
+            design_voltage: known(int(p, 5)?),
+            model: text(p, 16),
+            serial: text(p, 17),
+            kind: text(p, 18),
+        })
+    }
+}
+
+impl BatteryState {
+    pub fn from_bst(v: &Value) -> Result<Self, Error> {
+        let p = v.as_package().filter(|p| p.len() >= 4).ok_or(Error::TypeMismatch)?;
+        let state = int(p, 0)?;
+        Ok(BatteryState {
+            discharging: state & 1 != 0,
+            charging: state & 2 != 0,
+            critical: state & 4 != 0,
+            rate: known(int(p, 1)?),
+            remaining: known(int(p, 2)?),
+            voltage: known(int(p, 3)?),
+        })
+    }
+}
+
+/// A `_HID` or `_CID` value as text: a string as it is, an integer as the
+/// compressed EISA ID it encodes (`PNP0C0D`).
+pub fn id_string(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(n) => {
+            let b = (*n as u32).to_le_bytes();
+            let letter = |x: u8| char::from((x & 0x1F) + 0x40);
+            let hex = |x: u8| char::from_digit(u32::from(x), 16).map(|c| c.to_ascii_uppercase());
+            let mut s = String::with_capacity(7);
+            s.push(letter(b[0] >> 2));
+            s.push(letter((b[0] & 0x3) << 3 | b[1] >> 5));
+            s.push(letter(b[1]));
+            for x in [b[2] >> 4, b[2] & 0xF, b[3] >> 4, b[3] & 0xF] {
+                s.push(hex(x)?);
+            }
+            Some(s)
+        }
+        _ => None,
+    }
+}
+
+impl Interpreter {
+    pub fn status(&mut self, host: &mut dyn Host, node: NodeId) -> Result<Status, Error> {
+        match self.evaluate_child(host, node, "_STA", Vec::new())? {
+            Some(v) => Ok(Status(v.to_integer(self.width())?)),
+            None => Ok(Status::ASSUMED),
+        }
+    }
+
+    /// Tell firmware which interrupt model the OS uses — `\_PIC(1)` for the
+    /// I/O APIC — before asking it for `_PRT`, which commonly returns the
+    /// 8259 routing until it has been told.
+    pub fn set_apic_mode(&mut self, host: &mut dyn Host) -> Result<(), Error> {
+        match self.evaluate(host, "\\_PIC", vec![Value::Integer(1)]) {
+            Ok(_) | Err(Error::NotFound) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run every device's `_INI` as ACPI 6.5 §6.5.1 orders it: `\_SB._INI`
+    /// first, then each device whose `_STA` says present, parents before
+    /// children, not descending below a device that is neither present nor
+    /// functioning. A failed `_INI` is reported and the walk goes on; a
+    /// device firmware could not initialise is no reason to skip its
+    /// siblings.
+    pub fn initialize(&mut self, host: &mut dyn Host) -> Vec<(NodeId, Error)> {
+        let mut failures = Vec::new();
+        let root = self.namespace().root();
+        if let Some(sb) = self.namespace().lookup("\\_SB_") {
+            if let Err(e) = self.evaluate_child(host, sb, "_INI", Vec::new()) {
+                failures.push((sb, e));
+            }
+        }
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let is_device = matches!(
+                self.namespace().object(node),
+                Some(Object::Device | Object::Processor { .. } | Object::ThermalZone)
+            );
+            if is_device {
+                let status = match self.status(host, node) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        failures.push((node, e));
+                        continue;
+                    }
+                };
+                if status.present() {
+                    if let Err(e) = self.evaluate_child(host, node, "_INI", Vec::new()) {
+                        failures.push((node, e));
+                    }
+                } else if !status.functioning() {
+                    continue;
+                }
+            }
+            let mut children: Vec<NodeId> = self.namespace().children(node).collect();
+            children.reverse();
+            stack.extend(children);
+        }
+        failures
+    }
+
+    /// Every device whose `_HID` or one of whose `_CID`s is `id`.
+    pub fn find_devices(&mut self, host: &mut dyn Host, id: &str) -> Vec<NodeId> {
+        let mut found = Vec::new();
+        let root = self.namespace().root();
+        for node in self.namespace().descendants(root) {
+            if !matches!(self.namespace().object(node), Some(Object::Device)) {
+                continue;
+            }
+            let hid = self.evaluate_child(host, node, "_HID", Vec::new()).ok().flatten();
+            let cid = self.evaluate_child(host, node, "_CID", Vec::new()).ok().flatten();
+            let mut ids = Vec::new();
+            ids.extend(hid);
+            match cid {
+                Some(Value::Package(p)) => ids.extend(p),
+                Some(other) => ids.push(other),
+                None => {}
+            }
+            if ids.iter().any(|v| id_string(v).as_deref() == Some(id)) {
+                found.push(node);
+            }
+        }
+        found
+    }
+
+    /// `_CRS`, decoded; `None` for a device without one.
+    pub fn resources(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+    ) -> Result<Option<Vec<Resource>>, Error> {
+        match self.evaluate_child(host, node, "_CRS", Vec::new())? {
+            Some(Value::Buffer(b)) => resource::parse(&b).map(Some),
+            Some(_) => Err(Error::TypeMismatch),
+            None => Ok(None),
+        }
+    }
+
+    /// A PCI root or PCI-to-PCI bridge's `_PRT`; empty for a bridge without
+    /// one.
+    pub fn routing(&mut self, host: &mut dyn Host, bridge: NodeId) -> Result<Vec<Route>, Error> {
+        let Some(table) = self.evaluate_child(host, bridge, "_PRT", Vec::new())? else {
+            return Ok(Vec::new());
+        };
+        let entries = table.as_package().ok_or(Error::TypeMismatch)?;
+        let mut routes = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let e = entry.as_package().filter(|e| e.len() >= 4).ok_or(Error::TypeMismatch)?;
+            let device = (int(e, 0)? >> 16) as u16;
+            let pin = int(e, 1)? as u8;
+            let index = int(e, 3)? as u32;
+            let source = match &e[2] {
+                Value::Integer(0) => RouteSource::Gsi(index),
+                Value::Reference(Reference::Node(node)) => RouteSource::Link { node: *node, index },
+                Value::Reference(Reference::Path { scope, path }) => {
+                    let node = self.namespace().resolve(*scope, path).ok_or(Error::NotFound)?;
+                    RouteSource::Link { node, index }
+                }
+                Value::String(path) => {
+                    let node = self.namespace().lookup(path).ok_or(Error::NotFound)?;
+                    RouteSource::Link { node, index }
+                }
+                _ => return Err(Error::TypeMismatch),
+            };
+            routes.push(Route { device, pin, source });
+        }
+        Ok(routes)
+    }
+
+    /// The interrupt a PCI interrupt link device routes to now: the first its
+    /// `_CRS` names, or `None` if it names none — a link firmware left
+    /// disabled.
+    pub fn link_interrupt(
+        &mut self,
+        host: &mut dyn Host,
+        link: NodeId,
+    ) -> Result<Option<u32>, Error> {
+        let resources = self.resources(host, link)?.unwrap_or_default();
+        Ok(resources.into_iter().find_map(|r| match r {
+            Resource::Irq { interrupts, .. } => interrupts.into_iter().find(|&i| i != 0),
+            _ => None,
+        }))
+    }
+
+    /// Put a device in D0 or D3 by its `_PS0` or `_PS3`. `Ok(false)` for a
+    /// device without the method, whose power state firmware does not manage.
+    pub fn set_power(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+        d3: bool,
+    ) -> Result<bool, Error> {
+        let method = if d3 { "_PS3" } else { "_PS0" };
+        Ok(self.evaluate_child(host, node, method, Vec::new())?.is_some())
+    }
+
+    /// The `SLP_TYPa` and `SLP_TYPb` values for sleeping state `state`, from
+    /// `\_Sx_`, or `None` if firmware does not offer the state. The package
+    /// may have one element for a machine whose two values are the same, or a
+    /// second the specification reserves; both are read as ACPI 6.5 §7.4.2
+    /// says, and anything else is [`Error::TypeMismatch`].
+    pub fn sleep_type(
+        &mut self,
+        host: &mut dyn Host,
+        state: u8,
+    ) -> Result<Option<(u8, u8)>, Error> {
+        let path = match state {
+            0..=5 => format!("\\_S{state}_"),
+            _ => return Err(Error::InvalidName),
+        };
+        let package = match self.evaluate(host, &path, Vec::new()) {
+            Ok(v) => v,
+            Err(Error::NotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let values = package.as_package().ok_or(Error::TypeMismatch)?;
+        let a = values.first().ok_or(Error::TypeMismatch)?.to_integer(self.width())?;
+        let b = match values.get(1) {
+            Some(v) => v.to_integer(self.width())?,
+            None => a,
+        };
+        Ok(Some((a as u8 & 0x7, b as u8 & 0x7)))
+    }
+
+    /// `\_PTS(state)`, which firmware uses to arm whatever wakes the machine
+    /// before the OS writes `SLP_EN`. Absent is fine: most machines have one,
+    /// and one that does not has nothing to prepare.
+    pub fn prepare_to_sleep(&mut self, host: &mut dyn Host, state: u8) -> Result<(), Error> {
+        match self.evaluate(host, "\\_PTS", vec![Value::Integer(u64::from(state))]) {
+            Ok(_) | Err(Error::NotFound) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `\_WAK(state)`, run once the machine is back. Its package is a status
+    /// and the power supply state, which firmware commonly leaves at zero and
+    /// nothing here acts on; only whether it ran is the caller's to know.
+    pub fn wake(&mut self, host: &mut dyn Host, state: u8) -> Result<(), Error> {
+        match self.evaluate(host, "\\_WAK", vec![Value::Integer(u64::from(state))]) {
+            Ok(_) | Err(Error::NotFound) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A battery's description, from `_BIX` where firmware has one and
+    /// `_BIF` otherwise.
+    pub fn battery(&mut self, host: &mut dyn Host, node: NodeId) -> Result<Battery, Error> {
+        if let Some(bix) = self.evaluate_child(host, node, "_BIX", Vec::new())? {
+            return Battery::from_bix(&bix);
+        }
+        let bif = self.evaluate_child(host, node, "_BIF", Vec::new())?.ok_or(Error::NotFound)?;
+        Battery::from_bif(&bif)
+    }
+
+    pub fn battery_state(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+    ) -> Result<BatteryState, Error> {
+        let bst = self.evaluate_child(host, node, "_BST", Vec::new())?.ok_or(Error::NotFound)?;
+        BatteryState::from_bst(&bst)
+    }
+
+    /// A thermal zone's temperature in tenths of a kelvin, from `_TMP`.
+    pub fn temperature(&mut self, host: &mut dyn Host, zone: NodeId) -> Result<u32, Error> {
+        self.thermal_point(host, zone, "_TMP")?.ok_or(Error::NotFound)
+    }
+
+    /// A thermal zone's trip point — `_CRT`, `_HOT` or `_PSV` — in tenths of
+    /// a kelvin, or `None` if firmware defines none.
+    pub fn thermal_point(
+        &mut self,
+        host: &mut dyn Host,
+        zone: NodeId,
+        name: &str,
+    ) -> Result<Option<u32>, Error> {
+        match self.evaluate_child(host, zone, name, Vec::new())? {
+            Some(v) => Ok(Some(v.to_integer(self.width())? as u32)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eisa_ids_decode_to_their_text() {
+        assert_eq!(id_string(&Value::Integer(0x080A_D041)).as_deref(), Some("PNP0A08"));
+        assert_eq!(id_string(&Value::Integer(0x0D0C_D041)).as_deref(), Some("PNP0C0D"));
+        assert_eq!(id_string(&Value::String("ACPI0013".into())).as_deref(), Some("ACPI0013"));
+        assert_eq!(id_string(&Value::Package(Vec::new())), None);
+    }
+
+    #[test]
+    fn an_unknown_battery_reading_is_none() {
+        let bst = Value::Package(vec![
+            Value::Integer(2),
+            Value::Integer(UNKNOWN),
+            Value::Integer(41_000),
+            Value::Integer(12_400),
+        ]);
+        let s = BatteryState::from_bst(&bst).unwrap();
+        assert!(s.charging && !s.discharging);
+        assert_eq!((s.rate, s.remaining), (None, Some(41_000)));
+        assert_eq!(BatteryState::from_bst(&Value::Integer(0)), Err(Error::TypeMismatch));
+    }
+}
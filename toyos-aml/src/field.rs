@@ -0,0 +1,358 @@
+//! Field units: the bits of a region a named field covers, read and written
+//! one access-width unit at a time through the host.
+//!
+//! A field is a run of bits at any offset and of any length; a region is
+//! accessed in units of 1, 2, 4 or 8 bytes at an address aligned to the unit.
+//! Reading a field reads every unit it touches and keeps its bits. Writing one
+//! writes every unit it touches, and what lands in the bits of a unit the field
+//! does not cover is the field's update rule: what was there (a read first),
+//! all ones, or all zeros.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::host::{Host, PciAddress, Space};
+use crate::interp::{buffer_fits, Interpreter};
+use crate::namespace::{Field, FieldKind, NodeId, Object};
+use crate::value::Value;
+use crate::Error;
+
+const PRESERVE: u8 = 0;
+const WRITE_AS_ONES: u8 = 1;
+
+/// How many bytes one access to the field is: as its flags say, or for
+/// `AnyAcc` the narrowest aligned unit that holds the whole field, so that a
+/// DWORD-aligned 32-bit field is one DWORD access as the hardware behind it
+/// most likely expects.
+fn unit_bytes(field: &Field) -> Result<u64, Error> {
+    match field.flags & 0x0F {
+        0 => Ok([1u64, 2, 4, 8]
+            .into_iter()
+            .find(|w| {
+                let bits = w * 8;
+                let last = field.bit_offset + field.bit_len.max(1) - 1;
+                field.bit_offset / bits == last / bits
+            })
+            .unwrap_or(1)),
+        1 => Ok(1),
+        2 => Ok(2),
+        3 => Ok(4),
+        4 => Ok(8),
+        _ => Err(Error::Unsupported),
+    }
+}
+
+fn mask(bits: u64) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << bits) - 1
+    }
+}
+
+/// `bits` bits of `src` from bit `at`, little-endian across bytes.
+fn get_bits(src: &[u8], at: u64, bits: u64) -> u64 {
+    let mut out = 0u64;
+    for i in 0..bits {
+        let bit = at + i;
+        let byte = src.get((bit / 8) as usize).copied().unwrap_or(0);
+        out |= u64::from((byte >> (bit % 8)) & 1) << i;
+    }
+    out
+}
+
+fn put_bits(dst: &mut [u8], at: u64, bits: u64, value: u64) {
+    for i in 0..bits {
+        let bit = at + i;
+        if let Some(byte) = dst.get_mut((bit / 8) as usize) {
+            let m = 1u8 << (bit % 8);
+            if (value >> i) & 1 != 0 {
+                *byte |= m;
+            } else {
+                *byte &= !m;
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    fn field(&self, node: NodeId) -> Result<Field, Error> {
+        match self.ns.object(node) {
+            Some(Object::Field(f)) => Ok(f.clone()),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// A field's contents: an integer if they fit one, a buffer otherwise.
+    pub(crate) fn read_field(&mut self, host: &mut dyn Host, node: NodeId) -> Result<Value, Error> {
+        let f = self.field(node)?;
+        let unit = unit_bytes(&f)?;
+        let unit_bits = unit * 8;
+        let mut out = vec![0u8; buffer_fits(f.bit_len.div_ceil(8))?];
+        let end = f.bit_offset + f.bit_len;
+        let mut done = 0u64;
+        let mut at = f.bit_offset;
+        while at < end {
+            self.step()?;
+            let index = at / unit_bits;
+            let lo = at - index * unit_bits;
+            let hi = (end - index * unit_bits).min(unit_bits);
+            let raw = self.read_unit(host, &f.kind, index * unit, unit)?;
+            put_bits(&mut out, done, hi - lo, (raw >> lo) & mask(hi - lo));
+            done += hi - lo;
+            at += hi - lo;
+        }
+        Ok(self.field_value(out, f.bit_len))
+    }
+
+    pub(crate) fn write_field(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+        value: Value,
+    ) -> Result<(), Error> {
+        let f = self.field(node)?;
+        let unit = unit_bytes(&f)?;
+        let unit_bits = unit * 8;
+        let src = match value {
+            Value::Integer(v) => v.to_le_bytes().to_vec(),
+            other => other.to_buffer(self.width)?,
+        };
+        let end = f.bit_offset + f.bit_len;
+        let mut done = 0u64;
+        let mut at = f.bit_offset;
+        while at < end {
+            self.step()?;
+            let index = at / unit_bits;
+            let lo = at - index * unit_bits;
+            let hi = (end - index * unit_bits).min(unit_bits);
+            let n = hi - lo;
+            let bits = get_bits(&src, done, n);
+            let covered = mask(n) << lo;
+            let rest = if n == unit_bits {
+                0
+            } else {
+                match (f.flags >> 5) & 0x3 {
+                    PRESERVE => self.read_unit(host, &f.kind, index * unit, unit)?,
+                    WRITE_AS_ONES => u64::MAX,
+                    _ => 0,
+                }
+            };
+            let word = (rest & !covered) | ((bits << lo) & covered);
+            self.write_unit(host, &f.kind, index * unit, unit, word & mask(unit_bits))?;
+            done += n;
+            at += n;
+        }
+        Ok(())
+    }
+
+    fn field_value(&self, bytes: Vec<u8>, bit_len: u64) -> Value {
+        if bit_len <= u64::from(self.width.bits()) {
+            Value::Integer(get_bits(&bytes, 0, bit_len))
+        } else {
+            Value::Buffer(bytes)
+        }
+    }
+
+    fn read_unit(
+        &mut self,
+        host: &mut dyn Host,
+        kind: &FieldKind,
+        offset: u64,
+        unit: u64,
+    ) -> Result<u64, Error> {
+        self.enter()?;
+        let result = match *kind {
+            FieldKind::Normal { region } => self.region_access(host, region, offset, unit, None),
+            FieldKind::Bank { region, bank, value } => self
+                .write_field(host, bank, Value::Integer(value))
+                .and_then(|()| self.region_access(host, region, offset, unit, None)),
+            FieldKind::Index { index, data } => self
+                .write_field(host, index, Value::Integer(offset))
+                .and_then(|()| self.read_field(host, data))
+                .and_then(|v| v.to_integer(self.width)),
+        };
+        self.depth -= 1;
+        result
+    }
+
+    fn write_unit(
+        &mut self,
+        host: &mut dyn Host,
+        kind: &FieldKind,
+        offset: u64,
+        unit: u64,
+        word: u64,
+    ) -> Result<(), Error> {
+        self.enter()?;
+        let result = match *kind {
+            FieldKind::Normal { region } => {
+                self.region_access(host, region, offset, unit, Some(word)).map(drop)
+            }
+            FieldKind::Bank { region, bank, value } => {
+                self.write_field(host, bank, Value::Integer(value)).and_then(|()| {
+                    self.region_access(host, region, offset, unit, Some(word)).map(drop)
+                })
+            }
+            FieldKind::Index { index, data } => self
+                .write_field(host, index, Value::Integer(offset))
+                .and_then(|()| self.write_field(host, data, Value::Integer(word))),
+        };
+        self.depth -= 1;
+        result
+    }
+
+    /// One access to a region, bounds-checked against its declared length:
+    /// a field that runs past its region is firmware asking for bytes it did
+    /// not declare, and the host is not asked.
+    fn region_access(
+        &mut self,
+        host: &mut dyn Host,
+        region: NodeId,
+        offset: u64,
+        unit: u64,
+        write: Option<u64>,
+    ) -> Result<u64, Error> {
+        let (space, base, length) = match self.ns.object(region) {
+            Some(Object::Region(r)) => (r.space, r.offset, r.length),
+            _ => return Err(Error::TypeMismatch),
+        };
+        if offset.checked_add(unit).is_none_or(|end| end > length) {
+            return Err(Error::OutOfRange);
+        }
+        let pci = if space == 2 { Some(self.pci_address(host, region)?) } else { None };
+        let space = Space::from_byte(space, pci);
+        let address = base.checked_add(offset).ok_or(Error::OutOfRange)?;
+        let width = (unit * 8) as u8;
+        match write {
+            Some(word) => host.write(space, address, width, word).map(|()| 0),
+            None => host.read(space, address, width),
+        }
+    }
+
+    /// The PCI function a config-space region belongs to: device and function
+    /// from the `_ADR` of the device that declares it, bus and segment from
+    /// the nearest `_BBN` and `_SEG` above. A region in a device behind a
+    /// PCI-to-PCI bridge would need the bridge's secondary bus read from its
+    /// own config space; firmware puts such regions on the root bus in
+    /// practice, and this takes the root bridge's.
+    fn pci_address(&mut self, host: &mut dyn Host, region: NodeId) -> Result<PciAddress, Error> {
+        if let Some(Object::Region(r)) = self.ns.object(region) {
+            if let Some(addr) = r.pci {
+                return Ok(addr);
+            }
+        }
+        let device = self.ns.parent(region).ok_or(Error::NotFound)?;
+        let adr = self.ancestor_value(host, device, "_ADR")?.unwrap_or(0);
+        let bus = self.ancestor_value(host, device, "_BBN")?.unwrap_or(0);
+        let segment = self.ancestor_value(host, device, "_SEG")?.unwrap_or(0);
+        let addr = PciAddress {
+            segment: segment as u16,
+            bus: bus as u8,
+            device: (adr >> 16) as u8,
+            function: adr as u8,
+        };
+        if let Some(Object::Region(r)) = self.ns.get_mut(region).map(|n| &mut n.object) {
+            r.pci = Some(addr);
+        }
+        Ok(addr)
+    }
+
+    /// `name` evaluated as an integer on the nearest of `node` and its
+    /// ancestors that has it.
+    fn ancestor_value(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+        name: &str,
+    ) -> Result<Option<u64>, Error> {
+        let seg = crate::NameSeg::parse(name).ok_or(Error::InvalidName)?;
+        let mut at = Some(node);
+        while let Some(here) = at {
+            if let Some(child) = self.ns.child(here, seg) {
+                let v = self.call(host, child, Vec::new())?;
+                return v.to_integer(self.width).map(Some);
+            }
+            at = self.ns.parent(here);
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn read_buffer_field(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+    ) -> Result<Value, Error> {
+        let Some(Object::BufferField(f)) = self.ns.object(node) else {
+            return Err(Error::TypeMismatch);
+        };
+        let (bit_offset, bit_len) = (f.bit_offset, f.bit_len);
+        let source = self.read_ref(host, &f.source.clone())?;
+        let Value::Buffer(bytes) = source else {
+            return Err(Error::TypeMismatch);
+        };
+        if bit_offset + bit_len > bytes.len() as u64 * 8 {
+            return Err(Error::OutOfRange);
+        }
+        let mut out = vec![0u8; buffer_fits(bit_len.div_ceil(8))?];
+        for chunk in (0..bit_len).step_by(64) {
+            let n = (bit_len - chunk).min(64);
+            put_bits(&mut out, chunk, n, get_bits(&bytes, bit_offset + chunk, n));
+        }
+        Ok(self.field_value(out, bit_len))
+    }
+
+    pub(crate) fn write_buffer_field(
+        &mut self,
+        _host: &mut dyn Host,
+        node: NodeId,
+        value: Value,
+    ) -> Result<(), Error> {
+        let Some(Object::BufferField(f)) = self.ns.object(node) else {
+            return Err(Error::TypeMismatch);
+        };
+        let (source, bit_offset, bit_len) = (f.source.clone(), f.bit_offset, f.bit_len);
+        let src = match value {
+            Value::Integer(v) => v.to_le_bytes().to_vec(),
+            other => other.to_buffer(self.width)?,
+        };
+        let bytes = self.buffer_mut(&source)?;
+        if bit_offset + bit_len > bytes.len() as u64 * 8 {
+            return Err(Error::OutOfRange);
+        }
+        for chunk in (0..bit_len).step_by(64) {
+            let n = (bit_len - chunk).min(64);
+            put_bits(bytes, bit_offset + chunk, n, get_bits(&src, chunk, n));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_cross_byte_boundaries() {
+        let mut b = [0u8; 3];
+        put_bits(&mut b, 4, 12, 0xABC);
+        assert_eq!(b, [0xC0, 0xAB, 0x00]);
+        assert_eq!(get_bits(&b, 4, 12), 0xABC);
+        assert_eq!(get_bits(&b, 20, 8), 0, "bits past the end read as zero");
+    }
+
+    #[test]
+    fn any_access_takes_the_narrowest_aligned_unit() {
+        let field = |bit_offset, bit_len| Field {
+            kind: FieldKind::Normal { region: NodeId(0) },
+            bit_offset,
+            bit_len,
+            flags: 0,
+        };
+        assert_eq!(unit_bytes(&field(0, 8)), Ok(1));
+        assert_eq!(unit_bytes(&field(0, 32)), Ok(4));
+        assert_eq!(unit_bytes(&field(4, 8)), Ok(2));
+        assert_eq!(unit_bytes(&field(60, 8)), Ok(1), "no unit holds it, so bytes");
+        assert_eq!(unit_bytes(&Field { flags: 5, ..field(0, 8) }), Err(Error::Unsupported));
+    }
+}
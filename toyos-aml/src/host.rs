@@ -0,0 +1,79 @@
+//! The one way out of the interpreter.
+//!
+//! AML reaches hardware only through operation regions, and every access to
+//! one is a call here. The host decides what is allowed: the kernel's refuses
+//! system memory that is RAM it manages, and answers the embedded controller's
+//! space by running the EC protocol rather than by touching a register, because
+//! no register *is* that space. A refusal is [`Error::Refused`] in the method
+//! that asked, and the method stops there.
+//!
+//! The host is passed to each call rather than held, so that the kernel can
+//! load tables at boot with one that spins and run GPE methods later, on a
+//! thread, with one that sleeps.
+
+use crate::value::Value;
+use crate::Error;
+
+/// Which address space an access is in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Space {
+    SystemMemory,
+    SystemIo,
+    /// The configuration space of one PCI function; the address is the
+    /// register offset within it.
+    PciConfig(PciAddress),
+    /// The embedded controller's 256 byte-wide registers.
+    EmbeddedControl,
+    /// Any other `RegionSpace` byte: SMBus, CMOS, GPIO, generic serial bus and
+    /// the OEM range, none of which this interpreter formats accesses for.
+    Other(u8),
+}
+
+impl Space {
+    /// The space a `RegionSpace` byte names, once the PCI address — which the
+    /// byte does not carry — is known.
+    pub(crate) fn from_byte(byte: u8, pci: Option<PciAddress>) -> Self {
+        match byte {
+            0 => Space::SystemMemory,
+            1 => Space::SystemIo,
+            2 => match pci {
+                Some(addr) => Space::PciConfig(addr),
+                None => Space::Other(2),
+            },
+            3 => Space::EmbeddedControl,
+            other => Space::Other(other),
+        }
+    }
+}
+
+/// A PCI function, as ACPI locates one: the segment from `_SEG`, the bus from
+/// the host bridge's `_BBN`, the device and function from `_ADR`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PciAddress {
+    pub segment: u16,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+/// What the interpreter needs from the machine.
+pub trait Host {
+    /// Read `width` bits — 8, 16, 32 or 64 — at `address` in `space`.
+    fn read(&mut self, space: Space, address: u64, width: u8) -> Result<u64, Error>;
+
+    /// Write the low `width` bits of `value` at `address` in `space`.
+    fn write(&mut self, space: Space, address: u64, width: u8, value: u64) -> Result<(), Error>;
+
+    /// `Sleep`: give up the CPU for at least `ms` milliseconds.
+    fn sleep(&mut self, ms: u64);
+
+    /// `Stall`: busy-wait `us` microseconds, never more than 100 by the spec
+    /// and clamped to that by the interpreter.
+    fn stall(&mut self, us: u64);
+
+    /// `Timer`: a monotonic count of 100 ns ticks.
+    fn timer(&mut self) -> u64;
+
+    /// A `Store` to `Debug`. Firmware writes its own trace this way.
+    fn debug(&mut self, _value: &Value) {}
+}
@@ -0,0 +1,1644 @@
+//! The interpreter: loading definition blocks, and evaluating what they
+//! define.
+//!
+//! One recursive evaluator runs both. A table's top-level terms are executed
+//! as they are read — a definition adds a node, and the occasional `If` at
+//! scope level is evaluated like any other — and a method body is the same
+//! terms, run later from the bytes its table left behind. AML has no separate
+//! parse: a term's operand count is fixed by its opcode, or for a method call
+//! by the method's definition, so reading and running cannot be split without
+//! knowing the namespace anyway.
+//!
+//! Everything is bounded. Each evaluation entered from outside starts a fresh
+//! [`STEP_BUDGET`]; nesting of blocks, expressions and calls together may not
+//! pass [`MAX_DEPTH`]; the objects a method builds are capped by
+//! [`MAX_BUFFER`] and [`MAX_PACKAGE`], and the namespace by
+//! [`crate::MAX_NODES`].
+//!
+//! Mutexes, events and the global lock are accepted and do nothing: one
+//! interpreter runs one method at a time, so nothing can contend for them
+//! here, and the host serialises the interpreter as a whole.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Write;
+
+use crate::host::Host;
+use crate::name::{NameSeg, NameString};
+use crate::namespace::{BufferField, Field, FieldKind, Method, Namespace, NodeId, Object, Region};
+use crate::stream::Cursor;
+use crate::value::{check_len, Reference, Value, Width};
+use crate::{Error, MAX_BUFFER, MAX_DEPTH, MAX_PACKAGE, STEP_BUDGET};
+
+/// What `Revision` evaluates to: the version of this interpreter's behaviour,
+/// bumped when a method could observe the difference.
+pub const REVISION: u64 = 1;
+
+/// The SDT header every definition block starts with.
+const HEADER_LEN: usize = 36;
+
+/// `Stall`'s ceiling, from ACPI 6.5 §19.6.129. Firmware that asks for longer
+/// is asking to spin a CPU for as long as it likes.
+const MAX_STALL_US: u64 = 100;
+
+/// The interfaces `_OSI` answers true for: every Windows release this
+/// interpreter's firmware might branch on, and the features Linux claims. A
+/// laptop's DSDT tends to take its best-tested path — the newest Windows it
+/// knows — only when asked for that, and `_OSI("Linux")` is deliberately
+/// false for the same reason it is on Linux.
+const INTERFACES: &[&str] = &[
+    "Windows 2000",
+    "Windows 2001",
+    "Windows 2001 SP1",
+    "Windows 2001.1",
+    "Windows 2001 SP2",
+    "Windows 2001.1 SP1",
+    "Windows 2006",
+    "Windows 2006.1",
+    "Windows 2006 SP1",
+    "Windows 2006 SP2",
+    "Windows 2009",
+    "Windows 2012",
+    "Windows 2013",
+    "Windows 2015",
+    "Windows 2016",
+    "Windows 2017",
+    "Windows 2017.2",
+    "Windows 2018",
+    "Windows 2018.2",
+    "Windows 2019",
+    "Windows 2020",
+    "Windows 2021",
+    "Windows 2022",
+    "Module Device",
+    "Processor Device",
+    "3.0 Thermal Model",
+    "Extended Address Space Descriptor",
+];
+
+/// A `Notify` a method executed: `value` for the object at `node`. The
+/// interpreter only records them; what a notification means is the caller's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub node: NodeId,
+    pub value: u8,
+}
+
+/// One method's activation.
+struct Frame {
+    args: [Value; 7],
+    locals: [Value; 8],
+    /// The nodes this call defined, removed when it returns. `None` for a
+    /// table load, whose definitions are the point.
+    created: Option<Vec<NodeId>>,
+}
+
+impl Frame {
+    fn new(args: Vec<Value>, temporary: bool) -> Self {
+        let mut frame = Frame {
+            args: Default::default(),
+            locals: Default::default(),
+            created: temporary.then(Vec::new),
+        };
+        for (slot, arg) in frame.args.iter_mut().zip(args) {
+            *slot = arg;
+        }
+        frame
+    }
+}
+
+/// How a block ended.
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// Where a result goes.
+enum Target {
+    Null,
+    Debug,
+    Ref(Reference),
+}
+
+pub struct Interpreter {
+    pub(crate) ns: Namespace,
+    pub(crate) width: Width,
+    frames: Vec<Frame>,
+    steps: u64,
+    pub(crate) depth: usize,
+    notifications: Vec<Notification>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            ns: Namespace::new(),
+            width: Width::Wide,
+            frames: Vec::new(),
+            steps: 0,
+            depth: 0,
+            notifications: Vec::new(),
+        }
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.ns
+    }
+
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
+    /// Every `Notify` executed since the last call, oldest first.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        core::mem::take(&mut self.notifications)
+    }
+
+    /// Load one definition block — the DSDT, then each SSDT — header and all.
+    ///
+    /// A failure leaves whatever the table defined before it in place: the
+    /// namespace is still the best account of the machine there is, and a
+    /// caller that wants nothing from a table that failed can build another
+    /// interpreter.
+    pub fn load_table(&mut self, host: &mut dyn Host, table: &[u8]) -> Result<(), Error> {
+        if table.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let len = u32::from_le_bytes([table[4], table[5], table[6], table[7]]) as usize;
+        if len < HEADER_LEN || len > table.len() {
+            return Err(Error::Truncated);
+        }
+        if &table[..4] == b"DSDT" && table[8] < 2 {
+            self.width = Width::Narrow;
+        }
+        let code: Arc<[u8]> = Arc::from(&table[HEADER_LEN..len]);
+        self.begin();
+        self.frames.push(Frame::new(Vec::new(), false));
+        let root = self.ns.root();
+        let result = self.exec_block(host, &mut Cursor::new(code), root);
+        self.frames.pop();
+        result.map(|_| ())
+    }
+
+    /// Evaluate the object at an absolute path: run it if it is a method,
+    /// read it if it is data or a field.
+    pub fn evaluate(
+        &mut self,
+        host: &mut dyn Host,
+        path: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let node = self.ns.lookup(path).ok_or(Error::NotFound)?;
+        self.evaluate_node(host, node, args)
+    }
+
+    pub fn evaluate_node(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+        args: Vec<Value>,
+    ) -> Result<Value, Error> {
+        self.begin();
+        self.call(host, node, args)
+    }
+
+    /// Evaluate `name` under `node`, or `None` if it has no such child — the
+    /// form every optional method (`_STA`, `_INI`, `_PS0`) is asked in.
+    pub fn evaluate_child(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, Error> {
+        let seg = NameSeg::parse(name).ok_or(Error::InvalidName)?;
+        match self.ns.child(node, seg) {
+            Some(child) => self.evaluate_node(host, child, args).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// A fresh budget for a call from outside. A nested call — `_ADR` run to
+    /// address a PCI region mid-method — shares its caller's.
+    fn begin(&mut self) {
+        if self.frames.is_empty() {
+            self.steps = 0;
+            self.depth = 0;
+        }
+    }
+
+    pub(crate) fn step(&mut self) -> Result<(), Error> {
+        self.steps += 1;
+        if self.steps > STEP_BUDGET {
+            return Err(Error::Budget);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn enter(&mut self) -> Result<(), Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::TooDeep);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn frame(&self) -> usize {
+        self.frames.len().saturating_sub(1)
+    }
+
+    /// Add a node and, inside a method, remember to remove it on return.
+    fn define(
+        &mut self,
+        scope: NodeId,
+        path: &NameString,
+        object: Object,
+    ) -> Result<NodeId, Error> {
+        let (parent, seg) = self.ns.parent_for(scope, path)?;
+        let id = self.ns.add(parent, seg, object)?;
+        if let Some(created) = self.frames.last_mut().and_then(|f| f.created.as_mut()) {
+            created.push(id);
+        }
+        Ok(id)
+    }
+
+    fn resolve(&self, scope: NodeId, path: &NameString) -> Result<NodeId, Error> {
+        self.ns.resolve(scope, path).ok_or(Error::NotFound)
+    }
+
+    /// Run a node: a method with `args`, `\_OSI`, or anything else read as a
+    /// value.
+    pub(crate) fn call(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+        args: Vec<Value>,
+    ) -> Result<Value, Error> {
+        match self.ns.object(node).ok_or(Error::NotFound)? {
+            Object::Method(m) => {
+                let m = m.clone();
+                self.invoke(host, node, &m, args)
+            }
+            Object::Osi => {
+                let asked = args.first().and_then(Value::as_str).ok_or(Error::Arguments)?;
+                let yes = INTERFACES.contains(&asked);
+                Ok(Value::Integer(if yes { self.width.ones() } else { 0 }))
+            }
+            _ => self.read_node(host, node),
+        }
+    }
+
+    fn invoke(
+        &mut self,
+        host: &mut dyn Host,
+        node: NodeId,
+        m: &Method,
+        args: Vec<Value>,
+    ) -> Result<Value, Error> {
+        if args.len() < usize::from(m.args) {
+            return Err(Error::Arguments);
+        }
+        self.enter()?;
+        self.frames.push(Frame::new(args, true));
+        let mut body = Cursor::window(m.code.clone(), m.start, m.end);
+        let result = self.exec_terms(host, &mut body, node);
+        let frame = self.frames.pop();
+        self.depth -= 1;
+        if let Some(created) = frame.and_then(|f| f.created) {
+            for id in created.into_iter().rev() {
+                self.ns.remove(id);
+            }
+        }
+        match result? {
+            Flow::Return(v) => Ok(v),
+            _ => Ok(Value::Uninitialized),
+        }
+    }
+
+    fn exec_block(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Flow, Error> {
+        self.enter()?;
+        let flow = self.exec_terms(host, c, scope);
+        self.depth -= 1;
+        flow
+    }
+
+    fn exec_terms(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Flow, Error> {
+        while !c.at_end() {
+            match self.exec_term(host, c, scope)? {
+                Flow::Next => {}
+                other => return Ok(other),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// One `TermObj`: a statement, a definition, or an expression whose result
+    /// is thrown away.
+    fn exec_term(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Flow, Error> {
+        self.step()?;
+        match c.peek()? {
+            0x06 => {
+                c.byte()?;
+                let source = NameString::read(c)?;
+                let alias = NameString::read(c)?;
+                let target = self.resolve(scope, &source)?;
+                self.define(scope, &alias, Object::Alias(target))?;
+            }
+            0x08 => {
+                c.byte()?;
+                let name = NameString::read(c)?;
+                let value = self.eval(host, c, scope)?;
+                self.define(scope, &name, Object::Name(value))?;
+            }
+            0x10 => {
+                c.byte()?;
+                let end = c.pkg_end()?;
+                let name = NameString::read(c)?;
+                let target = self.resolve(scope, &name)?;
+                let mut body = c.split(end);
+                if let Flow::Return(v) = self.exec_block(host, &mut body, target)? {
+                    return Ok(Flow::Return(v));
+                }
+            }
+            0x14 => {
+                c.byte()?;
+                let end = c.pkg_end()?;
+                let name = NameString::read(c)?;
+                let flags = c.byte()?;
+                let method = Method {
+                    code: c.code().clone(),
+                    start: c.pos(),
+                    end,
+                    args: flags & 0x7,
+                    serialized: flags & 0x8 != 0,
+                };
+                c.skip_to(end);
+                self.define(scope, &name, Object::Method(method))?;
+            }
+            0x15 => {
+                // External: a name some other table defines, and its type and
+                // argument count, for a disassembler. The namespace learns
+                // nothing until that table loads.
+                c.byte()?;
+                NameString::read(c)?;
+                c.bytes(2)?;
+            }
+            0xA0 => return self.exec_if(host, c, scope),
+            0xA1 => {
+                // An Else whose If was not taken was consumed with it; one
+                // reached here follows a taken If, or nothing.
+                c.byte()?;
+                let end = c.pkg_end()?;
+                c.skip_to(end);
+            }
+            0xA2 => return self.exec_while(host, c, scope),
+            0xA3 | 0xCC => {
+                c.byte()?;
+            }
+            0xA4 => {
+                c.byte()?;
+                let value =
+                    if c.at_end() { Value::Uninitialized } else { self.eval(host, c, scope)? };
+                return Ok(Flow::Return(value));
+            }
+            0xA5 => {
+                c.byte()?;
+                return Ok(Flow::Break);
+            }
+            0x9F => {
+                c.byte()?;
+                return Ok(Flow::Continue);
+            }
+            0x86 => {
+                c.byte()?;
+                let target = self.target(host, c, scope)?;
+                let value = self.eval_int(host, c, scope)?;
+                if let Target::Ref(Reference::Node(node)) = target {
+                    self.notifications.push(Notification { node, value: value as u8 });
+                } else {
+                    return Err(Error::TypeMismatch);
+                }
+            }
+            0x5B => return self.exec_ext(host, c, scope),
+            _ => {
+                self.eval(host, c, scope)?;
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exec_ext(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Flow, Error> {
+        let Some(op) = c.peek_second() else {
+            return Err(Error::Truncated);
+        };
+        match op {
+            0x01 => {
+                c.bytes(2)?;
+                let name = NameString::read(c)?;
+                let sync = c.byte()? & 0xF;
+                self.define(scope, &name, Object::Mutex { sync })?;
+            }
+            0x02 => {
+                c.bytes(2)?;
+                let name = NameString::read(c)?;
+                self.define(scope, &name, Object::Event)?;
+            }
+            0x80 => {
+                c.bytes(2)?;
+                let name = NameString::read(c)?;
+                let space = c.byte()?;
+                let offset = self.eval_int(host, c, scope)?;
+                let length = self.eval_int(host, c, scope)?;
+                self.define(
+                    scope,
+                    &name,
+                    Object::Region(Region { space, offset, length, pci: None }),
+                )?;
+            }
+            0x88 => {
+                // DataRegion: a region over another ACPI table, wherever the
+                // host mapped it. Defined so that fields in it parse; given a
+                // space in the OEM range so that any access to it is refused
+                // by a host that, like the kernel's, knows no such space.
+                c.bytes(2)?;
+                let name = NameString::read(c)?;
+                for _ in 0..3 {
+                    self.eval(host, c, scope)?;
+                }
+                let region = Region { space: 0x80, offset: 0, length: 0, pci: None };
+                self.define(scope, &name, Object::Region(region))?;
+            }
+            0x81 | 0x86 | 0x87 => {
+                c.bytes(2)?;
+                let end = c.pkg_end()?;
+                let mut body = c.split(end);
+                let kind = match op {
+                    0x81 => {
+                        let region = NameString::read(&mut body)?;
+                        FieldKind::Normal { region: self.resolve(scope, &region)? }
+                    }
+                    0x86 => {
+                        let index = NameString::read(&mut body)?;
+                        let data = NameString::read(&mut body)?;
+                        FieldKind::Index {
+                            index: self.resolve(scope, &index)?,
+                            data: self.resolve(scope, &data)?,
+                        }
+                    }
+                    _ => {
+                        let region = NameString::read(&mut body)?;
+                        let bank = NameString::read(&mut body)?;
+                        let region = self.resolve(scope, &region)?;
+                        let bank = self.resolve(scope, &bank)?;
+                        let value = self.eval_int(host, &mut body, scope)?;
+                        FieldKind::Bank { region, bank, value }
+                    }
+                };
+                let flags = body.byte()?;
+                self.field_list(&mut body, scope, kind, flags)?;
+            }
+            0x82..=0x85 => {
+                c.bytes(2)?;
+                let end = c.pkg_end()?;
+                let mut body = c.split(end);
+                let name = NameString::read(&mut body)?;
+                let object = match op {
+                    0x82 => Object::Device,
+                    0x83 => {
+                        let id = body.byte()?;
+                        let block = body.dword()?;
+                        let block_len = body.byte()?;
+                        Object::Processor { id, block, block_len }
+                    }
+                    0x84 => {
+                        let level = body.byte()?;
+                        let order = body.word()?;
+                        Object::PowerResource { level, order }
+                    }
+                    _ => Object::ThermalZone,
+                };
+                let node = self.define(scope, &name, object)?;
+                if let Flow::Return(v) = self.exec_block(host, &mut body, node)? {
+                    return Ok(Flow::Return(v));
+                }
+            }
+            0x21 => {
+                c.bytes(2)?;
+                let us = self.eval_int(host, c, scope)?;
+                host.stall(us.min(MAX_STALL_US));
+            }
+            0x22 => {
+                c.bytes(2)?;
+                let ms = self.eval_int(host, c, scope)?;
+                host.sleep(ms);
+            }
+            0x24 | 0x26 | 0x27 => {
+                // Signal, Reset, Release.
+                c.bytes(2)?;
+                self.target(host, c, scope)?;
+            }
+            0x32 => {
+                c.bytes(2)?;
+                c.byte()?;
+                c.dword()?;
+                self.eval(host, c, scope)?;
+                return Err(Error::Fatal);
+            }
+            0x1F | 0x20 | 0x2A => return Err(Error::Unsupported),
+            _ => {
+                self.eval(host, c, scope)?;
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exec_if(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Flow, Error> {
+        c.byte()?;
+        let end = c.pkg_end()?;
+        let mut body = c.split(end);
+        let taken = self.eval_int(host, &mut body, scope)? != 0;
+        let has_else = c.peek().ok() == Some(0xA1);
+        let mut flow = Flow::Next;
+        if taken {
+            flow = self.exec_block(host, &mut body, scope)?;
+        }
+        if has_else {
+            c.byte()?;
+            let end = c.pkg_end()?;
+            let mut other = c.split(end);
+            if !taken {
+                flow = self.exec_block(host, &mut other, scope)?;
+            }
+        }
+        Ok(flow)
+    }
+
+    fn exec_while(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Flow, Error> {
+        c.byte()?;
+        let end = c.pkg_end()?;
+        let body = c.split(end);
+        loop {
+            self.step()?;
+            let mut pass = body.clone();
+            if self.eval_int(host, &mut pass, scope)? == 0 {
+                return Ok(Flow::Next);
+            }
+            match self.exec_block(host, &mut pass, scope)? {
+                Flow::Break => return Ok(Flow::Next),
+                Flow::Return(v) => return Ok(Flow::Return(v)),
+                Flow::Next | Flow::Continue => {}
+            }
+        }
+    }
+
+    /// A `FieldList`: named fields laid end to end from bit 0, with reserved
+    /// gaps and changes of access type between them.
+    fn field_list(
+        &mut self,
+        c: &mut Cursor,
+        scope: NodeId,
+        kind: FieldKind,
+        mut flags: u8,
+    ) -> Result<(), Error> {
+        let mut bit_offset = 0u64;
+        while !c.at_end() {
+            self.step()?;
+            match c.peek()? {
+                0x00 => {
+                    c.byte()?;
+                    bit_offset += u64::from(c.pkg_length_raw()?);
+                }
+                0x01 => {
+                    c.byte()?;
+                    flags = (flags & !0x0F) | (c.byte()? & 0x0F);
+                    c.byte()?;
+                }
+                0x02 => {
+                    // ConnectField, for GPIO and serial-bus fields: a name or a
+                    // buffer this interpreter has no space to use.
+                    c.byte()?;
+                    if c.peek()? == 0x11 {
+                        c.byte()?;
+                        let end = c.pkg_end()?;
+                        c.skip_to(end);
+                    } else {
+                        NameString::read(c)?;
+                    }
+                }
+                0x03 => {
+                    c.byte()?;
+                    flags = (flags & !0x0F) | (c.byte()? & 0x0F);
+                    c.bytes(2)?;
+                }
+                _ => {
+                    let seg = NameSeg::read(c)?;
+                    let bit_len = u64::from(c.pkg_length_raw()?);
+                    let field = Field { kind: kind.clone(), bit_offset, bit_len, flags };
+                    let path = NameString { root: false, parents: 0, segs: vec![seg] };
+                    self.define(scope, &path, Object::Field(field))?;
+                    bit_offset += bit_len;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn eval_int(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<u64, Error> {
+        self.eval(host, c, scope)?.to_integer(self.width)
+    }
+
+    /// One `TermArg`, evaluated.
+    fn eval(&mut self, host: &mut dyn Host, c: &mut Cursor, scope: NodeId) -> Result<Value, Error> {
+        self.step()?;
+        self.enter()?;
+        let value = self.eval_inner(host, c, scope);
+        self.depth -= 1;
+        value
+    }
+
+    fn eval_inner(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Value, Error> {
+        let width = self.width;
+        let op = c.peek()?;
+        if NameString::starts(op) {
+            return self.eval_name(host, c, scope);
+        }
+        c.byte()?;
+        let int = |v: u64| Ok(Value::Integer(width.truncate(v)));
+        match op {
+            0x00 => int(0),
+            0x01 => int(1),
+            0xFF => int(u64::MAX),
+            0x0A => int(u64::from(c.byte()?)),
+            0x0B => int(u64::from(c.word()?)),
+            0x0C => int(u64::from(c.dword()?)),
+            0x0E => int(c.qword()?),
+            0x0D => {
+                let mut s = Vec::new();
+                loop {
+                    match c.byte()? {
+                        0 => break,
+                        b => s.push(b),
+                    }
+                    check_len(s.len())?;
+                }
+                Ok(Value::String(String::from_utf8_lossy(&s).into_owned()))
+            }
+            0x11 => {
+                let end = c.pkg_end()?;
+                let mut body = c.split(end);
+                let size = self.eval_int(host, &mut body, scope)?;
+                let init = body.bytes(body.end() - body.pos())?;
+                let size = usize::try_from(size).map_err(|_| Error::TooLarge)?.max(init.len());
+                check_len(size)?;
+                let mut buf = vec![0u8; size];
+                buf[..init.len()].copy_from_slice(init);
+                Ok(Value::Buffer(buf))
+            }
+            0x12 | 0x13 => {
+                let end = c.pkg_end()?;
+                let mut body = c.split(end);
+                let count = if op == 0x12 {
+                    u64::from(body.byte()?)
+                } else {
+                    self.eval_int(host, &mut body, scope)?
+                };
+                let count = usize::try_from(count)
+                    .ok()
+                    .filter(|&n| n <= MAX_PACKAGE)
+                    .ok_or(Error::TooLarge)?;
+                let mut elements = Vec::new();
+                while !body.at_end() {
+                    if elements.len() >= MAX_PACKAGE {
+                        return Err(Error::TooLarge);
+                    }
+                    elements.push(self.package_element(host, &mut body, scope)?);
+                }
+                elements.truncate(count);
+                elements.resize(count, Value::Uninitialized);
+                Ok(Value::Package(elements))
+            }
+            0x60..=0x67 => {
+                let frame = self.frames.last().ok_or(Error::TypeMismatch)?;
+                Ok(frame.locals[usize::from(op - 0x60)].clone())
+            }
+            0x68..=0x6E => {
+                let frame = self.frames.last().ok_or(Error::TypeMismatch)?;
+                Ok(frame.args[usize::from(op - 0x68)].clone())
+            }
+            0x70 => {
+                let value = self.eval(host, c, scope)?;
+                let target = self.target(host, c, scope)?;
+                self.store(host, target, value.clone())?;
+                Ok(value)
+            }
+            0x71 => match self.target(host, c, scope)? {
+                Target::Ref(r) => Ok(Value::Reference(r)),
+                _ => Err(Error::TypeMismatch),
+            },
+            0x72 | 0x74 | 0x77 | 0x79 | 0x7A | 0x7B | 0x7C | 0x7D | 0x7E | 0x7F | 0x85 => {
+                let a = self.eval_int(host, c, scope)?;
+                let b = self.eval_int(host, c, scope)?;
+                let result = match op {
+                    0x72 => a.wrapping_add(b),
+                    0x74 => a.wrapping_sub(b),
+                    0x77 => a.wrapping_mul(b),
+                    0x79 => {
+                        if b >= u64::from(width.bits()) {
+                            0
+                        } else {
+                            a << b
+                        }
+                    }
+                    0x7A => {
+                        if b >= u64::from(width.bits()) {
+                            0
+                        } else {
+                            a >> b
+                        }
+                    }
+                    0x7B => a & b,
+                    0x7C => !(a & b),
+                    0x7D => a | b,
+                    0x7E => !(a | b),
+                    0x7F => a ^ b,
+                    _ => a.checked_rem(b).ok_or(Error::DivideByZero)?,
+                };
+                self.result(host, c, scope, Value::Integer(width.truncate(result)))
+            }
+            0x73 => {
+                let a = self.eval(host, c, scope)?;
+                let b = self.eval(host, c, scope)?;
+                let joined = self.concat(a, b)?;
+                self.result(host, c, scope, joined)
+            }
+            0x75 | 0x76 => {
+                let target = self.target(host, c, scope)?;
+                let Target::Ref(r) = &target else {
+                    return Err(Error::TypeMismatch);
+                };
+                let v = self.read_ref(host, r)?.to_integer(width)?;
+                let v = if op == 0x75 { v.wrapping_add(1) } else { v.wrapping_sub(1) };
+                let v = Value::Integer(width.truncate(v));
+                self.store(host, target, v.clone())?;
+                Ok(v)
+            }
+            0x78 => {
+                let a = self.eval_int(host, c, scope)?;
+                let b = self.eval_int(host, c, scope)?;
+                let quotient = a.checked_div(b).ok_or(Error::DivideByZero)?;
+                let remainder = a % b;
+                let rem_target = self.target(host, c, scope)?;
+                self.store(host, rem_target, Value::Integer(remainder))?;
+                self.result(host, c, scope, Value::Integer(quotient))
+            }
+            0x80 => {
+                let a = self.eval_int(host, c, scope)?;
+                self.result(host, c, scope, Value::Integer(width.truncate(!a)))
+            }
+            0x81 | 0x82 => {
+                let a = self.eval_int(host, c, scope)?;
+                let bit = match (a, op) {
+                    (0, _) => 0,
+                    (_, 0x81) => 64 - u64::from(a.leading_zeros()),
+                    _ => u64::from(a.trailing_zeros()) + 1,
+                };
+                self.result(host, c, scope, Value::Integer(bit))
+            }
+            0x83 => {
+                let v = self.eval(host, c, scope)?;
+                self.deref(host, scope, v)
+            }
+            0x84 => {
+                let a = self.eval(host, c, scope)?.to_buffer(width)?;
+                let b = self.eval(host, c, scope)?.to_buffer(width)?;
+                let mut joined = without_end_tag(&a).to_vec();
+                joined.extend_from_slice(without_end_tag(&b));
+                joined.extend_from_slice(&[0x79, 0x00]);
+                check_len(joined.len())?;
+                self.result(host, c, scope, Value::Buffer(joined))
+            }
+            0x87 => {
+                let v = self.target_value(host, c, scope)?;
+                Ok(Value::Integer(v.size()?))
+            }
+            0x88 => {
+                let holder = self.location(host, c, scope)?;
+                let index = self.eval_int(host, c, scope)?;
+                let len = self.read_ref(host, &holder)?.size()?;
+                if index >= len {
+                    return Err(Error::OutOfRange);
+                }
+                let r = Reference::Element { holder: Box::new(holder), index: index as usize };
+                let target = self.target(host, c, scope)?;
+                self.store(host, target, Value::Reference(r.clone()))?;
+                Ok(Value::Reference(r))
+            }
+            0x89 => self.eval_match(host, c, scope),
+            0x8A..=0x8D | 0x8F => {
+                let source = self.location(host, c, scope)?;
+                let index = self.eval_int(host, c, scope)?;
+                let (bit_offset, bit_len) = match op {
+                    0x8A => (index.saturating_mul(8), 32),
+                    0x8B => (index.saturating_mul(8), 16),
+                    0x8C => (index.saturating_mul(8), 8),
+                    0x8D => (index, 1),
+                    _ => (index.saturating_mul(8), 64),
+                };
+                let name = NameString::read(c)?;
+                self.create_field(host, scope, source, bit_offset, bit_len, &name)?;
+                Ok(Value::Uninitialized)
+            }
+            0x8E => {
+                let code = match self.target(host, c, scope)? {
+                    Target::Ref(Reference::Node(id)) => {
+                        self.ns.object(id).map_or(0, Object::type_code)
+                    }
+                    Target::Ref(r) => self.read_ref(host, &r)?.type_code(),
+                    Target::Debug => 16,
+                    Target::Null => 0,
+                };
+                Ok(Value::Integer(code))
+            }
+            0x90 | 0x91 => {
+                let a = self.eval_int(host, c, scope)? != 0;
+                let b = self.eval_int(host, c, scope)? != 0;
+                Ok(self.boolean(if op == 0x90 { a && b } else { a || b }))
+            }
+            0x92 => {
+                let negated = match c.peek().ok() {
+                    Some(inner @ 0x93..=0x95) => {
+                        c.byte()?;
+                        let ord = self.compare(host, c, scope)?;
+                        match inner {
+                            0x93 => ord != Ordering::Equal,
+                            0x94 => ord != Ordering::Greater,
+                            _ => ord != Ordering::Less,
+                        }
+                    }
+                    _ => self.eval_int(host, c, scope)? == 0,
+                };
+                Ok(self.boolean(negated))
+            }
+            0x93 => {
+                let ord = self.compare(host, c, scope)?;
+                Ok(self.boolean(ord == Ordering::Equal))
+            }
+            0x94 => {
+                let ord = self.compare(host, c, scope)?;
+                Ok(self.boolean(ord == Ordering::Greater))
+            }
+            0x95 => {
+                let ord = self.compare(host, c, scope)?;
+                Ok(self.boolean(ord == Ordering::Less))
+            }
+            0x96 => {
+                let b = self.eval(host, c, scope)?.to_buffer(width)?;
+                self.result(host, c, scope, Value::Buffer(b))
+            }
+            0x97 | 0x98 => {
+                let v = self.eval(host, c, scope)?;
+                let s = if op == 0x97 { decimal_string(&v)? } else { hex_string(&v, width)? };
+                self.result(host, c, scope, Value::String(s))
+            }
+            0x99 => {
+                let v = self.eval(host, c, scope)?.to_integer_explicit(width)?;
+                self.result(host, c, scope, Value::Integer(v))
+            }
+            0x9C => {
+                let b = self.eval(host, c, scope)?.to_buffer(width)?;
+                let limit = self.eval_int(host, c, scope)?;
+                let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+                let text: Vec<u8> = b.iter().copied().take(limit).take_while(|&b| b != 0).collect();
+                let s = String::from_utf8_lossy(&text).into_owned();
+                self.result(host, c, scope, Value::String(s))
+            }
+            0x9D => {
+                let value = self.eval(host, c, scope)?;
+                match self.target(host, c, scope)? {
+                    Target::Ref(r) => self.overwrite(host, &r, value.clone())?,
+                    Target::Debug => host.debug(&value),
+                    Target::Null => {}
+                }
+                Ok(value)
+            }
+            0x9E => {
+                let source = self.eval(host, c, scope)?;
+                let index = self.eval_int(host, c, scope)?;
+                let length = self.eval_int(host, c, scope)?;
+                let mid = |len: usize| {
+                    let start = usize::try_from(index).unwrap_or(usize::MAX).min(len);
+                    let end = start
+                        .saturating_add(usize::try_from(length).unwrap_or(usize::MAX))
+                        .min(len);
+                    start..end
+                };
+                let v = match source {
+                    Value::String(s) => {
+                        let r = mid(s.len());
+                        Value::String(String::from_utf8_lossy(&s.as_bytes()[r]).into_owned())
+                    }
+                    Value::Buffer(b) => Value::Buffer(b[mid(b.len())].to_vec()),
+                    _ => return Err(Error::TypeMismatch),
+                };
+                self.result(host, c, scope, v)
+            }
+            0x5B => self.eval_ext(host, c, scope),
+            other => Err(Error::UnknownOpcode(u16::from(other))),
+        }
+    }
+
+    fn eval_ext(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Value, Error> {
+        let width = self.width;
+        let op = c.byte()?;
+        match op {
+            0x30 => Ok(Value::Integer(REVISION)),
+            0x33 => Ok(Value::Integer(host.timer())),
+            0x12 => {
+                let found = if NameString::starts(c.peek()?) {
+                    let name = NameString::read(c)?;
+                    self.ns.resolve(scope, &name).map(Reference::Node)
+                } else {
+                    match self.target(host, c, scope)? {
+                        Target::Ref(r) => Some(r),
+                        _ => None,
+                    }
+                };
+                let target = self.target(host, c, scope)?;
+                match found {
+                    Some(r) => {
+                        self.store(host, target, Value::Reference(r))?;
+                        Ok(self.boolean(true))
+                    }
+                    None => Ok(self.boolean(false)),
+                }
+            }
+            0x13 => {
+                let source = self.location(host, c, scope)?;
+                let bit_offset = self.eval_int(host, c, scope)?;
+                let bit_len = self.eval_int(host, c, scope)?;
+                let name = NameString::read(c)?;
+                self.create_field(host, scope, source, bit_offset, bit_len, &name)?;
+                Ok(Value::Uninitialized)
+            }
+            0x23 => {
+                // Acquire: nothing else runs AML, so the mutex is always free.
+                self.target(host, c, scope)?;
+                c.word()?;
+                Ok(Value::Integer(0))
+            }
+            0x25 => {
+                // Wait: nothing else runs AML to signal the event, so a wait
+                // with a timeout times out and one without would never end.
+                self.target(host, c, scope)?;
+                self.eval_int(host, c, scope)?;
+                Ok(Value::Integer(width.ones()))
+            }
+            0x28 => {
+                let bcd = self.eval_int(host, c, scope)?;
+                let mut v = 0u64;
+                for i in (0..16).rev() {
+                    v = v.wrapping_mul(10).wrapping_add((bcd >> (i * 4)) & 0xF);
+                }
+                self.result(host, c, scope, Value::Integer(width.truncate(v)))
+            }
+            0x29 => {
+                let mut v = self.eval_int(host, c, scope)?;
+                let mut bcd = 0u64;
+                for i in 0..16 {
+                    bcd |= (v % 10) << (i * 4);
+                    v /= 10;
+                }
+                self.result(host, c, scope, Value::Integer(width.truncate(bcd)))
+            }
+            0x1F | 0x20 | 0x2A => Err(Error::Unsupported),
+            other => Err(Error::UnknownOpcode(0x5B00 | u16::from(other))),
+        }
+    }
+
+    /// A name in an operand: a method call with as many arguments as the
+    /// method declares, or the value of whatever else it names.
+    fn eval_name(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Value, Error> {
+        let name = NameString::read(c)?;
+        let node = self.resolve(scope, &name)?;
+        let argc = match self.ns.object(node) {
+            Some(Object::Method(m)) => usize::from(m.args),
+            Some(Object::Osi) => 1,
+            _ => return self.read_node(host, node),
+        };
+        let mut args = Vec::with_capacity(argc);
+        for _ in 0..argc {
+            args.push(self.eval(host, c, scope)?);
+        }
+        self.call(host, node, args)
+    }
+
+    /// A package element: data, or a name kept as a reference.
+    fn package_element(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Value, Error> {
+        if NameString::starts(c.peek()?) {
+            let path = NameString::read(c)?;
+            let r = match self.ns.resolve(scope, &path) {
+                Some(node) => Reference::Node(node),
+                None => Reference::Path { scope, path },
+            };
+            return Ok(Value::Reference(r));
+        }
+        self.eval(host, c, scope)
+    }
+
+    fn boolean(&self, b: bool) -> Value {
+        Value::Integer(if b { self.width.ones() } else { 0 })
+    }
+
+    fn compare(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Ordering, Error> {
+        let a = self.eval(host, c, scope)?;
+        let b = self.eval(host, c, scope)?;
+        a.compare(&b, self.width)
+    }
+
+    /// Store an operator's result in its `Target` operand, and return it.
+    fn result(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+        value: Value,
+    ) -> Result<Value, Error> {
+        let target = self.target(host, c, scope)?;
+        self.store(host, target, value.clone())?;
+        Ok(value)
+    }
+
+    fn concat(&self, a: Value, b: Value) -> Result<Value, Error> {
+        let width = self.width;
+        let joined = match a {
+            Value::Integer(_) => {
+                let mut bytes = a.to_buffer(width)?;
+                bytes.extend_from_slice(&Value::Integer(b.to_integer(width)?).to_buffer(width)?);
+                Value::Buffer(bytes)
+            }
+            Value::String(mut s) => {
+                s.push_str(&b.to_string(width)?);
+                check_len(s.len())?;
+                Value::String(s)
+            }
+            Value::Buffer(mut bytes) => {
+                bytes.extend_from_slice(&b.to_buffer(width)?);
+                check_len(bytes.len())?;
+                Value::Buffer(bytes)
+            }
+            _ => return Err(Error::TypeMismatch),
+        };
+        Ok(joined)
+    }
+
+    fn eval_match(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Value, Error> {
+        let package = self.eval(host, c, scope)?;
+        let op1 = c.byte()?;
+        let obj1 = self.eval(host, c, scope)?;
+        let op2 = c.byte()?;
+        let obj2 = self.eval(host, c, scope)?;
+        let start = self.eval_int(host, c, scope)?;
+        let Value::Package(elements) = package else {
+            return Err(Error::TypeMismatch);
+        };
+        let width = self.width;
+        let test = |op: u8, element: &Value, obj: &Value| -> bool {
+            if op == 0 {
+                return true;
+            }
+            let Ok(ord) = element.compare(obj, width) else {
+                return false;
+            };
+            match op {
+                1 => ord == Ordering::Equal,
+                2 => ord != Ordering::Greater,
+                3 => ord == Ordering::Less,
+                4 => ord != Ordering::Less,
+                5 => ord == Ordering::Greater,
+                _ => false,
+            }
+        };
+        let start = usize::try_from(start).unwrap_or(usize::MAX);
+        for (i, element) in elements.iter().enumerate().skip(start) {
+            self.step()?;
+            if test(op1, element, &obj1) && test(op2, element, &obj2) {
+                return Ok(Value::Integer(i as u64));
+            }
+        }
+        Ok(Value::Integer(width.ones()))
+    }
+
+    /// `DerefOf`: what a reference refers to, or what a string names.
+    fn deref(&mut self, host: &mut dyn Host, scope: NodeId, v: Value) -> Result<Value, Error> {
+        match v {
+            Value::Reference(r) => self.read_ref(host, &r),
+            Value::String(s) => {
+                let path = NameString::parse(&s).ok_or(Error::InvalidName)?;
+                let node = self.resolve(scope, &path)?;
+                self.read_node(host, node)
+            }
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// A `SuperName` or `NullName`: where a result is to go.
+    fn target(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Target, Error> {
+        let op = c.peek()?;
+        let frame = self.frame();
+        match op {
+            0x00 => {
+                c.byte()?;
+                Ok(Target::Null)
+            }
+            0x5B if c.peek_second() == Some(0x31) => {
+                c.bytes(2)?;
+                Ok(Target::Debug)
+            }
+            0x60..=0x67 => {
+                c.byte()?;
+                Ok(Target::Ref(Reference::Local { frame, index: op - 0x60 }))
+            }
+            0x68..=0x6E => {
+                c.byte()?;
+                Ok(Target::Ref(Reference::Arg { frame, index: op - 0x68 }))
+            }
+            _ if NameString::starts(op) => {
+                let name = NameString::read(c)?;
+                let node = self.resolve(scope, &name)?;
+                if matches!(self.ns.object(node), Some(Object::Method(_))) {
+                    // A method call whose result is the reference to store
+                    // through.
+                    let argc = match self.ns.object(node) {
+                        Some(Object::Method(m)) => usize::from(m.args),
+                        _ => 0,
+                    };
+                    let mut args = Vec::with_capacity(argc);
+                    for _ in 0..argc {
+                        args.push(self.eval(host, c, scope)?);
+                    }
+                    return match self.call(host, node, args)? {
+                        Value::Reference(r) => Ok(Target::Ref(r)),
+                        _ => Err(Error::TypeMismatch),
+                    };
+                }
+                Ok(Target::Ref(Reference::Node(node)))
+            }
+            _ => match self.eval(host, c, scope)? {
+                Value::Reference(r) => Ok(Target::Ref(r)),
+                _ => Err(Error::TypeMismatch),
+            },
+        }
+    }
+
+    /// The value a `SuperName` operand holds, for `SizeOf`.
+    fn target_value(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Value, Error> {
+        match self.target(host, c, scope)? {
+            Target::Ref(r) => {
+                let v = self.read_ref(host, &r)?;
+                // SizeOf(Local0) where Local0 is RefOf(X) is the size of X.
+                match v {
+                    Value::Reference(inner) => self.read_ref(host, &inner),
+                    other => Ok(other),
+                }
+            }
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// The operand of `Index` and the `Create*Field`s: where the package or
+    /// buffer lives, so that a store through the result reaches it.
+    fn location(
+        &mut self,
+        host: &mut dyn Host,
+        c: &mut Cursor,
+        scope: NodeId,
+    ) -> Result<Reference, Error> {
+        let op = c.peek()?;
+        let frame = self.frame();
+        let r = match op {
+            0x60..=0x6E => {
+                c.byte()?;
+                let slot = if op < 0x68 {
+                    Reference::Local { frame, index: op - 0x60 }
+                } else {
+                    Reference::Arg { frame, index: op - 0x68 }
+                };
+                // One holding RefOf(X) locates X.
+                match self.read_ref(host, &slot)? {
+                    Value::Reference(inner) => inner,
+                    _ => slot,
+                }
+            }
+            _ if NameString::starts(op) => {
+                let mut peek = c.clone();
+                let name = NameString::read(&mut peek)?;
+                let node = self.resolve(scope, &name)?;
+                if matches!(self.ns.object(node), Some(Object::Name(_))) {
+                    *c = peek;
+                    Reference::Node(node)
+                } else {
+                    Reference::Temp(Box::new(self.eval(host, c, scope)?))
+                }
+            }
+            _ => match self.eval(host, c, scope)? {
+                Value::Reference(r) => r,
+                other => Reference::Temp(Box::new(other)),
+            },
+        };
+        Ok(r)
+    }
+
+    fn create_field(
+        &mut self,
+        host: &mut dyn Host,
+        scope: NodeId,
+        source: Reference,
+        bit_offset: u64,
+        bit_len: u64,
+        name: &NameString,
+    ) -> Result<(), Error> {
+        let len = match self.read_ref(host, &source)? {
+            Value::Buffer(b) => b.len() as u64,
+            _ => return Err(Error::TypeMismatch),
+        };
+        let end = bit_offset.checked_add(bit_len).ok_or(Error::OutOfRange)?;
+        if bit_len == 0 || end > len * 8 {
+            return Err(Error::OutOfRange);
+        }
+        self.define(scope, name, Object::BufferField(BufferField { source, bit_offset, bit_len }))?;
+        Ok(())
+    }
+
+    /// Read what a node holds: data, a field's contents, or a reference to
+    /// the node itself for an object that is neither.
+    pub(crate) fn read_node(&mut self, host: &mut dyn Host, node: NodeId) -> Result<Value, Error> {
+        match self.ns.object(node).ok_or(Error::NotFound)? {
+            Object::Name(v) => Ok(v.clone()),
+            Object::Field(_) => self.read_field(host, node),
+            Object::BufferField(_) => self.read_buffer_field(host, node),
+            _ => Ok(Value::Reference(Reference::Node(node))),
+        }
+    }
+
+    pub(crate) fn read_ref(&mut self, host: &mut dyn Host, r: &Reference) -> Result<Value, Error> {
+        match r {
+            Reference::Node(node) => self.read_node(host, *node),
+            Reference::Path { scope, path } => {
+                let node = self.resolve(*scope, path)?;
+                self.read_node(host, node)
+            }
+            Reference::Local { frame, index } => {
+                let f = self.frames.get(*frame).ok_or(Error::NotFound)?;
+                Ok(f.locals[usize::from(*index)].clone())
+            }
+            Reference::Arg { frame, index } => {
+                let f = self.frames.get(*frame).ok_or(Error::NotFound)?;
+                Ok(f.args[usize::from(*index)].clone())
+            }
+            Reference::Element { holder, index } => {
+                self.enter()?;
+                let container = self.read_ref(host, holder);
+                self.depth -= 1;
+                match container? {
+                    Value::Package(p) => p.into_iter().nth(*index).ok_or(Error::OutOfRange),
+                    Value::Buffer(b) => {
+                        b.get(*index).map(|&x| Value::Integer(x.into())).ok_or(Error::OutOfRange)
+                    }
+                    Value::String(s) => s
+                        .as_bytes()
+                        .get(*index)
+                        .map(|&x| Value::Integer(x.into()))
+                        .ok_or(Error::OutOfRange),
+                    _ => Err(Error::TypeMismatch),
+                }
+            }
+            Reference::Temp(v) => Ok((**v).clone()),
+        }
+    }
+
+    fn store(&mut self, host: &mut dyn Host, target: Target, value: Value) -> Result<(), Error> {
+        match target {
+            Target::Null => Ok(()),
+            Target::Debug => {
+                host.debug(&value);
+                Ok(())
+            }
+            Target::Ref(r) => self.write_ref(host, &r, value),
+        }
+    }
+
+    /// `Store`: a local is overwritten, an argument passed by reference is
+    /// stored through, and a named integer, string or buffer keeps its type
+    /// and converts what it is given.
+    pub(crate) fn write_ref(
+        &mut self,
+        host: &mut dyn Host,
+        r: &Reference,
+        value: Value,
+    ) -> Result<(), Error> {
+        let width = self.width;
+        match r {
+            Reference::Arg { frame, index } => {
+                let f = self.frames.get_mut(*frame).ok_or(Error::NotFound)?;
+                let slot = &mut f.args[usize::from(*index)];
+                if let Value::Reference(inner) = slot {
+                    let inner = inner.clone();
+                    self.enter()?;
+                    let result = self.write_ref(host, &inner, value);
+                    self.depth -= 1;
+                    return result;
+                }
+                *slot = value;
+                Ok(())
+            }
+            Reference::Node(node) => match self.ns.object(*node).ok_or(Error::NotFound)? {
+                Object::Field(_) => self.write_field(host, *node, value),
+                Object::BufferField(_) => self.write_buffer_field(host, *node, value),
+                Object::Name(existing) => {
+                    let converted = convert_for_store(existing, value, width)?;
+                    if let Some(n) = self.ns.get_mut(*node) {
+                        n.object = Object::Name(converted);
+                    }
+                    Ok(())
+                }
+                _ => Err(Error::TypeMismatch),
+            },
+            Reference::Path { scope, path } => {
+                let node = self.resolve(*scope, path)?;
+                self.write_ref(host, &Reference::Node(node), value)
+            }
+            Reference::Element { .. } | Reference::Local { .. } | Reference::Temp(_) => {
+                self.overwrite(host, r, value)
+            }
+        }
+    }
+
+    /// `CopyObject`, and a store to a local or an element: the value replaces
+    /// what was there, whatever its type.
+    fn overwrite(&mut self, host: &mut dyn Host, r: &Reference, value: Value) -> Result<(), Error> {
+        let width = self.width;
+        match r {
+            Reference::Element { holder, index } => {
+                if holder.is_temp() {
+                    return Ok(());
+                }
+                let index = *index;
+                let container = self.location_mut(holder)?;
+                match container {
+                    Value::Package(p) => *p.get_mut(index).ok_or(Error::OutOfRange)? = value,
+                    Value::Buffer(b) => {
+                        *b.get_mut(index).ok_or(Error::OutOfRange)? = value.to_integer(width)? as u8
+                    }
+                    Value::String(s) => {
+                        let mut bytes = core::mem::take(s).into_bytes();
+                        *bytes.get_mut(index).ok_or(Error::OutOfRange)? =
+                            value.to_integer(width)? as u8;
+                        *s = String::from_utf8_lossy(&bytes).into_owned();
+                    }
+                    _ => return Err(Error::TypeMismatch),
+                }
+                Ok(())
+            }
+            Reference::Temp(_) => Ok(()),
+            Reference::Node(node) => match self.ns.object(*node) {
+                Some(Object::Name(_)) => {
+                    if let Some(n) = self.ns.get_mut(*node) {
+                        n.object = Object::Name(value);
+                    }
+                    Ok(())
+                }
+                Some(_) => self.write_ref(host, r, value),
+                None => Err(Error::NotFound),
+            },
+            _ => {
+                *self.location_mut(r)? = value;
+                Ok(())
+            }
+        }
+    }
+
+    /// The storage a reference names, for a store into part of it.
+    fn location_mut(&mut self, r: &Reference) -> Result<&mut Value, Error> {
+        match r {
+            Reference::Local { frame, index } => {
+                Ok(&mut self.frames.get_mut(*frame).ok_or(Error::NotFound)?.locals
+                    [usize::from(*index)])
+            }
+            Reference::Arg { frame, index } => {
+                let f = self.frames.get(*frame).ok_or(Error::NotFound)?;
+                if let Value::Reference(inner) = &f.args[usize::from(*index)] {
+                    let inner = inner.clone();
+                    return self.location_mut(&inner);
+                }
+                Ok(&mut self.frames[*frame].args[usize::from(*index)])
+            }
+            Reference::Node(node) => match self.ns.get_mut(*node).map(|n| &mut n.object) {
+                Some(Object::Name(v)) => Ok(v),
+                Some(_) => Err(Error::TypeMismatch),
+                None => Err(Error::NotFound),
+            },
+            Reference::Path { scope, path } => {
+                let node = self.resolve(*scope, path)?;
+                self.location_mut(&Reference::Node(node))
+            }
+            Reference::Element { holder, index } => match self.location_mut(holder)? {
+                Value::Package(p) => p.get_mut(*index).ok_or(Error::OutOfRange),
+                _ => Err(Error::TypeMismatch),
+            },
+            Reference::Temp(_) => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// The bytes behind a buffer field's source, for the field to read or to
+    /// write a part of.
+    pub(crate) fn buffer_mut(&mut self, r: &Reference) -> Result<&mut Vec<u8>, Error> {
+        match self.location_mut(r)? {
+            Value::Buffer(b) => Ok(b),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// Run `_REG(space, 1)` under every scope with a region in `space`, to
+    /// tell firmware the host can now reach it. Firmware commonly keeps an
+    /// "EC is usable" flag that only this sets, and returns made-up values
+    /// until it is.
+    pub fn connect_space(&mut self, host: &mut dyn Host, space: u8) -> Vec<(NodeId, Error)> {
+        let mut scopes: Vec<NodeId> = Vec::new();
+        for node in self.ns.descendants(self.ns.root()) {
+            if let Some(Object::Region(region)) = self.ns.object(node) {
+                if region.space == space {
+                    if let Some(parent) = self.ns.parent(node) {
+                        if !scopes.contains(&parent) {
+                            scopes.push(parent);
+                        }
+                    }
+                }
+            }
+        }
+        let mut failures = Vec::new();
+        for scope in scopes {
+            let args = vec![Value::Integer(space.into()), Value::Integer(1)];
+            if let Err(e) = self.evaluate_child(host, scope, "_REG", args) {
+                failures.push((scope, e));
+            }
+        }
+        failures
+    }
+}
+
+impl Reference {
+    fn is_temp(&self) -> bool {
+        match self {
+            Reference::Temp(_) => true,
+            Reference::Element { holder, .. } => holder.is_temp(),
+            _ => false,
+        }
+    }
+}
+
+/// What a `Store` into a named object leaves there: an integer, string or
+/// buffer keeps its type, and a buffer its length; anything else is replaced.
+fn convert_for_store(existing: &Value, value: Value, width: Width) -> Result<Value, Error> {
+    Ok(match existing {
+        Value::Integer(_) => Value::Integer(value.to_integer(width)?),
+        Value::String(_) => Value::String(value.to_string(width)?),
+        Value::Buffer(old) if !old.is_empty() => {
+            let mut bytes = value.to_buffer(width)?;
+            bytes.resize(old.len(), 0);
+            Value::Buffer(bytes)
+        }
+        _ => value,
+    })
+}
+
+/// A resource template without its End Tag, for `ConcatenateResTemplate`.
+fn without_end_tag(b: &[u8]) -> &[u8] {
+    match b {
+        [rest @ .., 0x79, _] => rest,
+        other => other,
+    }
+}
+
+fn decimal_string(v: &Value) -> Result<String, Error> {
+    let mut s = String::new();
+    match v {
+        Value::Integer(n) => {
+            let _ = write!(s, "{n}");
+        }
+        Value::Buffer(b) => {
+            check_len(b.len() * 4)?;
+            for (i, byte) in b.iter().enumerate() {
+                let _ = write!(s, "{}{byte}", if i > 0 { "," } else { "" });
+            }
+        }
+        Value::String(t) => s.clone_from(t),
+        _ => return Err(Error::TypeMismatch),
+    }
+    Ok(s)
+}
+
+fn hex_string(v: &Value, width: Width) -> Result<String, Error> {
+    match v {
+        Value::Buffer(b) => {
+            check_len(b.len() * 5)?;
+            let mut s = String::new();
+            for (i, byte) in b.iter().enumerate() {
+                let _ = write!(s, "{}0x{byte:02X}", if i > 0 { "," } else { "" });
+            }
+            Ok(s)
+        }
+        other => other.to_string(width),
+    }
+}
+
+/// Whether `len` more bytes fit under [`MAX_BUFFER`]; for a field's contents.
+pub(crate) fn buffer_fits(len: u64) -> Result<usize, Error> {
+    usize::try_from(len).ok().filter(|&n| n <= MAX_BUFFER).ok_or(Error::TooLarge)
+}
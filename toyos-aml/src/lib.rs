@@ -0,0 +1,139 @@
+//! ACPI Machine Language: the namespace firmware builds, and the methods it
+//! writes, run by a kernel that does not trust either.
+//!
+//! `toyos-acpi` answers what the fixed hardware means. Everything else ACPI
+//! says about a machine — which interrupt a PCI pin is routed to, whether a
+//! device is present, what a lid or a battery is reporting, how to put a device
+//! in D3 — is said only by AML in the DSDT and its SSDTs, and only by running
+//! it. This crate runs it:
+//!
+//! - [`name`]: NameSegs and the paths made of them.
+//! - [`namespace`]: the tree definition blocks build, and the search rules a
+//!   name in one is resolved by.
+//! - [`value`]: the objects AML computes with, and the implicit conversions
+//!   between them.
+//! - [`host`]: the one way out — every operation-region access, sleep and
+//!   timer read is a call on a [`Host`] the kernel implements.
+//! - [`interp`]: the [`Interpreter`], which loads tables and evaluates methods.
+//! - [`resource`]: `_CRS` buffers, as the ranges and interrupts they name.
+//! - [`device`]: the methods a kernel asks of devices, by what they mean —
+//!   `_STA`, `_PRT`, `_PS0`/`_PS3`, a battery's `_BIF`/`_BST`, a thermal
+//!   zone's `_TMP`.
+//!
+//! The tables are firmware's, and firmware is a program: it loops, recurses,
+//! computes its own sizes and names its own registers. Every run is bounded by
+//! a step budget, a depth and caps on what it may allocate, and refuses past
+//! them rather than hanging or exhausting the heap; every read of the table is
+//! bounds-checked and no input panics. What a method may *touch* is the host's
+//! to decide — the interpreter asks, and a refusal is an error in the method
+//! that asked.
+//!
+//! `no_std`, with allocation, no `unsafe`.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+pub mod device;
+pub mod host;
+pub mod interp;
+pub mod name;
+pub mod namespace;
+pub mod resource;
+pub mod value;
+
+mod field;
+mod stream;
+
+use core::fmt;
+
+pub use device::{Battery, BatteryState, Route, RouteSource, Status};
+pub use host::{Host, PciAddress, Space};
+pub use interp::{Interpreter, Notification};
+pub use name::{NameSeg, NameString};
+pub use namespace::{Namespace, NodeId, Object};
+pub use resource::Resource;
+pub use value::{Reference, Value};
+
+/// Terms one [`Interpreter::load_table`] or [`Interpreter::evaluate`] may
+/// execute before it is abandoned. A `_PRT` or a `_STA` is tens of terms and a
+/// whole QEMU DSDT's load a few thousand; an EC query loop that polls for a
+/// million iterations is firmware waiting on hardware that will not answer.
+pub const STEP_BUDGET: u64 = 1 << 20;
+
+/// How deep terms, blocks and method calls may nest, counted together because
+/// they share one stack: each level is a few interpreter frames on whatever
+/// thread evaluates, and the kernel's are 128 KiB. A test in
+/// `tests/limits.rs` holds this number to that.
+pub const MAX_DEPTH: usize = 48;
+
+/// Objects the namespace may hold. QEMU's q35 DSDT builds about 600 and a
+/// laptop's DSDT and SSDTs a few thousand.
+pub const MAX_NODES: usize = 1 << 16;
+
+/// The longest buffer or string a method may build, in bytes.
+pub const MAX_BUFFER: usize = 64 * 1024;
+
+/// The most elements a package may have.
+pub const MAX_PACKAGE: usize = 4096;
+
+/// Why a table did not load or a method did not return.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// A term runs past the end of its package, its method or the table.
+    Truncated,
+    /// A NameSeg with a character no compiler emits, or a path with more
+    /// carets than the scope has parents.
+    InvalidName,
+    /// A byte that begins no term this interpreter knows.
+    UnknownOpcode(u16),
+    /// A name that resolves to nothing.
+    NotFound,
+    /// A named object defined twice in one scope.
+    AlreadyExists,
+    /// An operand of a type no implicit conversion makes the one required.
+    TypeMismatch,
+    /// An index past the end of a package, buffer or string, or a field past
+    /// the end of its region.
+    OutOfRange,
+    DivideByZero,
+    /// More than [`STEP_BUDGET`] terms.
+    Budget,
+    /// More than [`MAX_DEPTH`] levels of nesting.
+    TooDeep,
+    /// A buffer, string or package past its cap, or a namespace past
+    /// [`MAX_NODES`].
+    TooLarge,
+    /// A construct this interpreter does not run: `Load`, `LoadTable`, a
+    /// region space the host has no access for, a `BufferAcc` field.
+    Unsupported,
+    /// The host refused an access.
+    Refused,
+    /// A method called with fewer arguments than it declares.
+    Arguments,
+    /// The method executed `Fatal`.
+    Fatal,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => f.write_str("a term runs past its end"),
+            Self::InvalidName => f.write_str("an invalid name"),
+            Self::UnknownOpcode(op) => write!(f, "unknown opcode {op:#x}"),
+            Self::NotFound => f.write_str("a name resolves to nothing"),
+            Self::AlreadyExists => f.write_str("a name is defined twice"),
+            Self::TypeMismatch => f.write_str("an operand of the wrong type"),
+            Self::OutOfRange => f.write_str("an index or field out of range"),
+            Self::DivideByZero => f.write_str("division by zero"),
+            Self::Budget => f.write_str("the step budget ran out"),
+            Self::TooDeep => f.write_str("nesting too deep"),
+            Self::TooLarge => f.write_str("an object past its cap"),
+            Self::Unsupported => f.write_str("a construct this interpreter does not run"),
+            Self::Refused => f.write_str("the host refused an access"),
+            Self::Arguments => f.write_str("too few arguments"),
+            Self::Fatal => f.write_str("the method executed Fatal"),
+        }
+    }
+}
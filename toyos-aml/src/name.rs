@@ -0,0 +1,218 @@
+//! Names: the four-character segments the namespace is built of, and the
+//! strings of them AML writes to reach one.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::stream::Cursor;
+use crate::Error;
+
+const ROOT_CHAR: u8 = b'\\';
+const PARENT_PREFIX: u8 = b'^';
+const DUAL_NAME_PREFIX: u8 = 0x2E;
+const MULTI_NAME_PREFIX: u8 = 0x2F;
+const NULL_NAME: u8 = 0x00;
+
+/// One `NameSeg`: four characters, the first a letter or `_`, the rest letters,
+/// digits or `_`. A name written shorter in ASL is padded with `_` by the
+/// compiler, so `_SB` is `_SB_` here as it is in the table.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NameSeg(pub [u8; 4]);
+
+impl NameSeg {
+    /// The segment `bytes` spell, or `None` for one no compiler emits.
+    pub fn new(bytes: [u8; 4]) -> Option<Self> {
+        let lead = bytes[0].is_ascii_uppercase() || bytes[0] == b'_';
+        let rest =
+            bytes[1..].iter().all(|&b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_');
+        (lead && rest).then_some(NameSeg(bytes))
+    }
+
+    /// A segment written the way ASL writes it: up to four characters, padded
+    /// with `_`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let bytes = text.as_bytes();
+        if bytes.is_empty() || bytes.len() > 4 {
+            return None;
+        }
+        let mut seg = [b'_'; 4];
+        seg[..bytes.len()].copy_from_slice(bytes);
+        Self::new(seg)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // `new` admits ASCII alone.
+        core::str::from_utf8(&self.0).unwrap_or("????")
+    }
+
+    pub(crate) fn read(c: &mut Cursor) -> Result<Self, Error> {
+        let bytes = [c.byte()?, c.byte()?, c.byte()?, c.byte()?];
+        Self::new(bytes).ok_or(Error::InvalidName)
+    }
+}
+
+impl fmt::Debug for NameSeg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for NameSeg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A `NameString`: where it starts — the root, or some number of scopes up from
+/// the current one — and the segments walked from there.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NameString {
+    pub root: bool,
+    pub parents: u8,
+    pub segs: Vec<NameSeg>,
+}
+
+impl NameString {
+    /// Whether the first byte of a term is the start of a name, which is how a
+    /// parser tells a method call or a name reference from an opcode.
+    pub(crate) fn starts(byte: u8) -> bool {
+        byte.is_ascii_uppercase()
+            || byte == b'_'
+            || byte == ROOT_CHAR
+            || byte == PARENT_PREFIX
+            || byte == DUAL_NAME_PREFIX
+            || byte == MULTI_NAME_PREFIX
+    }
+
+    pub(crate) fn read(c: &mut Cursor) -> Result<Self, Error> {
+        let mut root = false;
+        let mut parents = 0u8;
+        if c.peek()? == ROOT_CHAR {
+            c.byte()?;
+            root = true;
+        } else {
+            while c.peek()? == PARENT_PREFIX {
+                c.byte()?;
+                parents = parents.checked_add(1).ok_or(Error::InvalidName)?;
+            }
+        }
+        let count = match c.peek()? {
+            NULL_NAME => {
+                c.byte()?;
+                0
+            }
+            DUAL_NAME_PREFIX => {
+                c.byte()?;
+                2
+            }
+            MULTI_NAME_PREFIX => {
+                c.byte()?;
+                c.byte()? as usize
+            }
+            _ => 1,
+        };
+        let mut segs = Vec::with_capacity(count);
+        for _ in 0..count {
+            segs.push(NameSeg::read(c)?);
+        }
+        Ok(NameString { root, parents, segs })
+    }
+
+    /// A path as ASL writes one — `\_SB.PCI0._PRT`, `^^FOO`, `_STA` — or
+    /// `None` for text that is not one.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut rest = text;
+        let root = rest.starts_with('\\');
+        if root {
+            rest = &rest[1..];
+        }
+        let mut parents = 0u8;
+        while !root && rest.starts_with('^') {
+            rest = &rest[1..];
+            parents = parents.checked_add(1)?;
+        }
+        let segs = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split('.').map(NameSeg::parse).collect::<Option<Vec<_>>>()?
+        };
+        Some(NameString { root, parents, segs })
+    }
+
+    /// Whether the ACPI search rules apply: a single segment with no prefix is
+    /// looked for in the current scope and then in each one above it.
+    pub fn searches(&self) -> bool {
+        !self.root && self.parents == 0 && self.segs.len() == 1
+    }
+}
+
+impl fmt::Display for NameString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.root {
+            f.write_str("\\")?;
+        }
+        for _ in 0..self.parents {
+            f.write_str("^")?;
+        }
+        for (i, seg) in self.segs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            f.write_str(seg.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+/// An absolute path, for a caller that wants one as text.
+pub(crate) fn join(segs: &[NameSeg]) -> String {
+    let mut out = String::from("\\");
+    for (i, seg) in segs.iter().enumerate() {
+        if i > 0 {
+            out.push('.');
+        }
+        out.push_str(seg.as_str());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::sync::Arc;
+
+    fn read(bytes: &[u8]) -> Result<NameString, Error> {
+        let code: Arc<[u8]> = Arc::from(bytes);
+        NameString::read(&mut Cursor::new(code))
+    }
+
+    #[test]
+    fn every_prefix_reads_back_as_written() {
+        assert_eq!(read(b"_STA").unwrap().to_string(), "_STA");
+        assert_eq!(read(b"\\_SB_").unwrap().to_string(), "\\_SB_");
+        assert_eq!(read(b"\\\x2e_SB_PCI0").unwrap().to_string(), "\\_SB_.PCI0");
+        assert_eq!(read(b"^^\x2f\x03ABCDEFGHIJKL").unwrap().to_string(), "^^ABCD.EFGH.IJKL");
+        assert_eq!(read(b"\\\x00").unwrap().to_string(), "\\");
+    }
+
+    #[test]
+    fn a_truncated_or_misspelt_name_is_refused() {
+        assert_eq!(read(b"\\\x2e_SB_PC"), Err(Error::Truncated));
+        assert_eq!(read(b"\x2f\xff_SB_"), Err(Error::Truncated));
+        assert_eq!(read(b"_sb_"), Err(Error::InvalidName));
+        assert_eq!(read(b"9ABC"), Err(Error::InvalidName));
+    }
+
+    #[test]
+    fn text_pads_short_segments() {
+        let path = NameString::parse("\\_SB.PCI0._PRT").unwrap();
+        assert!(path.root);
+        assert_eq!(path.to_string(), "\\_SB_.PCI0._PRT");
+        assert!(NameString::parse("_STA").unwrap().searches());
+        assert!(!NameString::parse("^_STA").unwrap().searches());
+        assert_eq!(NameString::parse("\\TOOLONG"), None);
+        assert_eq!(NameString::parse("\\a"), None);
+    }
+}
@@ -0,0 +1,423 @@
+//! The ACPI namespace: the tree every definition block adds to, and the rules
+//! a name written inside one is found by.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::host::PciAddress;
+use crate::name::{self, NameSeg, NameString};
+use crate::value::{Reference, Value};
+use crate::{Error, MAX_NODES};
+
+/// A node of the tree. Stable for as long as the node exists; a method's
+/// temporary objects are removed when it returns and their ids reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub(crate) u32);
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: NameSeg,
+    pub parent: Option<NodeId>,
+    pub object: Object,
+    children: BTreeMap<NameSeg, NodeId>,
+}
+
+/// What a node is.
+#[derive(Clone, Debug)]
+pub enum Object {
+    /// A scope and nothing else: the root and the predefined `\_SB_` and its
+    /// siblings.
+    Scope,
+    Name(Value),
+    Method(Method),
+    /// `\_OSI`, answered by the interpreter rather than by AML.
+    Osi,
+    Device,
+    Processor {
+        id: u8,
+        block: u32,
+        block_len: u8,
+    },
+    PowerResource {
+        level: u8,
+        order: u16,
+    },
+    ThermalZone,
+    Region(Region),
+    Field(Field),
+    BufferField(BufferField),
+    Mutex {
+        sync: u8,
+    },
+    Event,
+    Alias(NodeId),
+}
+
+impl Object {
+    /// `ObjectType`'s number.
+    pub(crate) fn type_code(&self) -> u64 {
+        match self {
+            Object::Scope => 0,
+            Object::Name(v) => v.type_code(),
+            Object::Field(_) => 5,
+            Object::Device => 6,
+            Object::Event => 7,
+            Object::Method(_) | Object::Osi => 8,
+            Object::Mutex { .. } => 9,
+            Object::Region(_) => 10,
+            Object::PowerResource { .. } => 11,
+            Object::Processor { .. } => 12,
+            Object::ThermalZone => 13,
+            Object::BufferField(_) => 14,
+            Object::Alias(_) => 0,
+        }
+    }
+}
+
+/// A method: where its body is, and how it is called.
+#[derive(Clone, Debug)]
+pub struct Method {
+    pub(crate) code: Arc<[u8]>,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub args: u8,
+    pub serialized: bool,
+}
+
+/// An `OperationRegion`.
+#[derive(Clone, Debug)]
+pub struct Region {
+    /// The `RegionSpace` byte: 0 system memory, 1 system I/O, 2 PCI config,
+    /// 3 the embedded controller, and on.
+    pub space: u8,
+    pub offset: u64,
+    pub length: u64,
+    /// For a PCI config region, the function it belongs to, worked out from
+    /// `_ADR`, `_BBN` and `_SEG` on first access.
+    pub(crate) pci: Option<PciAddress>,
+}
+
+/// A field unit, in a region or through another field.
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub(crate) kind: FieldKind,
+    pub(crate) bit_offset: u64,
+    pub(crate) bit_len: u64,
+    /// `FieldFlags`: access type in bits 0-3, lock in bit 4, update rule in
+    /// bits 5-6.
+    pub(crate) flags: u8,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum FieldKind {
+    Normal {
+        region: NodeId,
+    },
+    /// Written through `index` and read or written through `data`, both
+    /// fields themselves.
+    Index {
+        index: NodeId,
+        data: NodeId,
+    },
+    /// In `region`, once `bank` has been written `value`.
+    Bank {
+        region: NodeId,
+        bank: NodeId,
+        value: u64,
+    },
+}
+
+/// A field over a buffer, from one of the `Create*Field` operators.
+#[derive(Clone, Debug)]
+pub struct BufferField {
+    pub(crate) source: Reference,
+    pub(crate) bit_offset: u64,
+    pub(crate) bit_len: u64,
+}
+
+/// The whole tree, as an arena.
+#[derive(Debug)]
+pub struct Namespace {
+    nodes: Vec<Option<Node>>,
+    free: Vec<u32>,
+    live: usize,
+}
+
+/// The scopes ACPI defines before any table loads (ACPI 6.5 §5.3.1).
+const PREDEFINED_SCOPES: [&str; 5] = ["_GPE", "_PR_", "_SB_", "_SI_", "_TZ_"];
+
+impl Default for Namespace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Namespace {
+    pub fn new() -> Self {
+        let root = Node {
+            name: NameSeg(*b"\\___"),
+            parent: None,
+            object: Object::Scope,
+            children: BTreeMap::new(),
+        };
+        let mut ns = Namespace { nodes: alloc::vec![Some(root)], free: Vec::new(), live: 1 };
+        let root = ns.root();
+        let seg = |s: &str| NameSeg::parse(s).expect("a predefined name");
+        for scope in PREDEFINED_SCOPES {
+            ns.add(root, seg(scope), Object::Scope).expect("an empty root");
+        }
+        let predefined = [
+            ("_OSI", Object::Osi),
+            ("_OS_", Object::Name(Value::String("Microsoft Windows NT".into()))),
+            // What Linux answers, and for its reason: firmware that branches on
+            // a higher `_REV` has been found to take paths only tested under
+            // one OS.
+            ("_REV", Object::Name(Value::Integer(2))),
+            ("_GL_", Object::Mutex { sync: 0 }),
+        ];
+        for (name, object) in predefined {
+            ns.add(root, seg(name), object).expect("an empty root");
+        }
+        ns
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// How many nodes exist, the root included.
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0 as usize)?.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0 as usize)?.as_mut()
+    }
+
+    pub fn object(&self, id: NodeId) -> Option<&Object> {
+        self.get(id).map(|n| &n.object)
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.get(id)?.parent
+    }
+
+    pub fn child(&self, id: NodeId, seg: NameSeg) -> Option<NodeId> {
+        self.get(id)?.children.get(&seg).copied()
+    }
+
+    /// A node's children, in name order.
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.get(id).into_iter().flat_map(|n| n.children.values().copied())
+    }
+
+    /// Every node under `id`, `id` first, each before its children.
+    pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        let mut stack = alloc::vec![id];
+        while let Some(next) = stack.pop() {
+            out.push(next);
+            let mut kids: Vec<NodeId> = self.children(next).collect();
+            kids.reverse();
+            stack.extend(kids);
+        }
+        out
+    }
+
+    /// The absolute path of a node, for a log line.
+    pub fn path(&self, id: NodeId) -> String {
+        let mut segs = Vec::new();
+        let mut at = Some(id);
+        while let Some(node) = at.and_then(|a| self.get(a)) {
+            if node.parent.is_some() {
+                segs.push(node.name);
+            }
+            at = node.parent;
+        }
+        segs.reverse();
+        name::join(&segs)
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        parent: NodeId,
+        seg: NameSeg,
+        object: Object,
+    ) -> Result<NodeId, Error> {
+        if self.child(parent, seg).is_some() {
+            return Err(Error::AlreadyExists);
+        }
+        if self.get(parent).is_none() {
+            return Err(Error::NotFound);
+        }
+        if self.live >= MAX_NODES {
+            return Err(Error::TooLarge);
+        }
+        let node = Node { name: seg, parent: Some(parent), object, children: BTreeMap::new() };
+        let id = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot as usize] = Some(node);
+                NodeId(slot)
+            }
+            None => {
+                self.nodes.push(Some(node));
+                NodeId(self.nodes.len() as u32 - 1)
+            }
+        };
+        self.live += 1;
+        if let Some(p) = self.get_mut(parent) {
+            p.children.insert(seg, id);
+        }
+        Ok(id)
+    }
+
+    /// Remove a node and everything under it.
+    pub(crate) fn remove(&mut self, id: NodeId) {
+        let Some(node) = self.get(id) else { return };
+        if let Some(parent) = node.parent {
+            let seg = node.name;
+            if let Some(p) = self.get_mut(parent) {
+                p.children.remove(&seg);
+            }
+        }
+        for dead in self.descendants(id) {
+            self.nodes[dead.0 as usize] = None;
+            self.free.push(dead.0);
+            self.live -= 1;
+        }
+    }
+
+    /// The node a name written in `scope` refers to.
+    ///
+    /// A single NameSeg with no prefix is looked for in `scope` and then in
+    /// each scope above it, the rule that lets a device's method call `_STA`
+    /// and a deeply nested one reach a name defined at `\_SB_`. Any other name
+    /// is walked exactly as written. An alias resolves to what it names.
+    pub fn resolve(&self, scope: NodeId, path: &NameString) -> Option<NodeId> {
+        let found = if path.searches() {
+            let seg = path.segs[0];
+            let mut at = Some(scope);
+            loop {
+                let here = at?;
+                if let Some(hit) = self.child(here, seg) {
+                    break hit;
+                }
+                at = self.parent(here);
+            }
+        } else {
+            let mut at = self.start(scope, path)?;
+            for &seg in &path.segs {
+                at = self.child(at, seg)?;
+            }
+            at
+        };
+        Some(self.unalias(found))
+    }
+
+    /// Where a name's walk begins: the root, or `scope` less one level per
+    /// caret.
+    fn start(&self, scope: NodeId, path: &NameString) -> Option<NodeId> {
+        if path.root {
+            return Some(self.root());
+        }
+        let mut at = scope;
+        for _ in 0..path.parents {
+            at = self.parent(at)?;
+        }
+        Some(at)
+    }
+
+    /// The scope a name being *defined* goes in, and the segment it is
+    /// defined as. Definitions never search.
+    pub(crate) fn parent_for(
+        &self,
+        scope: NodeId,
+        path: &NameString,
+    ) -> Result<(NodeId, NameSeg), Error> {
+        let (&last, walk) = path.segs.split_last().ok_or(Error::InvalidName)?;
+        let mut at = self.start(scope, path).ok_or(Error::InvalidName)?;
+        for &seg in walk {
+            at = self.child(at, seg).ok_or(Error::NotFound)?;
+        }
+        Ok((self.unalias(at), last))
+    }
+
+    fn unalias(&self, mut id: NodeId) -> NodeId {
+        // An alias of an alias is legal; a cycle of them is not, and is cut
+        // off after as many hops as there are nodes.
+        for _ in 0..self.nodes.len() {
+            match self.object(id) {
+                Some(Object::Alias(target)) => id = *target,
+                _ => break,
+            }
+        }
+        id
+    }
+
+    /// An absolute path written as text, such as `\_SB.PCI0._PRT`.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        let path = NameString::parse(path)?;
+        if path.segs.is_empty() {
+            return path.root.then(|| self.root());
+        }
+        self.resolve(self.root(), &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(s: &str) -> NameSeg {
+        NameSeg::parse(s).unwrap()
+    }
+
+    #[test]
+    fn a_bare_name_searches_upward_and_a_path_does_not() {
+        let mut ns = Namespace::new();
+        let sb = ns.lookup("\\_SB").unwrap();
+        let pci = ns.add(sb, seg("PCI0"), Object::Device).unwrap();
+        let lpc = ns.add(pci, seg("LPCB"), Object::Device).unwrap();
+        let sta = ns.add(sb, seg("_STA"), Object::Name(Value::Integer(0xF))).unwrap();
+
+        assert_eq!(ns.resolve(lpc, &NameString::parse("_STA").unwrap()), Some(sta));
+        assert_eq!(ns.resolve(lpc, &NameString::parse("PCI0.LPCB").unwrap()), None);
+        assert_eq!(ns.resolve(lpc, &NameString::parse("^^PCI0.LPCB").unwrap()), Some(lpc));
+        assert_eq!(ns.resolve(lpc, &NameString::parse("^^^^^_STA").unwrap()), None);
+        assert_eq!(ns.path(lpc), "\\_SB_.PCI0.LPCB");
+    }
+
+    #[test]
+    fn removal_frees_a_whole_subtree() {
+        let mut ns = Namespace::new();
+        let before = ns.len();
+        let sb = ns.lookup("\\_SB_").unwrap();
+        let dev = ns.add(sb, seg("DEV0"), Object::Device).unwrap();
+        ns.add(dev, seg("_HID"), Object::Name(Value::Integer(1))).unwrap();
+        assert_eq!(ns.add(sb, seg("DEV0"), Object::Device), Err(Error::AlreadyExists));
+        ns.remove(dev);
+        assert_eq!(ns.len(), before);
+        assert_eq!(ns.lookup("\\_SB.DEV0"), None);
+        assert_eq!(ns.lookup("\\_SB.DEV0._HID"), None);
+    }
+
+    #[test]
+    fn an_alias_cycle_ends() {
+        let mut ns = Namespace::new();
+        let root = ns.root();
+        let a = ns.add(root, seg("AAAA"), Object::Scope).unwrap();
+        let b = ns.add(root, seg("BBBB"), Object::Alias(a)).unwrap();
+        ns.get_mut(a).unwrap().object = Object::Alias(b);
+        assert!(ns.lookup("\\AAAA").is_some());
+    }
+}
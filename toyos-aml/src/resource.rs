@@ -0,0 +1,236 @@
+//! Resource templates: the buffers `_CRS` and `_PRS` return, as the ranges
+//! and interrupts they name (ACPI 6.5 §6.4).
+//!
+//! A template is a run of descriptors, each a tag and a length, ended by an
+//! End Tag. Every length is the firmware's, and one that runs past the buffer
+//! or is shorter than its descriptor's fixed part is refused rather than read
+//! around.
+
+use alloc::vec::Vec;
+
+use crate::{Error, MAX_PACKAGE};
+
+/// One descriptor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resource {
+    /// An IRQ or Extended Interrupt descriptor: the interrupts it names —
+    /// ISA IRQs for the first, GSIs for the second — and how they signal.
+    Irq { interrupts: Vec<u32>, edge: bool, active_low: bool, shared: bool },
+    /// An I/O port range: `len` ports at some base in `min..=max` aligned to
+    /// `align`. A fixed one has `min == max`.
+    Io { min: u16, max: u16, align: u8, len: u8 },
+    /// A fixed or relocatable 32-bit memory range, at its lowest base.
+    Memory { base: u64, len: u64, writable: bool },
+    /// A Word, DWord, QWord or Extended address space descriptor: what a host
+    /// bridge decodes, or a bus number range.
+    Address { kind: AddressKind, min: u64, max: u64, translation: u64, len: u64 },
+    /// A descriptor this decoder names by tag alone: DMA, vendor, GPIO, serial
+    /// bus and the dependent-function markers.
+    Other(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressKind {
+    Memory,
+    Io,
+    BusNumber,
+    Other(u8),
+}
+
+impl AddressKind {
+    fn from_byte(b: u8) -> Self {
+        match b {
+            0 => AddressKind::Memory,
+            1 => AddressKind::Io,
+            2 => AddressKind::BusNumber,
+            other => AddressKind::Other(other),
+        }
+    }
+}
+
+const END_TAG: u8 = 0x0F;
+
+fn le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0u64, |acc, &b| (acc << 8) | u64::from(b))
+}
+
+/// Decode a template.
+pub fn parse(buf: &[u8]) -> Result<Vec<Resource>, Error> {
+    let mut out = Vec::new();
+    let mut at = 0usize;
+    while at < buf.len() {
+        if out.len() >= MAX_PACKAGE {
+            return Err(Error::TooLarge);
+        }
+        let tag = buf[at];
+        if tag & 0x80 == 0 {
+            let kind = (tag >> 3) & 0x0F;
+            let len = usize::from(tag & 0x07);
+            let body = buf.get(at + 1..at + 1 + len).ok_or(Error::Truncated)?;
+            at += 1 + len;
+            let need = |n: usize| if len < n { Err(Error::Truncated) } else { Ok(()) };
+            match kind {
+                END_TAG => break,
+                0x04 => {
+                    need(2)?;
+                    let mask = le(&body[..2]) as u32;
+                    // No flags byte means edge-triggered, active high.
+                    let flags = body.get(2).copied().unwrap_or(0x01);
+                    out.push(Resource::Irq {
+                        interrupts: (0..16).filter(|i| mask & (1 << i) != 0).collect(),
+                        edge: flags & 0x01 != 0,
+                        active_low: flags & 0x08 != 0,
+                        shared: flags & 0x10 != 0,
+                    });
+                }
+                0x08 => {
+                    need(7)?;
+                    out.push(Resource::Io {
+                        min: le(&body[1..3]) as u16,
+                        max: le(&body[3..5]) as u16,
+                        align: body[5],
+                        len: body[6],
+                    });
+                }
+                0x09 => {
+                    need(3)?;
+                    let base = (le(&body[..2]) & 0x3FF) as u16;
+                    out.push(Resource::Io { min: base, max: base, align: 1, len: body[2] });
+                }
+                other => out.push(Resource::Other(other)),
+            }
+        } else {
+            let kind = tag & 0x7F;
+            let len = usize::from(u16::from_le_bytes([
+                *buf.get(at + 1).ok_or(Error::Truncated)?,
+                *buf.get(at + 2).ok_or(Error::Truncated)?,
+            ]));
+            let body = buf.get(at + 3..at + 3 + len).ok_or(Error::Truncated)?;
+            at += 3 + len;
+            let need = |n: usize| if len < n { Err(Error::Truncated) } else { Ok(()) };
+            match kind {
+                0x05 => {
+                    need(17)?;
+                    out.push(Resource::Memory {
+                        base: le(&body[1..5]),
+                        len: le(&body[13..17]),
+                        writable: body[0] & 1 != 0,
+                    });
+                }
+                0x06 => {
+                    need(9)?;
+                    out.push(Resource::Memory {
+                        base: le(&body[1..5]),
+                        len: le(&body[5..9]),
+                        writable: body[0] & 1 != 0,
+                    });
+                }
+                0x07 | 0x08 | 0x0A | 0x0B => {
+                    // Resource type, general flags, type-specific flags, then
+                    // granularity, minimum, maximum, translation and length at
+                    // the descriptor's width; the Extended one has a revision
+                    // and a reserved byte before them.
+                    let (size, skip) = match kind {
+                        0x08 => (2, 3),
+                        0x07 => (4, 3),
+                        0x0A => (8, 3),
+                        _ => (8, 5),
+                    };
+                    need(skip + 5 * size)?;
+                    let word = |i: usize| le(&body[skip + i * size..skip + (i + 1) * size]);
+                    out.push(Resource::Address {
+                        kind: AddressKind::from_byte(body[0]),
+                        min: word(1),
+                        max: word(2),
+                        translation: word(3),
+                        len: word(4),
+                    });
+                }
+                0x09 => {
+                    need(2)?;
+                    let flags = body[0];
+                    let count = usize::from(body[1]);
+                    need(2 + count * 4)?;
+                    let interrupts =
+                        (0..count).map(|i| le(&body[2 + i * 4..6 + i * 4]) as u32).collect();
+                    out.push(Resource::Irq {
+                        interrupts,
+                        edge: flags & 0x02 != 0,
+                        active_low: flags & 0x04 != 0,
+                        shared: flags & 0x08 != 0,
+                    });
+                }
+                other => out.push(Resource::Other(0x80 | other)),
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn the_small_descriptors_a_uart_and_a_ps2_controller_use() {
+        // IO (Decode16, 0x3F8, 0x3F8, 0x01, 0x08), IRQNoFlags () {4}, End.
+        let uart = [0x47, 0x01, 0xF8, 0x03, 0xF8, 0x03, 0x01, 0x08, 0x22, 0x10, 0x00, 0x79, 0x00];
+        assert_eq!(
+            parse(&uart),
+            Ok(vec![
+                Resource::Io { min: 0x3F8, max: 0x3F8, align: 1, len: 8 },
+                Resource::Irq { interrupts: vec![4], edge: true, active_low: false, shared: false },
+            ])
+        );
+        // FixedIO (0x60, 1), IRQ (Level, ActiveLow, Shared) {9, 11}.
+        let fixed = [0x4B, 0x60, 0x00, 0x01, 0x23, 0x00, 0x0A, 0x18, 0x79, 0x00];
+        assert_eq!(
+            parse(&fixed),
+            Ok(vec![
+                Resource::Io { min: 0x60, max: 0x60, align: 1, len: 1 },
+                Resource::Irq {
+                    interrupts: vec![9, 11],
+                    edge: false,
+                    active_low: true,
+                    shared: true
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn address_space_descriptors_at_every_width() {
+        // WordBusNumber 0..=0xFF, length 0x100.
+        let word =
+            [0x88, 0x0D, 0x00, 0x02, 0x0C, 0x00, 0, 0, 0, 0, 0xFF, 0, 0, 0, 0, 0x01, 0x79, 0];
+        let Ok(r) = parse(&word) else { panic!() };
+        assert_eq!(
+            r[0],
+            Resource::Address {
+                kind: AddressKind::BusNumber,
+                min: 0,
+                max: 0xFF,
+                translation: 0,
+                len: 0x100
+            }
+        );
+        // Memory32Fixed (ReadWrite, 0xFEC00000, 0x1000).
+        let fixed =
+            [0x86, 0x09, 0x00, 0x01, 0x00, 0x00, 0xC0, 0xFE, 0x00, 0x10, 0x00, 0x00, 0x79, 0];
+        assert_eq!(
+            parse(&fixed).unwrap()[0],
+            Resource::Memory { base: 0xFEC0_0000, len: 0x1000, writable: true }
+        );
+    }
+
+    #[test]
+    fn a_descriptor_that_lies_about_its_length_is_refused() {
+        assert_eq!(parse(&[0x47, 0x01, 0xF8]), Err(Error::Truncated));
+        assert_eq!(parse(&[0x89, 0xFF, 0xFF, 0x00]), Err(Error::Truncated));
+        // Extended IRQ claiming 200 interrupts in a 6-byte body.
+        assert_eq!(parse(&[0x89, 0x06, 0x00, 0x01, 0xC8, 5, 0, 0, 0]), Err(Error::Truncated));
+        // An I/O descriptor too short for its fixed part.
+        assert_eq!(parse(&[0x42, 0x00, 0x00, 0x79, 0x00]), Err(Error::Truncated));
+    }
+}
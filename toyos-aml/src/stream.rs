@@ -0,0 +1,173 @@
+//! Reading AML: a position in a table, and the end it may not pass.
+
+use alloc::sync::Arc;
+
+use crate::Error;
+
+/// A window on a table's bytes. Shared, because a method keeps running the
+/// table that defined it long after the load returned, and cheap to copy
+/// because every nested package gets a cursor of its own that ends where the
+/// package does.
+#[derive(Clone)]
+pub(crate) struct Cursor {
+    code: Arc<[u8]>,
+    pos: usize,
+    end: usize,
+}
+
+impl Cursor {
+    pub(crate) fn new(code: Arc<[u8]>) -> Self {
+        let end = code.len();
+        Cursor { code, pos: 0, end }
+    }
+
+    /// The same table from `pos` to `end`, for a method body.
+    pub(crate) fn window(code: Arc<[u8]>, pos: usize, end: usize) -> Self {
+        let end = end.min(code.len());
+        Cursor { code, pos: pos.min(end), end }
+    }
+
+    pub(crate) fn code(&self) -> &Arc<[u8]> {
+        &self.code
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn end(&self) -> usize {
+        self.end
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.pos >= self.end
+    }
+
+    /// A cursor over this one's next bytes up to `end`, and this one moved
+    /// past them.
+    pub(crate) fn split(&mut self, end: usize) -> Cursor {
+        let end = end.clamp(self.pos, self.end);
+        let inner = Cursor { code: self.code.clone(), pos: self.pos, end };
+        self.pos = end;
+        inner
+    }
+
+    pub(crate) fn skip_to(&mut self, pos: usize) {
+        self.pos = pos.min(self.end);
+    }
+
+    pub(crate) fn peek(&self) -> Result<u8, Error> {
+        if self.pos < self.end {
+            Ok(self.code[self.pos])
+        } else {
+            Err(Error::Truncated)
+        }
+    }
+
+    /// The byte after the next, for the two-byte opcodes and for telling
+    /// `LNotEqual` from `LNot`.
+    pub(crate) fn peek_second(&self) -> Option<u8> {
+        (self.pos + 1 < self.end).then(|| self.code[self.pos + 1])
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, Error> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    pub(crate) fn bytes(&mut self, n: usize) -> Result<&[u8], Error> {
+        let end = self.pos.checked_add(n).ok_or(Error::Truncated)?;
+        if end > self.end {
+            return Err(Error::Truncated);
+        }
+        let start = self.pos;
+        self.pos = end;
+        Ok(&self.code[start..end])
+    }
+
+    pub(crate) fn word(&mut self) -> Result<u16, Error> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub(crate) fn dword(&mut self) -> Result<u32, Error> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub(crate) fn qword(&mut self) -> Result<u64, Error> {
+        let b = self.bytes(8)?;
+        let mut q = [0u8; 8];
+        q.copy_from_slice(b);
+        Ok(u64::from_le_bytes(q))
+    }
+
+    /// A `PkgLength`'s raw value: the top two bits of the lead byte say how
+    /// many bytes follow, and with any following the lead contributes its low
+    /// nibble alone.
+    pub(crate) fn pkg_length_raw(&mut self) -> Result<u32, Error> {
+        let lead = self.byte()?;
+        let follow = lead >> 6;
+        if follow == 0 {
+            return Ok(u32::from(lead & 0x3F));
+        }
+        let mut len = u32::from(lead & 0x0F);
+        for i in 0..follow {
+            len |= u32::from(self.byte()?) << (4 + 8 * u32::from(i));
+        }
+        Ok(len)
+    }
+
+    /// A `PkgLength` as the absolute end of what it measures. It counts from
+    /// its own first byte, and an end past this cursor's is a table that lies
+    /// about its own size.
+    pub(crate) fn pkg_end(&mut self) -> Result<usize, Error> {
+        let start = self.pos;
+        let len = self.pkg_length_raw()? as usize;
+        let end = start.checked_add(len).ok_or(Error::Truncated)?;
+        if end > self.end || end < self.pos {
+            return Err(Error::Truncated);
+        }
+        Ok(end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(bytes: &[u8]) -> Cursor {
+        Cursor::new(Arc::from(bytes))
+    }
+
+    #[test]
+    fn every_pkg_length_size_counts_from_its_first_byte() {
+        assert_eq!(cursor(&[0x05, 0, 0, 0, 0]).pkg_end(), Ok(5));
+        // 0x48 0x01: one following byte, 0x8 | 0x01 << 4 = 0x18.
+        let mut long = [0u8; 0x18];
+        long[0] = 0x48;
+        long[1] = 0x01;
+        assert_eq!(cursor(&long).pkg_end(), Ok(0x18));
+        assert_eq!(cursor(&[0xC0, 0, 0, 0x10]).pkg_length_raw(), Ok(0x0100_0000));
+    }
+
+    #[test]
+    fn a_length_past_the_window_is_truncated() {
+        assert_eq!(cursor(&[0x06, 0, 0]).pkg_end(), Err(Error::Truncated));
+        assert_eq!(cursor(&[0x48]).pkg_end(), Err(Error::Truncated));
+        assert_eq!(cursor(&[0xFF, 0xFF, 0xFF, 0xFF]).pkg_end(), Err(Error::Truncated));
+        // A zero length would end before the PkgLength that states it.
+        assert_eq!(cursor(&[0x40, 0x00]).pkg_end(), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn reads_stop_at_the_end_of_a_split() {
+        let mut outer = cursor(&[1, 2, 3, 4, 5]);
+        let mut inner = outer.split(2);
+        assert_eq!(inner.word(), Ok(0x0201));
+        assert_eq!(inner.byte(), Err(Error::Truncated));
+        assert_eq!(outer.byte(), Ok(3));
+        assert_eq!(outer.dword(), Err(Error::Truncated));
+    }
+}
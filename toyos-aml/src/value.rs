@@ -0,0 +1,278 @@
+//! The objects AML computes with, and the conversions ACPI makes between them
+//! whenever an operator is handed a type other than the one it wants.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Write;
+
+use crate::name::NameString;
+use crate::namespace::NodeId;
+use crate::{Error, MAX_BUFFER};
+
+/// One data object.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum Value {
+    /// A local never stored to, an argument never passed, or a method that
+    /// returned nothing.
+    #[default]
+    Uninitialized,
+    Integer(u64),
+    String(String),
+    Buffer(Vec<u8>),
+    Package(Vec<Value>),
+    Reference(Reference),
+}
+
+/// Where a value lives, for `RefOf`, `Index` and a name inside a package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reference {
+    /// A named object.
+    Node(NodeId),
+    /// A name written inside a package, resolved when it is used rather than
+    /// when the package is built: a `_PRT` names link devices the table may
+    /// define after it.
+    Path { scope: NodeId, path: NameString },
+    /// `LocalN` of the method running at call depth `frame`.
+    Local { frame: usize, index: u8 },
+    /// `ArgN` of the method running at call depth `frame`.
+    Arg { frame: usize, index: u8 },
+    /// Element `index` of the package, buffer or string `holder` refers to.
+    Element { holder: Box<Reference>, index: usize },
+    /// A value with no location, so that `Index` of a literal package still
+    /// refers to something. Stores through it are discarded.
+    Temp(Box<Value>),
+}
+
+/// How wide an integer is: 32 bits in a table whose revision is below 2, 64
+/// otherwise. The DSDT's revision decides for every table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    Narrow,
+    Wide,
+}
+
+impl Width {
+    pub fn bytes(self) -> usize {
+        match self {
+            Width::Narrow => 4,
+            Width::Wide => 8,
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        self.bytes() as u32 * 8
+    }
+
+    /// `Ones`: every bit of an integer this wide.
+    pub fn ones(self) -> u64 {
+        match self {
+            Width::Narrow => u64::from(u32::MAX),
+            Width::Wide => u64::MAX,
+        }
+    }
+
+    pub fn truncate(self, v: u64) -> u64 {
+        v & self.ones()
+    }
+}
+
+impl Value {
+    pub fn as_integer(&self) -> Option<u64> {
+        match self {
+            Value::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_package(&self) -> Option<&[Value]> {
+        match self {
+            Value::Package(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn as_buffer(&self) -> Option<&[u8]> {
+        match self {
+            Value::Buffer(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// `ObjectType`'s number for a data object.
+    pub(crate) fn type_code(&self) -> u64 {
+        match self {
+            Value::Uninitialized => 0,
+            Value::Integer(_) => 1,
+            Value::String(_) => 2,
+            Value::Buffer(_) => 3,
+            Value::Package(_) => 4,
+            Value::Reference(_) => 20,
+        }
+    }
+
+    /// The implicit conversion to an integer. A string is read as hex — the
+    /// only base implicit conversion knows — up to its first character that is
+    /// not a digit; a buffer is its first bytes, little-endian.
+    pub fn to_integer(&self, width: Width) -> Result<u64, Error> {
+        match self {
+            Value::Integer(v) => Ok(width.truncate(*v)),
+            Value::String(s) => Ok(width.truncate(parse_hex(s))),
+            Value::Buffer(b) => {
+                let mut bytes = [0u8; 8];
+                let n = b.len().min(width.bytes());
+                bytes[..n].copy_from_slice(&b[..n]);
+                Ok(u64::from_le_bytes(bytes))
+            }
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// `ToInteger`: decimal unless the string says `0x`.
+    pub(crate) fn to_integer_explicit(&self, width: Width) -> Result<u64, Error> {
+        match self {
+            Value::String(s) => {
+                let s = s.trim_start();
+                let v = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                    Some(hex) => parse_hex(hex),
+                    None => s.bytes().take_while(u8::is_ascii_digit).fold(0u64, |acc, d| {
+                        acc.wrapping_mul(10).wrapping_add(u64::from(d - b'0'))
+                    }),
+                };
+                Ok(width.truncate(v))
+            }
+            other => other.to_integer(width),
+        }
+    }
+
+    /// The implicit conversion to a buffer: an integer's bytes at the table's
+    /// width, a string's bytes and its terminator.
+    pub fn to_buffer(&self, width: Width) -> Result<Vec<u8>, Error> {
+        match self {
+            Value::Integer(v) => Ok(v.to_le_bytes()[..width.bytes()].to_vec()),
+            Value::String(s) => {
+                let mut b = Vec::with_capacity(s.len() + 1);
+                b.extend_from_slice(s.as_bytes());
+                b.push(0);
+                Ok(b)
+            }
+            Value::Buffer(b) => Ok(b.clone()),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// The implicit conversion to a string: an integer as fixed-width hex, a
+    /// buffer as its bytes in hex with spaces between.
+    pub fn to_string(&self, width: Width) -> Result<String, Error> {
+        match self {
+            Value::Integer(v) => {
+                let mut s = String::new();
+                let _ = write!(s, "{:0w$X}", width.truncate(*v), w = width.bytes() * 2);
+                Ok(s)
+            }
+            Value::String(s) => Ok(s.clone()),
+            Value::Buffer(b) => {
+                check_len(b.len() * 3)?;
+                let mut s = String::new();
+                for (i, byte) in b.iter().enumerate() {
+                    if i > 0 {
+                        s.push(' ');
+                    }
+                    let _ = write!(s, "{byte:02X}");
+                }
+                Ok(s)
+            }
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// `SizeOf`.
+    pub(crate) fn size(&self) -> Result<u64, Error> {
+        match self {
+            Value::String(s) => Ok(s.len() as u64),
+            Value::Buffer(b) => Ok(b.len() as u64),
+            Value::Package(p) => Ok(p.len() as u64),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// The logical comparisons: the right operand is converted to the left's
+    /// type, then integers compare as numbers and strings and buffers byte by
+    /// byte, a shorter one that is a prefix of the longer ordering first.
+    pub(crate) fn compare(&self, other: &Value, width: Width) -> Result<Ordering, Error> {
+        match self {
+            Value::Integer(a) => Ok(width.truncate(*a).cmp(&other.to_integer(width)?)),
+            Value::String(a) => {
+                let b = match other {
+                    Value::Buffer(b) => String::from_utf8_lossy(b).into_owned(),
+                    other => other.to_string(width)?,
+                };
+                Ok(a.as_bytes().cmp(b.as_bytes()))
+            }
+            Value::Buffer(a) => Ok(a.as_slice().cmp(other.to_buffer(width)?.as_slice())),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+}
+
+/// A string read as hex, stopping at the first character that is not a digit.
+/// Overflow wraps, as a register-sized value read from text would.
+fn parse_hex(s: &str) -> u64 {
+    let s = s.trim_start();
+    let s = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    s.chars().map_while(|c| c.to_digit(16)).fold(0u64, |acc, d| (acc << 4) | u64::from(d))
+}
+
+/// Refuse a buffer or string that would grow past [`MAX_BUFFER`].
+pub(crate) fn check_len(len: usize) -> Result<(), Error> {
+    if len > MAX_BUFFER {
+        Err(Error::TooLarge)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn strings_convert_as_hex_implicitly_and_decimal_explicitly() {
+        let s = Value::String("1F".into());
+        assert_eq!(s.to_integer(Width::Wide), Ok(0x1F));
+        assert_eq!(Value::String("0x10 trailing".into()).to_integer(Width::Wide), Ok(0x10));
+        assert_eq!(Value::String("42".into()).to_integer_explicit(Width::Wide), Ok(42));
+        assert_eq!(Value::String("0x42".into()).to_integer_explicit(Width::Wide), Ok(0x42));
+        assert_eq!(Value::String("FFFFFFFFF".into()).to_integer(Width::Narrow), Ok(0xFFFF_FFFF));
+    }
+
+    #[test]
+    fn buffers_are_little_endian_at_the_table_width() {
+        let b = Value::Buffer(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(b.to_integer(Width::Wide), Ok(0x0807_0605_0403_0201));
+        assert_eq!(b.to_integer(Width::Narrow), Ok(0x0403_0201));
+        assert_eq!(Value::Integer(0x0102).to_buffer(Width::Narrow), Ok(vec![2, 1, 0, 0]));
+        assert_eq!(Value::String("AB".into()).to_buffer(Width::Wide), Ok(vec![b'A', b'B', 0]));
+    }
+
+    #[test]
+    fn the_right_operand_takes_the_left_ones_type() {
+        let n = Value::Integer(0x10);
+        assert_eq!(n.compare(&Value::String("10".into()), Width::Wide), Ok(Ordering::Equal));
+        let s = Value::String("abc".into());
+        assert_eq!(s.compare(&Value::String("abd".into()), Width::Wide), Ok(Ordering::Less));
+        assert_eq!(s.compare(&Value::String("ab".into()), Width::Wide), Ok(Ordering::Greater));
+        let p = Value::Package(vec![]);
+        assert_eq!(p.compare(&n, Width::Wide), Err(Error::TypeMismatch));
+        assert_eq!(Value::Integer(1).to_string(Width::Narrow).unwrap(), "00000001");
+    }
+}
@@ -0,0 +1,316 @@
+//! An AML assembler small enough to read, and a host that records what it is
+//! asked.
+//!
+//! Deliberately not a compiler: it emits exactly the bytes each call names,
+//! including lengths and counts no compiler would, because a table written to
+//! break the interpreter is half of what these tests are.
+
+use std::collections::HashMap;
+
+use toyos_aml::{Error, Host, Space, Value};
+
+/// A `PkgLength` for `len` bytes of content, counting itself.
+pub fn pkg_length(len: usize) -> Vec<u8> {
+    for extra in 0..4 {
+        let total = len + 1 + extra;
+        let fits = match extra {
+            0 => total < 0x40,
+            1 => total < 0x1000,
+            2 => total < 0x10_0000,
+            _ => total < 0x1000_0000,
+        };
+        if fits {
+            if extra == 0 {
+                return vec![total as u8];
+            }
+            let mut out = vec![(extra as u8) << 6 | (total & 0xF) as u8];
+            for i in 0..extra {
+                out.push((total >> (4 + 8 * i)) as u8);
+            }
+            return out;
+        }
+    }
+    panic!("no PkgLength holds {len}");
+}
+
+/// `op`, a PkgLength, then `body`.
+pub fn pkg(op: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut out = op.to_vec();
+    out.extend(pkg_length(body.len()));
+    out.extend_from_slice(body);
+    out
+}
+
+pub fn cat(parts: &[&[u8]]) -> Vec<u8> {
+    parts.concat()
+}
+
+/// A NameString as ASL writes it: `\_SB.PCI0`, `^FOO`, `_STA`.
+pub fn name(path: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut rest = path;
+    while let Some(r) = rest.strip_prefix('\\').or_else(|| rest.strip_prefix('^')) {
+        out.push(rest.as_bytes()[0]);
+        rest = r;
+    }
+    let segs: Vec<Vec<u8>> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split('.')
+            .map(|s| {
+                let mut seg = s.as_bytes().to_vec();
+                seg.resize(4, b'_');
+                seg
+            })
+            .collect()
+    };
+    match segs.len() {
+        0 => out.push(0),
+        1 => {}
+        2 => out.push(0x2E),
+        n => {
+            out.push(0x2F);
+            out.push(n as u8);
+        }
+    }
+    for seg in segs {
+        out.extend(seg);
+    }
+    out
+}
+
+pub fn int(v: u64) -> Vec<u8> {
+    match v {
+        0 => vec![0x00],
+        1 => vec![0x01],
+        v if v <= 0xFF => vec![0x0A, v as u8],
+        v if v <= 0xFFFF => cat(&[&[0x0B], &(v as u16).to_le_bytes()]),
+        v if v <= 0xFFFF_FFFF => cat(&[&[0x0C], &(v as u32).to_le_bytes()]),
+        v => cat(&[&[0x0E], &v.to_le_bytes()]),
+    }
+}
+
+pub fn string(s: &str) -> Vec<u8> {
+    cat(&[&[0x0D], s.as_bytes(), &[0]])
+}
+
+pub fn buffer(bytes: &[u8]) -> Vec<u8> {
+    pkg(&[0x11], &cat(&[&int(bytes.len() as u64), bytes]))
+}
+
+pub fn package(elements: &[Vec<u8>]) -> Vec<u8> {
+    let mut body = vec![elements.len() as u8];
+    for e in elements {
+        body.extend(e);
+    }
+    pkg(&[0x12], &body)
+}
+
+pub fn local(n: u8) -> Vec<u8> {
+    vec![0x60 + n]
+}
+
+pub fn arg(n: u8) -> Vec<u8> {
+    vec![0x68 + n]
+}
+
+pub fn def_name(n: &str, value: &[u8]) -> Vec<u8> {
+    cat(&[&[0x08], &name(n), value])
+}
+
+pub fn method(n: &str, args: u8, body: &[u8]) -> Vec<u8> {
+    pkg(&[0x14], &cat(&[&name(n), &[args], body]))
+}
+
+pub fn scope(n: &str, body: &[u8]) -> Vec<u8> {
+    pkg(&[0x10], &cat(&[&name(n), body]))
+}
+
+pub fn device(n: &str, body: &[u8]) -> Vec<u8> {
+    pkg(&[0x5B, 0x82], &cat(&[&name(n), body]))
+}
+
+pub fn ret(value: &[u8]) -> Vec<u8> {
+    cat(&[&[0xA4], value])
+}
+
+pub fn store(value: &[u8], target: &[u8]) -> Vec<u8> {
+    cat(&[&[0x70], value, target])
+}
+
+pub fn if_(predicate: &[u8], body: &[u8]) -> Vec<u8> {
+    pkg(&[0xA0], &cat(&[predicate, body]))
+}
+
+pub fn else_(body: &[u8]) -> Vec<u8> {
+    pkg(&[0xA1], body)
+}
+
+pub fn while_(predicate: &[u8], body: &[u8]) -> Vec<u8> {
+    pkg(&[0xA2], &cat(&[predicate, body]))
+}
+
+/// `op a b Target` for the binary integer operators.
+pub fn binary(op: u8, a: &[u8], b: &[u8], target: &[u8]) -> Vec<u8> {
+    cat(&[&[op], a, b, target])
+}
+
+pub fn lequal(a: &[u8], b: &[u8]) -> Vec<u8> {
+    cat(&[&[0x93], a, b])
+}
+
+pub fn lless(a: &[u8], b: &[u8]) -> Vec<u8> {
+    cat(&[&[0x95], a, b])
+}
+
+pub fn region(n: &str, space: u8, offset: u64, len: u64) -> Vec<u8> {
+    cat(&[&[0x5B, 0x80], &name(n), &[space], &int(offset), &int(len)])
+}
+
+/// `Field (region, flags) { name, bits; ... }`, with `None` as a name for a
+/// reserved gap.
+pub fn field(region: &str, flags: u8, units: &[(Option<&str>, u32)]) -> Vec<u8> {
+    let mut body = cat(&[&name(region), &[flags]]);
+    for &(n, bits) in units {
+        match n {
+            Some(n) => body.extend(name(n)),
+            None => body.push(0x00),
+        }
+        body.extend(raw_length(bits));
+    }
+    pkg(&[0x5B, 0x81], &body)
+}
+
+/// A bare PkgLength encoding `v` itself, for field widths.
+pub fn raw_length(v: u32) -> Vec<u8> {
+    if v < 0x40 {
+        return vec![v as u8];
+    }
+    let extra = if v < 0x1000 {
+        1
+    } else if v < 0x10_0000 {
+        2
+    } else {
+        3
+    };
+    let mut out = vec![(extra as u8) << 6 | (v & 0xF) as u8];
+    for i in 0..extra {
+        out.push((v >> (4 + 8 * i)) as u8);
+    }
+    out
+}
+
+/// A definition block: the 36-byte header, then `body`.
+pub fn table(signature: &[u8; 4], revision: u8, body: &[u8]) -> Vec<u8> {
+    let mut t = Vec::with_capacity(36 + body.len());
+    t.extend_from_slice(signature);
+    t.extend(((36 + body.len()) as u32).to_le_bytes());
+    t.push(revision);
+    t.push(0);
+    t.extend_from_slice(b"TOYOS ");
+    t.extend_from_slice(b"AMLTEST ");
+    t.extend(1u32.to_le_bytes());
+    t.extend_from_slice(b"TOYS");
+    t.extend(1u32.to_le_bytes());
+    t.extend_from_slice(body);
+    let sum = t.iter().fold(0u8, |a, &b| a.wrapping_add(b));
+    t[9] = 0u8.wrapping_sub(sum);
+    t
+}
+
+pub fn dsdt(body: &[u8]) -> Vec<u8> {
+    table(b"DSDT", 2, body)
+}
+
+/// One access the host was asked for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read(Space, u64, u8),
+    Write(Space, u64, u8, u64),
+}
+
+/// Memory-backed regions: every space is a sparse byte array, and every
+/// access is recorded.
+#[derive(Default)]
+pub struct MockHost {
+    pub bytes: HashMap<(SpaceKey, u64), u8>,
+    pub log: Vec<Access>,
+    pub slept_ms: u64,
+    pub stalled_us: u64,
+    pub debug: Vec<Value>,
+    /// Addresses in system memory the host refuses, as the kernel's refuses
+    /// RAM it manages.
+    pub forbidden: Option<std::ops::Range<u64>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpaceKey {
+    Memory,
+    Io,
+    Pci(u16, u8, u8, u8),
+    Ec,
+}
+
+fn key(space: Space) -> Result<SpaceKey, Error> {
+    match space {
+        Space::SystemMemory => Ok(SpaceKey::Memory),
+        Space::SystemIo => Ok(SpaceKey::Io),
+        Space::PciConfig(a) => Ok(SpaceKey::Pci(a.segment, a.bus, a.device, a.function)),
+        Space::EmbeddedControl => Ok(SpaceKey::Ec),
+        Space::Other(_) => Err(Error::Unsupported),
+    }
+}
+
+impl MockHost {
+    pub fn poke(&mut self, space: SpaceKey, address: u64, value: u64, bytes: u64) {
+        for i in 0..bytes {
+            self.bytes.insert((space, address + i), (value >> (8 * i)) as u8);
+        }
+    }
+
+    pub fn peek(&self, space: SpaceKey, address: u64, bytes: u64) -> u64 {
+        (0..bytes).fold(0, |acc, i| {
+            acc | u64::from(*self.bytes.get(&(space, address + i)).unwrap_or(&0)) << (8 * i)
+        })
+    }
+}
+
+impl Host for MockHost {
+    fn read(&mut self, space: Space, address: u64, width: u8) -> Result<u64, Error> {
+        self.log.push(Access::Read(space, address, width));
+        if space == Space::SystemMemory
+            && self.forbidden.as_ref().is_some_and(|r| r.contains(&address))
+        {
+            return Err(Error::Refused);
+        }
+        Ok(self.peek(key(space)?, address, u64::from(width / 8)))
+    }
+
+    fn write(&mut self, space: Space, address: u64, width: u8, value: u64) -> Result<(), Error> {
+        self.log.push(Access::Write(space, address, width, value));
+        if space == Space::SystemMemory
+            && self.forbidden.as_ref().is_some_and(|r| r.contains(&address))
+        {
+            return Err(Error::Refused);
+        }
+        let k = key(space)?;
+        self.poke(k, address, value, u64::from(width / 8));
+        Ok(())
+    }
+
+    fn sleep(&mut self, ms: u64) {
+        self.slept_ms += ms;
+    }
+
+    fn stall(&mut self, us: u64) {
+        self.stalled_us += us;
+    }
+
+    fn timer(&mut self) -> u64 {
+        42
+    }
+
+    fn debug(&mut self, value: &Value) {
+        self.debug.push(value.clone());
+    }
+}
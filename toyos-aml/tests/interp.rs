@@ -0,0 +1,630 @@
+//! What the interpreter promises for well-formed AML: the operators compute
+//! what ACPI 6.5 §19.6 says, stores convert as §19.3.5 says, fields reach the
+//! host as the units they cover, and the device helpers decode what firmware
+//! returns.
+
+// Each test file uses a different part of the assembler.
+#[allow(dead_code)]
+mod common;
+
+use common::*;
+use toyos_aml::host::PciAddress;
+use toyos_aml::{Error, Interpreter, Resource, Route, RouteSource, Space, Value};
+
+fn load(body: &[u8]) -> (Interpreter, MockHost) {
+    let mut host = MockHost::default();
+    let mut aml = Interpreter::new();
+    aml.load_table(&mut host, &dsdt(body)).expect("the table loads");
+    (aml, host)
+}
+
+fn eval(aml: &mut Interpreter, host: &mut MockHost, path: &str, args: Vec<Value>) -> Value {
+    aml.evaluate(host, path, args).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+fn integer(aml: &mut Interpreter, host: &mut MockHost, path: &str, args: Vec<Value>) -> u64 {
+    eval(aml, host, path, args).as_integer().expect("an integer")
+}
+
+#[test]
+fn a_loop_computes_a_factorial() {
+    // Method (FACT, 1) { Local0 = 1; While (Arg0) { Local0 *= Arg0; Arg0-- } Return (Local0) }
+    let body = method(
+        "FACT",
+        1,
+        &cat(&[
+            &store(&int(1), &local(0)),
+            &while_(
+                &arg(0),
+                &cat(&[&binary(0x77, &local(0), &arg(0), &local(0)), &[0x76], &arg(0)]),
+            ),
+            &ret(&local(0)),
+        ]),
+    );
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(integer(&mut aml, &mut host, "\\FACT", vec![Value::Integer(10)]), 3_628_800);
+}
+
+#[test]
+fn methods_recurse_and_call_by_searching_upward() {
+    // \_SB.FIB(n) = n < 2 ? n : FIB(n-1) + FIB(n-2), called from a device
+    // below by its bare name.
+    let fib = method(
+        "FIB_",
+        1,
+        &cat(&[
+            &if_(&lless(&arg(0), &int(2)), &ret(&arg(0))),
+            &ret(&binary(
+                0x72,
+                &cat(&[&name("FIB_"), &binary(0x74, &arg(0), &int(1), &[0])]),
+                &cat(&[&name("FIB_"), &binary(0x74, &arg(0), &int(2), &[0])]),
+                &[0],
+            )),
+        ]),
+    );
+    let body = scope(
+        "\\_SB",
+        &cat(&[&fib, &device("DEV0", &method("CALL", 0, &ret(&cat(&[&name("FIB_"), &int(12)]))))]),
+    );
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(integer(&mut aml, &mut host, "\\_SB.DEV0.CALL", vec![]), 144);
+}
+
+#[test]
+fn if_else_break_and_continue() {
+    // Sum the odd numbers below 10, stopping at 7: 1 + 3 + 5 = 9.
+    let body = method(
+        "ODDS",
+        0,
+        &cat(&[
+            &store(&int(0), &local(0)),
+            &store(&int(0), &local(1)),
+            &while_(
+                &lless(&local(0), &int(10)),
+                &cat(&[
+                    &[0x75],
+                    &local(0),
+                    &if_(&lequal(&local(0), &int(7)), &[0xA5]),
+                    &if_(&lequal(&binary(0x7B, &local(0), &int(1), &[0]), &int(0)), &[0x9F]),
+                    &else_(&binary(0x72, &local(1), &local(0), &local(1))),
+                ]),
+            ),
+            &ret(&local(1)),
+        ]),
+    );
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(integer(&mut aml, &mut host, "\\ODDS", vec![]), 9);
+}
+
+#[test]
+fn a_store_to_a_name_keeps_its_type() {
+    let body = cat(&[
+        &def_name("INT_", &int(0)),
+        &def_name("STR_", &string("")),
+        &def_name("BUF_", &buffer(&[0; 4])),
+        &method(
+            "SET_",
+            0,
+            &cat(&[
+                &store(&string("1F"), &name("INT_")),
+                &store(&int(0x41), &name("STR_")),
+                &store(&int(0x1122_3344_5566), &name("BUF_")),
+            ]),
+        ),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    eval(&mut aml, &mut host, "\\SET_", vec![]);
+    assert_eq!(eval(&mut aml, &mut host, "\\INT_", vec![]), Value::Integer(0x1F));
+    assert_eq!(
+        eval(&mut aml, &mut host, "\\STR_", vec![]),
+        Value::String("0000000000000041".into())
+    );
+    assert_eq!(
+        eval(&mut aml, &mut host, "\\BUF_", vec![]),
+        Value::Buffer(vec![0x66, 0x55, 0x44, 0x33])
+    );
+}
+
+#[test]
+fn index_refers_into_a_package_and_stores_through() {
+    let body = cat(&[
+        &def_name("PKG_", &package(&[int(1), int(2), string("three")])),
+        &method(
+            "POKE",
+            0,
+            &cat(&[
+                &store(&int(5), &cat(&[&[0x88], &name("PKG_"), &int(1), &[0]])),
+                &ret(&binary(
+                    0x72,
+                    &cat(&[&[0x83, 0x88], &name("PKG_"), &int(1), &[0]]),
+                    &cat(&[&[0x87], &name("PKG_")]),
+                    &[0],
+                )),
+            ]),
+        ),
+        &method("PAST", 0, &ret(&cat(&[&[0x88], &name("PKG_"), &int(3), &[0]]))),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(integer(&mut aml, &mut host, "\\POKE", vec![]), 5 + 3);
+    let pkg = eval(&mut aml, &mut host, "\\PKG_", vec![]);
+    assert_eq!(pkg.as_package().unwrap()[1], Value::Integer(5));
+    assert_eq!(aml.evaluate(&mut host, "\\PAST", vec![]), Err(Error::OutOfRange));
+}
+
+#[test]
+fn an_argument_passed_by_reference_is_stored_through() {
+    let body = cat(&[
+        &method("SET7", 1, &store(&int(7), &arg(0))),
+        &method(
+            "CALL",
+            0,
+            &cat(&[&store(&int(0), &local(0)), &name("SET7"), &[0x71], &local(0), &ret(&local(0))]),
+        ),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(integer(&mut aml, &mut host, "\\CALL", vec![]), 7);
+}
+
+#[test]
+fn a_buffer_field_over_an_argument_writes_the_argument() {
+    // The shape of every _OSC: Method (_OSC, 4) { CreateDWordField (Arg3, 0,
+    // CDW1); CDW1 |= 4; Return (Arg3) }.
+    let body = method(
+        "_OSC",
+        4,
+        &cat(&[
+            &[0x8A],
+            &arg(3),
+            &int(0),
+            &name("CDW1"),
+            &binary(0x7D, &name("CDW1"), &int(4), &name("CDW1")),
+            &ret(&arg(3)),
+        ]),
+    );
+    let (mut aml, mut host) = load(&body);
+    let caps = Value::Buffer(vec![1, 0, 0, 0, 0xAA, 0xBB]);
+    let args = vec![Value::Buffer(vec![0; 16]), Value::Integer(1), Value::Integer(2), caps];
+    assert_eq!(
+        eval(&mut aml, &mut host, "\\_OSC", args),
+        Value::Buffer(vec![5, 0, 0, 0, 0xAA, 0xBB])
+    );
+    // CDW1 was the call's, and went with it.
+    assert_eq!(aml.namespace().lookup("\\CDW1"), None);
+}
+
+#[test]
+fn a_methods_own_names_go_when_it_returns() {
+    let body = method("TEMP", 0, &cat(&[&def_name("TMP_", &int(3)), &ret(&name("TMP_"))]));
+    let (mut aml, mut host) = load(&body);
+    let before = aml.namespace().len();
+    assert_eq!(integer(&mut aml, &mut host, "\\TEMP", vec![]), 3);
+    assert_eq!(integer(&mut aml, &mut host, "\\TEMP", vec![]), 3, "a second call redefines it");
+    assert_eq!(aml.namespace().len(), before);
+}
+
+#[test]
+fn a_narrow_table_computes_in_32_bits() {
+    let body = method("WRAP", 0, &ret(&binary(0x72, &[0xFF], &int(2), &[0])));
+    let mut host = MockHost::default();
+    let mut aml = Interpreter::new();
+    aml.load_table(&mut host, &table(b"DSDT", 1, &body)).unwrap();
+    assert_eq!(integer(&mut aml, &mut host, "\\WRAP", vec![]), 1);
+}
+
+#[test]
+fn conversions_and_string_operators() {
+    let body = cat(&[
+        &method("CAT_", 0, &ret(&binary(0x73, &string("AB"), &string("CD"), &[0]))),
+        &method("HEX_", 0, &ret(&cat(&[&[0x98], &buffer(&[1, 0xAB]), &[0]]))),
+        &method("DEC_", 0, &ret(&cat(&[&[0x97], &int(1234), &[0]]))),
+        &method("MID_", 0, &ret(&cat(&[&[0x9E], &string("firmware"), &int(4), &int(3), &[0]]))),
+        &method("TOI_", 0, &ret(&cat(&[&[0x99], &string("0x1F"), &[0]]))),
+        &method("TOS_", 0, &ret(&cat(&[&[0x9C], &buffer(b"LID\0junk"), &[0xFF], &[0]]))),
+        &method("FSLB", 0, &ret(&cat(&[&[0x81], &int(0x90), &[0]]))),
+        &method("FSRB", 0, &ret(&cat(&[&[0x82], &int(0x90), &[0]]))),
+        &method(
+            "DIV_",
+            0,
+            &cat(&[
+                &[0x78],
+                &int(17),
+                &int(5),
+                &local(1),
+                &local(0),
+                &ret(&binary(0x77, &local(0), &int(10), &[0])),
+            ]),
+        ),
+        &method("DIVR", 0, &cat(&[&[0x78], &int(17), &int(5), &local(1), &[0], &ret(&local(1))])),
+        &method("BCD_", 0, &ret(&cat(&[&[0x5B, 0x29], &int(1234), &[0]]))),
+        &method("SHL_", 0, &ret(&binary(0x79, &int(1), &int(100), &[0]))),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(eval(&mut aml, &mut host, "\\CAT_", vec![]), Value::String("ABCD".into()));
+    assert_eq!(eval(&mut aml, &mut host, "\\HEX_", vec![]), Value::String("0x01,0xAB".into()));
+    assert_eq!(eval(&mut aml, &mut host, "\\DEC_", vec![]), Value::String("1234".into()));
+    assert_eq!(eval(&mut aml, &mut host, "\\MID_", vec![]), Value::String("war".into()));
+    assert_eq!(integer(&mut aml, &mut host, "\\TOI_", vec![]), 0x1F);
+    assert_eq!(eval(&mut aml, &mut host, "\\TOS_", vec![]), Value::String("LID".into()));
+    assert_eq!(integer(&mut aml, &mut host, "\\FSLB", vec![]), 8);
+    assert_eq!(integer(&mut aml, &mut host, "\\FSRB", vec![]), 5);
+    assert_eq!(integer(&mut aml, &mut host, "\\DIV_", vec![]), 30);
+    assert_eq!(integer(&mut aml, &mut host, "\\DIVR", vec![]), 2);
+    assert_eq!(integer(&mut aml, &mut host, "\\BCD_", vec![]), 0x1234);
+    assert_eq!(integer(&mut aml, &mut host, "\\SHL_", vec![]), 0, "a shift past the width is zero");
+}
+
+#[test]
+fn match_finds_the_first_element_meeting_both_tests() {
+    // Match (Package {1, 5, 9, 12}, MGT, 4, MLT, 10, 0) is index 1.
+    let pkg = package(&[int(1), int(5), int(9), int(12)]);
+    let body = cat(&[
+        &method("MAT_", 0, &ret(&cat(&[&[0x89], &pkg, &[5], &int(4), &[3], &int(10), &int(0)]))),
+        &method("NONE", 0, &ret(&cat(&[&[0x89], &pkg, &[1], &int(7), &[0], &int(0), &int(0)]))),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(integer(&mut aml, &mut host, "\\MAT_", vec![]), 1);
+    assert_eq!(integer(&mut aml, &mut host, "\\NONE", vec![]), u64::MAX);
+}
+
+#[test]
+fn osi_answers_for_windows_and_not_for_linux() {
+    let (mut aml, mut host) = load(&[]);
+    let osi = |aml: &mut Interpreter, host: &mut MockHost, s: &str| {
+        integer(aml, host, "\\_OSI", vec![Value::String(s.into())])
+    };
+    assert_eq!(osi(&mut aml, &mut host, "Windows 2015"), u64::MAX);
+    assert_eq!(osi(&mut aml, &mut host, "Linux"), 0);
+    assert_eq!(integer(&mut aml, &mut host, "\\_REV", vec![]), 2);
+}
+
+#[test]
+fn notify_is_recorded_for_the_caller() {
+    let body = scope(
+        "\\_SB",
+        &cat(&[
+            &device("LID0", &[]),
+            &method("_L0D", 0, &cat(&[&[0x86], &name("LID0"), &int(0x80)])),
+        ]),
+    );
+    let (mut aml, mut host) = load(&body);
+    eval(&mut aml, &mut host, "\\_SB._L0D", vec![]);
+    let lid = aml.namespace().lookup("\\_SB.LID0").unwrap();
+    let notes = aml.take_notifications();
+    assert_eq!(notes.len(), 1);
+    assert_eq!((notes[0].node, notes[0].value), (lid, 0x80));
+    assert!(aml.take_notifications().is_empty());
+}
+
+#[test]
+fn a_preserved_field_reads_before_it_writes() {
+    // OperationRegion (PORT, SystemIO, 0x80, 2)
+    // Field (PORT, ByteAcc, NoLock, Preserve) { LOW_, 4, HIGH, 4, NEXT, 8 }
+    let body = cat(&[
+        &region("PORT", 1, 0x80, 2),
+        &field("PORT", 0x01, &[(Some("LOW_"), 4), (Some("HIGH"), 4), (Some("NEXT"), 8)]),
+        &method("SETH", 0, &store(&int(0xA), &name("HIGH"))),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    host.poke(SpaceKey::Io, 0x80, 0x05, 1);
+    host.poke(SpaceKey::Io, 0x81, 0x77, 1);
+    eval(&mut aml, &mut host, "\\SETH", vec![]);
+    assert_eq!(
+        host.log,
+        vec![Access::Read(Space::SystemIo, 0x80, 8), Access::Write(Space::SystemIo, 0x80, 8, 0xA5)]
+    );
+    assert_eq!(integer(&mut aml, &mut host, "\\NEXT", vec![]), 0x77);
+    assert_eq!(integer(&mut aml, &mut host, "\\LOW_", vec![]), 0x5);
+}
+
+#[test]
+fn a_field_spanning_units_is_split_and_write_as_zeros_does_not_read() {
+    // DWordAcc, WriteAsZeros: a 16-bit field at bit 24 touches two DWORDs.
+    let body = cat(&[
+        &region("MMIO", 0, 0xFED0_0000, 8),
+        &field("MMIO", 0x03 | 2 << 5, &[(None, 24), (Some("SPAN"), 16)]),
+        &method("SETS", 0, &store(&int(0xBEEF), &name("SPAN"))),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    eval(&mut aml, &mut host, "\\SETS", vec![]);
+    assert_eq!(
+        host.log,
+        vec![
+            Access::Write(Space::SystemMemory, 0xFED0_0000, 32, 0xEF00_0000),
+            Access::Write(Space::SystemMemory, 0xFED0_0004, 32, 0x0000_00BE),
+        ]
+    );
+    host.log.clear();
+    assert_eq!(integer(&mut aml, &mut host, "\\SPAN", vec![]), 0xBEEF);
+    assert_eq!(host.log.len(), 2);
+}
+
+#[test]
+fn an_index_field_writes_its_index_then_its_data() {
+    // The CMOS shape: index at 0x70, data at 0x71.
+    let index_field = pkg(
+        &[0x5B, 0x86],
+        &cat(&[
+            &name("IDX_"),
+            &name("DAT_"),
+            &[0x01],
+            &[0x00],
+            &raw_length(0x10 * 8),
+            &name("CENT"),
+            &[8],
+        ]),
+    );
+    let body = cat(&[
+        &region("CMOS", 1, 0x70, 2),
+        &field("CMOS", 0x01, &[(Some("IDX_"), 8), (Some("DAT_"), 8)]),
+        &index_field,
+    ]);
+    let (mut aml, mut host) = load(&body);
+    host.poke(SpaceKey::Io, 0x71, 0x20, 1);
+    assert_eq!(integer(&mut aml, &mut host, "\\CENT", vec![]), 0x20);
+    assert_eq!(
+        host.log,
+        vec![Access::Write(Space::SystemIo, 0x70, 8, 0x10), Access::Read(Space::SystemIo, 0x71, 8)]
+    );
+}
+
+#[test]
+fn a_pci_config_region_is_addressed_by_adr_and_bbn() {
+    // \_SB.PCI0 (_BBN 0) . LPCB (_ADR 0x001F0000) with a config region at 0x40.
+    let lpc = device(
+        "LPCB",
+        &cat(&[
+            &def_name("_ADR", &int(0x001F_0000)),
+            &region("LPCR", 2, 0x40, 0x10),
+            &field("LPCR", 0x01, &[(Some("PIRA"), 8)]),
+        ]),
+    );
+    let body = scope(
+        "\\_SB",
+        &device(
+            "PCI0",
+            &cat(&[&def_name("_HID", &int(0x080A_D041)), &def_name("_BBN", &int(0)), &lpc]),
+        ),
+    );
+    let (mut aml, mut host) = load(&body);
+    host.poke(SpaceKey::Pci(0, 0, 0x1F, 0), 0x40, 0x0B, 1);
+    assert_eq!(integer(&mut aml, &mut host, "\\_SB.PCI0.LPCB.PIRA", vec![]), 0x0B);
+    let addr = PciAddress { segment: 0, bus: 0, device: 0x1F, function: 0 };
+    assert_eq!(host.log, vec![Access::Read(Space::PciConfig(addr), 0x40, 8)]);
+}
+
+#[test]
+fn a_refused_access_fails_the_method_that_made_it() {
+    let body = cat(&[
+        &region("RAM_", 0, 0x10_0000, 4),
+        &field("RAM_", 0x03, &[(Some("WORD"), 32)]),
+        &method("PEEK", 0, &ret(&name("WORD"))),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    host.forbidden = Some(0..0x4000_0000);
+    assert_eq!(aml.evaluate(&mut host, "\\PEEK", vec![]), Err(Error::Refused));
+}
+
+#[test]
+fn prt_routes_through_links_defined_after_it() {
+    // The q35 shape: _PRT returns one table under \_PIC(1) and another under
+    // \_PIC(0), naming link devices that come later in the table.
+    let apic = package(&[
+        package(&[int(0x0001_FFFF), int(0), int(0), int(16)]),
+        package(&[int(0x0002_FFFF), int(1), name("LNKB"), int(0)]),
+    ]);
+    let pic = package(&[package(&[int(0x0001_FFFF), int(0), name("LNKB"), int(0)])]);
+    let pci = device(
+        "PCI0",
+        &cat(&[
+            &def_name("PRTA", &apic),
+            &def_name("PRTP", &pic),
+            &method(
+                "_PRT",
+                0,
+                &cat(&[&if_(&name("\\PICM"), &ret(&name("PRTA"))), &ret(&name("PRTP"))]),
+            ),
+        ]),
+    );
+    // IRQ (Level, ActiveLow, Shared) {11}, End.
+    let crs = buffer(&[0x23, 0x00, 0x08, 0x18, 0x79, 0x00]);
+    let link =
+        device("LNKB", &cat(&[&def_name("_HID", &int(0x0F0C_D041)), &def_name("_CRS", &crs)]));
+    let body = cat(&[
+        &def_name("PICM", &int(0)),
+        &method("_PIC", 1, &store(&arg(0), &name("PICM"))),
+        &scope("\\_SB", &cat(&[&pci, &link])),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    let pci = aml.namespace().lookup("\\_SB.PCI0").unwrap();
+    let link = aml.namespace().lookup("\\_SB.LNKB").unwrap();
+
+    let legacy = aml.routing(&mut host, pci).unwrap();
+    assert_eq!(
+        legacy,
+        vec![Route { device: 1, pin: 0, source: RouteSource::Link { node: link, index: 0 } }]
+    );
+
+    aml.set_apic_mode(&mut host).unwrap();
+    let routes = aml.routing(&mut host, pci).unwrap();
+    assert_eq!(
+        routes,
+        vec![
+            Route { device: 1, pin: 0, source: RouteSource::Gsi(16) },
+            Route { device: 2, pin: 1, source: RouteSource::Link { node: link, index: 0 } },
+        ]
+    );
+    assert_eq!(aml.link_interrupt(&mut host, link), Ok(Some(11)));
+    assert_eq!(aml.find_devices(&mut host, "PNP0C0F"), vec![link]);
+}
+
+#[test]
+fn initialize_follows_sta() {
+    // _INI counts into \INIS; ABSN is absent and not functioning, so its
+    // child's _INI never runs; FUNC is absent but functioning, so its child's
+    // does.
+    let ini = method("_INI", 0, &[0x75, b'\\', b'I', b'N', b'I', b'S']);
+    let sta = |v: u64| method("_STA", 0, &ret(&int(v)));
+    let body = cat(&[
+        &def_name("INIS", &int(0)),
+        &scope(
+            "\\_SB",
+            &cat(&[
+                &ini,
+                &device("PRES", &ini),
+                &device("ABSN", &cat(&[&sta(0), &ini, &device("KID_", &ini)])),
+                &device("FUNC", &cat(&[&sta(0x08), &ini, &device("KID_", &ini)])),
+            ]),
+        ),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert!(aml.initialize(&mut host).is_empty());
+    // \_SB, PRES and FUNC.KID_.
+    assert_eq!(integer(&mut aml, &mut host, "\\INIS", vec![]), 3);
+}
+
+#[test]
+fn reg_is_run_for_each_scope_with_a_region_in_the_space() {
+    let ec = device(
+        "EC0_",
+        &cat(&[
+            &def_name("ECOK", &int(0)),
+            &region("ERAM", 3, 0, 0xFF),
+            &field("ERAM", 0x01, &[(None, 0x30 * 8), (Some("BATS"), 8)]),
+            &method("_REG", 2, &if_(&lequal(&arg(0), &int(3)), &store(&arg(1), &name("ECOK")))),
+            &method("BATT", 0, &cat(&[&if_(&name("ECOK"), &ret(&name("BATS"))), &ret(&[0xFF])])),
+        ]),
+    );
+    let (mut aml, mut host) = load(&scope("\\_SB", &ec));
+    host.poke(SpaceKey::Ec, 0x30, 0x1F, 1);
+    assert_eq!(integer(&mut aml, &mut host, "\\_SB.EC0.BATT", vec![]), u64::MAX);
+    assert!(aml.connect_space(&mut host, 3).is_empty());
+    assert_eq!(integer(&mut aml, &mut host, "\\_SB.EC0.BATT", vec![]), 0x1F);
+    assert_eq!(host.log, vec![Access::Read(Space::EmbeddedControl, 0x30, 8)]);
+}
+
+#[test]
+fn battery_and_thermal_zone_readings() {
+    let bif = package(&[
+        int(0),
+        int(50_000),
+        int(45_000),
+        int(1),
+        int(11_400),
+        int(5000),
+        int(1000),
+        int(100),
+        int(100),
+        string("5B10W13930"),
+        string("1234"),
+        string("LION"),
+        string("Sunwoda"),
+    ]);
+    let bst = package(&[int(1), int(9_000), int(30_000), int(0xFFFF_FFFF)]);
+    let bat = device(
+        "BAT0",
+        &cat(&[
+            &def_name("_HID", &int(0x0A0C_D041)),
+            &method("_BIF", 0, &ret(&bif)),
+            &method("_BST", 0, &ret(&bst)),
+        ]),
+    );
+    let tz = pkg(
+        &[0x5B, 0x85],
+        &cat(&[&name("THM0"), &method("_TMP", 0, &ret(&int(3182))), &def_name("_CRT", &int(3732))]),
+    );
+    let (mut aml, mut host) = load(&cat(&[&scope("\\_SB", &bat), &scope("\\_TZ", &tz)]));
+
+    let bat = aml.namespace().lookup("\\_SB.BAT0").unwrap();
+    assert_eq!(aml.find_devices(&mut host, "PNP0C0A"), vec![bat]);
+    let info = aml.battery(&mut host, bat).unwrap();
+    assert!(info.milliwatts && info.rechargeable);
+    assert_eq!((info.design_capacity, info.last_full_capacity), (Some(50_000), Some(45_000)));
+    assert_eq!(info.model, "5B10W13930");
+    let state = aml.battery_state(&mut host, bat).unwrap();
+    assert!(state.discharging && !state.charging);
+    assert_eq!((state.rate, state.remaining, state.voltage), (Some(9_000), Some(30_000), None));
+
+    let zone = aml.namespace().lookup("\\_TZ.THM0").unwrap();
+    assert_eq!(aml.temperature(&mut host, zone), Ok(3182));
+    assert_eq!(aml.thermal_point(&mut host, zone, "_CRT"), Ok(Some(3732)));
+    assert_eq!(aml.thermal_point(&mut host, zone, "_HOT"), Ok(None));
+}
+
+#[test]
+fn power_methods_run_when_present() {
+    let dev = device(
+        "DEV0",
+        &cat(&[&def_name("PSTA", &int(0)), &method("_PS3", 0, &store(&int(3), &name("PSTA")))]),
+    );
+    let (mut aml, mut host) = load(&scope("\\_SB", &dev));
+    let node = aml.namespace().lookup("\\_SB.DEV0").unwrap();
+    assert_eq!(aml.set_power(&mut host, node, true), Ok(true));
+    assert_eq!(aml.set_power(&mut host, node, false), Ok(false));
+    assert_eq!(integer(&mut aml, &mut host, "\\_SB.DEV0.PSTA", vec![]), 3);
+}
+
+#[test]
+fn resources_decode_from_crs() {
+    let crs =
+        buffer(&[0x47, 0x01, 0xF8, 0x03, 0xF8, 0x03, 0x01, 0x08, 0x22, 0x10, 0x00, 0x79, 0x00]);
+    let (mut aml, mut host) = load(&scope("\\_SB", &device("COM1", &def_name("_CRS", &crs))));
+    let com = aml.namespace().lookup("\\_SB.COM1").unwrap();
+    let r = aml.resources(&mut host, com).unwrap().unwrap();
+    assert_eq!(r[0], Resource::Io { min: 0x3F8, max: 0x3F8, align: 1, len: 8 });
+    let sb = aml.namespace().lookup("\\_SB").unwrap();
+    assert_eq!(aml.resources(&mut host, sb), Ok(None));
+}
+
+#[test]
+fn sleep_stall_timer_and_debug_reach_the_host() {
+    let body = method(
+        "WAIT",
+        0,
+        &cat(&[
+            &[0x5B, 0x22],
+            &int(20),
+            &[0x5B, 0x21],
+            &int(50),
+            &store(&string("hello"), &[0x5B, 0x31]),
+            &ret(&[0x5B, 0x33]),
+        ]),
+    );
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(integer(&mut aml, &mut host, "\\WAIT", vec![]), 42);
+    assert_eq!((host.slept_ms, host.stalled_us), (20, 50));
+    assert_eq!(host.debug, vec![Value::String("hello".into())]);
+}
+
+#[test]
+fn sleeping_states_and_their_methods() {
+    // QEMU's shape: \_S3_ with one value and a reserved pair, \_S4_ with two,
+    // no \_S1_, and a _PTS and _WAK that record the state they were given.
+    let body = cat(&[
+        &def_name("_S3_", &package(&[int(1), int(0), int(0), int(0)])),
+        &def_name("_S4_", &package(&[int(2), int(3)])),
+        &def_name("SLPS", &int(0)),
+        &def_name("WAKS", &int(0)),
+        &method("_PTS", 1, &store(&arg(0), &name("SLPS"))),
+        &method(
+            "_WAK",
+            1,
+            &cat(&[&store(&arg(0), &name("WAKS")), &ret(&package(&[int(0), int(0)]))]),
+        ),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(aml.sleep_type(&mut host, 3), Ok(Some((1, 0))));
+    assert_eq!(aml.sleep_type(&mut host, 4), Ok(Some((2, 3))));
+    assert_eq!(aml.sleep_type(&mut host, 1), Ok(None));
+    assert_eq!(aml.sleep_type(&mut host, 9), Err(Error::InvalidName));
+
+    aml.prepare_to_sleep(&mut host, 3).unwrap();
+    aml.wake(&mut host, 3).unwrap();
+    assert_eq!(integer(&mut aml, &mut host, "\\SLPS", vec![]), 3);
+    assert_eq!(integer(&mut aml, &mut host, "\\WAKS", vec![]), 3);
+
+    // A machine without either method has nothing to run.
+    let (mut bare, mut host) = load(&def_name("_S3_", &package(&[int(5)])));
+    assert_eq!(bare.sleep_type(&mut host, 3), Ok(Some((5, 5))));
+    assert_eq!(bare.prepare_to_sleep(&mut host, 3), Ok(()));
+    assert_eq!(bare.wake(&mut host, 3), Ok(()));
+}
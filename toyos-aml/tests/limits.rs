@@ -0,0 +1,264 @@
+//! What the interpreter promises for AML that is wrong, on purpose or not: it
+//! stops at its budget, its depth and its caps with an error rather than
+//! hanging, overflowing the stack or exhausting the heap, and no table it is
+//! handed panics it.
+
+// Each test file uses a different part of the assembler.
+#[allow(dead_code)]
+mod common;
+
+use common::*;
+use toyos_aml::{Error, Interpreter, Value, MAX_DEPTH};
+
+/// The kernel's stack, which every evaluation runs on.
+const KERNEL_STACK: usize = 128 * 1024;
+
+static FIRECRACKER: &[u8] = include_bytes!("fixtures/firecracker-dsdt.aml");
+
+fn load(body: &[u8]) -> (Interpreter, MockHost) {
+    let mut host = MockHost::default();
+    let mut aml = Interpreter::new();
+    aml.load_table(&mut host, &dsdt(body)).expect("the table loads");
+    (aml, host)
+}
+
+/// Runs `f` on a thread with the kernel's stack, so an interpreter that
+/// nests deeper than [`MAX_DEPTH`] allows for overflows here rather than in
+/// the kernel. The measure holds at the root workspace's `[profile.dev]`
+/// `opt-level = 2`, which is the kernel's `toyos` profile's too; an
+/// unoptimised build spends several times the stack per level and is no
+/// evidence either way.
+fn on_kernel_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new()
+        .stack_size(KERNEL_STACK)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .expect("no overflow")
+}
+
+/// A deterministic xorshift, so a failing mutation is the same one next run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[test]
+fn an_endless_loop_runs_out_of_budget() {
+    let (mut aml, mut host) = load(&method("SPIN", 0, &while_(&[0x01], &[])));
+    assert_eq!(aml.evaluate(&mut host, "\\SPIN", vec![]), Err(Error::Budget));
+    // The budget is per evaluation, not spent for good.
+    let (mut aml2, _) = load(&method("ONE_", 0, &ret(&[0x01])));
+    assert_eq!(aml2.evaluate(&mut host, "\\ONE_", vec![]), Ok(Value::Integer(1)));
+    assert_eq!(aml.evaluate(&mut host, "\\SPIN", vec![]), Err(Error::Budget));
+}
+
+#[test]
+fn an_endless_loop_at_load_runs_out_of_budget() {
+    let mut host = MockHost::default();
+    let mut aml = Interpreter::new();
+    assert_eq!(aml.load_table(&mut host, &dsdt(&while_(&[0x01], &[]))), Err(Error::Budget));
+}
+
+#[test]
+fn unbounded_recursion_is_too_deep_on_the_kernels_stack() {
+    let result = on_kernel_stack(|| {
+        let (mut aml, mut host) = load(&method("RECU", 0, &ret(&name("RECU"))));
+        aml.evaluate(&mut host, "\\RECU", vec![])
+    });
+    assert_eq!(result, Err(Error::TooDeep));
+}
+
+#[test]
+fn deeply_nested_terms_are_too_deep_on_the_kernels_stack() {
+    // Add (Add (Add (... 1, 1), 1), 1) two hundred deep.
+    let mut expr = int(1);
+    for _ in 0..200 {
+        expr = binary(0x72, &expr, &int(1), &[0]);
+    }
+    let result = on_kernel_stack(move || {
+        let (mut aml, mut host) = load(&method("DEEP", 0, &ret(&expr)));
+        aml.evaluate(&mut host, "\\DEEP", vec![])
+    });
+    assert_eq!(result, Err(Error::TooDeep));
+}
+
+#[test]
+fn nesting_just_inside_the_limit_fits_the_kernels_stack() {
+    // Ifs inside Ifs around a method that recurses: as deep as MAX_DEPTH
+    // lets anything get, mixing the kinds of level.
+    let result = on_kernel_stack(|| {
+        let mut body = ret(&binary(
+            0x72,
+            &cat(&[&name("NEST"), &binary(0x74, &arg(0), &int(1), &[0])]),
+            &int(1),
+            &[0],
+        ));
+        for _ in 0..4 {
+            body = if_(&[0x01], &body);
+        }
+        let body = cat(&[&if_(&lequal(&arg(0), &int(0)), &ret(&int(0))), &body]);
+        let (mut aml, mut host) = load(&method("NEST", 1, &body));
+        let mut deepest = 0;
+        for n in 0..MAX_DEPTH as u64 {
+            match aml.evaluate(&mut host, "\\NEST", vec![Value::Integer(n)]) {
+                Ok(v) => {
+                    assert_eq!(v, Value::Integer(n));
+                    deepest = n;
+                }
+                Err(e) => {
+                    assert_eq!(e, Error::TooDeep);
+                    break;
+                }
+            }
+        }
+        deepest
+    });
+    assert!(result > 0, "some recursion fits");
+}
+
+#[test]
+fn a_huge_buffer_or_package_is_refused_not_allocated() {
+    let body = cat(&[
+        &method("BUFF", 0, &ret(&pkg(&[0x11], &int(0xFFFF_FFFF)))),
+        &method("VPKG", 0, &ret(&pkg(&[0x13], &int(0xFFFF_FFFF)))),
+        &method(
+            "GROW",
+            0,
+            &cat(&[
+                &store(&string("xxxxxxxxxxxxxxxx"), &local(0)),
+                &while_(&[0x01], &binary(0x73, &local(0), &local(0), &local(0))),
+            ]),
+        ),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(aml.evaluate(&mut host, "\\BUFF", vec![]), Err(Error::TooLarge));
+    assert_eq!(aml.evaluate(&mut host, "\\VPKG", vec![]), Err(Error::TooLarge));
+    assert_eq!(aml.evaluate(&mut host, "\\GROW", vec![]), Err(Error::TooLarge));
+}
+
+#[test]
+fn a_length_past_its_container_is_truncated() {
+    // A method whose PkgLength claims more than the table holds.
+    let mut body = method("LONG", 0, &ret(&int(1)));
+    body[1] = 0x3F;
+    let mut host = MockHost::default();
+    let mut aml = Interpreter::new();
+    assert_eq!(aml.load_table(&mut host, &dsdt(&body)), Err(Error::Truncated));
+    // A header that claims more than the slice.
+    let mut table = dsdt(&[]);
+    table[4] = 0xFF;
+    assert_eq!(Interpreter::new().load_table(&mut host, &table), Err(Error::Truncated));
+}
+
+#[test]
+fn division_by_zero_and_bad_indices_are_errors() {
+    let body = cat(&[
+        &method("DIV0", 0, &ret(&cat(&[&[0x78], &int(1), &int(0), &[0], &[0]]))),
+        &method("MOD0", 0, &ret(&binary(0x85, &int(1), &int(0), &[0]))),
+        &method("IDX_", 0, &ret(&cat(&[&[0x83, 0x88], &buffer(&[1, 2]), &int(2), &[0]]))),
+        &method("ARGS", 2, &ret(&arg(1))),
+        &method("CALL", 0, &ret(&cat(&[&name("ARGS"), &int(1)]))),
+        &method("FATL", 0, &cat(&[&[0x5B, 0x32, 0x01], &0u32.to_le_bytes(), &int(0)])),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(aml.evaluate(&mut host, "\\DIV0", vec![]), Err(Error::DivideByZero));
+    assert_eq!(aml.evaluate(&mut host, "\\MOD0", vec![]), Err(Error::DivideByZero));
+    assert_eq!(aml.evaluate(&mut host, "\\IDX_", vec![]), Err(Error::OutOfRange));
+    assert_eq!(aml.evaluate(&mut host, "\\ARGS", vec![Value::Integer(1)]), Err(Error::Arguments));
+    assert!(aml.evaluate(&mut host, "\\CALL", vec![]).is_err());
+    assert_eq!(aml.evaluate(&mut host, "\\FATL", vec![]), Err(Error::Fatal));
+}
+
+#[test]
+fn a_field_past_its_region_never_reaches_the_host() {
+    let body = cat(&[
+        &region("SMAL", 1, 0x80, 1),
+        &field("SMAL", 0x01, &[(None, 8), (Some("PAST"), 8)]),
+        &method("READ", 0, &ret(&name("PAST"))),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    assert_eq!(aml.evaluate(&mut host, "\\READ", vec![]), Err(Error::OutOfRange));
+    assert!(host.log.is_empty());
+}
+
+#[test]
+fn a_stall_is_clamped_and_a_failed_method_leaves_the_namespace_as_it_was() {
+    let body = cat(&[
+        &method("STAL", 0, &cat(&[&[0x5B, 0x21], &int(1_000_000)])),
+        &method("FAIL", 0, &cat(&[&def_name("TMP_", &int(1)), &ret(&name("NONE"))])),
+    ]);
+    let (mut aml, mut host) = load(&body);
+    aml.evaluate(&mut host, "\\STAL", vec![]).unwrap();
+    assert!(host.stalled_us <= 100);
+    let before = aml.namespace().len();
+    assert_eq!(aml.evaluate(&mut host, "\\FAIL", vec![]), Err(Error::NotFound));
+    assert_eq!(aml.namespace().len(), before);
+    assert_eq!(aml.evaluate(&mut host, "\\FAIL", vec![]), Err(Error::NotFound));
+}
+
+/// Loads `table` and, if it loads, evaluates every method in it with integer
+/// arguments: whatever happens must be a `Result`.
+fn exercise(table: &[u8]) {
+    let mut host = MockHost::default();
+    let mut aml = Interpreter::new();
+    if aml.load_table(&mut host, table).is_err() {
+        return;
+    }
+    let ns = aml.namespace();
+    let methods: Vec<_> = ns
+        .descendants(ns.root())
+        .into_iter()
+        .filter(|&n| matches!(ns.object(n), Some(toyos_aml::Object::Method(_))))
+        .collect();
+    for node in methods {
+        let args = (0..7).map(Value::Integer).collect();
+        let _ = aml.evaluate_node(&mut host, node, args);
+    }
+}
+
+#[test]
+fn every_truncation_of_a_real_table_is_survived() {
+    on_kernel_stack(|| {
+        for len in (0..FIRECRACKER.len()).step_by(7) {
+            let mut table = FIRECRACKER[..len].to_vec();
+            if table.len() >= 8 {
+                table[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+            }
+            exercise(&table);
+        }
+    });
+}
+
+#[test]
+fn random_mutations_of_a_real_table_are_survived() {
+    on_kernel_stack(|| {
+        let mut rng = Rng(0x5EED_AC91);
+        for _ in 0..400 {
+            let mut table = FIRECRACKER.to_vec();
+            for _ in 0..1 + rng.next() % 8 {
+                let at = 36 + (rng.next() as usize) % (table.len() - 36);
+                table[at] = rng.next() as u8;
+            }
+            exercise(&table);
+        }
+    });
+}
+
+#[test]
+fn random_bytes_are_survived() {
+    on_kernel_stack(|| {
+        let mut rng = Rng(0xB16B_00B5);
+        for _ in 0..400 {
+            let len = (rng.next() % 256) as usize;
+            let body: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+            exercise(&dsdt(&body));
+        }
+    });
+}
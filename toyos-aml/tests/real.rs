@@ -0,0 +1,186 @@
+//! The positive control: the interpreter agrees with a real DSDT.
+//!
+//! Every other test in this crate hands the interpreter AML assembled by
+//! `common`, and an interpreter that agreed with its own assembler's mistakes
+//! would pass all of them. The fixture is the DSDT of a Firecracker microVM,
+//! as that VMM generates it, copied from `/sys/firmware/acpi/tables/DSDT` in
+//! the microVM this crate was written in: iASL-shaped output from a generator
+//! that shares nothing with `common`. It is small — a PCI root with 32 hotplug
+//! slots, a serial port, a PS/2 controller and a generic event device — but
+//! every device in it is evaluated below and every method in it is run.
+//!
+//! QEMU's q35 and the T14's tables are the ones the kernel meets at boot and
+//! are not here yet: nobody has taken an `acpidump -b` of either into the
+//! tree. Until someone does, q35's are covered where they are generated:
+//! `aml_q35_tables` in `tests/toyos.rs` boots q35 with S3 on and holds the
+//! kernel's survey of the namespace, `\_S3_` and `_PRT` to what QEMU's tables
+//! must answer. The T14's battery `_BST`/`_BIF` and thermal zones have no
+//! real-table coverage at all. `interp.rs` runs assembled ones, and the first
+//! dump of the T14 belongs here with a test for each. Refreshing this file's
+//! fixture moves the addresses below with whatever Firecracker version wrote it.
+
+// Each test file uses a different part of the assembler.
+#[allow(dead_code)]
+mod common;
+
+use common::MockHost;
+use toyos_aml::resource::AddressKind;
+use toyos_aml::{Interpreter, Object, Resource, Route, RouteSource, Value};
+
+const DSDT: &[u8] = include_bytes!("fixtures/firecracker-dsdt.aml");
+
+/// The PCI Firmware Specification's `_DSM` UUID, E5C937D0-3553-4D7A-9117-
+/// EA4D19C3434D, in the byte order `ToUUID` stores it.
+const PCI_DSM: [u8; 16] = [
+    0xD0, 0x37, 0xC9, 0xE5, 0x53, 0x35, 0x7A, 0x4D, 0x91, 0x17, 0xEA, 0x4D, 0x19, 0xC3, 0x43, 0x4D,
+];
+
+fn load() -> (Interpreter, MockHost) {
+    let mut host = MockHost::default();
+    let mut aml = Interpreter::new();
+    aml.load_table(&mut host, DSDT).expect("Firecracker's own DSDT");
+    assert!(host.log.is_empty(), "loading touches no hardware");
+    (aml, host)
+}
+
+#[test]
+fn every_device_is_found_by_its_ids() {
+    let (mut aml, mut host) = load();
+    let path = |aml: &Interpreter, id| aml.namespace().lookup(id).unwrap();
+    for (id, at) in [
+        ("PNP0A08", "\\_SB.PC00"),
+        // PC00's `_CID`.
+        ("PNP0A03", "\\_SB.PC00"),
+        ("PNP0501", "\\_SB.COM1"),
+        ("PNP0303", "\\_SB.PS2"),
+        ("ACPI0013", "\\_SB.GED"),
+        ("VMGENCTR", "\\_SB.VGEN"),
+        ("AMZNC10C", "\\_SB.VCLK"),
+    ] {
+        assert_eq!(aml.find_devices(&mut host, id), vec![path(&aml, at)], "{id}");
+    }
+    assert!(aml.initialize(&mut host).is_empty());
+    assert!(host.log.is_empty());
+}
+
+#[test]
+fn crs_decodes_to_the_ranges_firecracker_reserves() {
+    let (mut aml, mut host) = load();
+    let mut crs = |path: &str| {
+        let node = aml.namespace().lookup(path).unwrap();
+        aml.resources(&mut host, node).unwrap().expect("a _CRS")
+    };
+
+    assert_eq!(
+        crs("\\_SB.COM1"),
+        vec![
+            Resource::Irq { interrupts: vec![4], edge: true, active_low: false, shared: false },
+            Resource::Io { min: 0x3F8, max: 0x3F8, align: 1, len: 8 },
+        ]
+    );
+    assert_eq!(
+        crs("\\_SB.PS2")[..2],
+        [
+            Resource::Io { min: 0x60, max: 0x60, align: 1, len: 1 },
+            Resource::Io { min: 0x64, max: 0x64, align: 1, len: 1 }
+        ]
+    );
+
+    let root = crs("\\_SB.PC00");
+    assert_eq!(root.len(), 7);
+    assert_eq!(
+        root[0],
+        Resource::Address { kind: AddressKind::BusNumber, min: 0, max: 0, translation: 0, len: 1 }
+    );
+    // Mechanism #1's ports, then the ECAM window.
+    assert_eq!(root[1], Resource::Io { min: 0xCF8, max: 0xCF8, align: 1, len: 8 });
+    assert_eq!(root[2], Resource::Memory { base: 0xEEC0_0000, len: 0x10_0000, writable: true });
+    // The 64-bit window, from a QWordMemory.
+    assert_eq!(
+        root[4],
+        Resource::Address {
+            kind: AddressKind::Memory,
+            min: 1 << 38,
+            max: (1 << 39) - 1,
+            translation: 0,
+            len: 1 << 38
+        }
+    );
+}
+
+#[test]
+fn prt_routes_every_slot() {
+    let (mut aml, mut host) = load();
+    aml.set_apic_mode(&mut host).unwrap();
+    let pci = aml.namespace().lookup("\\_SB.PC00").unwrap();
+    let routes = aml.routing(&mut host, pci).unwrap();
+    assert_eq!(routes.len(), 32);
+    for (slot, route) in routes.iter().enumerate() {
+        assert_eq!(*route, Route { device: slot as u16, pin: 0, source: RouteSource::Gsi(0) });
+    }
+}
+
+/// Firecracker has no sleep states, batteries or thermal zones, so this table
+/// can only say that the kernel's queries for them come back empty on a real
+/// namespace rather than failing. The answers with something in them are owed
+/// to the q35 and T14 dumps.
+#[test]
+fn sleep_battery_and_thermal_queries_find_nothing() {
+    let (mut aml, mut host) = load();
+    for state in 0..=5 {
+        assert_eq!(aml.sleep_type(&mut host, state), Ok(None), "S{state}");
+    }
+    assert_eq!(aml.find_devices(&mut host, "PNP0C0A"), vec![]);
+    let root = aml.namespace().root();
+    assert!(!aml
+        .namespace()
+        .descendants(root)
+        .into_iter()
+        .any(|n| matches!(aml.namespace().object(n), Some(Object::ThermalZone))));
+}
+
+#[test]
+fn dsm_answers_only_for_its_uuid() {
+    let (mut aml, mut host) = load();
+    let dsm = |uuid: [u8; 16]| {
+        vec![
+            Value::Buffer(uuid.to_vec()),
+            Value::Integer(2),
+            Value::Integer(0),
+            Value::Package(vec![]),
+        ]
+    };
+    assert_eq!(
+        aml.evaluate(&mut host, "\\_SB.PC00._DSM", dsm(PCI_DSM)),
+        Ok(Value::Buffer(vec![0x21]))
+    );
+    assert_eq!(
+        aml.evaluate(&mut host, "\\_SB.PC00._DSM", dsm([0; 16])),
+        Ok(Value::Buffer(vec![0]))
+    );
+}
+
+#[test]
+fn hotplug_and_event_methods_notify() {
+    let (mut aml, mut host) = load();
+    // DVNT (Arg0 = slots, Arg1 = notify value): slots 0 and 2, device check.
+    aml.evaluate(&mut host, "\\_SB.PC00.DVNT", vec![Value::Integer(0b101), Value::Integer(1)])
+        .unwrap();
+    let notes = aml.take_notifications();
+    let got: Vec<_> = notes.iter().map(|n| (aml.namespace().path(n.node), n.value)).collect();
+    assert_eq!(got, [("\\_SB_.PC00.S000".to_string(), 1), ("\\_SB_.PC00.S002".to_string(), 1)]);
+
+    // The GED's interrupt 5 is VMGenID's.
+    aml.evaluate(&mut host, "\\_SB.GED._EVT", vec![Value::Integer(5)]).unwrap();
+    let vgen = aml.namespace().lookup("\\_SB.VGEN").unwrap();
+    let notes = aml.take_notifications();
+    assert_eq!(notes.iter().map(|n| (n.node, n.value)).collect::<Vec<_>>(), [(vgen, 0x80)]);
+
+    // A slot's _EJ0 calls into a hotplug controller this table does not
+    // define: an error in the method, not in the interpreter.
+    assert!(aml.evaluate(&mut host, "\\_SB.PC00.S001._EJ0", vec![Value::Integer(1)]).is_err());
+    assert_eq!(
+        aml.evaluate(&mut host, "\\_SB.PC00.S001._ADR", vec![]),
+        Ok(Value::Integer(0x1_0000))
+    );
+}
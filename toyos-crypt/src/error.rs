@@ -0,0 +1,70 @@
+use core::fmt;
+
+/// Why a header, a slot or a device operation was refused.
+///
+/// Exhaustive on purpose, as in `toyos-ext4`: the kernel maps each arm to what
+/// it tells the person at the prompt, and a new arm should be a compile error
+/// there rather than a silent "something went wrong".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Block 0 does not start with [`MAGIC`](crate::MAGIC): this is not an
+    /// encrypted volume at all. The answer for every plain disk, so it is not
+    /// an error anyone reports.
+    NotEncrypted,
+    /// The magic is there and the version is not one this crate writes.
+    UnsupportedVersion,
+    /// The header's checksum does not match its contents: torn by a write
+    /// that did not finish, or damaged since.
+    BadChecksum,
+    /// A field the header names cannot be right: a cipher this crate does not
+    /// have, a data area that starts past the end of the device, a slot state
+    /// that is neither empty nor active.
+    Corrupt,
+    /// A slot's scrypt parameters are outside what this crate will run.
+    BadParams,
+    /// No active slot opens with this passphrase.
+    WrongPassphrase,
+    /// The key offered to [`Header::add_slot`](crate::Header::add_slot) is
+    /// not the one this header's digest was made from.
+    WrongKey,
+    /// Every slot is in use.
+    NoFreeSlot,
+    /// The slot named is empty or does not exist.
+    NoSuchSlot,
+    /// Removing this slot would leave the volume with no way in.
+    LastSlot,
+    /// The passphrase is empty. Too long is not reachable: a
+    /// [`Passphrase`](crate::Passphrase) refuses the key past
+    /// [`MAX_KEYS`](crate::MAX_KEYS) when it is pressed.
+    BadPassphrase,
+    /// The derivation's table could not be allocated.
+    OutOfMemory,
+    /// The device refused a transfer.
+    Device,
+}
+
+impl Error {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Error::NotEncrypted => "not an encrypted volume",
+            Error::UnsupportedVersion => "unsupported encrypted-volume version",
+            Error::BadChecksum => "encrypted-volume header checksum mismatch",
+            Error::Corrupt => "corrupt encrypted-volume header",
+            Error::BadParams => "key slot names key-derivation parameters out of range",
+            Error::WrongPassphrase => "no key slot opens with that passphrase",
+            Error::WrongKey => "that is not this volume's key",
+            Error::NoFreeSlot => "every key slot is in use",
+            Error::NoSuchSlot => "no such key slot",
+            Error::LastSlot => "refusing to remove the last key slot",
+            Error::BadPassphrase => "passphrase is empty",
+            Error::OutOfMemory => "out of memory for key derivation",
+            Error::Device => "device I/O error",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,325 @@
+//! The header at block 0, and the key slots in it.
+//!
+//! The data is encrypted under a random 512-bit master key that never
+//! changes. Each slot holds that key wrapped under one passphrase, so a new
+//! passphrase is a new slot and an old one is a slot cleared — one block
+//! rewritten, and not a byte of the data touched. That is the whole reason
+//! for the indirection: re-encrypting a 244 GB namespace to change a
+//! passphrase is an afternoon with the machine unusable and a power cut away
+//! from half a disk under each key.
+//!
+//! # Layout
+//!
+//! All integers little-endian. Everything past the last slot and before the
+//! checksum is zero and reserved.
+//!
+//! | offset | size | field |
+//! |---|---|---|
+//! | 0 | 16 | [`MAGIC`] |
+//! | 16 | 4 | version, [`VERSION`] |
+//! | 20 | 4 | cipher, 1 = AES-256-XTS with the block number as tweak |
+//! | 24 | 8 | first block of the data area |
+//! | 32 | 4 | PBKDF2 rounds for the master-key digest |
+//! | 64 | 32 | digest salt |
+//! | 96 | 32 | digest: PBKDF2-HMAC-SHA-256 of the master key |
+//! | 128 | 128 × [`SLOTS`] | key slots |
+//! | 4064 | 32 | SHA-256 of bytes 0..4064 |
+//!
+//! A slot is: state (`u32`, 0 empty or 1 active), `log_n` (`u8`, then three
+//! zero bytes), `r` and `p` (`u32` each), a 32-byte salt, and the 64-byte
+//! master key XORed with 64 bytes of scrypt output.
+//!
+//! The XOR is a one-time pad and is used as one: every slot write draws a
+//! fresh salt, so no two wraps ever share a pad, and the pad is as strong as
+//! the derivation behind it — which is the only thing a slot could ever be.
+//!
+//! The checksum finds a torn or damaged header and is not a MAC. Anybody who
+//! can write the disk can rewrite it; what they cannot do is make a slot open
+//! without a passphrase, because the digest is checked against the unwrapped
+//! key and the key is what decrypts the data.
+
+use crate::passphrase::Passphrase;
+use crate::scrypt::{scrypt, Params};
+use crate::sha256::{pbkdf2, Sha256, DIGEST};
+use crate::{Error, MasterKey, MASTER_KEY_BYTES};
+
+/// What block 0 of an encrypted volume starts with. Distinct from both
+/// bcachefs's superblock magic and the designation stamp, so the three
+/// answers `bcachefs_adapter::probe` reads off block 0 stay three.
+pub const MAGIC: [u8; 16] = *b"TOYOS-ENCRYPTED\0";
+
+pub const VERSION: u32 = 1;
+
+/// Bytes the header occupies: one filesystem block.
+pub const HEADER_BYTES: usize = 4096;
+
+/// Key slots per header.
+pub const SLOTS: usize = 8;
+
+/// Where a new volume's data area starts: 64 KiB in, which leaves the header
+/// room to grow without moving the data.
+pub const DEFAULT_DATA_OFFSET: u64 = 16;
+
+/// PBKDF2 rounds a new header's digest uses.
+///
+/// Not a brute-force defence — the digest is of a 512-bit random key, which
+/// nobody is guessing — only the cost of checking one slot's output, paid
+/// once per slot per attempt. A header may name up to [`MAX_DIGEST_ROUNDS`].
+pub const DIGEST_ROUNDS: u32 = 1000;
+
+/// The most digest rounds a header may name, for [`Params`]' reason: the
+/// number is read before anything is authenticated.
+pub const MAX_DIGEST_ROUNDS: u32 = 1_000_000;
+
+const CIPHER_AES_XTS: u32 = 1;
+
+const SLOT_BYTES: usize = 128;
+const SLOTS_AT: usize = 128;
+const CHECKSUM_AT: usize = HEADER_BYTES - DIGEST;
+
+const SLOT_EMPTY: u32 = 0;
+const SLOT_ACTIVE: u32 = 1;
+
+/// One passphrase's way in.
+#[derive(Clone)]
+struct Slot {
+    params: Params,
+    salt: [u8; 32],
+    wrapped: [u8; MASTER_KEY_BYTES],
+}
+
+/// A parsed header. Nothing in it is secret: the master key exists only
+/// wrapped, and the digest of it is salted and stretched.
+#[derive(Clone)]
+pub struct Header {
+    data_offset: u64,
+    digest_rounds: u32,
+    digest_salt: [u8; 32],
+    digest: [u8; DIGEST],
+    slots: [Option<Slot>; SLOTS],
+}
+
+impl Header {
+    /// A header for a new volume under `master`, with no slots yet. It cannot
+    /// be written until [`add_slot`](Self::add_slot) has given it one.
+    pub fn new(master: &MasterKey, digest_salt: [u8; 32], data_offset: u64) -> Self {
+        Self {
+            data_offset,
+            digest_rounds: DIGEST_ROUNDS,
+            digest_salt,
+            digest: key_digest(master, &digest_salt, DIGEST_ROUNDS),
+            slots: Default::default(),
+        }
+    }
+
+    /// Parse block 0 of a device `device_blocks` long.
+    ///
+    /// Everything is checked before anything is believed: the magic, the
+    /// version, the checksum, and then every field a crafted header could use
+    /// to make an unlock attempt misbehave — a data area past the device, a
+    /// slot state that is neither, parameters outside the ceilings.
+    pub fn parse(block: &[u8; HEADER_BYTES], device_blocks: u64) -> Result<Self, Error> {
+        if block[..MAGIC.len()] != MAGIC {
+            return Err(Error::NotEncrypted);
+        }
+        if u32_at(block, 16) != VERSION {
+            return Err(Error::UnsupportedVersion);
+        }
+        if Sha256::digest(&block[..CHECKSUM_AT]) != block[CHECKSUM_AT..] {
+            return Err(Error::BadChecksum);
+        }
+        let data_offset = u64_at(block, 24);
+        let digest_rounds = u32_at(block, 32);
+        if u32_at(block, 20) != CIPHER_AES_XTS
+            || data_offset == 0
+            || data_offset >= device_blocks
+            || digest_rounds == 0
+            || digest_rounds > MAX_DIGEST_ROUNDS
+        {
+            return Err(Error::Corrupt);
+        }
+
+        let mut slots: [Option<Slot>; SLOTS] = Default::default();
+        for (i, slot) in slots.iter_mut().enumerate() {
+            let at = SLOTS_AT + i * SLOT_BYTES;
+            match u32_at(block, at) {
+                SLOT_EMPTY => continue,
+                SLOT_ACTIVE => {}
+                _ => return Err(Error::Corrupt),
+            }
+            let params =
+                Params { log_n: block[at + 4], r: u32_at(block, at + 8), p: u32_at(block, at + 12) };
+            params.check()?;
+            *slot = Some(Slot {
+                params,
+                salt: array_at(block, at + 16),
+                wrapped: array_at(block, at + 48),
+            });
+        }
+        if slots.iter().all(Option::is_none) {
+            return Err(Error::Corrupt);
+        }
+
+        Ok(Self {
+            data_offset,
+            digest_rounds,
+            digest_salt: array_at(block, 64),
+            digest: array_at(block, 96),
+            slots,
+        })
+    }
+
+    /// The header as block 0, checksum included.
+    pub fn to_block(&self) -> [u8; HEADER_BYTES] {
+        let mut block = [0u8; HEADER_BYTES];
+        block[..MAGIC.len()].copy_from_slice(&MAGIC);
+        block[16..20].copy_from_slice(&VERSION.to_le_bytes());
+        block[20..24].copy_from_slice(&CIPHER_AES_XTS.to_le_bytes());
+        block[24..32].copy_from_slice(&self.data_offset.to_le_bytes());
+        block[32..36].copy_from_slice(&self.digest_rounds.to_le_bytes());
+        block[64..96].copy_from_slice(&self.digest_salt);
+        block[96..128].copy_from_slice(&self.digest);
+        for (i, slot) in self.slots.iter().enumerate() {
+            let Some(slot) = slot else { continue };
+            let at = SLOTS_AT + i * SLOT_BYTES;
+            block[at..at + 4].copy_from_slice(&SLOT_ACTIVE.to_le_bytes());
+            block[at + 4] = slot.params.log_n;
+            block[at + 8..at + 12].copy_from_slice(&slot.params.r.to_le_bytes());
+            block[at + 12..at + 16].copy_from_slice(&slot.params.p.to_le_bytes());
+            block[at + 16..at + 48].copy_from_slice(&slot.salt);
+            block[at + 48..at + 112].copy_from_slice(&slot.wrapped);
+        }
+        let checksum = Sha256::digest(&block[..CHECKSUM_AT]);
+        block[CHECKSUM_AT..].copy_from_slice(&checksum);
+        block
+    }
+
+    /// The first block of the data area. Block `n` of the decrypted volume is
+    /// block `data_offset + n` of the device.
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// The indices of the slots in use.
+    pub fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.slots.iter().enumerate().filter(|(_, s)| s.is_some()).map(|(i, _)| i)
+    }
+
+    /// The master key, and which slot gave it up, if any slot opens with
+    /// `passphrase`.
+    ///
+    /// Every active slot is tried, at its own cost, in slot order. A wrong
+    /// passphrase therefore costs the sum of every slot's derivation, which is
+    /// what it should cost.
+    pub fn unlock(&self, passphrase: &Passphrase) -> Result<(MasterKey, usize), Error> {
+        let secret = passphrase.as_bytes()?;
+        for (i, slot) in self.slots.iter().enumerate() {
+            let Some(slot) = slot else { continue };
+            let mut pad = [0u8; MASTER_KEY_BYTES];
+            scrypt(secret, &slot.salt, slot.params, &mut pad)?;
+            let mut candidate = [0u8; MASTER_KEY_BYTES];
+            for (c, (w, p)) in candidate.iter_mut().zip(slot.wrapped.iter().zip(&pad)) {
+                *c = w ^ p;
+            }
+            crate::wipe(&mut pad);
+            let candidate = MasterKey::from_bytes(candidate);
+            if self.opens(&candidate) {
+                return Ok((candidate, i));
+            }
+        }
+        Err(Error::WrongPassphrase)
+    }
+
+    /// Wrap `master` under `passphrase` into the first free slot, and return
+    /// that slot's index.
+    ///
+    /// `salt` must be fresh random bytes for every call — see the module docs
+    /// for why it is the whole of a slot's security. `master` must be this
+    /// volume's key, and is checked: a slot wrapping any other key is a way in
+    /// that opens nothing, found out only when it is the last one left.
+    pub fn add_slot(
+        &mut self,
+        master: &MasterKey,
+        passphrase: &Passphrase,
+        params: Params,
+        salt: [u8; 32],
+    ) -> Result<usize, Error> {
+        if !self.opens(master) {
+            return Err(Error::WrongKey);
+        }
+        let index = self.slots.iter().position(Option::is_none).ok_or(Error::NoFreeSlot)?;
+        let mut pad = [0u8; MASTER_KEY_BYTES];
+        scrypt(passphrase.as_bytes()?, &salt, params, &mut pad)?;
+        let mut wrapped = [0u8; MASTER_KEY_BYTES];
+        for (w, (m, p)) in wrapped.iter_mut().zip(master.as_bytes().iter().zip(&pad)) {
+            *w = m ^ p;
+        }
+        crate::wipe(&mut pad);
+        self.slots[index] = Some(Slot { params, salt, wrapped });
+        Ok(index)
+    }
+
+    /// Clear slot `index`. Refused for the last active slot: a header with no
+    /// slots is a volume nobody can open, and that is what deleting the disk
+    /// is for.
+    pub fn remove_slot(&mut self, index: usize) -> Result<(), Error> {
+        if self.slots.get(index).is_none_or(Option::is_none) {
+            return Err(Error::NoSuchSlot);
+        }
+        if self.active_slots().count() == 1 {
+            return Err(Error::LastSlot);
+        }
+        self.slots[index] = None;
+        Ok(())
+    }
+
+    /// Replace the slot `old` opens with one `new` opens, and return the new
+    /// slot's index. The data is untouched; write [`to_block`](Self::to_block)
+    /// back to block 0 and the change is made.
+    ///
+    /// Added before removed, so a header this returns an error for still has
+    /// the slot it came in with — and so a volume with every slot full cannot
+    /// change a passphrase until one is removed, which is [`Error::NoFreeSlot`].
+    pub fn change_passphrase(
+        &mut self,
+        old: &Passphrase,
+        new: &Passphrase,
+        params: Params,
+        salt: [u8; 32],
+    ) -> Result<usize, Error> {
+        let (master, old_slot) = self.unlock(old)?;
+        let new_slot = self.add_slot(&master, new, params, salt)?;
+        self.remove_slot(old_slot)?;
+        Ok(new_slot)
+    }
+
+    /// Whether `candidate` is the key this header's digest was made from.
+    /// Compared without an early exit, so how long the answer takes says
+    /// nothing about how close a candidate came.
+    fn opens(&self, candidate: &MasterKey) -> bool {
+        let digest = key_digest(candidate, &self.digest_salt, self.digest_rounds);
+        digest.iter().zip(&self.digest).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+}
+
+fn key_digest(master: &MasterKey, salt: &[u8; 32], rounds: u32) -> [u8; DIGEST] {
+    let mut out = [0u8; DIGEST];
+    pbkdf2(master.as_bytes(), salt, rounds, &mut out);
+    out
+}
+
+fn u32_at(block: &[u8; HEADER_BYTES], at: usize) -> u32 {
+    u32::from_le_bytes(array_at(block, at))
+}
+
+fn u64_at(block: &[u8; HEADER_BYTES], at: usize) -> u64 {
+    u64::from_le_bytes(array_at(block, at))
+}
+
+/// `N` bytes at a constant offset inside the header. Every offset here is a
+/// layout constant, never a field's value, so the slice is always in range.
+fn array_at<const N: usize>(block: &[u8; HEADER_BYTES], at: usize) -> [u8; N] {
+    let mut out = [0u8; N];
+    out.copy_from_slice(&block[at..at + N]);
+    out
+}
@@ -0,0 +1,117 @@
+//! An encrypted block device: AES-256-XTS under a key that passphrases unlock.
+//!
+//! `/home` holds `sshd`'s host key and everything anyone keeps on the machine,
+//! and a laptop that goes missing takes its disk with it. This is the layer
+//! that makes the disk worthless to whoever has it: every block the
+//! filesystem writes is encrypted with a 512-bit master key, and the master
+//! key is on the disk only wrapped under passphrases, each in its own
+//! [`header`] slot, each through a memory-hard derivation.
+//!
+//! # A header is untrusted input
+//!
+//! As in `toyos-fat32` and `toyos-ext4`: block 0 comes off a disk anything
+//! could have written, and the kernel parses it before a passphrase exists to
+//! check anything against. No path that touches it may panic, and nothing in
+//! it may decide how much work an unlock attempt does without a ceiling —
+//! scrypt's parameters are the sharp edge, because they *are* an allocation
+//! size and a loop count. [`Header::parse`] refuses every field out of range
+//! before [`Header::unlock`] can act on one.
+//!
+//! # What this crate does not do
+//!
+//! - **No authentication of the data.** XTS is a length-preserving cipher, so
+//!   a block that was tampered with decrypts to noise rather than an error,
+//!   and a block replaced with an older ciphertext of itself decrypts
+//!   perfectly. That is the trade every full-disk cipher makes for having no
+//!   room to store a tag; the filesystem's own checksums are what notice.
+//! - **No constant-time AES.** The S-box is a table indexed by key-dependent
+//!   bytes, and a process sharing a cache with the kernel can in principle
+//!   learn from which lines it touches. The defence is AES-NI, which is an
+//!   `unsafe` intrinsic and so belongs in the kernel, behind [`Cipher`], and
+//!   not in a crate that forbids `unsafe` — the tests here are what it will be
+//!   checked against.
+//! - **No guarantee a key is gone.** Secrets are overwritten on drop, followed
+//!   by a compiler fence, and that is best effort twice over: safe Rust cannot
+//!   force a store the optimiser has proved dead, and a copy the compiler made
+//!   in a register spill or a moved-from stack slot is out of reach entirely.
+//! - **No key management beyond the slots.** There is no recovery key, no
+//!   keyfile and no TPM: a person who forgets every passphrase has a disk of
+//!   noise, which is what this is for.
+//!
+//! # Shape
+//!
+//! [`Header`] is block 0. [`Cipher`] is the master key expanded, with one
+//! call each way per 4 KiB block. [`Encrypted`] puts the two together over a
+//! [`bcachefs::BlockIO`], which is what host tooling formats through; the
+//! kernel does the same over its own `BlockDevice`. [`Passphrase`] and
+//! [`Prompt`] are the secret as key presses, and why it is that is in
+//! [`passphrase`].
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+mod aes;
+mod error;
+pub mod header;
+pub mod passphrase;
+mod scrypt;
+mod sha256;
+mod volume;
+mod xts;
+
+pub use aes::Aes256;
+pub use error::Error;
+pub use header::{Header, DEFAULT_DATA_OFFSET, HEADER_BYTES, MAGIC, SLOTS};
+pub use passphrase::{Passphrase, Prompt, Step, MAX_KEYS};
+pub use scrypt::{scrypt, Params};
+pub use sha256::{pbkdf2, Hmac, Sha256};
+pub use volume::{read_header, Encrypted};
+pub use xts::{Cipher, DATA_UNIT};
+
+/// Bytes in a master key: two AES-256 keys, one for the data and one for the
+/// tweak.
+pub const MASTER_KEY_BYTES: usize = 64;
+
+/// The key the data is encrypted under. Wiped on drop.
+#[derive(Clone)]
+pub struct MasterKey([u8; MASTER_KEY_BYTES]);
+
+impl MasterKey {
+    /// A key from bytes the caller drew from a source it trusts. This crate
+    /// has none: random numbers are the caller's, here as for every salt.
+    pub fn from_bytes(bytes: [u8; MASTER_KEY_BYTES]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; MASTER_KEY_BYTES] {
+        &self.0
+    }
+
+    /// The data key and the tweak key, in that order.
+    pub(crate) fn halves(&self) -> (&[u8; 32], &[u8; 32]) {
+        let (halves, _) = self.0.as_chunks::<32>();
+        (&halves[0], &halves[1])
+    }
+}
+
+/// Redacted: a key in a log line or a panic message is a key on the screen.
+impl core::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+/// Overwrite a secret. The fence makes the writes harder for the compiler to
+/// prove dead, not impossible — the crate docs say what that leaves.
+pub(crate) fn wipe(bytes: &mut [u8]) {
+    bytes.fill(0);
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
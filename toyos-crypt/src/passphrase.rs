@@ -0,0 +1,200 @@
+//! A passphrase as the keys that were pressed, not the characters they typed.
+//!
+//! The kernel asks for the passphrase before anything in userland exists, and
+//! what a key *types* is a layout — which `kernel/src/keyboard.rs` says in its
+//! first paragraph is none of the kernel's business, and which the person at
+//! the prompt may not have chosen yet. So the secret is the sequence of HID
+//! usages and the Shift level each was pressed at: the same fingers open the
+//! volume whatever layout userland later loads, and no layout table has to be
+//! in the kernel for it to be read.
+//!
+//! The cost is on the other side. Whoever *sets* a passphrase from text has to
+//! say which layout that text was meant for, and [`Passphrase::from_us_text`]
+//! is the one this crate has: the layout the test harness types with, and the
+//! one printed on the keys of the machine this was written for.
+
+use crate::Error;
+
+/// The most keys a passphrase may have. A bound so the kernel's prompt holds
+/// it in a fixed buffer and never allocates while a secret is being typed.
+pub const MAX_KEYS: usize = 128;
+
+const ENTER: u8 = 0x28;
+const ESCAPE: u8 = 0x29;
+const BACKSPACE: u8 = 0x2A;
+const TAB: u8 = 0x2B;
+const LEFT_SHIFT: u8 = 0xE1;
+const RIGHT_SHIFT: u8 = 0xE5;
+
+/// The usages a passphrase may contain: the letters, the digit row, space and
+/// the punctuation block, `0x04..=0x38` less Enter, Escape, Backspace and Tab,
+/// which the prompt reserves for itself.
+pub fn is_passphrase_key(usage: u8) -> bool {
+    matches!(usage, 0x04..=0x38) && !matches!(usage, ENTER | ESCAPE | BACKSPACE | TAB)
+}
+
+/// Up to [`MAX_KEYS`] presses, each a usage and whether Shift was held. Wiped
+/// on drop.
+#[derive(Clone)]
+pub struct Passphrase {
+    keys: [u8; 2 * MAX_KEYS],
+    len: usize,
+}
+
+impl Default for Passphrase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Passphrase {
+    pub const fn new() -> Self {
+        Self { keys: [0; 2 * MAX_KEYS], len: 0 }
+    }
+
+    /// Append one press. False if the passphrase is full or `usage` is not a
+    /// passphrase key, in which case nothing changed.
+    pub fn push(&mut self, usage: u8, shift: bool) -> bool {
+        if self.len == MAX_KEYS || !is_passphrase_key(usage) {
+            return false;
+        }
+        self.keys[2 * self.len] = usage;
+        self.keys[2 * self.len + 1] = u8::from(shift);
+        self.len += 1;
+        true
+    }
+
+    /// Drop the last press, if there is one.
+    pub fn pop(&mut self) {
+        if self.len > 0 {
+            self.len -= 1;
+            crate::wipe(&mut self.keys[2 * self.len..2 * self.len + 2]);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        crate::wipe(&mut self.keys);
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// What the key derivation is given: two bytes per press, usage then
+    /// Shift. Refused when empty, since an empty passphrase is a volume
+    /// anyone can open and nobody means to make one.
+    pub fn as_bytes(&self) -> Result<&[u8], Error> {
+        if self.len == 0 {
+            return Err(Error::BadPassphrase);
+        }
+        Ok(&self.keys[..2 * self.len])
+    }
+
+    /// The presses that type `text` on a US keyboard, or `None` if a
+    /// character of it has no key there (or there are too many).
+    pub fn from_us_text(text: &str) -> Option<Self> {
+        let mut out = Self::new();
+        for ch in text.chars() {
+            let (usage, shift) = us_key(ch)?;
+            if !out.push(usage, shift) {
+                return None;
+            }
+        }
+        Some(out)
+    }
+}
+
+impl Drop for Passphrase {
+    fn drop(&mut self) {
+        crate::wipe(&mut self.keys);
+    }
+}
+
+/// The US key for `ch`: the layout's unshifted and shifted rows, indexed by
+/// usage from `0x1E` (the `1` key) to `0x38` (`/`).
+fn us_key(ch: char) -> Option<(u8, bool)> {
+    const PLAIN: &[u8; 27] = b"1234567890\0\0\0\0 -=[]\\\0;'`,./";
+    const SHIFTED: &[u8; 27] = b"!@#$%^&*()\0\0\0\0\0_+{}|\0:\"~<>?";
+    match ch {
+        'a'..='z' => Some((0x04 + (ch as u8 - b'a'), false)),
+        'A'..='Z' => Some((0x04 + (ch as u8 - b'A'), true)),
+        _ if ch.is_ascii() && ch != '\0' => {
+            let b = ch as u8;
+            if let Some(i) = PLAIN.iter().position(|&p| p == b) {
+                return Some((0x1E + i as u8, false));
+            }
+            SHIFTED.iter().position(|&p| p == b).map(|i| (0x1E + i as u8, true))
+        }
+        _ => None,
+    }
+}
+
+/// What one key transition did to a prompt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Still typing.
+    Typing,
+    /// Enter, with at least one key typed: the passphrase is ready.
+    Submit,
+    /// Escape: the person at the prompt declined to unlock.
+    Cancel,
+}
+
+/// The whole of a passphrase prompt's keyboard handling: Shift, Backspace,
+/// Enter and Escape, fed the transitions a key decoder emits.
+///
+/// Here rather than in the kernel so it runs on the host: the kernel's half
+/// is polling a controller and painting a line, and this is the half with
+/// decisions in it.
+#[derive(Default)]
+pub struct Prompt {
+    passphrase: Passphrase,
+    left_shift: bool,
+    right_shift: bool,
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key(&mut self, usage: u8, pressed: bool) -> Step {
+        match usage {
+            LEFT_SHIFT => self.left_shift = pressed,
+            RIGHT_SHIFT => self.right_shift = pressed,
+            _ if !pressed => {}
+            ENTER if !self.passphrase.is_empty() => return Step::Submit,
+            ESCAPE => return Step::Cancel,
+            BACKSPACE => self.passphrase.pop(),
+            _ => {
+                self.passphrase.push(usage, self.left_shift || self.right_shift);
+            }
+        }
+        Step::Typing
+    }
+
+    /// Keys typed so far, for a prompt that shows progress without showing
+    /// the keys.
+    pub fn len(&self) -> usize {
+        self.passphrase.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passphrase.is_empty()
+    }
+
+    pub fn passphrase(&self) -> &Passphrase {
+        &self.passphrase
+    }
+
+    /// Start over, for the next attempt. Shift state is kept: the key is
+    /// still physically down or up.
+    pub fn clear(&mut self) {
+        self.passphrase.clear();
+    }
+}
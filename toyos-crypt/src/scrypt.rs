@@ -0,0 +1,209 @@
+//! scrypt (RFC 7914): the memory-hard function between a passphrase and a key
+//! slot.
+//!
+//! Memory-hard because the attacker this exists for has the disk and all the
+//! time in the world, and what a passphrase typed at a boot prompt has is
+//! perhaps forty bits. A function that costs only CPU is one a GPU runs
+//! thousands of at once; one that needs `128 · r · N` bytes resident for every
+//! guess is one it runs as many of as it has memory for.
+
+use alloc::vec::Vec;
+
+use crate::sha256::pbkdf2;
+use crate::Error;
+
+/// The cost of one derivation, stored in every key slot so a slot written
+/// with a harder setting still opens after the default moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Params {
+    /// `N = 2^log_n`: the number of blocks in the scratch table.
+    pub log_n: u8,
+    /// Block size in 128-byte units.
+    pub r: u32,
+    /// Parallelism: independent passes over the table, run here one after
+    /// the other.
+    pub p: u32,
+}
+
+/// The most memory one derivation may ask for: 64 MiB.
+///
+/// A header is read off a disk before anything on it is authenticated, so
+/// these three numbers are the first untrusted input this crate meets. Without
+/// a ceiling a crafted slot names `log_n = 63` and the kernel tries to
+/// allocate the address space at a boot prompt.
+pub const MAX_MEMORY: usize = 64 << 20;
+
+/// The most passes one derivation may ask for. Time rather than memory, and
+/// for the same reason as [`MAX_MEMORY`].
+pub const MAX_P: u32 = 16;
+
+/// The table is allocated in pieces of this size rather than as one.
+///
+/// The kernel's heap serves nothing larger than one 2 MiB page
+/// (`MAX_HEAP_ALLOC`, and it asserts rather than failing), and the kernel is
+/// where the derivation that matters runs. A quarter of a MiB so that seven
+/// pieces share one backing page: at a whole MiB the allocator's bookkeeping
+/// leaves room for one per page, and the default's 16 MiB table would hold 32
+/// MiB of the machine at the prompt. The cost is a division per table access,
+/// which is noise beside the Salsa20/8 it feeds.
+const SEGMENT: usize = 256 << 10;
+
+impl Params {
+    /// What a new slot gets: `N = 2^14`, `r = 8`, `p = 1` — 16 MiB. The
+    /// scrypt paper's interactive-login setting, which is what a boot prompt
+    /// is, and small enough that the kernel can hold it at the storage phase
+    /// of a 512 MiB machine without noticing.
+    pub const DEFAULT: Self = Self { log_n: 14, r: 8, p: 1 };
+
+    /// Bytes the scratch table occupies.
+    pub fn memory(&self) -> Option<usize> {
+        let n = 1usize.checked_shl(u32::from(self.log_n))?;
+        n.checked_mul(128)?.checked_mul(usize::try_from(self.r).ok()?)
+    }
+
+    /// Whether these are parameters this crate will run. The RFC's own
+    /// constraints (`N > 1`, `r ≥ 1`, `p ≥ 1`), the two ceilings above, and
+    /// `B` — all `p` blocks, which is one allocation — no larger than a table
+    /// segment, for the segment's reason.
+    pub fn check(&self) -> Result<(), Error> {
+        let memory = self.memory().ok_or(Error::BadParams)?;
+        if self.log_n == 0
+            || self.r == 0
+            || self.p == 0
+            || self.p > MAX_P
+            // After the bound on `p`, which is what keeps this product in range.
+            || 128 * u64::from(self.r) * u64::from(self.p) > SEGMENT as u64
+            || memory > MAX_MEMORY
+        {
+            return Err(Error::BadParams);
+        }
+        Ok(())
+    }
+}
+
+/// Derive `out` from `password` and `salt` at cost `params`.
+pub fn scrypt(password: &[u8], salt: &[u8], params: Params, out: &mut [u8]) -> Result<(), Error> {
+    params.check()?;
+    let r = params.r as usize;
+    let block = 128 * r;
+    let n = 1usize << params.log_n;
+
+    let mut b = Vec::new();
+    b.try_reserve_exact(block * params.p as usize).map_err(|_| Error::OutOfMemory)?;
+    b.resize(block * params.p as usize, 0);
+    pbkdf2(password, salt, 1, &mut b);
+
+    let mut v = Table::new(n, block / 4)?;
+    let mut x = alloc::vec![0u32; block / 4];
+    let mut y = alloc::vec![0u32; block / 4];
+
+    for chunk in b.chunks_mut(block) {
+        for (x, word) in x.iter_mut().zip(chunk.as_chunks::<4>().0) {
+            *x = u32::from_le_bytes(*word);
+        }
+        ro_mix(&mut x, &mut y, &mut v, n);
+        for (word, x) in chunk.as_chunks_mut::<4>().0.iter_mut().zip(&x) {
+            *word = x.to_le_bytes();
+        }
+    }
+
+    pbkdf2(password, &b, 1, out);
+    crate::wipe(&mut b);
+    for segment in &mut v.segments {
+        wipe_words(segment);
+    }
+    wipe_words(&mut x);
+    wipe_words(&mut y);
+    Ok(())
+}
+
+/// `scryptROMix`: fill the table sequentially, then read it back in an order
+/// the data decides. The second loop is what makes the memory necessary.
+fn ro_mix(x: &mut [u32], y: &mut [u32], v: &mut Table, n: usize) {
+    let words = x.len();
+    for i in 0..n {
+        v.block_mut(i).copy_from_slice(x);
+        block_mix(x, y);
+    }
+    for _ in 0..n {
+        // `Integerify`: the first word of the last 64-byte block, mod N.
+        let j = x[words - 16] as usize & (n - 1);
+        for (x, v) in x.iter_mut().zip(v.block_mut(j).iter()) {
+            *x ^= v;
+        }
+        block_mix(x, y);
+    }
+}
+
+/// `V`, the `N`-block table, in [`SEGMENT`]-sized pieces.
+struct Table {
+    segments: Vec<Vec<u32>>,
+    words: usize,
+    per_segment: usize,
+}
+
+impl Table {
+    fn new(n: usize, words: usize) -> Result<Self, Error> {
+        let per_segment = SEGMENT / 4 / words;
+        let count = n.div_ceil(per_segment);
+        let mut segments = Vec::new();
+        segments.try_reserve_exact(count).map_err(|_| Error::OutOfMemory)?;
+        for i in 0..count {
+            let blocks = per_segment.min(n - i * per_segment);
+            let mut segment = Vec::new();
+            segment.try_reserve_exact(blocks * words).map_err(|_| Error::OutOfMemory)?;
+            segment.resize(blocks * words, 0);
+            segments.push(segment);
+        }
+        Ok(Self { segments, words, per_segment })
+    }
+
+    fn block_mut(&mut self, i: usize) -> &mut [u32] {
+        let at = (i % self.per_segment) * self.words;
+        &mut self.segments[i / self.per_segment][at..at + self.words]
+    }
+}
+
+/// `scryptBlockMix`, with the RFC's interleaving: even outputs first, then
+/// odd. `y` is scratch the size of `x`.
+fn block_mix(b: &mut [u32], y: &mut [u32]) {
+    let r2 = b.len() / 16;
+    let mut t = [0u32; 16];
+    t.copy_from_slice(&b[(r2 - 1) * 16..]);
+    for i in 0..r2 {
+        for (t, b) in t.iter_mut().zip(&b[i * 16..(i + 1) * 16]) {
+            *t ^= b;
+        }
+        salsa20_8(&mut t);
+        let at = if i % 2 == 0 { i / 2 } else { r2 / 2 + i / 2 };
+        y[at * 16..(at + 1) * 16].copy_from_slice(&t);
+    }
+    b.copy_from_slice(y);
+}
+
+/// Salsa20/8, the core only.
+fn salsa20_8(b: &mut [u32; 16]) {
+    let mut x = *b;
+    for _ in 0..4 {
+        // Four columns, then four rows. Each entry is one quarter-round's
+        // indices in the order RFC 7914 writes to them, and every write adds
+        // the two words written before it.
+        for [w0, w1, w2, w3] in [
+            [4, 8, 12, 0], [9, 13, 1, 5], [14, 2, 6, 10], [3, 7, 11, 15],
+            [1, 2, 3, 0], [6, 7, 4, 5], [11, 8, 9, 10], [12, 13, 14, 15],
+        ] {
+            x[w0] ^= x[w3].wrapping_add(x[w2]).rotate_left(7);
+            x[w1] ^= x[w0].wrapping_add(x[w3]).rotate_left(9);
+            x[w2] ^= x[w1].wrapping_add(x[w0]).rotate_left(13);
+            x[w3] ^= x[w2].wrapping_add(x[w1]).rotate_left(18);
+        }
+    }
+    for (b, x) in b.iter_mut().zip(x) {
+        *b = b.wrapping_add(x);
+    }
+}
+
+fn wipe_words(words: &mut [u32]) {
+    words.fill(0);
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
@@ -0,0 +1,97 @@
+//! The decrypted volume as a [`BlockIO`] over the encrypted one.
+//!
+//! What the host formats through and what the tests run over `VecBlockIO`.
+//! The kernel does not use this type — its device trait is `BlockDevice`, not
+//! `BlockIO`, and `kernel/src/crypt_device.rs` is the same forty lines over
+//! [`Cipher`] against that — but both are the same mapping: block `n` of the
+//! volume is block `data_offset + n` of the device, encrypted with `n` as the
+//! tweak.
+
+use bcachefs::{BlockBuf, BlockIO, BlockNum, DeviceError};
+
+use crate::header::{Header, HEADER_BYTES};
+use crate::passphrase::Passphrase;
+use crate::xts::Cipher;
+use crate::{Error, MasterKey};
+
+pub struct Encrypted<D> {
+    inner: D,
+    cipher: Cipher,
+    data_offset: u64,
+}
+
+impl<D: BlockIO> Encrypted<D> {
+    /// Write `header` to block 0 of `inner` and return the empty volume it
+    /// describes.
+    ///
+    /// Nothing in the data area is touched. Through the volume, what was there
+    /// reads back as noise, since the key is new; on the device it is still
+    /// the old bytes until a filesystem writes over them. A disk that held
+    /// secrets in the clear is wiped before this, by whoever knows it did.
+    pub fn format(inner: D, header: &Header, master: &MasterKey) -> Result<Self, Error> {
+        let mut block = BlockBuf::zeroed();
+        block.0 = header.to_block();
+        inner.write_block(BlockNum::new(0), &block).map_err(|DeviceError| Error::Device)?;
+        inner.sync().map_err(|DeviceError| Error::Device)?;
+        Ok(Self::new(inner, master, header.data_offset()))
+    }
+
+    /// Read the header off `inner` and open the volume with `passphrase`.
+    pub fn unlock(inner: D, passphrase: &Passphrase) -> Result<Self, Error> {
+        let header = read_header(&inner)?;
+        let (master, _) = header.unlock(passphrase)?;
+        Ok(Self::new(inner, &master, header.data_offset()))
+    }
+
+    /// The volume under an already-unwrapped key.
+    pub fn new(inner: D, master: &MasterKey, data_offset: u64) -> Self {
+        Self { inner, cipher: Cipher::new(master), data_offset }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// Where block `block` of the volume lives on the device, or `None` past
+    /// the volume's end.
+    fn device_block(&self, block: BlockNum) -> Option<BlockNum> {
+        if block.raw() >= self.block_count() {
+            return None;
+        }
+        BlockNum::new(self.data_offset).checked_add(block.raw())
+    }
+}
+
+/// Block 0 of `dev`, parsed.
+pub fn read_header<D: BlockIO>(dev: &D) -> Result<Header, Error> {
+    let mut block = BlockBuf::zeroed();
+    dev.read_block(BlockNum::new(0), &mut block).map_err(|DeviceError| Error::Device)?;
+    let bytes: &[u8; HEADER_BYTES] = block.as_bytes();
+    Header::parse(bytes, dev.block_count())
+}
+
+impl<D: BlockIO> BlockIO for Encrypted<D> {
+    fn read_block(&self, block: BlockNum, buf: &mut BlockBuf) -> Result<(), DeviceError> {
+        let at = self.device_block(block).ok_or(DeviceError)?;
+        self.inner.read_block(at, buf)?;
+        self.cipher.decrypt(block.raw(), buf.as_bytes_mut());
+        Ok(())
+    }
+
+    /// Encrypts a copy: the caller's buffer is plaintext it may go on using.
+    fn write_block(&self, block: BlockNum, buf: &BlockBuf) -> Result<(), DeviceError> {
+        let at = self.device_block(block).ok_or(DeviceError)?;
+        let mut sealed = BlockBuf::zeroed();
+        sealed.0 = buf.0;
+        self.cipher.encrypt(block.raw(), sealed.as_bytes_mut());
+        self.inner.write_block(at, &sealed)
+    }
+
+    fn block_count(&self) -> u64 {
+        self.inner.block_count().saturating_sub(self.data_offset)
+    }
+
+    fn sync(&self) -> Result<(), DeviceError> {
+        self.inner.sync()
+    }
+}
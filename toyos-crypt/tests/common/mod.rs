@@ -0,0 +1,41 @@
+//! What every test file here shares: hex, a fixed key, and a cheap cost.
+
+#![allow(dead_code)]
+
+use toyos_crypt::{Header, MasterKey, Params, Passphrase, DEFAULT_DATA_OFFSET};
+
+/// scrypt at `N = 16`, `r = 1`: the algorithm in full at a cost a debug build
+/// pays in microseconds. Every path a slot takes is the same at any cost, and
+/// the default's cost is what the tests must not pay hundreds of times.
+pub const CHEAP: Params = Params { log_n: 4, r: 1, p: 1 };
+
+pub fn hex(s: &str) -> Vec<u8> {
+    let s: String = s.split_whitespace().collect();
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).expect("hex"))
+        .collect()
+}
+
+pub fn master() -> MasterKey {
+    let mut bytes = [0u8; 64];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (i as u8).wrapping_mul(37).wrapping_add(11);
+    }
+    MasterKey::from_bytes(bytes)
+}
+
+pub fn salt(n: u8) -> [u8; 32] {
+    [n; 32]
+}
+
+pub fn passphrase(text: &str) -> Passphrase {
+    Passphrase::from_us_text(text).expect("a US-typable passphrase")
+}
+
+/// A header under [`master`] with one slot, opened by `text`.
+pub fn header(text: &str) -> Header {
+    let mut header = Header::new(&master(), salt(0xD1), DEFAULT_DATA_OFFSET);
+    header.add_slot(&master(), &passphrase(text), CHEAP, salt(1)).expect("first slot");
+    header
+}
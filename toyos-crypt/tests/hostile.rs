@@ -0,0 +1,171 @@
+//! A header off a disk somebody else wrote.
+//!
+//! Everything here is refused by name, and nothing panics: the kernel parses
+//! block 0 of whatever NVMe namespace it finds before anybody has typed
+//! anything.
+
+mod common;
+
+use std::sync::OnceLock;
+
+use common::{header, passphrase};
+use toyos_crypt::{Error, Header, Sha256, HEADER_BYTES, MAGIC};
+
+const DEVICE_BLOCKS: u64 = 4096;
+
+type Edit = fn(&mut [u8; HEADER_BYTES]);
+
+/// A valid header's block, made once: a header costs two digests to build,
+/// and the sweep below edits over a thousand copies of it.
+fn valid() -> [u8; HEADER_BYTES] {
+    static VALID: OnceLock<[u8; HEADER_BYTES]> = OnceLock::new();
+    *VALID.get_or_init(|| header("correct horse").to_block())
+}
+
+/// [`valid`], edited by `f`, with the checksum recomputed so the edit reaches
+/// the field checks instead of stopping at the checksum.
+fn edited(f: impl FnOnce(&mut [u8; HEADER_BYTES])) -> [u8; HEADER_BYTES] {
+    let mut block = valid();
+    f(&mut block);
+    reseal(&mut block);
+    block
+}
+
+fn reseal(block: &mut [u8; HEADER_BYTES]) {
+    let sum = Sha256::digest(&block[..HEADER_BYTES - 32]);
+    block[HEADER_BYTES - 32..].copy_from_slice(&sum);
+}
+
+fn parse(block: &[u8; HEADER_BYTES]) -> Result<Header, Error> {
+    Header::parse(block, DEVICE_BLOCKS)
+}
+
+#[test]
+fn a_plain_disk_is_not_encrypted() {
+    assert_eq!(parse(&[0; HEADER_BYTES]).err(), Some(Error::NotEncrypted));
+    let mut bcachefs = [0u8; HEADER_BYTES];
+    bcachefs[..4].copy_from_slice(b"BCFS");
+    assert_eq!(parse(&bcachefs).err(), Some(Error::NotEncrypted));
+    let mut designated = [0u8; HEADER_BYTES];
+    designated[..16].copy_from_slice(&bcachefs::DESIGNATION_MAGIC);
+    assert_eq!(parse(&designated).err(), Some(Error::NotEncrypted));
+}
+
+#[test]
+fn a_written_header_parses_back_to_itself() {
+    let block = valid();
+    let parsed = parse(&block).unwrap();
+    assert!(parsed.to_block() == block);
+    assert!(parsed.unlock(&passphrase("correct horse")).is_ok());
+}
+
+#[test]
+fn every_flipped_bit_is_caught_by_the_checksum() {
+    let block = valid();
+    for byte in (MAGIC.len() + 4..HEADER_BYTES).step_by(61) {
+        let mut torn = block;
+        torn[byte] ^= 0x10;
+        assert_eq!(parse(&torn).err(), Some(Error::BadChecksum), "byte {byte}");
+    }
+}
+
+#[test]
+fn a_version_from_the_future_is_named() {
+    let block = edited(|b| b[16] = 2);
+    assert_eq!(parse(&block).err(), Some(Error::UnsupportedVersion));
+}
+
+#[test]
+fn fields_out_of_range_are_corrupt() {
+    let cases: [(&str, Edit); 6] = [
+        ("cipher", |b| b[20] = 9),
+        ("data area at block 0", |b| b[24..32].copy_from_slice(&0u64.to_le_bytes())),
+        ("data area past the device", |b| {
+            b[24..32].copy_from_slice(&DEVICE_BLOCKS.to_le_bytes())
+        }),
+        ("zero digest rounds", |b| b[32..36].copy_from_slice(&0u32.to_le_bytes())),
+        ("a billion digest rounds", |b| {
+            b[32..36].copy_from_slice(&1_000_000_000u32.to_le_bytes())
+        }),
+        ("slot state", |b| b[128] = 7),
+    ];
+    for (what, f) in cases {
+        assert_eq!(parse(&edited(f)).err(), Some(Error::Corrupt), "{what}");
+    }
+}
+
+#[test]
+fn a_header_with_no_slots_is_corrupt() {
+    let block = edited(|b| b[128..132].copy_from_slice(&0u32.to_le_bytes()));
+    assert_eq!(parse(&block).err(), Some(Error::Corrupt));
+}
+
+/// The parameters are an allocation size and a loop count read before
+/// anything is authenticated. Each of these would be an out-of-memory, an
+/// overflow or an hour at the prompt if it reached `scrypt`.
+#[test]
+fn slot_parameters_past_the_ceilings_are_refused_at_parse() {
+    let cases: [(&str, Edit); 8] = [
+        ("log_n = 0", |b| b[128 + 4] = 0),
+        ("log_n = 63", |b| b[128 + 4] = 63),
+        ("log_n = 255", |b| b[128 + 4] = 255),
+        ("r = 0", |b| b[128 + 8..128 + 12].copy_from_slice(&0u32.to_le_bytes())),
+        ("r = u32::MAX", |b| b[128 + 8..128 + 12].copy_from_slice(&u32::MAX.to_le_bytes())),
+        ("p = 0", |b| b[128 + 12..128 + 16].copy_from_slice(&0u32.to_le_bytes())),
+        ("p = 1000", |b| b[128 + 12..128 + 16].copy_from_slice(&1000u32.to_le_bytes())),
+        // Each within its own bound, and `B` is then 4 MiB in one piece: past
+        // what the kernel's heap serves, where it is an assert and not an error.
+        ("r = 2048, p = 16", |b| {
+            b[128 + 8..128 + 12].copy_from_slice(&2048u32.to_le_bytes());
+            b[128 + 12..128 + 16].copy_from_slice(&16u32.to_le_bytes());
+        }),
+    ];
+    for (what, f) in cases {
+        assert_eq!(parse(&edited(f)).err(), Some(Error::BadParams), "{what}");
+    }
+}
+
+/// 64 MiB of table is the ceiling, and one step past it is refused even
+/// though every field is individually sane.
+#[test]
+fn the_memory_ceiling_is_the_product_not_the_fields() {
+    // N = 2^17, r = 8: 128 MiB.
+    let block = edited(|b| {
+        b[128 + 4] = 17;
+        b[128 + 8..128 + 12].copy_from_slice(&8u32.to_le_bytes());
+    });
+    assert_eq!(parse(&block).err(), Some(Error::BadParams));
+    // N = 2^16, r = 8: exactly 64 MiB, allowed.
+    let block = edited(|b| {
+        b[128 + 4] = 16;
+        b[128 + 8..128 + 12].copy_from_slice(&8u32.to_le_bytes());
+    });
+    assert!(parse(&block).is_ok());
+}
+
+/// A slot whose wrapped key was edited opens nothing: the digest is checked
+/// against what comes out, so a header that parses can still only be opened by
+/// the passphrase that made it.
+#[test]
+fn an_edited_wrapped_key_is_a_wrong_passphrase_and_not_a_wrong_key() {
+    let block = edited(|b| b[128 + 48] ^= 1);
+    let header = parse(&block).unwrap();
+    assert_eq!(header.unlock(&passphrase("correct horse")).err(), Some(Error::WrongPassphrase));
+}
+
+/// Every single-byte value at every offset of the fixed part and the first
+/// slot, resealed so each reaches the field checks. None may panic; which
+/// error each gets is the tests above. The slot's state and parameters are
+/// also unlocked with, since those are the bytes that steer a derivation —
+/// the salt and the wrapped key only change what comes out of one.
+#[test]
+fn no_single_byte_edit_panics() {
+    for at in 16..128 + 128 {
+        for value in [0u8, 1, 0x7F, 0x80, 0xFF] {
+            let parsed = parse(&edited(|b| b[at] = value));
+            if (128..128 + 16).contains(&at) {
+                let _ = parsed.map(|h| h.unlock(&passphrase("correct horse")));
+            }
+        }
+    }
+}
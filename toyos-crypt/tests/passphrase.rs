@@ -0,0 +1,98 @@
+//! The prompt's keyboard handling, fed the transitions a key decoder emits.
+
+use toyos_crypt::{Passphrase, Prompt, Step, MAX_KEYS};
+
+const SHIFT: u8 = 0xE1;
+const RIGHT_SHIFT: u8 = 0xE5;
+const ENTER: u8 = 0x28;
+const ESCAPE: u8 = 0x29;
+const BACKSPACE: u8 = 0x2A;
+const CAPS_LOCK: u8 = 0x39;
+const A: u8 = 0x04;
+const ONE: u8 = 0x1E;
+
+fn tap(prompt: &mut Prompt, usage: u8) -> Step {
+    let step = prompt.key(usage, true);
+    prompt.key(usage, false);
+    step
+}
+
+fn bytes(p: &Passphrase) -> Vec<u8> {
+    p.as_bytes().unwrap().to_vec()
+}
+
+/// What the host derives from text and what the kernel derives from the same
+/// keys have to be the same bytes, or a volume made on the host never opens.
+#[test]
+fn typed_keys_are_what_the_us_text_says() {
+    let mut prompt = Prompt::new();
+    tap(&mut prompt, A);
+    prompt.key(SHIFT, true);
+    tap(&mut prompt, A);
+    tap(&mut prompt, ONE);
+    prompt.key(SHIFT, false);
+    tap(&mut prompt, 0x2C);
+    tap(&mut prompt, 0x38);
+    assert_eq!(tap(&mut prompt, ENTER), Step::Submit);
+    assert_eq!(
+        bytes(prompt.passphrase()),
+        bytes(&Passphrase::from_us_text("aA! /").unwrap())
+    );
+}
+
+#[test]
+fn either_shift_is_shift() {
+    let mut left = Prompt::new();
+    left.key(SHIFT, true);
+    tap(&mut left, A);
+    let mut right = Prompt::new();
+    right.key(RIGHT_SHIFT, true);
+    tap(&mut right, A);
+    assert_eq!(bytes(left.passphrase()), bytes(right.passphrase()));
+}
+
+#[test]
+fn backspace_takes_back_one_key_and_enter_on_nothing_is_ignored() {
+    let mut prompt = Prompt::new();
+    assert_eq!(tap(&mut prompt, ENTER), Step::Typing);
+    tap(&mut prompt, A);
+    tap(&mut prompt, ONE);
+    tap(&mut prompt, BACKSPACE);
+    assert_eq!(prompt.len(), 1);
+    tap(&mut prompt, BACKSPACE);
+    tap(&mut prompt, BACKSPACE);
+    assert!(prompt.is_empty());
+    assert_eq!(tap(&mut prompt, ENTER), Step::Typing);
+}
+
+/// Caps Lock's state is the keyboard's and not knowable from here, so a
+/// passphrase that depended on it could not be typed the same way twice.
+#[test]
+fn keys_that_type_nothing_portable_are_not_part_of_it() {
+    let mut prompt = Prompt::new();
+    for usage in [CAPS_LOCK, 0x3A, 0x4F, 0xE0, 0xE3] {
+        tap(&mut prompt, usage);
+    }
+    assert!(prompt.is_empty());
+    assert_eq!(tap(&mut prompt, ESCAPE), Step::Cancel);
+}
+
+#[test]
+fn a_passphrase_stops_at_its_bound() {
+    let mut prompt = Prompt::new();
+    for _ in 0..MAX_KEYS + 10 {
+        tap(&mut prompt, A);
+    }
+    assert_eq!(prompt.len(), MAX_KEYS);
+    assert!(Passphrase::from_us_text(&"a".repeat(MAX_KEYS)).is_some());
+    assert!(Passphrase::from_us_text(&"a".repeat(MAX_KEYS + 1)).is_none());
+}
+
+#[test]
+fn text_with_no_us_key_is_refused() {
+    for text in ["é", "\t", "line\n", "nul\0"] {
+        assert!(Passphrase::from_us_text(text).is_none(), "{text:?}");
+    }
+    let every: String = (0x20u8..0x7F).map(char::from).collect();
+    assert_eq!(Passphrase::from_us_text(&every).unwrap().len(), every.len());
+}
@@ -0,0 +1,166 @@
+//! Every primitive against a vector somebody else computed.
+//!
+//! The standards' own where they have one (FIPS-197, FIPS 180-4, RFC 4231,
+//! RFC 7914), and the host's Python `hashlib` and `cryptography` where they do
+//! not — XTS over a whole 4 KiB unit with a 64-bit tweak is not in IEEE 1619's
+//! table, and it is the only shape this crate uses.
+
+mod common;
+
+use common::hex;
+use toyos_crypt::{pbkdf2, scrypt, Aes256, Cipher, Hmac, MasterKey, Params, Sha256, DATA_UNIT};
+
+#[test]
+fn aes256_matches_fips197_appendix_c3() {
+    let key: [u8; 32] = core::array::from_fn(|i| i as u8);
+    let aes = Aes256::new(&key);
+    let mut block: [u8; 16] = hex("00112233445566778899aabbccddeeff").try_into().unwrap();
+    aes.encrypt(&mut block);
+    assert_eq!(block.to_vec(), hex("8ea2b7ca516745bfeafc49904b496089"));
+    aes.decrypt(&mut block);
+    assert_eq!(block.to_vec(), hex("00112233445566778899aabbccddeeff"));
+}
+
+#[test]
+fn sha256_matches_fips180_examples() {
+    assert_eq!(
+        Sha256::digest(b"").to_vec(),
+        hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+    );
+    assert_eq!(
+        Sha256::digest(b"abc").to_vec(),
+        hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+    );
+    assert_eq!(
+        Sha256::digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq").to_vec(),
+        hex("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")
+    );
+}
+
+/// A million bytes fed in uneven pieces, so the partial-block path is
+/// exercised at every offset a piece can end on.
+#[test]
+fn sha256_streams_across_block_boundaries() {
+    let mut h = Sha256::new();
+    let mut left = 1_000_000usize;
+    let mut step = 1;
+    while left > 0 {
+        let n = step.min(left);
+        h.update(&vec![b'a'; n]);
+        left -= n;
+        step = step % 127 + 1;
+    }
+    assert_eq!(
+        h.finish().to_vec(),
+        hex("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0")
+    );
+}
+
+#[test]
+fn hmac_matches_rfc4231() {
+    assert_eq!(
+        Hmac::mac(b"Jefe", b"what do ya want for nothing?").to_vec(),
+        hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+    );
+    // Case 6: a key longer than the block is hashed first.
+    assert_eq!(
+        Hmac::mac(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First").to_vec(),
+        hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")
+    );
+}
+
+#[test]
+fn pbkdf2_matches_rfc7914_and_hashlib() {
+    let mut out = [0u8; 64];
+    pbkdf2(b"passwd", b"salt", 1, &mut out);
+    assert_eq!(
+        out.to_vec(),
+        hex("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc
+             49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783")
+    );
+    let mut out = [0u8; 32];
+    pbkdf2(b"password", b"salt", 4096, &mut out);
+    assert_eq!(
+        out.to_vec(),
+        hex("c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a")
+    );
+}
+
+#[test]
+fn scrypt_matches_rfc7914() {
+    let mut out = [0u8; 64];
+    scrypt(b"", b"", Params { log_n: 4, r: 1, p: 1 }, &mut out).unwrap();
+    assert_eq!(
+        out.to_vec(),
+        hex("77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442
+             fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906")
+    );
+    scrypt(b"password", b"NaCl", Params { log_n: 10, r: 8, p: 16 }, &mut out).unwrap();
+    assert_eq!(
+        out.to_vec(),
+        hex("fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162
+             2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640")
+    );
+}
+
+/// The table is allocated in 256 KiB pieces; a table of several pieces must
+/// agree with a derivation that never crosses one, which is what the
+/// reference's own answer is.
+#[test]
+fn scrypt_across_table_segments_matches_hashlib() {
+    // N = 2^12, r = 4: 2 MiB of table, eight segments. From
+    // `hashlib.scrypt(b"toyos", salt=b"segments", n=4096, r=4, p=1, dklen=32)`.
+    let mut out = [0u8; 32];
+    scrypt(b"toyos", b"segments", Params { log_n: 12, r: 4, p: 1 }, &mut out).unwrap();
+    assert_eq!(
+        out.to_vec(),
+        hex("77edc8e816e99826b0c8a0486fe9d4071df78696dbf10a8f4837163a277c3cd4")
+    );
+}
+
+fn xts_key() -> MasterKey {
+    MasterKey::from_bytes(core::array::from_fn(|i| i as u8))
+}
+
+/// From Python's `cryptography`: `Cipher(AES(bytes(range(64))),
+/// XTS(lba.to_bytes(16, "little")))` over the same 4 KiB.
+#[test]
+fn xts_matches_the_reference_over_one_block() {
+    let cipher = Cipher::new(&xts_key());
+    let plain: [u8; DATA_UNIT] = core::array::from_fn(|i| (i * 7 + 3) as u8);
+    let mut unit = plain;
+    cipher.encrypt(0x1_2345_6789, &mut unit);
+    assert_eq!(unit[..32].to_vec(), hex("2b3cdcb48ff3b542e393b6ef4557e336ee97868dbeb66a744f31c5e9cd3c2bb8"));
+    assert_eq!(unit[DATA_UNIT - 16..].to_vec(), hex("e13bbe9bcdd6d76671515e6e2b9a4b3a"));
+    assert_eq!(
+        Sha256::digest(&unit).to_vec(),
+        hex("1b9958015f862f3b8e0415dd3ef35a25453053adebcddf544dd00369e953ff51")
+    );
+    cipher.decrypt(0x1_2345_6789, &mut unit);
+    assert!(unit == plain);
+
+    let mut zeros = [0u8; DATA_UNIT];
+    cipher.encrypt(0, &mut zeros);
+    assert_eq!(zeros[..16].to_vec(), hex("cd6b103236fbd87dba93e9001e29bc3d"));
+    assert_eq!(
+        Sha256::digest(&zeros).to_vec(),
+        hex("0836550e86225337ef77d4090922a59a09174e085feeff09f141a22f042c1c8a")
+    );
+}
+
+/// The tweak is what keeps two blocks holding the same bytes from looking
+/// the same on the disk — a filesystem is mostly zeros.
+#[test]
+fn identical_blocks_at_different_addresses_encrypt_differently() {
+    let cipher = Cipher::new(&xts_key());
+    let mut a = [0u8; DATA_UNIT];
+    let mut b = [0u8; DATA_UNIT];
+    cipher.encrypt(7, &mut a);
+    cipher.encrypt(8, &mut b);
+    assert!(a != b);
+    // And within one block, no two 16-byte pieces repeat either.
+    let pieces = a.as_chunks::<16>().0;
+    for (i, x) in pieces.iter().enumerate() {
+        assert!(pieces[i + 1..].iter().all(|y| y != x), "piece {i} repeats");
+    }
+}
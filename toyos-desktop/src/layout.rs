@@ -188,13 +188,18 @@ impl Chrome {
             Some((w, h)) if w > 0 && h > 0 => {
                 let fw = w + self.chrome_w();
                 let fh = h + self.chrome_h();
-                Rect::new((screen.w() - fw).max(0) / 2, (work.h() - fh).max(0) / 2, fw, fh)
+                Rect::new(
+                    screen.x0 + (screen.w() - fw).max(0) / 2,
+                    work.y0 + (work.h() - fh).max(0) / 2,
+                    fw,
+                    fh,
+                )
             }
             _ => {
                 let offset = self.cascade * (live % 10) as i32;
                 Rect::new(
-                    self.initial_margin + offset,
-                    self.initial_margin + offset,
+                    screen.x0 + self.initial_margin + offset,
+                    work.y0 + self.initial_margin + offset,
                     screen.w() - self.initial_margin * 2,
                     work.h() - self.initial_margin * 2,
                 )
@@ -332,6 +337,15 @@ mod tests {
         assert_eq!(C.initial_content(None, 10, SCREEN).origin(), first.origin());
     }
 
+    #[test]
+    fn a_new_window_opens_on_the_screen_it_was_placed_for() {
+        let right = Rect::new(1920, 0, 1280, 1024);
+        for requested in [Some((800, 600)), None] {
+            let frame = C.frame(C.initial_content(requested, 0, right));
+            assert!(C.work_area(right).contains(frame), "{requested:?}: {frame:?}");
+        }
+    }
+
     #[test]
     fn a_drag_cannot_put_a_title_bar_off_the_top() {
         let content = Rect::new(100, 100, 400, 300);
@@ -1,8 +1,9 @@
 //! Everything the desktop decides, as pure functions over pure state.
 //!
-//! Where a window is, what changed since the last frame, what the pointer is
-//! over, who has the keyboard, what a key means, what is visible inside a
-//! damaged region and which pixels of a client's buffer reach the screen. No
+//! Where a window is, which monitor it is on, what changed since the last
+//! frame, what the pointer is over, who has the keyboard, what a key means,
+//! what is visible inside a damaged region and which pixels of a client's
+//! buffer reach the screen. No
 //! I/O, no drawing, no shared memory, no connections — those are the
 //! compositor's, and it is the only caller.
 //!
@@ -26,6 +27,7 @@ pub mod damage;
 pub mod hit;
 pub mod input;
 pub mod layout;
+pub mod outputs;
 pub mod plan;
 pub mod rect;
 pub mod stack;
@@ -40,6 +42,7 @@ pub use input::{
     Held, KeyAction, MouseSample, Released, TabAction, DRAG_THRESHOLD, MOUSE_EVENT_LEN,
 };
 pub use layout::{set_mode, Chrome, Desk};
+pub use outputs::{arrange, format_arrangement, parse_arrangement, Outputs};
 pub use plan::{compose, content_blit, Blit, Layer};
 pub use rect::{Point, Rect};
 pub use stack::Stack;
@@ -0,0 +1,279 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Index;
+
+use crate::layout::{Chrome, Desk};
+use crate::rect::{Point, Rect};
+
+/// Every monitor the desktop spans, each one a [`Desk`] with its own bar and
+/// its own work area, all of them in one coordinate space.
+///
+/// **Windows live in that space and not on a monitor.** A window straddling two
+/// outputs is one rectangle both of them show part of, and dragging it from one
+/// to the next is the drag it always was. What a window *belongs* to is asked
+/// only where the answer decides something — which work area a maximize fills
+/// ([`holding`](Self::holding)), which bar a click landed on
+/// ([`at`](Self::at)) — and is answered from its geometry every time rather
+/// than remembered, so there is no field that can disagree with where the
+/// window actually is.
+///
+/// Never empty: a display has at least one output, and [`primary`] is the one
+/// every question with no better answer falls back to.
+///
+/// [`primary`]: Self::primary
+#[derive(Clone, Debug)]
+pub struct Outputs {
+    desks: Vec<Desk>,
+}
+
+impl Outputs {
+    /// The desks, one per output and in the display's order.
+    ///
+    /// # Panics
+    /// On an empty list, which no display describes.
+    pub fn new(desks: Vec<Desk>) -> Self {
+        assert!(!desks.is_empty(), "a desktop has at least one output");
+        Self { desks }
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Desk> {
+        self.desks.iter()
+    }
+
+    /// Output `i`, if the display has that many.
+    pub fn get(&self, i: usize) -> Option<&Desk> {
+        self.desks.get(i)
+    }
+
+    /// The first output — where the desktop starts, and where a question about
+    /// no output in particular is answered.
+    pub fn primary(&self) -> &Desk {
+        &self.desks[0]
+    }
+
+    /// The furniture's metrics, which are every output's: a window's frame is
+    /// the same shape on whichever monitor shows it.
+    pub fn chrome(&self) -> &Chrome {
+        &self.desks[0].chrome
+    }
+
+    /// The smallest rectangle holding every output. The part of it no output
+    /// covers is never shown.
+    pub fn bounds(&self) -> Rect {
+        self.desks.iter().fold(Rect::EMPTY, |acc, d| acc.union(d.screen))
+    }
+
+    /// The biggest single output, which is what bounds a window's buffer: a
+    /// client asks for a window that fits a screen, not one that fits the gap
+    /// between two.
+    pub fn largest(&self) -> Rect {
+        self.desks.iter().map(|d| d.screen).max_by_key(|s| s.area()).unwrap_or(Rect::EMPTY)
+    }
+
+    /// The output `p` is on, or the nearest one when it is on none.
+    pub fn at(&self, p: Point) -> usize {
+        self.desks
+            .iter()
+            .position(|d| d.screen.contains_point(p))
+            .unwrap_or_else(|| self.nearest(p))
+    }
+
+    /// The output a window with this frame belongs to: the one showing most of
+    /// it, or the one nearest its centre when none shows any.
+    ///
+    /// Ties go to the earlier output, so a window split exactly down a seam
+    /// maximizes onto the left-hand monitor every time rather than onto
+    /// whichever the last pixel of rounding favoured.
+    pub fn holding(&self, frame: Rect) -> usize {
+        let mut best = None;
+        let mut most = 0;
+        for (i, d) in self.desks.iter().enumerate() {
+            let area = d.screen.intersect(frame).area();
+            if area > most {
+                most = area;
+                best = Some(i);
+            }
+        }
+        best.unwrap_or_else(|| {
+            self.nearest(Point {
+                x: i32::midpoint(frame.x0, frame.x1),
+                y: i32::midpoint(frame.y0, frame.y1),
+            })
+        })
+    }
+
+    /// `p`, moved onto the nearest output if it is on none.
+    ///
+    /// The bounding box of two monitors of different heights has a corner
+    /// neither of them shows, and a pointer that could rest there is a pointer
+    /// the user cannot see.
+    pub fn confine(&self, p: Point) -> Point {
+        let s = self.desks[self.at(p)].screen;
+        Point {
+            x: p.x.clamp(s.x0, (s.x1 - 1).max(s.x0)),
+            y: p.y.clamp(s.y0, (s.y1 - 1).max(s.y0)),
+        }
+    }
+
+    fn nearest(&self, p: Point) -> usize {
+        let distance = |s: Rect| {
+            let dx = (s.x0 - p.x).max(p.x - (s.x1 - 1)).max(0) as i64;
+            let dy = (s.y0 - p.y).max(p.y - (s.y1 - 1)).max(0) as i64;
+            dx * dx + dy * dy
+        };
+        self.desks
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, d)| distance(d.screen))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+impl Index<usize> for Outputs {
+    type Output = Desk;
+
+    fn index(&self, i: usize) -> &Desk {
+        &self.desks[i]
+    }
+}
+
+/// Where the outputs go, given where they are and where the user last put
+/// some of them.
+///
+/// `saved` is what [`parse_arrangement`] read: an output index and a position.
+/// An output it names moves there; one it does not keeps its place in
+/// `current`. The answer is `None` — keep `current` — when the result would
+/// have two outputs overlap, which is what a file saved against a different
+/// set of monitors looks like: the same index, a different size.
+///
+/// The answer is translated so its bounding box starts at the origin, which
+/// is where the kernel puts it too: positions are only ever relative to each
+/// other, and comparing an answer with `current` has to compare like with
+/// like.
+pub fn arrange(current: &[Rect], saved: &[(usize, Point)]) -> Option<Vec<Rect>> {
+    let mut placed: Vec<Rect> = current.to_vec();
+    for &(index, at) in saved {
+        let Some(r) = placed.get_mut(index) else { continue };
+        *r = Rect::new(at.x, at.y, r.w(), r.h());
+    }
+    for (i, a) in placed.iter().enumerate() {
+        if a.is_empty() || placed[..i].iter().any(|b| b.overlaps(*a)) {
+            return None;
+        }
+    }
+    let bounds = placed.iter().fold(Rect::EMPTY, |acc, r| acc.union(*r));
+    Some(placed.iter().map(|r| r.translate(-bounds.x0, -bounds.y0)).collect())
+}
+
+/// The arrangement file, one output per line: its index, then its position.
+///
+/// Blank lines and `#` comments are skipped, and so is any line that does not
+/// parse — a hand-edited file with one bad line still places every monitor it
+/// got right.
+pub fn parse_arrangement(text: &str) -> Vec<(usize, Point)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let index = fields.next()?.parse().ok()?;
+            let x = fields.next()?.parse().ok()?;
+            let y = fields.next()?.parse().ok()?;
+            fields.next().is_none().then_some((index, Point { x, y }))
+        })
+        .collect()
+}
+
+/// The arrangement file for `outputs`, which [`parse_arrangement`] reads back.
+pub fn format_arrangement(outputs: &[Rect]) -> String {
+    let mut text = String::from("# output x y\n");
+    for (i, r) in outputs.iter().enumerate() {
+        let _ = writeln!(text, "{i} {} {}", r.x0, r.y0);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn desk(screen: Rect) -> Desk {
+        Desk { chrome: Chrome::DEFAULT, screen, font_w: 8, apps: 2 }
+    }
+
+    /// A laptop panel with a taller monitor docked to its right.
+    fn docked() -> Outputs {
+        Outputs::new(vec![desk(Rect::new(0, 0, 1280, 720)), desk(Rect::new(1280, 0, 1920, 1080))])
+    }
+
+    fn at(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    #[test]
+    fn the_bounds_span_every_output_and_the_largest_is_one_of_them() {
+        let o = docked();
+        assert_eq!(o.bounds(), Rect::new(0, 0, 3200, 1080));
+        assert_eq!(o.largest(), Rect::new(1280, 0, 1920, 1080));
+    }
+
+    #[test]
+    fn a_point_belongs_to_the_output_it_is_on() {
+        let o = docked();
+        assert_eq!(o.at(at(1279, 10)), 0);
+        assert_eq!(o.at(at(1280, 10)), 1);
+    }
+
+    #[test]
+    fn the_corner_no_monitor_shows_belongs_to_the_nearest_and_the_pointer_cannot_rest_there() {
+        let o = docked();
+        // Under the laptop panel, beside the tall monitor's lower half.
+        let hidden = at(1270, 1000);
+        assert_eq!(o.at(hidden), 1);
+        let p = o.confine(hidden);
+        assert!(o.iter().any(|d| d.screen.contains_point(p)), "{p:?}");
+        assert_eq!(p, at(1280, 1000));
+        // On a screen already, it does not move.
+        assert_eq!(o.confine(at(5, 5)), at(5, 5));
+    }
+
+    #[test]
+    fn a_window_belongs_to_the_output_showing_most_of_it() {
+        let o = docked();
+        assert_eq!(o.holding(Rect::new(1200, 100, 400, 300)), 1);
+        assert_eq!(o.holding(Rect::new(1000, 100, 200, 300)), 0);
+        // Split down the seam: the earlier output, every time.
+        assert_eq!(o.holding(Rect::new(1080, 100, 400, 300)), 0);
+        // Shown by neither: the one nearest its centre.
+        assert_eq!(o.holding(Rect::new(100, 800, 200, 100)), 0);
+        assert_eq!(o.holding(Rect::new(5000, 100, 200, 100)), 1);
+    }
+
+    #[test]
+    fn a_saved_arrangement_moves_the_outputs_it_names_and_starts_at_the_origin() {
+        let current = [Rect::new(0, 0, 1280, 720), Rect::new(1280, 0, 1920, 1080)];
+        // The monitor to the left of the laptop, bottoms aligned.
+        let placed = arrange(&current, &[(1, at(-1920, -360))]).unwrap();
+        assert_eq!(placed, vec![Rect::new(1920, 360, 1280, 720), Rect::new(0, 0, 1920, 1080)]);
+    }
+
+    #[test]
+    fn an_arrangement_that_overlaps_or_names_a_missing_output_is_not_applied_blindly() {
+        let current = [Rect::new(0, 0, 1280, 720), Rect::new(1280, 0, 1920, 1080)];
+        assert_eq!(arrange(&current, &[(1, at(640, 0))]), None);
+        // An index past the end is a monitor that is not plugged in: ignored.
+        assert_eq!(arrange(&current, &[(7, at(0, 0))]).unwrap(), current.to_vec());
+    }
+
+    #[test]
+    fn the_file_round_trips_and_a_bad_line_costs_only_itself() {
+        let outputs = [Rect::new(1920, 360, 1280, 720), Rect::new(0, 0, 1920, 1080)];
+        let text = format_arrangement(&outputs);
+        assert_eq!(parse_arrangement(&text), vec![(0, at(1920, 360)), (1, at(0, 0))]);
+
+        let edited = "0 0 0\n1 right of 0\n\n# a comment\n2 -10 5\n3 1 2 3\n";
+        assert_eq!(parse_arrangement(edited), vec![(0, at(0, 0)), (2, at(-10, 5))]);
+    }
+}
@@ -36,7 +36,7 @@ impl Taskbar {
     /// One window's tab, whether or not it is on screen.
     pub fn tab(&self, i: usize) -> Rect {
         let strip = self.strip();
-        let x = self.desk.chrome.taskbar_item * i as i32;
+        let x = strip.x0 + self.desk.chrome.taskbar_item * i as i32;
         Rect::corners(x, strip.y0, x + self.desk.chrome.taskbar_item, strip.y1)
     }
 
@@ -57,7 +57,7 @@ impl Taskbar {
     /// The `+` button that opens the launcher, square and after the last tab.
     pub fn new_button(&self) -> Rect {
         let strip = self.strip();
-        let x = self.desk.chrome.taskbar_item * self.windows as i32;
+        let x = strip.x0 + self.desk.chrome.taskbar_item * self.windows as i32;
         Rect::corners(x, strip.y0, x + self.desk.chrome.taskbar, strip.y1)
     }
 
@@ -86,8 +86,8 @@ impl Taskbar {
 
     /// The launcher popup, which grows upward from the `+` button.
     pub fn launcher(&self) -> Rect {
+        let x = self.new_button().x0;
         let strip = self.strip();
-        let x = self.desk.chrome.taskbar_item * self.windows as i32;
         let h = self.desk.chrome.launcher_item * self.desk.apps as i32;
         Rect::corners(x, strip.y0 - h, x + self.desk.chrome.launcher_width, strip.y0)
     }
@@ -166,6 +166,24 @@ mod tests {
         assert!(b.strip().contains(b.status()));
     }
 
+    /// A second monitor's bar starts at that monitor's left edge, tabs and
+    /// launcher with it — not at the left edge of the desktop.
+    #[test]
+    fn a_bar_on_an_output_to_the_right_is_laid_out_from_its_own_edge() {
+        let desk = Desk {
+            chrome: Chrome::DEFAULT,
+            screen: Rect::new(1920, 0, 1280, 720),
+            font_w: 8,
+            apps: 2,
+        };
+        let b = desk.taskbar(2);
+        assert_eq!(b.tab(0).x0, 1920);
+        for r in [b.tab(1), b.new_button(), b.status(), b.launcher()] {
+            assert!(r.x0 >= 1920 && r.x1 <= 3200, "{r:?}");
+        }
+        assert_eq!(b.launcher().x0, b.new_button().x0);
+    }
+
     #[test]
     fn the_launcher_sits_on_the_bar_and_grows_upward() {
         let b = bar(2);
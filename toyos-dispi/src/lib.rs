@@ -0,0 +1,22 @@
+//! The Bochs VBE display interface ("DISPI") that QEMU's standard VGA and
+//! `bochs-display` implement, as decisions separated from their effects.
+//!
+//! The kernel reads a register, asks this crate what it means, and writes what
+//! this crate says to write; nothing here touches MMIO. What is decided here
+//! is every number between a requested resolution and the registers that set
+//! it:
+//!
+//! - [`regs`]: where the registers are in the MMIO BAR, their indices, the
+//!   enable bits, and which interface versions this driver accepts.
+//! - [`mode`]: what the device can show, whether a mode fits it, the writes
+//!   that set one, and whether the device's read-back says it took.
+//!
+//! `no_std`, no allocation, no `unsafe`.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+pub mod mode;
+pub mod regs;
+
+pub use mode::{Caps, Mode, Refusal, State};
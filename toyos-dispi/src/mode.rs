@@ -0,0 +1,250 @@
+//! Modes: what the device can show, whether a mode fits, how to set one and
+//! whether it took.
+//!
+//! Every mode this driver sets is 32 bits per pixel with the line pitch equal
+//! to the width — the layout the compositor draws in, so a frame is one copy
+//! and never a conversion. That fixes the stride, and the one remaining bound
+//! is that `width * height * 4` fits the video memory: the device's maxima
+//! from `GETCAPS` are per-axis and say nothing about the product.
+
+use crate::regs::{
+    ENABLED, GETCAPS, INDEX_BANK, INDEX_BPP, INDEX_ENABLE, INDEX_VIRT_WIDTH, INDEX_XRES,
+    INDEX_X_OFFSET, INDEX_YRES, INDEX_Y_OFFSET, LFB_ENABLED,
+};
+
+/// The only depth this driver sets or keeps.
+pub const BPP: u16 = 32;
+const BYTES_PER_PIXEL: u64 = BPP as u64 / 8;
+
+/// What the device can show, read once at probe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Caps {
+    pub max_width: u16,
+    pub max_height: u16,
+    pub vram_bytes: u64,
+}
+
+impl Caps {
+    /// Caps from the registers read with [`GETCAPS`] set and the video memory
+    /// register. `None` for a device this driver cannot use: one that cannot
+    /// do 32 bits per pixel, or that reports no memory or no resolution.
+    pub fn from_regs(max_x: u16, max_y: u16, max_bpp: u16, vram_64k: u16) -> Option<Caps> {
+        if max_bpp < BPP || vram_64k == 0 || max_x == 0 || max_y == 0 {
+            return None;
+        }
+        Some(Caps { max_width: max_x, max_height: max_y, vram_bytes: u64::from(vram_64k) << 16 })
+    }
+}
+
+/// A mode, with the stride the device scans it out at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mode {
+    pub width: u16,
+    pub height: u16,
+    /// Bytes per line.
+    pub stride: u32,
+}
+
+impl Mode {
+    /// Bytes the mode scans out of video memory.
+    pub fn bytes(&self) -> u64 {
+        u64::from(self.stride) * u64::from(self.height)
+    }
+}
+
+/// Why [`plan`] refused a resolution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Refusal {
+    /// Zero width or height.
+    Empty,
+    /// Wider than the device's maximum.
+    TooWide,
+    /// Taller than the device's maximum.
+    TooTall,
+    /// Within both maxima, but the frame does not fit the video memory.
+    NoMemory,
+}
+
+/// The mode that shows `width` × `height` on a device with `caps`, or why
+/// there is none. Sizes past `u16` are refused as too wide or too tall rather
+/// than truncated into a mode nobody asked for.
+pub fn plan(caps: Caps, width: u32, height: u32) -> Result<Mode, Refusal> {
+    if width == 0 || height == 0 {
+        return Err(Refusal::Empty);
+    }
+    if width > u32::from(caps.max_width) {
+        return Err(Refusal::TooWide);
+    }
+    if height > u32::from(caps.max_height) {
+        return Err(Refusal::TooTall);
+    }
+    // Both fit in u16 now, so neither conversion nor the stride overflows.
+    let mode = Mode {
+        width: width as u16,
+        height: height as u16,
+        stride: (u64::from(width) * BYTES_PER_PIXEL) as u32,
+    };
+    if mode.bytes() > caps.vram_bytes {
+        return Err(Refusal::NoMemory);
+    }
+    Ok(mode)
+}
+
+/// The mode registers as read back, with [`GETCAPS`] clear.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct State {
+    pub xres: u16,
+    pub yres: u16,
+    pub bpp: u16,
+    pub virt_width: u16,
+    pub enable: u16,
+}
+
+/// The mode the device is scanning out, if it is one this driver can draw
+/// into: the DISPI mode on, through the linear framebuffer, at 32 bits per
+/// pixel. Anything else — legacy VGA text, a banked mode, 16 bits per pixel —
+/// is `None`, and the caller sets a mode of its own.
+///
+/// The stride is the virtual width's, which is what the device scans at; a
+/// virtual width narrower than the mode is not one the device would accept,
+/// so it is taken as the register not meaning what it says.
+pub fn current(state: State) -> Option<Mode> {
+    let on = ENABLED | LFB_ENABLED;
+    if state.enable & on != on || state.enable & GETCAPS != 0 || state.bpp != BPP {
+        return None;
+    }
+    if state.xres == 0 || state.yres == 0 || state.virt_width < state.xres {
+        return None;
+    }
+    Some(Mode {
+        width: state.xres,
+        height: state.yres,
+        stride: (u64::from(state.virt_width) * BYTES_PER_PIXEL) as u32,
+    })
+}
+
+/// The register writes that set `mode`, as `(index, value)` in the order they
+/// must be made.
+///
+/// The mode is turned off first: QEMU recomputes the scanout on every write
+/// while it is enabled, and a resolution larger than the old virtual width
+/// would be briefly scanned at a pitch that does not match it. The enable is
+/// the last write, so the device switches once, to the finished mode. Video
+/// memory is cleared by that write — [`NO_CLEAR_MEM`](crate::regs::NO_CLEAR_MEM)
+/// is left off, because the old frame at a new pitch is noise, not content.
+pub fn writes(mode: Mode) -> [(u16, u16); 9] {
+    [
+        (INDEX_ENABLE, 0),
+        (INDEX_BPP, BPP),
+        (INDEX_XRES, mode.width),
+        (INDEX_YRES, mode.height),
+        (INDEX_VIRT_WIDTH, mode.width),
+        (INDEX_BANK, 0),
+        (INDEX_X_OFFSET, 0),
+        (INDEX_Y_OFFSET, 0),
+        (INDEX_ENABLE, ENABLED | LFB_ENABLED),
+    ]
+}
+
+/// Whether the read-back `state` is `mode`. The device clamps what it cannot
+/// do rather than refusing it, so a write it did not take shows up only here.
+pub fn took(mode: Mode, state: State) -> bool {
+    current(state) == Some(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A std VGA with QEMU's default 16 MiB of video memory.
+    const QEMU: Caps = Caps { max_width: 2560, max_height: 1600, vram_bytes: 16 << 20 };
+
+    fn read_back(mode: Mode) -> State {
+        State {
+            xres: mode.width,
+            yres: mode.height,
+            bpp: BPP,
+            virt_width: (mode.stride / 4) as u16,
+            enable: ENABLED | LFB_ENABLED,
+        }
+    }
+
+    #[test]
+    fn caps_need_32bpp_and_memory() {
+        assert_eq!(
+            Caps::from_regs(2560, 1600, 32, 256),
+            Some(Caps { max_width: 2560, max_height: 1600, vram_bytes: 16 << 20 })
+        );
+        assert_eq!(Caps::from_regs(2560, 1600, 16, 256), None);
+        assert_eq!(Caps::from_regs(2560, 1600, 32, 0), None);
+        assert_eq!(Caps::from_regs(0, 1600, 32, 256), None);
+    }
+
+    #[test]
+    fn a_mode_within_the_caps_is_packed_at_four_bytes_a_pixel() {
+        assert_eq!(plan(QEMU, 1920, 1080), Ok(Mode { width: 1920, height: 1080, stride: 7680 }));
+    }
+
+    #[test]
+    fn per_axis_maxima_are_enforced() {
+        assert_eq!(plan(QEMU, 2561, 100), Err(Refusal::TooWide));
+        assert_eq!(plan(QEMU, 100, 1601), Err(Refusal::TooTall));
+        assert_eq!(plan(QEMU, 70_000, 100), Err(Refusal::TooWide));
+        assert_eq!(plan(QEMU, 0, 100), Err(Refusal::Empty));
+        assert_eq!(plan(QEMU, 100, 0), Err(Refusal::Empty));
+    }
+
+    #[test]
+    fn a_mode_within_both_maxima_can_still_outgrow_memory() {
+        // 2560x1600x4 is 16000 KiB: fits 16 MiB, not 8.
+        assert!(plan(QEMU, 2560, 1600).is_ok());
+        let small = Caps { vram_bytes: 8 << 20, ..QEMU };
+        assert_eq!(plan(small, 2560, 1600), Err(Refusal::NoMemory));
+        assert!(plan(small, 1920, 1080).is_ok());
+    }
+
+    #[test]
+    fn only_an_enabled_linear_32bpp_mode_is_current() {
+        let mode = plan(QEMU, 1024, 768).unwrap();
+        assert_eq!(current(read_back(mode)), Some(mode));
+        assert_eq!(current(State { enable: ENABLED, ..read_back(mode) }), None);
+        assert_eq!(current(State { enable: 0, ..read_back(mode) }), None);
+        assert_eq!(current(State { bpp: 16, ..read_back(mode) }), None);
+        assert_eq!(
+            current(State { enable: ENABLED | LFB_ENABLED | GETCAPS, ..read_back(mode) }),
+            None
+        );
+        assert_eq!(current(State::default()), None);
+    }
+
+    #[test]
+    fn the_current_stride_is_the_virtual_width() {
+        let state = State { virt_width: 1280, ..read_back(plan(QEMU, 1024, 768).unwrap()) };
+        assert_eq!(current(state), Some(Mode { width: 1024, height: 768, stride: 5120 }));
+        let state = State { virt_width: 800, ..state };
+        assert_eq!(current(state), None);
+    }
+
+    #[test]
+    fn writes_disable_first_and_leave_the_enable_for_last() {
+        let mode = plan(QEMU, 800, 600).unwrap();
+        let w = writes(mode);
+        assert_eq!(w[0], (INDEX_ENABLE, 0));
+        assert_eq!(w[w.len() - 1], (INDEX_ENABLE, ENABLED | LFB_ENABLED));
+        assert!(w[1..w.len() - 1].iter().all(|&(index, _)| index != INDEX_ENABLE));
+        assert!(w.contains(&(INDEX_XRES, 800)));
+        assert!(w.contains(&(INDEX_YRES, 600)));
+        assert!(w.contains(&(INDEX_VIRT_WIDTH, 800)));
+        assert!(w.contains(&(INDEX_BPP, 32)));
+    }
+
+    #[test]
+    fn a_clamped_read_back_is_not_the_mode() {
+        let mode = plan(QEMU, 1920, 1080).unwrap();
+        assert!(took(mode, read_back(mode)));
+        let clamped = State { xres: 1600, ..read_back(mode) };
+        assert!(!took(mode, clamped));
+        let off = State { enable: 0, ..read_back(mode) };
+        assert!(!took(mode, off));
+    }
+}
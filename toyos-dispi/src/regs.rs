@@ -0,0 +1,83 @@
+//! The DISPI register file, as QEMU lays it out behind BAR 2.
+//!
+//! BAR 2 is 4 KiB of MMIO holding three things a driver reads: the EDID blob
+//! at offset 0, the legacy VGA ports at `0x400` and the DISPI registers at
+//! [`DISPI_BASE`], one 16-bit register per index. The same registers are
+//! reachable through I/O ports `0x1CE`/`0x1CF` on a standard VGA, but
+//! `bochs-display` has no ports at all, so the MMIO window is the one
+//! interface both devices share.
+
+/// Offset of the EDID blob in BAR 2.
+pub const EDID_BASE: u64 = 0x000;
+/// How much of it is read: a base block and one extension, which is what
+/// QEMU generates — the extension is the DisplayID block it adds for modes
+/// past what the base block can describe.
+pub const EDID_BYTES: usize = 256;
+
+/// Offset of register index 0 in BAR 2.
+pub const DISPI_BASE: u64 = 0x500;
+
+pub const INDEX_ID: u16 = 0x0;
+pub const INDEX_XRES: u16 = 0x1;
+pub const INDEX_YRES: u16 = 0x2;
+pub const INDEX_BPP: u16 = 0x3;
+pub const INDEX_ENABLE: u16 = 0x4;
+pub const INDEX_BANK: u16 = 0x5;
+pub const INDEX_VIRT_WIDTH: u16 = 0x6;
+pub const INDEX_VIRT_HEIGHT: u16 = 0x7;
+pub const INDEX_X_OFFSET: u16 = 0x8;
+pub const INDEX_Y_OFFSET: u16 = 0x9;
+/// Video memory in 64 KiB units. Interface [`ID4`] and later.
+pub const INDEX_VIDEO_MEMORY_64K: u16 = 0xA;
+
+/// [`INDEX_ENABLE`]: the DISPI mode is on and legacy VGA is off.
+pub const ENABLED: u16 = 0x01;
+/// [`INDEX_ENABLE`]: while set, `XRES`, `YRES` and `BPP` read back the
+/// device's maxima rather than the mode.
+pub const GETCAPS: u16 = 0x02;
+/// [`INDEX_ENABLE`]: the framebuffer is the linear one in BAR 0 rather than a
+/// 64 KiB bank window.
+pub const LFB_ENABLED: u16 = 0x40;
+/// [`INDEX_ENABLE`]: do not clear video memory on the mode set.
+pub const NO_CLEAR_MEM: u16 = 0x80;
+
+/// Interface version 3: the first with [`GETCAPS`].
+pub const ID3: u16 = 0xB0C3;
+/// Interface version 4: the first that reports its video memory.
+pub const ID4: u16 = 0xB0C4;
+/// The newest version QEMU reports.
+pub const ID5: u16 = 0xB0C5;
+
+/// Where register `index` is in BAR 2.
+pub const fn offset(index: u16) -> u64 {
+    DISPI_BASE + index as u64 * 2
+}
+
+/// Whether the interface version in [`INDEX_ID`] is one this driver can use:
+/// it must say how much video memory there is, because that is the bound on
+/// every mode, and it must be a DISPI interface at all — a register window
+/// that reads back `0xFFFF` is not one.
+pub fn supported(id: u16) -> bool {
+    (ID4..=ID5).contains(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers_are_sixteen_bits_apart_from_the_dispi_base() {
+        assert_eq!(offset(INDEX_ID), 0x500);
+        assert_eq!(offset(INDEX_ENABLE), 0x508);
+        assert_eq!(offset(INDEX_VIDEO_MEMORY_64K), 0x514);
+    }
+
+    #[test]
+    fn only_an_interface_that_reports_its_memory_is_driven() {
+        assert!(supported(ID4));
+        assert!(supported(ID5));
+        assert!(!supported(ID3));
+        assert!(!supported(0xFFFF));
+        assert!(!supported(0));
+    }
+}
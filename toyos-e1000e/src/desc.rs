@@ -0,0 +1,149 @@
+//! The legacy receive and transmit descriptors, 16 bytes each.
+//!
+//! **As two quadwords and not as a struct,** because that is how the driver
+//! moves them: one 64-bit store for the buffer address, one for the rest, each
+//! a single volatile access the device cannot observe half of. The fields are
+//! taken out of the second quadword here, once, so the driver never shifts a
+//! device-written word itself.
+
+use toyos_untrusted::Untrusted;
+
+/// Descriptor Done: the device has finished with this descriptor and written
+/// the rest of it back. Bit 0 of the status byte in both directions.
+const STA_DD: u8 = 1 << 0;
+/// End Of Packet: this descriptor holds the last byte of the frame.
+const STA_EOP: u8 = 1 << 1;
+
+/// Receive errors that mean the frame's bytes are not the frame that was
+/// sent: CRC (`CE`), symbol (`SE`), sequence (`SEQ`), carrier extension
+/// (`CXE`) and data (`RXE`). The two checksum-offload bits are left out — this
+/// driver does not enable the offload, and with it off they mean nothing.
+pub const RX_ERRORS: u8 = 0x01 | 0x02 | 0x04 | 0x10 | 0x80;
+
+/// A receive descriptor as the device wrote it back.
+#[derive(Clone, Copy, Debug)]
+pub struct RxDesc {
+    length: Untrusted<u16>,
+    status: u8,
+    errors: u8,
+}
+
+impl RxDesc {
+    /// The two quadwords to store when posting the buffer at `phys`: the
+    /// address, and a zero second word so a stale `DD` from the last time the
+    /// slot was written back cannot be read as this posting's.
+    pub const fn post(phys: u64) -> [u64; 2] {
+        [phys, 0]
+    }
+
+    /// Decode the second quadword. The first is the buffer address, which the
+    /// device does not write.
+    pub fn from_word(word: u64) -> Self {
+        Self {
+            length: Untrusted::new(word as u16),
+            status: (word >> 32) as u8,
+            errors: (word >> 40) as u8,
+        }
+    }
+
+    /// Whether the device has written this descriptor back.
+    pub fn done(&self) -> bool {
+        self.status & STA_DD != 0
+    }
+
+    pub fn end_of_packet(&self) -> bool {
+        self.status & STA_EOP != 0
+    }
+
+    /// The error bits among [`RX_ERRORS`], zero for a clean frame.
+    pub fn errors(&self) -> u8 {
+        self.errors & RX_ERRORS
+    }
+
+    /// The byte count the device says it wrote, not yet compared with the
+    /// buffer it wrote into.
+    pub fn length(&self) -> Untrusted<u16> {
+        self.length
+    }
+}
+
+/// Transmit command bits: end of packet, insert the FCS, report status.
+const CMD_EOP: u8 = 1 << 0;
+const CMD_IFCS: u8 = 1 << 1;
+const CMD_RS: u8 = 1 << 3;
+
+/// A transmit descriptor as the device wrote it back.
+#[derive(Clone, Copy, Debug)]
+pub struct TxDesc {
+    status: u8,
+}
+
+impl TxDesc {
+    /// The two quadwords for a whole frame of `len` bytes at `phys`.
+    ///
+    /// `RS` is what makes the device write `DD` back at all, and `DD` is the
+    /// only way this driver learns the buffer may be written again.
+    pub const fn frame(phys: u64, len: u16) -> [u64; 2] {
+        let cmd = CMD_EOP | CMD_IFCS | CMD_RS;
+        [phys, len as u64 | (cmd as u64) << 24]
+    }
+
+    pub fn from_word(word: u64) -> Self {
+        Self { status: (word >> 32) as u8 }
+    }
+
+    pub fn done(&self) -> bool {
+        self.status & STA_DD != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The second quadword of a receive write-back.
+    fn rx_word(length: u16, status: u8, errors: u8) -> u64 {
+        length as u64 | (status as u64) << 32 | (errors as u64) << 40
+    }
+
+    #[test]
+    fn posting_clears_the_written_back_half() {
+        assert_eq!(RxDesc::post(0x1234_5000), [0x1234_5000, 0]);
+        assert!(!RxDesc::from_word(RxDesc::post(0x1000)[1]).done());
+    }
+
+    #[test]
+    fn receive_write_back_decodes_every_field() {
+        let d = RxDesc::from_word(rx_word(60, STA_DD | STA_EOP, 0));
+        assert!(d.done() && d.end_of_packet());
+        assert_eq!(d.errors(), 0);
+        assert!(d.length().is(60));
+    }
+
+    #[test]
+    fn checksum_bits_are_not_frame_errors() {
+        // TCPE and IPE, which only mean something with the offload on.
+        let d = RxDesc::from_word(rx_word(60, STA_DD | STA_EOP, 0x20 | 0x40));
+        assert_eq!(d.errors(), 0);
+        let d = RxDesc::from_word(rx_word(60, STA_DD | STA_EOP, 0x01));
+        assert_eq!(d.errors(), 0x01);
+    }
+
+    #[test]
+    fn the_checksum_field_does_not_reach_the_length() {
+        // Bits 31:16 are the packet checksum, which the device writes whether
+        // or not anyone asked.
+        let d = RxDesc::from_word(0xBEEF_0040 | (STA_DD as u64) << 32);
+        assert!(d.length().is(0x40));
+    }
+
+    #[test]
+    fn transmit_frame_requests_status_and_fcs() {
+        let [addr, word] = TxDesc::frame(0xABC000, 1514);
+        assert_eq!(addr, 0xABC000);
+        assert_eq!(word & 0xFFFF, 1514);
+        assert_eq!((word >> 24) as u8, CMD_EOP | CMD_IFCS | CMD_RS);
+        assert!(!TxDesc::from_word(word).done());
+        assert!(TxDesc::from_word(word | 1 << 32).done());
+    }
+}
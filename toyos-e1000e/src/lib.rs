@@ -0,0 +1,29 @@
+//! The Intel 82574 (e1000e) and its legacy descriptor rings, as decisions
+//! separated from their effects.
+//!
+//! The kernel reads a register or a written-back descriptor, asks this crate
+//! what it means, and acts; nothing here touches MMIO or DMA. What is decided
+//! here is every number the driver takes from the device and turns into a
+//! length, an index or a verdict on a frame:
+//!
+//! - [`regs`]: register offsets and the control words this driver programs,
+//!   and what `STATUS` says about the link.
+//! - [`desc`]: the 16-byte receive and transmit descriptors, as the two
+//!   quadwords the driver stores and the fields the device writes back.
+//! - [`ring`]: which receive descriptor is next, whether what the device wrote
+//!   there is a frame netd may read, and when the one transmit buffer is free.
+//! - [`mac`]: the station address out of receive-address register 0.
+//!
+//! `no_std`, no allocation, no `unsafe`.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+pub mod desc;
+pub mod mac;
+pub mod regs;
+pub mod ring;
+
+pub use desc::{RxDesc, TxDesc};
+pub use regs::Link;
+pub use ring::{Discard, Reaped, RxRing, TxRing};
@@ -0,0 +1,87 @@
+//! The station address, out of receive-address register 0.
+//!
+//! The device loads `RAL0`/`RAH0` from its NVM after every reset and sets the
+//! address-valid bit when it has. That is the only source this driver reads:
+//! an 82574 whose NVM did not provide an address has no identity the driver
+//! could honestly give it, and inventing one would put a second machine on the
+//! wire with whatever address was picked.
+
+/// Address Valid, bit 31 of `RAH`.
+const RAH_AV: u32 = 1 << 31;
+
+/// Why receive-address register 0 holds no address this driver will use.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Unusable {
+    /// `AV` is clear: the NVM did not load one.
+    NotValid,
+    /// All zeros, which no interface is.
+    Zero,
+    /// The group bit is set, so it is a multicast address and not a station.
+    Multicast([u8; 6]),
+}
+
+impl core::fmt::Display for Unusable {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NotValid => f.write_str("its receive address is not marked valid"),
+            Self::Zero => f.write_str("its receive address is all zeros"),
+            Self::Multicast(m) => write!(
+                f,
+                "its receive address {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} is a multicast one",
+                m[0], m[1], m[2], m[3], m[4], m[5]
+            ),
+        }
+    }
+}
+
+/// The address in `RAL0` (the first four bytes, lowest first) and `RAH0` (the
+/// last two, and `AV`).
+pub fn from_receive_address(ral: u32, rah: u32) -> Result<[u8; 6], Unusable> {
+    if rah & RAH_AV == 0 {
+        return Err(Unusable::NotValid);
+    }
+    let [a, b, c, d] = ral.to_le_bytes();
+    let [e, f, ..] = rah.to_le_bytes();
+    let mac = [a, b, c, d, e, f];
+    if mac == [0; 6] {
+        return Err(Unusable::Zero);
+    }
+    if a & 1 != 0 {
+        return Err(Unusable::Multicast(mac));
+    }
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qemu_default_address_decodes_in_wire_order() {
+        // 52:54:00:12:34:56, as QEMU's NVM loads it.
+        assert_eq!(
+            from_receive_address(0x1200_5452, RAH_AV | 0x5634),
+            Ok([0x52, 0x54, 0x00, 0x12, 0x34, 0x56])
+        );
+    }
+
+    #[test]
+    fn the_high_half_of_rah_is_not_address() {
+        // Address-select and pool bits sit between the address and AV.
+        assert_eq!(
+            from_receive_address(0x1200_5452, RAH_AV | 0x0003_0000 | 0x5634),
+            Ok([0x52, 0x54, 0x00, 0x12, 0x34, 0x56])
+        );
+    }
+
+    #[test]
+    fn an_address_not_marked_valid_is_refused() {
+        assert_eq!(from_receive_address(0x1200_5452, 0x5634), Err(Unusable::NotValid));
+    }
+
+    #[test]
+    fn zero_and_multicast_are_refused() {
+        assert_eq!(from_receive_address(0, RAH_AV), Err(Unusable::Zero));
+        assert!(matches!(from_receive_address(0x0000_0001, RAH_AV), Err(Unusable::Multicast(_))));
+    }
+}
@@ -0,0 +1,183 @@
+//! Register offsets into BAR 0, and the control words this driver writes.
+//!
+//! Offsets are `u64` because that is what the kernel's `Mmio` takes; the
+//! values written at them are the device's 32-bit registers.
+//!
+//! Only what the driver uses is named. The 82574 has several hundred
+//! registers and a driver that enables one queue in each direction, takes one
+//! interrupt cause and never offloads anything touches two dozen of them; a
+//! constant nobody reads is a constant nobody checked.
+
+/// Device Control.
+pub const CTRL: u64 = 0x0000;
+/// Device Status.
+pub const STATUS: u64 = 0x0008;
+/// NVM (EEPROM) Control.
+pub const EECD: u64 = 0x0010;
+/// Extended Device Control.
+pub const CTRL_EXT: u64 = 0x0018;
+/// Interrupt Cause Read. Write-1-to-clear.
+pub const ICR: u64 = 0x00C0;
+/// Interrupt Mask Set.
+pub const IMS: u64 = 0x00D0;
+/// Interrupt Mask Clear.
+pub const IMC: u64 = 0x00D8;
+/// Interrupt Vector Allocation: which MSI-X entry each cause is sent on.
+pub const IVAR: u64 = 0x00E4;
+/// Receive Control.
+pub const RCTL: u64 = 0x0100;
+/// Transmit Control.
+pub const TCTL: u64 = 0x0400;
+/// Transmit Inter-Packet Gap.
+pub const TIPG: u64 = 0x0410;
+/// The multicast table, 128 dwords of hash bits.
+pub const MTA: u64 = 0x5200;
+pub const MTA_DWORDS: u64 = 128;
+/// Receive Address Low and High for entry 0: the station address.
+pub const RAL0: u64 = 0x5400;
+pub const RAH0: u64 = 0x5404;
+
+/// Receive queue 0: base address, length in bytes, head and tail.
+pub const RDBAL: u64 = 0x2800;
+pub const RDBAH: u64 = 0x2804;
+pub const RDLEN: u64 = 0x2808;
+pub const RDH: u64 = 0x2810;
+pub const RDT: u64 = 0x2818;
+/// Transmit queue 0, the same four.
+pub const TDBAL: u64 = 0x3800;
+pub const TDBAH: u64 = 0x3804;
+pub const TDLEN: u64 = 0x3808;
+pub const TDH: u64 = 0x3810;
+pub const TDT: u64 = 0x3818;
+
+/// How much of BAR 0 the offsets above reach into, rounded to a page.
+pub const BAR0_BYTES: u64 = 0x6000;
+
+pub const CTRL_SLU: u32 = 1 << 6;
+/// Self-clearing: the device resets and clears the bit when it is done.
+pub const CTRL_RST: u32 = 1 << 26;
+
+/// The NVM auto-read after reset has finished, so `RAL0`/`RAH0` hold the
+/// address the NVM carries rather than whatever the reset left.
+pub const EECD_AUTO_RD: u32 = 1 << 9;
+
+/// Pending-bit-array bits clear when their MSI-X message is sent, which is
+/// what a driver that never reads the PBA wants.
+pub const CTRL_EXT_PBA_CLR: u32 = 1 << 31;
+
+/// A receive descriptor written back, in INTx and MSI mode.
+pub const ICR_RXT0: u32 = 1 << 7;
+/// The same event, as it is named in MSI-X mode, where it is routed by
+/// [`IVAR`] rather than raised on the one message.
+pub const ICR_RXQ0: u32 = 1 << 20;
+/// Every cause this driver takes: receive, under both of its names.
+pub const ICR_RX: u32 = ICR_RXT0 | ICR_RXQ0;
+
+/// `IVAR` routing receive queue 0 to MSI-X entry `entry` and nothing else.
+/// Transmit and the "other" causes are left unrouted: transmit completion is
+/// polled on the descriptor, and the link is read when it is asked about.
+pub const fn ivar_rx_only(entry: u16) -> u32 {
+    const VALID: u32 = 1 << 3;
+    (entry as u32 & 0x7) | VALID
+}
+
+const RCTL_EN: u32 = 1 << 1;
+const RCTL_BAM: u32 = 1 << 15;
+const RCTL_SECRC: u32 = 1 << 26;
+
+/// The receive control word: enabled, broadcasts accepted, the CRC stripped
+/// so a frame's length is the frame's, 2048-byte buffers (`BSIZE` 00 with
+/// `BSEX` clear) and legacy descriptors (`DTYP` 00).
+///
+/// No promiscuous or multicast-promiscuous bit, and no long-packet bit: a
+/// frame longer than a standard one does not fit a buffer, and the device
+/// drops it rather than splitting it across several, which is one discard
+/// the ring never has to make.
+pub const fn rctl() -> u32 {
+    RCTL_EN | RCTL_BAM | RCTL_SECRC
+}
+
+const TCTL_EN: u32 = 1 << 1;
+const TCTL_PSP: u32 = 1 << 3;
+
+/// The transmit control word: enabled, short frames padded to the minimum,
+/// and the collision threshold and distance the datasheet recommends for
+/// full duplex (`CT` 0x0F, `COLD` 0x3F).
+pub const fn tctl() -> u32 {
+    TCTL_EN | TCTL_PSP | (0x0F << 4) | (0x3F << 12)
+}
+
+/// The inter-packet gap for copper: `IPGT` 8, `IPGR1` 8, `IPGR2` 6.
+pub const TIPG_COPPER: u32 = 8 | (8 << 10) | (6 << 20);
+
+/// What `STATUS` says about the link.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Link {
+    Down,
+    Up { mbps: u16, full_duplex: bool },
+}
+
+impl Link {
+    pub fn from_status(status: u32) -> Self {
+        const FD: u32 = 1 << 0;
+        const LU: u32 = 1 << 1;
+        if status & LU == 0 {
+            return Self::Down;
+        }
+        // `SPEED` is bits 7:6; 11 is reserved and reads as gigabit, which is
+        // what every driver for this part takes it to be.
+        let mbps = match (status >> 6) & 0b11 {
+            0b00 => 10,
+            0b01 => 100,
+            _ => 1000,
+        };
+        Self::Up { mbps, full_duplex: status & FD != 0 }
+    }
+}
+
+impl core::fmt::Display for Link {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Down => f.write_str("link down"),
+            Self::Up { mbps, full_duplex } => {
+                write!(f, "link up, {mbps} Mb/s {}", if *full_duplex { "full duplex" } else { "half duplex" })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receive_control_uses_2k_buffers_and_legacy_descriptors() {
+        // BSIZE (17:16), BSEX (25) and DTYP (11:10) all zero.
+        assert_eq!(rctl() & (0b11 << 16 | 1 << 25 | 0b11 << 10), 0);
+        // Enabled, broadcast accepted, CRC stripped; nothing promiscuous.
+        assert_eq!(rctl(), 0x0400_8002);
+    }
+
+    #[test]
+    fn transmit_control_pads_short_frames() {
+        assert_eq!(tctl(), 0x0003_F0FA);
+    }
+
+    #[test]
+    fn ivar_routes_only_receive() {
+        assert_eq!(ivar_rx_only(0), 0x8);
+        assert_eq!(ivar_rx_only(3), 0xB);
+        // Transmit (11:8) and other (19:16) stay unrouted.
+        assert_eq!(ivar_rx_only(0) & (0xF << 8 | 0xF << 16), 0);
+    }
+
+    #[test]
+    fn link_decodes_speed_and_duplex() {
+        assert_eq!(Link::from_status(0), Link::Down);
+        // Speed bits without LU are still down.
+        assert_eq!(Link::from_status(0b10 << 6 | 1), Link::Down);
+        assert_eq!(Link::from_status(0b10 << 6 | 0b11), Link::Up { mbps: 1000, full_duplex: true });
+        assert_eq!(Link::from_status(0b01 << 6 | 0b10), Link::Up { mbps: 100, full_duplex: false });
+        assert_eq!(Link::from_status(0b11 << 6 | 0b11), Link::Up { mbps: 1000, full_duplex: true });
+    }
+}
@@ -0,0 +1,320 @@
+//! Which descriptor is next, and what the device writing one back means.
+//!
+//! **A descriptor slot is not a buffer.** netd hands receive buffers back in
+//! whatever order it finished with them, and the device consumes descriptors
+//! strictly in ring order, so the driver writes whichever buffer came back into
+//! whichever slot is at the tail. This module deals in slots only; the map from
+//! slot to buffer is the driver's, and so is the answer to "which buffer did
+//! netd just return".
+//!
+//! The ring is one longer than the most it will ever hold. Head equal to tail
+//! is an empty ring to the device, so a ring of `len` descriptors carries at
+//! most `len - 1` buffers, and the driver sizes it so that every buffer it owns
+//! fits at once — a refill that finds the ring full is then a bug in the
+//! driver's own accounting, which [`RxRing::post`] answers with `None` rather
+//! than with a slot the device still owns.
+
+use toyos_untrusted::Refused;
+
+use crate::desc::{RxDesc, TxDesc};
+
+/// The shortest frame this driver passes up: an Ethernet header.
+pub const ETH_HEADER: u64 = 14;
+
+/// The receive ring's bookkeeping: where the device is expected to write back
+/// next, where the next buffer goes, and how many it holds.
+#[derive(Clone, Copy, Debug)]
+pub struct RxRing {
+    len: u16,
+    buf_size: u16,
+    /// The slot the device writes back next, if it holds a buffer.
+    next: u16,
+    /// The slot the next posted buffer goes into, and the value `RDT` holds.
+    tail: u16,
+    posted: u16,
+    /// A frame's first descriptors arrived without `EOP`; everything up to and
+    /// including the one that has it is the same frame and is dropped too.
+    discarding: bool,
+}
+
+/// What the descriptor at the ring's head says.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reaped {
+    /// Nothing written back yet, or nothing posted to write back into.
+    Idle,
+    /// A whole, clean frame of `len` bytes is in the buffer at `slot`.
+    Frame { slot: u16, len: u16 },
+    /// The device finished with `slot` but what it wrote is not a frame to
+    /// pass up. The buffer is the driver's again, to post straight back.
+    Dropped { slot: u16, why: Discard },
+}
+
+/// Why a written-back descriptor was not passed up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Discard {
+    /// The device flagged the frame; the bits are among `desc::RX_ERRORS`.
+    Errors(u8),
+    /// Part of a frame spread over several descriptors. With 2048-byte buffers
+    /// and long packets off, a device that does this is not behaving as
+    /// programmed, and the parts are not reassembled.
+    Fragment,
+    /// Shorter than an Ethernet header.
+    Runt(u16),
+    /// A length past the buffer it was written into.
+    Overlong(Refused),
+}
+
+impl core::fmt::Display for Discard {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Errors(bits) => write!(f, "the device flagged it (errors {bits:#04x})"),
+            Self::Fragment => f.write_str("it was part of a frame split across descriptors"),
+            Self::Runt(len) => write!(f, "{len} bytes is shorter than an Ethernet header"),
+            Self::Overlong(why) => write!(f, "its length does not fit its buffer: {why}"),
+        }
+    }
+}
+
+impl RxRing {
+    /// `len` descriptors, each given a buffer of `buf_size` bytes.
+    ///
+    /// `RDLEN` is in bytes and must be a multiple of 128, which is eight
+    /// descriptors. Both numbers are the driver's own constants, so a shape the
+    /// device cannot take is refused at compile time, where the driver builds
+    /// its ring in a `const`.
+    pub const fn new(len: u16, buf_size: u16) -> Self {
+        assert!(len >= 8 && len.is_multiple_of(8), "RDLEN is a multiple of 128 bytes");
+        Self { len, buf_size, next: 0, tail: 0, posted: 0, discarding: false }
+    }
+
+    /// How many buffers the ring can hold at once.
+    pub const fn capacity(&self) -> u16 {
+        self.len - 1
+    }
+
+    /// `RDT`: one past the last slot the device may write.
+    pub fn tail(&self) -> u16 {
+        self.tail
+    }
+
+    /// The slot [`Self::reap`] would look at, or `None` when the device holds
+    /// no buffer to write a frame into.
+    pub fn next(&self) -> Option<u16> {
+        (self.posted > 0).then_some(self.next)
+    }
+
+    /// The slot to write a buffer into, after which `RDT` is [`Self::tail`].
+    /// `None` when the ring already holds [`Self::capacity`] buffers.
+    pub fn post(&mut self) -> Option<u16> {
+        if self.posted == self.capacity() {
+            return None;
+        }
+        let slot = self.tail;
+        self.tail = (self.tail + 1) % self.len;
+        self.posted += 1;
+        Some(slot)
+    }
+
+    /// Decide what the descriptor at [`Self::next`] says, given what was read
+    /// there. Anything but [`Reaped::Idle`] hands the slot back to the driver.
+    pub fn reap(&mut self, desc: RxDesc) -> Reaped {
+        if self.posted == 0 || !desc.done() {
+            return Reaped::Idle;
+        }
+        let slot = self.next;
+        self.next = (self.next + 1) % self.len;
+        self.posted -= 1;
+
+        let why = if self.discarding || !desc.end_of_packet() {
+            self.discarding = !desc.end_of_packet();
+            Discard::Fragment
+        } else if desc.errors() != 0 {
+            Discard::Errors(desc.errors())
+        } else {
+            match desc.length().at_most(self.buf_size as u64) {
+                Ok(len) if len < ETH_HEADER => Discard::Runt(len as u16),
+                // Exact: at most `buf_size`, a `u16`.
+                Ok(len) => return Reaped::Frame { slot, len: len as u16 },
+                Err(why) => Discard::Overlong(why),
+            }
+        };
+        Reaped::Dropped { slot, why }
+    }
+}
+
+/// The transmit ring, with one frame in flight at a time.
+///
+/// One because there is one transmit buffer: netd writes the next frame into
+/// the same bytes the last one was sent from, so the slot after it cannot be
+/// used until the device has said it is done reading.
+#[derive(Clone, Copy, Debug)]
+pub struct TxRing {
+    len: u16,
+    tail: u16,
+    in_flight: Option<u16>,
+}
+
+impl TxRing {
+    pub const fn new(len: u16) -> Self {
+        assert!(len >= 8 && len.is_multiple_of(8), "TDLEN is a multiple of 128 bytes");
+        Self { len, tail: 0, in_flight: None }
+    }
+
+    /// `TDT`: one past the last slot the device may read.
+    pub fn tail(&self) -> u16 {
+        self.tail
+    }
+
+    /// The slot a frame was submitted in and not yet seen done.
+    pub fn in_flight(&self) -> Option<u16> {
+        self.in_flight
+    }
+
+    /// The slot for the next frame, or `None` while one is in flight.
+    pub fn submit(&mut self) -> Option<u16> {
+        if self.in_flight.is_some() {
+            return None;
+        }
+        let slot = self.tail;
+        self.tail = (self.tail + 1) % self.len;
+        self.in_flight = Some(slot);
+        Some(slot)
+    }
+
+    /// Given what was read at [`Self::in_flight`], whether the buffer is free.
+    pub fn settle(&mut self, desc: TxDesc) -> bool {
+        if self.in_flight.is_some() && desc.done() {
+            self.in_flight = None;
+        }
+        self.in_flight.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DD: u64 = 1 << 32;
+    const EOP: u64 = 1 << 33;
+
+    fn written(len: u16, status: u64, errors: u8) -> RxDesc {
+        RxDesc::from_word(len as u64 | status | (errors as u64) << 40)
+    }
+
+    fn clean(len: u16) -> RxDesc {
+        written(len, DD | EOP, 0)
+    }
+
+    fn filled(len: u16, buf_size: u16) -> RxRing {
+        let mut ring = RxRing::new(len, buf_size);
+        while ring.post().is_some() {}
+        ring
+    }
+
+    #[test]
+    fn a_ring_holds_one_fewer_than_its_length() {
+        let mut ring = RxRing::new(8, 2048);
+        for want in 0..7 {
+            assert_eq!(ring.post(), Some(want));
+        }
+        assert_eq!(ring.post(), None);
+        // Head 0, tail 7: the device owns 0..=6.
+        assert_eq!(ring.tail(), 7);
+    }
+
+    #[test]
+    fn tail_wraps_after_reaping() {
+        let mut ring = filled(8, 2048);
+        assert_eq!(ring.reap(clean(60)), Reaped::Frame { slot: 0, len: 60 });
+        assert_eq!(ring.post(), Some(7));
+        assert_eq!(ring.tail(), 0);
+        assert_eq!(ring.reap(clean(60)), Reaped::Frame { slot: 1, len: 60 });
+        assert_eq!(ring.post(), Some(0));
+        assert_eq!(ring.tail(), 1);
+    }
+
+    #[test]
+    fn nothing_written_back_is_idle_and_keeps_the_slot() {
+        let mut ring = filled(8, 2048);
+        assert_eq!(ring.reap(written(60, 0, 0)), Reaped::Idle);
+        assert_eq!(ring.next(), Some(0));
+    }
+
+    #[test]
+    fn an_empty_ring_ignores_a_done_bit() {
+        // A descriptor the driver never posted can still read as done — it is
+        // whatever the last write-back left — and must not be taken.
+        let mut ring = RxRing::new(8, 2048);
+        assert_eq!(ring.next(), None);
+        assert_eq!(ring.reap(clean(60)), Reaped::Idle);
+    }
+
+    #[test]
+    fn a_length_past_the_buffer_is_dropped() {
+        let mut ring = filled(8, 2048);
+        assert_eq!(ring.reap(clean(2048)), Reaped::Frame { slot: 0, len: 2048 });
+        assert_eq!(
+            ring.reap(clean(2049)),
+            Reaped::Dropped {
+                slot: 1,
+                why: Discard::Overlong(Refused::PastBound { value: 2049, bound: 2048 })
+            }
+        );
+        assert!(matches!(ring.reap(clean(0xFFFF)), Reaped::Dropped { slot: 2, why: Discard::Overlong(_) }));
+    }
+
+    #[test]
+    fn a_runt_is_dropped() {
+        let mut ring = filled(8, 2048);
+        assert_eq!(ring.reap(clean(0)), Reaped::Dropped { slot: 0, why: Discard::Runt(0) });
+        assert_eq!(ring.reap(clean(13)), Reaped::Dropped { slot: 1, why: Discard::Runt(13) });
+        assert_eq!(ring.reap(clean(14)), Reaped::Frame { slot: 2, len: 14 });
+    }
+
+    #[test]
+    fn a_flagged_frame_is_dropped() {
+        let mut ring = filled(8, 2048);
+        assert_eq!(ring.reap(written(60, DD | EOP, 0x01)), Reaped::Dropped { slot: 0, why: Discard::Errors(0x01) });
+        assert_eq!(ring.reap(clean(60)), Reaped::Frame { slot: 1, len: 60 });
+    }
+
+    #[test]
+    fn every_part_of_a_split_frame_is_dropped() {
+        let mut ring = filled(8, 2048);
+        let part = written(2048, DD, 0);
+        assert_eq!(ring.reap(part), Reaped::Dropped { slot: 0, why: Discard::Fragment });
+        assert_eq!(ring.reap(part), Reaped::Dropped { slot: 1, why: Discard::Fragment });
+        // The last part carries EOP, and a clean length, and is still the
+        // same frame.
+        assert_eq!(ring.reap(clean(100)), Reaped::Dropped { slot: 2, why: Discard::Fragment });
+        assert_eq!(ring.reap(clean(100)), Reaped::Frame { slot: 3, len: 100 });
+    }
+
+    #[test]
+    fn transmit_waits_for_the_frame_in_flight() {
+        let mut ring = TxRing::new(8);
+        assert_eq!(ring.submit(), Some(0));
+        assert_eq!(ring.tail(), 1);
+        assert_eq!(ring.submit(), None);
+        assert!(!ring.settle(TxDesc::from_word(0)));
+        assert_eq!(ring.in_flight(), Some(0));
+        assert!(ring.settle(TxDesc::from_word(DD)));
+        assert_eq!(ring.submit(), Some(1));
+    }
+
+    #[test]
+    fn transmit_tail_wraps() {
+        let mut ring = TxRing::new(8);
+        for want in (0..8).chain(0..2) {
+            assert_eq!(ring.submit(), Some(want));
+            assert!(ring.settle(TxDesc::from_word(DD)));
+        }
+        assert_eq!(ring.tail(), 2);
+    }
+
+    #[test]
+    fn settling_with_nothing_in_flight_is_free() {
+        let mut ring = TxRing::new(8);
+        assert!(ring.settle(TxDesc::from_word(0)));
+    }
+}
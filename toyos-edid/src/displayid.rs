@@ -0,0 +1,96 @@
+//! DisplayID timings carried in an EDID extension block.
+//!
+//! A monitor past what EDID's 12-bit fields can describe — 4K at high rates,
+//! 8K, a tiled panel — names those modes in a DisplayID section instead,
+//! inside an extension block tagged `0x70`. Only the timing data blocks are
+//! read: type I from DisplayID 1.3 and type VII from 2.0, which share a 20-byte
+//! layout and differ in the pixel clock's unit.
+
+use crate::mode::Mode;
+use crate::timing::mode_of;
+
+/// The extension tag of a block holding a DisplayID section.
+pub const EXTENSION_TAG: u8 = 0x70;
+
+const TYPE_I_TIMING: u8 = 0x03;
+const TYPE_VII_TIMING: u8 = 0x22;
+const DESCRIPTOR_LEN: usize = 20;
+
+/// One timing from a DisplayID section, and whether it is the preferred one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timing {
+    pub mode: Mode,
+    pub preferred: bool,
+}
+
+/// Every progressive timing in the DisplayID section of `block`, an extension
+/// block whose first byte is [`EXTENSION_TAG`].
+///
+/// A section whose length runs past the block or whose checksum does not add
+/// up yields nothing: the section is the monitor's and a bad one is skipped
+/// whole rather than half-trusted. A data block that runs past the section
+/// ends the walk there.
+pub fn timings(block: &[u8]) -> impl Iterator<Item = Timing> + '_ {
+    section(block).into_iter().flat_map(DataBlocks::new).flat_map(|(tag, payload)| {
+        let (descriptors, unit_hz) = match tag {
+            TYPE_I_TIMING => (payload, 10_000),
+            TYPE_VII_TIMING => (payload, 1_000),
+            _ => (&[][..], 0),
+        };
+        let (descriptors, _) = descriptors.as_chunks::<DESCRIPTOR_LEN>();
+        descriptors.iter().filter_map(move |d| timing(d, unit_hz))
+    })
+}
+
+/// The data blocks of the section: bytes 5 up to the section's own length,
+/// once the section's checksum has held.
+fn section(block: &[u8]) -> Option<&[u8]> {
+    if block.first() != Some(&EXTENSION_TAG) {
+        return None;
+    }
+    let len = usize::from(*block.get(2)?);
+    // The section is bytes 1..=5+len: a four-byte header, the data blocks,
+    // and its own checksum byte.
+    let end = 5 + len;
+    let whole = block.get(1..=end)?;
+    let sum = whole.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    (sum == 0).then(|| &block[5..end])
+}
+
+struct DataBlocks<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> DataBlocks<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { rest: data }
+    }
+}
+
+impl<'a> Iterator for DataBlocks<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [tag, _revision, len, ref tail @ ..] = *self.rest else {
+            return None;
+        };
+        // Tag 0 is the padding that fills the section after the last block.
+        if tag == 0 {
+            return None;
+        }
+        let payload = tail.get(..usize::from(len))?;
+        self.rest = &tail[usize::from(len)..];
+        Some((tag, payload))
+    }
+}
+
+fn timing(d: &[u8; DESCRIPTOR_LEN], unit_hz: u64) -> Option<Timing> {
+    let field = |i: usize| u32::from(u16::from_le_bytes([d[i], d[i + 1]])) + 1;
+    let clock = u64::from(u32::from_le_bytes([d[0], d[1], d[2], 0])) + 1;
+    let flags = d[3];
+    if flags & 0x10 != 0 {
+        return None;
+    }
+    let mode = mode_of(field(4), field(6), field(12), field(14), clock * unit_hz)?;
+    Some(Timing { mode, preferred: flags & 0x80 != 0 })
+}
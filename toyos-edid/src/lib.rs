@@ -0,0 +1,218 @@
+//! EDID and DisplayID: what a monitor says about itself, read into the modes it
+//! can show.
+//!
+//! The GPU driver fetches a monitor's EDID once per output and hands the bytes
+//! to [`Edid::parse`]. What comes back is everything the desktop asks of a
+//! monitor:
+//!
+//! - [`mode`]: a mode, and the deduplicated list of them a picker offers.
+//! - [`timing`]: the base block's detailed, standard and established timings.
+//! - [`displayid`]: the timings a DisplayID extension adds for monitors past
+//!   what EDID's fields can describe.
+//!
+//! The block is the monitor's, and a monitor — or a KVM switch, or a cable
+//! adapter between the two — can send anything. Only the base block has to be
+//! right for the parse to succeed; an extension that is wrong is skipped, and
+//! nothing here panics or reads outside the bytes it was given.
+//!
+//! `no_std`, no allocation, no `unsafe`.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+pub mod displayid;
+pub mod mode;
+pub mod timing;
+
+use core::fmt;
+
+pub use mode::{Mode, Modes, MAX_MODES};
+
+/// One EDID block, base or extension.
+pub const BLOCK: usize = 128;
+
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const CEA_TAG: u8 = 0x02;
+const TAG_NAME: u8 = 0xFC;
+const TAG_STANDARD: u8 = 0xFA;
+
+/// Why a base block was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Fewer than [`BLOCK`] bytes.
+    Truncated,
+    /// The eight-byte header is not `00 FF FF FF FF FF FF 00`.
+    Header,
+    /// The base block's bytes do not sum to zero.
+    Checksum,
+    /// An EDID version other than 1.
+    Version,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Truncated => "shorter than one EDID block",
+            Self::Header => "no EDID header",
+            Self::Checksum => "the base block's checksum does not add up",
+            Self::Version => "not EDID version 1",
+        })
+    }
+}
+
+/// What one monitor's EDID says.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edid {
+    /// Every mode it names, once each, largest first.
+    pub modes: Modes,
+    /// The mode the monitor wants: its first detailed timing, which EDID 1.4
+    /// makes the native one, or a DisplayID timing flagged preferred when the
+    /// base block names none.
+    pub preferred: Option<Mode>,
+    /// The image's width and height in millimetres, from the preferred timing
+    /// where it states one and from the base block's centimetres where not.
+    pub size_mm: Option<(u32, u32)>,
+    name: [u8; 13],
+    name_len: usize,
+}
+
+impl Edid {
+    /// Read the base block at the front of `bytes` and every extension block
+    /// after it that is present.
+    ///
+    /// `bytes` may be longer than the blocks it holds — a driver's buffer is
+    /// sized for the most a monitor sends — and the base block's extension
+    /// count decides how much of it is read.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let (blocks, _) = bytes.as_chunks::<BLOCK>();
+        let (base, extensions) = blocks.split_first().ok_or(Error::Truncated)?;
+        if base[..8] != HEADER {
+            return Err(Error::Header);
+        }
+        if !sums_to_zero(base) {
+            return Err(Error::Checksum);
+        }
+        if base[18] != 1 {
+            return Err(Error::Version);
+        }
+        let revision = base[19];
+
+        let mut edid = Self {
+            modes: Modes::new(),
+            preferred: None,
+            size_mm: None,
+            name: [0; 13],
+            name_len: 0,
+        };
+        // EDID 1.4 makes the first detailed timing the native mode outright;
+        // 1.3 says so with a feature bit, which every monitor of the last two
+        // decades sets.
+        let first_is_preferred = revision >= 4 || base[24] & 0x02 != 0;
+
+        for (i, d) in descriptors(&base[54..126]).enumerate() {
+            if let Some(t) = timing::detailed(d) {
+                if i == 0 && first_is_preferred {
+                    edid.preferred = Some(t.mode);
+                    edid.size_mm = t.size_mm;
+                }
+                edid.modes.push(t.mode);
+                continue;
+            }
+            match d[3] {
+                TAG_NAME => edid.set_name(&d[5..]),
+                TAG_STANDARD => {
+                    for pair in d[5..17].as_chunks::<2>().0 {
+                        if let Some(m) = timing::standard(*pair, revision) {
+                            edid.modes.push(m);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        for pair in base[38..54].as_chunks::<2>().0 {
+            if let Some(m) = timing::standard(*pair, revision) {
+                edid.modes.push(m);
+            }
+        }
+
+        let count = usize::from(base[126]);
+        for block in extensions.iter().take(count) {
+            if sums_to_zero(block) {
+                edid.extension(block);
+            }
+        }
+        for m in timing::established([base[35], base[36], base[37]]) {
+            edid.modes.push(m);
+        }
+
+        if edid.size_mm.is_none() && base[21] != 0 && base[22] != 0 {
+            edid.size_mm = Some((u32::from(base[21]) * 10, u32::from(base[22]) * 10));
+        }
+        edid.modes.sort();
+        Ok(edid)
+    }
+
+    /// The monitor's name, from its name descriptor, or `""` when it has none.
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    /// Dots per inch across and down for `mode` on this monitor, when it
+    /// states its size. Rounded to the nearest whole dot.
+    pub fn dpi(&self, mode: Mode) -> Option<(u32, u32)> {
+        let (w_mm, h_mm) = self.size_mm?;
+        let per_inch =
+            |px: u32, mm: u32| (u64::from(px) * 254 + u64::from(mm) * 5) / (u64::from(mm) * 10);
+        Some((per_inch(mode.width, w_mm) as u32, per_inch(mode.height, h_mm) as u32))
+    }
+
+    fn extension(&mut self, block: &[u8]) {
+        match block[0] {
+            // CEA-861: detailed timings from the offset in byte 2 to the
+            // checksum, after the data block collection.
+            CEA_TAG => {
+                let start = usize::from(block[2]);
+                if !(4..BLOCK).contains(&start) {
+                    return;
+                }
+                for d in descriptors(&block[start..BLOCK - 1]) {
+                    match timing::detailed(d) {
+                        Some(t) => {
+                            self.modes.push(t.mode);
+                        }
+                        None => break,
+                    }
+                }
+            }
+            displayid::EXTENSION_TAG => {
+                for t in displayid::timings(block) {
+                    if t.preferred && self.preferred.is_none() {
+                        self.preferred = Some(t.mode);
+                    }
+                    self.modes.push(t.mode);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Up to 13 bytes of ASCII, ended by a line feed and padded with spaces.
+    fn set_name(&mut self, text: &[u8]) {
+        let text = &text[..text.iter().position(|&b| b == b'\n').unwrap_or(text.len())];
+        let len = text.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        if text[..len].iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            self.name[..len].copy_from_slice(&text[..len]);
+            self.name_len = len;
+        }
+    }
+}
+
+fn sums_to_zero(block: &[u8]) -> bool {
+    block.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) == 0
+}
+
+/// The 18-byte descriptors that fit in `bytes`.
+fn descriptors(bytes: &[u8]) -> impl Iterator<Item = &[u8; 18]> {
+    bytes.as_chunks::<18>().0.iter()
+}
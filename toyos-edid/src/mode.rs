@@ -0,0 +1,144 @@
+//! A display mode, and the bounded set of them one monitor offers.
+
+/// The most modes one monitor's list keeps. A desktop monitor's base block and
+/// one extension name twenty or so once duplicates are gone; past the cap the
+/// rest are dropped, and they are the ones parsed last — the established
+/// timings of 1990s CRTs, not the panel's own detailed timings.
+pub const MAX_MODES: usize = 32;
+
+/// One progressive mode: the active area and how often it is refreshed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Mode {
+    pub width: u32,
+    pub height: u32,
+    /// In millihertz, because a detailed timing's rate is a ratio and not a
+    /// whole number: 59.940 Hz and 60 Hz are different timings to the monitor.
+    pub refresh_mhz: u32,
+}
+
+impl Mode {
+    pub const fn new(width: u32, height: u32, refresh_mhz: u32) -> Self {
+        Self { width, height, refresh_mhz }
+    }
+
+    /// The refresh rate rounded to whole hertz, which is how a picker shows it
+    /// and what two modes are compared by.
+    pub const fn refresh_hz(self) -> u32 {
+        (self.refresh_mhz + 500) / 1000
+    }
+
+    /// Whether the two are one entry on a list a person chooses from: the same
+    /// size at the same whole-hertz rate.
+    pub const fn same_as(self, other: Mode) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.refresh_hz() == other.refresh_hz()
+    }
+}
+
+/// The modes a monitor offers, each once.
+///
+/// A monitor names most of its modes two or three times over — a detailed
+/// timing, then the same size as a standard timing, then again in an
+/// extension block — and a picker listing all of them lists the same choice
+/// three times. [`push`](Self::push) keeps the first spelling, which is the
+/// detailed one with the exact rate because detailed timings are parsed first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Modes {
+    modes: [Mode; MAX_MODES],
+    len: usize,
+}
+
+impl Default for Modes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Modes {
+    pub const fn new() -> Self {
+        Self { modes: [Mode::new(0, 0, 0); MAX_MODES], len: 0 }
+    }
+
+    /// Add `mode` unless the list already has it. `false` when it was a
+    /// duplicate or the list is full.
+    pub fn push(&mut self, mode: Mode) -> bool {
+        if self.len == MAX_MODES || self.as_slice().iter().any(|m| m.same_as(mode)) {
+            return false;
+        }
+        self.modes[self.len] = mode;
+        self.len += 1;
+        true
+    }
+
+    pub fn as_slice(&self) -> &[Mode] {
+        &self.modes[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether some mode is `width` by `height`, at any rate.
+    pub fn contains(&self, width: u32, height: u32) -> bool {
+        self.as_slice().iter().any(|m| m.width == width && m.height == height)
+    }
+
+    /// Largest first, then widest, then fastest: the order a picker lists them
+    /// in, with the mode a monitor is sold by at the top.
+    pub(crate) fn sort(&mut self) {
+        self.modes[..self.len].sort_unstable_by(|a, b| {
+            let area = |m: &Mode| u64::from(m.width) * u64::from(m.height);
+            area(b)
+                .cmp(&area(a))
+                .then(b.width.cmp(&a.width))
+                .then(b.refresh_mhz.cmp(&a.refresh_mhz))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_size_at_the_same_whole_rate_is_listed_once() {
+        let mut modes = Modes::new();
+        assert!(modes.push(Mode::new(1920, 1080, 60_000)));
+        assert!(!modes.push(Mode::new(1920, 1080, 59_940)));
+        assert!(modes.push(Mode::new(1920, 1080, 50_000)));
+        assert_eq!(modes.len(), 2);
+        assert_eq!(modes.as_slice()[0].refresh_mhz, 60_000);
+    }
+
+    #[test]
+    fn a_full_list_refuses_rather_than_overwrites() {
+        let mut modes = Modes::new();
+        for i in 0..MAX_MODES as u32 {
+            assert!(modes.push(Mode::new(640 + i, 480, 60_000)));
+        }
+        assert!(!modes.push(Mode::new(3840, 2160, 60_000)));
+        assert_eq!(modes.as_slice()[0].width, 640);
+    }
+
+    #[test]
+    fn the_list_sorts_largest_then_fastest() {
+        let mut modes = Modes::new();
+        modes.push(Mode::new(1280, 720, 60_000));
+        modes.push(Mode::new(1920, 1080, 50_000));
+        modes.push(Mode::new(1920, 1080, 60_000));
+        modes.sort();
+        assert_eq!(
+            modes.as_slice(),
+            &[
+                Mode::new(1920, 1080, 60_000),
+                Mode::new(1920, 1080, 50_000),
+                Mode::new(1280, 720, 60_000)
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! The three ways an EDID base block names a mode, decoded one at a time.
+//!
+//! Each decoder takes the bytes of one entry and answers `None` for an entry
+//! that names nothing this driver can scan out — an unused slot, an interlaced
+//! timing, a timing whose totals would divide by zero. None of them panics on
+//! any input.
+
+use crate::mode::Mode;
+
+/// One detailed timing: the mode, and the image size the monitor printed next
+/// to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Detailed {
+    pub mode: Mode,
+    /// Millimetres, or `None` where the descriptor leaves it zero.
+    pub size_mm: Option<(u32, u32)>,
+}
+
+/// An 18-byte detailed timing descriptor (EDID 1.4 §3.10.2).
+///
+/// `None` for a display descriptor — the same 18 bytes with a zero pixel
+/// clock, holding a name or a range instead of a timing.
+pub fn detailed(d: &[u8; 18]) -> Option<Detailed> {
+    let clock_10khz = u64::from(u16::from_le_bytes([d[0], d[1]]));
+    if clock_10khz == 0 || d[17] & 0x80 != 0 {
+        return None;
+    }
+    let width = u32::from(d[2]) | (u32::from(d[4] >> 4) << 8);
+    let h_blank = u32::from(d[3]) | (u32::from(d[4] & 0x0F) << 8);
+    let height = u32::from(d[5]) | (u32::from(d[7] >> 4) << 8);
+    let v_blank = u32::from(d[6]) | (u32::from(d[7] & 0x0F) << 8);
+    let mode = mode_of(width, h_blank, height, v_blank, clock_10khz * 10_000)?;
+
+    let w_mm = u32::from(d[12]) | (u32::from(d[14] >> 4) << 8);
+    let h_mm = u32::from(d[13]) | (u32::from(d[14] & 0x0F) << 8);
+    let size_mm = (w_mm != 0 && h_mm != 0).then_some((w_mm, h_mm));
+    Some(Detailed { mode, size_mm })
+}
+
+/// A mode from its active and blanking extents and its pixel clock in hertz.
+pub(crate) fn mode_of(
+    width: u32,
+    h_blank: u32,
+    height: u32,
+    v_blank: u32,
+    clock_hz: u64,
+) -> Option<Mode> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let frame = u64::from(width + h_blank) * u64::from(height + v_blank);
+    let refresh_mhz = (clock_hz * 1000 + frame / 2) / frame;
+    Some(Mode::new(width, height, u32::try_from(refresh_mhz).ok()?))
+}
+
+/// A two-byte standard timing (EDID 1.4 §3.9): a width in steps of eight, an
+/// aspect ratio, and a whole-hertz rate.
+///
+/// `revision` is the base block's EDID revision, because the aspect code `00`
+/// means 1:1 before 1.3 and 16:10 from 1.3 on.
+pub fn standard(b: [u8; 2], revision: u8) -> Option<Mode> {
+    // `01 01` is the spelling of an unused slot; `00` is reserved and a block
+    // that writes it means the same.
+    if b[0] == 0x00 || b == [0x01, 0x01] {
+        return None;
+    }
+    let width = (u32::from(b[0]) + 31) * 8;
+    let height = match b[1] >> 6 {
+        0 if revision < 3 => width,
+        0 => width * 10 / 16,
+        1 => width * 3 / 4,
+        2 => width * 4 / 5,
+        _ => width * 9 / 16,
+    };
+    let hz = u32::from(b[1] & 0x3F) + 60;
+    Some(Mode::new(width, height, hz * 1000))
+}
+
+/// The established timings, bytes 35 to 37 of the base block, most
+/// significant bit of byte 35 first (EDID 1.4 table 3.18). The interlaced
+/// 1024×768 at 87 Hz is left out, and so are the manufacturer's bits after
+/// the seventeenth.
+const ESTABLISHED: [Option<(u32, u32, u32)>; 17] = [
+    Some((720, 400, 70)),
+    Some((720, 400, 88)),
+    Some((640, 480, 60)),
+    Some((640, 480, 67)),
+    Some((640, 480, 72)),
+    Some((640, 480, 75)),
+    Some((800, 600, 56)),
+    Some((800, 600, 60)),
+    Some((800, 600, 72)),
+    Some((800, 600, 75)),
+    Some((832, 624, 75)),
+    None,
+    Some((1024, 768, 60)),
+    Some((1024, 768, 70)),
+    Some((1024, 768, 75)),
+    Some((1280, 1024, 75)),
+    Some((1152, 870, 75)),
+];
+
+/// Every mode the established-timings bitmap sets.
+pub fn established(bits: [u8; 3]) -> impl Iterator<Item = Mode> {
+    let word = u32::from(bits[0]) << 16 | u32::from(bits[1]) << 8 | u32::from(bits[2]);
+    ESTABLISHED.into_iter().enumerate().filter_map(move |(i, entry)| {
+        let (w, h, hz) = entry?;
+        (word & (1 << (23 - i)) != 0).then_some(Mode::new(w, h, hz * 1000))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_standard_timing_reads_its_aspect_by_revision() {
+        // 1920 wide, code 11 (16:9), 60 Hz.
+        assert_eq!(standard([0xD1, 0xC0], 4), Some(Mode::new(1920, 1080, 60_000)));
+        // 1280 wide, code 00: 16:10 in 1.3, square before it.
+        assert_eq!(standard([0x81, 0x00], 3), Some(Mode::new(1280, 800, 60_000)));
+        assert_eq!(standard([0x81, 0x00], 2), Some(Mode::new(1280, 1280, 60_000)));
+        assert_eq!(standard([0x01, 0x01], 4), None);
+    }
+
+    #[test]
+    fn the_established_bitmap_reads_most_significant_first() {
+        let modes: [Mode; 2] = [Mode::new(720, 400, 70_000), Mode::new(1152, 870, 75_000)];
+        assert!(established([0x80, 0x00, 0x80]).eq(modes));
+        // The interlaced bit names nothing.
+        assert_eq!(established([0x00, 0x10, 0x00]).count(), 0);
+    }
+}
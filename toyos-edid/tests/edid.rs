@@ -0,0 +1,237 @@
+//! What the parse promises, checked against blocks laid out as monitors ship
+//! them and against ones written to break it. QEMU's virtio-gpu sends one
+//! monitor's EDID with one preferred timing; everything else here is a block
+//! the driver would otherwise meet first on real hardware.
+
+use toyos_edid::{Edid, Error, Mode, BLOCK, MAX_MODES};
+
+/// 1920×1080 at 60 Hz, CEA-861 timing 16: 148.5 MHz over 2200×1125, on a
+/// 600×340 mm panel.
+const DTD_1080P: [u8; 18] = [
+    0x02, 0x3A, 0x80, 0x18, 0x71, 0x38, 0x2D, 0x40, 0x58, 0x2C, 0x45, 0x00, 0x58, 0x54, 0x21, 0x00,
+    0x00, 0x1E,
+];
+
+/// 1280×720 at 60 Hz: 74.25 MHz over 1650×750, no size.
+const DTD_720P: [u8; 18] = [
+    0x01, 0x1D, 0x00, 0x72, 0x51, 0xD0, 0x1E, 0x20, 0x6E, 0x28, 0x55, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x1E,
+];
+
+/// 3840×2160 at 60 Hz as a DisplayID timing: 594 MHz over 4400×2250. Clock and
+/// extents are stored minus one.
+fn displayid_4k(preferred: bool, type_vii: bool) -> [u8; 20] {
+    let clock = if type_vii { 594_000u32 - 1 } else { 59_400 - 1 };
+    let c = clock.to_le_bytes();
+    let flags = if preferred { 0x80 } else { 0x00 };
+    [
+        c[0], c[1], c[2], flags, 0xFF, 0x0E, 0x2F, 0x02, 0x00, 0x00, 0x00, 0x00, 0x6F, 0x08, 0x59,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ]
+}
+
+fn fix_checksum(block: &mut [u8]) {
+    let sum = block[..BLOCK - 1].iter().fold(0u8, |a, b| a.wrapping_add(*b));
+    block[BLOCK - 1] = 0u8.wrapping_sub(sum);
+}
+
+/// An EDID 1.4 base block for a 27-inch 1080p desktop monitor: its native
+/// timing first, a name, one standard timing, three established ones.
+fn monitor() -> [u8; BLOCK] {
+    let mut b = [0u8; BLOCK];
+    b[..8].copy_from_slice(&[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+    b[18] = 1;
+    b[19] = 4;
+    (b[21], b[22]) = (60, 34);
+    // 640×480@60 and 800×600@60, then 1024×768@60.
+    (b[35], b[36], b[37]) = (0x21, 0x08, 0x00);
+    b[38..54].fill(0x01);
+    // 1280 wide, 5:4, 60 Hz.
+    (b[38], b[39]) = (0x81, 0x80);
+    b[54..72].copy_from_slice(&DTD_1080P);
+    b[72..90].copy_from_slice(&[
+        0x00, 0x00, 0x00, 0xFC, 0x00, b'T', b'o', b'y', b'O', b'S', b' ', b'2', b'7', b'\n', b' ',
+        b' ', b' ', b' ',
+    ]);
+    b[90..108].copy_from_slice(&[0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    b[108..126].copy_from_slice(&[0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    fix_checksum(&mut b);
+    b
+}
+
+/// `base` with `extensions` after it and its count and checksum fixed up.
+fn with_extensions(mut base: [u8; BLOCK], extensions: &[[u8; BLOCK]]) -> Vec<u8> {
+    base[126] = extensions.len() as u8;
+    fix_checksum(&mut base);
+    let mut bytes = base.to_vec();
+    for e in extensions {
+        bytes.extend_from_slice(e);
+    }
+    bytes
+}
+
+fn cea(dtds: &[[u8; 18]]) -> [u8; BLOCK] {
+    let mut b = [0u8; BLOCK];
+    (b[0], b[1], b[2]) = (0x02, 3, 4);
+    for (i, d) in dtds.iter().enumerate() {
+        b[4 + i * 18..22 + i * 18].copy_from_slice(d);
+    }
+    fix_checksum(&mut b);
+    b
+}
+
+/// A DisplayID extension holding one timing data block.
+fn displayid(tag: u8, timings: &[[u8; 20]]) -> [u8; BLOCK] {
+    let mut b = [0u8; BLOCK];
+    let payload = timings.len() * 20;
+    let len = 3 + payload;
+    (b[0], b[1], b[2], b[3], b[4]) = (0x70, 0x13, len as u8, 0x03, 0);
+    (b[5], b[6], b[7]) = (tag, 0, payload as u8);
+    for (i, t) in timings.iter().enumerate() {
+        b[8 + i * 20..28 + i * 20].copy_from_slice(t);
+    }
+    let sum = b[1..5 + len].iter().fold(0u8, |a, x| a.wrapping_add(*x));
+    b[5 + len] = 0u8.wrapping_sub(sum);
+    fix_checksum(&mut b);
+    b
+}
+
+#[test]
+fn a_desktop_monitor_offers_its_native_mode_first_and_each_mode_once() {
+    let edid = Edid::parse(&monitor()).unwrap();
+    assert_eq!(edid.preferred, Some(Mode::new(1920, 1080, 60_000)));
+    assert_eq!(edid.name(), "ToyOS 27");
+    assert_eq!(
+        edid.modes.as_slice(),
+        &[
+            Mode::new(1920, 1080, 60_000),
+            Mode::new(1280, 1024, 60_000),
+            Mode::new(1024, 768, 60_000),
+            Mode::new(800, 600, 60_000),
+            Mode::new(640, 480, 60_000),
+        ]
+    );
+}
+
+#[test]
+fn the_physical_size_comes_from_the_native_timing_and_gives_the_dpi() {
+    let edid = Edid::parse(&monitor()).unwrap();
+    assert_eq!(edid.size_mm, Some((600, 340)));
+    assert_eq!(edid.dpi(Mode::new(1920, 1080, 60_000)), Some((81, 81)));
+
+    // A timing with no size falls back to the base block's centimetres.
+    let mut b = monitor();
+    b[66..69].fill(0);
+    fix_checksum(&mut b);
+    assert_eq!(Edid::parse(&b).unwrap().size_mm, Some((600, 340)));
+
+    // A projector states neither.
+    b[21] = 0;
+    fix_checksum(&mut b);
+    let edid = Edid::parse(&b).unwrap();
+    assert_eq!(edid.size_mm, None);
+    assert_eq!(edid.dpi(Mode::new(1920, 1080, 60_000)), None);
+}
+
+#[test]
+fn a_block_that_is_not_an_edid_is_refused_by_name() {
+    assert_eq!(Edid::parse(&monitor()[..100]), Err(Error::Truncated));
+
+    let mut b = monitor();
+    b[1] = 0;
+    fix_checksum(&mut b);
+    assert_eq!(Edid::parse(&b), Err(Error::Header));
+
+    let mut b = monitor();
+    b[40] ^= 0xFF;
+    assert_eq!(Edid::parse(&b), Err(Error::Checksum));
+
+    let mut b = monitor();
+    b[18] = 2;
+    fix_checksum(&mut b);
+    assert_eq!(Edid::parse(&b), Err(Error::Version));
+}
+
+#[test]
+fn a_cea_extension_adds_its_detailed_timings() {
+    let bytes = with_extensions(monitor(), &[cea(&[DTD_720P, DTD_1080P])]);
+    let edid = Edid::parse(&bytes).unwrap();
+    assert!(edid.modes.contains(1280, 720));
+    // 1080p was already there: once is enough.
+    let count = edid.modes.as_slice().iter().filter(|m| m.width == 1920).count();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn an_extension_with_a_bad_checksum_is_skipped_and_the_base_still_parses() {
+    let mut bad = cea(&[DTD_720P]);
+    bad[10] ^= 0x55;
+    let edid = Edid::parse(&with_extensions(monitor(), &[bad])).unwrap();
+    assert!(!edid.modes.contains(1280, 720));
+    assert_eq!(edid.preferred, Some(Mode::new(1920, 1080, 60_000)));
+}
+
+#[test]
+fn displayid_names_the_modes_edid_cannot() {
+    for (tag, type_vii) in [(0x03, false), (0x22, true)] {
+        let bytes = with_extensions(monitor(), &[displayid(tag, &[displayid_4k(true, type_vii)])]);
+        let edid = Edid::parse(&bytes).unwrap();
+        assert_eq!(edid.modes.as_slice()[0], Mode::new(3840, 2160, 60_000), "tag {tag:#x}");
+        // The base block's native timing still wins.
+        assert_eq!(edid.preferred, Some(Mode::new(1920, 1080, 60_000)));
+    }
+}
+
+#[test]
+fn a_displayid_preferred_timing_stands_in_when_the_base_block_has_none() {
+    let mut b = monitor();
+    // The native timing's slot becomes a dummy descriptor.
+    b[54..72].copy_from_slice(&[0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let bytes = with_extensions(b, &[displayid(0x03, &[displayid_4k(true, false)])]);
+    let edid = Edid::parse(&bytes).unwrap();
+    assert_eq!(edid.preferred, Some(Mode::new(3840, 2160, 60_000)));
+}
+
+#[test]
+fn a_displayid_section_longer_than_its_block_is_ignored() {
+    let mut ext = displayid(0x03, &[displayid_4k(true, false)]);
+    ext[2] = 200;
+    fix_checksum(&mut ext);
+    let edid = Edid::parse(&with_extensions(monitor(), &[ext])).unwrap();
+    assert!(!edid.modes.contains(3840, 2160));
+}
+
+#[test]
+fn an_extension_count_past_the_bytes_given_reads_only_what_is_there() {
+    let mut b = monitor();
+    b[126] = 255;
+    fix_checksum(&mut b);
+    let mut bytes = b.to_vec();
+    bytes.extend_from_slice(&cea(&[DTD_720P]));
+    // Half a block after the whole one: not read.
+    bytes.extend_from_slice(&[0xAB; 64]);
+    let edid = Edid::parse(&bytes).unwrap();
+    assert!(edid.modes.contains(1280, 720));
+}
+
+#[test]
+fn every_single_byte_corruption_parses_or_refuses_without_panicking() {
+    let clean = with_extensions(
+        monitor(),
+        &[cea(&[DTD_720P]), displayid(0x22, &[displayid_4k(false, true)])],
+    );
+    for at in 0..clean.len() {
+        for value in [0x00, 0x01, 0x7F, 0x80, 0xFF] {
+            let mut bytes = clean.clone();
+            bytes[at] = value;
+            // Re-sum every block, so the corruption reaches the parse past
+            // the checksum rather than stopping at it.
+            for block in bytes.as_chunks_mut::<BLOCK>().0 {
+                fix_checksum(block);
+            }
+            if let Ok(edid) = Edid::parse(&bytes) {
+                assert!(edid.modes.len() <= MAX_MODES);
+            }
+        }
+    }
+}
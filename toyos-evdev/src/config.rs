@@ -0,0 +1,119 @@
+//! What a virtio-input device says about itself (VirtIO 1.2 §5.8.2).
+//!
+//! The configuration space is a window the driver selects into: write
+//! `select` and `subsel`, read `size`, and the answer is in the union at
+//! offset 8. What comes back is the answer an evdev `ioctl` would have given —
+//! a bitmap of the codes one event type can carry, or the range of one
+//! absolute axis — so it lives here with the events it describes, and the
+//! kernel only does the register reads.
+
+/// `u8 select`, `u8 subsel`, `u8 size`, five reserved bytes, then the union.
+pub const SELECT: u64 = 0;
+pub const SUBSEL: u64 = 1;
+pub const SIZE: u64 = 2;
+pub const UNION: u64 = 8;
+/// The union's length, and so the longest bitmap a device can answer with:
+/// 1024 codes, which is every `EV_KEY` code evdev has room for.
+pub const UNION_BYTES: usize = 128;
+
+/// The device's name, as a string of `size` bytes with no terminator.
+pub const ID_NAME: u8 = 0x01;
+/// The codes `subsel` (an event type) can carry, as a little-endian bitmap.
+pub const EV_BITS: u8 = 0x11;
+/// The range of absolute axis `subsel`: [`ABS_INFO_BYTES`] of `le32`s.
+pub const ABS_INFO: u8 = 0x12;
+/// `min`, `max`, `fuzz`, `flat`, `res`.
+pub const ABS_INFO_BYTES: usize = 20;
+
+/// The range one absolute axis reports in, which the decoder maps onto the
+/// 0..=32767 the compositor's cursor lives in.
+///
+/// Carried rather than assumed: QEMU's tablet happens to report in exactly
+/// that range, and a device on another hypervisor that reports in pixels of
+/// its own window would otherwise put the cursor in the top-left sixteenth of
+/// the screen and keep it there.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Axis {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Axis {
+    /// The range a device that never answered `ABS_INFO` is taken to use. It
+    /// is the compositor's own, so the scale is the identity.
+    pub const UNSPECIFIED: Self = Self { min: 0, max: 32767 };
+
+    /// The `min` and `max` of an `ABS_INFO` answer. `None` for an empty or
+    /// inverted range, which no value can be scaled into.
+    pub fn parse(info: &[u8; ABS_INFO_BYTES]) -> Option<Self> {
+        let word = |at: usize| i32::from_le_bytes([info[at], info[at + 1], info[at + 2], info[at + 3]]);
+        let (min, max) = (word(0), word(4));
+        (max > min).then_some(Self { min, max })
+    }
+
+    /// `value` on this axis, in 0..=32767. A value outside the range the
+    /// device declared is held at its edge rather than wrapped.
+    pub fn scale(self, value: i32) -> u16 {
+        let span = self.max as i64 - self.min as i64;
+        if span <= 0 {
+            return 0;
+        }
+        let at = (value as i64).clamp(self.min as i64, self.max as i64) - self.min as i64;
+        (at * 32767 / span) as u16
+    }
+}
+
+/// Whether bit `code` is set in an `EV_BITS` bitmap. A code past the end of
+/// what the device answered is a code it does not have.
+pub fn has(bits: &[u8], code: u16) -> bool {
+    bits.get(code as usize / 8).is_some_and(|b| b & (1 << (code % 8)) != 0)
+}
+
+/// What a device can report, from its three `EV_BITS` answers.
+///
+/// A device is whatever its bitmaps say, and may be more than one thing: the
+/// names QEMU gives (`QEMU Virtio Keyboard`, `… Tablet`) are for the log, and
+/// a keyboard with a pointing stick is one device that is both.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Capabilities {
+    /// Types keys, which is to say has letters. A power button is an
+    /// `EV_KEY` device too, and is not a keyboard.
+    pub keys: bool,
+    /// Reports relative motion on both axes: a mouse.
+    pub relative: bool,
+    /// Reports absolute position on both axes: a tablet.
+    pub absolute: bool,
+}
+
+impl Capabilities {
+    pub fn read(key_bits: &[u8], rel_bits: &[u8], abs_bits: &[u8]) -> Self {
+        use crate::event::{ABS_X, ABS_Y, KEY_A, KEY_SPACE, REL_X, REL_Y};
+        Self {
+            keys: has(key_bits, KEY_A) && has(key_bits, KEY_SPACE),
+            relative: has(rel_bits, REL_X) && has(rel_bits, REL_Y),
+            absolute: has(abs_bits, ABS_X) && has(abs_bits, ABS_Y),
+        }
+    }
+
+    /// Moves the cursor, and so needs a pointer source of its own.
+    pub fn is_pointer(self) -> bool {
+        self.relative || self.absolute
+    }
+
+    /// Nothing this kernel reads. Such a device is not bound.
+    pub fn is_empty(self) -> bool {
+        !self.keys && !self.is_pointer()
+    }
+
+    /// What to call the device in a log line.
+    pub fn name(self) -> &'static str {
+        match (self.keys, self.absolute, self.relative) {
+            (true, false, false) => "keyboard",
+            (false, true, _) => "tablet",
+            (false, false, true) => "mouse",
+            (true, true, _) => "keyboard and tablet",
+            (true, false, true) => "keyboard and mouse",
+            (false, false, false) => "nothing this kernel reads",
+        }
+    }
+}
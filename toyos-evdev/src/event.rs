@@ -0,0 +1,245 @@
+//! The event stream: one `(type, code, value)` at a time, and a frame closed by
+//! `SYN_REPORT`.
+//!
+//! ```text
+//! bytes 0..2: le16 type
+//! bytes 2..4: le16 code
+//! bytes 4..8: le32 value (signed for EV_REL and EV_ABS)
+//! ```
+//!
+//! **A pointer sample is the frame, not the event.** A diagonal move is an
+//! `REL_X` and an `REL_Y` followed by `SYN_REPORT`, and a tablet that reports
+//! only the axis that changed sends `ABS_X` alone — so publishing per event
+//! would draw every diagonal as a staircase, and a click that shares a frame
+//! with the motion to its target would land one event early, where the cursor
+//! was. Keys are not held for the frame: a keystroke is complete in its own
+//! event, and the held-set in `keyboard.rs` is what orders it.
+
+use crate::config::Axis;
+use crate::key;
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+pub const EV_ABS: u16 = 0x03;
+
+pub const SYN_REPORT: u16 = 0;
+/// The device's own buffer overflowed and events are missing. Whatever a
+/// release was among them is gone, and the frame in progress is not one.
+pub const SYN_DROPPED: u16 = 3;
+
+pub const REL_X: u16 = 0x00;
+pub const REL_Y: u16 = 0x01;
+pub const REL_WHEEL: u16 = 0x08;
+
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+
+pub const KEY_SPACE: u16 = 57;
+pub const KEY_A: u16 = 30;
+
+/// The first `EV_KEY` code that is a button rather than a key. Everything below
+/// goes through the keycode table.
+pub const BTN_MISC: u16 = 0x100;
+pub const BTN_LEFT: u16 = 0x110;
+pub const BTN_RIGHT: u16 = 0x111;
+pub const BTN_MIDDLE: u16 = 0x112;
+
+/// One event as the device wrote it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Event {
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl Event {
+    pub const BYTES: usize = 8;
+
+    pub fn parse(bytes: &[u8; Self::BYTES]) -> Self {
+        Self {
+            kind: u16::from_le_bytes([bytes[0], bytes[1]]),
+            code: u16::from_le_bytes([bytes[2], bytes[3]]),
+            value: i32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        }
+    }
+}
+
+/// Where a sample puts the pointer: the two shapes `mouse::Motion` takes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Motion {
+    Relative { dx: i32, dy: i32 },
+    /// Already in 0..=32767 on both axes.
+    Absolute { x: u16, y: u16 },
+}
+
+/// One frame's worth of pointer, in the shape `mouse::handle_motion` takes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sample {
+    /// HID boot-mouse order: bit 0 left, 1 right, 2 middle.
+    pub buttons: u8,
+    pub motion: Motion,
+    /// Wheel detents, positive away from the user — evdev's sign and HID's.
+    pub scroll: i8,
+}
+
+/// What one event produced.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// Taken into the frame in progress. Whether it moved anything is not
+    /// known until the frame ends.
+    Pending,
+    /// Named nothing: a key this kernel has no usage for, an autorepeat, a
+    /// frame with no pointer in it, or an event thrown away after a drop.
+    None,
+    Key { usage: u8, pressed: bool },
+    Pointer(Sample),
+    /// The device lost events. Everything this device holds must be released:
+    /// the release for it may be among what was lost.
+    Lost,
+}
+
+/// Events in, key transitions and pointer samples out.
+///
+/// Holds the pointer's buttons and absolute position, because evdev reports
+/// both as changes: a frame with a click and no motion says nothing about
+/// where the cursor is, and the sample it becomes has to. It holds no record of
+/// which keys are down, for the reason `toyos_ps2::KeyDecoder` gives.
+pub struct Decoder {
+    x: Axis,
+    y: Axis,
+    buttons: u8,
+    /// Where the device last said it was, scaled. `None` until it has said so
+    /// on both axes.
+    at: (Option<u16>, Option<u16>),
+    dx: i32,
+    dy: i32,
+    wheel: i32,
+    /// Something in this frame changes the pointer.
+    moved: bool,
+    /// This frame's motion was absolute.
+    absolute: bool,
+    /// After `SYN_DROPPED`, until the `SYN_REPORT` that closes what is left of
+    /// the broken frame.
+    dropping: bool,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new(Axis::UNSPECIFIED, Axis::UNSPECIFIED)
+    }
+}
+
+impl Decoder {
+    pub const fn new(x: Axis, y: Axis) -> Self {
+        Self {
+            x,
+            y,
+            buttons: 0,
+            at: (None, None),
+            dx: 0,
+            dy: 0,
+            wheel: 0,
+            moved: false,
+            absolute: false,
+            dropping: false,
+        }
+    }
+
+    /// Abandon the frame in progress. The response to a hole in the event
+    /// stream that the device did not announce.
+    pub fn reset(&mut self) {
+        self.dx = 0;
+        self.dy = 0;
+        self.wheel = 0;
+        self.moved = false;
+        self.absolute = false;
+    }
+
+    pub fn feed(&mut self, event: Event) -> Outcome {
+        if self.dropping {
+            if event.kind == EV_SYN && event.code == SYN_REPORT {
+                self.dropping = false;
+            }
+            return Outcome::None;
+        }
+        match event.kind {
+            EV_SYN => self.sync(event.code),
+            EV_KEY if event.code < BTN_MISC => match event.value {
+                // 2 is the device's own autorepeat. The held-set already has
+                // the key, so it would queue nothing — and a repeat rate is the
+                // reader's to choose, not the hypervisor's.
+                0 | 1 => match key::usage(event.code) {
+                    0 => Outcome::None,
+                    usage => Outcome::Key { usage, pressed: event.value == 1 },
+                },
+                _ => Outcome::None,
+            },
+            EV_KEY => {
+                let bit = match event.code {
+                    BTN_LEFT => 0x01,
+                    BTN_RIGHT => 0x02,
+                    BTN_MIDDLE => 0x04,
+                    _ => return Outcome::None,
+                };
+                let buttons = if event.value != 0 { self.buttons | bit } else { self.buttons & !bit };
+                if buttons != self.buttons {
+                    self.buttons = buttons;
+                    self.moved = true;
+                }
+                Outcome::Pending
+            }
+            EV_REL => {
+                match event.code {
+                    REL_X => self.dx = self.dx.saturating_add(event.value),
+                    REL_Y => self.dy = self.dy.saturating_add(event.value),
+                    REL_WHEEL => self.wheel = self.wheel.saturating_add(event.value),
+                    _ => return Outcome::None,
+                }
+                self.moved = true;
+                Outcome::Pending
+            }
+            EV_ABS => {
+                match event.code {
+                    ABS_X => self.at.0 = Some(self.x.scale(event.value)),
+                    ABS_Y => self.at.1 = Some(self.y.scale(event.value)),
+                    _ => return Outcome::None,
+                }
+                self.moved = true;
+                self.absolute = true;
+                Outcome::Pending
+            }
+            _ => Outcome::None,
+        }
+    }
+
+    fn sync(&mut self, code: u16) -> Outcome {
+        match code {
+            SYN_REPORT => {}
+            SYN_DROPPED => {
+                self.reset();
+                self.buttons = 0;
+                self.dropping = true;
+                return Outcome::Lost;
+            }
+            _ => return Outcome::None,
+        }
+        if !self.moved {
+            return Outcome::None;
+        }
+        let motion = match self.at {
+            (Some(x), Some(y)) if self.absolute => Motion::Absolute { x, y },
+            // A click from a tablet that has not yet said where it is moves
+            // nothing. Anywhere it could be placed is a position it never
+            // reported, and the compositor would deliver the click there.
+            _ => Motion::Relative { dx: self.dx, dy: self.dy },
+        };
+        let sample = Sample {
+            buttons: self.buttons,
+            motion,
+            scroll: self.wheel.clamp(i8::MIN as i32, i8::MAX as i32) as i8,
+        };
+        self.reset();
+        Outcome::Pointer(sample)
+    }
+}
@@ -0,0 +1,55 @@
+//! Linux keycode → HID usage.
+//!
+//! Keycodes below `0x59` are the set-1 scancodes with the release bit taken
+//! off — Linux numbered its keys after the AT keyboard — so that half of the
+//! table is the i8042's. Above it are the keys set 1 reached through `0xE0`,
+//! renumbered, and a run of keys this kernel has no usage for.
+//!
+//! Only usages a keyboard can produce on the other two paths are emitted. A
+//! media key that arrived here as HID `0x7F` would be a keystroke the layouts
+//! have no entry for, from one keyboard and not the others.
+
+/// Unmapped. Never emitted.
+const X: u8 = 0x00;
+
+/// Keycode → HID usage, for every keycode below 128.
+#[rustfmt::skip]
+pub const KEYCODES: [u8; 128] = [
+//  RESERV ESC  1     2     3     4     5     6
+    X,    0x29, 0x1E, 0x1F, 0x20, 0x21, 0x22, 0x23,
+//  7     8     9     0     -     =     BkSp  Tab
+    0x24, 0x25, 0x26, 0x27, 0x2D, 0x2E, 0x2A, 0x2B,
+//  Q     W     E     R     T     Y     U     I
+    0x14, 0x1A, 0x08, 0x15, 0x17, 0x1C, 0x18, 0x0C,
+//  O     P     [     ]     Enter LCtrl A     S
+    0x12, 0x13, 0x2F, 0x30, 0x28, 0xE0, 0x04, 0x16,
+//  D     F     G     H     J     K     L     ;
+    0x07, 0x09, 0x0A, 0x0B, 0x0D, 0x0E, 0x0F, 0x33,
+//  '     `     LShft \     Z     X     C     V
+    0x34, 0x35, 0xE1, 0x31, 0x1D, 0x1B, 0x06, 0x19,
+//  B     N     M     ,     .     /     RShft KP*
+    0x05, 0x11, 0x10, 0x36, 0x37, 0x38, 0xE5, 0x55,
+//  LAlt  Space Caps  F1    F2    F3    F4    F5
+    0xE2, 0x2C, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E,
+//  F6    F7    F8    F9    F10   NumLk ScrLk KP7
+    0x3F, 0x40, 0x41, 0x42, 0x43, 0x53, 0x47, 0x5F,
+//  KP8   KP9   KP-   KP4   KP5   KP6   KP+   KP1
+    0x60, 0x61, 0x56, 0x5C, 0x5D, 0x5E, 0x57, 0x59,
+//  KP2   KP3   KP0   KP.   84    Zenk  ISO   F11
+    0x5A, 0x5B, 0x62, 0x63, X,    X,    0x64, 0x44,
+//  F12   Ro    Kata  Hira  Henk  K/H   Muhen KPJP,
+    0x45, X,    X,    X,    X,    X,    X,    X,
+//  KPEnt RCtrl KP/   SysRq RAlt  LF    Home  Up
+    0x58, 0xE4, 0x54, 0x46, 0xE6, X,    0x4A, 0x52,
+//  PgUp  Left  Right End   Down  PgDn  Ins   Del
+    0x4B, 0x50, 0x4F, 0x4D, 0x51, 0x4E, 0x49, 0x4C,
+//  Macro Mute  Vol-  Vol+  Power KP=   KP+-  Pause
+    X,    X,    X,    X,    X,    X,    X,    0x48,
+//  Scale KP,   Hang  Hanja Yen   LMeta RMeta Menu
+    X,    X,    X,    X,    X,    0xE3, 0xE7, 0x65,
+];
+
+/// The HID usage for `code`, or 0 for a key this kernel does not have.
+pub fn usage(code: u16) -> u8 {
+    KEYCODES.get(code as usize).copied().unwrap_or(X)
+}
@@ -0,0 +1,23 @@
+//! evdev decoding: `(type, code, value)` events in, HID usages and pointer
+//! samples out.
+//!
+//! This is what a virtio-input device puts in its event queue — the device is
+//! a Linux input device forwarded by the hypervisor, and each 8-byte buffer is
+//! one `struct input_event` without its timestamp. The decoding is small and
+//! has the shape of the PS/2 crate's: a keycode table, and a frame that means
+//! nothing until its `SYN_REPORT`. Both are one line away from a bug QEMU would
+//! show only as "the cursor is a little odd", so both live here where a test
+//! can name the line.
+//!
+//! The kernel side (`kernel/src/drivers/virtio_input.rs`) owns the devices,
+//! the queues and the interrupt. Nothing in here touches hardware, allocates,
+//! or knows what a lock is.
+
+#![no_std]
+
+pub mod config;
+pub mod event;
+pub mod key;
+
+pub use config::{Axis, Capabilities};
+pub use event::{Decoder, Event, Motion, Outcome, Sample};
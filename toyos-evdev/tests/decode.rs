@@ -0,0 +1,265 @@
+//! What the decoder promises, checked against the stream a virtio-input device
+//! writes. Nothing here needs QEMU, and the two bugs it exists for — a
+//! diagonal drawn as a staircase, a tablet mapped into one corner — look in
+//! QEMU like a cursor that is merely a little odd.
+
+use toyos_evdev::config::{self, ABS_INFO_BYTES};
+use toyos_evdev::event::*;
+use toyos_evdev::key::{self, KEYCODES};
+use toyos_evdev::{Axis, Capabilities, Decoder, Event, Motion, Outcome, Sample};
+
+fn ev(kind: u16, code: u16, value: i32) -> Event {
+    Event { kind, code, value }
+}
+
+const SYN: Event = Event { kind: EV_SYN, code: SYN_REPORT, value: 0 };
+
+fn feed(decoder: &mut Decoder, events: &[Event]) -> Vec<Outcome> {
+    events.iter().map(|&e| decoder.feed(e)).filter(|o| *o != Outcome::Pending).collect()
+}
+
+fn is_hid_keyboard_usage(usage: u8) -> bool {
+    (0x04..=0x65).contains(&usage) || (0xE0..=0xE7).contains(&usage)
+}
+
+#[test]
+fn the_table_holds_only_real_usages_and_no_duplicates() {
+    let mut seen = [false; 256];
+    for (code, &usage) in KEYCODES.iter().enumerate() {
+        if usage == 0 {
+            continue;
+        }
+        assert!(is_hid_keyboard_usage(usage), "KEYCODES[{code}] = {usage:#04x} is not a HID keyboard usage");
+        assert!(!seen[usage as usize], "two keycodes map to usage {usage:#04x}; {code} is the second");
+        seen[usage as usize] = true;
+    }
+}
+
+/// Linux numbered its keys after set 1, so below `0x59` the two tables are one
+/// table. A keystroke that types one thing on the laptop's keyboard and another
+/// in the VM is this test failing.
+#[test]
+fn the_set1_half_agrees_with_the_i8042() {
+    for (code, (&ours, &set1)) in KEYCODES.iter().zip(&toyos_ps2::key::SET1).take(0x59).enumerate() {
+        assert_eq!(ours, set1, "keycode {code} disagrees with set 1");
+    }
+}
+
+/// The usages `keyboard.rs` and the compositor key off, as the PS/2 test lists
+/// them, plus the ones set 1 reaches only through `0xE0`.
+#[test]
+fn every_usage_the_rest_of_the_tree_names_is_reachable() {
+    let mut reachable = [false; 256];
+    for &usage in KEYCODES.iter() {
+        reachable[usage as usize] = true;
+    }
+    let required: Vec<u8> = (0x04u8..=0x38)
+        .chain([0x46, 0x54, 0x58, 0x64, 0x65])
+        .chain(0x49u8..=0x52)
+        .chain(0xE0u8..=0xE7)
+        .collect();
+    for usage in required {
+        // Non-US hash/tilde: no evdev keycode of its own, as in set 1.
+        if usage == 0x32 {
+            continue;
+        }
+        assert!(reachable[usage as usize], "no keycode produces HID usage {usage:#04x}");
+    }
+}
+
+#[test]
+fn a_code_past_the_table_is_no_key() {
+    assert_eq!(key::usage(128), 0);
+    assert_eq!(key::usage(0x2FF), 0);
+    assert_eq!(key::usage(u16::MAX), 0);
+}
+
+#[test]
+fn press_and_release_come_out_without_waiting_for_the_frame() {
+    let mut d = Decoder::default();
+    assert_eq!(d.feed(ev(EV_KEY, 30, 1)), Outcome::Key { usage: 0x04, pressed: true });
+    assert_eq!(d.feed(SYN), Outcome::None);
+    assert_eq!(d.feed(ev(EV_KEY, 30, 0)), Outcome::Key { usage: 0x04, pressed: false });
+    assert_eq!(d.feed(ev(EV_KEY, 103, 1)), Outcome::Key { usage: 0x52, pressed: true });
+}
+
+#[test]
+fn autorepeat_and_unmapped_keys_name_nothing() {
+    let mut d = Decoder::default();
+    assert_eq!(d.feed(ev(EV_KEY, 30, 2)), Outcome::None);
+    // KEY_MUTE: a real key, and no usage the layouts have.
+    assert_eq!(d.feed(ev(EV_KEY, 113, 1)), Outcome::None);
+    // EV_MSC and EV_LED carry nothing the kernel reads.
+    assert_eq!(d.feed(ev(0x04, 0x04, 0x70004)), Outcome::None);
+    assert_eq!(d.feed(ev(0x11, 0, 1)), Outcome::None);
+}
+
+#[test]
+fn a_diagonal_is_one_sample() {
+    let mut d = Decoder::default();
+    let out = feed(&mut d, &[ev(EV_REL, REL_X, 5), ev(EV_REL, REL_Y, -3), SYN]);
+    assert_eq!(
+        out,
+        [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Relative { dx: 5, dy: -3 }, scroll: 0 })]
+    );
+    // And the next frame starts from nothing.
+    let out = feed(&mut d, &[ev(EV_REL, REL_X, 1), SYN]);
+    assert_eq!(
+        out,
+        [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Relative { dx: 1, dy: 0 }, scroll: 0 })]
+    );
+}
+
+#[test]
+fn a_click_in_the_same_frame_as_the_motion_lands_where_the_motion_ended() {
+    let mut d = Decoder::default();
+    let out = feed(&mut d, &[ev(EV_ABS, ABS_X, 100), ev(EV_ABS, ABS_Y, 200), ev(EV_KEY, BTN_LEFT, 1), SYN]);
+    assert_eq!(
+        out,
+        [Outcome::Pointer(Sample { buttons: 1, motion: Motion::Absolute { x: 100, y: 200 }, scroll: 0 })]
+    );
+}
+
+#[test]
+fn buttons_are_held_across_frames_and_in_boot_mouse_order() {
+    let mut d = Decoder::default();
+    feed(&mut d, &[ev(EV_KEY, BTN_RIGHT, 1), SYN]);
+    let out = feed(&mut d, &[ev(EV_KEY, BTN_MIDDLE, 1), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0b110, motion: Motion::Relative { dx: 0, dy: 0 }, scroll: 0 })]);
+    let out = feed(&mut d, &[ev(EV_REL, REL_X, 2), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0b110, motion: Motion::Relative { dx: 2, dy: 0 }, scroll: 0 })]);
+    let out = feed(&mut d, &[ev(EV_KEY, BTN_RIGHT, 0), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0b100, motion: Motion::Relative { dx: 0, dy: 0 }, scroll: 0 })]);
+    // BTN_SIDE is a button the boot-mouse byte has no bit for.
+    assert_eq!(feed(&mut d, &[ev(EV_KEY, 0x113, 1), SYN]), [Outcome::None, Outcome::None]);
+}
+
+#[test]
+fn the_wheel_keeps_its_sign_and_saturates() {
+    let mut d = Decoder::default();
+    let out = feed(&mut d, &[ev(EV_REL, REL_WHEEL, -1), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Relative { dx: 0, dy: 0 }, scroll: -1 })]);
+    let out = feed(&mut d, &[ev(EV_REL, REL_WHEEL, 1000), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Relative { dx: 0, dy: 0 }, scroll: 127 })]);
+}
+
+/// A tablet sends only the axis that changed. The other one is where it was,
+/// not zero — which would be the cursor jumping to the top edge on every
+/// horizontal move.
+#[test]
+fn an_absolute_axis_not_in_the_frame_keeps_its_last_value() {
+    let mut d = Decoder::default();
+    feed(&mut d, &[ev(EV_ABS, ABS_X, 10), ev(EV_ABS, ABS_Y, 20), SYN]);
+    let out = feed(&mut d, &[ev(EV_ABS, ABS_X, 11), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Absolute { x: 11, y: 20 }, scroll: 0 })]);
+}
+
+#[test]
+fn a_tablet_that_has_not_said_where_it_is_clicks_in_place() {
+    let mut d = Decoder::default();
+    let out = feed(&mut d, &[ev(EV_KEY, BTN_LEFT, 1), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 1, motion: Motion::Relative { dx: 0, dy: 0 }, scroll: 0 })]);
+    let out = feed(&mut d, &[ev(EV_ABS, ABS_X, 5), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 1, motion: Motion::Relative { dx: 0, dy: 0 }, scroll: 0 })]);
+}
+
+#[test]
+fn a_declared_range_is_mapped_onto_the_whole_screen() {
+    let x = Axis { min: 0, max: 1919 };
+    let y = Axis { min: -100, max: 100 };
+    let mut d = Decoder::new(x, y);
+    let out = feed(&mut d, &[ev(EV_ABS, ABS_X, 1919), ev(EV_ABS, ABS_Y, -100), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Absolute { x: 32767, y: 0 }, scroll: 0 })]);
+    let out = feed(&mut d, &[ev(EV_ABS, ABS_X, 0), ev(EV_ABS, ABS_Y, 0), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Absolute { x: 0, y: 16383 }, scroll: 0 })]);
+    // Past the declared edge is the edge.
+    assert_eq!(x.scale(5000), 32767);
+    assert_eq!(x.scale(-5), 0);
+    assert_eq!(Axis::UNSPECIFIED.scale(12345), 12345);
+}
+
+#[test]
+fn an_extreme_range_does_not_overflow() {
+    let axis = Axis { min: i32::MIN, max: i32::MAX };
+    assert_eq!(axis.scale(i32::MIN), 0);
+    assert_eq!(axis.scale(i32::MAX), 32767);
+}
+
+#[test]
+fn abs_info_reads_min_and_max_and_refuses_an_empty_range() {
+    let mut info = [0u8; ABS_INFO_BYTES];
+    info[0..4].copy_from_slice(&(-5i32).to_le_bytes());
+    info[4..8].copy_from_slice(&4095i32.to_le_bytes());
+    assert_eq!(Axis::parse(&info), Some(Axis { min: -5, max: 4095 }));
+    info[4..8].copy_from_slice(&(-5i32).to_le_bytes());
+    assert_eq!(Axis::parse(&info), None);
+}
+
+/// A drop loses releases, and what is left of the frame it broke is not a
+/// frame: it is thrown away through the next `SYN_REPORT`, and the frame after
+/// that starts with no buttons held.
+#[test]
+fn a_drop_is_reported_once_and_the_broken_frame_thrown_away() {
+    let mut d = Decoder::default();
+    feed(&mut d, &[ev(EV_KEY, BTN_LEFT, 1), SYN]);
+    let out = feed(
+        &mut d,
+        &[
+            ev(EV_REL, REL_X, 9),
+            ev(EV_SYN, SYN_DROPPED, 0),
+            ev(EV_REL, REL_Y, 9),
+            ev(EV_KEY, 30, 1),
+            SYN,
+        ],
+    );
+    assert_eq!(out, [Outcome::Lost, Outcome::None, Outcome::None, Outcome::None]);
+    let out = feed(&mut d, &[ev(EV_REL, REL_X, 1), SYN]);
+    assert_eq!(out, [Outcome::Pointer(Sample { buttons: 0, motion: Motion::Relative { dx: 1, dy: 0 }, scroll: 0 })]);
+}
+
+#[test]
+fn an_empty_frame_is_no_sample() {
+    let mut d = Decoder::default();
+    assert_eq!(d.feed(SYN), Outcome::None);
+    // A release of a button that was not held changes nothing either.
+    assert_eq!(feed(&mut d, &[ev(EV_KEY, BTN_LEFT, 0), SYN]), [Outcome::None]);
+}
+
+#[test]
+fn events_parse_little_endian_and_signed() {
+    let bytes = [0x02, 0x00, 0x01, 0x00, 0xFD, 0xFF, 0xFF, 0xFF];
+    assert_eq!(Event::parse(&bytes), ev(EV_REL, REL_Y, -3));
+}
+
+fn bitmap(codes: &[u16]) -> Vec<u8> {
+    let mut bits = vec![0u8; config::UNION_BYTES];
+    for &c in codes {
+        bits[c as usize / 8] |= 1 << (c % 8);
+    }
+    bits
+}
+
+/// The three devices QEMU offers, as their bitmaps describe them.
+#[test]
+fn devices_are_what_their_bitmaps_say() {
+    let keyboard = Capabilities::read(&bitmap(&[1, KEY_A, KEY_SPACE, 103]), &[], &[]);
+    assert_eq!(keyboard, Capabilities { keys: true, relative: false, absolute: false });
+    assert_eq!(keyboard.name(), "keyboard");
+
+    let mouse = Capabilities::read(
+        &bitmap(&[BTN_LEFT, BTN_RIGHT, BTN_MIDDLE]),
+        &bitmap(&[REL_X, REL_Y, REL_WHEEL]),
+        &[],
+    );
+    assert_eq!(mouse, Capabilities { keys: false, relative: true, absolute: false });
+    assert!(mouse.is_pointer());
+
+    let tablet = Capabilities::read(&bitmap(&[BTN_LEFT]), &bitmap(&[REL_WHEEL]), &bitmap(&[ABS_X, ABS_Y]));
+    assert_eq!(tablet.name(), "tablet");
+
+    // A power button has EV_KEY and no letters.
+    let button = Capabilities::read(&bitmap(&[116]), &[], &[]);
+    assert!(button.is_empty());
+    // A short answer is a device without the codes past its end.
+    assert!(!config::has(&[0xFF], 8));
+}
@@ -0,0 +1,80 @@
+#!/bin/sh
+# Regenerates the three committed images. Not run by any test: the images are
+# the fixture, and a test that rebuilt them would be asserting whatever the
+# host's e2fsprogs happens to produce today. Run by hand, once, when the
+# corpus needs to change — and commit what it writes.
+#
+# Made with e2fsprogs 1.47.0. Every source of nondeterminism mke2fs has is
+# pinned (UUID, hash seed, both tools' clocks), so two runs of the same e2fsprogs give
+# the same bytes.
+set -eu
+cd "$(dirname "$0")"
+
+export E2FSPROGS_FAKE_TIME=1760000000 E2FSCK_TIME=1760000000
+UUID=5f1c2c6e-0b3a-4d5e-9a1f-2b7c8d9e0f10
+SEED=8f3e2d1c-4b5a-6978-8a9b-0c1d2e3f4a5b
+
+tree=$(mktemp -d)
+trap 'rm -rf "$tree"' EXIT
+
+# The tree every image carries. `expected.txt` beside the images is its
+# listing, so the tests compare against the host's account and not against
+# this crate's own.
+mkdir -p "$tree/sub/deeper" "$tree/many"
+printf 'hello from a linux disk\n' > "$tree/hello.txt"
+: > "$tree/empty"
+python3 - "$tree" <<'PY'
+import os, sys
+t = sys.argv[1]
+open(f'{t}/sub/deeper/pattern.bin', 'wb').write(bytes((i * 131 + 7) % 251 for i in range(300 * 1024)))
+open(f'{t}/sub/one-block.bin', 'wb').write(bytes((i * 7) % 256 for i in range(4096)))
+for i in range(400):
+    open(f'{t}/many/entry-{i:04d}-with-a-longer-name.txt', 'w').write(f'entry {i}\n')
+# Six data regions 64 KiB apart: six extents, which is more than the four an
+# inode holds, so the extent tree has to grow an index level.
+with open(f'{t}/sparse.bin', 'wb') as f:
+    for r in range(6):
+        f.seek(r * 65536)
+        f.write(bytes([0x41 + r]) * 5000)
+    f.truncate(6 * 65536 + 1234)
+PY
+ln -s hello.txt "$tree/fast-link"
+ln -s sub/deeper/../deeper/pattern.bin/../../../this-is-a-long-symlink-target-that-does-not-fit-in-sixty-bytes \
+    "$tree/slow-link"
+# `mke2fs -d` copies each file's own times, so they are pinned too.
+find "$tree" -exec touch -h -d @1760000000 {} +
+
+# One line per name: `f <size> <path>`, `d <path>` or `l <target> <path>`,
+# sorted. Written by `find`, which has never heard of this crate.
+(cd "$tree" && find . -mindepth 1 \( -type f -printf 'f %s %P\n' \) -o \( -type d -printf 'd %P\n' \) \
+    -o \( -type l -printf 'l %l %P\n' \)) | LC_ALL=C sort -k3 > expected.txt
+
+{
+    for f in / $(cd "$tree" && find . -mindepth 1 -printf '/%P\n'); do
+        echo "sif $f ctime @1760000000"
+        echo "sif $f atime @1760000000"
+    done
+} > "$tree.times"
+trap 'rm -rf "$tree" "$tree.times"' EXIT
+
+make() {
+    out=$1; size=$2; shift 2
+    rm -f "$out"
+    mke2fs -q -F -U "$UUID" -E "hash_seed=$SEED" -d "$tree" "$@" "$out" "$size"
+    # ctime and atime come from the host's stat and cannot be pinned with
+    # `touch`; set them in the image instead.
+    debugfs -w -f "$tree.times" "$out" >/dev/null 2>&1
+    # `-D` rebuilds every directory large enough to want one as an htree, which
+    # `mke2fs -d` does not do by itself.
+    e2fsck -fyD "$out" >/dev/null 2>&1 || [ $? -eq 1 ]
+    e2fsck -fn "$out" >/dev/null
+}
+
+# ext2, 1 KiB blocks: block maps with single and double indirection.
+make ext2-1k.img 3M -t ext2 -b 1024 -L ext2-fixture
+# ext3, 1 KiB blocks, with its journal: block maps again, plus the journal
+# inode and the compatible feature a reader has to ignore.
+make ext3-1k.img 4M -t ext3 -b 1024 -J size=1 -L ext3-fixture
+# ext4 as mke2fs makes it today, minus the journal to keep the image small:
+# extents, 64bit, flex_bg, metadata_csum, 4 KiB blocks.
+make ext4-4k.img 4M -t ext4 -b 4096 -O ^has_journal -L ext4-fixture
@@ -0,0 +1,31 @@
+/// The volume this crate reads.
+///
+/// Byte-addressed, for `toyos-fat32`'s reason: an ext volume's block size is
+/// 1, 2, 4 or 64 KiB and is a field of the superblock, so a trait that spoke
+/// the volume's blocks would need the superblock parsed before it could serve
+/// the read that parses it. The kernel's `BlockDevice` speaks 4096-byte blocks
+/// and bridges in its implementation of this.
+///
+/// **There is no `write_at`.** Not a method left unimplemented — a method that
+/// does not exist, so no code in this crate can be reached that writes, and an
+/// audit of that claim is a `grep` rather than a reading of every caller.
+///
+/// Offsets are relative to the start of the volume, not the disk. A partition
+/// is the implementor's business.
+pub trait BlockAccess {
+    /// Bytes in the volume. Used once, at mount, to reject a superblock that
+    /// describes more volume than exists.
+    fn capacity(&self) -> u64;
+
+    /// Fill `buf` from `offset`. Reading past [`capacity`](Self::capacity) is
+    /// an [`IoError`], not a short read.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError>;
+}
+
+/// The device could not do it.
+///
+/// Carries no detail, for the reason `toyos_fat32::IoError` carries none:
+/// every failure becomes [`Error::Io`](crate::Error::Io) and reaches a caller
+/// that owns the device and already knows more about it than a code could say.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoError;
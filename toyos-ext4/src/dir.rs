@@ -0,0 +1,290 @@
+//! Directory blocks, read linearly or through the htree index.
+//!
+//! Every directory, indexed or not, is a sequence of blocks of entries, and a
+//! linear scan over them is always a correct answer: an htree's root and
+//! interior nodes are disguised as one entry spanning the block, precisely so
+//! that a reader that has never heard of the index still reads the directory.
+//! So the index is an accelerator and nothing more. Anything about it that
+//! does not check out — a version this crate does not know, a count past its
+//! limit, hashes out of order — sends the lookup to the scan rather than to an
+//! error, because the scan needs none of the bytes that were wrong.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::device::BlockAccess;
+use crate::error::Error;
+use crate::hash;
+use crate::inode::{Inode, FL_CASEFOLD, FL_INDEX};
+use crate::le::{le_u16, le_u32};
+use crate::map;
+use crate::superblock::{Superblock, COMPAT_DIR_INDEX, INCOMPAT_FILETYPE, INCOMPAT_LARGEDIR};
+
+/// The longest directory this crate will read, in bytes.
+///
+/// Policy, and a bound on *work*: a directory's size is a field the volume
+/// chose, and every block of it is a device read on every lookup that falls
+/// through to a scan. 128 MiB of 4 KiB blocks is well over a million entries
+/// of ordinary length — far past any directory a person copies files out of —
+/// and still a scan that finishes.
+pub const MAX_DIR_BYTES: u64 = 128 * 1024 * 1024;
+
+/// The longest name an entry can carry: `name_len` is one byte.
+pub const MAX_NAME_BYTES: usize = 255;
+
+/// One live entry of a directory block.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RawEntry<'a> {
+    pub inode: u32,
+    pub name: &'a [u8],
+}
+
+impl RawEntry<'_> {
+    pub fn is_dot_or_dotdot(&self) -> bool {
+        self.name == b"." || self.name == b".."
+    }
+}
+
+/// Walk the entries of one directory block, in order, skipping the unused
+/// ones (inode 0) — which is also what skips an htree node's disguise and a
+/// `metadata_csum` tail.
+///
+/// The first record that does not tile the block ends the walk with
+/// [`Error::CorruptDirectory`]. Every record advances by at least eight bytes,
+/// so the walk is bounded by the block whatever the bytes say.
+pub(crate) fn entries<'a>(
+    sb: &Superblock,
+    block: &'a [u8],
+    mut f: impl FnMut(RawEntry<'a>) -> Result<bool, Error>,
+) -> Result<bool, Error> {
+    let filetype = sb.has_incompat(INCOMPAT_FILETYPE);
+    let mut at = 0usize;
+    while at < block.len() {
+        let rest = &block[at..];
+        if rest.len() < 8 {
+            return Err(Error::CorruptDirectory);
+        }
+        let inode = le_u32(rest, 0);
+        let rec_len = rec_len(sb, le_u16(rest, 4));
+        let name_len = if filetype { rest[6] as usize } else { le_u16(rest, 6) as usize };
+        if rec_len < 8 || !rec_len.is_multiple_of(4) || rec_len > rest.len() || 8 + name_len > rec_len {
+            return Err(Error::CorruptDirectory);
+        }
+        if inode != 0 {
+            if name_len == 0 || name_len > MAX_NAME_BYTES {
+                return Err(Error::CorruptDirectory);
+            }
+            let name = &rest[8..8 + name_len];
+            if name.contains(&b'/') || name.contains(&0) {
+                return Err(Error::CorruptDirectory);
+            }
+            if f(RawEntry { inode, name })? {
+                return Ok(true);
+            }
+        }
+        at += rec_len;
+    }
+    Ok(false)
+}
+
+/// `ext4_rec_len_from_disk`: on a 64 KiB-block volume a whole-block record
+/// does not fit in sixteen bits, so the two low bits — always zero in a
+/// four-aligned length — carry the high bits.
+fn rec_len(sb: &Superblock, raw: u16) -> usize {
+    if sb.block_size < 65_536 {
+        return raw as usize;
+    }
+    if raw == 0xFFFF || raw == 0 {
+        return 65_536;
+    }
+    (raw as usize & 0xFFFC) | ((raw as usize & 3) << 16)
+}
+
+/// Blocks in `dir`, after its size has been held to [`MAX_DIR_BYTES`].
+pub(crate) fn dir_blocks(sb: &Superblock, dir: &Inode) -> Result<u64, Error> {
+    if dir.size > MAX_DIR_BYTES {
+        return Err(Error::LimitExceeded);
+    }
+    Ok(dir.size.div_ceil(sb.block_size as u64))
+}
+
+/// Read block `logical` of `dir` into `buf`. A hole in a directory is
+/// corruption, not zeroes: zeroes would parse as a record of length 0.
+pub(crate) fn read_dir_block<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    node: &mut Vec<u8>,
+    dir: &Inode,
+    logical: u64,
+    buf: &mut [u8],
+) -> Result<(), Error> {
+    let run = map::map(sb, dev, node, dir, logical).map_err(|e| match e {
+        Error::CorruptMap => Error::CorruptDirectory,
+        other => other,
+    })?;
+    let Some(block) = run.physical else {
+        return Err(Error::CorruptDirectory);
+    };
+    dev.read_at(sb.block_offset(block)?, buf)?;
+    Ok(())
+}
+
+/// Call `f` on every live entry of `dir`, `.` and `..` included, until it
+/// answers `true`. Answers whether it did.
+pub(crate) fn scan<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    dir: &Inode,
+    mut f: impl FnMut(RawEntry<'_>) -> Result<bool, Error>,
+) -> Result<bool, Error> {
+    let blocks = dir_blocks(sb, dir)?;
+    let mut node = map::scratch(sb);
+    let mut buf = vec![0u8; sb.block_size as usize];
+    for logical in 0..blocks {
+        read_dir_block(sb, dev, &mut node, dir, logical, &mut buf)?;
+        if entries(sb, &buf, &mut f)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The inode `name` names in `dir`, or `None`.
+pub(crate) fn lookup<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    dir: &Inode,
+    name: &[u8],
+) -> Result<Option<u32>, Error> {
+    if let Some(answer) = htree_lookup(sb, dev, dir, name)? {
+        return Ok(answer);
+    }
+    let mut found = None;
+    scan(sb, dev, dir, |e| {
+        if e.name == name {
+            found = Some(e.inode);
+            return Ok(true);
+        }
+        Ok(false)
+    })?;
+    Ok(found)
+}
+
+/// `dx_root_info.info_length`: eight bytes, and nothing else has ever been
+/// written there.
+const DX_INFO_LEN: u8 = 8;
+
+/// An htree lookup, or `None` when the index cannot be used and the caller
+/// should scan. `Some(None)` is a confident "not here".
+///
+/// The root block is the directory's first: `.` (12 bytes), then `..` spanning
+/// the rest of the block, whose record hides the root info at byte 24 and the
+/// root's entries after it. Each entry is a hash and the logical block of the
+/// subtree holding names that hash at or above it; entry 0's hash is implied
+/// zero and its slot holds the entry array's limit and count instead. Interior
+/// nodes are the same array behind an eight-byte empty record.
+fn htree_lookup<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    dir: &Inode,
+    name: &[u8],
+) -> Result<Option<Option<u32>>, Error> {
+    // Casefolded directories hash a folded name with a different function, and
+    // an exact comparison over a linear scan is what this crate offers them.
+    if !dir.has_flag(FL_INDEX) || !sb.has_compat(COMPAT_DIR_INDEX) || dir.has_flag(FL_CASEFOLD) {
+        return Ok(None);
+    }
+    let bs = sb.block_size as usize;
+    let blocks = dir_blocks(sb, dir)?;
+    let mut node = map::scratch(sb);
+    let mut buf = vec![0u8; bs];
+    read_dir_block(sb, dev, &mut node, dir, 0, &mut buf)?;
+
+    let dot = buf.get(6) == Some(&1) && buf.get(8) == Some(&b'.');
+    let dotdot = buf.get(18) == Some(&2) && buf.get(20..22) == Some(&b".."[..]);
+    if le_u16(&buf, 4) != 12 || !dot || !dotdot {
+        return Ok(None);
+    }
+    let version = buf[28];
+    let info_len = buf[29];
+    let levels = buf[30];
+    let max_levels = if sb.has_incompat(INCOMPAT_LARGEDIR) { 3 } else { 2 };
+    if info_len != DX_INFO_LEN || levels >= max_levels {
+        return Ok(None);
+    }
+    let Some(target) = hash::dx_hash(name, version, sb.unsigned_hash, sb.hash_seed) else {
+        return Ok(None);
+    };
+
+    // Down the interior levels: at each, the entry with the greatest hash not
+    // above the target.
+    let mut at = 24 + info_len as usize;
+    let mut leaf_entries: Vec<(u32, u32)> = Vec::new();
+    let mut chosen = 0usize;
+    for level in 0..=levels {
+        let Some(array) = dx_entries(&buf[at..], blocks) else { return Ok(None) };
+        chosen = array.iter().rposition(|&(h, _)| h <= target).unwrap_or(0);
+        if level == levels {
+            leaf_entries = array;
+            break;
+        }
+        let child = array[chosen].1 as u64;
+        read_dir_block(sb, dev, &mut node, dir, child, &mut buf)?;
+        // An interior node is one empty record spanning the block.
+        if le_u32(&buf, 0) != 0 || rec_len(sb, le_u16(&buf, 4)) != bs {
+            return Ok(None);
+        }
+        at = 8;
+    }
+
+    // The leaf, and then as many following leaves as carry the continuation
+    // bit for this same hash — a run of colliding names can span blocks, and
+    // the index marks where it does.
+    let mut i = chosen;
+    loop {
+        let Some(&(_, block)) = leaf_entries.get(i) else { return Ok(None) };
+        read_dir_block(sb, dev, &mut node, dir, block as u64, &mut buf)?;
+        let mut found = None;
+        entries(sb, &buf, |e| {
+            if e.name == name {
+                found = Some(e.inode);
+                return Ok(true);
+            }
+            Ok(false)
+        })?;
+        if found.is_some() {
+            return Ok(Some(found));
+        }
+        match leaf_entries.get(i + 1) {
+            Some(&(h, _)) if h & 1 == 1 && h & !1 == target => i += 1,
+            // A run that continues past the end of this node's array continues
+            // in the next node, which this walk did not descend into. The scan
+            // is cheaper to be right with than a second descent.
+            None if levels > 0 => return Ok(None),
+            _ => return Ok(Some(None)),
+        }
+    }
+}
+
+/// One node's `(hash, block)` array, checked: a count within a limit the block
+/// can hold, hashes in order, every block inside the directory. Entry 0's hash
+/// is zero by definition.
+fn dx_entries(array: &[u8], dir_blocks: u64) -> Option<Vec<(u32, u32)>> {
+    let limit = le_u16(array, 0) as usize;
+    let count = le_u16(array, 2) as usize;
+    if count == 0 || count > limit || limit * 8 > array.len() {
+        return None;
+    }
+    let mut out = Vec::with_capacity(count);
+    let mut prev = 0u32;
+    for i in 0..count {
+        let hash = if i == 0 { 0 } else { le_u32(array, i * 8) };
+        let block = le_u32(array, i * 8 + 4);
+        if hash < prev || block as u64 >= dir_blocks {
+            return None;
+        }
+        prev = hash;
+        out.push((hash, block));
+    }
+    Some(out)
+}
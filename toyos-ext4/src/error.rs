@@ -0,0 +1,84 @@
+use crate::device::IoError;
+
+/// Everything that can go wrong, as data rather than a panic.
+///
+/// Exhaustive on purpose, as `toyos_fat32::Error` is: an adapter mapping these
+/// to `SyscallError` should stop compiling when a new one appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The device refused a read.
+    Io,
+    /// No ext2/3/4 superblock: bad magic, or a field outside its legal set.
+    NotExt,
+    /// The superblock describes a volume larger than the device.
+    Truncated,
+    /// The volume uses something this reader does not implement — an
+    /// incompatible feature flag it has never heard of, or one of the few it
+    /// knows and refuses (see the crate documentation). Per inode for inline
+    /// data and encryption, which a volume can use on some files and not
+    /// others.
+    Unsupported,
+    /// The journal has transactions that were never replayed, so the metadata
+    /// in place is not the volume's current state. Replaying is a write; the
+    /// answer is to let Linux mount it once.
+    NeedsRecovery,
+    /// A `metadata_csum` checksum did not hold over the superblock, a group
+    /// descriptor or an inode.
+    BadChecksum,
+    /// An inode's fields contradict each other or the volume: a number outside
+    /// the inode table, a size no block map could back, a group whose inode
+    /// table was never initialised.
+    CorruptInode,
+    /// An extent tree or block map is not one: bad magic, a depth past the
+    /// format's, entries out of order, a block outside the volume.
+    CorruptMap,
+    /// A directory's contents are not directory entries: a record length that
+    /// does not tile the block, a name longer than its record, a hole, or a
+    /// directory past [`MAX_DIR_BYTES`](crate::MAX_DIR_BYTES).
+    CorruptDirectory,
+    NotFound,
+    /// A path component that is not the last named something other than a
+    /// directory.
+    NotADirectory,
+    /// The operation is defined only for files and the target is a directory.
+    IsADirectory,
+    /// `read_link` of something that is not a symbolic link, or a link whose
+    /// target is longer than [`MAX_LINK_BYTES`](crate::MAX_LINK_BYTES).
+    NotASymlink,
+    /// A caller-supplied bound, or one of this crate's own, was reached. The
+    /// operation returned nothing rather than part of an answer.
+    LimitExceeded,
+}
+
+impl From<IoError> for Error {
+    fn from(_: IoError) -> Self {
+        Error::Io
+    }
+}
+
+impl Error {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Error::Io => "device I/O failed",
+            Error::NotExt => "not an ext2/3/4 volume",
+            Error::Truncated => "volume larger than device",
+            Error::Unsupported => "uses a feature this reader does not implement",
+            Error::NeedsRecovery => "journal needs recovery",
+            Error::BadChecksum => "metadata checksum mismatch",
+            Error::CorruptInode => "corrupt inode",
+            Error::CorruptMap => "corrupt extent tree or block map",
+            Error::CorruptDirectory => "corrupt directory",
+            Error::NotFound => "no such file or directory",
+            Error::NotADirectory => "not a directory",
+            Error::IsADirectory => "is a directory",
+            Error::NotASymlink => "not a symbolic link",
+            Error::LimitExceeded => "limit exceeded",
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
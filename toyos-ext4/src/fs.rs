@@ -0,0 +1,408 @@
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::device::BlockAccess;
+use crate::dir;
+use crate::error::Error;
+use crate::inode::{Inode, Kind, ROOT_INODE};
+use crate::map;
+use crate::superblock::Superblock;
+
+/// The longest symbolic link target this crate will read: Linux's `PATH_MAX`,
+/// which no link Linux wrote can exceed.
+pub const MAX_LINK_BYTES: u64 = 4096;
+
+/// Symbolic links one path resolution will follow, as Linux's
+/// `MAXSYMLINKS`. A cycle of links is [`Error::LimitExceeded`] when it is
+/// reached rather than a walk that never ends.
+pub const MAX_LINK_FOLLOWS: usize = 40;
+
+/// Directory nesting [`Ext4::walk`] will descend. The visited set already
+/// stops a cycle; this stops a very deep tree, whose every level costs a path
+/// longer than the last.
+const MAX_WALK_DEPTH: usize = 64;
+
+/// Components one resolution may consume, links' targets included. Each link
+/// followed can add up to [`MAX_LINK_BYTES`] / 2 of them, so without this the
+/// follow bound alone would allow a path of 80,000 components.
+const MAX_RESOLVE_COMPONENTS: usize = 4096;
+
+/// A mounted ext2, ext3 or ext4 volume.
+pub struct Ext4<D: BlockAccess> {
+    dev: D,
+    sb: Superblock,
+    /// One volume block, for the extent and block-map walks.
+    node: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub kind: Kind,
+    pub len: u64,
+    pub inode: u32,
+    /// Permission bits, without the file type.
+    pub permissions: u16,
+    pub modified_unix: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub kind: Kind,
+    pub len: u64,
+}
+
+/// An open file: its inode, as read when it was opened.
+///
+/// Plain data with no tie to the volume, and it cannot go stale the way a
+/// `toyos_fat32::File` can, because nothing in this crate writes. It is what
+/// lets a reader that already resolved a path — the kernel's page-fault path —
+/// read a page without resolving it again.
+#[derive(Debug, Clone, Copy)]
+pub struct File {
+    inode: Inode,
+}
+
+impl File {
+    pub fn len(&self) -> u64 {
+        self.inode.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inode.is_empty()
+    }
+
+    pub fn inode(&self) -> u32 {
+        self.inode.number()
+    }
+}
+
+fn metadata_of(inode: &Inode) -> Metadata {
+    Metadata {
+        kind: inode.kind(),
+        len: inode.len(),
+        inode: inode.number(),
+        permissions: inode.permissions(),
+        modified_unix: inode.modified_unix(),
+    }
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = &str> {
+    path.split('/').filter(|c| !c.is_empty() && *c != ".")
+}
+
+impl<D: BlockAccess> Ext4<D> {
+    /// Read and validate the superblock without taking ownership. A total
+    /// read, like everything else here.
+    pub fn probe(dev: &mut D) -> Result<Superblock, Error> {
+        Superblock::read(dev)
+    }
+
+    /// Mount, which is the superblock plus a root inode that is a directory.
+    pub fn mount(mut dev: D) -> Result<Ext4<D>, Error> {
+        let sb = Superblock::read(&mut dev)?;
+        let root = Inode::read(&sb, &mut dev, ROOT_INODE)?;
+        if root.kind() != Kind::Directory {
+            return Err(Error::CorruptInode);
+        }
+        let node = map::scratch(&sb);
+        Ok(Ext4 { dev, sb, node })
+    }
+
+    pub fn superblock(&self) -> &Superblock {
+        &self.sb
+    }
+
+    pub fn device(&mut self) -> &mut D {
+        &mut self.dev
+    }
+
+    pub fn into_device(self) -> D {
+        self.dev
+    }
+
+    fn inode(&mut self, number: u32) -> Result<Inode, Error> {
+        Inode::read(&self.sb, &mut self.dev, number)
+    }
+
+    fn lookup(&mut self, dir: &Inode, name: &[u8]) -> Result<Option<Inode>, Error> {
+        if dir.kind() != Kind::Directory {
+            return Err(Error::NotADirectory);
+        }
+        dir.check_readable(&self.sb)?;
+        match dir::lookup(&self.sb, &mut self.dev, dir, name)? {
+            Some(number) => Ok(Some(self.inode(number)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The inode `path` names, following symbolic links in every component
+    /// and — when `follow_last` — in the last one too.
+    ///
+    /// Links resolve the way Linux resolves them on this volume: a relative
+    /// target against the link's own directory, an absolute one against the
+    /// volume's root, `..` never above the root. Iterative, with the ancestors
+    /// held on a stack so `..` needs no on-disk `..` entry to be honest.
+    fn resolve(&mut self, path: &str, follow_last: bool) -> Result<Inode, Error> {
+        let root = self.inode(ROOT_INODE)?;
+        let mut stack: Vec<Inode> = vec![root];
+        // Components still to consume, last first, so a link's target is
+        // pushed in front of what followed the link.
+        let mut pending: Vec<Vec<u8>> = components(path).rev().map(|c| c.as_bytes().to_vec()).collect();
+        let mut follows = 0usize;
+        let mut consumed = 0usize;
+
+        while let Some(comp) = pending.pop() {
+            consumed += 1;
+            if consumed > MAX_RESOLVE_COMPONENTS {
+                return Err(Error::LimitExceeded);
+            }
+            if comp == b".." {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                continue;
+            }
+            let dir = *stack.last().ok_or(Error::NotFound)?;
+            let node = self.lookup(&dir, &comp)?.ok_or(Error::NotFound)?;
+            let last = pending.is_empty();
+            if node.kind() == Kind::Symlink && (!last || follow_last) {
+                follows += 1;
+                if follows > MAX_LINK_FOLLOWS {
+                    return Err(Error::LimitExceeded);
+                }
+                let target = self.link_target(&node)?;
+                if target.first() == Some(&b'/') {
+                    stack.truncate(1);
+                }
+                for c in target.split(|&b| b == b'/').rev() {
+                    if !c.is_empty() && c != b"." {
+                        pending.push(c.to_vec());
+                    }
+                }
+                continue;
+            }
+            // The next component may be `..`, which never reaches `lookup`;
+            // Linux refuses `file/..`, and so does this.
+            if !last && node.kind() != Kind::Directory {
+                return Err(Error::NotADirectory);
+            }
+            stack.push(node);
+        }
+        stack.pop().ok_or(Error::NotFound)
+    }
+
+    pub fn metadata(&mut self, path: &str) -> Result<Metadata, Error> {
+        let inode = self.resolve(path, true)?;
+        Ok(metadata_of(&inode))
+    }
+
+    /// Like [`metadata`](Self::metadata), but a symbolic link in the last
+    /// component is described rather than followed.
+    pub fn symlink_metadata(&mut self, path: &str) -> Result<Metadata, Error> {
+        let inode = self.resolve(path, false)?;
+        Ok(metadata_of(&inode))
+    }
+
+    pub fn exists(&mut self, path: &str) -> Result<bool, Error> {
+        match self.resolve(path, false) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound) | Err(Error::NotADirectory) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Every entry of one directory, `.` and `..` excluded, refusing above
+    /// `limit` rather than truncating.
+    ///
+    /// A name that is not UTF-8 is left out: the caller names files with
+    /// `str`, so listing one would hand back a name that does not open. It is
+    /// the one way this listing is short of the directory, and it is the
+    /// volume's names and not its structure that cause it.
+    pub fn read_dir(&mut self, path: &str, limit: usize) -> Result<Vec<DirEntry>, Error> {
+        let dir = self.resolve(path, true)?;
+        if dir.kind() != Kind::Directory {
+            return Err(Error::NotADirectory);
+        }
+        let raw = self.children(&dir, limit)?;
+        let mut out = Vec::with_capacity(raw.len());
+        for (name, number) in raw {
+            let inode = self.inode(number)?;
+            out.push(DirEntry { name, kind: inode.kind(), len: inode.len() });
+        }
+        Ok(out)
+    }
+
+    /// `dir`'s entries as names and inode numbers, `.`, `..` and non-UTF-8
+    /// names left out, refusing above `limit`.
+    fn children(&mut self, dir: &Inode, limit: usize) -> Result<Vec<(String, u32)>, Error> {
+        dir.check_readable(&self.sb)?;
+        let mut out = Vec::new();
+        let mut over = false;
+        dir::scan(&self.sb, &mut self.dev, dir, |e| {
+            if e.is_dot_or_dotdot() {
+                return Ok(false);
+            }
+            let Ok(name) = core::str::from_utf8(e.name) else { return Ok(false) };
+            if out.len() >= limit {
+                over = true;
+                return Ok(true);
+            }
+            out.push((String::from(name), e.inode));
+            Ok(false)
+        })?;
+        if over {
+            return Err(Error::LimitExceeded);
+        }
+        Ok(out)
+    }
+
+    /// Every file and symbolic link in the volume, as a path relative to the
+    /// root paired with its size — the shape ToyOS's VFS `list` expects, as
+    /// `toyos_fat32::Fat32::walk` returns it.
+    ///
+    /// Directories appear only as a prefix on the paths inside them, so an
+    /// empty one is invisible here. Device nodes, FIFOs and sockets are left
+    /// out: there is nothing to read in any of them. Links are listed and not
+    /// followed.
+    ///
+    /// Iterative, with a visited set of directory inodes and a depth bound,
+    /// because a crafted volume can hard-link a directory into its own
+    /// subtree. `limit` bounds files and directories alike; either exceeding
+    /// it abandons the whole listing.
+    pub fn walk(&mut self, limit: usize) -> Result<Vec<(String, u64)>, Error> {
+        let mut out = Vec::new();
+        let mut visited = BTreeSet::new();
+        let root = self.inode(ROOT_INODE)?;
+        visited.insert(ROOT_INODE);
+        let mut queue: Vec<(Inode, String, usize)> = vec![(root, String::new(), 0)];
+
+        while let Some((dir, prefix, depth)) = queue.pop() {
+            for (name, number) in self.children(&dir, limit)? {
+                let inode = self.inode(number)?;
+                let mut path = String::with_capacity(prefix.len() + name.len() + 1);
+                path.push_str(&prefix);
+                path.push_str(&name);
+                match inode.kind() {
+                    Kind::Directory => {
+                        if depth + 1 > MAX_WALK_DEPTH || visited.len() >= limit {
+                            return Err(Error::LimitExceeded);
+                        }
+                        if visited.insert(number) {
+                            path.push('/');
+                            queue.push((inode, path, depth + 1));
+                        }
+                    }
+                    Kind::File | Kind::Symlink => {
+                        if out.len() >= limit {
+                            return Err(Error::LimitExceeded);
+                        }
+                        out.push((path, inode.len()));
+                    }
+                    Kind::Other => {}
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Open a regular file, following links. Directories are refused, as is
+    /// anything whose bytes are not on the volume as themselves — inline data
+    /// and encrypted files.
+    pub fn open(&mut self, path: &str) -> Result<File, Error> {
+        let inode = self.resolve(path, true)?;
+        match inode.kind() {
+            Kind::File => {}
+            Kind::Directory => return Err(Error::IsADirectory),
+            Kind::Symlink | Kind::Other => return Err(Error::Unsupported),
+        }
+        inode.check_readable(&self.sb)?;
+        Ok(File { inode })
+    }
+
+    /// Read from `offset`, stopping at the end of the file. Answers how many
+    /// bytes were read, which is short only there.
+    pub fn read(&mut self, file: &File, offset: u64, buf: &mut [u8]) -> Result<usize, Error> {
+        let size = file.inode.len();
+        if offset >= size {
+            return Ok(0);
+        }
+        let n = usize::try_from(size - offset).map_or(buf.len(), |left| left.min(buf.len()));
+        map::read(&self.sb, &mut self.dev, &mut self.node, &file.inode, offset, &mut buf[..n])?;
+        Ok(n)
+    }
+
+    /// The whole file, refusing one longer than `max` bytes before allocating.
+    pub fn read_to_vec(&mut self, path: &str, max: u64) -> Result<Vec<u8>, Error> {
+        let file = self.open(path)?;
+        if file.len() > max {
+            return Err(Error::LimitExceeded);
+        }
+        let mut out = vec![0u8; file.len() as usize];
+        self.read(&file, 0, &mut out)?;
+        Ok(out)
+    }
+
+    /// A link's target bytes, exactly as stored.
+    ///
+    /// Short targets live in `i_block` itself (a "fast" link); longer ones in
+    /// a data block like a file's. The size field says which, together with
+    /// the extents flag — a fast link has no map to walk.
+    fn link_target(&mut self, inode: &Inode) -> Result<Vec<u8>, Error> {
+        if inode.kind() != Kind::Symlink || inode.is_empty() || inode.len() > MAX_LINK_BYTES {
+            return Err(Error::NotASymlink);
+        }
+        inode.check_readable(&self.sb)?;
+        let len = inode.len() as usize;
+        if len < inode.block.len() && !inode.has_flag(crate::inode::FL_EXTENTS) {
+            return Ok(inode.block[..len].to_vec());
+        }
+        let mut out = vec![0u8; len];
+        map::read(&self.sb, &mut self.dev, &mut self.node, inode, 0, &mut out)?;
+        Ok(out)
+    }
+
+    /// What the link at `path` points at, as stored. [`Error::NotASymlink`]
+    /// if it is not one.
+    pub fn read_link(&mut self, path: &str) -> Result<Vec<u8>, Error> {
+        let inode = self.resolve(path, false)?;
+        self.link_target(&inode)
+    }
+
+    /// What the link at `path` points at, rewritten as a path from the
+    /// volume's root — `None` if `path` is not a link.
+    ///
+    /// The shape a mount wants: it has no notion of a link's own directory,
+    /// and resolves every target it is handed from the mount's root. A
+    /// relative target is joined to the link's directory and normalised, an
+    /// absolute one loses its leading `/`, and `..` stops at the root, as it
+    /// does on Linux.
+    ///
+    /// Normalised lexically: `file/..` collapses here where Linux would refuse
+    /// it, so a link that dangles on Linux for that reason can resolve through
+    /// a mount. It names a file on the same volume either way.
+    pub fn read_link_from_root(&mut self, path: &str) -> Result<Option<String>, Error> {
+        let target = match self.read_link(path) {
+            Ok(t) => t,
+            Err(Error::NotASymlink) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let target = String::from_utf8(target).map_err(|_| Error::Unsupported)?;
+
+        let mut parts: Vec<&str> = Vec::new();
+        if !target.starts_with('/') {
+            parts.extend(components(path));
+            parts.pop();
+        }
+        for c in components(&target) {
+            if c == ".." {
+                parts.pop();
+            } else {
+                parts.push(c);
+            }
+        }
+        Ok(Some(parts.join("/")))
+    }
+}
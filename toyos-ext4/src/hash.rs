@@ -0,0 +1,173 @@
+//! The directory-index hash, as `fs/ext4/hash.c` computes it.
+//!
+//! An htree is only a faster way to find a name that a linear scan would also
+//! find, so the cost of a wrong hash here is a lookup that falls through to
+//! the scan or answers `NotFound` for a name that exists — never a bad read.
+//! The second is still wrong, which is why the fixtures' 400-entry directory
+//! is looked up name by name through the index in the tests.
+
+/// `dx_root_info.hash_version`. The unsigned variants are the signed ones plus
+/// three, chosen per volume by a superblock flag rather than written into the
+/// directory.
+pub(crate) const LEGACY: u8 = 0;
+pub(crate) const HALF_MD4: u8 = 1;
+pub(crate) const TEA: u8 = 2;
+const UNSIGNED_OFFSET: u8 = 3;
+
+const DEFAULT_SEED: [u32; 4] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+
+/// The major hash of `name`, or `None` for a version this crate does not know
+/// — which the caller answers with a linear scan.
+pub(crate) fn dx_hash(name: &[u8], version: u8, unsigned: bool, seed: [u32; 4]) -> Option<u32> {
+    let version = if unsigned && version <= TEA { version + UNSIGNED_OFFSET } else { version };
+    let (algorithm, unsigned) = match version {
+        LEGACY..=TEA => (version, false),
+        3..=5 => (version - UNSIGNED_OFFSET, true),
+        _ => return None,
+    };
+    let mut buf = if seed.iter().any(|&w| w != 0) { seed } else { DEFAULT_SEED };
+
+    let hash = match algorithm {
+        LEGACY => legacy(name, unsigned),
+        HALF_MD4 => {
+            let mut words = [0u32; 8];
+            for at in (0..name.len()).step_by(32) {
+                str2hashbuf(&name[at..], &mut words, unsigned);
+                half_md4_transform(&mut buf, &words);
+            }
+            buf[1]
+        }
+        _ => {
+            let mut words = [0u32; 8];
+            for at in (0..name.len()).step_by(16) {
+                str2hashbuf(&name[at..], &mut words[..4], unsigned);
+                tea_transform(&mut buf, &words);
+            }
+            buf[0]
+        }
+    };
+    let hash = hash & !1;
+    // `EXT4_HTREE_EOF_32BIT << 1` is reserved as the end-of-directory cookie.
+    Some(if hash == 0xFFFF_FFFE { 0xFFFF_FFFC } else { hash })
+}
+
+fn char_value(b: u8, unsigned: bool) -> u32 {
+    if unsigned {
+        b as u32
+    } else {
+        b as i8 as i32 as u32
+    }
+}
+
+fn legacy(name: &[u8], unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12A3_FE2Du32, 0x37AB_E8F9u32);
+    for &b in name {
+        let mut hash = hash1.wrapping_add(hash0 ^ char_value(b, unsigned).wrapping_mul(7_152_373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7FFF_FFFF);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Pack up to `out.len() * 4` bytes of `msg` into words, padded with a value
+/// derived from `msg.len()` — the length of the *whole remaining name*, not of
+/// the chunk being packed, which is the detail every reimplementation gets
+/// wrong once.
+fn str2hashbuf(msg: &[u8], out: &mut [u32], unsigned: bool) {
+    let len32 = msg.len() as u32;
+    let mut pad = len32 | (len32 << 8);
+    pad |= pad << 16;
+
+    let mut val = pad;
+    let take = msg.len().min(out.len() * 4);
+    let mut words = out.iter_mut();
+    for (i, &b) in msg[..take].iter().enumerate() {
+        val = char_value(b, unsigned).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            if let Some(w) = words.next() {
+                *w = val;
+            }
+            val = pad;
+        }
+    }
+    if !take.is_multiple_of(4) {
+        if let Some(w) = words.next() {
+            *w = val;
+        }
+    }
+    for w in words {
+        *w = pad;
+    }
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], x: &[u32; 8]) {
+    const K2: u32 = 0o13240474631;
+    const K3: u32 = 0o15666365641;
+    fn f(x: u32, y: u32, z: u32) -> u32 {
+        z ^ (x & (y ^ z))
+    }
+    fn g(x: u32, y: u32, z: u32) -> u32 {
+        (x & y).wrapping_add((x ^ y) & z)
+    }
+    fn h(x: u32, y: u32, z: u32) -> u32 {
+        x ^ y ^ z
+    }
+    fn round(func: fn(u32, u32, u32) -> u32, a: u32, b: u32, c: u32, d: u32, x: u32, s: u32) -> u32 {
+        a.wrapping_add(func(b, c, d)).wrapping_add(x).rotate_left(s)
+    }
+
+    let [mut a, mut b, mut c, mut d] = *buf;
+
+    a = round(f, a, b, c, d, x[0], 3);
+    d = round(f, d, a, b, c, x[1], 7);
+    c = round(f, c, d, a, b, x[2], 11);
+    b = round(f, b, c, d, a, x[3], 19);
+    a = round(f, a, b, c, d, x[4], 3);
+    d = round(f, d, a, b, c, x[5], 7);
+    c = round(f, c, d, a, b, x[6], 11);
+    b = round(f, b, c, d, a, x[7], 19);
+
+    a = round(g, a, b, c, d, x[1].wrapping_add(K2), 3);
+    d = round(g, d, a, b, c, x[3].wrapping_add(K2), 5);
+    c = round(g, c, d, a, b, x[5].wrapping_add(K2), 9);
+    b = round(g, b, c, d, a, x[7].wrapping_add(K2), 13);
+    a = round(g, a, b, c, d, x[0].wrapping_add(K2), 3);
+    d = round(g, d, a, b, c, x[2].wrapping_add(K2), 5);
+    c = round(g, c, d, a, b, x[4].wrapping_add(K2), 9);
+    b = round(g, b, c, d, a, x[6].wrapping_add(K2), 13);
+
+    a = round(h, a, b, c, d, x[3].wrapping_add(K3), 3);
+    d = round(h, d, a, b, c, x[7].wrapping_add(K3), 9);
+    c = round(h, c, d, a, b, x[2].wrapping_add(K3), 11);
+    b = round(h, b, c, d, a, x[6].wrapping_add(K3), 15);
+    a = round(h, a, b, c, d, x[1].wrapping_add(K3), 3);
+    d = round(h, d, a, b, c, x[5].wrapping_add(K3), 9);
+    c = round(h, c, d, a, b, x[0].wrapping_add(K3), 11);
+    b = round(h, b, c, d, a, x[4].wrapping_add(K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+fn tea_transform(buf: &mut [u32; 4], x: &[u32; 8]) {
+    const DELTA: u32 = 0x9E37_79B9;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let (a, b, c, d) = (x[0], x[1], x[2], x[3]);
+    let mut sum = 0u32;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
@@ -0,0 +1,203 @@
+use crate::crc32c::crc32c;
+use crate::device::BlockAccess;
+use crate::error::Error;
+use crate::le::{le_u16, le_u32};
+use crate::superblock::Superblock;
+
+/// The root directory's inode number, fixed by the format.
+pub const ROOT_INODE: u32 = 2;
+
+/// The inode bytes this crate reads: the 128 every revision has, plus the
+/// extra fields a larger inode carries up to and including `i_crtime`. The
+/// checksum covers the whole on-disk inode and is computed over a separate
+/// read of it.
+const INODE_PREFIX: usize = 160;
+
+const S_IFMT: u16 = 0xF000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFREG: u16 = 0x8000;
+const S_IFLNK: u16 = 0xA000;
+
+const FL_ENCRYPT: u32 = 0x800;
+pub(crate) const FL_INDEX: u32 = 0x1000;
+pub(crate) const FL_EXTENTS: u32 = 0x8_0000;
+const FL_INLINE_DATA: u32 = 0x1000_0000;
+pub(crate) const FL_CASEFOLD: u32 = 0x4000_0000;
+
+const OFF_CHECKSUM_LO: usize = 0x7C;
+const OFF_CHECKSUM_HI: usize = 0x82;
+
+/// What an inode is, as far as a reader cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Directory,
+    Symlink,
+    /// A device node, FIFO or socket. Listed by [`Ext4::read_dir`] and
+    /// refused by everything that would read it.
+    ///
+    /// [`Ext4::read_dir`]: crate::Ext4::read_dir
+    Other,
+}
+
+/// One inode, after the fields this crate acts on have been checked.
+///
+/// Plain data: nothing here borrows the volume, and because nothing in this
+/// crate writes, nothing can make a copy of it stale except another writer
+/// on the same device — which a read-only mount of a disk somebody unplugged
+/// from Linux does not have.
+#[derive(Debug, Clone, Copy)]
+pub struct Inode {
+    pub(crate) number: u32,
+    pub(crate) mode: u16,
+    pub(crate) flags: u32,
+    pub(crate) size: u64,
+    pub(crate) mtime: u64,
+    /// `i_block`: an extent tree root, a block map, or a fast symlink's target.
+    pub(crate) block: [u8; 60],
+}
+
+impl Inode {
+    pub(crate) fn read<D: BlockAccess>(sb: &Superblock, dev: &mut D, number: u32) -> Result<Inode, Error> {
+        let offset = sb.inode_offset(dev, number)?;
+        let mut raw = [0u8; INODE_PREFIX];
+        let have = (sb.inode_size as usize).min(INODE_PREFIX);
+        dev.read_at(offset, &mut raw[..have])?;
+
+        // Counted from byte 128, and only meaningful on an inode big enough
+        // to have it.
+        let extra = if sb.inode_size > 128 { le_u16(&raw, 0x80) as usize } else { 0 };
+        if 128 + extra > sb.inode_size as usize || !extra.is_multiple_of(4) {
+            return Err(Error::CorruptInode);
+        }
+
+        if let Some(seed) = sb.csum_seed {
+            check_inode_csum(sb, dev, offset, number, seed, extra, &raw)?;
+        }
+
+        let mode = le_u16(&raw, 0);
+        let flags = le_u32(&raw, 32);
+        let size = le_u32(&raw, 4) as u64 | (le_u32(&raw, 108) as u64) << 32;
+        // A logical block number is 32 bits wide in both block maps and
+        // extents, so a size past 2^32 blocks names bytes no map can hold.
+        if size > (1u64 << 32) * sb.block_size as u64 {
+            return Err(Error::CorruptInode);
+        }
+
+        // `i_mtime` is signed seconds; the two low bits of `i_mtime_extra`
+        // extend it past 2038 when the inode has room for the field.
+        let mut mtime = le_u32(&raw, 16) as i32 as i64;
+        if extra >= 0x8C - 0x80 {
+            mtime += ((le_u32(&raw, 0x88) & 3) as i64) << 32;
+        }
+
+        let mut block = [0u8; 60];
+        block.copy_from_slice(&raw[40..100]);
+        Ok(Inode { number, mode, flags, size, mtime: mtime.max(0) as u64, block })
+    }
+
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self.mode & S_IFMT {
+            S_IFREG => Kind::File,
+            S_IFDIR => Kind::Directory,
+            S_IFLNK => Kind::Symlink,
+            _ => Kind::Other,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Permission bits and the sticky/setuid/setgid bits, without the type.
+    pub fn permissions(&self) -> u16 {
+        self.mode & !S_IFMT
+    }
+
+    /// Seconds since the Unix epoch, UTC, clamped at the epoch.
+    pub fn modified_unix(&self) -> u64 {
+        self.mtime
+    }
+
+    pub(crate) fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Whether this inode's bytes can be read through its block map at all.
+    ///
+    /// Inline data lives in `i_block` plus an extended attribute, and an
+    /// encrypted file's blocks are ciphertext: refusing either is honest and
+    /// returning their blocks is not, because the caller would receive bytes
+    /// that are not the file.
+    pub(crate) fn check_readable(&self, sb: &Superblock) -> Result<(), Error> {
+        if self.has_flag(FL_ENCRYPT) && sb.has_encrypt() {
+            return Err(Error::Unsupported);
+        }
+        if self.has_flag(FL_INLINE_DATA) && sb.has_inline_data() {
+            return Err(Error::Unsupported);
+        }
+        Ok(())
+    }
+}
+
+/// `metadata_csum`'s check of one inode: a chain seeded from the volume, the
+/// inode number and its generation, over the whole on-disk inode with both
+/// halves of the checksum field skipped.
+///
+/// The high half exists only when the extra fields reach it; an inode too
+/// small for it is checked on the low 16 bits, as Linux checks it.
+fn check_inode_csum<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    offset: u64,
+    number: u32,
+    seed: u32,
+    extra: usize,
+    prefix: &[u8; INODE_PREFIX],
+) -> Result<(), Error> {
+    let mut whole = [0u8; 1024];
+    let size = sb.inode_size as usize;
+    let whole = whole.get_mut(..size).ok_or(Error::CorruptInode)?;
+    if size <= INODE_PREFIX {
+        whole.copy_from_slice(&prefix[..size]);
+    } else {
+        dev.read_at(offset, whole)?;
+    }
+
+    let has_hi = size > 128 && extra >= OFF_CHECKSUM_HI + 2 - 128;
+    let generation = le_u32(whole, 100);
+    let mut crc = crc32c(seed, &number.to_le_bytes());
+    crc = crc32c(crc, &generation.to_le_bytes());
+    crc = crc32c(crc, &whole[..OFF_CHECKSUM_LO]);
+    crc = crc32c(crc, &[0, 0]);
+    crc = crc32c(crc, &whole[OFF_CHECKSUM_LO + 2..128]);
+    if size > 128 {
+        crc = crc32c(crc, &whole[128..OFF_CHECKSUM_HI]);
+        let rest = if has_hi {
+            crc = crc32c(crc, &[0, 0]);
+            OFF_CHECKSUM_HI + 2
+        } else {
+            OFF_CHECKSUM_HI
+        };
+        crc = crc32c(crc, &whole[rest..]);
+    }
+
+    let lo = le_u16(whole, OFF_CHECKSUM_LO) as u32;
+    let (stored, computed) = if has_hi {
+        (lo | (le_u16(whole, OFF_CHECKSUM_HI) as u32) << 16, crc)
+    } else {
+        (lo, crc & 0xFFFF)
+    };
+    if stored != computed {
+        return Err(Error::BadChecksum);
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! Little-endian fields out of a byte slice, without a path that panics.
+//!
+//! An offset past the end reads as zero. Every caller passes an offset that is
+//! a constant of the format into a slice already checked to be at least that
+//! long, so the fallback is unreachable — and exists so that being wrong about
+//! that is a wrong number, which the structural checks then refuse, rather than
+//! a panic on bytes somebody else wrote.
+
+pub fn le_u16(buf: &[u8], at: usize) -> u16 {
+    match buf.get(at..at.wrapping_add(2)) {
+        Some(&[a, b]) => u16::from_le_bytes([a, b]),
+        _ => 0,
+    }
+}
+
+pub fn le_u32(buf: &[u8], at: usize) -> u32 {
+    match buf.get(at..at.wrapping_add(4)) {
+        Some(&[a, b, c, d]) => u32::from_le_bytes([a, b, c, d]),
+        _ => 0,
+    }
+}
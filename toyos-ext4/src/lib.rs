@@ -0,0 +1,84 @@
+//! ext2, ext3 and ext4, read-only, over a byte-addressed volume.
+//!
+//! The disk a person is most likely to plug into a ToyOS machine, after the
+//! stick it booted from, is one a Linux machine wrote. This reads it: the
+//! superblock and group descriptors, inode tables, extent trees and the older
+//! indirect block maps, linear and htree-indexed directories, and fast and
+//! slow symbolic links.
+//!
+//! # A volume is untrusted input
+//!
+//! As in `toyos-fat32`, and for the same reason: the bytes come off a disk
+//! anything could have written, so no path that touches them may panic — no
+//! `unwrap`, no indexing by a disk-derived value that was not bounded first,
+//! no arithmetic that can overflow. Every such failure is an [`Error`].
+//!
+//! The hazards particular to this format, and what closes each:
+//!
+//! - **Extent trees can loop.** Every step down an extent tree must land on a
+//!   node whose depth is exactly one less than its parent's, and the root's
+//!   depth is at most five, so the walk is at most six reads whatever the
+//!   pointers say. A loop is a depth mismatch, [`Error::CorruptMap`].
+//! - **Directory trees can be cyclic.** A hard link to a directory is
+//!   something only a crafted volume has, and it has one. [`Ext4::walk`] is
+//!   iterative with a visited set of directory inodes and a depth bound;
+//!   path resolution never reads an on-disk `..` and bounds the symbolic
+//!   links it follows at [`MAX_LINK_FOLLOWS`].
+//! - **Sizes are fields.** A directory past [`MAX_DIR_BYTES`], a link past
+//!   [`MAX_LINK_BYTES`], a listing past the caller's `limit` — each is
+//!   [`Error::LimitExceeded`] before anything of that size is allocated.
+//! - **The htree is a second opinion.** An index that does not check out
+//!   sends the lookup to a linear scan of the same blocks, which every
+//!   directory supports by construction (see `dir.rs`), so a corrupt index
+//!   costs time and never a wrong answer.
+//! - **Checksums are checked when the volume has them.** On a `metadata_csum`
+//!   volume the superblock, every group descriptor read and every inode read
+//!   is verified, and a mismatch is [`Error::BadChecksum`] rather than a
+//!   structure believed.
+//!
+//! # What this crate does not do
+//!
+//! - **No writes.** [`BlockAccess`] has no write method. Writing ext4 honestly
+//!   means the journal, and a half-honest writer is how a Linux disk gets
+//!   corrupted by the machine it was lent to.
+//! - **No journal replay.** A volume whose journal needs recovery is
+//!   [`Error::NeedsRecovery`] at mount: its metadata in place is not its
+//!   current state, and replaying is a write.
+//! - **No inline data, encryption, or casefolded name matching.** Inline-data
+//!   and encrypted inodes are [`Error::Unsupported`] per inode, since a volume
+//!   can use either on some files and not others; a casefolded directory is
+//!   looked up by exact name over a linear scan.
+//! - **No extended attributes or ACLs.** Nothing in ToyOS asks for them.
+//! - **No caching.** As in `toyos-fat32`: the kernel's page cache sits under
+//!   [`BlockAccess`], and every metadata block this crate re-reads is a hit in
+//!   it.
+//!
+//! # Shape
+//!
+//! [`Ext4`] owns a [`BlockAccess`] and the parsed [`Superblock`], and every
+//! path-based call resolves from the root. [`Ext4::open`] hands back a [`File`]
+//! holding a copy of the inode, so repeated reads of one file resolve nothing;
+//! with no writer anywhere, the copy cannot go stale.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+mod crc32c;
+mod device;
+mod dir;
+mod error;
+mod fs;
+mod hash;
+mod inode;
+mod le;
+mod map;
+mod superblock;
+
+pub use device::{BlockAccess, IoError};
+pub use dir::{MAX_DIR_BYTES, MAX_NAME_BYTES};
+pub use error::Error;
+pub use fs::{DirEntry, Ext4, File, Metadata, MAX_LINK_BYTES, MAX_LINK_FOLLOWS};
+pub use inode::Kind;
+pub use superblock::{Superblock, MAX_BLOCK_BYTES, SUPERBLOCK_OFFSET};
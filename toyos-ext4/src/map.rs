@@ -0,0 +1,274 @@
+//! From a file's logical block to the volume's: extent trees and the older
+//! direct/indirect block maps.
+//!
+//! Both answer in runs rather than single blocks, because a caller reading a
+//! page out of a 1 KiB-block volume would otherwise walk the tree four times
+//! for four adjacent blocks that one extent already covered.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::device::BlockAccess;
+use crate::error::Error;
+use crate::inode::{Inode, FL_EXTENTS};
+use crate::le::{le_u16, le_u32};
+use crate::superblock::{Superblock, INCOMPAT_EXTENTS};
+
+const EXTENT_MAGIC: u16 = 0xF30A;
+
+/// The deepest extent tree the format allows. The bound is on *this* walk as
+/// well as on the volume: every step down must land on a node whose own depth
+/// is exactly one less, so a loop in the tree is a depth mismatch and not a
+/// hang.
+const MAX_EXTENT_DEPTH: u16 = 5;
+
+/// An extent whose length field is above this is allocated but unwritten, and
+/// reads as zeroes; its true length is the field minus this.
+const EXTENT_INIT_MAX: u16 = 32_768;
+
+/// Logical blocks are 32 bits wide in every map this crate reads.
+const LOGICAL_LIMIT: u64 = 1 << 32;
+
+/// Where a run of a file's logical blocks lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Run {
+    /// The volume block the run starts at, or `None` for a hole or an
+    /// unwritten extent — both of which read as zeroes.
+    pub physical: Option<u64>,
+    /// Blocks in the run, at least one.
+    pub len: u64,
+}
+
+/// Map `logical` through `inode`'s extent tree or block map.
+///
+/// `node` is scratch of one volume block, owned by the caller so a read that
+/// maps many runs allocates it once.
+pub(crate) fn map<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    node: &mut Vec<u8>,
+    inode: &Inode,
+    logical: u64,
+) -> Result<Run, Error> {
+    if logical >= LOGICAL_LIMIT {
+        return Err(Error::CorruptMap);
+    }
+    node.resize(sb.block_size as usize, 0);
+    if inode.has_flag(FL_EXTENTS) {
+        if !sb.has_incompat(INCOMPAT_EXTENTS) {
+            return Err(Error::CorruptMap);
+        }
+        extent_run(sb, dev, node, inode, logical)
+    } else {
+        block_map_run(sb, dev, node, inode, logical)
+    }
+}
+
+fn extent_run<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    node: &mut [u8],
+    inode: &Inode,
+    logical: u64,
+) -> Result<Run, Error> {
+    let bs = sb.block_size as usize;
+    // The root lives in `i_block`; every other node is a whole block. `in_root`
+    // says which of the two `here` is.
+    let mut in_root = true;
+    // The logical range the current node is responsible for: everything from
+    // its parent index's key up to the next one.
+    let mut limit = LOGICAL_LIMIT;
+    let mut expected_depth: Option<u16> = None;
+
+    loop {
+        let here: &[u8] = if in_root { &inode.block } else { &node[..bs] };
+        let entries = le_u16(here, 2) as usize;
+        let max = le_u16(here, 4) as usize;
+        let depth = le_u16(here, 6);
+        if le_u16(here, 0) != EXTENT_MAGIC
+            || entries > max
+            || 12 + max * 12 > here.len()
+            || depth > MAX_EXTENT_DEPTH
+            || expected_depth.is_some_and(|d| d != depth)
+        {
+            return Err(Error::CorruptMap);
+        }
+
+        if depth == 0 {
+            let mut prev_end = 0u64;
+            for i in 0..entries {
+                let e = &here[12 + i * 12..24 + i * 12];
+                let first = le_u32(e, 0) as u64;
+                let raw_len = le_u16(e, 4);
+                let (len, written) = if raw_len > EXTENT_INIT_MAX {
+                    ((raw_len - EXTENT_INIT_MAX) as u64, false)
+                } else {
+                    (raw_len as u64, true)
+                };
+                let start = le_u32(e, 8) as u64 | (le_u16(e, 6) as u64) << 32;
+                if len == 0 || first < prev_end || first + len > limit {
+                    return Err(Error::CorruptMap);
+                }
+                if start.checked_add(len).is_none_or(|end| end > sb.blocks_count) {
+                    return Err(Error::CorruptMap);
+                }
+                prev_end = first + len;
+
+                if logical < first {
+                    return Ok(Run { physical: None, len: first - logical });
+                }
+                if logical < first + len {
+                    let within = logical - first;
+                    return Ok(Run {
+                        physical: written.then_some(start + within),
+                        len: len - within,
+                    });
+                }
+            }
+            return Ok(Run { physical: None, len: limit - logical });
+        }
+
+        // An index node with no entries names nothing, and a tree is not
+        // allowed to have one anywhere but an empty root — which is depth 0.
+        if entries == 0 {
+            return Err(Error::CorruptMap);
+        }
+        let mut chosen: Option<(u64, u64)> = None;
+        let mut next_limit = limit;
+        let mut prev_key: Option<u64> = None;
+        for i in 0..entries {
+            let e = &here[12 + i * 12..24 + i * 12];
+            let key = le_u32(e, 0) as u64;
+            if prev_key.is_some_and(|p| key <= p) || key >= limit {
+                return Err(Error::CorruptMap);
+            }
+            prev_key = Some(key);
+            if key <= logical {
+                let child = le_u32(e, 4) as u64 | (le_u16(e, 8) as u64) << 32;
+                chosen = Some((key, child));
+            } else {
+                next_limit = key;
+                break;
+            }
+        }
+        let Some((_, child)) = chosen else {
+            // Before the first key: nothing below this node maps it.
+            let first = le_u32(&here[12..24], 0) as u64;
+            return Ok(Run { physical: None, len: first - logical });
+        };
+
+        let offset = sb.block_offset(child)?;
+        dev.read_at(offset, &mut node[..bs])?;
+        in_root = false;
+        limit = next_limit;
+        expected_depth = Some(depth - 1);
+    }
+}
+
+/// Direct, single-, double- and triple-indirect, as ext2 and ext3 lay them
+/// out. Each level multiplies the reach by the pointers in one block, and the
+/// walk down is a fixed number of reads — three at most — so it carries no
+/// loop that a crafted volume could lengthen.
+fn block_map_run<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    node: &mut [u8],
+    inode: &Inode,
+    logical: u64,
+) -> Result<Run, Error> {
+    let bs = sb.block_size as usize;
+    let per = (bs / 4) as u64;
+
+    if logical < 12 {
+        return pointer_run(sb, &inode.block[..48], logical as usize);
+    }
+
+    // Which top-level pointer, how many levels of indirection under it, and
+    // where in that pointer's reach `logical` falls.
+    let rel = logical - 12;
+    let (slot, levels, mut rel) = if rel < per {
+        (12, 1, rel)
+    } else if rel - per < per * per {
+        (13, 2, rel - per)
+    } else if rel - per - per * per < per * per * per {
+        (14, 3, rel - per - per * per)
+    } else {
+        return Err(Error::CorruptMap);
+    };
+
+    let mut pointer = le_u32(&inode.block, slot * 4) as u64;
+    // Blocks `pointer` is responsible for.
+    let mut span = per.pow(levels);
+    for _ in 0..levels {
+        if pointer == 0 {
+            // A missing indirect block is a hole as long as everything it
+            // would have mapped from here to the end of its reach.
+            return Ok(Run { physical: None, len: span - rel });
+        }
+        dev.read_at(sb.block_offset(pointer)?, &mut node[..bs])?;
+        span /= per;
+        let index = (rel / span) as usize;
+        rel %= span;
+        if span == 1 {
+            return pointer_run(sb, &node[..bs], index);
+        }
+        pointer = le_u32(&node[..bs], index * 4) as u64;
+    }
+    Err(Error::CorruptMap)
+}
+
+/// A run out of one array of block pointers, starting at `index`: a hole as
+/// long as the zeroes that follow, or a block as long as the pointers after it
+/// stay contiguous. Never past the array, so the run's end needs no check
+/// beyond its last block's.
+fn pointer_run(sb: &Superblock, pointers: &[u8], index: usize) -> Result<Run, Error> {
+    let count = pointers.len() / 4;
+    let first = le_u32(pointers, index * 4) as u64;
+    let mut len = 1u64;
+    for i in index + 1..count {
+        let p = le_u32(pointers, i * 4) as u64;
+        let follows = if first == 0 { p == 0 } else { p == first + len };
+        if !follows {
+            break;
+        }
+        len += 1;
+    }
+    if first == 0 {
+        return Ok(Run { physical: None, len });
+    }
+    sb.block_offset(first + len - 1)?;
+    Ok(Run { physical: Some(first), len })
+}
+
+/// Read `buf.len()` bytes of `inode`'s data from `offset`, holes as zeroes.
+/// The caller has bounded the range by the inode's size.
+pub(crate) fn read<D: BlockAccess>(
+    sb: &Superblock,
+    dev: &mut D,
+    node: &mut Vec<u8>,
+    inode: &Inode,
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<(), Error> {
+    let bs = sb.block_size as u64;
+    let mut done = 0usize;
+    while done < buf.len() {
+        let pos = offset + done as u64;
+        let run = map(sb, dev, node, inode, pos / bs)?;
+        let within = pos % bs;
+        let run_bytes = run.len.saturating_mul(bs) - within;
+        let take = (buf.len() - done).min(usize::try_from(run_bytes).unwrap_or(usize::MAX));
+        let out = &mut buf[done..done + take];
+        match run.physical {
+            None => out.fill(0),
+            Some(block) => dev.read_at(sb.block_offset(block)? + within, out)?,
+        }
+        done += take;
+    }
+    Ok(())
+}
+
+/// One volume block of scratch.
+pub(crate) fn scratch(sb: &Superblock) -> Vec<u8> {
+    vec![0u8; sb.block_size as usize]
+}
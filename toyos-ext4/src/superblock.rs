@@ -0,0 +1,356 @@
+use crate::crc32c::crc32c;
+use crate::device::BlockAccess;
+use crate::error::Error;
+use crate::le::{le_u16, le_u32};
+
+/// Where the superblock is, in bytes from the start of the volume, whatever
+/// the block size — the first 1024 bytes are the boot block's.
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_BYTES: usize = 1024;
+
+const MAGIC: u16 = 0xEF53;
+
+/// The largest block size the format defines: `s_log_block_size` of 6.
+pub const MAX_BLOCK_BYTES: u32 = 64 * 1024;
+
+// `s_feature_compat`.
+pub(crate) const COMPAT_DIR_INDEX: u32 = 0x20;
+pub(crate) const COMPAT_SPARSE_SUPER2: u32 = 0x200;
+
+// `s_feature_incompat`.
+const INCOMPAT_COMPRESSION: u32 = 0x1;
+pub(crate) const INCOMPAT_FILETYPE: u32 = 0x2;
+const INCOMPAT_RECOVER: u32 = 0x4;
+const INCOMPAT_JOURNAL_DEV: u32 = 0x8;
+const INCOMPAT_META_BG: u32 = 0x10;
+pub(crate) const INCOMPAT_EXTENTS: u32 = 0x40;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_MMP: u32 = 0x100;
+const INCOMPAT_FLEX_BG: u32 = 0x200;
+const INCOMPAT_EA_INODE: u32 = 0x400;
+const INCOMPAT_DIRDATA: u32 = 0x1000;
+const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub(crate) const INCOMPAT_LARGEDIR: u32 = 0x4000;
+const INCOMPAT_INLINE_DATA: u32 = 0x8000;
+const INCOMPAT_ENCRYPT: u32 = 0x1_0000;
+const INCOMPAT_CASEFOLD: u32 = 0x2_0000;
+
+/// Every incompatible feature this reader can read a volume carrying.
+///
+/// Some of them are refused per inode rather than per volume (inline data,
+/// encryption) and the rest change nothing a reader does: `flex_bg` moves
+/// where the inode tables are, which the group descriptors already say; `mmp`
+/// guards against two *writers*; `ea_inode` puts large extended attributes in
+/// inodes nobody here reads; `casefold` changes how a directory hashes, and a
+/// casefolded directory is searched linearly with an exact comparison.
+///
+/// Not in the set and so refused by name: compression, which no Linux ever
+/// shipped; `journal_dev`, which is an external journal and not a filesystem;
+/// `dirdata`, which is Lustre's and changes the directory entry format; and
+/// `recover`, which gets its own error.
+const INCOMPAT_READABLE: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_META_BG
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_MMP
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_EA_INODE
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR
+    | INCOMPAT_INLINE_DATA
+    | INCOMPAT_ENCRYPT
+    | INCOMPAT_CASEFOLD;
+
+const _: () = assert!(INCOMPAT_READABLE & (INCOMPAT_COMPRESSION | INCOMPAT_JOURNAL_DEV | INCOMPAT_DIRDATA) == 0);
+
+// `s_feature_ro_compat`. A reader may ignore every one of these by definition
+// — that is what read-only-compatible means — except the one that tells it
+// what the checksums are.
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub(crate) const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+
+/// `s_flags`: the directory hash treats name bytes as unsigned `char`.
+const FLAG_UNSIGNED_HASH: u32 = 0x2;
+
+// `bg_flags`.
+const BG_INODE_UNINIT: u16 = 0x1;
+
+/// The superblock, once every field this crate computes an offset from has
+/// been checked against the others and against the device.
+#[derive(Debug, Clone)]
+pub struct Superblock {
+    pub block_size: u32,
+    pub blocks_count: u64,
+    pub inodes_count: u32,
+    pub group_count: u32,
+    pub uuid: [u8; 16],
+    /// `s_volume_name`, up to the first NUL.
+    pub label: [u8; 16],
+    pub(crate) first_data_block: u32,
+    pub(crate) blocks_per_group: u32,
+    pub(crate) inodes_per_group: u32,
+    pub(crate) inode_size: u16,
+    pub(crate) desc_size: u16,
+    pub(crate) first_meta_bg: u32,
+    pub(crate) backup_bgs: [u32; 2],
+    pub(crate) compat: u32,
+    pub(crate) incompat: u32,
+    pub(crate) ro_compat: u32,
+    pub(crate) hash_seed: [u32; 4],
+    pub(crate) unsigned_hash: bool,
+    /// Seed for every per-object checksum, or `None` without `metadata_csum`.
+    pub(crate) csum_seed: Option<u32>,
+}
+
+impl Superblock {
+    pub fn read<D: BlockAccess>(dev: &mut D) -> Result<Superblock, Error> {
+        let mut raw = [0u8; SUPERBLOCK_BYTES];
+        dev.read_at(SUPERBLOCK_OFFSET, &mut raw)?;
+        Superblock::parse(&raw, dev.capacity())
+    }
+
+    pub fn parse(raw: &[u8; SUPERBLOCK_BYTES], capacity: u64) -> Result<Superblock, Error> {
+        if le_u16(raw, 56) != MAGIC {
+            return Err(Error::NotExt);
+        }
+        let rev_level = le_u32(raw, 76);
+        if rev_level > 1 {
+            return Err(Error::NotExt);
+        }
+        let log_block = le_u32(raw, 24);
+        if log_block > 6 {
+            return Err(Error::NotExt);
+        }
+        let block_size = 1024u32 << log_block;
+
+        // A revision-0 volume predates every feature field, and what it has
+        // there is not guaranteed to be zero.
+        let (compat, incompat, ro_compat, inode_size) = if rev_level == 0 {
+            (0, 0, 0, 128)
+        } else {
+            (le_u32(raw, 92), le_u32(raw, 96), le_u32(raw, 100), le_u16(raw, 88))
+        };
+        if incompat & INCOMPAT_RECOVER != 0 {
+            return Err(Error::NeedsRecovery);
+        }
+        if incompat & !INCOMPAT_READABLE != 0 {
+            return Err(Error::Unsupported);
+        }
+
+        if ro_compat & RO_COMPAT_METADATA_CSUM != 0 {
+            // `s_checksum_type`: 1 is CRC32C and is the only one defined.
+            if raw[0x175] != 1 {
+                return Err(Error::Unsupported);
+            }
+            let stored = le_u32(raw, 0x3FC);
+            if crc32c(!0, &raw[..0x3FC]) != stored {
+                return Err(Error::BadChecksum);
+            }
+        }
+
+        if inode_size < 128 || !inode_size.is_power_of_two() || inode_size as u32 > block_size {
+            return Err(Error::NotExt);
+        }
+
+        let blocks_lo = le_u32(raw, 4) as u64;
+        let (blocks_count, desc_size) = if incompat & INCOMPAT_64BIT != 0 {
+            let desc = le_u16(raw, 254);
+            if desc < 64 || !desc.is_power_of_two() || desc as u32 > block_size.min(1024) {
+                return Err(Error::NotExt);
+            }
+            (blocks_lo | (le_u32(raw, 336) as u64) << 32, desc)
+        } else {
+            (blocks_lo, 32)
+        };
+
+        let first_data_block = le_u32(raw, 20);
+        let blocks_per_group = le_u32(raw, 32);
+        let inodes_per_group = le_u32(raw, 40);
+        let inodes_count = le_u32(raw, 0);
+        // A group's bitmaps are one block each, so neither count may exceed a
+        // block's bits — the bound the format itself lives within, and the one
+        // that keeps the arithmetic below far from overflowing.
+        let per_group_max = block_size * 8;
+        if first_data_block > 1
+            || first_data_block as u64 >= blocks_count
+            || blocks_per_group == 0
+            || blocks_per_group > per_group_max
+            || inodes_per_group == 0
+            || inodes_per_group > per_group_max
+            || inodes_count == 0
+        {
+            return Err(Error::NotExt);
+        }
+        let group_count = (blocks_count - first_data_block as u64).div_ceil(blocks_per_group as u64);
+        let group_count = u32::try_from(group_count).map_err(|_| Error::NotExt)?;
+        if inodes_count as u64 > group_count as u64 * inodes_per_group as u64 {
+            return Err(Error::NotExt);
+        }
+
+        let bytes = blocks_count.checked_mul(block_size as u64).ok_or(Error::NotExt)?;
+        if bytes > capacity {
+            return Err(Error::Truncated);
+        }
+
+        let mut uuid = [0u8; 16];
+        uuid.copy_from_slice(&raw[104..120]);
+        let mut label = [0u8; 16];
+        label.copy_from_slice(&raw[120..136]);
+
+        let mut hash_seed = [0u32; 4];
+        for (i, word) in hash_seed.iter_mut().enumerate() {
+            *word = le_u32(raw, 236 + i * 4);
+        }
+
+        let csum_seed = if ro_compat & RO_COMPAT_METADATA_CSUM == 0 {
+            None
+        } else if incompat & INCOMPAT_CSUM_SEED != 0 {
+            Some(le_u32(raw, 0x270))
+        } else {
+            Some(crc32c(!0, &uuid))
+        };
+
+        Ok(Superblock {
+            block_size,
+            blocks_count,
+            inodes_count,
+            uuid,
+            label,
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            inode_size,
+            desc_size,
+            group_count,
+            first_meta_bg: if incompat & INCOMPAT_META_BG != 0 { le_u32(raw, 260) } else { u32::MAX },
+            backup_bgs: [le_u32(raw, 0x24C), le_u32(raw, 0x250)],
+            compat,
+            incompat,
+            ro_compat,
+            hash_seed,
+            unsigned_hash: le_u32(raw, 352) & FLAG_UNSIGNED_HASH != 0,
+            csum_seed,
+        })
+    }
+
+    /// `s_volume_name` as text, or `None` if it is not UTF-8.
+    pub fn label_str(&self) -> Option<&str> {
+        let end = self.label.iter().position(|&b| b == 0).unwrap_or(self.label.len());
+        core::str::from_utf8(&self.label[..end]).ok()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.blocks_count * self.block_size as u64
+    }
+
+    pub(crate) fn has_incompat(&self, bit: u32) -> bool {
+        self.incompat & bit != 0
+    }
+
+    pub(crate) fn has_compat(&self, bit: u32) -> bool {
+        self.compat & bit != 0
+    }
+
+    pub(crate) fn has_encrypt(&self) -> bool {
+        self.has_incompat(INCOMPAT_ENCRYPT)
+    }
+
+    pub(crate) fn has_inline_data(&self) -> bool {
+        self.has_incompat(INCOMPAT_INLINE_DATA)
+    }
+
+    /// The byte offset of block `block`, or [`Error::CorruptMap`] if the
+    /// volume has no such block. Every block number read off the volume
+    /// reaches the device through here.
+    pub(crate) fn block_offset(&self, block: u64) -> Result<u64, Error> {
+        if block >= self.blocks_count {
+            return Err(Error::CorruptMap);
+        }
+        Ok(block * self.block_size as u64)
+    }
+
+    /// Whether group `group` carries a backup superblock and descriptor
+    /// table, which is what decides where a `meta_bg` descriptor block sits.
+    fn has_super(&self, group: u32) -> bool {
+        if group == 0 {
+            return true;
+        }
+        if self.has_compat(COMPAT_SPARSE_SUPER2) {
+            return self.backup_bgs.contains(&group);
+        }
+        if self.ro_compat & RO_COMPAT_SPARSE_SUPER == 0 {
+            return true;
+        }
+        group == 1 || is_power_of(group, 3) || is_power_of(group, 5) || is_power_of(group, 7)
+    }
+
+    fn group_first_block(&self, group: u32) -> u64 {
+        self.first_data_block as u64 + group as u64 * self.blocks_per_group as u64
+    }
+
+    /// The byte offset of group `group`'s descriptor.
+    fn descriptor_offset(&self, group: u32) -> Result<u64, Error> {
+        let per_block = self.block_size / self.desc_size as u32;
+        let meta_group = group / per_block;
+        if meta_group < self.first_meta_bg {
+            let table = self.block_offset(self.first_data_block as u64 + 1)?;
+            return Ok(table + group as u64 * self.desc_size as u64);
+        }
+        let first = meta_group * per_block;
+        let block = self.group_first_block(first) + self.has_super(first) as u64;
+        Ok(self.block_offset(block)? + (group % per_block) as u64 * self.desc_size as u64)
+    }
+
+    /// Where inode `ino`'s bytes are: the group's inode table, checked
+    /// against the volume, plus the inode's slot in it.
+    pub(crate) fn inode_offset<D: BlockAccess>(&self, dev: &mut D, ino: u32) -> Result<u64, Error> {
+        if ino == 0 || ino > self.inodes_count {
+            return Err(Error::CorruptInode);
+        }
+        let group = (ino - 1) / self.inodes_per_group;
+        let index = (ino - 1) % self.inodes_per_group;
+
+        let mut desc = [0u8; 1024];
+        let desc = &mut desc[..self.desc_size as usize];
+        dev.read_at(self.descriptor_offset(group)?, desc)?;
+        self.check_descriptor(group, desc)?;
+
+        let mut table = le_u32(desc, 8) as u64;
+        if self.desc_size >= 64 {
+            table |= (le_u32(desc, 40) as u64) << 32;
+        }
+        // Only trusted when something keeps it up to date: without a group
+        // checksum feature the kernel never maintains this flag.
+        if self.csum_seed.is_some() && le_u16(desc, 18) & BG_INODE_UNINIT != 0 {
+            return Err(Error::CorruptInode);
+        }
+        let table_bytes = self.inodes_per_group as u64 * self.inode_size as u64;
+        let last = table + table_bytes.div_ceil(self.block_size as u64) - 1;
+        self.block_offset(last).map_err(|_| Error::CorruptInode)?;
+        Ok(self.block_offset(table)? + index as u64 * self.inode_size as u64)
+    }
+
+    /// `metadata_csum`'s check of one group descriptor: the low 16 bits of a
+    /// chain over the group number and the descriptor with its own checksum
+    /// field skipped. The older `gdt_csum` (CRC16) is not checked — it guards
+    /// against the same accidents, and the structural checks are what stand
+    /// between hostile bytes and an offset either way.
+    fn check_descriptor(&self, group: u32, desc: &[u8]) -> Result<(), Error> {
+        let Some(seed) = self.csum_seed else { return Ok(()) };
+        let mut crc = crc32c(seed, &group.to_le_bytes());
+        crc = crc32c(crc, &desc[..0x1E]);
+        crc = crc32c(crc, &[0, 0]);
+        crc = crc32c(crc, &desc[0x20..]);
+        if crc as u16 != le_u16(desc, 0x1E) {
+            return Err(Error::BadChecksum);
+        }
+        Ok(())
+    }
+}
+
+fn is_power_of(mut n: u32, base: u32) -> bool {
+    while n > 1 && n.is_multiple_of(base) {
+        n /= base;
+    }
+    n == 1
+}
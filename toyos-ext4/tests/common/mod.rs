@@ -0,0 +1,207 @@
+//! Host-side scaffolding: the committed images, a device that carries one, and
+//! the host's own account of what is in them.
+//!
+//! The images are made by `fixtures/make.sh` with e2fsprogs and checked by
+//! `e2fsck`; `fixtures/expected.txt` is `find`'s listing of the tree they were
+//! made from. Nothing here parses the volume the way the crate does except the
+//! few raw offsets the hostile tests aim at, which are read straight out of the
+//! on-disk format and never through the crate.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use toyos_ext4::{BlockAccess, IoError};
+
+pub const IMAGES: [&str; 3] = ["ext2-1k.img", "ext3-1k.img", "ext4-4k.img"];
+
+/// Every file and directory in the fixtures carries this mtime.
+pub const FIXTURE_TIME: u64 = 1_760_000_000;
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+pub fn image(name: &str) -> Vec<u8> {
+    std::fs::read(fixtures().join(name)).unwrap_or_else(|e| panic!("read fixture {name}: {e}"))
+}
+
+/// A volume in memory, counting what the crate asks of it.
+#[derive(Clone)]
+pub struct MemDevice {
+    pub bytes: Vec<u8>,
+    /// Reads served so far.
+    pub reads: u32,
+    /// Fail every read after this many.
+    pub fail_after: Option<u32>,
+}
+
+impl MemDevice {
+    pub fn new(bytes: Vec<u8>) -> MemDevice {
+        MemDevice { bytes, reads: 0, fail_after: None }
+    }
+
+    pub fn fixture(name: &str) -> MemDevice {
+        MemDevice::new(image(name))
+    }
+
+    pub fn poke(&mut self, offset: u64, bytes: &[u8]) {
+        let at = offset as usize;
+        self.bytes[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    pub fn peek(&self, offset: u64, len: usize) -> &[u8] {
+        &self.bytes[offset as usize..offset as usize + len]
+    }
+
+    pub fn u16_at(&self, offset: u64) -> u16 {
+        u16::from_le_bytes(self.peek(offset, 2).try_into().unwrap())
+    }
+
+    pub fn u32_at(&self, offset: u64) -> u32 {
+        u32::from_le_bytes(self.peek(offset, 4).try_into().unwrap())
+    }
+}
+
+impl BlockAccess for MemDevice {
+    fn capacity(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError> {
+        if self.fail_after.is_some_and(|n| self.reads >= n) {
+            return Err(IoError);
+        }
+        self.reads += 1;
+        let end = offset.checked_add(buf.len() as u64).ok_or(IoError)?;
+        if end > self.bytes.len() as u64 {
+            return Err(IoError);
+        }
+        buf.copy_from_slice(&self.bytes[offset as usize..end as usize]);
+        Ok(())
+    }
+}
+
+/// One line of `expected.txt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    File { path: String, size: u64 },
+    Dir { path: String },
+    Link { path: String, target: String },
+}
+
+pub fn expected() -> Vec<Expected> {
+    let text = std::fs::read_to_string(fixtures().join("expected.txt")).expect("expected.txt");
+    text.lines()
+        .map(|line| {
+            let (kind, rest) = line.split_once(' ').expect("kind");
+            match kind {
+                "d" => Expected::Dir { path: rest.into() },
+                "f" => {
+                    let (size, path) = rest.split_once(' ').expect("size");
+                    Expected::File { path: path.into(), size: size.parse().expect("size") }
+                }
+                "l" => {
+                    let (target, path) = rest.split_once(' ').expect("target");
+                    Expected::Link { path: path.into(), target: target.into() }
+                }
+                other => panic!("bad line kind {other:?}"),
+            }
+        })
+        .collect()
+}
+
+/// What `make.sh` wrote into each file, regenerated from the same formulas.
+pub fn content(path: &str) -> Vec<u8> {
+    match path {
+        "hello.txt" => b"hello from a linux disk\n".to_vec(),
+        "empty" => Vec::new(),
+        "sub/deeper/pattern.bin" => (0..300 * 1024).map(|i: u32| ((i * 131 + 7) % 251) as u8).collect(),
+        "sub/one-block.bin" => (0..4096).map(|i: u32| ((i * 7) % 256) as u8).collect(),
+        "sparse.bin" => {
+            let mut out = vec![0u8; 6 * 65536 + 1234];
+            for r in 0..6 {
+                out[r * 65536..r * 65536 + 5000].fill(0x41 + r as u8);
+            }
+            out
+        }
+        _ => {
+            let n: u32 = path
+                .strip_prefix("many/entry-")
+                .and_then(|rest| rest.get(..4))
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| panic!("no content known for {path}"));
+            format!("entry {n}\n").into_bytes()
+        }
+    }
+}
+
+// ---------------------------------------------------------------- raw layout
+
+/// The handful of superblock fields the hostile tests need to find an inode,
+/// read off the raw bytes.
+pub struct Layout {
+    pub block_size: u64,
+    pub inodes_per_group: u32,
+    pub inode_size: u64,
+    pub desc_size: u64,
+    pub first_data_block: u64,
+}
+
+pub fn layout(dev: &MemDevice) -> Layout {
+    let sb = 1024;
+    let incompat = dev.u32_at(sb + 96);
+    Layout {
+        block_size: 1024 << dev.u32_at(sb + 24),
+        inodes_per_group: dev.u32_at(sb + 40),
+        inode_size: dev.u16_at(sb + 88) as u64,
+        desc_size: if incompat & 0x80 != 0 { dev.u16_at(sb + 254) as u64 } else { 32 },
+        first_data_block: dev.u32_at(sb + 20) as u64,
+    }
+}
+
+impl Layout {
+    /// Where group `group`'s descriptor sits — none of the fixtures uses
+    /// `meta_bg`, so it is the table after the superblock.
+    pub fn descriptor(&self, group: u32) -> u64 {
+        (self.first_data_block + 1) * self.block_size + group as u64 * self.desc_size
+    }
+
+    pub fn inode(&self, dev: &MemDevice, ino: u32) -> u64 {
+        let group = (ino - 1) / self.inodes_per_group;
+        let index = (ino - 1) % self.inodes_per_group;
+        let table = dev.u32_at(self.descriptor(group) + 8) as u64;
+        table * self.block_size + index as u64 * self.inode_size
+    }
+
+    /// The block `i_block[0]` names: the first data block of a block-mapped
+    /// inode, or — for an extent-mapped one with a depth-0 root — the start of
+    /// its first extent.
+    pub fn first_block(&self, dev: &MemDevice, ino: u32) -> u64 {
+        let inode = self.inode(dev, ino);
+        let flags = dev.u32_at(inode + 32);
+        if flags & 0x8_0000 != 0 {
+            assert_eq!(dev.u16_at(inode + 40 + 6), 0, "first_block wants a depth-0 extent root");
+            dev.u32_at(inode + 40 + 12 + 8) as u64
+        } else {
+            dev.u32_at(inode + 40) as u64
+        }
+    }
+
+    /// The byte offset of `name`'s entry in directory `dir`, searching its
+    /// first block only.
+    pub fn dir_entry(&self, dev: &MemDevice, dir: u32, name: &str) -> u64 {
+        let start = self.first_block(dev, dir) * self.block_size;
+        let mut at = start;
+        while at < start + self.block_size {
+            let rec_len = dev.u16_at(at + 4) as u64;
+            let name_len = dev.peek(at + 6, 1)[0] as usize;
+            if dev.u32_at(at) != 0 && dev.peek(at + 8, name_len) == name.as_bytes() {
+                return at;
+            }
+            assert!(rec_len >= 8, "fixture directory is not what the test expects");
+            at += rec_len;
+        }
+        panic!("{name} is not in the first block of inode {dir}");
+    }
+}
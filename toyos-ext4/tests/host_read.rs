@@ -0,0 +1,189 @@
+//! e2fsprogs writes the volume, this crate reads it.
+//!
+//! Every assertion has ground truth outside this repository: the images came
+//! from `mke2fs -d` and passed `e2fsck -fn`, the listing they are compared
+//! against is `find`'s, and the contents are regenerated from the formulas
+//! `make.sh` wrote them with. A reader that agrees with itself cannot pass.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{content, expected, Expected, MemDevice, FIXTURE_TIME, IMAGES};
+use toyos_ext4::{Error, Ext4, Kind};
+
+fn mount(name: &str) -> Ext4<MemDevice> {
+    Ext4::mount(MemDevice::fixture(name)).unwrap_or_else(|e| panic!("{name}: {e}"))
+}
+
+#[test]
+fn every_image_mounts_with_its_label() {
+    for name in IMAGES {
+        let fs = mount(name);
+        let label = name.split('-').next().unwrap();
+        assert_eq!(fs.superblock().label_str(), Some(format!("{label}-fixture").as_str()));
+    }
+}
+
+#[test]
+fn walk_lists_what_find_listed() {
+    let mut want: BTreeMap<String, u64> = BTreeMap::new();
+    for e in expected() {
+        match e {
+            Expected::File { path, size } => {
+                want.insert(path, size);
+            }
+            Expected::Link { path, target } => {
+                want.insert(path, target.len() as u64);
+            }
+            Expected::Dir { .. } => {}
+        }
+    }
+    for name in IMAGES {
+        let mut fs = mount(name);
+        let got: BTreeMap<String, u64> = fs.walk(10_000).expect("walk").into_iter().collect();
+        assert_eq!(got, want, "{name}");
+    }
+}
+
+#[test]
+fn every_file_reads_back_its_bytes() {
+    for name in IMAGES {
+        let mut fs = mount(name);
+        for e in expected() {
+            let Expected::File { path, size } = e else { continue };
+            let data = fs.read_to_vec(&path, 1 << 20).unwrap_or_else(|e| panic!("{name}: {path}: {e}"));
+            assert_eq!(data.len() as u64, size, "{name}: {path}");
+            assert!(data == content(&path), "{name}: {path} differs");
+        }
+    }
+}
+
+#[test]
+fn reads_at_odd_offsets_match_whole_reads() {
+    for name in IMAGES {
+        let mut fs = mount(name);
+        let want = content("sub/deeper/pattern.bin");
+        let file = fs.open("sub/deeper/pattern.bin").expect("open");
+        for (offset, len) in [(0u64, 1usize), (1023, 2), (4095, 4098), (12 * 1024 - 7, 300), (299 * 1024, 4096)] {
+            let mut buf = vec![0xEEu8; len];
+            let n = fs.read(&file, offset, &mut buf).expect("read");
+            let end = (offset as usize + len).min(want.len());
+            assert_eq!(n, end - offset as usize, "{name} @{offset}");
+            assert_eq!(&buf[..n], &want[offset as usize..end], "{name} @{offset}");
+        }
+        let mut buf = [0u8; 16];
+        assert_eq!(fs.read(&file, want.len() as u64, &mut buf), Ok(0));
+    }
+}
+
+#[test]
+fn holes_read_as_zeroes() {
+    for name in IMAGES {
+        let mut fs = mount(name);
+        let file = fs.open("sparse.bin").expect("open");
+        let mut buf = vec![0xEEu8; 60_000];
+        fs.read(&file, 5000, &mut buf).expect("read");
+        assert!(buf.iter().all(|&b| b == 0), "{name}");
+        let mut one = [0u8; 1];
+        fs.read(&file, 5 * 65536 + 4999, &mut one).expect("read");
+        assert_eq!(one, [0x46], "{name}");
+    }
+}
+
+#[test]
+fn metadata_matches_the_tree() {
+    for name in IMAGES {
+        let mut fs = mount(name);
+        let m = fs.metadata("hello.txt").expect("hello");
+        assert_eq!((m.kind, m.len, m.modified_unix), (Kind::File, 24, FIXTURE_TIME));
+        assert_eq!(fs.metadata("sub/deeper").expect("dir").kind, Kind::Directory);
+        assert_eq!(fs.metadata("/sub/./deeper/../one-block.bin").expect("dots").len, 4096);
+        assert_eq!(fs.metadata("nope"), Err(Error::NotFound));
+        assert_eq!(fs.metadata("hello.txt/more"), Err(Error::NotADirectory));
+        assert_eq!(fs.metadata("hello.txt/.."), Err(Error::NotADirectory));
+        assert_eq!(fs.open("sub").err(), Some(Error::IsADirectory));
+    }
+}
+
+#[test]
+fn read_dir_lists_one_level() {
+    for name in IMAGES {
+        let mut fs = mount(name);
+        let mut names: Vec<(String, Kind)> =
+            fs.read_dir("sub", 100).expect("read_dir").into_iter().map(|e| (e.name, e.kind)).collect();
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(names, [("deeper".into(), Kind::Directory), ("one-block.bin".into(), Kind::File)], "{name}");
+        assert_eq!(fs.read_dir("many", 1000).expect("many").len(), 400);
+        assert_eq!(fs.read_dir("many", 399).map(|v| v.len()), Err(Error::LimitExceeded));
+        assert_eq!(fs.read_dir("hello.txt", 10).map(|v| v.len()), Err(Error::NotADirectory));
+    }
+}
+
+#[test]
+fn symbolic_links_fast_and_slow() {
+    let slow_target = expected()
+        .into_iter()
+        .find_map(|e| match e {
+            Expected::Link { path, target } if path == "slow-link" => Some(target),
+            _ => None,
+        })
+        .expect("slow-link in expected.txt");
+    assert!(slow_target.len() >= 60, "the slow link must not fit in i_block");
+
+    for name in IMAGES {
+        let mut fs = mount(name);
+        assert_eq!(fs.read_link("fast-link").expect("fast"), b"hello.txt");
+        assert_eq!(fs.read_link("slow-link").expect("slow"), slow_target.as_bytes());
+        assert_eq!(fs.read_link("hello.txt"), Err(Error::NotASymlink));
+
+        // Followed, a link is its target; not followed, itself.
+        assert_eq!(fs.metadata("fast-link").expect("follow").len, 24);
+        assert_eq!(fs.symlink_metadata("fast-link").expect("nofollow").kind, Kind::Symlink);
+        assert_eq!(fs.read_to_vec("fast-link", 100).expect("through link"), content("hello.txt"));
+        // `pattern.bin/..` is not a path on Linux, so the slow link dangles.
+        assert_eq!(fs.metadata("slow-link"), Err(Error::NotADirectory));
+
+        assert_eq!(fs.read_link_from_root("fast-link"), Ok(Some("hello.txt".into())));
+        assert_eq!(
+            fs.read_link_from_root("slow-link"),
+            Ok(Some("this-is-a-long-symlink-target-that-does-not-fit-in-sixty-bytes".into()))
+        );
+        assert_eq!(fs.read_link_from_root("hello.txt"), Ok(None));
+    }
+}
+
+/// The htree is checked by looking every name up through it: a lookup that
+/// fell back to a scan would still succeed, so the read count is what shows
+/// the index was used, and a wrong hash is a `NotFound` for a name that
+/// exists.
+///
+/// The yardstick is a lookup of the same depth in `sub`, a one-block
+/// directory: an indexed lookup in `many` costs the index root and one leaf
+/// where that costs one block, plus one map block apiece to find them — an
+/// indirect block on the older images, an extent leaf on ext4, where `many`
+/// is fragmented enough to need one. A scan would cost every block of `many`
+/// and its map blocks besides.
+#[test]
+fn indexed_lookups_find_every_name_without_a_scan() {
+    for name in IMAGES {
+        let mut fs = mount(name);
+        let bs = fs.superblock().block_size as u64;
+        let dir_blocks = fs.metadata("many").expect("many").len / bs;
+        assert!(dir_blocks >= 5, "{name}: `many` is too small to need an index");
+
+        let before = fs.device().reads;
+        fs.metadata("sub/one-block.bin").expect("yardstick");
+        let yardstick = fs.device().reads - before;
+
+        for i in 0..400 {
+            let path = format!("many/entry-{i:04}-with-a-longer-name.txt");
+            let before = fs.device().reads;
+            let m = fs.metadata(&path).unwrap_or_else(|e| panic!("{name}: {path}: {e}"));
+            let extra = (fs.device().reads - before).saturating_sub(yardstick);
+            assert_eq!(m.len, format!("entry {i}\n").len() as u64);
+            assert!(extra <= 3, "{name}: {path} took {extra} reads more than a one-block directory");
+        }
+        assert_eq!(fs.metadata("many/entry-0400-with-a-longer-name.txt"), Err(Error::NotFound));
+    }
+}
@@ -0,0 +1,356 @@
+//! A volume is untrusted input, and this is where that is proved rather than
+//! claimed.
+//!
+//! Every test starts from one of the committed images — e2fsprogs made it,
+//! `e2fsck` passed it — and breaks it on purpose. The assertion is always a
+//! typed error and never a panic, a hang, or an allocation the volume chose
+//! the size of. The block-mapped ext2 image is the corpus wherever a checksum
+//! would otherwise catch the damage first; the ext4 image is used to show that
+//! the checksums do catch it.
+
+mod common;
+
+use common::{layout, MemDevice};
+use toyos_ext4::{Error, Ext4, Kind};
+
+const SB: u64 = 1024;
+
+fn ext2() -> MemDevice {
+    MemDevice::fixture("ext2-1k.img")
+}
+
+fn ext4() -> MemDevice {
+    MemDevice::fixture("ext4-4k.img")
+}
+
+fn inode_of(dev: &MemDevice, path: &str) -> u32 {
+    let mut fs = Ext4::mount(dev.clone()).expect("pristine mounts");
+    fs.symlink_metadata(path).expect("pristine path").inode
+}
+
+fn mount_err(dev: MemDevice) -> Error {
+    match Ext4::mount(dev) {
+        Ok(_) => panic!("mounted a broken volume"),
+        Err(e) => e,
+    }
+}
+
+/// Everything a reader might do, all of which must come back as a result.
+fn exercise(dev: MemDevice) {
+    let Ok(mut fs) = Ext4::mount(dev) else { return };
+    let _ = fs.walk(10_000);
+    for path in ["hello.txt", "sub/deeper/pattern.bin", "sparse.bin", "fast-link", "slow-link", "many"] {
+        let _ = fs.metadata(path);
+        let _ = fs.read_link_from_root(path);
+        let _ = fs.read_dir(path, 10_000);
+        let _ = fs.read_to_vec(path, 1 << 20);
+    }
+}
+
+// ---------------------------------------------------------------- superblock
+
+#[test]
+fn a_bad_magic_is_not_ext() {
+    let mut dev = ext2();
+    dev.poke(SB + 56, &[0, 0]);
+    assert_eq!(mount_err(dev), Error::NotExt);
+}
+
+#[test]
+fn illegal_superblock_fields_are_refused() {
+    // (offset, bytes): log block size 7, zero blocks per group, zero inodes
+    // per group, an inode size that is not a power of two, and a first data
+    // block of 2.
+    for (at, bytes) in [
+        (24u64, &7u32.to_le_bytes()[..]),
+        (32, &0u32.to_le_bytes()),
+        (40, &0u32.to_le_bytes()),
+        (88, &130u16.to_le_bytes()),
+        (20, &2u32.to_le_bytes()),
+    ] {
+        let mut dev = ext2();
+        dev.poke(SB + at, bytes);
+        assert_eq!(mount_err(dev), Error::NotExt, "field at {at}");
+    }
+}
+
+#[test]
+fn a_volume_larger_than_its_device_is_truncated() {
+    let mut dev = ext2();
+    let blocks = dev.u32_at(SB + 4);
+    dev.bytes.truncate(dev.bytes.len() / 2);
+    assert_eq!(mount_err(dev), Error::Truncated, "{blocks} blocks on half a device");
+}
+
+#[test]
+fn unknown_incompatible_features_are_unsupported() {
+    let mut dev = ext2();
+    let incompat = dev.u32_at(SB + 96);
+    dev.poke(SB + 96, &(incompat | 0x0800_0000).to_le_bytes());
+    assert_eq!(mount_err(dev), Error::Unsupported);
+}
+
+#[test]
+fn a_journal_that_needs_recovery_is_refused() {
+    let mut dev = MemDevice::fixture("ext3-1k.img");
+    let incompat = dev.u32_at(SB + 96);
+    dev.poke(SB + 96, &(incompat | 0x4).to_le_bytes());
+    assert_eq!(mount_err(dev), Error::NeedsRecovery);
+}
+
+// ---------------------------------------------------------------- checksums
+
+#[test]
+fn a_superblock_checksum_mismatch_is_refused() {
+    let mut dev = ext4();
+    let label = dev.peek(SB + 120, 1)[0];
+    dev.poke(SB + 120, &[label ^ 1]);
+    assert_eq!(mount_err(dev), Error::BadChecksum);
+}
+
+#[test]
+fn a_group_descriptor_checksum_mismatch_is_refused() {
+    let mut dev = ext4();
+    let l = layout(&dev);
+    let free = dev.u16_at(l.descriptor(0) + 12);
+    dev.poke(l.descriptor(0) + 12, &(free ^ 1).to_le_bytes());
+    assert_eq!(mount_err(dev), Error::BadChecksum);
+}
+
+#[test]
+fn an_inode_checksum_mismatch_is_refused() {
+    let mut dev = ext4();
+    let ino = inode_of(&dev, "hello.txt");
+    let at = layout(&dev).inode(&dev, ino) + 16;
+    let mtime = dev.u32_at(at);
+    dev.poke(at, &(mtime ^ 1).to_le_bytes());
+    let mut fs = Ext4::mount(dev).expect("mount");
+    assert_eq!(fs.metadata("hello.txt"), Err(Error::BadChecksum));
+    assert_eq!(fs.metadata("sub").map(|m| m.kind), Ok(Kind::Directory));
+}
+
+// ---------------------------------------------------------------- inodes
+
+#[test]
+fn a_size_no_map_could_back_is_refused() {
+    let mut dev = ext2();
+    let ino = inode_of(&dev, "hello.txt");
+    let at = layout(&dev).inode(&dev, ino);
+    dev.poke(at + 108, &u32::MAX.to_le_bytes());
+    let mut fs = Ext4::mount(dev).expect("mount");
+    assert_eq!(fs.metadata("hello.txt"), Err(Error::CorruptInode));
+}
+
+#[test]
+fn a_directory_entry_naming_a_nonexistent_inode_is_refused() {
+    let mut dev = ext2();
+    let l = layout(&dev);
+    let at = l.dir_entry(&dev, 2, "hello.txt");
+    dev.poke(at, &u32::MAX.to_le_bytes());
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.metadata("hello.txt"), Err(Error::CorruptInode));
+    exercise(dev);
+}
+
+#[test]
+fn a_root_that_is_not_a_directory_does_not_mount() {
+    let mut dev = ext2();
+    let at = layout(&dev).inode(&dev, 2);
+    dev.poke(at, &0x81A4u16.to_le_bytes());
+    assert_eq!(mount_err(dev), Error::CorruptInode);
+}
+
+// ---------------------------------------------------------------- maps
+
+#[test]
+fn a_block_pointer_outside_the_volume_is_refused() {
+    let mut dev = ext2();
+    let ino = inode_of(&dev, "sub/deeper/pattern.bin");
+    let at = layout(&dev).inode(&dev, ino);
+    // i_block[12], the single-indirect block.
+    dev.poke(at + 40 + 48, &0xFFFF_FFF0u32.to_le_bytes());
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    let file = fs.open("sub/deeper/pattern.bin").expect("open");
+    let mut buf = [0u8; 1024];
+    assert_eq!(fs.read(&file, 0, &mut buf), Ok(1024), "the direct blocks are intact");
+    assert_eq!(fs.read(&file, 12 * 1024, &mut buf), Err(Error::CorruptMap));
+    exercise(dev);
+}
+
+/// Where `sparse.bin`'s extent leaf is: its root is an index of depth 1.
+fn sparse_leaf(dev: &MemDevice) -> u64 {
+    let l = layout(dev);
+    let ino = inode_of(dev, "sparse.bin");
+    let root = l.inode(dev, ino) + 40;
+    assert_eq!(dev.u16_at(root + 6), 1, "the fixture's sparse.bin has an index root");
+    dev.u32_at(root + 12 + 4) as u64 * l.block_size
+}
+
+#[test]
+fn an_extent_node_with_bad_magic_is_refused() {
+    let mut dev = ext4();
+    let leaf = sparse_leaf(&dev);
+    dev.poke(leaf, &[0, 0]);
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    let file = fs.open("sparse.bin").expect("open");
+    let mut buf = [0u8; 16];
+    assert_eq!(fs.read(&file, 0, &mut buf), Err(Error::CorruptMap));
+    exercise(dev);
+}
+
+#[test]
+fn an_extent_tree_that_loops_is_a_depth_mismatch() {
+    // The leaf claims to be an index whose one child is itself. Followed
+    // naively that is a walk that never ends.
+    let mut dev = ext4();
+    let leaf = sparse_leaf(&dev);
+    let leaf_block = (leaf / 4096) as u32;
+    dev.poke(leaf + 2, &1u16.to_le_bytes());
+    dev.poke(leaf + 6, &1u16.to_le_bytes());
+    dev.poke(leaf + 12, &0u32.to_le_bytes());
+    dev.poke(leaf + 16, &leaf_block.to_le_bytes());
+    dev.poke(leaf + 20, &0u16.to_le_bytes());
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    let file = fs.open("sparse.bin").expect("open");
+    let mut buf = [0u8; 16];
+    assert_eq!(fs.read(&file, 0, &mut buf), Err(Error::CorruptMap));
+    exercise(dev);
+}
+
+#[test]
+fn overlapping_extents_are_refused() {
+    let mut dev = ext4();
+    let leaf = sparse_leaf(&dev);
+    // The second extent starts where the first does.
+    let first = dev.u32_at(leaf + 12);
+    dev.poke(leaf + 24, &first.to_le_bytes());
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    let file = fs.open("sparse.bin").expect("open");
+    let mut buf = [0u8; 16];
+    assert_eq!(fs.read(&file, 70_000, &mut buf), Err(Error::CorruptMap));
+    exercise(dev);
+}
+
+// ---------------------------------------------------------------- directories
+
+#[test]
+fn a_record_length_that_does_not_tile_the_block_is_refused() {
+    for bad in [0u16, 7, 13, 0xFFF0] {
+        let mut dev = ext2();
+        let l = layout(&dev);
+        let sub = inode_of(&dev, "sub");
+        let at = l.dir_entry(&dev, sub, "..");
+        dev.poke(at + 4, &bad.to_le_bytes());
+        let mut fs = Ext4::mount(dev.clone()).expect("mount");
+        assert_eq!(fs.read_dir("sub", 100).map(|v| v.len()), Err(Error::CorruptDirectory), "rec_len {bad}");
+        exercise(dev);
+    }
+}
+
+#[test]
+fn a_name_longer_than_its_record_is_refused() {
+    let mut dev = ext2();
+    let l = layout(&dev);
+    let at = l.dir_entry(&dev, 2, "hello.txt");
+    dev.poke(at + 6, &[200]);
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.read_dir("", 100).map(|v| v.len()), Err(Error::CorruptDirectory));
+    exercise(dev);
+}
+
+#[test]
+fn a_directory_tree_cycle_terminates() {
+    // `sub/deeper/pattern.bin` becomes a second name for the root directory.
+    let mut dev = ext2();
+    let l = layout(&dev);
+    let deeper = inode_of(&dev, "sub/deeper");
+    let at = l.dir_entry(&dev, deeper, "pattern.bin");
+    dev.poke(at, &2u32.to_le_bytes());
+    dev.poke(at + 7, &[2]);
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    let listed = fs.walk(10_000).expect("walk");
+    assert!(listed.iter().all(|(p, _)| !p.starts_with("sub/deeper/pattern.bin/")), "the cycle was entered");
+    assert_eq!(fs.metadata("sub/deeper/pattern.bin/sub/deeper/pattern.bin/hello.txt").map(|m| m.len), Ok(24));
+    exercise(dev);
+}
+
+#[test]
+fn a_symlink_loop_is_bounded() {
+    // `fast-link` -> `hello.txt` becomes `fast-link` -> `fast-link`, the same
+    // nine bytes.
+    let mut dev = ext2();
+    let ino = inode_of(&dev, "fast-link");
+    let at = layout(&dev).inode(&dev, ino);
+    dev.poke(at + 40, b"fast-link");
+    let mut fs = Ext4::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.metadata("fast-link"), Err(Error::LimitExceeded));
+    assert_eq!(fs.read_link("fast-link").as_deref(), Ok(&b"fast-link"[..]));
+    exercise(dev);
+}
+
+#[test]
+fn a_corrupt_index_falls_back_to_the_scan() {
+    // An unknown hash version, an info length that is not eight, and an
+    // entry count past its limit: each sends the lookup to the linear scan,
+    // which needs none of those bytes and still finds every name.
+    let dev = ext2();
+    let l = layout(&dev);
+    let many = inode_of(&dev, "many");
+    let root = l.first_block(&dev, many) * l.block_size;
+    for (at, byte) in [(28u64, 9u8), (29, 4), (24 + 8 + 1, 0xFF)] {
+        let mut dev = dev.clone();
+        dev.poke(root + at, &[byte]);
+        let mut fs = Ext4::mount(dev).expect("mount");
+        for i in [0, 199, 399] {
+            let path = format!("many/entry-{i:04}-with-a-longer-name.txt");
+            assert!(fs.metadata(&path).is_ok(), "{path} with byte {at} = {byte}");
+        }
+    }
+}
+
+#[test]
+fn the_caller_limit_refuses_rather_than_truncates() {
+    let mut fs = Ext4::mount(ext2()).expect("mount");
+    assert_eq!(fs.walk(10).map(|v| v.len()), Err(Error::LimitExceeded));
+    assert_eq!(fs.read_to_vec("sub/deeper/pattern.bin", 1000), Err(Error::LimitExceeded));
+}
+
+#[test]
+fn a_device_that_fails_mid_read_reports_it() {
+    let whole = |dev: MemDevice| -> Result<usize, Error> {
+        let mut fs = Ext4::mount(dev)?;
+        fs.read_to_vec("sub/deeper/pattern.bin", 1 << 20).map(|v| v.len())
+    };
+    let mut counted = ext4();
+    counted.fail_after = Some(u32::MAX);
+    let mut fs = Ext4::mount(counted).expect("mount");
+    fs.read_to_vec("sub/deeper/pattern.bin", 1 << 20).expect("read");
+    let reads = fs.device().reads;
+
+    for n in 0..reads {
+        let mut dev = ext4();
+        dev.fail_after = Some(n);
+        assert_eq!(whole(dev), Err(Error::Io), "failing after {n} of {reads} reads");
+    }
+}
+
+/// Every byte of the metadata a lookup touches, flipped one at a time. Not a
+/// fuzzer — a sweep, so it is the same sweep on every run — over the
+/// superblock, the group descriptors, the first inode-table block and the
+/// root directory's block.
+#[test]
+fn single_byte_damage_never_panics() {
+    let dev = ext2();
+    let l = layout(&dev);
+    let table = l.inode(&dev, 1) - (l.inode(&dev, 1) % l.block_size);
+    let root = l.first_block(&dev, 2) * l.block_size;
+    let ranges = [(SB, 1024u64), (l.descriptor(0), 64), (table, l.block_size), (root, l.block_size)];
+    for (start, len) in ranges {
+        for at in start..start + len {
+            let mut dev = dev.clone();
+            let b = dev.peek(at, 1)[0];
+            dev.poke(at, &[b ^ 0xA5]);
+            exercise(dev);
+        }
+    }
+}
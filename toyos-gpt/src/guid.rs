@@ -29,6 +29,18 @@ impl Guid {
         [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B],
     );
 
+    /// `0FC63DAF-8483-4772-8E79-3D69D8477DE4` — Linux filesystem data, the
+    /// type every ext2/3/4 partition a Linux installer makes carries.
+    ///
+    /// Also a type, with the same caveat as [`EFI_SYSTEM`](Self::EFI_SYSTEM):
+    /// it selects only for a mount that cannot write.
+    pub const LINUX_FILESYSTEM: Self = Self::from_fields(
+        0x0FC6_3DAF,
+        0x8483,
+        0x4772,
+        [0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4],
+    );
+
     pub const fn from_fields(a: u32, b: u16, c: u16, d: [u8; 8]) -> Self {
         let a = a.to_le_bytes();
         let b = b.to_le_bytes();
@@ -15,6 +15,14 @@
 //! path panics. The kernel's fail-fast rule is for kernel bugs, never for
 //! input that crossed a trust boundary.
 //!
+//! There is one other question, and [`partitions_of_type`] answers it: which
+//! partitions on a disk that is *not* the boot disk are of a given type. That
+//! is the selection the paragraph above forbids, and it is allowed for exactly
+//! one kind of caller — a mount with no write path at all, like the kernel's
+//! read-only ext4 adapter. Picking the wrong partition by type is a defect
+//! because of what the caller writes next; a caller that cannot write has
+//! nothing to do to the wrong partition but read it.
+//!
 //! `no_std`, no allocation, no `unsafe`: the entry array is streamed a block
 //! at a time through [`Sectors`], so nothing here is sized by a number the
 //! disk chose.
@@ -250,11 +258,28 @@ fn locate_at(
     read(dev, header_lba, block)?;
     let header = parse_header(block, lba_bytes, lba_count, header_lba)?;
 
-    let (found, used_entries) = scan_entries(dev, &header, target, lba_bytes)?;
+    let mut found: Option<Partition> = None;
+    let used_entries = scan_entries(dev, &header, lba_bytes, |p| {
+        if p.unique_guid == target && found.is_none() {
+            found = Some(p);
+        }
+    })?;
     let Some(partition) = found else {
         return Err(GptError::NotFound { used_entries });
     };
+    check_partition(dev, &header, &partition, lba_bytes)?;
+
+    Ok(Located { partition, disk_guid: header.disk_guid, used_entries })
+}
 
+/// The matched partition must sit inside the usable range and share no block
+/// with any other entry.
+fn check_partition(
+    dev: &mut dyn Sectors,
+    header: &Header,
+    partition: &Partition,
+    lba_bytes: u32,
+) -> Result<(), GptError> {
     if partition.first_lba > partition.last_lba
         || partition.first_lba < header.first_usable_lba
         || partition.last_lba > header.last_usable_lba
@@ -264,9 +289,90 @@ fn locate_at(
             last: partition.last_lba,
         });
     }
-    check_no_overlap(dev, &header, &partition, lba_bytes)?;
+    check_no_overlap(dev, header, partition, lba_bytes)
+}
 
-    Ok(Located { partition, disk_guid: header.disk_guid, used_entries })
+/// What [`partitions_of_type`] found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Listed {
+    /// How many of the caller's slots were filled, from the front.
+    pub count: usize,
+    /// Entries of the type asked for, which is more than `count` when the
+    /// caller's slice was too short. Said rather than dropped, so a caller
+    /// can tell "the disk has two" from "the disk has more than I looked at".
+    pub matching: u32,
+    pub disk_guid: Guid,
+    pub used_entries: u32,
+}
+
+/// Every partition of type `type_guid` on `dev`, in table order, into `out`.
+///
+/// **For read-only mounts only** — see the crate documentation for why type
+/// is an acceptable key there and nowhere else. The table is read and checked
+/// exactly as [`locate`] reads it, backup fallback included, and every
+/// partition returned has passed the same range and overlap checks a located
+/// one has. One that fails them refuses the whole listing rather than being
+/// skipped: a table with an overlap in it is not one to pick survivors from.
+///
+/// No allocation: the caller's slice is the bound on what is kept.
+pub fn partitions_of_type(
+    dev: &mut dyn Sectors,
+    type_guid: Guid,
+    out: &mut [Partition],
+) -> Result<Listed, GptError> {
+    let lba_bytes = dev.lba_bytes();
+    if !(MIN_LBA_BYTES..=MAX_LBA_BYTES).contains(&lba_bytes) || !lba_bytes.is_power_of_two() {
+        return Err(GptError::UnsupportedLbaSize(lba_bytes));
+    }
+    let lba_count = dev.lba_count();
+    if lba_count < 3 {
+        return Err(GptError::DeviceTooSmall(lba_count));
+    }
+
+    let mut block = [0u8; MAX_LBA_BYTES as usize];
+    let block = &mut block[..lba_bytes as usize];
+    read(dev, 0, block)?;
+    check_protective_mbr(block)?;
+
+    match list_at(dev, 1, type_guid, out, lba_bytes, lba_count) {
+        Ok(listed) => Ok(listed),
+        Err(primary_err) if primary_err.primary_never_checked_out() => {
+            list_at(dev, lba_count - 1, type_guid, out, lba_bytes, lba_count).or(Err(primary_err))
+        }
+        Err(primary_err) => Err(primary_err),
+    }
+}
+
+fn list_at(
+    dev: &mut dyn Sectors,
+    header_lba: u64,
+    type_guid: Guid,
+    out: &mut [Partition],
+    lba_bytes: u32,
+    lba_count: u64,
+) -> Result<Listed, GptError> {
+    let mut block = [0u8; MAX_LBA_BYTES as usize];
+    let block = &mut block[..lba_bytes as usize];
+
+    read(dev, header_lba, block)?;
+    let header = parse_header(block, lba_bytes, lba_count, header_lba)?;
+
+    let mut count = 0usize;
+    let mut matching = 0u32;
+    let used_entries = scan_entries(dev, &header, lba_bytes, |p| {
+        if p.type_guid == type_guid {
+            if let Some(slot) = out.get_mut(count) {
+                *slot = p;
+                count += 1;
+            }
+            matching += 1;
+        }
+    })?;
+    for partition in &out[..count] {
+        check_partition(dev, &header, partition, lba_bytes)?;
+    }
+
+    Ok(Listed { count, matching, disk_guid: header.disk_guid, used_entries })
 }
 
 fn read(dev: &mut dyn Sectors, lba: u64, buf: &mut [u8]) -> Result<(), GptError> {
@@ -394,26 +500,26 @@ fn header_crc(lba1: &[u8], header_bytes: usize) -> u32 {
     crc.finish()
 }
 
-/// Walk the entry array once, checking its CRC as we go, and remember the
-/// entry carrying `target`.
+/// Walk the entry array once, checking its CRC as we go, handing every used
+/// entry to `seen`. Answers how many there were.
 ///
-/// The match is returned only if the CRC holds. Acting on an entry read out of
-/// an array whose checksum then failed would make the checksum decorative —
-/// which is the shape of every "we validated it" that turns out not to have
-/// been load-bearing.
+/// What `seen` kept may be acted on only if this returns `Ok`, which it does
+/// only if the CRC holds. Acting on an entry read out of an array whose
+/// checksum then failed would make the checksum decorative — which is the
+/// shape of every "we validated it" that turns out not to have been
+/// load-bearing.
 fn scan_entries(
     dev: &mut dyn Sectors,
     header: &Header,
-    target: Guid,
     lba_bytes: u32,
-) -> Result<(Option<Partition>, u32), GptError> {
+    mut seen: impl FnMut(Partition),
+) -> Result<u32, GptError> {
     let mut block = [0u8; MAX_LBA_BYTES as usize];
     let block = &mut block[..lba_bytes as usize];
 
     let entries_per_lba = lba_bytes / header.entry_bytes;
     let mut crc = Crc32::new();
     let mut remaining = header.entry_count as u64 * header.entry_bytes as u64;
-    let mut found: Option<Partition> = None;
     let mut used = 0u32;
     let mut index = 0u32;
     let mut lba = header.entry_array_lba;
@@ -432,16 +538,13 @@ fn scan_entries(
             let type_guid = read_guid(entry, 0);
             if !type_guid.is_zero() {
                 used += 1;
-                let unique_guid = read_guid(entry, 16);
-                if unique_guid == target && found.is_none() {
-                    found = Some(Partition {
-                        index,
-                        type_guid,
-                        unique_guid,
-                        first_lba: le_u64(entry, 32),
-                        last_lba: le_u64(entry, 40),
-                    });
-                }
+                seen(Partition {
+                    index,
+                    type_guid,
+                    unique_guid: read_guid(entry, 16),
+                    first_lba: le_u64(entry, 32),
+                    last_lba: le_u64(entry, 40),
+                });
             }
             index += 1;
         }
@@ -454,7 +557,7 @@ fn scan_entries(
     if computed != header.entry_array_crc {
         return Err(GptError::EntryArrayCrc { stored: header.entry_array_crc, computed });
     }
-    Ok((found, used))
+    Ok(used)
 }
 
 /// Nothing else in the table may claim a block the matched partition claims.
@@ -6,7 +6,7 @@
 //! none of the broken ones panics, allocates by a number the disk chose, or
 //! returns a partition anyway.
 
-use toyos_gpt::{crc32, GptError, Guid, Located, Sectors};
+use toyos_gpt::{crc32, GptError, Guid, Listed, Located, Partition, Sectors};
 
 const LBA: u32 = 512;
 const ENTRY: u32 = 128;
@@ -212,6 +212,9 @@ impl Image {
     fn locate(&mut self, target: Guid) -> Result<Located, GptError> {
         toyos_gpt::locate(self, target)
     }
+    fn list(&mut self, type_guid: Guid, out: &mut [Partition]) -> Result<Listed, GptError> {
+        toyos_gpt::partitions_of_type(self, type_guid, out)
+    }
 }
 
 impl Sectors for Image {
@@ -600,3 +603,63 @@ fn no_byte_of_the_table_can_panic_the_parser() {
     assert!(located > 0, "every single-byte change broke the table");
     assert!(img.locate(guid(0xC3)).is_ok(), "the sweep did not put the table back");
 }
+
+/// The Linux filesystem type's canonical text and its on-disk bytes, both
+/// directions, for the reason the ESP type's test gives.
+#[test]
+fn the_linux_filesystem_type_is_its_on_disk_bytes() {
+    const ON_DISK: [u8; 16] =
+        [0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47, 0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4];
+    assert_eq!(Guid::LINUX_FILESYSTEM, Guid(ON_DISK));
+    assert_eq!(Guid::LINUX_FILESYSTEM.to_string(), "0FC63DAF-8483-4772-8E79-3D69D8477DE4");
+}
+
+const NO_PARTITION: Partition =
+    Partition { index: 0, type_guid: Guid::ZERO, unique_guid: Guid::ZERO, first_lba: 0, last_lba: 0 };
+
+#[test]
+fn listing_by_type_keeps_table_order_and_says_how_many_it_dropped() {
+    let mut b = Builder::default();
+    b.entries[1] = Entry::new(Guid::LINUX_FILESYSTEM, guid(0xB2), 100, 199);
+    let mut img = b.build();
+
+    let mut out = [NO_PARTITION; 8];
+    let listed = img.list(TYPE_ESP, &mut out).expect("a valid table");
+    assert_eq!((listed.count, listed.matching, listed.used_entries), (3, 3, 4));
+    let indices: Vec<u32> = out[..listed.count].iter().map(|p| p.index).collect();
+    assert_eq!(indices, [0, 2, 3]);
+    assert_eq!(listed.disk_guid, guid(0x5D));
+
+    let mut short = [NO_PARTITION; 2];
+    let listed = img.list(TYPE_ESP, &mut short).expect("a valid table");
+    assert_eq!((listed.count, listed.matching), (2, 3));
+
+    let listed = img.list(Guid::LINUX_FILESYSTEM, &mut out).expect("a valid table");
+    assert_eq!((listed.count, out[0].unique_guid, out[0].first_lba), (1, guid(0xB2), 100));
+    assert_eq!(img.list(TYPE_OTHER, &mut out).map(|l| l.count), Ok(0));
+}
+
+/// Listing is no looser than locating: the same CRC, the same range and
+/// overlap checks, and the same fallback to the backup.
+#[test]
+fn listing_by_type_refuses_what_locate_refuses() {
+    let mut out = [NO_PARTITION; 8];
+
+    let mut b = Builder::default();
+    b.entries[3] = Entry::new(TYPE_OTHER, guid(0xD4), 150, 400);
+    let mut img = b.build();
+    assert_eq!(img.list(TYPE_OTHER, &mut out), Err(GptError::PartitionOverlap { index: 3 }));
+
+    let mut b = Builder::default();
+    b.entries[1] = Entry::new(TYPE_OTHER, guid(0xB2), 100, DISK_LBAS + 5);
+    let mut img = b.build();
+    assert_eq!(img.list(TYPE_OTHER, &mut out), Err(GptError::PartitionRange { first: 100, last: DISK_LBAS + 5 }));
+
+    let mut img = Builder::default().build();
+    *img.at(ARRAY_LBA, 40) ^= 1;
+    assert!(matches!(img.list(TYPE_OTHER, &mut out), Err(GptError::EntryArrayCrc { .. })));
+
+    let mut img = Builder { backup: true, ..Default::default() }.build();
+    *img.at(1, 0) = b'X';
+    assert_eq!(img.list(TYPE_OTHER, &mut out).map(|l| l.count), Ok(1));
+}
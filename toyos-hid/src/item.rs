@@ -0,0 +1,160 @@
+//! A report descriptor as the items it is made of (HID 1.11 §6.2.2).
+//!
+//! ```text
+//! short item: prefix = tag[7:4] type[3:2] size[1:0], then 0, 1, 2 or 4 bytes
+//! long item:  0xFE, bDataSize, bLongItemTag, then bDataSize bytes
+//! ```
+//!
+//! No tag is given a meaning here; [`crate::layout`] does that. What is decided
+//! here is only where one item ends and the next begins, which is the one thing
+//! a hostile descriptor can get wrong in a way that reads past the buffer.
+
+/// Which of the three item families a short item belongs to. The fourth value
+/// of the type field is reserved and is handed out as [`Type::Reserved`], so
+/// that a parser skipping it does so because it said so.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Type {
+    Main,
+    Global,
+    Local,
+    Reserved,
+}
+
+/// One short item: its family, its tag within the family, and its data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Item {
+    pub kind: Type,
+    pub tag: u8,
+    /// How many data bytes the item carried: 0, 1, 2 or 4.
+    pub size: u8,
+    /// The data bytes, little-endian and zero-extended.
+    pub data: u32,
+}
+
+impl Item {
+    /// The data as a signed number of the item's own width, which is how a
+    /// logical minimum reads: `0x81` in one byte is -127, not 129.
+    pub fn signed(&self) -> i32 {
+        match self.size {
+            1 => self.data as u8 as i8 as i32,
+            2 => self.data as u16 as i16 as i32,
+            _ => self.data as i32,
+        }
+    }
+}
+
+/// The descriptor ended inside an item: its prefix promised more bytes than
+/// were left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Truncated;
+
+const LONG_ITEM: u8 = 0xFE;
+
+/// The short items of a descriptor, in order.
+///
+/// Long items are stepped over: HID 1.11 defines no long item tag, so one is
+/// either vendor data or garbage, and neither describes a report. The first
+/// truncated item ends the iteration after yielding its error once.
+pub struct Items<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Items<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, at: 0 }
+    }
+}
+
+impl Iterator for Items<'_> {
+    type Item = Result<Item, Truncated>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let prefix = *self.bytes.get(self.at)?;
+            if prefix == LONG_ITEM {
+                let Some(&len) = self.bytes.get(self.at + 1) else {
+                    return self.truncated();
+                };
+                let end = self.at + 3 + len as usize;
+                if end > self.bytes.len() {
+                    return self.truncated();
+                }
+                self.at = end;
+                continue;
+            }
+            let size = [0u8, 1, 2, 4][(prefix & 0b11) as usize];
+            let end = self.at + 1 + size as usize;
+            let Some(data) = self.bytes.get(self.at + 1..end) else {
+                return self.truncated();
+            };
+            self.at = end;
+            let data = data.iter().rev().fold(0u32, |acc, &b| acc << 8 | b as u32);
+            let kind = match (prefix >> 2) & 0b11 {
+                0 => Type::Main,
+                1 => Type::Global,
+                2 => Type::Local,
+                _ => Type::Reserved,
+            };
+            return Some(Ok(Item { kind, tag: prefix >> 4, size, data }));
+        }
+    }
+}
+
+impl Items<'_> {
+    fn truncated(&mut self) -> Option<Result<Item, Truncated>> {
+        self.at = self.bytes.len();
+        Some(Err(Truncated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_size_code_reads_its_bytes_little_endian() {
+        let bytes = [0x05, 0x01, 0x26, 0xFF, 0x7F, 0x27, 0x78, 0x56, 0x34, 0x12, 0xC0];
+        let items: [Item; 4] = core::array::from_fn({
+            let mut it = Items::new(&bytes);
+            move |_| it.next().unwrap().unwrap()
+        });
+        assert_eq!(items[0], Item { kind: Type::Global, tag: 0, size: 1, data: 1 });
+        assert_eq!(items[1], Item { kind: Type::Global, tag: 2, size: 2, data: 0x7FFF });
+        assert_eq!(items[2].data, 0x1234_5678);
+        assert_eq!(items[2].size, 4);
+        assert_eq!(items[3], Item { kind: Type::Main, tag: 0xC, size: 0, data: 0 });
+    }
+
+    #[test]
+    fn signed_data_is_extended_from_the_items_own_width() {
+        let one = Item { kind: Type::Global, tag: 1, size: 1, data: 0x81 };
+        assert_eq!(one.signed(), -127);
+        let two = Item { kind: Type::Global, tag: 1, size: 2, data: 0x8001 };
+        assert_eq!(two.signed(), -32767);
+        let four = Item { kind: Type::Global, tag: 1, size: 4, data: 0xFFFF_FFFF };
+        assert_eq!(four.signed(), -1);
+    }
+
+    #[test]
+    fn a_long_item_is_stepped_over_whole() {
+        let bytes = [0xFE, 2, 0x10, 0xAA, 0xBB, 0xC0];
+        let mut items = Items::new(&bytes);
+        assert_eq!(items.next(), Some(Ok(Item { kind: Type::Main, tag: 0xC, size: 0, data: 0 })));
+        assert_eq!(items.next(), None);
+    }
+
+    /// Every prefix of a descriptor either ends on an item boundary or says it
+    /// was cut, exactly once, and never reads past what it was given.
+    #[test]
+    fn a_cut_descriptor_yields_one_error_and_stops() {
+        let bytes = [0x05, 0x01, 0x27, 0x78, 0x56, 0x34, 0x12, 0xFE, 1, 0, 9];
+        let boundaries = [0, 2, 7, 11];
+        for cut in 0..=bytes.len() {
+            let results = Items::new(&bytes[..cut]);
+            let errors = results.filter(|r| r.is_err()).count();
+            let want = usize::from(!boundaries.contains(&cut));
+            assert_eq!(errors, want, "cut at {cut}");
+        }
+    }
+}
@@ -0,0 +1,570 @@
+//! What a report descriptor says one report holds.
+//!
+//! A descriptor describes every report an interface sends, and this driver
+//! binds each interface in one role: keyboard, pointer or game controller. So
+//! the parse looks for the **first top-level application collection whose
+//! usage is one of those**, records where in its report each thing it can use
+//! lives, and stops when that collection closes. A keyboard that also sends a
+//! consumer-control report under another report ID is a keyboard whose volume
+//! keys are ignored, not a device this parse refuses.
+//!
+//! Everything a hostile descriptor could use to make the parse unbounded is
+//! capped and refused past the cap, rather than truncated into a layout that
+//! decodes the wrong bits: the collection depth, the Push stack, the usages one
+//! main item names, the report IDs whose offsets are tracked, the fields kept,
+//! and the length of the report itself — [`MAX_REPORT`], which is the DMA slot
+//! the kernel receives reports into.
+
+use core::fmt;
+
+use crate::item::{Item, Items, Type};
+
+/// The longest report a layout may describe, ID byte included. A full-speed
+/// interrupt endpoint moves at most 64 bytes per transfer, and every game
+/// controller and keyboard in reach fits one.
+pub const MAX_REPORT: usize = 64;
+
+/// The most fields a layout keeps. A run of keys or buttons is one field, so
+/// this is axes, hats and runs; a gamepad needs a dozen.
+pub const MAX_FIELDS: usize = 32;
+
+/// The most usages one main item may name one at a time. Ranges do not count
+/// against it, which is how a keyboard names its 232 keys.
+const MAX_USAGES: usize = 32;
+const MAX_DEPTH: u8 = 16;
+const MAX_PUSH: usize = 4;
+const MAX_REPORT_IDS: usize = 16;
+
+const PAGE_DESKTOP: u16 = 0x01;
+const PAGE_KEYBOARD: u16 = 0x07;
+const PAGE_BUTTON: u16 = 0x09;
+
+/// What a bound interface is, as far as where its reports go depends on it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Keyboard,
+    /// A pointer whose X is relative.
+    Mouse,
+    /// A pointer whose X is absolute, as QEMU's `usb-tablet` is.
+    Tablet,
+    Joystick,
+    Gamepad,
+}
+
+impl Kind {
+    /// What the kernel calls a device of this kind in every line about it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Keyboard => "keyboard",
+            Self::Mouse => "mouse",
+            Self::Tablet => "tablet",
+            Self::Joystick => "joystick",
+            Self::Gamepad => "gamepad",
+        }
+    }
+}
+
+/// Why a descriptor produced no layout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// An item ran past the end of the descriptor.
+    Truncated,
+    /// Collections or Push items nested past what this parse tracks.
+    TooDeep,
+    /// An End Collection with no collection open, a Pop with nothing pushed, or
+    /// the chosen collection never closed.
+    Unbalanced,
+    /// One main item named more usages one at a time than this parse keeps.
+    TooManyUsages,
+    /// More distinct report IDs than this parse tracks offsets for.
+    TooManyReports,
+    /// The chosen report is longer than [`MAX_REPORT`].
+    TooLong,
+    /// The chosen collection has more fields than [`MAX_FIELDS`].
+    TooManyFields,
+    /// A Report ID of 0, which HID 1.11 §6.2.2.7 reserves, or one wider than
+    /// the byte a report carries it in.
+    ReportId,
+    /// No keyboard, pointer, joystick or gamepad collection.
+    Unsupported,
+    /// The collection chosen describes nothing its role could use: a pointer
+    /// with no X and Y, a keyboard with no keys, a pad with no controls.
+    Incomplete,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Truncated => "an item runs past the end of the descriptor",
+            Self::TooDeep => "collections or pushes nest too deep",
+            Self::Unbalanced => "collections or pushes do not balance",
+            Self::TooManyUsages => "a main item names too many usages",
+            Self::TooManyReports => "too many report IDs",
+            Self::TooLong => "the report is longer than one transfer",
+            Self::TooManyFields => "too many fields in one report",
+            Self::ReportId => "a report ID of 0 or past 255",
+            Self::Unsupported => "no keyboard, pointer, joystick or gamepad collection",
+            Self::Incomplete => "the collection describes nothing usable",
+        })
+    }
+}
+
+/// What one field carries, for the role the layout binds in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Role {
+    /// Keyboard-page keys, one bit each, the first of which is this usage.
+    /// Modifiers are keys E0..E7 of this page, so they are a run of these too.
+    Keys(u8),
+    /// A keyboard's array of pressed keys: each slot holds this usage plus its
+    /// value less the logical minimum.
+    KeyArray(u16),
+    /// Button-page buttons, one bit each, the first of which is this index
+    /// (button 1 is index 0).
+    Buttons(u8),
+    X,
+    Y,
+    Wheel,
+    /// A game controller's axis, numbered from Generic Desktop X (0x30)
+    /// through Dial (0x37).
+    Axis(u8),
+    Hat,
+    /// One of Generic Desktop's D-pad usages, numbered from D-pad Up (0x90):
+    /// up, down, right, left.
+    DPad(u8),
+}
+
+/// Where one field lives in the report and what its values mean.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Field {
+    pub role: Role,
+    /// Bits from the start of the report, ID byte included.
+    pub offset: u16,
+    /// Bits per value, 1 to 32.
+    pub size: u8,
+    /// Values in the run, each `size` bits past the last.
+    pub count: u16,
+    pub min: i64,
+    pub max: i64,
+    pub relative: bool,
+}
+
+/// One report's layout, as the role it binds in and the fields that role uses.
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    pub(crate) kind: Kind,
+    pub(crate) report_id: u8,
+    pub(crate) len: u8,
+    pub(crate) fields: [Field; MAX_FIELDS],
+    pub(crate) n: u8,
+}
+
+/// The application collections this driver binds, before the fields say
+/// whether a pointer is relative.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum App {
+    Keyboard,
+    Pointer,
+    Pad { joystick: bool },
+}
+
+impl App {
+    fn of(usage: u32) -> Option<Self> {
+        match usage {
+            0x0001_0001 | 0x0001_0002 => Some(Self::Pointer),
+            0x0001_0004 | 0x0001_0008 => Some(Self::Pad { joystick: true }),
+            0x0001_0005 => Some(Self::Pad { joystick: false }),
+            0x0001_0006 | 0x0001_0007 => Some(Self::Keyboard),
+            _ => None,
+        }
+    }
+
+    /// What a value under `usage` is to a device of this kind, or `None` for
+    /// a usage it has no use for.
+    fn role(self, usage: u32) -> Option<Role> {
+        let (page, id) = ((usage >> 16) as u16, usage as u16);
+        match (self, page, id) {
+            (Self::Keyboard, PAGE_KEYBOARD, 0..=0xFF) => Some(Role::Keys(id as u8)),
+            (Self::Pointer | Self::Pad { .. }, PAGE_BUTTON, 1..=32) => {
+                Some(Role::Buttons(id as u8 - 1))
+            }
+            (Self::Pointer, PAGE_DESKTOP, 0x30) => Some(Role::X),
+            (Self::Pointer, PAGE_DESKTOP, 0x31) => Some(Role::Y),
+            (Self::Pointer, PAGE_DESKTOP, 0x38) => Some(Role::Wheel),
+            (Self::Pad { .. }, PAGE_DESKTOP, 0x30..=0x37) => Some(Role::Axis(id as u8 - 0x30)),
+            (Self::Pad { .. }, PAGE_DESKTOP, 0x39) => Some(Role::Hat),
+            (Self::Pad { .. }, PAGE_DESKTOP, 0x90..=0x93) => Some(Role::DPad(id as u8 - 0x90)),
+            _ => None,
+        }
+    }
+}
+
+/// The global items in effect, which Push and Pop save and restore whole.
+#[derive(Clone, Copy, Default)]
+struct Globals {
+    page: u16,
+    min: i64,
+    /// The raw maximum and its width. Whether it is signed depends on the
+    /// minimum (HID 1.11 §6.2.2.7: a maximum is signed only if its minimum
+    /// is), and the minimum may come after it.
+    max: (u32, u8),
+    size: u32,
+    count: u32,
+    id: u8,
+}
+
+impl Globals {
+    fn max(&self) -> i64 {
+        let (data, size) = self.max;
+        if self.min < 0 {
+            Item { kind: Type::Global, tag: 2, size, data }.signed() as i64
+        } else {
+            data as i64
+        }
+    }
+}
+
+/// A usage as the local item gave it: a four-byte one names its own page, and
+/// a shorter one takes the page in effect when the main item closes it.
+#[derive(Clone, Copy, Default)]
+struct Usage {
+    data: u32,
+    extended: bool,
+}
+
+impl Usage {
+    fn of(item: &Item) -> Self {
+        Self { data: item.data, extended: item.size == 4 }
+    }
+
+    fn resolve(self, page: u16) -> u32 {
+        if self.extended { self.data } else { (page as u32) << 16 | (self.data & 0xFFFF) }
+    }
+}
+
+/// The local items since the last main item.
+#[derive(Default)]
+struct Locals {
+    usages: [Usage; MAX_USAGES],
+    n: usize,
+    min: Option<Usage>,
+    max: Option<Usage>,
+}
+
+impl Locals {
+    /// The usage of the `i`th value of a main item: the named usages in order,
+    /// then the range, and past both the last one again (HID 1.11 §6.2.2.8).
+    /// Zero — no usage — where the item named none.
+    fn at(&self, i: usize, page: u16) -> u32 {
+        if i < self.n {
+            return self.usages[i].resolve(page);
+        }
+        if let (Some(lo), Some(hi)) = (self.min, self.max) {
+            let (lo, hi) = (lo.resolve(page), hi.resolve(page));
+            let step = (i - self.n).min(hi.saturating_sub(lo) as usize) as u32;
+            return lo + step;
+        }
+        match (self.n, self.min) {
+            (0, Some(lo)) => lo.resolve(page),
+            (0, None) => 0,
+            (n, _) => self.usages[n - 1].resolve(page),
+        }
+    }
+}
+
+/// How far into its report each report ID's input items have reached, in bits.
+struct Offsets {
+    ids: [(u8, u64); MAX_REPORT_IDS],
+    n: usize,
+}
+
+impl Offsets {
+    fn of(&mut self, id: u8) -> Result<&mut u64, Error> {
+        let i = match self.ids[..self.n].iter().position(|&(seen, _)| seen == id) {
+            Some(i) => i,
+            None if self.n < MAX_REPORT_IDS => {
+                // A report that has an ID starts with it.
+                self.ids[self.n] = (id, if id != 0 { 8 } else { 0 });
+                self.n += 1;
+                self.n - 1
+            }
+            None => return Err(Error::TooManyReports),
+        };
+        Ok(&mut self.ids[i].1)
+    }
+}
+
+/// The parse of one descriptor, until its chosen collection closes.
+struct Parse {
+    globals: Globals,
+    stack: [Globals; MAX_PUSH],
+    pushed: usize,
+    locals: Locals,
+    depth: u8,
+    offsets: Offsets,
+    /// The collection whose fields are being kept, while it is open.
+    app: Option<App>,
+    /// The report ID its first input item used, which every field kept after
+    /// it must share.
+    id: Option<u8>,
+    fields: [Field; MAX_FIELDS],
+    n: usize,
+}
+
+const INPUT: u8 = 0x8;
+const COLLECTION: u8 = 0xA;
+const END_COLLECTION: u8 = 0xC;
+const COLLECTION_APPLICATION: u32 = 1;
+
+const FLAG_CONSTANT: u32 = 1 << 0;
+const FLAG_VARIABLE: u32 = 1 << 1;
+const FLAG_RELATIVE: u32 = 1 << 2;
+
+impl Layout {
+    /// Read a report descriptor for the first collection this driver binds.
+    pub fn parse(descriptor: &[u8]) -> Result<Self, Error> {
+        let blank = Field {
+            role: Role::X,
+            offset: 0,
+            size: 0,
+            count: 0,
+            min: 0,
+            max: 0,
+            relative: false,
+        };
+        let mut p = Parse {
+            globals: Globals::default(),
+            stack: [Globals::default(); MAX_PUSH],
+            pushed: 0,
+            locals: Locals::default(),
+            depth: 0,
+            offsets: Offsets { ids: [(0, 0); MAX_REPORT_IDS], n: 0 },
+            app: None,
+            id: None,
+            fields: [blank; MAX_FIELDS],
+            n: 0,
+        };
+        for item in Items::new(descriptor) {
+            let item = item.map_err(|_| Error::Truncated)?;
+            match item.kind {
+                Type::Main => {
+                    let closed = p.main(&item)?;
+                    p.locals = Locals::default();
+                    if closed {
+                        return p.finish();
+                    }
+                }
+                Type::Global => p.global(&item)?,
+                Type::Local => p.local(&item)?,
+                Type::Reserved => {}
+            }
+        }
+        match p.app {
+            Some(_) => Err(Error::Unbalanced),
+            None => Err(Error::Unsupported),
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// The report ID this layout's reports begin with, or 0 where the device
+    /// uses none.
+    pub fn report_id(&self) -> u8 {
+        self.report_id
+    }
+
+    /// The report's length in bytes, ID byte included.
+    pub fn report_len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub(crate) fn fields(&self) -> &[Field] {
+        &self.fields[..self.n as usize]
+    }
+}
+
+impl Parse {
+    fn global(&mut self, item: &Item) -> Result<(), Error> {
+        let g = &mut self.globals;
+        match item.tag {
+            0 => g.page = item.data as u16,
+            1 => g.min = item.signed() as i64,
+            2 => g.max = (item.data, item.size),
+            7 => g.size = item.data,
+            8 => {
+                if item.data == 0 || item.data > 0xFF {
+                    return Err(Error::ReportId);
+                }
+                g.id = item.data as u8;
+            }
+            9 => g.count = item.data,
+            10 => {
+                let slot = self.stack.get_mut(self.pushed).ok_or(Error::TooDeep)?;
+                *slot = *g;
+                self.pushed += 1;
+            }
+            11 => {
+                self.pushed = self.pushed.checked_sub(1).ok_or(Error::Unbalanced)?;
+                *g = self.stack[self.pushed];
+            }
+            // Physical extent and units say what a value measures, which
+            // nothing here converts.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn local(&mut self, item: &Item) -> Result<(), Error> {
+        let l = &mut self.locals;
+        match item.tag {
+            0 => {
+                let slot = l.usages.get_mut(l.n).ok_or(Error::TooManyUsages)?;
+                *slot = Usage::of(item);
+                l.n += 1;
+            }
+            1 => l.min = Some(Usage::of(item)),
+            2 => l.max = Some(Usage::of(item)),
+            // Designators, strings and delimiters choose among usages a
+            // device offers alternatives of; the first one named is used.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// One main item. `true` when it closed the chosen collection.
+    fn main(&mut self, item: &Item) -> Result<bool, Error> {
+        match item.tag {
+            INPUT => self.input(item.data)?,
+            COLLECTION => {
+                self.depth += 1;
+                if self.depth > MAX_DEPTH {
+                    return Err(Error::TooDeep);
+                }
+                if self.depth == 1
+                    && item.data == COLLECTION_APPLICATION
+                    && self.app.is_none()
+                {
+                    self.app = App::of(self.locals.at(0, self.globals.page));
+                }
+            }
+            END_COLLECTION => {
+                self.depth = self.depth.checked_sub(1).ok_or(Error::Unbalanced)?;
+                return Ok(self.depth == 0 && self.app.is_some());
+            }
+            // Output and Feature reports travel the other way or on request,
+            // and neither moves an input report's offsets.
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    fn input(&mut self, flags: u32) -> Result<(), Error> {
+        let g = self.globals;
+        let bits = g.size as u64 * g.count as u64;
+        let at = self.offsets.of(g.id)?;
+        let offset = *at;
+        *at = offset.saturating_add(bits);
+        let end = *at;
+
+        let Some(app) = self.app else { return Ok(()) };
+        if flags & FLAG_CONSTANT != 0 {
+            // Padding, but padding of the chosen report is its length too.
+            if self.id == Some(g.id) && end > MAX_REPORT as u64 * 8 {
+                return Err(Error::TooLong);
+            }
+            return Ok(());
+        }
+        match self.id {
+            None => self.id = Some(g.id),
+            Some(id) if id != g.id => return Ok(()),
+            Some(_) => {}
+        }
+        if end > MAX_REPORT as u64 * 8 {
+            return Err(Error::TooLong);
+        }
+        // A value wider than this parse reads is stepped over. `end` is within
+        // the report, so `count` is at most its bits and the loop is bounded.
+        if g.size == 0 || g.size > 32 {
+            return Ok(());
+        }
+        let relative = flags & FLAG_RELATIVE != 0;
+        let (min, max) = (g.min, g.max());
+        if flags & FLAG_VARIABLE == 0 {
+            // An array: each value names a usage rather than being one. Only a
+            // keyboard's is read.
+            let first = self.locals.at(0, g.page);
+            if app == App::Keyboard && first >> 16 == PAGE_KEYBOARD as u32 {
+                self.push(Field {
+                    role: Role::KeyArray(first as u16),
+                    offset: offset as u16,
+                    size: g.size as u8,
+                    count: g.count as u16,
+                    min,
+                    max,
+                    relative,
+                })?;
+            }
+            return Ok(());
+        }
+        for i in 0..g.count as usize {
+            let Some(role) = app.role(self.locals.at(i, g.page)) else { continue };
+            self.push(Field {
+                role,
+                offset: (offset + i as u64 * g.size as u64) as u16,
+                size: g.size as u8,
+                count: 1,
+                min,
+                max,
+                relative,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Keep one field, as the next of the last one's run where it is that.
+    fn push(&mut self, field: Field) -> Result<(), Error> {
+        if let Some(last) = self.fields[..self.n].last_mut() {
+            let next = |a: u8, b: u8| b as u16 == a as u16 + last.count;
+            let continues = match (last.role, field.role) {
+                (Role::Keys(a), Role::Keys(b)) | (Role::Buttons(a), Role::Buttons(b)) => next(a, b),
+                _ => false,
+            };
+            if continues
+                && field.size == last.size
+                && field.offset as u32 == last.offset as u32 + last.size as u32 * last.count as u32
+                && (field.min, field.max) == (last.min, last.max)
+            {
+                last.count += 1;
+                return Ok(());
+            }
+        }
+        let slot = self.fields.get_mut(self.n).ok_or(Error::TooManyFields)?;
+        *slot = field;
+        self.n += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Layout, Error> {
+        let app = self.app.expect("only the chosen collection closes a parse");
+        let fields = &self.fields[..self.n];
+        let has = |want: fn(Role) -> bool| fields.iter().any(|f| want(f.role));
+        let kind = match app {
+            App::Keyboard if has(|r| matches!(r, Role::Keys(_) | Role::KeyArray(_))) => {
+                Kind::Keyboard
+            }
+            App::Pointer if has(|r| r == Role::X) && has(|r| r == Role::Y) => {
+                let x = fields.iter().find(|f| f.role == Role::X).expect("has an X");
+                if x.relative { Kind::Mouse } else { Kind::Tablet }
+            }
+            App::Pad { joystick } if self.n > 0 => {
+                if joystick { Kind::Joystick } else { Kind::Gamepad }
+            }
+            _ => return Err(Error::Incomplete),
+        };
+        let id = self.id.expect("a kept field set the report ID");
+        let Parse { mut offsets, fields, n, .. } = self;
+        let bits = *offsets.of(id)?;
+        Ok(Layout { kind, report_id: id, len: bits.div_ceil(8) as u8, fields, n: n as u8 })
+    }
+}
@@ -0,0 +1,31 @@
+//! HID report descriptors: the program a device writes to say what its reports
+//! hold, and the reports read through it.
+//!
+//! A boot-protocol keyboard or mouse reports in a format fixed by the HID
+//! specification's appendix B, and the kernel has always read those by hand.
+//! Everything else — a keyboard without a boot interface, a tablet, every game
+//! controller — reports in a format only its report descriptor states. The
+//! kernel reads that descriptor once at enumeration, asks this crate for a
+//! [`Layout`], and hands each report that arrives to [`Layout::decode`]:
+//!
+//! - [`item`]: where each item of a descriptor starts and ends.
+//! - [`layout`]: which application collection is bound, and where in its
+//!   report each key, button, axis and hat lives.
+//! - [`report`]: one report as a boot keyboard report, a pointer sample or a
+//!   pad state.
+//!
+//! The descriptor is the device's, so every count in it is treated as hostile:
+//! the parse is bounded by caps it refuses past, never panics, and never reads
+//! outside the bytes it was given.
+//!
+//! `no_std`, no allocation, no `unsafe`.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+pub mod item;
+pub mod layout;
+pub mod report;
+
+pub use layout::{Error, Kind, Layout, MAX_FIELDS, MAX_REPORT};
+pub use report::{Motion, Pad, Pointer, Report, AXES, HAT_CENTERED};
@@ -0,0 +1,249 @@
+//! One report, read through the layout its descriptor gave.
+//!
+//! Each role comes out in the shape the kernel already consumes, so a device
+//! that reports in its own format joins the same paths a boot-protocol one
+//! takes: a keyboard as an eight-byte boot report for the keyboard's diff, a
+//! pointer as the buttons, motion and wheel `mouse::handle_motion` takes, and
+//! a game controller as one [`Pad`] state.
+
+use crate::layout::{Field, Kind, Layout, Role};
+
+/// Axes a [`Pad`] carries: Generic Desktop X, Y, Z, Rx, Ry, Rz, Slider and
+/// Dial, in that order, whether or not the device has each.
+pub const AXES: usize = 8;
+
+/// The hat value for "not pressed". Positions are 0..8 clockwise from up.
+pub const HAT_CENTERED: u8 = 8;
+
+/// What one report says.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Report {
+    /// In boot format: modifiers, a reserved byte, then up to six keys.
+    Keyboard([u8; 8]),
+    Pointer(Pointer),
+    Pad(Pad),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Pointer {
+    /// Boot-mouse order: bit 0 is button 1.
+    pub buttons: u8,
+    pub motion: Motion,
+    pub wheel: i8,
+}
+
+/// The two shapes `mouse::Motion` takes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Motion {
+    Relative { dx: i32, dy: i32 },
+    /// Scaled from the device's logical range to 0..=32767 on both axes.
+    Absolute { x: u16, y: u16 },
+}
+
+/// A game controller's whole state, which every report restates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Pad {
+    /// Bit n is button n + 1.
+    pub buttons: u32,
+    /// Each axis scaled from the device's logical range to -32767..=32767,
+    /// centre 0; an axis the device does not have reads 0.
+    pub axes: [i16; AXES],
+    pub hat: u8,
+}
+
+/// Boot-report slots past the modifiers. A seventh key down at once is a
+/// rollover the boot format has no way to say.
+const KEY_SLOTS: usize = 6;
+const ERROR_ROLL_OVER: i64 = 1;
+
+impl Layout {
+    /// What one report says, or `None` for a report this layout does not
+    /// describe: another report ID, one shorter than the layout, or a keyboard
+    /// rolling over — which states no keys at all, so passing it on would
+    /// release every key that is still down.
+    pub fn decode(&self, report: &[u8]) -> Option<Report> {
+        if report.len() < self.report_len() {
+            return None;
+        }
+        if self.report_id != 0 && report[0] != self.report_id {
+            return None;
+        }
+        match self.kind {
+            Kind::Keyboard => self.keyboard(report).map(Report::Keyboard),
+            Kind::Mouse | Kind::Tablet => Some(Report::Pointer(self.pointer(report))),
+            Kind::Joystick | Kind::Gamepad => Some(Report::Pad(self.pad(report))),
+        }
+    }
+
+    fn keyboard(&self, report: &[u8]) -> Option<[u8; 8]> {
+        let mut boot = [0u8; 8];
+        let mut keys = 0;
+        let mut press = |usage: i64| -> Option<()> {
+            match usage {
+                0xE0..=0xE7 => boot[0] |= 1 << (usage - 0xE0),
+                // Below 4 is no key or an error code, and past 0xDF is no key.
+                4..=0xDF => {
+                    if keys == KEY_SLOTS {
+                        return None;
+                    }
+                    boot[2 + keys] = usage as u8;
+                    keys += 1;
+                }
+                _ => {}
+            }
+            Some(())
+        };
+        for field in self.fields() {
+            match field.role {
+                Role::Keys(first) => {
+                    for i in 0..field.count {
+                        if value(report, field, i) != 0 {
+                            press(first as i64 + i as i64)?;
+                        }
+                    }
+                }
+                Role::KeyArray(first) => {
+                    for i in 0..field.count {
+                        let v = value(report, field, i);
+                        if v < field.min || v > field.max {
+                            continue;
+                        }
+                        let usage = (first & 0xFF) as i64 + (v - field.min);
+                        if usage == ERROR_ROLL_OVER {
+                            return None;
+                        }
+                        press(usage)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(boot)
+    }
+
+    fn pointer(&self, report: &[u8]) -> Pointer {
+        let mut buttons = 0u8;
+        let (mut x, mut y, mut wheel) = (None, None, 0i64);
+        for field in self.fields() {
+            match field.role {
+                Role::Buttons(first) => {
+                    for i in 0..field.count {
+                        let n = first as u16 + i;
+                        if n < 8 && value(report, field, i) != 0 {
+                            buttons |= 1 << n;
+                        }
+                    }
+                }
+                Role::X => x = Some((field, value(report, field, 0))),
+                Role::Y => y = Some((field, value(report, field, 0))),
+                Role::Wheel => wheel = value(report, field, 0),
+                _ => {}
+            }
+        }
+        let ((fx, vx), (fy, vy)) = (x.expect("a pointer has an X"), y.expect("and a Y"));
+        let motion = if self.kind == Kind::Mouse {
+            let clamp = |v: i64| v.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+            Motion::Relative { dx: clamp(vx), dy: clamp(vy) }
+        } else {
+            Motion::Absolute { x: absolute(fx, vx), y: absolute(fy, vy) }
+        };
+        Pointer { buttons, motion, wheel: wheel.clamp(i8::MIN as i64, i8::MAX as i64) as i8 }
+    }
+
+    fn pad(&self, report: &[u8]) -> Pad {
+        let mut pad = Pad { buttons: 0, axes: [0; AXES], hat: HAT_CENTERED };
+        let mut dpad = 0u8;
+        let mut hat = false;
+        for field in self.fields() {
+            match field.role {
+                Role::Buttons(first) => {
+                    for i in 0..field.count {
+                        let n = first as u16 + i;
+                        if n < 32 && value(report, field, i) != 0 {
+                            pad.buttons |= 1 << n;
+                        }
+                    }
+                }
+                Role::Axis(a) => pad.axes[a as usize] = centred(field, value(report, field, 0)),
+                Role::Hat => {
+                    hat = true;
+                    pad.hat = hat_position(field, value(report, field, 0));
+                }
+                Role::DPad(d) if value(report, field, 0) != 0 => dpad |= 1 << d,
+                _ => {}
+            }
+        }
+        // A controller that states its D-pad as four buttons rather than a
+        // hat is given one, so a game reads one shape whichever it has.
+        if !hat {
+            pad.hat = dpad_hat(dpad);
+        }
+        pad
+    }
+}
+
+/// The `i`th value of a field, sign-extended where its logical minimum is
+/// negative. The layout holds every field inside [`crate::MAX_REPORT`] and the
+/// report is at least as long, so every byte read here is in it.
+fn value(report: &[u8], field: &Field, i: u16) -> i64 {
+    let size = field.size as u32;
+    let start = field.offset as u32 + i as u32 * size;
+    let mut raw = 0u64;
+    let first = start / 8;
+    for (k, byte) in (first..=(start + size - 1) / 8).enumerate() {
+        raw |= (*report.get(byte as usize).unwrap_or(&0) as u64) << (8 * k);
+    }
+    let raw = (raw >> (start % 8)) & ((1u64 << size) - 1);
+    if field.min < 0 {
+        let shift = 64 - size;
+        ((raw << shift) as i64) >> shift
+    } else {
+        raw as i64
+    }
+}
+
+fn absolute(field: &Field, v: i64) -> u16 {
+    if field.max <= field.min {
+        return 0;
+    }
+    ((v.clamp(field.min, field.max) - field.min) * 32767 / (field.max - field.min)) as u16
+}
+
+fn centred(field: &Field, v: i64) -> i16 {
+    if field.max <= field.min {
+        return 0;
+    }
+    ((v.clamp(field.min, field.max) - field.min) * 65534 / (field.max - field.min) - 32767) as i16
+}
+
+/// A hat's value as one of eight positions. A four-position hat steps by
+/// quarter turns; any other count, and any value outside the range — which is
+/// how a hat with a null state says it is released — is centred.
+fn hat_position(field: &Field, v: i64) -> u8 {
+    if v < field.min || v > field.max {
+        return HAT_CENTERED;
+    }
+    match field.max - field.min + 1 {
+        8 => (v - field.min) as u8,
+        4 => (v - field.min) as u8 * 2,
+        _ => HAT_CENTERED,
+    }
+}
+
+/// Four D-pad bits — up, down, right, left — as a hat position. Opposite
+/// directions together cancel.
+fn dpad_hat(bits: u8) -> u8 {
+    let vertical = (bits & 0b10 != 0) as i8 - (bits & 0b01 != 0) as i8;
+    let horizontal = (bits & 0b0100 != 0) as i8 - (bits & 0b1000 != 0) as i8;
+    match (vertical, horizontal) {
+        (-1, 0) => 0,
+        (-1, 1) => 1,
+        (0, 1) => 2,
+        (1, 1) => 3,
+        (1, 0) => 4,
+        (1, -1) => 5,
+        (0, -1) => 6,
+        (-1, -1) => 7,
+        _ => HAT_CENTERED,
+    }
+}
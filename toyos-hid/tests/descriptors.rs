@@ -0,0 +1,288 @@
+//! What the parse and the decode promise, checked against descriptors devices
+//! really ship and against ones written to break them. QEMU offers exactly one
+//! report-protocol device, the tablet; every other descriptor here is one the
+//! kernel would otherwise meet first on real hardware.
+
+use toyos_hid::{Error, Kind, Layout, Motion, Pad, Pointer, Report, HAT_CENTERED};
+
+/// QEMU's `usb-tablet` (hw/usb/dev-hid.c): three buttons, 16-bit absolute X and
+/// Y over 0..=0x7FFF, and a relative wheel.
+const QEMU_TABLET: &[u8] = &[
+    0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x09, 0x01, 0xA1, 0x00, 0x05, 0x09, 0x19, 0x01, 0x29,
+    0x03, 0x15, 0x00, 0x25, 0x01, 0x95, 0x03, 0x75, 0x01, 0x81, 0x02, 0x95, 0x01, 0x75, 0x05,
+    0x81, 0x01, 0x05, 0x01, 0x09, 0x30, 0x09, 0x31, 0x15, 0x00, 0x26, 0xFF, 0x7F, 0x35, 0x00,
+    0x46, 0xFF, 0x7F, 0x75, 0x10, 0x95, 0x02, 0x81, 0x02, 0x05, 0x01, 0x09, 0x38, 0x15, 0x81,
+    0x25, 0x7F, 0x35, 0x00, 0x45, 0x00, 0x75, 0x08, 0x95, 0x01, 0x81, 0x06, 0xC0, 0xC0,
+];
+
+/// The keyboard of HID 1.11 appendix B.1 as a report-protocol device ships it:
+/// modifier bits, a reserved byte, five LED outputs and their padding, and a
+/// six-key array.
+const KEYBOARD: &[u8] = &[
+    0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0x05, 0x07, 0x19, 0xE0, 0x29, 0xE7, 0x15, 0x00, 0x25,
+    0x01, 0x75, 0x01, 0x95, 0x08, 0x81, 0x02, 0x95, 0x01, 0x75, 0x08, 0x81, 0x01, 0x95, 0x05,
+    0x75, 0x01, 0x05, 0x08, 0x19, 0x01, 0x29, 0x05, 0x91, 0x02, 0x95, 0x01, 0x75, 0x03, 0x91,
+    0x01, 0x95, 0x06, 0x75, 0x08, 0x15, 0x00, 0x25, 0x65, 0x05, 0x07, 0x19, 0x00, 0x29, 0x65,
+    0x81, 0x00, 0xC0,
+];
+
+/// An n-key-rollover keyboard behind report ID 1: modifiers, then one bit per
+/// key from 0x04 to 0x67, with a consumer-control collection after it under
+/// report ID 2 that the layout must leave alone.
+const NKRO: &[u8] = &[
+    0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0x85, 0x01, 0x05, 0x07, 0x19, 0xE0, 0x29, 0xE7, 0x15,
+    0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x08, 0x81, 0x02, 0x19, 0x04, 0x29, 0x67, 0x95, 0x64,
+    0x81, 0x02, 0x95, 0x04, 0x81, 0x01, 0xC0, 0x05, 0x0C, 0x09, 0x01, 0xA1, 0x01, 0x85, 0x02,
+    0x15, 0x00, 0x26, 0xFF, 0x03, 0x19, 0x00, 0x2A, 0xFF, 0x03, 0x75, 0x10, 0x95, 0x01, 0x81,
+    0x00, 0xC0,
+];
+
+/// A typical DirectInput gamepad, report ID 1: four 8-bit sticks (X, Y, Z,
+/// Rz), an eight-way hat with a null state and its padding, and twelve
+/// buttons padded to sixteen.
+const GAMEPAD: &[u8] = &[
+    0x05, 0x01, 0x09, 0x05, 0xA1, 0x01, 0x85, 0x01, 0xA1, 0x00, 0x09, 0x30, 0x09, 0x31, 0x09,
+    0x32, 0x09, 0x35, 0x15, 0x00, 0x26, 0xFF, 0x00, 0x75, 0x08, 0x95, 0x04, 0x81, 0x02, 0xC0,
+    0x09, 0x39, 0x15, 0x00, 0x25, 0x07, 0x35, 0x00, 0x46, 0x3B, 0x01, 0x65, 0x14, 0x75, 0x04,
+    0x95, 0x01, 0x81, 0x42, 0x65, 0x00, 0x75, 0x04, 0x95, 0x01, 0x81, 0x01, 0x05, 0x09, 0x19,
+    0x01, 0x29, 0x0C, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x0C, 0x81, 0x02, 0x75, 0x01,
+    0x95, 0x04, 0x81, 0x01, 0xC0,
+];
+
+/// A joystick with signed 16-bit X and Y, four buttons, and its D-pad as four
+/// usages rather than a hat.
+const DPAD_JOYSTICK: &[u8] = &[
+    0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0x09, 0x30, 0x09, 0x31, 0x16, 0x01, 0x80, 0x26, 0xFF,
+    0x7F, 0x75, 0x10, 0x95, 0x02, 0x81, 0x02, 0x05, 0x09, 0x19, 0x01, 0x29, 0x04, 0x15, 0x00,
+    0x25, 0x01, 0x75, 0x01, 0x95, 0x04, 0x81, 0x02, 0x05, 0x01, 0x09, 0x90, 0x09, 0x91, 0x09,
+    0x92, 0x09, 0x93, 0x95, 0x04, 0x81, 0x02, 0xC0,
+];
+
+#[test]
+fn the_qemu_tablet_is_a_tablet_with_the_report_the_kernel_always_read() {
+    let layout = Layout::parse(QEMU_TABLET).unwrap();
+    assert_eq!(layout.kind(), Kind::Tablet);
+    assert_eq!(layout.report_id(), 0);
+    assert_eq!(layout.report_len(), 6);
+    let report = [0b101, 0xFF, 0x7F, 0x00, 0x40, 0xFF];
+    assert_eq!(
+        layout.decode(&report),
+        Some(Report::Pointer(Pointer {
+            buttons: 0b101,
+            motion: Motion::Absolute { x: 32767, y: 0x4000 },
+            wheel: -1,
+        }))
+    );
+}
+
+/// A relative pointer is the same collection with the relative flag on X; the
+/// boot mouse's own descriptor says so.
+#[test]
+fn a_relative_pointer_is_a_mouse_and_its_deltas_are_signed() {
+    let mut mouse = QEMU_TABLET.to_vec();
+    // X and Y: 8-bit, -127..=127, relative.
+    let xy = mouse.windows(6).position(|w| w == [0x75, 0x10, 0x95, 0x02, 0x81, 0x02]).unwrap();
+    mouse.splice(xy..xy + 6, [0x15, 0x81, 0x25, 0x7F, 0x75, 0x08, 0x95, 0x02, 0x81, 0x06]);
+    let layout = Layout::parse(&mouse).unwrap();
+    assert_eq!(layout.kind(), Kind::Mouse);
+    assert_eq!(layout.report_len(), 4);
+    assert_eq!(
+        layout.decode(&[1, 0xFE, 5, 0]),
+        Some(Report::Pointer(Pointer {
+            buttons: 1,
+            motion: Motion::Relative { dx: -2, dy: 5 },
+            wheel: 0,
+        }))
+    );
+}
+
+#[test]
+fn a_report_protocol_keyboard_decodes_to_the_boot_report_it_would_have_sent() {
+    let layout = Layout::parse(KEYBOARD).unwrap();
+    assert_eq!(layout.kind(), Kind::Keyboard);
+    assert_eq!(layout.report_len(), 8);
+    let report = [0x22, 0, 0x04, 0x05, 0, 0, 0, 0];
+    assert_eq!(layout.decode(&report), Some(Report::Keyboard(report)));
+}
+
+/// ErrorRollOver in every slot states no keys, so it is not a report that
+/// releases them.
+#[test]
+fn a_keyboard_rolling_over_is_not_a_report() {
+    let layout = Layout::parse(KEYBOARD).unwrap();
+    assert_eq!(layout.decode(&[0, 0, 1, 1, 1, 1, 1, 1]), None);
+}
+
+#[test]
+fn a_bitmap_keyboard_is_read_as_six_keys_and_its_other_report_is_ignored() {
+    let layout = Layout::parse(NKRO).unwrap();
+    assert_eq!(layout.kind(), Kind::Keyboard);
+    assert_eq!(layout.report_id(), 1);
+    // ID, modifiers, 100 key bits and 4 of padding.
+    assert_eq!(layout.report_len(), 15);
+    let mut report = [0u8; 15];
+    report[0] = 1;
+    report[1] = 0x01;
+    // Keys 0x04 (A) and 0x28 (Enter): bits 0 and 36 of the key run.
+    report[2] |= 1;
+    report[2 + 36 / 8] |= 1 << (36 % 8);
+    assert_eq!(layout.decode(&report), Some(Report::Keyboard([0x01, 0, 0x04, 0x28, 0, 0, 0, 0])));
+
+    // Seven keys is more than the boot format holds.
+    report[2] = 0x7F;
+    assert_eq!(layout.decode(&report), None);
+
+    let mut consumer = [0u8; 15];
+    consumer[0] = 2;
+    assert_eq!(layout.decode(&consumer), None);
+}
+
+#[test]
+fn a_gamepad_reports_centred_axes_its_hat_and_its_buttons() {
+    let layout = Layout::parse(GAMEPAD).unwrap();
+    assert_eq!(layout.kind(), Kind::Gamepad);
+    assert_eq!(layout.report_id(), 1);
+    assert_eq!(layout.report_len(), 8);
+    // Sticks at rest, hat released (its null state, 8), button 1 and 12.
+    let Some(Report::Pad(rest)) = layout.decode(&[1, 0x80, 0x80, 0x80, 0x80, 0x08, 0x01, 0x08])
+    else {
+        panic!("not a pad report")
+    };
+    assert_eq!(rest.hat, HAT_CENTERED);
+    assert_eq!(rest.buttons, 1 | 1 << 11);
+    for axis in [0, 1, 2, 5] {
+        assert!(rest.axes[axis].abs() <= 128, "axis {axis} at rest reads {}", rest.axes[axis]);
+    }
+    assert_eq!(rest.axes[3], 0, "a missing axis reads 0");
+
+    let Some(Report::Pad(full)) = layout.decode(&[1, 0x00, 0xFF, 0x00, 0xFF, 0x03, 0, 0]) else {
+        panic!("not a pad report")
+    };
+    assert_eq!((full.axes[0], full.axes[1]), (-32767, 32767));
+    assert_eq!((full.axes[2], full.axes[5]), (-32767, 32767));
+    assert_eq!(full.hat, 3);
+    assert_eq!(full.buttons, 0);
+}
+
+#[test]
+fn a_dpad_without_a_hat_is_given_one() {
+    let layout = Layout::parse(DPAD_JOYSTICK).unwrap();
+    assert_eq!(layout.kind(), Kind::Joystick);
+    assert_eq!(layout.report_len(), 5);
+    let pad = |bits: u8| match layout.decode(&[0, 0, 0, 0, bits << 4 | 0b0010]) {
+        Some(Report::Pad(Pad { hat, buttons, .. })) => {
+            assert_eq!(buttons, 0b0010);
+            hat
+        }
+        other => panic!("{other:?}"),
+    };
+    // Up, down, right, left.
+    assert_eq!(pad(0b0001), 0);
+    assert_eq!(pad(0b0101), 1);
+    assert_eq!(pad(0b0010), 4);
+    assert_eq!(pad(0b1010), 5);
+    assert_eq!(pad(0b0000), HAT_CENTERED);
+    assert_eq!(pad(0b0011), HAT_CENTERED);
+
+    let Some(Report::Pad(full)) = layout.decode(&[0x01, 0x80, 0xFF, 0x7F, 0]) else { panic!() };
+    assert_eq!((full.axes[0], full.axes[1]), (-32767, 32767));
+}
+
+#[test]
+fn a_report_shorter_than_the_layout_is_not_decoded() {
+    let layout = Layout::parse(GAMEPAD).unwrap();
+    assert_eq!(layout.decode(&[1, 0x80, 0x80]), None);
+    assert_eq!(layout.decode(&[]), None);
+}
+
+#[test]
+fn a_descriptor_with_nothing_this_driver_binds_is_unsupported() {
+    // A consumer-control collection alone.
+    let at = NKRO.windows(2).position(|w| w == [0x05, 0x0C]).unwrap();
+    assert_eq!(Layout::parse(&NKRO[at..]).unwrap_err(), Error::Unsupported);
+    assert_eq!(Layout::parse(&[]).unwrap_err(), Error::Unsupported);
+}
+
+/// Every cap refuses rather than truncates.
+#[test]
+fn a_hostile_descriptor_is_refused_at_each_cap() {
+    let deep: Vec<u8> = [0x05, 0x01, 0x09, 0x05]
+        .into_iter()
+        .chain([0xA1, 0x01].repeat(17))
+        .collect();
+    assert_eq!(Layout::parse(&deep).unwrap_err(), Error::TooDeep);
+
+    let pushes = [0xA4].repeat(5);
+    assert_eq!(Layout::parse(&pushes).unwrap_err(), Error::TooDeep);
+    assert_eq!(Layout::parse(&[0xB4]).unwrap_err(), Error::Unbalanced);
+    assert_eq!(Layout::parse(&[0xC0]).unwrap_err(), Error::Unbalanced);
+
+    let usages = [0x09, 0x30].repeat(33);
+    assert_eq!(Layout::parse(&usages).unwrap_err(), Error::TooManyUsages);
+
+    let ids: Vec<u8> = (1..=17u8).flat_map(|id| [0x85, id, 0x75, 0x08, 0x95, 0x01, 0x81, 0x02]).collect();
+    assert_eq!(Layout::parse(&ids).unwrap_err(), Error::TooManyReports);
+
+    assert_eq!(Layout::parse(&[0x85, 0x00]).unwrap_err(), Error::ReportId);
+
+    // A gamepad whose one report is 65 bytes.
+    let long = [0x05, 0x01, 0x09, 0x05, 0xA1, 0x01, 0x09, 0x30, 0x75, 0x08, 0x95, 0x41, 0x81, 0x02, 0xC0];
+    assert_eq!(Layout::parse(&long).unwrap_err(), Error::TooLong);
+
+    // Thirty-three separate axes, each its own field.
+    let mut many = vec![0x05, 0x01, 0x09, 0x05, 0xA1, 0x01, 0x75, 0x08, 0x95, 0x01];
+    for _ in 0..33 {
+        many.extend([0x09, 0x30, 0x81, 0x02]);
+    }
+    many.push(0xC0);
+    assert_eq!(Layout::parse(&many).unwrap_err(), Error::TooManyFields);
+
+    // A gamepad collection that is never closed.
+    assert_eq!(Layout::parse(&GAMEPAD[..GAMEPAD.len() - 1]).unwrap_err(), Error::Unbalanced);
+}
+
+#[test]
+fn a_pointer_without_both_axes_is_incomplete() {
+    let buttons_only = &[
+        0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x05, 0x09, 0x19, 0x01, 0x29, 0x03, 0x75, 0x01, 0x95,
+        0x03, 0x81, 0x02, 0xC0,
+    ];
+    assert_eq!(Layout::parse(buttons_only).unwrap_err(), Error::Incomplete);
+}
+
+/// Every prefix of every descriptor here, and a few thousand descriptors of
+/// noise, parse to a verdict — and every layout that comes out decodes every
+/// report length without panicking.
+#[test]
+fn no_descriptor_and_no_report_panics() {
+    let mut seed = 0x2545_F491_4F6C_DD1Du64;
+    let mut next = || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as u8
+    };
+    let mut descriptors: Vec<Vec<u8>> = Vec::new();
+    for real in [QEMU_TABLET, KEYBOARD, NKRO, GAMEPAD, DPAD_JOYSTICK] {
+        for cut in 0..=real.len() {
+            descriptors.push(real[..cut].to_vec());
+        }
+        for _ in 0..200 {
+            let mut mutated = real.to_vec();
+            let at = next() as usize % mutated.len();
+            mutated[at] = next();
+            descriptors.push(mutated);
+        }
+    }
+    for _ in 0..2000 {
+        let len = next() as usize;
+        descriptors.push((0..len).map(|_| next()).collect());
+    }
+    let report: Vec<u8> = (0..toyos_hid::MAX_REPORT).map(|_| next()).collect();
+    for descriptor in &descriptors {
+        if let Ok(layout) = Layout::parse(descriptor) {
+            assert!(layout.report_len() <= toyos_hid::MAX_REPORT);
+            for len in 0..=report.len() {
+                let _ = layout.decode(&report[..len]);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+#!/bin/sh
+# Regenerates the committed image. Not run by any test, for toyos-ext4's
+# reason: the image is the fixture, and a test that rebuilt it would be
+# asserting whatever the host's libarchive happens to produce today. Run by
+# hand, once, when the corpus needs to change — and commit what it writes.
+#
+# Made with bsdtar 3.8.2 (libarchive). The tree's times are pinned; the
+# volume's own creation stamps and libarchive's note in the system area are
+# not, and nothing reads either.
+set -eu
+cd "$(dirname "$0")"
+
+tree=$(mktemp -d)
+trap 'rm -rf "$tree"' EXIT
+
+# The tree the image carries. The three `expected-*.txt` beside it are its
+# listing as libarchive's own reader sees each namespace, so the tests compare
+# against the host's account and not against this crate's own.
+mkdir -p "$tree/sub/deeper" "$tree/many" "$tree/a/b/c/d/e/f/g/h/i"
+printf 'hello from a compact disc\n' > "$tree/hello.txt"
+: > "$tree/empty"
+python3 - "$tree" <<'PY'
+import sys
+t = sys.argv[1]
+open(f'{t}/sub/deeper/pattern.bin', 'wb').write(bytes((i * 131 + 7) % 251 for i in range(300 * 1024)))
+open(f'{t}/sub/one-sector.bin', 'wb').write(bytes((i * 7) % 256 for i in range(2048)))
+# Fifteen sectors of directory records: a lookup has to cross sector
+# boundaries, and the primary names collide after 8.3 truncation.
+for i in range(200):
+    open(f'{t}/many/entry-{i:04d}.txt', 'w').write(f'entry {i}\n')
+PY
+# Nine levels: past ISO 9660's eight, so the Rock Ridge tree relocates `h`
+# under `rr_moved` and leaves a CL entry where it was.
+printf 'nine levels down\n' > "$tree/a/b/c/d/e/f/g/h/i/deep.txt"
+# Longer than Joliet's 64 characters, so the two trees disagree on its name.
+printf 'long\n' > "$tree/a-name-that-is-far-too-long-for-joliet-which-stops-at-sixty-four-characters-while-rock-ridge-does-not.txt"
+ln -s hello.txt "$tree/fast-link"
+ln -s /sub/deeper/pattern.bin "$tree/abs-link"
+ln -s ../hello.txt "$tree/sub/up"
+# A no-emulation boot image, so there is a catalog to read.
+python3 -c 'import sys; sys.stdout.buffer.write(b"\x90" * 2048)' > "$tree/boot.img"
+find "$tree" -exec touch -h -d @1760000000 {} +
+
+rm -f tree.iso
+bsdtar --format iso9660 \
+    --options 'iso9660:volume-id=ISO_FIXTURE,iso9660:rockridge,iso9660:joliet,iso9660:boot=boot.img,iso9660:boot-type=no-emulation,iso9660:boot-catalog=boot.catalog,iso9660:!pad' \
+    -cf tree.iso -C "$tree" .
+
+# One line per name: `f <size> <path>`, `d <path>` or `l <target> <path>`,
+# sorted by path, in the shape toyos-ext4's `expected.txt` has. Written from
+# libarchive's reader, once per namespace: Rock Ridge, then Joliet with Rock
+# Ridge turned off, then the bare ISO 9660 names with both turned off.
+listing() {
+    bsdtar --options "$1" -tvf tree.iso | awk '
+        $NF == "." { next }
+        /^d/ { print "d " $NF; next }
+        /^l/ { print "l " $NF " " $(NF-2); next }
+        { print "f " $5 " " $NF }' | awk '{ print $NF "\t" $0 }' | LC_ALL=C sort | cut -f2
+}
+listing 'iso9660:rockridge' > expected-rr.txt
+listing 'iso9660:!rockridge' > expected-joliet.txt
+listing 'iso9660:!rockridge,iso9660:!joliet' > expected-primary.txt
@@ -0,0 +1,26 @@
+/// The volume this crate reads.
+///
+/// Byte-addressed, like toyos-ext4's: a disc's logical block is 2048 bytes,
+/// the kernel's `BlockDevice` speaks 4096, and a hybrid image on a USB stick
+/// may sit behind 512-byte sectors. The bridge is the implementor's business.
+///
+/// **There is no `write_at`.** As in toyos-ext4: a method that does not exist,
+/// so the audit that nothing here writes is a `grep`.
+///
+/// Offsets are relative to the start of the volume, which for a disc is the
+/// start of the medium.
+pub trait BlockAccess {
+    /// Bytes in the volume. Used once, at mount, to reject descriptors that
+    /// describe more volume than exists.
+    fn capacity(&self) -> u64;
+
+    /// Fill `buf` from `offset`. Reading past [`capacity`](Self::capacity) is
+    /// an [`IoError`], not a short read.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError>;
+}
+
+/// The device could not do it.
+///
+/// Carries no detail, for the reason `toyos_fat32::IoError` carries none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoError;
@@ -0,0 +1,74 @@
+//! Directories: a run of sectors, each holding whole records.
+
+use alloc::vec::Vec;
+
+use crate::device::BlockAccess;
+use crate::error::Error;
+use crate::record::{self, Record};
+use crate::volume::{Volume, SECTOR_BYTES};
+
+/// The largest directory this crate will read: over 100,000 records of the
+/// smallest size, more than any mastering tool puts in one directory. Past it
+/// is [`Error::LimitExceeded`], so a crafted length costs a bounded scan.
+pub const MAX_DIR_BYTES: u64 = 4 * 1024 * 1024;
+
+/// The longest name this crate returns, in bytes of UTF-8: Linux's
+/// `NAME_MAX`. A Rock Ridge `NM` chain can say more.
+pub const MAX_NAME_BYTES: usize = 255;
+
+/// Call `f` with every record of the directory at `extent`, `len` bytes long,
+/// in on-disk order, until it answers `true`.
+///
+/// A record never crosses a sector: a zero length byte is the padding that
+/// ends one, and a record that would run past the end of its sector is
+/// [`Error::CorruptDirectory`]. `f` is handed the device too, because a Rock
+/// Ridge continuation area is read while the record that named it is still
+/// borrowed from this sector.
+pub fn scan<D: BlockAccess>(
+    dev: &mut D,
+    volume: &Volume,
+    extent: u32,
+    len: u32,
+    mut f: impl FnMut(&mut D, &Record<'_>) -> Result<bool, Error>,
+) -> Result<(), Error> {
+    if len as u64 > MAX_DIR_BYTES {
+        return Err(Error::LimitExceeded);
+    }
+    let sectors = (len as u64).div_ceil(SECTOR_BYTES);
+    if !volume.contains(extent, sectors) {
+        return Err(Error::CorruptDirectory);
+    }
+    let mut buf = [0u8; SECTOR_BYTES as usize];
+    for i in 0..sectors {
+        dev.read_at((extent as u64 + i) * SECTOR_BYTES, &mut buf)?;
+        let mut at = 0usize;
+        while at < buf.len() {
+            let rec_len = buf[at] as usize;
+            if rec_len == 0 {
+                break;
+            }
+            let rec = record::parse(&buf[at..])?;
+            if f(dev, &rec)? {
+                return Ok(());
+            }
+            at += rec_len;
+        }
+    }
+    Ok(())
+}
+
+/// The `.` record's System Use area of the directory at `extent`, and its
+/// length — where a Rock Ridge volume announces itself, and where a relocated
+/// directory's size has to be read from.
+pub fn dot<D: BlockAccess>(dev: &mut D, volume: &Volume, extent: u32) -> Result<(u32, Vec<u8>), Error> {
+    if !volume.contains(extent, 1) {
+        return Err(Error::CorruptDirectory);
+    }
+    let mut buf = [0u8; SECTOR_BYTES as usize];
+    dev.read_at(extent as u64 * SECTOR_BYTES, &mut buf)?;
+    let rec = record::parse(&buf)?;
+    if rec.name != [0] || !rec.is_dir() {
+        return Err(Error::CorruptDirectory);
+    }
+    Ok((rec.len, rec.system_use.to_vec()))
+}
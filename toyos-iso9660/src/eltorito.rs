@@ -0,0 +1,104 @@
+//! The El Torito boot catalog: which images firmware may start, for which
+//! platform.
+//!
+//! One sector of 32-byte entries. A validation entry that checksums to zero,
+//! then the initial (default) entry, then any number of sections, each a
+//! header naming a platform and that many entries. Read only so a host or a
+//! test can ask what a disc boots; the kernel never boots anything.
+
+use alloc::vec::Vec;
+
+use crate::device::BlockAccess;
+use crate::error::Error;
+use crate::le::{le_u16, le_u32};
+use crate::volume::{Volume, SECTOR_BYTES};
+
+/// The BIOS platform ID.
+pub const PLATFORM_X86: u8 = 0x00;
+/// The UEFI platform ID, which is what a hybrid image's ESP entry carries.
+pub const PLATFORM_EFI: u8 = 0xEF;
+
+const ENTRY_BYTES: usize = 32;
+const HEADER_VALIDATION: u8 = 0x01;
+const HEADER_MORE: u8 = 0x90;
+const HEADER_FINAL: u8 = 0x91;
+const BOOTABLE: u8 = 0x88;
+const EXTENSION: u8 = 0x44;
+
+/// How an image is presented to the code firmware starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Media {
+    /// The image is loaded as it is, `sector_count` virtual sectors of it. What
+    /// UEFI and every modern BIOS boot uses.
+    NoEmulation,
+    /// Floppy or hard-disk emulation, by its media type byte.
+    Emulated(u8),
+}
+
+/// One boot entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootEntry {
+    pub platform: u8,
+    pub bootable: bool,
+    pub media: Media,
+    pub load_segment: u16,
+    /// In 512-byte virtual sectors. Zero on a hybrid image's ESP entry whose
+    /// size does not fit the field: firmware then reads to the end of the
+    /// volume.
+    pub sector_count: u16,
+    /// The image's first logical block.
+    pub load_rba: u32,
+}
+
+fn entry(platform: u8, e: &[u8]) -> BootEntry {
+    let media = e[1] & 0x0F;
+    BootEntry {
+        platform,
+        bootable: e[0] == BOOTABLE,
+        media: if media == 0 { Media::NoEmulation } else { Media::Emulated(media) },
+        load_segment: le_u16(e, 2),
+        sector_count: le_u16(e, 6),
+        load_rba: le_u32(e, 8),
+    }
+}
+
+/// Every entry in the catalog at `block`, the initial one first.
+pub fn read<D: BlockAccess>(dev: &mut D, volume: &Volume, block: u32) -> Result<Vec<BootEntry>, Error> {
+    if !volume.contains(block, 1) {
+        return Err(Error::CorruptCatalog);
+    }
+    let mut buf = [0u8; SECTOR_BYTES as usize];
+    dev.read_at(block as u64 * SECTOR_BYTES, &mut buf)?;
+    let mut entries = buf.as_chunks::<ENTRY_BYTES>().0.iter().peekable();
+
+    let validation = entries.next().ok_or(Error::CorruptCatalog)?;
+    let sum = validation.as_chunks::<2>().0.iter().fold(0u16, |s, w| s.wrapping_add(u16::from_le_bytes(*w)));
+    if validation[0] != HEADER_VALIDATION || validation[30..32] != [0x55, 0xAA] || sum != 0 {
+        return Err(Error::CorruptCatalog);
+    }
+    let mut platform = validation[1];
+
+    let mut out = Vec::new();
+    out.push(entry(platform, entries.next().ok_or(Error::CorruptCatalog)?));
+
+    // Sections. The catalog is one sector here; a header whose entries would
+    // run past it is corrupt rather than continued.
+    while let Some(header) = entries.next() {
+        let last = match header[0] {
+            HEADER_MORE => false,
+            HEADER_FINAL => true,
+            _ => break,
+        };
+        platform = header[1];
+        for _ in 0..le_u16(header, 2) {
+            out.push(entry(platform, entries.next().ok_or(Error::CorruptCatalog)?));
+            // Selection criteria that did not fit the entry run on in
+            // extension entries, which boot nothing themselves.
+            while entries.next_if(|e| e[0] == EXTENSION).is_some() {}
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(out)
+}
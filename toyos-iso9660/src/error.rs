@@ -0,0 +1,75 @@
+use crate::device::IoError;
+
+/// Everything that can go wrong, as data rather than a panic.
+///
+/// Exhaustive on purpose, as `toyos_ext4::Error` is: an adapter mapping these
+/// to `SyscallError` should stop compiling when a new one appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The device refused a read.
+    Io,
+    /// No ISO 9660 primary volume descriptor in the descriptor set, or a set
+    /// with no terminator within [`MAX_DESCRIPTORS`](crate::MAX_DESCRIPTORS).
+    NotIso,
+    /// The descriptors describe a volume larger than the device.
+    Truncated,
+    /// The volume, or one file on it, uses something this reader does not
+    /// implement: a logical block that is not 2048 bytes, a namespace the disc
+    /// does not carry, or a file recorded in a way the crate documentation
+    /// lists as refused.
+    Unsupported,
+    /// A directory's contents are not directory records: a record shorter than
+    /// its fixed part, one that crosses a sector, a name longer than its
+    /// record, an extent outside the volume, or a directory past
+    /// [`MAX_DIR_BYTES`](crate::MAX_DIR_BYTES).
+    CorruptDirectory,
+    /// A System Use entry is not one: a length that runs past its area, a
+    /// continuation area outside the volume, or a Rock Ridge entry too short
+    /// for its own fields.
+    CorruptExtension,
+    /// The El Torito boot catalog's validation entry does not check out, or an
+    /// entry runs past the catalog's sector.
+    CorruptCatalog,
+    NotFound,
+    /// A path component that is not the last named something other than a
+    /// directory.
+    NotADirectory,
+    /// The operation is defined only for files and the target is a directory.
+    IsADirectory,
+    /// `read_link` of something that is not a symbolic link.
+    NotASymlink,
+    /// A caller-supplied bound, or one of this crate's own, was reached. The
+    /// operation returned nothing rather than part of an answer.
+    LimitExceeded,
+}
+
+impl From<IoError> for Error {
+    fn from(_: IoError) -> Self {
+        Error::Io
+    }
+}
+
+impl Error {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Error::Io => "device I/O failed",
+            Error::NotIso => "not an ISO 9660 volume",
+            Error::Truncated => "volume larger than device",
+            Error::Unsupported => "uses a feature this reader does not implement",
+            Error::CorruptDirectory => "corrupt directory record",
+            Error::CorruptExtension => "corrupt system use entry",
+            Error::CorruptCatalog => "corrupt boot catalog",
+            Error::NotFound => "no such file or directory",
+            Error::NotADirectory => "not a directory",
+            Error::IsADirectory => "is a directory",
+            Error::NotASymlink => "not a symbolic link",
+            Error::LimitExceeded => "limit exceeded",
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
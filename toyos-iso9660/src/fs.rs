@@ -0,0 +1,602 @@
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::device::BlockAccess;
+use crate::dir;
+use crate::eltorito::{self, BootEntry};
+use crate::error::Error;
+use crate::record::{Record, FLAG_ASSOCIATED, FLAG_MULTI_EXTENT};
+use crate::rrip::{self, S_IFDIR, S_IFLNK, S_IFREG};
+use crate::time;
+use crate::volume::{Root, Volume, SECTOR_BYTES};
+
+/// The longest symbolic link target this crate will assemble: Linux's
+/// `PATH_MAX`, as in toyos-ext4.
+pub const MAX_LINK_BYTES: u64 = 4096;
+
+/// Symbolic links one path resolution will follow, as Linux's `MAXSYMLINKS`.
+pub const MAX_LINK_FOLLOWS: usize = 40;
+
+/// Directory nesting [`Iso9660::walk`] will descend. ISO 9660 itself stops at
+/// eight; Rock Ridge relocation and Joliet both go past it.
+const MAX_WALK_DEPTH: usize = 64;
+
+/// Components one resolution may consume, links' targets included, for
+/// toyos-ext4's reason.
+const MAX_RESOLVE_COMPONENTS: usize = 4096;
+
+/// Which of a disc's name trees paths are resolved in.
+///
+/// A disc can carry three, and they are not three views of one tree: Joliet
+/// has a directory hierarchy of its own, and Rock Ridge annotates the primary
+/// one — relocating what ISO 9660 nests too deep — with names, modes, times
+/// and links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    /// The primary tree, named by its `NM` entries, with POSIX modes and
+    /// symbolic links.
+    RockRidge,
+    /// The supplementary tree's UCS-2 names, with the `;1` version dropped.
+    Joliet,
+    /// The primary tree's own names as Linux shows them by default: lower
+    /// case, with the `;1` version and a trailing `.` dropped.
+    Primary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Directory,
+    Symlink,
+    /// A Rock Ridge device, FIFO or socket, or a file recorded in a way this
+    /// crate refuses to read (see the crate documentation).
+    Other,
+}
+
+/// A mounted ISO 9660 volume.
+pub struct Iso9660<D: BlockAccess> {
+    dev: D,
+    volume: Volume,
+    namespace: Namespace,
+    /// Bytes at the start of every System Use area that are not entries, from
+    /// the root's `SP` entry. `None` on a volume without SUSP.
+    susp_skip: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub kind: Kind,
+    pub len: u64,
+    /// The first logical block of the data: the nearest thing the format has
+    /// to an inode number.
+    pub extent: u32,
+    /// Permission bits, without the file type. Rock Ridge's own where the
+    /// volume has them; read-only for everyone otherwise, as Linux shows them.
+    pub permissions: u16,
+    pub modified_unix: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub kind: Kind,
+    pub len: u64,
+}
+
+/// An open file: where its bytes are, and how many.
+///
+/// Plain data with no tie to the volume, as `toyos_ext4::File` is, and for the
+/// same reason it cannot go stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct File {
+    extent: u32,
+    len: u64,
+}
+
+impl File {
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn extent(&self) -> u32 {
+        self.extent
+    }
+}
+
+/// One name in a tree, as its directory record and Rock Ridge entries describe
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    kind: Kind,
+    extent: u32,
+    len: u64,
+    permissions: u16,
+    modified_unix: u64,
+    link: Option<Vec<u8>>,
+}
+
+impl Node {
+    fn root(root: Root) -> Node {
+        Node {
+            kind: Kind::Directory,
+            extent: root.extent,
+            len: root.len as u64,
+            permissions: 0o555,
+            modified_unix: root.modified_unix,
+            link: None,
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: self.kind,
+            len: self.len,
+            extent: self.extent,
+            permissions: self.permissions,
+            modified_unix: self.modified_unix,
+        }
+    }
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = &str> {
+    path.split('/').filter(|c| !c.is_empty() && *c != ".")
+}
+
+/// `NAME.EXT;1` as Linux's `map=normal` shows it: `name.ext`. A directory
+/// name has no version, and a file with no extension keeps a trailing `.` on
+/// the disc that no one means.
+fn primary_name(raw: &[u8]) -> Vec<u8> {
+    let end = raw.iter().position(|&b| b == b';').unwrap_or(raw.len());
+    let mut name = raw[..end].to_ascii_lowercase();
+    if name.len() > 1 && name.last() == Some(&b'.') {
+        name.pop();
+    }
+    name
+}
+
+/// A Joliet name: UCS-2, big-endian, with the same `;1` version a primary
+/// name carries. `None` for an odd length or an unpaired surrogate — a name
+/// nobody could type.
+fn joliet_name(raw: &[u8]) -> Option<Vec<u8>> {
+    let (units, []) = raw.as_chunks::<2>() else { return None };
+    let units = units.iter().map(|u| u16::from_be_bytes(*u));
+    let mut name = String::new();
+    for c in char::decode_utf16(units) {
+        name.push(c.ok()?);
+    }
+    if let Some((stem, version)) = name.rsplit_once(';') {
+        if !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()) {
+            let len = stem.len();
+            name.truncate(len);
+        }
+    }
+    Some(name.into_bytes())
+}
+
+/// A name a path can reach: not empty, not `.` or `..`, no `/` and no NUL.
+fn addressable(name: &[u8]) -> bool {
+    !name.is_empty() && name != b"." && name != b".." && !name.iter().any(|&b| b == b'/' || b == 0)
+}
+
+/// What one record is in `namespace`, and what it is called there. `None` for
+/// a record that is not listed at all: a directory Rock Ridge relocated, which
+/// appears where its `CL` entry stands instead, or a name no path can reach.
+fn describe<D: BlockAccess>(
+    dev: &mut D,
+    volume: &Volume,
+    namespace: Namespace,
+    susp_skip: Option<usize>,
+    rec: &Record<'_>,
+) -> Result<Option<(Vec<u8>, Node)>, Error> {
+    let mut node = Node {
+        kind: if rec.is_dir() { Kind::Directory } else { Kind::File },
+        extent: rec.extent,
+        len: rec.len as u64,
+        permissions: if rec.is_dir() { 0o555 } else { 0o444 },
+        modified_unix: time::recorded(rec.recorded),
+        link: None,
+    };
+    if node.kind == Kind::File && (rec.interleaved || rec.flags & FLAG_MULTI_EXTENT != 0) {
+        node.kind = Kind::Other;
+    }
+
+    let name = match namespace {
+        Namespace::Primary => primary_name(rec.name),
+        Namespace::Joliet => match joliet_name(rec.name) {
+            Some(name) => name,
+            None => return Ok(None),
+        },
+        Namespace::RockRidge => {
+            let rr = rrip::parse(dev, volume, rec.system_use, susp_skip.unwrap_or(0))?;
+            if rr.relocated {
+                return Ok(None);
+            }
+            if let Some(mode) = rr.mode {
+                node.permissions = (mode & 0o7777) as u16;
+            }
+            if let Some(t) = rr.modified_unix {
+                node.modified_unix = t;
+            }
+            if let Some(child) = rr.child {
+                // The record here is a placeholder file; the directory is the
+                // one at `child`, and only its own `.` says how long it is.
+                let (len, _) = dir::dot(dev, volume, child)?;
+                node.kind = Kind::Directory;
+                node.extent = child;
+                node.len = len as u64;
+            } else if !rec.is_dir() && node.kind == Kind::File {
+                match (rr.file_type(), rr.link) {
+                    (Some(S_IFLNK), Some(link)) => {
+                        node.kind = Kind::Symlink;
+                        node.len = link.len() as u64;
+                        node.link = Some(link);
+                    }
+                    (None | Some(S_IFREG), _) if !rr.compressed => {}
+                    (Some(S_IFDIR), _) => return Err(Error::CorruptExtension),
+                    _ => node.kind = Kind::Other,
+                }
+            }
+            rr.name.unwrap_or_else(|| primary_name(rec.name))
+        }
+    };
+    if !addressable(&name) {
+        return Ok(None);
+    }
+    Ok(Some((name, node)))
+}
+
+impl<D: BlockAccess> Iso9660<D> {
+    /// Read and validate the descriptor set without taking ownership.
+    pub fn probe(dev: &mut D) -> Result<Volume, Error> {
+        Volume::read(dev)
+    }
+
+    /// Mount in the richest namespace the disc carries: Rock Ridge, else
+    /// Joliet, else the primary names.
+    pub fn mount(mut dev: D) -> Result<Iso9660<D>, Error> {
+        let volume = Volume::read(&mut dev)?;
+        let susp_skip = Self::rock_ridge(&mut dev, &volume)?;
+        let namespace = match (susp_skip, volume.has_joliet()) {
+            (Some(_), _) => Namespace::RockRidge,
+            (None, true) => Namespace::Joliet,
+            (None, false) => Namespace::Primary,
+        };
+        Ok(Iso9660 { dev, volume, namespace, susp_skip })
+    }
+
+    /// Mount in `namespace`, which is [`Error::Unsupported`] if the disc does
+    /// not carry it. Every disc carries [`Namespace::Primary`].
+    pub fn mount_namespace(mut dev: D, namespace: Namespace) -> Result<Iso9660<D>, Error> {
+        let volume = Volume::read(&mut dev)?;
+        let susp_skip = Self::rock_ridge(&mut dev, &volume)?;
+        let carried = match namespace {
+            Namespace::RockRidge => susp_skip.is_some(),
+            Namespace::Joliet => volume.has_joliet(),
+            Namespace::Primary => true,
+        };
+        if !carried {
+            return Err(Error::Unsupported);
+        }
+        Ok(Iso9660 { dev, volume, namespace, susp_skip })
+    }
+
+    /// Whether the primary tree has Rock Ridge, and the `SP` skip if so: an
+    /// `SP` entry opening the root's `.` record, and a `PX` among that
+    /// record's entries to say the protocol is carrying Rock Ridge and not
+    /// some other extension.
+    fn rock_ridge(dev: &mut D, volume: &Volume) -> Result<Option<usize>, Error> {
+        let (_, su) = dir::dot(dev, volume, volume.primary.extent)?;
+        let Some(skip) = rrip::sharing_protocol(&su) else { return Ok(None) };
+        let rr = rrip::parse(dev, volume, &su, skip)?;
+        Ok(rr.mode.map(|_| skip))
+    }
+
+    pub fn volume(&self) -> &Volume {
+        &self.volume
+    }
+
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    pub fn device(&mut self) -> &mut D {
+        &mut self.dev
+    }
+
+    pub fn into_device(self) -> D {
+        self.dev
+    }
+
+    fn root(&self) -> Node {
+        match (self.namespace, self.volume.joliet) {
+            (Namespace::Joliet, Some(root)) => Node::root(root),
+            _ => Node::root(self.volume.primary),
+        }
+    }
+
+    /// Call `f` with every listed entry of `dir`, until it answers `true`.
+    ///
+    /// Leaves out `.` and `..`, associated files, and every record after the
+    /// first of a multi-extent file, which lists once, as [`Kind::Other`].
+    fn entries(
+        &mut self,
+        dir: &Node,
+        mut f: impl FnMut(Vec<u8>, Node) -> Result<bool, Error>,
+    ) -> Result<(), Error> {
+        if dir.kind != Kind::Directory {
+            return Err(Error::NotADirectory);
+        }
+        let len = u32::try_from(dir.len).map_err(|_| Error::LimitExceeded)?;
+        let (volume, namespace, susp_skip) = (&self.volume, self.namespace, self.susp_skip);
+        // The name of a multi-extent file whose next record is another of
+        // its extents, if the last record said one follows.
+        let mut continuing: Option<Vec<u8>> = None;
+        dir::scan(&mut self.dev, volume, dir.extent, len, |dev, rec| {
+            if rec.is_dot_or_dotdot() {
+                return Ok(false);
+            }
+            let first = continuing.as_deref() != Some(rec.name);
+            continuing = (rec.flags & FLAG_MULTI_EXTENT != 0).then(|| rec.name.to_vec());
+            if !first || rec.flags & FLAG_ASSOCIATED != 0 {
+                return Ok(false);
+            }
+            match describe(dev, volume, namespace, susp_skip, rec)? {
+                Some((name, node)) => f(name, node),
+                None => Ok(false),
+            }
+        })
+    }
+
+    fn lookup(&mut self, dir: &Node, name: &[u8]) -> Result<Option<Node>, Error> {
+        let mut found = None;
+        self.entries(dir, |n, node| {
+            if n == name {
+                found = Some(node);
+                return Ok(true);
+            }
+            Ok(false)
+        })?;
+        Ok(found)
+    }
+
+    /// The node `path` names, following symbolic links in every component
+    /// and — when `follow_last` — in the last one too.
+    ///
+    /// toyos-ext4's resolution, step for step: a relative target against the
+    /// link's own directory, an absolute one against the volume's root, `..`
+    /// never above the root and never read off the disc.
+    fn resolve(&mut self, path: &str, follow_last: bool) -> Result<Node, Error> {
+        let mut stack: Vec<Node> = vec![self.root()];
+        let mut pending: Vec<Vec<u8>> = components(path).rev().map(|c| c.as_bytes().to_vec()).collect();
+        let mut follows = 0usize;
+        let mut consumed = 0usize;
+
+        while let Some(comp) = pending.pop() {
+            consumed += 1;
+            if consumed > MAX_RESOLVE_COMPONENTS {
+                return Err(Error::LimitExceeded);
+            }
+            if comp == b".." {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                continue;
+            }
+            let dir = stack.last().ok_or(Error::NotFound)?.clone();
+            let node = self.lookup(&dir, &comp)?.ok_or(Error::NotFound)?;
+            let last = pending.is_empty();
+            if node.kind == Kind::Symlink && (!last || follow_last) {
+                follows += 1;
+                if follows > MAX_LINK_FOLLOWS {
+                    return Err(Error::LimitExceeded);
+                }
+                let target = node.link.unwrap_or_default();
+                if target.first() == Some(&b'/') {
+                    stack.truncate(1);
+                }
+                for c in target.split(|&b| b == b'/').rev() {
+                    if !c.is_empty() && c != b"." {
+                        pending.push(c.to_vec());
+                    }
+                }
+                continue;
+            }
+            if !last && node.kind != Kind::Directory {
+                return Err(Error::NotADirectory);
+            }
+            stack.push(node);
+        }
+        stack.pop().ok_or(Error::NotFound)
+    }
+
+    pub fn metadata(&mut self, path: &str) -> Result<Metadata, Error> {
+        Ok(self.resolve(path, true)?.metadata())
+    }
+
+    /// Like [`metadata`](Self::metadata), but a symbolic link in the last
+    /// component is described rather than followed.
+    pub fn symlink_metadata(&mut self, path: &str) -> Result<Metadata, Error> {
+        Ok(self.resolve(path, false)?.metadata())
+    }
+
+    pub fn exists(&mut self, path: &str) -> Result<bool, Error> {
+        match self.resolve(path, false) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound) | Err(Error::NotADirectory) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `dir`'s entries as names and nodes, non-UTF-8 names left out, refusing
+    /// above `limit`.
+    fn children(&mut self, dir: &Node, limit: usize) -> Result<Vec<(String, Node)>, Error> {
+        let mut out = Vec::new();
+        let mut over = false;
+        self.entries(dir, |name, node| {
+            let Ok(name) = String::from_utf8(name) else { return Ok(false) };
+            if out.len() >= limit {
+                over = true;
+                return Ok(true);
+            }
+            out.push((name, node));
+            Ok(false)
+        })?;
+        if over {
+            return Err(Error::LimitExceeded);
+        }
+        Ok(out)
+    }
+
+    /// Every entry of one directory, `.` and `..` excluded, refusing above
+    /// `limit` rather than truncating. A name that is not UTF-8 is left out,
+    /// for toyos-ext4's reason.
+    pub fn read_dir(&mut self, path: &str, limit: usize) -> Result<Vec<DirEntry>, Error> {
+        let dir = self.resolve(path, true)?;
+        if dir.kind != Kind::Directory {
+            return Err(Error::NotADirectory);
+        }
+        Ok(self
+            .children(&dir, limit)?
+            .into_iter()
+            .map(|(name, node)| DirEntry { name, kind: node.kind, len: node.len })
+            .collect())
+    }
+
+    /// Every file and symbolic link in the tree, as a path relative to the
+    /// root paired with its size — the shape `toyos_ext4::Ext4::walk` returns,
+    /// with the same rules: directories only as prefixes, links listed and not
+    /// followed, [`Kind::Other`] left out.
+    ///
+    /// Iterative, with a visited set of directory extents and a depth bound,
+    /// because a crafted `CL` entry can name an ancestor. `limit` bounds files
+    /// and directories alike; either exceeding it abandons the listing.
+    pub fn walk(&mut self, limit: usize) -> Result<Vec<(String, u64)>, Error> {
+        let mut out = Vec::new();
+        let root = self.root();
+        let mut visited = BTreeSet::new();
+        visited.insert(root.extent);
+        let mut queue: Vec<(Node, String, usize)> = vec![(root, String::new(), 0)];
+
+        while let Some((dir, prefix, depth)) = queue.pop() {
+            for (name, node) in self.children(&dir, limit)? {
+                let mut path = String::with_capacity(prefix.len() + name.len() + 1);
+                path.push_str(&prefix);
+                path.push_str(&name);
+                match node.kind {
+                    Kind::Directory => {
+                        if depth + 1 > MAX_WALK_DEPTH || visited.len() >= limit {
+                            return Err(Error::LimitExceeded);
+                        }
+                        if visited.insert(node.extent) {
+                            path.push('/');
+                            queue.push((node, path, depth + 1));
+                        }
+                    }
+                    Kind::File | Kind::Symlink => {
+                        if out.len() >= limit {
+                            return Err(Error::LimitExceeded);
+                        }
+                        out.push((path, node.len));
+                    }
+                    Kind::Other => {}
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Open a regular file, following links. Directories are refused, and so
+    /// is anything [`Kind::Other`].
+    pub fn open(&mut self, path: &str) -> Result<File, Error> {
+        let node = self.resolve(path, true)?;
+        match node.kind {
+            Kind::File => {}
+            Kind::Directory => return Err(Error::IsADirectory),
+            Kind::Symlink | Kind::Other => return Err(Error::Unsupported),
+        }
+        // An empty file's extent is whatever the mastering tool put there —
+        // bsdtar counts down from the top of the address space — and nothing
+        // reads through it.
+        if node.len != 0 && !self.volume.contains(node.extent, node.len.div_ceil(SECTOR_BYTES)) {
+            return Err(Error::CorruptDirectory);
+        }
+        Ok(File { extent: node.extent, len: node.len })
+    }
+
+    /// Read from `offset`, stopping at the end of the file. Answers how many
+    /// bytes were read, which is short only there.
+    ///
+    /// One device read, whatever the length: a file is one contiguous extent.
+    pub fn read(&mut self, file: &File, offset: u64, buf: &mut [u8]) -> Result<usize, Error> {
+        if offset >= file.len {
+            return Ok(0);
+        }
+        let n = usize::try_from(file.len - offset).map_or(buf.len(), |left| left.min(buf.len()));
+        // `open` checked the extent against the volume, and a `File` is
+        // constructed nowhere else.
+        self.dev.read_at(file.extent as u64 * SECTOR_BYTES + offset, &mut buf[..n])?;
+        Ok(n)
+    }
+
+    /// The whole file, refusing one longer than `max` bytes before allocating.
+    pub fn read_to_vec(&mut self, path: &str, max: u64) -> Result<Vec<u8>, Error> {
+        let file = self.open(path)?;
+        if file.len() > max {
+            return Err(Error::LimitExceeded);
+        }
+        let mut out = vec![0u8; file.len() as usize];
+        self.read(&file, 0, &mut out)?;
+        Ok(out)
+    }
+
+    /// What the link at `path` points at, as its `SL` entries spell it.
+    /// [`Error::NotASymlink`] if it is not one — which, outside
+    /// [`Namespace::RockRidge`], nothing is.
+    pub fn read_link(&mut self, path: &str) -> Result<Vec<u8>, Error> {
+        match self.resolve(path, false)? {
+            Node { kind: Kind::Symlink, link: Some(link), .. } => Ok(link),
+            _ => Err(Error::NotASymlink),
+        }
+    }
+
+    /// What the link at `path` points at, rewritten as a path from the
+    /// volume's root — `None` if `path` is not a link. The rules, and the
+    /// lexical normalisation, are `toyos_ext4::Ext4::read_link_from_root`'s.
+    pub fn read_link_from_root(&mut self, path: &str) -> Result<Option<String>, Error> {
+        let target = match self.read_link(path) {
+            Ok(t) => t,
+            Err(Error::NotASymlink) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let target = String::from_utf8(target).map_err(|_| Error::Unsupported)?;
+
+        let mut parts: Vec<&str> = Vec::new();
+        if !target.starts_with('/') {
+            parts.extend(components(path));
+            parts.pop();
+        }
+        for c in components(&target) {
+            if c == ".." {
+                parts.pop();
+            } else {
+                parts.push(c);
+            }
+        }
+        Ok(Some(parts.join("/")))
+    }
+
+    /// The El Torito boot catalog's entries, the initial one first. Empty on a
+    /// disc with no boot record.
+    pub fn boot_entries(&mut self) -> Result<Vec<BootEntry>, Error> {
+        match self.volume.boot_catalog {
+            Some(block) => eltorito::read(&mut self.dev, &self.volume, block),
+            None => Ok(Vec::new()),
+        }
+    }
+}
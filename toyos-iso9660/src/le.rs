@@ -0,0 +1,21 @@
+//! Little-endian fields out of a byte slice, without a path that panics.
+//!
+//! Only the little-endian half of ISO 9660's both-endian fields is ever read
+//! (see the crate documentation). An offset past the end reads as zero, for
+//! toyos-ext4's reason: every caller passes a constant of the format into a
+//! slice already checked to be that long, and being wrong about that should be
+//! a wrong number the structural checks refuse rather than a panic.
+
+pub fn le_u16(buf: &[u8], at: usize) -> u16 {
+    match buf.get(at..at.wrapping_add(2)) {
+        Some(&[a, b]) => u16::from_le_bytes([a, b]),
+        _ => 0,
+    }
+}
+
+pub fn le_u32(buf: &[u8], at: usize) -> u32 {
+    match buf.get(at..at.wrapping_add(4)) {
+        Some(&[a, b, c, d]) => u32::from_le_bytes([a, b, c, d]),
+        _ => 0,
+    }
+}
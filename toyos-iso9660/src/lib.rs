@@ -0,0 +1,89 @@
+//! ISO 9660, read-only, with Joliet and Rock Ridge names and the El Torito
+//! boot catalog, over a byte-addressed volume.
+//!
+//! What QEMU and every virtualisation host present install media as, and what
+//! a ToyOS release ships as: a hybrid image that is an ISO 9660 volume to a
+//! CD-ROM drive and a GPT disk to a USB stick. This reads the volume
+//! descriptors, directory records in all three namespaces a disc can carry,
+//! the System Use Sharing Protocol entries Rock Ridge is written in, and the
+//! boot catalog firmware starts from.
+//!
+//! # A volume is untrusted input
+//!
+//! As in `toyos-fat32` and `toyos-ext4`, and for the same reason: a disc is
+//! something anybody could have mastered, so no path that touches its bytes may
+//! panic. Every such failure is an [`Error`].
+//!
+//! The hazards particular to this format, and what closes each:
+//!
+//! - **Every number is written twice.** Most fields are "both-endian", a
+//!   little-endian copy beside a big-endian one, and a crafted disc can make
+//!   them disagree. Only the little-endian half is read, as Linux reads it;
+//!   the other half is never consulted, so there is no second opinion to be
+//!   confused by.
+//! - **Continuation areas chain.** A Rock Ridge entry set can continue in
+//!   another sector, which can continue in another. The chain is followed at
+//!   most [`MAX_CONTINUATIONS`] times per record, and each area must lie in
+//!   one sector of the volume, so a loop is [`Error::LimitExceeded`].
+//! - **Relocation points anywhere.** A Rock Ridge `CL` entry names the
+//!   directory that really lives at this place in the tree; a crafted one
+//!   names an ancestor. [`Iso9660::walk`] keeps a visited set of directory
+//!   extents and a depth bound, and path resolution never reads an on-disk
+//!   `..`.
+//! - **Extents are fields.** A directory or file extent outside the volume is
+//!   refused when it is met, before anything reads through it, and a directory
+//!   past [`MAX_DIR_BYTES`] is [`Error::LimitExceeded`].
+//! - **Descriptors are a list with no length.** The set is read until its
+//!   terminator and no further than [`MAX_DESCRIPTORS`] sectors, so a disc
+//!   without a terminator is [`Error::NotIso`] rather than a read of the whole
+//!   volume.
+//!
+//! # What this crate does not do
+//!
+//! - **No writes.** A disc has nowhere to put them; [`BlockAccess`] has no
+//!   write method for the same reason toyos-ext4's has none.
+//! - **No multi-extent files, interleaving or zisofs.** A file split over
+//!   several records, recorded in interleaved units, or compressed with a Rock
+//!   Ridge `ZF` entry lists as [`Kind::Other`], and opening it is
+//!   [`Error::Unsupported`]. None of them is something a mastering tool writes
+//!   unless asked.
+//! - **No multisession.** The descriptors read are the ones at sector 16, which
+//!   is the first session's; a later session's are the host's business to
+//!   locate, and no ToyOS image has one.
+//! - **No UDF.** A UDF-only disc is [`Error::NotIso`]. Bridge discs carry an
+//!   ISO 9660 tree too, and it is that tree this reads.
+//! - **No caching.** As in the other two readers: the kernel's page cache sits
+//!   under [`BlockAccess`].
+//!
+//! # Shape
+//!
+//! [`Iso9660`] owns a [`BlockAccess`], the parsed [`Volume`] descriptors and
+//! the [`Namespace`] it was mounted with — by default the richest the disc has:
+//! Rock Ridge, else Joliet, else the bare ISO 9660 names. Every path-based call
+//! resolves from that namespace's root. [`Iso9660::open`] hands back a
+//! [`File`], which is an extent and a length, so repeated reads of one file
+//! resolve nothing.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+mod device;
+mod dir;
+mod eltorito;
+mod error;
+mod fs;
+mod le;
+mod record;
+mod rrip;
+mod time;
+mod volume;
+
+pub use device::{BlockAccess, IoError};
+pub use dir::{MAX_DIR_BYTES, MAX_NAME_BYTES};
+pub use eltorito::{BootEntry, Media, PLATFORM_EFI, PLATFORM_X86};
+pub use error::Error;
+pub use fs::{DirEntry, File, Iso9660, Kind, Metadata, Namespace, MAX_LINK_BYTES, MAX_LINK_FOLLOWS};
+pub use rrip::MAX_CONTINUATIONS;
+pub use volume::{Volume, MAX_DESCRIPTORS, SECTOR_BYTES};
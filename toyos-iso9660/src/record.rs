@@ -0,0 +1,68 @@
+//! One directory record, as it sits in a directory's sector.
+
+use crate::error::Error;
+use crate::le::le_u32;
+
+/// The fixed part of a record, before its name.
+pub const FIXED_BYTES: usize = 33;
+
+pub const FLAG_DIRECTORY: u8 = 0x02;
+/// An "associated file" — a resource fork, in practice. Never listed.
+pub const FLAG_ASSOCIATED: u8 = 0x04;
+/// More records of the same name follow, each another extent of this file.
+pub const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+/// A directory record, borrowed from the sector it was read out of.
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+    /// First logical block of the data, past any extended attribute record.
+    pub extent: u32,
+    pub len: u32,
+    pub flags: u8,
+    pub recorded: &'a [u8],
+    /// Nonzero in either is an interleaved file.
+    pub interleaved: bool,
+    pub name: &'a [u8],
+    /// The System Use area: whatever follows the name, where Rock Ridge lives.
+    pub system_use: &'a [u8],
+}
+
+impl Record<'_> {
+    pub fn is_dir(&self) -> bool {
+        self.flags & FLAG_DIRECTORY != 0
+    }
+
+    /// `.` and `..`, which the format spells as the one-byte names 0 and 1.
+    pub fn is_dot_or_dotdot(&self) -> bool {
+        matches!(self.name, [0] | [1])
+    }
+}
+
+/// Parse the record at the start of `buf`, which runs to the end of the
+/// record's sector: a record longer than that would cross into the next one,
+/// and is refused.
+pub fn parse(buf: &[u8]) -> Result<Record<'_>, Error> {
+    let len = *buf.first().ok_or(Error::CorruptDirectory)? as usize;
+    if len < FIXED_BYTES + 1 || len > buf.len() {
+        return Err(Error::CorruptDirectory);
+    }
+    let buf = &buf[..len];
+    let name_len = buf[32] as usize;
+    let name_end = FIXED_BYTES + name_len;
+    if name_len == 0 || name_end > len {
+        return Err(Error::CorruptDirectory);
+    }
+    // A pad byte keeps the System Use area on an even offset when the name's
+    // length is even; a record that ends on the name has no area at all.
+    let su_start = (name_end + (name_len + 1) % 2).min(len);
+    let extent = le_u32(buf, 2).checked_add(buf[1] as u32).ok_or(Error::CorruptDirectory)?;
+    Ok(Record {
+        extent,
+        len: le_u32(buf, 10),
+        flags: buf[25],
+        recorded: &buf[18..25],
+        interleaved: buf[26] != 0 || buf[27] != 0,
+        name: &buf[FIXED_BYTES..name_end],
+        system_use: &buf[su_start..],
+    })
+}
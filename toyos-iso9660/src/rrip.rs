@@ -0,0 +1,219 @@
+//! Rock Ridge, as System Use Sharing Protocol entries.
+//!
+//! Each entry is a two-letter signature, a length and a version, then its
+//! fields. The set for one record starts in the record's System Use area (past
+//! `skip` bytes the root's `SP` entry announces) and may continue, through a
+//! `CE` entry, in an area of its own elsewhere on the volume.
+
+use alloc::vec::Vec;
+
+use crate::device::BlockAccess;
+use crate::dir::MAX_NAME_BYTES;
+use crate::error::Error;
+use crate::fs::MAX_LINK_BYTES;
+use crate::le::le_u32;
+use crate::time;
+use crate::volume::{Volume, SECTOR_BYTES};
+
+/// Continuation areas followed for one record. Any Rock Ridge writer needs one
+/// at most, for a long name or link; a chain longer than this is a loop.
+pub const MAX_CONTINUATIONS: usize = 16;
+
+const S_IFMT: u32 = 0o170000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFLNK: u32 = 0o120000;
+
+const NM_CONTINUE: u8 = 0x01;
+const NM_CURRENT: u8 = 0x02;
+const NM_PARENT: u8 = 0x04;
+
+const SL_CONTINUE: u8 = 0x01;
+const SL_CURRENT: u8 = 0x02;
+const SL_PARENT: u8 = 0x04;
+const SL_ROOT: u8 = 0x08;
+
+const TF_CREATION: u8 = 0x01;
+const TF_MODIFY: u8 = 0x02;
+const TF_LONG_FORM: u8 = 0x80;
+
+/// What one record's entries said. Every field is optional: a Rock Ridge
+/// volume must write `PX` for every record, but a reader that insisted would
+/// refuse discs Linux mounts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RockRidge {
+    pub name: Option<Vec<u8>>,
+    pub mode: Option<u32>,
+    pub link: Option<Vec<u8>>,
+    pub modified_unix: Option<u64>,
+    /// `RE`: this directory was moved here from deeper in the tree, and is
+    /// listed where its `CL` entry stands instead.
+    pub relocated: bool,
+    /// `CL`: the directory that belongs at this place in the tree lives at
+    /// this extent.
+    pub child: Option<u32>,
+    /// `ZF`: the file's extent is zisofs-compressed.
+    pub compressed: bool,
+}
+
+impl RockRidge {
+    pub fn file_type(&self) -> Option<u32> {
+        self.mode.map(|m| m & S_IFMT)
+    }
+}
+
+/// `SP` at the start of the root's `.` System Use area is how a volume says it
+/// uses SUSP at all, and how many bytes every area starts with that are not
+/// entries. `None` if it is not there.
+pub fn sharing_protocol(area: &[u8]) -> Option<usize> {
+    match area {
+        [b'S', b'P', 7, 1, 0xBE, 0xEF, skip, ..] => Some(*skip as usize),
+        _ => None,
+    }
+}
+
+/// Parse one record's entries, following its continuation areas.
+pub fn parse<D: BlockAccess>(dev: &mut D, volume: &Volume, area: &[u8], skip: usize) -> Result<RockRidge, Error> {
+    let mut out = RockRidge::default();
+    let mut state = State::default();
+    let mut continuation = area.get(skip..).map_or(Vec::new(), |a| a.to_vec());
+    let mut follows = 0usize;
+
+    while let Some((block, offset, len)) = entries(&continuation, &mut out, &mut state)? {
+        follows += 1;
+        if follows > MAX_CONTINUATIONS {
+            return Err(Error::LimitExceeded);
+        }
+        // One sector, wholly inside the volume, as Linux requires of it.
+        if !volume.contains(block, 1) || offset.checked_add(len).is_none_or(|end| end as u64 > SECTOR_BYTES) {
+            return Err(Error::CorruptExtension);
+        }
+        continuation.resize(len as usize, 0);
+        dev.read_at(block as u64 * SECTOR_BYTES + offset as u64, &mut continuation)?;
+    }
+
+    if state.has_name {
+        out.name = Some(state.name);
+    }
+    if state.has_link {
+        out.link = Some(state.link);
+    }
+    Ok(out)
+}
+
+/// What is being assembled across entries, and across areas: a name can be
+/// split over several `NM` entries and a link over several `SL` entries, each
+/// marked `CONTINUE` but the last.
+#[derive(Default)]
+struct State {
+    name: Vec<u8>,
+    has_name: bool,
+    /// The last `NM` had no `CONTINUE`, so any later one is not part of it.
+    /// The format allows no second name; the first is what Linux keeps.
+    name_done: bool,
+    link: Vec<u8>,
+    has_link: bool,
+    link_done: bool,
+    /// The last component ended with `CONTINUE`, so the next is joined to it
+    /// without a `/`.
+    joined: bool,
+    /// Nothing has been written yet, or only the root: the next component
+    /// needs no separator.
+    at_start: bool,
+}
+
+/// Apply the entries in one area. Answers the continuation area a `CE` entry
+/// named, if one did.
+fn entries(area: &[u8], out: &mut RockRidge, st: &mut State) -> Result<Option<(u32, u32, u32)>, Error> {
+    let mut next = None;
+    let mut at = 0usize;
+    // Fewer than four bytes left is padding, not an entry.
+    while at + 4 <= area.len() {
+        let len = area[at + 2] as usize;
+        if len < 4 || at + len > area.len() {
+            return Err(Error::CorruptExtension);
+        }
+        let e = &area[at..at + len];
+        at += len;
+        match (&e[..2], e.len()) {
+            (b"ST", _) => break,
+            (b"CE", 28..) => next = Some((le_u32(e, 4), le_u32(e, 12), le_u32(e, 20))),
+            (b"PX", 36..) => out.mode = Some(le_u32(e, 4)),
+            (b"NM", 5..) => {
+                let flags = e[4];
+                if st.name_done || flags & (NM_CURRENT | NM_PARENT) != 0 {
+                    continue;
+                }
+                if st.name.len() + e.len() - 5 > MAX_NAME_BYTES {
+                    return Err(Error::LimitExceeded);
+                }
+                st.has_name = true;
+                st.name.extend_from_slice(&e[5..]);
+                st.name_done = flags & NM_CONTINUE == 0;
+            }
+            (b"SL", 5..) => {
+                if st.link_done {
+                    continue;
+                }
+                if !st.has_link {
+                    st.has_link = true;
+                    st.at_start = true;
+                }
+                components(&e[5..], st)?;
+                st.link_done = e[4] & SL_CONTINUE == 0;
+            }
+            (b"TF", 5..) => {
+                let flags = e[4];
+                if flags & TF_MODIFY != 0 {
+                    let width = if flags & TF_LONG_FORM != 0 { 17 } else { 7 };
+                    let index = usize::from(flags & TF_CREATION != 0);
+                    let stamp = e.get(5 + index * width..5 + (index + 1) * width).ok_or(Error::CorruptExtension)?;
+                    out.modified_unix =
+                        Some(if width == 17 { time::long_form(stamp) } else { time::recorded(stamp) });
+                }
+            }
+            (b"RE", _) => out.relocated = true,
+            (b"CL", 12..) => out.child = Some(le_u32(e, 4)),
+            (b"ZF", _) => out.compressed = true,
+            // One of the above, too short for its own fields.
+            (b"CE" | b"PX" | b"NM" | b"SL" | b"TF" | b"CL", _) => return Err(Error::CorruptExtension),
+            // SP outside the root, ER, ES, PN, PL and anything unknown.
+            _ => {}
+        }
+    }
+    Ok(next)
+}
+
+/// An `SL` entry's component records, appended to the link being built.
+fn components(mut rest: &[u8], st: &mut State) -> Result<(), Error> {
+    while !rest.is_empty() {
+        let [flags, len, ..] = *rest else { return Err(Error::CorruptExtension) };
+        let end = 2 + len as usize;
+        let body = rest.get(2..end).ok_or(Error::CorruptExtension)?;
+        rest = &rest[end..];
+
+        if flags & SL_ROOT != 0 {
+            st.link.clear();
+            st.link.push(b'/');
+            st.at_start = true;
+            st.joined = false;
+            continue;
+        }
+        if !st.at_start && !st.joined {
+            st.link.push(b'/');
+        }
+        if flags & SL_CURRENT != 0 {
+            st.link.push(b'.');
+        } else if flags & SL_PARENT != 0 {
+            st.link.extend_from_slice(b"..");
+        } else {
+            st.link.extend_from_slice(body);
+        }
+        st.at_start = false;
+        st.joined = flags & SL_CONTINUE != 0;
+        if st.link.len() as u64 > MAX_LINK_BYTES {
+            return Err(Error::LimitExceeded);
+        }
+    }
+    Ok(())
+}
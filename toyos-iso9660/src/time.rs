@@ -0,0 +1,66 @@
+use toyos_wallclock::Civil;
+
+/// Seconds in one unit of a recording date's zone offset.
+const OFFSET_UNIT: i64 = 15 * 60;
+
+/// A directory record's recording date: seven bytes of year since 1900,
+/// month, day, hour, minute, second and a signed offset from GMT in quarter
+/// hours.
+///
+/// Total, like every date this tree decodes: the fields go through
+/// [`Civil::to_unix_secs`], which has an answer for every combination a byte
+/// can hold, and the offset is applied saturating. An all-zero date — "not
+/// recorded" — and any instant before 1970 read as the epoch.
+pub fn recorded(d: &[u8]) -> u64 {
+    let [year, month, day, hour, min, sec, offset] = match d.get(..7) {
+        Some(&[a, b, c, e, f, g, h]) => [a, b, c, e, f, g, h],
+        _ => return 0,
+    };
+    if year < 70 {
+        return 0;
+    }
+    let civil = Civil {
+        year: 1900 + year as u64,
+        month: month as u64,
+        day: day as u64,
+        hour: hour as u64,
+        min: min as u64,
+        sec: sec as u64,
+    };
+    in_gmt(civil, offset as i8)
+}
+
+/// The seventeen-byte form the volume descriptors and a Rock Ridge `TF` entry
+/// with `LONG_FORM` set use: sixteen ASCII digits `YYYYMMDDHHMMSScc` and the
+/// same quarter-hour offset. Hundredths are dropped; a non-digit reads as the
+/// epoch, which is also what the all-`'0'` "not specified" date is.
+pub fn long_form(d: &[u8]) -> u64 {
+    let (Some(digits), Some(&offset)) = (d.get(..16), d.get(16)) else { return 0 };
+    let mut fields = [0u64; 6];
+    let widths = [4usize, 2, 2, 2, 2, 2];
+    let mut at = 0;
+    for (field, width) in fields.iter_mut().zip(widths) {
+        for &c in &digits[at..at + width] {
+            if !c.is_ascii_digit() {
+                return 0;
+            }
+            *field = *field * 10 + (c - b'0') as u64;
+        }
+        at += width;
+    }
+    let [year, month, day, hour, min, sec] = fields;
+    if year < 1970 {
+        return 0;
+    }
+    in_gmt(Civil { year, month, day, hour, min, sec }, offset as i8)
+}
+
+fn in_gmt(civil: Civil, offset: i8) -> u64 {
+    let local = civil.to_unix_secs();
+    let shift = offset as i64 * OFFSET_UNIT;
+    if shift >= 0 {
+        local.saturating_sub(shift as u64)
+    } else {
+        local.saturating_add(shift.unsigned_abs())
+    }
+}
@@ -0,0 +1,148 @@
+//! The volume descriptor set: where the trees start, and the boot record.
+
+use crate::device::BlockAccess;
+use crate::error::Error;
+use crate::le::{le_u16, le_u32};
+use crate::record;
+use crate::time;
+
+/// ISO 9660's logical block, and the only one this reader accepts. The format
+/// allows 512 and 1024 too; nothing has written either in thirty years.
+pub const SECTOR_BYTES: u64 = 2048;
+
+/// Where the descriptor set starts. Everything before it is the system area,
+/// which is the hybrid image's MBR and GPT.
+pub const FIRST_DESCRIPTOR: u64 = 16;
+
+/// Descriptor sectors read before a set without a terminator is refused. A
+/// disc has one primary, a supplementary or two, a boot record and a
+/// terminator.
+pub const MAX_DESCRIPTORS: u64 = 64;
+
+const TYPE_BOOT_RECORD: u8 = 0;
+const TYPE_PRIMARY: u8 = 1;
+const TYPE_SUPPLEMENTARY: u8 = 2;
+const TYPE_TERMINATOR: u8 = 255;
+
+const EL_TORITO: &[u8] = b"EL TORITO SPECIFICATION";
+
+/// Where one namespace's tree starts: the root directory's extent and length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Root {
+    pub extent: u32,
+    pub len: u32,
+    pub modified_unix: u64,
+}
+
+/// The descriptor set, as far as this reader needs it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    /// Logical blocks in the volume, from the primary descriptor.
+    pub blocks: u32,
+    /// The primary descriptor's volume identifier, space-padded.
+    pub label: [u8; 32],
+    /// The primary tree's root, which Rock Ridge annotates.
+    pub(crate) primary: Root,
+    /// The Joliet tree's root, when a supplementary descriptor carries one of
+    /// the three UCS-2 escape sequences.
+    pub(crate) joliet: Option<Root>,
+    /// The El Torito boot catalog's logical block, when there is a boot record.
+    pub boot_catalog: Option<u32>,
+}
+
+impl Volume {
+    /// Bytes in the volume.
+    pub fn bytes(&self) -> u64 {
+        self.blocks as u64 * SECTOR_BYTES
+    }
+
+    /// The label with its padding removed, if it is text.
+    pub fn label_str(&self) -> Option<&str> {
+        let end = self.label.iter().rposition(|&b| b != b' ' && b != 0).map_or(0, |i| i + 1);
+        core::str::from_utf8(&self.label[..end]).ok()
+    }
+
+    pub fn has_joliet(&self) -> bool {
+        self.joliet.is_some()
+    }
+
+    /// Whether `sectors` sectors from `extent` lie in the volume.
+    pub(crate) fn contains(&self, extent: u32, sectors: u64) -> bool {
+        (extent as u64).checked_add(sectors).is_some_and(|end| end <= self.blocks as u64)
+    }
+
+    /// Read the descriptor set from sector 16 to its terminator.
+    pub fn read<D: BlockAccess>(dev: &mut D) -> Result<Volume, Error> {
+        let mut buf = [0u8; SECTOR_BYTES as usize];
+        let mut primary: Option<(u32, [u8; 32], Root)> = None;
+        let mut joliet = None;
+        let mut boot_catalog = None;
+        let mut terminated = false;
+
+        for sector in FIRST_DESCRIPTOR..FIRST_DESCRIPTOR + MAX_DESCRIPTORS {
+            let offset = sector * SECTOR_BYTES;
+            if offset + SECTOR_BYTES > dev.capacity() {
+                break;
+            }
+            dev.read_at(offset, &mut buf)?;
+            if &buf[1..6] != b"CD001" {
+                break;
+            }
+            match buf[0] {
+                TYPE_TERMINATOR => {
+                    terminated = true;
+                    break;
+                }
+                TYPE_PRIMARY if primary.is_none() => {
+                    if le_u16(&buf, 128) as u64 != SECTOR_BYTES {
+                        return Err(Error::Unsupported);
+                    }
+                    let mut label = [0u8; 32];
+                    label.copy_from_slice(&buf[40..72]);
+                    primary = Some((le_u32(&buf, 80), label, root(&buf)?));
+                }
+                TYPE_SUPPLEMENTARY if joliet.is_none() && is_joliet(&buf[88..120]) => {
+                    if le_u16(&buf, 128) as u64 != SECTOR_BYTES {
+                        return Err(Error::Unsupported);
+                    }
+                    joliet = Some(root(&buf)?);
+                }
+                TYPE_BOOT_RECORD if buf[7..7 + EL_TORITO.len()] == *EL_TORITO => {
+                    boot_catalog = Some(le_u32(&buf, 71));
+                }
+                _ => {}
+            }
+        }
+
+        let (blocks, label, primary) = match primary {
+            Some(p) if terminated => p,
+            _ => return Err(Error::NotIso),
+        };
+        let volume = Volume { blocks, label, primary, joliet, boot_catalog };
+        if volume.bytes() > dev.capacity() {
+            return Err(Error::Truncated);
+        }
+        for root in [Some(volume.primary), volume.joliet].into_iter().flatten() {
+            if !volume.contains(root.extent, (root.len as u64).div_ceil(SECTOR_BYTES)) {
+                return Err(Error::CorruptDirectory);
+            }
+        }
+        Ok(volume)
+    }
+}
+
+/// The root directory record every volume descriptor carries at 156, whose
+/// 34 bytes are the whole of what says where its tree is.
+fn root(descriptor: &[u8]) -> Result<Root, Error> {
+    let rec = record::parse(&descriptor[156..190])?;
+    if !rec.is_dir() {
+        return Err(Error::CorruptDirectory);
+    }
+    Ok(Root { extent: rec.extent, len: rec.len, modified_unix: time::recorded(rec.recorded) })
+}
+
+/// A supplementary descriptor is Joliet's when its escape sequences name one
+/// of the three UCS-2 levels: `%/@`, `%/C` or `%/E`.
+fn is_joliet(escapes: &[u8]) -> bool {
+    matches!(escapes, [0x25, 0x2F, 0x40 | 0x43 | 0x45, ..])
+}
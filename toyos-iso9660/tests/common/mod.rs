@@ -0,0 +1,189 @@
+//! Host-side scaffolding: the committed image, a device that carries it, and
+//! libarchive's account of what is in it.
+//!
+//! The image is made by `fixtures/make.sh` with bsdtar; the three
+//! `expected-*.txt` are bsdtar's own listings of it, one per namespace. Nothing
+//! here parses the volume the way the crate does except the few raw offsets the
+//! hostile tests aim at, which are read straight out of the on-disk format and
+//! never through the crate.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use toyos_iso9660::{BlockAccess, IoError, Namespace};
+
+pub const IMAGE: &str = "tree.iso";
+
+pub const SECTOR: u64 = 2048;
+
+/// Every file and directory in the tree carries this mtime.
+pub const FIXTURE_TIME: u64 = 1_760_000_000;
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+pub fn image() -> Vec<u8> {
+    std::fs::read(fixtures().join(IMAGE)).unwrap_or_else(|e| panic!("read fixture {IMAGE}: {e}"))
+}
+
+/// A volume in memory, counting what the crate asks of it.
+#[derive(Clone)]
+pub struct MemDevice {
+    pub bytes: Vec<u8>,
+    /// Reads served so far.
+    pub reads: u32,
+    /// Fail every read after this many.
+    pub fail_after: Option<u32>,
+}
+
+impl MemDevice {
+    pub fn new(bytes: Vec<u8>) -> MemDevice {
+        MemDevice { bytes, reads: 0, fail_after: None }
+    }
+
+    pub fn fixture() -> MemDevice {
+        MemDevice::new(image())
+    }
+
+    pub fn poke(&mut self, offset: u64, bytes: &[u8]) {
+        let at = offset as usize;
+        self.bytes[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    pub fn peek(&self, offset: u64, len: usize) -> &[u8] {
+        &self.bytes[offset as usize..offset as usize + len]
+    }
+
+    pub fn u32_at(&self, offset: u64) -> u32 {
+        u32::from_le_bytes(self.peek(offset, 4).try_into().unwrap())
+    }
+}
+
+impl BlockAccess for MemDevice {
+    fn capacity(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), IoError> {
+        if self.fail_after.is_some_and(|n| self.reads >= n) {
+            return Err(IoError);
+        }
+        self.reads += 1;
+        let end = offset.checked_add(buf.len() as u64).ok_or(IoError)?;
+        if end > self.bytes.len() as u64 {
+            return Err(IoError);
+        }
+        buf.copy_from_slice(&self.bytes[offset as usize..end as usize]);
+        Ok(())
+    }
+}
+
+/// One line of an `expected-*.txt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    File { path: String, size: u64 },
+    Dir { path: String },
+    Link { path: String, target: String },
+}
+
+impl Expected {
+    pub fn path(&self) -> &str {
+        match self {
+            Expected::File { path, .. } | Expected::Dir { path } | Expected::Link { path, .. } => path,
+        }
+    }
+}
+
+/// bsdtar's listing of `namespace`. The primary names are as the disc has
+/// them, upper case, and are lowered here the way Linux shows them.
+pub fn expected(namespace: Namespace) -> Vec<Expected> {
+    let file = match namespace {
+        Namespace::RockRidge => "expected-rr.txt",
+        Namespace::Joliet => "expected-joliet.txt",
+        Namespace::Primary => "expected-primary.txt",
+    };
+    let text = std::fs::read_to_string(fixtures().join(file)).expect(file);
+    let fold = |p: &str| if namespace == Namespace::Primary { p.to_ascii_lowercase() } else { p.to_string() };
+    text.lines()
+        .map(|line| {
+            let (kind, rest) = line.split_once(' ').expect("kind");
+            match kind {
+                "d" => Expected::Dir { path: fold(rest) },
+                "f" => {
+                    let (size, path) = rest.split_once(' ').expect("size");
+                    Expected::File { path: fold(path), size: size.parse().expect("size") }
+                }
+                "l" => {
+                    let (target, path) = rest.split_once(' ').expect("target");
+                    Expected::Link { path: path.into(), target: target.into() }
+                }
+                other => panic!("bad line kind {other:?}"),
+            }
+        })
+        .collect()
+}
+
+/// What `make.sh` wrote into each file, regenerated from the same formulas.
+/// `None` for the boot catalog, which bsdtar wrote, and for the primary
+/// tree's `many/` names, which 8.3 truncation shuffled.
+pub fn content(path: &str) -> Option<Vec<u8>> {
+    Some(match path {
+        "hello.txt" => b"hello from a compact disc\n".to_vec(),
+        "empty" => Vec::new(),
+        "sub/deeper/pattern.bin" => (0..300 * 1024).map(|i: u32| ((i * 131 + 7) % 251) as u8).collect(),
+        "sub/one-sector.bin" => (0..2048).map(|i: u32| ((i * 7) % 256) as u8).collect(),
+        "a/b/c/d/e/f/g/h/i/deep.txt" => b"nine levels down\n".to_vec(),
+        "boot.img" => vec![0x90; 2048],
+        p if p.starts_with("a-name-that-is-far-too-long") => b"long\n".to_vec(),
+        p => {
+            let n: u32 = p.strip_prefix("many/entry-")?.strip_suffix(".txt")?.parse().ok()?;
+            format!("entry {n}\n").into_bytes()
+        }
+    })
+}
+
+// ---------------------------------------------------------------- raw layout
+
+/// Byte offset of the descriptor in sector `sector`.
+pub fn descriptor(sector: u64) -> u64 {
+    sector * SECTOR
+}
+
+/// The primary tree's root directory extent, off the raw descriptor.
+pub fn primary_root(dev: &MemDevice) -> u64 {
+    dev.u32_at(descriptor(16) + 156 + 2) as u64
+}
+
+/// The byte offset of the record named `name` (as the disc spells it) in the
+/// first sector of the directory at `extent`.
+pub fn record(dev: &MemDevice, extent: u64, name: &[u8]) -> u64 {
+    let start = extent * SECTOR;
+    let mut at = start;
+    while at < start + SECTOR {
+        let len = dev.peek(at, 1)[0] as u64;
+        assert!(len != 0, "{:?} is not in the first sector of {extent}", String::from_utf8_lossy(name));
+        let name_len = dev.peek(at + 32, 1)[0] as usize;
+        if dev.peek(at + 33, name_len) == name {
+            return at;
+        }
+        at += len;
+    }
+    panic!("{:?} is not in the first sector of {extent}", String::from_utf8_lossy(name));
+}
+
+/// The byte offset, inside the record at `rec`, of the System Use entry with
+/// `signature`, searching the record's own area only.
+pub fn su_entry(dev: &MemDevice, rec: u64, signature: &[u8; 2]) -> u64 {
+    let len = dev.peek(rec, 1)[0] as u64;
+    let name_len = dev.peek(rec + 32, 1)[0] as u64;
+    let mut at = rec + 33 + name_len + (name_len + 1) % 2;
+    while at + 4 <= rec + len {
+        if dev.peek(at, 2) == signature {
+            return at;
+        }
+        at += dev.peek(at + 2, 1)[0] as u64;
+    }
+    panic!("no {} entry in the record at {rec}", String::from_utf8_lossy(signature));
+}
@@ -0,0 +1,207 @@
+//! libarchive masters the disc, this crate reads it.
+//!
+//! Every assertion has ground truth outside this repository: the image came
+//! from `bsdtar`, the listings it is compared against are bsdtar's own reader's
+//! in each namespace, and the contents are regenerated from the formulas
+//! `make.sh` wrote them with.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{content, expected, Expected, MemDevice, FIXTURE_TIME};
+use toyos_iso9660::{Error, Iso9660, Kind, Media, Namespace, PLATFORM_X86};
+
+const NAMESPACES: [Namespace; 3] = [Namespace::RockRidge, Namespace::Joliet, Namespace::Primary];
+
+fn mount(namespace: Namespace) -> Iso9660<MemDevice> {
+    Iso9660::mount_namespace(MemDevice::fixture(), namespace).unwrap_or_else(|e| panic!("{namespace:?}: {e}"))
+}
+
+#[test]
+fn mount_picks_rock_ridge_and_reads_the_label() {
+    let fs = Iso9660::mount(MemDevice::fixture()).expect("mount");
+    assert_eq!(fs.namespace(), Namespace::RockRidge);
+    assert_eq!(fs.volume().label_str(), Some("ISO_FIXTURE"));
+    assert!(fs.volume().has_joliet());
+    assert_eq!(fs.volume().bytes(), common::image().len() as u64);
+}
+
+#[test]
+fn walk_lists_what_bsdtar_listed_in_every_namespace() {
+    for namespace in NAMESPACES {
+        let mut want: BTreeMap<String, u64> = BTreeMap::new();
+        for e in expected(namespace) {
+            match e {
+                Expected::File { path, size } => {
+                    want.insert(path, size);
+                }
+                Expected::Link { path, target } => {
+                    want.insert(path, target.len() as u64);
+                }
+                Expected::Dir { .. } => {}
+            }
+        }
+        let mut fs = mount(namespace);
+        let got: BTreeMap<String, u64> = fs.walk(10_000).expect("walk").into_iter().collect();
+        assert_eq!(got, want, "{namespace:?}");
+    }
+}
+
+#[test]
+fn every_directory_bsdtar_listed_is_one() {
+    for namespace in NAMESPACES {
+        let mut fs = mount(namespace);
+        for e in expected(namespace) {
+            let Expected::Dir { path } = e else { continue };
+            let m = fs.metadata(&path).unwrap_or_else(|e| panic!("{namespace:?}: {path}: {e}"));
+            assert_eq!(m.kind, Kind::Directory, "{namespace:?}: {path}");
+        }
+    }
+}
+
+#[test]
+fn every_file_reads_back_its_bytes() {
+    for namespace in NAMESPACES {
+        let mut fs = mount(namespace);
+        let mut checked = 0;
+        for e in expected(namespace) {
+            let Expected::File { path, size } = e else { continue };
+            let data = fs.read_to_vec(&path, 1 << 20).unwrap_or_else(|e| panic!("{namespace:?}: {path}: {e}"));
+            assert_eq!(data.len() as u64, size, "{namespace:?}: {path}");
+            if let Some(want) = content(&path) {
+                assert!(data == want, "{namespace:?}: {path} differs");
+                checked += 1;
+            }
+        }
+        // Four even in the primary tree, whose 8.3 names hide the rest.
+        assert!(checked >= 4, "{namespace:?}: only {checked} files had known contents");
+    }
+}
+
+#[test]
+fn reads_at_odd_offsets_match_whole_reads() {
+    let mut fs = mount(Namespace::RockRidge);
+    let want = content("sub/deeper/pattern.bin").unwrap();
+    let file = fs.open("sub/deeper/pattern.bin").expect("open");
+    for (offset, len) in [(0u64, 1usize), (2047, 2), (4095, 4098), (100_001, 300), (299 * 1024, 4096)] {
+        let mut buf = vec![0xEEu8; len];
+        let n = fs.read(&file, offset, &mut buf).expect("read");
+        let end = (offset as usize + len).min(want.len());
+        assert_eq!(n, end - offset as usize, "@{offset}");
+        assert_eq!(&buf[..n], &want[offset as usize..end], "@{offset}");
+    }
+    let mut buf = [0u8; 16];
+    assert_eq!(fs.read(&file, want.len() as u64, &mut buf), Ok(0));
+}
+
+#[test]
+fn metadata_matches_the_tree() {
+    for namespace in NAMESPACES {
+        let mut fs = mount(namespace);
+        let m = fs.metadata("hello.txt").expect("hello");
+        assert_eq!((m.kind, m.len, m.modified_unix), (Kind::File, 26, FIXTURE_TIME), "{namespace:?}");
+        assert_eq!(fs.metadata("sub/deeper").expect("dir").kind, Kind::Directory);
+        assert_eq!(fs.metadata("/sub/./deeper/../pattern.bin").map(|m| m.kind), Err(Error::NotFound));
+        assert_eq!(fs.metadata("nope"), Err(Error::NotFound));
+        assert_eq!(fs.metadata("hello.txt/more"), Err(Error::NotADirectory));
+        assert_eq!(fs.open("sub").err(), Some(Error::IsADirectory));
+    }
+    let mut fs = mount(Namespace::RockRidge);
+    assert_eq!(fs.metadata("hello.txt").expect("hello").permissions, 0o444);
+    assert_eq!(fs.metadata("sub").expect("sub").permissions, 0o555);
+}
+
+#[test]
+fn read_dir_lists_one_level() {
+    for namespace in NAMESPACES {
+        let mut fs = mount(namespace);
+        let mut names: Vec<(String, Kind)> =
+            fs.read_dir("sub", 100).expect("read_dir").into_iter().map(|e| (e.name, e.kind)).collect();
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        let want: Vec<(String, Kind)> = expected(namespace)
+            .into_iter()
+            .filter_map(|e| {
+                let name = e.path().strip_prefix("sub/")?;
+                if name.contains('/') {
+                    return None;
+                }
+                let kind = match e {
+                    Expected::Dir { .. } => Kind::Directory,
+                    Expected::File { .. } => Kind::File,
+                    Expected::Link { .. } => Kind::Symlink,
+                };
+                Some((name.to_string(), kind))
+            })
+            .collect();
+        assert_eq!(names, want, "{namespace:?}");
+        assert_eq!(fs.read_dir("many", 1000).expect("many").len(), 200, "{namespace:?}");
+        assert_eq!(fs.read_dir("many", 199).map(|v| v.len()), Err(Error::LimitExceeded));
+        assert_eq!(fs.read_dir("hello.txt", 10).map(|v| v.len()), Err(Error::NotADirectory));
+    }
+}
+
+#[test]
+fn names_are_each_namespace_s_own() {
+    let long = "a-name-that-is-far-too-long-for-joliet-which-stops-at-sixty-four-characters-while-rock-ridge-does-not.txt";
+    let mut rr = mount(Namespace::RockRidge);
+    assert_eq!(rr.read_to_vec(long, 100).expect("rock ridge"), b"long\n");
+    let mut joliet = mount(Namespace::Joliet);
+    assert_eq!(joliet.read_to_vec(&long[..64], 100).expect("joliet"), b"long\n");
+    assert_eq!(joliet.metadata(long), Err(Error::NotFound));
+    let mut primary = mount(Namespace::Primary);
+    assert_eq!(primary.read_to_vec("a_name_t.txt", 100).expect("primary"), b"long\n");
+    assert_eq!(primary.metadata("HELLO.TXT"), Err(Error::NotFound));
+}
+
+#[test]
+fn symbolic_links_are_rock_ridge_s() {
+    let mut fs = mount(Namespace::RockRidge);
+    assert_eq!(fs.read_link("fast-link").expect("relative"), b"hello.txt");
+    assert_eq!(fs.read_link("abs-link").expect("absolute"), b"/sub/deeper/pattern.bin");
+    assert_eq!(fs.read_link("sub/up").expect("parent"), b"../hello.txt");
+    assert_eq!(fs.read_link("hello.txt"), Err(Error::NotASymlink));
+
+    assert_eq!(fs.metadata("fast-link").expect("follow").len, 26);
+    assert_eq!(fs.symlink_metadata("fast-link").expect("nofollow").kind, Kind::Symlink);
+    assert_eq!(fs.metadata("abs-link").expect("absolute").len, 300 * 1024);
+    assert_eq!(fs.read_to_vec("sub/up", 100).expect("through link"), content("hello.txt").unwrap());
+
+    assert_eq!(fs.read_link_from_root("fast-link"), Ok(Some("hello.txt".into())));
+    assert_eq!(fs.read_link_from_root("abs-link"), Ok(Some("sub/deeper/pattern.bin".into())));
+    assert_eq!(fs.read_link_from_root("sub/up"), Ok(Some("hello.txt".into())));
+    assert_eq!(fs.read_link_from_root("hello.txt"), Ok(None));
+
+    // Without Rock Ridge a link is the empty file the primary record is.
+    let mut joliet = mount(Namespace::Joliet);
+    assert_eq!(joliet.read_link("fast-link"), Err(Error::NotASymlink));
+    assert_eq!(joliet.metadata("fast-link").expect("joliet").len, 0);
+}
+
+/// `h` sits nine levels down, past ISO 9660's eight, so bsdtar moved it under
+/// `rr_moved` and left a `CL` entry where it was. Rock Ridge shows it in place,
+/// and only there; the primary tree shows where it really is.
+#[test]
+fn relocated_directories_are_where_rock_ridge_says() {
+    let mut rr = mount(Namespace::RockRidge);
+    let h = rr.metadata("a/b/c/d/e/f/g/h").expect("relocated");
+    assert_eq!(h.kind, Kind::Directory);
+    assert_eq!(rr.read_to_vec("a/b/c/d/e/f/g/h/i/deep.txt", 100).expect("deep"), b"nine levels down\n");
+    assert_eq!(rr.read_dir("rr_moved", 10).expect("rr_moved"), []);
+
+    let mut primary = mount(Namespace::Primary);
+    assert_eq!(primary.metadata("a/b/c/d/e/f/g/h").expect("placeholder").kind, Kind::File);
+    assert_eq!(primary.metadata("rr_moved/h").expect("moved").extent, h.extent);
+}
+
+#[test]
+fn the_boot_catalog_names_the_boot_image() {
+    let mut fs = mount(Namespace::RockRidge);
+    let entries = fs.boot_entries().expect("catalog");
+    let [initial] = entries.as_slice() else { panic!("one entry expected, got {entries:?}") };
+    assert_eq!(initial.platform, PLATFORM_X86);
+    assert!(initial.bootable);
+    assert_eq!(initial.media, Media::NoEmulation);
+    assert_eq!(initial.load_rba, fs.metadata("boot.img").expect("boot.img").extent);
+    assert_eq!(Some(fs.metadata("boot.catalog").expect("catalog").extent), fs.volume().boot_catalog);
+}
@@ -0,0 +1,321 @@
+//! A disc is untrusted input, and this is where that is proved rather than
+//! claimed.
+//!
+//! Every test starts from the committed image — bsdtar mastered it — and breaks
+//! it on purpose. The assertion is always a typed error and never a panic, a
+//! hang, or an allocation the disc chose the size of.
+
+mod common;
+
+use common::{descriptor, primary_root, record, su_entry, MemDevice, SECTOR};
+use toyos_iso9660::{Error, Iso9660, Kind, Namespace};
+
+fn iso() -> MemDevice {
+    MemDevice::fixture()
+}
+
+/// The extent of the directory at `path` in the primary tree, asked of the
+/// pristine image.
+fn dir_extent(dev: &MemDevice, path: &str) -> u64 {
+    let mut fs = Iso9660::mount_namespace(dev.clone(), Namespace::Primary).expect("pristine mounts");
+    fs.metadata(path).expect("pristine path").extent as u64
+}
+
+fn mount_err(dev: MemDevice) -> Error {
+    match Iso9660::mount(dev) {
+        Ok(_) => panic!("mounted a broken volume"),
+        Err(e) => e,
+    }
+}
+
+/// Everything a reader might do, in every namespace, all of which must come
+/// back as a result.
+fn exercise(dev: MemDevice) {
+    for namespace in [Namespace::RockRidge, Namespace::Joliet, Namespace::Primary] {
+        let Ok(mut fs) = Iso9660::mount_namespace(dev.clone(), namespace) else { continue };
+        let _ = fs.walk(10_000);
+        let _ = fs.boot_entries();
+        for path in ["hello.txt", "sub/deeper/pattern.bin", "fast-link", "abs-link", "many", "a/b/c/d/e/f/g/h/i"] {
+            let _ = fs.metadata(path);
+            let _ = fs.read_link_from_root(path);
+            let _ = fs.read_dir(path, 10_000);
+            let _ = fs.read_to_vec(path, 1 << 20);
+        }
+    }
+}
+
+// ---------------------------------------------------------------- descriptors
+
+#[test]
+fn a_bad_identifier_is_not_iso() {
+    let mut dev = iso();
+    dev.poke(descriptor(16) + 1, b"CD002");
+    assert_eq!(mount_err(dev), Error::NotIso);
+}
+
+#[test]
+fn a_set_with_no_terminator_is_not_iso() {
+    // The terminator becomes a volume partition descriptor, which this reader
+    // skips; the sector after it is not a descriptor at all.
+    let mut dev = iso();
+    dev.poke(descriptor(19), &[3]);
+    assert_eq!(mount_err(dev), Error::NotIso);
+
+    // Every sector a descriptor and none a terminator: bounded, not a read of
+    // the whole volume.
+    let mut dev = iso();
+    let pvd = dev.peek(descriptor(16), SECTOR as usize).to_vec();
+    for sector in 17..17 + 80 {
+        dev.poke(descriptor(sector), &pvd);
+        dev.poke(descriptor(sector), &[3]);
+    }
+    assert_eq!(mount_err(dev), Error::NotIso);
+}
+
+#[test]
+fn a_logical_block_that_is_not_2048_is_unsupported() {
+    let mut dev = iso();
+    dev.poke(descriptor(16) + 128, &512u16.to_le_bytes());
+    assert_eq!(mount_err(dev), Error::Unsupported);
+}
+
+#[test]
+fn a_volume_larger_than_its_device_is_truncated() {
+    let mut dev = iso();
+    let blocks = dev.u32_at(descriptor(16) + 80);
+    dev.poke(descriptor(16) + 80, &(blocks + 1).to_le_bytes());
+    assert_eq!(mount_err(dev), Error::Truncated);
+}
+
+#[test]
+fn a_root_outside_the_volume_is_refused() {
+    let mut dev = iso();
+    let blocks = dev.u32_at(descriptor(16) + 80);
+    dev.poke(descriptor(16) + 156 + 2, &blocks.to_le_bytes());
+    assert_eq!(mount_err(dev), Error::CorruptDirectory);
+}
+
+// ---------------------------------------------------------------- records
+
+#[test]
+fn records_that_do_not_fit_are_refused() {
+    let dev = iso();
+    let hello = record(&dev, primary_root(&dev), b"HELLO.TXT;1");
+    // Shorter than the fixed part, a name longer than the record, and a
+    // record that runs past the end of its sector.
+    for (at, byte) in [(0u64, 20u8), (32, 200)] {
+        let mut dev = dev.clone();
+        dev.poke(hello + at, &[byte]);
+        let mut fs = Iso9660::mount(dev.clone()).expect("mount");
+        assert_eq!(fs.read_dir("", 100).map(|v| v.len()), Err(Error::CorruptDirectory), "byte {at} = {byte}");
+        exercise(dev);
+    }
+    // Joliet's `many` fills whole sectors with records too short to carry a
+    // System Use area; its first sector's last record, grown past the end.
+    let mut dev = dev.clone();
+    let mut joliet = Iso9660::mount_namespace(dev.clone(), Namespace::Joliet).expect("pristine mounts");
+    let start = joliet.metadata("many").expect("many").extent as u64 * SECTOR;
+    let mut last = start;
+    while dev.peek(last + dev.peek(last, 1)[0] as u64, 1)[0] != 0 {
+        last += dev.peek(last, 1)[0] as u64;
+    }
+    let room = start + SECTOR - last;
+    assert!(room < 255, "the fixture's `many` sector is not nearly full");
+    dev.poke(last, &[room as u8 + 1]);
+    let mut fs = Iso9660::mount_namespace(dev.clone(), Namespace::Joliet).expect("mount");
+    assert_eq!(fs.read_dir("many", 1000).map(|v| v.len()), Err(Error::CorruptDirectory));
+    exercise(dev);
+}
+
+#[test]
+fn a_file_extent_outside_the_volume_is_refused() {
+    let mut dev = iso();
+    let blocks = dev.u32_at(descriptor(16) + 80);
+    let hello = record(&dev, primary_root(&dev), b"HELLO.TXT;1");
+    dev.poke(hello + 2, &blocks.to_le_bytes());
+    let mut fs = Iso9660::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.metadata("hello.txt").map(|m| m.len), Ok(26));
+    assert_eq!(fs.open("hello.txt"), Err(Error::CorruptDirectory));
+    exercise(dev);
+}
+
+#[test]
+fn a_directory_past_the_bound_is_refused() {
+    let mut dev = iso();
+    let many = record(&dev, primary_root(&dev), b"MANY");
+    dev.poke(many + 10, &u32::MAX.to_le_bytes());
+    let mut fs = Iso9660::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.read_dir("many", 100).map(|v| v.len()), Err(Error::LimitExceeded));
+    exercise(dev);
+}
+
+#[test]
+fn multi_extent_and_interleaved_files_are_not_read() {
+    let dev = iso();
+    let hello = record(&dev, primary_root(&dev), b"HELLO.TXT;1");
+    for (at, byte) in [(25u64, 0x80u8), (26, 1)] {
+        let mut dev = dev.clone();
+        dev.poke(hello + at, &[byte]);
+        let mut fs = Iso9660::mount(dev.clone()).expect("mount");
+        assert_eq!(fs.metadata("hello.txt").map(|m| m.kind), Ok(Kind::Other));
+        assert_eq!(fs.open("hello.txt"), Err(Error::Unsupported));
+        // The next record has another name, so it is not taken for an extent
+        // of this one.
+        assert_eq!(fs.metadata("many").map(|m| m.kind), Ok(Kind::Directory));
+        assert!(fs.walk(10_000).expect("walk").iter().all(|(p, _)| p != "hello.txt"));
+    }
+}
+
+// ---------------------------------------------------------------- rock ridge
+
+#[test]
+fn an_entry_running_past_its_area_is_corrupt() {
+    let mut dev = iso();
+    let hello = record(&dev, primary_root(&dev), b"HELLO.TXT;1");
+    dev.poke(su_entry(&dev, hello, b"NM") + 2, &[250]);
+    let mut fs = Iso9660::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.metadata("hello.txt"), Err(Error::CorruptExtension));
+    // The other trees never read the System Use area.
+    let mut joliet = Iso9660::mount_namespace(dev.clone(), Namespace::Joliet).expect("joliet");
+    assert_eq!(joliet.metadata("hello.txt").map(|m| m.len), Ok(26));
+    exercise(dev);
+}
+
+#[test]
+fn a_continuation_loop_is_bounded() {
+    // The root's `.` record continues its entries elsewhere; pointed at
+    // itself, the area names itself again forever.
+    let mut dev = iso();
+    let root = primary_root(&dev) * SECTOR;
+    let ce = su_entry(&dev, root, b"CE");
+    let offset = (ce - root) as u32;
+    dev.poke(ce + 4, &(primary_root(&dev) as u32).to_le_bytes());
+    dev.poke(ce + 12, &offset.to_le_bytes());
+    dev.poke(ce + 20, &28u32.to_le_bytes());
+    assert_eq!(mount_err(dev), Error::LimitExceeded);
+}
+
+#[test]
+fn a_continuation_outside_the_volume_is_corrupt() {
+    let dev = iso();
+    let root = primary_root(&dev) * SECTOR;
+    let ce = su_entry(&dev, root, b"CE");
+    let blocks = dev.u32_at(descriptor(16) + 80);
+    for (at, value) in [(4u64, blocks), (12, 2000), (20, 2049)] {
+        let mut dev = dev.clone();
+        dev.poke(ce + at, &value.to_le_bytes());
+        assert_eq!(mount_err(dev), Error::CorruptExtension, "field {at} = {value}");
+    }
+}
+
+#[test]
+fn a_relocation_cycle_terminates() {
+    // `h`'s CL entry names the root instead of `rr_moved/h`.
+    let mut dev = iso();
+    let g = dir_extent(&dev, "a/b/c/d/e/f/g");
+    let h = record(&dev, g, b"H");
+    dev.poke(su_entry(&dev, h, b"CL") + 4, &(primary_root(&dev) as u32).to_le_bytes());
+    let mut fs = Iso9660::mount(dev.clone()).expect("mount");
+    let listed = fs.walk(10_000).expect("walk");
+    assert!(listed.iter().all(|(p, _)| !p.starts_with("a/b/c/d/e/f/g/h/")), "the cycle was entered");
+    assert_eq!(fs.metadata("a/b/c/d/e/f/g/h/a/b/c/d/e/f/g/h/hello.txt").map(|m| m.len), Ok(26));
+    exercise(dev);
+}
+
+#[test]
+fn a_symlink_loop_is_bounded() {
+    // `fast-link` -> `hello.txt` becomes `fast-link` -> `fast-link`, the same
+    // nine bytes.
+    let mut dev = iso();
+    let link = record(&dev, primary_root(&dev), b"FAST_LIN.;1");
+    dev.poke(su_entry(&dev, link, b"SL") + 5 + 2, b"fast-link");
+    let mut fs = Iso9660::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.metadata("fast-link"), Err(Error::LimitExceeded));
+    assert_eq!(fs.read_link("fast-link").as_deref(), Ok(&b"fast-link"[..]));
+    exercise(dev);
+}
+
+#[test]
+fn a_volume_without_rock_ridge_falls_back_to_joliet() {
+    let mut dev = iso();
+    let root = primary_root(&dev) * SECTOR;
+    let sp = su_entry(&dev, root, b"SP");
+    dev.poke(sp, b"XX");
+    let fs = Iso9660::mount(dev.clone()).expect("mount");
+    assert_eq!(fs.namespace(), Namespace::Joliet);
+    assert_eq!(Iso9660::mount_namespace(dev, Namespace::RockRidge).err(), Some(Error::Unsupported));
+}
+
+// ---------------------------------------------------------------- el torito
+
+#[test]
+fn a_catalog_that_does_not_check_out_is_refused() {
+    let dev = iso();
+    let catalog = dev.u32_at(descriptor(17) + 71) as u64 * SECTOR;
+    let blocks = dev.u32_at(descriptor(16) + 80);
+    // The header ID, the checksum, and the key bytes.
+    for (at, byte) in [(0u64, 2u8), (28, 0x12), (30, 0)] {
+        let mut dev = dev.clone();
+        dev.poke(catalog + at, &[byte]);
+        let mut fs = Iso9660::mount(dev).expect("mount");
+        assert_eq!(fs.boot_entries(), Err(Error::CorruptCatalog), "byte {at} = {byte}");
+    }
+    let mut dev = dev.clone();
+    dev.poke(descriptor(17) + 71, &blocks.to_le_bytes());
+    let mut fs = Iso9660::mount(dev).expect("mount");
+    assert_eq!(fs.boot_entries(), Err(Error::CorruptCatalog));
+}
+
+// ---------------------------------------------------------------- devices
+
+#[test]
+fn the_caller_limit_refuses_rather_than_truncates() {
+    let mut fs = Iso9660::mount(iso()).expect("mount");
+    assert_eq!(fs.walk(10).map(|v| v.len()), Err(Error::LimitExceeded));
+    assert_eq!(fs.read_to_vec("sub/deeper/pattern.bin", 1000), Err(Error::LimitExceeded));
+}
+
+#[test]
+fn a_device_that_fails_mid_read_reports_it() {
+    let whole = |dev: MemDevice| -> Result<usize, Error> {
+        let mut fs = Iso9660::mount(dev)?;
+        fs.read_to_vec("a/b/c/d/e/f/g/h/i/deep.txt", 1 << 20).map(|v| v.len())
+    };
+    let mut counted = iso();
+    counted.fail_after = Some(u32::MAX);
+    let mut fs = Iso9660::mount(counted).expect("mount");
+    fs.read_to_vec("a/b/c/d/e/f/g/h/i/deep.txt", 1 << 20).expect("read");
+    let reads = fs.device().reads;
+
+    for n in 0..reads {
+        let mut dev = iso();
+        dev.fail_after = Some(n);
+        assert_eq!(whole(dev), Err(Error::Io), "failing after {n} of {reads} reads");
+    }
+}
+
+/// Every byte of the metadata a lookup touches, flipped one at a time: the
+/// descriptor set, the root directory, the root's continuation area, and the
+/// boot catalog. A sweep, not a fuzzer, so it is the same sweep every run.
+#[test]
+fn single_byte_damage_never_panics() {
+    let dev = iso();
+    let root = primary_root(&dev) * SECTOR;
+    let ce = su_entry(&dev, root, b"CE");
+    let continuation = dev.u32_at(ce + 4) as u64 * SECTOR + dev.u32_at(ce + 12) as u64;
+    let catalog = dev.u32_at(descriptor(17) + 71) as u64 * SECTOR;
+    let ranges = [
+        (descriptor(16), 4 * SECTOR),
+        (root, SECTOR),
+        (continuation, dev.u32_at(ce + 20) as u64),
+        (catalog, 128),
+    ];
+    for (start, len) in ranges {
+        for at in start..start + len {
+            let mut dev = dev.clone();
+            let b = dev.peek(at, 1)[0];
+            dev.poke(at, &[b ^ 0xA5]);
+            exercise(dev);
+        }
+    }
+}
@@ -35,6 +35,16 @@ impl Dll {
         self.period = self.nominal_period;
     }
 
+    /// Take the period from the device rather than from the timestamps: a USB
+    /// DAC on its own crystal says how fast it consumes frames, and that is
+    /// exact where the timestamps carry however late the xHCI poll ran. Only
+    /// the period is taken — the phase is still the timestamps', since only
+    /// they say *when* a period ended — and the same clamp holds, because the
+    /// number is still the device's.
+    pub fn steer(&mut self, period_nanos: f64) {
+        self.period = period_nanos.clamp(self.nominal_period * 0.5, self.nominal_period * 2.0);
+    }
+
     /// Feed one completion record: `n_periods` buffers finished with a single
     /// interrupt at `t_actual`. The batch timestamp belongs to the *last* of
     /// the n grid points, so the prediction error is measured against
@@ -140,6 +150,22 @@ mod tests {
         assert_eq!(singly.t_estimated, Some(5.0 * NOMINAL));
     }
 
+    /// A steered period is the one the next prediction is made with, and the
+    /// phase is left where the timestamps put it; a period past the clamp is
+    /// clamped like a tracked one.
+    #[test]
+    fn a_steered_period_replaces_the_tracked_one_and_keeps_the_phase() {
+        let mut dll = Dll::new(NOMINAL);
+        dll.update(0.0, 1);
+        dll.steer(NOMINAL * 1.001);
+        assert_eq!(dll.period, NOMINAL * 1.001);
+        assert_eq!(dll.t_estimated, Some(NOMINAL));
+        dll.update(NOMINAL, 1);
+        assert_eq!(dll.t_estimated, Some(NOMINAL + NOMINAL * 1.001));
+        dll.steer(NOMINAL * 10.0);
+        assert_eq!(dll.period, NOMINAL * 2.0);
+    }
+
     /// A reset drops the grid entirely: the device restarts its period grid
     /// from whatever is submitted next, so an estimate carried across would
     /// read the discontinuity as drift.
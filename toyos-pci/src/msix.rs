@@ -31,6 +31,25 @@ pub const ENTRY_VECTOR_CONTROL: u64 = 0xC;
 /// set: an entry programmed and left alone delivers nothing.
 pub const ENTRY_UNMASKED: u32 = 0;
 
+/// The LAPIC window every message is written to (SDM Vol. 3A §11.11.1).
+const MESSAGE_WINDOW: u32 = 0xFEE0_0000;
+/// The widest destination the address format carries: bits 19:12, eight of
+/// them. An x2APIC id past it is reachable only through interrupt remapping.
+const MAX_DESTINATION: u32 = 0xFF;
+
+/// The message address that delivers to the LAPIC whose id is `apic_id`:
+/// physical destination mode, no redirection hint, so exactly that CPU.
+///
+/// `None` for an id the eight-bit field cannot name. Truncating it would route
+/// the interrupt to whichever CPU owns the low byte, which is a CPU that never
+/// asked for it.
+pub const fn message_address(apic_id: u32) -> Option<u32> {
+    if apic_id > MAX_DESTINATION {
+        return None;
+    }
+    Some(MESSAGE_WINDOW | apic_id << 12)
+}
+
 /// Why this function's MSI-X cannot be armed. Not a failure of the kernel: a
 /// device that publishes one of these is a device whose interrupts the driver
 /// has to make its own decision about, and the decision differs — an xHC falls
@@ -180,6 +199,14 @@ mod tests {
         assert_eq!(Msix::enabled(0x0003), ENABLE | 0x0003);
     }
 
+    #[test]
+    fn the_destination_is_bits_19_to_12_and_only_eight_of_them() {
+        assert_eq!(message_address(0), Some(0xFEE0_0000));
+        assert_eq!(message_address(3), Some(0xFEE0_3000));
+        assert_eq!(message_address(0xFF), Some(0xFEEF_F000));
+        assert_eq!(message_address(0x100), None, "not the CPU whose id is the low byte");
+    }
+
     #[test]
     fn an_entry_is_four_dwords_in_the_order_the_spec_gives_them() {
         assert_eq!(
@@ -74,7 +74,8 @@ mod tests {
         ("MouseEvent", 6, 2),
         ("Stat", 24, 8),
         ("SchedInfo", 24, 8),
-        ("FramebufferInfo", 32, 4),
+        ("FramebufferInfo", 292, 4),
+        ("Output", 16, 4),
         ("SpawnArgs", 80, 8),
         ("NamespaceBuild", 56, 8),
         ("InboxSetup", 16, 8),
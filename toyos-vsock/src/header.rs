@@ -0,0 +1,163 @@
+use core::fmt;
+
+use toyos_untrusted::Untrusted;
+
+use crate::{Addr, HEADER_BYTES, TYPE_STREAM};
+
+/// What a packet asks of the connection it names. Virtio 1.2 §5.10.6.
+///
+/// `0`, `VIRTIO_VSOCK_OP_INVALID`, has no variant: it is the number the
+/// specification reserves so that a zeroed header is not a request, and a
+/// packet carrying it is refused as [`Malformed::Op`] like any other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum Op {
+    Request = 1,
+    Response = 2,
+    Rst = 3,
+    Shutdown = 4,
+    Rw = 5,
+    CreditUpdate = 6,
+    CreditRequest = 7,
+}
+
+impl Op {
+    fn from_raw(raw: u16) -> Option<Self> {
+        Some(match raw {
+            1 => Self::Request,
+            2 => Self::Response,
+            3 => Self::Rst,
+            4 => Self::Shutdown,
+            5 => Self::Rw,
+            6 => Self::CreditUpdate,
+            7 => Self::CreditRequest,
+            _ => return None,
+        })
+    }
+}
+
+/// Why a packet was not read.
+///
+/// Exhaustive on purpose, as `toyos_9p::Error` is: whoever logs these should
+/// stop compiling when a new one appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// Fewer bytes than a header.
+    Short(usize),
+    /// The header's `len` is not the payload that came with it.
+    Length { claimed: u32, carried: usize },
+    /// A socket type other than [`TYPE_STREAM`].
+    Type(u16),
+    /// An operation number the specification does not define, or `0`.
+    Op(u16),
+    /// A payload on an operation that carries none.
+    PayloadOnControl(Op),
+}
+
+impl fmt::Display for Malformed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Short(len) => write!(f, "{len} bytes is shorter than a header"),
+            Self::Length { claimed, carried } => {
+                write!(f, "header claims {claimed} payload bytes and {carried} came with it")
+            }
+            Self::Type(kind) => write!(f, "socket type {kind} is not a stream"),
+            Self::Op(op) => write!(f, "operation {op} is not one the protocol defines"),
+            Self::PayloadOnControl(op) => write!(f, "a {op:?} carrying a payload"),
+        }
+    }
+}
+
+/// One packet's header, decoded.
+///
+/// `type` has no field because it is always [`TYPE_STREAM`]: parsing refuses
+/// anything else and writing supplies it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub src: Addr,
+    pub dst: Addr,
+    /// Payload bytes following the header. Zero for everything but [`Op::Rw`].
+    pub len: u32,
+    pub op: Op,
+    pub flags: u32,
+    /// What the sender will buffer for this connection, in total.
+    pub buf_alloc: u32,
+    /// What the sender has consumed of it, ever. Wraps.
+    pub fwd_cnt: u32,
+}
+
+impl Header {
+    /// The header the device will read, as bytes.
+    pub fn to_bytes(&self) -> [u8; HEADER_BYTES] {
+        let mut out = [0u8; HEADER_BYTES];
+        out[0..8].copy_from_slice(&self.src.cid.to_le_bytes());
+        out[8..16].copy_from_slice(&self.dst.cid.to_le_bytes());
+        out[16..20].copy_from_slice(&self.src.port.to_le_bytes());
+        out[20..24].copy_from_slice(&self.dst.port.to_le_bytes());
+        out[24..28].copy_from_slice(&self.len.to_le_bytes());
+        out[28..30].copy_from_slice(&TYPE_STREAM.to_le_bytes());
+        out[30..32].copy_from_slice(&(self.op as u16).to_le_bytes());
+        out[32..36].copy_from_slice(&self.flags.to_le_bytes());
+        out[36..40].copy_from_slice(&self.buf_alloc.to_le_bytes());
+        out[40..44].copy_from_slice(&self.fwd_cnt.to_le_bytes());
+        out
+    }
+
+    /// Read a whole packet: its header, and the payload the header says
+    /// follows it.
+    ///
+    /// `packet` is exactly what arrived — the length the device wrote, never
+    /// the buffer it was given — so that "the payload is `len` bytes" is a
+    /// comparison with something the peer did not choose.
+    pub fn parse(packet: &[u8]) -> Result<(Self, &[u8]), Malformed> {
+        let Some((head, payload)) = packet.split_first_chunk::<HEADER_BYTES>() else {
+            return Err(Malformed::Short(packet.len()));
+        };
+        let u64_at = |at: usize| u64::from_le_bytes(head[at..at + 8].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(head[at..at + 4].try_into().unwrap());
+        let u16_at = |at: usize| u16::from_le_bytes(head[at..at + 2].try_into().unwrap());
+
+        let kind = u16_at(28);
+        if kind != TYPE_STREAM {
+            return Err(Malformed::Type(kind));
+        }
+        let raw_op = u16_at(30);
+        let op = Op::from_raw(raw_op).ok_or(Malformed::Op(raw_op))?;
+        let claimed = u32_at(24);
+        if payload.len() > u32::MAX as usize || !Untrusted::new(claimed).is(payload.len() as u32) {
+            return Err(Malformed::Length { claimed, carried: payload.len() });
+        }
+        if op != Op::Rw && !payload.is_empty() {
+            return Err(Malformed::PayloadOnControl(op));
+        }
+        let header = Self {
+            src: Addr { cid: u64_at(0), port: u32_at(16) },
+            dst: Addr { cid: u64_at(8), port: u32_at(20) },
+            len: claimed,
+            op,
+            flags: u32_at(32),
+            buf_alloc: u32_at(36),
+            fwd_cnt: u32_at(40),
+        };
+        Ok((header, payload))
+    }
+
+    /// The reset that answers `packet`: addressed back to its sender, from the
+    /// port it was sent to, advertising nothing.
+    ///
+    /// For a packet naming no connection this side has. Answering every one of
+    /// those — except a reset, which would make two such sides volley forever —
+    /// is what tells a host-side `connect` "refused" rather than leaving it to
+    /// time out.
+    pub fn rst_for(packet: &Header) -> Option<Self> {
+        (packet.op != Op::Rst).then_some(Self {
+            src: packet.dst,
+            dst: packet.src,
+            len: 0,
+            op: Op::Rst,
+            flags: 0,
+            buf_alloc: 0,
+            fwd_cnt: 0,
+        })
+    }
+}
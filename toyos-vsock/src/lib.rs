@@ -0,0 +1,71 @@
+//! virtio-vsock stream packets: the header, and one connection's credit.
+//!
+//! A vsock device carries sockets between the guest and the host with no
+//! network in between — `vhost-vsock-pci` on the QEMU side, `AF_VSOCK` in the
+//! host's kernel. What crosses the device is a packet: a 44-byte header naming
+//! both ends by `(cid, port)`, an operation, and for `RW` a payload. This crate
+//! reads and writes the header ([`Header`]) and keeps one stream's state and
+//! flow control ([`Stream`]). Queues and the device are the kernel's
+//! (`kernel/src/drivers/virtio_vsock.rs`); sockets and the client protocol are
+//! vsockd's (`userland/vsockd`).
+//!
+//! # Every packet is untrusted
+//!
+//! The peer is a host process, and a packet is bytes the host wrote into guest
+//! memory. So [`Header::parse`] never panics, and the hazards particular to
+//! this format are each closed by name:
+//!
+//! - **The length is a field.** `len` is the peer's claim about how much
+//!   payload follows, and the packet is refused with
+//!   [`Malformed::Length`] unless the buffer the device returned carries
+//!   exactly that much. Shorter is a payload that is not there; longer is bytes
+//!   nobody accounted for.
+//! - **Only `RW` carries data.** A control operation with a payload is refused
+//!   rather than having its payload ignored, because a peer that sends one is
+//!   not one whose other fields are worth believing.
+//! - **Credit is a promise the peer made.** Each side advertises how much it
+//!   will buffer (`buf_alloc`) and how much it has consumed (`fwd_cnt`), and a
+//!   sender may have at most the difference in flight. A peer that sends past
+//!   what this side advertised is [`Violation::Overrun`], and the stream is
+//!   reset rather than buffered without bound. A peer that claims to have
+//!   consumed more than was ever sent opens no window: [`Stream::window`]
+//!   saturates at zero rather than wrapping into four gigabytes.
+//!
+//! # What this crate does not do
+//!
+//! - **No allocation, no buffering.** A stream counts bytes; where they wait
+//!   is the caller's.
+//! - **Streams only.** `SOCK_SEQPACKET` (type 2) is refused at
+//!   [`Header::parse`], because nothing in the guest negotiates it.
+//! - **No port allocation or connection table.** Those are policy, and live in
+//!   vsockd with the rest of it.
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+mod header;
+mod stream;
+
+pub use header::{Header, Malformed, Op};
+pub use stream::{Event, State, Stream, Violation};
+
+/// A header's size on the wire. Virtio 1.2 §5.10.6.
+pub const HEADER_BYTES: usize = 44;
+
+/// The host's context id. Every guest has its own, which the device
+/// configuration space reports; the host is always this.
+pub const CID_HOST: u64 = 2;
+
+/// `VIRTIO_VSOCK_TYPE_STREAM`, the only type this crate speaks.
+pub const TYPE_STREAM: u16 = 1;
+
+/// `Shutdown` flags: this side will receive no more, and will send no more.
+pub const SHUTDOWN_RCV: u32 = 1;
+pub const SHUTDOWN_SEND: u32 = 2;
+
+/// One end of a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Addr {
+    pub cid: u64,
+    pub port: u32,
+}
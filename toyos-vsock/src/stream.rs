@@ -0,0 +1,271 @@
+use core::fmt;
+
+use crate::{Addr, Header, Op, SHUTDOWN_RCV, SHUTDOWN_SEND};
+
+/// Where a stream is in its life.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// This side sent `Request` and has had no answer.
+    Connecting,
+    /// Either side may send data, until it says it will not.
+    Established,
+    /// Reset, by either side. Nothing more crosses it.
+    Closed,
+}
+
+/// What a packet meant to the stream it arrived on, when it meant something.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The peer accepted this side's `Request`.
+    Established,
+    /// This many payload bytes are the stream's next ones.
+    Data(u32),
+    /// The peer set these `Shutdown` flags, cumulatively.
+    Shutdown(u32),
+    /// The peer reset the stream.
+    Reset,
+    /// The peer asked where this side's credit stands; answer with
+    /// [`Stream::credit_update`].
+    CreditRequested,
+    /// Nothing but the credit fields every header carries, which are already
+    /// taken.
+    Credit,
+}
+
+/// A packet this stream should not have been sent. Each one is answered with
+/// [`Stream::reset`]: a peer breaking the protocol on one connection is not
+/// one to keep talking to on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// More data than this side advertised room for.
+    Overrun { unread: u32, buf_alloc: u32 },
+    /// Data after the peer said it would send no more.
+    AfterShutdown,
+    /// An operation that makes no sense in this state: a second `Response`, a
+    /// `Request` on an open stream, anything but an answer while connecting.
+    Unexpected(Op),
+    /// Anything at all on a stream that has been reset.
+    Closed,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overrun { unread, buf_alloc } => {
+                write!(f, "{unread} bytes in flight past the {buf_alloc} advertised")
+            }
+            Self::AfterShutdown => f.write_str("data after the peer shut its sending side"),
+            Self::Unexpected(op) => write!(f, "a {op:?} the stream's state does not admit"),
+            Self::Closed => f.write_str("a packet on a stream already reset"),
+        }
+    }
+}
+
+/// One stream: both ends, its state, and the four counters flow control is.
+///
+/// **Every counter is a wrapping `u32` and only their differences mean
+/// anything**, which is how the specification defines them: `rx_cnt` and
+/// `tx_cnt` count bytes ever received and sent, `fwd_cnt` and `peer_fwd_cnt`
+/// bytes ever consumed by each side, and a stream that moves more than four
+/// gigabytes wraps every one of them together.
+#[derive(Clone, Debug)]
+pub struct Stream {
+    local: Addr,
+    peer: Addr,
+    state: State,
+    buf_alloc: u32,
+    rx_cnt: u32,
+    fwd_cnt: u32,
+    /// `fwd_cnt` as of the last header this side sent, which is what the peer
+    /// believes it.
+    advertised_fwd: u32,
+    tx_cnt: u32,
+    peer_buf_alloc: u32,
+    peer_fwd_cnt: u32,
+    local_shutdown: u32,
+    peer_shutdown: u32,
+}
+
+impl Stream {
+    fn new(local: Addr, peer: Addr, state: State, buf_alloc: u32) -> Self {
+        Self {
+            local,
+            peer,
+            state,
+            buf_alloc,
+            rx_cnt: 0,
+            fwd_cnt: 0,
+            advertised_fwd: 0,
+            tx_cnt: 0,
+            peer_buf_alloc: 0,
+            peer_fwd_cnt: 0,
+            local_shutdown: 0,
+            peer_shutdown: 0,
+        }
+    }
+
+    /// Open a stream to `peer`, buffering at most `buf_alloc` of what it sends.
+    /// The header is the `Request` to send.
+    pub fn connect(local: Addr, peer: Addr, buf_alloc: u32) -> (Self, Header) {
+        let mut stream = Self::new(local, peer, State::Connecting, buf_alloc);
+        let request = stream.header(Op::Request, 0, 0);
+        (stream, request)
+    }
+
+    /// Accept `request`, which the caller has matched to a listening port. The
+    /// header is the `Response` to send.
+    pub fn accept(request: &Header, buf_alloc: u32) -> (Self, Header) {
+        let mut stream = Self::new(request.dst, request.src, State::Established, buf_alloc);
+        stream.take_credit(request);
+        let response = stream.header(Op::Response, 0, 0);
+        (stream, response)
+    }
+
+    pub fn local(&self) -> Addr {
+        self.local
+    }
+
+    pub fn peer(&self) -> Addr {
+        self.peer
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Bytes received and not yet [`consumed`](Self::consumed).
+    pub fn unread(&self) -> u32 {
+        self.rx_cnt.wrapping_sub(self.fwd_cnt)
+    }
+
+    /// How many bytes this side may send now.
+    ///
+    /// Saturating, because both terms are the peer's numbers: a `fwd_cnt`
+    /// claiming more was consumed than was ever sent would otherwise wrap the
+    /// subtraction and open a window of nearly four gigabytes.
+    pub fn window(&self) -> u32 {
+        if self.state != State::Established || self.local_shutdown & SHUTDOWN_SEND != 0 {
+            return 0;
+        }
+        let in_flight = self.tx_cnt.wrapping_sub(self.peer_fwd_cnt);
+        self.peer_buf_alloc.saturating_sub(in_flight)
+    }
+
+    /// Whether the peer has said it will send nothing more.
+    pub fn peer_done_sending(&self) -> bool {
+        self.state == State::Closed || self.peer_shutdown & SHUTDOWN_SEND != 0
+    }
+
+    /// Whether both directions are finished, so the stream can be reset and
+    /// forgotten.
+    pub fn finished(&self) -> bool {
+        self.state == State::Closed
+            || (self.peer_done_sending() && self.local_shutdown & SHUTDOWN_SEND != 0)
+    }
+
+    /// Take a packet the caller has already matched to this stream by both
+    /// addresses.
+    pub fn receive(&mut self, packet: &Header) -> Result<Event, Violation> {
+        if self.state == State::Closed {
+            return Err(Violation::Closed);
+        }
+        if packet.op == Op::Rst {
+            self.state = State::Closed;
+            return Ok(Event::Reset);
+        }
+        self.take_credit(packet);
+        match (self.state, packet.op) {
+            (State::Connecting, Op::Response) => {
+                self.state = State::Established;
+                Ok(Event::Established)
+            }
+            (State::Established, Op::Rw) => {
+                if self.peer_shutdown & SHUTDOWN_SEND != 0 {
+                    return Err(Violation::AfterShutdown);
+                }
+                let unread = self.unread();
+                if packet.len > self.buf_alloc.saturating_sub(unread) {
+                    return Err(Violation::Overrun {
+                        unread: unread.saturating_add(packet.len),
+                        buf_alloc: self.buf_alloc,
+                    });
+                }
+                self.rx_cnt = self.rx_cnt.wrapping_add(packet.len);
+                Ok(Event::Data(packet.len))
+            }
+            (State::Established, Op::Shutdown) => {
+                self.peer_shutdown |= packet.flags & (SHUTDOWN_RCV | SHUTDOWN_SEND);
+                Ok(Event::Shutdown(self.peer_shutdown))
+            }
+            (State::Established, Op::CreditUpdate) => Ok(Event::Credit),
+            (State::Established, Op::CreditRequest) => Ok(Event::CreditRequested),
+            (_, op) => Err(Violation::Unexpected(op)),
+        }
+    }
+
+    /// The header for `len` bytes of data, which the caller sends with them.
+    /// `None` past the [`window`](Self::window), or for nothing at all.
+    pub fn send(&mut self, len: u32) -> Option<Header> {
+        if len == 0 || len > self.window() {
+            return None;
+        }
+        self.tx_cnt = self.tx_cnt.wrapping_add(len);
+        Some(self.header(Op::Rw, len, 0))
+    }
+
+    /// The caller has taken `len` received bytes off this stream's buffer.
+    ///
+    /// Clamped to what was received, so a caller that miscounts opens no more
+    /// credit than there was data.
+    pub fn consumed(&mut self, len: u32) {
+        self.fwd_cnt = self.fwd_cnt.wrapping_add(len.min(self.unread()));
+    }
+
+    /// A `CreditUpdate`, if the peer's idea of this side's room is stale by at
+    /// least half of it.
+    ///
+    /// Every header carries the credit fields, so on a busy stream the data
+    /// going the other way keeps the peer current and this never fires. It is
+    /// for the one-way stream, where the sender would otherwise fill the window
+    /// and wait for an update nobody was going to send.
+    pub fn credit_update_due(&mut self) -> Option<Header> {
+        let stale = self.fwd_cnt.wrapping_sub(self.advertised_fwd);
+        (self.state == State::Established && stale >= self.buf_alloc / 2 && stale > 0)
+            .then(|| self.credit_update())
+    }
+
+    pub fn credit_update(&mut self) -> Header {
+        self.header(Op::CreditUpdate, 0, 0)
+    }
+
+    /// Say this side will send no more, or receive no more, or both.
+    pub fn shutdown(&mut self, flags: u32) -> Header {
+        self.local_shutdown |= flags & (SHUTDOWN_RCV | SHUTDOWN_SEND);
+        self.header(Op::Shutdown, 0, self.local_shutdown)
+    }
+
+    /// End the stream. Idempotent in effect; the header is only worth sending
+    /// the first time.
+    pub fn reset(&mut self) -> Header {
+        self.state = State::Closed;
+        self.header(Op::Rst, 0, 0)
+    }
+
+    fn take_credit(&mut self, packet: &Header) {
+        self.peer_buf_alloc = packet.buf_alloc;
+        self.peer_fwd_cnt = packet.fwd_cnt;
+    }
+
+    fn header(&mut self, op: Op, len: u32, flags: u32) -> Header {
+        self.advertised_fwd = self.fwd_cnt;
+        Header {
+            src: self.local,
+            dst: self.peer,
+            len,
+            op,
+            flags,
+            buf_alloc: self.buf_alloc,
+            fwd_cnt: self.fwd_cnt,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Headers written and read back, and packets no well-behaved peer sends.
+
+use toyos_vsock::{Addr, Header, Malformed, Op, CID_HOST, HEADER_BYTES, TYPE_STREAM};
+
+fn header(op: Op, len: u32) -> Header {
+    Header {
+        src: Addr { cid: 3, port: 1024 },
+        dst: Addr { cid: CID_HOST, port: 5000 },
+        len,
+        op,
+        flags: 0,
+        buf_alloc: 65536,
+        fwd_cnt: 12,
+    }
+}
+
+fn packet(header: &Header, payload: &[u8]) -> Vec<u8> {
+    let mut out = header.to_bytes().to_vec();
+    out.extend_from_slice(payload);
+    out
+}
+
+#[test]
+fn layout_matches_the_specification() {
+    let bytes = header(Op::Rw, 5).to_bytes();
+    assert_eq!(bytes.len(), HEADER_BYTES);
+    assert_eq!(u64::from_le_bytes(bytes[0..8].try_into().unwrap()), 3);
+    assert_eq!(u64::from_le_bytes(bytes[8..16].try_into().unwrap()), CID_HOST);
+    assert_eq!(u32::from_le_bytes(bytes[16..20].try_into().unwrap()), 1024);
+    assert_eq!(u32::from_le_bytes(bytes[20..24].try_into().unwrap()), 5000);
+    assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 5);
+    assert_eq!(u16::from_le_bytes(bytes[28..30].try_into().unwrap()), TYPE_STREAM);
+    assert_eq!(u16::from_le_bytes(bytes[30..32].try_into().unwrap()), Op::Rw as u16);
+    assert_eq!(u32::from_le_bytes(bytes[36..40].try_into().unwrap()), 65536);
+    assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 12);
+}
+
+#[test]
+fn a_packet_reads_back_as_written() {
+    let sent = header(Op::Rw, 5);
+    let bytes = packet(&sent, b"hello");
+    let (got, payload) = Header::parse(&bytes).unwrap();
+    assert_eq!(got, sent);
+    assert_eq!(payload, b"hello");
+
+    let control = header(Op::CreditRequest, 0);
+    assert_eq!(Header::parse(&control.to_bytes()).unwrap(), (control, &[][..]));
+}
+
+#[test]
+fn shorter_than_a_header_is_refused() {
+    let bytes = header(Op::Request, 0).to_bytes();
+    assert_eq!(Header::parse(&bytes[..43]), Err(Malformed::Short(43)));
+    assert_eq!(Header::parse(&[]), Err(Malformed::Short(0)));
+}
+
+#[test]
+fn a_length_the_payload_does_not_match_is_refused() {
+    let claims_more = packet(&header(Op::Rw, 6), b"hello");
+    assert_eq!(
+        Header::parse(&claims_more),
+        Err(Malformed::Length { claimed: 6, carried: 5 })
+    );
+    let claims_less = packet(&header(Op::Rw, 4), b"hello");
+    assert_eq!(
+        Header::parse(&claims_less),
+        Err(Malformed::Length { claimed: 4, carried: 5 })
+    );
+}
+
+#[test]
+fn a_payload_on_a_control_operation_is_refused() {
+    let bytes = packet(&header(Op::Shutdown, 1), b"x");
+    assert_eq!(Header::parse(&bytes), Err(Malformed::PayloadOnControl(Op::Shutdown)));
+}
+
+#[test]
+fn unknown_operations_and_types_are_refused() {
+    let mut bytes = header(Op::Request, 0).to_bytes();
+    bytes[30..32].copy_from_slice(&0u16.to_le_bytes());
+    assert_eq!(Header::parse(&bytes), Err(Malformed::Op(0)));
+    bytes[30..32].copy_from_slice(&8u16.to_le_bytes());
+    assert_eq!(Header::parse(&bytes), Err(Malformed::Op(8)));
+
+    let mut seqpacket = header(Op::Request, 0).to_bytes();
+    seqpacket[28..30].copy_from_slice(&2u16.to_le_bytes());
+    assert_eq!(Header::parse(&seqpacket), Err(Malformed::Type(2)));
+}
+
+#[test]
+fn a_reset_answers_everything_but_a_reset() {
+    let request = header(Op::Request, 0);
+    let rst = Header::rst_for(&request).unwrap();
+    assert_eq!((rst.op, rst.src, rst.dst), (Op::Rst, request.dst, request.src));
+    assert_eq!((rst.buf_alloc, rst.fwd_cnt), (0, 0));
+    assert_eq!(Header::rst_for(&rst), None);
+}
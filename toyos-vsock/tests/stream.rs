@@ -0,0 +1,133 @@
+//! One stream from both ends: the handshake, credit, and a peer that breaks
+//! the protocol.
+
+use toyos_vsock::{
+    Addr, Event, Header, Op, State, Stream, Violation, CID_HOST, SHUTDOWN_RCV, SHUTDOWN_SEND,
+};
+
+const GUEST: Addr = Addr { cid: 3, port: 49152 };
+const HOST: Addr = Addr { cid: CID_HOST, port: 5000 };
+
+/// A connected pair: the guest opened it, the host accepted with `host_buf`.
+fn pair(guest_buf: u32, host_buf: u32) -> (Stream, Stream) {
+    let (mut guest, request) = Stream::connect(GUEST, HOST, guest_buf);
+    assert_eq!(request.op, Op::Request);
+    let (host, response) = Stream::accept(&request, host_buf);
+    assert_eq!((response.src, response.dst), (HOST, GUEST));
+    assert_eq!(guest.receive(&response), Ok(Event::Established));
+    (guest, host)
+}
+
+#[test]
+fn a_handshake_establishes_both_ends() {
+    let (guest, host) = pair(4096, 8192);
+    assert_eq!((guest.state(), host.state()), (State::Established, State::Established));
+    assert_eq!(guest.window(), 8192);
+    assert_eq!(host.window(), 4096);
+}
+
+#[test]
+fn data_is_bounded_by_the_window_until_credit_returns() {
+    let (mut guest, mut host) = pair(4096, 1000);
+    let first = guest.send(600).unwrap();
+    assert_eq!(host.receive(&first), Ok(Event::Data(600)));
+    assert_eq!(guest.window(), 400);
+    assert_eq!(guest.send(401), None);
+    let second = guest.send(400).unwrap();
+    assert_eq!(host.receive(&second), Ok(Event::Data(400)));
+    assert_eq!(guest.window(), 0);
+
+    host.consumed(1000);
+    let update = host.credit_update_due().expect("all of it was consumed");
+    assert_eq!(update.op, Op::CreditUpdate);
+    assert_eq!(guest.receive(&update), Ok(Event::Credit));
+    assert_eq!(guest.window(), 1000);
+}
+
+#[test]
+fn a_credit_update_waits_for_half_the_buffer() {
+    let (mut guest, mut host) = pair(4096, 1000);
+    let data = guest.send(1000).unwrap();
+    host.receive(&data).unwrap();
+    host.consumed(499);
+    assert_eq!(host.credit_update_due(), None);
+    host.consumed(1);
+    assert!(host.credit_update_due().is_some());
+    assert_eq!(host.credit_update_due(), None, "the update just sent made it current");
+}
+
+#[test]
+fn consuming_more_than_arrived_opens_nothing() {
+    let (mut guest, mut host) = pair(4096, 1000);
+    host.receive(&guest.send(10).unwrap()).unwrap();
+    host.consumed(500);
+    assert_eq!(host.unread(), 0);
+    let update = host.credit_update();
+    assert_eq!(update.fwd_cnt, 10);
+}
+
+#[test]
+fn sending_past_the_advertised_buffer_is_an_overrun() {
+    let (_, mut host) = pair(4096, 100);
+    let mut forged = Header { src: GUEST, dst: HOST, len: 101, op: Op::Rw, flags: 0, buf_alloc: 4096, fwd_cnt: 0 };
+    assert_eq!(host.receive(&forged), Err(Violation::Overrun { unread: 101, buf_alloc: 100 }));
+    forged.len = 100;
+    assert_eq!(host.receive(&forged), Ok(Event::Data(100)));
+    forged.len = 1;
+    assert_eq!(host.receive(&forged), Err(Violation::Overrun { unread: 101, buf_alloc: 100 }));
+}
+
+#[test]
+fn a_forged_fwd_cnt_opens_no_window() {
+    let (mut guest, _) = pair(4096, 100);
+    let lie = Header { src: HOST, dst: GUEST, len: 0, op: Op::CreditUpdate, flags: 0, buf_alloc: 100, fwd_cnt: 50 };
+    assert_eq!(guest.receive(&lie), Ok(Event::Credit));
+    assert_eq!(guest.window(), 0, "50 consumed of nothing sent is not room for 150");
+}
+
+#[test]
+fn counters_wrap_together() {
+    let (mut guest, mut host) = pair(u32::MAX, u32::MAX);
+    for _ in 0..5 {
+        let data = guest.send(1 << 30).unwrap();
+        host.receive(&data).unwrap();
+        host.consumed(1 << 30);
+        guest.receive(&host.credit_update()).unwrap();
+    }
+    assert_eq!(guest.window(), u32::MAX);
+    assert_eq!(host.unread(), 0);
+}
+
+#[test]
+fn shutdown_and_reset() {
+    let (mut guest, mut host) = pair(4096, 4096);
+    let fin = guest.shutdown(SHUTDOWN_SEND);
+    assert_eq!(guest.window(), 0);
+    assert_eq!(host.receive(&fin), Ok(Event::Shutdown(SHUTDOWN_SEND)));
+    assert!(host.peer_done_sending());
+    assert!(!host.finished());
+    let data = Header { src: GUEST, dst: HOST, len: 1, op: Op::Rw, flags: 0, buf_alloc: 4096, fwd_cnt: 0 };
+    assert_eq!(host.receive(&data), Err(Violation::AfterShutdown));
+
+    let fin = host.shutdown(SHUTDOWN_SEND | SHUTDOWN_RCV);
+    assert_eq!(fin.flags, SHUTDOWN_SEND | SHUTDOWN_RCV);
+    assert!(host.finished());
+    assert_eq!(guest.receive(&fin), Ok(Event::Shutdown(SHUTDOWN_SEND | SHUTDOWN_RCV)));
+    assert!(guest.finished());
+
+    let rst = host.reset();
+    assert_eq!(guest.receive(&rst), Ok(Event::Reset));
+    assert_eq!(guest.state(), State::Closed);
+    assert_eq!(guest.receive(&rst), Err(Violation::Closed));
+}
+
+#[test]
+fn out_of_state_operations_are_violations() {
+    let (mut connecting, _) = Stream::connect(GUEST, HOST, 4096);
+    let early = Header { src: HOST, dst: GUEST, len: 1, op: Op::Rw, flags: 0, buf_alloc: 4096, fwd_cnt: 0 };
+    assert_eq!(connecting.receive(&early), Err(Violation::Unexpected(Op::Rw)));
+
+    let (mut guest, _) = pair(4096, 4096);
+    let again = Header { op: Op::Response, len: 0, ..early };
+    assert_eq!(guest.receive(&again), Err(Violation::Unexpected(Op::Response)));
+}
@@ -19,7 +19,7 @@ use toyos_xhci::port::{Flaw, GaveUp, Gone, Nanos, PortState, Reset, Step};
 use toyos_xhci::recovery::{Act, EndpointState, NeedsConfigure, Recovery};
 use toyos_xhci::Protocol;
 
-use crate::hub::FakePort;
+use crate::hub::Port;
 
 /// An effect the driver performed on the *port*, in the order it performed
 /// them. The slot, the pool block and the endpoint are asked about separately,
@@ -309,7 +309,7 @@ impl Driver {
 
     /// Everything the driver has to do at `now`, with every step checked
     /// against the word that produced it.
-    pub fn pump(&mut self, port: &mut FakePort, now: Nanos) -> Result<(), Stuck> {
+    pub fn pump(&mut self, port: &mut impl Port, now: Nanos) -> Result<(), Stuck> {
         port.tick(now);
         self.collect(now);
         self.advance(now)?;
@@ -346,10 +346,10 @@ impl Driver {
                     self.wake_at = self.outstanding.wake_at();
                     return Ok(());
                 }
-                Step::Write(write) => port.write(write.raw(), now),
+                Step::Write(write) => port.write(write, now),
                 Step::Reset(kind, write) => {
                     self.did.push(Did::Reset(kind));
-                    port.write(write.raw(), now);
+                    port.write(write, now);
                 }
                 Step::Teardown(why, pending) => {
                     if let Some(at) = busy {
@@ -515,7 +515,7 @@ impl Driver {
     /// is left. Only the configuration descriptor carries any.
     fn learnt(&self, act: enumerate::Act) -> Learnt {
         match act {
-            enumerate::Act::Request(Request::ConfigDescriptor) => Learnt::Function(self.function),
+            enumerate::Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(self.function),
             _ => Learnt::Nothing,
         }
     }
@@ -536,7 +536,7 @@ impl Driver {
 
     /// Start the bound endpoint's recovery, if one is owed and the controller
     /// is not already answering something else.
-    fn recover(&mut self, port: &FakePort, now: Nanos) {
+    fn recover(&mut self, port: &impl Port, now: Nanos) {
         if !self.broke || self.outstanding.busy() {
             return;
         }
@@ -625,7 +625,7 @@ impl Driver {
     /// is what a scheduler pass on a busy machine looks like.
     pub fn run_to(
         &mut self,
-        port: &mut FakePort,
+        port: &mut impl Port,
         from: Nanos,
         deadline: Nanos,
         step: Nanos,
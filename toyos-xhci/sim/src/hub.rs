@@ -7,10 +7,24 @@
 //! is set by a reset that finds a device and cleared by a write of '1'. The
 //! last of those is what QEMU does not implement and what disabled every port
 //! on the T14.
+//!
+//! [`FakeHubPort`] is the same for a port on an external hub, where the rules
+//! are USB 2.0 §11.24.2's instead: the driver never writes a register at all,
+//! it asks, and what it reads is the hub's last answer.
 
+use toyos_xhci::hub::{self as class, Feature, PortStatus};
 use toyos_xhci::port::Nanos;
+use toyos_xhci::portsc::Write;
 use toyos_xhci::Portsc;
 
+/// Anything the driver loop can drive a port machine against: a root port's
+/// register or a hub's port behind its requests.
+pub trait Port {
+    fn read(&self) -> Portsc;
+    fn write(&mut self, write: Write, now: Nanos);
+    fn tick(&mut self, now: Nanos);
+}
+
 const CCS: u32 = 1 << 0;
 const PED: u32 = 1 << 1;
 const PR: u32 = 1 << 4;
@@ -190,3 +204,166 @@ impl FakePort {
         self.raw = (self.raw & !(0xF << PLS_SHIFT)) | (pls << PLS_SHIFT);
     }
 }
+
+impl Port for FakePort {
+    fn read(&self) -> Portsc {
+        FakePort::read(self)
+    }
+
+    fn write(&mut self, write: Write, now: Nanos) {
+        FakePort::write(self, write.raw(), now)
+    }
+
+    fn tick(&mut self, now: Nanos) {
+        FakePort::tick(self, now)
+    }
+}
+
+/// A downstream port of an external hub, driven through the requests
+/// [`class::features`] makes of the machine's writes.
+///
+/// The status is the hub's: wPortStatus and wPortChange, with every change
+/// flag cleared only by its own CLEAR_FEATURE and a reset started only by
+/// SET_FEATURE. A request the translation got wrong leaves a change set that
+/// the machine reads again on the next pass, which is how a crossed selector
+/// shows up here as a port that never comes to rest.
+pub struct FakeHubPort {
+    status: PortStatus,
+    superspeed: bool,
+    behaviour: ResetBehaviour,
+    resetting_since: Option<Nanos>,
+    /// The hub itself has left the bus. What the port reads is then the
+    /// driver's own last answer, marked disconnected, and nothing is asked of
+    /// anybody — which is what the kernel does with a hub's ports when the
+    /// hub's own slot is torn down.
+    orphaned: bool,
+    /// Every request the driver made of the hub, in order.
+    pub requests: Vec<Feature>,
+}
+
+impl FakeHubPort {
+    /// A powered port with nothing in it: a USB 2.0 hub's, which every hub
+    /// this driver has met powers its ports for once the driver has asked.
+    pub fn empty(behaviour: ResetBehaviour) -> Self {
+        Self {
+            status: PortStatus { status: class::PORT_POWER_USB2, change: 0 },
+            superspeed: false,
+            behaviour,
+            resetting_since: None,
+            orphaned: false,
+            requests: Vec::new(),
+        }
+    }
+
+    pub fn occupied(behaviour: ResetBehaviour) -> Self {
+        let mut port = Self::empty(behaviour);
+        port.attach();
+        port
+    }
+
+    /// A SuperSpeed hub's port, whose link trains itself as a root port's does.
+    pub fn superspeed(behaviour: ResetBehaviour) -> Self {
+        Self {
+            status: PortStatus { status: class::PORT_POWER_USB3 | (5 << 5), change: 0 },
+            superspeed: true,
+            ..Self::empty(behaviour)
+        }
+    }
+
+    pub fn attach(&mut self) {
+        if self.orphaned || self.status.status & class::PORT_CONNECTION != 0 {
+            return;
+        }
+        self.status.status |= class::PORT_CONNECTION;
+        self.status.change |= class::C_PORT_CONNECTION;
+        if self.superspeed {
+            self.status.status =
+                (self.status.status & !class::PORT_LINK_STATE_USB3) | class::PORT_ENABLE;
+        }
+    }
+
+    pub fn detach(&mut self) {
+        if self.orphaned || self.status.status & class::PORT_CONNECTION == 0 {
+            return;
+        }
+        self.status.status &= !(class::PORT_CONNECTION | class::PORT_ENABLE | class::PORT_RESET);
+        self.status.change |= class::C_PORT_CONNECTION;
+        self.resetting_since = None;
+    }
+
+    pub fn replug(&mut self) {
+        self.detach();
+        self.attach();
+    }
+
+    /// The hub is pulled, with this port's device still in it.
+    pub fn hub_gone(&mut self) {
+        self.orphaned = true;
+        self.status = PortStatus::GONE;
+        self.resetting_since = None;
+    }
+
+    /// A request as the hub performs it.
+    fn perform(&mut self, feature: Feature, now: Nanos) {
+        let connected = self.status.status & class::PORT_CONNECTION != 0;
+        match feature {
+            Feature::Clear(selector) => {
+                if let Some(bit) = class::cleared_by(selector, self.superspeed) {
+                    self.status.change &= !bit;
+                }
+            }
+            Feature::Set(class::F_PORT_POWER) => {
+                let bit = if self.superspeed { class::PORT_POWER_USB3 } else { class::PORT_POWER_USB2 };
+                self.status.status |= bit;
+            }
+            Feature::Set(class::F_PORT_RESET | class::F_BH_PORT_RESET) if connected => {
+                if self.status.status & class::PORT_RESET == 0 {
+                    self.resetting_since = Some(now);
+                }
+                self.status.status = (self.status.status | class::PORT_RESET) & !class::PORT_ENABLE;
+            }
+            Feature::Set(_) => {}
+        }
+    }
+}
+
+impl Port for FakeHubPort {
+    fn read(&self) -> Portsc {
+        self.status.portsc(self.superspeed)
+    }
+
+    fn write(&mut self, write: Write, now: Nanos) {
+        let features = class::features(write, self.status, self.superspeed);
+        for &feature in features.as_slice() {
+            if self.orphaned {
+                // Nobody to ask. The clears are applied to the last answer so
+                // the machine's acknowledges land, and nothing else happens.
+                if let Feature::Clear(selector) = feature {
+                    if let Some(bit) = class::cleared_by(selector, self.superspeed) {
+                        self.status.change &= !bit;
+                    }
+                }
+                continue;
+            }
+            self.requests.push(feature);
+            self.perform(feature, now);
+        }
+    }
+
+    fn tick(&mut self, now: Nanos) {
+        let Some(since) = self.resetting_since else { return };
+        let after = match self.behaviour {
+            ResetBehaviour::Completes { after } => after,
+            // A hub port has no link of its own to kill: the hub's port is the
+            // other end of it, and it answers for the reset either way.
+            ResetBehaviour::HotResetKillsTheLink { .. } => 1_000_000,
+            ResetBehaviour::Never => return,
+        };
+        if now.saturating_sub(since) < after {
+            return;
+        }
+        self.resetting_since = None;
+        self.status.status = (self.status.status & !class::PORT_RESET) | class::PORT_ENABLE;
+        self.status.change |= class::C_PORT_RESET;
+    }
+}
@@ -64,7 +64,7 @@ const BOOT_HID: [Act; 8] = [
     Act::Command(Command::AddressDevice),
     Act::Request(Request::DeviceDescriptor { want: 8 }),
     Act::Request(Request::DeviceDescriptor { want: 18 }),
-    Act::Request(Request::ConfigDescriptor),
+    Act::Request(Request::ConfigDescriptor { index: 0 }),
     Act::Request(Request::SetConfiguration),
     Act::Request(Request::SetProtocol),
     Act::Command(Command::ConfigureEndpoint),
@@ -0,0 +1,138 @@
+//! The port machine on a hub's downstream port, where every write is a request
+//! and every read is the hub's last answer.
+//!
+//! The machine is the root port's, unchanged; what is under test is that the
+//! translation both ways leaves it deciding the same things. **A crossed
+//! selector does not fail loudly** — it clears one change and leaves another
+//! set, and the port then reads a change on every pass forever — so these
+//! assert both where the port ended up and what the hub was asked.
+
+use toyos_xhci::enumerate::{self, Function};
+use toyos_xhci::hub::{
+    Feature, F_C_PORT_CONNECTION, F_C_PORT_RESET, F_PORT_POWER, F_PORT_RESET,
+};
+use toyos_xhci::port::{GaveUp, Gone, Nanos, Reset, DEBOUNCE_NS, RESET_DEADLINE_NS};
+use toyos_xhci::Protocol;
+use toyos_xhci_sim::driver::{Did, Driver};
+use toyos_xhci_sim::hub::{FakeHubPort, ResetBehaviour};
+
+const QUICK: ResetBehaviour = ResetBehaviour::Completes { after: 1_000_000 };
+const PASS: Nanos = 1_000_000;
+const SETTLED: Nanos = 4 * DEBOUNCE_NS;
+
+/// A keyboard on port 1 of a USB 2.0 hub, enumerated and settled.
+fn bound() -> (FakeHubPort, Driver) {
+    let mut port = FakeHubPort::occupied(QUICK);
+    let mut driver = Driver::new();
+    driver.run_to(&mut port, 0, SETTLED, PASS).unwrap();
+    assert_eq!(driver.enumerations(), 1, "{:?}", driver.did);
+    (port, driver)
+}
+
+/// The fresh attach, as the hub sees it: the connect change cleared, one
+/// PORT_RESET, its change cleared, and nothing else — in particular no
+/// PORT_POWER for a port that already has it, which is what a neutral write
+/// carrying PP back would otherwise ask for on every acknowledge.
+#[test]
+fn a_device_behind_a_hub_is_reset_once_and_enumerated() {
+    let (port, driver) = bound();
+    assert_eq!(driver.resets(), [Reset::Hot]);
+    assert_eq!(
+        port.requests,
+        [
+            Feature::Clear(F_C_PORT_CONNECTION),
+            Feature::Set(F_PORT_RESET),
+            Feature::Clear(F_C_PORT_RESET),
+        ]
+    );
+    assert!(!port.requests.contains(&Feature::Set(F_PORT_POWER)));
+    assert!(driver.attached());
+}
+
+/// What enumerates behind a hub may be a hub, and its sequence owes the
+/// descriptor its slot context is built from.
+#[test]
+fn a_hub_behind_a_hub_is_described_before_its_endpoints() {
+    let mut port = FakeHubPort::occupied(QUICK);
+    let mut driver = Driver::new().presenting(Function::Hub);
+    driver.run_to(&mut port, 0, SETTLED, PASS).unwrap();
+    assert_eq!(driver.enumerations(), 1);
+    let n = driver.acts.len();
+    assert_eq!(driver.acts[n - 2..], [
+        enumerate::Act::Request(enumerate::Request::HubDescriptor),
+        enumerate::Act::Command(enumerate::Command::ConfigureEndpoint),
+    ]);
+}
+
+#[test]
+fn a_device_pulled_from_a_hub_is_torn_down_and_its_slot_given_back() {
+    let (mut port, mut driver) = bound();
+    port.detach();
+    driver.run_to(&mut port, SETTLED, SETTLED + 4 * DEBOUNCE_NS, PASS).unwrap();
+    assert_eq!(driver.did.last(), Some(&Did::ToreDown(Gone::Disconnected)));
+    assert_eq!(driver.disabled, 1);
+    assert!(!driver.block_held());
+    assert!(!driver.attached());
+}
+
+/// The hub leaving takes its devices with it. Its ports read the connect change
+/// the kernel leaves in their last answer, the machine tears each device down
+/// the way it would a pulled one, and **nothing is asked of the hub that is no
+/// longer there** — a request to a slot being disabled is a transfer that can
+/// only time out.
+#[test]
+fn a_hub_that_leaves_takes_its_devices_with_it_and_is_asked_nothing() {
+    let (mut port, mut driver) = bound();
+    let asked = port.requests.len();
+    port.hub_gone();
+    driver.run_to(&mut port, SETTLED, SETTLED + 4 * DEBOUNCE_NS, PASS).unwrap();
+    assert_eq!(driver.did.last(), Some(&Did::ToreDown(Gone::Disconnected)));
+    assert_eq!(driver.disabled, 1);
+    assert!(!driver.attached());
+    assert_eq!(port.requests.len(), asked, "{:?}", &port.requests[asked..]);
+    // And the port comes to rest: the orphan's own acknowledge cleared the
+    // change, so nothing is left to decide about.
+    let before = driver.did.len();
+    driver.run_to(&mut port, SETTLED + 4 * DEBOUNCE_NS, SETTLED + 8 * DEBOUNCE_NS, PASS).unwrap();
+    assert_eq!(driver.did.len(), before);
+}
+
+/// Pulled and pushed back between two interrupt transfers: the hub reports a
+/// connection that reads the same and a change that says otherwise, and the
+/// old device is torn down before the new one is enumerated.
+#[test]
+fn a_replug_below_a_hub_is_two_devices_and_not_one() {
+    let (mut port, mut driver) = bound();
+    port.replug();
+    driver.run_to(&mut port, SETTLED, SETTLED + 4 * DEBOUNCE_NS, PASS).unwrap();
+    assert_eq!(driver.teardowns(), 1, "{:?}", driver.did);
+    assert_eq!(driver.enumerations(), 2, "{:?}", driver.did);
+    assert_eq!(driver.disabled, 1);
+    assert!(driver.attached());
+}
+
+/// A hub port whose reset never finishes is given up on like a root port's,
+/// rather than polled with GET_STATUS for the life of the boot.
+#[test]
+fn a_hub_port_whose_reset_never_finishes_is_given_up_on() {
+    let mut port = FakeHubPort::occupied(ResetBehaviour::Never);
+    let mut driver = Driver::new();
+    driver.run_to(&mut port, 0, DEBOUNCE_NS + 2 * RESET_DEADLINE_NS, PASS).unwrap();
+    assert_eq!(driver.enumerations(), 0);
+    assert!(driver.did.contains(&Did::GaveUp(GaveUp::ResetNeverFinished(Reset::Hot))), "{:?}", driver.did);
+    let resets = port.requests.iter().filter(|f| **f == Feature::Set(F_PORT_RESET)).count();
+    assert_eq!(resets, 1, "{:?}", port.requests);
+}
+
+/// A SuperSpeed hub's port trains its own link, as a root port of that kind
+/// does, and is enumerated without a reset anybody asked for.
+#[test]
+fn a_superspeed_hub_port_that_trained_is_not_reset() {
+    let mut port = FakeHubPort::superspeed(QUICK);
+    port.attach();
+    let mut driver = Driver::new().speaking(Protocol::Usb3);
+    driver.run_to(&mut port, 0, SETTLED, PASS).unwrap();
+    assert_eq!(driver.enumerations(), 1, "{:?}", driver.did);
+    assert_eq!(driver.resets(), []);
+    assert_eq!(port.requests, [Feature::Clear(F_C_PORT_CONNECTION)]);
+}
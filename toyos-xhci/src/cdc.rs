@@ -0,0 +1,656 @@
+//! A USB Ethernet adapter's class protocol: what its descriptors say, what its
+//! interrupt endpoint reports, and how a frame is carried over its bulk pipes.
+//!
+//! Two subclasses of the Communications class are served. **ECM** (CDC ECM
+//! 1.2) moves one Ethernet frame per bulk transfer, which is what QEMU's
+//! `usb-net` and most older dongles speak. **NCM** (CDC NCM 1.0) wraps frames in
+//! a Transfer Block with a header and a datagram table, which is what most
+//! gigabit dongles speak; only its 16-bit block format is used, since NCM 1.0
+//! §3.2 makes that the one every device has.
+//!
+//! Both put the station address in a string descriptor, both report the link on
+//! the communication interface's interrupt endpoint with the same two
+//! notifications, and both put their bulk endpoints in alternate setting 1 of a
+//! data interface whose setting 0 has none. Every number decoded here is the
+//! device's, so every decode says what it refused rather than reading past it.
+
+/// bInterfaceClass of a CDC communication interface, and the two subclasses
+/// this driver binds.
+pub const CLASS_COMM: u8 = 0x02;
+pub const SUBCLASS_ECM: u8 = 0x06;
+pub const SUBCLASS_NCM: u8 = 0x0D;
+/// bInterfaceClass of the data interface the bulk endpoints are on.
+pub const CLASS_DATA: u8 = 0x0A;
+
+/// A class-specific interface descriptor, which is what every functional
+/// descriptor is (CDC 1.2 §5.2.3).
+pub const CS_INTERFACE: u8 = 0x24;
+/// The functional descriptors read here, by bDescriptorSubtype.
+pub const FUNC_UNION: u8 = 0x06;
+pub const FUNC_ETHERNET: u8 = 0x0F;
+
+/// The longest frame either subclass carries here: a standard Ethernet frame
+/// without its FCS, which both strip.
+pub const MAX_FRAME: usize = 1514;
+/// The shortest: an Ethernet header.
+pub const MIN_FRAME: usize = 14;
+
+/// The Ethernet Networking functional descriptor's two fields this driver
+/// uses (CDC ECM 1.2 §5.4).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Ethernet {
+    /// The string descriptor index of the station address.
+    pub mac_string: u8,
+    /// The largest frame the device will send or accept, header included.
+    pub max_segment: u16,
+}
+
+impl Ethernet {
+    /// Decode one functional descriptor, or `None` for a different subtype or
+    /// one too short to carry the fields.
+    ///
+    /// A zero string index is refused here: it is the index of the language
+    /// table and never of an address, so a device stating it has none.
+    pub fn parse(desc: &[u8]) -> Option<Self> {
+        if desc.len() < 13 || desc[1] != CS_INTERFACE || desc[2] != FUNC_ETHERNET || desc[3] == 0 {
+            return None;
+        }
+        Some(Self { mac_string: desc[3], max_segment: u16::from_le_bytes([desc[8], desc[9]]) })
+    }
+}
+
+/// The Union functional descriptor's first subordinate: the data interface
+/// that belongs to this communication interface (CDC 1.2 §5.2.3.2).
+pub fn union_data(desc: &[u8]) -> Option<u8> {
+    (desc.len() >= 5 && desc[1] == CS_INTERFACE && desc[2] == FUNC_UNION).then(|| desc[4])
+}
+
+/// GET_DESCRIPTOR(String) asks in one language, and US English is the one
+/// every adapter answers in. A device with no US English string table has no
+/// address this driver can read, which is the same answer as a bad one.
+pub const LANG_EN_US: u16 = 0x0409;
+
+/// The string descriptor is twelve UTF-16 hex digits after its two-byte header.
+pub const MAC_STRING_BYTES: u16 = 2 + 12 * 2;
+
+/// Why a string descriptor is not a station address.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BadMac {
+    /// Not a string descriptor, or fewer than twelve characters.
+    Short(usize),
+    /// A character that is not a hex digit, at this position.
+    NotHex(usize),
+    /// The group bit is set, so it names a multicast group and not a station.
+    Multicast([u8; 6]),
+    /// All zeros, which no interface is.
+    Zero,
+}
+
+impl core::fmt::Display for BadMac {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Short(n) => write!(f, "its address string is {n} bytes, not twelve digits"),
+            Self::NotHex(at) => write!(f, "character {at} of its address string is not a hex digit"),
+            Self::Multicast(m) => write!(
+                f,
+                "its address {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} is a multicast one",
+                m[0], m[1], m[2], m[3], m[4], m[5]
+            ),
+            Self::Zero => f.write_str("its address is all zeros"),
+        }
+    }
+}
+
+/// The station address out of the string descriptor `iMACAddress` names:
+/// `bLength`, `bDescriptorType` 3, then the digits most significant first.
+pub fn mac_from_string(desc: &[u8]) -> Result<[u8; 6], BadMac> {
+    let stated = desc.first().copied().unwrap_or(0) as usize;
+    let len = stated.min(desc.len());
+    if len < MAC_STRING_BYTES as usize || desc[1] != 3 {
+        return Err(BadMac::Short(len));
+    }
+    let mut mac = [0u8; 6];
+    for at in 0..12 {
+        let unit = u16::from_le_bytes([desc[2 + at * 2], desc[3 + at * 2]]);
+        let digit = char::from_u32(unit as u32)
+            .and_then(|c| c.to_digit(16))
+            .ok_or(BadMac::NotHex(at))?;
+        mac[at / 2] = mac[at / 2] << 4 | digit as u8;
+    }
+    if mac == [0; 6] {
+        return Err(BadMac::Zero);
+    }
+    if mac[0] & 1 != 0 {
+        return Err(BadMac::Multicast(mac));
+    }
+    Ok(mac)
+}
+
+/// A control request on the communication interface, as the setup packet's
+/// fields and what the data stage carries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Setup {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl Setup {
+    /// GET_DESCRIPTOR(String) for the address.
+    pub const fn mac_string(index: u8) -> Self {
+        Self {
+            request_type: 0x80,
+            request: 6,
+            value: 0x0300 | index as u16,
+            index: LANG_EN_US,
+            length: MAC_STRING_BYTES,
+        }
+    }
+
+    /// SET_NTB_INPUT_SIZE on `iface`, whose four-byte data stage is
+    /// [`ntb::IN_SIZE`] little-endian (NCM 1.0 §6.2.7).
+    pub const fn ntb_input_size(iface: u8) -> Self {
+        Self { request_type: 0x21, request: 0x86, value: 0, index: iface as u16, length: 4 }
+    }
+}
+
+/// What the interrupt endpoint said.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Notification {
+    /// NETWORK_CONNECTION: the cable, or whatever stands for it, is connected
+    /// or not (CDC 1.2 §6.3.1).
+    Connection(bool),
+    /// CONNECTION_SPEED_CHANGE, in bits per second each way (§6.3.3).
+    Speed { down: u32, up: u32 },
+    /// A notification these two subclasses may send and this driver has no
+    /// use for, by its code.
+    Other(u8),
+}
+
+/// Why eight or sixteen bytes off the interrupt endpoint are not a
+/// notification.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BadNotification {
+    /// Shorter than the header, or than the data the header states.
+    Short(usize),
+    /// A request type other than class, interface, device-to-host.
+    NotClass(u8),
+}
+
+impl core::fmt::Display for BadNotification {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Short(n) => write!(f, "{n} bytes is shorter than the notification it starts"),
+            Self::NotClass(t) => write!(f, "request type {t:#04x} is not a class notification"),
+        }
+    }
+}
+
+/// The header's bmRequestType for every CDC notification.
+const NOTIFICATION_TYPE: u8 = 0xA1;
+const NETWORK_CONNECTION: u8 = 0x00;
+const CONNECTION_SPEED_CHANGE: u8 = 0x2A;
+
+/// The largest notification decoded: the speed change, whose eight bytes of
+/// data follow the eight of the header. The interrupt transfer is this long.
+pub const NOTIFICATION_BYTES: usize = 16;
+
+impl Notification {
+    /// Decode what one interrupt transfer delivered. `bytes` is as long as
+    /// what arrived, not as the buffer.
+    pub fn parse(bytes: &[u8]) -> Result<Self, BadNotification> {
+        if bytes.len() < 8 {
+            return Err(BadNotification::Short(bytes.len()));
+        }
+        if bytes[0] != NOTIFICATION_TYPE {
+            return Err(BadNotification::NotClass(bytes[0]));
+        }
+        let value = u16::from_le_bytes([bytes[2], bytes[3]]);
+        match bytes[1] {
+            NETWORK_CONNECTION => Ok(Self::Connection(value != 0)),
+            CONNECTION_SPEED_CHANGE => {
+                let data = bytes.get(8..16).ok_or(BadNotification::Short(bytes.len()))?;
+                let word = |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+                Ok(Self::Speed { down: word(0), up: word(4) })
+            }
+            code => Ok(Self::Other(code)),
+        }
+    }
+}
+
+/// Whether a bulk OUT transfer of `len` bytes has to be followed by a zero-length
+/// one for the device to see where it ends.
+///
+/// A transfer ends at a packet shorter than the endpoint's maximum, so one that
+/// is an exact multiple of it has not ended until a short packet arrives — and
+/// the next frame would be read as its continuation (CDC ECM 1.2 §3.3.1, NCM
+/// 1.0 §3.2.2).
+pub fn needs_zero_length(len: usize, max_packet: u16) -> bool {
+    max_packet != 0 && len != 0 && len.is_multiple_of(max_packet as usize)
+}
+
+/// Why one ECM bulk IN transfer is not a frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BadFrame {
+    Runt(usize),
+    Overlong(usize),
+}
+
+impl core::fmt::Display for BadFrame {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Runt(n) => write!(f, "{n} bytes is shorter than an Ethernet header"),
+            Self::Overlong(n) => write!(f, "{n} bytes is longer than an Ethernet frame"),
+        }
+    }
+}
+
+/// An ECM transfer is one frame, and this says whether it is one.
+pub fn ecm_frame(len: usize) -> Result<usize, BadFrame> {
+    match len {
+        n if n < MIN_FRAME => Err(BadFrame::Runt(n)),
+        n if n > MAX_FRAME => Err(BadFrame::Overlong(n)),
+        n => Ok(n),
+    }
+}
+
+/// NCM's 16-bit Transfer Block: a header (NTH16), a datagram pointer table
+/// (NDP16), and the frames (NCM 1.0 §3.2–3.3).
+pub mod ntb {
+    use core::ops::Range;
+
+    use super::{MAX_FRAME, MIN_FRAME};
+
+    /// "NCMH" and "NCM0" (no CRC) / "NCM1" (CRC appended), little-endian.
+    const NTH16_SIGNATURE: u32 = 0x484D_434E;
+    const NDP16_NO_CRC: u32 = 0x304D_434E;
+    const NDP16_CRC: u32 = 0x314D_434E;
+    const NTH16_BYTES: usize = 12;
+    const NDP16_HEADER: usize = 8;
+
+    /// The block size the host asks the device to send at most, and the size
+    /// of each buffer a bulk IN transfer lands in. NCM 1.0 §6.2.7 makes 2048
+    /// the least a device accepts; four frames' worth is what a burst of
+    /// small packets fills without the device having to close every block at
+    /// one frame.
+    pub const IN_SIZE: u32 = 8192;
+
+    /// What the driver writes in front of a frame to send it: one NTH16 and one
+    /// NDP16 with a single entry and its terminator. 28 bytes, which keeps the
+    /// datagram at the four-byte alignment every device's `wNdpOutDivisor`
+    /// allows.
+    pub const TX_HEADER: usize = NTH16_BYTES + NDP16_HEADER + 2 * 4;
+
+    /// The header for one frame of `frame_len` bytes at [`TX_HEADER`], with
+    /// sequence number `seq`.
+    pub fn header(seq: u16, frame_len: u16) -> [u8; TX_HEADER] {
+        let mut h = [0u8; TX_HEADER];
+        let block = (TX_HEADER as u16).saturating_add(frame_len);
+        h[0..4].copy_from_slice(&NTH16_SIGNATURE.to_le_bytes());
+        h[4..6].copy_from_slice(&(NTH16_BYTES as u16).to_le_bytes());
+        h[6..8].copy_from_slice(&seq.to_le_bytes());
+        h[8..10].copy_from_slice(&block.to_le_bytes());
+        h[10..12].copy_from_slice(&(NTH16_BYTES as u16).to_le_bytes());
+        h[12..16].copy_from_slice(&NDP16_NO_CRC.to_le_bytes());
+        h[16..18].copy_from_slice(&((NDP16_HEADER + 2 * 4) as u16).to_le_bytes());
+        // wNextNdpIndex 0: this is the only table.
+        h[20..22].copy_from_slice(&(TX_HEADER as u16).to_le_bytes());
+        h[22..24].copy_from_slice(&frame_len.to_le_bytes());
+        // The terminating entry is the zeroes already there.
+        h
+    }
+
+    /// Why a received block, or one datagram in it, was refused.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Malformed {
+        /// The block header is not an NTH16, or states a block longer than
+        /// what arrived.
+        Header,
+        /// A table pointer lands outside the block, or on something that is
+        /// not an NDP16.
+        Table(usize),
+        /// A datagram's range is outside the block, or not a frame's length.
+        Datagram { index: usize, len: usize },
+        /// A table names one at or before itself as the next. Every honest
+        /// chain runs forwards through the block, and one that does not would
+        /// deliver the same frames again on every lap.
+        Chain,
+    }
+
+    impl core::fmt::Display for Malformed {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                Self::Header => f.write_str("its block header is not an NTH16 that fits"),
+                Self::Table(at) => write!(f, "its datagram table at {at} is not one that fits"),
+                Self::Datagram { index, len } => {
+                    write!(f, "a datagram of {len} bytes at {index} is not a frame inside it")
+                }
+                Self::Chain => f.write_str("its datagram tables chain backwards"),
+            }
+        }
+    }
+
+    fn le16(b: &[u8], at: usize) -> Option<usize> {
+        Some(u16::from_le_bytes([*b.get(at)?, *b.get(at + 1)?]) as usize)
+    }
+
+    fn le32(b: &[u8], at: usize) -> Option<u32> {
+        Some(u32::from_le_bytes([*b.get(at)?, *b.get(at + 1)?, *b.get(at + 2)?, *b.get(at + 3)?]))
+    }
+
+    /// The frames in one received block, in table order, each as the byte range
+    /// it occupies. `block` is what the transfer delivered.
+    ///
+    /// An `Err` ends the walk: a table that lies about one datagram is not
+    /// believed about the rest, and the frames before it were already yielded.
+    pub fn datagrams(block: &[u8]) -> Datagrams<'_> {
+        let start = match (le32(block, 0), le16(block, 4), le16(block, 8), le16(block, 10)) {
+            (Some(NTH16_SIGNATURE), Some(NTH16_BYTES), Some(len), Some(ndp))
+                if len >= NTH16_BYTES && len <= block.len() =>
+            {
+                Ok((len, ndp))
+            }
+            _ => Err(Malformed::Header),
+        };
+        match start {
+            Ok((len, ndp)) => Datagrams { block: &block[..len], table: Some(ndp), entry: 0, failed: None },
+            Err(why) => Datagrams { block, table: None, entry: 0, failed: Some(why) },
+        }
+    }
+
+    /// See [`datagrams`].
+    pub struct Datagrams<'a> {
+        block: &'a [u8],
+        /// The table being read, and the entry in it next.
+        table: Option<usize>,
+        entry: usize,
+        failed: Option<Malformed>,
+    }
+
+    impl Datagrams<'_> {
+        /// The table at `at`, as where its entries start and how many there are
+        /// room for, and where the next table is.
+        fn table(&self, at: usize) -> Result<(usize, usize, usize), Malformed> {
+            let sig = le32(self.block, at).ok_or(Malformed::Table(at))?;
+            let len = le16(self.block, at + 4).ok_or(Malformed::Table(at))?;
+            let next = le16(self.block, at + 6).ok_or(Malformed::Table(at))?;
+            if sig != NDP16_NO_CRC && sig != NDP16_CRC {
+                return Err(Malformed::Table(at));
+            }
+            if at < NTH16_BYTES || len < NDP16_HEADER + 2 * 4 || !len.is_multiple_of(4) || at + len > self.block.len() {
+                return Err(Malformed::Table(at));
+            }
+            Ok((at + NDP16_HEADER, (len - NDP16_HEADER) / 4, next))
+        }
+    }
+
+    impl Iterator for Datagrams<'_> {
+        type Item = Result<Range<usize>, Malformed>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if let Some(why) = self.failed.take() {
+                self.table = None;
+                return Some(Err(why));
+            }
+            loop {
+                let at = self.table?;
+                let (entries, room, next) = match self.table(at) {
+                    Ok(t) => t,
+                    Err(why) => {
+                        self.table = None;
+                        return Some(Err(why));
+                    }
+                };
+                // A table with no terminator inside it ends at its own end.
+                let entry = (self.entry < room).then(|| entries + self.entry * 4);
+                let pair = entry.and_then(|e| Some((le16(self.block, e)?, le16(self.block, e + 2)?)));
+                match pair {
+                    Some((index, len)) if index != 0 && len != 0 => {
+                        self.entry += 1;
+                        // The CRC variant's frames carry four trailing bytes
+                        // of it, which the entry counts and the frame does
+                        // not. Not checked: the link already did.
+                        let crc = le32(self.block, at) == Some(NDP16_CRC);
+                        let frame = if crc { len.saturating_sub(4) } else { len };
+                        if index + len > self.block.len() || !(MIN_FRAME..=MAX_FRAME).contains(&frame) {
+                            self.table = None;
+                            return Some(Err(Malformed::Datagram { index, len }));
+                        }
+                        return Some(Ok(index..index + frame));
+                    }
+                    _ => {
+                        if next == 0 {
+                            self.table = None;
+                            return None;
+                        }
+                        if next <= at {
+                            self.table = None;
+                            return Some(Err(Malformed::Chain));
+                        }
+                        self.table = Some(next);
+                        self.entry = 0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ops::Range;
+
+    use super::ntb::{self, Malformed};
+    use super::*;
+
+    /// The string QEMU's `usb-net` answers for `iMACAddress` with its default
+    /// address.
+    fn qemu_mac_string() -> [u8; 26] {
+        let mut s = [0u8; 26];
+        s[0] = 26;
+        s[1] = 3;
+        for (i, c) in "525400123457".bytes().enumerate() {
+            s[2 + i * 2] = c;
+        }
+        s
+    }
+
+    #[test]
+    fn the_address_string_decodes_most_significant_digit_first() {
+        assert_eq!(mac_from_string(&qemu_mac_string()), Ok([0x52, 0x54, 0x00, 0x12, 0x34, 0x57]));
+        // Lower case is as much hex as upper.
+        let mut lower = qemu_mac_string();
+        lower[2 + 11 * 2] = b'a';
+        assert_eq!(mac_from_string(&lower), Ok([0x52, 0x54, 0x00, 0x12, 0x34, 0x5A]));
+    }
+
+    #[test]
+    fn a_string_that_is_not_an_address_is_refused() {
+        let mut short = qemu_mac_string();
+        short[0] = 24;
+        assert_eq!(mac_from_string(&short), Err(BadMac::Short(24)));
+        // A device stating more than it sent is read as what arrived.
+        assert_eq!(mac_from_string(&qemu_mac_string()[..10]), Err(BadMac::Short(10)));
+        let mut not_hex = qemu_mac_string();
+        not_hex[2 + 4 * 2] = b'g';
+        assert_eq!(mac_from_string(&not_hex), Err(BadMac::NotHex(4)));
+        // A UTF-16 unit past ASCII whose low byte is a digit is not that digit.
+        let mut wide = qemu_mac_string();
+        wide[3] = 0x01;
+        assert_eq!(mac_from_string(&wide), Err(BadMac::NotHex(0)));
+        let mut group = qemu_mac_string();
+        // The second digit is the nibble the group bit is in.
+        group[4] = b'3';
+        assert!(matches!(mac_from_string(&group), Err(BadMac::Multicast(_))));
+        let mut zero = qemu_mac_string();
+        for i in 0..12 {
+            zero[2 + i * 2] = b'0';
+        }
+        assert_eq!(mac_from_string(&zero), Err(BadMac::Zero));
+    }
+
+    #[test]
+    fn the_ethernet_descriptor_names_its_address_string() {
+        let desc = [13, CS_INTERFACE, FUNC_ETHERNET, 3, 0, 0, 0, 0, 0xEA, 0x05, 0, 0, 0];
+        assert_eq!(Ethernet::parse(&desc), Some(Ethernet { mac_string: 3, max_segment: 1514 }));
+        assert_eq!(Ethernet::parse(&desc[..12]), None);
+        let mut no_string = desc;
+        no_string[3] = 0;
+        assert_eq!(Ethernet::parse(&no_string), None);
+        assert_eq!(union_data(&[5, CS_INTERFACE, FUNC_UNION, 0, 1]), Some(1));
+        assert_eq!(union_data(&[5, CS_INTERFACE, FUNC_ETHERNET, 0, 1]), None);
+    }
+
+    #[test]
+    fn link_notifications_decode() {
+        assert_eq!(Notification::parse(&[0xA1, 0x00, 1, 0, 0, 0, 0, 0]), Ok(Notification::Connection(true)));
+        assert_eq!(Notification::parse(&[0xA1, 0x00, 0, 0, 0, 0, 0, 0]), Ok(Notification::Connection(false)));
+        let mut speed = [0u8; 16];
+        speed[..8].copy_from_slice(&[0xA1, 0x2A, 0, 0, 0, 0, 8, 0]);
+        speed[8..12].copy_from_slice(&100_000_000u32.to_le_bytes());
+        speed[12..16].copy_from_slice(&10_000_000u32.to_le_bytes());
+        assert_eq!(Notification::parse(&speed), Ok(Notification::Speed { down: 100_000_000, up: 10_000_000 }));
+        // The speed change's data is not there to read.
+        assert_eq!(Notification::parse(&speed[..8]), Err(BadNotification::Short(8)));
+        assert_eq!(Notification::parse(&[0xA1, 0x00, 1]), Err(BadNotification::Short(3)));
+        assert_eq!(Notification::parse(&[0x21, 0x00, 1, 0, 0, 0, 0, 0]), Err(BadNotification::NotClass(0x21)));
+        assert_eq!(Notification::parse(&[0xA1, 0x01, 0, 0, 0, 0, 0, 0]), Ok(Notification::Other(0x01)));
+    }
+
+    #[test]
+    fn only_an_exact_multiple_of_the_packet_size_needs_a_zero_length_packet() {
+        assert!(needs_zero_length(512, 512));
+        assert!(needs_zero_length(1024, 512));
+        assert!(!needs_zero_length(1514, 512));
+        assert!(!needs_zero_length(0, 512));
+        assert!(needs_zero_length(128, 64));
+        assert!(!needs_zero_length(128, 0));
+    }
+
+    #[test]
+    fn an_ecm_transfer_is_a_frame_or_refused() {
+        assert_eq!(ecm_frame(60), Ok(60));
+        assert_eq!(ecm_frame(MAX_FRAME), Ok(MAX_FRAME));
+        assert_eq!(ecm_frame(13), Err(BadFrame::Runt(13)));
+        assert_eq!(ecm_frame(MAX_FRAME + 1), Err(BadFrame::Overlong(MAX_FRAME + 1)));
+    }
+
+    /// A block holding `frames`, one table, laid out the way a device builds
+    /// one: header, table, then the frames four-byte aligned.
+    fn block(frames: &[usize]) -> ([u8; 4096], usize) {
+        let mut b = [0u8; 4096];
+        let table_len = 8 + (frames.len() + 1) * 4;
+        let mut at = (12 + table_len).next_multiple_of(4);
+        b[0..4].copy_from_slice(b"NCMH");
+        b[4..6].copy_from_slice(&12u16.to_le_bytes());
+        b[10..12].copy_from_slice(&12u16.to_le_bytes());
+        b[12..16].copy_from_slice(b"NCM0");
+        b[16..18].copy_from_slice(&(table_len as u16).to_le_bytes());
+        for (i, len) in frames.iter().enumerate() {
+            let e = 20 + i * 4;
+            b[e..e + 2].copy_from_slice(&(at as u16).to_le_bytes());
+            b[e + 2..e + 4].copy_from_slice(&(*len as u16).to_le_bytes());
+            b[at] = i as u8 + 1;
+            at = (at + len).next_multiple_of(4);
+        }
+        b[8..10].copy_from_slice(&(at as u16).to_le_bytes());
+        (b, at)
+    }
+
+    /// Everything a walk yields, and how many that was, sized past anything a
+    /// test block holds so a walk that does not end runs off it rather than
+    /// passing. No heap in this crate, as `enumerate`'s tests say.
+    struct Walked {
+        got: [Option<Result<Range<usize>, Malformed>>; 16],
+        n: usize,
+    }
+
+    impl Walked {
+        fn of(block: &[u8]) -> Self {
+            let mut got = [const { None }; 16];
+            let mut n = 0;
+            for item in ntb::datagrams(block) {
+                assert!(n < got.len(), "the walk did not end");
+                got[n] = Some(item);
+                n += 1;
+            }
+            Self { got, n }
+        }
+
+        fn at(&self, i: usize) -> Result<Range<usize>, Malformed> {
+            self.got[i].clone().expect("the walk yielded this many")
+        }
+    }
+
+    #[test]
+    fn a_block_yields_every_frame_in_table_order() {
+        let (b, len) = block(&[60, 1514, 98]);
+        let walked = Walked::of(&b[..len]);
+        assert_eq!(walked.n, 3);
+        for (i, want) in [60, 1514, 98].into_iter().enumerate() {
+            let range = walked.at(i).unwrap();
+            assert_eq!(range.len(), want);
+            assert_eq!(b[range.start], i as u8 + 1);
+        }
+    }
+
+    #[test]
+    fn a_block_longer_than_what_arrived_is_refused_whole() {
+        let (b, len) = block(&[60]);
+        let walked = Walked::of(&b[..len - 1]);
+        assert_eq!((walked.n, walked.at(0)), (1, Err(Malformed::Header)));
+        let walked = Walked::of(b"NCMH");
+        assert_eq!((walked.n, walked.at(0)), (1, Err(Malformed::Header)));
+    }
+
+    #[test]
+    fn a_datagram_outside_the_block_ends_the_walk_after_the_good_ones() {
+        let (mut b, len) = block(&[60, 60]);
+        // The second entry's length runs past the block.
+        b[26..28].copy_from_slice(&2000u16.to_le_bytes());
+        let walked = Walked::of(&b[..len]);
+        assert_eq!(walked.n, 2);
+        assert!(walked.at(0).is_ok());
+        assert!(matches!(walked.at(1), Err(Malformed::Datagram { len: 2000, .. })));
+    }
+
+    #[test]
+    fn a_table_that_is_not_one_is_refused() {
+        let (mut b, len) = block(&[60]);
+        b[12..16].copy_from_slice(b"XXXX");
+        let walked = Walked::of(&b[..len]);
+        assert_eq!((walked.n, walked.at(0)), (1, Err(Malformed::Table(12))));
+        // A table pointer into the header itself.
+        let (mut b, len) = block(&[60]);
+        b[10..12].copy_from_slice(&4u16.to_le_bytes());
+        let walked = Walked::of(&b[..len]);
+        assert_eq!((walked.n, walked.at(0)), (1, Err(Malformed::Table(4))));
+    }
+
+    #[test]
+    fn a_table_that_names_itself_next_is_refused_as_a_chain() {
+        // One frame, then the table names itself as the next one — and a
+        // device sending that would have every frame in it delivered again on
+        // each lap.
+        let (mut b, len) = block(&[60]);
+        b[18..20].copy_from_slice(&12u16.to_le_bytes());
+        let walked = Walked::of(&b[..len]);
+        assert_eq!(walked.at(0).map(|r| r.len()), Ok(60));
+        assert_eq!(walked.at(walked.n - 1), Err(Malformed::Chain));
+    }
+
+    #[test]
+    fn the_transmit_header_is_a_block_the_receive_walk_reads_back() {
+        let frame_len = 98u16;
+        let h = ntb::header(7, frame_len);
+        let mut b = [0u8; 256];
+        b[..ntb::TX_HEADER].copy_from_slice(&h);
+        let total = ntb::TX_HEADER + frame_len as usize;
+        let walked = Walked::of(&b[..total]);
+        assert_eq!((walked.n, walked.at(0)), (1, Ok(ntb::TX_HEADER..total)));
+        assert_eq!(u16::from_le_bytes([h[6], h[7]]), 7);
+        assert_eq!(ntb::TX_HEADER % 4, 0);
+    }
+}
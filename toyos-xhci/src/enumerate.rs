@@ -73,11 +73,52 @@ pub enum Request {
     /// decides whether a longer read can be transferred at all — so the prefix
     /// is read at a size every device of the speed can answer at.
     DeviceDescriptor { want: u16 },
-    ConfigDescriptor,
+    /// GET_DESCRIPTOR(Configuration) for the configuration at `index`, which
+    /// is a position in the device's list and not its `bConfigurationValue`.
+    ConfigDescriptor { index: u8 },
     SetConfiguration,
     /// SET_PROTOCOL(boot), which only an interface that has a boot protocol
     /// has: asking a tablet for one is a request it may stall for.
     SetProtocol,
+    /// GET_DESCRIPTOR(Report), which only a HID interface without a boot
+    /// protocol is asked for: its reports are in a format the descriptor
+    /// states and nothing else does. After SET_CONFIGURATION, because the
+    /// descriptor is the interface's and a device may answer for an interface
+    /// only once its configuration is the one selected.
+    ReportDescriptor,
+    /// SET_INTERFACE to the alternate setting the bind chose. A configuration
+    /// starts every interface at setting 0, so this is owed only when the
+    /// chosen one is another — and before Configure Endpoint, because the
+    /// endpoints the controller is told about are the chosen setting's.
+    SetInterface,
+    /// GET_DESCRIPTOR(Hub), which says how many ports there are and how long
+    /// their power takes. Before Configure Endpoint, because the hub's slot
+    /// context carries the port count and the controller reads it there.
+    HubDescriptor,
+    /// SET_HUB_DEPTH, which only a SuperSpeed hub has and which it needs
+    /// before its interrupt endpoint is of any use: a SuperSpeed hub routes by
+    /// its own nibble of the Route String, and this is how it knows which.
+    SetHubDepth,
+    /// GET_DESCRIPTOR(String) for the Ethernet functional descriptor's
+    /// `iMACAddress`: a CDC network function states its station address as
+    /// twelve hex digits and nowhere else (CDC ECM 1.2 §5.4).
+    MacAddress,
+    /// SET_NTB_INPUT_SIZE, which only NCM has. Before SET_INTERFACE, because
+    /// NCM 1.0 §7.2 lets the host change it only while the data interface is
+    /// in its setting with no endpoints — and it is owed at all because the
+    /// device's own maximum may be far past the buffers the driver posts.
+    NtbInputSize,
+    /// The `step`th request of a serial adapter's line setup, which is its
+    /// family's (see [`crate::serial::line_step`]). Before Configure
+    /// Endpoint, because an FTDI chip's reset flushes what it received and a
+    /// ring posted first would have read bytes the reset then threw away.
+    LineSetup { step: u8 },
+    /// The rate request of an audio function (see
+    /// [`crate::uac::sample_rate`]). After SET_INTERFACE, because a UAC1 rate
+    /// is its endpoint's and the endpoint exists only in the setting that has
+    /// it; before Configure Endpoint, because a device that changes rate
+    /// changes how large its packets are.
+    SampleRate,
 }
 
 /// What the driver does next.
@@ -96,9 +137,31 @@ pub enum Act {
 pub enum Function {
     /// A HID interface with a boot protocol to select.
     BootHid,
-    /// A HID interface with none — a tablet, which reports in its own format.
+    /// A HID interface with none — a tablet, a game controller, a keyboard
+    /// without a boot interface — which reports in the format its report
+    /// descriptor states, so the descriptor is read.
     Hid,
     Msc,
+    /// A mass-storage interface whose transport is an alternate setting rather
+    /// than setting 0 — the UAS setting of a stick that offers Bulk-Only at 0,
+    /// which is how nearly every UAS device ships.
+    MscAlternate,
+    /// A hub below SuperSpeed: its descriptor is read before its endpoints are
+    /// configured.
+    Hub,
+    /// A SuperSpeed hub, which is also told its depth.
+    SuperSpeedHub,
+    /// A CDC-ECM network function: its address is read, then its data
+    /// interface is moved to the setting that has endpoints.
+    Net,
+    /// A CDC-NCM one, which is also told how large a transfer block to send.
+    NetNcm,
+    /// A serial adapter, whose line is set up in `steps` requests.
+    Serial { steps: u8 },
+    /// An audio function, whose streaming interface is moved to the setting
+    /// that plays — setting 0 has no endpoints, by the class's own rule — and
+    /// whose rate is set where `rate` says the device has a control for it.
+    Audio { rate: bool },
 }
 
 /// What the driver learnt from the act it just performed, where the order of
@@ -112,8 +175,19 @@ pub enum Learnt {
     Ep0PacketWrong,
     /// The configuration descriptor named a function this driver can bind.
     Function(Function),
+    /// The device descriptor's `bNumConfigurations`: how many configurations
+    /// there are to look through for one.
+    Configurations(u8),
 }
 
+/// The most configurations read from one device before it is refused.
+///
+/// More than one is not rare. QEMU's `usb-net` offers RNDIS first and ECM
+/// second, and Realtek's gigabit dongles offer their vendor protocol first and
+/// ECM second — a driver that reads only the first refuses both. The count is
+/// the device's byte and each read is a control transfer, so it is capped.
+pub const MAX_CONFIGURATIONS: u8 = 4;
+
 /// Where the sequence goes after the act that has just completed.
 ///
 /// A sum and not another [`Act`] variant, because the two ends are terminal and
@@ -144,6 +218,14 @@ enum At {
     Config,
     Configuration,
     Protocol,
+    ReportDescriptor,
+    Interface,
+    HubDescriptor,
+    HubDepth,
+    MacAddress,
+    NtbSize,
+    LineSetup,
+    SampleRate,
     Endpoints,
 }
 
@@ -155,13 +237,48 @@ pub struct Enumeration {
     /// thing the later acts still need to know about what the configuration
     /// said.
     boot_protocol: bool,
+    /// Whether it has a report descriptor to read instead.
+    report: bool,
+    /// Whether the bind chose an alternate setting, which is another.
+    alternate: bool,
+    /// Whether this is a hub, whose descriptor is owed, and whether it is a
+    /// SuperSpeed one, whose depth is too.
+    hub: bool,
+    hub_depth: bool,
+    /// Whether this is a network function, whose address is owed, and whether
+    /// it is an NCM one, whose transfer block size is too.
+    net: bool,
+    ncm: bool,
+    /// How many line setup requests a serial adapter is owed, and which one is
+    /// outstanding.
+    line_steps: u8,
+    line_step: u8,
+    /// Whether this is an audio function with a rate to set.
+    sample_rate: bool,
+    /// Which configuration is being read, and how many the device has.
+    config: u8,
+    configs: u8,
 }
 
 impl Enumeration {
     /// The first act every device needs, whatever it turns out to be.
     pub fn begin() -> (Self, Act) {
         (
-            Self { at: At::Slot, boot_protocol: false },
+            Self {
+                at: At::Slot,
+                boot_protocol: false,
+                report: false,
+                alternate: false,
+                hub: false,
+                hub_depth: false,
+                net: false,
+                ncm: false,
+                line_steps: 0,
+                line_step: 0,
+                sample_rate: false,
+                config: 0,
+                configs: 1,
+            },
             Act::Command(Command::EnableSlot),
         )
     }
@@ -180,18 +297,81 @@ impl Enumeration {
             At::Prefix | At::Ep0 => {
                 (At::Descriptor, Act::Request(Request::DeviceDescriptor { want: 18 }))
             }
-            At::Descriptor => (At::Config, Act::Request(Request::ConfigDescriptor)),
+            At::Descriptor => {
+                // A device that states no configurations is read for one anyway:
+                // index 0 is what every device had before this was asked.
+                if let Learnt::Configurations(n) = learnt {
+                    self.configs = n.clamp(1, MAX_CONFIGURATIONS);
+                }
+                (At::Config, Act::Request(Request::ConfigDescriptor { index: 0 }))
+            }
+            At::Config if !matches!(learnt, Learnt::Function(_)) => {
+                self.config += 1;
+                if self.config >= self.configs {
+                    return Next::Refuse;
+                }
+                (At::Config, Act::Request(Request::ConfigDescriptor { index: self.config }))
+            }
             At::Config => {
                 let Learnt::Function(function) = learnt else { return Next::Refuse };
                 self.boot_protocol = function == Function::BootHid;
+                self.report = function == Function::Hid;
+                self.net = matches!(function, Function::Net | Function::NetNcm);
+                self.ncm = function == Function::NetNcm;
+                // A network function's data interface is an alternate setting
+                // too: setting 0 has no endpoints, by CDC's own rule, so a
+                // host can stop the traffic without a new configuration. An
+                // audio function's streaming interface is, for the same reason.
+                let audio = matches!(function, Function::Audio { .. });
+                self.alternate = function == Function::MscAlternate || self.net || audio;
+                self.sample_rate = function == Function::Audio { rate: true };
+                self.hub = matches!(function, Function::Hub | Function::SuperSpeedHub);
+                self.hub_depth = function == Function::SuperSpeedHub;
+                self.line_steps = match function {
+                    Function::Serial { steps } => steps,
+                    _ => 0,
+                };
                 (At::Configuration, Act::Request(Request::SetConfiguration))
             }
             At::Configuration if self.boot_protocol => {
                 (At::Protocol, Act::Request(Request::SetProtocol))
             }
-            At::Configuration | At::Protocol => {
-                (At::Endpoints, Act::Command(Command::ConfigureEndpoint))
+            At::Configuration if self.report => {
+                (At::ReportDescriptor, Act::Request(Request::ReportDescriptor))
+            }
+            At::Configuration if self.net => {
+                (At::MacAddress, Act::Request(Request::MacAddress))
+            }
+            At::MacAddress if self.ncm => (At::NtbSize, Act::Request(Request::NtbInputSize)),
+            At::Configuration | At::MacAddress | At::NtbSize if self.alternate => {
+                (At::Interface, Act::Request(Request::SetInterface))
+            }
+            At::Interface if self.sample_rate => {
+                (At::SampleRate, Act::Request(Request::SampleRate))
+            }
+            At::Configuration if self.hub => {
+                (At::HubDescriptor, Act::Request(Request::HubDescriptor))
+            }
+            At::HubDescriptor if self.hub_depth => {
+                (At::HubDepth, Act::Request(Request::SetHubDepth))
+            }
+            At::Configuration if self.line_steps > 0 => {
+                (At::LineSetup, Act::Request(Request::LineSetup { step: 0 }))
             }
+            At::LineSetup if self.line_step + 1 < self.line_steps => {
+                self.line_step += 1;
+                (At::LineSetup, Act::Request(Request::LineSetup { step: self.line_step }))
+            }
+            At::Configuration
+            | At::Protocol
+            | At::ReportDescriptor
+            | At::Interface
+            | At::HubDescriptor
+            | At::HubDepth
+            | At::MacAddress
+            | At::NtbSize
+            | At::LineSetup
+            | At::SampleRate => (At::Endpoints, Act::Command(Command::ConfigureEndpoint)),
             At::Endpoints => return Next::Bind,
         };
         Next::Act(Self { at, ..self }, act)
@@ -204,7 +384,21 @@ mod tests {
 
     /// Sized past the longest route so a sequence that grew one runs off it
     /// rather than being silently truncated into a passing comparison.
-    const LONGEST: usize = 12;
+    const LONGEST: usize = 16;
+
+    const ALL: [Function; 11] = [
+        Function::BootHid,
+        Function::Hid,
+        Function::Msc,
+        Function::MscAlternate,
+        Function::Hub,
+        Function::SuperSpeedHub,
+        Function::Net,
+        Function::NetNcm,
+        Function::Serial { steps: 5 },
+        Function::Audio { rate: true },
+        Function::Audio { rate: false },
+    ];
 
     /// A route, as the acts it produced and how it ended. `Copy` so the
     /// assertions below can compare slices of it without a heap this crate does
@@ -248,7 +442,7 @@ mod tests {
     /// controller was told correctly does.
     fn keyboard(act: Act) -> Learnt {
         match act {
-            Act::Request(Request::ConfigDescriptor) => Learnt::Function(Function::BootHid),
+            Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(Function::BootHid),
             _ => Learnt::Nothing,
         }
     }
@@ -264,7 +458,7 @@ mod tests {
                 Act::Command(Command::AddressDevice),
                 Act::Request(Request::DeviceDescriptor { want: 8 }),
                 Act::Request(Request::DeviceDescriptor { want: 18 }),
-                Act::Request(Request::ConfigDescriptor),
+                Act::Request(Request::ConfigDescriptor { index: 0 }),
                 Act::Request(Request::SetConfiguration),
                 Act::Request(Request::SetProtocol),
                 Act::Command(Command::ConfigureEndpoint),
@@ -296,9 +490,9 @@ mod tests {
     /// is a request the device may stall for.
     #[test]
     fn only_an_interface_with_a_boot_protocol_is_asked_for_one() {
-        for function in [Function::Msc, Function::Hid] {
+        for function in [Function::Msc, Function::MscAlternate, Function::Hid] {
             let route = route(|act| match act {
-                Act::Request(Request::ConfigDescriptor) => Learnt::Function(function),
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(function),
                 _ => Learnt::Nothing,
             });
             assert_eq!(route.end, Next::Bind);
@@ -311,14 +505,40 @@ mod tests {
         }
     }
 
+    /// Only a HID interface without a boot protocol has its report descriptor
+    /// read, once, between the configuration and the endpoints: its reports
+    /// mean nothing without it, and a boot device's format is the
+    /// specification's.
+    #[test]
+    fn only_a_report_protocol_hid_reads_its_report_descriptor() {
+        let hid = route(|act| match act {
+            Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(Function::Hid),
+            _ => Learnt::Nothing,
+        });
+        assert_eq!(hid.end, Next::Bind);
+        let n = hid.acts().len();
+        assert_eq!(hid.acts()[n - 3..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::ReportDescriptor),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        for function in ALL.iter().filter(|f| **f != Function::Hid) {
+            let other = route(|act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(*function),
+                _ => Learnt::Nothing,
+            });
+            assert_eq!(other.count(Act::Request(Request::ReportDescriptor)), 0, "{function:?}");
+        }
+    }
+
     /// Every route configures the endpoints exactly once and immediately before
     /// the bind. A bind that ran first would put a device's transfer rings in
     /// pool memory no endpoint context names yet.
     #[test]
     fn every_route_configures_its_endpoints_last() {
-        for function in [Function::BootHid, Function::Hid, Function::Msc] {
+        for function in ALL {
             let route = route(|act| match act {
-                Act::Request(Request::ConfigDescriptor) => Learnt::Function(function),
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(function),
                 _ => Learnt::Nothing,
             });
             assert_eq!(route.acts().last(), Some(&Act::Command(Command::ConfigureEndpoint)));
@@ -327,14 +547,233 @@ mod tests {
         }
     }
 
-    /// A device offering nothing this driver binds — a hub, a camera — stops
+    /// A UAS setting is selected after the configuration and before the
+    /// endpoints, and setting 0 is never asked for: it is what SET_CONFIGURATION
+    /// already left every interface in, and a stick that stalls the redundant
+    /// request would be refused for asking nothing.
+    #[test]
+    fn only_an_alternate_setting_is_selected_and_before_the_endpoints() {
+        let uas = route(|act| match act {
+            Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(Function::MscAlternate),
+            _ => Learnt::Nothing,
+        });
+        assert_eq!(uas.end, Next::Bind);
+        let n = uas.acts().len();
+        assert_eq!(uas.acts()[n - 3..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::SetInterface),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        for function in [Function::BootHid, Function::Hid, Function::Msc] {
+            let other = route(|act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(function),
+                _ => Learnt::Nothing,
+            });
+            assert_eq!(other.count(Act::Request(Request::SetInterface)), 0, "{function:?}");
+        }
+    }
+
+    /// A hub is described before its endpoints are configured, since its slot
+    /// context carries the port count, and only a SuperSpeed hub is told a
+    /// depth — a USB 2.0 hub has no such request to answer and may stall it.
+    #[test]
+    fn a_hub_is_described_and_a_superspeed_one_told_its_depth_before_its_endpoints() {
+        let hub = |function| {
+            route(move |act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(function),
+                _ => Learnt::Nothing,
+            })
+        };
+        let usb2 = hub(Function::Hub);
+        assert_eq!(usb2.end, Next::Bind);
+        let n = usb2.acts().len();
+        assert_eq!(usb2.acts()[n - 3..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::HubDescriptor),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        let usb3 = hub(Function::SuperSpeedHub);
+        let n = usb3.acts().len();
+        assert_eq!(usb3.acts()[n - 4..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::HubDescriptor),
+            Act::Request(Request::SetHubDepth),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        for function in [Function::BootHid, Function::Hid, Function::Msc, Function::MscAlternate] {
+            let other = hub(function);
+            assert_eq!(other.count(Act::Request(Request::HubDescriptor)), 0, "{function:?}");
+            assert_eq!(other.count(Act::Request(Request::SetHubDepth)), 0, "{function:?}");
+        }
+    }
+
+    /// A network function's address is read once the configuration is set, an
+    /// NCM one is told its transfer block size while its data interface still
+    /// has no endpoints, and only then is the setting that has them selected.
+    #[test]
+    fn a_network_function_is_addressed_then_moved_to_its_data_setting() {
+        let net = |function| {
+            route(move |act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(function),
+                _ => Learnt::Nothing,
+            })
+        };
+        let ecm = net(Function::Net);
+        assert_eq!(ecm.end, Next::Bind);
+        let n = ecm.acts().len();
+        assert_eq!(ecm.acts()[n - 4..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::MacAddress),
+            Act::Request(Request::SetInterface),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        assert_eq!(ecm.count(Act::Request(Request::NtbInputSize)), 0);
+        let ncm = net(Function::NetNcm);
+        let n = ncm.acts().len();
+        assert_eq!(ncm.acts()[n - 5..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::MacAddress),
+            Act::Request(Request::NtbInputSize),
+            Act::Request(Request::SetInterface),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        for function in &ALL[..6] {
+            let other = net(*function);
+            assert_eq!(other.count(Act::Request(Request::MacAddress)), 0, "{function:?}");
+            assert_eq!(other.count(Act::Request(Request::NtbInputSize)), 0, "{function:?}");
+        }
+    }
+
+    /// A serial adapter's line is set up one request at a time, in order, after
+    /// the configuration and before the endpoints — and an adapter whose family
+    /// needs none goes straight to them.
+    #[test]
+    fn a_serial_adapter_has_its_line_set_up_step_by_step_before_its_endpoints() {
+        let serial = |steps| {
+            route(move |act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => {
+                    Learnt::Function(Function::Serial { steps })
+                }
+                _ => Learnt::Nothing,
+            })
+        };
+        let ftdi = serial(5);
+        assert_eq!(ftdi.end, Next::Bind);
+        let n = ftdi.acts().len();
+        assert_eq!(ftdi.acts()[n - 7..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::LineSetup { step: 0 }),
+            Act::Request(Request::LineSetup { step: 1 }),
+            Act::Request(Request::LineSetup { step: 2 }),
+            Act::Request(Request::LineSetup { step: 3 }),
+            Act::Request(Request::LineSetup { step: 4 }),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        let none = serial(0);
+        let n = none.acts().len();
+        assert_eq!(none.acts()[n - 2..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        for function in &ALL[..8] {
+            let other = route(|act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(*function),
+                _ => Learnt::Nothing,
+            });
+            assert!(
+                !other.acts().iter().any(|a| matches!(a, Act::Request(Request::LineSetup { .. }))),
+                "{function:?}"
+            );
+        }
+    }
+
+    /// An audio function is moved to its playing setting and only then has its
+    /// rate set, since a UAC1 rate is addressed to an endpoint that setting 0
+    /// does not have; one with no rate control is not asked.
+    #[test]
+    fn an_audio_function_selects_its_setting_then_its_rate_before_its_endpoints() {
+        let audio = |rate| {
+            route(move |act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => {
+                    Learnt::Function(Function::Audio { rate })
+                }
+                _ => Learnt::Nothing,
+            })
+        };
+        let dac = audio(true);
+        assert_eq!(dac.end, Next::Bind);
+        let n = dac.acts().len();
+        assert_eq!(dac.acts()[n - 4..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::SetInterface),
+            Act::Request(Request::SampleRate),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        let fixed = audio(false);
+        let n = fixed.acts().len();
+        assert_eq!(fixed.acts()[n - 3..], [
+            Act::Request(Request::SetConfiguration),
+            Act::Request(Request::SetInterface),
+            Act::Command(Command::ConfigureEndpoint),
+        ]);
+        for function in &ALL[..9] {
+            let other = route(|act| match act {
+                Act::Request(Request::ConfigDescriptor { .. }) => Learnt::Function(*function),
+                _ => Learnt::Nothing,
+            });
+            assert_eq!(other.count(Act::Request(Request::SampleRate)), 0, "{function:?}");
+        }
+    }
+
+    /// A device offering nothing this driver binds — a camera, a printer — stops
     /// the sequence where the answer is known, rather than configuring a
     /// device with no interface behind it.
     #[test]
     fn a_configuration_with_nothing_to_bind_is_refused_where_it_is_read() {
         let route = route(|_| Learnt::Nothing);
         assert_eq!(route.end, Next::Refuse);
-        assert_eq!(route.acts().last(), Some(&Act::Request(Request::ConfigDescriptor)));
+        assert_eq!(
+            route.acts().last(),
+            Some(&Act::Request(Request::ConfigDescriptor { index: 0 }))
+        );
+    }
+
+    /// A configuration with nothing to bind sends the sequence to the next, in
+    /// order, and the one that has something is the one configured.
+    #[test]
+    fn a_later_configuration_is_read_when_an_earlier_one_has_nothing() {
+        let route = route(|act| match act {
+            Act::Request(Request::DeviceDescriptor { want: 18 }) => Learnt::Configurations(2),
+            Act::Request(Request::ConfigDescriptor { index: 1 }) => Learnt::Function(Function::Net),
+            _ => Learnt::Nothing,
+        });
+        assert_eq!(route.end, Next::Bind);
+        assert_eq!(route.acts()[4..7], [
+            Act::Request(Request::ConfigDescriptor { index: 0 }),
+            Act::Request(Request::ConfigDescriptor { index: 1 }),
+            Act::Request(Request::SetConfiguration),
+        ]);
+    }
+
+    /// The count is the device's byte, so a device claiming 255 costs
+    /// [`MAX_CONFIGURATIONS`] reads and no more; one claiming none is read once.
+    #[test]
+    fn the_configurations_read_are_capped_and_at_least_one() {
+        for (stated, reads) in [(255u8, MAX_CONFIGURATIONS as usize), (0, 1), (1, 1), (3, 3)] {
+            let route = route(|act| match act {
+                Act::Request(Request::DeviceDescriptor { want: 18 }) => {
+                    Learnt::Configurations(stated)
+                }
+                _ => Learnt::Nothing,
+            });
+            assert_eq!(route.end, Next::Refuse);
+            let read = route
+                .acts()
+                .iter()
+                .filter(|a| matches!(a, Act::Request(Request::ConfigDescriptor { .. })))
+                .count();
+            assert_eq!(read, reads, "{stated} configurations stated: {:?}", route.acts());
+        }
     }
 
     /// Address Device carries a packet size and only these speeds have one, so
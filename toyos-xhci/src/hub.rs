@@ -0,0 +1,741 @@
+//! An external hub's downstream ports, as the PORTSC word the port machine
+//! already drives.
+//!
+//! A root-hub port is a register and a hub's port is a pair of 16-bit words
+//! behind a control transfer (USB 2.0 §11.24.2.7, USB 3.2 §10.16.2.6), but
+//! what the driver has to *decide* about either is the same: whether something
+//! is attached, whether it held still for a debounce, which reset it needs and
+//! whether the reset finished. [`crate::port::PortState`] is that decision, so
+//! a hub port is given one too — fed a [`Portsc`] built from the hub's answer,
+//! and its writes turned back into the SET_FEATURE and CLEAR_FEATURE requests
+//! that perform them. A second machine for the same question, driven only on
+//! the docks nobody in CI plugs into, is the one that would rot.
+//!
+//! The rest is what the controller needs told about a device that is not on a
+//! root port: the Route String that names the path to it, and the Transaction
+//! Translator a low- or full-speed device behind a high-speed hub is reached
+//! through (xHCI 1.2 §4.5.2, §6.2.2).
+
+use crate::portsc::{self, Portsc};
+
+/// bDeviceClass and bInterfaceClass of every hub.
+pub const CLASS: u8 = 0x09;
+
+/// The hub descriptor's type: USB 2.0 §11.23.2.1 below SuperSpeed, and USB 3.2
+/// §10.15.2.1's enhanced SuperSpeed one at it. The two are different layouts
+/// and a hub answers only for its own.
+pub const DESCRIPTOR_USB2: u8 = 0x29;
+pub const DESCRIPTOR_USB3: u8 = 0x2A;
+
+/// How much of the hub descriptor is asked for. Twelve is the whole of a
+/// SuperSpeed hub's and more than every field of a USB 2.0 one this driver
+/// reads; the variable-length bitmaps behind those are per-port policy this
+/// driver does not apply.
+pub const DESCRIPTOR_BYTES: u16 = 12;
+
+/// The most downstream ports one hub is served on.
+///
+/// **A Route String has four bits per tier** (xHCI 1.2 §8.9), so a port past
+/// 15 has no number the controller can be told. Hubs that wide exist only as
+/// stacks of smaller ones inside one enclosure, and each of those is a hub of
+/// its own with its own tier.
+pub const MAX_PORTS: u8 = 15;
+
+/// How many hubs may sit between a root port and a device: five nibbles of
+/// Route String, and USB 2.0 §4.1.1's own limit on the topology.
+pub const MAX_TIERS: u8 = 5;
+
+/// The xHCI Protocol Speed IDs a hub port can report (§7.2.2.1.1's defaults).
+pub const SPEED_FULL: u8 = 1;
+pub const SPEED_LOW: u8 = 2;
+pub const SPEED_HIGH: u8 = 3;
+pub const SPEED_SUPER: u8 = 4;
+
+// wPortStatus. The low five bits mean the same thing at both speeds; power and
+// the speed bits do not, which is the whole reason `superspeed` is an argument.
+pub const PORT_CONNECTION: u16 = 1 << 0;
+pub const PORT_ENABLE: u16 = 1 << 1;
+pub const PORT_OVER_CURRENT: u16 = 1 << 3;
+pub const PORT_RESET: u16 = 1 << 4;
+pub const PORT_POWER_USB2: u16 = 1 << 8;
+pub const PORT_LOW_SPEED: u16 = 1 << 9;
+pub const PORT_HIGH_SPEED: u16 = 1 << 10;
+/// PORT_LINK_STATE, USB 3.2 Table 10-11 — in the same four bits and with the
+/// same encoding as PORTSC's PLS, which is no accident of this driver's: xHCI
+/// took the field from the hub specification.
+pub const PORT_LINK_STATE_USB3: u16 = 0xF << 5;
+pub const PORT_POWER_USB3: u16 = 1 << 9;
+
+// wPortChange.
+pub const C_PORT_CONNECTION: u16 = 1 << 0;
+pub const C_PORT_ENABLE: u16 = 1 << 1;
+pub const C_PORT_SUSPEND: u16 = 1 << 2;
+pub const C_PORT_OVER_CURRENT: u16 = 1 << 3;
+pub const C_PORT_RESET: u16 = 1 << 4;
+pub const C_BH_PORT_RESET: u16 = 1 << 5;
+pub const C_PORT_LINK_STATE: u16 = 1 << 6;
+pub const C_PORT_CONFIG_ERROR: u16 = 1 << 7;
+
+// Feature selectors, USB 2.0 Table 11-17 and USB 3.2 Table 10-9.
+pub const F_PORT_RESET: u16 = 4;
+pub const F_PORT_POWER: u16 = 8;
+pub const F_C_PORT_CONNECTION: u16 = 16;
+pub const F_C_PORT_ENABLE: u16 = 17;
+pub const F_C_PORT_SUSPEND: u16 = 18;
+pub const F_C_PORT_OVER_CURRENT: u16 = 19;
+pub const F_C_PORT_RESET: u16 = 20;
+pub const F_C_PORT_LINK_STATE: u16 = 25;
+pub const F_C_PORT_CONFIG_ERROR: u16 = 26;
+pub const F_BH_PORT_RESET: u16 = 28;
+pub const F_C_BH_PORT_RESET: u16 = 29;
+
+/// PEC, OCC, PLC and CEC — the PORTSC change flags `portsc` does not name
+/// because nothing on a root port decides on them.
+const PEC: u32 = 1 << 18;
+const OCC: u32 = 1 << 20;
+const PLC: u32 = 1 << 22;
+const CEC: u32 = 1 << 23;
+
+/// One change flag at both ends: the PORTSC bit the machine reads and
+/// acknowledges, the wPortChange bit the hub reports it in, and the feature
+/// that clears it. `None` where a hub of that speed has no such flag.
+struct Change {
+    portsc: u32,
+    usb2: Option<(u16, u16)>,
+    usb3: Option<(u16, u16)>,
+}
+
+/// Every change flag a hub port has, in the order the acknowledge clears them.
+/// PLC is USB 2.0's suspend change below SuperSpeed, because that is the link
+/// change a USB 2.0 hub has.
+const CHANGES: [Change; 7] = [
+    Change {
+        portsc: portsc::CSC,
+        usb2: Some((C_PORT_CONNECTION, F_C_PORT_CONNECTION)),
+        usb3: Some((C_PORT_CONNECTION, F_C_PORT_CONNECTION)),
+    },
+    Change { portsc: PEC, usb2: Some((C_PORT_ENABLE, F_C_PORT_ENABLE)), usb3: None },
+    Change { portsc: portsc::WRC, usb2: None, usb3: Some((C_BH_PORT_RESET, F_C_BH_PORT_RESET)) },
+    Change {
+        portsc: OCC,
+        usb2: Some((C_PORT_OVER_CURRENT, F_C_PORT_OVER_CURRENT)),
+        usb3: Some((C_PORT_OVER_CURRENT, F_C_PORT_OVER_CURRENT)),
+    },
+    Change {
+        portsc: portsc::PRC,
+        usb2: Some((C_PORT_RESET, F_C_PORT_RESET)),
+        usb3: Some((C_PORT_RESET, F_C_PORT_RESET)),
+    },
+    Change {
+        portsc: PLC,
+        usb2: Some((C_PORT_SUSPEND, F_C_PORT_SUSPEND)),
+        usb3: Some((C_PORT_LINK_STATE, F_C_PORT_LINK_STATE)),
+    },
+    Change { portsc: CEC, usb2: None, usb3: Some((C_PORT_CONFIG_ERROR, F_C_PORT_CONFIG_ERROR)) },
+];
+
+impl Change {
+    fn at(&self, superspeed: bool) -> Option<(u16, u16)> {
+        if superspeed { self.usb3 } else { self.usb2 }
+    }
+}
+
+/// What a hub descriptor says that this driver acts on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Descriptor {
+    /// bNbrPorts, as the hub stated it. [`Self::served`] is how many of them
+    /// this driver numbers.
+    pub ports: u8,
+    /// bPwrOn2PwrGood in milliseconds: how long after SET_FEATURE(PORT_POWER)
+    /// a port's power is good, and so how long before its connect state means
+    /// anything.
+    pub power_good_ms: u32,
+    /// TT Think Time, wHubCharacteristics bits 6:5, in the encoding the slot
+    /// context takes. Only a high-speed hub has a TT to think with.
+    pub think: u8,
+}
+
+/// Why a hub descriptor was not one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Refused {
+    /// Fewer bytes than the fields read here.
+    Short(usize),
+    /// A descriptor of another type, which is a hub answering for the other
+    /// speed's layout.
+    Type(u8),
+    /// A hub with no downstream ports.
+    NoPorts,
+}
+
+impl Descriptor {
+    /// Read the descriptor a hub at `superspeed` returned.
+    pub fn parse(bytes: &[u8], superspeed: bool) -> Result<Self, Refused> {
+        if bytes.len() < 7 {
+            return Err(Refused::Short(bytes.len()));
+        }
+        let want = if superspeed { DESCRIPTOR_USB3 } else { DESCRIPTOR_USB2 };
+        if bytes[1] != want {
+            return Err(Refused::Type(bytes[1]));
+        }
+        let ports = bytes[2];
+        if ports == 0 {
+            return Err(Refused::NoPorts);
+        }
+        let characteristics = u16::from_le_bytes([bytes[3], bytes[4]]);
+        Ok(Self {
+            ports,
+            power_good_ms: bytes[5] as u32 * 2,
+            think: if superspeed { 0 } else { ((characteristics >> 5) & 0x3) as u8 },
+        })
+    }
+
+    /// The ports this driver numbers: the first [`MAX_PORTS`] of them.
+    pub fn served(&self) -> u8 {
+        self.ports.min(MAX_PORTS)
+    }
+}
+
+/// One downstream port's GET_STATUS answer: wPortStatus and wPortChange.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PortStatus {
+    pub status: u16,
+    pub change: u16,
+}
+
+impl PortStatus {
+    /// What a port reads once the hub it is on has left the bus: nothing
+    /// attached, and the connect change that says something was. The machine
+    /// then tears the device down exactly as it would a pulled one, which is
+    /// what it is.
+    pub const GONE: Self = Self { status: 0, change: C_PORT_CONNECTION };
+
+    /// The four bytes GET_STATUS(port) returns.
+    pub fn decode(bytes: [u8; 4]) -> Self {
+        Self {
+            status: u16::from_le_bytes([bytes[0], bytes[1]]),
+            change: u16::from_le_bytes([bytes[2], bytes[3]]),
+        }
+    }
+
+    pub fn powered(self, superspeed: bool) -> bool {
+        let bit = if superspeed { PORT_POWER_USB3 } else { PORT_POWER_USB2 };
+        self.status & bit != 0
+    }
+
+    /// The same port as the PORTSC word a root port in its state would read.
+    ///
+    /// Speed is the Protocol Speed ID the controller would have put there. A
+    /// USB 2.0 hub says which of its three speeds a device trained at in two
+    /// bits; a SuperSpeed hub's downstream ports are SuperSpeed, since a
+    /// slower device on the same receptacle is on the companion USB 2.0 hub
+    /// instead. Link state passes straight through at SuperSpeed, and below it
+    /// is U0 for an enabled port and RxDetect otherwise — the only two
+    /// readings a USB 2.0 port is ever decided on.
+    pub fn portsc(self, superspeed: bool) -> Portsc {
+        let (status, change) = (self.status, self.change);
+        let mut raw = 0u32;
+        for (bit, to) in [
+            (PORT_CONNECTION, portsc::CCS),
+            (PORT_ENABLE, portsc::PED),
+            (PORT_OVER_CURRENT, portsc::OCA),
+            (PORT_RESET, portsc::PR),
+        ] {
+            if status & bit != 0 {
+                raw |= to;
+            }
+        }
+        if self.powered(superspeed) {
+            raw |= portsc::PP;
+        }
+        let connected = status & PORT_CONNECTION != 0;
+        let speed = if superspeed {
+            raw |= (status & PORT_LINK_STATE_USB3) as u32;
+            SPEED_SUPER
+        } else {
+            let pls = if status & PORT_ENABLE != 0 { 0 } else { 5 };
+            raw |= pls << 5;
+            if status & PORT_LOW_SPEED != 0 {
+                SPEED_LOW
+            } else if status & PORT_HIGH_SPEED != 0 {
+                SPEED_HIGH
+            } else {
+                SPEED_FULL
+            }
+        };
+        if connected {
+            raw |= (speed as u32) << 10;
+        }
+        for c in &CHANGES {
+            if let Some((bit, _)) = c.at(superspeed) {
+                if change & bit != 0 {
+                    raw |= c.portsc;
+                }
+            }
+        }
+        Portsc::from_raw(raw)
+    }
+}
+
+/// One class request on one downstream port.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Feature {
+    Set(u16),
+    Clear(u16),
+}
+
+/// A control request's setup stage, field for field (USB 2.0 §9.3).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Setup {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl Setup {
+    /// GET_DESCRIPTOR(Hub) for the layout a hub of this speed answers in.
+    pub const fn descriptor(superspeed: bool) -> Self {
+        let kind = if superspeed { DESCRIPTOR_USB3 } else { DESCRIPTOR_USB2 };
+        Self {
+            request_type: 0xA0,
+            request: 6,
+            value: (kind as u16) << 8,
+            index: 0,
+            length: DESCRIPTOR_BYTES,
+        }
+    }
+
+    /// GET_STATUS(port), whose four bytes are [`PortStatus::decode`]'s.
+    pub const fn port_status(port: u8) -> Self {
+        Self { request_type: 0xA3, request: 0, value: 0, index: port as u16, length: 4 }
+    }
+
+    /// SET_HUB_DEPTH, which a SuperSpeed hub needs before it can route a
+    /// packet at all: it reads its own nibble of the Route String at this
+    /// depth (USB 3.2 §10.16.2.9).
+    pub const fn hub_depth(depth: u8) -> Self {
+        Self { request_type: 0x20, request: 12, value: depth as u16, index: 0, length: 0 }
+    }
+
+    /// SET_FEATURE or CLEAR_FEATURE on `port`.
+    pub const fn feature(feature: Feature, port: u8) -> Self {
+        let (request, value) = match feature {
+            Feature::Set(f) => (3, f),
+            Feature::Clear(f) => (1, f),
+        };
+        Self { request_type: 0x23, request, value, index: port as u16, length: 0 }
+    }
+}
+
+/// The requests that perform `write` on a port that last read `seen`.
+///
+/// Change flags become CLEAR_FEATURE of the matching C_ selector, PR and WPR
+/// become SET_FEATURE of PORT_RESET and BH_PORT_RESET, and PP becomes
+/// SET_FEATURE(PORT_POWER) **only where the port was not already powered** — a
+/// neutral write carries PP back as it was read, which on a register is
+/// harmless and here would be a request per acknowledge for nothing. A warm
+/// reset of a USB 2.0 port is nothing, as WPR is on a root port of that kind.
+///
+/// The change flags go first, so a reset's own PRC is never cleared by the
+/// acknowledge issued alongside it.
+pub fn features(write: portsc::Write, seen: PortStatus, superspeed: bool) -> Features {
+    let raw = write.raw();
+    let mut out = Features { items: [Feature::Clear(0); 10], n: 0 };
+    for c in &CHANGES {
+        if let (true, Some((_, selector))) = (raw & c.portsc != 0, c.at(superspeed)) {
+            out.push(Feature::Clear(selector));
+        }
+    }
+    if raw & portsc::PP != 0 && !seen.powered(superspeed) {
+        out.push(Feature::Set(F_PORT_POWER));
+    }
+    if raw & portsc::PR != 0 {
+        out.push(Feature::Set(F_PORT_RESET));
+    }
+    if raw & portsc::WPR != 0 && superspeed {
+        out.push(Feature::Set(F_BH_PORT_RESET));
+    }
+    out
+}
+
+/// The wPortChange bit CLEAR_FEATURE(`selector`) clears on a hub of this
+/// speed, and `None` for a selector that is not a change flag's. What a port
+/// with no hub left to ask applies to its own last answer, so the machine's
+/// acknowledges still land somewhere and the port comes to rest.
+pub fn cleared_by(selector: u16, superspeed: bool) -> Option<u16> {
+    CHANGES.iter().find_map(|c| match c.at(superspeed) {
+        Some((bit, s)) if s == selector => Some(bit),
+        _ => None,
+    })
+}
+
+/// The power a port is given at bring-up, which a hub with power switching
+/// leaves off until asked.
+pub const POWER: Feature = Feature::Set(F_PORT_POWER);
+
+/// What [`features`] produced, in order. A fixed array because this crate has
+/// no heap, and ten because that is every flag plus every set.
+#[derive(Clone, Copy, Debug)]
+pub struct Features {
+    items: [Feature; 10],
+    n: usize,
+}
+
+impl Features {
+    fn push(&mut self, feature: Feature) {
+        self.items[self.n] = feature;
+        self.n += 1;
+    }
+
+    pub fn as_slice(&self) -> &[Feature] {
+        &self.items[..self.n]
+    }
+}
+
+/// Whether the status-change bitmap a hub's interrupt endpoint delivered names
+/// `port`. Bit 0 is the hub itself and bit N port N (USB 2.0 §11.12.4).
+pub fn changed(bitmap: &[u8], port: u8) -> bool {
+    bitmap.get(port as usize / 8).is_some_and(|byte| byte & (1 << (port % 8)) != 0)
+}
+
+/// The Transaction Translator a low- or full-speed device is reached through:
+/// the high-speed hub that has it, and which of that hub's ports leads down
+/// to the device.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Tt {
+    pub hub_slot: u8,
+    pub port: u8,
+}
+
+/// Where a device sits: the root port its path starts at, the Route String
+/// down from there, and the TT a slow device here would be translated by.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Place {
+    root_port: u8,
+    route: u32,
+    /// How many hubs are between the root port and this place, which is also
+    /// the depth a hub *at* this place is told by SET_HUB_DEPTH.
+    tiers: u8,
+    tt: Option<Tt>,
+}
+
+/// What a hub's own slot context carries beyond any device's.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HubSlot {
+    pub ports: u8,
+    pub think: u8,
+}
+
+impl Place {
+    /// A device on root port `port`, 1-based as the slot context numbers it.
+    pub const fn root(port: u8) -> Self {
+        Self { root_port: port, route: 0, tiers: 0, tt: None }
+    }
+
+    pub const fn root_port(&self) -> u8 {
+        self.root_port
+    }
+
+    pub const fn route(&self) -> u32 {
+        self.route
+    }
+
+    pub const fn tiers(&self) -> u8 {
+        self.tiers
+    }
+
+    pub const fn tt(&self) -> Option<Tt> {
+        self.tt
+    }
+
+    /// Port `port` of the hub at this place, which the controller gave
+    /// `hub_slot` and which runs at `hub_speed`. `None` past the fifth tier or
+    /// past the fifteenth port, neither of which a Route String can say.
+    ///
+    /// **The TT is the nearest high-speed hub's**, and only ever a high-speed
+    /// hub's. A full-speed hub has none of its own, so what is below it is
+    /// translated by whatever translates the hub; a SuperSpeed hub has no
+    /// low- or full-speed devices below it at all.
+    pub fn below(self, hub_slot: u8, hub_speed: u8, port: u8) -> Option<Self> {
+        if self.tiers >= MAX_TIERS || port == 0 || port > MAX_PORTS {
+            return None;
+        }
+        let tt = match hub_speed {
+            SPEED_HIGH => Some(Tt { hub_slot, port }),
+            SPEED_FULL | SPEED_LOW => self.tt,
+            _ => None,
+        };
+        Some(Self {
+            root_port: self.root_port,
+            route: self.route | ((port as u32) << (4 * self.tiers as u32)),
+            tiers: self.tiers + 1,
+            tt,
+        })
+    }
+
+    /// Slot context dwords 0 to 2 for a device at this place that runs at
+    /// `speed` and whose last endpoint context is `entries`, with `hub` set for
+    /// a hub's own slot (xHCI 1.2 §6.2.2).
+    ///
+    /// The TT fields are written only for a low- or full-speed device, which
+    /// is the only kind they are read for; MTT stays clear because this
+    /// driver leaves every hub in its single-TT default setting. TT Think
+    /// Time is a high-speed hub's and nobody else's.
+    pub fn slot_context(self, speed: u8, entries: u8, hub: Option<HubSlot>) -> [u32; 3] {
+        let mut dw0 = self.route | ((speed as u32) << 20) | ((entries as u32) << 27);
+        let mut dw1 = (self.root_port as u32) << 16;
+        let mut dw2 = 0;
+        if let (SPEED_FULL | SPEED_LOW, Some(tt)) = (speed, self.tt) {
+            dw2 |= tt.hub_slot as u32 | ((tt.port as u32) << 8);
+        }
+        if let Some(hub) = hub {
+            dw0 |= 1 << 26;
+            dw1 |= (hub.ports as u32) << 24;
+            if speed == SPEED_HIGH {
+                dw2 |= ((hub.think & 0x3) as u32) << 16;
+            }
+        }
+        [dw0, dw1, dw2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::port::{self, Reset};
+    use crate::protocol::Protocol;
+    use crate::LinkState;
+
+    fn status(status: u16, change: u16) -> PortStatus {
+        PortStatus { status, change }
+    }
+
+    #[test]
+    fn a_usb2_hub_descriptor_is_read_for_ports_power_and_think_time() {
+        // Four ports, individual power switching, TT think time 2, 50 ms to
+        // power good.
+        let bytes = [9, 0x29, 4, 0x41, 0x00, 25, 0, 0, 0xFF];
+        let d = Descriptor::parse(&bytes, false).unwrap();
+        assert_eq!(d, Descriptor { ports: 4, power_good_ms: 50, think: 2 });
+        assert_eq!(d.served(), 4);
+    }
+
+    #[test]
+    fn a_superspeed_hub_descriptor_has_no_think_time() {
+        let bytes = [12, 0x2A, 4, 0x60, 0x00, 50, 0, 0, 0, 0, 0, 0];
+        let d = Descriptor::parse(&bytes, true).unwrap();
+        assert_eq!(d, Descriptor { ports: 4, power_good_ms: 100, think: 0 });
+    }
+
+    #[test]
+    fn a_descriptor_that_is_not_one_is_refused_by_name() {
+        assert_eq!(Descriptor::parse(&[9, 0x29, 4], false), Err(Refused::Short(3)));
+        let usb3 = [12, 0x2A, 4, 0, 0, 50, 0, 0, 0, 0, 0, 0];
+        assert_eq!(Descriptor::parse(&usb3, false), Err(Refused::Type(0x2A)));
+        assert_eq!(Descriptor::parse(&[9, 0x29, 0, 0, 0, 1, 0, 0, 0], false), Err(Refused::NoPorts));
+    }
+
+    /// A Route String cannot name a sixteenth port, so a hub wider than that
+    /// is served on the ports it can.
+    #[test]
+    fn only_the_ports_a_route_string_can_name_are_served() {
+        let bytes = [9, 0x29, 28, 0, 0, 1, 0, 0, 0];
+        assert_eq!(Descriptor::parse(&bytes, false).unwrap().served(), 15);
+    }
+
+    #[test]
+    fn a_usb2_port_reads_as_the_portsc_a_root_port_in_its_state_would() {
+        // Connected, powered, connect changed: the fresh attach.
+        let p = status(PORT_CONNECTION | PORT_POWER_USB2, C_PORT_CONNECTION).portsc(false);
+        assert!(p.connected() && p.connect_changed() && !p.enabled());
+        assert_eq!(p.raw() & portsc::PP, portsc::PP);
+        // Enabled after a reset, at each speed the two bits can name.
+        for (bits, speed) in [(0, SPEED_FULL), (PORT_LOW_SPEED, SPEED_LOW), (PORT_HIGH_SPEED, SPEED_HIGH)] {
+            let p = status(PORT_CONNECTION | PORT_ENABLE | PORT_POWER_USB2 | bits, C_PORT_RESET)
+                .portsc(false);
+            assert!(p.enabled() && p.reset_changed());
+            assert_eq!(p.speed(), speed);
+            assert_eq!(p.link_state(), LinkState::Active);
+        }
+        // Nothing attached says no speed at all.
+        assert_eq!(status(PORT_POWER_USB2, 0).portsc(false).speed(), 0);
+    }
+
+    #[test]
+    fn a_superspeed_port_carries_its_link_state_and_power_where_usb3_puts_them() {
+        // Inactive, powered at bit 9 rather than 8.
+        let p = status(PORT_CONNECTION | PORT_POWER_USB3 | (6 << 5), 0).portsc(true);
+        assert_eq!(p.link_state(), LinkState::Inactive);
+        assert_eq!(p.raw() & portsc::PP, portsc::PP);
+        assert_eq!(p.speed(), SPEED_SUPER);
+        // Bit 8 is a link-state bit at SuperSpeed, not power.
+        assert_eq!(status(PORT_POWER_USB2, 0).portsc(true).raw() & portsc::PP, 0);
+        // A warm reset's own change flag is WRC, which the machine reads as a
+        // finished reset like PRC.
+        assert!(status(PORT_CONNECTION, C_BH_PORT_RESET).portsc(true).reset_changed());
+    }
+
+    /// Acknowledging what was read clears exactly the flags that were set, by
+    /// their own selectors, and nothing else.
+    #[test]
+    fn an_acknowledge_clears_each_change_by_its_own_feature() {
+        let seen = status(PORT_CONNECTION | PORT_POWER_USB2, C_PORT_CONNECTION | C_PORT_ENABLE);
+        let read = seen.portsc(false);
+        let out = features(read.neutral().acknowledging(read), seen, false);
+        assert_eq!(
+            out.as_slice(),
+            [Feature::Clear(F_C_PORT_CONNECTION), Feature::Clear(F_C_PORT_ENABLE)]
+        );
+    }
+
+    /// Every change flag a hub reports comes back out of the round trip as the
+    /// feature that clears that same flag, at both speeds. A table with one
+    /// row crossed would clear one change and leave another set forever — and
+    /// a change left set is one the hub never reports again.
+    #[test]
+    fn every_change_flag_round_trips_to_the_feature_that_clears_it() {
+        for superspeed in [false, true] {
+            for c in &CHANGES {
+                let Some((bit, selector)) = c.at(superspeed) else { continue };
+                let seen = status(PORT_CONNECTION, bit);
+                let read = seen.portsc(superspeed);
+                let out = features(read.neutral().acknowledging(read), seen, superspeed);
+                assert_eq!(out.as_slice(), [Feature::Clear(selector)], "{bit:#x} at {superspeed}");
+            }
+        }
+    }
+
+    /// A neutral write carries PP back as it was read. On a register that is
+    /// nothing; here it would be a SET_FEATURE per acknowledge.
+    #[test]
+    fn power_is_asked_for_only_where_the_port_has_none() {
+        let powered = status(PORT_POWER_USB2, 0);
+        let read = powered.portsc(false);
+        assert_eq!(features(read.neutral().powered(), powered, false).as_slice(), []);
+        let off = status(0, 0);
+        let read = off.portsc(false);
+        assert_eq!(features(read.neutral().powered(), off, false).as_slice(), [POWER]);
+    }
+
+    /// The reset the port machine chooses is the one the hub is asked for, and
+    /// a warm reset exists only at SuperSpeed.
+    #[test]
+    fn the_machines_resets_become_the_hubs() {
+        let attached = status(PORT_CONNECTION | PORT_POWER_USB2, 0);
+        let read = attached.portsc(false);
+        let kind = port::reset_needed(Some(Protocol::Usb2), read).unwrap();
+        assert_eq!(kind, Reset::Hot);
+        let out = features(port::reset_write(kind, read), attached, false);
+        assert_eq!(out.as_slice(), [Feature::Set(F_PORT_RESET)]);
+
+        let inactive = status(PORT_CONNECTION | PORT_POWER_USB3 | (6 << 5), 0);
+        let read = inactive.portsc(true);
+        let kind = port::reset_needed(Some(Protocol::Usb3), read).unwrap();
+        assert_eq!(kind, Reset::Warm);
+        let out = features(port::reset_write(kind, read), inactive, true);
+        assert_eq!(out.as_slice(), [Feature::Set(F_BH_PORT_RESET)]);
+        let usb2 = status(PORT_CONNECTION | PORT_POWER_USB2, 0);
+        let read = usb2.portsc(false);
+        assert_eq!(features(read.neutral().warm_resetting(), usb2, false).as_slice(), []);
+
+        // A SuperSpeed port whose link trained on its own needs nothing.
+        let trained = status(PORT_CONNECTION | PORT_ENABLE | PORT_POWER_USB3, C_PORT_CONNECTION);
+        assert_eq!(port::reset_needed(Some(Protocol::Usb3), trained.portsc(true)), None);
+    }
+
+    #[test]
+    fn each_clear_names_the_change_it_clears_at_its_own_speed() {
+        assert_eq!(cleared_by(F_C_PORT_CONNECTION, false), Some(C_PORT_CONNECTION));
+        assert_eq!(cleared_by(F_C_PORT_SUSPEND, false), Some(C_PORT_SUSPEND));
+        assert_eq!(cleared_by(F_C_PORT_LINK_STATE, true), Some(C_PORT_LINK_STATE));
+        assert_eq!(cleared_by(F_C_BH_PORT_RESET, false), None);
+        assert_eq!(cleared_by(F_PORT_RESET, false), None);
+    }
+
+    #[test]
+    fn a_bitmap_names_the_hub_at_bit_zero_and_each_port_at_its_number() {
+        let bitmap = [0b0000_0101, 0b1000_0000];
+        assert!(changed(&bitmap, 0));
+        assert!(!changed(&bitmap, 1));
+        assert!(changed(&bitmap, 2));
+        assert!(changed(&bitmap, 15));
+        assert!(!changed(&bitmap, 16), "past the end of what was delivered");
+    }
+
+    #[test]
+    fn the_setup_packets_are_the_class_requests_the_specification_names() {
+        assert_eq!(
+            Setup::port_status(3),
+            Setup { request_type: 0xA3, request: 0, value: 0, index: 3, length: 4 }
+        );
+        assert_eq!(Setup::descriptor(false).value, 0x2900);
+        assert_eq!(Setup::descriptor(true).value, 0x2A00);
+        assert_eq!(Setup::feature(POWER, 2), Setup {
+            request_type: 0x23, request: 3, value: 8, index: 2, length: 0,
+        });
+        assert_eq!(Setup::feature(Feature::Clear(F_C_PORT_RESET), 1).request, 1);
+        assert_eq!(Setup::hub_depth(2), Setup {
+            request_type: 0x20, request: 12, value: 2, index: 0, length: 0,
+        });
+    }
+
+    /// Each tier takes the next nibble, starting from the least significant,
+    /// and the root port is not part of the string at all.
+    #[test]
+    fn a_route_string_has_one_nibble_per_hub_below_the_root() {
+        let root = Place::root(3);
+        let first = root.below(1, SPEED_HIGH, 2).unwrap();
+        assert_eq!((first.root_port(), first.route(), first.tiers()), (3, 0x2, 1));
+        let second = first.below(4, SPEED_HIGH, 7).unwrap();
+        assert_eq!(second.route(), 0x72);
+        assert_eq!(second.tiers(), 2);
+    }
+
+    #[test]
+    fn a_sixth_tier_and_a_sixteenth_port_are_refused() {
+        let mut place = Place::root(1);
+        for tier in 0..MAX_TIERS {
+            place = place.below(tier + 1, SPEED_HIGH, 15).unwrap();
+        }
+        assert_eq!(place.route(), 0xF_FFFF);
+        assert_eq!(place.below(9, SPEED_HIGH, 1), None);
+        assert_eq!(Place::root(1).below(1, SPEED_HIGH, 16), None);
+        assert_eq!(Place::root(1).below(1, SPEED_HIGH, 0), None);
+    }
+
+    /// The TT is the nearest high-speed hub's: its own below it, inherited
+    /// through a full-speed hub, and none at all below a root port or a
+    /// SuperSpeed hub.
+    #[test]
+    fn a_slow_device_is_translated_by_the_nearest_high_speed_hub() {
+        let hs = Place::root(1).below(5, SPEED_HIGH, 3).unwrap();
+        assert_eq!(hs.tt(), Some(Tt { hub_slot: 5, port: 3 }));
+        let through_fs = hs.below(6, SPEED_FULL, 2).unwrap();
+        assert_eq!(through_fs.tt(), Some(Tt { hub_slot: 5, port: 3 }));
+        assert_eq!(Place::root(1).below(5, SPEED_FULL, 3).unwrap().tt(), None);
+        assert_eq!(Place::root(1).below(5, SPEED_SUPER, 3).unwrap().tt(), None);
+    }
+
+    #[test]
+    fn the_slot_context_says_where_the_device_is_and_what_a_hub_has() {
+        let place = Place::root(2).below(5, SPEED_HIGH, 3).unwrap();
+        // A full-speed keyboard behind the high-speed hub: route, speed,
+        // entries, root port and the TT that reaches it.
+        let [dw0, dw1, dw2] = place.slot_context(SPEED_FULL, 3, None);
+        assert_eq!(dw0, 0x3 | (1 << 20) | (3 << 27));
+        assert_eq!(dw1, 2 << 16);
+        assert_eq!(dw2, 5 | (3 << 8));
+        // A high-speed device there is not translated by anything.
+        assert_eq!(place.slot_context(SPEED_HIGH, 1, None)[2], 0);
+        // A high-speed hub's own slot: the Hub flag, its port count and its
+        // think time.
+        let hub = Some(HubSlot { ports: 4, think: 2 });
+        let [dw0, dw1, dw2] = Place::root(2).slot_context(SPEED_HIGH, 3, hub);
+        assert_eq!(dw0 & (1 << 26), 1 << 26);
+        assert_eq!(dw1, (4 << 24) | (2 << 16));
+        assert_eq!(dw2, 2 << 16);
+        // Think time is a high-speed hub's and nobody else's.
+        assert_eq!(Place::root(2).slot_context(SPEED_FULL, 3, hub)[2], 0);
+    }
+}
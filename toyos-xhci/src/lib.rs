@@ -8,13 +8,18 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 
+pub mod cdc;
 pub mod enumerate;
+pub mod hub;
 pub mod invariants;
 pub mod job;
 pub mod port;
 pub mod protocol;
 pub mod portsc;
 pub mod recovery;
+pub mod serial;
+pub mod uac;
+pub mod uas;
 
 pub use job::{Await, Outcome, Outstanding};
 pub use port::{Effect, Gone, Nanos, PortState, Step};
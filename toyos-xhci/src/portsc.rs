@@ -40,31 +40,31 @@ impl LinkState {
 }
 
 /// CCS — a device is attached. Read-only.
-const CCS: u32 = 1 << 0;
+pub(crate) const CCS: u32 = 1 << 0;
 /// PED — the port is enabled. RW1CS, which is why nothing here can set it.
-const PED: u32 = 1 << 1;
+pub(crate) const PED: u32 = 1 << 1;
 /// OCA — over-current. Read-only.
-const OCA: u32 = 1 << 3;
+pub(crate) const OCA: u32 = 1 << 3;
 /// PR — port reset. RW1S.
-const PR: u32 = 1 << 4;
+pub(crate) const PR: u32 = 1 << 4;
 /// PLS — the link state. RWS, written only through LWS, which this never sets.
-const PLS: u32 = 0xF << 5;
+pub(crate) const PLS: u32 = 0xF << 5;
 /// WRC — a warm reset finished. RW1CS, and USB3 ports only.
-const WRC: u32 = 1 << 19;
+pub(crate) const WRC: u32 = 1 << 19;
 /// WPR — warm port reset. RW1S, USB3 ports only, and the only way out of
 /// Inactive (§4.19.1.2.4).
-const WPR: u32 = 1 << 31;
+pub(crate) const WPR: u32 = 1 << 31;
 /// PP — port power. RWS.
-const PP: u32 = 1 << 9;
+pub(crate) const PP: u32 = 1 << 9;
 /// The speed the port trained at. Read-only, and §4.19.5 says it is not valid
 /// until PR has gone from '1' to '0'.
-const SPEED: u32 = 0xF << 10;
+pub(crate) const SPEED: u32 = 0xF << 10;
 /// PIC — port indicator control. RWS.
 const PIC: u32 = 0x3 << 14;
 /// CSC — the connect state changed.
-const CSC: u32 = 1 << 17;
+pub(crate) const CSC: u32 = 1 << 17;
 /// PRC — the reset finished.
-const PRC: u32 = 1 << 21;
+pub(crate) const PRC: u32 = 1 << 21;
 /// The wake enables. RWS.
 const WAKE: u32 = 0x7 << 25;
 /// DR — the device is removable. Read-only.
@@ -76,7 +76,7 @@ const DR: u32 = 1 << 30;
 /// Status Change Event when one of these goes '0' to '1' and **only then**, so
 /// a flag left set is a change the controller has already reported and will not
 /// report again.
-const CHANGES: u32 = 0x7F << 17;
+pub(crate) const CHANGES: u32 = 0x7F << 17;
 
 /// The bits a write cannot change.
 const READ_ONLY: u32 = CCS | OCA | SPEED | DR;
@@ -0,0 +1,328 @@
+//! A USB serial adapter's class protocol: which devices are one, and the
+//! control requests that set the line up before its bulk pipes carry bytes.
+//!
+//! Four families are served. **CDC-ACM** (CDC PSTN 1.2) is the class every
+//! microcontroller's USB stack and most modern adapters speak: a communication
+//! interface with a Union descriptor naming a data interface, whose setting 0
+//! has the bulk pair. **FTDI** FT232 and its successors are vendor-specific,
+//! and are what QEMU's `usb-serial` emulates. **Silicon Labs CP210x** is
+//! vendor-specific too and is on a large share of the boards that are not
+//! FTDI, and **WCH CH340/CH341** is on most of the rest — every cheap cable
+//! and clone board. No vendor family says what it is in its descriptors —
+//! interface class 0xFF is "ask the vendor" — so they are known by their IDs.
+//!
+//! What is configured is the same for all four: 115200 baud, eight data bits,
+//! no parity, one stop bit, no flow control, DTR and RTS raised. The console on
+//! the other end of the cable is a person's terminal, and that is what every
+//! terminal program starts at. Every number decoded here is the device's, so
+//! every decode takes the device's length rather than the one it should have.
+
+use crate::cdc::Setup;
+
+/// The family an adapter belongs to, which decides its line setup and whether
+/// its bulk IN transfers carry a status prefix.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Acm,
+    Ftdi,
+    Cp210x,
+    Ch34x,
+}
+
+/// bInterfaceSubclass of a CDC communication interface that is an Abstract
+/// Control Model one, under [`crate::cdc::CLASS_COMM`].
+pub const SUBCLASS_ACM: u8 = 0x02;
+/// bInterfaceClass of an interface whose protocol is the vendor's.
+pub const CLASS_VENDOR: u8 = 0xFF;
+
+/// The rate every line is set to.
+pub const BAUD: u32 = 115_200;
+
+/// The vendor family a device is by its IDs, or `None` when its descriptors
+/// have to say.
+///
+/// Only product IDs known to be the UART parts are listed. FTDI's and Silicon
+/// Labs' vendor IDs also cover JTAG probes and multi-protocol bridges whose
+/// interface 0 is not a serial port, and binding one of those as a console
+/// would write log lines into a debugger.
+pub fn vendor_kind(vendor: u16, product: u16) -> Option<Kind> {
+    match (vendor, product) {
+        // FT232R/BM (QEMU's `usb-serial`), FT232H, FT230X.
+        (0x0403, 0x6001 | 0x6014 | 0x6015) => Some(Kind::Ftdi),
+        // CP2102/CP2104, which is every CP210x board that ships with the
+        // default IDs.
+        (0x10C4, 0xEA60) => Some(Kind::Cp210x),
+        // CH340 (and CH340K) and CH341A in its UART mode. WCH's other product
+        // IDs are the CH341's I2C/SPI and parallel-port modes.
+        (0x1A86, 0x7523 | 0x7522 | 0x5523) => Some(Kind::Ch34x),
+        _ => None,
+    }
+}
+
+/// The 14-bit divisor and 3-bit fraction an FTDI chip takes for `baud`, as the
+/// value of its SET_BAUD_RATE request.
+///
+/// The chip divides a 3 MHz clock by `n + f/8`. The fraction is not encoded as
+/// its eighths: bits 14–15 of the value and bit 0 of the index carry a code
+/// whose order is FTDI's (AN232B-05), and only the two low bits fit in the
+/// value. Every fraction this driver asks for fits there: 115200 is 26 exactly.
+pub fn ftdi_divisor(baud: u32) -> u16 {
+    /// The fraction in eighths, as the code the chip reads.
+    const FRACTION: [u16; 8] = [0, 3, 2, 4, 1, 5, 6, 7];
+    let eighths = (3_000_000 * 8 + baud / 2) / baud;
+    let whole = (eighths / 8).min(0x3FFF) as u16;
+    let code = FRACTION[(eighths % 8) as usize];
+    whole | (code & 3) << 14
+}
+
+/// The prescaler and divisor a CH34x takes for `baud`, as the value of its
+/// divisor register pair — `0x13` (divisor) in the high byte, `0x12`
+/// (prescaler) in the low.
+///
+/// The chip divides 48 MHz by a prescaler of `2^(12 - 3·ps - fact)` and then
+/// by `div`, which is written as `256 - div`. The largest prescaler `ps` whose
+/// slowest rate is still below `baud` is taken, the base clock halved when the
+/// divisor falls outside 9..=255, the nearer of `div` and `div + 1` kept, and
+/// an even divisor halved in place of a whole base clock — the order Linux's
+/// `ch341.c` arrives at, which is the only description of the part there is.
+/// Rates outside 46..=2000000 are clamped to it. Bit 7 of the prescaler byte
+/// is not set here; see [`line_step`].
+pub fn ch34x_divisor(baud: u32) -> u16 {
+    const CLOCK: u32 = 48_000_000;
+    let clk_div = |ps: u32, fact: u32| 1u32 << (12 - 3 * ps - fact);
+    let baud = baud.clamp(46, 2_000_000);
+    let ps = (0..4u32).rev().find(|&ps| baud > CLOCK / (clk_div(ps, 1) * 512)).unwrap_or(0);
+    let mut fact = 1;
+    let mut clk = clk_div(ps, fact);
+    let mut div = CLOCK / (clk * baud);
+    if !(9..=255).contains(&div) {
+        div /= 2;
+        clk *= 2;
+        fact = 0;
+    }
+    let div = div.max(2);
+    // Sixteenths, so a low rate's rounding does not pick the wrong neighbour.
+    let div = if 16 * CLOCK / (clk * div) - 16 * baud >= 16 * baud - 16 * CLOCK / (clk * (div + 1)) {
+        div + 1
+    } else {
+        div
+    };
+    let (div, fact) = if fact == 1 && div % 2 == 0 { (div / 2, 0) } else { (div, fact) };
+    ((0x100 - div.min(0x100)) as u16) << 8 | (fact << 2) as u16 | ps as u16
+}
+
+/// The bytes an FTDI chip received, out of one bulk IN transfer, into `out`;
+/// how many there were.
+///
+/// **Every packet starts with two status bytes** — modem and line status — and
+/// the chip sends them even when it has nothing else, every 16 ms. They are not
+/// the line's, so a driver that hands the transfer through prints two bytes of
+/// noise per packet. A transfer that spans several packets has a prefix in
+/// each, so the strip is per `max_packet` and not once.
+pub fn ftdi_data(transfer: &[u8], max_packet: u16, out: &mut [u8]) -> usize {
+    let max_packet = (max_packet as usize).max(3);
+    let mut n = 0;
+    for packet in transfer.chunks(max_packet) {
+        for &b in packet.get(2..).unwrap_or(&[]) {
+            if n == out.len() {
+                return n;
+            }
+            out[n] = b;
+            n += 1;
+        }
+    }
+    n
+}
+
+/// How many control requests `kind`'s line setup is.
+pub fn line_steps(kind: Kind) -> u8 {
+    match kind {
+        Kind::Acm => 2,
+        Kind::Ftdi => 5,
+        Kind::Cp210x => 4,
+        Kind::Ch34x => 4,
+    }
+}
+
+/// The `step`th control request of `kind`'s line setup on interface `iface`,
+/// with the data stage it carries, or `None` past the last.
+///
+/// Every request is host-to-device. A data stage of `length` zero has none.
+pub fn line_step(kind: Kind, iface: u8, step: u8) -> Option<(Setup, [u8; 8])> {
+    let iface = iface as u16;
+    let mut data = [0u8; 8];
+    let setup = match (kind, step) {
+        // SET_LINE_CODING: dwDTERate, bCharFormat (1 stop bit), bParityType
+        // (none), bDataBits (PSTN 1.2 §6.3.11).
+        (Kind::Acm, 0) => {
+            data[..4].copy_from_slice(&BAUD.to_le_bytes());
+            data[6] = 8;
+            Setup { request_type: 0x21, request: 0x20, value: 0, index: iface, length: 7 }
+        }
+        // SET_CONTROL_LINE_STATE with DTR and RTS. Many ACM firmwares hold
+        // their output until DTR is up, since that is how a terminal says it
+        // has opened the port.
+        (Kind::Acm, 1) => {
+            Setup { request_type: 0x21, request: 0x22, value: 3, index: iface, length: 0 }
+        }
+        // FTDI's requests name the port in the index, and port 0 is "the
+        // only one" on a single-port part.
+        (Kind::Ftdi, _) => {
+            let (request, value) = match step {
+                // RESET, which flushes whatever the chip buffered before the
+                // host was there to read it.
+                0 => (0x00, 0),
+                1 => (0x03, ftdi_divisor(BAUD)),
+                // SET_DATA: eight bits, no parity, one stop bit.
+                2 => (0x04, 8),
+                // MODEM_CTRL: DTR and RTS high, with their enable bits.
+                3 => (0x01, 0x0303),
+                // SET_FLOW_CTRL: none, or the chip waits on a CTS nobody wired.
+                4 => (0x02, 0),
+                _ => return None,
+            };
+            Setup { request_type: 0x40, request, value, index: 0, length: 0 }
+        }
+        // Silicon Labs' requests are to the interface (AN571).
+        (Kind::Cp210x, 0) => {
+            // IFC_ENABLE: the UART is off until this.
+            Setup { request_type: 0x41, request: 0x00, value: 1, index: iface, length: 0 }
+        }
+        (Kind::Cp210x, 1) => {
+            // SET_BAUDRATE, whose rate is the data stage.
+            data[..4].copy_from_slice(&BAUD.to_le_bytes());
+            Setup { request_type: 0x41, request: 0x1E, value: 0, index: iface, length: 4 }
+        }
+        (Kind::Cp210x, 2) => {
+            // SET_LINE_CTL: eight data bits in 8–11, no parity, one stop bit.
+            Setup { request_type: 0x41, request: 0x03, value: 0x0800, index: iface, length: 0 }
+        }
+        (Kind::Cp210x, 3) => {
+            // SET_MHS: DTR and RTS high, with their mask bits.
+            Setup { request_type: 0x41, request: 0x07, value: 0x0303, index: iface, length: 0 }
+        }
+        // WCH's requests are to the device, with a register pair in the value
+        // and what is written to it in the index.
+        (Kind::Ch34x, _) => {
+            let (request, value, index) = match step {
+                // SERIAL_INIT, which starts the UART.
+                0 => (0xA1, 0, 0),
+                // WRITE_REG of the prescaler (0x12) and divisor (0x13). Bit 7
+                // of the prescaler byte is "no wait": without it a CH341A of
+                // version 0x27 or later holds received bytes back until its
+                // buffer fills, and an older part ignores it.
+                1 => (0x9A, 0x1312, ch34x_divisor(BAUD) | 0x80),
+                // WRITE_REG of LCR (0x18) and LCR2 (0x25): receiver and
+                // transmitter on, eight data bits, no parity, one stop bit.
+                2 => (0x9A, 0x2518, 0x00C3),
+                // MODEM_CTRL, whose bits are active low: DTR (5) and RTS (6)
+                // raised by being the two bits clear.
+                3 => (0xA4, !0x60u16, 0),
+                _ => return None,
+            };
+            Setup { request_type: 0x40, request, value, index, length: 0 }
+        }
+        _ => return None,
+    };
+    Some((setup, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_uart_parts_are_known_by_their_ids() {
+        assert_eq!(vendor_kind(0x0403, 0x6001), Some(Kind::Ftdi));
+        assert_eq!(vendor_kind(0x10C4, 0xEA60), Some(Kind::Cp210x));
+        assert_eq!(vendor_kind(0x1A86, 0x7523), Some(Kind::Ch34x));
+        // The CH341 in its I2C/SPI mode, which has no UART behind it.
+        assert_eq!(vendor_kind(0x1A86, 0x5512), None);
+        // FTDI's FT2232H, which is as often a JTAG probe as a serial port.
+        assert_eq!(vendor_kind(0x0403, 0x6010), None);
+        // A product ID of one vendor under another's.
+        assert_eq!(vendor_kind(0x10C4, 0x6001), None);
+    }
+
+    /// The two rates AN232B-05 works through: 115200 is a whole divisor, and
+    /// 9600 is 312.5 — a half, whose code is 1 in bits 14–15.
+    #[test]
+    fn the_ftdi_divisor_encodes_its_fraction_in_the_chips_order() {
+        assert_eq!(ftdi_divisor(115_200), 26);
+        assert_eq!(ftdi_divisor(9600), 0x4138);
+        // A rate past what the divisor reaches is clamped, not wrapped into a
+        // fast one.
+        assert_eq!(ftdi_divisor(1) & 0x3FFF, 0x3FFF);
+    }
+
+    #[test]
+    fn the_ftdi_status_is_stripped_from_every_packet() {
+        let mut out = [0u8; 16];
+        // A status-only packet, which is most of them.
+        assert_eq!(ftdi_data(&[0x01, 0x60], 64, &mut out), 0);
+        // Two packets of 4 bytes each carrying two bytes of line data.
+        let n = ftdi_data(&[0x01, 0x60, b'o', b'k', 0x01, 0x60, b'!', b'\n'], 4, &mut out);
+        assert_eq!(&out[..n], b"ok!\n");
+        // A runt packet carrying less than its own status is nothing.
+        assert_eq!(ftdi_data(&[0x01], 64, &mut out), 0);
+        // And the output is not overrun by a device that sends more than asked.
+        let mut small = [0u8; 2];
+        assert_eq!(ftdi_data(&[0x01, 0x60, 1, 2, 3], 64, &mut small), 2);
+    }
+
+    #[test]
+    fn every_line_setup_is_its_stated_length_and_host_to_device() {
+        for kind in [Kind::Acm, Kind::Ftdi, Kind::Cp210x, Kind::Ch34x] {
+            let steps = line_steps(kind);
+            for step in 0..steps {
+                let (setup, _) = line_step(kind, 1, step).expect("a step within the count");
+                assert_eq!(setup.request_type & 0x80, 0, "{kind:?} {step}");
+                assert!(setup.length <= 8, "{kind:?} {step}");
+            }
+            assert_eq!(line_step(kind, 1, steps), None, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn the_acm_line_coding_is_115200_8n1() {
+        let (setup, data) = line_step(Kind::Acm, 2, 0).unwrap();
+        assert_eq!((setup.request, setup.index, setup.length), (0x20, 2, 7));
+        assert_eq!(data[..7], [0x00, 0xC2, 0x01, 0x00, 0, 0, 8]);
+        let (setup, _) = line_step(Kind::Acm, 2, 1).unwrap();
+        assert_eq!((setup.request, setup.value, setup.length), (0x22, 3, 0));
+    }
+
+    #[test]
+    fn the_cp210x_rate_is_its_data_stage() {
+        let (setup, data) = line_step(Kind::Cp210x, 0, 1).unwrap();
+        assert_eq!((setup.request, setup.length), (0x1E, 4));
+        assert_eq!(u32::from_le_bytes([data[0], data[1], data[2], data[3]]), BAUD);
+    }
+
+    /// The values Linux's `ch341.c` writes for the two rates every terminal
+    /// has offered: 115200 is prescaler 3 and divisor 52, 9600 is prescaler 2
+    /// and divisor 78, both on the halved base clock.
+    #[test]
+    fn the_ch34x_divisor_matches_what_the_part_is_known_to_take() {
+        assert_eq!(ch34x_divisor(115_200), 0xCC03);
+        assert_eq!(ch34x_divisor(9600), 0xB202);
+        // Clamped at both ends rather than wrapped into a rate nobody asked for.
+        assert_eq!(ch34x_divisor(1), ch34x_divisor(46));
+        assert_eq!(ch34x_divisor(u32::MAX), ch34x_divisor(2_000_000));
+    }
+
+    #[test]
+    fn the_ch34x_setup_is_init_rate_format_and_lines() {
+        let requests: [(u8, u16, u16); 4] = core::array::from_fn(|step| {
+            let (setup, _) = line_step(Kind::Ch34x, 0, step as u8).unwrap();
+            assert_eq!((setup.request_type, setup.length), (0x40, 0));
+            (setup.request, setup.value, setup.index)
+        });
+        assert_eq!(requests, [
+            (0xA1, 0, 0),
+            (0x9A, 0x1312, 0xCC83),
+            (0x9A, 0x2518, 0x00C3),
+            (0xA4, 0xFF9F, 0),
+        ]);
+    }
+}
@@ -0,0 +1,411 @@
+//! A USB audio device's class protocol: which streaming setting plays what the
+//! mixer makes, how the rate is set, what a feedback endpoint reports, and how
+//! many frames each isochronous packet carries.
+//!
+//! Both versions of the Audio Class are served for playback. **UAC1** (Audio
+//! Device Class 1.0) is what every full-speed DAC speaks and what QEMU's
+//! `usb-audio` emulates: the rate is a property of the endpoint, set with a
+//! request to it. **UAC2** (Audio Device Class 2.0) is what nearly every
+//! high-speed DAC speaks: the rate is a property of a Clock Source entity on
+//! the control interface, and the streaming setting only says its format.
+//!
+//! What is played is always the same: two channels of 16-bit PCM at 48 kHz,
+//! which is what soundd mixes. A setting that cannot take exactly that is not
+//! chosen — converting here would be a second mixer in the kernel. Every
+//! number decoded here is the device's, so every decode takes the device's
+//! length rather than the one it should have.
+
+use crate::cdc::Setup;
+
+/// bInterfaceClass of both audio interfaces, and the two subclasses.
+pub const CLASS_AUDIO: u8 = 0x01;
+pub const SUBCLASS_CONTROL: u8 = 0x01;
+pub const SUBCLASS_STREAMING: u8 = 0x02;
+/// bInterfaceProtocol of a UAC2 interface; a UAC1 one states 0.
+pub const PROTOCOL_UAC2: u8 = 0x20;
+
+/// The class-specific interface descriptors read here, by bDescriptorSubtype.
+/// The first two are a streaming interface's; the third a UAC2 control
+/// interface's, where the same numbers mean other things.
+pub const AS_GENERAL: u8 = 0x01;
+pub const FORMAT_TYPE: u8 = 0x02;
+pub const CLOCK_SOURCE: u8 = 0x0A;
+/// A class-specific endpoint descriptor, which follows the endpoint it
+/// describes (UAC1 §4.6.1.2).
+pub const CS_ENDPOINT: u8 = 0x25;
+
+/// The one format played: what soundd mixes, and what every DAC takes.
+pub const RATE: u32 = 48_000;
+pub const CHANNELS: u8 = 2;
+/// Bytes per sample, which UAC1 calls a subframe and UAC2 a subslot.
+pub const SUBFRAME: u8 = 2;
+pub const FRAME_BYTES: u32 = CHANNELS as u32 * SUBFRAME as u32;
+
+/// Which version of the class a function speaks, from its control interface.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Version {
+    Uac1,
+    Uac2,
+}
+
+impl Version {
+    /// The version a bInterfaceProtocol says. Anything that is not UAC2 is
+    /// read as UAC1: 1.0 predates the field and left it zero, and UAC3 devices
+    /// offer a UAC1 or UAC2 configuration beside their own.
+    pub fn of(protocol: u8) -> Self {
+        if protocol == PROTOCOL_UAC2 { Self::Uac2 } else { Self::Uac1 }
+    }
+}
+
+/// A UAC2 Clock Source entity (UAC2 §4.7.2.1): its ID, which the rate request
+/// is addressed to, and whether the host may set its frequency.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Clock {
+    pub id: u8,
+    pub programmable: bool,
+}
+
+impl Clock {
+    /// Decode one control interface descriptor, or `None` for another subtype
+    /// or one too short to carry the fields.
+    pub fn parse(desc: &[u8]) -> Option<Self> {
+        if desc.len() < 8 || desc[1] != 0x24 || desc[2] != CLOCK_SOURCE {
+            return None;
+        }
+        // bmControls bits 1–0 are the frequency control, and 0b11 is "host
+        // programmable"; 0b01 is read-only, a crystal the host cannot change.
+        Some(Self { id: desc[3], programmable: desc[5] & 0b11 == 0b11 })
+    }
+}
+
+/// What one streaming setting's class-specific descriptors said, as they are
+/// read. Two descriptors and not one: UAC1 puts the channel count in the
+/// format and UAC2 in the general descriptor before it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Stream {
+    pub pcm: bool,
+    pub channels: u8,
+    pub subframe: u8,
+    pub bits: u8,
+    /// Whether the setting can run at [`RATE`]. A UAC1 format lists its rates;
+    /// a UAC2 one has none, since the rate is its clock's, so this is only
+    /// ever false for a UAC1 setting that listed others.
+    pub rate: bool,
+}
+
+impl Stream {
+    /// Read one class-specific interface descriptor of the setting into what
+    /// is known. Another subtype, or one too short, teaches nothing.
+    pub fn read(&mut self, desc: &[u8], version: Version) {
+        if desc.len() < 3 || desc[1] != 0x24 {
+            return;
+        }
+        match (desc[2], version) {
+            // wFormatTag 1 is PCM (UAC1 Formats §A.1.1).
+            (AS_GENERAL, Version::Uac1) if desc.len() >= 7 => {
+                self.pcm = u16::from_le_bytes([desc[5], desc[6]]) == 1;
+            }
+            // bmFormats bit 0 is PCM, bNrChannels follows (UAC2 §4.9.2).
+            (AS_GENERAL, Version::Uac2) if desc.len() >= 11 => {
+                self.pcm = desc[5] == 1 && desc[6] & 1 != 0;
+                self.channels = desc[10];
+                self.rate = true;
+            }
+            // Type I, whose rates follow as three-byte values: a table of
+            // bSamFreqType of them, or a continuous range when that is zero.
+            (FORMAT_TYPE, Version::Uac1) if desc.len() >= 8 && desc[3] == 1 => {
+                self.channels = desc[4];
+                self.subframe = desc[5];
+                self.bits = desc[6];
+                let rate = |at: usize| {
+                    desc.get(at..at + 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]))
+                };
+                self.rate = match desc[7] {
+                    0 => matches!((rate(8), rate(11)), (Some(lo), Some(hi)) if (lo..=hi).contains(&RATE)),
+                    n => (0..n as usize).any(|i| rate(8 + 3 * i) == Some(RATE)),
+                };
+            }
+            (FORMAT_TYPE, Version::Uac2) if desc.len() >= 6 && desc[3] == 1 => {
+                self.subframe = desc[4];
+                self.bits = desc[5];
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the setting plays what soundd sends, exactly.
+    pub fn plays(&self) -> bool {
+        self.pcm
+            && self.channels == CHANNELS
+            && self.subframe == SUBFRAME
+            && self.bits == 8 * SUBFRAME
+            && self.rate
+    }
+}
+
+/// Whether a UAC1 class-specific endpoint descriptor says its endpoint has a
+/// Sampling Frequency control (UAC1 §4.6.1.2, bmAttributes bit 0). One that
+/// does not runs at the one rate its format listed, and asking it to set one
+/// is a request it may stall.
+pub fn endpoint_rate_control(desc: &[u8]) -> bool {
+    desc.len() >= 4 && desc[1] == CS_ENDPOINT && desc[2] == 1 && desc[3] & 1 != 0
+}
+
+/// Whether an isochronous endpoint's bmAttributes say it is asynchronous: it
+/// runs on its own clock, and says how fast on a feedback endpoint.
+pub fn asynchronous(attributes: u8) -> bool {
+    (attributes >> 2) & 0b11 == 0b01
+}
+
+/// The request that sets the rate to [`RATE`], with its data stage: the
+/// endpoint's for UAC1 (§5.2.3.2.3.1), three bytes; the clock's for UAC2
+/// (§5.2.5.1.1), four, addressed through the control interface.
+pub fn sample_rate(version: Version, endpoint: u8, clock: u8, control_iface: u8) -> (Setup, [u8; 4]) {
+    let data = RATE.to_le_bytes();
+    let setup = match version {
+        Version::Uac1 => {
+            Setup { request_type: 0x22, request: 0x01, value: 0x0100, index: endpoint as u16, length: 3 }
+        }
+        Version::Uac2 => Setup {
+            request_type: 0x21,
+            request: 0x01,
+            value: 0x0100,
+            index: (clock as u16) << 8 | control_iface as u16,
+            length: 4,
+        },
+    };
+    (setup, data)
+}
+
+/// A packet's service interval in microseconds, from the endpoint's bInterval:
+/// an exponent of frames below High Speed and of microframes at it.
+pub fn interval_us(high_speed: bool, b_interval: u8) -> u32 {
+    let exp = b_interval.clamp(1, 16) as u32 - 1;
+    (if high_speed { 125 } else { 1000 }) << exp.min(if high_speed { 15 } else { 5 })
+}
+
+/// `rate` as frames per millisecond in 16.16 fixed point, which is the unit a
+/// feedback value is decoded into.
+pub fn per_ms(rate: u32) -> u32 {
+    ((rate as u64) * 65536 / 1000) as u32
+}
+
+/// What a feedback packet says the device consumes, as frames per millisecond
+/// in 16.16, or `None` when it says nothing believable.
+///
+/// **The format is the speed's, and devices get it wrong.** Full speed is
+/// 10.14 per frame in three bytes and high speed 16.16 per microframe in four
+/// (USB 2.0 §5.12.4.2), but plenty of full-speed devices send the high-speed
+/// layout and some high-speed ones the full-speed one. So the value is read
+/// the way the speed says and then the other way, and the first reading
+/// within an eighth of `nominal` is the one believed: a DAC whose crystal is
+/// off by more than that is not one anybody would listen to, and the two
+/// readings differ by far more than that.
+pub fn feedback(bytes: &[u8], high_speed: bool, nominal: u32) -> Option<u32> {
+    let u24 = bytes.get(..3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]) << 2);
+    let u32_ = bytes.get(..4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let (first, second) = if high_speed { (u32_, u24) } else { (u24, u32_) };
+    let scale = if high_speed { 8 } else { 1 };
+    let plausible = |v: u32| {
+        let v = v.checked_mul(scale)?;
+        (v.abs_diff(nominal) <= nominal / 8).then_some(v)
+    };
+    first.and_then(plausible).or_else(|| second.and_then(plausible))
+}
+
+/// How many frames each packet carries, so that the packets average out to
+/// the rate — 48 at a time at 48 kHz, and 44 or 45 at 44.1.
+///
+/// A 16.16 accumulator and not a per-packet division: the fraction a packet
+/// could not carry is carried by a later one, so no rate drifts against its
+/// packets however long it plays. [`Self::steer`] is where an asynchronous
+/// device's feedback moves the rate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Pacer {
+    /// Frames per packet, 16.16.
+    per_packet: u64,
+    /// The fraction of a frame not yet carried, 16.16.
+    carry: u64,
+    interval_us: u32,
+    max_frames: u32,
+}
+
+impl Pacer {
+    /// `max_frames` is what the endpoint's max packet size has room for. A
+    /// packet never carries more, even if the rate asks it to.
+    pub fn new(rate: u32, interval_us: u32, max_frames: u32) -> Self {
+        Self {
+            per_packet: (rate as u64 * interval_us as u64 * 65536).div_ceil(1_000_000),
+            carry: 0,
+            interval_us,
+            max_frames,
+        }
+    }
+
+    /// Follow the rate a feedback packet reported, frames per millisecond in
+    /// 16.16 as [`feedback`] decodes it.
+    pub fn steer(&mut self, per_ms: u32) {
+        self.per_packet = per_ms as u64 * self.interval_us as u64 / 1000;
+    }
+
+    /// The frames the next packet carries.
+    pub fn frames(&mut self) -> u32 {
+        let due = self.carry + self.per_packet;
+        self.carry = due & 0xFFFF;
+        ((due >> 16) as u32).min(self.max_frames)
+    }
+
+    /// The rate being played, in millihertz, which is what soundd is told so
+    /// its timing can follow a device that does not run at the nominal one.
+    pub fn rate_millihz(&self) -> u32 {
+        (self.per_packet * 1_000_000_000 / (self.interval_us as u64 * 65536)) as u32
+    }
+}
+
+/// The periods of a ring of `periods` periods, `period_bytes` each, that
+/// finished playing when the bytes played went from `before` to `after`, as a
+/// mask: bit `p` for period `p`.
+///
+/// A period finishes when its last byte has gone, so a packet that ends
+/// exactly on a boundary completes the period before it and not the one after.
+/// A gap of a whole ring or more is every period.
+pub fn periods_done(before: u64, after: u64, period_bytes: u32, periods: u32) -> u32 {
+    let (first, last) = (before / period_bytes as u64, after / period_bytes as u64);
+    if last - first >= periods as u64 {
+        return if periods >= 32 { u32::MAX } else { (1 << periods) - 1 };
+    }
+    (first..last).fold(0, |mask, p| mask | 1 << (p % periods as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// QEMU's `usb-audio` streaming setting 1: PCM, a Type I format of two
+    /// 16-bit channels at the one rate 48000.
+    const UAC1_GENERAL: [u8; 7] = [7, 0x24, 1, 1, 1, 1, 0];
+    const UAC1_FORMAT: [u8; 11] = [11, 0x24, 2, 1, 2, 2, 16, 1, 0x80, 0xBB, 0x00];
+
+    #[test]
+    fn a_uac1_stereo_48k_setting_plays() {
+        let mut stream = Stream::default();
+        stream.read(&UAC1_GENERAL, Version::Uac1);
+        stream.read(&UAC1_FORMAT, Version::Uac1);
+        assert!(stream.plays(), "{stream:?}");
+        // The same setting listing only 44.1 kHz does not.
+        let mut format = UAC1_FORMAT;
+        format[8..11].copy_from_slice(&[0x44, 0xAC, 0x00]);
+        let mut other = Stream::default();
+        other.read(&UAC1_GENERAL, Version::Uac1);
+        other.read(&format, Version::Uac1);
+        assert!(!other.plays());
+    }
+
+    /// A continuous range covers the rate if it contains it, and a table is
+    /// read no further than the descriptor's own length.
+    #[test]
+    fn a_uac1_rate_range_and_a_short_table_are_bounded_by_the_descriptor() {
+        let range = [14, 0x24, 2, 1, 2, 2, 16, 0, 0x44, 0xAC, 0, 0x00, 0x77, 0x01];
+        let mut stream = Stream::default();
+        stream.read(&range, Version::Uac1);
+        assert!(stream.rate);
+        // A table claiming three rates with room for one, which is 44.1.
+        let short = [11, 0x24, 2, 1, 2, 2, 16, 3, 0x44, 0xAC, 0x00];
+        let mut stream = Stream::default();
+        stream.read(&short, Version::Uac1);
+        assert!(!stream.rate);
+    }
+
+    #[test]
+    fn a_uac2_setting_takes_its_channels_from_the_general_descriptor() {
+        let general = [16, 0x24, 1, 1, 0, 1, 1, 0, 0, 0, 2, 3, 0, 0, 0, 0];
+        let format = [6, 0x24, 2, 1, 2, 16];
+        let mut stream = Stream::default();
+        stream.read(&general, Version::Uac2);
+        stream.read(&format, Version::Uac2);
+        assert!(stream.plays(), "{stream:?}");
+        // 24 bits in four-byte subslots is not what soundd sends.
+        let mut wide = Stream::default();
+        wide.read(&general, Version::Uac2);
+        wide.read(&[6, 0x24, 2, 1, 4, 24], Version::Uac2);
+        assert!(!wide.plays());
+    }
+
+    #[test]
+    fn the_rate_request_is_the_endpoints_for_uac1_and_the_clocks_for_uac2() {
+        let (setup, data) = sample_rate(Version::Uac1, 0x01, 0, 0);
+        assert_eq!(setup, Setup { request_type: 0x22, request: 1, value: 0x0100, index: 1, length: 3 });
+        assert_eq!(data[..3], [0x80, 0xBB, 0x00]);
+        let (setup, _) = sample_rate(Version::Uac2, 0x01, 0x29, 0);
+        assert_eq!((setup.request_type, setup.index, setup.length), (0x21, 0x2900, 4));
+        assert_eq!(Clock::parse(&[8, 0x24, 0x0A, 0x29, 3, 0b11, 0, 0]),
+            Some(Clock { id: 0x29, programmable: true }));
+        assert_eq!(Clock::parse(&[8, 0x24, 0x0A, 0x29, 1, 0b01, 0, 0]).map(|c| c.programmable),
+            Some(false));
+    }
+
+    /// 48 kHz at full speed is 48.0 frames per frame: 10.14 puts 48 << 14 in
+    /// three bytes. A device sending the high-speed layout at full speed is
+    /// read that way instead, and nonsense is not read at all.
+    #[test]
+    fn feedback_is_decoded_in_whichever_layout_is_believable() {
+        let nominal = per_ms(RATE);
+        let fs = (48u32 << 14).to_le_bytes();
+        assert_eq!(feedback(&fs[..3], false, nominal), Some(48 << 16));
+        let wrong = (48u32 << 16).to_le_bytes();
+        assert_eq!(feedback(&wrong, false, nominal), Some(48 << 16));
+        // 6.0 frames per microframe is 48 per millisecond.
+        let hs = (6u32 << 16).to_le_bytes();
+        assert_eq!(feedback(&hs, true, nominal), Some(48 << 16));
+        assert_eq!(feedback(&[0xFF, 0xFF, 0xFF, 0xFF], false, nominal), None);
+        assert_eq!(feedback(&[1], false, nominal), None);
+    }
+
+    #[test]
+    fn packets_average_out_to_the_rate() {
+        let mut pacer = Pacer::new(RATE, 1000, 48);
+        assert!((0..1000).all(|_| pacer.frames() == 48));
+        assert_eq!(pacer.rate_millihz(), RATE * 1000);
+        // 44.1 kHz is nine packets of 44 and one of 45, in every ten.
+        let mut pacer = Pacer::new(44_100, 1000, 64);
+        let frames: u32 = (0..1000).map(|_| pacer.frames()).sum();
+        assert!((44_100..=44_101).contains(&frames), "{frames}");
+        // High speed at one packet per microframe is six at a time.
+        let mut pacer = Pacer::new(RATE, interval_us(true, 1), 64);
+        assert_eq!(pacer.frames(), 6);
+    }
+
+    /// A device that reports it runs slow is fed slower, and the room in the
+    /// packet caps what it is fed however fast it claims to be.
+    #[test]
+    fn feedback_steers_the_pacer_within_the_packet() {
+        let mut pacer = Pacer::new(RATE, 1000, 49);
+        pacer.steer(per_ms(47_952));
+        let frames: u32 = (0..1000).map(|_| pacer.frames()).sum();
+        assert!((47_950..=47_953).contains(&frames), "{frames}");
+        assert!(pacer.rate_millihz().abs_diff(47_952_000) < 1000, "{}", pacer.rate_millihz());
+        pacer.steer(per_ms(96_000));
+        assert_eq!(pacer.frames(), 49);
+    }
+
+    #[test]
+    fn a_period_completes_when_its_last_byte_has_played() {
+        assert_eq!(periods_done(0, 511, 512, 8), 0);
+        assert_eq!(periods_done(0, 512, 512, 8), 0b1);
+        assert_eq!(periods_done(500, 1100, 512, 8), 0b11);
+        // Across the ring's end, period 7 then period 0.
+        assert_eq!(periods_done(7 * 512 + 10, 8 * 512 + 600, 512, 8), 0b1000_0001);
+        assert_eq!(periods_done(0, 100 * 512, 512, 8), 0xFF);
+    }
+
+    #[test]
+    fn only_an_asynchronous_endpoint_has_feedback_worth_reading() {
+        // QEMU's endpoint is adaptive (0x0D); a typical DAC's is async (0x05).
+        assert!(!asynchronous(0x0D));
+        assert!(asynchronous(0x05));
+        assert!(endpoint_rate_control(&[7, 0x25, 1, 1, 0, 0, 0]));
+        assert!(!endpoint_rate_control(&[7, 0x25, 1, 0, 0, 0, 0]));
+        assert_eq!(interval_us(false, 1), 1000);
+        assert_eq!(interval_us(true, 4), 1000);
+    }
+}
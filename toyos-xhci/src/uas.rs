@@ -0,0 +1,728 @@
+//! USB Attached SCSI (interface protocol 0x62): the information units a command
+//! travels in, and who still owes what for every command in flight at once.
+//!
+//! Bulk-Only puts one command on the wire and reads its status before the next
+//! may go out, so a disk behind it serves a queue depth of one however deep its
+//! own queue is. UAS splits a command across four pipes — command, status,
+//! data-in, data-out — and names every piece of it with a **tag**, so several
+//! commands are outstanding at once and the device finishes them in whatever
+//! order it likes (UAS-2 §4).
+//!
+//! **Two ways of pairing a tag with its transfers, and the pipe decides.**
+//! With bulk streams (SuperSpeed), the status receive and the data transfer of
+//! a tag are queued on stream `tag` of their pipes *before* the command goes
+//! out, and the device moves whichever stream it is ready to. Without them
+//! (High Speed), each pipe has one ring: the device says which tag it is ready
+//! to move with a Read Ready or Write Ready IU on the status pipe, the host
+//! queues that tag's data then, and a single status receive is kept armed for
+//! whatever the device says next.
+//!
+//! **What is here is the bookkeeping and nothing else**, in
+//! [`crate::recovery`]'s shape: the driver reports what completed, this says
+//! what to put on a ring next as [`Act`]s, and a command that is over comes out
+//! of [`Uas::finished`] with its [`Verdict`]. Rings, TRBs, doorbells and the
+//! bytes of the data window are the driver's.
+
+/// Information unit identifiers (UAS-2 Table 11).
+pub const IU_COMMAND: u8 = 0x01;
+pub const IU_SENSE: u8 = 0x03;
+pub const IU_RESPONSE: u8 = 0x04;
+pub const IU_TASK_MANAGEMENT: u8 = 0x05;
+pub const IU_READ_READY: u8 = 0x06;
+pub const IU_WRITE_READY: u8 = 0x07;
+
+/// The Pipe Usage descriptor's Pipe IDs (UAS-2 Table 9), which are how an
+/// interface says which of its four bulk endpoints is which.
+pub const PIPE_COMMAND: u8 = 1;
+pub const PIPE_STATUS: u8 = 2;
+pub const PIPE_DATA_IN: u8 = 3;
+pub const PIPE_DATA_OUT: u8 = 4;
+
+/// A Command IU with no additional CDB bytes: a 16-byte header and a 16-byte
+/// CDB field, which holds every CDB this driver sends.
+pub const COMMAND_IU_BYTES: usize = 32;
+
+/// A Task Management IU, which has no payload.
+pub const TASK_IU_BYTES: usize = 16;
+
+/// The longest IU the status pipe carries: a Sense IU's 16-byte header and the
+/// 252 bytes SPC-4 caps sense data at. A receive sized below what the device
+/// sends is a babble, and a babble halts the pipe.
+pub const STATUS_IU_BYTES: usize = 16 + 252;
+
+/// LOGICAL UNIT RESET (UAS-2 Table 20): every command on the unit is aborted.
+pub const LOGICAL_UNIT_RESET: u8 = 0x08;
+
+/// The Response IU's codes for a task management function that was carried
+/// out (UAS-2 Table 17). Anything else is refused or not understood.
+pub const RESPONSE_COMPLETE: u8 = 0x00;
+pub const RESPONSE_SUCCEEDED: u8 = 0x08;
+
+/// SCSI status GOOD (SAM-5 Table 38). Every other status is a refusal.
+pub const STATUS_GOOD: u8 = 0x00;
+
+/// Commands one [`Uas`] can have in flight, plus none: tags are 1-based so
+/// that, with streams, stream 0 — which is reserved — is never one of them.
+pub const MAX_DEPTH: usize = 8;
+
+/// A Command IU for `cdb` on LUN 0 with a SIMPLE task attribute.
+///
+/// # Panics
+///
+/// On a CDB past 16 bytes, which would need the additional CDB length field
+/// nothing this driver sends uses.
+pub fn command_iu(tag: u16, cdb: &[u8]) -> [u8; COMMAND_IU_BYTES] {
+    assert!(cdb.len() <= 16, "a {}-byte CDB does not fit a command IU", cdb.len());
+    let mut iu = [0u8; COMMAND_IU_BYTES];
+    iu[0] = IU_COMMAND;
+    iu[2..4].copy_from_slice(&tag.to_be_bytes());
+    iu[16..16 + cdb.len()].copy_from_slice(cdb);
+    iu
+}
+
+/// A Task Management IU asking for `function` on LUN 0, naming `managed` as
+/// the tag it is about (which only the functions that abort one task read).
+pub fn task_iu(tag: u16, function: u8, managed: u16) -> [u8; TASK_IU_BYTES] {
+    let mut iu = [0u8; TASK_IU_BYTES];
+    iu[0] = IU_TASK_MANAGEMENT;
+    iu[2..4].copy_from_slice(&tag.to_be_bytes());
+    iu[4] = function;
+    iu[6..8].copy_from_slice(&managed.to_be_bytes());
+    iu
+}
+
+/// The three bytes of sense data a refusal is reported by.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sense {
+    pub key: u8,
+    pub asc: u8,
+    pub ascq: u8,
+}
+
+impl Sense {
+    /// Fixed (0x70/0x71) or descriptor (0x72/0x73) format sense data, or `None`
+    /// for anything too short to hold the three bytes or in neither format.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        match data.first()? & 0x7F {
+            0x70 | 0x71 if data.len() >= 14 => {
+                Some(Self { key: data[2] & 0x0F, asc: data[12], ascq: data[13] })
+            }
+            0x72 | 0x73 if data.len() >= 4 => {
+                Some(Self { key: data[1] & 0x0F, asc: data[2], ascq: data[3] })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An IU as it arrives on the status pipe.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Iu {
+    /// The command is over. `sense` is what a non-GOOD status came with, when
+    /// it came with any the parser reads.
+    Sense { tag: u16, status: u8, sense: Option<Sense> },
+    /// An answer to a task management function, or a command the target
+    /// refused at the transport and never ran.
+    Response { tag: u16, code: u8 },
+    ReadReady { tag: u16 },
+    WriteReady { tag: u16 },
+}
+
+impl Iu {
+    /// `None` for a receive too short for its kind or of a kind the status
+    /// pipe does not carry.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        let tag = u16::from_be_bytes([bytes[2], bytes[3]]);
+        match bytes[0] {
+            IU_SENSE if bytes.len() >= 16 => {
+                let length = u16::from_be_bytes([bytes[14], bytes[15]]) as usize;
+                let data = bytes.get(16..16 + length)?;
+                Some(Self::Sense { tag, status: bytes[6], sense: Sense::parse(data) })
+            }
+            IU_RESPONSE if bytes.len() >= 8 => Some(Self::Response { tag, code: bytes[7] }),
+            IU_READ_READY => Some(Self::ReadReady { tag }),
+            IU_WRITE_READY => Some(Self::WriteReady { tag }),
+            _ => None,
+        }
+    }
+}
+
+/// The data phase a command has, and its most.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Data {
+    None,
+    In(u32),
+    Out(u32),
+}
+
+/// What the driver puts on a ring next.
+///
+/// `stream` is 0 on a pipe without streams, and the tag itself on one with
+/// them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Act {
+    /// A receive of up to [`STATUS_IU_BYTES`] on the status pipe.
+    ReceiveStatus { stream: u16 },
+    /// `tag`'s data transfer, on the data pipe its direction names.
+    QueueData { tag: u16, stream: u16 },
+    /// `tag`'s IU on the command pipe. Always the last act of its
+    /// [`Uas::begin`], so a device that answers at once finds somewhere to.
+    SendCommand { tag: u16 },
+    /// `tag` is over and its data transfer is still on the ring, which the
+    /// device is never going to move now: stop the pipe, take it back and say
+    /// [`Uas::reclaimed`].
+    Reclaim { tag: u16 },
+}
+
+/// The acts one report produced, in the order they are to be done.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Acts {
+    acts: [Act; 3],
+    n: u8,
+    at: u8,
+}
+
+impl Acts {
+    const fn none() -> Self {
+        Self { acts: [Act::SendCommand { tag: 0 }; 3], n: 0, at: 0 }
+    }
+
+    fn push(&mut self, act: Act) {
+        self.acts[self.n as usize] = act;
+        self.n += 1;
+    }
+
+    pub fn as_slice(&self) -> &[Act] {
+        &self.acts[self.at as usize..self.n as usize]
+    }
+}
+
+impl Iterator for Acts {
+    type Item = Act;
+
+    fn next(&mut self) -> Option<Act> {
+        let act = *self.as_slice().first()?;
+        self.at += 1;
+        Some(act)
+    }
+}
+
+/// How a command ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    /// GOOD status, having moved `moved` bytes of its data phase.
+    Good { moved: u32 },
+    /// Any other SCSI status — CHECK CONDITION with the sense it came with,
+    /// BUSY, TASK SET FULL. The device is fine; the command was not run.
+    Refused { status: u8, sense: Option<Sense> },
+    /// A task management function's Response code.
+    Task { code: u8 },
+}
+
+/// The transport said something that cannot be true of what is in flight.
+/// Every one of these is answered the same way — recover the pipes and
+/// [`Uas::abandon`] — so the variants are for the log line.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Broke {
+    /// An IU too short for its kind, or of a kind the status pipe does not
+    /// carry.
+    Malformed,
+    /// An IU or a completion for a tag with nothing in flight.
+    Stray { tag: u16 },
+    /// An IU on a stream other than its tag's.
+    WrongStream { tag: u16, stream: u16 },
+    /// A Ready for the other direction, for a command without data, a second
+    /// one, or any on pipes that have streams and so never send one.
+    WrongReady { tag: u16 },
+    /// A data transfer that moved more than the command has.
+    Overrun { tag: u16 },
+    /// A command the target refused at the transport with this Response code,
+    /// or a task answered with a Sense IU.
+    Response { tag: u16, code: u8 },
+}
+
+/// Where one tag's data phase is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Leg {
+    /// No data phase, or one the device has not asked for (without streams).
+    Unqueued,
+    Queued,
+    Moved(u32),
+    Reclaiming,
+}
+
+/// What the status pipe said about one tag.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Answer {
+    Status { status: u8, sense: Option<Sense> },
+    Task { code: u8 },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Slot {
+    busy: bool,
+    task: bool,
+    data: Data,
+    sent: bool,
+    leg: Leg,
+    answer: Option<Answer>,
+}
+
+const FREE: Slot = Slot {
+    busy: false,
+    task: false,
+    data: Data::None,
+    sent: false,
+    leg: Leg::Unqueued,
+    answer: None,
+};
+
+/// Every tag of one UAS interface.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Uas {
+    streams: bool,
+    depth: u8,
+    /// Index `tag - 1`; the one past `depth` is the task management tag's.
+    slots: [Slot; MAX_DEPTH + 1],
+    /// Without streams: whether the one status receive is on the ring.
+    armed: bool,
+}
+
+impl Uas {
+    /// `depth` commands at once, clamped to `1..=MAX_DEPTH`. With `streams`,
+    /// the pipes must have streams `1..=depth + 1` — the last for
+    /// [`Uas::begin_task`].
+    pub const fn new(streams: bool, depth: u8) -> Self {
+        let depth = if depth == 0 {
+            1
+        } else if depth as usize > MAX_DEPTH {
+            MAX_DEPTH as u8
+        } else {
+            depth
+        };
+        Self { streams, depth, slots: [FREE; MAX_DEPTH + 1], armed: false }
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn streams(&self) -> bool {
+        self.streams
+    }
+
+    /// Nothing in flight: no command, no task.
+    pub fn idle(&self) -> bool {
+        self.slots.iter().all(|s| !s.busy)
+    }
+
+    /// What `tag` was begun with.
+    pub fn data(&self, tag: u16) -> Option<Data> {
+        self.slot(tag).ok().filter(|s| s.busy).map(|s| s.data)
+    }
+
+    fn task_tag(&self) -> u16 {
+        self.depth as u16 + 1
+    }
+
+    fn slot(&self, tag: u16) -> Result<&Slot, Broke> {
+        match tag {
+            1.. if tag <= self.task_tag() => Ok(&self.slots[tag as usize - 1]),
+            _ => Err(Broke::Stray { tag }),
+        }
+    }
+
+    fn busy_slot(&mut self, tag: u16) -> Result<&mut Slot, Broke> {
+        self.slot(tag)?;
+        let slot = &mut self.slots[tag as usize - 1];
+        if !slot.busy {
+            return Err(Broke::Stray { tag });
+        }
+        Ok(slot)
+    }
+
+    fn stream(&self, tag: u16) -> u16 {
+        if self.streams {
+            tag
+        } else {
+            0
+        }
+    }
+
+    /// A free tag for a command with `data`, and the acts that put it on the
+    /// wire; `None` when all `depth` are in flight.
+    pub fn begin(&mut self, data: Data) -> Option<(u16, Acts)> {
+        let index = self.slots[..self.depth as usize].iter().position(|s| !s.busy)?;
+        let tag = index as u16 + 1;
+        Some((tag, self.start(tag, data, false)))
+    }
+
+    /// The task management tag and the acts that send its IU. Only when
+    /// nothing else is in flight: this driver manages tasks in recovery, after
+    /// every command has been abandoned.
+    pub fn begin_task(&mut self) -> Option<(u16, Acts)> {
+        if !self.idle() {
+            return None;
+        }
+        let tag = self.task_tag();
+        Some((tag, self.start(tag, Data::None, true)))
+    }
+
+    fn start(&mut self, tag: u16, data: Data, task: bool) -> Acts {
+        let mut acts = Acts::none();
+        let queue_now = self.streams && data != Data::None;
+        self.slots[tag as usize - 1] = Slot {
+            busy: true,
+            task,
+            data,
+            leg: if queue_now { Leg::Queued } else { Leg::Unqueued },
+            ..FREE
+        };
+        if self.streams {
+            acts.push(Act::ReceiveStatus { stream: tag });
+        } else if !self.armed {
+            self.armed = true;
+            acts.push(Act::ReceiveStatus { stream: 0 });
+        }
+        if queue_now {
+            acts.push(Act::QueueData { tag, stream: tag });
+        }
+        acts.push(Act::SendCommand { tag });
+        acts
+    }
+
+    /// `tag`'s IU left on the command pipe.
+    pub fn sent(&mut self, tag: u16) -> Result<(), Broke> {
+        self.busy_slot(tag)?.sent = true;
+        Ok(())
+    }
+
+    /// A receive on the status pipe's `stream` completed with `bytes`.
+    pub fn status(&mut self, stream: u16, bytes: &[u8]) -> Result<Acts, Broke> {
+        let mut acts = Acts::none();
+        if !self.streams {
+            self.armed = false;
+        }
+        let iu = Iu::parse(bytes).ok_or(Broke::Malformed)?;
+        let (Iu::Sense { tag, .. }
+        | Iu::Response { tag, .. }
+        | Iu::ReadReady { tag }
+        | Iu::WriteReady { tag }) = iu;
+        if stream != self.stream(tag) {
+            return Err(Broke::WrongStream { tag, stream });
+        }
+        let streams = self.streams;
+        let slot = self.busy_slot(tag)?;
+        match iu {
+            Iu::ReadReady { .. } | Iu::WriteReady { .. } => {
+                let wanted = match slot.data {
+                    Data::In(_) => matches!(iu, Iu::ReadReady { .. }),
+                    Data::Out(_) => matches!(iu, Iu::WriteReady { .. }),
+                    Data::None => false,
+                };
+                if streams || !wanted || slot.leg != Leg::Unqueued || slot.answer.is_some() {
+                    return Err(Broke::WrongReady { tag });
+                }
+                slot.leg = Leg::Queued;
+                acts.push(Act::QueueData { tag, stream: 0 });
+            }
+            Iu::Sense { status, sense, .. } => {
+                if slot.task || slot.answer.is_some() {
+                    return Err(Broke::Response { tag, code: status });
+                }
+                slot.answer = Some(Answer::Status { status, sense });
+                // GOOD with the data still queued is a completion the event
+                // ring has not delivered yet, not one that is not coming.
+                if slot.leg == Leg::Queued && status != STATUS_GOOD {
+                    slot.leg = Leg::Reclaiming;
+                    acts.push(Act::Reclaim { tag });
+                }
+            }
+            Iu::Response { code, .. } => {
+                if !slot.task || slot.answer.is_some() {
+                    return Err(Broke::Response { tag, code });
+                }
+                slot.answer = Some(Answer::Task { code });
+            }
+        }
+        if !streams && self.slots.iter().any(|s| s.busy && s.answer.is_none()) {
+            self.armed = true;
+            acts.push(Act::ReceiveStatus { stream: 0 });
+        }
+        Ok(acts)
+    }
+
+    /// `tag`'s data transfer completed having moved `moved` bytes.
+    pub fn moved(&mut self, tag: u16, moved: u32) -> Result<(), Broke> {
+        let slot = self.busy_slot(tag)?;
+        let most = match slot.data {
+            Data::In(n) | Data::Out(n) => n,
+            Data::None => return Err(Broke::Stray { tag }),
+        };
+        // Reclaiming: the completion beat the stop, and it is still the truth.
+        if !matches!(slot.leg, Leg::Queued | Leg::Reclaiming) {
+            return Err(Broke::Stray { tag });
+        }
+        if moved > most {
+            return Err(Broke::Overrun { tag });
+        }
+        slot.leg = Leg::Moved(moved);
+        Ok(())
+    }
+
+    /// The data transfer [`Act::Reclaim`] named is off its ring. A completion
+    /// that arrived first already said what moved.
+    pub fn reclaimed(&mut self, tag: u16) -> Result<(), Broke> {
+        let slot = self.busy_slot(tag)?;
+        if slot.leg == Leg::Reclaiming {
+            slot.leg = Leg::Moved(0);
+        }
+        Ok(())
+    }
+
+    /// A command or task that is over, with how; its tag is free again.
+    /// `None` while everything in flight still owes something.
+    pub fn finished(&mut self) -> Option<(u16, Verdict)> {
+        let index = self.slots.iter().position(|s| {
+            s.busy && s.sent && s.answer.is_some() && !matches!(s.leg, Leg::Queued | Leg::Reclaiming)
+        })?;
+        let slot = core::mem::replace(&mut self.slots[index], FREE);
+        let moved = match slot.leg {
+            Leg::Moved(n) => n,
+            _ => 0,
+        };
+        let verdict = match slot.answer? {
+            Answer::Task { code } => Verdict::Task { code },
+            Answer::Status { status: STATUS_GOOD, .. } => Verdict::Good { moved },
+            Answer::Status { status, sense } => Verdict::Refused { status, sense },
+        };
+        Some((index as u16 + 1, verdict))
+    }
+
+    /// Everything in flight is gone with the rings it was on — the driver
+    /// recovered the pipes — and every tag is free.
+    pub fn abandon(&mut self) {
+        self.slots = [FREE; MAX_DEPTH + 1];
+        self.armed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const READ10: [u8; 10] = [0x28, 0, 0, 0, 0, 8, 0, 0, 1, 0];
+
+    fn sense_iu(tag: u16, status: u8, sense: &[u8]) -> [u8; 64] {
+        let mut iu = [0u8; 64];
+        iu[0] = IU_SENSE;
+        iu[2..4].copy_from_slice(&tag.to_be_bytes());
+        iu[6] = status;
+        iu[14..16].copy_from_slice(&(sense.len() as u16).to_be_bytes());
+        iu[16..16 + sense.len()].copy_from_slice(sense);
+        iu
+    }
+
+    fn good(tag: u16) -> [u8; 64] {
+        sense_iu(tag, STATUS_GOOD, &[])
+    }
+
+    fn ready(id: u8, tag: u16) -> [u8; 4] {
+        let [hi, lo] = tag.to_be_bytes();
+        [id, 0, hi, lo]
+    }
+
+    fn response(tag: u16, code: u8) -> [u8; 8] {
+        let [hi, lo] = tag.to_be_bytes();
+        [IU_RESPONSE, 0, hi, lo, 0, 0, 0, code]
+    }
+
+    /// Fixed-format NOT READY / MEDIUM NOT PRESENT.
+    const NOT_READY: [u8; 18] = [0x70, 0, 0x02, 0, 0, 0, 0, 10, 0, 0, 0, 0, 0x3A, 0x00, 0, 0, 0, 0];
+
+    #[test]
+    fn a_command_iu_carries_its_tag_big_endian_and_its_cdb_at_sixteen() {
+        let iu = command_iu(0x0102, &READ10);
+        assert_eq!(iu[0], IU_COMMAND);
+        assert_eq!(iu[2..4], [0x01, 0x02]);
+        assert_eq!(iu[4..16], [0; 12], "SIMPLE, no additional CDB, LUN 0");
+        assert_eq!(iu[16..26], READ10);
+        assert_eq!(iu[26..], [0; 6]);
+
+        let task = task_iu(9, LOGICAL_UNIT_RESET, 0);
+        assert_eq!(task[..8], [IU_TASK_MANAGEMENT, 0, 0, 9, LOGICAL_UNIT_RESET, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sense_is_read_in_either_format_and_a_truncated_iu_is_not_read_at_all() {
+        let want = Sense { key: 0x02, asc: 0x3A, ascq: 0x00 };
+        assert_eq!(
+            Iu::parse(&sense_iu(3, 0x02, &NOT_READY)),
+            Some(Iu::Sense { tag: 3, status: 0x02, sense: Some(want) })
+        );
+        let descriptor = [0x72, 0x05, 0x24, 0x01, 0, 0, 0, 0];
+        assert_eq!(
+            Sense::parse(&descriptor),
+            Some(Sense { key: 0x05, asc: 0x24, ascq: 0x01 })
+        );
+        assert_eq!(Iu::parse(&response(5, RESPONSE_SUCCEEDED)), Some(Iu::Response { tag: 5, code: 8 }));
+        assert_eq!(Iu::parse(&ready(IU_WRITE_READY, 2)), Some(Iu::WriteReady { tag: 2 }));
+
+        // A sense length past the receive, a header cut short, an IU the
+        // status pipe never carries.
+        assert_eq!(Iu::parse(&sense_iu(3, 0x02, &NOT_READY)[..30]), None);
+        assert_eq!(Iu::parse(&[IU_SENSE, 0, 0, 1, 0, 0, 0]), None);
+        assert_eq!(Iu::parse(&command_iu(1, &READ10)), None);
+    }
+
+    /// With streams the device may answer the moment the command lands, so
+    /// both of the tag's receives are already on their rings when it does.
+    #[test]
+    fn with_streams_status_and_data_are_queued_before_the_command() {
+        let mut uas = Uas::new(true, 4);
+        let (tag, acts) = uas.begin(Data::In(4096)).unwrap();
+        assert_eq!(
+            acts.as_slice(),
+            [
+                Act::ReceiveStatus { stream: tag },
+                Act::QueueData { tag, stream: tag },
+                Act::SendCommand { tag },
+            ]
+        );
+        uas.sent(tag).unwrap();
+        uas.moved(tag, 4096).unwrap();
+        assert_eq!(uas.finished(), None, "the data moved but the status has not arrived");
+        assert!(uas.status(tag, &good(tag)).unwrap().as_slice().is_empty());
+        assert_eq!(uas.finished(), Some((tag, Verdict::Good { moved: 4096 })));
+        assert!(uas.idle());
+    }
+
+    /// The device is done with a command it refused, so a data transfer still
+    /// queued for it is never going to move and would be handed to the next
+    /// command on that stream.
+    #[test]
+    fn a_refusal_that_arrives_before_the_data_reclaims_the_data() {
+        let mut uas = Uas::new(true, 4);
+        let (tag, _) = uas.begin(Data::Out(8192)).unwrap();
+        uas.sent(tag).unwrap();
+        let acts = uas.status(tag, &sense_iu(tag, 0x02, &NOT_READY)).unwrap();
+        assert_eq!(acts.as_slice(), [Act::Reclaim { tag }]);
+        assert_eq!(uas.finished(), None, "the transfer is still on the ring");
+        uas.reclaimed(tag).unwrap();
+        let sense = Some(Sense { key: 0x02, asc: 0x3A, ascq: 0x00 });
+        assert_eq!(uas.finished(), Some((tag, Verdict::Refused { status: 0x02, sense })));
+    }
+
+    /// GOOD before the data completion is the two events arriving in the
+    /// other order, and reclaiming there would throw the data away.
+    #[test]
+    fn good_status_before_the_data_completion_waits_for_it() {
+        let mut uas = Uas::new(true, 4);
+        let (tag, _) = uas.begin(Data::In(512)).unwrap();
+        uas.sent(tag).unwrap();
+        assert!(uas.status(tag, &good(tag)).unwrap().as_slice().is_empty());
+        assert_eq!(uas.finished(), None);
+        uas.moved(tag, 512).unwrap();
+        assert_eq!(uas.finished(), Some((tag, Verdict::Good { moved: 512 })));
+    }
+
+    /// Without streams the device says which tag it will move, and the one
+    /// status receive is re-armed for as long as somebody is owed a status.
+    #[test]
+    fn without_streams_data_waits_for_the_devices_ready() {
+        let mut uas = Uas::new(false, 4);
+        let (a, acts) = uas.begin(Data::In(4096)).unwrap();
+        assert_eq!(acts.as_slice(), [Act::ReceiveStatus { stream: 0 }, Act::SendCommand { tag: a }]);
+        let (b, acts) = uas.begin(Data::Out(4096)).unwrap();
+        assert_eq!(acts.as_slice(), [Act::SendCommand { tag: b }], "the receive is already armed");
+        uas.sent(a).unwrap();
+        uas.sent(b).unwrap();
+
+        let acts = uas.status(0, &ready(IU_WRITE_READY, b)).unwrap();
+        assert_eq!(
+            acts.as_slice(),
+            [Act::QueueData { tag: b, stream: 0 }, Act::ReceiveStatus { stream: 0 }]
+        );
+        uas.moved(b, 4096).unwrap();
+        uas.status(0, &good(b)).unwrap();
+        assert_eq!(uas.finished(), Some((b, Verdict::Good { moved: 4096 })));
+
+        uas.status(0, &ready(IU_READ_READY, a)).unwrap();
+        uas.moved(a, 1024).unwrap();
+        let acts = uas.status(0, &good(a)).unwrap();
+        assert!(acts.as_slice().is_empty(), "nobody is owed a status, so nothing is armed");
+        assert_eq!(uas.finished(), Some((a, Verdict::Good { moved: 1024 })));
+        assert_eq!(uas.finished(), None);
+    }
+
+    #[test]
+    fn a_ready_the_command_cannot_want_is_broken() {
+        let mut uas = Uas::new(false, 2);
+        let (read, _) = uas.begin(Data::In(512)).unwrap();
+        let (none, _) = uas.begin(Data::None).unwrap();
+        assert_eq!(uas.status(0, &ready(IU_WRITE_READY, read)), Err(Broke::WrongReady { tag: read }));
+        assert_eq!(uas.status(0, &ready(IU_READ_READY, none)), Err(Broke::WrongReady { tag: none }));
+
+        let mut uas = Uas::new(true, 2);
+        let (tag, _) = uas.begin(Data::In(512)).unwrap();
+        assert_eq!(uas.status(tag, &ready(IU_READ_READY, tag)), Err(Broke::WrongReady { tag }));
+    }
+
+    /// Every tag up to the depth, and then none until one finishes — in
+    /// whatever order the device finishes them.
+    #[test]
+    fn tags_are_handed_out_to_the_depth_and_freed_in_any_order() {
+        let mut uas = Uas::new(true, 4);
+        let tags: [u16; 4] = core::array::from_fn(|_| uas.begin(Data::In(512)).unwrap().0);
+        assert_eq!(tags, [1, 2, 3, 4]);
+        assert_eq!(uas.begin(Data::None), None);
+
+        for tag in tags {
+            uas.sent(tag).unwrap();
+        }
+        uas.moved(3, 512).unwrap();
+        uas.status(3, &good(3)).unwrap();
+        assert_eq!(uas.finished(), Some((3, Verdict::Good { moved: 512 })));
+        assert_eq!(uas.begin(Data::None).map(|(tag, _)| tag), Some(3));
+    }
+
+    #[test]
+    fn what_cannot_be_true_of_what_is_in_flight_is_broken() {
+        let mut uas = Uas::new(true, 4);
+        let (tag, _) = uas.begin(Data::In(512)).unwrap();
+        assert_eq!(uas.status(2, &good(2)), Err(Broke::Stray { tag: 2 }));
+        assert_eq!(uas.status(9, &good(9)), Err(Broke::Stray { tag: 9 }));
+        assert_eq!(uas.status(3, &good(tag)), Err(Broke::WrongStream { tag, stream: 3 }));
+        assert_eq!(uas.status(tag, &[0xFF; 16]), Err(Broke::Malformed));
+        assert_eq!(uas.moved(tag, 513), Err(Broke::Overrun { tag }));
+        assert_eq!(uas.status(tag, &response(tag, 0x02)), Err(Broke::Response { tag, code: 0x02 }));
+    }
+
+    /// The task tag is the one past the depth — stream `depth + 1` — and is
+    /// only handed out when every command has been abandoned.
+    #[test]
+    fn a_logical_unit_reset_runs_on_its_own_tag_once_nothing_is_in_flight() {
+        let mut uas = Uas::new(true, 4);
+        uas.begin(Data::In(512)).unwrap();
+        assert_eq!(uas.begin_task(), None);
+        uas.abandon();
+        assert!(uas.idle());
+
+        let (tag, acts) = uas.begin_task().unwrap();
+        assert_eq!(tag, 5);
+        assert_eq!(acts.as_slice(), [Act::ReceiveStatus { stream: 5 }, Act::SendCommand { tag: 5 }]);
+        uas.sent(tag).unwrap();
+        assert_eq!(uas.status(tag, &good(tag)), Err(Broke::Response { tag, code: STATUS_GOOD }));
+        uas.status(tag, &response(tag, RESPONSE_SUCCEEDED)).unwrap();
+        assert_eq!(uas.finished(), Some((tag, Verdict::Task { code: RESPONSE_SUCCEEDED })));
+    }
+
+    #[test]
+    fn the_depth_is_clamped_to_what_the_tags_hold() {
+        assert_eq!(Uas::new(true, 0).depth(), 1);
+        assert_eq!(Uas::new(true, 200).depth(), MAX_DEPTH as u8);
+    }
+}
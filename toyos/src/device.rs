@@ -65,6 +65,39 @@ impl AsHandle for Mouse {
     fn as_handle(&self) -> RawHandle { self.0.as_handle() }
 }
 
+pub struct Gamepad(pub(crate) Device);
+
+impl Gamepad {
+    /// Non-blocking read of pending pad states, whole
+    /// [`toyos_abi::input::GamepadEvent`]s; empty surfaces as
+    /// `Err(WouldBlock)`, for the reason [`Keyboard::read_nonblock`] gives.
+    pub fn read_nonblock(&self, buf: &mut [u8]) -> Result<usize, SyscallError> {
+        self.0.0.read_nonblock(buf)
+    }
+}
+
+impl AsHandle for Gamepad {
+    fn as_handle(&self) -> RawHandle { self.0.as_handle() }
+}
+
+/// The machine's power button, sleep button and lid. Claimed with a `SysCap`
+/// that carries `Rights::POWER` as well as `Rights::DEVICE`: a press is a
+/// request to turn the machine off, and hearing it belongs to whoever may.
+pub struct PowerDev(pub(crate) Device);
+
+impl PowerDev {
+    /// Non-blocking read of pending presses, whole
+    /// [`toyos_abi::power::PowerEvent`]s; empty surfaces as
+    /// `Err(WouldBlock)`. The holder waits on a poller, never in a read.
+    pub fn read_nonblock(&self, buf: &mut [u8]) -> Result<usize, SyscallError> {
+        self.0.0.read_nonblock(buf)
+    }
+}
+
+impl AsHandle for PowerDev {
+    fn as_handle(&self) -> RawHandle { self.0.as_handle() }
+}
+
 pub struct FramebufferDev(pub(crate) Device);
 
 impl FramebufferDev {
@@ -99,6 +132,11 @@ impl Nic {
     pub fn tx(&self, total_len: u64) -> Result<(), SyscallError> {
         syscall::nic_tx(self.0.as_handle(), total_len)
     }
+
+    /// Whether the link is up; `NotFound` once the NIC itself has gone.
+    pub fn link(&self) -> Result<bool, SyscallError> {
+        syscall::nic_link(self.0.as_handle())
+    }
 }
 
 impl AsHandle for Nic {
@@ -171,7 +209,7 @@ impl HdaDev {
     pub fn completions(&self) -> Result<toyos_abi::audio::AudioCompletionRecord, SyscallError> {
         let mut record = toyos_abi::audio::AudioCompletionRecord {
             mask: 0,
-            _pad: 0,
+            feedback_rate: 0,
             timestamp_nanos: 0,
         };
         let buf = unsafe {
@@ -210,3 +248,93 @@ impl HdaDev {
 impl AsHandle for HdaDev {
     fn as_handle(&self) -> RawHandle { self.0.as_handle() }
 }
+
+/// A USB Audio Class DAC, as a PCM ring the kernel plays.
+///
+/// The kernel sizes and sends every isochronous packet; what crosses this
+/// claim is the ring's description, one completion record per read as
+/// [`HdaDev::completions`] returns it, a start or a stop, and whether there is
+/// a DAC at all — the claim outlives the device, as [`Nic`]'s does.
+pub struct UsbAudioDev(pub(crate) Device);
+
+impl UsbAudioDev {
+    pub fn info(&self) -> Result<toyos_abi::usb_audio::UsbAudioInfo, SyscallError> {
+        read_info(&self.0)
+    }
+
+    /// The periods that have played since the last read, or `Err(WouldBlock)`,
+    /// with the device's measured rate in `feedback_rate` once it has said.
+    pub fn completions(&self) -> Result<toyos_abi::audio::AudioCompletionRecord, SyscallError> {
+        let mut record = toyos_abi::audio::AudioCompletionRecord {
+            mask: 0,
+            feedback_rate: 0,
+            timestamp_nanos: 0,
+        };
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(
+                &mut record as *mut _ as *mut u8,
+                toyos_abi::audio::AudioCompletionRecord::SIZE,
+            )
+        };
+        let n = syscall::read_nonblock(self.0.0.0, buf)?;
+        assert_eq!(
+            n,
+            toyos_abi::audio::AudioCompletionRecord::SIZE,
+            "partial USB audio completion record ({n} bytes)"
+        );
+        Ok(record)
+    }
+
+    /// Play from the period after the last one reported.
+    pub fn start(&self) -> Result<(), SyscallError> {
+        syscall::write(self.0.as_handle(), &[toyos_abi::usb_audio::START]).map(|_| ())
+    }
+
+    pub fn stop(&self) -> Result<(), SyscallError> {
+        syscall::write(self.0.as_handle(), &[toyos_abi::usb_audio::STOP]).map(|_| ())
+    }
+
+    /// The DAC plugged in, or `None`. The claim is held with none, and wakes
+    /// its holder when one binds or leaves; this is what the wake was for.
+    pub fn dac(&self) -> Result<Option<toyos_abi::usb_audio::UsbAudioDac>, SyscallError> {
+        syscall::usb_audio_dac(self.0.as_handle())
+    }
+}
+
+impl AsHandle for UsbAudioDev {
+    fn as_handle(&self) -> RawHandle { self.0.as_handle() }
+}
+
+/// A virtio-vsock device, as packets.
+///
+/// The kernel keeps the queues; what crosses this claim is one whole packet
+/// per call in each direction, in the wire format `toyos-vsock` reads. The
+/// kernel refuses to send a packet whose source is any context id but this
+/// guest's, so the claimant can speak for nobody else.
+pub struct VsockDev(pub(crate) Device);
+
+impl VsockDev {
+    pub fn info(&self) -> Result<toyos_abi::vsock::VsockInfo, SyscallError> {
+        read_info(&self.0)
+    }
+
+    /// The oldest packet received, into `buf`; nothing queued surfaces as
+    /// `Err(WouldBlock)`.
+    ///
+    /// `buf` must hold [`toyos_abi::vsock::MAX_PACKET`]: a packet does not fit
+    /// partially, and one that does not fit is `Err(InvalidArgument)` and stays
+    /// queued.
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize, SyscallError> {
+        syscall::read_nonblock(self.0.0.0, buf)
+    }
+
+    /// Put one packet on the wire. `Err(WouldBlock)` while the transmit ring is
+    /// full, which the device clears on its own time.
+    pub fn send(&self, packet: &[u8]) -> Result<(), SyscallError> {
+        syscall::write(self.0.as_handle(), packet).map(|_| ())
+    }
+}
+
+impl AsHandle for VsockDev {
+    fn as_handle(&self) -> RawHandle { self.0.as_handle() }
+}
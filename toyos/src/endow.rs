@@ -70,10 +70,14 @@ from_handle! {
     Device => Device,
     crate::Keyboard => |h| crate::Keyboard(Device(h)),
     crate::Mouse => |h| crate::Mouse(Device(h)),
+    crate::Gamepad => |h| crate::Gamepad(Device(h)),
+    crate::PowerDev => |h| crate::PowerDev(Device(h)),
     crate::FramebufferDev => |h| crate::FramebufferDev(Device(h)),
     crate::Nic => |h| crate::Nic(Device(h)),
     crate::HdaDev => |h| crate::HdaDev(Device(h)),
     crate::VirtioSoundDev => |h| crate::VirtioSoundDev(Device(h)),
+    crate::VsockDev => |h| crate::VsockDev(Device(h)),
+    crate::UsbAudioDev => |h| crate::UsbAudioDev(Device(h)),
 }
 
 /// This process's endowment table, parsed once.
@@ -5,7 +5,7 @@
 //! so it is an argument — and a program with no framebuffer claim cannot write
 //! the call at all.
 
-use toyos_abi::FramebufferInfo;
+use toyos_abi::{DisplayModes, FramebufferInfo, Output};
 use toyos_abi::syscall::{self, SyscallError};
 
 use crate::device::FramebufferDev;
@@ -45,4 +45,35 @@ impl FramebufferDev {
         }
         Ok(info)
     }
+
+    /// Place every output, one entry per output in the order
+    /// [`FramebufferInfo::outputs`] lists them. Like a mode change, the answer
+    /// names fresh buffers sized to the arrangement's bounding box.
+    pub fn arrange(&self, outputs: &[Output]) -> Result<FramebufferInfo, SyscallError> {
+        let mut info = unsafe { core::mem::zeroed::<FramebufferInfo>() };
+        // SAFETY: `info` is this frame's own storage and outlives the call.
+        unsafe {
+            syscall::gpu_arrange(
+                self.as_handle(),
+                outputs,
+                &mut info as *mut FramebufferInfo as *mut u8,
+            )?;
+        }
+        Ok(info)
+    }
+
+    /// The modes output `output`'s monitor offers, with the one it is showing
+    /// flagged [`MODE_CURRENT`](toyos_abi::MODE_CURRENT).
+    pub fn modes(&self, output: u32) -> Result<DisplayModes, SyscallError> {
+        let mut modes = DisplayModes::EMPTY;
+        // SAFETY: `modes` is this frame's own storage and outlives the call.
+        unsafe {
+            syscall::gpu_modes(
+                self.as_handle(),
+                output,
+                &mut modes as *mut DisplayModes as *mut u8,
+            )?;
+        }
+        Ok(modes)
+    }
 }
@@ -28,9 +28,10 @@ pub mod surface;
 pub mod shm;
 pub mod syscap;
 pub mod system;
+pub mod vsock;
 
 pub use ipc::Connection;
-pub use device::{Keyboard, Mouse, FramebufferDev, Nic, VirtioSoundDev, HdaDev};
+pub use device::{Keyboard, Mouse, Gamepad, PowerDev, FramebufferDev, Nic, VirtioSoundDev, HdaDev, VsockDev, UsbAudioDev};
 
 pub use toyos_abi::RawHandle;
 
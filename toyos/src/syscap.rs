@@ -54,6 +54,13 @@ impl SysCap {
         syscall::shutdown(self.0.raw())
     }
 
+    /// Suspend the machine to RAM and return once it has woken. Needs
+    /// [`Rights::POWER`], as [`Self::shutdown`] does; what each refusal means
+    /// is [`syscall::suspend`]'s.
+    pub fn suspend(&self) -> Result<(), SyscallError> {
+        syscall::suspend(self.0.raw())
+    }
+
     /// The machine's header, then one entry per live thread for as much of
     /// `buf` as is left. Answers the bytes written, and `0` if it was refused.
     ///
@@ -38,6 +38,16 @@ pub fn sysinfo(buf: &mut [u8; SYSINFO_HEADER_SIZE]) -> usize {
     syscall::sysinfo(toyos_abi::handle::HANDLE_INVALID, buf)
 }
 
+/// Tell the device under the filesystem `path` is on that every free block
+/// is free, answering how many 4 KiB blocks that was. What `/bin/fstrim` is.
+///
+/// Deleting a file already does this for its blocks at the next sync; this is
+/// for the ones that were missed — freed before the device could take the hint,
+/// or by a kernel that did not give it.
+pub fn fstrim(path: &str) -> Result<u64, toyos_abi::syscall::SyscallError> {
+    syscall::fstrim(path.as_bytes())
+}
+
 // Powering the machine off used to be a free function here, over a syscall that
 // took no argument. It is [`crate::syscap::SysCap::shutdown`] now: it is an
 // authority over the whole machine, and every one of those is a bit on a
@@ -0,0 +1,326 @@
+//! Host↔guest stream sockets over virtio-vsock.
+//!
+//! Owns the vsockd IPC protocol and the client functions for it. A vsock
+//! stream is addressed by `(cid, port)` rather than by IP: the host is always
+//! [`CID_HOST`], and this guest is whatever the device says, which
+//! [`local_cid`] reports. There is no network underneath, so there is nothing
+//! to configure and nothing to resolve — a host process on the other end of
+//! `AF_VSOCK` is reachable as soon as vsockd is running.
+//!
+//! The data path is the one `toyos::net` uses: the client makes two pipes,
+//! keeps the ends facing itself, and hands vsockd the other two with the
+//! request that opens the stream. Closing the write end is a shutdown of this
+//! side's sending half; closing both ends resets the stream.
+
+use crate::ipc::{IpcError, IpcHeader, IpcPayload};
+use crate::ipc_payload;
+use crate::{Connection, Pipe, RawHandle};
+
+/// The host's context id.
+pub const CID_HOST: u64 = 2;
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MsgType {
+    Listen = 1,
+    Accept = 2,
+    Connect = 3,
+    LocalCid = 4,
+}
+
+impl MsgType {
+    pub fn from_u32(v: u32) -> Option<Self> {
+        match v {
+            1 => Some(Self::Listen),
+            2 => Some(Self::Accept),
+            3 => Some(Self::Connect),
+            4 => Some(Self::LocalCid),
+            _ => None,
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RespType {
+    Result = 128,
+    Error = 129,
+}
+
+// Error codes (on the wire). The numbers are `toyos::net`'s where the meaning
+// is the same, so a program that speaks both reads them alike.
+
+pub const ERR_CONNECTION_REFUSED: u32 = 1;
+pub const ERR_CONNECTION_RESET: u32 = 2;
+pub const ERR_TIMED_OUT: u32 = 3;
+pub const ERR_ADDR_IN_USE: u32 = 4;
+/// An accept with no connection waiting for it. A client that reads its
+/// listener's notify pipe first never sees this.
+pub const ERR_NOT_CONNECTED: u32 = 5;
+pub const ERR_INVALID_INPUT: u32 = 6;
+/// vsockd is holding as many streams as it will; retryable once one closes.
+pub const ERR_RESOURCE_EXHAUSTED: u32 = 7;
+pub const ERR_OTHER: u32 = 255;
+
+/// A stream is two pipe ends sent with the request that opens it, in this
+/// order — the same pair, and the same order, as `toyos::net`'s
+/// [`DATA_HANDLES`](crate::net::DATA_HANDLES).
+pub const DATA_HANDLES: usize = 2;
+/// The end vsockd writes into and the client reads from.
+pub const DATA_TO_CLIENT: usize = 0;
+/// The end the client writes into and vsockd reads from.
+pub const DATA_FROM_CLIENT: usize = 1;
+
+/// A listen sends one end: vsockd writes one byte into it per connection
+/// waiting to be accepted.
+pub const NOTIFY_HANDLES: usize = 1;
+
+ipc_payload! {
+    pub struct ListenRequest {
+        /// Zero asks vsockd to pick one.
+        pub port: u32,
+    }
+
+    pub struct ListenResponse {
+        pub listener_id: u32,
+        pub port: u32,
+    }
+
+    pub struct AcceptRequest {
+        pub listener_id: u32,
+    }
+
+    pub struct AcceptResponse {
+        pub peer_cid: u64,
+        pub peer_port: u32,
+        pub local_port: u32,
+    }
+
+    pub struct ConnectRequest {
+        pub cid: u64,
+        pub port: u32,
+        /// Zero waits as long as the peer takes to answer.
+        pub timeout_ms: u32,
+    }
+
+    pub struct ConnectResponse {
+        pub local_port: u32,
+    }
+
+    pub struct CidResponse {
+        pub cid: u64,
+    }
+
+    pub struct ErrorResponse {
+        pub code: u32,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsockError {
+    /// No vsockd to reach: the manifest gave this program none, or this
+    /// machine has no vsock device and it exited.
+    VsockdNotFound,
+    ConnectionRefused,
+    ConnectionReset,
+    TimedOut,
+    AddrInUse,
+    NotConnected,
+    InvalidInput,
+    ResourceExhausted,
+    Protocol(u32),
+    Io,
+}
+
+impl VsockError {
+    pub fn from_error_code(code: u32) -> Self {
+        match code {
+            ERR_CONNECTION_REFUSED => Self::ConnectionRefused,
+            ERR_CONNECTION_RESET => Self::ConnectionReset,
+            ERR_TIMED_OUT => Self::TimedOut,
+            ERR_ADDR_IN_USE => Self::AddrInUse,
+            ERR_NOT_CONNECTED => Self::NotConnected,
+            ERR_INVALID_INPUT => Self::InvalidInput,
+            ERR_RESOURCE_EXHAUSTED => Self::ResourceExhausted,
+            ERR_OTHER => Self::Io,
+            code => Self::Protocol(code),
+        }
+    }
+}
+
+/// One open stream: read [`rx`](Self::rx) for what the peer sent, write
+/// [`tx`](Self::tx) to send. A read of zero is the peer's shutdown or reset.
+pub struct VsockStream {
+    pub rx: Pipe,
+    pub tx: Pipe,
+    pub local_port: u32,
+    pub peer_cid: u64,
+    pub peer_port: u32,
+}
+
+/// A port vsockd accepts connections on for this process. Dropping it stops
+/// the listening, and resets whatever was waiting to be accepted.
+pub struct VsockListener {
+    pub notify: Pipe,
+    pub listener_id: u32,
+    pub port: u32,
+}
+
+/// One connection to vsockd, through this process's own namespace, for one
+/// request.
+struct VsockdConn(Connection);
+
+impl VsockdConn {
+    fn connect() -> Result<Self, VsockError> {
+        crate::endow::service("vsockd").map(Self).map_err(|e| match e {
+            crate::endow::EndowError::NotEndowed
+            | crate::endow::EndowError::ServerGone => VsockError::VsockdNotFound,
+            crate::endow::EndowError::Refused(
+                toyos_abi::syscall::SyscallError::ResourceExhausted,
+            ) => VsockError::ResourceExhausted,
+            crate::endow::EndowError::Refused(_) => VsockError::Io,
+        })
+    }
+
+    fn request<Req: IpcPayload>(self, msg_type: MsgType, payload: &Req) -> Result<Self, VsockError> {
+        self.0.send(msg_type as u32, payload).map_err(hangup)?;
+        Ok(self)
+    }
+
+    /// **The handles are moved whether or not this answers `Ok`**, as with
+    /// `toyos::net`'s `request_with_handles`: a refused send drops the batch.
+    fn request_with_handles<Req: IpcPayload>(
+        self,
+        handles: &[RawHandle],
+        msg_type: MsgType,
+        payload: &Req,
+    ) -> Result<Self, VsockError> {
+        self.0.send_with_handles(handles, msg_type as u32, payload).map_err(hangup)?;
+        Ok(self)
+    }
+
+    fn response<Resp: IpcPayload>(self) -> Result<Resp, VsockError> {
+        let header: IpcHeader = self.0.recv_header().map_err(hangup)?;
+        if header.msg_type == RespType::Error as u32 {
+            let err: ErrorResponse = self.0.recv_payload(&header).map_err(hangup)?;
+            return Err(VsockError::from_error_code(err.code));
+        }
+        if header.msg_type != RespType::Result as u32 {
+            return Err(VsockError::Protocol(header.msg_type));
+        }
+        self.0.recv_payload(&header).map_err(hangup)
+    }
+}
+
+/// A vsockd that hung up mid-exchange is a vsockd that is not there — the same
+/// three words, for the same reasons, as `toyos::net`'s `hangup` spells out.
+fn hangup(e: IpcError) -> VsockError {
+    match e {
+        IpcError::Disconnected
+        | IpcError::Syscall(
+            toyos_abi::syscall::SyscallError::Gone
+            | toyos_abi::syscall::SyscallError::NotFound,
+        ) => VsockError::VsockdNotFound,
+        _ => VsockError::Io,
+    }
+}
+
+/// Both pipes of a stream, split into the ends the caller keeps and the
+/// handles vsockd is sent, in [`DATA_TO_CLIENT`] / [`DATA_FROM_CLIENT`] order.
+fn data_path() -> Result<(Pipe, Pipe, [RawHandle; DATA_HANDLES]), VsockError> {
+    let (rx, vsockd_tx) = crate::pipe_pair().map_err(|_| VsockError::Io)?;
+    let (vsockd_rx, tx) = crate::pipe_pair().map_err(|_| VsockError::Io)?;
+    Ok((rx, tx, [vsockd_tx.into_raw(), vsockd_rx.into_raw()]))
+}
+
+/// This guest's context id: what a host process passes to `connect` to reach
+/// a [`listen`]ing port here.
+pub fn local_cid() -> Result<u64, VsockError> {
+    let resp: CidResponse = VsockdConn::connect()?.request(MsgType::LocalCid, &0u32)?.response()?;
+    Ok(resp.cid)
+}
+
+/// Accept connections on `port`, or on one vsockd picks if `port` is zero.
+pub fn listen(port: u32) -> Result<VsockListener, VsockError> {
+    let vsockd = VsockdConn::connect()?;
+    let (notify, vsockd_notify) = crate::pipe_pair().map_err(|_| VsockError::Io)?;
+    let resp: ListenResponse = vsockd
+        .request_with_handles(&[vsockd_notify.into_raw()], MsgType::Listen, &ListenRequest { port })?
+        .response()?;
+    Ok(VsockListener { notify, listener_id: resp.listener_id, port: resp.port })
+}
+
+/// Wait for the next connection on `listener` and take it.
+///
+/// Blocks on the notify pipe rather than asking vsockd until one turns up:
+/// vsockd writes a byte there per connection it is holding, so once the byte
+/// is read the accept cannot find the queue empty.
+pub fn accept(listener: &VsockListener) -> Result<VsockStream, VsockError> {
+    let mut byte = [0u8; 1];
+    match listener.notify.read(&mut byte) {
+        Ok(1) => {}
+        // vsockd closed its end: it has exited.
+        Ok(_) => return Err(VsockError::VsockdNotFound),
+        Err(_) => return Err(VsockError::Io),
+    }
+    let vsockd = VsockdConn::connect()?;
+    let (rx, tx, handles) = data_path()?;
+    let resp: AcceptResponse = vsockd
+        .request_with_handles(&handles, MsgType::Accept, &AcceptRequest {
+            listener_id: listener.listener_id,
+        })?
+        .response()?;
+    Ok(VsockStream {
+        rx,
+        tx,
+        local_port: resp.local_port,
+        peer_cid: resp.peer_cid,
+        peer_port: resp.peer_port,
+    })
+}
+
+/// Open a stream to `(cid, port)`. A `timeout_ms` of zero waits for as long as
+/// the peer takes to answer; a host with nothing listening answers at once,
+/// with a reset, which is [`VsockError::ConnectionRefused`].
+pub fn connect(cid: u64, port: u32, timeout_ms: u32) -> Result<VsockStream, VsockError> {
+    let vsockd = VsockdConn::connect()?;
+    let (rx, tx, handles) = data_path()?;
+    let resp: ConnectResponse = vsockd
+        .request_with_handles(&handles, MsgType::Connect, &ConnectRequest { cid, port, timeout_ms })?
+        .response()?;
+    Ok(VsockStream { rx, tx, local_port: resp.local_port, peer_cid: cid, peer_port: port })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toyos_abi::syscall::SyscallError;
+
+    /// The hang-ups a connection to an exited vsockd can surface as, and only
+    /// those, are the daemon's absence; see `toyos::net`'s tests for why each.
+    #[test]
+    fn only_a_hangup_is_a_missing_vsockd() {
+        for e in [
+            IpcError::Disconnected,
+            IpcError::Syscall(SyscallError::Gone),
+            IpcError::Syscall(SyscallError::NotFound),
+        ] {
+            assert_eq!(hangup(e), VsockError::VsockdNotFound);
+        }
+        for e in [
+            IpcError::Syscall(SyscallError::PermissionDenied),
+            IpcError::Syscall(SyscallError::WouldBlock),
+            IpcError::Malformed,
+            IpcError::TooLarge,
+        ] {
+            assert_eq!(hangup(e), VsockError::Io);
+        }
+    }
+
+    #[test]
+    fn an_unknown_code_is_still_an_error_and_says_which() {
+        assert_eq!(VsockError::from_error_code(ERR_CONNECTION_REFUSED), VsockError::ConnectionRefused);
+        assert_eq!(VsockError::from_error_code(ERR_OTHER), VsockError::Io);
+        assert_eq!(VsockError::from_error_code(4242), VsockError::Protocol(4242));
+    }
+}
@@ -39,12 +39,12 @@ pub const DRAIN_BUDGET: Duration = FRAME_INTERVAL;
 pub const FLAG_HARDWARE_CURSOR: u32 = 1 << 0;
 
 /// Handles the compositor watches that are not windows: keyboard, mouse,
-/// listener.
-pub const FIXED_POLL_HANDLES: u32 = 3;
+/// gamepad, listener.
+pub const FIXED_POLL_HANDLES: u32 = 4;
 
 /// Hard ceiling on live windows, from the poller rather than from memory.
 ///
-/// Every window's handle is registered in the same batch as the three fixed
+/// Every window's handle is registered in the same batch as the four fixed
 /// ones
 /// and the pending connections, and [`Poller::MAX_HANDLES`] is the widest set
 /// one poller can carry. Unlike the memory budget this does not move when the
@@ -59,9 +59,15 @@ pub const MAX_WINDOW_SLOTS: u32 = Poller::MAX_HANDLES - FIXED_POLL_HANDLES - MAX
 /// the size the software cursor damages.
 pub const CURSOR_PX: u32 = 20;
 
+/// Where the monitors were last put, as `toyos_desktop::format_arrangement`
+/// writes it. Read once at startup and written on every `MSG_PLACE_OUTPUT`
+/// that moved something; a single-output display neither reads nor writes it,
+/// so a file left by a docked session is still there when the dock is.
+pub const DISPLAYS_CONFIG: &str = "/home/root/.config/displays";
+
 /// What the launcher offers: the label it shows and the program it starts.
 pub const LAUNCHER_APPS: &[(&str, &str)] =
-    &[("Terminal", "/bin/terminal"), ("Files", "/bin/files")];
+    &[("Terminal", "/bin/terminal"), ("Files", "/bin/files"), ("Display", "/bin/display")];
 
 fn main() {
     let mut session = session::Session::start();
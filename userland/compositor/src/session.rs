@@ -15,13 +15,13 @@ use toyos::ipc::RxStep;
 use toyos::poller::{Poller, READABLE};
 use toyos::port::Acceptor;
 use toyos::shm::SharedMemory;
-use toyos::{ipc, system, AsHandle, FramebufferDev, Keyboard, Mouse};
+use toyos::{ipc, system, AsHandle, FramebufferDev, Gamepad, Keyboard, Mouse};
 use toyos_abi::syscall::DeviceType;
-use toyos_abi::RawHandle;
+use toyos_abi::{Output, RawHandle};
 use toyos_desktop::{
-    cursor_from_abs, cursor_style, fold_mouse, hit_test, key_action, set_mode, tab_action, Chrome,
-    CursorStyle, Damage, Desk, Grab, Held, Hit, KeyAction, Point, Rect, Released, Stack, TabAction,
-    Verdict, Window, WindowMode,
+    cursor_from_abs, cursor_style, fold_mouse, format_arrangement, hit_test, key_action,
+    parse_arrangement, set_mode, tab_action, Chrome, CursorStyle, Damage, Desk, Grab, Held, Hit,
+    KeyAction, Outputs, Point, Rect, Released, Stack, TabAction, Verdict, Window, WindowMode,
 };
 use window::Screen;
 
@@ -33,8 +33,8 @@ use crate::client::{
 use crate::render::{self, Assets, BackBuffer, SystemStats, TitleBarIcons};
 use crate::stats::FrameStats;
 use crate::{
-    CURSOR_PX, DOUBLE_CLICK_TIME, DRAIN_BUDGET, FIXED_POLL_HANDLES, FLAG_HARDWARE_CURSOR,
-    FRAME_INTERVAL, LAUNCHER_APPS, MAX_WINDOW_SLOTS, STATS_INTERVAL,
+    CURSOR_PX, DISPLAYS_CONFIG, DOUBLE_CLICK_TIME, DRAIN_BUDGET, FIXED_POLL_HANDLES,
+    FLAG_HARDWARE_CURSOR, FRAME_INTERVAL, LAUNCHER_APPS, MAX_WINDOW_SLOTS, STATS_INTERVAL,
 };
 
 struct Cursors {
@@ -58,20 +58,32 @@ struct Wallpaper {
     raw: Vec<u8>,
     w: usize,
     h: usize,
-    /// Scaled to the current screen and in its pixel format.
+    /// Screen-sized and in its pixel format, with the whole image scaled into
+    /// each output's own rectangle: every monitor shows the picture rather
+    /// than its share of one stretched across all of them.
     scaled: Vec<u8>,
 }
 
 impl Wallpaper {
-    fn rescale(&mut self, screen: &Screen) {
-        self.scaled = render::scale_wallpaper(
-            &self.raw[8..],
-            self.w,
-            self.h,
-            screen.width(),
-            screen.height(),
-            screen.pixel_format_raw() != 0,
-        );
+    fn rescale(&mut self, screen: &Screen, outputs: &Outputs) {
+        let width = screen.width();
+        let mut scaled = vec![0u8; width * screen.height() * 4];
+        for desk in outputs.iter() {
+            let s = desk.screen;
+            let part = render::scale_wallpaper(
+                &self.raw[8..],
+                self.w,
+                self.h,
+                s.w() as usize,
+                s.h() as usize,
+                screen.pixel_format_raw() != 0,
+            );
+            for (row, line) in part.chunks_exact(s.w() as usize * 4).enumerate() {
+                let at = ((s.y0 as usize + row) * width + s.x0 as usize) * 4;
+                scaled[at..at + line.len()].copy_from_slice(line);
+            }
+        }
+        self.scaled = scaled;
     }
 }
 
@@ -79,6 +91,7 @@ pub struct Session {
     acceptor: Acceptor,
     kb: Keyboard,
     mouse: Mouse,
+    gamepad: Gamepad,
     poller: Poller,
 
     /// The claim, and the thing every screen call is made *through*: closing it
@@ -101,7 +114,7 @@ pub struct Session {
     icons: TitleBarIcons,
     wallpaper: Wallpaper,
 
-    desk: Desk,
+    outputs: Outputs,
     stack: Stack<Client>,
     pending: Vec<PendingConn>,
     damage: Damage,
@@ -112,6 +125,9 @@ pub struct Session {
     last_click_at: Instant,
     clipboard: String,
     launcher_open: bool,
+    /// The output whose bar the open launcher hangs from. Meaningless while
+    /// `launcher_open` is false; see [`Session::launcher_open_on`].
+    launcher_on: usize,
     total_mem: u64,
     max_windows: usize,
 
@@ -143,10 +159,24 @@ impl Session {
             .expect("the manifest gives this program the keyboard");
         let mouse: Mouse = endow::device(DeviceType::Mouse)
             .expect("the manifest gives this program the mouse");
+        let gamepad: Gamepad = endow::device(DeviceType::Gamepad)
+            .expect("the manifest gives this program the gamepads");
         let fb_dev: FramebufferDev = endow::device(DeviceType::Framebuffer)
             .expect("the manifest gives this program the framebuffer");
 
-        let fb_info = fb_dev.info().expect("failed to read framebuffer info");
+        let mut fb_info = fb_dev.info().expect("failed to read framebuffer info");
+        // The mode before the arrangement: a mode set lays the row out side
+        // by side again, and the arrangement is placed around the sizes the
+        // monitors end up with.
+        for restore in [restore_mode, restore_arrangement] {
+            if let Some(info) = restore(&fb_dev, &fb_info) {
+                // The superseded description's buffers are the old layout's,
+                // and nothing has mapped them yet.
+                toyos_abi::syscall::close(fb_info.scanout[0]);
+                toyos_abi::syscall::close(fb_info.cursor);
+                fb_info = info;
+            }
+        }
         let fb_size = fb_info.stride as usize * fb_info.height as usize * 4;
         // The scanout and cursor buffers are handles the claim's own read
         // installed, so a refusal here is the kernel contradicting itself and
@@ -181,6 +211,7 @@ impl Session {
             .expect("failed to read font");
         let font = font::Font::from_prebuilt(&font_data);
 
+        let outputs = outputs_of(&fb_info, &font);
         let raw = std::fs::read("/share/wallpaper.rgb").expect("failed to read wallpaper");
         let mut wallpaper = Wallpaper {
             w: u32::from_le_bytes(raw[0..4].try_into().unwrap()) as usize,
@@ -189,13 +220,14 @@ impl Session {
             scaled: Vec::new(),
         };
         eprintln!(
-            "compositor: wallpaper {}x{}, scaling to {}x{}",
+            "compositor: wallpaper {}x{}, scaling to {}x{} across {} output(s)",
             wallpaper.w,
             wallpaper.h,
             screen.width(),
-            screen.height()
+            screen.height(),
+            outputs.iter().len()
         );
-        wallpaper.rescale(&screen);
+        wallpaper.rescale(&screen, &outputs);
 
         let icons = TitleBarIcons {
             minimize: read_sprite("/share/icons/minus-bold.svg", 14, [255, 255, 255]),
@@ -203,33 +235,34 @@ impl Session {
             close: read_sprite("/share/icons/x-bold.svg", 14, [255, 255, 255]),
         };
 
-        let desk = desk_of(&screen, &font);
         let total_mem = total_memory();
         let max_windows =
-            toyos_desktop::max_windows(total_mem, desk.screen, MAX_WINDOW_SLOTS as usize);
+            toyos_desktop::max_windows(total_mem, outputs.largest(), MAX_WINDOW_SLOTS as usize);
         eprintln!(
             "compositor: at most {max_windows} windows ({} MiB each of {} MiB total)",
-            toyos_desktop::window_bytes(desk.screen) / (1024 * 1024),
+            toyos_desktop::window_bytes(outputs.largest()) / (1024 * 1024),
             total_mem / (1024 * 1024),
         );
 
         // Sized for the slot ceiling rather than for `max_windows`: the batch
-        // between two `wait` calls is the three fixed registrations, one per
+        // between two `wait` calls is the four fixed registrations, one per
         // live window
         // and one per pending connection, and `MSG_SET_RESOLUTION` can raise
         // `max_windows` mid-run.
         let poller = Poller::new(FIXED_POLL_HANDLES + MAX_WINDOW_SLOTS + MAX_PENDING_CONNS);
         poller.watch(&kb, READABLE, kb.as_handle().0 as u64);
         poller.watch(&mouse, READABLE, mouse.as_handle().0 as u64);
+        poller.watch(&gamepad, READABLE, gamepad.as_handle().0 as u64);
         poller.watch(&acceptor, READABLE, acceptor.as_handle().0 as u64);
 
-        let cursor = Point { x: desk.screen.w() / 2, y: desk.screen.h() / 2 };
+        let home = outputs.primary().screen;
+        let cursor = Point { x: home.x0 + home.w() / 2, y: home.y0 + home.h() / 2 };
         if hw_cursor {
             fb_dev.move_cursor(cursor.x as u32, cursor.y as u32)
                 .expect("compositor holds the framebuffer claim");
         }
         let mut damage = Damage::default();
-        damage.add(desk.screen);
+        damage.add(outputs.bounds());
 
         eprintln!("compositor: ready");
 
@@ -240,6 +273,7 @@ impl Session {
             acceptor,
             kb,
             mouse,
+            gamepad,
             poller,
             fb_dev,
             fb_info,
@@ -254,7 +288,7 @@ impl Session {
             font,
             icons,
             wallpaper,
-            desk,
+            outputs,
             stack: Stack::default(),
             pending: Vec::new(),
             damage,
@@ -265,6 +299,7 @@ impl Session {
             last_click_at: now,
             clipboard: String::new(),
             launcher_open: false,
+            launcher_on: 0,
             total_mem,
             max_windows,
             dead: Vec::new(),
@@ -313,6 +348,7 @@ impl Session {
 
         let kb_ready = self.is_ready(self.kb.as_handle());
         let mouse_ready = self.is_ready(self.mouse.as_handle());
+        let pad_ready = self.is_ready(self.gamepad.as_handle());
         let accept_ready = self.is_ready(self.acceptor.as_handle());
         let client_ready = self.stack.iter().any(|w| self.is_ready(w.client.conn.as_handle()))
             || self.pending.iter().any(|p| self.is_ready(p.conn.as_handle()));
@@ -331,7 +367,7 @@ impl Session {
         }
         self.pending.retain(|p| now.duration_since(p.since) < HANDSHAKE_TIMEOUT);
 
-        if !kb_ready && !mouse_ready && !accept_ready && !client_ready {
+        if !kb_ready && !mouse_ready && !pad_ready && !accept_ready && !client_ready {
             return false;
         }
 
@@ -342,13 +378,16 @@ impl Session {
         if mouse_ready {
             self.pointer();
         }
+        if pad_ready {
+            self.pads();
+        }
         if accept_ready {
             self.accept();
         }
         let frames = self.take_frames();
         self.dispatch(frames);
         self.reap();
-        self.rearm(kb_ready, mouse_ready, accept_ready);
+        self.rearm(kb_ready, mouse_ready, pad_ready, accept_ready);
         true
     }
 
@@ -406,19 +445,19 @@ impl Session {
                 // moves.
                 KeyAction::SetMode(mode) => {
                     let Some(idx) = focused else { continue };
-                    self.damage.add(self.stack[idx].frame(&self.desk.chrome));
-                    self.retarget(idx, mode);
+                    self.damage.add(self.stack[idx].frame(self.outputs.chrome()));
+                    self.retarget(idx, mode, self.home(idx));
                     self.damage_all();
                 }
                 KeyAction::Minimize => {
                     let Some(idx) = focused else { continue };
-                    self.damage.add(self.stack[idx].frame(&self.desk.chrome));
+                    self.damage.add(self.stack[idx].frame(self.outputs.chrome()));
                     self.stack[idx].minimized = true;
                     self.damage_all();
                 }
                 KeyAction::CloseFocused => {
                     let Some(idx) = focused else { continue };
-                    self.damage.add(self.stack[idx].frame(&self.desk.chrome));
+                    self.damage.add(self.stack[idx].frame(self.outputs.chrome()));
                     let win = self.stack.remove(idx);
                     note_closed("GUI+Q", win.client.conn.as_handle(), self.stack.len());
                     let _ = win.client.conn.try_signal(window::MSG_WINDOW_CLOSE);
@@ -428,6 +467,31 @@ impl Session {
         }
     }
 
+    /// Every pad state queued, to the focused window and to nobody else.
+    ///
+    /// Read even with no window focused: the queue is the kernel's and drops
+    /// its oldest past its bound, so a pad nobody reads is a pad whose states
+    /// are thrown away one by one rather than all at once — and the window
+    /// focused next starts from the pad's next report either way. Nothing
+    /// here is the compositor's own: no pad moves focus or opens anything.
+    fn pads(&mut self) {
+        let mut events = [window::GamepadEvent::EMPTY; 16];
+        // SAFETY: `GamepadEvent` is an `ipc_payload!`, which is `repr(C)`,
+        // padding-free and valid for every bit pattern, so viewing the array
+        // as bytes for the read to fill is sound — as it is for `keys`.
+        let buf = unsafe {
+            std::slice::from_raw_parts_mut(
+                events.as_mut_ptr() as *mut u8,
+                std::mem::size_of_val(&events),
+            )
+        };
+        let n = self.gamepad.read_nonblock(buf).unwrap_or(0);
+        let Some(i) = self.stack.focused() else { return };
+        for event in &events[..n / std::mem::size_of::<window::GamepadEvent>()] {
+            deliver(&mut self.dead, &self.stack[i], window::MSG_GAMEPAD_INPUT, event);
+        }
+    }
+
     fn pointer(&mut self) {
         let mut buf = [0u8; 512];
         let n = self.mouse.read_nonblock(&mut buf).unwrap_or(0);
@@ -437,7 +501,10 @@ impl Session {
         }
 
         let was = self.cursor;
-        self.cursor = cursor_from_abs(sample.abs_x, sample.abs_y, self.desk.screen);
+        // The tablet's range is the whole framebuffer, which is the bounding
+        // box of the outputs and can include a corner none of them shows.
+        self.cursor =
+            self.outputs.confine(cursor_from_abs(sample.abs_x, sample.abs_y, self.outputs.bounds()));
         if self.hw_cursor {
             self.fb_dev.move_cursor(self.cursor.x as u32, self.cursor.y as u32)
                 .expect("compositor holds the framebuffer claim");
@@ -448,8 +515,14 @@ impl Session {
         }
         let delta = Point { x: self.cursor.x - was.x, y: self.cursor.y - was.y };
 
-        let wanted =
-            cursor_style(&self.desk, &self.stack, &self.grab, self.cursor, self.launcher_open);
+        let d = self.outputs.at(self.cursor);
+        let wanted = cursor_style(
+            &self.outputs[d],
+            &self.stack,
+            &self.grab,
+            self.cursor,
+            self.launcher_open_on(d),
+        );
         if wanted != self.current_cursor {
             self.current_cursor = wanted;
             render::upload_cursor(
@@ -471,7 +544,7 @@ impl Session {
         }
         if sample.scroll != 0 {
             if let Hit::Content(idx) =
-                hit_test(&self.desk, &self.stack, self.cursor, self.launcher_open)
+                hit_test(&self.outputs[d], &self.stack, self.cursor, self.launcher_open_on(d))
             {
                 let ev = mouse_event(
                     &self.stack[idx],
@@ -489,11 +562,12 @@ impl Session {
 
     fn press(&mut self, buttons: u8) {
         let at = self.cursor;
-        match hit_test(&self.desk, &self.stack, at, self.launcher_open) {
+        let d = self.outputs.at(at);
+        match hit_test(&self.outputs[d], &self.stack, at, self.launcher_open_on(d)) {
             Hit::CloseButton(idx) => {
                 let win = self.stack.remove(idx);
                 note_closed("its close button", win.client.conn.as_handle(), self.stack.len());
-                self.damage.add(win.frame(&self.desk.chrome));
+                self.damage.add(win.frame(self.outputs.chrome()));
                 let _ = win.client.conn.try_signal(window::MSG_WINDOW_CLOSE);
                 self.damage_all();
             }
@@ -503,20 +577,20 @@ impl Session {
             }
             Hit::MaximizeButton(idx) => {
                 let i = self.stack.raise(idx);
-                self.damage.add(self.stack[i].frame(&self.desk.chrome));
-                self.retarget(i, toggled(self.stack[i].mode));
+                self.damage.add(self.stack[i].frame(self.outputs.chrome()));
+                self.retarget(i, toggled(self.stack[i].mode), self.home(i));
                 self.damage_all();
             }
             Hit::TitleBar(idx) => {
                 let i = self.stack.raise(idx);
-                self.damage.add(self.stack[i].frame(&self.desk.chrome));
+                self.damage.add(self.stack[i].frame(self.outputs.chrome()));
 
                 let now = Instant::now();
                 let handle = self.stack[i].client.conn.as_handle();
                 let double = Some(handle) == self.last_click_handle
                     && now.duration_since(self.last_click_at) < DOUBLE_CLICK_TIME;
                 if double {
-                    self.retarget(i, toggled(self.stack[i].mode));
+                    self.retarget(i, toggled(self.stack[i].mode), self.home(i));
                     self.last_click_handle = None;
                     self.last_click_at = now - DOUBLE_CLICK_TIME;
                 } else {
@@ -558,9 +632,16 @@ impl Session {
                 self.damage_all();
             }
             Hit::TaskbarNew => {
-                self.launcher_open = !self.launcher_open;
+                // One launcher, on the bar that was clicked: a click on
+                // another output's button moves it there rather than closing
+                // it.
+                if self.launcher_open {
+                    self.damage.add(self.launcher_rect());
+                }
+                self.launcher_open = !self.launcher_open_on(d);
+                self.launcher_on = d;
                 self.damage.add(self.launcher_rect());
-                self.damage.add(self.desk.taskbar(self.stack.len()).strip());
+                self.damage.add(self.outputs[d].taskbar(self.stack.len()).strip());
             }
             Hit::LauncherItem(idx) => {
                 Command::new(LAUNCHER_APPS[idx].1).spawn().ok();
@@ -582,11 +663,14 @@ impl Session {
                 mouse_event(&self.stack[i], self.cursor, buttons, window::MOUSE_RELEASE, 1, 0);
             deliver(&mut self.dead, &self.stack[i], window::MSG_MOUSE_INPUT, &ev);
         }
-        match self.grab.release(&self.desk, &self.stack, self.cursor) {
+        // A snap fills the output the pointer let go on, which is the one
+        // whose edge it touched — not the one showing most of the window.
+        let d = self.outputs.at(self.cursor);
+        match self.grab.release(&self.outputs[d], &self.stack, self.cursor) {
             Released::Nothing => {}
             Released::Snapped { window: idx, mode } => {
-                self.damage.add(self.stack[idx].frame(&self.desk.chrome));
-                self.retarget(idx, mode);
+                self.damage.add(self.stack[idx].frame(self.outputs.chrome()));
+                self.retarget(idx, mode, d);
                 self.damage_all();
             }
             Released::Resized { window: idx } => {
@@ -598,7 +682,8 @@ impl Session {
     }
 
     fn hold(&mut self, buttons: u8, delta: Point) {
-        match self.grab.hold(&self.desk, &mut self.stack, self.cursor, delta) {
+        let d = self.outputs.at(self.cursor);
+        match self.grab.hold(&self.outputs[d], &mut self.stack, self.cursor, delta) {
             Held::Idle => {}
             Held::Free => {
                 if let Some(i) = self.stack.focused() {
@@ -610,7 +695,7 @@ impl Session {
             Held::Restored { window: idx } => {
                 let pf = self.pixel_format();
                 settle(&mut self.stack[idx], pf, &mut self.dead);
-                self.damage.add(self.desk.screen);
+                self.damage.add(self.outputs.bounds());
             }
             Held::Moved { from, to, .. } => {
                 self.damage.add(from);
@@ -724,7 +809,7 @@ impl Session {
                     if let Some(i) = self.stack.find(|w| w.client.conn.as_handle() == handle) {
                         let gone = self.stack.remove(i);
                         note_closed("the client itself", gone.client.conn.as_handle(), self.stack.len());
-                        self.damage.add(gone.frame(&self.desk.chrome));
+                        self.damage.add(gone.frame(self.outputs.chrome()));
                         self.damage_all();
                     }
                 }
@@ -794,10 +879,34 @@ impl Session {
                     self.set_resolution(req.width, req.height);
                     self.answer_resolution(handle);
                 }
+                window::MSG_PLACE_OUTPUT => {
+                    let Ok(req) = ipc::decode_payload::<window::OutputPlacement>(frame.payload())
+                    else {
+                        mark_dead(&mut self.dead, handle, DropReason::OutOfProtocol);
+                        continue;
+                    };
+                    self.place_output(req.index as usize, Point { x: req.x, y: req.y });
+                    self.answer_resolution(handle);
+                }
                 // The one message a client can ask for faster than it can read
                 // the answer: eight bytes in, sixteen out. Blocking here is a
                 // client filling its own pipe and taking the desktop with it.
                 window::MSG_GET_RESOLUTION => self.answer_resolution(handle),
+                window::MSG_GET_MODES => {
+                    // No payload is the primary output, which is what a
+                    // single-monitor picker means without having to say so.
+                    let index = match frame.payload() {
+                        [] => 0,
+                        bytes => match ipc::decode_payload::<u32>(bytes) {
+                            Ok(index) => index,
+                            Err(_) => {
+                                mark_dead(&mut self.dead, handle, DropReason::OutOfProtocol);
+                                continue;
+                            }
+                        },
+                    };
+                    self.answer_modes(handle, index);
+                }
                 _ => {}
             }
         }
@@ -824,7 +933,7 @@ impl Session {
         // is a panic and none is a silent shrink of what was asked for.
         let refusal = match toyos_desktop::create_verdict(
             (req.width, req.height),
-            self.desk.screen,
+            self.outputs.largest(),
             self.stack.len(),
             self.max_windows,
         ) {
@@ -847,10 +956,13 @@ impl Session {
             return;
         }
 
-        let content = self.desk.chrome.initial_content(
+        // On the output the user is looking at, which is the one the pointer
+        // is on.
+        let desk = &self.outputs[self.outputs.at(self.cursor)];
+        let content = desk.chrome.initial_content(
             Some((req.width as i32, req.height as i32)),
             self.stack.len(),
-            self.desk.screen,
+            desk.screen,
         );
         let shm = match SharedMemory::create((content.area() * 4) as usize) {
             Ok(shm) => shm,
@@ -929,10 +1041,63 @@ impl Session {
         }
     }
 
+    /// Send `handle` the modes of output `index`, an empty list when there is
+    /// no such output. Non-blocking for `answer_resolution`'s reason.
+    fn answer_modes(&mut self, handle: RawHandle, index: u32) {
+        let modes = self.fb_dev.modes(index).unwrap_or(toyos_abi::DisplayModes::EMPTY);
+        if ipc::try_send_bytes(handle, window::MSG_MODES, modes.as_bytes()).is_err() {
+            mark_dead(&mut self.dead, handle, DropReason::NotReading);
+        }
+    }
+
     fn set_resolution(&mut self, width: u32, height: u32) {
         let Ok(info) = self.fb_dev.set_resolution(width, height) else {
             return;
         };
+        self.adopt(info);
+    }
+
+    /// Move output `index` to `at` and remember it there.
+    ///
+    /// The move is checked here before the kernel is asked, so an arrangement
+    /// that would overlap two monitors costs a log line and not a mode set.
+    fn place_output(&mut self, index: usize, at: Point) {
+        let current = output_rects(&self.fb_info);
+        let Some(placed) = toyos_desktop::arrange(&current, &[(index, at)]) else {
+            eprintln!(
+                "compositor: output {index} at ({}, {}) would overlap another, left as it was",
+                at.x, at.y
+            );
+            return;
+        };
+        if placed == current {
+            return;
+        }
+        let info = match self.fb_dev.arrange(&wire(&placed)) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("compositor: the display refused the arrangement ({e:?})");
+                return;
+            }
+        };
+        self.adopt(info);
+        let text = format_arrangement(&output_rects(&self.fb_info));
+        let saved = std::path::Path::new(DISPLAYS_CONFIG)
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(DISPLAYS_CONFIG, text));
+        if let Err(e) = saved {
+            eprintln!("compositor: cannot save the arrangement to {DISPLAYS_CONFIG}: {e}");
+        }
+    }
+
+    /// Take over a new description of the display: the scanout, the back
+    /// buffer, the outputs, and every window's place on them.
+    ///
+    /// A window goes with the output it was on. When a mode set or an
+    /// arrangement moves that output, the window moves by the same amount and
+    /// is then fitted to it; when the output is gone, it lands on the first.
+    fn adopt(&mut self, info: toyos_abi::FramebufferInfo) {
         self.fb_info = info;
         let size = info.stride as usize * info.height as usize * 4;
         self._fb_shm = SharedMemory::adopt(info.scanout[0], size)
@@ -950,26 +1115,48 @@ impl Session {
         // zero.
         self.reported_traffic = self.screen.traffic();
         self.reported_composed = self.back.surface.traffic();
-        self.desk = desk_of(&self.screen, &self.font);
+
+        // Which output each window was on is a question about the old
+        // outputs, so it is asked before they are replaced.
+        let homes: Vec<usize> = (0..self.stack.len()).map(|i| self.home(i)).collect();
+        let old = std::mem::replace(&mut self.outputs, outputs_of(&info, &self.font));
         // What a window costs moved, so what we can afford moved with it.
         // Windows already open are left alone if the new figure is below their
         // count — the cap gates creation, it does not evict.
-        self.max_windows =
-            toyos_desktop::max_windows(self.total_mem, self.desk.screen, MAX_WINDOW_SLOTS as usize);
-        self.wallpaper.rescale(&self.screen);
+        self.max_windows = toyos_desktop::max_windows(
+            self.total_mem,
+            self.outputs.largest(),
+            MAX_WINDOW_SLOTS as usize,
+        );
+        self.wallpaper.rescale(&self.screen, &self.outputs);
 
-        for i in 0..self.stack.len() {
+        for (i, was) in homes.into_iter().enumerate() {
+            let (home, shift) = match self.outputs.get(was) {
+                Some(now) => {
+                    let before = old[was].screen;
+                    (was, Point { x: now.screen.x0 - before.x0, y: now.screen.y0 - before.y0 })
+                }
+                None => (0, Point { x: 0, y: 0 }),
+            };
             match self.stack[i].mode {
                 WindowMode::Normal => {
-                    self.stack[i].content =
-                        self.desk.chrome.reflow(self.stack[i].content, self.desk.screen)
+                    let moved = self.stack[i].content.translate(shift.x, shift.y);
+                    let desk = &self.outputs[home];
+                    self.stack[i].content = desk.chrome.reflow(moved, desk.screen);
                 }
-                mode => self.retarget(i, mode),
+                mode => self.retarget(i, mode, home),
             }
         }
-        self.cursor.x = self.cursor.x.min(self.desk.screen.x1 - 1);
-        self.cursor.y = self.cursor.y.min(self.desk.screen.y1 - 1);
-        self.damage.add(self.desk.screen);
+        if self.launcher_on >= self.outputs.iter().len() {
+            self.launcher_open = false;
+            self.launcher_on = 0;
+        }
+        self.cursor = self.outputs.confine(self.cursor);
+        if self.hw_cursor {
+            self.fb_dev.move_cursor(self.cursor.x as u32, self.cursor.y as u32)
+                .expect("compositor holds the framebuffer claim");
+        }
+        self.damage.add(self.outputs.bounds());
     }
 
     fn reap(&mut self) {
@@ -984,7 +1171,7 @@ impl Session {
             .stack
             .iter()
             .filter(|w| self.dead.iter().any(|(handle, _)| *handle == w.client.conn.as_handle()))
-            .map(|w| w.frame(&self.desk.chrome))
+            .map(|w| w.frame(self.outputs.chrome()))
             .collect();
         let dead = std::mem::take(&mut self.dead);
         self.stack.retain(|w| !dead.iter().any(|(handle, _)| *handle == w.client.conn.as_handle()));
@@ -999,13 +1186,16 @@ impl Session {
     }
 
     /// Re-arm the one-shot poll registrations for every handle that fired.
-    fn rearm(&mut self, kb: bool, mouse: bool, acceptor: bool) {
+    fn rearm(&mut self, kb: bool, mouse: bool, pad: bool, acceptor: bool) {
         if kb {
             self.poller.watch(&self.kb, READABLE, self.kb.as_handle().0 as u64);
         }
         if mouse {
             self.poller.watch(&self.mouse, READABLE, self.mouse.as_handle().0 as u64);
         }
+        if pad {
+            self.poller.watch(&self.gamepad, READABLE, self.gamepad.as_handle().0 as u64);
+        }
         if acceptor {
             let h = self.acceptor.as_handle();
             self.poller.watch(&self.acceptor, READABLE, h.0 as u64);
@@ -1051,14 +1241,17 @@ impl Session {
         // Only the readout, which is the only thing about the bar a second
         // changes. A whole-bar repaint here is what the owner saw as the
         // taskbar flickering once a second.
-        self.damage.add(self.desk.taskbar(self.stack.len()).status());
+        let n = self.stack.len();
+        for desk in self.outputs.iter() {
+            self.damage.add(desk.taskbar(n).status());
+        }
     }
 
     fn present(&mut self) {
         if self.damage.is_empty() {
             return;
         }
-        let regions = self.damage.take(self.desk.screen);
+        let regions = self.damage.take(self.outputs.bounds());
         if regions.is_empty() {
             return;
         }
@@ -1071,16 +1264,25 @@ impl Session {
             wallpaper: &self.wallpaper.scaled,
             apps: LAUNCHER_APPS,
         };
+        // Each output paints its own part of a region: its bar, its launcher,
+        // its wallpaper. What no output covers is never shown and is left as
+        // it was.
         for region in &regions {
-            render::paint(
-                &self.back.surface,
-                &self.desk,
-                &self.stack,
-                &assets,
-                self.launcher_open,
-                &self.cached_stats,
-                *region,
-            );
+            for (d, desk) in self.outputs.iter().enumerate() {
+                let clip = region.intersect(desk.screen);
+                if clip.is_empty() {
+                    continue;
+                }
+                render::paint(
+                    &self.back.surface,
+                    desk,
+                    &self.stack,
+                    &assets,
+                    self.launcher_open_on(d),
+                    &self.cached_stats,
+                    clip,
+                );
+            }
         }
 
         // Into the back buffer, so a region containing the cursor carries it
@@ -1151,7 +1353,7 @@ impl Session {
     fn frame_callbacks(&mut self, regions: &[Rect]) {
         let mut dead: Vec<Dead> = Vec::new();
         for i in 0..self.stack.len() {
-            let rect = self.stack[i].frame(&self.desk.chrome);
+            let rect = self.stack[i].frame(self.outputs.chrome());
             if self.stack[i].presented
                 && !self.stack[i].minimized
                 && regions.iter().any(|r| r.overlaps(rect))
@@ -1167,7 +1369,7 @@ impl Session {
         for win in
             self.stack.iter().filter(|w| dead.iter().any(|(h, _)| *h == w.client.conn.as_handle()))
         {
-            self.damage.add(win.frame(&self.desk.chrome));
+            self.damage.add(win.frame(self.outputs.chrome()));
         }
         self.stack.retain(|w| !dead.iter().any(|(handle, _)| *handle == w.client.conn.as_handle()));
         self.damage_all();
@@ -1184,19 +1386,32 @@ impl Session {
     /// day be wrong.
     fn damage_all(&mut self) {
         for i in 0..self.stack.len() {
-            let rect = self.stack[i].frame(&self.desk.chrome);
+            let rect = self.stack[i].frame(self.outputs.chrome());
             self.damage.add(rect);
         }
-        self.damage.add(self.desk.taskbar(self.stack.len()).strip());
+        let n = self.stack.len();
+        for desk in self.outputs.iter() {
+            self.damage.add(desk.taskbar(n).strip());
+        }
+    }
+
+    fn launcher_open_on(&self, desk: usize) -> bool {
+        self.launcher_open && self.launcher_on == desk
     }
 
     fn launcher_rect(&self) -> Rect {
-        self.desk.taskbar(self.stack.len()).launcher()
+        self.outputs[self.launcher_on].taskbar(self.stack.len()).launcher()
+    }
+
+    /// The output the window at `idx` belongs to, for a mode change that has
+    /// no better answer — a maximize fills the monitor showing most of it.
+    fn home(&self, idx: usize) -> usize {
+        self.outputs.holding(self.stack[idx].frame(self.outputs.chrome()))
     }
 
-    fn retarget(&mut self, idx: usize, mode: WindowMode) {
+    fn retarget(&mut self, idx: usize, mode: WindowMode, desk: usize) {
         let pf = self.pixel_format();
-        set_mode(&self.desk, &mut self.stack, idx, mode);
+        set_mode(&self.outputs[desk], &mut self.stack, idx, mode);
         settle(&mut self.stack[idx], pf, &mut self.dead);
     }
 
@@ -1252,12 +1467,93 @@ impl Session {
     }
 }
 
-fn desk_of(screen: &Screen, font: &font::Font) -> Desk {
-    Desk {
-        chrome: Chrome::DEFAULT,
-        screen: Rect::new(0, 0, screen.width() as i32, screen.height() as i32),
-        font_w: font.width() as i32,
-        apps: LAUNCHER_APPS.len(),
+fn outputs_of(info: &toyos_abi::FramebufferInfo, font: &font::Font) -> Outputs {
+    Outputs::new(
+        output_rects(info)
+            .into_iter()
+            .map(|screen| Desk {
+                chrome: Chrome::DEFAULT,
+                screen,
+                font_w: font.width() as i32,
+                apps: LAUNCHER_APPS.len(),
+            })
+            .collect(),
+    )
+}
+
+/// Where each output is in the framebuffer. A driver that describes none —
+/// one panel, nothing to arrange — is one output the size of the framebuffer.
+fn output_rects(info: &toyos_abi::FramebufferInfo) -> Vec<Rect> {
+    if info.outputs().is_empty() {
+        return vec![Rect::new(0, 0, info.width as i32, info.height as i32)];
+    }
+    info.outputs()
+        .iter()
+        .map(|o| Rect::new(o.x as i32, o.y as i32, o.width as i32, o.height as i32))
+        .collect()
+}
+
+fn wire(outputs: &[Rect]) -> Vec<Output> {
+    outputs
+        .iter()
+        .map(|r| Output { x: r.x0 as u32, y: r.y0 as u32, width: r.w() as u32, height: r.h() as u32 })
+        .collect()
+}
+
+/// Put the primary output back in the mode [`window::MODE_CONFIG`] says the
+/// user kept.
+///
+/// `None` is "leave the mode the kernel chose": no file, the mode already
+/// showing, one this monitor does not offer — the file outlives the monitor it
+/// was written for — or a kernel that said no.
+fn restore_mode(
+    fb_dev: &FramebufferDev,
+    info: &toyos_abi::FramebufferInfo,
+) -> Option<toyos_abi::FramebufferInfo> {
+    let text = std::fs::read_to_string(window::MODE_CONFIG).ok()?;
+    let (w, h) = text.trim().split_once('x')?;
+    let (width, height) = (w.parse::<u32>().ok()?, h.parse::<u32>().ok()?);
+    let primary = info.outputs().first()?;
+    if (width, height) == (primary.width, primary.height) {
+        return None;
+    }
+    let offered = fb_dev.modes(0).ok()?;
+    if !offered.modes().iter().any(|m| m.width == width && m.height == height) {
+        eprintln!("compositor: {width}x{height} in {} is not offered, left unset", window::MODE_CONFIG);
+        return None;
+    }
+    match fb_dev.set_resolution(width, height) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            eprintln!("compositor: cannot restore {width}x{height} from {} ({e:?})", window::MODE_CONFIG);
+            None
+        }
+    }
+}
+
+/// Put the outputs back where [`DISPLAYS_CONFIG`] says the user left them.
+///
+/// `None` is "leave the display as the kernel described it": one output, no
+/// file, a file that no longer fits these monitors, or a kernel that said no.
+fn restore_arrangement(
+    fb_dev: &FramebufferDev,
+    info: &toyos_abi::FramebufferInfo,
+) -> Option<toyos_abi::FramebufferInfo> {
+    let current = output_rects(info);
+    if current.len() < 2 {
+        return None;
+    }
+    let text = std::fs::read_to_string(DISPLAYS_CONFIG).ok()?;
+    let placed = toyos_desktop::arrange(&current, &parse_arrangement(&text))?;
+    if placed == current {
+        return None;
+    }
+    match fb_dev.arrange(&wire(&placed)) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            eprintln!("compositor: cannot restore the arrangement in {DISPLAYS_CONFIG} ({e:?})");
+            None
+        }
     }
 }
 
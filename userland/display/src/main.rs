@@ -0,0 +1,431 @@
+//! Display settings: the primary monitor's modes, as its EDID lists them, and
+//! a way to choose one.
+//!
+//! A mode the monitor lists can still be one it shows badly — a KVM switch
+//! passing on the wrong monitor's EDID, a panel claiming a rate it cannot hold
+//! — and someone looking at a black screen cannot find a Revert button. So a
+//! mode set here is a trial: it undoes itself after [`CONFIRM_SECS`] unless it
+//! is kept, and only a kept mode is written to [`window::MODE_CONFIG`] for
+//! the compositor to restore at the next boot.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use font::Font;
+use toyos::endow;
+use toyos::Connection;
+use toyos_abi::{DisplayMode, DisplayModes, MODE_CURRENT, MODE_PREFERRED};
+use window::{Color, Event, Framebuffer, Window};
+
+// --- Colors (matching the file picker) ---
+
+const BG: Color = Color { r: 0x1e, g: 0x1e, b: 0x2e };
+const TEXT_FG: Color = Color { r: 0xcd, g: 0xd6, b: 0xf4 };
+const DIM_FG: Color = Color { r: 0x6c, g: 0x70, b: 0x86 };
+const CURRENT_FG: Color = Color { r: 0x89, g: 0xb4, b: 0xfa };
+const SEL_BG: Color = Color { r: 0x45, g: 0x47, b: 0x5a };
+const BAR_BG: Color = Color { r: 0x31, g: 0x32, b: 0x44 };
+const BUTTON_BG: Color = Color { r: 0x45, g: 0x47, b: 0x5a };
+const BUTTON_FG: Color = Color { r: 0xcd, g: 0xd6, b: 0xf4 };
+const ACCENT_BG: Color = Color { r: 0x89, g: 0xb4, b: 0xfa };
+const ACCENT_FG: Color = Color { r: 0x1e, g: 0x1e, b: 0x2e };
+
+// --- HID keycodes ---
+
+const KEY_UP: u8 = 0x52;
+const KEY_DOWN: u8 = 0x51;
+const KEY_ENTER: u8 = 0x28;
+const KEY_ESCAPE: u8 = 0x29;
+
+// --- Layout ---
+
+const WIDTH: u32 = 420;
+const HEIGHT: u32 = 380;
+const PADDING: usize = 8;
+const BAR_HEIGHT: usize = 24;
+const ROW_HEIGHT: usize = 20;
+const FOOTER_HEIGHT: usize = 64;
+const BUTTON_WIDTH: usize = 80;
+const BUTTON_HEIGHT: usize = 24;
+
+/// How long a new mode lasts before it is undone unasked. Long enough to find
+/// the button on a screen that came back, short enough that a screen which
+/// did not is back before anyone reaches for the power switch.
+const CONFIRM_SECS: u64 = 15;
+
+enum Stage {
+    Choosing,
+    /// A mode has been set, and `previous` is put back at `deadline` unless
+    /// the new one is kept first.
+    Confirming {
+        previous: DisplayMode,
+        deadline: Instant,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Button {
+    Apply,
+    Keep,
+    Revert,
+}
+
+struct Settings {
+    window: Window,
+    fb: Framebuffer,
+    font: Font,
+    /// A connection to the compositor of its own, apart from the window's:
+    /// the window's carries events, and an answer read off it in the middle
+    /// of them would be an event lost.
+    conn: Connection,
+    modes: DisplayModes,
+    selected: usize,
+    /// The first row shown, when the list is longer than the window.
+    scroll: usize,
+    stage: Stage,
+    /// What the last action came to, shown under the list until the next.
+    status: String,
+}
+
+impl Settings {
+    fn new(conn: Connection) -> Self {
+        let window = Window::create_with_title(WIDTH, HEIGHT, "Display").unwrap_or_else(|e| {
+            eprintln!("display: {e}");
+            std::process::exit(1);
+        });
+        let fb = window.framebuffer();
+        let font_data =
+            fs::read("/share/fonts/JetBrainsMono-Regular-8x16.font").expect("failed to read font");
+        let font = Font::from_prebuilt(&font_data);
+
+        let mut settings = Self {
+            window,
+            fb,
+            font,
+            conn,
+            modes: DisplayModes::EMPTY,
+            selected: 0,
+            scroll: 0,
+            stage: Stage::Choosing,
+            status: String::new(),
+        };
+        settings.refresh();
+        settings.draw();
+        settings
+    }
+
+    /// Ask for the mode list again and select the mode now showing.
+    fn refresh(&mut self) {
+        self.modes = query_modes(&self.conn);
+        let current = self.modes.modes().iter().position(|m| m.flags & MODE_CURRENT != 0);
+        self.selected = current.unwrap_or(0);
+        self.ensure_visible();
+    }
+
+    fn current(&self) -> Option<DisplayMode> {
+        self.modes.modes().iter().copied().find(|m| m.flags & MODE_CURRENT != 0)
+    }
+
+    /// Set the selected mode and start the countdown that undoes it.
+    fn apply(&mut self) {
+        let Some(&mode) = self.modes.modes().get(self.selected) else { return };
+        let Some(previous) = self.current() else { return };
+        if mode.flags & MODE_CURRENT != 0 {
+            self.status = format!("Already showing {}x{}", mode.width, mode.height);
+            return;
+        }
+        set_mode(&self.conn, mode);
+        self.refresh();
+        // The compositor answers a refused mode the same as a set one, with
+        // the size it ended up at, so whether it took is read off the list.
+        let took = self.current().is_some_and(|c| (c.width, c.height) == (mode.width, mode.height));
+        if took {
+            let deadline = Instant::now() + Duration::from_secs(CONFIRM_SECS);
+            self.stage = Stage::Confirming { previous, deadline };
+            self.status.clear();
+        } else {
+            self.status = format!("The display refused {}x{}", mode.width, mode.height);
+        }
+    }
+
+    /// Keep the mode on trial, and write it down for the next boot.
+    fn keep(&mut self) {
+        let Stage::Confirming { .. } = self.stage else { return };
+        self.stage = Stage::Choosing;
+        let Some(mode) = self.current() else { return };
+        let path = window::MODE_CONFIG;
+        let saved = Path::new(path)
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(path, format!("{}x{}\n", mode.width, mode.height)));
+        self.status = match saved {
+            Ok(()) => format!("Kept {}x{}", mode.width, mode.height),
+            Err(e) => {
+                eprintln!("display: cannot save the mode to {path}: {e}");
+                format!("Kept {}x{} until the next boot", mode.width, mode.height)
+            }
+        };
+    }
+
+    /// Put back the mode the trial replaced.
+    fn revert(&mut self) {
+        let Stage::Confirming { previous, .. } = self.stage else { return };
+        self.stage = Stage::Choosing;
+        set_mode(&self.conn, previous);
+        self.refresh();
+        self.status = format!("Reverted to {}x{}", previous.width, previous.height);
+    }
+
+    fn key(&mut self, keycode: u8) {
+        let trial = matches!(self.stage, Stage::Confirming { .. });
+        match (trial, keycode) {
+            (false, KEY_UP) => self.select(self.selected.saturating_sub(1)),
+            (false, KEY_DOWN) => self.select(self.selected + 1),
+            (false, KEY_ENTER) => self.apply(),
+            (true, KEY_ENTER) => self.keep(),
+            (true, KEY_ESCAPE) => self.revert(),
+            _ => {}
+        }
+    }
+
+    fn click(&mut self, x: usize, y: usize) {
+        for (button, bx, by) in self.buttons() {
+            if (bx..bx + BUTTON_WIDTH).contains(&x) && (by..by + BUTTON_HEIGHT).contains(&y) {
+                match button {
+                    Button::Apply => self.apply(),
+                    Button::Keep => self.keep(),
+                    Button::Revert => self.revert(),
+                }
+                return;
+            }
+        }
+        // The list is not a choice while a mode is on trial: the choice on
+        // the screen is keep or revert.
+        if let Stage::Choosing = self.stage {
+            if let Some(row) = self.row_at(y) {
+                self.select(row);
+            }
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        let count = self.modes.modes().len();
+        if count == 0 {
+            return;
+        }
+        self.selected = index.min(count - 1);
+        self.ensure_visible();
+    }
+
+    fn list_top(&self) -> usize {
+        BAR_HEIGHT + PADDING
+    }
+
+    fn visible_rows(&self) -> usize {
+        let list = self.fb.height().saturating_sub(self.list_top() + FOOTER_HEIGHT);
+        (list / ROW_HEIGHT).max(1)
+    }
+
+    fn ensure_visible(&mut self) {
+        let rows = self.visible_rows();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+    }
+
+    fn row_at(&self, y: usize) -> Option<usize> {
+        let offset = y.checked_sub(self.list_top())? / ROW_HEIGHT;
+        if offset >= self.visible_rows() {
+            return None;
+        }
+        let row = self.scroll + offset;
+        (row < self.modes.modes().len()).then_some(row)
+    }
+
+    /// The buttons this stage offers, each with its top-left corner,
+    /// right-aligned along the bottom of the window.
+    fn buttons(&self) -> Vec<(Button, usize, usize)> {
+        let y = self.fb.height().saturating_sub(PADDING + BUTTON_HEIGHT);
+        let right = self.fb.width().saturating_sub(PADDING + BUTTON_WIDTH);
+        match self.stage {
+            Stage::Choosing => vec![(Button::Apply, right, y)],
+            Stage::Confirming { .. } => vec![
+                (Button::Revert, right, y),
+                (Button::Keep, right.saturating_sub(PADDING + BUTTON_WIDTH), y),
+            ],
+        }
+    }
+
+    fn draw(&self) {
+        let w = self.fb.width();
+        let h = self.fb.height();
+        let fw = self.font.width();
+        let fh = self.font.height();
+        self.fb.fill_rect(0, 0, w, h, BG);
+
+        // Header: the monitor's physical size, which is what the DPI below
+        // comes from.
+        self.fb.fill_rect(0, 0, w, BAR_HEIGHT, BAR_BG);
+        let header = match (self.modes.width_mm, self.modes.height_mm) {
+            (0, _) | (_, 0) => "Monitor size not reported".to_string(),
+            (w_mm, h_mm) => format!("Monitor {w_mm} x {h_mm} mm"),
+        };
+        self.font.draw_string(&self.fb, PADDING, (BAR_HEIGHT - fh) / 2, &header, DIM_FG, BAR_BG);
+
+        let modes = self.modes.modes();
+        if modes.is_empty() {
+            let text = "No modes to offer";
+            self.font.draw_string(&self.fb, PADDING, self.list_top(), text, DIM_FG, BG);
+        }
+        let trial = matches!(self.stage, Stage::Confirming { .. });
+        for (i, mode) in modes.iter().enumerate().skip(self.scroll).take(self.visible_rows()) {
+            let y = self.list_top() + (i - self.scroll) * ROW_HEIGHT;
+            let bg = if i == self.selected && !trial { SEL_BG } else { BG };
+            self.fb.fill_rect(0, y, w, ROW_HEIGHT, bg);
+            let current = mode.flags & MODE_CURRENT != 0;
+            let fg = if current { CURRENT_FG } else { TEXT_FG };
+            let marker = if current { "*" } else { " " };
+            let text = format!("{marker} {}", label(mode));
+            let max_chars = w.saturating_sub(2 * PADDING) / fw;
+            let text: String = text.chars().take(max_chars).collect();
+            self.font.draw_string(&self.fb, PADDING, y + (ROW_HEIGHT - fh) / 2, &text, fg, bg);
+        }
+
+        // Footer: the countdown while a mode is on trial, and otherwise the
+        // last action's result or the selected mode's DPI.
+        let footer_y = h.saturating_sub(FOOTER_HEIGHT);
+        self.fb.fill_rect(0, footer_y, w, FOOTER_HEIGHT, BAR_BG);
+        let line = match self.stage {
+            Stage::Confirming { deadline, .. } => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                format!("Keep this mode? Reverting in {} s", left.as_millis().div_ceil(1000))
+            }
+            Stage::Choosing if !self.status.is_empty() => self.status.clone(),
+            Stage::Choosing => {
+                modes.get(self.selected).and_then(|m| self.dpi(m)).unwrap_or_default()
+            }
+        };
+        self.font.draw_string(&self.fb, PADDING, footer_y + PADDING, &line, TEXT_FG, BAR_BG);
+
+        for (button, x, y) in self.buttons() {
+            let (text, bg, fg) = match button {
+                Button::Apply => ("Apply", ACCENT_BG, ACCENT_FG),
+                Button::Keep => ("Keep", ACCENT_BG, ACCENT_FG),
+                Button::Revert => ("Revert", BUTTON_BG, BUTTON_FG),
+            };
+            self.fb.fill_rect(x, y, BUTTON_WIDTH, BUTTON_HEIGHT, bg);
+            let tx = x + BUTTON_WIDTH.saturating_sub(text.len() * fw) / 2;
+            self.font.draw_string(&self.fb, tx, y + (BUTTON_HEIGHT - fh) / 2, text, fg, bg);
+        }
+        self.window.present();
+    }
+
+    /// `mode`'s dots per inch on this monitor, or `None` when it does not say
+    /// how big it is.
+    fn dpi(&self, mode: &DisplayMode) -> Option<String> {
+        let (w_mm, h_mm) = (self.modes.width_mm, self.modes.height_mm);
+        if w_mm == 0 || h_mm == 0 {
+            return None;
+        }
+        let per_inch =
+            |px: u32, mm: u32| (u64::from(px) * 254 + u64::from(mm) * 5) / (u64::from(mm) * 10);
+        let (x, y) = (per_inch(mode.width, w_mm), per_inch(mode.height, h_mm));
+        Some(if x == y { format!("{x} dpi") } else { format!("{x} x {y} dpi") })
+    }
+
+    fn run(&mut self) {
+        loop {
+            let event = match self.stage {
+                Stage::Choosing => Some(self.window.recv_event()),
+                Stage::Confirming { deadline, .. } => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        self.revert();
+                        self.draw();
+                        continue;
+                    }
+                    // Wake on each whole second left, so the countdown counts.
+                    let to_tick = left.as_nanos() % 1_000_000_000;
+                    let wait = if to_tick == 0 { 1_000_000_000 } else { to_tick as u64 };
+                    self.window.poll_event(wait)
+                }
+            };
+            match event {
+                None => {}
+                Some(Event::KeyInput(key)) if key.pressed() => self.key(key.keycode),
+                Some(Event::MouseInput(ev)) => {
+                    if ev.event_type == window::MOUSE_PRESS && ev.changed == 1 {
+                        self.click(ev.x as usize, ev.y as usize);
+                    } else if ev.event_type == window::MOUSE_SCROLL {
+                        let max = self.modes.modes().len().saturating_sub(self.visible_rows());
+                        if ev.scroll < 0 {
+                            self.scroll = (self.scroll + 1).min(max);
+                        } else if ev.scroll > 0 {
+                            self.scroll = self.scroll.saturating_sub(1);
+                        }
+                    }
+                }
+                // A mode set moves and resizes windows with the monitor, this
+                // one among them.
+                Some(Event::Resized) => {
+                    self.fb = self.window.framebuffer();
+                    self.ensure_visible();
+                }
+                // Closing the window is not keeping the mode.
+                Some(Event::Close) => {
+                    self.revert();
+                    break;
+                }
+                Some(_) => continue,
+            }
+            self.draw();
+        }
+    }
+}
+
+/// One row of the list: `1920 x 1080  60 Hz`, and which mode the monitor
+/// wants. A rate of zero is a mode the kernel knows only by its size.
+fn label(mode: &DisplayMode) -> String {
+    let mut text = format!("{} x {}", mode.width, mode.height);
+    if mode.refresh_mhz != 0 {
+        text += &format!("  {} Hz", (mode.refresh_mhz + 500) / 1000);
+    }
+    if mode.flags & MODE_PREFERRED != 0 {
+        text += "  (preferred)";
+    }
+    text
+}
+
+/// The primary output's modes, or an empty list when the compositor gave none.
+fn query_modes(conn: &Connection) -> DisplayModes {
+    if conn.signal(window::MSG_GET_MODES).is_err() {
+        return DisplayModes::EMPTY;
+    }
+    let Ok(header) = conn.recv_header() else { return DisplayModes::EMPTY };
+    let mut buf = [0u8; size_of::<DisplayModes>()];
+    let Ok(n) = conn.recv_bytes(&header, &mut buf) else { return DisplayModes::EMPTY };
+    if header.msg_type != window::MSG_MODES {
+        return DisplayModes::EMPTY;
+    }
+    DisplayModes::from_bytes(&buf[..n]).unwrap_or(DisplayModes::EMPTY)
+}
+
+/// Ask for `mode` on the primary output and wait for the answer, which is the
+/// same whether the mode was set or refused.
+fn set_mode(conn: &Connection, mode: DisplayMode) {
+    let req = window::ResolutionRequest { width: mode.width, height: mode.height };
+    if conn.send(window::MSG_SET_RESOLUTION, &req).is_ok() {
+        let _ = conn.recv::<window::ResolutionInfo>();
+    }
+}
+
+fn main() {
+    let conn = endow::service("compositor").unwrap_or_else(|e| {
+        eprintln!("display: no compositor to ask ({e:?})");
+        std::process::exit(1);
+    });
+    Settings::new(conn).run();
+}
@@ -357,7 +357,7 @@ impl FileBrowser {
                 // Every key this browser reads is a HID usage — arrows, Enter,
                 // Backspace — so no layout moves any of them.
                 window::Event::LayoutChanged => {}
-                window::Event::Frame => {}
+                window::Event::Frame | window::Event::GamepadInput(_) => {}
                 window::Event::Close => break,
             }
         }
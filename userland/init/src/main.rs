@@ -40,7 +40,8 @@ use toyos::namespace::{self, Namespace};
 use toyos::poller::{Poller, READABLE};
 use toyos::port::{self, Acceptor, Connector};
 use toyos::syscap::SysCap;
-use toyos::AsHandle;
+use toyos::{AsHandle, PowerDev};
+use toyos_abi::power::{PowerEvent, POWER_BUTTON};
 use toyos_abi::syscall::{
     DeviceType, DEV_PREFIX, PROVIDE_PREFIX, SERVE_PREFIX, SVC_LABEL, SYSCAP_LABEL,
 };
@@ -89,9 +90,11 @@ struct Pending {
 
 /// The poll token for the `launcher` acceptor. A pending connection's token is
 /// [`TOKEN_PENDING_BASE`] plus its handle, which is unique among the
-/// connections init holds at once.
+/// connections init holds at once. The power claim's is the one number no
+/// handle offset can reach.
 const TOKEN_ACCEPTOR: u64 = 0;
 const TOKEN_PENDING_BASE: u64 = 1;
+const TOKEN_POWER: u64 = u64::MAX;
 
 fn main() {
     let syscap: SysCap = Endowments::get()
@@ -146,7 +149,40 @@ fn main() {
     let launcher = acceptors
         .remove(LAUNCHER)
         .expect("init: the manifest declares init serves `launcher`");
-    launch_forever(&launcher, &system, &syscap, &mut acceptors, &connectors);
+
+    // init's own claim, never endowed: what a press of the power button means
+    // is decided by whoever may turn the machine off, and that is this cap.
+    // After `[boot] start`, so a press during boot is not a shutdown before
+    // anything is up to be shut down.
+    let power = match syscap.claim::<PowerDev>(DeviceType::Power) {
+        Ok(power) => Some(power),
+        Err(e) => {
+            say!("init: no power buttons ({e:?}); the machine powers off only when asked");
+            None
+        }
+    };
+    launch_forever(&launcher, power.as_ref(), &system, &syscap, &mut acceptors, &connectors);
+}
+
+/// Everything the power claim has queued. A press of the power button is an
+/// orderly shutdown: `SYS_SHUTDOWN` syncs every filesystem and waits for logd
+/// to make the last lines durable, which is why every daemon is left running
+/// until it does. Returns only if the kernel refused.
+fn drain_power(power: &PowerDev, syscap: &SysCap) {
+    let mut buf = [0u8; 16 * std::mem::size_of::<PowerEvent>()];
+    while let Ok(n) = power.read_nonblock(&mut buf) {
+        let events = buf[..n].chunks_exact(std::mem::size_of::<PowerEvent>());
+        for event in events.filter_map(PowerEvent::from_bytes) {
+            // A sleep button or a lid is a request there is no answer to yet,
+            // and an unknown kind is a later kernel's.
+            if event.kind != POWER_BUTTON {
+                continue;
+            }
+            say!("init: power button — shutting down");
+            let e = syscap.shutdown();
+            say!("init: shutdown refused: {e:?}");
+        }
+    }
 }
 
 /// Serve `launcher` for the rest of the machine's life.
@@ -162,16 +198,20 @@ fn main() {
 /// init alive and looking healthy.
 fn launch_forever<'a>(
     launcher: &Acceptor,
+    power: Option<&PowerDev>,
     system: &'a Manifest,
     syscap: &SysCap,
     acceptors: &mut BTreeMap<&'a str, Acceptor>,
     connectors: &BTreeMap<&str, Connector>,
 ) -> ! {
-    let poller = Poller::new(1 + MAX_PENDING_LAUNCHES as u32);
+    let poller = Poller::new(2 + MAX_PENDING_LAUNCHES as u32);
     let mut pending: Vec<Pending> = Vec::new();
     let mut ready: Vec<u64> = Vec::new();
     loop {
         poller.watch(launcher, READABLE, TOKEN_ACCEPTOR);
+        if let Some(power) = power {
+            poller.watch(power, READABLE, TOKEN_POWER);
+        }
         for p in &pending {
             poller.watch(&p.conn, READABLE, TOKEN_PENDING_BASE + p.conn.as_handle().0 as u64);
         }
@@ -204,6 +244,10 @@ fn launch_forever<'a>(
         }
         pending.retain(|p| now.duration_since(p.since) < HANDSHAKE_TIMEOUT);
 
+        if let Some(power) = power.filter(|_| ready.contains(&TOKEN_POWER)) {
+            drain_power(power, syscap);
+        }
+
         // Accept and the request are two events. Nothing is read here.
         if ready.contains(&TOKEN_ACCEPTOR) {
             let conn = match launcher.accept() {
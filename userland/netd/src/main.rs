@@ -27,7 +27,7 @@ macro_rules! say {
 
 use toyos::endow;
 use toyos::shm;
-use toyos_abi::syscall::DeviceType;
+use toyos_abi::syscall::{DeviceType, SyscallError};
 use toyos::{Nic as NicDev, Pipe};
 
 use toyos::net::*;
@@ -50,6 +50,11 @@ struct DmaNic {
     net_hdr_size: usize,
     mac: [u8; 6],
     nic: NicDev,
+    /// What [`NicDev::link`] last answered.
+    link: bool,
+    /// The NIC went away under the claim — a USB adapter pulled out — and
+    /// every call on it answers `NotFound` until one is plugged back in.
+    gone: bool,
 }
 
 impl DmaNic {
@@ -78,6 +83,42 @@ impl DmaNic {
             net_hdr_size: info.net_hdr_size as usize,
             mac: info.mac,
             nic: nic_dev,
+            link: true,
+            gone: false,
+        }
+    }
+
+    /// Ask what became of the NIC itself, which is what a wake on the claim
+    /// with no frame behind it is: the link went up or down, or the device
+    /// went or came back. A NIC that went takes the interface's address and
+    /// route with it, so sockets are refused rather than left queueing frames
+    /// nobody sends; one that comes back is given them again.
+    ///
+    /// The one the kernel puts back is behind the same page this process
+    /// mapped at claim time, so only the address needs restoring. It keeps
+    /// the MAC netd started with, which the claim described once.
+    fn refresh(&mut self, iface: &mut Interface) {
+        match self.nic.link() {
+            Ok(up) => {
+                if self.gone {
+                    self.gone = false;
+                    say!("netd: the NIC is back; bringing the interface up");
+                    configure(iface);
+                }
+                if up != self.link {
+                    self.link = up;
+                    say!("netd: link {}", if up { "up" } else { "down" });
+                }
+            }
+            Err(SyscallError::NotFound) => {
+                if !self.gone {
+                    self.gone = true;
+                    say!("netd: the NIC has gone; taking the interface down");
+                    iface.update_ip_addrs(|addrs| addrs.clear());
+                    iface.routes_mut().remove_default_ipv4_route();
+                }
+            }
+            Err(e) => panic!("netd: the kernel refused a link query on its own NIC claim: {e:?}"),
         }
     }
 
@@ -91,9 +132,15 @@ impl Device for DmaNic {
     type TxToken<'a> = DmaTxToken<'a>;
 
     fn receive(&mut self, _timestamp: SmoltcpInstant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        if self.gone { return None; }
         // netd holds the NIC claim, so a refusal here is a kernel-side bug,
-        // not a condition to swallow.
-        let v = self.nic.rx_poll().expect("netd holds the NIC claim");
+        // not a condition to swallow — except `NotFound`, which is the NIC
+        // leaving, and which `refresh` hears about on the wake it comes with.
+        let v = match self.nic.rx_poll() {
+            Ok(v) => v,
+            Err(SyscallError::NotFound) => return None,
+            Err(e) => panic!("netd: the kernel refused a poll on its own NIC claim: {e:?}"),
+        };
         if v == 0 { return None; }
         let (buf_idx, frame_len) = ((v >> 16) as usize, (v & 0xFFFF) as usize);
         // Safety: The data slice borrows from the DMA region via the device's lifetime 'a.
@@ -113,6 +160,7 @@ impl Device for DmaNic {
     }
 
     fn transmit(&mut self, _timestamp: SmoltcpInstant) -> Option<Self::TxToken<'_>> {
+        if self.gone { return None; }
         Some(DmaTxToken { tx_buf: self.tx_buf, net_hdr_size: self.net_hdr_size, nic: &self.nic })
     }
 
@@ -138,7 +186,9 @@ impl<'a> phy::RxToken for DmaRxToken<'a> {
         F: FnOnce(&[u8]) -> R,
     {
         let result = f(self.data);
-        self.nic.rx_done(self.buf_idx as u64).expect("netd holds the NIC claim");
+        // Refused only for a buffer of a NIC that has since gone: the one
+        // behind the claim now never lent it, and it is its own to fill.
+        let _ = self.nic.rx_done(self.buf_idx as u64);
         result
     }
 }
@@ -161,7 +211,11 @@ impl<'a> phy::TxToken for DmaTxToken<'a> {
                 len,
             );
             let result = f(frame);
-            self.nic.tx((self.net_hdr_size + len) as u64).expect("netd holds the NIC claim");
+            // `NotFound` is a NIC that went between the poll and this send;
+            // the frame goes nowhere, as it would have on a pulled cable.
+            if let Err(e) = self.nic.tx((self.net_hdr_size + len) as u64) {
+                assert_eq!(e, SyscallError::NotFound, "netd holds the NIC claim");
+            }
             result
         }
     }
@@ -658,7 +712,7 @@ impl NetDaemon {
             // request, so an empty pipe is a client naming bytes it never put
             // there. A blocking read here waits for a second write that a
             // conforming client never makes.
-            Err(toyos_abi::syscall::SyscallError::WouldBlock) => {
+            Err(SyscallError::WouldBlock) => {
                 msg.client.error(ERR_INVALID_INPUT);
                 return;
             }
@@ -1211,6 +1265,16 @@ impl NetDaemon {
     }
 }
 
+/// QEMU's user-mode network: the address slirp hands out, and its gateway.
+fn configure(iface: &mut Interface) {
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(IpAddress::v4(10, 0, 2, 15), 24)).ok();
+    });
+    iface.routes_mut()
+        .add_default_ipv4_route(Ipv4Addr::new(10, 0, 2, 2))
+        .ok();
+}
+
 fn main() {
     // **The order this used to have was load-bearing and is now moot.** The
     // device was claimed before the name was published, because a client that
@@ -1239,12 +1303,7 @@ fn main() {
     let now = SmoltcpInstant::from_millis(0);
     let mut iface = Interface::new(config, &mut device, now);
 
-    iface.update_ip_addrs(|addrs| {
-        addrs.push(IpCidr::new(IpAddress::v4(10, 0, 2, 15), 24)).ok();
-    });
-    iface.routes_mut()
-        .add_default_ipv4_route(Ipv4Addr::new(10, 0, 2, 2))
-        .ok();
+    configure(&mut iface);
 
     let mut socket_set = SocketSet::new(vec![]);
 
@@ -1342,6 +1401,10 @@ fn main() {
         let mut ready: Vec<u64> = Vec::new();
         poller.wait(1, timeout, |token| ready.push(token));
 
+        if ready.contains(&TOKEN_NIC) {
+            device.refresh(&mut iface);
+        }
+
         // A handshake that never completes is why this deadline exists, and the
         // sweep has to happen on a pass that found nothing ready too —
         // otherwise a silent client is only ever timed out by some *other*
@@ -1,18 +1,19 @@
-//! The device half of the mix loop, and the two devices behind it.
+//! The device half of the mix loop, and the three devices behind it.
 //!
-//! Everything that differs between virtio-sound and HDA is a method below. The
-//! mixer, the ramps, the DLL, the underrun accounting and the suspend/resume
-//! structure are one body of code either way, which is what makes gate A one
-//! instrument for both — and what [`Pipeline`] exists to keep honest, because
-//! the two differ in *who owns a period soundd has not refilled* and nothing
-//! else in the loop can be written without knowing which.
+//! Everything that differs between virtio-sound, HDA and a USB DAC is a method
+//! below. The mixer, the ramps, the DLL, the underrun accounting and the
+//! suspend/resume structure are one body of code whichever it is, which is
+//! what makes gate A one instrument for all of them — and what [`Pipeline`]
+//! exists to keep honest, because they differ in *who owns a period soundd has
+//! not refilled* and nothing else in the loop can be written without knowing
+//! which.
 
 use toyos::AsHandle;
 use toyos_abi::audio::AudioCompletionRecord;
 use toyos_abi::syscall;
 use toyos_abi::RawHandle;
 
-use crate::{hda, virtio};
+use crate::{hda, usb, virtio};
 
 /// Who owns a period soundd has been given back and has not refilled.
 ///
@@ -29,7 +30,9 @@ pub(crate) enum Pipeline {
     /// does not have. Holding one costs nothing, indefinitely, and the play
     /// order is the submit order.
     Queue,
-    /// HDA: the engine owns every period for as long as it runs. It returns to
+    /// HDA, and the USB DAC whose kernel driver copies packets out of a ring
+    /// laid out the same way: the engine owns every period for as long as it
+    /// runs. It returns to
     /// buffer `i` exactly `num_buffers` periods after completing it and plays
     /// whatever is there, so a period soundd holds back is played as the
     /// silence `released` left in it *and* completed a second time — a
@@ -40,12 +43,12 @@ pub(crate) enum Pipeline {
 
 /// The device half of the mix loop.
 ///
-/// Two implementations and no more: a framework before there are three is an
-/// abstraction with no evidence behind it. Both are drivers in this process
-/// now, and what differs between the two devices is exactly the methods below;
-/// the mixer, the ramps, the DLL, the underrun accounting and the
-/// suspend/resume structure are one body of code either way, which is what
-/// makes gate A one instrument for both.
+/// Three implementations, and the third cost no new method: a USB DAC is a
+/// [`Pipeline::Ring`] with a different start and stop, which is the evidence
+/// the trait was drawn in the right place. What differs between the devices is
+/// exactly the methods below; the mixer, the ramps, the DLL, the underrun
+/// accounting and the suspend/resume structure are one body of code whichever
+/// it is, which is what makes gate A one instrument for all three.
 pub(crate) trait Backend {
     /// Who owns a freed period soundd does not refill.
     fn pipeline(&self) -> Pipeline;
@@ -79,6 +82,12 @@ pub(crate) trait Backend {
 
     /// Stop the stream. Idempotent, and cheap when it is already stopped.
     fn stop(&mut self);
+
+    /// The device was reset across a sleep
+    /// ([`AudioCompletionRecord::RESET`]): its stream is stopped and what the
+    /// kernel does not keep for it is configured again here. The mix loop
+    /// takes every period back itself.
+    fn reset(&mut self);
 }
 
 pub(crate) struct VirtioBackend {
@@ -115,6 +124,12 @@ impl Backend for VirtioBackend {
     fn stop(&mut self) {
         self.virtio.stop();
     }
+
+    fn reset(&mut self) {
+        if let Err(e) = self.virtio.reset() {
+            panic!("soundd: virtio-sound could not be configured again after the sleep: {e}");
+        }
+    }
 }
 
 pub(crate) struct HdaBackend {
@@ -158,4 +173,62 @@ impl Backend for HdaBackend {
     fn stop(&mut self) {
         self.hda.stop();
     }
+
+    fn reset(&mut self) {
+        self.hda.reset();
+    }
+}
+
+/// The HDA backend's ring discipline over the USB claim's PCM ring.
+///
+/// The kernel keeps packetizing whatever the ring holds for as long as the
+/// stream runs, so a period nobody refills is replayed exactly as it is on
+/// HDA, and `released` zeroes it for the same reason.
+pub(crate) struct UsbAudioBackend {
+    pub(crate) usb: usb::Usb,
+    pub(crate) buffers: Vec<*mut u8>,
+    pub(crate) period_bytes: usize,
+}
+
+impl Backend for UsbAudioBackend {
+    fn pipeline(&self) -> Pipeline {
+        Pipeline::Ring
+    }
+
+    fn handle(&self) -> RawHandle {
+        self.usb.dev().as_handle()
+    }
+
+    fn buffer(&self, idx: usize) -> *mut u8 {
+        self.buffers[idx]
+    }
+
+    fn completions(&mut self, out: &mut [AudioCompletionRecord]) -> usize {
+        match self.usb.dev().completions() {
+            Ok(record) => {
+                out[0] = record;
+                1
+            }
+            Err(syscall::SyscallError::WouldBlock) => 0,
+            Err(e) => panic!("soundd: usb audio completions failed: {e:?}"),
+        }
+    }
+
+    fn released(&mut self, idx: usize) {
+        unsafe { core::ptr::write_bytes(self.buffers[idx], 0, self.period_bytes) };
+    }
+
+    fn submit(&mut self, _idx: usize, _bytes: usize) {
+        self.usb.start();
+    }
+
+    fn stop(&mut self) {
+        self.usb.stop();
+    }
+
+    /// Never asked: the kernel keeps a machine with a DAC bound awake, because
+    /// this process holds the DAC's PCM ring.
+    fn reset(&mut self) {
+        panic!("soundd: the USB DAC reports a reset, and a machine with one bound does not sleep");
+    }
 }
@@ -26,13 +26,13 @@ use crate::command::{remove, submit, CommandRing, MixCommand};
 
 /// Control connections soundd will hold at once.
 ///
-/// The control thread watches one handle per client plus the acceptor in a
-/// single poller and io_uring rings are powers of two, so the limit is a ring
-/// size minus one. 64 costs the same 2 MiB page as 32; 63 simultaneous streams
-/// is already past what the mixer renders inside one 2.9 ms period, and costs
-/// 189 of the kernel's 4096 handle slots (a control connection plus both signal
-/// pipe ends per client).
-pub(crate) const MAX_CONTROL_CLIENTS: usize = 63;
+/// The control thread watches one handle per client plus the acceptor and the
+/// reroute pipe in a single poller and io_uring rings are powers of two, so the
+/// limit is a ring size minus two. 64 costs the same 2 MiB page as 32; 62
+/// simultaneous streams is already past what the mixer renders inside one
+/// 2.9 ms period, and costs 186 of the kernel's 4096 handle slots (a control
+/// connection plus both signal pipe ends per client).
+pub(crate) const MAX_CONTROL_CLIENTS: usize = 62;
 
 /// The widest control payload soundd decodes, **plus one**.
 ///
@@ -127,19 +127,27 @@ fn reject_open(req: &StreamOpenRequest) -> Option<&'static str> {
     None
 }
 
+/// Serve one session's connections until `reroute_read` is written, and hand
+/// the acceptor back for the next.
+///
+/// Every stream opened here is opened against the one device shape it was
+/// given, so a session whose output moved has no client this thread could go
+/// on serving: they are all dropped with it.
 pub(crate) fn control_thread(
     acceptor: Acceptor,
     cmd_ring: &CommandRing,
     cmd_pipe_write: RawHandle,
+    reroute_read: RawHandle,
     device_sample_rate: u32,
     device_channels: u16,
     device_period_frames: u32,
     slot_count: u32,
     ramp_frames: u32,
-) {
-    // One handle per client plus the acceptor; `MAX_CONTROL_CLIENTS` is derived
-    // from this ring, so the set always fits in one batch.
-    let poller = Poller::new(MAX_CONTROL_CLIENTS as u32 + 1);
+) -> Acceptor {
+    // One handle per client plus the acceptor and the reroute pipe;
+    // `MAX_CONTROL_CLIENTS` is derived from this ring, so the set always fits
+    // in one batch.
+    let poller = Poller::new(MAX_CONTROL_CLIENTS as u32 + 2);
     let period_nanos = period_nanos(device_period_frames as u64, device_sample_rate as u64);
 
     struct ControlClient {
@@ -162,9 +170,11 @@ pub(crate) fn control_thread(
     let mut next_idx: usize = 0;
 
     const TOKEN_ACCEPT: u64 = u64::MAX;
+    const TOKEN_REROUTE: u64 = u64::MAX - 1;
 
     loop {
         poller.watch(&acceptor, READABLE, TOKEN_ACCEPT);
+        poller.watch_raw(reroute_read, READABLE, TOKEN_REROUTE);
         for (i, client) in clients.iter().enumerate() {
             poller.watch(&client.conn, READABLE, i as u64);
         }
@@ -172,6 +182,12 @@ pub(crate) fn control_thread(
         let mut ready: Vec<u64> = Vec::new();
         poller.wait(1, u64::MAX, |t| ready.push(t));
 
+        // Before anything is read or accepted: whatever it asked for would be
+        // answered with the shape of a device that has stopped playing.
+        if ready.contains(&TOKEN_REROUTE) {
+            return acceptor;
+        }
+
         if ready.contains(&TOKEN_ACCEPT) {
             match acceptor.accept() {
                 // Refused rather than left queued: a connection past the
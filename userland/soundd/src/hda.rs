@@ -196,6 +196,14 @@ impl Hda {
         self.running = true;
     }
 
+    /// The controller came back from a sleep. The kernel sent the codec this
+    /// driver's verbs again and put back the format and tag, and it left the
+    /// engine stopped. So a reset costs this driver only the belief that the
+    /// engine is running.
+    pub fn reset(&mut self) {
+        self.running = false;
+    }
+
     pub fn stop(&mut self) {
         if !self.running {
             return;
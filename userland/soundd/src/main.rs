@@ -14,24 +14,32 @@
 //!
 //! | | |
 //! |---|---|
-//! | [`backend`] | the two devices, and the one thing that differs between them |
+//! | [`backend`] | the three devices, and the one thing that differs between them |
 //! | [`client`] | one stream: its ring, its ramp, and how it ended |
 //! | [`command`] | the ring the control thread hands the mix thread |
 //! | [`control`] | the connections, the framing, and what a client may ask for |
 //! | [`mix`] | the two loops — a device's, and the null sink's |
-//! | [`hda`], [`virtio`] | the two drivers |
+//! | [`hda`], [`usb`], [`virtio`] | the three drivers |
 //!
 //! This file is the fourth thing: which of them the machine gets. A sound card
 //! this process was endowed with runs the device loop; anything else — no card,
 //! a card that cannot carry audio, a shape the mixer cannot render — runs the
 //! null sink, because §6 says soundd always runs and always accepts streams.
+//!
+//! **And it gets asked again.** A USB DAC is plugged in and pulled out while
+//! the machine runs, so the answer is a session rather than a choice made once:
+//! one device shape, one control thread serving streams against it, and one
+//! loop mixing them, until the DAC arrives or leaves. Then the session ends,
+//! every stream in it with it, and the next one is chosen the same way.
 
 use toyos::endow;
 use toyos::port::Acceptor;
 use toyos::shm::SharedMemory;
-use toyos::{HdaDev, VirtioSoundDev};
+use toyos::AsHandle;
+use toyos::{HdaDev, UsbAudioDev, VirtioSoundDev};
 use toyos_abi::syscall::{self, DeviceType};
-use toyos_mixer::{period_frames, ramp_frames};
+use toyos_abi::RawHandle;
+use toyos_mixer::{period_frames, period_nanos, ramp_frames};
 
 use std::sync::Arc;
 
@@ -65,12 +73,13 @@ mod command;
 mod control;
 mod hda;
 mod mix;
+mod usb;
 mod virtio;
 
-use backend::{Backend, HdaBackend, VirtioBackend};
+use backend::{Backend, HdaBackend, UsbAudioBackend, VirtioBackend};
 use command::CommandRing;
 use control::control_thread;
-use mix::{mix_thread, null_sink_thread};
+use mix::{abandon, mix_thread, null_sink_thread, Route};
 
 /// The virtual output soundd presents when the machine has no audio hardware.
 /// These match the one configuration cpal's ToyOS backend advertises
@@ -80,13 +89,13 @@ use mix::{mix_thread, null_sink_thread};
 const NULL_SINK_RATE: u32 = 44_100;
 const NULL_SINK_CHANNELS: u16 = 2;
 const NULL_SINK_PERIOD_FRAMES: usize = 128;
-/// Same DMA-pipeline depth as both hardware backends: the client ring
+/// Same DMA-pipeline depth as every hardware backend: the client ring
 /// is as deep, so a client may fill `NULL_SINK_BUFFERS - 1` periods ahead and
 /// its backpressure is the device's. Power of two — ring indices wrap mod 2^32.
 pub(crate) const NULL_SINK_BUFFERS: usize = 8;
 
 fn main() {
-    let acceptor = endow::acceptor("soundd")
+    let mut acceptor = endow::acceptor("soundd")
         .expect("the manifest declares this program serves `soundd`");
 
     // **"Which sound card does this machine have?" is already answered.** init
@@ -99,59 +108,156 @@ fn main() {
     // presents a virtual output and discards what is played to it, so a client
     // building a stream succeeds whether or not hardware is present.
     //
-    // The order is virtio first, and it is not a preference between two cards:
-    // no machine in this project has both. The T14 has only the second.
-    if let Some(dev) = endow::device::<VirtioSoundDev>(DeviceType::VirtioSound) {
-        match virtio::Virtio::claim(dev) {
-            Ok((virtio, rate, channels)) => return run_virtio(acceptor, virtio, rate, channels),
+    // The USB audio claim is the exception to "what the machine had": it is
+    // the class and not the device, minted on any machine with an xHCI
+    // controller, because a DAC plugged in after boot can only be heard about
+    // through a claim already held.
+    let mut dac = endow::device::<UsbAudioDev>(DeviceType::UsbAudio).and_then(|dev| {
+        match usb::Usb::claim(dev) {
+            Ok(usb) => Some(DacClaim::map(usb)),
             Err(why) => {
-                say!("soundd: the virtio-sound device cannot carry audio: {why}");
-                return run_null_sink(acceptor);
+                say!("soundd: the USB audio claim cannot carry audio: {why}");
+                None
             }
         }
+    });
+    let mut card = Card::claim();
+
+    // A plugged-in DAC before the card, and this *is* a preference: a laptop
+    // with a DAC plugged in has both, and the DAC is the output somebody chose
+    // by plugging it in. Pulling it out hands the machine back to its card.
+    loop {
+        acceptor = match dac.as_mut() {
+            None => card.run(acceptor, Route::Fixed),
+            Some(claim) => match claim.backend.usb.plugged() {
+                Some(found) => {
+                    claim.backend.usb.describe(found);
+                    run_usb(acceptor, claim)
+                }
+                None => {
+                    let route = Route::Card { dac: claim.backend.usb.dev().as_handle() };
+                    card.run(acceptor, route)
+                }
+            },
+        };
     }
-    let Some(dev) = endow::device::<HdaDev>(DeviceType::HdaAudio) else {
-        return run_null_sink(acceptor);
-    };
-    match hda::Hda::claim(dev) {
-        Ok((hda, _path, channels)) => run_hda(acceptor, hda, channels),
-        Err(why) => {
-            say!("soundd: the HDA controller cannot carry audio: {why}");
-            run_null_sink(acceptor)
+}
+
+/// The machine's own output: the card it was built with, or nothing.
+enum Card {
+    Virtio { backend: VirtioBackend, rate: u32, channels: u8 },
+    /// The PCM ring is kept beside the backend that points into it, for as long
+    /// as the backend can be handed to a session.
+    Hda { backend: HdaBackend, channels: u8, _ring: SharedMemory },
+    None,
+}
+
+impl Card {
+    /// Claim the card once, for every session that plays through it.
+    ///
+    /// The order is virtio first, and it is not a preference between two
+    /// cards: no machine in this project has both. The T14 has only the second.
+    fn claim() -> Card {
+        if let Some(dev) = endow::device::<VirtioSoundDev>(DeviceType::VirtioSound) {
+            return match virtio::Virtio::claim(dev) {
+                Ok((virtio, rate, channels)) => {
+                    Card::Virtio { backend: VirtioBackend { virtio }, rate, channels }
+                }
+                Err(why) => {
+                    say!("soundd: the virtio-sound device cannot carry audio: {why}");
+                    Card::None
+                }
+            };
+        }
+        let Some(dev) = endow::device::<HdaDev>(DeviceType::HdaAudio) else {
+            return Card::None;
+        };
+        match hda::Hda::claim(dev) {
+            Ok((hda, _path, channels)) => {
+                let info = hda.info();
+                let period_bytes = info.period_bytes as usize;
+                let ring = SharedMemory::adopt(info.pcm, 2 * 1024 * 1024)
+                    .expect("the PCM buffer the HDA claim just handed over");
+                // One region, `periods` buffers end to end: the buffer
+                // descriptor list the kernel built points at exactly these
+                // offsets.
+                let buffers = periods(&ring, info.periods as usize, period_bytes);
+                Card::Hda { backend: HdaBackend { hda, buffers, period_bytes }, channels, _ring: ring }
+            }
+            Err(why) => {
+                say!("soundd: the HDA controller cannot carry audio: {why}");
+                Card::None
+            }
+        }
+    }
+
+    fn run(&mut self, acceptor: Acceptor, route: Route) -> Acceptor {
+        match self {
+            Card::Virtio { backend, rate, channels } => run_with_device(
+                acceptor,
+                backend,
+                toyos_abi::virtio_sound::PERIODS,
+                *rate,
+                *channels as u16,
+                toyos_abi::virtio_sound::PERIOD_BYTES,
+                route,
+            ),
+            Card::Hda { backend, channels, .. } => {
+                let num_buffers = backend.buffers.len();
+                let period_bytes = backend.period_bytes;
+                run_with_device(
+                    acceptor,
+                    backend,
+                    num_buffers,
+                    toyos_hda::config::RATE,
+                    *channels as u16,
+                    period_bytes,
+                    route,
+                )
+            }
+            Card::None => run_null_sink(acceptor, route),
         }
     }
 }
 
-fn run_virtio(acceptor: Acceptor, virtio: virtio::Virtio, rate: u32, channels: u8) {
-    run_with_device(
-        acceptor,
-        &mut VirtioBackend { virtio },
-        toyos_abi::virtio_sound::PERIODS,
-        rate,
-        channels as u16,
-        toyos_abi::virtio_sound::PERIOD_BYTES,
-    );
+/// The USB audio claim, mapped once. The PCM ring is the same memory whichever
+/// DAC is plugged in, and the claim describes it to one read only.
+struct DacClaim {
+    backend: UsbAudioBackend,
+    _ring: SharedMemory,
 }
 
-fn run_hda(acceptor: Acceptor, hda: hda::Hda, channels: u8) {
-    let info = hda.info();
-    let num_buffers = info.periods as usize;
-    let period_bytes = info.period_bytes as usize;
-    let ring = SharedMemory::adopt(info.pcm, 2 * 1024 * 1024)
-        .expect("the PCM buffer the HDA claim just handed over");
-    // One region, `periods` buffers end to end: the buffer descriptor list the
-    // kernel built points at exactly these offsets.
+impl DacClaim {
+    fn map(usb: usb::Usb) -> DacClaim {
+        let info = usb.info();
+        let period_bytes = info.period_bytes as usize;
+        let ring = SharedMemory::adopt(info.pcm, 2 * 1024 * 1024)
+            .expect("the PCM buffer the USB audio claim just handed over");
+        // The same layout as HDA's: the kernel packetizes from offset
+        // `i * period_bytes` for period `i`.
+        let buffers = periods(&ring, info.periods as usize, period_bytes);
+        DacClaim { backend: UsbAudioBackend { usb, buffers, period_bytes }, _ring: ring }
+    }
+}
+
+/// Where each of `count` periods of `period_bytes` starts, laid end to end.
+fn periods(ring: &SharedMemory, count: usize, period_bytes: usize) -> Vec<*mut u8> {
     let base = ring.as_ptr();
-    let buffers = (0..num_buffers).map(|i| unsafe { base.add(i * period_bytes) }).collect();
+    (0..count).map(|i| unsafe { base.add(i * period_bytes) }).collect()
+}
 
+fn run_usb(acceptor: Acceptor, claim: &mut DacClaim) -> Acceptor {
+    let info = claim.backend.usb.info();
+    let route = Route::Dac { dac: claim.backend.usb.dev().as_handle() };
     run_with_device(
         acceptor,
-        &mut HdaBackend { hda, buffers, period_bytes },
-        num_buffers,
-        toyos_hda::config::RATE,
-        channels as u16,
-        period_bytes,
-    );
+        &mut claim.backend,
+        info.periods as usize,
+        info.rate,
+        info.channels as u16,
+        info.period_bytes as usize,
+        route,
+    )
 }
 
 fn run_with_device(
@@ -161,7 +267,8 @@ fn run_with_device(
     device_sample_rate: u32,
     device_channels: u16,
     device_period_bytes: usize,
-) {
+    route: Route,
+) -> Acceptor {
     // A shape this mixer cannot render is named and the machine gets the null
     // sink, which is what §6 is for. It is checked before any arithmetic
     // derives anything from it — a zero channel count divides by zero on the
@@ -171,7 +278,7 @@ fn run_with_device(
         Ok(frames) => frames,
         Err(why) => {
             say!("soundd: this audio device's shape cannot carry audio: {why}");
-            return run_null_sink(acceptor);
+            return run_null_sink(acceptor, route);
         }
     };
 
@@ -179,83 +286,119 @@ fn run_with_device(
     // at most num_buffers periods, so a full client ring always covers it.
     let slot_count = num_buffers as u32;
 
-    let ramp_frames = ramp_frames(device_sample_rate);
-
     say!("soundd: ready, {} buffers, {}Hz {}ch, {} bytes/period, {} frames/period",
         num_buffers, device_sample_rate, device_channels, device_period_bytes, device_period_frames);
 
-    let cmd_ring = Arc::new(CommandRing::new());
-    let cmd_pipe = syscall::pipe().expect("soundd: failed to create the command pipe");
-
-    let cmd_ring2 = cmd_ring.clone();
-    std::thread::Builder::new()
-        .name("soundd-ctrl".into())
-        .spawn(move || {
-            control_thread(
-                acceptor,
-                &cmd_ring2,
-                cmd_pipe.write,
-                device_sample_rate,
-                device_channels,
-                device_period_frames as u32,
-                slot_count,
-                ramp_frames,
-            );
-        })
-        .expect("soundd: failed to spawn control thread");
-
-    mix_thread(
-        backend,
-        &cmd_ring,
-        cmd_pipe.read,
-        num_buffers,
+    serve(
+        acceptor,
         device_sample_rate,
         device_channels,
-        device_period_bytes,
         device_period_frames,
-        ramp_frames,
-    );
+        slot_count,
+        |cmd_ring, cmd_pipe_read, ramp_frames| {
+            mix_thread(
+                backend,
+                cmd_ring,
+                cmd_pipe_read,
+                num_buffers,
+                device_sample_rate,
+                device_channels,
+                device_period_bytes,
+                device_period_frames,
+                ramp_frames,
+                route,
+            )
+        },
+    )
 }
 
-fn run_null_sink(acceptor: Acceptor) {
+fn run_null_sink(acceptor: Acceptor, route: Route) -> Acceptor {
     let device_sample_rate = NULL_SINK_RATE;
     let device_channels = NULL_SINK_CHANNELS;
     let device_period_frames = NULL_SINK_PERIOD_FRAMES;
-    let slot_count = NULL_SINK_BUFFERS as u32;
-
-    let ramp_frames = ramp_frames(device_sample_rate);
 
     say!(
         "soundd: no audio device, presenting a null sink ({}Hz {}ch, {} frames/period, streams discarded)",
         device_sample_rate, device_channels, device_period_frames
     );
 
+    serve(
+        acceptor,
+        device_sample_rate,
+        device_channels,
+        device_period_frames,
+        NULL_SINK_BUFFERS as u32,
+        |cmd_ring, cmd_pipe_read, ramp_frames| {
+            null_sink_thread(
+                cmd_ring,
+                cmd_pipe_read,
+                device_sample_rate,
+                device_channels,
+                device_period_frames,
+                ramp_frames,
+                route,
+            )
+        },
+    )
+}
+
+/// One session: a control thread serving streams against one device shape, and
+/// `mix` mixing them on this thread, until the loop returns because the output
+/// moved. Hands the acceptor back for the next session.
+///
+/// **Every connection ends with the session**, the ones with a stream and the
+/// ones still deciding what to ask for. A stream's ring was sized and its
+/// resampler built for this shape, and a client mid-open was about to be
+/// answered with it; the SDK reports both as `Disconnected`, which is what a
+/// stream whose device went away is. Connections waiting in the port's queue
+/// are untouched and are accepted by the next session's thread.
+fn serve(
+    acceptor: Acceptor,
+    device_sample_rate: u32,
+    device_channels: u16,
+    device_period_frames: usize,
+    slot_count: u32,
+    mix: impl FnOnce(&CommandRing, RawHandle, u32),
+) -> Acceptor {
+    let ramp_frames = ramp_frames(device_sample_rate);
+
     let cmd_ring = Arc::new(CommandRing::new());
     let cmd_pipe = syscall::pipe().expect("soundd: failed to create the command pipe");
+    let reroute = syscall::pipe().expect("soundd: failed to create the reroute pipe");
 
     let cmd_ring2 = cmd_ring.clone();
-    std::thread::Builder::new()
+    let control = std::thread::Builder::new()
         .name("soundd-ctrl".into())
         .spawn(move || {
             control_thread(
                 acceptor,
                 &cmd_ring2,
                 cmd_pipe.write,
+                reroute.read,
                 device_sample_rate,
                 device_channels,
                 device_period_frames as u32,
                 slot_count,
                 ramp_frames,
-            );
+            )
         })
         .expect("soundd: failed to spawn control thread");
 
-    null_sink_thread(
-        &cmd_ring,
-        cmd_pipe.read,
-        device_sample_rate,
-        device_channels,
-        device_period_frames,
-        ramp_frames,
-    );
+    mix(&cmd_ring, cmd_pipe.read, ramp_frames);
+
+    // The control thread is told, and the ring is drained until it has
+    // listened. Draining is not tidiness: `submit` waits for room with no
+    // limit, and the thread that made room is the one that just returned.
+    let _ = syscall::write_nonblock(reroute.write, &[1]);
+    let period = period_nanos(device_period_frames as u64, device_sample_rate as u64);
+    while !control.is_finished() {
+        abandon(&cmd_ring);
+        syscall::nanosleep(period);
+    }
+    let acceptor = control.join().expect("soundd: the control thread panicked");
+    abandon(&cmd_ring);
+    for end in [cmd_pipe.read, cmd_pipe.write, reroute.read, reroute.write] {
+        syscall::close(end);
+    }
+    acceptor
 }
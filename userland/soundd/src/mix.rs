@@ -10,6 +10,9 @@
 //!
 //! **What a period *is* was decided in `toyos-mixer`** before either loop ran:
 //! this file spends the answers and owns the effects around them.
+//!
+//! Either loop can end, and for one reason: a USB DAC arrived or left, which
+//! [`Route`] is how they hear.
 
 use toyos::endow::Endowments;
 use toyos::poller::{Poller, READABLE};
@@ -22,6 +25,8 @@ use toyos_mixer::{
     deferral_floor_nanos, period_nanos, quantize_period, scratch_frames, Dll, MixStats, Xorshift32,
 };
 
+use std::sync::Once;
+
 use crate::backend::{Backend, Pipeline};
 use crate::client::{mix_client, ClientStream, Departure};
 use crate::command::{CommandRing, MixCommand};
@@ -29,6 +34,80 @@ use crate::NULL_SINK_BUFFERS;
 
 const STATS_INTERVAL_NANOS: u64 = 2_000_000_000;
 
+/// What ends a loop, when anything does.
+///
+/// A USB DAC is the one output on a machine that comes and goes. The claim
+/// soundd holds for it outlives the device and is made readable when one binds
+/// or leaves, so a loop playing through anything else watches it for the DAC's
+/// arrival, and a loop playing through the DAC hears its departure on the
+/// handle it already waits on. Either way the loop returns and `main` chooses
+/// again.
+#[derive(Clone, Copy)]
+pub(crate) enum Route {
+    /// Nothing can move the output: this machine has no DAC claim.
+    Fixed,
+    /// The card or the null sink, until a DAC is plugged in behind `dac`.
+    Card { dac: RawHandle },
+    /// The DAC behind `dac`, until it is pulled out.
+    Dac { dac: RawHandle },
+}
+
+impl Route {
+    /// The claim to watch beside whatever the loop already waits on, if any.
+    fn watched(self) -> Option<RawHandle> {
+        match self {
+            Route::Fixed => None,
+            Route::Card { dac } | Route::Dac { dac } => Some(dac),
+        }
+    }
+
+    /// Whether the news on the claim moved the output. Asking clears it, so a
+    /// wake that was only a DAC being described again costs one syscall.
+    fn moved(self) -> bool {
+        let plugged = |dac: RawHandle| match syscall::usb_audio_dac(dac) {
+            Ok(found) => found.is_some(),
+            Err(e) => panic!("soundd: usb audio could not say whether a DAC is plugged in: {e:?}"),
+        };
+        match self {
+            Route::Fixed => false,
+            Route::Card { dac } => plugged(dac),
+            Route::Dac { dac } => !plugged(dac),
+        }
+    }
+}
+
+/// End every stream a loop was mixing, because the device it was opened
+/// against is not the one that plays next. No ramp: the DAC that left cannot
+/// play one, and the card a DAC replaced is stopped the same instant.
+fn reroute(streams: &mut Vec<ClientStream>) {
+    for s in streams.drain(..) {
+        say!("soundd: client {} removed (rerouted)", s.client_id);
+        syscall::close(s.signal_write);
+    }
+}
+
+/// Close the streams the control thread opened and no loop will mix — the
+/// `AddClient`s still in the ring when a session ended.
+///
+/// The signal pipe is what tells a client its stream is over. Dropping the
+/// command unread would leave the write end open in this process, and the
+/// client waiting on a stream nothing mixes for as long as soundd runs.
+pub(crate) fn abandon(cmd_ring: &CommandRing) {
+    while let Some(cmd) = cmd_ring.pop() {
+        if let MixCommand::AddClient(client) = cmd {
+            say!("soundd: client {} removed (rerouted)", client.client_id);
+            syscall::close(client.signal_write);
+        }
+    }
+}
+
+/// Enter the RT band the first time a device loop runs, and never again.
+///
+/// The capability is endowed once and taken once, and a thread in the band
+/// stays there: a session after a DAC arrived or left runs on the same thread,
+/// already in it.
+static RT_BAND: Once = Once::new();
+
 /// One reporting window on the console. One line, one `write`.
 ///
 /// The counters are `toyos_mixer::MixStats`, and what they mean is documented
@@ -122,6 +201,7 @@ pub(crate) fn mix_thread(
     device_period_bytes: usize,
     device_period_frames: usize,
     ramp_frames: u32,
+    route: Route,
 ) {
     let device_period_samples = device_period_frames * device_channels as usize;
     let period_nanos = period_nanos(device_period_frames as u64, device_sample_rate as u64);
@@ -197,10 +277,12 @@ pub(crate) fn mix_thread(
     // `RT`-only capability the manifest's `syscap = ["rt"]` row asks init for,
     // and soundd is the only program in the tree that has one. Mixing on
     // without the band would show up only as glitches, so a refusal is loud.
-    let rt: SysCap = Endowments::get()
-        .take(toyos_abi::syscall::SYSCAP_LABEL)
-        .expect("the manifest declares this program `syscap = [\"rt\"]`");
-    rt.enter_rt().expect("an RT capability refused the band it names");
+    RT_BAND.call_once(|| {
+        let rt: SysCap = Endowments::get()
+            .take(toyos_abi::syscall::SYSCAP_LABEL)
+            .expect("the manifest declares this program `syscap = [\"rt\"]`");
+        rt.enter_rt().expect("an RT capability refused the band it names");
+    });
 
     let poller = Poller::new(64);
     let mut mix_f32 = vec![0.0f32; device_period_samples];
@@ -211,10 +293,11 @@ pub(crate) fn mix_thread(
     let mut convert_buf = vec![0.0f32; max_client_frames * 2];
     let mut dither_rng = Xorshift32::new(syscall::clock_nanos() as u32);
     let mut dll = Dll::new(period_nanos as f64);
-    let mut records = [AudioCompletionRecord { mask: 0, _pad: 0, timestamp_nanos: 0 }; 16];
+    let mut records = [AudioCompletionRecord { mask: 0, feedback_rate: 0, timestamp_nanos: 0 }; 16];
 
     const TOKEN_AUDIO: u64 = u64::MAX - 1;
     const TOKEN_CMD: u64 = u64::MAX - 2;
+    const TOKEN_ROUTE: u64 = u64::MAX - 3;
 
     // Buffers the previous cycle deliberately left unfilled. Read by the drain
     // site, which must not mistake soundd's own restraint for a device stall.
@@ -271,11 +354,18 @@ pub(crate) fn mix_thread(
 
         poller.watch_raw(backend.handle(), READABLE, TOKEN_AUDIO);
         poller.watch_raw(cmd_pipe_read, READABLE, TOKEN_CMD);
+        // The DAC's own loop already waits on its claim.
+        if let Route::Card { dac } = route {
+            poller.watch_raw(dac, READABLE, TOKEN_ROUTE);
+        }
 
         let mut cmd_ready = false;
+        let mut audio_ready = false;
+        let mut route_ready = false;
         poller.wait(1, timeout, |token| match token {
-            TOKEN_AUDIO => {}
+            TOKEN_AUDIO => audio_ready = true,
             TOKEN_CMD => cmd_ready = true,
+            TOKEN_ROUTE => route_ready = true,
             other => panic!("soundd: unexpected poll token {other}"),
         });
 
@@ -295,6 +385,24 @@ pub(crate) fn mix_thread(
         }
 
         let n_records = backend.completions(&mut records);
+
+        // A DAC arriving is news on the claim watched beside the card's. One
+        // leaving is news on the claim this loop plays through, and reads as a
+        // wake with nothing played: an unplugged DAC completes nothing again.
+        let news = match route {
+            Route::Fixed => false,
+            Route::Card { .. } => route_ready,
+            Route::Dac { .. } => audio_ready && n_records == 0,
+        };
+        if news && route.moved() {
+            reroute(&mut streams);
+            if started {
+                backend.stop();
+                say!("soundd: suspended");
+            }
+            return;
+        }
+
         if n_records > 0 {
             // Read before the record loop, because it is what a *pickup* is
             // measured to: the instant soundd first held the record, not the
@@ -302,6 +410,23 @@ pub(crate) fn mix_thread(
             let seen_at = syscall::clock_nanos();
             let mut wake_completions = 0u32;
             for rec in &records[..n_records] {
+                // The machine slept and the device was reset. Nothing it was
+                // given plays, so every period is soundd's again and the
+                // stream is stopped. From here it is boot's SUSPENDED, and the
+                // next client's refill starts the stream as at boot.
+                if rec.is_reset() {
+                    backend.reset();
+                    free_mask = (1u32 << num_buffers) - 1;
+                    unplayed = 0;
+                    ring_cursor = 0;
+                    dll.reset();
+                    if started {
+                        started = false;
+                        say!("soundd: suspended");
+                    }
+                    say!("soundd: the device was reset across a sleep");
+                    continue;
+                }
                 let n = rec.mask.count_ones();
                 assert!(n > 0, "soundd: completion record with empty mask");
                 assert_eq!(free_mask & rec.mask, 0, "soundd: repeated completion for free buffer");
@@ -341,6 +466,12 @@ pub(crate) fn mix_thread(
                 }
                 wake_completions += n;
                 dll.update(rec.timestamp_nanos as f64, n);
+                // A device that says how fast it plays is believed over the
+                // timestamps, which carry the jitter of whatever reported
+                // them. The rate is in millihertz, hence 1e12 and not 1e9.
+                if rec.feedback_rate != 0 {
+                    dll.steer(device_period_frames as f64 * 1e12 / rec.feedback_rate as f64);
+                }
             }
             // Measured against the prediction this wait was *armed* on, not
             // against whatever the DLL holds when the wait returns. They differ
@@ -613,7 +744,9 @@ pub(crate) fn mix_thread(
 /// Idle discipline matches §5.8: with no streams it holds no timer and takes no
 /// wakes, blocking on the command pipe alone, so an audience of zero costs
 /// exactly zero CPU. It does not request the RT band — it protects no audible
-/// output, so there is nothing for the band to protect.
+/// output, so there is nothing for the band to protect. A null sink that
+/// follows a DAC out of the machine runs on the thread that played it, and is
+/// in the band for that reason only.
 pub(crate) fn null_sink_thread(
     cmd_ring: &CommandRing,
     cmd_pipe_read: RawHandle,
@@ -621,6 +754,7 @@ pub(crate) fn null_sink_thread(
     device_channels: u16,
     device_period_frames: usize,
     ramp_frames: u32,
+    route: Route,
 ) {
     let device_period_samples = device_period_frames * device_channels as usize;
     let period_nanos = period_nanos(device_period_frames as u64, device_sample_rate as u64);
@@ -635,6 +769,7 @@ pub(crate) fn null_sink_thread(
     let mut convert_buf = vec![0.0f32; max_client_frames * 2];
 
     const TOKEN_CMD: u64 = u64::MAX - 2;
+    const TOKEN_ROUTE: u64 = u64::MAX - 3;
 
     let mut stats = MixStats::default();
     let mut next_stats_ns = syscall::clock_nanos() + STATS_INTERVAL_NANOS;
@@ -660,9 +795,16 @@ pub(crate) fn null_sink_thread(
         };
 
         poller.watch_raw(cmd_pipe_read, READABLE, TOKEN_CMD);
+        // Whichever way the DAC moved, it is news on its claim: there is no
+        // device handle here for a departure to arrive on instead.
+        if let Some(dac) = route.watched() {
+            poller.watch_raw(dac, READABLE, TOKEN_ROUTE);
+        }
         let mut cmd_ready = false;
+        let mut route_ready = false;
         poller.wait(1, timeout, |token| match token {
             TOKEN_CMD => cmd_ready = true,
+            TOKEN_ROUTE => route_ready = true,
             other => panic!("soundd: unexpected null-sink poll token {other}"),
         });
 
@@ -676,6 +818,11 @@ pub(crate) fn null_sink_thread(
         }
         apply_commands(cmd_ring, &mut streams, ramp_frames);
 
+        if route_ready && route.moved() {
+            reroute(&mut streams);
+            return;
+        }
+
         // Start the grid when the first client of a run connects, and reset the
         // reporting window so no idle stretch dilutes it.
         if !was_streaming && !streams.is_empty() {
@@ -0,0 +1,113 @@
+//! soundd as the claimant of a USB Audio Class DAC.
+//!
+//! The kernel chose the streaming interface's alternate setting, set the
+//! sampling frequency, and sends a packet every service interval out of the
+//! PCM ring this process fills. There is nothing left to decide here but when
+//! the stream runs: a claim is one read of its description and one byte per
+//! start or stop, and everything else is the HDA backend's ring discipline
+//! over different memory.
+//!
+//! What an asynchronous device adds arrives in the completion record and not
+//! here: its measured rate, which the mix loop hands the DLL.
+//!
+//! The claim is held whether or not a DAC is plugged in, and says which one is
+//! when asked: [`Usb::plugged`] is that question, and `main` asks it every time
+//! it chooses where a session plays.
+
+use toyos::UsbAudioDev;
+use toyos_abi::syscall::SyscallError;
+use toyos_abi::usb_audio::{UsbAudioDac, UsbAudioInfo};
+
+pub struct Usb {
+    dev: UsbAudioDev,
+    info: UsbAudioInfo,
+    /// Set once the kernel has been told to play, so a stop and a resume are
+    /// one write each and not one per period.
+    running: bool,
+}
+
+/// Why this machine's USB DAC cannot carry audio.
+///
+/// There is one reason only, because every property of the device that could
+/// refuse — no 48 kHz, no 16-bit stereo setting, no isochronous OUT endpoint —
+/// refused at enumeration, and a DAC that did never became a claim.
+pub enum Refusal {
+    /// The kernel's answers stopped making sense, which is a bug here or there
+    /// and never a property of the machine.
+    Kernel(SyscallError),
+}
+
+impl core::fmt::Display for Refusal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Kernel(e) => write!(f, "the kernel refused a call this driver has to make ({e})"),
+        }
+    }
+}
+
+impl Usb {
+    /// Read the claim's description. Nothing is written to the device: the
+    /// kernel configured it at enumeration and it sits idle until [`start`].
+    ///
+    /// [`start`]: Usb::start
+    pub fn claim(dev: UsbAudioDev) -> Result<Self, Refusal> {
+        let info = dev.info().map_err(Refusal::Kernel)?;
+        Ok(Usb { dev, info, running: false })
+    }
+
+    /// The DAC plugged in behind the claim, if there is one. Asking clears the
+    /// news a bind or an unplug left on it.
+    pub fn plugged(&self) -> Option<UsbAudioDac> {
+        match self.dev.dac() {
+            Ok(dac) => dac,
+            Err(e) => panic!("soundd: usb audio could not say whether a DAC is plugged in: {e:?}"),
+        }
+    }
+
+    /// Name the DAC a session is about to play through. Its own two fields
+    /// come from `dac` rather than the description, which was read once and
+    /// may have been read before it was plugged in.
+    pub fn describe(&self, dac: UsbAudioDac) {
+        say!(
+            "soundd: usb audio UAC{} DAC, {} Hz {} ch, {} periods of {} bytes, {}",
+            dac.version,
+            self.info.rate,
+            self.info.channels,
+            self.info.periods,
+            self.info.period_bytes,
+            if dac.feedback { "asynchronous" } else { "paced by the host" },
+        );
+    }
+
+    pub fn info(&self) -> UsbAudioInfo {
+        self.info
+    }
+
+    pub fn dev(&self) -> &UsbAudioDev {
+        &self.dev
+    }
+
+    /// Start the stream, which is one write and only on the edge.
+    ///
+    /// The kernel resumes from the period after the last one it reported, which
+    /// is the one the mix loop fills next, so a resume plays nothing twice.
+    pub fn start(&mut self) {
+        if self.running {
+            return;
+        }
+        if let Err(e) = self.dev.start() {
+            panic!("soundd: usb audio could not start its stream: {e:?}");
+        }
+        self.running = true;
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running {
+            return;
+        }
+        if let Err(e) = self.dev.stop() {
+            panic!("soundd: usb audio could not stop its stream: {e:?}");
+        }
+        self.running = false;
+    }
+}
@@ -250,6 +250,9 @@ pub struct Virtio {
     events_done: Used,
     tx: Avail,
     running: bool,
+    /// The rate and channel count the stream was configured with, which a
+    /// reset device has to be told again.
+    params: (u32, u8),
 }
 
 impl Virtio {
@@ -295,6 +298,7 @@ impl Virtio {
             events_done: used(abi::OFF_EVENT_USED, abi::EVENT_QUEUE_SIZE),
             tx: avail(abi::OFF_TX_AVAIL, abi::TX_QUEUE_SIZE, info.notify_tx, abi::TX_QUEUE),
             running: false,
+            params: (0, 0),
         };
 
         for i in 0..abi::EVENT_BUFS {
@@ -369,6 +373,24 @@ impl Virtio {
         say!("virtio-sound: stream {STREAM_ID} stopped");
     }
 
+    /// The device came back from a sleep with nothing it was given.
+    ///
+    /// The kernel put the queues back with their rings emptied. Each avail
+    /// index lives in its ring and so is already zero, and the two used ones
+    /// this process shadows start again from zero here. Then it is `claim`'s
+    /// work over again, without the question: the event buffers, and the
+    /// parameters the stream was configured with.
+    pub fn reset(&mut self) -> Result<(), Refusal> {
+        self.control_done.last = 0;
+        self.events_done.last = 0;
+        self.running = false;
+        for i in 0..abi::EVENT_BUFS {
+            self.events.publish(&self.dev, i as u16);
+        }
+        let (rate, channels) = self.params;
+        self.configure(rate, channels)
+    }
+
     /// Report what the device says went wrong, and repost the buffer it said it
     /// in. The device's own view of an underrun, which soundd's counters cannot
     /// see: they measure what this process failed to submit.
@@ -433,6 +455,7 @@ impl Virtio {
         };
         self.ctrl(&params, "SET_PARAMS")?;
         self.simple_ctrl(R_PCM_PREPARE, "PREPARE")?;
+        self.params = (rate, channels);
         say!("virtio-sound: configured stream {STREAM_ID}: {rate}Hz {channels}ch s16le");
         Ok(())
     }
@@ -186,7 +186,7 @@ fn main() {
                     console.resize(window.screen());
                     present(&console, &window);
                 }
-                window::Event::Frame => {}
+                window::Event::Frame | window::Event::GamepadInput(_) => {}
             }
         }
     }
@@ -0,0 +1,26 @@
+//! Tell the disk under a filesystem which of its blocks are free.
+//!
+//! Deleting a file already tells the device about that file's blocks, at the
+//! next sync. This catches whatever that missed, and takes as long as the
+//! volume is big: the kernel walks the whole allocation bitmap. `/home` is the
+//! default because it is the one filesystem that knows how.
+
+use toyos::system;
+
+pub fn main(args: Vec<String>) {
+    let path = match args.as_slice() {
+        [] => "/home",
+        [path] => path.as_str(),
+        _ => {
+            eprintln!("Usage: fstrim [mountpoint]");
+            std::process::exit(2);
+        }
+    };
+    match system::fstrim(path) {
+        Ok(blocks) => println!("{path}: {} MiB ({} bytes) trimmed", blocks * 4096 / (1024 * 1024), blocks * 4096),
+        Err(e) => {
+            eprintln!("fstrim: {path}: {e:?}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -2,6 +2,7 @@ mod cat;
 mod cp;
 mod echo;
 mod free;
+mod fstrim;
 mod grep;
 mod hexdump;
 mod locale;
@@ -16,7 +17,9 @@ mod screen;
 mod shutdown;
 mod spin;
 mod stats;
+mod suspend;
 mod tone;
+mod vsock;
 
 macro_rules! commands {
     ($($name:ident),*) => {
@@ -29,7 +32,7 @@ macro_rules! commands {
     };
 }
 
-commands!(cat, cp, echo, free, grep, hexdump, locale, ls, mkdir, mv, net, ps, pwd, rm, screen, shutdown, spin, stats, tone);
+commands!(cat, cp, echo, free, fstrim, grep, hexdump, locale, ls, mkdir, mv, net, ps, pwd, rm, screen, shutdown, spin, stats, suspend, tone, vsock);
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
@@ -13,7 +13,25 @@ pub fn main(args: Vec<String>) {
         }
     };
 
-    if args.is_empty() {
+    if args.first().map(String::as_str) == Some("place") {
+        // `screen place 1 -1920 0`: the second monitor to the left of the
+        // first. The answer is the size of the whole desktop afterwards.
+        let field = |i: usize| -> i32 {
+            args.get(i).and_then(|a| a.parse().ok()).unwrap_or_else(|| {
+                eprintln!("Usage: screen place OUTPUT X Y");
+                std::process::exit(1);
+            })
+        };
+        let (index, x, y) = (field(1), field(2), field(3));
+        let Ok(index) = u32::try_from(index) else {
+            eprintln!("invalid output");
+            std::process::exit(1);
+        };
+        conn.send(window::MSG_PLACE_OUTPUT, &window::OutputPlacement { index, x, y }).ok();
+        let (msg_type, info): (u32, window::ResolutionInfo) = conn.recv().expect("compositor disconnected");
+        assert_eq!(msg_type, window::MSG_RESOLUTION_CHANGED);
+        println!("{}x{}", info.width, info.height);
+    } else if args.is_empty() {
         conn.signal(window::MSG_GET_RESOLUTION).ok();
         let (msg_type, info): (u32, window::ResolutionInfo) = conn.recv().expect("compositor disconnected");
         assert_eq!(msg_type, window::MSG_RESOLUTION_CHANGED);
@@ -22,7 +40,7 @@ pub fn main(args: Vec<String>) {
         let (width, height) = if args.len() == 1 {
             let parts: Vec<&str> = args[0].split('x').collect();
             if parts.len() != 2 {
-                eprintln!("Usage: screen [WIDTHxHEIGHT]");
+                eprintln!("Usage: screen [WIDTHxHEIGHT | place OUTPUT X Y]");
                 return;
             }
             let w: u32 = parts[0].parse().unwrap_or_else(|_| { eprintln!("invalid width"); std::process::exit(1); });
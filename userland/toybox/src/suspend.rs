@@ -0,0 +1,31 @@
+//! Suspend the machine to RAM and come back.
+//!
+//! The capability is `/bin/shutdown`'s, and found the same way: this is
+//! `/bin/toybox` under another name, and the `[programs.toybox]` row's `power`
+//! is what the kernel checks. Unlike shutdown there is an answer to print —
+//! the call returns after the wake, or with the reason the machine stayed up.
+
+use toyos::endow::{Endowments, SYSCAP_LABEL};
+use toyos::syscap::SysCap;
+use toyos_abi::syscall::SyscallError;
+
+pub fn main(_args: Vec<String>) {
+    let Some(cap) = Endowments::get().take::<SysCap>(SYSCAP_LABEL) else {
+        eprintln!("suspend: this program was endowed no system capability");
+        std::process::exit(1);
+    };
+    match cap.suspend() {
+        Ok(()) => println!("suspend: resumed"),
+        Err(e) => {
+            let why = match e {
+                SyscallError::PermissionDenied => "this capability carries no POWER",
+                SyscallError::NotSupported => "this machine cannot sleep; the kernel log says why",
+                SyscallError::WouldBlock => "the machine could not be stopped this time",
+                SyscallError::Io => "the chipset ignored the sleep",
+                _ => "unexpected",
+            };
+            eprintln!("suspend: refused ({e:?}) — {why}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Talk to the host over virtio-vsock, one stream at a time.
+//!
+//! `vsock listen PORT` takes one connection, prints everything it sends until
+//! it shuts down, and answers with how many bytes that was. `vsock connect CID
+//! PORT MESSAGE` is the other direction: it sends the message, says it is done
+//! sending, and prints what comes back. Between them a host script can push a
+//! command in and pull a result out with nothing but `socat` on its side.
+
+use std::io::Write;
+
+use toyos::vsock::{self, VsockStream};
+
+const USAGE: &str = "Usage: vsock cid | vsock listen PORT | vsock connect CID PORT MESSAGE";
+
+pub fn main(args: Vec<String>) {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["cid"] => vsock::local_cid().map(|cid| println!("{cid}")),
+        ["listen", port] => listen(parse(port)),
+        ["connect", cid, port, message] => connect(parse(cid), parse(port), message),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("vsock: {e:?}");
+        std::process::exit(1);
+    }
+}
+
+fn parse<T: std::str::FromStr>(arg: &str) -> T {
+    arg.parse().unwrap_or_else(|_| {
+        eprintln!("vsock: {arg:?} is not a number\n{USAGE}");
+        std::process::exit(2);
+    })
+}
+
+fn listen(port: u32) -> Result<(), vsock::VsockError> {
+    let listener = vsock::listen(port)?;
+    // The line a host waits for before it connects.
+    println!("vsock: listening on port {}", listener.port);
+    let stream = vsock::accept(&listener)?;
+    let received = copy_to_stdout(&stream);
+    write_all(&stream, format!("{received} bytes\n").as_bytes());
+    Ok(())
+}
+
+fn connect(cid: u64, port: u32, message: &str) -> Result<(), vsock::VsockError> {
+    let stream = vsock::connect(cid, port, 10_000)?;
+    write_all(&stream, message.as_bytes());
+    // Dropping the write end is the shutdown: vsockd sees the pipe close and
+    // tells the peer this side will send no more.
+    let VsockStream { rx, tx, .. } = stream;
+    drop(tx);
+    let mut buf = [0u8; 4096];
+    let mut out = std::io::stdout();
+    while let Ok(n @ 1..) = rx.read(&mut buf) {
+        let _ = out.write_all(&buf[..n]);
+    }
+    Ok(())
+}
+
+fn copy_to_stdout(stream: &VsockStream) -> usize {
+    let mut buf = [0u8; 4096];
+    let mut out = std::io::stdout();
+    let mut total = 0;
+    while let Ok(n @ 1..) = stream.rx.read(&mut buf) {
+        let _ = out.write_all(&buf[..n]);
+        total += n;
+    }
+    let _ = out.flush();
+    total
+}
+
+fn write_all(stream: &VsockStream, mut bytes: &[u8]) {
+    while !bytes.is_empty() {
+        match stream.tx.write(bytes) {
+            Ok(n @ 1..) => bytes = &bytes[n..],
+            _ => return,
+        }
+    }
+}
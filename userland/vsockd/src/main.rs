@@ -0,0 +1,741 @@
+//! vsockd: stream sockets between this guest and its host, over virtio-vsock.
+//!
+//! The kernel owns the device's queues and hands the claim one packet per read
+//! (`toyos_abi::vsock`); everything a socket is — ports, connections, credit,
+//! shutdown — is here. Clients speak `toyos::vsock`'s protocol and get a pair
+//! of pipes per stream, exactly as netd's piped sockets work, so a program
+//! reads and writes a host connection the way it reads and writes a TCP one.
+//!
+//! **A listening port accepts on the wire before its holder accepts.** A
+//! `Request` to a port somebody is listening on is answered with `Response` at
+//! once and the stream waits in the listener's backlog, buffering at most what
+//! it advertised, until a client's `Accept` collects it. That is what a
+//! kernel's listen queue does for TCP, and it means a host-side `connect` does
+//! not hang on a guest program that is slow to call `accept`.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use toyos::endow;
+use toyos::ipc;
+use toyos::ipc::{Connection, IpcPayload, RxStep};
+use toyos::poller::{READABLE, Poller};
+use toyos::vsock::*;
+use toyos::{AsHandle, Pipe, VsockDev};
+use toyos_abi::syscall::{DeviceType, SyscallError};
+use toyos_abi::vsock::{MAX_PACKET, TRANSPORT_RESET};
+use toyos_vsock::{Addr, Event, HEADER_BYTES, Header, Op, SHUTDOWN_RCV, SHUTDOWN_SEND, State, Stream};
+
+/// One line, one `write` — netd's macro, for netd's reason.
+macro_rules! say {
+    ($($arg:tt)*) => {{
+        use std::io::Write;
+        let mut line = format!($($arg)*);
+        line.push('\n');
+        let _ = std::io::stderr().write_all(line.as_bytes());
+    }};
+}
+
+/// What each stream advertises it will buffer, and therefore the most that can
+/// be waiting in vsockd for a client that is not reading. The peer may not send
+/// past it: [`Stream::receive`] resets one that does.
+const BUF_ALLOC: u32 = 64 * 1024;
+
+/// The most payload one packet carries: a receive buffer, less its header.
+const MAX_PAYLOAD: usize = MAX_PACKET - HEADER_BYTES;
+
+/// Packets queued for the device before vsockd stops reading client pipes.
+///
+/// The transmit ring is the kernel's and it refuses with `WouldBlock` when
+/// full, so what has been built and not yet accepted waits here, in order.
+/// Data is what fills it, so data is what stops; control packets are always
+/// queued, because dropping a `Response` or a `Shutdown` breaks a stream where
+/// dropping an unread pipe's bytes only delays it.
+const OUTBOX_DATA_LIMIT: usize = 32;
+
+/// Answers to strangers — resets for packets that name no stream — past which
+/// vsockd stops answering. A peer that sprays packets at ports nobody holds
+/// grows nothing here; it is simply not told "refused" past this.
+const OUTBOX_LIMIT: usize = 256;
+
+/// Connections a listener holds that nobody has accepted yet. Past it a
+/// `Request` is answered with a reset, which is what a full listen queue says.
+const MAX_BACKLOG: usize = 16;
+
+/// Where ports vsockd picks for itself start: the same ephemeral range netd
+/// uses, for no better reason than that it is the conventional one.
+const EPHEMERAL_FIRST: u32 = 49152;
+
+/// Poll registrations that are not streams: the service acceptor and the
+/// device claim.
+const FIXED_POLL_HANDLES: u32 = 2;
+
+/// Connections accepted and not yet carrying a whole request; netd's allowance,
+/// with netd's [`HANDSHAKE_TIMEOUT`] draining it.
+const MAX_PENDING_CONNS: u32 = 32;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Every request vsockd accepts is a small typed struct; `ConnectRequest` is
+/// the widest at 16 bytes.
+const MAX_KEPT_REQUEST: usize = 64;
+
+/// Live streams, bounded by the poller: each one registers its client's write
+/// end. A stream costs its client two 2 MiB pipes and vsockd at most
+/// [`BUF_ALLOC`], so on any machine the harness boots this is the cap that
+/// binds before memory does.
+const MAX_STREAMS: usize = (Poller::MAX_HANDLES - FIXED_POLL_HANDLES - MAX_PENDING_CONNS) as usize;
+
+type ClientRx = ipc::FrameRx<MAX_KEPT_REQUEST>;
+
+const RESP_RESULT: u32 = RespType::Result as u32;
+const RESP_ERROR: u32 = RespType::Error as u32;
+
+/// One client's connection, answered exactly once; see netd's `Client`.
+struct Client {
+    conn: Connection,
+}
+
+impl Client {
+    fn result<T: IpcPayload>(&self, payload: &T) {
+        self.answered(self.conn.try_send(RESP_RESULT, payload));
+    }
+
+    fn error(&self, code: u32) {
+        self.answered(self.conn.try_send(RESP_ERROR, &ErrorResponse { code }));
+    }
+
+    fn answered(&self, sent: Result<(), ipc::TrySendError>) {
+        if let Err(e) = sent {
+            let why = match e {
+                ipc::TrySendError::Full => {
+                    "its pipe will not take the answer and it is not reading"
+                }
+                ipc::TrySendError::TooLarge => "the answer vsockd built is larger than a frame",
+                ipc::TrySendError::Syscall(_) => "its connection is gone",
+            };
+            say!("vsockd: dropping client {} — {why}", self.conn.as_handle().0);
+        }
+    }
+}
+
+struct PendingConn {
+    conn: Connection,
+    rx: ClientRx,
+    since: Instant,
+}
+
+struct Request {
+    client: Client,
+    msg_type: u32,
+    payload: [u8; MAX_KEPT_REQUEST],
+    payload_len: usize,
+}
+
+impl Request {
+    fn payload(&self) -> &[u8] {
+        &self.payload[..self.payload_len]
+    }
+}
+
+fn map_pipe_ring(pipe: &Pipe) -> *const toyos_abi::ring::RingHeader {
+    pipe.pipe_map()
+        .expect("pipe_map failed") as *const toyos_abi::ring::RingHeader
+}
+
+/// A stream's two pipes, as its client handed them over, and the ring behind
+/// the one vsockd writes, so a client that stopped reading is seen without a
+/// syscall.
+struct Pipes {
+    to_client: Option<Pipe>,
+    from_client: Option<Pipe>,
+    to_ring: *const toyos_abi::ring::RingHeader,
+}
+
+impl Pipes {
+    /// Take the pair the frame just read off `client` promised.
+    fn take(client: &Client) -> Option<Self> {
+        let [to_client, from_client] = client.conn.recv_handles_exact::<{ DATA_HANDLES }>()?;
+        let to_client = unsafe { Pipe::from_raw(to_client) };
+        let from_client = unsafe { Pipe::from_raw(from_client) };
+        Some(Self {
+            to_ring: map_pipe_ring(&to_client),
+            to_client: Some(to_client),
+            from_client: Some(from_client),
+        })
+    }
+}
+
+/// Which stream a packet belongs to: this side's port and the whole far end.
+/// A guest has one context id, so the local cid adds nothing to the key.
+type Key = (u32, Addr);
+
+struct Conn {
+    stream: Stream,
+    /// `None` while the stream waits in a listener's backlog.
+    pipes: Option<Pipes>,
+    /// Received and not yet taken by the client's pipe. Never more than
+    /// [`BUF_ALLOC`]: the stream's credit is counted against exactly this.
+    inbound: VecDeque<u8>,
+    /// The client of a `Connect` still waiting for the peer's `Response`.
+    connecting: Option<(Client, Option<Instant>)>,
+    /// This side has said it will receive no more.
+    rcv_shut: bool,
+}
+
+struct Listener {
+    notify: Pipe,
+    notify_ring: *const toyos_abi::ring::RingHeader,
+    backlog: VecDeque<Key>,
+}
+
+struct Daemon {
+    dev: VsockDev,
+    cid: u64,
+    conns: HashMap<Key, Conn>,
+    listeners: HashMap<u32, Listener>,
+    outbox: VecDeque<Vec<u8>>,
+    next_port: u32,
+}
+
+fn packet(header: &Header, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_BYTES + payload.len());
+    out.extend_from_slice(&header.to_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+impl Daemon {
+    fn local(&self, port: u32) -> Addr {
+        Addr { cid: self.cid, port }
+    }
+
+    fn port_in_use(&self, port: u32) -> bool {
+        self.listeners.contains_key(&port) || self.conns.keys().any(|&(p, _)| p == port)
+    }
+
+    fn alloc_port(&mut self) -> u32 {
+        loop {
+            let port = self.next_port;
+            self.next_port = if port == u32::MAX { EPHEMERAL_FIRST } else { port + 1 };
+            if !self.port_in_use(port) {
+                return port;
+            }
+        }
+    }
+
+    fn queue(&mut self, header: &Header) {
+        self.outbox.push_back(packet(header, &[]));
+    }
+
+    /// Hand the device what it will take, oldest first.
+    fn flush(&mut self) {
+        while let Some(front) = self.outbox.front() {
+            match self.dev.send(front) {
+                Ok(()) => {
+                    self.outbox.pop_front();
+                }
+                Err(SyscallError::WouldBlock) => return,
+                // Every packet here was built by `toyos_vsock` from this
+                // guest's own cid, which is all the kernel checks; a refusal
+                // is a cid that changed under it, and the reset notice that
+                // says so is already on its way.
+                Err(e) => {
+                    say!("vsockd: the device refused a packet ({e:?}), dropping it");
+                    self.outbox.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Take everything the device has for this side.
+    fn receive(&mut self) {
+        let mut buf = vec![0u8; MAX_PACKET];
+        loop {
+            let n = match self.dev.recv(&mut buf) {
+                Ok(n) => n,
+                Err(SyscallError::WouldBlock) => return,
+                Err(e) => panic!("vsockd holds the vsock claim and its read failed: {e:?}"),
+            };
+            let bytes = &buf[..n];
+            if n == TRANSPORT_RESET && bytes[28..32] == [0; 4] {
+                let cid = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+                self.transport_reset(cid);
+                continue;
+            }
+            // The kernel has already refused what does not parse, so this is
+            // a kernel and a crate disagreeing — said, and not acted on.
+            match Header::parse(bytes) {
+                Ok((header, payload)) => self.dispatch(&header, payload),
+                Err(e) => say!("vsockd: a packet the kernel passed does not parse: {e}"),
+            }
+        }
+    }
+
+    /// The host forgot every connection. Nothing is sent for them — there is
+    /// nobody left who remembers them to tell — and listeners stay, because a
+    /// port this guest listens on is still one it listens on.
+    fn transport_reset(&mut self, cid: u64) {
+        say!(
+            "vsockd: transport reset, {} streams lost, guest cid {} -> {cid}",
+            self.conns.len(),
+            self.cid
+        );
+        for (_, conn) in self.conns.drain() {
+            if let Some((client, _)) = conn.connecting {
+                client.error(ERR_CONNECTION_RESET);
+            }
+        }
+        for listener in self.listeners.values_mut() {
+            listener.backlog.clear();
+        }
+        self.outbox.clear();
+        self.cid = cid;
+    }
+
+    fn dispatch(&mut self, header: &Header, payload: &[u8]) {
+        let key = (header.dst.port, header.src);
+        let Some(conn) = self.conns.get_mut(&key) else {
+            self.stranger(header);
+            return;
+        };
+        match conn.stream.receive(header) {
+            Ok(Event::Established) => {
+                if let Some((client, _)) = conn.connecting.take() {
+                    client.result(&ConnectResponse { local_port: key.0 });
+                }
+            }
+            // Past this side's own `SHUTDOWN_RCV` there is nobody to give it
+            // to; taking it off the books keeps the peer's credit open so its
+            // writes fail at its end rather than as an overrun at this one.
+            Ok(Event::Data(n)) if conn.rcv_shut => conn.stream.consumed(n),
+            Ok(Event::Data(_)) => conn.inbound.extend(payload),
+            Ok(Event::CreditRequested) => {
+                let update = conn.stream.credit_update();
+                self.queue(&update);
+            }
+            // Acted on in `bridge`: a peer that is done sending is an EOF once
+            // what it did send has reached the client.
+            Ok(Event::Shutdown(_) | Event::Credit) => {}
+            Ok(Event::Reset) => {
+                let conn = self.conns.remove(&key).expect("just found");
+                if let Some((client, _)) = conn.connecting {
+                    client.error(ERR_CONNECTION_REFUSED);
+                }
+                self.forget_backlogged(key);
+            }
+            Err(violation) => {
+                say!(
+                    "vsockd: resetting the stream from {}:{} to port {} — {violation}",
+                    key.1.cid,
+                    key.1.port,
+                    key.0
+                );
+                let rst = conn.stream.reset();
+                let conn = self.conns.remove(&key).expect("just found");
+                if let Some((client, _)) = conn.connecting {
+                    client.error(ERR_CONNECTION_RESET);
+                }
+                self.forget_backlogged(key);
+                self.queue(&rst);
+            }
+        }
+    }
+
+    /// A packet naming no stream: a connection request, or a reset answer.
+    fn stranger(&mut self, header: &Header) {
+        if header.op == Op::Request && header.dst.cid == self.cid {
+            let room = self.conns.len() < MAX_STREAMS;
+            if let Some(listener) = self.listeners.get_mut(&header.dst.port) {
+                if room && listener.backlog.len() < MAX_BACKLOG {
+                    let (stream, response) = Stream::accept(header, BUF_ALLOC);
+                    let key = (header.dst.port, header.src);
+                    listener.backlog.push_back(key);
+                    let _ = toyos_abi::syscall::write_nonblock(listener.notify.as_handle(), &[1]);
+                    self.conns.insert(key, Conn {
+                        stream,
+                        pipes: None,
+                        inbound: VecDeque::new(),
+                        connecting: None,
+                        rcv_shut: false,
+                    });
+                    self.queue(&response);
+                    return;
+                }
+                say!(
+                    "vsockd: refusing {}:{} on port {} — {} streams, {} waiting to be accepted",
+                    header.src.cid,
+                    header.src.port,
+                    header.dst.port,
+                    self.conns.len(),
+                    listener.backlog.len()
+                );
+            }
+        }
+        if self.outbox.len() < OUTBOX_LIMIT
+            && let Some(rst) = Header::rst_for(header)
+        {
+            self.queue(&rst);
+        }
+    }
+
+    fn forget_backlogged(&mut self, key: Key) {
+        if let Some(listener) = self.listeners.get_mut(&key.0) {
+            listener.backlog.retain(|&k| k != key);
+        }
+    }
+
+    fn handle_message(&mut self, req: Request) {
+        match MsgType::from_u32(req.msg_type) {
+            Some(MsgType::Listen) => self.handle_listen(&req),
+            Some(MsgType::Accept) => self.handle_accept(&req),
+            Some(MsgType::Connect) => self.handle_connect(req),
+            Some(MsgType::LocalCid) => req.client.result(&CidResponse { cid: self.cid }),
+            None => {
+                say!("vsockd: unknown message type {}", req.msg_type);
+                req.client.error(ERR_INVALID_INPUT);
+            }
+        }
+    }
+
+    fn handle_listen(&mut self, msg: &Request) {
+        let Ok(req) = ipc::decode_payload::<ListenRequest>(msg.payload()) else {
+            msg.client.error(ERR_INVALID_INPUT);
+            return;
+        };
+        let Some([notify]) = msg.client.conn.recv_handles_exact::<{ NOTIFY_HANDLES }>() else {
+            msg.client.error(ERR_INVALID_INPUT);
+            return;
+        };
+        let notify = unsafe { Pipe::from_raw(notify) };
+        let port = if req.port == 0 { self.alloc_port() } else { req.port };
+        if self.port_in_use(port) {
+            msg.client.error(ERR_ADDR_IN_USE);
+            return;
+        }
+        let notify_ring = map_pipe_ring(&notify);
+        self.listeners.insert(port, Listener { notify, notify_ring, backlog: VecDeque::new() });
+        // The listener is its port: there is one per port, so the id a client
+        // accepts with is the port it asked for.
+        msg.client.result(&ListenResponse { listener_id: port, port });
+    }
+
+    fn handle_accept(&mut self, msg: &Request) {
+        let Ok(req) = ipc::decode_payload::<AcceptRequest>(msg.payload()) else {
+            msg.client.error(ERR_INVALID_INPUT);
+            return;
+        };
+        let Some(pipes) = Pipes::take(&msg.client) else {
+            msg.client.error(ERR_INVALID_INPUT);
+            return;
+        };
+        let Some(key) = self.listeners.get_mut(&req.listener_id).and_then(|l| l.backlog.pop_front())
+        else {
+            msg.client.error(ERR_NOT_CONNECTED);
+            return;
+        };
+        let conn = self.conns.get_mut(&key).expect("a backlogged stream is a live one");
+        conn.pipes = Some(pipes);
+        msg.client.result(&AcceptResponse {
+            peer_cid: key.1.cid,
+            peer_port: key.1.port,
+            local_port: key.0,
+        });
+    }
+
+    fn handle_connect(&mut self, msg: Request) {
+        let Ok(req) = ipc::decode_payload::<ConnectRequest>(msg.payload()) else {
+            msg.client.error(ERR_INVALID_INPUT);
+            return;
+        };
+        if self.conns.len() >= MAX_STREAMS {
+            say!("vsockd: refusing connect, {} streams already (max {MAX_STREAMS})", self.conns.len());
+            msg.client.error(ERR_RESOURCE_EXHAUSTED);
+            return;
+        }
+        let Some(pipes) = Pipes::take(&msg.client) else {
+            msg.client.error(ERR_INVALID_INPUT);
+            return;
+        };
+        // A stream to itself would need the device to loop packets back, and
+        // virtio-vsock does not.
+        if req.cid == self.cid {
+            msg.client.error(ERR_CONNECTION_REFUSED);
+            return;
+        }
+        let port = self.alloc_port();
+        let peer = Addr { cid: req.cid, port: req.port };
+        let (stream, request) = Stream::connect(self.local(port), peer, BUF_ALLOC);
+        let deadline = (req.timeout_ms > 0)
+            .then(|| Instant::now() + Duration::from_millis(req.timeout_ms as u64));
+        self.conns.insert((port, peer), Conn {
+            stream,
+            pipes: Some(pipes),
+            inbound: VecDeque::new(),
+            connecting: Some((msg.client, deadline)),
+            rcv_shut: false,
+        });
+        self.queue(&request);
+    }
+
+    /// Move bytes between every accepted stream and its client's pipes, and
+    /// retire the streams both sides are done with.
+    fn bridge(&mut self) {
+        let mut done = Vec::new();
+        let mut out = Vec::new();
+        let data_room = OUTBOX_DATA_LIMIT.saturating_sub(self.outbox.len());
+        for (&key, conn) in self.conns.iter_mut() {
+            if conn.stream.state() != State::Established {
+                continue;
+            }
+            let Some(pipes) = conn.pipes.as_mut() else { continue };
+            let to_ring = unsafe { &*pipes.to_ring };
+
+            // Peer → client, as far as the pipe will take it.
+            if let Some(pipe) = &pipes.to_client {
+                while !conn.inbound.is_empty() {
+                    let (front, _) = conn.inbound.as_slices();
+                    match toyos_abi::syscall::write_nonblock(pipe.as_handle(), front) {
+                        Ok(n) if n > 0 => {
+                            conn.inbound.drain(..n);
+                            conn.stream.consumed(n as u32);
+                        }
+                        _ => break,
+                    }
+                }
+                if let Some(update) = conn.stream.credit_update_due() {
+                    out.push(packet(&update, &[]));
+                }
+                if conn.inbound.is_empty() && conn.stream.peer_done_sending() {
+                    pipes.to_client = None;
+                }
+            }
+            // The client stopped reading: nothing it is sent can go anywhere.
+            if pipes.to_client.is_some() && to_ring.is_reader_closed() {
+                pipes.to_client = None;
+            }
+            if pipes.to_client.is_none() && !conn.rcv_shut && !conn.stream.peer_done_sending() {
+                conn.rcv_shut = true;
+                conn.inbound.clear();
+                out.push(packet(&conn.stream.shutdown(SHUTDOWN_RCV), &[]));
+            }
+
+            // Client → peer, as far as the peer's credit and the outbox allow.
+            if let Some(pipe) = &pipes.from_client {
+                let mut buf = [0u8; MAX_PAYLOAD];
+                let mut eof = false;
+                while out.len() < data_room {
+                    let want = (conn.stream.window() as usize).min(MAX_PAYLOAD);
+                    if want == 0 {
+                        break;
+                    }
+                    match toyos_abi::syscall::read_nonblock(pipe.as_handle(), &mut buf[..want]) {
+                        Ok(0) => {
+                            eof = true;
+                            break;
+                        }
+                        Ok(n) => {
+                            let header = conn.stream.send(n as u32).expect("within the window");
+                            out.push(packet(&header, &buf[..n]));
+                        }
+                        Err(_) => break,
+                    }
+                }
+                // A read of zero and nothing else: the ring says the writer has
+                // gone while its last bytes may still be in the pipe.
+                if eof {
+                    pipes.from_client = None;
+                    out.push(packet(&conn.stream.shutdown(SHUTDOWN_SEND), &[]));
+                }
+            }
+
+            // `finished` alone is not enough: the peer may be done while what
+            // it sent is still on its way into the client's pipe.
+            let client_gone = pipes.to_client.is_none() && pipes.from_client.is_none();
+            if client_gone || (conn.stream.finished() && pipes.to_client.is_none()) {
+                done.push(key);
+            }
+        }
+        self.outbox.extend(out);
+        for key in done {
+            let mut conn = self.conns.remove(&key).expect("collected above");
+            // The side that finishes says so: the peer forgets the stream on
+            // the reset, not on the shutdowns before it.
+            let rst = conn.stream.reset();
+            self.queue(&rst);
+        }
+    }
+
+    /// Connects past their deadline, and listeners whose holder has gone.
+    fn expire(&mut self) {
+        let now = Instant::now();
+        let late: Vec<Key> = self
+            .conns
+            .iter()
+            .filter(|(_, c)| matches!(c.connecting, Some((_, Some(d))) if now >= d))
+            .map(|(&k, _)| k)
+            .collect();
+        for key in late {
+            let mut conn = self.conns.remove(&key).expect("collected above");
+            let rst = conn.stream.reset();
+            self.queue(&rst);
+            if let Some((client, _)) = conn.connecting {
+                client.error(ERR_TIMED_OUT);
+            }
+        }
+
+        let dead: Vec<u32> = self
+            .listeners
+            .iter()
+            .filter(|(_, l)| unsafe { &*l.notify_ring }.is_reader_closed())
+            .map(|(&port, _)| port)
+            .collect();
+        for port in dead {
+            let listener = self.listeners.remove(&port).expect("collected above");
+            for key in listener.backlog {
+                if let Some(mut conn) = self.conns.remove(&key) {
+                    let rst = conn.stream.reset();
+                    self.queue(&rst);
+                }
+            }
+        }
+    }
+
+    fn has_deadline(&self) -> bool {
+        self.conns.values().any(|c| matches!(c.connecting, Some((_, Some(_)))))
+    }
+}
+
+fn main() {
+    let Some(dev) = endow::device::<VsockDev>(DeviceType::Vsock) else {
+        say!("vsockd: no vsock device on this machine, exiting");
+        return;
+    };
+    let acceptor = endow::acceptor("vsockd")
+        .expect("the manifest declares this program serves `vsockd`");
+    let info = dev.info().expect("vsockd: failed to read VsockInfo");
+    say!("vsockd: ready, guest cid {}, at most {MAX_STREAMS} streams", info.guest_cid);
+
+    let mut daemon = Daemon {
+        dev,
+        cid: info.guest_cid,
+        conns: HashMap::new(),
+        listeners: HashMap::new(),
+        outbox: VecDeque::new(),
+        next_port: EPHEMERAL_FIRST,
+    };
+
+    let poller = Poller::new(FIXED_POLL_HANDLES + MAX_STREAMS as u32 + MAX_PENDING_CONNS);
+    const TOKEN_LISTENER: u64 = 0;
+    const TOKEN_DEVICE: u64 = 1;
+    const TOKEN_STREAM: u64 = 2;
+    // Clear of a connection's own handle by more than `MAX_HANDLES`, as netd's.
+    const TOKEN_PENDING_BASE: u64 = 0x1_0000;
+
+    let mut pending: Vec<PendingConn> = Vec::new();
+
+    loop {
+        daemon.receive();
+        daemon.expire();
+        daemon.bridge();
+        daemon.flush();
+
+        poller.watch(&acceptor, READABLE, TOKEN_LISTENER);
+        poller.watch(&daemon.dev, READABLE, TOKEN_DEVICE);
+        for conn in daemon.conns.values() {
+            if let Some(pipe) = conn.pipes.as_ref().and_then(|p| p.from_client.as_ref()) {
+                poller.watch(pipe, READABLE, TOKEN_STREAM);
+            }
+        }
+        for p in pending.iter() {
+            poller.watch(&p.conn, READABLE, TOKEN_PENDING_BASE + p.conn.as_handle().0 as u64);
+        }
+
+        // Nothing wakes vsockd when a client drains its pipe, when the
+        // transmit ring frees a slot, or when a connect runs out of time, so
+        // while any of those could be owed the loop polls at netd's 1 ms. A
+        // client that stops reading wakes nothing either, and with streams
+        // open that is looked for at a slower 10 ms.
+        let busy = !daemon.outbox.is_empty()
+            || daemon.conns.values().any(|c| !c.inbound.is_empty())
+            || daemon.has_deadline();
+        let mut timeout = if busy {
+            Duration::from_millis(1).as_nanos() as u64
+        } else if !daemon.conns.is_empty() {
+            Duration::from_millis(10).as_nanos() as u64
+        } else {
+            u64::MAX
+        };
+        if !pending.is_empty() {
+            timeout = timeout.min(HANDSHAKE_TIMEOUT.as_nanos() as u64);
+        }
+
+        let mut ready: Vec<u64> = Vec::new();
+        poller.wait(1, timeout, |token| ready.push(token));
+
+        let now = Instant::now();
+        for p in pending.iter().filter(|p| now.duration_since(p.since) >= HANDSHAKE_TIMEOUT) {
+            say!(
+                "vsockd: dropping client {} — it never finished its request",
+                p.conn.as_handle().0
+            );
+        }
+        pending.retain(|p| now.duration_since(p.since) < HANDSHAKE_TIMEOUT);
+
+        if ready.contains(&TOKEN_LISTENER) {
+            let conn = acceptor.accept().expect("accept failed");
+            if pending.len() >= MAX_PENDING_CONNS as usize {
+                say!(
+                    "vsockd: refusing client {} — {MAX_PENDING_CONNS} connections are already \
+                     waiting to say what they want",
+                    conn.as_handle().0
+                );
+            } else {
+                pending.push(PendingConn { conn, rx: ClientRx::new(), since: Instant::now() });
+            }
+        }
+
+        let mut requests: Vec<Request> = Vec::new();
+        let mut i = 0;
+        while i < pending.len() {
+            let handle = pending[i].conn.as_handle();
+            if !ready.contains(&(TOKEN_PENDING_BASE + handle.0 as u64)) {
+                i += 1;
+                continue;
+            }
+            let step = {
+                let p = &mut pending[i];
+                p.rx.pump(&p.conn)
+            };
+            match step {
+                RxStep::Idle => i += 1,
+                RxStep::Eof => {
+                    pending.remove(i);
+                }
+                RxStep::Malformed => {
+                    say!(
+                        "vsockd: dropping client {} — it sent a frame this protocol cannot \
+                         describe",
+                        pending[i].conn.as_handle().0
+                    );
+                    pending.remove(i);
+                }
+                RxStep::Frame { msg_type, payload_len } => {
+                    let mut payload = [0u8; MAX_KEPT_REQUEST];
+                    payload[..payload_len].copy_from_slice(pending[i].rx.payload(payload_len));
+                    let p = pending.remove(i);
+                    requests.push(Request {
+                        client: Client { conn: p.conn },
+                        msg_type,
+                        payload,
+                        payload_len,
+                    });
+                }
+            }
+        }
+
+        for request in requests {
+            daemon.handle_message(request);
+        }
+    }
+}
@@ -25,6 +25,15 @@ pub const MSG_DESTROY_WINDOW: u32 = 4;
 pub const MSG_SET_CURSOR: u32 = 5;
 pub const MSG_SET_RESOLUTION: u32 = 6;
 pub const MSG_GET_RESOLUTION: u32 = 7;
+/// Move one monitor of a multi-head display. Payload is an
+/// [`OutputPlacement`]; answered with [`MSG_RESOLUTION_CHANGED`], carrying the
+/// size of the whole desktop whether or not the move was possible — an
+/// arrangement that would overlap two monitors is refused by leaving things as
+/// they were, and the asker reads the answer the same either way.
+pub const MSG_PLACE_OUTPUT: u32 = 8;
+/// The modes one monitor can show. Payload is the output's index as a `u32`,
+/// or none for the primary output; answered with [`MSG_MODES`].
+pub const MSG_GET_MODES: u32 = 9;
 
 /// Cursor styles. A style the compositor does not implement — 0 among them —
 /// is the default cursor rather than an index into anything
@@ -61,6 +70,23 @@ pub const MSG_CLIPBOARD_PASTE_SHM: u32 = 11;
 /// to every window it holds.
 pub const MSG_LAYOUT_CHANGED: u32 = 12;
 
+/// A game controller's state changed. Payload is a [`GamepadEvent`]. Sent to
+/// the focused window only, for the reason a key is: a pad is one player's,
+/// and two windows reading it would be two games moving on one thumb.
+pub const MSG_GAMEPAD_INPUT: u32 = 13;
+/// The answer to [`MSG_GET_MODES`]: a
+/// [`DisplayModes`](toyos_abi::DisplayModes) as bytes, because at over five
+/// hundred of them it is no typed payload. Read it back with
+/// [`DisplayModes::from_bytes`](toyos_abi::DisplayModes::from_bytes); an output
+/// that does not exist answers with an empty list.
+pub const MSG_MODES: u32 = 14;
+
+/// The primary output's mode as the user last kept it, `WIDTHxHEIGHT`. Written
+/// by the display settings window once a mode is confirmed and read by the
+/// compositor at startup, which is the only time it is read: a mode that
+/// stopped being offered — a different monitor plugged in — is left unset.
+pub const MODE_CONFIG: &str = "/home/root/.config/display_mode";
+
 /// Wire reasons carried by [`MSG_WINDOW_REFUSED`]. A client that does not know
 /// a reason still knows it was refused, so adding one is backwards compatible
 /// with an older client — [`CreateError::Refused`] carries the raw value.
@@ -167,6 +193,15 @@ toyos::ipc_payload! {
         pub height: u32,
     }
 
+    /// Output `index`, with its top-left corner at (`x`, `y`) relative to the
+    /// others. Only the relation matters: the compositor moves the whole
+    /// desktop so it starts at the origin.
+    pub struct OutputPlacement {
+        pub index: u32,
+        pub x: i32,
+        pub y: i32,
+    }
+
     pub struct CreateWindowRequest {
         pub width: u32,
         pub height: u32,
@@ -277,6 +312,60 @@ impl From<toyos_abi::input::RawKeyEvent> for KeyEvent {
     }
 }
 
+toyos::ipc_payload! {
+    /// The kernel's [`toyos_abi::input::GamepadEvent`], as the compositor
+    /// forwards it — one pad's whole state, byte-identical to the device's
+    /// and asserted so below, for the reason [`KeyEvent`] is.
+    pub struct GamepadEvent {
+        /// Bit n is button n + 1.
+        pub buttons: u32,
+        /// X, Y, Z, Rx, Ry, Rz, Slider and Dial, each -32767..=32767 with 0 at
+        /// centre; one the pad does not have reads 0.
+        pub axes: [i16; toyos_abi::input::GAMEPAD_AXES],
+        /// Which pad, lowest free first as they are plugged in.
+        pub pad: u8,
+        /// 0 (up) through 7 clockwise, or [`toyos_abi::input::HAT_CENTERED`].
+        pub hat: u8,
+        /// [`toyos_abi::input::PAD_GAMEPAD`] or [`toyos_abi::input::PAD_JOYSTICK`].
+        pub kind: u8,
+        pub flags: u8,
+    }
+}
+
+const _: () = assert!(
+    core::mem::size_of::<GamepadEvent>()
+        == core::mem::size_of::<toyos_abi::input::GamepadEvent>(),
+);
+
+impl GamepadEvent {
+    pub const EMPTY: Self = Self {
+        buttons: 0,
+        axes: [0; toyos_abi::input::GAMEPAD_AXES],
+        pad: 0,
+        hat: toyos_abi::input::HAT_CENTERED,
+        kind: 0,
+        flags: 0,
+    };
+
+    /// Whether button `n` (1-based, as the pad numbers them) is down.
+    pub fn pressed(&self, n: u8) -> bool {
+        (1..=32).contains(&n) && self.buttons & (1 << (n - 1)) != 0
+    }
+
+    /// The pad was unplugged and this is its last state, with everything
+    /// released.
+    pub fn disconnected(&self) -> bool {
+        self.flags & toyos_abi::input::PAD_DISCONNECTED != 0
+    }
+}
+
+impl From<toyos_abi::input::GamepadEvent> for GamepadEvent {
+    fn from(pad: toyos_abi::input::GamepadEvent) -> Self {
+        let toyos_abi::input::GamepadEvent { buttons, axes, pad, hat, kind, flags } = pad;
+        Self { buttons, axes, pad, hat, kind, flags }
+    }
+}
+
 /// A transition and what it types here, from [`Window::press`].
 ///
 /// Separate from [`KeyEvent`] because they are answers to different questions:
@@ -306,6 +395,7 @@ impl KeyPress {
 pub enum Event {
     KeyInput(KeyEvent),
     MouseInput(MouseEvent),
+    GamepadInput(GamepadEvent),
     ClipboardPaste(Vec<u8>),
     Resized,
     Close,
@@ -535,6 +625,10 @@ impl Window {
                 Ok(ev) => Event::MouseInput(ev),
                 Err(_) => Event::Close,
             },
+            MSG_GAMEPAD_INPUT => match self.conn.recv_payload(header) {
+                Ok(ev) => Event::GamepadInput(ev),
+                Err(_) => Event::Close,
+            },
             MSG_WINDOW_RESIZED => {
                 let Ok(info) = self.conn.recv_payload::<ResizeInfo>(header) else {
                     return Event::Close;